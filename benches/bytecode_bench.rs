@@ -0,0 +1,71 @@
+//! Stack backend against register backend on the same programs
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ream::bytecode::{assemble, BytecodeProgram, BytecodeVM, StackToRegister};
+
+/// Recursive fib(20)
+const FIB: &str = "
+    .const 0 int 20
+    .const 1 int 2
+    .const 2 int 1
+    .function 0 fib params=1 locals=1
+
+    const 0
+    call fib
+    jump end
+    fib:
+    store 0
+    load 0
+    const 1
+    lt
+    jump_if_not recurse
+    load 0
+    ret
+    recurse:
+    load 0
+    const 2
+    sub
+    call fib
+    load 0
+    const 1
+    sub
+    call fib
+    add
+    ret
+    end:
+";
+
+/// Builds and sorts a 64 element list 100 times
+fn list_sort() -> String {
+    let mut source = String::from(".const 0 int 100\n.const 1 int 1\n.const 2 int 64\n");
+    for i in 0..64 {
+        source.push_str(&format!(".const {} int {}\n", i + 3, (i * 37) % 64));
+    }
+    source.push_str("const 0\nstore 0\nloop:\nload 0\njump_if_not done\n");
+    for i in 0..64 {
+        source.push_str(&format!("const {}\n", i + 3));
+    }
+    source.push_str("const 2\nlist_new\narray_sort\nstore 1\nload 0\nconst 1\nsub\nstore 0\njump loop\ndone:\nload 1\n");
+    source
+}
+
+fn bench_backends(c: &mut Criterion) {
+    let workloads = [("fib", assemble(FIB).unwrap()), ("list_sort", assemble(&list_sort()).unwrap())];
+    let mut group = c.benchmark_group("backend");
+    for (name, program) in &workloads {
+        group.bench_with_input(BenchmarkId::new("stack", name), program, |b, program: &BytecodeProgram| {
+            let mut vm = BytecodeVM::new();
+            b.iter(|| vm.execute_program(black_box(program)).unwrap())
+        });
+
+        let registers = StackToRegister.translate(program).unwrap();
+        group.bench_with_input(BenchmarkId::new("register", name), &registers, |b, registers| {
+            let mut vm = BytecodeVM::new();
+            b.iter(|| vm.execute_registers(black_box(registers)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_backends);
+criterion_main!(benches);
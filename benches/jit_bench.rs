@@ -0,0 +1,60 @@
+//! Numeric list loops on the VM, in compiled code, and with vector instructions
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ream::bytecode::{assemble, BytecodeProgram, BytecodeVM};
+use ream::jit::simd::vector_width;
+use ream::jit::ReamJIT;
+
+/// Elements in each list
+const LEN: i64 = 1024;
+
+/// Builds `xs` (local 1) and `ys` (local 3) of `LEN` integers from
+/// constants, then runs `body` for each index (local 0) with the
+/// accumulator in local 2, and leaves local `result`
+fn list_loop(body: &str, result: u32) -> String {
+    let mut source = format!(".const 0 int 0\n.const 1 int 1\n.const 2 int 3\n.const 3 int {}\n", LEN);
+    let mut code = String::new();
+    for (offset, local) in [(0, 1), (1, 3)] {
+        for i in 0..LEN {
+            let index = source.lines().count();
+            source.push_str(&format!(".const {} int {}\n", index, i + offset));
+            code.push_str(&format!("    const {}\n", index));
+        }
+        code.push_str(&format!("    const 3\n    list_new\n    store {}\n", local));
+    }
+    code.push_str("    const 0\n    store 2\n    const 0\n    store 0\n");
+    code.push_str("loop:\n    load 0\n    load 1\n    list_len\n    lt\n    jump_if_not done\n");
+    code.push_str(body);
+    code.push_str(&format!("    load 0\n    const 1\n    add\n    store 0\n    jump loop\ndone:\n    load {}\n", result));
+    source + &code
+}
+
+fn bench_vector_loops(c: &mut Criterion) {
+    let workloads = [
+        ("sum", list_loop("    load 2\n    load 1\n    load 0\n    list_get\n    add\n    store 2\n", 2)),
+        ("dot", list_loop("    load 2\n    load 1\n    load 0\n    list_get\n    load 3\n    load 0\n    list_get\n    mul\n    add\n    store 2\n", 2)),
+        ("map", list_loop("    load 1\n    load 0\n    load 1\n    load 0\n    list_get\n    const 2\n    mul\n    list_set\n    store 1\n", 1)),
+    ];
+    let mut group = c.benchmark_group(format!("vector_loops_{}", vector_width()));
+    group.sample_size(20);
+    for (name, source) in &workloads {
+        let program = assemble(source).unwrap();
+        group.bench_with_input(BenchmarkId::new("vm", name), &program, |b, program: &BytecodeProgram| {
+            let mut vm = BytecodeVM::new();
+            b.iter(|| vm.execute_program(black_box(program)).unwrap())
+        });
+
+        // Optimization level 0 leaves every loop to the VM's instructions
+        let mut scalar = ReamJIT::new();
+        scalar.set_optimization_level(0);
+        let scalar = scalar.compile_program(&program).unwrap();
+        group.bench_function(BenchmarkId::new("jit", name), |b| b.iter(|| scalar.call0().unwrap()));
+
+        let vector = ReamJIT::new().compile_program(&program).unwrap();
+        group.bench_function(BenchmarkId::new("jit_simd", name), |b| b.iter(|| vector.call0().unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_vector_loops);
+criterion_main!(benches);
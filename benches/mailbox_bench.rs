@@ -0,0 +1,81 @@
+//! Ping-pong between two processes, one message per quantum against
+//! batched quanta
+
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ream::error::RuntimeResult;
+use ream::runtime::{Mailbox, Process, QuantumBudget, ReamActor};
+use ream::types::{MessagePayload, Pid, Priority};
+
+/// Balls in play at once
+const BALLS: usize = 64;
+
+/// Messages handled by both players per iteration
+const EXCHANGES: usize = 20_000;
+
+/// Returns every ball to the other player
+struct Player {
+    pid: Pid,
+    peer: Arc<OnceLock<Arc<RwLock<Mailbox>>>>,
+}
+
+impl ReamActor for Player {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        self.peer.get().unwrap().write().unwrap().send(message);
+        Ok(())
+    }
+
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        Ok(())
+    }
+}
+
+fn player(budget: QuantumBudget) -> (Process, Arc<OnceLock<Arc<RwLock<Mailbox>>>>) {
+    let pid = Pid::new();
+    let peer = Arc::new(OnceLock::new());
+    let mut process = Process::new(pid, Box::new(Player { pid, peer: Arc::clone(&peer) }), Priority::Normal);
+    process.set_budget(budget);
+    (process, peer)
+}
+
+fn ping_pong(budget: QuantumBudget) {
+    let (mut ping, ping_peer) = player(budget);
+    let (mut pong, pong_peer) = player(budget);
+    ping_peer.set(pong.mailbox()).unwrap();
+    pong_peer.set(ping.mailbox()).unwrap();
+    for _ in 0..BALLS {
+        ping.mailbox().write().unwrap().send(MessagePayload::Text("ball".to_string()));
+    }
+
+    let mut exchanges = 0;
+    while exchanges < EXCHANGES {
+        exchanges += ping.run_quantum().unwrap();
+        exchanges += pong.run_quantum().unwrap();
+    }
+}
+
+fn bench_ping_pong(c: &mut Criterion) {
+    let time_slice = Duration::from_millis(1);
+    let budgets = [
+        ("one_per_quantum", QuantumBudget { max_messages: 1, batch_size: 1, time_slice }),
+        ("batch_16", QuantumBudget { max_messages: 64, batch_size: 16, time_slice }),
+        ("batch_64", QuantumBudget { max_messages: 64, batch_size: 64, time_slice }),
+    ];
+    let mut group = c.benchmark_group("ping_pong");
+    group.throughput(Throughput::Elements(EXCHANGES as u64));
+    for (name, budget) in budgets {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &budget, |b, budget| {
+            b.iter(|| ping_pong(*budget))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_ping_pong);
+criterion_main!(benches);
@@ -485,6 +485,169 @@ pub fn derive_bridge_impl(input: TokenStream) -> TokenStream {
     }
 }
 
+//
+// ORM Macro Implementations
+//
+
+pub fn derive_model_impl(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+
+    match generate_model_impl(&input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Column options from `#[model(...)]` on a field
+#[derive(Default)]
+struct ModelColumn {
+    column: Option<String>,
+    primary_key: bool,
+    unique: bool,
+}
+
+fn generate_model_impl(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &input.ident;
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "Model derive only supports structs with named fields",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "Model derive does not support generic structs"));
+    }
+
+    let mut table = to_snake_case(&struct_name.to_string());
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("model")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `table = \"...\"`"))
+            }
+        })?;
+    }
+
+    let mut consts = Vec::new();
+    let mut columns = Vec::new();
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let mut options = ModelColumn::default();
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("model")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("primary_key") {
+                    options.primary_key = true;
+                } else if meta.path.is_ident("unique") {
+                    options.unique = true;
+                } else if meta.path.is_ident("column") {
+                    options.column = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else {
+                    return Err(meta.error("expected `primary_key`, `unique` or `column = \"...\"`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let field_name = ident.to_string();
+        let field_name = field_name.trim_start_matches("r#");
+        let column = options.column.unwrap_or_else(|| field_name.to_string());
+        let const_name = syn::Ident::new(&field_name.to_uppercase(), ident.span());
+        let doc = format!("The `{}` column", column);
+
+        consts.push(quote! {
+            #[doc = #doc]
+            pub const #const_name: ::ream::orm::typed_query::ModelField<#struct_name, #ty> =
+                ::ream::orm::typed_query::ModelField::new(#column);
+        });
+
+        let mut definition = quote! {
+            ::ream::orm::schema::Column::new(
+                #column,
+                <#ty as ::ream::orm::typed_query::FieldType>::DATA_TYPE,
+            )
+        };
+        definition = quote! {{
+            let column = #definition;
+            if <#ty as ::ream::orm::typed_query::FieldType>::NULLABLE { column } else { column.not_null() }
+        }};
+        if options.primary_key {
+            definition = quote! { #definition.primary_key() };
+        }
+        if options.unique {
+            definition = quote! { #definition.unique() };
+        }
+        columns.push(definition);
+
+        reads.push(quote! {
+            #ident: ::ream::orm::types::get_typed::<#ty, _>(&row, #column)?
+        });
+        writes.push(quote! {
+            values.insert(#column.to_string(), ::ream::orm::types::ToValue::to_value(&self.#ident));
+        });
+    }
+
+    Ok(quote! {
+        impl #struct_name {
+            #(#consts)*
+        }
+
+        impl ::ream::orm::typed_query::Model for #struct_name {
+            const TABLE: &'static str = #table;
+
+            fn columns() -> ::std::vec::Vec<::ream::orm::schema::Column> {
+                ::std::vec![#(#columns),*]
+            }
+        }
+
+        impl ::ream::orm::types::FromRow for #struct_name {
+            fn from_row<R: ::ream::orm::types::Row>(
+                row: R,
+            ) -> ::std::result::Result<Self, ::ream::orm::types::TypeConversionError> {
+                ::std::result::Result::Ok(Self {
+                    #(#reads),*
+                })
+            }
+        }
+
+        impl ::ream::orm::types::ToRow for #struct_name {
+            fn to_row(
+                &self,
+            ) -> ::std::result::Result<
+                ::std::collections::HashMap<::std::string::String, ::ream::sqlite::types::Value>,
+                ::ream::orm::types::TypeConversionError,
+            > {
+                let mut values = ::std::collections::HashMap::new();
+                #(#writes)*
+                ::std::result::Result::Ok(values)
+            }
+        }
+    })
+}
+
+/// `UserProfile` -> `user_profile`
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (position, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if position > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
 //
 // Test Macro Implementations
 //
@@ -217,6 +217,17 @@ pub fn derive_bridge(input: TokenStream) -> TokenStream {
     derive_bridge_impl(input)
 }
 
+//
+// ORM Macros
+//
+
+/// Derive an ORM model: table columns, `FromRow`/`ToRow` and a typed field
+/// constant per column for building queries
+#[proc_macro_derive(Model, attributes(model))]
+pub fn derive_model(input: TokenStream) -> TokenStream {
+    derive_model_impl(input)
+}
+
 //
 // Testing Macros
 //
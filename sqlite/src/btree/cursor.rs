@@ -1,5 +1,7 @@
 use crate::btree::node::{BTreeNode, BTreeNodeType};
+use crate::collation::Collation;
 use crate::types::{Row, RowId, Value};
+use std::cmp::Ordering;
 use std::sync::Arc;
 
 /// B-Tree cursor for iteration following coalgebraic structure
@@ -8,6 +10,8 @@ pub struct BTreeCursor<'a> {
     root: &'a BTreeNode,
     stack: Vec<CursorFrame<'a>>,
     current_position: Option<CursorPosition>,
+    /// Order of the tree's keys, used to seek
+    collation: Collation,
 }
 
 /// Cursor frame for tracking position in tree traversal
@@ -32,11 +36,18 @@ impl<'a> BTreeCursor<'a> {
             root,
             stack: Vec::new(),
             current_position: None,
+            collation: Collation::binary(),
         };
         cursor.move_to_first();
         cursor
     }
 
+    /// Seek by `collation` rather than BINARY, for a tree ordered by it
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
     /// Move cursor to the first element
     pub fn move_to_first(&mut self) {
         self.stack.clear();
@@ -235,7 +246,7 @@ impl<'a> BTreeCursor<'a> {
     fn seek_key(&mut self, node: &'a BTreeNode, key: &Value) {
         match &node.node_type {
             BTreeNodeType::Leaf { keys, .. } => {
-                let pos = keys.partition_point(|k| k < key);
+                let pos = keys.partition_point(|k| self.collation.compare_values(k, key) == Ordering::Less);
                 
                 self.stack.push(CursorFrame {
                     node,
@@ -249,7 +260,8 @@ impl<'a> BTreeCursor<'a> {
                 });
             }
             BTreeNodeType::Internal { keys, children } => {
-                let pos = keys.partition_point(|k| k < key);
+                // A separator is the first key of the child to its right
+                let pos = keys.partition_point(|k| self.collation.compare_values(k, key) != Ordering::Greater);
                 
                 self.stack.push(CursorFrame {
                     node,
@@ -6,8 +6,10 @@ pub use node::{BTreeNode, BTreeNodeType};
 pub use cursor::BTreeCursor;
 pub use algebra::BTreeAlgebra;
 
+use crate::collation::Collation;
 use crate::error::{SqlError, SqlResult};
 use crate::types::{Row, RowId, Value};
+use std::cmp::Ordering;
 use std::sync::Arc;
 
 /// B-Tree implementation following algebraic patterns
@@ -15,24 +17,40 @@ use std::sync::Arc;
 pub struct BTree {
     root: Arc<BTreeNode>,
     order: usize, // Maximum number of keys per node
+    /// How keys are ordered; text keys differing only in a way the
+    /// collation ignores are the same key
+    collation: Collation,
 }
 
 impl BTree {
     pub fn new(order: usize) -> Self {
+        Self::with_collation(order, Collation::binary())
+    }
+
+    pub fn with_collation(order: usize, collation: Collation) -> Self {
         BTree {
             root: Arc::new(BTreeNode::new_leaf()),
             order,
+            collation,
         }
     }
 
+    pub fn collation(&self) -> &Collation {
+        &self.collation
+    }
+
     pub fn empty() -> Self {
         Self::new(4) // Default order
     }
 
     /// Insert a key-value pair into the B-Tree
     pub fn insert(&mut self, key: Value, row_id: RowId, row: Row) -> SqlResult<()> {
-        let new_root = self.insert_recursive(&self.root, key, row_id, row)?;
-        self.root = Arc::new(new_root);
+        let (node, split) = self.insert_recursive(&self.root, key, row_id, row)?;
+        self.root = Arc::new(match split {
+            // The root split: the tree grows a level
+            Some((separator, right)) => BTreeNode::new_internal(vec![separator], vec![Arc::new(node), Arc::new(right)]),
+            None => node,
+        });
         Ok(())
     }
 
@@ -49,8 +67,8 @@ impl BTree {
     }
 
     /// Create a cursor for iterating over the B-Tree
-    pub fn cursor(&self) -> BTreeCursor {
-        BTreeCursor::new(&self.root)
+    pub fn cursor(&self) -> BTreeCursor<'_> {
+        BTreeCursor::new(&self.root).with_collation(self.collation.clone())
     }
 
     /// Get all key-value pairs in sorted order
@@ -68,22 +86,37 @@ impl BTree {
     }
 
     // Private helper methods
+
+    /// Child of an internal node that holds `key`; each separator is the
+    /// first key of the child to its right
+    fn child_position(&self, keys: &[Value], key: &Value) -> usize {
+        keys.partition_point(|k| self.compare(k, key) != Ordering::Greater)
+    }
+
+    fn compare(&self, left: &Value, right: &Value) -> Ordering {
+        self.collation.compare_values(left, right)
+    }
+
+    fn find(&self, keys: &[Value], key: &Value) -> Result<usize, usize> {
+        keys.binary_search_by(|k| self.compare(k, key))
+    }
+
+    /// Insert below `node`, returning its new version and, if it split,
+    /// the separator and the new right sibling
     fn insert_recursive(
         &self,
         node: &BTreeNode,
         key: Value,
         row_id: RowId,
         row: Row,
-    ) -> SqlResult<BTreeNode> {
+    ) -> SqlResult<(BTreeNode, Option<(Value, BTreeNode)>)> {
         match &node.node_type {
             BTreeNodeType::Leaf { keys, values } => {
                 let mut new_keys = keys.clone();
                 let mut new_values = values.clone();
 
                 // Find insertion position
-                let pos = new_keys
-                    .binary_search(&key)
-                    .unwrap_or_else(|pos| pos);
+                let pos = self.find(&new_keys, &key).unwrap_or_else(|pos| pos);
 
                 // Insert key and value
                 new_keys.insert(pos, key);
@@ -91,38 +124,39 @@ impl BTree {
 
                 // Check if split is needed
                 if new_keys.len() > self.order {
-                    self.split_leaf(new_keys, new_values)
+                    Ok(self.split_leaf(new_keys, new_values))
                 } else {
-                    Ok(BTreeNode {
-                        node_type: BTreeNodeType::Leaf {
-                            keys: new_keys,
-                            values: new_values,
+                    Ok((
+                        BTreeNode {
+                            node_type: BTreeNodeType::Leaf {
+                                keys: new_keys,
+                                values: new_values,
+                            },
                         },
-                    })
+                        None,
+                    ))
                 }
             }
             BTreeNodeType::Internal { keys, children } => {
                 // Find child to insert into
-                let pos = keys.partition_point(|k| k < &key);
-                let child = &children[pos];
+                let pos = self.child_position(keys, &key);
 
                 // Recursively insert into child
-                let new_child = self.insert_recursive(child, key, row_id, row)?;
+                let (new_child, split) = self.insert_recursive(&children[pos], key, row_id, row)?;
 
-                // Update children
+                // Update children, taking in the child's new sibling
+                let mut new_keys = keys.clone();
                 let mut new_children = children.clone();
                 new_children[pos] = Arc::new(new_child);
+                if let Some((separator, right)) = split {
+                    new_keys.insert(pos, separator);
+                    new_children.insert(pos + 1, Arc::new(right));
+                }
 
-                // Check if child was split
-                if new_children[pos].is_overfull(self.order) {
-                    self.split_internal(keys.clone(), new_children, pos)
+                if new_keys.len() > self.order {
+                    Ok(self.split_internal(new_keys, new_children))
                 } else {
-                    Ok(BTreeNode {
-                        node_type: BTreeNodeType::Internal {
-                            keys: keys.clone(),
-                            children: new_children,
-                        },
-                    })
+                    Ok((BTreeNode::new_internal(new_keys, new_children), None))
                 }
             }
         }
@@ -131,14 +165,14 @@ impl BTree {
     fn search_recursive(&self, node: &BTreeNode, key: &Value) -> SqlResult<Option<(RowId, Row)>> {
         match &node.node_type {
             BTreeNodeType::Leaf { keys, values } => {
-                if let Ok(pos) = keys.binary_search(key) {
+                if let Ok(pos) = self.find(keys, key) {
                     Ok(Some(values[pos].clone()))
                 } else {
                     Ok(None)
                 }
             }
             BTreeNodeType::Internal { keys, children } => {
-                let pos = keys.partition_point(|k| k < key);
+                let pos = self.child_position(keys, key);
                 self.search_recursive(&children[pos], key)
             }
         }
@@ -151,7 +185,7 @@ impl BTree {
     ) -> SqlResult<(Option<(RowId, Row)>, BTreeNode)> {
         match &node.node_type {
             BTreeNodeType::Leaf { keys, values } => {
-                if let Ok(pos) = keys.binary_search(key) {
+                if let Ok(pos) = self.find(keys, key) {
                     let mut new_keys = keys.clone();
                     let mut new_values = values.clone();
                     let deleted = new_values.remove(pos);
@@ -171,7 +205,7 @@ impl BTree {
                 }
             }
             BTreeNodeType::Internal { keys, children } => {
-                let pos = keys.partition_point(|k| k < key);
+                let pos = self.child_position(keys, key);
                 let (deleted, new_child) = self.delete_recursive(&children[pos], key)?;
 
                 let mut new_children = children.clone();
@@ -221,7 +255,7 @@ impl BTree {
         match &node.node_type {
             BTreeNodeType::Leaf { keys, values } => {
                 for (key, (row_id, row)) in keys.iter().zip(values.iter()) {
-                    if key >= start && key <= end {
+                    if self.compare(key, start) != Ordering::Less && self.compare(key, end) != Ordering::Greater {
                         results.push((key.clone(), *row_id, row.clone()));
                     }
                 }
@@ -233,9 +267,10 @@ impl BTree {
                     let should_search = if i == 0 {
                         true // First child
                     } else if i == children.len() - 1 {
-                        keys[i - 1] <= *end // Last child
+                        self.compare(&keys[i - 1], end) != Ordering::Greater // Last child
                     } else {
-                        keys[i - 1] <= *end && keys[i] >= *start
+                        self.compare(&keys[i - 1], end) != Ordering::Greater
+                            && self.compare(&keys[i], start) != Ordering::Less
                     };
 
                     if should_search {
@@ -247,48 +282,90 @@ impl BTree {
         }
     }
 
-    fn split_leaf(
-        &self,
-        keys: Vec<Value>,
-        values: Vec<(RowId, Row)>,
-    ) -> SqlResult<BTreeNode> {
+    fn split_leaf(&self, keys: Vec<Value>, values: Vec<(RowId, Row)>) -> (BTreeNode, Option<(Value, BTreeNode)>) {
         let mid = keys.len() / 2;
         let (left_keys, right_keys) = keys.split_at(mid);
         let (left_values, right_values) = values.split_at(mid);
 
-        let left_child = Arc::new(BTreeNode {
+        let left = BTreeNode {
             node_type: BTreeNodeType::Leaf {
                 keys: left_keys.to_vec(),
                 values: left_values.to_vec(),
             },
-        });
-
-        let right_child = Arc::new(BTreeNode {
+        };
+        let right = BTreeNode {
             node_type: BTreeNodeType::Leaf {
                 keys: right_keys.to_vec(),
                 values: right_values.to_vec(),
             },
-        });
+        };
 
-        // Promote middle key to new internal node
-        Ok(BTreeNode {
-            node_type: BTreeNodeType::Internal {
-                keys: vec![right_keys[0].clone()],
-                children: vec![left_child, right_child],
-            },
-        })
+        // The right half's first key separates the halves in the parent
+        (left, Some((right_keys[0].clone(), right)))
     }
 
     fn split_internal(
         &self,
-        keys: Vec<Value>,
-        children: Vec<Arc<BTreeNode>>,
-        _split_pos: usize,
-    ) -> SqlResult<BTreeNode> {
-        // For simplicity, just return the original node
-        // In a full implementation, this would handle internal node splits
-        Ok(BTreeNode {
-            node_type: BTreeNodeType::Internal { keys, children },
-        })
+        mut keys: Vec<Value>,
+        mut children: Vec<Arc<BTreeNode>>,
+    ) -> (BTreeNode, Option<(Value, BTreeNode)>) {
+        // The middle key moves up to the parent
+        let mid = keys.len() / 2;
+        let right_keys = keys.split_off(mid + 1);
+        let separator = keys.pop().expect("an overfull node has keys");
+        let right_children = children.split_off(mid + 1);
+        (
+            BTreeNode::new_internal(keys, children),
+            Some((separator, BTreeNode::new_internal(right_keys, right_children))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_search_delete_and_range() {
+        let key = |i: u64| Value::Text(format!("k{:03}", i));
+        let mut tree = BTree::new(4);
+        for i in 0..200 {
+            tree.insert(key((i * 37) % 200), RowId(i), Row::empty()).unwrap();
+        }
+        assert!((0..200).all(|i| tree.search(&key(i)).unwrap().is_some()));
+        assert!(tree.root.height() <= 5);
+
+        for i in (0..200).step_by(3) {
+            assert!(tree.delete(&key(i)).unwrap().is_some());
+        }
+        assert!((0..200).all(|i| tree.search(&key(i)).unwrap().is_some() == (i % 3 != 0)));
+        assert_eq!(tree.scan().unwrap().len(), 133);
+
+        let range: Vec<Value> = tree
+            .range_scan(&Value::Text("k05".to_string()), &Value::Text("k05\u{10FFFF}".to_string()))
+            .unwrap()
+            .into_iter()
+            .map(|(key, _, _)| key)
+            .collect();
+        assert_eq!(range, [50, 52, 53, 55, 56, 58, 59].map(key));
+    }
+
+    #[test]
+    fn test_keys_ordered_by_collation() {
+        let key = |s: &str| Value::Text(s.to_string());
+        let mut tree = BTree::with_collation(4, Collation::find("NOCASE").unwrap());
+        for (i, name) in ["delta", "Alpha", "charlie", "Bravo", "echo", "Foxtrot"].into_iter().enumerate() {
+            tree.insert(key(name), RowId(i as u64), Row::empty()).unwrap();
+        }
+        let keys: Vec<Value> = tree.scan().unwrap().into_iter().map(|(key, _, _)| key).collect();
+        assert_eq!(keys, ["Alpha", "Bravo", "charlie", "delta", "echo", "Foxtrot"].map(key));
+        assert_eq!(tree.search(&key("ALPHA")).unwrap().unwrap().0, RowId(1));
+        let range = tree.range_scan(&key("b"), &key("D")).unwrap();
+        assert_eq!(range.len(), 2);
+        assert!(tree.delete(&key("FOXTROT")).unwrap().is_some());
+
+        let mut cursor = tree.cursor();
+        cursor.seek(&key("CHARLIE"));
+        assert_eq!(cursor.current().map(|(key, _, _)| key.clone()), Some(key("charlie")));
     }
 }
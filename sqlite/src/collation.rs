@@ -0,0 +1,148 @@
+//! Collating sequences: how text values compare and sort
+//!
+//! `BINARY` compares bytes, `NOCASE` folds ASCII letters to lower case and
+//! `RTRIM` ignores trailing spaces, as in SQLite. Other collations are
+//! registered by name with [`register_collation`]. Values other than text
+//! compare the same under every collation.
+
+use crate::error::{SqlError, SqlResult};
+use crate::types::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Compares two strings for a collation
+pub type CollationFn = dyn Fn(&str, &str) -> Ordering + Send + Sync;
+
+/// A named text ordering; cheap to clone
+#[derive(Clone)]
+pub struct Collation {
+    name: Arc<str>,
+    compare: Arc<CollationFn>,
+}
+
+static BINARY: LazyLock<Collation> = LazyLock::new(|| Collation::new("BINARY", |a: &str, b: &str| a.cmp(b)));
+
+/// Every known collation, by upper-case name. Shared by the databases of
+/// the process, so a registered collation is usable from all of them.
+static REGISTRY: LazyLock<RwLock<HashMap<String, Collation>>> = LazyLock::new(|| {
+    let builtins = [
+        BINARY.clone(),
+        Collation::new("NOCASE", |a: &str, b: &str| {
+            a.bytes().map(|c| c.to_ascii_lowercase()).cmp(b.bytes().map(|c| c.to_ascii_lowercase()))
+        }),
+        Collation::new("RTRIM", |a: &str, b: &str| a.trim_end_matches(' ').cmp(b.trim_end_matches(' '))),
+    ];
+    RwLock::new(builtins.into_iter().map(|collation| (collation.name.to_string(), collation)).collect())
+});
+
+/// Make a collation available to `COLLATE name` clauses, replacing any
+/// collation of the same name
+pub fn register_collation(name: &str, compare: impl Fn(&str, &str) -> Ordering + Send + Sync + 'static) {
+    let collation = Collation::new(&name.to_ascii_uppercase(), compare);
+    REGISTRY
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(collation.name.to_string(), collation);
+}
+
+impl Collation {
+    fn new(name: &str, compare: impl Fn(&str, &str) -> Ordering + Send + Sync + 'static) -> Self {
+        Collation {
+            name: name.into(),
+            compare: Arc::new(compare),
+        }
+    }
+
+    /// The collation registered as `name`, matched case-insensitively
+    pub fn find(name: &str) -> SqlResult<Self> {
+        REGISTRY
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&name.to_ascii_uppercase())
+            .cloned()
+            .ok_or_else(|| SqlError::runtime_error(format!("no such collation sequence: {}", name)))
+    }
+
+    /// The default collation, comparing bytes
+    pub fn binary() -> Self {
+        BINARY.clone()
+    }
+
+    /// Upper-case name of the collation
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_binary(&self) -> bool {
+        &*self.name == "BINARY"
+    }
+
+    pub fn compare_text(&self, left: &str, right: &str) -> Ordering {
+        (self.compare)(left, right)
+    }
+
+    /// Compare for `=`, `<` and the like; `None` when either side is NULL
+    /// or the values cannot be compared
+    pub fn partial_cmp(&self, left: &Value, right: &Value) -> Option<Ordering> {
+        match (left, right) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Text(a), Value::Text(b)) => Some(self.compare_text(a, b)),
+            _ => left.partial_cmp(right),
+        }
+    }
+
+    /// Compare in SQLite sort order, see [`compare_values`]
+    ///
+    /// [`compare_values`]: crate::query::sort::compare_values
+    pub fn compare_values(&self, left: &Value, right: &Value) -> Ordering {
+        match (left, right) {
+            (Value::Text(a), Value::Text(b)) => self.compare_text(a, b),
+            _ => crate::query::sort::compare_values(left, right),
+        }
+    }
+}
+
+impl Default for Collation {
+    fn default() -> Self {
+        Self::binary()
+    }
+}
+
+impl PartialEq for Collation {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for Collation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Collation({})", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_and_registered_collations() {
+        let text = |s: &str| Value::Text(s.to_string());
+        let nocase = Collation::find("nocase").unwrap();
+        assert_eq!(nocase.partial_cmp(&text("Apple"), &text("aPPLE")), Some(Ordering::Equal));
+        assert_eq!(nocase.compare_values(&text("a"), &text("B")), Ordering::Less);
+        assert_eq!(Collation::binary().compare_values(&text("b"), &text("A")), Ordering::Greater);
+        assert_eq!(Collation::binary().compare_values(&text("a"), &text("B")), Ordering::Greater);
+        let rtrim = Collation::find("RTRIM").unwrap();
+        assert_eq!(rtrim.partial_cmp(&text("x  "), &text("x")), Some(Ordering::Equal));
+        assert_eq!(rtrim.partial_cmp(&text("x"), &Value::Null), None);
+        assert_eq!(nocase.compare_values(&Value::Integer(2), &text("1")), Ordering::Less);
+
+        assert!(Collation::find("reverse_test").is_err());
+        register_collation("reverse_test", |a, b| b.cmp(a));
+        let reverse = Collation::find("REVERSE_TEST").unwrap();
+        assert_eq!(reverse.name(), "REVERSE_TEST");
+        assert_eq!(reverse.compare_text("a", "b"), Ordering::Greater);
+    }
+}
@@ -34,8 +34,8 @@ pub struct DatabaseConfig {
     pub enable_connection_pooling: bool,
     
     // Maintenance configuration
-    pub auto_vacuum: bool,
-    pub vacuum_threshold: f64, // Percentage of free space to trigger vacuum
+    pub auto_vacuum: AutoVacuum,
+    pub vacuum_threshold: f64, // Fraction of free pages at which maintenance vacuums
     pub auto_checkpoint: bool,
     pub checkpoint_threshold: usize, // Number of WAL entries to trigger checkpoint
     
@@ -83,8 +83,8 @@ impl Default for DatabaseConfig {
             enable_connection_pooling: true,
             
             // Maintenance defaults
-            auto_vacuum: true,
-            vacuum_threshold: 0.25, // 25% free space
+            auto_vacuum: AutoVacuum::Incremental,
+            vacuum_threshold: 0.25, // 25% free pages
             auto_checkpoint: true,
             checkpoint_threshold: 1000,
             
@@ -268,6 +268,21 @@ impl DatabaseConfig {
     }
 }
 
+/// How free pages of a database file are given back to the file system.
+/// They are reused by later writes either way; `VACUUM` reclaims them
+/// whatever the mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoVacuum {
+    /// Only `VACUUM` shrinks the file
+    None,
+    /// Free pages at the end of the file are released at every commit,
+    /// and `perform_maintenance` vacuums past the threshold
+    Full,
+    /// `perform_maintenance` vacuums once the fraction of free pages
+    /// reaches `vacuum_threshold`
+    Incremental,
+}
+
 /// Log levels
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogLevel {
@@ -311,6 +326,11 @@ impl DatabaseConfigBuilder {
         self
     }
 
+    pub fn auto_vacuum(mut self, mode: AutoVacuum) -> Self {
+        self.config.auto_vacuum = mode;
+        self
+    }
+
     pub fn isolation_level(mut self, level: IsolationLevel) -> Self {
         self.config.default_isolation_level = level;
         self
@@ -1,18 +1,26 @@
 pub mod config;
+pub mod prepared;
+pub mod session;
 
-pub use config::DatabaseConfig;
+pub use config::{AutoVacuum, DatabaseConfig};
+pub use prepared::{PreparedStatement, StatementCache};
+pub use session::{Session, TransactionStatus};
 
 use crate::btree::BTree;
 use crate::error::{SqlError, SqlResult};
+use crate::fdw::ForeignScan;
 use crate::page_cache::{PageCache, PageCacheConfig};
-use crate::parser::parse_sql;
-use crate::query::{QueryProcessor, QueryResult};
+use crate::parser::ast::{ExplainMode, Statement};
+use crate::query::{IndexInfo, QueryProcessor, QueryResult, TableEdit, TableSchema};
 use crate::schema::{Schema, SchemaRegistry};
-use crate::transaction::{TransactionManager, TransactionConfig};
-use crate::types::{DatabaseMode, DatabaseState};
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use crate::storage::{Backup, BackupContents, BackupKind, Pager, PagerConfig, PagerTransaction, VacuumStats};
+use crate::transaction::{MvccStore, TableChanges, TransactionManager, TransactionConfig};
+use crate::types::{DatabaseMode, DatabaseState, RowChange, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, RwLock};
 
 /// Complete SQLite engine as categorical composition
 /// 
@@ -27,13 +35,27 @@ use tokio::sync::RwLock;
 pub struct CategoricalSQLite {
     // Core storage components
     page_cache: Arc<PageCache>,
+    /// Database file; `None` for an in-memory database
+    pager: Option<Arc<Pager>>,
     btrees: Arc<RwLock<HashMap<String, BTree>>>, // Table name -> B-Tree
     
     // Query processing
+    /// Tables as the writer changes them
     query_processor: Arc<QueryProcessor>,
+    /// Tables as of the last commit, which read-only statements query.
+    /// Replaced after each write rather than changed, so readers never
+    /// wait for the writer nor see what it has not committed.
+    read_view: Arc<std::sync::RwLock<Arc<QueryProcessor>>>,
+    statements: Arc<StatementCache>,
     
     // Transaction management
     transaction_manager: Arc<RwLock<TransactionManager>>,
+    /// Committed row versions, which session transactions read and write
+    /// through; every writer brings them in line with the tables it changed
+    mvcc: Arc<MvccStore>,
+    /// Held by each write statement and session COMMIT, and briefly by
+    /// BEGIN to take its snapshot: there is one writer at a time
+    writer: Arc<Mutex<()>>,
     
     // Schema management
     schema_registry: Arc<RwLock<SchemaRegistry>>,
@@ -43,20 +65,95 @@ pub struct CategoricalSQLite {
     
     // Current state
     current_mode: Arc<RwLock<DatabaseMode>>,
+
+    /// Rows changed by each commit, for `subscribe_changes`
+    changes: broadcast::Sender<Arc<Vec<RowChange>>>,
 }
 
+/// Commits a change subscriber may fall behind by before it misses some
+const CHANGE_FEED_CAPACITY: usize = 1024;
+
 impl CategoricalSQLite {
-    /// Create a new SQLite engine instance
+    /// Create a new in-memory SQLite engine instance
     pub fn new(config: DatabaseConfig) -> Self {
-        let page_cache = Arc::new(PageCache::new(PageCacheConfig {
+        let page_cache = Arc::new(PageCache::new(Self::page_cache_config(&config)));
+        Self::with_storage(config, page_cache, None)
+    }
+
+    /// Open (or create) a database file; tables, indexes and statistics
+    /// stored in it are loaded, and every successful write statement is
+    /// committed to it
+    pub async fn open(path: impl AsRef<Path>, config: DatabaseConfig) -> SqlResult<Self> {
+        let pager = Arc::new(
+            Pager::open(
+                path,
+                PagerConfig {
+                    cache: Self::page_cache_config(&config),
+                    enable_wal: config.enable_wal,
+                    sync_mode: config.wal_sync_mode,
+                    checkpoint_threshold: config.auto_checkpoint.then_some(config.checkpoint_threshold),
+                    auto_vacuum: config.auto_vacuum == AutoVacuum::Full,
+                },
+            )
+            .await?,
+        );
+        if config.enable_wal && config.auto_checkpoint {
+            Self::spawn_checkpointer(Arc::downgrade(&pager), config.checkpoint_interval);
+        }
+        let db = Self::with_storage(config, pager.cache(), Some(pager.clone()));
+
+        let mut transaction = pager.begin().await;
+        for (name, schema) in transaction.tables() {
+            let rows = transaction.read_table(&name).await?;
+            db.query_processor.restore_table(name, schema, rows).await;
+        }
+        for index in transaction.indexes() {
+            db.query_processor.restore_index(index).await?;
+        }
+        db.query_processor.statistics().write().await.extend(transaction.stats());
+        drop(transaction);
+        db.sync_versions(&db.tables().await).await;
+        db.publish(None).await;
+        Ok(db)
+    }
+
+    /// Checkpoint the WAL every `interval` until the pager is dropped
+    fn spawn_checkpointer(pager: Weak<Pager>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let Some(pager) = pager.upgrade() else { break };
+                // A failed checkpoint leaves the WAL intact; the next one
+                // (or recovery on open) retries it
+                let _ = pager.checkpoint();
+            }
+        });
+    }
+
+    /// Copy the commits waiting in the WAL into the database file; returns
+    /// the number of pages copied
+    pub async fn checkpoint(&self) -> SqlResult<usize> {
+        match &self.pager {
+            Some(pager) => pager.checkpoint(),
+            None => Ok(0),
+        }
+    }
+
+    fn page_cache_config(config: &DatabaseConfig) -> PageCacheConfig {
+        PageCacheConfig {
             capacity: config.cache_size,
             page_size: config.page_size,
             enable_write_through: config.enable_write_through,
             enable_prefetch: config.enable_prefetch,
             max_dirty_pages: config.max_dirty_pages,
-        }));
+        }
+    }
 
-        let transaction_manager = Arc::new(RwLock::new(TransactionManager::new(
+    fn with_storage(config: DatabaseConfig, page_cache: Arc<PageCache>, pager: Option<Arc<Pager>>) -> Self {
+
+        let transaction_manager = TransactionManager::new(
             TransactionConfig {
                 default_isolation_level: config.default_isolation_level,
                 transaction_timeout: config.transaction_timeout,
@@ -65,31 +162,146 @@ impl CategoricalSQLite {
                 wal_sync_mode: config.wal_sync_mode,
                 checkpoint_interval: config.checkpoint_interval,
             }
-        )));
+        );
+        let mvcc = transaction_manager.mvcc();
+        let transaction_manager = Arc::new(RwLock::new(transaction_manager));
+
+        let statement_cache_size = if config.enable_query_cache { config.query_cache_size } else { 0 };
+
+        // The registry is the one the catalog tables read
+        let query_processor = Arc::new(QueryProcessor::new());
+        // Writes reach the database file and the readers' view as the rows
+        // they changed
+        query_processor.capture_edits(true);
+        let schema_registry = query_processor.schema_registry();
+        // Nothing is committed yet
+        let read_view = Arc::new(QueryProcessor::with_registry(schema_registry.clone()));
 
         CategoricalSQLite {
             page_cache,
+            pager,
             btrees: Arc::new(RwLock::new(HashMap::new())),
-            query_processor: Arc::new(QueryProcessor::new()),
+            query_processor,
+            read_view: Arc::new(std::sync::RwLock::new(read_view)),
+            statements: Arc::new(StatementCache::new(statement_cache_size)),
             transaction_manager,
-            schema_registry: Arc::new(RwLock::new(SchemaRegistry::new())),
+            mvcc,
+            writer: Arc::new(Mutex::new(())),
+            schema_registry,
             config,
             current_mode: Arc::new(RwLock::new(DatabaseMode::ReadWrite)),
+            changes: broadcast::channel(CHANGE_FEED_CAPACITY).0,
         }
     }
 
     /// Primary SQL operation: categorical composition of all patterns
     pub async fn execute_sql(&self, sql: &str) -> SqlResult<QueryResult> {
         // 1. Parse SQL into AST (interpreter pattern)
-        let ast = parse_sql(sql)?;
-        
+        let statement = self.prepare(sql)?;
+        self.execute(&statement, &[]).await
+    }
+
+    /// Parse a statement with `?`, `?NNN` or `:name` placeholders once, to
+    /// run it with different values; repeated SQL comes from the cache
+    pub fn prepare(&self, sql: &str) -> SqlResult<Arc<PreparedStatement>> {
+        self.statements.prepare(sql)
+    }
+
+    /// Run a prepared statement with one value per parameter, in order
+    pub async fn execute(&self, statement: &PreparedStatement, params: &[Value]) -> SqlResult<QueryResult> {
+        let ast = statement.bind(params)?;
+        self.execute_statement(ast).await
+    }
+
+    /// Run a prepared statement with values given by parameter name
+    pub async fn execute_named(
+        &self,
+        statement: &PreparedStatement,
+        params: &[(&str, Value)],
+    ) -> SqlResult<QueryResult> {
+        let ast = statement.bind_named(params)?;
+        self.execute_statement(ast).await
+    }
+
+    /// Receive the rows changed by each commit from now on
+    ///
+    /// Every commit that inserted, updated or deleted rows sends them
+    /// together, in the order they were changed; ON DELETE actions show
+    /// up as changes of the referencing tables. Statements that fail and
+    /// transactions rolled back send nothing. Changes are captured only
+    /// while there are receivers.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<Arc<Vec<RowChange>>> {
+        self.changes.subscribe()
+    }
+
+    /// Read the foreign tables declared with `SERVER name` through `scan`,
+    /// in place of any server of that name
+    pub async fn register_foreign_server(&self, name: &str, scan: Arc<dyn ForeignScan>) {
+        self.query_processor.register_foreign_server(name, scan.clone()).await;
+        self.read_view().register_foreign_server(name, scan).await;
+    }
+
+    /// Capture the changes of the writer about to run if anyone receives
+    /// them; called once the writer lock is held
+    fn capture_changes(&self) {
+        self.query_processor.capture_changes(self.changes.receiver_count() > 0);
+    }
+
+    /// Send the changes of the writer that just committed
+    fn send_changes(&self) {
+        let changes = self.query_processor.take_changes();
+        if !changes.is_empty() {
+            // No receivers left is fine; capture stops at the next write
+            let _ = self.changes.send(Arc::new(changes));
+        }
+    }
+
+    /// Start a session, in which BEGIN, COMMIT and ROLLBACK can group
+    /// statements into a transaction
+    pub fn session(&self) -> Session {
+        Session::new(self.clone())
+    }
+
+    async fn execute_statement(&self, ast: Statement) -> SqlResult<QueryResult> {
+        match ast {
+            Statement::Transaction(_) => {
+                return Err(SqlError::transaction_error(
+                    "BEGIN, COMMIT and ROLLBACK need a session; see CategoricalSQLite::session",
+                ))
+            }
+            Statement::Vacuum => {
+                self.vacuum().await?;
+                return Ok(QueryResult::Vacuum);
+            }
+            _ => {}
+        }
+        if ast.is_read_only() {
+            return self.read_view().process_statement(ast).await;
+        }
+        // Wait for the current writer, which may be a session committing,
+        // to finish
+        let _writer = self.writer.lock().await;
+        self.capture_changes();
+
         // 2. Begin transaction if needed
         let mut tx_manager = self.transaction_manager.write().await;
         let tx = tx_manager.begin_transaction().await?;
         drop(tx_manager);
         
-        // 3. Process the statement through query processor
-        let result = self.query_processor.process_statement(ast).await;
+        // 3. Process the statement through query processor, then make its
+        //    changes durable
+        let tables = self.query_processor.written_tables(&ast).await;
+        let (result, edits) = self.process_write(ast, &tables).await;
+        match &edits {
+            Some(edits) => self.commit_versions(edits),
+            None => self.sync_versions(&tables).await,
+        }
+        self.publish(edits.as_deref()).await;
+        if result.is_ok() {
+            self.send_changes();
+        } else {
+            self.query_processor.take_changes();
+        }
         
         // 4. Handle transaction completion
         let mut tx_manager = self.transaction_manager.write().await;
@@ -105,6 +317,178 @@ impl CategoricalSQLite {
         }
     }
 
+    /// The tables as of the last commit
+    fn read_view(&self) -> Arc<QueryProcessor> {
+        self.read_view.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Let readers see the tables as they are now; called by the writer
+    /// once its changes are committed, with the rows it changed if that is
+    /// all it changed since the last call
+    ///
+    /// Readers get a snapshot, which shares its rows with the writer's
+    /// tables, so the writer's next change to a table would copy it. Given
+    /// the rows changed, the view published last is brought up to date
+    /// instead and replaces the snapshot, if no reader is still using it.
+    async fn publish(&self, edits: Option<&[(String, TableEdit)]>) {
+        let snapshot = Arc::new(self.query_processor.snapshot().await);
+        let previous = std::mem::replace(&mut *self.read_view.write().unwrap_or_else(|poisoned| poisoned.into_inner()), snapshot);
+        let Some(edits) = edits else {
+            return;
+        };
+        // Readers arriving from now on get the snapshot
+        if let Ok(view) = Arc::try_unwrap(previous) {
+            view.apply_edits(edits).await;
+            *self.read_view.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(view);
+        }
+    }
+
+    /// Commit the rows of `tables` as they are now to the row versions
+    /// session transactions read; a table that is gone has no rows
+    async fn sync_versions(&self, tables: &[String]) {
+        for table in tables {
+            let rows = self.query_processor.table_snapshot(table).await.map(|(_, rows)| rows);
+            self.mvcc.sync_table(table, rows.unwrap_or_default());
+        }
+    }
+
+    /// Commit the rows a write changed to the row versions session
+    /// transactions read
+    fn commit_versions(&self, edits: &[(String, TableEdit)]) {
+        let changes = edits
+            .iter()
+            .map(|(table, edit)| {
+                let changes = match edit {
+                    TableEdit::Append(rows) => TableChanges { inserted: rows.clone(), ..Default::default() },
+                    TableEdit::Update(rows) => TableChanges { updated: rows.clone(), ..Default::default() },
+                    TableEdit::Delete(positions) => TableChanges { deleted: positions.clone(), ..Default::default() },
+                };
+                (table.clone(), changes)
+            })
+            .collect();
+        self.mvcc.commit_changes(changes);
+    }
+
+    /// Run a statement writing `tables` and commit what it changed to the
+    /// database file, if there is one. If the commit fails the tables are
+    /// read back from the file, which still has them as they were. Also
+    /// returns the rows the statement changed, if that is all it changed.
+    async fn process_write(
+        &self,
+        statement: Statement,
+        tables: &[String],
+    ) -> (SqlResult<QueryResult>, Option<Vec<(String, TableEdit)>>) {
+        let result = self.query_processor.process_statement(statement.clone()).await;
+        let edits = self.query_processor.take_edits();
+        let result = match (result, &self.pager) {
+            // A failed statement leaves the tables as they were
+            (Err(error), _) => return (Err(error), Some(Vec::new())),
+            (Ok(result), Some(pager)) => {
+                if let Err(error) = self.persist(pager, &statement, tables, &edits).await {
+                    self.reload_tables(pager, tables).await;
+                    return (Err(error), None);
+                }
+                Ok(result)
+            }
+            (result, None) => result,
+        };
+        (result, changes_only_rows(&statement).then_some(edits))
+    }
+
+    /// Put `tables` back the way the database file has them
+    async fn reload_tables(&self, pager: &Pager, tables: &[String]) {
+        let mut transaction = pager.begin().await;
+        let stored: HashMap<String, TableSchema> = transaction.tables().into_iter().collect();
+        for table in tables {
+            match stored.get(table) {
+                Some(schema) => {
+                    // A file that cannot be read leaves the table as it is
+                    if let Ok(rows) = transaction.read_table(table).await {
+                        self.query_processor.restore_table(table.clone(), schema.clone(), rows).await;
+                    }
+                }
+                None => self.query_processor.remove_table(table).await,
+            }
+        }
+    }
+
+    async fn persist(
+        &self,
+        pager: &Pager,
+        statement: &Statement,
+        tables: &[String],
+        edits: &[(String, TableEdit)],
+    ) -> SqlResult<()> {
+        // EXPLAIN ANALYZE runs its statement, whose changes must be kept
+        if let Statement::Explain(explain) = statement {
+            if explain.mode == ExplainMode::Analyze {
+                return Box::pin(self.persist(pager, &explain.statement, tables, edits)).await;
+            }
+        }
+        let mut transaction = pager.begin().await;
+        match statement {
+            Statement::Insert(_) | Statement::Update(_) | Statement::Delete(_) => {
+                self.persist_edits(&mut transaction, tables, edits).await?
+            }
+            Statement::CreateTable(_)
+            | Statement::CreateVirtualTable(_)
+            | Statement::CreateForeignTable(_) => {
+                for table in tables {
+                    self.persist_table(&mut transaction, table).await?;
+                }
+            }
+            Statement::DropTable(drop) => transaction.drop_table(&drop.table_name).await?,
+            Statement::CreateIndex(create) => transaction.put_index(IndexInfo::new(
+                create.index_name.clone(),
+                create.table_name.clone(),
+                create.columns.clone(),
+                create.unique,
+            )),
+            Statement::DropIndex(drop) => transaction.drop_index(&drop.index_name),
+            Statement::Analyze(_) => {
+                let stats = self.query_processor.statistics().read().await.clone();
+                transaction.put_stats(stats);
+            }
+            Statement::Select(_)
+            | Statement::Explain(_)
+            | Statement::Transaction(_)
+            | Statement::Vacuum => return Ok(()),
+        }
+        transaction.commit().await
+    }
+
+    /// Write the rows `edits` changed in `tables`. Only those rows are
+    /// written, except to a table that is not in the file yet, e.g. one
+    /// registered outside SQL.
+    async fn persist_edits(
+        &self,
+        transaction: &mut PagerTransaction<'_>,
+        tables: &[String],
+        edits: &[(String, TableEdit)],
+    ) -> SqlResult<()> {
+        let stored: HashSet<String> = transaction.tables().into_iter().map(|(name, _)| name).collect();
+        for table in tables.iter().filter(|table| !stored.contains(*table)) {
+            self.persist_table(transaction, table).await?;
+        }
+        for (table, edit) in edits.iter().filter(|(table, _)| stored.contains(table)) {
+            match edit {
+                TableEdit::Append(rows) => transaction.append_rows(table, rows).await?,
+                TableEdit::Update(rows) => transaction.update_rows(table, rows).await?,
+                TableEdit::Delete(positions) => transaction.delete_rows(table, positions).await?,
+            }
+        }
+        Ok(())
+    }
+
+    async fn persist_table(&self, transaction: &mut PagerTransaction<'_>, table: &str) -> SqlResult<()> {
+        let (schema, rows) = self
+            .query_processor
+            .table_snapshot(table)
+            .await
+            .ok_or_else(|| SqlError::table_not_found(table.to_string()))?;
+        transaction.write_table(table, schema, &rows).await
+    }
+
     /// Execute a query with explicit transaction control
     pub async fn execute_in_transaction<F, R>(&self, f: F) -> SqlResult<R>
     where
@@ -187,13 +571,82 @@ impl CategoricalSQLite {
         registry.schema_names()
     }
 
+    /// Columns and types of a table created with SQL, if it exists
+    pub async fn table_schema(&self, name: &str) -> Option<TableSchema> {
+        self.query_processor.table_schema(name).await
+    }
+
+    /// Make a collation available to `COLLATE name` clauses; `compare`
+    /// orders two strings. Collations are shared by every database in the
+    /// process. Tables already using `name`, e.g. ones loaded from disk
+    /// before it was registered, are re-sorted and re-indexed by it;
+    /// session transactions already open keep their own copies.
+    pub async fn register_collation(
+        &self,
+        name: &str,
+        compare: impl Fn(&str, &str) -> std::cmp::Ordering + Send + Sync + 'static,
+    ) {
+        crate::collation::register_collation(name, compare);
+        let _writer = self.writer.lock().await;
+        for table in self.tables().await {
+            let Some((schema, rows)) = self.query_processor.table_snapshot(&table).await else {
+                continue;
+            };
+            let uses = schema
+                .columns
+                .iter()
+                .any(|column| column.collation.as_deref().is_some_and(|used| used.eq_ignore_ascii_case(name)));
+            if uses {
+                self.query_processor.restore_table(table, schema, rows).await;
+            }
+        }
+        self.publish(None).await;
+    }
+
+    /// Names of the tables created with SQL, sorted
+    pub async fn tables(&self) -> Vec<String> {
+        self.query_processor.table_names().await
+    }
+
+    /// Indexes on a table created with SQL, by name
+    pub async fn indexes_on(&self, table: &str) -> Vec<IndexInfo> {
+        self.query_processor.indexes_on(table).await
+    }
+
     /// Get index names from schema
     pub async fn get_index_names(&self) -> Vec<String> {
         let registry = self.schema_registry.read().await;
         registry.index_names()
     }
 
-    /// Perform database maintenance
+    /// Rebuild the database file without its free pages, shrinking it to
+    /// the pages in use. Waits for the write or commit in progress.
+    /// An in-memory database has nothing to reclaim.
+    pub async fn vacuum(&self) -> SqlResult<VacuumStats> {
+        let _writer = self.writer.lock().await;
+        match &self.pager {
+            Some(pager) => pager.vacuum().await,
+            None => Ok(VacuumStats {
+                pages_before: 0,
+                pages_after: 0,
+            }),
+        }
+    }
+
+    /// Pages in the database file and how many of them are free; zero
+    /// for an in-memory database
+    pub async fn page_counts(&self) -> (u32, u32) {
+        match &self.pager {
+            Some(pager) => {
+                let header = pager.header().await;
+                (header.page_count, header.freelist_count)
+            }
+            None => (0, 0),
+        }
+    }
+
+    /// Perform database maintenance, vacuuming the file when the auto-vacuum
+    /// mode asks for it and free pages make up `vacuum_threshold` of it
     pub async fn perform_maintenance(&self) -> SqlResult<()> {
         // Perform page cache maintenance
         self.page_cache.perform_maintenance().await?;
@@ -201,10 +654,20 @@ impl CategoricalSQLite {
         // Perform transaction log cleanup
         let mut tx_manager = self.transaction_manager.write().await;
         tx_manager.cleanup_completed_transactions().await?;
+        drop(tx_manager);
         
         // Perform schema validation
         let registry = self.schema_registry.read().await;
         registry.validate_all_schemas()?;
+        drop(registry);
+
+        let (pages, free) = self.page_counts().await;
+        if self.config.auto_vacuum != AutoVacuum::None
+            && free > 0
+            && free as f64 >= pages as f64 * self.config.vacuum_threshold
+        {
+            self.vacuum().await?;
+        }
         
         Ok(())
     }
@@ -216,6 +679,7 @@ impl CategoricalSQLite {
         let tx_stats = tx_manager.get_statistics();
         let registry = self.schema_registry.read().await;
         let schema_stats = registry.get_statistics();
+        let (page_count, free_pages) = self.page_counts().await;
         
         DatabaseStatistics {
             page_cache_stats,
@@ -223,51 +687,91 @@ impl CategoricalSQLite {
             schema_stats,
             total_tables: self.get_table_names().await.len(),
             total_indexes: self.get_index_names().await.len(),
+            page_count,
+            free_pages,
         }
     }
 
-    /// Backup database to file
-    pub async fn backup_to_file(&self, path: &str) -> SqlResult<()> {
-        // Simplified backup implementation
-        // In a real implementation, this would:
-        // 1. Create a consistent snapshot
-        // 2. Write all pages to backup file
-        // 3. Include schema and metadata
-        
-        // For now, just flush all dirty pages
-        self.page_cache.flush_all().await?;
-        
-        // Write backup marker
-        tokio::fs::write(path, b"SQLite backup placeholder").await
-            .map_err(|e| SqlError::io_error(e.to_string()))?;
-        
+    /// Write a full backup of the database to `path`. Writers are not
+    /// held up: the backup is a snapshot of the last commit.
+    pub async fn backup_to_file(&self, path: impl AsRef<Path>) -> SqlResult<BackupInfo> {
+        let backup = match &self.pager {
+            Some(pager) => Backup::take(pager, None).await?,
+            None => Backup::of_contents(&self.contents().await, self.config.page_size).await?,
+        };
+        backup.write(path)?;
+        Ok(BackupInfo::of(&backup))
+    }
+
+    /// Write the pages changed since the backup at `since_lsn` to `path`;
+    /// restoring needs that backup too
+    pub async fn backup_incremental(&self, path: impl AsRef<Path>, since_lsn: u64) -> SqlResult<BackupInfo> {
+        let pager = self
+            .pager
+            .as_ref()
+            .ok_or_else(|| SqlError::io_error("Incremental backups need a database file"))?;
+        let backup = Backup::take(pager, Some(since_lsn)).await?;
+        backup.write(path)?;
+        Ok(BackupInfo::of(&backup))
+    }
+
+    /// Replace the contents of the database with a full backup
+    pub async fn restore_from_file(&self, path: impl AsRef<Path>) -> SqlResult<()> {
+        self.restore_from_files(&[path]).await
+    }
+
+    /// Replace the contents of the database with a full backup and the
+    /// incremental backups taken after it, in order. Tables are rebuilt
+    /// from the backed-up rows and indexes from their definitions.
+    pub async fn restore_from_files(&self, paths: &[impl AsRef<Path>]) -> SqlResult<()> {
+        let chain = paths.iter().map(Backup::read).collect::<SqlResult<Vec<_>>>()?;
+        let contents = Backup::merge(chain)?.contents().await?;
+        let _writer = self.writer.lock().await;
+
+        if let Some(pager) = &self.pager {
+            let mut transaction = pager.begin().await;
+            for (name, _) in transaction.tables() {
+                transaction.drop_table(&name).await?;
+            }
+            for index in transaction.indexes() {
+                transaction.drop_index(&index.name);
+            }
+            for (name, schema, rows) in &contents.tables {
+                transaction.write_table(name, schema.clone(), rows).await?;
+            }
+            for index in &contents.indexes {
+                transaction.put_index(index.clone());
+            }
+            transaction.commit().await?;
+        }
+        self.install(contents).await?;
+        self.publish(None).await;
         Ok(())
     }
 
-    /// Restore database from file
-    pub async fn restore_from_file(&self, path: &str) -> SqlResult<()> {
-        // Simplified restore implementation
-        // In a real implementation, this would:
-        // 1. Read backup file
-        // 2. Restore all pages
-        // 3. Rebuild indexes
-        // 4. Validate consistency
-        
-        let _content = tokio::fs::read(path).await
-            .map_err(|e| SqlError::io_error(e.to_string()))?;
-        
-        // Clear current state
-        self.page_cache.clear().await?;
-        
-        let mut btrees = self.btrees.write().await;
-        btrees.clear();
-        
-        let mut registry = self.schema_registry.write().await;
-        registry.clear();
-        
+    /// Replace the in-memory tables and indexes with `contents`, and the
+    /// row versions of the tables removed or replaced with them
+    async fn install(&self, contents: BackupContents) -> SqlResult<()> {
+        let mut replaced = self.query_processor.table_names().await;
+        for name in &replaced {
+            self.query_processor.remove_table(name).await;
+        }
+        for (name, schema, rows) in contents.tables {
+            replaced.push(name.clone());
+            self.query_processor.restore_table(name, schema, rows).await;
+        }
+        for index in contents.indexes {
+            self.query_processor.restore_index(index).await?;
+        }
+        self.sync_versions(&replaced).await;
         Ok(())
     }
 
+    async fn contents(&self) -> BackupContents {
+        let (tables, indexes) = self.query_processor.database_snapshot().await;
+        BackupContents { tables, indexes }
+    }
+
     /// Close the database
     pub async fn close(&self) -> SqlResult<()> {
         // Commit any pending transactions
@@ -276,6 +780,7 @@ impl CategoricalSQLite {
         
         // Flush all dirty pages
         self.page_cache.flush_all().await?;
+        self.checkpoint().await?;
         
         // Clear caches
         self.page_cache.clear().await?;
@@ -327,6 +832,41 @@ pub struct DatabaseStatistics {
     pub schema_stats: crate::schema::SchemaStatistics,
     pub total_tables: usize,
     pub total_indexes: usize,
+    /// Pages in the database file, and those of them on the freelist
+    pub page_count: u32,
+    pub free_pages: u32,
+}
+
+/// What a backup holds, to find the LSN the next incremental backup
+/// starts from
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub kind: BackupKind,
+    /// LSN of the last commit in the backup
+    pub lsn: u64,
+    /// LSN an incremental backup was taken since; 0 for a full backup
+    pub base_lsn: u64,
+    pub pages: usize,
+}
+
+impl BackupInfo {
+    fn of(backup: &Backup) -> Self {
+        BackupInfo {
+            kind: backup.kind,
+            lsn: backup.lsn,
+            base_lsn: backup.base_lsn,
+            pages: backup.pages.len(),
+        }
+    }
+}
+
+/// Whether all a statement changes are rows of existing tables
+fn changes_only_rows(statement: &Statement) -> bool {
+    match statement {
+        Statement::Insert(_) | Statement::Update(_) | Statement::Delete(_) => true,
+        Statement::Explain(explain) => explain.mode == ExplainMode::Analyze && changes_only_rows(&explain.statement),
+        _ => false,
+    }
 }
 
 /// Health status
@@ -341,12 +881,18 @@ impl Clone for CategoricalSQLite {
     fn clone(&self) -> Self {
         CategoricalSQLite {
             page_cache: Arc::clone(&self.page_cache),
+            pager: self.pager.clone(),
             btrees: Arc::clone(&self.btrees),
             query_processor: Arc::clone(&self.query_processor),
+            read_view: Arc::clone(&self.read_view),
+            statements: Arc::clone(&self.statements),
             transaction_manager: Arc::clone(&self.transaction_manager),
+            mvcc: Arc::clone(&self.mvcc),
+            writer: Arc::clone(&self.writer),
             schema_registry: Arc::clone(&self.schema_registry),
             config: self.config.clone(),
             current_mode: Arc::clone(&self.current_mode),
+            changes: self.changes.clone(),
         }
     }
 }
@@ -354,6 +900,7 @@ impl Clone for CategoricalSQLite {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{Row, Value};
 
     #[tokio::test]
     async fn test_engine_creation() {
@@ -385,4 +932,479 @@ mod tests {
         assert!(health.is_healthy);
         assert!(health.issues.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_database_file_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+
+        {
+            let engine = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+            engine
+                .execute_sql("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")
+                .await
+                .unwrap();
+            for (name, age) in [("Alice", 30), ("Bob", 25), ("Carol", 41)] {
+                let sql = format!("INSERT INTO users (name, age) VALUES ('{}', {})", name, age);
+                engine.execute_sql(&sql).await.unwrap();
+            }
+            engine.execute_sql("UPDATE users SET age = 26 WHERE name = 'Bob'").await.unwrap();
+            engine.execute_sql("DELETE FROM users WHERE age > 40").await.unwrap();
+            engine.execute_sql("CREATE UNIQUE INDEX idx_users_name ON users (name)").await.unwrap();
+            // A statement that fails leaves the file untouched
+            assert!(engine.execute_sql("INSERT INTO users (name) VALUES ('Alice')").await.is_err());
+            engine.close().await.unwrap();
+        }
+
+        let engine = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+        match engine.execute_sql("SELECT id, name, age FROM users ORDER BY id").await.unwrap() {
+            QueryResult::Select { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    Row::new(vec![Value::Integer(1), Value::Text("Alice".to_string()), Value::Integer(30)]),
+                    Row::new(vec![Value::Integer(2), Value::Text("Bob".to_string()), Value::Integer(26)]),
+                ]
+            ),
+            other => panic!("Expected SELECT result, got {:?}", other),
+        }
+        // The unique index was rebuilt from the stored rows
+        assert!(engine.execute_sql("INSERT INTO users (name) VALUES ('Bob')").await.is_err());
+
+        engine.execute_sql("DROP TABLE users").await.unwrap();
+        drop(engine);
+        let engine = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+        assert!(engine.execute_sql("INSERT INTO users (name) VALUES ('Dan')").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_writes_commit_only_the_rows_they_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("writes.db");
+        let config = DatabaseConfig {
+            auto_checkpoint: false,
+            ..DatabaseConfig::default()
+        };
+        let rows = |engine: CategoricalSQLite, sql: &'static str| async move {
+            match engine.execute_sql(sql).await.unwrap() {
+                QueryResult::Select { rows, .. } => rows,
+                other => panic!("Expected SELECT result, got {:?}", other),
+            }
+        };
+
+        let engine = CategoricalSQLite::open(&path, config.clone()).await.unwrap();
+        engine.execute_sql("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT UNIQUE)").await.unwrap();
+        engine
+            .execute_sql(
+                "CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users ON DELETE CASCADE, \
+                 editor_id INTEGER REFERENCES users ON DELETE SET NULL)",
+            )
+            .await
+            .unwrap();
+        let values: Vec<String> = (1..=4000).map(|n| format!("('user{}')", n)).collect();
+        engine
+            .execute_sql(&format!("INSERT INTO users (name) VALUES {}", values.join(", ")))
+            .await
+            .unwrap();
+
+        // Each write commits a few pages however large the table is
+        let pager = engine.pager.clone().unwrap();
+        for sql in [
+            "INSERT INTO users (name) VALUES ('late')",
+            "UPDATE users SET name = 'middle' WHERE id = 2000",
+            "DELETE FROM users WHERE id = 1000",
+        ] {
+            let frames = pager.wal_frames();
+            engine.execute_sql(sql).await.unwrap();
+            assert!(pager.wal_frames() - frames <= 4, "{} wrote {} pages", sql, pager.wal_frames() - frames);
+        }
+        assert!(engine.execute_sql("INSERT INTO users (name) VALUES ('middle')").await.is_err());
+        assert!(engine.execute_sql("UPDATE users SET name = 'late' WHERE id = 3").await.is_err());
+
+        engine
+            .execute_sql("INSERT INTO posts (user_id, editor_id) VALUES (1, 2), (2, 1), (3, 3), (2, 4)")
+            .await
+            .unwrap();
+        engine.execute_sql("DELETE FROM users WHERE id <= 2").await.unwrap();
+        // The next rowid follows the largest key, not the row count
+        engine.execute_sql("INSERT INTO users (name) VALUES ('last')").await.unwrap();
+        let expected = rows(engine.clone(), "SELECT id, name FROM users ORDER BY id").await;
+        assert_eq!(expected.len(), 3999);
+        assert_eq!(expected[3998].values[0], Value::Integer(4002));
+        engine.close().await.unwrap();
+        drop(engine);
+
+        let engine = CategoricalSQLite::open(&path, config).await.unwrap();
+        assert_eq!(rows(engine.clone(), "SELECT id, name FROM users ORDER BY id").await, expected);
+        assert_eq!(
+            rows(engine.clone(), "SELECT id, user_id, editor_id FROM posts").await,
+            vec![Row::new(vec![Value::Integer(3), Value::Integer(3), Value::Integer(3)])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_statistics_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.db");
+        let stats = |engine: CategoricalSQLite| async move {
+            let sql = "SELECT table_name, column_name, row_count, distinct_count, max FROM catalog.stats";
+            match engine.execute_sql(sql).await.unwrap() {
+                QueryResult::Select { rows, .. } => rows,
+                other => panic!("Expected SELECT result, got {:?}", other),
+            }
+        };
+
+        let engine = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+        engine.execute_sql("CREATE TABLE t (id INTEGER PRIMARY KEY, n INTEGER)").await.unwrap();
+        engine.execute_sql("CREATE TABLE u (id INTEGER PRIMARY KEY)").await.unwrap();
+        for n in [1, 2, 2, 5] {
+            engine.execute_sql(&format!("INSERT INTO t (n) VALUES ({})", n)).await.unwrap();
+        }
+        engine.execute_sql("ANALYZE").await.unwrap();
+        let analyzed = stats(engine.clone()).await;
+        assert_eq!(analyzed.len(), 3);
+        engine.close().await.unwrap();
+
+        let engine = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+        assert_eq!(stats(engine.clone()).await, analyzed);
+        assert_eq!(
+            analyzed[1],
+            Row::new(vec![
+                Value::Text("t".to_string()),
+                Value::Text("n".to_string()),
+                Value::Integer(4),
+                Value::Integer(3),
+                Value::Integer(5),
+            ])
+        );
+
+        // Statistics go with their table
+        engine.execute_sql("DROP TABLE t").await.unwrap();
+        drop(engine);
+        let engine = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+        assert_eq!(stats(engine).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_and_maintenance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let config = DatabaseConfig {
+            page_size: 1024,
+            auto_vacuum: AutoVacuum::None,
+            ..DatabaseConfig::default()
+        };
+        let engine = CategoricalSQLite::open(&path, config.clone()).await.unwrap();
+        engine.execute_sql("CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT)").await.unwrap();
+        for i in 0..200 {
+            let sql = format!("INSERT INTO notes (body) VALUES ('note number {} with some text')", i);
+            engine.execute_sql(&sql).await.unwrap();
+        }
+        engine.execute_sql("DELETE FROM notes WHERE id > 10").await.unwrap();
+        let (pages, free) = engine.page_counts().await;
+        assert!(free > pages / 2);
+
+        // Without auto-vacuum, maintenance leaves the free pages alone
+        engine.perform_maintenance().await.unwrap();
+        assert_eq!(engine.page_counts().await, (pages, free));
+
+        let mut session = engine.session();
+        session.execute_sql("BEGIN").await.unwrap();
+        assert!(session.execute_sql("VACUUM").await.is_err());
+        session.execute_sql("ROLLBACK").await.unwrap();
+        drop(session);
+        assert!(matches!(engine.execute_sql("VACUUM").await, Ok(QueryResult::Vacuum)));
+        let (vacuumed, free) = engine.page_counts().await;
+        assert_eq!(free, 0);
+        assert!(vacuumed < pages / 2);
+        engine.close().await.unwrap();
+
+        let config = DatabaseConfig {
+            auto_vacuum: AutoVacuum::Incremental,
+            ..config
+        };
+        let engine = CategoricalSQLite::open(&path, config).await.unwrap();
+        match engine.execute_sql("SELECT COUNT(*) FROM notes").await.unwrap() {
+            QueryResult::Select { rows, .. } => assert_eq!(rows[0].values[0], Value::Integer(10)),
+            other => panic!("Expected SELECT result, got {:?}", other),
+        }
+        for i in 0..100 {
+            let sql = format!("INSERT INTO notes (body) VALUES ('another note {} with more text')", i);
+            engine.execute_sql(&sql).await.unwrap();
+        }
+        engine.execute_sql("DELETE FROM notes WHERE id > 2").await.unwrap();
+        assert!(engine.page_counts().await.1 > 0);
+        engine.perform_maintenance().await.unwrap();
+        assert_eq!(engine.page_counts().await.1, 0);
+        assert_eq!(engine.get_statistics().await.free_pages, 0);
+    }
+
+    #[tokio::test]
+    async fn test_readers_do_not_wait_for_the_writer() {
+        let engine = CategoricalSQLite::new(DatabaseConfig::default());
+        engine.execute_sql("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT)").await.unwrap();
+        engine.execute_sql("INSERT INTO items (name) VALUES ('a'), ('b')").await.unwrap();
+        engine.execute_sql("CREATE INDEX idx_name ON items (name)").await.unwrap();
+
+        let mut session = engine.session();
+        session.execute_sql("BEGIN").await.unwrap();
+        session.execute_sql("INSERT INTO items (name) VALUES ('c')").await.unwrap();
+        session.execute_sql("DROP INDEX idx_name").await.unwrap();
+
+        // Readers on other threads see the last commit while the session
+        // is writing, without waiting for it
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+                    runtime.block_on(async {
+                        let count = engine.execute_sql("SELECT COUNT(*) FROM items").await.unwrap();
+                        let plan = engine
+                            .execute_sql("EXPLAIN QUERY PLAN SELECT id FROM items WHERE name = 'a'")
+                            .await
+                            .unwrap();
+                        (count.rows().unwrap()[0].values[0].clone(), plan.to_string().contains("idx_name"))
+                    })
+                })
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), (Value::Integer(2), true));
+        }
+
+        session.execute_sql("COMMIT").await.unwrap();
+        let count = engine.execute_sql("SELECT COUNT(*) FROM items").await.unwrap();
+        assert_eq!(count.rows().unwrap()[0].values[0], Value::Integer(3));
+        assert!(engine.execute_sql("DROP INDEX idx_name").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prepared_statements() {
+        let engine = CategoricalSQLite::new(DatabaseConfig::default());
+        engine
+            .execute_sql("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")
+            .await
+            .unwrap();
+
+        let insert = engine.prepare("INSERT INTO users (name, age) VALUES (?, ?)").unwrap();
+        for (name, age) in [("Alice", 30), ("O'Brien', 0); DROP TABLE users; --", 41), ("Carol", 25)] {
+            engine
+                .execute(&insert, &[Value::Text(name.to_string()), Value::Integer(age)])
+                .await
+                .unwrap();
+        }
+        assert!(engine.execute(&insert, &[Value::Text("Dan".to_string())]).await.is_err());
+
+        let select = engine.prepare("SELECT name FROM users WHERE age > :min ORDER BY id").unwrap();
+        assert!(Arc::ptr_eq(&select, &engine.prepare("SELECT name FROM users WHERE age > :min ORDER BY id").unwrap()));
+        match engine.execute_named(&select, &[("min", Value::Integer(26))]).await.unwrap() {
+            QueryResult::Select { rows, .. } => assert_eq!(
+                rows,
+                vec![
+                    Row::new(vec![Value::Text("Alice".to_string())]),
+                    Row::new(vec![Value::Text("O'Brien', 0); DROP TABLE users; --".to_string())]),
+                ]
+            ),
+            other => panic!("Expected SELECT result, got {:?}", other),
+        }
+        // Unbound parameters are an error, not NULL
+        assert!(engine.execute_sql("SELECT name FROM users WHERE age > ?").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_change_feed() {
+        use crate::types::ChangeKind;
+
+        let engine = CategoricalSQLite::new(DatabaseConfig::default());
+        engine.execute_sql("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)").await.unwrap();
+        engine
+            .execute_sql("CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users ON DELETE SET NULL)")
+            .await
+            .unwrap();
+        let mut changes = engine.subscribe_changes();
+
+        engine.execute_sql("INSERT INTO users (name) VALUES ('ann'), ('bob')").await.unwrap();
+        let inserted = changes.recv().await.unwrap();
+        assert_eq!(inserted.len(), 2);
+        assert_eq!(inserted[1].kind, ChangeKind::Insert);
+        assert_eq!(inserted[1].new_value("id"), Some(&Value::Integer(2)));
+        assert_eq!(inserted[1].old, None);
+
+        // Failed statements and rolled back transactions send nothing
+        assert!(engine.execute_sql("INSERT INTO users (name) VALUES (NULL)").await.is_err());
+        let mut session = engine.session();
+        session.execute_sql("BEGIN").await.unwrap();
+        session.execute_sql("DELETE FROM users").await.unwrap();
+        session.execute_sql("ROLLBACK").await.unwrap();
+        assert!(changes.try_recv().is_err());
+
+        // A transaction sends its changes together at COMMIT
+        session.execute_sql("BEGIN").await.unwrap();
+        session.execute_sql("UPDATE users SET name = 'bea' WHERE id = 2").await.unwrap();
+        session.execute_sql("INSERT INTO posts (user_id) VALUES (2)").await.unwrap();
+        assert!(changes.try_recv().is_err());
+        session.execute_sql("COMMIT").await.unwrap();
+        let committed = changes.recv().await.unwrap();
+        assert_eq!(committed.len(), 2);
+        assert_eq!(committed[0].kind, ChangeKind::Update);
+        assert_eq!(committed[0].old_value("name"), Some(&Value::Text("bob".to_string())));
+        assert_eq!(committed[0].new_value("name"), Some(&Value::Text("bea".to_string())));
+        assert_eq!(committed[1].table, "posts");
+
+        // ON DELETE actions are changes of the referencing table
+        engine.execute_sql("DELETE FROM users WHERE id = 2").await.unwrap();
+        let deleted = changes.recv().await.unwrap();
+        assert_eq!(deleted.len(), 2);
+        assert_eq!((deleted[0].table.as_str(), deleted[0].kind), ("users", ChangeKind::Delete));
+        assert_eq!(deleted[0].new, None);
+        assert_eq!((deleted[1].table.as_str(), deleted[1].kind), ("posts", ChangeKind::Update));
+        assert_eq!(deleted[1].new_value("user_id"), Some(&Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_online_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = CategoricalSQLite::open(dir.path().join("live.db"), DatabaseConfig::default()).await.unwrap();
+        engine
+            .execute_sql("CREATE TABLE events (id INTEGER PRIMARY KEY, payload TEXT)")
+            .await
+            .unwrap();
+        engine.execute_sql("CREATE INDEX idx_events_payload ON events (payload)").await.unwrap();
+        for _ in 0..20 {
+            engine.execute_sql("INSERT INTO events (payload) VALUES ('before')").await.unwrap();
+        }
+
+        // Writes keep committing while the full backup is taken
+        let writer = async {
+            for _ in 0..20 {
+                engine.execute_sql("INSERT INTO events (payload) VALUES ('during')").await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        };
+        let (full, _) = tokio::join!(engine.backup_to_file(dir.path().join("full.bak")), writer);
+        let full = full.unwrap();
+        assert_eq!(full.kind, BackupKind::Full);
+        engine.execute_sql("DELETE FROM events WHERE id <= 5").await.unwrap();
+        let incremental = engine
+            .backup_incremental(dir.path().join("incremental.bak"), full.lsn)
+            .await
+            .unwrap();
+        assert_eq!(incremental.base_lsn, full.lsn);
+        assert!(incremental.pages < full.pages + 20);
+
+        // The full backup holds a prefix of the inserts, never part of one
+        let restored = CategoricalSQLite::new(DatabaseConfig::default());
+        restored.restore_from_file(dir.path().join("full.bak")).await.unwrap();
+        let ids = count_rows(&restored).await;
+        assert!(ids.len() >= 20);
+        assert_eq!(ids, (1..=ids.len() as i64).collect::<Vec<_>>());
+        // An in-memory database is backed up from its tables
+        restored.backup_to_file(dir.path().join("memory.bak")).await.unwrap();
+        let copy = CategoricalSQLite::new(DatabaseConfig::default());
+        copy.restore_from_file(dir.path().join("memory.bak")).await.unwrap();
+        assert_eq!(count_rows(&copy).await, ids);
+
+        let restored = CategoricalSQLite::open(dir.path().join("restored.db"), DatabaseConfig::default()).await.unwrap();
+        restored
+            .restore_from_files(&[dir.path().join("full.bak"), dir.path().join("incremental.bak")])
+            .await
+            .unwrap();
+        assert_eq!(count_rows(&restored).await, (6..=40).collect::<Vec<_>>());
+        // The index was rebuilt from its definition
+        assert!(restored
+            .execute_sql("CREATE INDEX idx_events_payload ON events (payload)")
+            .await
+            .is_err());
+        drop(restored);
+        let reopened = CategoricalSQLite::open(dir.path().join("restored.db"), DatabaseConfig::default()).await.unwrap();
+        assert_eq!(count_rows(&reopened).await, (6..=40).collect::<Vec<_>>());
+
+        assert!(engine.restore_from_file(dir.path().join("incremental.bak")).await.is_err());
+        assert!(CategoricalSQLite::new(DatabaseConfig::default())
+            .backup_incremental(dir.path().join("memory.bak"), 0)
+            .await
+            .is_err());
+    }
+
+    /// Set in the child process of `test_kill_and_restart` to the database
+    /// it should write to until it is killed
+    const CRASH_DB_VAR: &str = "CATEGORICAL_SQLITE_CRASH_DB";
+
+    async fn count_rows(engine: &CategoricalSQLite) -> Vec<i64> {
+        match engine.execute_sql("SELECT id FROM events ORDER BY id").await.unwrap() {
+            QueryResult::Select { rows, .. } => rows
+                .iter()
+                .map(|row| match &row.values[0] {
+                    Value::Integer(id) => *id,
+                    other => panic!("Expected integer id, got {:?}", other),
+                })
+                .collect(),
+            other => panic!("Expected SELECT result, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kill_and_restart() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::process::{Command, Stdio};
+
+        let config = DatabaseConfig {
+            wal_sync_mode: crate::transaction::WalSyncMode::Full,
+            checkpoint_threshold: 16,
+            ..DatabaseConfig::default()
+        };
+
+        // Child: commit rows one at a time, reporting each commit, until
+        // the parent kills the process
+        if let Ok(path) = std::env::var(CRASH_DB_VAR) {
+            let engine = CategoricalSQLite::open(&path, config).await.unwrap();
+            let mut stdout = std::io::stdout();
+            loop {
+                engine
+                    .execute_sql("INSERT INTO events (payload) VALUES ('some event payload')")
+                    .await
+                    .unwrap();
+                writeln!(stdout, "committed").unwrap();
+                stdout.flush().unwrap();
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("crash.db");
+        {
+            let engine = CategoricalSQLite::open(&path, config.clone()).await.unwrap();
+            engine
+                .execute_sql("CREATE TABLE events (id INTEGER PRIMARY KEY, payload TEXT)")
+                .await
+                .unwrap();
+        }
+
+        let mut committed = 0;
+        for round in 0..3 {
+            let mut child = Command::new(std::env::current_exe().unwrap())
+                .args(["engine::tests::test_kill_and_restart", "--exact", "--nocapture", "--test-threads=1"])
+                .env(CRASH_DB_VAR, &path)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+            let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+            // Kill at a different point of the checkpoint cycle each round
+            for _ in 0..20 + round * 7 {
+                let line = lines.next().expect("child exited early").unwrap();
+                if line == "committed" {
+                    committed += 1;
+                }
+            }
+            child.kill().unwrap();
+            child.wait().unwrap();
+
+            // Every reported commit survived, and nothing was half-applied
+            let engine = CategoricalSQLite::open(&path, config.clone()).await.unwrap();
+            let ids = count_rows(&engine).await;
+            assert!(ids.len() >= committed, "lost commits: {} < {}", ids.len(), committed);
+            assert_eq!(ids, (1..=ids.len() as i64).collect::<Vec<_>>());
+            committed = ids.len();
+        }
+    }
 }
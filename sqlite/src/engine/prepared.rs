@@ -0,0 +1,204 @@
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::Statement;
+use crate::parser::parse_sql_with_parameters;
+use crate::types::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// A parsed statement whose placeholders are bound on every execution
+///
+/// Placeholders are `?`, `?NNN`, `:name`, `@name` and `$name`. Values are
+/// bound as literals, so they are never interpolated into SQL text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedStatement {
+    sql: String,
+    statement: Statement,
+    /// Name of each parameter by index; `None` for positional ones
+    parameters: Vec<Option<String>>,
+}
+
+impl PreparedStatement {
+    pub fn new(sql: impl Into<String>) -> SqlResult<Self> {
+        let sql = sql.into();
+        let (statement, parameters) = parse_sql_with_parameters(&sql)?;
+        Ok(PreparedStatement { sql, statement, parameters })
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// The largest parameter index used
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// Name of parameter `index` (counting from 1) with its prefix, e.g.
+    /// `:name`; `None` for positional parameters
+    pub fn parameter_name(&self, index: usize) -> Option<&str> {
+        self.parameters.get(index.checked_sub(1)?)?.as_deref()
+    }
+
+    /// Index of a named parameter; the prefix may be left out
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameters
+            .iter()
+            .position(|parameter| {
+                parameter
+                    .as_deref()
+                    .is_some_and(|parameter| parameter == name || parameter[1..] == *name)
+            })
+            .map(|position| position + 1)
+    }
+
+    /// The statement with `values[N - 1]` in place of every `?N`
+    pub fn bind(&self, values: &[Value]) -> SqlResult<Statement> {
+        if values.len() != self.parameter_count() {
+            return Err(SqlError::runtime_error(format!(
+                "Statement expects {} parameter(s), {} given",
+                self.parameter_count(),
+                values.len()
+            )));
+        }
+        let mut statement = self.statement.clone();
+        statement.bind_parameters(values)?;
+        Ok(statement)
+    }
+
+    /// The statement with named parameters bound; positional parameters
+    /// can be given as `?N`
+    pub fn bind_named(&self, values: &[(&str, Value)]) -> SqlResult<Statement> {
+        let mut positional: Vec<Option<Value>> = vec![None; self.parameter_count()];
+        for (name, value) in values {
+            let index = match name.strip_prefix('?') {
+                Some(digits) => digits.parse().ok().filter(|index| (1..=positional.len()).contains(index)),
+                None => self.parameter_index(name),
+            }
+            .ok_or_else(|| SqlError::runtime_error(format!("Statement has no parameter {}", name)))?;
+            positional[index - 1] = Some(value.clone());
+        }
+        let values = positional
+            .into_iter()
+            .enumerate()
+            .map(|(position, value)| {
+                value.ok_or_else(|| {
+                    let name = self.parameter_name(position + 1).map_or(format!("?{}", position + 1), str::to_string);
+                    SqlError::runtime_error(format!("Parameter {} is not bound", name))
+                })
+            })
+            .collect::<SqlResult<Vec<_>>>()?;
+        self.bind(&values)
+    }
+}
+
+/// Prepared statements keyed by SQL text, so repeated SQL is parsed once;
+/// the least recently prepared statement is evicted past `capacity`.
+/// Plans are still built on every execution so they follow schema and
+/// index changes.
+#[derive(Debug)]
+pub struct StatementCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    statements: HashMap<String, Arc<PreparedStatement>>,
+    order: VecDeque<String>,
+    hits: u64,
+    misses: u64,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> Self {
+        StatementCache {
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    pub fn prepare(&self, sql: &str) -> SqlResult<Arc<PreparedStatement>> {
+        if let Some(statement) = self.lookup(sql) {
+            return Ok(statement);
+        }
+        let statement = Arc::new(PreparedStatement::new(sql)?);
+        if self.capacity > 0 {
+            let mut entries = self.entries.lock().unwrap();
+            if entries.statements.insert(sql.to_string(), statement.clone()).is_none() {
+                entries.order.push_back(sql.to_string());
+            }
+            while entries.order.len() > self.capacity {
+                if let Some(evicted) = entries.order.pop_front() {
+                    entries.statements.remove(&evicted);
+                }
+            }
+        }
+        Ok(statement)
+    }
+
+    fn lookup(&self, sql: &str) -> Option<Arc<PreparedStatement>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.statements.get(sql).cloned() {
+            Some(statement) => {
+                entries.hits += 1;
+                Some(statement)
+            }
+            None => {
+                entries.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Number of `prepare` calls answered from and missing the cache
+    pub fn stats(&self) -> (u64, u64) {
+        let entries = self.entries.lock().unwrap();
+        (entries.hits, entries.misses)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().statements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_positional_and_named() {
+        let statement = PreparedStatement::new("SELECT * FROM users WHERE age > ? AND name = :name").unwrap();
+        assert_eq!(statement.parameter_count(), 2);
+        assert_eq!(statement.parameter_name(2), Some(":name"));
+        assert_eq!(statement.parameter_index("name"), Some(2));
+
+        let positional = statement
+            .bind(&[Value::Integer(30), Value::Text("O'Brien".to_string())])
+            .unwrap();
+        let named = statement
+            .bind_named(&[(":name", Value::Text("O'Brien".to_string())), ("?1", Value::Integer(30))])
+            .unwrap();
+        assert_eq!(positional, named);
+
+        assert!(statement.bind(&[Value::Integer(30)]).is_err());
+        assert!(statement.bind_named(&[("?1", Value::Integer(30))]).is_err());
+        assert!(statement.bind_named(&[(":other", Value::Null)]).is_err());
+    }
+
+    #[test]
+    fn test_statement_cache_evicts_oldest() {
+        let cache = StatementCache::new(2);
+        let first = cache.prepare("SELECT * FROM a WHERE id = ?").unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.prepare("SELECT * FROM a WHERE id = ?").unwrap()));
+        cache.prepare("SELECT * FROM b").unwrap();
+        cache.prepare("SELECT * FROM c").unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(!Arc::ptr_eq(&first, &cache.prepare("SELECT * FROM a WHERE id = ?").unwrap()));
+        assert_eq!(cache.stats(), (1, 4));
+        assert!(cache.prepare("SELECT * FROM").is_err());
+    }
+}
@@ -0,0 +1,629 @@
+use crate::engine::{changes_only_rows, CategoricalSQLite, PreparedStatement};
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::{ExplainMode, Statement, TransactionStatement};
+use crate::query::{QueryProcessor, QueryResult, TableEdit, TableSchema};
+use crate::transaction::{IsolationLevel, MvccTransaction, TableChanges};
+use crate::types::{RowChange, Value};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Transaction state of a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// No transaction is open; every statement commits on its own
+    Idle,
+    /// Inside BEGIN ... COMMIT
+    InTransaction,
+    /// A statement of the open transaction failed; everything up to
+    /// COMMIT or ROLLBACK is refused, and either ends it rolled back
+    Failed,
+}
+
+/// One client's connection to the database
+///
+/// Outside a transaction a statement runs as `execute_sql` would. BEGIN
+/// starts a snapshot transaction: its statements run on a copy of the
+/// tables as of BEGIN, which shares their rows until it changes them, and
+/// the rows they change are kept as the writes of an [`MvccTransaction`]
+/// until COMMIT. Other sessions and writers neither wait for it nor see
+/// its changes before COMMIT, and it does not see what they commit
+/// meanwhile.
+///
+/// COMMIT fails with a serialization failure, and the transaction can be
+/// retried from the start, if another commit since BEGIN changed a row it
+/// changed too (the first committer wins) or, under Serializable
+/// isolation, a table it read. The weaker isolation levels get snapshot
+/// isolation, as rows are not locked. Unique keys are checked again at
+/// COMMIT against what others committed; foreign keys are not. ROLLBACK
+/// discards the transaction.
+#[derive(Debug)]
+pub struct Session {
+    db: CategoricalSQLite,
+    transaction: Option<OpenTransaction>,
+}
+
+#[derive(Debug)]
+struct OpenTransaction {
+    /// Id in the transaction manager
+    id: Uuid,
+    /// Rows read and written, against the snapshot taken at BEGIN
+    rows: MvccTransaction,
+    /// The transaction's copy of the tables, which its statements run on
+    workspace: QueryProcessor,
+    /// Tables the transaction may have changed
+    written: BTreeSet<String>,
+    /// Statements that created or dropped tables and indexes, replayed on
+    /// the database at COMMIT
+    schema_changes: Vec<Statement>,
+    /// Rows changed, sent to change subscribers at COMMIT
+    changes: Vec<RowChange>,
+    failed: bool,
+}
+
+impl Session {
+    pub fn new(db: CategoricalSQLite) -> Self {
+        Session { db, transaction: None }
+    }
+
+    pub fn database(&self) -> &CategoricalSQLite {
+        &self.db
+    }
+
+    pub fn status(&self) -> TransactionStatus {
+        match &self.transaction {
+            None => TransactionStatus::Idle,
+            Some(transaction) if transaction.failed => TransactionStatus::Failed,
+            Some(_) => TransactionStatus::InTransaction,
+        }
+    }
+
+    /// Columns and types of a table as the session sees it, which inside a
+    /// transaction includes the tables it created or dropped
+    pub async fn table_schema(&self, name: &str) -> Option<TableSchema> {
+        match &self.transaction {
+            Some(transaction) => transaction.workspace.table_schema(name).await,
+            None => self.db.table_schema(name).await,
+        }
+    }
+
+    pub async fn execute_sql(&mut self, sql: &str) -> SqlResult<QueryResult> {
+        let statement = self.db.prepare(sql)?;
+        self.execute(&statement, &[]).await
+    }
+
+    /// Run a prepared statement with one value per parameter, in order
+    pub async fn execute(&mut self, statement: &PreparedStatement, params: &[Value]) -> SqlResult<QueryResult> {
+        let ast = statement.bind(params)?;
+        self.execute_statement(ast).await
+    }
+
+    async fn execute_statement(&mut self, ast: Statement) -> SqlResult<QueryResult> {
+        if let Statement::Transaction(command) = ast {
+            return self.run_command(command).await;
+        }
+        let Some(transaction) = &mut self.transaction else {
+            return self.db.execute_statement(ast).await;
+        };
+        if transaction.failed {
+            return Err(SqlError::transaction_error(
+                "current transaction is aborted, commands ignored until end of transaction block",
+            ));
+        }
+        if matches!(ast, Statement::Vacuum) {
+            return Err(SqlError::transaction_error("cannot VACUUM from within a transaction"));
+        }
+        let result = self.db.run_in_transaction(transaction, ast).await;
+        transaction.failed = result.is_err();
+        result
+    }
+
+    async fn run_command(&mut self, command: TransactionStatement) -> SqlResult<QueryResult> {
+        match (command, self.transaction.take()) {
+            (TransactionStatement::Begin, Some(transaction)) => {
+                self.transaction = Some(transaction);
+                Err(SqlError::transaction_error("there is already a transaction in progress"))
+            }
+            (TransactionStatement::Begin, None) => {
+                self.transaction = Some(self.db.begin_session().await?);
+                Ok(QueryResult::Begin)
+            }
+            (TransactionStatement::Commit | TransactionStatement::Rollback, None) => {
+                Err(SqlError::transaction_error("there is no transaction in progress"))
+            }
+            (TransactionStatement::Commit, Some(transaction)) if !transaction.failed => {
+                self.db.commit_session(transaction).await?;
+                Ok(QueryResult::Commit)
+            }
+            (_, Some(transaction)) => {
+                self.db.rollback_session(transaction).await?;
+                Ok(QueryResult::Rollback)
+            }
+        }
+    }
+
+    /// End the session, rolling back an open transaction
+    pub async fn close(mut self) -> SqlResult<()> {
+        match self.transaction.take() {
+            Some(transaction) => self.db.rollback_session(transaction).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Session {
+    /// A session dropped inside a transaction is rolled back in the
+    /// background. There may be no runtime to spawn a task on, so the
+    /// rollback gets a thread and runtime of its own.
+    fn drop(&mut self) {
+        let Some(transaction) = self.transaction.take() else {
+            return;
+        };
+        let db = self.db.clone();
+        std::thread::spawn(move || {
+            if let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                let _ = runtime.block_on(db.rollback_session(transaction));
+            }
+        });
+    }
+}
+
+/// The statement, or the one EXPLAIN ANALYZE runs, if it creates or drops
+/// tables or indexes or gathers statistics
+fn schema_change(statement: &Statement) -> Option<&Statement> {
+    match statement {
+        Statement::CreateTable(_)
+        | Statement::CreateVirtualTable(_)
+        | Statement::CreateForeignTable(_)
+        | Statement::DropTable(_)
+        | Statement::CreateIndex(_)
+        | Statement::DropIndex(_)
+        | Statement::Analyze(_) => Some(statement),
+        Statement::Explain(explain) if explain.mode == ExplainMode::Analyze => schema_change(&explain.statement),
+        _ => None,
+    }
+}
+
+impl CategoricalSQLite {
+    /// Start a session transaction at snapshot isolation, or Serializable
+    /// if that is the configured level
+    async fn begin_session(&self) -> SqlResult<OpenTransaction> {
+        let isolation_level = match self.config.default_isolation_level {
+            IsolationLevel::Serializable => IsolationLevel::Serializable,
+            _ => IsolationLevel::RepeatableRead,
+        };
+        let id = self.transaction_manager.write().await.begin_transaction().await?.id;
+        // Taken between writes, so the snapshot and the copied schemas and
+        // indexes agree
+        let (rows, workspace) = {
+            let _writer = self.writer.lock().await;
+            (self.mvcc.begin(isolation_level), self.query_processor.snapshot().await)
+        };
+        workspace.capture_changes(self.changes.receiver_count() > 0);
+        workspace.capture_edits(true);
+        Ok(OpenTransaction {
+            id,
+            rows,
+            workspace,
+            written: BTreeSet::new(),
+            schema_changes: Vec::new(),
+            changes: Vec::new(),
+            failed: false,
+        })
+    }
+
+    /// Run a statement of a session transaction on its copy of the tables
+    /// and record the rows it changed as writes of the transaction
+    async fn run_in_transaction(
+        &self,
+        transaction: &mut OpenTransaction,
+        statement: Statement,
+    ) -> SqlResult<QueryResult> {
+        let workspace = &transaction.workspace;
+        let written = workspace.written_tables(&statement).await;
+        // A statement that cannot be planned fails when it runs
+        let used = workspace.referenced_tables(&statement).await.unwrap_or_default();
+        for table in used.iter().chain(&written) {
+            transaction.rows.record_scan(table);
+        }
+
+        let result = workspace.process_statement(statement.clone()).await?;
+        let edits = workspace.take_edits();
+        if changes_only_rows(&statement) {
+            for (table, edit) in edits {
+                self.write_edit(&mut transaction.rows, &table, edit)?;
+            }
+        } else {
+            for table in &written {
+                let rows = workspace.table_snapshot(table).await.map(|(_, rows)| rows);
+                self.mvcc.write_table(&mut transaction.rows, table, rows.unwrap_or_default());
+            }
+        }
+        transaction.written.extend(written);
+        transaction.schema_changes.extend(schema_change(&statement).cloned());
+        transaction.changes.extend(workspace.take_changes());
+        Ok(result)
+    }
+
+    /// Buffer the rows a statement changed in one table as writes of the
+    /// transaction
+    fn write_edit(&self, rows: &mut MvccTransaction, table: &str, edit: TableEdit) -> SqlResult<()> {
+        match edit {
+            TableEdit::Append(new) => {
+                for row in new {
+                    self.mvcc.insert(rows, table, row);
+                }
+            }
+            TableEdit::Update(new) => {
+                for (position, row) in new {
+                    self.mvcc.update_at(rows, table, position, row)?;
+                }
+            }
+            TableEdit::Delete(positions) => self.mvcc.delete_at(rows, table, &positions)?,
+        }
+        Ok(())
+    }
+
+    /// Commit a session transaction's rows unless another commit since
+    /// BEGIN conflicts with them, then bring the tables in line with them
+    /// and write those to the database file. If that fails the transaction
+    /// is rolled back.
+    async fn commit_session(&self, transaction: OpenTransaction) -> SqlResult<()> {
+        let OpenTransaction {
+            id,
+            rows,
+            written,
+            schema_changes,
+            changes,
+            ..
+        } = transaction;
+        // Conflicts are checked under the writer lock, so no other commit
+        // comes between the check and the tables being changed
+        let _writer = self.writer.lock().await;
+        let committed = if schema_changes.is_empty() {
+            self.commit_rows(rows).await
+        } else {
+            self.commit_schema_changes(rows, &written, schema_changes).await
+        };
+        if let Err(error) = committed {
+            self.transaction_manager.write().await.rollback_transaction(id).await?;
+            return Err(error);
+        }
+        if !changes.is_empty() {
+            // No receivers left is fine
+            let _ = self.changes.send(Arc::new(changes));
+        }
+        self.transaction_manager.write().await.commit_transaction(id).await
+    }
+
+    /// Commit a transaction that only changed rows: only the rows it
+    /// wrote are applied to the tables and written to the database file
+    async fn commit_rows(&self, rows: MvccTransaction) -> SqlResult<()> {
+        let edits = match self.mvcc.prepare_commit(&rows) {
+            Ok(changes) => self.session_edits(changes).await,
+            Err(error) => Err(error),
+        };
+        let applied = match edits {
+            Ok(edits) => self.apply_session_edits(&edits).await.map(|()| edits),
+            Err(error) => Err(error),
+        };
+        let edits = match applied {
+            Ok(edits) => edits,
+            Err(error) => {
+                self.mvcc.rollback(rows);
+                return Err(error);
+            }
+        };
+        // Nothing committed since the check, as the writer lock is held
+        self.mvcc.commit(rows)?;
+        self.publish(Some(&edits)).await;
+        Ok(())
+    }
+
+    /// The edits that make the tables hold what a committing transaction
+    /// changed; rows of a table that is gone since can only be deleted
+    async fn session_edits(&self, changes: Vec<(String, TableChanges)>) -> SqlResult<Vec<(String, TableEdit)>> {
+        let mut edits = Vec::new();
+        for (table, changes) in changes {
+            match self.query_processor.table_schema(&table).await {
+                // Foreign tables keep no rows
+                Some(schema) if schema.foreign.is_some() => continue,
+                Some(_) => {}
+                None if changes.updated.is_empty() && changes.inserted.is_empty() => continue,
+                None => {
+                    return Err(SqlError::serialization_failure(format!(
+                        "table {} was dropped by another transaction",
+                        table
+                    )))
+                }
+            }
+            if !changes.updated.is_empty() {
+                edits.push((table.clone(), TableEdit::Update(changes.updated)));
+            }
+            if !changes.deleted.is_empty() {
+                edits.push((table.clone(), TableEdit::Delete(changes.deleted)));
+            }
+            if !changes.inserted.is_empty() {
+                edits.push((table, TableEdit::Append(changes.inserted)));
+            }
+        }
+        Ok(edits)
+    }
+
+    /// Make a committing transaction's edits to the tables and the database
+    /// file; if either refuses them the tables are left as they were
+    async fn apply_session_edits(&self, edits: &[(String, TableEdit)]) -> SqlResult<()> {
+        // The transaction's statements kept its own rows unique, so a
+        // duplicate key is one committed by someone else since
+        self.query_processor.check_edits(edits).await.map_err(|error| {
+            SqlError::serialization_failure(format!("could not commit rows: {}", error))
+        })?;
+        self.query_processor.apply_edits(edits).await;
+
+        let Some(pager) = &self.pager else {
+            return Ok(());
+        };
+        let mut tables: Vec<String> = edits.iter().map(|(table, _)| table.clone()).collect();
+        tables.dedup();
+        let mut commit = pager.begin().await;
+        let persisted = match self.persist_edits(&mut commit, &tables, edits).await {
+            Ok(()) => commit.commit().await,
+            Err(error) => Err(error),
+        };
+        if persisted.is_err() {
+            self.reload_tables(pager, &tables).await;
+        }
+        persisted
+    }
+
+    /// Commit a transaction that created or dropped tables or indexes: its
+    /// schema changes are replayed, and the tables it wrote replaced with
+    /// their latest rows and written to the database file whole
+    async fn commit_schema_changes(
+        &self,
+        rows: MvccTransaction,
+        written: &BTreeSet<String>,
+        schema_changes: Vec<Statement>,
+    ) -> SqlResult<()> {
+        self.mvcc.commit(rows)?;
+        let before = self.contents().await;
+        if let Err(error) = self.apply_session(written, schema_changes).await {
+            self.install(before).await?;
+            let written: Vec<String> = written.iter().cloned().collect();
+            self.sync_versions(&written).await;
+            return Err(error);
+        }
+        self.publish(None).await;
+        Ok(())
+    }
+
+    /// Replay a committed session transaction's schema changes, replace the
+    /// rows of the tables it wrote with their latest versions, and write
+    /// those tables and the index definitions to the database file
+    async fn apply_session(&self, written: &BTreeSet<String>, schema_changes: Vec<Statement>) -> SqlResult<()> {
+        for statement in schema_changes {
+            self.query_processor.process_statement(statement).await?;
+        }
+        for table in written {
+            let rows = self.mvcc.latest_rows(table);
+            match self.query_processor.table_schema(table).await {
+                Some(schema) if schema.foreign.is_some() => {}
+                // The transaction's statements kept its own rows unique, so
+                // a duplicate key is one committed by someone else since
+                Some(_) => self.query_processor.replace_table(table, rows).await.map_err(|error| {
+                    SqlError::serialization_failure(format!("could not commit rows of {}: {}", table, error))
+                })?,
+                None if rows.is_empty() => {}
+                None => {
+                    return Err(SqlError::serialization_failure(format!(
+                        "table {} was dropped by another transaction",
+                        table
+                    )))
+                }
+            }
+        }
+
+        let Some(pager) = &self.pager else {
+            return Ok(());
+        };
+        let mut commit = pager.begin().await;
+        for table in written {
+            match self.query_processor.table_snapshot(table).await {
+                Some((schema, rows)) => commit.write_table(table, schema, &rows).await?,
+                None => commit.drop_table(table).await?,
+            }
+        }
+        let indexes = self.contents().await.indexes;
+        for stale in commit.indexes() {
+            if !indexes.iter().any(|index| index.name == stale.name) {
+                commit.drop_index(&stale.name);
+            }
+        }
+        for index in indexes {
+            commit.put_index(index);
+        }
+        commit.commit().await
+    }
+
+    /// Discard a session transaction; nothing of it reached the tables
+    async fn rollback_session(&self, transaction: OpenTransaction) -> SqlResult<()> {
+        self.mvcc.rollback(transaction.rows);
+        self.transaction_manager.write().await.rollback_transaction(transaction.id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::DatabaseConfig;
+    use std::time::Duration;
+
+    async fn count(session: &mut Session) -> Value {
+        let result = session.execute_sql("SELECT COUNT(*) FROM accounts").await.unwrap();
+        result.rows().unwrap()[0].values[0].clone()
+    }
+
+    #[tokio::test]
+    async fn test_session_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.db");
+        let db = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+        db.execute_sql("CREATE TABLE accounts (id INTEGER PRIMARY KEY, owner TEXT NOT NULL)")
+            .await
+            .unwrap();
+
+        let mut session = db.session();
+        assert!(session.execute_sql("COMMIT").await.is_err());
+        assert!(matches!(session.execute_sql("BEGIN").await, Ok(QueryResult::Begin)));
+        assert_eq!(session.status(), TransactionStatus::InTransaction);
+        session.execute_sql("INSERT INTO accounts (owner) VALUES ('ann')").await.unwrap();
+        session.execute_sql("CREATE INDEX idx_owner ON accounts (owner)").await.unwrap();
+        assert_eq!(count(&mut session).await, Value::Integer(1));
+        assert!(matches!(session.execute_sql("ROLLBACK").await, Ok(QueryResult::Rollback)));
+        assert_eq!(session.status(), TransactionStatus::Idle);
+        assert_eq!(count(&mut session).await, Value::Integer(0));
+        assert!(db.execute_sql("DROP INDEX idx_owner").await.is_err());
+
+        // An error fails the transaction, and COMMIT then rolls it back
+        session.execute_sql("BEGIN").await.unwrap();
+        session.execute_sql("INSERT INTO accounts (owner) VALUES ('bob')").await.unwrap();
+        assert!(session.execute_sql("INSERT INTO accounts (owner) VALUES (NULL)").await.is_err());
+        assert_eq!(session.status(), TransactionStatus::Failed);
+        assert!(session.execute_sql("SELECT COUNT(*) FROM accounts").await.is_err());
+        assert!(matches!(session.execute_sql("COMMIT").await, Ok(QueryResult::Rollback)));
+        assert_eq!(count(&mut session).await, Value::Integer(0));
+
+        // Other writers do not wait for the open transaction, and it sees
+        // neither their commits nor they its changes before COMMIT
+        session.execute_sql("BEGIN").await.unwrap();
+        session.execute_sql("INSERT INTO accounts (owner) VALUES ('cy')").await.unwrap();
+        assert_eq!(count(&mut session).await, Value::Integer(1));
+        let result = db.execute_sql("SELECT COUNT(*) FROM accounts").await.unwrap();
+        assert_eq!(result.rows().unwrap()[0].values[0], Value::Integer(0));
+        let writing = db.execute_sql("INSERT INTO accounts (id, owner) VALUES (10, 'dee')");
+        tokio::time::timeout(Duration::from_millis(500), writing).await.unwrap().unwrap();
+        assert_eq!(count(&mut session).await, Value::Integer(1));
+        session.execute_sql("COMMIT").await.unwrap();
+        let result = db.execute_sql("SELECT COUNT(*) FROM accounts").await.unwrap();
+        assert_eq!(result.rows().unwrap()[0].values[0], Value::Integer(2));
+        session.close().await.unwrap();
+        db.close().await.unwrap();
+        drop(db);
+
+        let reopened = CategoricalSQLite::open(&path, DatabaseConfig::default()).await.unwrap();
+        let mut session = reopened.session();
+        assert_eq!(count(&mut session).await, Value::Integer(2));
+    }
+
+    async fn balance(session: &mut Session, id: i64) -> Value {
+        let sql = format!("SELECT balance FROM accounts WHERE id = {}", id);
+        let result = session.execute_sql(&sql).await.unwrap();
+        result.rows().unwrap()[0].values[0].clone()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_sessions() {
+        let db = CategoricalSQLite::new(DatabaseConfig::default());
+        db.execute_sql("CREATE TABLE accounts (id INTEGER PRIMARY KEY, owner TEXT NOT NULL, balance INTEGER)")
+            .await
+            .unwrap();
+        db.execute_sql("INSERT INTO accounts (owner, balance) VALUES ('ann', 100), ('bob', 50)")
+            .await
+            .unwrap();
+        let mut first = db.session();
+        let mut second = db.session();
+
+        // Each transaction reads the snapshot taken at BEGIN, even after
+        // the other one commits
+        first.execute_sql("BEGIN").await.unwrap();
+        second.execute_sql("BEGIN").await.unwrap();
+        first.execute_sql("UPDATE accounts SET balance = balance - 30 WHERE id = 1").await.unwrap();
+        assert_eq!(balance(&mut first, 1).await, Value::Integer(70));
+        assert_eq!(balance(&mut second, 1).await, Value::Integer(100));
+        first.execute_sql("COMMIT").await.unwrap();
+        assert_eq!(balance(&mut second, 1).await, Value::Integer(100));
+        assert_eq!(balance(&mut first, 1).await, Value::Integer(70));
+
+        // Writing the row the first one committed since would lose its
+        // update: the first committer wins
+        second.execute_sql("UPDATE accounts SET balance = balance - 10 WHERE id = 1").await.unwrap();
+        let error = second.execute_sql("COMMIT").await.unwrap_err();
+        assert!(error.is_serialization_failure());
+        assert_eq!(second.status(), TransactionStatus::Idle);
+        assert_eq!(balance(&mut second, 1).await, Value::Integer(70));
+
+        // Writes to different rows both commit
+        first.execute_sql("BEGIN").await.unwrap();
+        second.execute_sql("BEGIN").await.unwrap();
+        first.execute_sql("UPDATE accounts SET balance = 0 WHERE id = 1").await.unwrap();
+        second.execute_sql("UPDATE accounts SET balance = 0 WHERE id = 2").await.unwrap();
+        first.execute_sql("COMMIT").await.unwrap();
+        second.execute_sql("COMMIT").await.unwrap();
+        let result = db.execute_sql("SELECT SUM(balance) FROM accounts").await.unwrap();
+        assert_eq!(result.rows().unwrap()[0].values[0], Value::Integer(0));
+
+        // Both take the next id; the second COMMIT finds it taken, and a
+        // retry gets the one after
+        first.execute_sql("BEGIN").await.unwrap();
+        second.execute_sql("BEGIN").await.unwrap();
+        first.execute_sql("INSERT INTO accounts (owner) VALUES ('cy')").await.unwrap();
+        second.execute_sql("INSERT INTO accounts (owner) VALUES ('dee')").await.unwrap();
+        first.execute_sql("COMMIT").await.unwrap();
+        assert!(second.execute_sql("COMMIT").await.unwrap_err().is_serialization_failure());
+        second.execute_sql("BEGIN").await.unwrap();
+        second.execute_sql("INSERT INTO accounts (owner) VALUES ('dee')").await.unwrap();
+        second.execute_sql("COMMIT").await.unwrap();
+        let result = db.execute_sql("SELECT id FROM accounts WHERE owner = 'dee'").await.unwrap();
+        assert_eq!(result.rows().unwrap()[0].values[0], Value::Integer(4));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_statements_cost_the_rows_they_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bulk.db");
+        let config = DatabaseConfig {
+            auto_checkpoint: false,
+            ..DatabaseConfig::default()
+        };
+        let db = CategoricalSQLite::open(&path, config.clone()).await.unwrap();
+        db.execute_sql("CREATE TABLE accounts (id INTEGER PRIMARY KEY, owner TEXT NOT NULL UNIQUE, balance INTEGER)")
+            .await
+            .unwrap();
+        for batch in 0..20 {
+            let values: Vec<String> = (0..1000).map(|n| format!("('owner{}', 0)", batch * 1000 + n)).collect();
+            db.execute_sql(&format!("INSERT INTO accounts (owner, balance) VALUES {}", values.join(", ")))
+                .await
+                .unwrap();
+        }
+
+        // Were each statement to copy or compare the whole table, these
+        // would take minutes
+        let mut session = db.session();
+        session.execute_sql("BEGIN").await.unwrap();
+        let started = std::time::Instant::now();
+        for n in 1..=2000 {
+            let insert = format!("INSERT INTO accounts (owner, balance) VALUES ('new{}', {})", n, n);
+            session.execute_sql(&insert).await.unwrap();
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed < Duration::from_secs(10), "2000 inserts took {:?}", elapsed);
+        session.execute_sql("UPDATE accounts SET balance = 1 WHERE id = 10").await.unwrap();
+        session.execute_sql("UPDATE accounts SET balance = balance + 1 WHERE id = 20010").await.unwrap();
+        session.execute_sql("DELETE FROM accounts WHERE id = 20001").await.unwrap();
+
+        // COMMIT writes the pages holding the rows changed, about 40 for
+        // the new rows, not the table's 400
+        let pager = db.pager.clone().unwrap();
+        let frames = pager.wal_frames();
+        session.execute_sql("COMMIT").await.unwrap();
+        assert!(pager.wal_frames() - frames <= 60, "COMMIT wrote {} pages", pager.wal_frames() - frames);
+        let totals = "SELECT COUNT(*), SUM(balance) FROM accounts";
+        let expected = vec![Value::Integer(21999), Value::Integer(2001001)];
+        assert_eq!(db.execute_sql(totals).await.unwrap().rows().unwrap()[0].values, expected);
+        db.close().await.unwrap();
+        drop(db);
+
+        let reopened = CategoricalSQLite::open(&path, config).await.unwrap();
+        assert_eq!(reopened.execute_sql(totals).await.unwrap().rows().unwrap()[0].values, expected);
+    }
+}
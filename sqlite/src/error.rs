@@ -19,6 +19,9 @@ pub enum SqlError {
     #[error("Transaction error: {message}")]
     TransactionError { message: String },
 
+    #[error("Serialization failure: {message}")]
+    SerializationFailure { message: String },
+
     #[error("Connection error: {message}")]
     ConnectionError { message: String },
     
@@ -71,6 +74,14 @@ impl SqlError {
         SqlError::TransactionError { message: message.into() }
     }
     
+    pub fn serialization_failure(message: impl Into<String>) -> Self {
+        SqlError::SerializationFailure { message: message.into() }
+    }
+
+    pub fn connection_error(message: impl Into<String>) -> Self {
+        SqlError::ConnectionError { message: message.into() }
+    }
+    
     pub fn schema_error(message: impl Into<String>) -> Self {
         SqlError::SchemaError { message: message.into() }
     }
@@ -106,4 +117,9 @@ impl SqlError {
     pub fn wal_error(message: impl Into<String>) -> Self {
         SqlError::WalError { message: message.into() }
     }
+
+    /// Whether the transaction that failed can be retried from the start
+    pub fn is_serialization_failure(&self) -> bool {
+        matches!(self, SqlError::SerializationFailure { .. })
+    }
 }
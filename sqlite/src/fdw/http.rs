@@ -0,0 +1,175 @@
+//! Foreign tables served over HTTP as JSON
+//!
+//! `SERVER http OPTIONS (url 'https://api.example.com/users', rows
+//! '$.data', filter 'country, id')` reads the JSON array at the `rows`
+//! path (by default the whole document) of a GET of `url`. Elements that
+//! are objects give each column the member of the same name; arrays give
+//! the columns in order. Equalities between a literal and a column listed
+//! in `filter` are sent as query parameters (`?country=NZ`), which the
+//! service must apply exactly.
+
+use super::{check_width, literal_fits, ForeignScan, ForeignTable, ScanFuture};
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::{BinaryOperator, Expression};
+use crate::query::json::{to_sql, JsonPath};
+use crate::types::{DataType, Row, Value};
+use serde_json::Value as JsonValue;
+use std::time::Duration;
+
+/// Seconds to wait for the service unless the `timeout` option says
+/// otherwise
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Reads foreign tables declared with `SERVER http`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpScan;
+
+impl HttpScan {
+    /// The query parameters that ask for the rows matching `pushed`
+    pub fn parameters(table: &ForeignTable, pushed: &[Expression]) -> SqlResult<Vec<(String, String)>> {
+        pushed
+            .iter()
+            .map(|predicate| {
+                filter_parameter(table, predicate)
+                    .ok_or_else(|| SqlError::runtime_error(format!("cannot send {} to http", predicate)))
+            })
+            .collect()
+    }
+
+    async fn fetch(table: &ForeignTable, parameters: &[(String, String)]) -> SqlResult<Vec<Row>> {
+        let failed = |error: reqwest::Error| {
+            SqlError::connection_error(format!("http server of foreign table {}: {}", table.name, error))
+        };
+        let client = reqwest::Client::builder()
+            .timeout(timeout(table)?)
+            .build()
+            .map_err(failed)?;
+        let document: JsonValue = client
+            .get(table.required_option("url")?)
+            .query(parameters)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json()
+            .await
+            .map_err(failed)?;
+
+        let path = JsonPath::parse(table.option("rows").unwrap_or("$"))?;
+        let Some(JsonValue::Array(elements)) = path.lookup(&document) else {
+            return Err(SqlError::runtime_error(format!(
+                "http server of foreign table {} did not return an array of rows",
+                table.name
+            )));
+        };
+        elements.iter().map(|element| row_of(table, element)).collect()
+    }
+}
+
+impl ForeignScan for HttpScan {
+    fn validate(&self, table: &ForeignTable) -> SqlResult<()> {
+        table.check_options(&["url", "rows", "filter", "timeout"])?;
+        let url = table.required_option("url")?;
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(SqlError::schema_error(format!("bad http url: {}", url)));
+        }
+        JsonPath::parse(table.option("rows").unwrap_or("$"))?;
+        if let Some(column) = filter_columns(table).find(|column| table.column(column).is_none()) {
+            return Err(SqlError::column_not_found(column.to_string()));
+        }
+        timeout(table)?;
+        Ok(())
+    }
+
+    fn supports(&self, table: &ForeignTable, predicate: &Expression) -> bool {
+        filter_parameter(table, predicate).is_some()
+    }
+
+    fn scan<'a>(&'a self, table: &'a ForeignTable, pushed: &'a [Expression]) -> ScanFuture<'a> {
+        Box::pin(async move {
+            let parameters = Self::parameters(table, pushed)?;
+            let rows = Self::fetch(table, &parameters).await?;
+            check_width(table, &rows)?;
+            Ok(rows)
+        })
+    }
+}
+
+fn timeout(table: &ForeignTable) -> SqlResult<Duration> {
+    match table.option("timeout") {
+        Some(seconds) => seconds
+            .parse()
+            .map(Duration::from_secs)
+            .map_err(|_| SqlError::schema_error(format!("bad timeout: {}", seconds))),
+        None => Ok(Duration::from_secs(DEFAULT_TIMEOUT)),
+    }
+}
+
+/// Columns the service filters by, from the `filter` option
+fn filter_columns(table: &ForeignTable) -> impl Iterator<Item = &str> {
+    table
+        .option("filter")
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+}
+
+/// `(column, value)` for `column = literal` on a filter column
+fn filter_parameter(table: &ForeignTable, predicate: &Expression) -> Option<(String, String)> {
+    let Expression::BinaryOp { left, op: BinaryOperator::Equal, right } = predicate else {
+        return None;
+    };
+    let (name, value) = match (&**left, &**right) {
+        (Expression::Column(name), Expression::Literal(value))
+        | (Expression::Literal(value), Expression::Column(name)) => (name, value),
+        _ => return None,
+    };
+    let column = table.column(name)?;
+    if !filter_columns(table).any(|filter| filter.eq_ignore_ascii_case(name)) || !literal_fits(value, &column.data_type) {
+        return None;
+    }
+    Some((column.name.clone(), value.to_string()))
+}
+
+/// The row of one element of the response
+fn row_of(table: &ForeignTable, element: &JsonValue) -> SqlResult<Row> {
+    let values = match element {
+        JsonValue::Object(members) => table
+            .columns
+            .iter()
+            .map(|column| {
+                let member = members.get(&column.name).or_else(|| {
+                    members
+                        .iter()
+                        .find(|(key, _)| key.eq_ignore_ascii_case(&column.name))
+                        .map(|(_, value)| value)
+                });
+                member.map_or(Value::Null, |value| value_of(value, &column.data_type))
+            })
+            .collect(),
+        JsonValue::Array(items) => items
+            .iter()
+            .zip(&table.columns)
+            .map(|(value, column)| value_of(value, &column.data_type))
+            .chain(std::iter::repeat(Value::Null))
+            .take(table.columns.len())
+            .collect(),
+        other => {
+            return Err(SqlError::runtime_error(format!(
+                "http server of foreign table {} returned {} as a row",
+                table.name, other
+            )))
+        }
+    };
+    Ok(Row::new(values))
+}
+
+fn value_of(json: &JsonValue, data_type: &DataType) -> Value {
+    match (json, data_type) {
+        (JsonValue::Bool(b), DataType::Boolean) => Value::Boolean(*b),
+        (JsonValue::Number(n), DataType::Real) => n.as_f64().map_or(Value::Null, Value::Real),
+        _ => to_sql(json),
+    }
+}
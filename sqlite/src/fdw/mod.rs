@@ -0,0 +1,298 @@
+//! Foreign data wrappers
+//!
+//! `CREATE FOREIGN TABLE orders (id INTEGER, total REAL) SERVER postgres
+//! OPTIONS (url 'postgres://app@db/shop')` declares a table whose rows live
+//! in another system. Its schema is stored like any other table's, but
+//! every scan fetches the rows from the server through a [`ForeignScan`],
+//! so queries can join them with local tables. WHERE conditions the server
+//! can evaluate are pushed down to it; the rest are applied locally.
+//!
+//! The `postgres` and `http` servers are built in; others are added with
+//! [`ForeignCatalog::register`].
+
+pub mod http;
+pub mod postgres;
+
+pub use http::HttpScan;
+pub use postgres::PostgresScan;
+
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::Expression;
+use crate::query::join::split_conjunction;
+use crate::query::plan::{ColumnSchema, TableSchema};
+use crate::types::{DataType, Row, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Rows fetched by a [`ForeignScan`]
+pub type ScanFuture<'a> = Pin<Box<dyn Future<Output = SqlResult<Vec<Row>>> + Send + 'a>>;
+
+/// Where a foreign table's rows come from, as declared by
+/// `SERVER name OPTIONS (key 'value', ...)`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ForeignOptions {
+    pub server: String,
+    /// Options in the order they were written
+    pub options: Vec<(String, String)>,
+}
+
+/// A foreign table as its server sees it
+#[derive(Debug, Clone)]
+pub struct ForeignTable {
+    pub name: String,
+    pub columns: Vec<ColumnSchema>,
+    pub server: String,
+    options: Vec<(String, String)>,
+}
+
+impl ForeignTable {
+    pub fn new(name: impl Into<String>, columns: Vec<ColumnSchema>, options: &ForeignOptions) -> Self {
+        ForeignTable {
+            name: name.into(),
+            columns,
+            server: options.server.clone(),
+            options: options.options.clone(),
+        }
+    }
+
+    /// The foreign table `name` of a schema, if it has one
+    pub fn of(name: &str, schema: &TableSchema) -> Option<Self> {
+        schema
+            .foreign
+            .as_ref()
+            .map(|options| ForeignTable::new(name, schema.columns.clone(), options))
+    }
+
+    /// Value of an option, matched case-insensitively
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Value of an option the table must have
+    pub fn required_option(&self, key: &str) -> SqlResult<&str> {
+        self.option(key).ok_or_else(|| {
+            SqlError::schema_error(format!("foreign table {} needs the {} option", self.name, key))
+        })
+    }
+
+    /// Reject options other than `allowed`
+    pub fn check_options(&self, allowed: &[&str]) -> SqlResult<()> {
+        match self.options.iter().find(|(name, _)| !allowed.iter().any(|key| key.eq_ignore_ascii_case(name))) {
+            Some((name, _)) => Err(SqlError::schema_error(format!(
+                "unrecognized {} option: {}",
+                self.server, name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// The declared column `name`, matched case-insensitively
+    pub fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|column| column.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Reads the rows of the foreign tables of one kind of server
+///
+/// Predicates handed to a scan have been accepted by
+/// [`supports`](ForeignScan::supports) and name the table's columns
+/// without qualifiers. The server applies them in place of the engine, so
+/// it must only accept predicates it evaluates exactly as the engine would.
+pub trait ForeignScan: Send + Sync {
+    /// Check the options of a table when it is created
+    fn validate(&self, table: &ForeignTable) -> SqlResult<()>;
+
+    /// Whether the server can evaluate `predicate` itself
+    fn supports(&self, table: &ForeignTable, predicate: &Expression) -> bool;
+
+    /// The rows of `table` that satisfy every predicate in `pushed`, with
+    /// one value per declared column, in order
+    fn scan<'a>(&'a self, table: &'a ForeignTable, pushed: &'a [Expression]) -> ScanFuture<'a>;
+}
+
+/// The foreign scans of the executor's tables, keyed by server name
+#[derive(Clone)]
+pub struct ForeignCatalog {
+    servers: Arc<RwLock<HashMap<String, Arc<dyn ForeignScan>>>>,
+}
+
+impl ForeignCatalog {
+    /// A catalog with the built-in `postgres` and `http` servers
+    pub fn new() -> Self {
+        let mut servers: HashMap<String, Arc<dyn ForeignScan>> = HashMap::new();
+        servers.insert("postgres".to_string(), Arc::new(PostgresScan));
+        servers.insert("http".to_string(), Arc::new(HttpScan));
+        ForeignCatalog {
+            servers: Arc::new(RwLock::new(servers)),
+        }
+    }
+
+    /// Serve the tables declared with `SERVER name` from `scan`, replacing
+    /// any server of that name
+    pub async fn register(&self, name: impl Into<String>, scan: Arc<dyn ForeignScan>) {
+        self.servers.write().await.insert(name.into().to_ascii_lowercase(), scan);
+    }
+
+    pub async fn get(&self, server: &str) -> SqlResult<Arc<dyn ForeignScan>> {
+        self.servers
+            .read()
+            .await
+            .get(&server.to_ascii_lowercase())
+            .cloned()
+            .ok_or_else(|| SqlError::schema_error(format!("no such foreign server: {}", server)))
+    }
+
+    /// Every registered server, for planning without holding the lock
+    pub async fn servers(&self) -> HashMap<String, Arc<dyn ForeignScan>> {
+        self.servers.read().await.clone()
+    }
+}
+
+impl Default for ForeignCatalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ForeignCatalog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = match self.servers.try_read() {
+            Ok(servers) => servers.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        f.debug_struct("ForeignCatalog").field("servers", &names).finish()
+    }
+}
+
+/// Split the conjuncts of `condition` that `scan` can evaluate on `table`
+/// from the rest. `names` are the names the query gives the table; column
+/// references qualified by one of them belong to it, and so do unqualified
+/// ones when `unqualified` is set. Pushed conjuncts come back without
+/// qualifiers.
+pub fn split_pushdown(
+    scan: &dyn ForeignScan,
+    table: &ForeignTable,
+    names: &[&str],
+    unqualified: bool,
+    condition: &Expression,
+) -> (Vec<Expression>, Vec<Expression>) {
+    let mut pushed = Vec::new();
+    let mut rest = Vec::new();
+    for conjunct in split_conjunction(condition) {
+        match localize(conjunct, table, names, unqualified) {
+            Some(local) if scan.supports(table, &local) => pushed.push(local),
+            _ => rest.push(conjunct.clone()),
+        }
+    }
+    (pushed, rest)
+}
+
+/// `expr` with its column references made unqualified, if all of them are
+/// columns of `table` and it has no subqueries or parameters
+fn localize(expr: &Expression, table: &ForeignTable, names: &[&str], unqualified: bool) -> Option<Expression> {
+    let local = |expr: &Expression| localize(expr, table, names, unqualified);
+    let boxed = |expr: &Expression| local(expr).map(Box::new);
+    Some(match expr {
+        Expression::Literal(_) => expr.clone(),
+        Expression::Column(name) if unqualified => Expression::Column(table.column(name)?.name.clone()),
+        Expression::QualifiedColumn { table: qualifier, column }
+            if names.iter().any(|name| name.eq_ignore_ascii_case(qualifier)) =>
+        {
+            Expression::Column(table.column(column)?.name.clone())
+        }
+        Expression::BinaryOp { left, op, right } => Expression::BinaryOp {
+            left: boxed(left)?,
+            op: op.clone(),
+            right: boxed(right)?,
+        },
+        Expression::UnaryOp { op, operand } => Expression::UnaryOp {
+            op: op.clone(),
+            operand: boxed(operand)?,
+        },
+        Expression::Function { name, args } => Expression::Function {
+            name: name.clone(),
+            args: args.iter().map(local).collect::<Option<_>>()?,
+        },
+        Expression::Collate { expr, collation } => Expression::Collate {
+            expr: boxed(expr)?,
+            collation: collation.clone(),
+        },
+        Expression::In { expr, list } => Expression::In {
+            expr: boxed(expr)?,
+            list: list.iter().map(local).collect::<Option<_>>()?,
+        },
+        Expression::Between { expr, low, high } => Expression::Between {
+            expr: boxed(expr)?,
+            low: boxed(low)?,
+            high: boxed(high)?,
+        },
+        Expression::IsNull(expr) => Expression::IsNull(boxed(expr)?),
+        Expression::IsNotNull(expr) => Expression::IsNotNull(boxed(expr)?),
+        Expression::Column(_)
+        | Expression::QualifiedColumn { .. }
+        | Expression::Subquery(_)
+        | Expression::InSubquery { .. }
+        | Expression::Exists(_)
+        | Expression::Parameter(_) => return None,
+    })
+}
+
+/// The value of a column of `data_type` sent by a server as text; text
+/// that does not read as the declared type is kept as TEXT
+pub fn value_from_text(text: &str, data_type: &DataType) -> Value {
+    let parsed = match data_type {
+        DataType::Integer => text.parse().ok().map(Value::Integer),
+        DataType::Real => text.parse().ok().map(Value::Real),
+        DataType::Boolean => match text {
+            "t" | "true" => Some(Value::Boolean(true)),
+            "f" | "false" => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        DataType::Blob => text.strip_prefix("\\x").and_then(decode_hex).map(Value::Blob),
+        DataType::Null | DataType::Text => None,
+    };
+    parsed.unwrap_or_else(|| Value::Text(text.to_string()))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(hex.get(start..start + 2)?, 16).ok())
+        .collect()
+}
+
+/// Whether a literal compares with a column of `data_type` the same way in
+/// the engine and in a typed remote system: numbers with numeric columns,
+/// text with text columns and booleans with boolean columns
+pub fn literal_fits(value: &Value, data_type: &DataType) -> bool {
+    matches!(
+        (value, data_type),
+        (Value::Integer(_) | Value::Real(_), DataType::Integer | DataType::Real)
+            | (Value::Text(_), DataType::Text)
+            | (Value::Boolean(_), DataType::Boolean)
+    )
+}
+
+/// Check that every row a server returned has one value per column
+pub fn check_width(table: &ForeignTable, rows: &[Row]) -> SqlResult<()> {
+    match rows.iter().find(|row| row.values.len() != table.columns.len()) {
+        Some(row) => Err(SqlError::runtime_error(format!(
+            "foreign table {} returned a row of {} values for {} columns",
+            table.name,
+            row.values.len(),
+            table.columns.len()
+        ))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,405 @@
+use crate::btree::BTree;
+use crate::error::{SqlError, SqlResult};
+use crate::fts::query::FtsQuery;
+use crate::fts::tokenizer::{Token, Tokenizer};
+use crate::types::{Row, RowId, Value};
+use std::collections::HashMap;
+
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+/// BM25 document length normalization
+const B: f64 = 0.75;
+
+/// Where a term occurs in one column of one document
+#[derive(Debug, Clone, PartialEq)]
+struct Posting {
+    doc: i64,
+    column: usize,
+    positions: Vec<u32>,
+}
+
+/// A term's postings as one B-tree row: `doc, column, count` followed by
+/// `count` positions, for every posting
+fn encode_postings(postings: &[Posting]) -> Row {
+    let mut values = Vec::new();
+    for posting in postings {
+        values.push(Value::Integer(posting.doc));
+        values.push(Value::Integer(posting.column as i64));
+        values.push(Value::Integer(posting.positions.len() as i64));
+        values.extend(posting.positions.iter().map(|&position| Value::Integer(position as i64)));
+    }
+    Row::new(values)
+}
+
+fn decode_postings(row: &Row) -> SqlResult<Vec<Posting>> {
+    let mut numbers = row.values.iter().map(|value| {
+        value
+            .as_integer()
+            .ok_or_else(|| SqlError::runtime_error("Corrupt full-text index posting"))
+    });
+    let mut postings = Vec::new();
+    while let Some(doc) = numbers.next() {
+        let doc = doc?;
+        let column = numbers.next().unwrap_or(Ok(0))? as usize;
+        let count = numbers.next().unwrap_or(Ok(0))? as usize;
+        let positions = numbers.by_ref().take(count).map(|position| position.map(|p| p as u32)).collect::<SqlResult<Vec<_>>>()?;
+        if positions.len() != count {
+            return Err(SqlError::runtime_error("Corrupt full-text index posting"));
+        }
+        postings.push(Posting { doc, column, positions });
+    }
+    Ok(postings)
+}
+
+/// Text of a column value as the tokenizer sees it; NULLs and blobs are
+/// not indexed
+fn indexed_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null | Value::Blob(_) => None,
+        Value::Text(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Relevance of each matching document, keyed by document id
+type Scores = HashMap<i64, f64>;
+
+/// Inverted index over the rows of a full-text table
+///
+/// Each term maps to its postings in the `terms` B-tree, and each document
+/// to its token count per column in `documents`. Documents get ids in
+/// insertion order that stay fixed while the row is updated, so a change
+/// to one row only touches the terms of that row.
+#[derive(Debug, Clone)]
+pub struct FtsIndex {
+    columns: Vec<String>,
+    tokenizer: Tokenizer,
+    terms: BTree,
+    documents: BTree,
+    /// Document id of each table row, by row position; ascending, since
+    /// rows are only ever appended
+    rows: Vec<i64>,
+    next_doc: i64,
+    total_tokens: u64,
+}
+
+impl FtsIndex {
+    pub fn new(columns: Vec<String>, tokenizer: Tokenizer) -> Self {
+        FtsIndex {
+            columns,
+            tokenizer,
+            terms: BTree::new(32),
+            documents: BTree::new(32),
+            rows: Vec::new(),
+            next_doc: 1,
+            total_tokens: 0,
+        }
+    }
+
+    /// Index every row of a table
+    pub fn build(columns: Vec<String>, tokenizer: Tokenizer, rows: &[Row]) -> SqlResult<Self> {
+        let mut index = FtsIndex::new(columns, tokenizer);
+        index.insert_rows(rows)?;
+        Ok(index)
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Number of distinct terms
+    pub fn term_count(&self) -> SqlResult<usize> {
+        Ok(self.terms.scan()?.len())
+    }
+
+    /// Index rows appended to the end of the table
+    pub fn insert_rows(&mut self, rows: &[Row]) -> SqlResult<()> {
+        for row in rows {
+            let doc = self.next_doc;
+            self.next_doc += 1;
+            self.add_document(doc, row)?;
+            self.rows.push(doc);
+        }
+        Ok(())
+    }
+
+    /// Remove the rows that were at `positions` (ascending), whose old
+    /// contents are `rows`
+    pub fn delete_rows(&mut self, positions: &[usize], rows: &[Row]) -> SqlResult<()> {
+        for (&position, row) in positions.iter().zip(rows).rev() {
+            let doc = self.rows.remove(position);
+            self.remove_document(doc, row)?;
+        }
+        Ok(())
+    }
+
+    /// Re-index the rows at `positions` after their contents changed from
+    /// `old` to `new`
+    pub fn update_rows(&mut self, positions: &[usize], old: &[Row], new: &[Row]) -> SqlResult<()> {
+        for ((&position, old), new) in positions.iter().zip(old).zip(new) {
+            let doc = self.rows[position];
+            self.remove_document(doc, old)?;
+            self.add_document(doc, new)?;
+        }
+        Ok(())
+    }
+
+    /// Row positions matching `query` with their BM25 rank, in row order.
+    /// As in FTS5 the rank is negated, so better matches sort first.
+    pub fn search(&self, query: &FtsQuery) -> SqlResult<Vec<(usize, f64)>> {
+        let scores = self.evaluate(query, None)?.unwrap_or_default();
+        let mut matches: Vec<(usize, f64)> = scores
+            .into_iter()
+            .filter_map(|(doc, score)| self.rows.binary_search(&doc).ok().map(|position| (position, -score)))
+            .collect();
+        matches.sort_by_key(|(position, _)| *position);
+        Ok(matches)
+    }
+
+    /// Tokens of each indexed column of a row
+    fn row_tokens(&self, row: &Row) -> Vec<Vec<Token>> {
+        (0..self.columns.len())
+            .map(|column| {
+                row.values
+                    .get(column)
+                    .and_then(indexed_text)
+                    .map(|text| self.tokenizer.tokenize(&text))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn add_document(&mut self, doc: i64, row: &Row) -> SqlResult<()> {
+        let tokens = self.row_tokens(row);
+        let mut occurrences: HashMap<String, Vec<(usize, u32)>> = HashMap::new();
+        for (column, tokens) in tokens.iter().enumerate() {
+            for token in tokens {
+                occurrences.entry(token.text.clone()).or_default().push((column, token.position));
+            }
+        }
+        for (term, places) in occurrences {
+            let mut postings = self.postings(&term)?;
+            for column in 0..self.columns.len() {
+                let positions: Vec<u32> =
+                    places.iter().filter(|(c, _)| *c == column).map(|(_, position)| *position).collect();
+                if !positions.is_empty() {
+                    postings.push(Posting { doc, column, positions });
+                }
+            }
+            self.put_postings(&term, &postings)?;
+        }
+
+        let lengths: Vec<Value> = tokens.iter().map(|tokens| Value::Integer(tokens.len() as i64)).collect();
+        self.total_tokens += tokens.iter().map(Vec::len).sum::<usize>() as u64;
+        self.documents.insert(Value::Integer(doc), RowId(doc as u64), Row::new(lengths))
+    }
+
+    fn remove_document(&mut self, doc: i64, row: &Row) -> SqlResult<()> {
+        let mut terms: Vec<String> = self.row_tokens(row).into_iter().flatten().map(|token| token.text).collect();
+        terms.sort();
+        terms.dedup();
+        for term in terms {
+            let mut postings = self.postings(&term)?;
+            postings.retain(|posting| posting.doc != doc);
+            self.put_postings(&term, &postings)?;
+        }
+        if let Some((_, lengths)) = self.documents.delete(&Value::Integer(doc))? {
+            let length: i64 = lengths.values.iter().filter_map(Value::as_integer).sum();
+            self.total_tokens = self.total_tokens.saturating_sub(length as u64);
+        }
+        Ok(())
+    }
+
+    fn postings(&self, term: &str) -> SqlResult<Vec<Posting>> {
+        match self.terms.search(&Value::Text(term.to_string()))? {
+            Some((_, row)) => decode_postings(&row),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Replace a term's postings; a term with none is removed
+    fn put_postings(&mut self, term: &str, postings: &[Posting]) -> SqlResult<()> {
+        let key = Value::Text(term.to_string());
+        self.terms.delete(&key)?;
+        if !postings.is_empty() {
+            self.terms.insert(key, RowId(postings.len() as u64), encode_postings(postings))?;
+        }
+        Ok(())
+    }
+
+    fn document_length(&self, doc: i64) -> SqlResult<f64> {
+        Ok(match self.documents.search(&Value::Integer(doc))? {
+            Some((_, lengths)) => lengths.values.iter().filter_map(Value::as_integer).sum::<i64>() as f64,
+            None => 0.0,
+        })
+    }
+
+    /// Scores of the documents matching `query`; `None` when the query has
+    /// no indexed words (only stop words), so it constrains nothing
+    fn evaluate(&self, query: &FtsQuery, column: Option<usize>) -> SqlResult<Option<Scores>> {
+        Ok(match query {
+            FtsQuery::Term { word, prefix: true } => {
+                let prefix = word.to_lowercase();
+                let end = format!("{}{}", prefix, char::MAX);
+                let mut frequencies = HashMap::new();
+                for (_, _, row) in self.terms.range_scan(&Value::Text(prefix), &Value::Text(end))? {
+                    for posting in decode_postings(&row)? {
+                        if column.is_none_or(|column| column == posting.column) {
+                            *frequencies.entry(posting.doc).or_insert(0) += posting.positions.len();
+                        }
+                    }
+                }
+                Some(self.score(frequencies)?)
+            }
+            FtsQuery::Term { word: text, .. } | FtsQuery::Phrase(text) => {
+                let tokens = self.tokenizer.tokenize(text);
+                if tokens.is_empty() {
+                    return Ok(None);
+                }
+                Some(self.score(self.phrase_frequencies(&tokens, column)?)?)
+            }
+            FtsQuery::Column { column: name, query } => {
+                let position = self
+                    .columns
+                    .iter()
+                    .position(|column| column.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| SqlError::column_not_found(name.clone()))?;
+                self.evaluate(query, Some(position))?
+            }
+            FtsQuery::And(left, right) => match (self.evaluate(left, column)?, self.evaluate(right, column)?) {
+                (Some(left), Some(right)) => Some(
+                    left.into_iter()
+                        .filter_map(|(doc, score)| right.get(&doc).map(|other| (doc, score + other)))
+                        .collect(),
+                ),
+                (left, right) => left.or(right),
+            },
+            FtsQuery::Or(left, right) => match (self.evaluate(left, column)?, self.evaluate(right, column)?) {
+                (Some(mut left), Some(right)) => {
+                    for (doc, score) in right {
+                        *left.entry(doc).or_insert(0.0) += score;
+                    }
+                    Some(left)
+                }
+                (left, right) => left.or(right),
+            },
+            FtsQuery::Not(left, right) => match (self.evaluate(left, column)?, self.evaluate(right, column)?) {
+                (Some(mut left), Some(right)) => {
+                    left.retain(|doc, _| !right.contains_key(doc));
+                    Some(left)
+                }
+                (left, _) => left,
+            },
+        })
+    }
+
+    /// Number of times the tokens occur as a phrase in each document
+    fn phrase_frequencies(
+        &self,
+        tokens: &[Token],
+        column: Option<usize>,
+    ) -> SqlResult<HashMap<i64, usize>> {
+        // Positions of each token, by document and column
+        let mut places: Vec<HashMap<(i64, usize), Vec<u32>>> = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let postings = self.postings(&token.text)?;
+            places.push(
+                postings
+                    .into_iter()
+                    .filter(|posting| column.is_none_or(|column| column == posting.column))
+                    .map(|posting| ((posting.doc, posting.column), posting.positions))
+                    .collect(),
+            );
+        }
+
+        let first = tokens[0].position;
+        let mut frequencies = HashMap::new();
+        for (key, starts) in &places[0] {
+            let count = starts
+                .iter()
+                .filter(|&&start| {
+                    tokens.iter().zip(&places).skip(1).all(|(token, places)| {
+                        places
+                            .get(key)
+                            .is_some_and(|positions| positions.contains(&(start + token.position - first)))
+                    })
+                })
+                .count();
+            if count > 0 {
+                *frequencies.entry(key.0).or_insert(0) += count;
+            }
+        }
+        Ok(frequencies)
+    }
+
+    /// BM25 score of each document from its frequency of one query term
+    fn score(&self, frequencies: HashMap<i64, usize>) -> SqlResult<Scores> {
+        let documents = self.rows.len() as f64;
+        let matching = frequencies.len() as f64;
+        let idf = ((documents - matching + 0.5) / (matching + 0.5) + 1.0).ln();
+        let average_length = (self.total_tokens as f64 / documents.max(1.0)).max(1.0);
+        frequencies
+            .into_iter()
+            .map(|(doc, frequency)| {
+                let frequency = frequency as f64;
+                let length = self.document_length(doc)?;
+                let norm = K1 * (1.0 - B + B * length / average_length);
+                Ok((doc, idf * frequency * (K1 + 1.0) / (frequency + norm)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(title: &str, body: &str) -> Row {
+        Row::new(vec![Value::Text(title.to_string()), Value::Text(body.to_string())])
+    }
+
+    fn positions(index: &FtsIndex, query: &str) -> Vec<usize> {
+        let query = FtsQuery::parse(query).unwrap();
+        index.search(&query).unwrap().into_iter().map(|(position, _)| position).collect()
+    }
+
+    #[test]
+    fn test_search_and_incremental_maintenance() {
+        let mut index = FtsIndex::build(
+            vec!["title".to_string(), "body".to_string()],
+            Tokenizer::parse("porter").unwrap(),
+            &[
+                row("Rust ownership", "Borrowing rules keep memory safe"),
+                row("Query planning", "The planner picks an index for each query"),
+                row("Indexes", "A B-tree index speeds up lookups; an index scan reads the index"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(positions(&index, "index"), vec![1, 2]);
+        assert_eq!(positions(&index, "title:index"), vec![2]);
+        assert_eq!(positions(&index, "\"index scan\""), vec![2]);
+        assert_eq!(positions(&index, "\"scan index\""), Vec::<usize>::new());
+        assert_eq!(positions(&index, "borrow OR plan*"), vec![0, 1]);
+        assert_eq!(positions(&index, "index NOT planner"), vec![2]);
+
+        // More occurrences in a shorter document rank better
+        let ranked = index.search(&FtsQuery::parse("index").unwrap()).unwrap();
+        assert!(ranked[1].1 < ranked[0].1, "{:?}", ranked);
+
+        index.delete_rows(&[0], &[row("Rust ownership", "Borrowing rules keep memory safe")]).unwrap();
+        assert_eq!(positions(&index, "borrow"), Vec::<usize>::new());
+        assert_eq!(positions(&index, "index"), vec![0, 1]);
+        index
+            .update_rows(&[0], &[row("Query planning", "The planner picks an index for each query")], &[row("Joins", "Hash joins")])
+            .unwrap();
+        index.insert_rows(&[row("Borrowing", "The borrow checker")]).unwrap();
+        assert_eq!(positions(&index, "index"), vec![1]);
+        assert_eq!(positions(&index, "join OR borrow"), vec![0, 2]);
+        assert_eq!(index.document_count(), 3);
+        assert!(index.search(&FtsQuery::parse("missing:x").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Full-text search
+//!
+//! `CREATE VIRTUAL TABLE docs USING fts(title, body)` creates a table whose
+//! columns are indexed word by word. `WHERE docs MATCH 'query'` (or
+//! `WHERE body MATCH ...` for one column) is answered from the inverted
+//! index, and the hidden `rank` column holds each match's BM25 rank.
+
+pub mod index;
+pub mod query;
+pub mod tokenizer;
+
+pub use index::FtsIndex;
+pub use query::FtsQuery;
+pub use tokenizer::{Token, TokenFilter, Tokenizer};
+
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::{BinaryOperator, Expression};
+use crate::query::join::{conjunction, split_conjunction};
+use crate::types::{Row, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Hidden column of a full-text scan with the BM25 rank of each row
+pub const RANK_COLUMN: &str = "rank";
+
+/// Options of a full-text table, from the `key = value` arguments of
+/// `CREATE VIRTUAL TABLE ... USING fts(...)`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FtsOptions {
+    /// Tokenizer spec, see [`Tokenizer::parse`]
+    pub tokenize: String,
+}
+
+impl FtsOptions {
+    pub fn tokenizer(&self) -> SqlResult<Tokenizer> {
+        Tokenizer::parse(&self.tokenize)
+    }
+}
+
+/// Split the MATCH conditions on a full-text table out of a WHERE clause.
+/// `table MATCH 'q'` searches every column and `column MATCH 'q'` one
+/// column; `names` are the table's name and alias. Returns the combined
+/// query, if there was one, and the rest of the clause.
+pub fn extract_match(
+    condition: &Expression,
+    names: &[&str],
+    columns: &[String],
+) -> SqlResult<(Option<FtsQuery>, Option<Expression>)> {
+    let names_table = |name: &str| names.iter().any(|table| table.eq_ignore_ascii_case(name));
+    let mut query: Option<FtsQuery> = None;
+    let mut rest = Vec::new();
+    for conjunct in split_conjunction(condition) {
+        let Expression::BinaryOp { left, op: BinaryOperator::Match, right } = conjunct else {
+            rest.push(conjunct.clone());
+            continue;
+        };
+        let Expression::Literal(Value::Text(text)) = &**right else {
+            rest.push(conjunct.clone());
+            continue;
+        };
+        let column = match &**left {
+            Expression::Column(name) if names_table(name) => None,
+            Expression::Column(name) => Some(name),
+            Expression::QualifiedColumn { table, column } if names_table(table) => Some(column),
+            _ => {
+                rest.push(conjunct.clone());
+                continue;
+            }
+        };
+        let mut matched = FtsQuery::parse(text)?;
+        if let Some(column) = column {
+            if !columns.iter().any(|name| name.eq_ignore_ascii_case(column)) {
+                return Err(SqlError::column_not_found(column.clone()));
+            }
+            matched = matched.in_column(column.clone());
+        }
+        query = Some(match query {
+            Some(query) => query.and(matched),
+            None => matched,
+        });
+    }
+    Ok((query, conjunction(rest)))
+}
+
+/// Full-text indexes of the executor's tables, keyed by table name
+#[derive(Debug, Clone, Default)]
+pub struct FtsCatalog {
+    indexes: Arc<RwLock<HashMap<String, Arc<FtsIndex>>>>,
+}
+
+impl FtsCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A separate catalog holding the indexes of this one as they are now;
+    /// an index is copied only when either catalog next changes it
+    pub async fn fork(&self) -> Self {
+        FtsCatalog {
+            indexes: Arc::new(RwLock::new(self.indexes.read().await.clone())),
+        }
+    }
+
+    /// Index the rows of a full-text table, replacing any existing index
+    pub async fn create(&self, table: &str, options: &FtsOptions, columns: Vec<String>, rows: &[Row]) -> SqlResult<()> {
+        let index = FtsIndex::build(columns, options.tokenizer()?, rows)?;
+        self.indexes.write().await.insert(table.to_string(), Arc::new(index));
+        Ok(())
+    }
+
+    pub async fn drop_index(&self, table: &str) {
+        self.indexes.write().await.remove(table);
+    }
+
+    pub async fn contains(&self, table: &str) -> bool {
+        self.indexes.read().await.contains_key(table)
+    }
+
+    /// Index rows appended to `table`; tables without an index are ignored
+    pub async fn insert(&self, table: &str, rows: &[Row]) -> SqlResult<()> {
+        match self.indexes.write().await.get_mut(table) {
+            Some(index) => Arc::make_mut(index).insert_rows(rows),
+            None => Ok(()),
+        }
+    }
+
+    /// Unindex the rows that were at `positions` of `table`
+    pub async fn delete(&self, table: &str, positions: &[usize], rows: &[Row]) -> SqlResult<()> {
+        match self.indexes.write().await.get_mut(table) {
+            Some(index) => Arc::make_mut(index).delete_rows(positions, rows),
+            None => Ok(()),
+        }
+    }
+
+    /// Re-index the rows at `positions` of `table`
+    pub async fn update(&self, table: &str, positions: &[usize], old: &[Row], new: &[Row]) -> SqlResult<()> {
+        match self.indexes.write().await.get_mut(table) {
+            Some(index) => Arc::make_mut(index).update_rows(positions, old, new),
+            None => Ok(()),
+        }
+    }
+
+    /// Positions of the rows of `table` matching `query`, with their rank
+    pub async fn search(&self, table: &str, query: &FtsQuery) -> SqlResult<Vec<(usize, f64)>> {
+        let indexes = self.indexes.read().await;
+        let index = indexes
+            .get(table)
+            .ok_or_else(|| SqlError::runtime_error(format!("Table {} has no full-text index", table)))?;
+        index.search(query)
+    }
+}
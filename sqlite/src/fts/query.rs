@@ -0,0 +1,252 @@
+use crate::error::{SqlError, SqlResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A parsed MATCH query
+///
+/// The syntax follows FTS5: bare words, `"quoted phrases"`, `prefix*`
+/// words, `column:` filters, parentheses and the operators `NOT`, `AND`
+/// and `OR` in decreasing order of precedence. Words written next to each
+/// other are joined with `AND`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FtsQuery {
+    /// A word, or with `prefix` every word that starts with it
+    Term { word: String, prefix: bool },
+    /// Words that must appear next to each other in this order
+    Phrase(String),
+    /// A query restricted to one column
+    Column { column: String, query: Box<FtsQuery> },
+    And(Box<FtsQuery>, Box<FtsQuery>),
+    Or(Box<FtsQuery>, Box<FtsQuery>),
+    /// Documents matching the left query but not the right one
+    Not(Box<FtsQuery>, Box<FtsQuery>),
+}
+
+impl FtsQuery {
+    pub fn parse(text: &str) -> SqlResult<Self> {
+        let mut parser = QueryParser { tokens: lex(text)?, position: 0 };
+        let query = parser.or_query()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(query),
+            Some(token) => Err(fts_syntax_error(format!("unexpected {}", token))),
+        }
+    }
+
+    /// Both queries must match
+    pub fn and(self, other: FtsQuery) -> FtsQuery {
+        FtsQuery::And(Box::new(self), Box::new(other))
+    }
+
+    /// The query restricted to `column`
+    pub fn in_column(self, column: impl Into<String>) -> FtsQuery {
+        FtsQuery::Column {
+            column: column.into(),
+            query: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for FtsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FtsQuery::Term { word, prefix: false } => write!(f, "{}", word),
+            FtsQuery::Term { word, prefix: true } => write!(f, "{}*", word),
+            FtsQuery::Phrase(phrase) => write!(f, "\"{}\"", phrase.replace('"', "\"\"")),
+            FtsQuery::Column { column, query } => write!(f, "{}:({})", column, query),
+            FtsQuery::And(left, right) => write!(f, "({} AND {})", left, right),
+            FtsQuery::Or(left, right) => write!(f, "({} OR {})", left, right),
+            FtsQuery::Not(left, right) => write!(f, "({} NOT {})", left, right),
+        }
+    }
+}
+
+fn fts_syntax_error(detail: impl fmt::Display) -> SqlError {
+    SqlError::parse_error(format!("fts5: syntax error in MATCH query: {}", detail))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueryToken {
+    Word(String),
+    Phrase(String),
+    Star,
+    Colon,
+    Open,
+    Close,
+}
+
+impl fmt::Display for QueryToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryToken::Word(word) => write!(f, "\"{}\"", word),
+            QueryToken::Phrase(phrase) => write!(f, "phrase \"{}\"", phrase),
+            QueryToken::Star => write!(f, "\"*\""),
+            QueryToken::Colon => write!(f, "\":\""),
+            QueryToken::Open => write!(f, "\"(\""),
+            QueryToken::Close => write!(f, "\")\""),
+        }
+    }
+}
+
+fn lex(text: &str) -> SqlResult<Vec<QueryToken>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        // `""` inside a phrase is a literal quote
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            phrase.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err(fts_syntax_error("unterminated phrase")),
+                    }
+                }
+                tokens.push(QueryToken::Phrase(phrase));
+            }
+            '*' | ':' | '(' | ')' => {
+                chars.next();
+                tokens.push(match c {
+                    '*' => QueryToken::Star,
+                    ':' => QueryToken::Colon,
+                    '(' => QueryToken::Open,
+                    _ => QueryToken::Close,
+                });
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(QueryToken::Word(word));
+            }
+            other => return Err(fts_syntax_error(format!("near \"{}\"", other))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct QueryParser {
+    tokens: Vec<QueryToken>,
+    position: usize,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&QueryToken> {
+        self.tokens.get(self.position)
+    }
+
+    /// Consume the operator `word` (operators are upper case, as in FTS5)
+    fn operator(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(QueryToken::Word(next)) if next == word) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or_query(&mut self) -> SqlResult<FtsQuery> {
+        let mut query = self.and_query()?;
+        while self.operator("OR") {
+            query = FtsQuery::Or(Box::new(query), Box::new(self.and_query()?));
+        }
+        Ok(query)
+    }
+
+    fn and_query(&mut self) -> SqlResult<FtsQuery> {
+        let mut query = self.not_query()?;
+        loop {
+            let explicit = self.operator("AND");
+            let next_starts_query = match self.peek() {
+                Some(QueryToken::Word(word)) => word != "OR" && word != "NOT" && word != "AND",
+                Some(QueryToken::Phrase(_)) | Some(QueryToken::Open) => true,
+                _ => false,
+            };
+            if !explicit && !next_starts_query {
+                return Ok(query);
+            }
+            query = query.and(self.not_query()?);
+        }
+    }
+
+    fn not_query(&mut self) -> SqlResult<FtsQuery> {
+        let mut query = self.primary()?;
+        while self.operator("NOT") {
+            query = FtsQuery::Not(Box::new(query), Box::new(self.primary()?));
+        }
+        Ok(query)
+    }
+
+    fn primary(&mut self) -> SqlResult<FtsQuery> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        match token {
+            Some(QueryToken::Open) => {
+                let query = self.or_query()?;
+                match self.peek() {
+                    Some(QueryToken::Close) => {
+                        self.position += 1;
+                        Ok(query)
+                    }
+                    _ => Err(fts_syntax_error("expected \")\"")),
+                }
+            }
+            Some(QueryToken::Phrase(phrase)) => {
+                let prefix = self.star();
+                Ok(match prefix {
+                    true => FtsQuery::Term { word: phrase, prefix },
+                    false => FtsQuery::Phrase(phrase),
+                })
+            }
+            Some(QueryToken::Word(word)) if matches!(word.as_str(), "AND" | "OR" | "NOT") => {
+                Err(fts_syntax_error(format!("unexpected \"{}\"", word)))
+            }
+            Some(QueryToken::Word(word)) if self.peek() == Some(&QueryToken::Colon) => {
+                self.position += 1;
+                Ok(self.primary()?.in_column(word))
+            }
+            Some(QueryToken::Word(word)) => {
+                let prefix = self.star();
+                Ok(FtsQuery::Term { word, prefix })
+            }
+            Some(other) => Err(fts_syntax_error(format!("unexpected {}", other))),
+            None => Err(fts_syntax_error("unexpected end of query")),
+        }
+    }
+
+    fn star(&mut self) -> bool {
+        let star = self.peek() == Some(&QueryToken::Star);
+        if star {
+            self.position += 1;
+        }
+        star
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_match_queries() {
+        let query = FtsQuery::parse("rust OR title:\"query planner\" data* NOT java").unwrap();
+        assert_eq!(query.to_string(), "(rust OR (title:(\"query planner\") AND (data* NOT java)))");
+        assert_eq!(
+            FtsQuery::parse("(a OR b) c").unwrap().to_string(),
+            "((a OR b) AND c)"
+        );
+        for invalid in ["", "a OR", "(a", "\"open", "a + b", "NOT a"] {
+            assert!(FtsQuery::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
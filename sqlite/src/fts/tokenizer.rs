@@ -0,0 +1,153 @@
+use crate::error::{SqlError, SqlResult};
+use serde::{Deserialize, Serialize};
+
+/// A word of indexed text with its position among the words of its column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub position: u32,
+}
+
+/// A step of the tokenizer pipeline, applied to every word in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenFilter {
+    /// Fold case, so `Rust` matches `rust`
+    Lowercase,
+    /// Drop common English words that say little about a document
+    StopWords,
+    /// Strip common English suffixes, so `searching` matches `searches`
+    Stem,
+}
+
+/// Common English words left out of the index by [`TokenFilter::StopWords`]
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it", "no", "not", "of",
+    "on", "or", "such", "that", "the", "their", "then", "there", "these", "they", "this", "to", "was", "will", "with",
+];
+
+/// Splits text into words on anything that is not a letter or digit, then
+/// runs each word through its filters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tokenizer {
+    filters: Vec<TokenFilter>,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Tokenizer::new(vec![TokenFilter::Lowercase])
+    }
+}
+
+impl Tokenizer {
+    pub fn new(filters: Vec<TokenFilter>) -> Self {
+        Tokenizer { filters }
+    }
+
+    /// Build a tokenizer from a `tokenize = '...'` option: `simple` (or
+    /// `unicode61`) folds case, `porter` also stems and `stopwords` also
+    /// drops stop words; they can be combined, e.g. `porter stopwords`
+    pub fn parse(spec: &str) -> SqlResult<Self> {
+        let mut filters = vec![TokenFilter::Lowercase];
+        for word in spec.split_whitespace() {
+            let filter = match word.to_ascii_lowercase().as_str() {
+                "simple" | "unicode61" => continue,
+                "porter" => TokenFilter::Stem,
+                "stopwords" => TokenFilter::StopWords,
+                _ => return Err(SqlError::parse_error(format!("Unknown tokenizer: {}", word))),
+            };
+            if !filters.contains(&filter) {
+                filters.push(filter);
+            }
+        }
+        // Stop words are matched before their suffixes are stripped
+        filters.sort_by_key(|filter| *filter as u8);
+        Ok(Tokenizer::new(filters))
+    }
+
+    pub fn filters(&self) -> &[TokenFilter] {
+        &self.filters
+    }
+
+    /// The words of `text` that survive the filters; a dropped word still
+    /// takes up a position, so phrases only match words that were adjacent
+    pub fn tokenize(&self, text: &str) -> Vec<Token> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .enumerate()
+            .filter_map(|(position, word)| {
+                self.normalize(word).map(|text| Token {
+                    text,
+                    position: position as u32,
+                })
+            })
+            .collect()
+    }
+
+    /// Run one word through the filters; `None` if it is dropped
+    pub fn normalize(&self, word: &str) -> Option<String> {
+        let mut word = word.to_string();
+        for filter in &self.filters {
+            match filter {
+                TokenFilter::Lowercase => word = word.to_lowercase(),
+                TokenFilter::StopWords if STOP_WORDS.contains(&word.as_str()) => return None,
+                TokenFilter::StopWords => {}
+                TokenFilter::Stem => word = stem(&word),
+            }
+        }
+        Some(word)
+    }
+}
+
+/// A light English stemmer: plural and `-ing`/`-ed`/`-ly` endings are
+/// removed from words long enough to keep a stem of three letters
+fn stem(word: &str) -> String {
+    if !word.is_ascii() || word.len() <= 3 {
+        return word.to_string();
+    }
+    let mut stem = word.to_string();
+    if let Some(base) = stem.strip_suffix("ies").filter(|base| base.len() >= 2) {
+        stem = format!("{}y", base);
+    } else if ["sses", "ches", "shes", "xes", "zes"].iter().any(|suffix| stem.ends_with(suffix)) {
+        stem.truncate(stem.len() - 2);
+    } else if stem.ends_with('s') && !stem.ends_with("ss") && !stem.ends_with("us") && !stem.ends_with("is") {
+        stem.pop();
+    }
+
+    for suffix in ["ing", "ed", "ly"] {
+        if let Some(base) = stem.strip_suffix(suffix).filter(|base| base.len() >= 3) {
+            let mut base = base.to_string();
+            // `running` -> `run`, but `falling` keeps its double `l`
+            let bytes = base.as_bytes();
+            let last = bytes[bytes.len() - 1];
+            if suffix != "ly" && last == bytes[bytes.len() - 2] && !b"aeioulsz".contains(&last) {
+                base.pop();
+            }
+            return base;
+        }
+    }
+    stem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenizer_pipeline() {
+        let simple = Tokenizer::default();
+        let tokens = simple.tokenize("Hello, World! It's 2024");
+        let words: Vec<&str> = tokens.iter().map(|token| token.text.as_str()).collect();
+        assert_eq!(words, vec!["hello", "world", "it", "s", "2024"]);
+
+        let porter = Tokenizer::parse("porter stopwords").unwrap();
+        let tokens = porter.tokenize("The runners were running and searches stopped");
+        let words: Vec<(&str, u32)> = tokens.iter().map(|token| (token.text.as_str(), token.position)).collect();
+        assert_eq!(
+            words,
+            vec![("runner", 1), ("were", 2), ("run", 3), ("search", 5), ("stop", 6)]
+        );
+        assert_eq!(porter.normalize("Studies"), Some("study".to_string()));
+        assert_eq!(porter.normalize("the"), None);
+        assert!(Tokenizer::parse("trigram").is_err());
+    }
+}
@@ -4,15 +4,22 @@
 //! and algebraic patterns for maximum correctness and compositionality.
 
 pub mod types;
+pub mod collation;
 pub mod error;
 pub mod parser;
 pub mod btree;
 pub mod page_cache;
+pub mod storage;
 pub mod query;
 pub mod transaction;
 pub mod schema;
 pub mod engine;
+pub mod fts;
+pub mod fdw;
+pub mod server;
+pub mod transfer;
 
+pub use collation::Collation;
 pub use engine::CategoricalSQLite;
 pub use error::{SqlError, SqlResult};
 pub use types::{Value, Row, DataType};
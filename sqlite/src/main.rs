@@ -1,6 +1,13 @@
+mod shell;
+
+use categorical_sqlite::engine::Session;
+use categorical_sqlite::server::{PasswordAuthenticator, Server};
+use categorical_sqlite::transfer::{self, Format, Progress, TransferOptions};
 use categorical_sqlite::{CategoricalSQLite, SqlError};
-use clap::{Parser, Subcommand};
-use std::io::{self, Write};
+use clap::{Args, Parser, Subcommand};
+use shell::{Output, Shell};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[derive(Parser)]
 #[command(name = "sqlite")]
@@ -30,6 +37,79 @@ enum Commands {
     Stats,
     /// Check database health
     Health,
+    /// Serve the database to PostgreSQL clients (psql, drivers)
+    Serve {
+        /// Address to listen on; `:PORT` listens on every interface
+        #[arg(long, default_value = ":5433")]
+        listen: String,
+        /// Require this user name and --password; without it every client
+        /// is let in
+        #[arg(long, requires = "password")]
+        user: Option<String>,
+        /// Password of --user, sent by clients in cleartext
+        #[arg(long, requires = "user")]
+        password: Option<String>,
+    },
+    /// Import a CSV or JSON file into a table, creating it if needed
+    Import(ImportArgs),
+    /// Export a table to a CSV or JSON file
+    Export(ExportArgs),
+}
+
+/// `import FILE TABLE`, also `.import` in the shell
+#[derive(Parser)]
+#[command(name = ".import")]
+struct ImportArgs {
+    file: PathBuf,
+    table: String,
+    #[command(flatten)]
+    options: TransferArgs,
+}
+
+/// `export TABLE FILE`, also `.export` in the shell
+#[derive(Parser)]
+#[command(name = ".export")]
+struct ExportArgs {
+    table: String,
+    file: PathBuf,
+    #[command(flatten)]
+    options: TransferArgs,
+}
+
+#[derive(Args)]
+struct TransferArgs {
+    /// csv, tsv, json or jsonl; taken from the file extension by default
+    #[arg(long)]
+    format: Option<String>,
+    /// CSV field delimiter: one character, or `\t` for tabs
+    #[arg(long)]
+    delimiter: Option<String>,
+    /// No header row of column names in the CSV file
+    #[arg(long)]
+    no_header: bool,
+}
+
+impl TransferArgs {
+    fn options(&self, file: &Path) -> Result<TransferOptions, SqlError> {
+        let mut options = match &self.format {
+            Some(format) => TransferOptions {
+                format: Format::from_name(format)?,
+                delimiter: if format.eq_ignore_ascii_case("tsv") { '\t' } else { ',' },
+                ..TransferOptions::default()
+            },
+            None => TransferOptions::for_path(file)?,
+        };
+        if let Some(delimiter) = &self.delimiter {
+            let mut chars = delimiter.chars();
+            options.delimiter = match (delimiter.as_str(), chars.next(), chars.next()) {
+                ("\\t" | "tab", _, _) => '\t',
+                (_, Some(c), None) => c,
+                _ => return Err(SqlError::parse_error(format!("Invalid delimiter '{}'", delimiter))),
+            };
+        }
+        options.header = !self.no_header;
+        Ok(options)
+    }
 }
 
 #[tokio::main]
@@ -37,11 +117,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
     
     let config = categorical_sqlite::engine::DatabaseConfig::default();
-    let db = CategoricalSQLite::new(config);
+    let db = match &cli.database {
+        Some(path) => CategoricalSQLite::open(path, config).await?,
+        None => CategoricalSQLite::new(config),
+    };
     
     match cli.command {
         Some(Commands::Shell) => {
-            run_interactive_shell(db).await?;
+            Shell::new(&db).run().await?;
         }
         Some(Commands::Execute { sql }) => {
             execute_sql(&db, &sql).await?;
@@ -61,96 +144,74 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(Commands::Health) => {
             check_health(&db).await?;
         }
+        Some(Commands::Import(args)) => {
+            let mut session = db.session();
+            run_import(&mut session, &args).await?;
+            session.close().await?;
+        }
+        Some(Commands::Export(args)) => {
+            let mut session = db.session();
+            run_export(&mut session, &args).await?;
+            session.close().await?;
+        }
+        Some(Commands::Serve { listen, user, password }) => {
+            let server = Server::new(db.clone());
+            let server = match (user, password) {
+                (Some(user), Some(password)) => {
+                    server.with_authenticator(PasswordAuthenticator::new().with_user(user, password))
+                }
+                _ => server,
+            };
+            println!("Listening for PostgreSQL clients on {}", listen);
+            server.listen(&listen).await?;
+        }
         None => {
-            run_interactive_shell(db).await?;
+            Shell::new(&db).run().await?;
         }
     }
-    
+
+    db.close().await?;
     Ok(())
 }
 
-async fn run_interactive_shell(db: CategoricalSQLite) -> Result<(), SqlError> {
-    println!("Categorical SQLite v0.1.0");
-    println!("Enter SQL commands (type .exit to quit):");
-    
-    loop {
-        print!("sqlite> ");
-        io::stdout().flush().unwrap();
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-        
-        if input == ".exit" {
-            break;
-        }
-        
-        if input.is_empty() {
-            continue;
-        }
-        
-        match execute_sql(&db, input).await {
-            Ok(()) => {}
-            Err(e) => println!("Error: {}", e),
-        }
-    }
-    
+async fn run_import(session: &mut Session, args: &ImportArgs) -> Result<(), SqlError> {
+    let options = args.options.options(&args.file)?;
+    let rows = transfer::import_file(session, &args.file, &args.table, &options, |progress| {
+        show_progress("Imported", progress)
+    })
+    .await;
+    eprintln!();
+    println!("Imported {} row(s) into {}", rows?, args.table);
+    Ok(())
+}
+
+async fn run_export(session: &mut Session, args: &ExportArgs) -> Result<(), SqlError> {
+    let options = args.options.options(&args.file)?;
+    let rows = transfer::export_file(session, &args.table, &args.file, &options, |progress| {
+        show_progress("Exported", progress)
+    })
+    .await;
+    eprintln!();
+    println!("Exported {} row(s) to {}", rows?, args.file.display());
     Ok(())
 }
 
+/// Overwrite the progress line on stderr
+fn show_progress(verb: &str, progress: &Progress) {
+    match progress.total_bytes.filter(|total| *total > 0) {
+        Some(total) => eprint!(
+            "\r{} {} rows ({:.0}%)",
+            verb,
+            progress.rows,
+            progress.bytes as f64 * 100.0 / total as f64
+        ),
+        None => eprint!("\r{} {} rows ({} bytes)", verb, progress.rows, progress.bytes),
+    }
+}
+
 async fn execute_sql(db: &CategoricalSQLite, sql: &str) -> Result<(), SqlError> {
     let result = db.execute_sql(sql).await?;
-    
-    // Display results
-    match result {
-        categorical_sqlite::query::QueryResult::Select { rows, columns } => {
-            // Print column headers
-            for (i, col) in columns.iter().enumerate() {
-                if i > 0 { print!(" | "); }
-                print!("{}", col);
-            }
-            println!();
-            
-            // Print separator
-            for (i, col) in columns.iter().enumerate() {
-                if i > 0 { print!("-+-"); }
-                print!("{}", "-".repeat(col.len()));
-            }
-            println!();
-            
-            // Print rows
-            for row in rows {
-                for (i, value) in row.values.iter().enumerate() {
-                    if i > 0 { print!(" | "); }
-                    print!("{}", value);
-                }
-                println!();
-            }
-        }
-        categorical_sqlite::query::QueryResult::Insert { rows_affected } => {
-            println!("Inserted {} row(s)", rows_affected);
-        }
-        categorical_sqlite::query::QueryResult::Update { rows_affected } => {
-            println!("Updated {} row(s)", rows_affected);
-        }
-        categorical_sqlite::query::QueryResult::Delete { rows_affected } => {
-            println!("Deleted {} row(s)", rows_affected);
-        }
-        categorical_sqlite::query::QueryResult::CreateTable => {
-            println!("Table created successfully");
-        }
-        categorical_sqlite::query::QueryResult::DropTable => {
-            println!("Table dropped successfully");
-        }
-        categorical_sqlite::query::QueryResult::CreateIndex => {
-            println!("Index created successfully");
-        }
-        categorical_sqlite::query::QueryResult::DropIndex => {
-            println!("Index dropped successfully");
-        }
-    }
-    
-    Ok(())
+    Output::default().print(result)
 }
 
 async fn show_schema(db: &CategoricalSQLite) -> Result<(), SqlError> {
@@ -263,6 +324,56 @@ async fn run_demonstration(db: &CategoricalSQLite) -> Result<(), Box<dyn std::er
     Ok(())
 }
 
+/// Queries per second `readers` threads manage on `benchmark_test` over a
+/// second, while another thread updates it
+fn read_throughput(db: &CategoricalSQLite, readers: usize) -> Result<f64, SqlError> {
+    let duration = std::time::Duration::from_secs(1);
+    let stop = AtomicBool::new(false);
+    let start = std::time::Instant::now();
+    let queries = std::thread::scope(|scope| {
+        let writer = scope.spawn(|| {
+            block_on(async {
+                while !stop.load(Ordering::Relaxed) {
+                    db.execute_sql("UPDATE benchmark_test SET data = 'updated' WHERE id = 1").await?;
+                }
+                Ok(())
+            })
+        });
+        let readers: Vec<_> = (0..readers)
+            .map(|_| {
+                scope.spawn(|| {
+                    block_on(async {
+                        let mut reads = 0u64;
+                        while start.elapsed() < duration {
+                            db.execute_sql("SELECT COUNT(*) FROM benchmark_test WHERE data LIKE 'test%'").await?;
+                            reads += 1;
+                        }
+                        Ok(reads)
+                    })
+                })
+            })
+            .collect();
+        let queries: Result<u64, SqlError> = readers
+            .into_iter()
+            .map(|reader| reader.join().expect("reader thread panicked"))
+            .sum();
+        stop.store(true, Ordering::Relaxed);
+        writer.join().expect("writer thread panicked")?;
+        queries
+    })?;
+    Ok(queries as f64 / start.elapsed().as_secs_f64())
+}
+
+/// Run `task` to completion on a runtime of the calling thread; statement
+/// futures are not `Send`, so threads cannot share one runtime
+fn block_on<T>(task: impl std::future::Future<Output = Result<T, SqlError>>) -> Result<T, SqlError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|error| SqlError::io_error(error.to_string()))?;
+    runtime.block_on(task)
+}
+
 async fn run_benchmarks(db: &CategoricalSQLite) -> Result<(), Box<dyn std::error::Error>> {
     println!("🏃 Performance Benchmarks");
     println!("=========================");
@@ -292,6 +403,16 @@ async fn run_benchmarks(db: &CategoricalSQLite) -> Result<(), Box<dyn std::error
     println!("  Query performance: {:?}", query_duration);
     println!("  Rows per second: {:.0}", 100.0 / insert_duration.as_secs_f64());
 
+    // Reads query the last commit and do not wait for the writer, so read
+    // throughput grows with the reader threads while a writer keeps going
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get()).clamp(2, 8);
+    let single = read_throughput(db, 1)?;
+    let many = read_throughput(db, threads)?;
+    println!();
+    println!("Concurrent reads (with a writer running):");
+    println!("  1 reader: {:.0} queries/s", single);
+    println!("  {} readers: {:.0} queries/s ({:.1}x)", threads, many, many / single);
+
     // Show cache statistics
     let stats = db.get_statistics().await;
     println!();
@@ -14,6 +14,7 @@ pub struct PageCacheCoalgebra {
     cache: HashMap<PageId, Arc<PageData>>,
     capacity: usize,
     access_order: Vec<PageId>, // For LRU eviction
+    evicted_dirty: Vec<(PageId, Arc<PageData>)>, // Awaiting write-back
 }
 
 impl PageCacheCoalgebra {
@@ -23,6 +24,7 @@ impl PageCacheCoalgebra {
             cache: HashMap::new(),
             capacity,
             access_order: Vec::new(),
+            evicted_dirty: Vec::new(),
         }
     }
 
@@ -90,6 +92,12 @@ impl PageCacheCoalgebra {
             .collect()
     }
 
+    /// Take the dirty pages evicted to make room since the last call; the
+    /// caller must write them back or they are lost
+    pub fn take_evicted_dirty(&mut self) -> Vec<(PageId, Arc<PageData>)> {
+        std::mem::take(&mut self.evicted_dirty)
+    }
+
     /// Get least recently used page ID
     pub fn get_lru_page(&self) -> Option<PageId> {
         self.access_order.first().cloned()
@@ -168,7 +176,11 @@ impl PageCacheCoalgebra {
     }
 
     fn evict_page(&mut self, page_id: PageId) {
-        self.cache.remove(&page_id);
+        if let Some(page_data) = self.cache.remove(&page_id) {
+            if page_data.is_dirty {
+                self.evicted_dirty.push((page_id, page_data));
+            }
+        }
         self.remove_from_access_order(page_id);
     }
 
@@ -190,6 +202,7 @@ impl Clone for PageCacheCoalgebra {
             cache: self.cache.clone(),
             capacity: self.capacity,
             access_order: self.access_order.clone(),
+            evicted_dirty: self.evicted_dirty.clone(),
         }
     }
 }
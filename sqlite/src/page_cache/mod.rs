@@ -1,10 +1,12 @@
 pub mod coalgebra;
 pub mod page;
 pub mod lru;
+pub mod storage;
 
 pub use coalgebra::PageCacheCoalgebra;
 pub use page::{Page, PageData, PageType};
 pub use lru::LruCache;
+pub use storage::{FilePageStorage, PageStorage};
 
 use crate::error::{SqlError, SqlResult};
 use crate::types::PageId;
@@ -83,6 +85,12 @@ pub struct PageCache {
     coalgebra: Arc<Mutex<PageCacheCoalgebra>>,
     config: PageCacheConfig,
     stats: Arc<Mutex<PageCacheStats>>,
+    storage: Option<Arc<dyn PageStorage>>,
+    /// Latch of each page being read from storage or stored. A miss reads
+    /// storage under the page's latch only, so readers of other pages
+    /// carry on, and a store cannot be overwritten by an older image of
+    /// the page read at the same time.
+    latches: Arc<Mutex<HashMap<PageId, Arc<tokio::sync::Mutex<()>>>>>,
 }
 
 impl PageCache {
@@ -91,44 +99,63 @@ impl PageCache {
             coalgebra: Arc::new(Mutex::new(PageCacheCoalgebra::new(config.capacity))),
             config,
             stats: Arc::new(Mutex::new(PageCacheStats::new())),
+            storage: None,
+            latches: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Create a cache backed by `storage`: misses are read from it and dirty
+    /// pages are written back on flush and eviction
+    pub fn with_storage(config: PageCacheConfig, storage: Arc<dyn PageStorage>) -> Self {
+        PageCache {
+            storage: Some(storage),
+            ..Self::new(config)
         }
     }
 
     /// Load a page from cache or storage
     pub async fn load_page(&self, page_id: PageId) -> SqlResult<Arc<PageData>> {
-        let mut coalgebra = self.coalgebra.lock().unwrap();
-        let mut stats = self.stats.lock().unwrap();
-
-        match coalgebra.process_input(PageCacheTransition::Load(page_id)) {
-            (Some(page_data), _) => {
-                stats.cache_hits += 1;
-                Ok(page_data)
-            }
-            (None, _) => {
-                stats.cache_misses += 1;
-                // Load from storage (simulated)
-                let page_data = self.load_from_storage(page_id).await?;
-                let arc_data = Arc::new(page_data.clone());
-                
-                // Store in cache
-                coalgebra.process_input(PageCacheTransition::Store(page_id, page_data));
-                stats.total_pages += 1;
-                
-                Ok(arc_data)
+        if let Some(page_data) = self.cached(page_id) {
+            return Ok(page_data);
+        }
+        let latch = self.latch(page_id);
+        let loaded = async {
+            let _latched = latch.lock().await;
+            // Another reader may have loaded the page while this one waited
+            if let Some(page_data) = self.cached(page_id) {
+                return Ok(page_data);
             }
+            self.stats.lock().unwrap().cache_misses += 1;
+            let page_data = self.load_from_storage(page_id).await?;
+            let arc_data = Arc::new(page_data.clone());
+
+            let mut coalgebra = self.coalgebra.lock().unwrap();
+            let mut stats = self.stats.lock().unwrap();
+            coalgebra.process_input(PageCacheTransition::Store(page_id, page_data));
+            stats.total_pages += 1;
+            self.write_back_evicted(&mut coalgebra, &mut stats)?;
+            Ok(arc_data)
         }
+        .await;
+        self.release_latch(page_id, latch);
+        loaded
     }
 
     /// Store a page in cache
     pub async fn store_page(&self, page_id: PageId, page_data: PageData) -> SqlResult<()> {
-        let mut coalgebra = self.coalgebra.lock().unwrap();
-        let mut stats = self.stats.lock().unwrap();
-
-        coalgebra.process_input(PageCacheTransition::Store(page_id, page_data.clone()));
-        
-        if page_data.is_dirty {
-            stats.dirty_pages += 1;
-        }
+        let latch = self.latch(page_id);
+        let stored = {
+            let _latched = latch.lock().await;
+            let mut coalgebra = self.coalgebra.lock().unwrap();
+            let mut stats = self.stats.lock().unwrap();
+            coalgebra.process_input(PageCacheTransition::Store(page_id, page_data.clone()));
+            if page_data.is_dirty {
+                stats.dirty_pages += 1;
+            }
+            self.write_back_evicted(&mut coalgebra, &mut stats)
+        };
+        self.release_latch(page_id, latch);
+        stored?;
 
         // Write through if enabled
         if self.config.enable_write_through {
@@ -153,6 +180,7 @@ impl PageCache {
                     is_dirty: false,
                     page_type: page_data.page_type,
                     checksum: page_data.checksum,
+                    lsn: page_data.lsn,
                 };
                 
                 coalgebra.process_input(PageCacheTransition::Store(page_id, clean_data));
@@ -169,9 +197,15 @@ impl PageCache {
         let mut coalgebra = self.coalgebra.lock().unwrap();
         let mut stats = self.stats.lock().unwrap();
 
+        if self.storage.is_some() {
+            for page_id in coalgebra.get_dirty_pages() {
+                if let (Some(page_data), _) = coalgebra.process_input(PageCacheTransition::Load(page_id)) {
+                    self.write_page_sync(page_id, &page_data)?;
+                }
+            }
+        }
         coalgebra.process_input(PageCacheTransition::FlushAll);
         
-        // In a real implementation, this would iterate through all dirty pages
         stats.dirty_pages = 0;
         stats.flushes += 1;
 
@@ -256,9 +290,36 @@ impl PageCache {
     }
 
     // Private helper methods
+
+    /// The page if it is cached, counting the hit
+    fn cached(&self, page_id: PageId) -> Option<Arc<PageData>> {
+        let mut coalgebra = self.coalgebra.lock().unwrap();
+        let (page_data, _) = coalgebra.process_input(PageCacheTransition::Load(page_id));
+        if page_data.is_some() {
+            self.stats.lock().unwrap().cache_hits += 1;
+        }
+        page_data
+    }
+
+    fn latch(&self, page_id: PageId) -> Arc<tokio::sync::Mutex<()>> {
+        self.latches.lock().unwrap().entry(page_id).or_default().clone()
+    }
+
+    /// Forget the latch of a page once no one else holds or waits for it
+    fn release_latch(&self, page_id: PageId, latch: Arc<tokio::sync::Mutex<()>>) {
+        let mut latches = self.latches.lock().unwrap();
+        // The map and `latch` are the only references left
+        if Arc::strong_count(&latch) == 2 {
+            latches.remove(&page_id);
+        }
+    }
     async fn load_from_storage(&self, page_id: PageId) -> SqlResult<PageData> {
-        // Simulate loading from storage
-        // In a real implementation, this would read from disk
+        if let Some(storage) = &self.storage {
+            if let Some(page_data) = storage.read_page(page_id)? {
+                return Ok(page_data);
+            }
+        }
+        // Pages that were never written read as zeroes
         Ok(PageData::new(
             vec![0; self.config.page_size],
             PageType::Data,
@@ -266,8 +327,29 @@ impl PageCache {
     }
 
     async fn write_to_storage(&self, page_id: PageId, page_data: &PageData) -> SqlResult<()> {
-        // Simulate writing to storage
-        // In a real implementation, this would write to disk
+        self.write_page_sync(page_id, page_data)
+    }
+
+    fn write_page_sync(&self, page_id: PageId, page_data: &PageData) -> SqlResult<()> {
+        match &self.storage {
+            Some(storage) => storage.write_page(page_id, page_data),
+            // Without backing storage writes are dropped
+            None => Ok(()),
+        }
+    }
+
+    /// Write back dirty pages the LRU policy evicted to make room
+    fn write_back_evicted(
+        &self,
+        coalgebra: &mut PageCacheCoalgebra,
+        stats: &mut PageCacheStats,
+    ) -> SqlResult<()> {
+        for (page_id, page_data) in coalgebra.take_evicted_dirty() {
+            self.write_page_sync(page_id, &page_data)?;
+            stats.evictions += 1;
+            stats.dirty_pages = stats.dirty_pages.saturating_sub(1);
+            stats.flushes += 1;
+        }
         Ok(())
     }
 }
@@ -278,6 +360,8 @@ impl Clone for PageCache {
             coalgebra: Arc::clone(&self.coalgebra),
             config: self.config.clone(),
             stats: Arc::clone(&self.stats),
+            storage: self.storage.clone(),
+            latches: Arc::clone(&self.latches),
         }
     }
 }
@@ -321,4 +405,66 @@ mod tests {
         let stats = cache.get_stats();
         assert_eq!(stats.flushes, 1);
     }
+
+    #[tokio::test]
+    async fn test_evicted_dirty_pages_are_written_back() {
+        let config = PageCacheConfig {
+            capacity: 2,
+            page_size: 16,
+            ..PageCacheConfig::default()
+        };
+        let storage = Arc::new(FilePageStorage::temporary(config.page_size).unwrap());
+        let cache = PageCache::with_storage(config, storage.clone());
+
+        for id in 0..5u32 {
+            let page = PageData::new_dirty(vec![id as u8; 4], PageType::Overflow);
+            cache.store_page(PageId(id), page).await.unwrap();
+        }
+        assert!(storage.read_page(PageId(0)).unwrap().is_some());
+
+        // Pages that fell out of the cache come back from storage
+        for id in 0..5u32 {
+            assert_eq!(cache.load_page(PageId(id)).await.unwrap().data, vec![id as u8; 4]);
+        }
+    }
+
+    /// Counts its reads, each of which takes a while
+    #[derive(Debug, Default)]
+    struct SlowStorage {
+        reads: std::sync::atomic::AtomicUsize,
+    }
+
+    impl PageStorage for SlowStorage {
+        fn read_page(&self, page_id: PageId) -> SqlResult<Option<PageData>> {
+            self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            Ok(Some(PageData::new(vec![page_id.0 as u8; 4], PageType::Data)))
+        }
+
+        fn write_page(&self, _page_id: PageId, _page: &PageData) -> SqlResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_misses_latch_each_page() {
+        let storage = Arc::new(SlowStorage::default());
+        let cache = Arc::new(PageCache::with_storage(PageCacheConfig::default(), storage.clone()));
+        let start = std::time::Instant::now();
+        let ids = [1u32, 1, 1, 2];
+        let loads: Vec<_> = ids
+            .iter()
+            .map(|&id| {
+                let cache = cache.clone();
+                tokio::spawn(async move { cache.load_page(PageId(id)).await.unwrap().data.clone() })
+            })
+            .collect();
+        for (load, id) in loads.into_iter().zip(ids) {
+            assert_eq!(load.await.unwrap(), vec![id as u8; 4]);
+        }
+        // One read per page, and the two pages were read side by side
+        assert_eq!(storage.reads.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert!(start.elapsed() < std::time::Duration::from_millis(390));
+        assert!(cache.latches.lock().unwrap().is_empty());
+    }
 }
@@ -76,6 +76,10 @@ pub struct PageData {
     pub page_type: PageType,
     /// Checksum for integrity verification
     pub checksum: u32,
+    /// Log sequence number of the commit that last wrote the page; 0 for
+    /// pages never committed through a pager
+    #[serde(default)]
+    pub lsn: u64,
 }
 
 impl PageData {
@@ -87,6 +91,7 @@ impl PageData {
             is_dirty: false,
             page_type,
             checksum,
+            lsn: 0,
         }
     }
 
@@ -98,9 +103,16 @@ impl PageData {
             is_dirty: true,
             page_type,
             checksum,
+            lsn: 0,
         }
     }
 
+    /// The same page, stamped with the LSN of the commit writing it
+    pub fn with_lsn(mut self, lsn: u64) -> Self {
+        self.lsn = lsn;
+        self
+    }
+
     /// Create empty page of given size
     pub fn empty(size: usize, page_type: PageType) -> Self {
         Self::new(vec![0; size], page_type)
@@ -0,0 +1,197 @@
+use crate::error::{SqlError, SqlResult};
+use crate::page_cache::{PageData, PageType};
+use crate::types::PageId;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Backing store the page cache reads missing pages from and writes dirty
+/// pages back to
+pub trait PageStorage: Send + Sync + fmt::Debug {
+    /// Read a page; `None` if it was never written
+    fn read_page(&self, page_id: PageId) -> SqlResult<Option<PageData>>;
+
+    /// Write a page, replacing any previous contents
+    fn write_page(&self, page_id: PageId, page: &PageData) -> SqlResult<()>;
+
+    /// Make every completed write durable
+    fn sync(&self) -> SqlResult<()> {
+        Ok(())
+    }
+}
+
+/// Size of the per-slot header: page type byte, padding, data length, LSN
+const SLOT_HEADER: usize = 16;
+
+/// Page storage in a single file of fixed-size slots, one per page id
+///
+/// Each slot holds a 16-byte header (page type, data length, LSN) followed
+/// by up to `page_size` bytes of page data.
+pub struct FilePageStorage {
+    file: Mutex<File>,
+    path: PathBuf,
+    page_size: usize,
+    temporary: bool,
+}
+
+impl FilePageStorage {
+    /// Open (or create) a page file at `path`
+    pub fn open(path: impl AsRef<Path>, page_size: usize) -> SqlResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| SqlError::io_error(format!("Cannot open {}: {}", path.display(), e)))?;
+        Ok(FilePageStorage {
+            file: Mutex::new(file),
+            path,
+            page_size,
+            temporary: false,
+        })
+    }
+
+    /// Create an anonymous page file in the system temp directory that is
+    /// removed when the storage is dropped
+    pub fn temporary(page_size: usize) -> SqlResult<Self> {
+        let path = std::env::temp_dir().join(format!("categorical-sqlite-{}.spill", uuid::Uuid::new_v4()));
+        let mut storage = Self::open(path, page_size)?;
+        storage.temporary = true;
+        Ok(storage)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Cut the file after its first `page_count` slots; a file that is
+    /// already no longer is left alone
+    pub fn truncate(&self, page_count: u32) -> SqlResult<()> {
+        let file = self.file.lock().unwrap();
+        let len = self.slot_offset(PageId(page_count));
+        if file.metadata().map_err(io_error)?.len() > len {
+            file.set_len(len).map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    fn slot_offset(&self, page_id: PageId) -> u64 {
+        page_id.0 as u64 * (self.page_size + SLOT_HEADER) as u64
+    }
+}
+
+impl PageStorage for FilePageStorage {
+    fn read_page(&self, page_id: PageId) -> SqlResult<Option<PageData>> {
+        let mut file = self.file.lock().unwrap();
+        let offset = self.slot_offset(page_id);
+        let file_len = file.metadata().map_err(io_error)?.len();
+        if offset + SLOT_HEADER as u64 > file_len {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; SLOT_HEADER];
+        file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+        file.read_exact(&mut header).map_err(io_error)?;
+        let page_type = match PageType::from_byte(header[0]) {
+            Some(page_type) => page_type,
+            // Slot inside the file that was never written (a hole)
+            None => return Ok(None),
+        };
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if len > self.page_size {
+            return Err(SqlError::page_cache_error(format!("Corrupted page slot {}", page_id)));
+        }
+        let mut lsn = [0u8; 8];
+        lsn.copy_from_slice(&header[8..]);
+
+        let mut data = vec![0u8; len];
+        file.read_exact(&mut data).map_err(io_error)?;
+        Ok(Some(PageData::new(data, page_type).with_lsn(u64::from_le_bytes(lsn))))
+    }
+
+    fn write_page(&self, page_id: PageId, page: &PageData) -> SqlResult<()> {
+        if page.data.len() > self.page_size {
+            return Err(SqlError::page_cache_error(format!(
+                "Page {} holds {} bytes, more than the page size {}",
+                page_id,
+                page.data.len(),
+                self.page_size
+            )));
+        }
+        let mut header = [0u8; SLOT_HEADER];
+        header[0] = page.page_type.as_byte();
+        header[4..8].copy_from_slice(&(page.data.len() as u32).to_le_bytes());
+        header[8..].copy_from_slice(&page.lsn.to_le_bytes());
+
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(self.slot_offset(page_id))).map_err(io_error)?;
+        file.write_all(&header).map_err(io_error)?;
+        file.write_all(&page.data).map_err(io_error)?;
+        Ok(())
+    }
+
+    fn sync(&self) -> SqlResult<()> {
+        self.file.lock().unwrap().sync_all().map_err(io_error)
+    }
+}
+
+impl fmt::Debug for FilePageStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilePageStorage")
+            .field("path", &self.path)
+            .field("page_size", &self.page_size)
+            .field("temporary", &self.temporary)
+            .finish()
+    }
+}
+
+impl Drop for FilePageStorage {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn io_error(error: std::io::Error) -> SqlError {
+    SqlError::io_error(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_storage_round_trip() {
+        let storage = FilePageStorage::temporary(64).unwrap();
+        let path = storage.path().to_path_buf();
+
+        assert!(storage.read_page(PageId(3)).unwrap().is_none());
+        storage
+            .write_page(PageId(3), &PageData::new(vec![7; 10], PageType::Overflow).with_lsn(9))
+            .unwrap();
+        storage
+            .write_page(PageId(0), &PageData::new(vec![1, 2, 3], PageType::Data))
+            .unwrap();
+
+        let page = storage.read_page(PageId(3)).unwrap().unwrap();
+        assert_eq!(page.data, vec![7; 10]);
+        assert_eq!(page.page_type, PageType::Overflow);
+        assert_eq!(page.lsn, 9);
+        assert!(storage.read_page(PageId(1)).unwrap().is_none());
+        assert!(storage
+            .write_page(PageId(1), &PageData::new(vec![0; 65], PageType::Data))
+            .is_err());
+
+        drop(storage);
+        assert!(!path.exists());
+    }
+}
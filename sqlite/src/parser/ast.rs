@@ -1,5 +1,7 @@
+use crate::error::{SqlError, SqlResult};
 use crate::types::{DataType, Value};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// SQL statement AST
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -9,14 +11,47 @@ pub enum Statement {
     Update(UpdateStatement),
     Delete(DeleteStatement),
     CreateTable(CreateTableStatement),
+    /// `CREATE VIRTUAL TABLE name USING module(arguments)`
+    CreateVirtualTable(CreateVirtualTableStatement),
+    /// `CREATE FOREIGN TABLE name (columns) SERVER server OPTIONS (...)`
+    CreateForeignTable(CreateForeignTableStatement),
     DropTable(DropTableStatement),
     CreateIndex(CreateIndexStatement),
     DropIndex(DropIndexStatement),
+    /// `EXPLAIN [QUERY PLAN | ANALYZE] <statement>`
+    Explain(ExplainStatement),
+    /// `ANALYZE [table]`: gather statistics for the query planner
+    Analyze(AnalyzeStatement),
+    /// `BEGIN`, `COMMIT` or `ROLLBACK`; run by a session
+    Transaction(TransactionStatement),
+    /// `VACUUM`: rebuild the database file without its free pages
+    Vacuum,
+}
+
+/// EXPLAIN statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplainStatement {
+    pub mode: ExplainMode,
+    pub statement: Box<Statement>,
+}
+
+/// What an EXPLAIN statement reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExplainMode {
+    /// `EXPLAIN QUERY PLAN`: one line per operator, as in SQLite
+    QueryPlan,
+    /// `EXPLAIN`: the operator tree with the algorithm chosen for each
+    /// operator and its estimated cost and rows; nothing is run
+    Plan,
+    /// `EXPLAIN ANALYZE`: runs the statement and adds the rows each
+    /// operator output and the time it took
+    Analyze,
 }
 
 /// SELECT statement
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SelectStatement {
+    pub distinct: bool,
     pub columns: Vec<SelectColumn>,
     pub from: Option<FromClause>,
     pub where_clause: Option<Expression>,
@@ -47,7 +82,7 @@ pub struct JoinClause {
     pub join_type: JoinType,
     pub table: String,
     pub alias: Option<String>,
-    pub condition: Expression,
+    pub constraint: JoinConstraint,
 }
 
 /// JOIN types
@@ -57,6 +92,18 @@ pub enum JoinType {
     Left,
     Right,
     Full,
+    Cross,
+}
+
+/// How rows of a JOIN are matched
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JoinConstraint {
+    /// `ON <expression>`
+    On(Expression),
+    /// `USING (col, ...)`
+    Using(Vec<String>),
+    /// No constraint (CROSS JOIN)
+    None,
 }
 
 /// ORDER BY clause
@@ -133,7 +180,16 @@ pub enum ColumnConstraint {
     PrimaryKey,
     Unique,
     Default(Value),
-    ForeignKey { table: String, column: String },
+    Check(Expression),
+    /// `COLLATE name`: how the column's text compares and sorts
+    Collate(String),
+    /// `REFERENCES table [(column)]`; without a column the parent's
+    /// primary key is referenced
+    ForeignKey {
+        table: String,
+        column: Option<String>,
+        on_delete: ForeignKeyAction,
+    },
 }
 
 /// Table constraints
@@ -141,13 +197,57 @@ pub enum ColumnConstraint {
 pub enum TableConstraint {
     PrimaryKey(Vec<String>),
     Unique(Vec<String>),
+    Check(Expression),
     ForeignKey {
         columns: Vec<String>,
         foreign_table: String,
+        /// Empty to reference the parent's primary key
         foreign_columns: Vec<String>,
+        on_delete: ForeignKeyAction,
     },
 }
 
+/// What happens to child rows when the parent row they reference is
+/// deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForeignKeyAction {
+    /// Reject the delete; the default
+    #[default]
+    NoAction,
+    /// Reject the delete
+    Restrict,
+    /// Delete the child rows too
+    Cascade,
+    /// Set the child's foreign key columns to NULL
+    SetNull,
+}
+
+/// CREATE VIRTUAL TABLE statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateVirtualTableStatement {
+    pub table_name: String,
+    pub module: String,
+    pub arguments: Vec<ModuleArgument>,
+}
+
+/// An argument of a virtual table module
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ModuleArgument {
+    Column(String),
+    /// `name = value`
+    Option { name: String, value: String },
+}
+
+/// CREATE FOREIGN TABLE statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateForeignTableStatement {
+    pub table_name: String,
+    pub columns: Vec<ColumnDefinition>,
+    pub server: String,
+    /// `key 'value'` pairs, in order
+    pub options: Vec<(String, String)>,
+}
+
 /// DROP TABLE statement
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DropTableStatement {
@@ -171,6 +271,24 @@ pub struct DropIndexStatement {
     pub if_exists: bool,
 }
 
+/// ANALYZE statement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalyzeStatement {
+    /// `None` analyzes every table
+    pub table_name: Option<String>,
+}
+
+/// Transaction control statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionStatement {
+    /// `BEGIN [TRANSACTION | WORK]` or `START TRANSACTION`
+    Begin,
+    /// `COMMIT [TRANSACTION | WORK]` or `END [TRANSACTION | WORK]`
+    Commit,
+    /// `ROLLBACK [TRANSACTION | WORK]`
+    Rollback,
+}
+
 /// SQL expressions
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Expression {
@@ -190,11 +308,24 @@ pub enum Expression {
         name: String,
         args: Vec<Expression>,
     },
+    /// `expr COLLATE name`: compare and sort `expr` with a collation
+    Collate {
+        expr: Box<Expression>,
+        collation: String,
+    },
+    /// Scalar subquery: the first column of its first row, or NULL
     Subquery(Box<SelectStatement>),
     In {
         expr: Box<Expression>,
         list: Vec<Expression>,
     },
+    /// `expr IN (SELECT ...)`; `NOT IN` wraps it in a `NOT`
+    InSubquery {
+        expr: Box<Expression>,
+        subquery: Box<SelectStatement>,
+    },
+    /// `EXISTS (SELECT ...)`; `NOT EXISTS` wraps it in a `NOT`
+    Exists(Box<SelectStatement>),
     Between {
         expr: Box<Expression>,
         low: Box<Expression>,
@@ -202,6 +333,9 @@ pub enum Expression {
     },
     IsNull(Box<Expression>),
     IsNotNull(Box<Expression>),
+    /// Placeholder bound when a prepared statement runs; `?N` is parameter
+    /// N, counting from 1
+    Parameter(usize),
 }
 
 /// Binary operators
@@ -223,6 +357,12 @@ pub enum BinaryOperator {
     Like,
     NotLike,
     Concat,
+    /// Full-text search: `table MATCH 'query'`
+    Match,
+    /// `json -> path`: the element at `path` as JSON text
+    JsonExtract,
+    /// `json ->> path`: the element at `path` as an SQL value
+    JsonExtractValue,
 }
 
 /// Unary operators
@@ -232,3 +372,301 @@ pub enum UnaryOperator {
     Minus,
     Not,
 }
+
+impl Expression {
+    /// Names of all columns this expression reads, without table qualifiers
+    /// (`COUNT(*)` reads none)
+    pub fn column_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_column_names(&mut names);
+        names
+    }
+
+    fn collect_column_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expression::Column(name) if name != "*" => names.push(name),
+            Expression::QualifiedColumn { column, .. } => names.push(column),
+            Expression::BinaryOp { left, right, .. } => {
+                left.collect_column_names(names);
+                right.collect_column_names(names);
+            }
+            Expression::UnaryOp { operand, .. } => operand.collect_column_names(names),
+            Expression::Function { args, .. } => {
+                args.iter().for_each(|arg| arg.collect_column_names(names));
+            }
+            Expression::IsNull(inner) | Expression::IsNotNull(inner) | Expression::Collate { expr: inner, .. } => {
+                inner.collect_column_names(names)
+            }
+            Expression::In { expr, list } => {
+                expr.collect_column_names(names);
+                list.iter().for_each(|item| item.collect_column_names(names));
+            }
+            Expression::Between { expr, low, high } => {
+                expr.collect_column_names(names);
+                low.collect_column_names(names);
+                high.collect_column_names(names);
+            }
+            Expression::InSubquery { expr, .. } => expr.collect_column_names(names),
+            Expression::Column(_)
+            | Expression::Literal(_)
+            | Expression::Subquery(_)
+            | Expression::Exists(_)
+            | Expression::Parameter(_) => {}
+        }
+    }
+
+    /// Whether the expression contains a subquery
+    pub fn has_subquery(&self) -> bool {
+        match self {
+            Expression::Subquery(_) | Expression::InSubquery { .. } | Expression::Exists(_) => true,
+            other => other.children().into_iter().any(Expression::has_subquery),
+        }
+    }
+
+    /// Direct operands, not including subqueries
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::UnaryOp { operand, .. } => vec![operand],
+            Expression::Function { args, .. } => args.iter().collect(),
+            Expression::IsNull(inner) | Expression::IsNotNull(inner) | Expression::Collate { expr: inner, .. } => {
+                vec![inner]
+            }
+            Expression::In { expr, list } => std::iter::once(&**expr).chain(list).collect(),
+            Expression::InSubquery { expr, .. } => vec![expr],
+            Expression::Between { expr, low, high } => vec![expr, low, high],
+            Expression::Literal(_)
+            | Expression::Column(_)
+            | Expression::QualifiedColumn { .. }
+            | Expression::Subquery(_)
+            | Expression::Exists(_)
+            | Expression::Parameter(_) => Vec::new(),
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::BinaryOp { left, right, .. } => vec![left, right],
+            Expression::UnaryOp { operand, .. } => vec![operand],
+            Expression::Function { args, .. } => args.iter_mut().collect(),
+            Expression::IsNull(inner) | Expression::IsNotNull(inner) | Expression::Collate { expr: inner, .. } => {
+                vec![inner]
+            }
+            Expression::In { expr, list } => std::iter::once(&mut **expr).chain(list).collect(),
+            Expression::InSubquery { expr, .. } => vec![expr],
+            Expression::Between { expr, low, high } => vec![expr, low, high],
+            Expression::Literal(_)
+            | Expression::Column(_)
+            | Expression::QualifiedColumn { .. }
+            | Expression::Subquery(_)
+            | Expression::Exists(_)
+            | Expression::Parameter(_) => Vec::new(),
+        }
+    }
+
+    /// Replace every parameter with its value (`values[N - 1]` for `?N`)
+    pub fn bind_parameters(&mut self, values: &[Value]) -> SqlResult<()> {
+        match self {
+            Expression::Parameter(index) => {
+                let value = index
+                    .checked_sub(1)
+                    .and_then(|position| values.get(position))
+                    .ok_or_else(|| SqlError::runtime_error(format!("Parameter ?{} is not bound", index)))?;
+                *self = Expression::Literal(value.clone());
+            }
+            Expression::BinaryOp { left, right, .. } => {
+                left.bind_parameters(values)?;
+                right.bind_parameters(values)?;
+            }
+            Expression::UnaryOp { operand, .. } => operand.bind_parameters(values)?,
+            Expression::Function { args, .. } => {
+                for arg in args {
+                    arg.bind_parameters(values)?;
+                }
+            }
+            Expression::IsNull(inner) | Expression::IsNotNull(inner) | Expression::Collate { expr: inner, .. } => {
+                inner.bind_parameters(values)?
+            }
+            Expression::In { expr, list } => {
+                expr.bind_parameters(values)?;
+                for item in list {
+                    item.bind_parameters(values)?;
+                }
+            }
+            Expression::Between { expr, low, high } => {
+                expr.bind_parameters(values)?;
+                low.bind_parameters(values)?;
+                high.bind_parameters(values)?;
+            }
+            Expression::InSubquery { expr, subquery } => {
+                expr.bind_parameters(values)?;
+                subquery.bind_parameters(values)?;
+            }
+            Expression::Subquery(select) | Expression::Exists(select) => select.bind_parameters(values)?,
+            Expression::Column(_) | Expression::QualifiedColumn { .. } | Expression::Literal(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl Statement {
+    /// Whether running the statement leaves the database as it was; such
+    /// statements can run alongside a writer
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Statement::Select(_) => true,
+            // EXPLAIN ANALYZE runs its statement
+            Statement::Explain(explain) => explain.mode != ExplainMode::Analyze || explain.statement.is_read_only(),
+            _ => false,
+        }
+    }
+
+    /// Replace every parameter in the statement with its value
+    pub fn bind_parameters(&mut self, values: &[Value]) -> SqlResult<()> {
+        match self {
+            Statement::Select(select) => select.bind_parameters(values),
+            Statement::Insert(insert) => insert
+                .values
+                .iter_mut()
+                .flatten()
+                .try_for_each(|value| value.bind_parameters(values)),
+            Statement::Update(update) => {
+                for assignment in &mut update.assignments {
+                    assignment.value.bind_parameters(values)?;
+                }
+                bind_optional(&mut update.where_clause, values)
+            }
+            Statement::Delete(delete) => bind_optional(&mut delete.where_clause, values),
+            Statement::Explain(explain) => explain.statement.bind_parameters(values),
+            Statement::CreateTable(_)
+            | Statement::CreateVirtualTable(_)
+            | Statement::CreateForeignTable(_)
+            | Statement::DropTable(_)
+            | Statement::CreateIndex(_)
+            | Statement::DropIndex(_)
+            | Statement::Analyze(_)
+            | Statement::Transaction(_)
+            | Statement::Vacuum => Ok(()),
+        }
+    }
+}
+
+impl SelectStatement {
+    /// Every expression of the statement: selected columns, join
+    /// conditions, WHERE, GROUP BY, HAVING and ORDER BY
+    pub fn expressions(&self) -> Vec<&Expression> {
+        let mut expressions: Vec<&Expression> = self
+            .columns
+            .iter()
+            .filter_map(|column| match column {
+                SelectColumn::Expression { expr, .. } => Some(expr),
+                SelectColumn::Wildcard => None,
+            })
+            .collect();
+        for join in self.from.iter().flat_map(|from| &from.joins) {
+            if let JoinConstraint::On(condition) = &join.constraint {
+                expressions.push(condition);
+            }
+        }
+        expressions.extend(self.where_clause.iter());
+        expressions.extend(self.group_by.iter().flatten());
+        expressions.extend(self.having.iter());
+        expressions.extend(self.order_by.iter().flatten().map(|clause| &clause.expression));
+        expressions
+    }
+
+    pub fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        let mut expressions: Vec<&mut Expression> = self
+            .columns
+            .iter_mut()
+            .filter_map(|column| match column {
+                SelectColumn::Expression { expr, .. } => Some(expr),
+                SelectColumn::Wildcard => None,
+            })
+            .collect();
+        for join in self.from.iter_mut().flat_map(|from| &mut from.joins) {
+            if let JoinConstraint::On(condition) = &mut join.constraint {
+                expressions.push(condition);
+            }
+        }
+        expressions.extend(self.where_clause.iter_mut());
+        expressions.extend(self.group_by.iter_mut().flatten());
+        expressions.extend(self.having.iter_mut());
+        expressions.extend(self.order_by.iter_mut().flatten().map(|clause| &mut clause.expression));
+        expressions
+    }
+
+    fn bind_parameters(&mut self, values: &[Value]) -> SqlResult<()> {
+        self.expressions_mut()
+            .into_iter()
+            .try_for_each(|expr| expr.bind_parameters(values))
+    }
+}
+
+fn bind_optional(expr: &mut Option<Expression>, values: &[Value]) -> SqlResult<()> {
+    match expr {
+        Some(expr) => expr.bind_parameters(values),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for Expression {
+    /// Render as SQL text; used to name result columns
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Literal(Value::Text(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            Expression::Literal(value) => write!(f, "{}", value),
+            Expression::Column(name) => write!(f, "{}", name),
+            Expression::QualifiedColumn { table, column } => write!(f, "{}.{}", table, column),
+            Expression::BinaryOp { left, op, right } => write!(f, "{} {} {}", left, op, right),
+            Expression::UnaryOp { op, operand } => match op {
+                UnaryOperator::Plus => write!(f, "+{}", operand),
+                UnaryOperator::Minus => write!(f, "-{}", operand),
+                UnaryOperator::Not => write!(f, "NOT {}", operand),
+            },
+            Expression::Function { name, args } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Expression::Subquery(_) => write!(f, "(subquery)"),
+            Expression::InSubquery { expr, .. } => write!(f, "{} IN (subquery)", expr),
+            Expression::Exists(_) => write!(f, "EXISTS (subquery)"),
+            Expression::In { expr, list } => {
+                let list: Vec<String> = list.iter().map(|item| item.to_string()).collect();
+                write!(f, "{} IN ({})", expr, list.join(", "))
+            }
+            Expression::Between { expr, low, high } => write!(f, "{} BETWEEN {} AND {}", expr, low, high),
+            Expression::Collate { expr, collation } => write!(f, "{} COLLATE {}", expr, collation),
+            Expression::IsNull(expr) => write!(f, "{} IS NULL", expr),
+            Expression::IsNotNull(expr) => write!(f, "{} IS NOT NULL", expr),
+            Expression::Parameter(index) => write!(f, "?{}", index),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Equal => "=",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Or => "OR",
+            BinaryOperator::Like => "LIKE",
+            BinaryOperator::NotLike => "NOT LIKE",
+            BinaryOperator::Concat => "||",
+            BinaryOperator::Match => "MATCH",
+            BinaryOperator::JsonExtract => "->",
+            BinaryOperator::JsonExtractValue => "->>",
+        };
+        write!(f, "{}", symbol)
+    }
+}
@@ -9,8 +9,163 @@ use crate::error::{SqlError, SqlResult};
 
 /// Parse SQL statement into AST
 pub fn parse_sql(input: &str) -> SqlResult<Statement> {
-    let parser = SqlParser::new();
-    parser.parse(input)
+    parse_sql_with_parameters(input).map(|(statement, _)| statement)
+}
+
+/// Parse a statement that may contain placeholders. Returns the name of
+/// every parameter by index (`result[N - 1]` for `?N`), `None` for
+/// `?`/`?NNN` placeholders and e.g. `Some(":name")` for named ones.
+pub fn parse_sql_with_parameters(input: &str) -> SqlResult<(Statement, Vec<Option<String>>)> {
+    let (numbered, parameters) = number_parameters(input)?;
+    let statement = SqlParser::new().parse(&numbered)?;
+    Ok((statement, parameters))
+}
+
+/// Split text holding several statements at the semicolons between them,
+/// leaving those inside quotes alone; blank statements are dropped
+pub fn split_statements(input: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    for (position, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ';') => {
+                statements.push(&input[start..position]);
+                start = position + 1;
+            }
+            (None, _) => {}
+        }
+    }
+    statements.push(&input[start..]);
+    statements.into_iter().map(str::trim).filter(|statement| !statement.is_empty()).collect()
+}
+
+/// Replace each `-- line comment` and `/* block comment */` outside quotes
+/// with a space
+pub fn strip_comments(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut quote: Option<char> = None;
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '-') if chars.peek() == Some(&'-') => {
+                // Keep the line break, so line numbers stay right
+                while chars.next_if(|c| *c != '\n').is_some() {}
+                output.push(' ');
+                continue;
+            }
+            (None, '/') if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                output.push(' ');
+                continue;
+            }
+            (None, _) => {}
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Whether `input` ends with a semicolon outside quotes and comments, so
+/// that a shell reading it line by line can run it
+pub fn is_complete(input: &str) -> bool {
+    let mut quote: Option<char> = None;
+    let mut ends_statement = false;
+    for c in strip_comments(input).chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                ends_statement = false;
+            }
+            (None, ';') => ends_statement = true,
+            (None, c) if c.is_whitespace() => {}
+            (None, _) => ends_statement = false,
+        }
+    }
+    quote.is_none() && ends_statement
+}
+
+/// Largest parameter number, as in SQLite (`SQLITE_MAX_VARIABLE_NUMBER`)
+pub const MAX_PARAMETERS: usize = 32766;
+
+/// Rewrite every placeholder as `?N`, numbered the way SQLite does: a bare
+/// `?` takes the next number after the largest so far, and each distinct
+/// `:name`, `@name` or `$name` takes a number on first use
+fn number_parameters(input: &str) -> SqlResult<(String, Vec<Option<String>>)> {
+    let mut output = String::with_capacity(input.len());
+    let mut parameters: Vec<Option<String>> = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            output.push(c);
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => {
+                quote = Some(c);
+                output.push(c);
+            }
+            '?' => {
+                let mut digits = String::new();
+                while let Some(d) = chars.peek().copied().filter(char::is_ascii_digit) {
+                    digits.push(d);
+                    chars.next();
+                }
+                let index = if digits.is_empty() {
+                    if parameters.len() == MAX_PARAMETERS {
+                        return Err(SqlError::parse_error("Too many parameters"));
+                    }
+                    parameters.len() + 1
+                } else {
+                    match digits.parse::<usize>() {
+                        Ok(index) if (1..=MAX_PARAMETERS).contains(&index) => index,
+                        _ => return Err(SqlError::parse_error(format!("Invalid parameter ?{}", digits))),
+                    }
+                };
+                if parameters.len() < index {
+                    parameters.resize(index, None);
+                }
+                output.push_str(&format!("?{}", index));
+            }
+            ':' | '@' | '$' if chars.peek().is_some_and(|n| n.is_alphabetic() || *n == '_') => {
+                let mut name = c.to_string();
+                while let Some(n) = chars.peek().copied().filter(|n| n.is_alphanumeric() || *n == '_') {
+                    name.push(n);
+                    chars.next();
+                }
+                let index = match parameters.iter().position(|p| p.as_deref() == Some(name.as_str())) {
+                    Some(position) => position + 1,
+                    None => {
+                        parameters.push(Some(name));
+                        parameters.len()
+                    }
+                };
+                output.push_str(&format!("?{}", index));
+            }
+            _ => output.push(c),
+        }
+    }
+    Ok((output, parameters))
 }
 
 #[cfg(test)]
@@ -46,6 +201,33 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_parse_table_constraints() {
+        let sql = "CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users ON DELETE CASCADE, \
+                   total INTEGER CHECK (total > 0), code TEXT, \
+                   CONSTRAINT uq_code UNIQUE (code, user_id), FOREIGN KEY (code) REFERENCES codes (name))";
+        let Ok(Statement::CreateTable(create)) = parse_sql(sql) else {
+            panic!("Expected CREATE TABLE statement");
+        };
+        assert_eq!(create.columns.len(), 4);
+        assert_eq!(
+            create.columns[1].constraints,
+            vec![ColumnConstraint::ForeignKey {
+                table: "users".to_string(),
+                column: None,
+                on_delete: ForeignKeyAction::Cascade,
+            }]
+        );
+        assert!(matches!(create.columns[2].constraints[..], [ColumnConstraint::Check(_)]));
+        assert_eq!(create.constraints.len(), 2);
+        assert!(matches!(&create.constraints[0], TableConstraint::Unique(columns) if columns.len() == 2));
+        assert!(matches!(
+            &create.constraints[1],
+            TableConstraint::ForeignKey { foreign_columns, on_delete: ForeignKeyAction::NoAction, .. }
+                if foreign_columns == &["name".to_string()]
+        ));
+    }
+
     #[test]
     fn test_parse_insert() {
         let sql = "INSERT INTO users (name, age) VALUES ('Alice', 30)";
@@ -59,4 +241,152 @@ mod tests {
             panic!("Expected INSERT statement");
         }
     }
+
+    #[test]
+    fn test_parse_joins() {
+        let sql = "SELECT * FROM users AS u LEFT OUTER JOIN orders AS o ON u.id = o.user_id \
+                   JOIN profiles USING (id) CROSS JOIN regions";
+        let result = parse_sql(sql);
+        assert!(result.is_ok());
+
+        if let Ok(Statement::Select(select)) = result {
+            let from = select.from.unwrap();
+            assert_eq!(from.alias.as_deref(), Some("u"));
+            assert_eq!(from.joins.len(), 3);
+            assert_eq!(from.joins[0].join_type, JoinType::Left);
+            assert_eq!(from.joins[0].alias.as_deref(), Some("o"));
+            assert!(matches!(from.joins[0].constraint, JoinConstraint::On(_)));
+            assert_eq!(from.joins[1].join_type, JoinType::Inner);
+            assert_eq!(from.joins[1].constraint, JoinConstraint::Using(vec!["id".to_string()]));
+            assert_eq!(from.joins[2].join_type, JoinType::Cross);
+            assert_eq!(from.joins[2].constraint, JoinConstraint::None);
+        } else {
+            panic!("Expected SELECT statement");
+        }
+    }
+
+    #[test]
+    fn test_parse_implicit_aliases() {
+        let sql = "SELECT a.n, b.n FROM q a JOIN q b ON a.n = b.n LEFT JOIN r USING (n) WHERE a.n > 1";
+        if let Ok(Statement::Select(select)) = parse_sql(sql) {
+            let from = select.from.unwrap();
+            assert_eq!(from.alias.as_deref(), Some("a"));
+            assert_eq!(from.joins.len(), 2);
+            assert_eq!(from.joins[0].alias.as_deref(), Some("b"));
+            assert!(matches!(from.joins[0].constraint, JoinConstraint::On(_)));
+            assert_eq!(from.joins[1].join_type, JoinType::Left);
+            assert_eq!(from.joins[1].alias, None);
+            assert!(select.where_clause.is_some());
+        } else {
+            panic!("Expected SELECT statement");
+        }
+
+        // Keywords after a table name are not taken as aliases
+        for sql in ["SELECT * FROM q ORDER BY n", "SELECT * FROM q limit 1", "SELECT * FROM q CROSS JOIN r"] {
+            if let Ok(Statement::Select(select)) = parse_sql(sql) {
+                assert_eq!(select.from.unwrap().alias, None, "{}", sql);
+            } else {
+                panic!("Expected SELECT statement: {}", sql);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_parameters() {
+        let sql = "SELECT * FROM users WHERE name = :name AND age > ? AND (id = ?5 OR id = ? OR id = :name) AND memo = ':x ?'";
+        let (statement, parameters) = parse_sql_with_parameters(sql).unwrap();
+        assert_eq!(
+            parameters,
+            vec![Some(":name".to_string()), None, None, None, None, None]
+        );
+
+        let Statement::Select(select) = statement else {
+            panic!("Expected SELECT statement");
+        };
+        let rendered = select.where_clause.unwrap().to_string();
+        assert_eq!(rendered, "name = ?1 AND age > ?2 AND id = ?5 OR id = ?6 OR id = ?1 AND memo = ':x ?'");
+        assert!(parse_sql("SELECT * FROM users WHERE id = ?0").is_err());
+        assert!(parse_sql("SELECT * FROM users WHERE id = ?32766").is_ok());
+        assert!(parse_sql("SELECT * FROM users WHERE id = ?47999999999").is_err());
+        assert!(parse_sql("SELECT * FROM users WHERE id = 99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_parse_predicates_and_subqueries() {
+        let sql = "SELECT * FROM users WHERE note IS NOT NULL AND age NOT BETWEEN 1 AND 5 \
+                   AND id NOT IN (1, 2) AND id IN (SELECT user_id FROM orders) AND NOT EXISTS (SELECT * FROM bans)";
+        let Statement::Select(select) = parse_sql(sql).unwrap() else {
+            panic!("Expected SELECT statement");
+        };
+        let parts = crate::query::join::split_conjunction(select.where_clause.as_ref().unwrap());
+        assert_eq!(parts.len(), 5);
+        assert!(matches!(parts[0], Expression::IsNotNull(_)));
+        assert!(matches!(parts[3], Expression::InSubquery { .. }));
+        assert!(matches!(parts[4], Expression::UnaryOp { operand, .. } if matches!(**operand, Expression::Exists(_))));
+        assert_eq!(parts[1].to_string(), "NOT age BETWEEN 1 AND 5");
+    }
+
+    #[test]
+    fn test_parse_json_operators() {
+        let Statement::Select(select) = parse_sql("SELECT data -> 'tags' ->> 0, data->>'$.n' * 2 FROM docs").unwrap() else {
+            panic!("Expected SELECT statement");
+        };
+        let rendered: Vec<String> = select
+            .columns
+            .iter()
+            .map(|column| match column {
+                SelectColumn::Expression { expr, .. } => expr.to_string(),
+                SelectColumn::Wildcard => "*".to_string(),
+            })
+            .collect();
+        assert_eq!(rendered, vec!["data -> 'tags' ->> 0", "data ->> '$.n' * 2"]);
+        let Some(SelectColumn::Expression { expr: Expression::BinaryOp { op, .. }, .. }) = select.columns.get(1) else {
+            panic!("Expected a binary expression");
+        };
+        assert_eq!(*op, BinaryOperator::Multiply);
+    }
+
+    #[test]
+    fn test_parse_explain_modes() {
+        for (sql, mode) in [
+            ("EXPLAIN SELECT id FROM users", ExplainMode::Plan),
+            ("EXPLAIN QUERY PLAN SELECT id FROM users", ExplainMode::QueryPlan),
+            ("explain analyze SELECT id FROM users", ExplainMode::Analyze),
+        ] {
+            let Statement::Explain(explain) = parse_sql(sql).unwrap() else {
+                panic!("Expected EXPLAIN statement for {}", sql);
+            };
+            assert_eq!(explain.mode, mode);
+            assert!(matches!(*explain.statement, Statement::Select(_)));
+        }
+        assert!(parse_sql("EXPLAIN ANALYZE").is_err());
+    }
+
+    #[test]
+    fn test_parse_transactions_and_split_statements() {
+        for (sql, command) in [
+            ("BEGIN", TransactionStatement::Begin),
+            ("start transaction", TransactionStatement::Begin),
+            ("COMMIT WORK", TransactionStatement::Commit),
+            ("END", TransactionStatement::Commit),
+            ("ROLLBACK TRANSACTION", TransactionStatement::Rollback),
+        ] {
+            assert_eq!(parse_sql(sql).unwrap(), Statement::Transaction(command), "{}", sql);
+        }
+        assert!(parse_sql("START").is_err());
+
+        let sql = "BEGIN; INSERT INTO t (s) VALUES ('a;b');; COMMIT;  ";
+        assert_eq!(split_statements(sql), vec!["BEGIN", "INSERT INTO t (s) VALUES ('a;b')", "COMMIT"]);
+        assert!(split_statements(" ; ").is_empty());
+
+        let script = "-- setup\nINSERT INTO t VALUES ('--x', 1); /* a;\nb */ SELECT 1;";
+        assert_eq!(
+            split_statements(&strip_comments(script)),
+            vec!["INSERT INTO t VALUES ('--x', 1)", "SELECT 1"]
+        );
+        assert!(is_complete("SELECT 1; -- done"));
+        assert!(!is_complete("SELECT ';"));
+        assert!(!is_complete("SELECT 1; SELECT"));
+        assert!(!is_complete("-- only a comment"));
+    }
 }
@@ -4,8 +4,8 @@ use crate::types::{DataType, Value};
 use nom::{
     branch::alt,
     bytes::complete::{tag, tag_no_case, take_while1},
-    character::complete::{alpha1, alphanumeric1, char, digit1, multispace0, multispace1},
-    combinator::{map, opt, recognize},
+    character::complete::{alpha1, alphanumeric1, char, digit1, multispace0, multispace1, satisfy},
+    combinator::{map, map_res, not, opt, peek, recognize, verify},
     multi::{many0, separated_list0, separated_list1},
     sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
@@ -38,20 +38,44 @@ impl SqlParser {
 // Main statement parser
 fn statement(input: &str) -> IResult<&str, Statement> {
     alt((
+        map(explain_statement, Statement::Explain),
         map(select_statement, Statement::Select),
         map(insert_statement, Statement::Insert),
         map(update_statement, Statement::Update),
         map(delete_statement, Statement::Delete),
         map(create_table_statement, Statement::CreateTable),
+        map(create_virtual_table_statement, Statement::CreateVirtualTable),
+        map(create_foreign_table_statement, Statement::CreateForeignTable),
         map(drop_table_statement, Statement::DropTable),
         map(create_index_statement, Statement::CreateIndex),
         map(drop_index_statement, Statement::DropIndex),
+        map(analyze_statement, Statement::Analyze),
+        map(keyword("VACUUM"), |_| Statement::Vacuum),
+        map(transaction_statement, Statement::Transaction),
     ))(input)
 }
 
+// EXPLAIN [QUERY PLAN | ANALYZE] parser
+fn explain_statement(input: &str) -> IResult<&str, ExplainStatement> {
+    let (input, _) = keyword("EXPLAIN")(input)?;
+    let (input, mode) = opt(alt((
+        map(tuple((keyword("QUERY"), keyword("PLAN"))), |_| ExplainMode::QueryPlan),
+        map(keyword("ANALYZE"), |_| ExplainMode::Analyze),
+    )))(input)?;
+    let (input, statement) = statement(input)?;
+    Ok((
+        input,
+        ExplainStatement {
+            mode: mode.unwrap_or(ExplainMode::Plan),
+            statement: Box::new(statement),
+        },
+    ))
+}
+
 // SELECT statement parser
 fn select_statement(input: &str) -> IResult<&str, SelectStatement> {
     let (input, _) = ws(tag_no_case("SELECT"))(input)?;
+    let (input, distinct) = opt(ws(tag_no_case("DISTINCT")))(input)?;
     let (input, columns) = select_columns(input)?;
     let (input, from) = opt(from_clause)(input)?;
     let (input, where_clause) = opt(where_clause)(input)?;
@@ -63,6 +87,7 @@ fn select_statement(input: &str) -> IResult<&str, SelectStatement> {
     Ok((
         input,
         SelectStatement {
+            distinct: distinct.is_some(),
             columns,
             from,
             where_clause,
@@ -92,28 +117,48 @@ fn select_column(input: &str) -> IResult<&str, SelectColumn> {
 // FROM clause parser
 fn from_clause(input: &str) -> IResult<&str, FromClause> {
     let (input, _) = ws(tag_no_case("FROM"))(input)?;
-    let (input, table) = identifier(input)?;
-    let (input, alias) = opt(preceded(ws(tag_no_case("AS")), identifier))(input)?;
+    let (input, table) = table_name(input)?;
+    let (input, alias) = opt(table_alias)(input)?;
     let (input, joins) = many0(join_clause)(input)?;
 
     Ok((
         input,
         FromClause {
-            source: FromSource::Table(table),
+            table,
             alias,
             joins,
         },
     ))
 }
 
+// Words that can follow a table name and so cannot be an alias left
+// without AS, as the `u` in `FROM users u`
+const RESERVED_AFTER_TABLE: &[&str] = &[
+    "WHERE", "GROUP", "HAVING", "ORDER", "LIMIT", "OFFSET", "ON", "USING",
+    "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "CROSS", "NATURAL", "OUTER",
+    "UNION", "INTERSECT", "EXCEPT", "WINDOW",
+];
+
+// Table alias, with or without AS
+fn table_alias(input: &str) -> IResult<&str, String> {
+    alt((
+        preceded(keyword("AS"), identifier),
+        verify(identifier, |name: &str| {
+            !RESERVED_AFTER_TABLE.iter().any(|word| word.eq_ignore_ascii_case(name))
+        }),
+    ))(input)
+}
+
 // JOIN clause parser
 fn join_clause(input: &str) -> IResult<&str, JoinClause> {
     let (input, join_type) = join_type(input)?;
-    let (input, _) = ws(tag_no_case("JOIN"))(input)?;
-    let (input, table) = identifier(input)?;
-    let (input, alias) = opt(preceded(ws(tag_no_case("AS")), identifier))(input)?;
-    let (input, _) = ws(tag_no_case("ON"))(input)?;
-    let (input, condition) = expression(input)?;
+    let (input, table) = table_name(input)?;
+    let (input, alias) = opt(table_alias)(input)?;
+    let (input, constraint) = if join_type == JoinType::Cross {
+        (input, JoinConstraint::None)
+    } else {
+        join_constraint(input)?
+    };
 
     Ok((
         input,
@@ -121,17 +166,40 @@ fn join_clause(input: &str) -> IResult<&str, JoinClause> {
             join_type,
             table,
             alias,
-            condition,
+            constraint,
         },
     ))
 }
 
 fn join_type(input: &str) -> IResult<&str, JoinType> {
+    let outer = || opt(ws(tag_no_case("OUTER")));
+    terminated(
+        alt((
+            map(ws(tag_no_case("INNER")), |_| JoinType::Inner),
+            map(pair(ws(tag_no_case("LEFT")), outer()), |_| JoinType::Left),
+            map(pair(ws(tag_no_case("RIGHT")), outer()), |_| JoinType::Right),
+            map(pair(ws(tag_no_case("FULL")), outer()), |_| JoinType::Full),
+            map(ws(tag_no_case("CROSS")), |_| JoinType::Cross),
+            map(multispace0, |_| JoinType::Inner),
+        )),
+        ws(tag_no_case("JOIN")),
+    )(input)
+}
+
+fn join_constraint(input: &str) -> IResult<&str, JoinConstraint> {
     alt((
-        map(ws(tag_no_case("INNER")), |_| JoinType::Inner),
-        map(ws(tag_no_case("LEFT")), |_| JoinType::Left),
-        map(ws(tag_no_case("RIGHT")), |_| JoinType::Right),
-        map(ws(tag_no_case("FULL")), |_| JoinType::Full),
+        map(preceded(ws(tag_no_case("ON")), expression), JoinConstraint::On),
+        map(
+            preceded(
+                ws(tag_no_case("USING")),
+                delimited(
+                    ws(char('(')),
+                    separated_list1(ws(char(',')), identifier),
+                    ws(char(')')),
+                ),
+            ),
+            JoinConstraint::Using,
+        ),
     ))(input)
 }
 
@@ -180,13 +248,24 @@ fn order_by_item(input: &str) -> IResult<&str, OrderByClause> {
 // LIMIT clause parser
 fn limit_clause(input: &str) -> IResult<&str, LimitClause> {
     let (input, _) = ws(tag_no_case("LIMIT"))(input)?;
-    let (input, count) = number(input)?;
+    let (input, first) = number(input)?;
+
+    // `LIMIT offset, count` is shorthand for `LIMIT count OFFSET offset`
+    if let Ok((input, count)) = preceded(ws(char(',')), number)(input) {
+        return Ok((
+            input,
+            LimitClause {
+                count: count as u64,
+                offset: Some(first as u64),
+            },
+        ));
+    }
     let (input, offset) = opt(preceded(ws(tag_no_case("OFFSET")), number))(input)?;
 
     Ok((
         input,
         LimitClause {
-            count: count as u64,
+            count: first as u64,
             offset: offset.map(|o| o as u64),
         },
     ))
@@ -264,19 +343,156 @@ fn create_table_statement(input: &str) -> IResult<&str, CreateTableStatement> {
     let (input, _) = ws(tag_no_case("TABLE"))(input)?;
     let (input, table_name) = identifier(input)?;
     let (input, _) = ws(char('('))(input)?;
-    let (input, columns) = separated_list1(ws(char(',')), column_definition)(input)?;
+    let (input, items) = separated_list1(ws(char(',')), table_item)(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
+    let mut columns = Vec::new();
+    let mut constraints = Vec::new();
+    for item in items {
+        match item {
+            TableItem::Column(column) => columns.push(column),
+            TableItem::Constraint(constraint) => constraints.push(constraint),
+        }
+    }
+
     Ok((
         input,
         CreateTableStatement {
             table_name,
             columns,
-            constraints: Vec::new(), // TODO: Parse table constraints
+            constraints,
         },
     ))
 }
 
+// CREATE VIRTUAL TABLE statement parser
+fn create_virtual_table_statement(input: &str) -> IResult<&str, CreateVirtualTableStatement> {
+    let (input, _) = keyword("CREATE")(input)?;
+    let (input, _) = keyword("VIRTUAL")(input)?;
+    let (input, _) = keyword("TABLE")(input)?;
+    let (input, table_name) = identifier(input)?;
+    let (input, _) = keyword("USING")(input)?;
+    let (input, module) = identifier(input)?;
+    let (input, arguments) = opt(delimited(
+        ws(char('(')),
+        separated_list0(ws(char(',')), module_argument),
+        ws(char(')')),
+    ))(input)?;
+
+    Ok((
+        input,
+        CreateVirtualTableStatement {
+            table_name,
+            module,
+            arguments: arguments.unwrap_or_default(),
+        },
+    ))
+}
+
+fn module_argument(input: &str) -> IResult<&str, ModuleArgument> {
+    alt((
+        map(
+            tuple((identifier, ws(char('=')), alt((ws(string_literal), identifier)))),
+            |(name, _, value)| ModuleArgument::Option { name, value },
+        ),
+        map(identifier, ModuleArgument::Column),
+    ))(input)
+}
+
+// CREATE FOREIGN TABLE statement parser
+fn create_foreign_table_statement(input: &str) -> IResult<&str, CreateForeignTableStatement> {
+    let (input, _) = keyword("CREATE")(input)?;
+    let (input, _) = keyword("FOREIGN")(input)?;
+    let (input, _) = keyword("TABLE")(input)?;
+    let (input, table_name) = identifier(input)?;
+    let (input, columns) = delimited(
+        ws(char('(')),
+        separated_list1(ws(char(',')), column_definition),
+        ws(char(')')),
+    )(input)?;
+    let (input, _) = keyword("SERVER")(input)?;
+    let (input, server) = identifier(input)?;
+    let (input, options) = opt(preceded(
+        keyword("OPTIONS"),
+        delimited(
+            ws(char('(')),
+            separated_list0(ws(char(',')), pair(identifier, ws(string_literal))),
+            ws(char(')')),
+        ),
+    ))(input)?;
+
+    Ok((
+        input,
+        CreateForeignTableStatement {
+            table_name,
+            columns,
+            server,
+            options: options.unwrap_or_default(),
+        },
+    ))
+}
+
+enum TableItem {
+    Column(ColumnDefinition),
+    Constraint(TableConstraint),
+}
+
+fn table_item(input: &str) -> IResult<&str, TableItem> {
+    alt((
+        map(table_constraint, TableItem::Constraint),
+        map(column_definition, TableItem::Column),
+    ))(input)
+}
+
+// [CONSTRAINT name] PRIMARY KEY (...) | UNIQUE (...) | CHECK (...) |
+// FOREIGN KEY (...) REFERENCES ...
+fn table_constraint(input: &str) -> IResult<&str, TableConstraint> {
+    let (input, _) = opt(preceded(keyword("CONSTRAINT"), identifier))(input)?;
+    alt((
+        map(
+            preceded(pair(keyword("PRIMARY"), keyword("KEY")), column_list),
+            TableConstraint::PrimaryKey,
+        ),
+        map(preceded(keyword("UNIQUE"), column_list), TableConstraint::Unique),
+        map(check_clause, TableConstraint::Check),
+        map(
+            pair(preceded(pair(keyword("FOREIGN"), keyword("KEY")), column_list), references_clause),
+            |(columns, (foreign_table, foreign_columns, on_delete))| TableConstraint::ForeignKey {
+                columns,
+                foreign_table,
+                foreign_columns,
+                on_delete,
+            },
+        ),
+    ))(input)
+}
+
+fn column_list(input: &str) -> IResult<&str, Vec<String>> {
+    delimited(ws(char('(')), separated_list1(ws(char(',')), identifier), ws(char(')')))(input)
+}
+
+fn check_clause(input: &str) -> IResult<&str, Expression> {
+    preceded(keyword("CHECK"), delimited(ws(char('(')), expression, ws(char(')'))))(input)
+}
+
+// REFERENCES table [(columns)] [ON DELETE action]
+fn references_clause(input: &str) -> IResult<&str, (String, Vec<String>, ForeignKeyAction)> {
+    let (input, _) = keyword("REFERENCES")(input)?;
+    let (input, table) = identifier(input)?;
+    let (input, columns) = opt(column_list)(input)?;
+    let (input, on_delete) = opt(preceded(pair(keyword("ON"), keyword("DELETE")), foreign_key_action))(input)?;
+    Ok((input, (table, columns.unwrap_or_default(), on_delete.unwrap_or_default())))
+}
+
+fn foreign_key_action(input: &str) -> IResult<&str, ForeignKeyAction> {
+    alt((
+        map(keyword("CASCADE"), |_| ForeignKeyAction::Cascade),
+        map(keyword("RESTRICT"), |_| ForeignKeyAction::Restrict),
+        map(pair(keyword("SET"), keyword("NULL")), |_| ForeignKeyAction::SetNull),
+        map(pair(keyword("NO"), keyword("ACTION")), |_| ForeignKeyAction::NoAction),
+    ))(input)
+}
+
 fn column_definition(input: &str) -> IResult<&str, ColumnDefinition> {
     let (input, name) = identifier(input)?;
     let (input, data_type) = data_type(input)?;
@@ -308,6 +524,13 @@ fn column_constraint(input: &str) -> IResult<&str, ColumnConstraint> {
         map(ws(tuple((tag_no_case("PRIMARY"), multispace1, tag_no_case("KEY")))), |_| ColumnConstraint::PrimaryKey),
         map(ws(tag_no_case("UNIQUE")), |_| ColumnConstraint::Unique),
         map(preceded(ws(tag_no_case("DEFAULT")), value), |v| ColumnConstraint::Default(v)),
+        map(check_clause, ColumnConstraint::Check),
+        map(preceded(keyword("COLLATE"), identifier), ColumnConstraint::Collate),
+        map(references_clause, |(table, columns, on_delete)| ColumnConstraint::ForeignKey {
+            table,
+            column: columns.into_iter().next(),
+            on_delete,
+        }),
     ))(input)
 }
 
@@ -366,6 +589,25 @@ fn drop_index_statement(input: &str) -> IResult<&str, DropIndexStatement> {
     ))
 }
 
+// ANALYZE [table] parser
+fn analyze_statement(input: &str) -> IResult<&str, AnalyzeStatement> {
+    let (input, _) = keyword("ANALYZE")(input)?;
+    let (input, table_name) = opt(identifier)(input)?;
+    Ok((input, AnalyzeStatement { table_name }))
+}
+
+// BEGIN / START TRANSACTION / COMMIT / END / ROLLBACK parser
+fn transaction_statement(input: &str) -> IResult<&str, TransactionStatement> {
+    let (input, command) = alt((
+        map(keyword("BEGIN"), |_| TransactionStatement::Begin),
+        map(tuple((keyword("START"), peek(keyword("TRANSACTION")))), |_| TransactionStatement::Begin),
+        map(alt((keyword("COMMIT"), keyword("END"))), |_| TransactionStatement::Commit),
+        map(keyword("ROLLBACK"), |_| TransactionStatement::Rollback),
+    ))(input)?;
+    let (input, _) = opt(alt((keyword("TRANSACTION"), keyword("WORK"))))(input)?;
+    Ok((input, command))
+}
+
 // Expression parser
 fn expression(input: &str) -> IResult<&str, Expression> {
     or_expression(input)
@@ -373,7 +615,7 @@ fn expression(input: &str) -> IResult<&str, Expression> {
 
 fn or_expression(input: &str) -> IResult<&str, Expression> {
     let (input, left) = and_expression(input)?;
-    let (input, rights) = many0(pair(ws(tag_no_case("OR")), and_expression))(input)?;
+    let (input, rights) = many0(pair(keyword("OR"), and_expression))(input)?;
 
     Ok((
         input,
@@ -389,7 +631,7 @@ fn or_expression(input: &str) -> IResult<&str, Expression> {
 
 fn and_expression(input: &str) -> IResult<&str, Expression> {
     let (input, left) = equality_expression(input)?;
-    let (input, rights) = many0(pair(ws(tag_no_case("AND")), equality_expression))(input)?;
+    let (input, rights) = many0(pair(keyword("AND"), equality_expression))(input)?;
 
     Ok((
         input,
@@ -428,7 +670,7 @@ fn equality_expression(input: &str) -> IResult<&str, Expression> {
 }
 
 fn comparison_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, left) = additive_expression(input)?;
+    let (input, left) = predicate_expression(input)?;
     let (input, op_right) = opt(pair(
         alt((
             map(ws(tag("<=")), |_| BinaryOperator::LessThanOrEqual),
@@ -437,6 +679,7 @@ fn comparison_expression(input: &str) -> IResult<&str, Expression> {
             map(ws(char('>')), |_| BinaryOperator::GreaterThan),
             map(ws(tag_no_case("LIKE")), |_| BinaryOperator::Like),
             map(ws(tuple((tag_no_case("NOT"), multispace1, tag_no_case("LIKE")))), |_| BinaryOperator::NotLike),
+            map(keyword("MATCH"), |_| BinaryOperator::Match),
         )),
         additive_expression,
     ))(input)?;
@@ -478,13 +721,36 @@ fn additive_expression(input: &str) -> IResult<&str, Expression> {
 }
 
 fn multiplicative_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, left) = unary_expression(input)?;
+    let (input, left) = json_expression(input)?;
     let (input, rights) = many0(pair(
         alt((
             map(ws(char('*')), |_| BinaryOperator::Multiply),
             map(ws(char('/')), |_| BinaryOperator::Divide),
             map(ws(char('%')), |_| BinaryOperator::Modulo),
         )),
+        json_expression,
+    ))(input)?;
+
+    Ok((
+        input,
+        rights.into_iter().fold(left, |acc, (op, right)| {
+            Expression::BinaryOp {
+                left: Box::new(acc),
+                op,
+                right: Box::new(right),
+            }
+        }),
+    ))
+}
+
+// `->` and `->>` bind tighter than the arithmetic operators, as in SQLite
+fn json_expression(input: &str) -> IResult<&str, Expression> {
+    let (input, left) = unary_expression(input)?;
+    let (input, rights) = many0(pair(
+        alt((
+            map(ws(tag("->>")), |_| BinaryOperator::JsonExtractValue),
+            map(ws(tag("->")), |_| BinaryOperator::JsonExtract),
+        )),
         unary_expression,
     ))(input)?;
 
@@ -507,35 +773,134 @@ fn unary_expression(input: &str) -> IResult<&str, Expression> {
                 alt((
                     map(ws(char('+')), |_| UnaryOperator::Plus),
                     map(ws(char('-')), |_| UnaryOperator::Minus),
-                    map(ws(tag_no_case("NOT")), |_| UnaryOperator::Not),
+                    map(keyword("NOT"), |_| UnaryOperator::Not),
                 )),
-                primary_expression,
+                collate_expression,
             ),
             |(op, operand)| Expression::UnaryOp {
                 op,
                 operand: Box::new(operand),
             },
         ),
-        primary_expression,
+        collate_expression,
     ))(input)
 }
 
+// `COLLATE` binds tighter than any operator, as in SQLite
+fn collate_expression(input: &str) -> IResult<&str, Expression> {
+    let (input, expr) = primary_expression(input)?;
+    let (input, collations) = many0(preceded(keyword("COLLATE"), identifier))(input)?;
+    Ok((
+        input,
+        collations.into_iter().fold(expr, |expr, collation| Expression::Collate {
+            expr: Box::new(expr),
+            collation,
+        }),
+    ))
+}
+
 fn primary_expression(input: &str) -> IResult<&str, Expression> {
     alt((
         map(value, Expression::Literal),
+        map(parameter, Expression::Parameter),
+        map(preceded(keyword("EXISTS"), subquery), Expression::Exists),
+        map(subquery, Expression::Subquery),
+        function_call,
         map(qualified_column, |(table, column)| {
             Expression::QualifiedColumn { table, column }
         }),
         map(identifier, Expression::Column),
-        function_call,
         delimited(ws(char('(')), expression, ws(char(')'))),
-        is_null_expression,
-        is_not_null_expression,
-        in_expression,
-        between_expression,
     ))(input)
 }
 
+fn subquery(input: &str) -> IResult<&str, Box<SelectStatement>> {
+    map(delimited(ws(char('(')), select_statement, ws(char(')'))), Box::new)(input)
+}
+
+// `expr [NOT] IN (...)`, `expr [NOT] BETWEEN a AND b` and
+// `expr IS [NOT] NULL`
+fn predicate_expression(input: &str) -> IResult<&str, Expression> {
+    let (input, expr) = additive_expression(input)?;
+    let (input, suffix) = opt(alt((in_suffix, between_suffix, is_null_suffix)))(input)?;
+    Ok((input, match suffix {
+        Some(suffix) => suffix.apply(expr),
+        None => expr,
+    }))
+}
+
+/// The part of a predicate after its operand; `negated` for the `NOT`
+/// forms
+enum PredicateSuffix {
+    In { list: Vec<Expression>, negated: bool },
+    InSubquery { subquery: Box<SelectStatement>, negated: bool },
+    Between { low: Expression, high: Expression, negated: bool },
+    IsNull { negated: bool },
+}
+
+impl PredicateSuffix {
+    fn apply(self, expr: Expression) -> Expression {
+        let expr = Box::new(expr);
+        let (predicate, negated) = match self {
+            PredicateSuffix::In { list, negated } => (Expression::In { expr, list }, negated),
+            PredicateSuffix::InSubquery { subquery, negated } => (Expression::InSubquery { expr, subquery }, negated),
+            PredicateSuffix::Between { low, high, negated } => (
+                Expression::Between {
+                    expr,
+                    low: Box::new(low),
+                    high: Box::new(high),
+                },
+                negated,
+            ),
+            PredicateSuffix::IsNull { negated: true } => return Expression::IsNotNull(expr),
+            PredicateSuffix::IsNull { negated: false } => return Expression::IsNull(expr),
+        };
+        if negated {
+            Expression::UnaryOp {
+                op: UnaryOperator::Not,
+                operand: Box::new(predicate),
+            }
+        } else {
+            predicate
+        }
+    }
+}
+
+fn in_suffix(input: &str) -> IResult<&str, PredicateSuffix> {
+    let (input, not) = opt(keyword("NOT"))(input)?;
+    let (input, _) = keyword("IN")(input)?;
+    let negated = not.is_some();
+    alt((
+        map(subquery, move |subquery| PredicateSuffix::InSubquery { subquery, negated }),
+        map(
+            delimited(ws(char('(')), separated_list1(ws(char(',')), expression), ws(char(')'))),
+            move |list| PredicateSuffix::In { list, negated },
+        ),
+    ))(input)
+}
+
+fn between_suffix(input: &str) -> IResult<&str, PredicateSuffix> {
+    let (input, not) = opt(keyword("NOT"))(input)?;
+    let (input, _) = keyword("BETWEEN")(input)?;
+    let (input, low) = additive_expression(input)?;
+    let (input, _) = keyword("AND")(input)?;
+    let (input, high) = additive_expression(input)?;
+    Ok((input, PredicateSuffix::Between { low, high, negated: not.is_some() }))
+}
+
+fn is_null_suffix(input: &str) -> IResult<&str, PredicateSuffix> {
+    let (input, _) = keyword("IS")(input)?;
+    let (input, not) = opt(keyword("NOT"))(input)?;
+    let (input, _) = keyword("NULL")(input)?;
+    Ok((input, PredicateSuffix::IsNull { negated: not.is_some() }))
+}
+
+// `?N` placeholder; bare `?` and named placeholders are numbered before
+// parsing (see `parser::number_parameters`)
+fn parameter(input: &str) -> IResult<&str, usize> {
+    map_res(ws(preceded(char('?'), digit1)), str::parse)(input)
+}
+
 fn qualified_column(input: &str) -> IResult<&str, (String, String)> {
     let (input, table) = identifier(input)?;
     let (input, _) = ws(char('.'))(input)?;
@@ -546,69 +911,22 @@ fn qualified_column(input: &str) -> IResult<&str, (String, String)> {
 fn function_call(input: &str) -> IResult<&str, Expression> {
     let (input, name) = identifier(input)?;
     let (input, _) = ws(char('('))(input)?;
-    let (input, args) = separated_list0(ws(char(',')), expression)(input)?;
+    // `COUNT(*)` is represented as a single `*` column argument
+    let (input, args) = alt((
+        map(ws(char('*')), |_| vec![Expression::Column("*".to_string())]),
+        separated_list0(ws(char(',')), expression),
+    ))(input)?;
     let (input, _) = ws(char(')'))(input)?;
 
     Ok((input, Expression::Function { name, args }))
 }
 
-fn is_null_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, expr) = primary_expression(input)?;
-    let (input, _) = ws(tuple((tag_no_case("IS"), multispace1, tag_no_case("NULL"))))(input)?;
-    Ok((input, Expression::IsNull(Box::new(expr))))
-}
-
-fn is_not_null_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, expr) = primary_expression(input)?;
-    let (input, _) = ws(tuple((
-        tag_no_case("IS"),
-        multispace1,
-        tag_no_case("NOT"),
-        multispace1,
-        tag_no_case("NULL"),
-    )))(input)?;
-    Ok((input, Expression::IsNotNull(Box::new(expr))))
-}
-
-fn in_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, expr) = primary_expression(input)?;
-    let (input, _) = ws(tag_no_case("IN"))(input)?;
-    let (input, _) = ws(char('('))(input)?;
-    let (input, list) = separated_list1(ws(char(',')), expression)(input)?;
-    let (input, _) = ws(char(')'))(input)?;
-
-    Ok((
-        input,
-        Expression::In {
-            expr: Box::new(expr),
-            list,
-        },
-    ))
-}
-
-fn between_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, expr) = primary_expression(input)?;
-    let (input, _) = ws(tag_no_case("BETWEEN"))(input)?;
-    let (input, low) = expression(input)?;
-    let (input, _) = ws(tag_no_case("AND"))(input)?;
-    let (input, high) = expression(input)?;
-
-    Ok((
-        input,
-        Expression::Between {
-            expr: Box::new(expr),
-            low: Box::new(low),
-            high: Box::new(high),
-        },
-    ))
-}
-
 // Value parser
 fn value(input: &str) -> IResult<&str, Value> {
     alt((
-        map(ws(tag_no_case("NULL")), |_| Value::Null),
-        map(ws(tag_no_case("TRUE")), |_| Value::Boolean(true)),
-        map(ws(tag_no_case("FALSE")), |_| Value::Boolean(false)),
+        map(keyword("NULL"), |_| Value::Null),
+        map(keyword("TRUE"), |_| Value::Boolean(true)),
+        map(keyword("FALSE"), |_| Value::Boolean(false)),
         map(float_number, Value::Real),
         map(integer_number, Value::Integer),
         map(string_literal, Value::Text),
@@ -616,23 +934,23 @@ fn value(input: &str) -> IResult<&str, Value> {
 }
 
 fn integer_number(input: &str) -> IResult<&str, i64> {
-    map(ws(digit1), |s: &str| s.parse().unwrap())(input)
+    map_res(ws(digit1), str::parse)(input)
 }
 
 fn float_number(input: &str) -> IResult<&str, f64> {
-    map(
+    map_res(
         ws(recognize(tuple((
             digit1,
             char('.'),
             digit1,
             opt(tuple((alt((char('e'), char('E'))), opt(alt((char('+'), char('-')))), digit1))),
         )))),
-        |s: &str| s.parse().unwrap(),
+        str::parse,
     )(input)
 }
 
 fn number(input: &str) -> IResult<&str, i64> {
-    map(ws(digit1), |s: &str| s.parse().unwrap())(input)
+    map_res(ws(digit1), str::parse)(input)
 }
 
 fn string_literal(input: &str) -> IResult<&str, String> {
@@ -654,6 +972,26 @@ fn identifier(input: &str) -> IResult<&str, String> {
     )(input)
 }
 
+// Table name in FROM and JOIN, optionally qualified by a schema as in
+// `catalog.tables`
+fn table_name(input: &str) -> IResult<&str, String> {
+    let (input, name) = identifier(input)?;
+    let (input, qualified) = opt(preceded(char('.'), identifier))(input)?;
+    Ok((input, match qualified {
+        Some(table) => format!("{}.{}", name, table),
+        None => name,
+    }))
+}
+
+// Keyword parser; unlike a bare tag it will not match the prefix of a
+// longer word (`OR` in `ORDER`)
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    ws(terminated(
+        tag_no_case(word),
+        not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+    ))
+}
+
 // Whitespace wrapper
 fn ws<'a, F: 'a, O>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O>
 where
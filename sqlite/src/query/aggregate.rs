@@ -0,0 +1,294 @@
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::Expression;
+use crate::query::plan::AggregateFunction;
+use crate::query::relation::{evaluate, resolve_column, ColumnRef, KeyPart, Relation};
+use crate::types::{Row, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Hash aggregation: groups rows by the values of the group expressions and
+/// folds every aggregate over each group
+///
+/// NULL group keys all fall into a single group. Without group expressions
+/// exactly one row is produced, even for empty input.
+#[derive(Debug, Clone)]
+pub struct HashAggregate {
+    group_by: Vec<Expression>,
+    aggregates: Vec<AggregateFunction>,
+}
+
+impl HashAggregate {
+    pub fn new(group_by: Vec<Expression>, aggregates: Vec<AggregateFunction>) -> Self {
+        HashAggregate { group_by, aggregates }
+    }
+
+    pub fn execute(&self, input: Relation) -> SqlResult<Relation> {
+        let columns = self.output_columns(&input.columns)?;
+
+        let mut index: HashMap<Vec<KeyPart>, usize> = HashMap::new();
+        let mut groups: Vec<(Vec<Value>, Vec<Accumulator>)> = Vec::new();
+        for row in &input.rows {
+            let keys = self
+                .group_by
+                .iter()
+                .map(|expr| evaluate(expr, &input.columns, row))
+                .collect::<SqlResult<Vec<_>>>()?;
+            let position = *index.entry(KeyPart::row_key(&keys)).or_insert_with(|| {
+                groups.push((keys, self.accumulators()));
+                groups.len() - 1
+            });
+            for (accumulator, aggregate) in groups[position].1.iter_mut().zip(&self.aggregates) {
+                let value = match aggregate.argument() {
+                    Some(arg) => Some(evaluate(arg, &input.columns, row)?),
+                    None => None,
+                };
+                accumulator.update(value)?;
+            }
+        }
+
+        if groups.is_empty() && self.group_by.is_empty() {
+            groups.push((Vec::new(), self.accumulators()));
+        }
+
+        let rows = groups
+            .into_iter()
+            .map(|(mut values, accumulators)| {
+                values.extend(accumulators.into_iter().map(Accumulator::finish));
+                Row::new(values)
+            })
+            .collect();
+        Ok(Relation::new(columns, rows))
+    }
+
+    fn accumulators(&self) -> Vec<Accumulator> {
+        self.aggregates.iter().map(Accumulator::new).collect()
+    }
+
+    /// Plain column keys keep their qualifier so `t.col` still resolves
+    /// above the aggregate; other keys are named by their SQL text
+    fn output_columns(&self, input: &[ColumnRef]) -> SqlResult<Vec<ColumnRef>> {
+        let mut columns = Vec::with_capacity(self.group_by.len() + self.aggregates.len());
+        for expr in &self.group_by {
+            let column = match expr {
+                Expression::Column(name) => input[resolve_column(input, None, name)?].clone(),
+                Expression::QualifiedColumn { table, column } => {
+                    input[resolve_column(input, Some(table), column)?].clone()
+                }
+                other => ColumnRef::new(None, other.to_string()),
+            };
+            columns.push(column);
+        }
+        columns.extend(
+            self.aggregates
+                .iter()
+                .map(|aggregate| ColumnRef::new(None, aggregate.output_name())),
+        );
+        Ok(columns)
+    }
+}
+
+/// Running state of one aggregate within one group
+#[derive(Debug, Clone)]
+enum Accumulator {
+    CountRows(i64),
+    CountValues(i64),
+    Sum(Option<Value>),
+    Avg { sum: f64, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+impl Accumulator {
+    fn new(aggregate: &AggregateFunction) -> Self {
+        match aggregate {
+            AggregateFunction::Count { column: None } => Accumulator::CountRows(0),
+            AggregateFunction::Count { column: Some(_) } => Accumulator::CountValues(0),
+            AggregateFunction::Sum { .. } => Accumulator::Sum(None),
+            AggregateFunction::Avg { .. } => Accumulator::Avg { sum: 0.0, count: 0 },
+            AggregateFunction::Min { .. } => Accumulator::Min(None),
+            AggregateFunction::Max { .. } => Accumulator::Max(None),
+        }
+    }
+
+    /// Fold one input value; NULLs are ignored by everything but `COUNT(*)`
+    fn update(&mut self, value: Option<Value>) -> SqlResult<()> {
+        if let Accumulator::CountRows(count) = self {
+            *count += 1;
+            return Ok(());
+        }
+        let value = match value {
+            Some(value) if !value.is_null() => value,
+            _ => return Ok(()),
+        };
+        match (self, value) {
+            (Accumulator::CountValues(count), _) => *count += 1,
+            (Accumulator::Sum(total), value) => {
+                let next = match (total.take(), &value) {
+                    (None, Value::Integer(_) | Value::Real(_)) => value,
+                    (Some(Value::Integer(a)), Value::Integer(b)) => Value::Integer(
+                        a.checked_add(*b)
+                            .ok_or_else(|| SqlError::runtime_error("integer overflow in SUM"))?,
+                    ),
+                    (Some(current), _) => match (current.as_real(), value.as_real()) {
+                        (Some(a), Some(b)) => Value::Real(a + b),
+                        _ => return Err(non_numeric("SUM", &value)),
+                    },
+                    (None, _) => return Err(non_numeric("SUM", &value)),
+                };
+                *total = Some(next);
+            }
+            (Accumulator::Avg { sum, count }, value) => {
+                *sum += value.as_real().ok_or_else(|| non_numeric("AVG", &value))?;
+                *count += 1;
+            }
+            (Accumulator::Min(current), value) => keep_if(current, value, Ordering::Less),
+            (Accumulator::Max(current), value) => keep_if(current, value, Ordering::Greater),
+            (Accumulator::CountRows(_), _) => {}
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        match self {
+            Accumulator::CountRows(count) | Accumulator::CountValues(count) => Value::Integer(count),
+            Accumulator::Sum(total) => total.unwrap_or(Value::Null),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => Value::Real(sum / count as f64),
+            Accumulator::Min(value) | Accumulator::Max(value) => value.unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Replace `current` with `value` if it compares as `wanted` (or nothing is
+/// held yet)
+fn keep_if(current: &mut Option<Value>, value: Value, wanted: Ordering) {
+    let replace = match current {
+        Some(held) => value.partial_cmp(held) == Some(wanted),
+        None => true,
+    };
+    if replace {
+        *current = Some(value);
+    }
+}
+
+fn non_numeric(function: &str, value: &Value) -> SqlError {
+    SqlError::type_error(format!("{}() requires numeric values, got {}", function, value.data_type()))
+}
+
+/// Hash-based duplicate elimination, keeping the first occurrence of each
+/// row; NULLs compare equal to each other here, as in `SELECT DISTINCT`
+#[derive(Debug, Clone, Default)]
+pub struct HashDistinct;
+
+impl HashDistinct {
+    pub fn new() -> Self {
+        HashDistinct
+    }
+
+    pub fn execute(&self, input: Relation) -> Relation {
+        let mut seen = HashSet::new();
+        let rows = input
+            .rows
+            .into_iter()
+            .filter(|row| seen.insert(KeyPart::row_key(&row.values)))
+            .collect();
+        Relation::new(input.columns, rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sales() -> Relation {
+        let text = |s: &str| Value::Text(s.to_string());
+        Relation::from_table(
+            "sales",
+            vec!["region".to_string(), "amount".to_string()],
+            vec![
+                Row::new(vec![text("eu"), Value::Integer(10)]),
+                Row::new(vec![text("us"), Value::Integer(5)]),
+                Row::new(vec![text("eu"), Value::Null]),
+                Row::new(vec![Value::Null, Value::Real(2.5)]),
+                Row::new(vec![Value::Null, Value::Integer(1)]),
+            ],
+        )
+    }
+
+    fn amount() -> Expression {
+        Expression::Column("amount".to_string())
+    }
+
+    #[test]
+    fn test_aggregates_ignore_nulls() {
+        let aggregate = HashAggregate::new(
+            vec![],
+            vec![
+                AggregateFunction::Count { column: None },
+                AggregateFunction::Count { column: Some(amount()) },
+                AggregateFunction::Sum { column: amount() },
+                AggregateFunction::Avg { column: amount() },
+                AggregateFunction::Min { column: amount() },
+                AggregateFunction::Max { column: amount() },
+            ],
+        );
+        let result = aggregate.execute(sales()).unwrap();
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["COUNT(*)", "COUNT(amount)", "SUM(amount)", "AVG(amount)", "MIN(amount)", "MAX(amount)"]
+        );
+        assert_eq!(
+            result.rows,
+            vec![Row::new(vec![
+                Value::Integer(5),
+                Value::Integer(4),
+                Value::Real(18.5),
+                Value::Real(18.5 / 4.0),
+                Value::Integer(1),
+                Value::Integer(10),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let empty = Relation::new(sales().columns, vec![]);
+        let aggregates = vec![
+            AggregateFunction::Count { column: None },
+            AggregateFunction::Sum { column: amount() },
+        ];
+
+        let ungrouped = HashAggregate::new(vec![], aggregates.clone()).execute(empty.clone()).unwrap();
+        assert_eq!(ungrouped.rows, vec![Row::new(vec![Value::Integer(0), Value::Null])]);
+
+        let grouped = HashAggregate::new(vec![Expression::Column("region".to_string())], aggregates)
+            .execute(empty)
+            .unwrap();
+        assert!(grouped.rows.is_empty());
+    }
+
+    #[test]
+    fn test_null_keys_form_one_group() {
+        let aggregate = HashAggregate::new(
+            vec![Expression::Column("region".to_string())],
+            vec![AggregateFunction::Sum { column: amount() }],
+        );
+        let result = aggregate.execute(sales()).unwrap();
+        assert_eq!(result.columns[0], ColumnRef::new(Some("sales".to_string()), "region"));
+        assert_eq!(
+            result.rows,
+            vec![
+                Row::new(vec![Value::Text("eu".to_string()), Value::Integer(10)]),
+                Row::new(vec![Value::Text("us".to_string()), Value::Integer(5)]),
+                Row::new(vec![Value::Null, Value::Real(3.5)]),
+            ]
+        );
+
+        let distinct = HashDistinct::new().execute(Relation::new(
+            result.columns.clone(),
+            vec![result.rows[2].clone(), result.rows[2].clone()],
+        ));
+        assert_eq!(distinct.rows.len(), 1);
+    }
+}
@@ -0,0 +1,283 @@
+//! Read-only catalog tables describing the schema
+//!
+//! `catalog.tables`, `catalog.columns`, `catalog.indexes` and
+//! `catalog.stats` can be read with plain SELECT, like `sqlite_master` and
+//! the `pragma_*` table-valued functions of SQLite. Their rows are built
+//! from the executor's catalogs when a query scans them, so they always
+//! describe the schema of that moment. Tables created with SQL belong to
+//! the schema `main`; tables added to the [`SchemaRegistry`] are listed
+//! under the name they were registered with.
+
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::Expression;
+use crate::query::index::IndexInfo;
+use crate::query::plan::TableSchema;
+use crate::query::relation::Relation;
+use crate::query::stats::TableStats;
+use crate::schema::{Schema, SchemaRegistry, Table};
+use crate::types::{Row, Value};
+use std::collections::HashMap;
+
+/// Qualifier of the catalog tables
+pub const CATALOG_SCHEMA: &str = "catalog";
+
+/// Schema name of the tables created with SQL
+pub const MAIN_SCHEMA: &str = "main";
+
+/// Names of the catalog tables, without the qualifier
+pub const CATALOG_TABLES: [&str; 4] = ["tables", "columns", "indexes", "stats"];
+
+/// Whether `table` names a catalog table, e.g. `catalog.tables`
+pub fn is_catalog_table(table: &str) -> bool {
+    catalog_table(table).is_some()
+}
+
+/// The catalog table `table` refers to, matched case-insensitively
+fn catalog_table(table: &str) -> Option<&'static str> {
+    let (schema, name) = table.split_once('.')?;
+    if !schema.eq_ignore_ascii_case(CATALOG_SCHEMA) {
+        return None;
+    }
+    CATALOG_TABLES.into_iter().find(|candidate| candidate.eq_ignore_ascii_case(name))
+}
+
+/// What the catalog tables are built from
+pub struct CatalogSnapshot<'a> {
+    pub schemas: &'a HashMap<String, TableSchema>,
+    pub indexes: &'a [IndexInfo],
+    pub stats: &'a HashMap<String, TableStats>,
+    pub registry: &'a SchemaRegistry,
+}
+
+impl CatalogSnapshot<'_> {
+    /// Rows of the catalog table `table`
+    pub fn relation(&self, table: &str) -> SqlResult<Relation> {
+        let (columns, rows) = match catalog_table(table) {
+            Some("tables") => (
+                vec!["schema", "name", "type", "column_count", "sql"],
+                self.tables(),
+            ),
+            Some("columns") => (
+                vec![
+                    "schema",
+                    "table_name",
+                    "position",
+                    "name",
+                    "type",
+                    "not_null",
+                    "default_value",
+                    "primary_key",
+                    "unique",
+                    "collation",
+                ],
+                self.columns(),
+            ),
+            Some("indexes") => (
+                vec!["schema", "name", "table_name", "columns", "unique", "automatic", "sql"],
+                self.indexes(),
+            ),
+            Some("stats") => (
+                vec!["table_name", "row_count", "column_name", "null_count", "distinct_count", "min", "max"],
+                self.stats(),
+            ),
+            _ => return Err(SqlError::table_not_found(table.to_string())),
+        };
+        let columns = columns.into_iter().map(str::to_string).collect();
+        Ok(Relation::from_table(table, columns, rows))
+    }
+
+    fn tables(&self) -> Vec<Row> {
+        let mut rows: Vec<Row> = sorted(self.schemas)
+            .map(|(name, schema)| {
+                let kind = if schema.fts.is_some() {
+                    "virtual"
+                } else if schema.foreign.is_some() {
+                    "foreign"
+                } else {
+                    "table"
+                };
+                Row::new(vec![
+                    text(MAIN_SCHEMA),
+                    text(name),
+                    text(kind),
+                    Value::Integer(schema.columns.len() as i64),
+                    text(&schema.create_sql(name)),
+                ])
+            })
+            .collect();
+        for (schema_name, table) in self.registry_tables() {
+            rows.push(Row::new(vec![
+                text(&schema_name),
+                text(&table.name),
+                text("table"),
+                Value::Integer(table.columns.len() as i64),
+                Value::Null,
+            ]));
+        }
+        rows
+    }
+
+    fn columns(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (table, schema) in sorted(self.schemas) {
+            let primary_key = schema.primary_key();
+            for (position, column) in schema.columns.iter().enumerate() {
+                rows.push(Row::new(vec![
+                    text(MAIN_SCHEMA),
+                    text(table),
+                    Value::Integer(position as i64),
+                    text(&column.name),
+                    type_name(&column.data_type),
+                    flag(!column.nullable),
+                    default_value(&column.default),
+                    flag(primary_key.contains(&column.name)),
+                    flag(column.unique),
+                    column.collation.as_deref().map_or(Value::Null, text),
+                ]));
+            }
+        }
+        for (schema_name, table) in self.registry_tables() {
+            for (position, column) in table.columns.iter().enumerate() {
+                rows.push(Row::new(vec![
+                    text(&schema_name),
+                    text(&table.name),
+                    Value::Integer(position as i64),
+                    text(&column.name),
+                    type_name(&column.data_type),
+                    flag(!column.nullable),
+                    default_value(&column.default_value),
+                    flag(column.primary_key),
+                    flag(column.unique),
+                    Value::Null,
+                ]));
+            }
+        }
+        rows
+    }
+
+    fn indexes(&self) -> Vec<Row> {
+        let mut rows: Vec<Row> = self
+            .indexes
+            .iter()
+            .map(|index| {
+                let sql = if index.is_automatic() { Value::Null } else { text(&index.create_sql()) };
+                Row::new(vec![
+                    text(MAIN_SCHEMA),
+                    text(&index.name),
+                    text(&index.table),
+                    text(&index.columns.join(", ")),
+                    flag(index.unique),
+                    flag(index.is_automatic()),
+                    sql,
+                ])
+            })
+            .collect();
+        for (schema_name, schema) in self.registry_schemas() {
+            for (_, index) in sorted(&schema.indexes) {
+                rows.push(Row::new(vec![
+                    text(&schema_name),
+                    text(&index.name),
+                    text(&index.table_name),
+                    text(&index.columns.join(", ")),
+                    flag(index.unique),
+                    flag(false),
+                    Value::Null,
+                ]));
+            }
+        }
+        rows
+    }
+
+    fn stats(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (table, stats) in sorted(self.stats) {
+            // Columns in table order; statistics are keyed by lower-case name
+            let mut columns: Vec<&String> = stats.columns.keys().collect();
+            match self.schemas.get(table) {
+                Some(schema) => {
+                    let position = |name: &String| {
+                        schema.columns.iter().position(|column| column.name.eq_ignore_ascii_case(name))
+                    };
+                    columns.sort_by_key(|name| (position(name), *name));
+                }
+                None => columns.sort(),
+            }
+            for column in columns {
+                let column_stats = &stats.columns[column];
+                rows.push(Row::new(vec![
+                    text(table),
+                    Value::Integer(stats.row_count as i64),
+                    text(column),
+                    Value::Integer(column_stats.null_count as i64),
+                    Value::Integer(column_stats.distinct as i64),
+                    column_stats.min.clone().unwrap_or(Value::Null),
+                    column_stats.histogram.last().cloned().unwrap_or(Value::Null),
+                ]));
+            }
+        }
+        rows
+    }
+
+    /// Schemas of the registry, by name
+    fn registry_schemas(&self) -> Vec<(String, &Schema)> {
+        let mut names = self.registry.schema_names();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| self.registry.get_schema(&name).map(|schema| (name, schema)))
+            .collect()
+    }
+
+    /// Tables of the registry, by schema then table name
+    fn registry_tables(&self) -> Vec<(String, &Table)> {
+        self.registry_schemas()
+            .into_iter()
+            .flat_map(|(name, schema)| sorted(&schema.tables).map(move |(_, table)| (name.clone(), table)))
+            .collect()
+    }
+}
+
+fn sorted<V>(map: &HashMap<String, V>) -> impl Iterator<Item = (&String, &V)> {
+    let mut entries: Vec<(&String, &V)> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries.into_iter()
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.to_string())
+}
+
+/// 1 or 0, as the `pragma_*` functions of SQLite report flags
+fn flag(set: bool) -> Value {
+    Value::Integer(set as i64)
+}
+
+/// Declared type; NULL for a column declared without one
+fn type_name(data_type: &crate::types::DataType) -> Value {
+    match data_type {
+        crate::types::DataType::Null => Value::Null,
+        data_type => text(&data_type.to_string()),
+    }
+}
+
+/// Default as SQL source text, e.g. `'none'` for a string
+fn default_value(default: &Option<Value>) -> Value {
+    match default {
+        Some(value) => text(&Expression::Literal(value.clone()).to_string()),
+        None => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_table_names() {
+        assert!(is_catalog_table("catalog.tables"));
+        assert!(is_catalog_table("CATALOG.Columns"));
+        assert!(!is_catalog_table("catalog.views"));
+        assert!(!is_catalog_table("tables"));
+        assert!(!is_catalog_table("main.tables"));
+    }
+}
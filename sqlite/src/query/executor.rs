@@ -1,8 +1,40 @@
+use crate::collation::Collation;
 use crate::error::{SqlError, SqlResult};
+use crate::fdw::{ForeignCatalog, ForeignTable};
+use crate::fts::{extract_match, FtsCatalog, FtsQuery, RANK_COLUMN};
+use crate::parser::ast::{
+    ExplainMode, Expression, ForeignKeyAction, JoinConstraint, JoinType, OrderDirection, SelectStatement, Statement,
+};
+use crate::query::catalog::CatalogSnapshot;
+use crate::query::aggregate::{HashAggregate, HashDistinct};
+use crate::query::index::{IndexCatalog, IndexInfo, IndexLookup};
+use crate::query::join::{self, SemiJoin};
+use crate::query::planner::QueryPlanner;
+use crate::query::subquery::{outer_references, substitute};
+use crate::query::sort::{ExternalSort, SortConfig};
 use crate::query::plan::*;
+use crate::query::profile;
+use crate::query::stats::{CostModel, StatsCatalog, TableStats};
+use crate::query::relation::{evaluate, expression_collation, is_truthy, ColumnRef, Relation};
 use crate::query::result::QueryResult;
-use crate::types::{Row, Value};
-use std::collections::HashMap;
+use crate::schema::SchemaRegistry;
+use crate::types::{Row, RowChange, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+/// How a statement changed the rows of one table, by their positions in
+/// it, for storage that keeps the rows in the same order
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableEdit {
+    /// Rows added after the last one
+    Append(Vec<Row>),
+    /// Rows replaced in place
+    Update(Vec<(usize, Row)>),
+    /// Rows removed, by their positions before the removal, ascending; the
+    /// rows after them move up
+    Delete(Vec<usize>),
+}
 
 /// Query executor that executes query plans
 #[derive(Debug)]
@@ -12,40 +44,436 @@ pub struct QueryExecutor {
     // - Transaction manager
     // - Index manager
     // - Statistics collector
+    /// In-memory tables registered for scans (table name -> rows); writes
+    /// change a relation in place unless a snapshot shares it, in which
+    /// case they change a copy
+    tables: Arc<RwLock<HashMap<String, Arc<Relation>>>>,
+    /// Declared schema of every table created or registered
+    schemas: SchemaCatalog,
+    /// Secondary indexes over the registered tables
+    indexes: IndexCatalog,
+    /// Inverted indexes of the full-text tables
+    fts: FtsCatalog,
+    /// Servers the foreign tables are read from
+    foreign: ForeignCatalog,
+    /// Statistics gathered by ANALYZE
+    stats: StatsCatalog,
+    /// Schemas registered outside SQL, listed by the catalog tables
+    registry: Arc<RwLock<SchemaRegistry>>,
+    /// Held by INSERT, UPDATE and DELETE, which evaluate their conditions
+    /// (and any subqueries in them) before taking the tables write lock
+    writer: Mutex<()>,
+    /// Rows changed by INSERT, UPDATE and DELETE since they were last
+    /// taken; `None` while changes are not captured
+    changes: std::sync::Mutex<Option<Vec<RowChange>>>,
+    /// How INSERT, UPDATE and DELETE changed each table since the edits
+    /// were last taken; `None` while edits are not captured
+    edits: std::sync::Mutex<Option<Vec<(String, TableEdit)>>>,
+    /// One past the largest INTEGER PRIMARY KEY of each table, kept up to
+    /// date by INSERT; a table missing here gets it from its rows
+    next_rowids: std::sync::Mutex<HashMap<String, i64>>,
+    sort_config: SortConfig,
 }
 
 impl QueryExecutor {
     pub fn new() -> Self {
-        QueryExecutor {}
+        Self::with_sort_config(SortConfig::default())
     }
 
-    /// Execute a query plan and return results
-    pub async fn execute_plan(&self, plan: QueryPlan) -> SqlResult<QueryResult> {
-        match plan {
-            QueryPlan::Scan { table, filter, projection } => {
-                self.execute_scan(table, filter, projection).await
-            }
-            QueryPlan::IndexScan { table, index, key, filter } => {
-                self.execute_index_scan(table, index, key, filter).await
+    /// Create an executor whose ORDER BY spills to disk past the given
+    /// memory budget
+    pub fn with_sort_config(sort_config: SortConfig) -> Self {
+        QueryExecutor {
+            tables: Arc::new(RwLock::new(HashMap::new())),
+            schemas: SchemaCatalog::default(),
+            indexes: IndexCatalog::new(),
+            fts: FtsCatalog::new(),
+            foreign: ForeignCatalog::new(),
+            stats: StatsCatalog::default(),
+            registry: Arc::new(RwLock::new(SchemaRegistry::new())),
+            writer: Mutex::new(()),
+            changes: std::sync::Mutex::new(None),
+            edits: std::sync::Mutex::new(None),
+            next_rowids: std::sync::Mutex::new(HashMap::new()),
+            sort_config,
+        }
+    }
+
+    /// Create an executor whose catalog tables list the schemas of
+    /// `registry`
+    pub fn with_registry(registry: Arc<RwLock<SchemaRegistry>>) -> Self {
+        QueryExecutor {
+            registry,
+            ..Self::new()
+        }
+    }
+
+    /// A copy of the tables and catalogs as of the last completed write,
+    /// for readers to query while writers go on changing this executor.
+    /// Rows and indexes are shared with it until a write replaces them;
+    /// the schema registry stays shared.
+    pub async fn snapshot(&self) -> Self {
+        let _writer = self.writer.lock().await;
+        QueryExecutor {
+            tables: Arc::new(RwLock::new(self.tables.read().await.clone())),
+            schemas: Arc::new(RwLock::new(self.schemas.read().await.clone())),
+            indexes: self.indexes.fork().await,
+            fts: self.fts.fork().await,
+            foreign: self.foreign.clone(),
+            stats: Arc::new(RwLock::new(self.stats.read().await.clone())),
+            registry: self.registry.clone(),
+            writer: Mutex::new(()),
+            changes: std::sync::Mutex::new(None),
+            edits: std::sync::Mutex::new(None),
+            next_rowids: std::sync::Mutex::new(self.lock_next_rowids().clone()),
+            sort_config: self.sort_config.clone(),
+        }
+    }
+
+    /// Start or stop recording the rows that INSERT, UPDATE and DELETE
+    /// change; stopping drops what was recorded
+    pub fn capture_changes(&self, capture: bool) {
+        let mut changes = self.changes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match (capture, changes.is_some()) {
+            (true, false) => *changes = Some(Vec::new()),
+            (false, true) => *changes = None,
+            _ => {}
+        }
+    }
+
+    /// The changes recorded since they were last taken, in the order they
+    /// were made
+    pub fn take_changes(&self) -> Vec<RowChange> {
+        self.changes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn capturing_changes(&self) -> bool {
+        self.changes.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).is_some()
+    }
+
+    /// Record the changes of a statement that succeeded; they are only
+    /// built when changes are captured
+    fn record_changes(&self, changes: impl FnOnce() -> Vec<RowChange>) {
+        if let Some(recorded) = self.changes.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).as_mut() {
+            recorded.extend(changes());
+        }
+    }
+
+    /// Start or stop recording how INSERT, UPDATE and DELETE change each
+    /// table; stopping drops what was recorded
+    pub fn capture_edits(&self, capture: bool) {
+        let mut edits = self.edits.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match (capture, edits.is_some()) {
+            (true, false) => *edits = Some(Vec::new()),
+            (false, true) => *edits = None,
+            _ => {}
+        }
+    }
+
+    /// The edits recorded since they were last taken, in the order they
+    /// were made
+    pub fn take_edits(&self) -> Vec<(String, TableEdit)> {
+        self.edits
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Record an edit of a statement that succeeded; it is only built when
+    /// edits are captured
+    fn record_edit(&self, table: &str, edit: impl FnOnce() -> TableEdit) {
+        if let Some(recorded) = self.edits.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).as_mut() {
+            recorded.push((table.to_string(), edit()));
+        }
+    }
+
+    /// Fail, changing nothing, if the unique indexes would not take the
+    /// edits. Their positions must all count the rows of each table as
+    /// they were before any of them, as those of a transaction's commit do.
+    pub async fn check_edits(&self, edits: &[(String, TableEdit)]) -> SqlResult<()> {
+        let mut changed: BTreeMap<&str, (BTreeSet<usize>, Vec<Row>)> = BTreeMap::new();
+        let tables = self.tables.read().await;
+        for (table, edit) in edits {
+            if !tables.contains_key(table) {
+                return Err(SqlError::table_not_found(table.clone()));
             }
-            QueryPlan::Join { left, right, join_type, condition } => {
-                self.execute_join(*left, *right, join_type, condition).await
+            let (replaced, rows) = changed.entry(table).or_default();
+            match edit {
+                TableEdit::Append(new) => rows.extend(new.iter().cloned()),
+                TableEdit::Update(new) => {
+                    for (position, row) in new {
+                        replaced.insert(*position);
+                        rows.push(row.clone());
+                    }
+                }
+                TableEdit::Delete(positions) => replaced.extend(positions),
             }
-            QueryPlan::Projection { input, columns, expressions } => {
-                self.execute_projection(*input, columns, expressions).await
+        }
+        drop(tables);
+        for (table, (replaced, rows)) in changed {
+            let replaced: Vec<usize> = replaced.into_iter().collect();
+            self.indexes.check(table, &replaced, &rows).await?;
+        }
+        Ok(())
+    }
+
+    /// Make row changes, without checking them: those another executor's
+    /// writes made, to keep a copy of its tables in step with it, or those
+    /// of a commit that [`check_edits`](Self::check_edits) accepted
+    pub async fn apply_edits(&self, edits: &[(String, TableEdit)]) {
+        let rowid_columns: HashMap<&str, usize> = {
+            let schemas = self.schemas.read().await;
+            edits
+                .iter()
+                .filter_map(|(table, _)| Some((table.as_str(), schemas.get(table)?.rowid_column()?)))
+                .collect()
+        };
+        let mut tables = self.tables.write().await;
+        for (table, edit) in edits {
+            let Some(relation) = tables.get_mut(table) else {
+                continue;
+            };
+            let stored = Arc::make_mut(relation);
+            // The indexes took the same changes in the other executor, so
+            // they take them here
+            match edit {
+                TableEdit::Append(rows) => {
+                    let first = stored.rows.len();
+                    stored.rows.extend(rows.iter().cloned());
+                    let _ = self.indexes.append(table, first, rows).await;
+                    let _ = self.fts.insert(table, rows).await;
+                    if let (Some(&column), Some(next)) =
+                        (rowid_columns.get(table.as_str()), self.lock_next_rowids().get_mut(table))
+                    {
+                        for id in rows.iter().filter_map(|row| row.values[column].as_integer()) {
+                            *next = (*next).max(id.saturating_add(1));
+                        }
+                    }
+                }
+                TableEdit::Update(rows) => {
+                    let (positions, new): (Vec<usize>, Vec<Row>) = rows.iter().cloned().unzip();
+                    let old: Vec<Row> = rows
+                        .iter()
+                        .map(|(position, row)| std::mem::replace(&mut stored.rows[*position], row.clone()))
+                        .collect();
+                    let _ = self.indexes.update(table, &positions, &old, &new).await;
+                    let _ = self.fts.update(table, &positions, &old, &new).await;
+                    self.forget_next_rowid(table);
+                }
+                TableEdit::Delete(positions) => {
+                    let removed: Vec<Row> = positions.iter().map(|&position| stored.rows[position].clone()).collect();
+                    self.indexes.delete(table, positions, &removed).await;
+                    let _ = self.fts.delete(table, positions, &removed).await;
+                    let mut position = 0;
+                    stored.rows.retain(|_| {
+                        let kept = positions.binary_search(&position).is_err();
+                        position += 1;
+                        kept
+                    });
+                    self.forget_next_rowid(table);
+                }
             }
-            QueryPlan::Selection { input, condition } => {
-                self.execute_selection(*input, condition).await
+        }
+    }
+
+    fn lock_next_rowids(&self) -> std::sync::MutexGuard<'_, HashMap<String, i64>> {
+        self.next_rowids.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Rowid the next row inserted into `table` gets if its INTEGER
+    /// PRIMARY KEY, at `column`, is left NULL
+    fn next_rowid(&self, table: &str, column: usize, relation: &Relation) -> i64 {
+        *self.lock_next_rowids().entry(table.to_string()).or_insert_with(|| {
+            relation
+                .rows
+                .iter()
+                .filter_map(|row| row.values[column].as_integer())
+                .max()
+                .map_or(1, |max| max.saturating_add(1))
+        })
+    }
+
+    /// Work the next rowid of `table` out from its rows again when needed,
+    /// after a write that may have lowered its largest key
+    fn forget_next_rowid(&self, table: &str) {
+        self.lock_next_rowids().remove(table);
+    }
+
+    /// Index catalog, shared with the planner so it can choose index scans
+    pub fn indexes(&self) -> IndexCatalog {
+        self.indexes.clone()
+    }
+
+    /// Table schemas, shared with the planner so it can decorrelate
+    /// subqueries
+    pub fn schemas(&self) -> SchemaCatalog {
+        self.schemas.clone()
+    }
+
+    /// Servers of the foreign tables, shared with the planner so it can
+    /// push conditions down to them
+    pub fn foreign_servers(&self) -> ForeignCatalog {
+        self.foreign.clone()
+    }
+
+    /// Table statistics, shared with the planner for its cost model
+    pub fn statistics(&self) -> StatsCatalog {
+        self.stats.clone()
+    }
+
+    /// Registry of schemas defined through the Rust API, shown in the
+    /// catalog tables next to the tables created with SQL
+    pub fn schema_registry(&self) -> Arc<RwLock<SchemaRegistry>> {
+        self.registry.clone()
+    }
+
+    /// Register an in-memory table that scans of `name` will read
+    pub async fn register_table(&self, name: impl Into<String>, columns: Vec<String>, rows: Vec<Row>) {
+        let name = name.into();
+        let schema = TableSchema::untyped(&columns);
+        self.restore_table(name, schema, rows).await;
+    }
+
+    /// Install a table with a known schema, e.g. one loaded from disk
+    pub async fn restore_table(&self, name: impl Into<String>, schema: TableSchema, rows: Vec<Row>) {
+        let name = name.into();
+        let relation = stored_relation(&name, &schema, rows);
+        let autoindexes = autoindexes(&name, &schema);
+        match &schema.fts {
+            // The options were checked when the table was created
+            Some(options) => {
+                let _ = self.fts.create(&name, options, schema.column_names(), &relation.rows).await;
             }
-            QueryPlan::Sort { input, order_by } => {
-                self.execute_sort(*input, order_by).await
+            None => self.fts.drop_index(&name).await,
+        }
+        self.schemas.write().await.insert(name.clone(), schema);
+        self.forget_next_rowid(&name);
+        let mut tables = self.tables.write().await;
+        // Row positions changed, so indexes on the table are rebuilt; an
+        // index that no longer applies (e.g. duplicate keys) is dropped
+        if self.indexes.rebuild(&name, &relation).await.is_err() {
+            for index in self.indexes.indexes_on(&name).await {
+                self.indexes.drop_index(&index.name).await;
             }
-            QueryPlan::Limit { input, count, offset } => {
-                self.execute_limit(*input, count, offset).await
+        }
+        let existing = self.indexes.indexes_on(&name).await;
+        for index in autoindexes {
+            if !existing.iter().any(|info| info.name == index.name) {
+                // Rows that break a UNIQUE key leave it unenforced, as an
+                // index that does not apply is dropped above
+                let _ = self.indexes.create(index, &relation).await;
             }
-            QueryPlan::GroupBy { input, group_columns, aggregates } => {
-                self.execute_group_by(*input, group_columns, aggregates).await
+        }
+        tables.insert(name, Arc::new(relation));
+    }
+
+    /// Replace the rows of a table, e.g. with those a session transaction
+    /// committed; fails, leaving the table as it was, if they break a
+    /// unique index
+    pub async fn replace_table(&self, name: &str, rows: Vec<Row>) -> SqlResult<()> {
+        let _writer = self.writer.lock().await;
+        let schema = self.table_schema(name).await?;
+        let relation = stored_relation(name, &schema, rows);
+        let mut tables = self.tables.write().await;
+        self.replace_rows(&mut tables, name, relation).await?;
+        self.forget_next_rowid(name);
+        if let Some(options) = &schema.fts {
+            self.fts.create(name, options, schema.column_names(), &tables[name].rows).await?;
+        }
+        Ok(())
+    }
+
+    /// Schema and rows of a table, if it exists
+    pub async fn table_snapshot(&self, name: &str) -> Option<(TableSchema, Vec<Row>)> {
+        let schema = self.schemas.read().await.get(name).cloned()?;
+        let rows = self.tables.read().await.get(name)?.rows.clone();
+        Some((schema, rows))
+    }
+
+    /// Names of every table, sorted
+    pub async fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.schemas.read().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Every table with its schema and rows, and the indexes made by
+    /// CREATE INDEX, as of one point between writes
+    pub async fn database_snapshot(&self) -> (Vec<(String, TableSchema, Vec<Row>)>, Vec<IndexInfo>) {
+        let _writer = self.writer.lock().await;
+        let schemas = self.schemas.read().await.clone();
+        let mut tables = Vec::with_capacity(schemas.len());
+        let mut indexes = Vec::new();
+        for name in self.table_names().await {
+            let Some(rows) = self.tables.read().await.get(&name).map(|relation| relation.rows.clone()) else {
+                continue;
+            };
+            indexes.extend(
+                self.indexes
+                    .indexes_on(&name)
+                    .await
+                    .into_iter()
+                    .filter(|index| !index.name.starts_with(AUTOINDEX_PREFIX)),
+            );
+            tables.push((name.clone(), schemas[&name].clone(), rows));
+        }
+        (tables, indexes)
+    }
+
+    /// Tables that an ON DELETE action on rows of `table` can change,
+    /// directly or through other tables
+    pub async fn dependent_tables(&self, table: &str) -> Vec<String> {
+        let schemas = self.schemas.read().await;
+        let mut dependents: Vec<String> = Vec::new();
+        let mut pending = vec![table.to_string()];
+        while let Some(parent) = pending.pop() {
+            for (child, _) in referencing_keys(&parent, &schemas) {
+                if child != table && !dependents.contains(&child) {
+                    dependents.push(child.clone());
+                    pending.push(child);
+                }
             }
+        }
+        dependents
+    }
+
+    /// Forget a table and its indexes without going through DROP TABLE
+    pub async fn remove_table(&self, name: &str) {
+        self.schemas.write().await.remove(name);
+        self.tables.write().await.remove(name);
+        self.forget_next_rowid(name);
+        for index in self.indexes.indexes_on(name).await {
+            self.indexes.drop_index(&index.name).await;
+        }
+        self.fts.drop_index(name).await;
+        self.stats.write().await.remove(name);
+    }
+
+    /// Execute a query plan and return results
+    pub async fn execute_plan(&self, plan: QueryPlan) -> SqlResult<QueryResult> {
+        if plan.is_relational() {
+            return Ok(self.execute_relation(plan).await?.into_result());
+        }
+        let span = profile::enter(&plan);
+        let result = self.execute_statement(plan).await;
+        let rows = result.as_ref().map_or(0, |result| match result.rows_affected() {
+            Some(affected) => affected as usize,
+            None => result.row_count().unwrap_or(0),
+        });
+        profile::exit(span, rows);
+        result
+    }
+
+    /// Execute a plan that is not a relational operator
+    async fn execute_statement(&self, plan: QueryPlan) -> SqlResult<QueryResult> {
+        match plan {
             QueryPlan::Insert { table, columns, values } => {
                 self.execute_insert(table, columns, values).await
             }
@@ -58,6 +486,23 @@ impl QueryExecutor {
             QueryPlan::CreateTable { table, schema } => {
                 self.execute_create_table(table, schema).await
             }
+            QueryPlan::DropTable { table, if_exists } => self.execute_drop_table(table, if_exists).await,
+            QueryPlan::CreateIndex { index } => self.execute_create_index(index).await,
+            QueryPlan::DropIndex { index, if_exists } => {
+                if index.starts_with(AUTOINDEX_PREFIX) {
+                    return Err(SqlError::schema_error(format!(
+                        "Index {} belongs to a UNIQUE or PRIMARY KEY constraint and cannot be dropped",
+                        index
+                    )));
+                }
+                if self.indexes.drop_index(&index).await || if_exists {
+                    Ok(QueryResult::drop_index())
+                } else {
+                    Err(SqlError::index_not_found(index))
+                }
+            }
+            QueryPlan::Analyze { table } => self.execute_analyze(table).await,
+            QueryPlan::Explain { plan, mode } => self.execute_explain(*plan, mode).await,
             QueryPlan::Union { left, right, all } => {
                 self.execute_union(*left, *right, all).await
             }
@@ -67,6 +512,144 @@ impl QueryExecutor {
             QueryPlan::Except { left, right } => {
                 self.execute_except(*left, *right).await
             }
+            relational => Ok(self.execute_relation(relational).await?.into_result()),
+        }
+    }
+
+    /// Describe `plan`; `EXPLAIN ANALYZE` also runs it and reports what
+    /// each operator did
+    async fn execute_explain(&self, plan: QueryPlan, mode: ExplainMode) -> SqlResult<QueryResult> {
+        let columns = ExplainNode::columns(mode).into_iter().map(str::to_string).collect();
+        let nodes = {
+            let stats = self.stats.read().await;
+            let model = CostModel::new(&stats);
+            plan.explain_tree_with(&|plan| {
+                let estimate = model.estimate(plan);
+                (estimate.cost, estimate.rows.round() as u64)
+            })
+        };
+        let stats = match mode {
+            ExplainMode::Analyze => {
+                let (result, stats) = profile::profile(&plan, Box::pin(self.execute_plan(plan.clone()))).await;
+                result?;
+                stats
+            }
+            _ => Vec::new(),
+        };
+        let rows = nodes
+            .into_iter()
+            .map(|node| {
+                let mut values = vec![Value::Integer(node.id), Value::Integer(node.parent)];
+                if mode == ExplainMode::QueryPlan {
+                    values.push(Value::Text(node.detail));
+                    return Row::new(values);
+                }
+                values.extend([
+                    Value::Text(node.operator),
+                    Value::Text(node.detail),
+                    Value::Real(node.cost),
+                    Value::Integer(node.rows as i64),
+                ]);
+                if mode == ExplainMode::Analyze {
+                    match stats.get(node.id as usize - 1).copied().flatten() {
+                        Some(stats) => values.extend([
+                            Value::Integer(stats.rows as i64),
+                            Value::Real(stats.elapsed.as_secs_f64() * 1000.0),
+                        ]),
+                        // The operator never ran
+                        None => values.extend([Value::Null, Value::Null]),
+                    }
+                }
+                Row::new(values)
+            })
+            .collect();
+        Ok(QueryResult::select(columns, rows))
+    }
+
+    /// Gather statistics of one table, or every table, for the planner;
+    /// returns the row count of each table analyzed
+    async fn execute_analyze(&self, table: Option<String>) -> SqlResult<QueryResult> {
+        let collected: Vec<(String, TableStats)> = {
+            let tables = self.tables.read().await;
+            // The rows of foreign tables are not kept here to be counted
+            let schemas = self.schemas.read().await;
+            let foreign = |name: &String| schemas.get(name).is_some_and(|schema| schema.foreign.is_some());
+            let mut names: Vec<&String> = match &table {
+                Some(name) if foreign(name) => {
+                    return Err(SqlError::schema_error(format!("cannot analyze foreign table {}", name)))
+                }
+                Some(name) => vec![tables
+                    .get_key_value(name)
+                    .map(|(name, _)| name)
+                    .ok_or_else(|| SqlError::table_not_found(name.clone()))?],
+                None => tables.keys().filter(|name| !foreign(name)).collect(),
+            };
+            names.sort();
+            names.into_iter().map(|name| (name.clone(), TableStats::collect(&tables[name]))).collect()
+        };
+        let rows = collected
+            .iter()
+            .map(|(name, stats)| Row::new(vec![Value::Text(name.clone()), Value::Integer(stats.row_count as i64)]))
+            .collect();
+        self.stats.write().await.extend(collected);
+        Ok(QueryResult::select(vec!["table".to_string(), "rows".to_string()], rows))
+    }
+
+    /// Execute a plan keeping table qualifiers on its columns, so joins and
+    /// filters can resolve `table.column` references
+    async fn execute_relation(&self, plan: QueryPlan) -> SqlResult<Relation> {
+        if !plan.is_relational() {
+            return Relation::from_result(Box::pin(self.execute_plan(plan)).await?);
+        }
+        let span = profile::enter(&plan);
+        let result = self.execute_operator(plan).await;
+        profile::exit(span, result.as_ref().map_or(0, |relation| relation.rows.len()));
+        result
+    }
+
+    async fn execute_operator(&self, plan: QueryPlan) -> SqlResult<Relation> {
+        match plan {
+            QueryPlan::Scan { table, filter, projection } => {
+                self.execute_scan(table, filter, projection).await
+            }
+            QueryPlan::IndexScan { table, index, lookup, filter, covering, .. } => {
+                self.execute_index_scan(table, index, lookup, filter, covering).await
+            }
+            QueryPlan::FtsScan { table, query, filter } => self.execute_fts_scan(table, query, filter).await,
+            QueryPlan::ForeignScan { table, pushed, filter, .. } => {
+                self.execute_foreign_scan(table, pushed, filter).await
+            }
+            QueryPlan::Join { left, right, join_type, condition } => {
+                self.execute_join(*left, *right, join_type, condition).await
+            }
+            QueryPlan::SemiJoin { left, right, keys, anti } => {
+                let left = Box::pin(self.execute_relation(*left)).await?;
+                let right = Box::pin(self.execute_relation(*right)).await?;
+                SemiJoin::new(keys, anti).execute(left, right)
+            }
+            QueryPlan::Alias { input, alias } => {
+                Ok(Box::pin(self.execute_relation(*input)).await?.with_alias(&alias))
+            }
+            QueryPlan::Projection { input, columns, expressions } => {
+                self.execute_projection(*input, columns, expressions).await
+            }
+            QueryPlan::Selection { input, condition } => {
+                self.execute_selection(*input, condition).await
+            }
+            QueryPlan::Sort { input, order_by } => {
+                self.execute_sort(*input, order_by, None).await
+            }
+            QueryPlan::Limit { input, count, offset } => {
+                self.execute_limit(*input, count, offset).await
+            }
+            QueryPlan::GroupBy { input, group_columns, aggregates } => {
+                self.execute_group_by(*input, group_columns, aggregates).await
+            }
+            QueryPlan::Distinct { input } => {
+                let input = Box::pin(self.execute_relation(*input)).await?;
+                Ok(HashDistinct::new().execute(input))
+            }
+            other => Relation::from_result(Box::pin(self.execute_plan(other)).await?),
         }
     }
 
@@ -74,9 +657,25 @@ impl QueryExecutor {
     async fn execute_scan(
         &self,
         table: String,
-        _filter: Option<crate::parser::ast::Expression>,
+        filter: Option<Expression>,
         _projection: Option<Vec<String>>,
-    ) -> SqlResult<QueryResult> {
+    ) -> SqlResult<Relation> {
+        if self.schemas.read().await.get(&table).is_some_and(|schema| schema.foreign.is_some()) {
+            return self.execute_foreign_scan(table, Vec::new(), filter).await;
+        }
+        let relation = match self.tables.read().await.get(&table) {
+            Some(relation) => Some(Relation::clone(relation)),
+            // Only the catalog tables have qualified names
+            None if table.contains('.') => Some(self.catalog_relation(&table).await?),
+            None => None,
+        };
+        if let Some(relation) = relation {
+            return match filter {
+                Some(condition) => self.filter_rows(relation, &condition).await,
+                None => Ok(relation),
+            };
+        }
+
         // Simulate table scan
         // In a real implementation, this would:
         // 1. Open the table
@@ -98,114 +697,245 @@ impl QueryExecutor {
             ]),
         ];
 
-        Ok(QueryResult::select(columns, rows))
+        Ok(Relation::from_table(&table, columns, rows))
     }
 
     async fn execute_index_scan(
         &self,
         table: String,
-        _index: String,
-        _key: Value,
-        _filter: Option<crate::parser::ast::Expression>,
-    ) -> SqlResult<QueryResult> {
-        // Simulate index scan (more efficient than table scan)
-        let columns = vec!["id".to_string(), "name".to_string()];
-        let rows = vec![Row::new(vec![
-            Value::Integer(1),
-            Value::Text("Alice".to_string()),
-        ])];
+        index: String,
+        lookup: IndexLookup,
+        filter: Option<Expression>,
+        covering: bool,
+    ) -> SqlResult<Relation> {
+        let candidates = {
+            let tables = self.tables.read().await;
+            let relation = tables
+                .get(&table)
+                .ok_or_else(|| SqlError::table_not_found(table.clone()))?;
+            self.indexes.scan(&index, relation, &lookup, covering).await?
+        };
+        match filter {
+            Some(condition) => self.filter_rows(candidates, &condition).await,
+            None => Ok(candidates),
+        }
+    }
 
-        Ok(QueryResult::select(columns, rows))
+    async fn execute_fts_scan(&self, table: String, query: FtsQuery, filter: Option<Expression>) -> SqlResult<Relation> {
+        let relation = self.table_relation(&table).await?;
+        let mut columns = relation.columns.clone();
+        columns.push(ColumnRef::hidden(Some(table.clone()), RANK_COLUMN));
+        let rows = self
+            .fts
+            .search(&table, &query)
+            .await?
+            .into_iter()
+            .map(|(position, rank)| {
+                let mut row = relation.rows[position].clone();
+                row.values.push(Value::Real(rank));
+                row
+            })
+            .collect();
+        let matches = Relation::new(columns, rows);
+        match filter {
+            Some(condition) => self.filter_rows(matches, &condition).await,
+            None => Ok(matches),
+        }
+    }
+
+    async fn execute_foreign_scan(
+        &self,
+        table: String,
+        pushed: Vec<Expression>,
+        filter: Option<Expression>,
+    ) -> SqlResult<Relation> {
+        let schema = self.table_schema(&table).await?;
+        let foreign = ForeignTable::of(&table, &schema)
+            .ok_or_else(|| SqlError::runtime_error(format!("{} is not a foreign table", table)))?;
+        let scan = self.foreign.get(&foreign.server).await?;
+        let rows = scan.scan(&foreign, &pushed).await?;
+        let relation = stored_relation(&table, &schema, rows);
+        match filter {
+            Some(condition) => self.filter_rows(relation, &condition).await,
+            None => Ok(relation),
+        }
     }
 
     async fn execute_join(
         &self,
         left: QueryPlan,
         right: QueryPlan,
-        _join_type: crate::parser::ast::JoinType,
-        _condition: crate::parser::ast::Expression,
-    ) -> SqlResult<QueryResult> {
+        join_type: JoinType,
+        condition: JoinConstraint,
+    ) -> SqlResult<Relation> {
         // Execute left and right plans
-        let left_result = Box::pin(self.execute_plan(left)).await?;
-        let right_result = Box::pin(self.execute_plan(right)).await?;
+        let left = Box::pin(self.execute_relation(left)).await?;
+        let right = Box::pin(self.execute_relation(right)).await?;
 
-        // Perform join operation
-        match (left_result, right_result) {
-            (
-                QueryResult::Select { columns: left_cols, rows: left_rows },
-                QueryResult::Select { columns: right_cols, rows: right_rows },
-            ) => {
-                // Simplified nested loop join
-                let mut joined_columns = left_cols;
-                joined_columns.extend(right_cols);
-
-                let mut joined_rows = Vec::new();
-                for left_row in &left_rows {
-                    for right_row in &right_rows {
-                        let mut joined_values = left_row.values.clone();
-                        joined_values.extend(right_row.values.clone());
-                        joined_rows.push(Row::new(joined_values));
-                    }
-                }
-
-                Ok(QueryResult::select(joined_columns, joined_rows))
-            }
-            _ => Err(SqlError::runtime_error("Cannot join non-SELECT results")),
-        }
+        // Hash join for equi-joins, nested loop otherwise
+        join::execute_join(left, right, join_type, &condition)
     }
 
     async fn execute_projection(
         &self,
         input: QueryPlan,
         columns: Vec<String>,
-        _expressions: Vec<crate::parser::ast::Expression>,
-    ) -> SqlResult<QueryResult> {
-        let input_result = Box::pin(self.execute_plan(input)).await?;
+        expressions: Vec<Expression>,
+    ) -> SqlResult<Relation> {
+        let input = Box::pin(self.execute_relation(input)).await?;
 
-        match input_result {
-            QueryResult::Select { columns: input_cols, rows } => {
-                // For simplicity, just return the specified columns
-                // In a real implementation, this would evaluate expressions
-                Ok(QueryResult::select(columns, rows))
+        // Column references keep their table qualifier and collation for
+        // ORDER BY above
+        let mut output_columns = Vec::with_capacity(columns.len());
+        for (name, expr) in columns.into_iter().zip(&expressions) {
+            let source = match expr {
+                Expression::Column(column) => Some(&input.columns[input.resolve(None, column)?]),
+                Expression::QualifiedColumn { table, column } => Some(&input.columns[input.resolve(Some(table), column)?]),
+                _ => None,
+            };
+            let mut output = ColumnRef::new(source.and_then(|column| column.table.clone()), name);
+            output.collation = expression_collation(expr, &input.columns)?;
+            output_columns.push(output);
+        }
+
+        let mut rows: Vec<Row> = input.rows.iter().map(|_| Row::new(Vec::with_capacity(expressions.len()))).collect();
+        for expr in &expressions {
+            for (row, value) in rows.iter_mut().zip(self.evaluate_rows(expr, &input).await?) {
+                row.values.push(value);
             }
-            other => Ok(other),
         }
+        Ok(Relation::new(output_columns, rows))
     }
 
     async fn execute_selection(
         &self,
         input: QueryPlan,
-        _condition: crate::parser::ast::Expression,
-    ) -> SqlResult<QueryResult> {
-        let input_result = Box::pin(self.execute_plan(input)).await?;
+        condition: Expression,
+    ) -> SqlResult<Relation> {
+        let input = Box::pin(self.execute_relation(input)).await?;
+        self.filter_rows(input, &condition).await
+    }
 
-        match input_result {
-            QueryResult::Select { columns, rows } => {
-                // For simplicity, filter out half the rows
-                // In a real implementation, this would evaluate the condition
-                let row_count = rows.len();
-                let filtered_rows: Vec<Row> = rows.into_iter().take(row_count / 2).collect();
-                Ok(QueryResult::select(columns, filtered_rows))
-            }
-            other => Ok(other),
+    /// Keep the rows for which `condition` is true
+    async fn filter_rows(&self, relation: Relation, condition: &Expression) -> SqlResult<Relation> {
+        if !condition.has_subquery() {
+            return relation.filter(condition);
         }
+        let keep = self.evaluate_rows(condition, &relation).await?;
+        let rows = relation
+            .rows
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(row, keep)| is_truthy(&keep).then_some(row))
+            .collect();
+        Ok(Relation::new(relation.columns, rows))
+    }
+
+    /// Evaluate `expr` on every row of `relation`. Subqueries that do not
+    /// depend on the row run once; correlated ones run for each row.
+    async fn evaluate_rows(&self, expr: &Expression, relation: &Relation) -> SqlResult<Vec<Value>> {
+        let expr = self.resolve_subqueries(expr, &relation.columns, None).await?;
+        let mut values = Vec::with_capacity(relation.rows.len());
+        for row in &relation.rows {
+            let value = if expr.has_subquery() {
+                let resolved = self.resolve_subqueries(&expr, &relation.columns, Some(row)).await?;
+                evaluate(&resolved, &relation.columns, row)?
+            } else {
+                evaluate(&expr, &relation.columns, row)?
+            };
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    /// Replace the subqueries in `expr` with their results. Without a row
+    /// only uncorrelated subqueries run; with one, correlated subqueries
+    /// run with the row's values in place of their outer references.
+    async fn resolve_subqueries(&self, expr: &Expression, columns: &[ColumnRef], row: Option<&Row>) -> SqlResult<Expression> {
+        let select = match expr {
+            Expression::Subquery(select) | Expression::Exists(select) => select,
+            Expression::InSubquery { subquery, .. } => subquery,
+            other if other.has_subquery() => {
+                let mut resolved = other.clone();
+                for child in resolved.children_mut() {
+                    *child = Box::pin(self.resolve_subqueries(child, columns, row)).await?;
+                }
+                return Ok(resolved);
+            }
+            other => return Ok(other.clone()),
+        };
+
+        let outer = outer_references(select, &*self.schemas.read().await);
+        let select = if outer.is_empty() {
+            (**select).clone()
+        } else {
+            let Some(row) = row else {
+                return Ok(expr.clone());
+            };
+            let values = outer
+                .into_iter()
+                .map(|reference| {
+                    let value = evaluate(&reference, columns, row)?;
+                    Ok((reference, value))
+                })
+                .collect::<SqlResult<Vec<_>>>()?;
+            substitute(select, &values, &*self.schemas.read().await)
+        };
+        let result = Box::pin(self.run_subquery(select)).await?;
+
+        let single_column = |result: &Relation| {
+            if result.columns.len() != 1 {
+                return Err(SqlError::runtime_error(format!(
+                    "Subquery returns {} columns - expected 1",
+                    result.columns.len()
+                )));
+            }
+            Ok(())
+        };
+        Ok(match expr {
+            Expression::Exists(_) => Expression::Literal(Value::Boolean(!result.rows.is_empty())),
+            Expression::InSubquery { expr, .. } => {
+                single_column(&result)?;
+                Expression::In {
+                    expr: Box::new(Box::pin(self.resolve_subqueries(expr, columns, row)).await?),
+                    list: result
+                        .rows
+                        .into_iter()
+                        .map(|row| Expression::Literal(row.values.into_iter().next().unwrap_or(Value::Null)))
+                        .collect(),
+                }
+            }
+            _ => {
+                // A scalar subquery is its first row's value, NULL if empty
+                single_column(&result)?;
+                let first = result.rows.into_iter().next();
+                Expression::Literal(first.and_then(|row| row.values.into_iter().next()).unwrap_or(Value::Null))
+            }
+        })
+    }
+
+    async fn run_subquery(&self, select: SelectStatement) -> SqlResult<Relation> {
+        let planner = QueryPlanner::with_catalog(
+            self.indexes.clone(),
+            self.schemas.clone(),
+            self.stats.clone(),
+            self.foreign.clone(),
+        );
+        let plan = planner.plan_statement(Statement::Select(select)).await?;
+        profile::hidden(self.execute_relation(plan)).await
     }
 
     async fn execute_sort(
         &self,
         input: QueryPlan,
-        _order_by: Vec<(String, crate::parser::ast::OrderDirection)>,
-    ) -> SqlResult<QueryResult> {
-        let input_result = Box::pin(self.execute_plan(input)).await?;
-
-        match input_result {
-            QueryResult::Select { columns, mut rows } => {
-                // For simplicity, reverse the order
-                // In a real implementation, this would sort by the specified columns
-                rows.reverse();
-                Ok(QueryResult::select(columns, rows))
-            }
-            other => Ok(other),
+        order_by: Vec<(Expression, OrderDirection)>,
+        limit: Option<usize>,
+    ) -> SqlResult<Relation> {
+        let input = Box::pin(self.execute_relation(input)).await?;
+        let sort = ExternalSort::new(order_by, self.sort_config.clone());
+        match limit {
+            Some(limit) => sort.with_limit(limit).execute(input).await,
+            None => sort.execute(input).await,
         }
     }
 
@@ -214,90 +944,478 @@ impl QueryExecutor {
         input: QueryPlan,
         count: u64,
         offset: Option<u64>,
-    ) -> SqlResult<QueryResult> {
-        let input_result = Box::pin(self.execute_plan(input)).await?;
-
-        match input_result {
-            QueryResult::Select { columns, rows } => {
-                let start = offset.unwrap_or(0) as usize;
-                let end = start + count as usize;
-
-                let limited_rows = if start < rows.len() {
-                    rows.into_iter().skip(start).take(count as usize).collect()
-                } else {
-                    Vec::new()
-                };
+    ) -> SqlResult<Relation> {
+        let offset = offset.unwrap_or(0) as usize;
+        let count = count as usize;
 
-                Ok(QueryResult::select(columns, limited_rows))
+        // A sort feeding a limit only has to produce the leading rows
+        let span = match &input {
+            QueryPlan::Sort { .. } => profile::enter(&input),
+            _ => None,
+        };
+        let mut relation = match input {
+            QueryPlan::Sort { input, order_by } => {
+                let sorted = self.execute_sort(*input, order_by, Some(offset.saturating_add(count))).await;
+                profile::exit(span, sorted.as_ref().map_or(0, |relation| relation.rows.len()));
+                sorted?
             }
-            other => Ok(other),
-        }
+            input => Box::pin(self.execute_relation(input)).await?,
+        };
+        relation.rows = relation.rows.into_iter().skip(offset).take(count).collect();
+        Ok(relation)
     }
 
     async fn execute_group_by(
         &self,
         input: QueryPlan,
-        _group_columns: Vec<String>,
-        _aggregates: Vec<AggregateFunction>,
-    ) -> SqlResult<QueryResult> {
-        let input_result = Box::pin(self.execute_plan(input)).await?;
-
-        match input_result {
-            QueryResult::Select { columns: _, rows } => {
-                // Simplified aggregation - just count rows
-                let result_columns = vec!["count".to_string()];
-                let result_rows = vec![Row::new(vec![Value::Integer(rows.len() as i64)])];
+        group_columns: Vec<Expression>,
+        aggregates: Vec<AggregateFunction>,
+    ) -> SqlResult<Relation> {
+        let input = Box::pin(self.execute_relation(input)).await?;
+        HashAggregate::new(group_columns, aggregates).execute(input)
+    }
 
-                Ok(QueryResult::select(result_columns, result_rows))
-            }
-            other => Ok(other),
+    async fn execute_create_index(&self, index: IndexInfo) -> SqlResult<QueryResult> {
+        if self.table_schema(&index.table).await.is_ok_and(|schema| schema.foreign.is_some()) {
+            return Err(SqlError::schema_error(format!("cannot index foreign table {}", index.table)));
         }
+        let tables = self.tables.read().await;
+        let relation = tables
+            .get(&index.table)
+            .ok_or_else(|| SqlError::table_not_found(index.table.clone()))?;
+        self.indexes.create(index, relation).await?;
+        Ok(QueryResult::create_index())
     }
 
     async fn execute_insert(
         &self,
-        _table: String,
-        _columns: Vec<String>,
+        table: String,
+        columns: Vec<String>,
         values: Vec<Vec<Value>>,
     ) -> SqlResult<QueryResult> {
-        // Simulate insert operation
-        // In a real implementation, this would:
-        // 1. Validate the data
-        // 2. Insert into storage
-        // 3. Update indexes
-        // 4. Log to WAL
+        let _writer = self.writer.lock().await;
+        let schema = self.writable_schema(&table).await?;
+        let positions = if columns.is_empty() {
+            (0..schema.columns.len()).collect()
+        } else {
+            columns
+                .iter()
+                .map(|column| {
+                    schema
+                        .position(column)
+                        .ok_or_else(|| SqlError::column_not_found(column.clone()))
+                })
+                .collect::<SqlResult<Vec<_>>>()?
+        };
+
+        let schemas = self.schemas.read().await.clone();
+        let mut tables = self.tables.write().await;
+        let mut relation = tables
+            .remove(&table)
+            .ok_or_else(|| SqlError::table_not_found(table.clone()))?;
+        let rowid_column = schema.rowid_column();
+        let mut next_rowid = rowid_column.map_or(1, |column| self.next_rowid(&table, column, &relation));
+
+        let mut new_rows = Vec::with_capacity(values.len());
+        for row_values in values {
+            if row_values.len() != positions.len() {
+                tables.insert(table.clone(), relation);
+                return Err(SqlError::runtime_error(format!(
+                    "Table {} has {} columns but {} values were supplied",
+                    table,
+                    positions.len(),
+                    row_values.len()
+                )));
+            }
+            let mut row: Vec<Value> = schema
+                .columns
+                .iter()
+                .map(|column| column.default.clone().unwrap_or(Value::Null))
+                .collect();
+            for (&position, value) in positions.iter().zip(row_values) {
+                row[position] = value;
+            }
+            // An INTEGER PRIMARY KEY left NULL is assigned the next rowid
+            if let Some(column) = rowid_column {
+                match row[column] {
+                    Value::Null => {
+                        row[column] = Value::Integer(next_rowid);
+                        next_rowid = next_rowid.saturating_add(1);
+                    }
+                    Value::Integer(id) => next_rowid = next_rowid.max(id.saturating_add(1)),
+                    _ => {}
+                }
+            }
+            new_rows.push(Row::new(row));
+        }
+
+        // The rows are appended in place, and taken off again if the
+        // constraints or a unique index reject them
+        let stored = Arc::make_mut(&mut relation);
+        let first_new = stored.rows.len();
+        stored.rows.extend(new_rows);
+        let new_rows = &stored.rows[first_new..];
+        let checked = match check_rows(&table, &schema, stored, new_rows, &schemas, &tables) {
+            Ok(()) => self.indexes.append(&table, first_new, new_rows).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = checked {
+            stored.rows.truncate(first_new);
+            tables.insert(table, relation);
+            return Err(error);
+        }
+        if rowid_column.is_some() {
+            self.lock_next_rowids().insert(table.clone(), next_rowid);
+        }
 
-        Ok(QueryResult::insert(values.len() as u64))
+        let inserted = new_rows.len() as u64;
+        self.record_changes(|| {
+            new_rows
+                .iter()
+                .map(|row| RowChange::insert(&table, schema.column_names(), row.values.clone()))
+                .collect()
+        });
+        self.record_edit(&table, || TableEdit::Append(new_rows.to_vec()));
+        let indexed = self.fts.insert(&table, new_rows).await;
+        tables.insert(table, relation);
+        indexed?;
+        Ok(QueryResult::insert(inserted))
     }
 
     async fn execute_update(
         &self,
-        _table: String,
-        _assignments: HashMap<String, crate::parser::ast::Expression>,
-        _condition: Option<crate::parser::ast::Expression>,
+        table: String,
+        assignments: HashMap<String, Expression>,
+        condition: Option<Expression>,
     ) -> SqlResult<QueryResult> {
-        // Simulate update operation
-        Ok(QueryResult::update(1))
+        let schema = self.writable_schema(&table).await?;
+        let assignments = assignments
+            .into_iter()
+            .map(|(column, expr)| {
+                schema
+                    .position(&column)
+                    .map(|position| (position, expr))
+                    .ok_or_else(|| SqlError::column_not_found(column))
+            })
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        let _writer = self.writer.lock().await;
+        let current = self.table_relation(&table).await?;
+        let matched = self.matching_rows(&table, condition.as_ref(), &current).await?;
+        let updated_rows: Vec<usize> = (0..current.rows.len()).filter(|&index| matched[index]).collect();
+
+        // Every assignment sees the rows as they were before the update
+        let targets = Relation::new(
+            current.columns.clone(),
+            updated_rows.iter().map(|&index| current.rows[index].clone()).collect(),
+        );
+        drop(current);
+        let mut updated = targets.rows.clone();
+        for (position, expr) in &assignments {
+            for (row, value) in updated.iter_mut().zip(self.evaluate_rows(expr, &targets).await?) {
+                row.values[*position] = value;
+            }
+        }
+
+        let schemas = self.schemas.read().await.clone();
+        let mut tables = self.tables.write().await;
+        let mut relation = tables
+            .remove(&table)
+            .ok_or_else(|| SqlError::table_not_found(table.clone()))?;
+        let stored = Arc::make_mut(&mut relation);
+        for (&index, row) in updated_rows.iter().zip(&updated) {
+            stored.rows[index] = row.clone();
+        }
+        // Keys still referenced by other rows must not change
+        let checked = check_rows(&table, &schema, stored, &updated, &schemas, &tables)
+            .and_then(|()| check_references(&table, stored, &schemas, &tables));
+        let checked = match checked {
+            Ok(()) => self.indexes.update(&table, &updated_rows, &targets.rows, &updated).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = checked {
+            for (&index, row) in updated_rows.iter().zip(&targets.rows) {
+                stored.rows[index] = row.clone();
+            }
+            tables.insert(table, relation);
+            return Err(error);
+        }
+        tables.insert(table.clone(), relation);
+        if schema
+            .rowid_column()
+            .is_some_and(|column| assignments.iter().any(|(position, _)| *position == column))
+        {
+            self.forget_next_rowid(&table);
+        }
+
+        self.fts.update(&table, &updated_rows, &targets.rows, &updated).await?;
+        self.record_changes(|| {
+            targets
+                .rows
+                .iter()
+                .zip(&updated)
+                .map(|(old, new)| RowChange::update(&table, schema.column_names(), old.values.clone(), new.values.clone()))
+                .collect()
+        });
+        if !updated.is_empty() {
+            self.record_edit(&table, || TableEdit::Update(updated_rows.iter().copied().zip(updated.iter().cloned()).collect()));
+        }
+        Ok(QueryResult::update(updated.len() as u64))
     }
 
     async fn execute_delete(
         &self,
-        _table: String,
-        _condition: Option<crate::parser::ast::Expression>,
+        table: String,
+        condition: Option<Expression>,
     ) -> SqlResult<QueryResult> {
-        // Simulate delete operation
-        Ok(QueryResult::delete(1))
+        let _writer = self.writer.lock().await;
+        self.writable_schema(&table).await?;
+        let current = self.table_relation(&table).await?;
+        let matched = self.matching_rows(&table, condition.as_ref(), &current).await?;
+        drop(current);
+        let removed_positions: Vec<usize> = (0..matched.len()).filter(|&position| matched[position]).collect();
+
+        let schemas = self.schemas.read().await.clone();
+        let mut tables = self.tables.write().await;
+        let deleted = removed_positions.len() as u64;
+
+        // Work out the ON DELETE actions of the rows referencing the
+        // deleted ones first, then change every table they reach together
+        let mut deletions: BTreeMap<String, Deletion> = BTreeMap::new();
+        let removed: Vec<Row> = {
+            let rows = &tables
+                .get(&table)
+                .ok_or_else(|| SqlError::table_not_found(table.clone()))?
+                .rows;
+            removed_positions.iter().map(|&position| rows[position].clone()).collect()
+        };
+        deletions.entry(table.clone()).or_default().removed.extend(&removed_positions);
+        let mut pending = vec![(table.clone(), removed)];
+        let capturing = self.capturing_changes();
+        let mut changes = Vec::new();
+        while let Some((parent, removed)) = pending.pop() {
+            if capturing {
+                let columns = schemas[&parent].column_names();
+                changes.extend(removed.iter().map(|row| RowChange::delete(&parent, columns.clone(), row.values.clone())));
+            }
+            for (child, foreign_key) in referencing_keys(&parent, &schemas) {
+                let parent_schema = &schemas[&parent];
+                let parent_positions = positions(parent_schema, &foreign_key.foreign_columns)?;
+                let removed_keys: Vec<Vec<Value>> = removed
+                    .iter()
+                    .filter_map(|row| key_of(row, &parent_positions))
+                    .collect();
+                let child_positions = positions(&schemas[&child], &foreign_key.columns)?;
+                let child_rows = &tables
+                    .get(&child)
+                    .ok_or_else(|| SqlError::table_not_found(child.clone()))?
+                    .rows;
+                let deletion = deletions.entry(child.clone()).or_default();
+                let references = |row: &Row| {
+                    key_of(row, &child_positions).is_some_and(|key| removed_keys.contains(&key))
+                };
+                let referencing: Vec<usize> = (0..child_rows.len())
+                    .filter(|position| !deletion.removed.contains(position))
+                    .filter(|&position| references(deletion.row(child_rows, position)))
+                    .collect();
+                if referencing.is_empty() {
+                    continue;
+                }
+                match foreign_key.on_delete {
+                    ForeignKeyAction::NoAction | ForeignKeyAction::Restrict => {
+                        return Err(foreign_key_failed());
+                    }
+                    ForeignKeyAction::Cascade => {
+                        let cascaded = referencing
+                            .iter()
+                            .map(|&position| deletion.row(child_rows, position).clone())
+                            .collect();
+                        deletion.removed.extend(referencing);
+                        pending.push((child.clone(), cascaded));
+                    }
+                    ForeignKeyAction::SetNull => {
+                        for position in referencing {
+                            let mut row = deletion.row(child_rows, position).clone();
+                            let old = capturing.then(|| row.values.clone());
+                            for &column in &child_positions {
+                                row.values[column] = Value::Null;
+                            }
+                            check_not_null(&child, &schemas[&child], &row)?;
+                            if let Some(old) = old {
+                                let columns = schemas[&child].column_names();
+                                changes.push(RowChange::update(&child, columns, old, row.values.clone()));
+                            }
+                            deletion.nulled.insert(position, row);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut unindexed = Vec::new();
+        for (name, deletion) in deletions {
+            let Some(mut relation) = tables.remove(&name) else {
+                continue;
+            };
+            let stored = Arc::make_mut(&mut relation);
+            // Rows set to NULL and then deleted are only deleted
+            let (positions, nulled): (Vec<usize>, Vec<Row>) = deletion
+                .nulled
+                .into_iter()
+                .filter(|(position, _)| !deletion.removed.contains(position))
+                .unzip();
+            if !positions.is_empty() {
+                let old: Vec<Row> = positions.iter().map(|&position| stored.rows[position].clone()).collect();
+                // A key with a NULL in it never collides, so this cannot fail
+                let _ = self.indexes.update(&name, &positions, &old, &nulled).await;
+                for (&position, row) in positions.iter().zip(&nulled) {
+                    stored.rows[position] = row.clone();
+                }
+                self.record_edit(&name, || TableEdit::Update(positions.iter().copied().zip(nulled).collect()));
+            }
+            let positions: Vec<usize> = deletion.removed.into_iter().collect();
+            if !positions.is_empty() {
+                let removed: Vec<Row> = positions.iter().map(|&position| stored.rows[position].clone()).collect();
+                self.indexes.delete(&name, &positions, &removed).await;
+                let mut position = 0;
+                stored.rows.retain(|_| {
+                    let kept = positions.binary_search(&position).is_err();
+                    position += 1;
+                    kept
+                });
+                self.record_edit(&name, || TableEdit::Delete(positions.clone()));
+                unindexed.push((name.clone(), positions, removed));
+            }
+            self.forget_next_rowid(&name);
+            tables.insert(name, relation);
+        }
+        for (name, positions, removed) in unindexed {
+            self.fts.delete(&name, &positions, &removed).await?;
+        }
+        self.record_changes(|| changes);
+        Ok(QueryResult::delete(deleted))
     }
 
     async fn execute_create_table(
         &self,
-        _table: String,
-        _schema: TableSchema,
+        table: String,
+        schema: TableSchema,
     ) -> SqlResult<QueryResult> {
-        // Simulate table creation
+        let mut schemas = self.schemas.write().await;
+        if schemas.contains_key(&table) {
+            return Err(SqlError::schema_error(format!("Table {} already exists", table)));
+        }
+        let relation = stored_relation(&table, &schema, Vec::new());
+        for index in autoindexes(&table, &schema) {
+            self.indexes.create(index, &relation).await?;
+        }
+        if let Some(options) = &schema.fts {
+            self.fts.create(&table, options, schema.column_names(), &[]).await?;
+        }
+        self.tables.write().await.insert(table.clone(), Arc::new(relation));
+        schemas.insert(table, schema);
         Ok(QueryResult::create_table())
     }
 
+    async fn execute_drop_table(&self, table: String, if_exists: bool) -> SqlResult<QueryResult> {
+        if self.schemas.read().await.contains_key(&table) {
+            self.remove_table(&table).await;
+        } else if !if_exists {
+            return Err(SqlError::table_not_found(table));
+        }
+        Ok(QueryResult::drop_table())
+    }
+
+    /// Which rows of `table` (all of them, in `relation`) an UPDATE or
+    /// DELETE condition selects; MATCH conditions on a full-text table are
+    /// answered by its index
+    async fn matching_rows(&self, table: &str, condition: Option<&Expression>, relation: &Relation) -> SqlResult<Vec<bool>> {
+        let Some(condition) = condition else {
+            return Ok(vec![true; relation.rows.len()]);
+        };
+        let schema = self.table_schema(table).await?;
+        let (query, rest) = match schema.fts {
+            Some(_) => extract_match(condition, &[table], &schema.column_names())?,
+            None => (None, Some(condition.clone())),
+        };
+        let mut matched = match rest {
+            Some(rest) => self.evaluate_rows(&rest, relation).await?.iter().map(is_truthy).collect(),
+            None => vec![true; relation.rows.len()],
+        };
+        if let Some(query) = query {
+            let mut found = vec![false; relation.rows.len()];
+            for (position, _) in self.fts.search(table, &query).await? {
+                found[position] = true;
+            }
+            for (matched, found) in matched.iter_mut().zip(found) {
+                *matched &= found;
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Rows of a catalog table such as `catalog.tables`, as of now
+    async fn catalog_relation(&self, table: &str) -> SqlResult<Relation> {
+        let schemas = self.schemas.read().await.clone();
+        let mut indexes = Vec::new();
+        let mut names: Vec<&String> = schemas.keys().collect();
+        names.sort();
+        for name in names {
+            indexes.extend(self.indexes.indexes_on(name).await);
+        }
+        let stats = self.stats.read().await.clone();
+        let registry = self.registry.read().await;
+        CatalogSnapshot {
+            schemas: &schemas,
+            indexes: &indexes,
+            stats: &stats,
+            registry: &registry,
+        }
+        .relation(table)
+    }
+
+    /// Current contents of a table
+    async fn table_relation(&self, table: &str) -> SqlResult<Arc<Relation>> {
+        self.tables
+            .read()
+            .await
+            .get(table)
+            .cloned()
+            .ok_or_else(|| SqlError::table_not_found(table.to_string()))
+    }
+
+    async fn table_schema(&self, table: &str) -> SqlResult<TableSchema> {
+        self.schemas
+            .read()
+            .await
+            .get(table)
+            .cloned()
+            .ok_or_else(|| SqlError::table_not_found(table.to_string()))
+    }
+
+    /// Schema of a table INSERT, UPDATE and DELETE may change; foreign
+    /// tables are read-only
+    async fn writable_schema(&self, table: &str) -> SqlResult<TableSchema> {
+        let schema = self.table_schema(table).await?;
+        if schema.foreign.is_some() {
+            return Err(SqlError::runtime_error(format!("cannot modify foreign table {}", table)));
+        }
+        Ok(schema)
+    }
+
+    /// Install the new contents of a table, rejecting them (and leaving the
+    /// table untouched) if a unique index would be violated
+    async fn replace_rows(
+        &self,
+        tables: &mut HashMap<String, Arc<Relation>>,
+        table: &str,
+        relation: Relation,
+    ) -> SqlResult<()> {
+        self.indexes.rebuild(table, &relation).await?;
+        tables.insert(table.to_string(), Arc::new(relation));
+        Ok(())
+    }
+
     async fn execute_union(
         &self,
         left: QueryPlan,
@@ -382,10 +1500,175 @@ impl Default for QueryExecutor {
     }
 }
 
+/// The relation holding a table's rows, its columns carrying their
+/// collations; a collation that is not registered (yet) compares as BINARY
+fn stored_relation(table: &str, schema: &TableSchema, rows: Vec<Row>) -> Relation {
+    let mut relation = Relation::from_table(table, schema.column_names(), rows);
+    for (column, declared) in relation.columns.iter_mut().zip(&schema.columns) {
+        column.collation = declared.collation.as_deref().and_then(|name| Collation::find(name).ok());
+    }
+    relation
+}
+
+/// Prefix of the indexes that enforce UNIQUE and PRIMARY KEY constraints
+pub(crate) const AUTOINDEX_PREFIX: &str = "sqlite_autoindex_";
+
+/// Unique indexes backing the UNIQUE and PRIMARY KEY constraints of a table
+fn autoindexes(table: &str, schema: &TableSchema) -> Vec<IndexInfo> {
+    schema
+        .unique_keys()
+        .into_iter()
+        .enumerate()
+        .map(|(n, columns)| IndexInfo::new(format!("{}{}_{}", AUTOINDEX_PREFIX, table, n + 1), table, columns, true))
+        .collect()
+}
+
+/// What a DELETE does to one table, by row position: the rows it removes
+/// and the rows whose foreign keys ON DELETE SET NULL clears
+#[derive(Debug, Default)]
+struct Deletion {
+    removed: BTreeSet<usize>,
+    nulled: BTreeMap<usize, Row>,
+}
+
+impl Deletion {
+    /// A row of the table as the DELETE has left it so far
+    fn row<'a>(&'a self, rows: &'a [Row], position: usize) -> &'a Row {
+        self.nulled.get(&position).unwrap_or(&rows[position])
+    }
+}
+
+/// Check the NOT NULL, CHECK and FOREIGN KEY constraints of rows written
+/// to `table`, whose new contents are `relation`
+fn check_rows(
+    table: &str,
+    schema: &TableSchema,
+    relation: &Relation,
+    rows: &[Row],
+    schemas: &HashMap<String, TableSchema>,
+    tables: &HashMap<String, Arc<Relation>>,
+) -> SqlResult<()> {
+    let foreign_keys = schema.foreign_keys(|parent| schemas.get(parent).map(TableSchema::primary_key));
+    for row in rows {
+        check_not_null(table, schema, row)?;
+        for check in schema.checks() {
+            // A CHECK that evaluates to NULL passes, as in SQLite
+            let value = evaluate(check, &relation.columns, row)?;
+            if !value.is_null() && !is_truthy(&value) {
+                return Err(SqlError::constraint_violation(format!("CHECK constraint failed: {}", check)));
+            }
+        }
+        for foreign_key in &foreign_keys {
+            let Some(key) = key_of(row, &positions(schema, &foreign_key.columns)?) else {
+                continue;
+            };
+            let parent = if foreign_key.foreign_table == table {
+                relation
+            } else {
+                tables
+                    .get(&foreign_key.foreign_table)
+                    .map(Arc::as_ref)
+                    .ok_or_else(|| SqlError::table_not_found(foreign_key.foreign_table.clone()))?
+            };
+            let parent_schema = schemas
+                .get(&foreign_key.foreign_table)
+                .ok_or_else(|| SqlError::table_not_found(foreign_key.foreign_table.clone()))?;
+            let parent_positions = positions(parent_schema, &foreign_key.foreign_columns)?;
+            if !parent.rows.iter().any(|row| key_of(row, &parent_positions).as_ref() == Some(&key)) {
+                return Err(foreign_key_failed());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that every row referencing `table` still finds its parent in the
+/// table's new contents, `relation`
+fn check_references(
+    table: &str,
+    relation: &Relation,
+    schemas: &HashMap<String, TableSchema>,
+    tables: &HashMap<String, Arc<Relation>>,
+) -> SqlResult<()> {
+    for (child, foreign_key) in referencing_keys(table, schemas) {
+        let parent_positions = positions(&schemas[table], &foreign_key.foreign_columns)?;
+        let keys: Vec<Vec<Value>> = relation
+            .rows
+            .iter()
+            .filter_map(|row| key_of(row, &parent_positions))
+            .collect();
+        let child_positions = positions(&schemas[&child], &foreign_key.columns)?;
+        let children = if child == table {
+            relation
+        } else {
+            tables
+                .get(&child)
+                .map(Arc::as_ref)
+                .ok_or_else(|| SqlError::table_not_found(child.clone()))?
+        };
+        let orphaned = children
+            .rows
+            .iter()
+            .filter_map(|row| key_of(row, &child_positions))
+            .any(|key| !keys.contains(&key));
+        if orphaned {
+            return Err(foreign_key_failed());
+        }
+    }
+    Ok(())
+}
+
+fn check_not_null(table: &str, schema: &TableSchema, row: &Row) -> SqlResult<()> {
+    for (column, value) in schema.columns.iter().zip(&row.values) {
+        if !column.nullable && value.is_null() {
+            return Err(SqlError::constraint_violation(format!(
+                "NOT NULL constraint failed: {}.{}",
+                table, column.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Tables with a foreign key into `parent`, and that key
+fn referencing_keys(parent: &str, schemas: &HashMap<String, TableSchema>) -> Vec<(String, ForeignKey)> {
+    let mut keys: Vec<(String, ForeignKey)> = schemas
+        .iter()
+        .flat_map(|(child, schema)| {
+            schema
+                .foreign_keys(|table| schemas.get(table).map(TableSchema::primary_key))
+                .into_iter()
+                .filter(|key| key.foreign_table == parent)
+                .map(move |key| (child.clone(), key))
+        })
+        .collect();
+    keys.sort_by(|a, b| a.0.cmp(&b.0));
+    keys
+}
+
+fn positions(schema: &TableSchema, columns: &[String]) -> SqlResult<Vec<usize>> {
+    columns
+        .iter()
+        .map(|column| schema.position(column).ok_or_else(|| SqlError::column_not_found(column.clone())))
+        .collect()
+}
+
+/// Values of a key, or `None` if any is NULL (such a key references
+/// nothing)
+fn key_of(row: &Row, positions: &[usize]) -> Option<Vec<Value>> {
+    positions
+        .iter()
+        .map(|&position| Some(row.values[position].clone()).filter(|value| !value.is_null()))
+        .collect()
+}
+
+fn foreign_key_failed() -> SqlError {
+    SqlError::constraint_violation("FOREIGN KEY constraint failed")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::ast::JoinType;
 
     #[tokio::test]
     async fn test_execute_scan() {
@@ -411,6 +1694,9 @@ mod tests {
     #[tokio::test]
     async fn test_execute_insert() {
         let executor = QueryExecutor::new();
+        executor
+            .register_table("users", vec!["id".to_string(), "name".to_string()], vec![])
+            .await;
         
         let plan = QueryPlan::Insert {
             table: "users".to_string(),
@@ -426,6 +1712,8 @@ mod tests {
             }
             _ => panic!("Expected INSERT result"),
         }
+        let (_, rows) = executor.table_snapshot("users").await.unwrap();
+        assert_eq!(rows, vec![Row::new(vec![Value::Null, Value::Text("Alice".to_string())])]);
     }
 
     #[tokio::test]
@@ -448,7 +1736,7 @@ mod tests {
             left: Box::new(left_plan),
             right: Box::new(right_plan),
             join_type: JoinType::Inner,
-            condition: crate::parser::ast::Expression::Literal(Value::Boolean(true)),
+            condition: JoinConstraint::On(Expression::Literal(Value::Boolean(true))),
         };
         
         let result = executor.execute_plan(join_plan).await.unwrap();
@@ -461,4 +1749,42 @@ mod tests {
             _ => panic!("Expected SELECT result"),
         }
     }
+
+    #[tokio::test]
+    async fn test_execute_sort_with_spilling() {
+        let executor = QueryExecutor::with_sort_config(SortConfig {
+            memory_rows: 4,
+            spill_cache: crate::page_cache::PageCacheConfig {
+                capacity: 2,
+                page_size: 64,
+                ..Default::default()
+            },
+        });
+        let rows = (0..50).map(|i| Row::new(vec![Value::Integer((i * 37) % 50)])).collect();
+        executor.register_table("numbers", vec!["n".to_string()], rows).await;
+
+        let plan = QueryPlan::Limit {
+            input: Box::new(QueryPlan::Sort {
+                input: Box::new(QueryPlan::Scan {
+                    table: "numbers".to_string(),
+                    filter: None,
+                    projection: None,
+                }),
+                order_by: vec![(
+                    Expression::Column("n".to_string()),
+                    OrderDirection::Desc,
+                )],
+            }),
+            count: 5,
+            offset: Some(10),
+        };
+
+        match executor.execute_plan(plan).await.unwrap() {
+            QueryResult::Select { rows, .. } => {
+                let values: Vec<_> = rows.into_iter().map(|row| row.values[0].clone()).collect();
+                assert_eq!(values, (35..40).rev().map(Value::Integer).collect::<Vec<_>>());
+            }
+            _ => panic!("Expected SELECT result"),
+        }
+    }
 }
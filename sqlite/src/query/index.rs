@@ -0,0 +1,753 @@
+use crate::collation::Collation;
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::{BinaryOperator, Expression};
+use crate::query::relation::Relation;
+use crate::types::{Row, Value};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Index definition as seen by the planner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexInfo {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+impl IndexInfo {
+    pub fn new(name: impl Into<String>, table: impl Into<String>, columns: Vec<String>, unique: bool) -> Self {
+        IndexInfo {
+            name: name.into(),
+            table: table.into(),
+            columns,
+            unique,
+        }
+    }
+
+    /// Whether every column in `needed` is stored in the index
+    pub fn covers(&self, needed: &[String]) -> bool {
+        needed
+            .iter()
+            .all(|name| self.columns.iter().any(|column| column.eq_ignore_ascii_case(name)))
+    }
+    /// Whether the index was made for a UNIQUE or PRIMARY KEY constraint
+    /// rather than by CREATE INDEX
+    pub fn is_automatic(&self) -> bool {
+        self.name.starts_with(super::executor::AUTOINDEX_PREFIX)
+    }
+
+    /// The CREATE INDEX statement that makes this index
+    pub fn create_sql(&self) -> String {
+        format!(
+            "CREATE {}INDEX {} ON {} ({})",
+            if self.unique { "UNIQUE " } else { "" },
+            self.name,
+            self.table,
+            self.columns.join(", ")
+        )
+    }
+}
+
+/// Key range an index scan reads: equality on a prefix of the index
+/// columns, optionally followed by a range on the next column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexLookup {
+    pub equal: Vec<Value>,
+    pub lower: Bound<Value>,
+    pub upper: Bound<Value>,
+}
+
+impl IndexLookup {
+    pub fn has_range(&self) -> bool {
+        !matches!((&self.lower, &self.upper), (Bound::Unbounded, Bound::Unbounded))
+    }
+
+    /// SQLite-style constraint summary, e.g. `(name=? AND age>?)`
+    pub fn describe(&self, columns: &[String]) -> String {
+        let mut terms: Vec<String> = columns
+            .iter()
+            .take(self.equal.len())
+            .map(|column| format!("{}=?", column))
+            .collect();
+        if let Some(column) = columns.get(self.equal.len()) {
+            match &self.lower {
+                Bound::Included(_) => terms.push(format!("{}>=?", column)),
+                Bound::Excluded(_) => terms.push(format!("{}>?", column)),
+                Bound::Unbounded => {}
+            }
+            match &self.upper {
+                Bound::Included(_) => terms.push(format!("{}<=?", column)),
+                Bound::Excluded(_) => terms.push(format!("{}<?", column)),
+                Bound::Unbounded => {}
+            }
+        }
+        format!("({})", terms.join(" AND "))
+    }
+}
+
+/// Index key ordered the way ORDER BY orders values, text by the
+/// collations of the indexed columns
+#[derive(Debug, Clone)]
+struct IndexKey {
+    values: Vec<Value>,
+    collations: Arc<[Collation]>,
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for ((a, b), collation) in self.values.iter().zip(&other.values).zip(self.collations.iter()) {
+            let ordering = collation.compare_values(a, b);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        self.values.len().cmp(&other.values.len())
+    }
+}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+/// Secondary index: ordered map from the indexed column values to the
+/// positions of the table rows holding them
+#[derive(Debug, Clone)]
+pub struct SecondaryIndex {
+    info: IndexInfo,
+    positions: Vec<usize>,
+    /// Collation of each indexed column, which keys compare by; a UNIQUE
+    /// index on a NOCASE column rejects keys differing only in case
+    collations: Arc<[Collation]>,
+    entries: BTreeMap<IndexKey, Vec<usize>>,
+}
+
+impl SecondaryIndex {
+    /// Build an index over `table`; unique indexes reject duplicate keys,
+    /// though keys containing NULL never collide
+    pub fn build(info: IndexInfo, table: &Relation) -> SqlResult<Self> {
+        let positions = info
+            .columns
+            .iter()
+            .map(|column| table.resolve(None, column))
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        let collations: Arc<[Collation]> = positions
+            .iter()
+            .map(|&p| table.columns[p].collation.clone().unwrap_or_default())
+            .collect();
+
+        let mut index = SecondaryIndex {
+            info,
+            positions,
+            collations,
+            entries: BTreeMap::new(),
+        };
+        for (row_index, row) in table.rows.iter().enumerate() {
+            let key = index.key(row);
+            let has_null = key_has_null(&index.positions, row);
+            let slot = index.entries.entry(key).or_default();
+            if index.info.unique && !slot.is_empty() && !has_null {
+                return Err(index.unique_failed());
+            }
+            slot.push(row_index);
+        }
+        Ok(index)
+    }
+
+    fn key(&self, row: &Row) -> IndexKey {
+        IndexKey {
+            values: self.positions.iter().map(|&p| row.values[p].clone()).collect(),
+            collations: self.collations.clone(),
+        }
+    }
+
+    fn unique_failed(&self) -> SqlError {
+        SqlError::constraint_violation(format!(
+            "UNIQUE constraint failed: {}.{}",
+            self.info.table,
+            self.info.columns.join(", ")
+        ))
+    }
+
+    /// Fail if a unique index cannot take the keys of `rows`, given that
+    /// the entries of the rows at `replaced` (ascending) are about to go
+    fn check_unique(&self, replaced: &[usize], rows: &[Row]) -> SqlResult<()> {
+        if !self.info.unique {
+            return Ok(());
+        }
+        let mut added = BTreeSet::new();
+        for row in rows.iter().filter(|row| !key_has_null(&self.positions, row)) {
+            let key = self.key(row);
+            let taken = self
+                .entries
+                .get(&key)
+                .is_some_and(|slot| slot.iter().any(|position| replaced.binary_search(position).is_err()));
+            if taken || !added.insert(key) {
+                return Err(self.unique_failed());
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the entry of the row at `position`; entries with the same key
+    /// stay in row order
+    fn insert_entry(&mut self, position: usize, row: &Row) {
+        let slot = self.entries.entry(self.key(row)).or_default();
+        let at = slot.partition_point(|&p| p < position);
+        slot.insert(at, position);
+    }
+
+    fn remove_entry(&mut self, position: usize, row: &Row) {
+        let key = self.key(row);
+        if let Some(slot) = self.entries.get_mut(&key) {
+            if let Ok(at) = slot.binary_search(&position) {
+                slot.remove(at);
+            }
+            if slot.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+
+    /// Drop the entries of the rows removed from `positions` (ascending)
+    /// and move the rows after them up
+    fn remove_rows(&mut self, positions: &[usize], removed: &[Row]) {
+        for (&position, row) in positions.iter().zip(removed) {
+            self.remove_entry(position, row);
+        }
+        for slot in self.entries.values_mut() {
+            for position in slot.iter_mut() {
+                *position -= positions.partition_point(|&removed| removed < *position);
+            }
+        }
+    }
+
+    pub fn info(&self) -> &IndexInfo {
+        &self.info
+    }
+
+    /// Number of distinct keys
+    pub fn key_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Positions of the rows matching `lookup`, in index order
+    pub fn lookup(&self, lookup: &IndexLookup) -> Vec<usize> {
+        let prefix = lookup.equal.len();
+        let mut start = lookup.equal.clone();
+        match &lookup.lower {
+            Bound::Included(value) | Bound::Excluded(value) => start.push(value.clone()),
+            Bound::Unbounded => {}
+        }
+
+        let start = IndexKey {
+            values: start,
+            collations: self.collations.clone(),
+        };
+        let mut matches = Vec::new();
+        for (key, rows) in self.entries.range((Bound::Included(start), Bound::Unbounded)) {
+            let values = &key.values;
+            if !values[..prefix]
+                .iter()
+                .zip(&lookup.equal)
+                .zip(self.collations.iter())
+                .all(|((a, b), collation)| collation.compare_values(a, b) == Ordering::Equal)
+            {
+                break;
+            }
+            if lookup.has_range() {
+                // NULL sorts first but never satisfies a comparison
+                let value = &values[prefix];
+                let collation = &self.collations[prefix];
+                if value.is_null() || !within(value, &lookup.lower, true, collation) {
+                    continue;
+                }
+                if !within(value, &lookup.upper, false, collation) {
+                    break;
+                }
+            }
+            matches.extend_from_slice(rows);
+        }
+        matches
+    }
+
+    /// Read the rows matching `lookup`; a covering scan returns just the
+    /// index columns instead of whole rows
+    pub fn scan(&self, table: &Relation, lookup: &IndexLookup, covering: bool) -> Relation {
+        let matches = self.lookup(lookup);
+        if covering {
+            let columns = self.positions.iter().map(|&p| table.columns[p].clone()).collect();
+            let rows = matches
+                .into_iter()
+                .map(|row| Row::new(self.positions.iter().map(|&p| table.rows[row].values[p].clone()).collect()))
+                .collect();
+            Relation::new(columns, rows)
+        } else {
+            let rows = matches.into_iter().map(|row| table.rows[row].clone()).collect();
+            Relation::new(table.columns.clone(), rows)
+        }
+    }
+}
+
+fn key_has_null(positions: &[usize], row: &Row) -> bool {
+    positions.iter().any(|&p| row.values[p].is_null())
+}
+
+/// Whether `value` is on the inner side of `bound`
+fn within(value: &Value, bound: &Bound<Value>, lower: bool, collation: &Collation) -> bool {
+    let (limit, inclusive) = match bound {
+        Bound::Included(limit) => (limit, true),
+        Bound::Excluded(limit) => (limit, false),
+        Bound::Unbounded => return true,
+    };
+    match (collation.compare_values(value, limit), lower) {
+        (Ordering::Equal, _) => inclusive,
+        (Ordering::Greater, true) | (Ordering::Less, false) => true,
+        _ => false,
+    }
+}
+
+/// Indexes of the in-memory tables, shared between the planner (which
+/// picks one) and the executor (which maintains and scans them)
+#[derive(Debug, Clone, Default)]
+pub struct IndexCatalog {
+    indexes: Arc<RwLock<HashMap<String, Arc<SecondaryIndex>>>>,
+}
+
+impl IndexCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A separate catalog holding the indexes of this one as they are now;
+    /// the indexes themselves are shared until a write changes them
+    pub async fn fork(&self) -> Self {
+        IndexCatalog {
+            indexes: Arc::new(RwLock::new(self.indexes.read().await.clone())),
+        }
+    }
+
+    pub async fn create(&self, info: IndexInfo, table: &Relation) -> SqlResult<()> {
+        let mut indexes = self.indexes.write().await;
+        if indexes.contains_key(&info.name) {
+            return Err(SqlError::schema_error(format!("Index {} already exists", info.name)));
+        }
+        let name = info.name.clone();
+        indexes.insert(name, Arc::new(SecondaryIndex::build(info, table)?));
+        Ok(())
+    }
+
+    /// Remove an index; false if it did not exist
+    pub async fn drop_index(&self, name: &str) -> bool {
+        self.indexes.write().await.remove(name).is_some()
+    }
+
+    /// Definitions of the indexes on `table`, sorted by name
+    pub async fn indexes_on(&self, table: &str) -> Vec<IndexInfo> {
+        let mut infos: Vec<IndexInfo> = self
+            .indexes
+            .read()
+            .await
+            .values()
+            .filter(|index| index.info.table.eq_ignore_ascii_case(table))
+            .map(|index| index.info.clone())
+            .collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    /// Rebuild every index on `table` after its rows were replaced; if any
+    /// index rejects the new rows (a unique violation) none are changed
+    pub async fn rebuild(&self, table: &str, relation: &Relation) -> SqlResult<()> {
+        let mut indexes = self.indexes.write().await;
+        let rebuilt = indexes
+            .values()
+            .filter(|index| index.info.table.eq_ignore_ascii_case(table))
+            .map(|index| SecondaryIndex::build(index.info.clone(), relation))
+            .collect::<SqlResult<Vec<_>>>()?;
+        for index in rebuilt {
+            indexes.insert(index.info.name.clone(), Arc::new(index));
+        }
+        Ok(())
+    }
+
+    /// Index the rows appended to `table` at `first` onwards; if any index
+    /// rejects them (a unique violation) none are changed
+    pub async fn append(&self, table: &str, first: usize, rows: &[Row]) -> SqlResult<()> {
+        self.change(table, &[], rows, |index| {
+            for (offset, row) in rows.iter().enumerate() {
+                index.insert_entry(first + offset, row);
+            }
+        })
+        .await
+    }
+
+    /// Re-key the rows of `table` at `positions` (ascending) from `old` to
+    /// `new`; if any index rejects the new keys none are changed
+    pub async fn update(&self, table: &str, positions: &[usize], old: &[Row], new: &[Row]) -> SqlResult<()> {
+        self.change(table, positions, new, |index| {
+            for (&position, row) in positions.iter().zip(old) {
+                index.remove_entry(position, row);
+            }
+            for (&position, row) in positions.iter().zip(new) {
+                index.insert_entry(position, row);
+            }
+        })
+        .await
+    }
+
+    /// Drop the rows removed from `table` at `positions` (ascending)
+    pub async fn delete(&self, table: &str, positions: &[usize], removed: &[Row]) {
+        // Removing rows cannot break a unique index
+        let _ = self
+            .change(table, positions, &[], |index| index.remove_rows(positions, removed))
+            .await;
+    }
+
+    /// Fail if an index on `table` would not take the keys of `rows` in
+    /// place of those of the rows at `replaced` (ascending)
+    pub async fn check(&self, table: &str, replaced: &[usize], rows: &[Row]) -> SqlResult<()> {
+        self.indexes
+            .read()
+            .await
+            .values()
+            .filter(|index| index.info.table.eq_ignore_ascii_case(table))
+            .try_for_each(|index| index.check_unique(replaced, rows))
+    }
+
+    /// Apply `change` to every index on `table`, once all of them accept
+    /// the keys of `rows` in place of those of the rows at `replaced`
+    async fn change(
+        &self,
+        table: &str,
+        replaced: &[usize],
+        rows: &[Row],
+        change: impl Fn(&mut SecondaryIndex),
+    ) -> SqlResult<()> {
+        let mut indexes = self.indexes.write().await;
+        let on_table: Vec<String> = indexes
+            .values()
+            .filter(|index| index.info.table.eq_ignore_ascii_case(table))
+            .map(|index| index.info.name.clone())
+            .collect();
+        for name in &on_table {
+            indexes[name].check_unique(replaced, rows)?;
+        }
+        for name in &on_table {
+            if let Some(index) = indexes.get_mut(name) {
+                change(Arc::make_mut(index));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn scan(
+        &self,
+        name: &str,
+        table: &Relation,
+        lookup: &IndexLookup,
+        covering: bool,
+    ) -> SqlResult<Relation> {
+        let indexes = self.indexes.read().await;
+        let index = indexes
+            .get(name)
+            .ok_or_else(|| SqlError::index_not_found(name.to_string()))?;
+        Ok(index.scan(table, lookup, covering))
+    }
+}
+
+/// Comparison operators an index can answer
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RangeOp {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// A sargable conjunct: `column <op> literal`
+#[derive(Debug, Clone, PartialEq)]
+struct ColumnPredicate {
+    column: String,
+    op: RangeOp,
+    value: Value,
+}
+
+/// Break a WHERE clause into the `column <op> literal` conjuncts an index
+/// can serve; anything else is left to the residual filter
+fn sargable_predicates(filter: &Expression) -> Vec<ColumnPredicate> {
+    let mut predicates = Vec::new();
+    collect_predicates(filter, &mut predicates);
+    predicates
+}
+
+fn collect_predicates(expr: &Expression, predicates: &mut Vec<ColumnPredicate>) {
+    match expr {
+        Expression::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            collect_predicates(left, predicates);
+            collect_predicates(right, predicates);
+        }
+        Expression::BinaryOp { left, op, right } => {
+            let op = match op {
+                BinaryOperator::Equal => RangeOp::Equal,
+                BinaryOperator::LessThan => RangeOp::Less,
+                BinaryOperator::LessThanOrEqual => RangeOp::LessOrEqual,
+                BinaryOperator::GreaterThan => RangeOp::Greater,
+                BinaryOperator::GreaterThanOrEqual => RangeOp::GreaterOrEqual,
+                _ => return,
+            };
+            // `5 < age` is `age > 5`
+            let flipped = match op {
+                RangeOp::Less => RangeOp::Greater,
+                RangeOp::LessOrEqual => RangeOp::GreaterOrEqual,
+                RangeOp::Greater => RangeOp::Less,
+                RangeOp::GreaterOrEqual => RangeOp::LessOrEqual,
+                RangeOp::Equal => RangeOp::Equal,
+            };
+            match (column_name(left), literal(right), column_name(right), literal(left)) {
+                (Some(column), Some(value), _, _) => predicates.push(ColumnPredicate { column, op, value }),
+                (_, _, Some(column), Some(value)) => predicates.push(ColumnPredicate {
+                    column,
+                    op: flipped,
+                    value,
+                }),
+                _ => {}
+            }
+        }
+        Expression::Between { expr, low, high } => {
+            if let (Some(column), Some(low), Some(high)) = (column_name(expr), literal(low), literal(high)) {
+                predicates.push(ColumnPredicate {
+                    column: column.clone(),
+                    op: RangeOp::GreaterOrEqual,
+                    value: low,
+                });
+                predicates.push(ColumnPredicate {
+                    column,
+                    op: RangeOp::LessOrEqual,
+                    value: high,
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+fn column_name(expr: &Expression) -> Option<String> {
+    match expr {
+        Expression::Column(name) => Some(name.clone()),
+        Expression::QualifiedColumn { column, .. } => Some(column.clone()),
+        _ => None,
+    }
+}
+
+/// Non-NULL literal; comparisons with NULL never match so cannot use an index
+fn literal(expr: &Expression) -> Option<Value> {
+    match expr {
+        Expression::Literal(value) if !value.is_null() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// The access path chosen for a filtered scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexChoice {
+    pub index: IndexInfo,
+    pub lookup: IndexLookup,
+    pub covering: bool,
+}
+
+/// Pick the index that constrains the most leading columns of `filter`.
+/// `needed` lists every column the query reads (`None` for `SELECT *`) and
+/// decides whether the index alone can answer the query.
+pub fn choose_index(indexes: &[IndexInfo], filter: &Expression, needed: Option<&[String]>) -> Option<IndexChoice> {
+    let mut best: Option<(usize, IndexChoice)> = None;
+    for (score, choice) in index_candidates(indexes, filter, needed) {
+        if best.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+            best = Some((score, choice));
+        }
+    }
+    best.map(|(_, choice)| choice)
+}
+
+/// Every index that can serve `filter`, with how well it matches: higher
+/// scores constrain more leading columns
+pub fn index_candidates(
+    indexes: &[IndexInfo],
+    filter: &Expression,
+    needed: Option<&[String]>,
+) -> Vec<(usize, IndexChoice)> {
+    let predicates = sargable_predicates(filter);
+    let mut candidates = Vec::new();
+
+    for index in indexes {
+        let mut equal = Vec::new();
+        let mut lower = Bound::Unbounded;
+        let mut upper = Bound::Unbounded;
+        for column in &index.columns {
+            let on_column: Vec<&ColumnPredicate> = predicates
+                .iter()
+                .filter(|p| p.column.eq_ignore_ascii_case(column))
+                .collect();
+            if let Some(eq) = on_column.iter().find(|p| p.op == RangeOp::Equal) {
+                equal.push(eq.value.clone());
+                continue;
+            }
+            for predicate in on_column {
+                match predicate.op {
+                    RangeOp::Greater if lower == Bound::Unbounded => lower = Bound::Excluded(predicate.value.clone()),
+                    RangeOp::GreaterOrEqual if lower == Bound::Unbounded => {
+                        lower = Bound::Included(predicate.value.clone())
+                    }
+                    RangeOp::Less if upper == Bound::Unbounded => upper = Bound::Excluded(predicate.value.clone()),
+                    RangeOp::LessOrEqual if upper == Bound::Unbounded => {
+                        upper = Bound::Included(predicate.value.clone())
+                    }
+                    _ => {}
+                }
+            }
+            break;
+        }
+
+        let lookup = IndexLookup { equal, lower, upper };
+        // Two points per equality column, one for a range; a fully matched
+        // unique index wins outright
+        let mut score = lookup.equal.len() * 2 + usize::from(lookup.has_range());
+        if score == 0 {
+            continue;
+        }
+        if index.unique && lookup.equal.len() == index.columns.len() {
+            score += 100;
+        }
+        let covering = needed.is_some_and(|needed| index.covers(needed));
+        let score = score * 2 + usize::from(covering);
+        candidates.push((
+            score,
+            IndexChoice {
+                index: index.clone(),
+                lookup,
+                covering,
+            },
+        ));
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> Relation {
+        let rows = [("ann", 31), ("bob", 25), ("cat", 31), ("dan", 40), ("eve", 19)]
+            .iter()
+            .map(|(name, age)| Row::new(vec![Value::Text(name.to_string()), Value::Integer(*age)]))
+            .chain(std::iter::once(Row::new(vec![Value::Text("fay".to_string()), Value::Null])))
+            .collect();
+        Relation::from_table("people", vec!["name".to_string(), "age".to_string()], rows)
+    }
+
+    fn age_index() -> IndexInfo {
+        IndexInfo::new("idx_age", "people", vec!["age".to_string()], false)
+    }
+
+    fn names(relation: &Relation) -> Vec<String> {
+        relation.rows.iter().map(|row| row.values[0].to_string()).collect()
+    }
+
+    #[test]
+    fn test_create_index_sql() {
+        assert_eq!(age_index().create_sql(), "CREATE INDEX idx_age ON people (age)");
+        let unique = IndexInfo::new("idx_name_age", "people", vec!["name".to_string(), "age".to_string()], true);
+        assert_eq!(unique.create_sql(), "CREATE UNIQUE INDEX idx_name_age ON people (name, age)");
+        assert!(!unique.is_automatic());
+        assert!(IndexInfo::new("sqlite_autoindex_people_1", "people", vec!["name".to_string()], true).is_automatic());
+    }
+
+    #[test]
+    fn test_equality_and_range_lookup() {
+        let table = people();
+        let index = SecondaryIndex::build(age_index(), &table).unwrap();
+
+        let equal = IndexLookup {
+            equal: vec![Value::Integer(31)],
+            lower: Bound::Unbounded,
+            upper: Bound::Unbounded,
+        };
+        assert_eq!(names(&index.scan(&table, &equal, false)), vec!["ann", "cat"]);
+
+        let range = IndexLookup {
+            equal: vec![],
+            lower: Bound::Excluded(Value::Integer(19)),
+            upper: Bound::Included(Value::Integer(31)),
+        };
+        assert_eq!(names(&index.scan(&table, &range, false)), vec!["bob", "ann", "cat"]);
+
+        // An upper bound alone must not return the NULL age
+        let below = IndexLookup {
+            equal: vec![],
+            lower: Bound::Unbounded,
+            upper: Bound::Excluded(Value::Integer(25)),
+        };
+        assert_eq!(names(&index.scan(&table, &below, false)), vec!["eve"]);
+    }
+
+    #[test]
+    fn test_unique_index_rejects_duplicates() {
+        let info = IndexInfo::new("idx_age", "people", vec!["age".to_string()], true);
+        assert!(SecondaryIndex::build(info, &people()).is_err());
+
+        let info = IndexInfo::new("idx_name", "people", vec!["name".to_string()], true);
+        assert!(SecondaryIndex::build(info, &people()).is_ok());
+    }
+
+    #[test]
+    fn test_choose_index_prefers_most_selective() {
+        let composite = IndexInfo::new(
+            "idx_name_age",
+            "people",
+            vec!["name".to_string(), "age".to_string()],
+            false,
+        );
+        let indexes = vec![age_index(), composite];
+        let filter = crate::parser::parse_sql("SELECT * FROM people WHERE age > 20 AND 'ann' = name AND age <= 40")
+            .map(|statement| match statement {
+                crate::parser::ast::Statement::Select(select) => select.where_clause.unwrap(),
+                _ => unreachable!(),
+            })
+            .unwrap();
+
+        let choice = choose_index(&indexes, &filter, Some(&["name".to_string(), "age".to_string()])).unwrap();
+        assert_eq!(choice.index.name, "idx_name_age");
+        assert_eq!(choice.lookup.equal, vec![Value::Text("ann".to_string())]);
+        assert_eq!(choice.lookup.lower, Bound::Excluded(Value::Integer(20)));
+        assert_eq!(choice.lookup.upper, Bound::Included(Value::Integer(40)));
+        assert!(choice.covering);
+        assert_eq!(choice.lookup.describe(&choice.index.columns), "(name=? AND age>? AND age<=?)");
+
+        let unrelated = Expression::BinaryOp {
+            left: Box::new(Expression::Column("name".to_string())),
+            op: BinaryOperator::Like,
+            right: Box::new(Expression::Literal(Value::Text("a%".to_string()))),
+        };
+        assert!(choose_index(&indexes, &unrelated, None).is_none());
+    }
+}
@@ -0,0 +1,470 @@
+use crate::collation::Collation;
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::{BinaryOperator, Expression, JoinConstraint, JoinType};
+use crate::query::planner::JoinStrategy;
+use crate::query::relation::{evaluate, is_truthy, resolve_column, sql_equals, ColumnRef, KeyPart, Relation};
+use crate::types::{Row, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Join condition split into equi-join keys and a residual predicate
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinPredicate {
+    /// Pairs of (left column index, right column index) that must be equal
+    pub keys: Vec<(usize, usize)>,
+    /// Remaining condition evaluated against the combined row
+    pub residual: Option<Expression>,
+    /// Right-hand columns merged into their left counterpart (USING)
+    pub merged: Vec<(usize, usize)>,
+}
+
+impl JoinPredicate {
+    /// Analyze a join constraint against the columns of both inputs
+    pub fn analyze(left: &[ColumnRef], right: &[ColumnRef], constraint: &JoinConstraint) -> SqlResult<Self> {
+        match constraint {
+            JoinConstraint::None => Ok(JoinPredicate {
+                keys: Vec::new(),
+                residual: None,
+                merged: Vec::new(),
+            }),
+            JoinConstraint::Using(columns) => {
+                let keys = columns
+                    .iter()
+                    .map(|name| Ok((resolve_column(left, None, name)?, resolve_column(right, None, name)?)))
+                    .collect::<SqlResult<Vec<_>>>()?;
+                Ok(JoinPredicate {
+                    merged: keys.clone(),
+                    keys,
+                    residual: None,
+                })
+            }
+            JoinConstraint::On(condition) => {
+                let mut keys = Vec::new();
+                let mut residual = Vec::new();
+                for conjunct in split_conjunction(condition) {
+                    match equi_key(left, right, conjunct) {
+                        Some(key) => keys.push(key),
+                        None => residual.push(conjunct.clone()),
+                    }
+                }
+                Ok(JoinPredicate {
+                    keys,
+                    residual: conjunction(residual),
+                    merged: Vec::new(),
+                })
+            }
+        }
+    }
+
+    /// Hash join needs at least one equi-join key; otherwise fall back to
+    /// a nested loop
+    pub fn strategy(&self) -> JoinStrategy {
+        if self.keys.is_empty() {
+            JoinStrategy::NestedLoop
+        } else {
+            JoinStrategy::HashJoin
+        }
+    }
+
+    fn residual_matches(&self, columns: &[ColumnRef], row: &Row) -> SqlResult<bool> {
+        match &self.residual {
+            Some(condition) => Ok(is_truthy(&evaluate(condition, columns, row)?)),
+            None => Ok(true),
+        }
+    }
+}
+
+/// Execute a join, choosing between hash join and nested loop
+pub fn execute_join(
+    left: Relation,
+    right: Relation,
+    join_type: JoinType,
+    constraint: &JoinConstraint,
+) -> SqlResult<Relation> {
+    if join_type == JoinType::Cross && *constraint != JoinConstraint::None {
+        return Err(SqlError::parse_error("CROSS JOIN does not accept ON or USING"));
+    }
+    let predicate = JoinPredicate::analyze(&left.columns, &right.columns, constraint)?;
+    match predicate.strategy() {
+        JoinStrategy::HashJoin => HashJoin::new(join_type, predicate).execute(left, right),
+        _ => NestedLoopJoin::new(join_type, predicate).execute(left, right),
+    }
+}
+
+/// Nested loop join: compares every pair of rows
+#[derive(Debug, Clone)]
+pub struct NestedLoopJoin {
+    join_type: JoinType,
+    predicate: JoinPredicate,
+}
+
+impl NestedLoopJoin {
+    pub fn new(join_type: JoinType, predicate: JoinPredicate) -> Self {
+        NestedLoopJoin { join_type, predicate }
+    }
+
+    pub fn execute(&self, left: Relation, right: Relation) -> SqlResult<Relation> {
+        let mut output = JoinOutput::new(&left, &right, self.join_type);
+        for left_row in &left.rows {
+            let mut matched = false;
+            for (right_index, right_row) in right.rows.iter().enumerate() {
+                let combined = output.combine(Some(left_row), Some(right_row));
+                let keys_match = self
+                    .predicate
+                    .keys
+                    .iter()
+                    .all(|(l, r)| sql_equals(&left_row.values[*l], &right_row.values[*r]) == Some(true));
+                if keys_match && self.predicate.residual_matches(&output.columns, &combined)? {
+                    matched = true;
+                    output.right_matched[right_index] = true;
+                    output.rows.push(combined);
+                }
+            }
+            if !matched {
+                output.emit_unmatched_left(left_row);
+            }
+        }
+        Ok(output.finish(&right, &self.predicate))
+    }
+}
+
+/// Hash join: builds a hash table on the right input's join keys and probes
+/// it with each left row
+#[derive(Debug, Clone)]
+pub struct HashJoin {
+    join_type: JoinType,
+    predicate: JoinPredicate,
+}
+
+impl HashJoin {
+    pub fn new(join_type: JoinType, predicate: JoinPredicate) -> Self {
+        HashJoin { join_type, predicate }
+    }
+
+    pub fn execute(&self, left: Relation, right: Relation) -> SqlResult<Relation> {
+        // Build phase; rows with a NULL key can never match
+        let mut table: HashMap<Vec<KeyPart>, Vec<usize>> = HashMap::new();
+        for (index, row) in right.rows.iter().enumerate() {
+            if let Some(key) = join_key(row, self.predicate.keys.iter().map(|(_, r)| *r)) {
+                table.entry(key).or_default().push(index);
+            }
+        }
+
+        // Probe phase
+        let mut output = JoinOutput::new(&left, &right, self.join_type);
+        for left_row in &left.rows {
+            let mut matched = false;
+            let key = join_key(left_row, self.predicate.keys.iter().map(|(l, _)| *l));
+            if let Some(candidates) = key.and_then(|key| table.get(&key)) {
+                for &right_index in candidates {
+                    let combined = output.combine(Some(left_row), Some(&right.rows[right_index]));
+                    if self.predicate.residual_matches(&output.columns, &combined)? {
+                        matched = true;
+                        output.right_matched[right_index] = true;
+                        output.rows.push(combined);
+                    }
+                }
+            }
+            if !matched {
+                output.emit_unmatched_left(left_row);
+            }
+        }
+        Ok(output.finish(&right, &self.predicate))
+    }
+}
+
+/// Semi join: keeps the left rows with at least one right row whose keys
+/// are equal (an anti join keeps those without). Only left columns are
+/// output, and each left row at most once.
+#[derive(Debug, Clone)]
+pub struct SemiJoin {
+    /// Pairs of (left expression, right expression) that must be equal
+    keys: Vec<(Expression, Expression)>,
+    anti: bool,
+}
+
+impl SemiJoin {
+    pub fn new(keys: Vec<(Expression, Expression)>, anti: bool) -> Self {
+        SemiJoin { keys, anti }
+    }
+
+    pub fn execute(&self, left: Relation, right: Relation) -> SqlResult<Relation> {
+        // Keys containing NULL never match
+        let key = |relation: &Relation, row: &Row, right_side: bool| -> SqlResult<Option<Vec<KeyPart>>> {
+            let mut parts = Vec::with_capacity(self.keys.len());
+            for (left_expr, right_expr) in &self.keys {
+                let expr = if right_side { right_expr } else { left_expr };
+                let value = evaluate(expr, &relation.columns, row)?;
+                if value.is_null() {
+                    return Ok(None);
+                }
+                parts.push(KeyPart::from_value(&value));
+            }
+            Ok(Some(parts))
+        };
+
+        let mut right_keys = HashSet::new();
+        for row in &right.rows {
+            if let Some(key) = key(&right, row, true)? {
+                right_keys.insert(key);
+            }
+        }
+        let mut rows = Vec::new();
+        for row in &left.rows {
+            let matched = key(&left, row, false)?.is_some_and(|key| right_keys.contains(&key));
+            if matched != self.anti {
+                rows.push(row.clone());
+            }
+        }
+        Ok(Relation::new(left.columns, rows))
+    }
+}
+
+/// Accumulates joined rows and handles outer-join padding
+struct JoinOutput {
+    join_type: JoinType,
+    columns: Vec<ColumnRef>,
+    left_width: usize,
+    right_width: usize,
+    right_matched: Vec<bool>,
+    rows: Vec<Row>,
+}
+
+impl JoinOutput {
+    fn new(left: &Relation, right: &Relation, join_type: JoinType) -> Self {
+        let mut columns = left.columns.clone();
+        columns.extend(right.columns.iter().cloned());
+        JoinOutput {
+            join_type,
+            columns,
+            left_width: left.columns.len(),
+            right_width: right.columns.len(),
+            right_matched: vec![false; right.rows.len()],
+            rows: Vec::new(),
+        }
+    }
+
+    fn combine(&self, left: Option<&Row>, right: Option<&Row>) -> Row {
+        let mut values = Vec::with_capacity(self.left_width + self.right_width);
+        match left {
+            Some(row) => values.extend(row.values.iter().cloned()),
+            None => values.resize(self.left_width, Value::Null),
+        }
+        match right {
+            Some(row) => values.extend(row.values.iter().cloned()),
+            None => values.resize(self.left_width + self.right_width, Value::Null),
+        }
+        Row::new(values)
+    }
+
+    fn emit_unmatched_left(&mut self, left_row: &Row) {
+        if matches!(self.join_type, JoinType::Left | JoinType::Full) {
+            let padded = self.combine(Some(left_row), None);
+            self.rows.push(padded);
+        }
+    }
+
+    fn finish(mut self, right: &Relation, predicate: &JoinPredicate) -> Relation {
+        if matches!(self.join_type, JoinType::Right | JoinType::Full) {
+            for (index, row) in right.rows.iter().enumerate() {
+                if !self.right_matched[index] {
+                    let padded = self.combine(None, Some(row));
+                    self.rows.push(padded);
+                }
+            }
+        }
+
+        if predicate.merged.is_empty() {
+            return Relation::new(self.columns, self.rows);
+        }
+
+        // USING columns appear once, taking the right value for rows that
+        // only exist on the right side
+        let dropped: Vec<usize> = predicate.merged.iter().map(|(_, r)| self.left_width + r).collect();
+        let keep = |index: &usize| !dropped.contains(index);
+        let rows = self
+            .rows
+            .into_iter()
+            .map(|mut row| {
+                for (l, r) in &predicate.merged {
+                    if row.values[*l].is_null() {
+                        row.values[*l] = row.values[self.left_width + r].clone();
+                    }
+                }
+                Row::new(
+                    row.values
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(index, _)| keep(index))
+                        .map(|(_, value)| value)
+                        .collect(),
+                )
+            })
+            .collect();
+        let columns = self
+            .columns
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| keep(index))
+            .map(|(_, column)| column)
+            .collect();
+        Relation::new(columns, rows)
+    }
+}
+
+/// Join key for a row; `None` if any part is NULL, since NULL never matches
+fn join_key(row: &Row, indices: impl Iterator<Item = usize>) -> Option<Vec<KeyPart>> {
+    indices
+        .map(|index| Some(KeyPart::from_value(&row.values[index])).filter(|part| *part != KeyPart::Null))
+        .collect()
+}
+
+/// The AND-ed parts of a condition
+pub fn split_conjunction(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::BinaryOp { left, op: BinaryOperator::And, right } => {
+            let mut parts = split_conjunction(left);
+            parts.extend(split_conjunction(right));
+            parts
+        }
+        other => vec![other],
+    }
+}
+
+/// AND the conditions together; `None` when there are none
+pub fn conjunction(parts: Vec<Expression>) -> Option<Expression> {
+    parts.into_iter().reduce(|acc, expr| Expression::BinaryOp {
+        left: Box::new(acc),
+        op: BinaryOperator::And,
+        right: Box::new(expr),
+    })
+}
+
+/// Recognize `a = b` where one side belongs only to the left input and the
+/// other only to the right input
+fn equi_key(left: &[ColumnRef], right: &[ColumnRef], expr: &Expression) -> Option<(usize, usize)> {
+    let Expression::BinaryOp { left: a, op: BinaryOperator::Equal, right: b } = expr else {
+        return None;
+    };
+    let side = |columns: &[ColumnRef], expr: &Expression| match expr {
+        Expression::Column(name) => resolve_column(columns, None, name).ok(),
+        Expression::QualifiedColumn { table, column } => resolve_column(columns, Some(table), column).ok(),
+        _ => None,
+    };
+    // Keys are hashed as stored, so columns with another collation are
+    // left to the residual condition
+    let only = |own: &[ColumnRef], other: &[ColumnRef], expr: &Expression| {
+        side(own, expr)
+            .filter(|_| side(other, expr).is_none())
+            .filter(|&index| own[index].collation.as_ref().is_none_or(Collation::is_binary))
+    };
+
+    if let (Some(l), Some(r)) = (only(left, right, a), only(right, left, b)) {
+        return Some((l, r));
+    }
+    if let (Some(l), Some(r)) = (only(left, right, b), only(right, left, a)) {
+        return Some((l, r));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> Relation {
+        Relation::from_table(
+            "users",
+            vec!["id".to_string(), "name".to_string()],
+            vec![
+                Row::new(vec![Value::Integer(1), Value::Text("Alice".to_string())]),
+                Row::new(vec![Value::Integer(2), Value::Text("Bob".to_string())]),
+                Row::new(vec![Value::Integer(3), Value::Text("Carol".to_string())]),
+            ],
+        )
+    }
+
+    fn orders() -> Relation {
+        Relation::from_table(
+            "orders",
+            vec!["id".to_string(), "user_id".to_string(), "total".to_string()],
+            vec![
+                Row::new(vec![Value::Integer(10), Value::Integer(1), Value::Integer(50)]),
+                Row::new(vec![Value::Integer(11), Value::Integer(1), Value::Integer(20)]),
+                Row::new(vec![Value::Integer(12), Value::Integer(2), Value::Integer(70)]),
+                Row::new(vec![Value::Integer(13), Value::Null, Value::Integer(5)]),
+            ],
+        )
+    }
+
+    fn on_user_id() -> JoinConstraint {
+        JoinConstraint::On(Expression::BinaryOp {
+            left: Box::new(Expression::QualifiedColumn {
+                table: "users".to_string(),
+                column: "id".to_string(),
+            }),
+            op: BinaryOperator::Equal,
+            right: Box::new(Expression::Column("user_id".to_string())),
+        })
+    }
+
+    #[test]
+    fn test_predicate_analysis() {
+        let predicate = JoinPredicate::analyze(&users().columns, &orders().columns, &on_user_id()).unwrap();
+        assert_eq!(predicate.keys, vec![(0, 1)]);
+        assert!(predicate.residual.is_none());
+        assert!(matches!(predicate.strategy(), JoinStrategy::HashJoin));
+
+        // Ambiguous unqualified `id` cannot be used as a key
+        let ambiguous = JoinConstraint::On(Expression::BinaryOp {
+            left: Box::new(Expression::Column("id".to_string())),
+            op: BinaryOperator::Equal,
+            right: Box::new(Expression::Column("user_id".to_string())),
+        });
+        let predicate = JoinPredicate::analyze(&users().columns, &orders().columns, &ambiguous).unwrap();
+        assert!(predicate.keys.is_empty());
+        assert!(matches!(predicate.strategy(), JoinStrategy::NestedLoop));
+    }
+
+    #[test]
+    fn test_hash_and_nested_loop_agree() {
+        for join_type in [JoinType::Inner, JoinType::Left, JoinType::Right, JoinType::Full] {
+            let predicate = JoinPredicate::analyze(&users().columns, &orders().columns, &on_user_id()).unwrap();
+            let hashed = HashJoin::new(join_type, predicate.clone()).execute(users(), orders()).unwrap();
+            let looped = NestedLoopJoin::new(join_type, predicate).execute(users(), orders()).unwrap();
+            assert_eq!(hashed, looped);
+        }
+    }
+
+    #[test]
+    fn test_outer_joins_pad_with_nulls() {
+        let inner = execute_join(users(), orders(), JoinType::Inner, &on_user_id()).unwrap();
+        assert_eq!(inner.rows.len(), 3);
+
+        let left = execute_join(users(), orders(), JoinType::Left, &on_user_id()).unwrap();
+        assert_eq!(left.rows.len(), 4);
+        let carol = left.rows.iter().find(|r| r.values[1] == Value::Text("Carol".to_string())).unwrap();
+        assert_eq!(carol.values[2..], [Value::Null, Value::Null, Value::Null]);
+
+        let full = execute_join(users(), orders(), JoinType::Full, &on_user_id()).unwrap();
+        assert_eq!(full.rows.len(), 5);
+    }
+
+    #[test]
+    fn test_cross_and_using() {
+        let cross = execute_join(users(), orders(), JoinType::Cross, &JoinConstraint::None).unwrap();
+        assert_eq!(cross.rows.len(), 12);
+        assert!(execute_join(users(), orders(), JoinType::Cross, &on_user_id()).is_err());
+
+        let renamed = Relation::from_table(
+            "profiles",
+            vec!["id".to_string(), "bio".to_string()],
+            vec![Row::new(vec![Value::Integer(2), Value::Text("hi".to_string())])],
+        );
+        let using = execute_join(users(), renamed, JoinType::Inner, &JoinConstraint::Using(vec!["id".to_string()])).unwrap();
+        assert_eq!(using.columns.len(), 3);
+        assert_eq!(
+            using.rows,
+            vec![Row::new(vec![Value::Integer(2), Value::Text("Bob".to_string()), Value::Text("hi".to_string())])]
+        );
+    }
+}
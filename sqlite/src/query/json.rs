@@ -0,0 +1,296 @@
+//! JSON functions
+//!
+//! As in SQLite, JSON documents are stored as TEXT and parsed when a
+//! function reads them. Paths use SQLite's syntax: `$` is the whole
+//! document, `.key` (or `."key"`) an object member, `[N]` an array element
+//! and `[#-N]` the N-th element from the end.
+
+use crate::error::{SqlError, SqlResult};
+use crate::types::Value;
+use serde_json::Value as JsonValue;
+
+/// One step of a JSON path
+#[derive(Debug, Clone, PartialEq)]
+enum PathStep {
+    Key(String),
+    Index(usize),
+    /// `[#-N]`; `[#]` (N = 0) is one past the last element, where
+    /// `json_set` appends
+    FromEnd(usize),
+}
+
+/// A parsed JSON path such as `$.tags[0]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    steps: Vec<PathStep>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> SqlResult<Self> {
+        let bad_path = || SqlError::runtime_error(format!("bad JSON path: '{}'", path));
+        let mut rest = path.strip_prefix('$').ok_or_else(bad_path)?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(member) = rest.strip_prefix('.') {
+                let (key, after) = match member.strip_prefix('"') {
+                    Some(quoted) => {
+                        let end = quoted.find('"').ok_or_else(bad_path)?;
+                        (&quoted[..end], &quoted[end + 1..])
+                    }
+                    None => {
+                        let end = member.find(['.', '[']).unwrap_or(member.len());
+                        (&member[..end], &member[end..])
+                    }
+                };
+                if key.is_empty() {
+                    return Err(bad_path());
+                }
+                steps.push(PathStep::Key(key.to_string()));
+                rest = after;
+            } else if let Some(element) = rest.strip_prefix('[') {
+                let end = element.find(']').ok_or_else(bad_path)?;
+                let index = &element[..end];
+                let step = match index.strip_prefix('#') {
+                    Some("") => PathStep::FromEnd(0),
+                    Some(back) => PathStep::FromEnd(
+                        back.strip_prefix('-').and_then(|n| n.parse().ok()).ok_or_else(bad_path)?,
+                    ),
+                    None => PathStep::Index(index.parse().map_err(|_| bad_path())?),
+                };
+                steps.push(step);
+                rest = &element[end + 1..];
+            } else {
+                return Err(bad_path());
+            }
+        }
+        Ok(JsonPath { steps })
+    }
+
+    /// The path of the `->`/`->>` right operand: a path, an object key or
+    /// an array index (negative counts from the end)
+    fn from_operand(operand: &Value) -> SqlResult<Self> {
+        let step = match operand {
+            Value::Text(path) if path.starts_with('$') => return JsonPath::parse(path),
+            Value::Text(key) => PathStep::Key(key.clone()),
+            Value::Integer(index) if *index >= 0 => PathStep::Index(*index as usize),
+            Value::Integer(index) => PathStep::FromEnd(index.unsigned_abs() as usize),
+            other => {
+                return Err(SqlError::type_error(format!(
+                    "JSON path must be TEXT or INTEGER, got {}",
+                    other.data_type()
+                )))
+            }
+        };
+        Ok(JsonPath { steps: vec![step] })
+    }
+
+    /// The element at this path, if there is one
+    pub fn lookup<'a>(&self, document: &'a JsonValue) -> Option<&'a JsonValue> {
+        self.steps.iter().try_fold(document, |node, step| match (step, node) {
+            (PathStep::Key(key), JsonValue::Object(members)) => members.get(key),
+            (PathStep::Index(index), JsonValue::Array(elements)) => elements.get(*index),
+            (PathStep::FromEnd(back), JsonValue::Array(elements)) => {
+                elements.len().checked_sub(*back).and_then(|index| elements.get(index))
+            }
+            _ => None,
+        })
+    }
+
+    /// Overwrite the element at this path, creating it (and missing parent
+    /// objects) when the parent exists or can be created; a path through a
+    /// value of the wrong kind leaves the document unchanged
+    pub fn set(&self, document: &mut JsonValue, value: JsonValue) {
+        let mut node = document;
+        for (depth, step) in self.steps.iter().enumerate() {
+            if node.is_null() && matches!(step, PathStep::Key(_)) {
+                *node = JsonValue::Object(Default::default());
+            }
+            let last = depth + 1 == self.steps.len();
+            let child = match (step, node) {
+                (PathStep::Key(key), JsonValue::Object(members)) => {
+                    if !members.contains_key(key) {
+                        if !last && !matches!(self.steps[depth + 1], PathStep::Key(_)) {
+                            return;
+                        }
+                        members.insert(key.clone(), JsonValue::Null);
+                    }
+                    members.get_mut(key)
+                }
+                (PathStep::Index(index), JsonValue::Array(elements)) => element_or_append(elements, *index, last),
+                (PathStep::FromEnd(back), JsonValue::Array(elements)) => match elements.len().checked_sub(*back) {
+                    Some(index) => element_or_append(elements, index, last),
+                    None => None,
+                },
+                _ => None,
+            };
+            match child {
+                Some(child) => node = child,
+                None => return,
+            }
+        }
+        *node = value;
+    }
+}
+
+/// The array element at `index`, or a new last element when `index` is
+/// the length of the array and the path ends there
+fn element_or_append(elements: &mut Vec<JsonValue>, index: usize, last: bool) -> Option<&mut JsonValue> {
+    if index == elements.len() && last {
+        elements.push(JsonValue::Null);
+    }
+    elements.get_mut(index)
+}
+
+/// Parse a JSON argument; `None` for NULL
+pub fn parse_document(value: &Value) -> SqlResult<Option<JsonValue>> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Text(text) => serde_json::from_str(text)
+            .map(Some)
+            .map_err(|_| SqlError::runtime_error("malformed JSON")),
+        other => to_json(other).map(Some),
+    }
+}
+
+/// The JSON form of an SQL value; TEXT becomes a JSON string
+pub fn to_json(value: &Value) -> SqlResult<JsonValue> {
+    Ok(match value {
+        Value::Null => JsonValue::Null,
+        Value::Integer(i) => JsonValue::from(*i),
+        Value::Real(r) => serde_json::Number::from_f64(*r).map_or(JsonValue::Null, JsonValue::Number),
+        Value::Text(s) => JsonValue::String(s.clone()),
+        Value::Boolean(b) => JsonValue::Bool(*b),
+        Value::Blob(_) => return Err(SqlError::type_error("JSON cannot hold BLOB values")),
+    })
+}
+
+/// The SQL form of a JSON element: scalars become the matching SQL value
+/// (`true`/`false` become 1/0) and arrays and objects their JSON text
+pub fn to_sql(json: &JsonValue) -> Value {
+    match json {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(b) => Value::Integer(*b as i64),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        JsonValue::String(s) => Value::Text(s.clone()),
+        composite => Value::Text(composite.to_string()),
+    }
+}
+
+/// `json(x)`: `x` checked and minified
+pub fn json(document: &Value) -> SqlResult<Value> {
+    Ok(parse_document(document)?.map_or(Value::Null, |json| Value::Text(json.to_string())))
+}
+
+/// `json_extract(x, path, ...)`: the element at one path as an SQL value,
+/// or the elements at several paths as a JSON array
+pub fn json_extract(document: &Value, paths: &[Value]) -> SqlResult<Value> {
+    let Some(json) = parse_document(document)? else {
+        return Ok(Value::Null);
+    };
+    let mut elements = Vec::with_capacity(paths.len());
+    for path in paths {
+        let Value::Text(path) = path else {
+            return Ok(Value::Null);
+        };
+        elements.push(JsonPath::parse(path)?.lookup(&json).cloned());
+    }
+    Ok(match elements.as_slice() {
+        [element] => element.as_ref().map_or(Value::Null, to_sql),
+        _ => Value::Text(JsonValue::Array(elements.into_iter().map(Option::unwrap_or_default).collect()).to_string()),
+    })
+}
+
+/// `json_set(x, path, value, ...)`: `x` with each value written at its
+/// path
+pub fn json_set(document: &Value, assignments: &[Value]) -> SqlResult<Value> {
+    if !assignments.len().is_multiple_of(2) {
+        return Err(SqlError::runtime_error("wrong number of arguments to function json_set()"));
+    }
+    let Some(mut json) = parse_document(document)? else {
+        return Ok(Value::Null);
+    };
+    for pair in assignments.chunks(2) {
+        let Value::Text(path) = &pair[0] else {
+            return Err(SqlError::runtime_error(format!("bad JSON path: '{}'", pair[0])));
+        };
+        JsonPath::parse(path)?.set(&mut json, to_json(&pair[1])?);
+    }
+    Ok(Value::Text(json.to_string()))
+}
+
+/// `json_array_length(x[, path])`: the number of elements of the array,
+/// 0 for other JSON values and NULL when the path does not exist
+pub fn json_array_length(document: &Value, path: Option<&Value>) -> SqlResult<Value> {
+    let Some(json) = parse_document(document)? else {
+        return Ok(Value::Null);
+    };
+    let element = match path {
+        Some(Value::Text(path)) => JsonPath::parse(path)?.lookup(&json),
+        Some(_) => return Ok(Value::Null),
+        None => Some(&json),
+    };
+    Ok(match element {
+        Some(JsonValue::Array(elements)) => Value::Integer(elements.len() as i64),
+        Some(_) => Value::Integer(0),
+        None => Value::Null,
+    })
+}
+
+/// `x -> path` (`as_text` false) returns the element as JSON text, and
+/// `x ->> path` as an SQL value
+pub fn arrow(document: &Value, path: &Value, as_text: bool) -> SqlResult<Value> {
+    let Some(json) = parse_document(document)? else {
+        return Ok(Value::Null);
+    };
+    if path.is_null() {
+        return Ok(Value::Null);
+    }
+    Ok(match JsonPath::from_operand(path)?.lookup(&json) {
+        Some(element) if as_text => to_sql(element),
+        Some(element) => Value::Text(element.to_string()),
+        None => Value::Null,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    #[test]
+    fn test_paths_and_functions() {
+        let doc = text(r#"{"name": "ream", "tags": ["db", "rust"], "meta": {"stars": 5, "public": true}}"#);
+        assert_eq!(json_extract(&doc, &[text("$.name")]).unwrap(), text("ream"));
+        assert_eq!(json_extract(&doc, &[text("$.tags[#-1]")]).unwrap(), text("rust"));
+        assert_eq!(json_extract(&doc, &[text("$.meta.public")]).unwrap(), Value::Integer(1));
+        assert_eq!(json_extract(&doc, &[text("$.missing")]).unwrap(), Value::Null);
+        assert_eq!(json_extract(&doc, &[text("$.meta.stars"), text("$.tags[0]")]).unwrap(), text(r#"[5,"db"]"#));
+        assert_eq!(json_array_length(&doc, Some(&text("$.tags"))).unwrap(), Value::Integer(2));
+        assert_eq!(json_array_length(&doc, None).unwrap(), Value::Integer(0));
+
+        let updated = json_set(
+            &doc,
+            &[text("$.meta.stars"), Value::Integer(6), text("$.tags[#]"), text("sql"), text("$.owner.id"), Value::Integer(1)],
+        )
+        .unwrap();
+        assert_eq!(
+            updated,
+            text(r#"{"name":"ream","tags":["db","rust","sql"],"meta":{"stars":6,"public":true},"owner":{"id":1}}"#)
+        );
+
+        assert_eq!(arrow(&doc, &text("meta"), false).unwrap(), text(r#"{"stars":5,"public":true}"#));
+        assert_eq!(arrow(&doc, &text("$.name"), false).unwrap(), text(r#""ream""#));
+        assert_eq!(arrow(&text("[1, 2, 3]"), &Value::Integer(-1), true).unwrap(), Value::Integer(3));
+
+        assert!(json(&text("{not json")).is_err());
+        for path in ["name", "$.", "$[x]", "$[#1]", "$.\"open"] {
+            assert!(JsonPath::parse(path).is_err(), "{}", path);
+        }
+    }
+}
@@ -2,15 +2,36 @@ pub mod planner;
 pub mod executor;
 pub mod plan;
 pub mod result;
+pub mod relation;
+pub mod join;
+pub mod aggregate;
+pub mod sort;
+pub mod index;
+pub mod subquery;
+pub mod json;
+pub mod profile;
+pub mod stats;
+pub mod catalog;
 
 pub use planner::{QueryPlanner, QueryPlannerCoalgebra};
-pub use executor::QueryExecutor;
+pub use executor::{QueryExecutor, TableEdit};
 pub use plan::*;
 pub use result::QueryResult;
+pub use relation::{ColumnRef, Relation};
+pub use join::{HashJoin, JoinPredicate, NestedLoopJoin, SemiJoin};
+pub use aggregate::{HashAggregate, HashDistinct};
+pub use sort::{ExternalSort, SortConfig, SortStats};
+pub use index::{IndexCatalog, IndexInfo, IndexLookup, SecondaryIndex};
+pub use stats::{ColumnStats, CostModel, StatsCatalog, TableStats};
+pub use catalog::is_catalog_table;
 
 use crate::error::{SqlError, SqlResult};
-use crate::parser::ast::Statement;
-use crate::types::{Row, Statistics};
+use crate::fdw::ForeignScan;
+use crate::parser::ast::{ExplainMode, Statement};
+use crate::schema::SchemaRegistry;
+use crate::types::{Row, RowChange, Statistics};
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
 /// Query processing pipeline
 #[derive(Debug)]
@@ -21,12 +42,78 @@ pub struct QueryProcessor {
 
 impl QueryProcessor {
     pub fn new() -> Self {
+        let executor = QueryExecutor::new();
         QueryProcessor {
-            planner: QueryPlanner::new(),
-            executor: QueryExecutor::new(),
+            planner: Self::planner_for(&executor),
+            executor,
         }
     }
 
+    /// A processor with no tables whose catalog tables list the schemas
+    /// of `registry`
+    pub fn with_registry(registry: Arc<RwLock<SchemaRegistry>>) -> Self {
+        let executor = QueryExecutor::with_registry(registry);
+        QueryProcessor {
+            planner: Self::planner_for(&executor),
+            executor,
+        }
+    }
+
+    /// A processor over a copy of this one's tables as they are now, which
+    /// later writes to this one do not change
+    pub async fn snapshot(&self) -> Self {
+        let executor = self.executor.snapshot().await;
+        QueryProcessor {
+            planner: Self::planner_for(&executor),
+            executor,
+        }
+    }
+
+    /// A planner over the catalogs of `executor`
+    fn planner_for(executor: &QueryExecutor) -> QueryPlanner {
+        QueryPlanner::with_catalog(
+            executor.indexes(),
+            executor.schemas(),
+            executor.statistics(),
+            executor.foreign_servers(),
+        )
+    }
+
+    /// Start or stop recording the rows that INSERT, UPDATE and DELETE
+    /// change
+    pub fn capture_changes(&self, capture: bool) {
+        self.executor.capture_changes(capture);
+    }
+
+    /// The changes recorded since they were last taken
+    pub fn take_changes(&self) -> Vec<RowChange> {
+        self.executor.take_changes()
+    }
+
+    /// Start or stop recording how INSERT, UPDATE and DELETE change the
+    /// rows of each table
+    pub fn capture_edits(&self, capture: bool) {
+        self.executor.capture_edits(capture);
+    }
+
+    /// The edits recorded since they were last taken
+    pub fn take_edits(&self) -> Vec<(String, TableEdit)> {
+        self.executor.take_edits()
+    }
+
+    /// Fail, changing nothing, if the unique indexes would not take a
+    /// commit's edits, whose positions count the rows as they were before
+    pub async fn check_edits(&self, edits: &[(String, TableEdit)]) -> SqlResult<()> {
+        self.executor.check_edits(edits).await
+    }
+
+    /// Make row changes without checking them: those another processor's
+    /// writes made, to keep a copy of its tables in step with it, or those
+    /// of a commit that [`check_edits`](Self::check_edits) accepted
+    pub async fn apply_edits(&self, edits: &[(String, TableEdit)]) {
+        self.executor.apply_edits(edits).await;
+    }
+
     /// Process a SQL statement end-to-end
     pub async fn process_statement(&self, statement: Statement) -> SqlResult<QueryResult> {
         // Plan the query
@@ -36,6 +123,11 @@ impl QueryProcessor {
         self.executor.execute_plan(plan).await
     }
 
+    /// Tables the plan of a statement reads or writes
+    pub async fn referenced_tables(&self, statement: &Statement) -> SqlResult<Vec<String>> {
+        Ok(self.planner.plan_statement(statement.clone()).await?.referenced_tables())
+    }
+
     /// Optimize and execute a query plan
     pub async fn optimize_and_execute(&self, plan: QueryPlan) -> SqlResult<QueryResult> {
         // Optimize the plan
@@ -45,6 +137,102 @@ impl QueryProcessor {
         self.executor.execute_plan(optimized_plan).await
     }
 
+    /// Register an in-memory table for SELECT statements to read
+    pub async fn register_table(&self, name: impl Into<String>, columns: Vec<String>, rows: Vec<Row>) {
+        self.executor.register_table(name, columns, rows).await;
+    }
+
+    /// Install a table with its declared schema, e.g. one loaded from disk
+    pub async fn restore_table(&self, name: impl Into<String>, schema: TableSchema, rows: Vec<Row>) {
+        self.executor.restore_table(name, schema, rows).await;
+    }
+
+    /// Table statistics gathered by ANALYZE, shared with the planner
+    pub fn statistics(&self) -> StatsCatalog {
+        self.executor.statistics()
+    }
+
+    /// Re-create an index loaded from disk over its (already restored) table
+    pub async fn restore_index(&self, index: IndexInfo) -> SqlResult<()> {
+        self.executor.execute_plan(QueryPlan::CreateIndex { index }).await?;
+        Ok(())
+    }
+
+    /// Replace the rows of a table, rejecting them if they break a unique
+    /// index
+    pub async fn replace_table(&self, name: &str, rows: Vec<Row>) -> SqlResult<()> {
+        self.executor.replace_table(name, rows).await
+    }
+
+    /// Schema and rows of a table, if it exists
+    pub async fn table_snapshot(&self, name: &str) -> Option<(TableSchema, Vec<Row>)> {
+        self.executor.table_snapshot(name).await
+    }
+
+    /// Declared schema of a table, if it exists
+    pub async fn table_schema(&self, name: &str) -> Option<TableSchema> {
+        self.executor.schemas().read().await.get(name).cloned()
+    }
+
+    /// Names of every table, sorted
+    pub async fn table_names(&self) -> Vec<String> {
+        self.executor.table_names().await
+    }
+
+    /// Indexes on a table, by name, including those made for UNIQUE and
+    /// PRIMARY KEY constraints
+    pub async fn indexes_on(&self, table: &str) -> Vec<IndexInfo> {
+        self.executor.indexes().indexes_on(table).await
+    }
+
+    /// Every table with its schema and rows, and the indexes made by
+    /// CREATE INDEX, consistent with each other
+    pub async fn database_snapshot(&self) -> (Vec<(String, TableSchema, Vec<Row>)>, Vec<IndexInfo>) {
+        self.executor.database_snapshot().await
+    }
+
+    /// Tables whose rows a statement can change; a DELETE also changes the
+    /// tables its ON DELETE actions reach
+    pub async fn written_tables(&self, statement: &Statement) -> Vec<String> {
+        match statement {
+            Statement::Insert(insert) => vec![insert.table.clone()],
+            Statement::Update(update) => vec![update.table.clone()],
+            Statement::Delete(delete) => {
+                let mut tables = vec![delete.table.clone()];
+                tables.extend(self.dependent_tables(&delete.table).await);
+                tables
+            }
+            Statement::CreateTable(create) => vec![create.table_name.clone()],
+            Statement::CreateVirtualTable(create) => vec![create.table_name.clone()],
+            Statement::CreateForeignTable(create) => vec![create.table_name.clone()],
+            Statement::DropTable(drop) => vec![drop.table_name.clone()],
+            Statement::Explain(explain) if explain.mode == ExplainMode::Analyze => {
+                Box::pin(self.written_tables(&explain.statement)).await
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Tables that deleting rows of `table` can change through foreign keys
+    pub async fn dependent_tables(&self, table: &str) -> Vec<String> {
+        self.executor.dependent_tables(table).await
+    }
+
+    /// Schemas defined through the Rust API; the catalog tables list them
+    pub fn schema_registry(&self) -> Arc<RwLock<SchemaRegistry>> {
+        self.executor.schema_registry()
+    }
+
+    /// Read the foreign tables declared with `SERVER name` through `scan`
+    pub async fn register_foreign_server(&self, name: impl Into<String>, scan: Arc<dyn ForeignScan>) {
+        self.executor.foreign_servers().register(name, scan).await;
+    }
+
+    /// Forget a table and its indexes
+    pub async fn remove_table(&self, name: &str) {
+        self.executor.remove_table(name).await;
+    }
+
     /// Get query statistics
     pub fn get_statistics(&self) -> &Statistics {
         self.planner.get_statistics()
@@ -73,6 +261,7 @@ mod tests {
         let processor = QueryProcessor::new();
         
         let statement = Statement::Select(SelectStatement {
+            distinct: false,
             columns: vec![SelectColumn::Wildcard],
             from: Some(FromClause {
                 table: "users".to_string(),
@@ -93,6 +282,9 @@ mod tests {
     #[tokio::test]
     async fn test_query_processor_insert() {
         let processor = QueryProcessor::new();
+        processor
+            .register_table("users", vec!["name".to_string(), "age".to_string()], vec![])
+            .await;
         
         let statement = Statement::Insert(InsertStatement {
             table: "users".to_string(),
@@ -106,4 +298,885 @@ mod tests {
         let result = processor.process_statement(statement).await;
         assert!(result.is_ok());
     }
+
+    async fn join_fixture() -> QueryProcessor {
+        let processor = QueryProcessor::new();
+        let text = |s: &str| Value::Text(s.to_string());
+        processor
+            .register_table(
+                "users",
+                vec!["id".to_string(), "name".to_string()],
+                vec![
+                    Row::new(vec![Value::Integer(1), text("Alice")]),
+                    Row::new(vec![Value::Integer(2), text("Bob")]),
+                    Row::new(vec![Value::Integer(3), text("Carol")]),
+                ],
+            )
+            .await;
+        processor
+            .register_table(
+                "orders",
+                vec!["id".to_string(), "user_id".to_string(), "total".to_string()],
+                vec![
+                    Row::new(vec![Value::Integer(10), Value::Integer(1), Value::Integer(50)]),
+                    Row::new(vec![Value::Integer(11), Value::Integer(1), Value::Integer(20)]),
+                    Row::new(vec![Value::Integer(12), Value::Integer(2), Value::Integer(70)]),
+                ],
+            )
+            .await;
+        processor
+            .register_table(
+                "regions",
+                vec!["region".to_string()],
+                vec![Row::new(vec![text("eu")]), Row::new(vec![text("us")])],
+            )
+            .await;
+        processor
+    }
+
+    async fn run(processor: &QueryProcessor, sql: &str) -> (Vec<String>, Vec<Row>) {
+        let statement = crate::parser::parse_sql(sql).unwrap();
+        match processor.process_statement(statement).await.unwrap() {
+            QueryResult::Select { columns, rows } => (columns, rows),
+            other => panic!("Expected SELECT result, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inner_and_left_join() {
+        let processor = join_fixture().await;
+
+        let (columns, rows) = run(
+            &processor,
+            "SELECT * FROM users AS u INNER JOIN orders AS o ON u.id = o.user_id",
+        )
+        .await;
+        assert_eq!(columns, vec!["id", "name", "id", "user_id", "total"]);
+        assert_eq!(rows.len(), 3);
+
+        let (_, rows) = run(
+            &processor,
+            "SELECT * FROM users LEFT JOIN orders ON users.id = orders.user_id WHERE users.name = 'Carol'",
+        )
+        .await;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].values[1], Value::Text("Carol".to_string()));
+        assert_eq!(rows[0].values[4], Value::Null);
+
+        // A condition on the table LEFT JOIN pads with NULLs sees the padding
+        let (_, rows) = run(
+            &processor,
+            "SELECT users.name FROM users LEFT JOIN orders ON users.id = orders.user_id WHERE orders.total IS NULL",
+        )
+        .await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Text("Carol".to_string())])]);
+
+        let (_, rows) = run(
+            &processor,
+            "SELECT * FROM users JOIN orders ON users.id = orders.user_id AND orders.total > 30",
+        )
+        .await;
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cross_and_using_join() {
+        let processor = join_fixture().await;
+
+        let (_, rows) = run(&processor, "SELECT * FROM users CROSS JOIN regions").await;
+        assert_eq!(rows.len(), 6);
+
+        let (columns, rows) = run(
+            &processor,
+            "SELECT * FROM users AS a JOIN users AS b USING (id) WHERE a.id = 2",
+        )
+        .await;
+        assert_eq!(columns, vec!["id", "name", "name"]);
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![
+                Value::Integer(2),
+                Value::Text("Bob".to_string()),
+                Value::Text("Bob".to_string()),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ambiguous_join_column_is_rejected() {
+        let processor = join_fixture().await;
+        let statement = crate::parser::parse_sql(
+            "SELECT * FROM users JOIN orders ON users.id = orders.user_id WHERE id = 1",
+        )
+        .unwrap();
+        assert!(processor.process_statement(statement).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_group_by_with_having() {
+        let processor = join_fixture().await;
+
+        let (columns, rows) = run(
+            &processor,
+            "SELECT user_id, COUNT(*), SUM(total) AS spent FROM orders GROUP BY user_id HAVING SUM(total) > 60",
+        )
+        .await;
+        assert_eq!(columns, vec!["user_id", "COUNT(*)", "spent"]);
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![Value::Integer(1), Value::Integer(2), Value::Integer(70)]),
+                Row::new(vec![Value::Integer(2), Value::Integer(1), Value::Integer(70)]),
+            ]
+        );
+
+        let (_, rows) = run(
+            &processor,
+            "SELECT u.name, MAX(o.total) FROM users AS u LEFT JOIN orders AS o ON u.id = o.user_id GROUP BY u.name",
+        )
+        .await;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2].values, vec![Value::Text("Carol".to_string()), Value::Null]);
+    }
+
+    #[tokio::test]
+    async fn test_aggregates_without_group_by() {
+        let processor = join_fixture().await;
+
+        let (columns, rows) = run(
+            &processor,
+            "SELECT COUNT(*), AVG(total), MIN(total) FROM orders WHERE total > 1000",
+        )
+        .await;
+        assert_eq!(columns, vec!["COUNT(*)", "AVG(total)", "MIN(total)"]);
+        assert_eq!(rows, vec![Row::new(vec![Value::Integer(0), Value::Null, Value::Null])]);
+
+        let (_, rows) = run(&processor, "SELECT COUNT(*) + 1 FROM orders").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Integer(4)])]);
+    }
+
+    #[tokio::test]
+    async fn test_select_distinct() {
+        let processor = join_fixture().await;
+
+        let (columns, rows) = run(&processor, "SELECT DISTINCT user_id FROM orders").await;
+        assert_eq!(columns, vec!["user_id"]);
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![Value::Integer(1)]), Row::new(vec![Value::Integer(2)])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_order_by_and_limit() {
+        let processor = join_fixture().await;
+
+        let (_, rows) = run(
+            &processor,
+            "SELECT id, total FROM orders WHERE total > 10 OR id = 0 ORDER BY user_id DESC, total",
+        )
+        .await;
+        let ids: Vec<_> = rows.iter().map(|row| row.values[0].clone()).collect();
+        assert_eq!(ids, vec![Value::Integer(12), Value::Integer(11), Value::Integer(10)]);
+
+        let (_, rows) = run(&processor, "SELECT name FROM users ORDER BY id DESC LIMIT 1 OFFSET 1").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Text("Bob".to_string())])]);
+
+        let (_, rows) = run(&processor, "SELECT name FROM users ORDER BY name LIMIT 1, 5").await;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].values[0], Value::Text("Bob".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_order_by_alias_position_and_aggregate() {
+        let processor = join_fixture().await;
+
+        let (_, rows) = run(&processor, "SELECT id, total * 2 AS doubled FROM orders ORDER BY doubled").await;
+        assert_eq!(rows[0].values, vec![Value::Integer(11), Value::Integer(40)]);
+
+        let (_, rows) = run(&processor, "SELECT id, total FROM orders ORDER BY 2 DESC").await;
+        assert_eq!(rows[0].values[0], Value::Integer(12));
+
+        let (_, rows) = run(
+            &processor,
+            "SELECT user_id FROM orders GROUP BY user_id ORDER BY SUM(total) DESC, user_id DESC",
+        )
+        .await;
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![Value::Integer(2)]), Row::new(vec![Value::Integer(1)])]
+        );
+
+        let (_, rows) = run(&processor, "SELECT DISTINCT user_id FROM orders ORDER BY user_id DESC").await;
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![Value::Integer(2)]), Row::new(vec![Value::Integer(1)])]
+        );
+    }
+
+    async fn explain(processor: &QueryProcessor, sql: &str) -> Vec<String> {
+        let (columns, rows) = run(processor, &format!("EXPLAIN QUERY PLAN {}", sql)).await;
+        assert_eq!(columns, vec!["id", "parent", "detail"]);
+        rows.into_iter()
+            .map(|row| match &row.values[2] {
+                Value::Text(detail) => detail.clone(),
+                other => panic!("Expected text detail, got {:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_index_scan_matches_full_scan() {
+        let processor = join_fixture().await;
+        let query = "SELECT id, total FROM orders WHERE user_id = 1 AND total > 25";
+        let (_, scanned) = run(&processor, query).await;
+        assert!(explain(&processor, query).await.iter().any(|line| line.starts_with("SCAN orders")));
+
+        let create = crate::parser::parse_sql("CREATE INDEX idx_orders_user ON orders (user_id, total)").unwrap();
+        processor.process_statement(create).await.unwrap();
+        let plan = explain(&processor, query).await;
+        assert!(
+            plan.iter()
+                .any(|line| line == "SEARCH orders USING INDEX idx_orders_user (user_id=? AND total>?)"),
+            "{:?}",
+            plan
+        );
+        let (_, indexed) = run(&processor, query).await;
+        assert_eq!(indexed, scanned);
+        assert_eq!(indexed, vec![Row::new(vec![Value::Integer(10), Value::Integer(50)])]);
+
+        let covered = "SELECT total FROM orders WHERE user_id >= 1 AND user_id <= 2";
+        let plan = explain(&processor, covered).await;
+        assert!(plan.iter().any(|line| line.contains("USING COVERING INDEX idx_orders_user")), "{:?}", plan);
+        let (_, rows) = run(&processor, covered).await;
+        assert_eq!(rows.len(), 3);
+
+        let drop = crate::parser::parse_sql("DROP INDEX idx_orders_user").unwrap();
+        processor.process_statement(drop).await.unwrap();
+        assert!(explain(&processor, query).await.iter().any(|line| line.starts_with("SCAN orders")));
+    }
+
+    #[tokio::test]
+    async fn test_explain_and_explain_analyze() {
+        let processor = join_fixture().await;
+        let query = "SELECT u.name, o.total FROM users AS u INNER JOIN orders AS o ON u.id = o.user_id \
+                     WHERE o.total > 30 ORDER BY o.total DESC LIMIT 1";
+        let column = |row: &Row, name: &str, columns: &[String]| {
+            row.values[columns.iter().position(|column| column == name).unwrap()].clone()
+        };
+
+        let (columns, plan) = run(&processor, &format!("EXPLAIN {}", query)).await;
+        assert_eq!(columns, vec!["id", "parent", "operator", "detail", "est_cost", "est_rows"]);
+        let operators: Vec<Value> = plan.iter().map(|row| column(row, "operator", &columns)).collect();
+        for operator in ["LIMIT", "TOP-N SORT", "HASH JOIN", "TABLE SCAN"] {
+            assert!(operators.contains(&Value::Text(operator.to_string())), "{:?}", operators);
+        }
+        assert!(plan.iter().all(|row| matches!(column(row, "est_cost", &columns), Value::Real(cost) if cost > 0.0)));
+        // The WHERE condition on orders filters its scan, below the join
+        let operator_row = |operator: &str| {
+            plan.iter().find(|row| column(row, "operator", &columns) == Value::Text(operator.to_string())).unwrap()
+        };
+        assert_eq!(column(operator_row("FILTER"), "parent", &columns), column(operator_row("HASH JOIN"), "id", &columns));
+
+        let (columns, analyzed) = run(&processor, &format!("EXPLAIN ANALYZE {}", query)).await;
+        assert_eq!(columns.len(), 8);
+        assert_eq!(analyzed.len(), plan.len());
+        let actual = |operator: &str| -> Vec<Value> {
+            analyzed
+                .iter()
+                .filter(|row| column(row, "operator", &columns) == Value::Text(operator.to_string()))
+                .map(|row| column(row, "actual_rows", &columns))
+                .collect()
+        };
+        assert_eq!(actual("LIMIT"), vec![Value::Integer(1)]);
+        assert_eq!(actual("TOP-N SORT"), vec![Value::Integer(1)]);
+        assert_eq!(actual("HASH JOIN"), vec![Value::Integer(2)]);
+        assert_eq!(actual("TABLE SCAN"), vec![Value::Integer(3), Value::Integer(3)]);
+        assert!(analyzed
+            .iter()
+            .all(|row| matches!(column(row, "time_ms", &columns), Value::Real(ms) if ms >= 0.0)));
+
+        // Subqueries run inside an operator and do not shift the ids
+        let nested = "SELECT name FROM users WHERE id IN (SELECT user_id FROM orders WHERE total > 60)";
+        let (columns, analyzed) = run(&processor, &format!("EXPLAIN ANALYZE {}", nested)).await;
+        assert_eq!(column(&analyzed[0], "actual_rows", &columns), Value::Integer(1));
+
+        // The statement really runs
+        let delete = crate::parser::parse_sql("EXPLAIN ANALYZE DELETE FROM orders WHERE total < 30").unwrap();
+        let QueryResult::Select { columns, rows } = processor.process_statement(delete).await.unwrap() else {
+            panic!("Expected EXPLAIN ANALYZE rows");
+        };
+        assert_eq!(column(&rows[0], "actual_rows", &columns), Value::Integer(1));
+        let (_, remaining) = run(&processor, "SELECT id FROM orders").await;
+        assert_eq!(remaining.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_drives_access_paths_and_join_order() {
+        let processor = QueryProcessor::new();
+        let integers = |values: &[i64]| Row::new(values.iter().map(|v| Value::Integer(*v)).collect());
+        let columns = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        // 2000 events over 200 sessions over 5 users; every event is recent
+        let events = (0..2000).map(|id| integers(&[id, id % 200, 1])).collect();
+        let sessions = (0..200).map(|id| integers(&[id, id % 5])).collect();
+        let users = (0..5).map(|id| integers(&[id])).collect();
+        processor.register_table("events", columns(&["id", "session_id", "recent"]), events).await;
+        processor.register_table("sessions", columns(&["id", "user_id"]), sessions).await;
+        processor.register_table("users", columns(&["id"]), users).await;
+        let create = crate::parser::parse_sql("CREATE INDEX idx_events_recent ON events (recent)").unwrap();
+        processor.process_statement(create).await.unwrap();
+
+        // Without statistics any usable index is taken
+        let unselective = "SELECT id FROM events WHERE recent = 1";
+        let uses_index = |plan: Vec<String>| plan.iter().any(|line| line.contains("USING INDEX idx_events_recent"));
+        assert!(uses_index(explain(&processor, unselective).await));
+        let join = "SELECT * FROM events AS e INNER JOIN sessions AS s ON e.session_id = s.id \
+                    INNER JOIN users AS u ON s.user_id = u.id WHERE e.id < 3";
+        let (written_columns, mut written_rows) = run(&processor, join).await;
+        let plan = explain(&processor, join).await;
+        assert!(plan.iter().position(|line| line.contains("SCAN events")) < plan.iter().position(|line| line.contains("SCAN users")));
+
+        let (columns, rows) = run(&processor, "ANALYZE").await;
+        assert_eq!(columns, vec!["table", "rows"]);
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![Value::Text("events".to_string()), Value::Integer(2000)]),
+                Row::new(vec![Value::Text("sessions".to_string()), Value::Integer(200)]),
+                Row::new(vec![Value::Text("users".to_string()), Value::Integer(5)]),
+            ]
+        );
+
+        // An index that returns every row loses to a full scan, and one
+        // that finds nothing wins
+        let plan = explain(&processor, unselective).await;
+        assert!(plan.contains(&"SCAN events WHERE recent = 1".to_string()), "{:?}", plan);
+        assert!(uses_index(explain(&processor, "SELECT id FROM events WHERE recent = 0").await));
+
+        // The small tables are joined first; results and columns are unchanged
+        let plan = explain(&processor, join).await;
+        assert!(plan.iter().position(|line| line.contains("SCAN users")) < plan.iter().position(|line| line.contains("SCAN events")), "{:?}", plan);
+        let (reordered_columns, mut reordered_rows) = run(&processor, join).await;
+        assert_eq!(reordered_columns, written_columns);
+        written_rows.sort_by(|a, b| a.values[0].to_string().cmp(&b.values[0].to_string()));
+        reordered_rows.sort_by(|a, b| a.values[0].to_string().cmp(&b.values[0].to_string()));
+        assert_eq!(reordered_rows, written_rows);
+        assert_eq!(reordered_rows.len(), 3);
+
+        // EXPLAIN estimates come from the statistics
+        let (columns, plan) = run(&processor, &format!("EXPLAIN {}", unselective)).await;
+        let rows_at = columns.iter().position(|column| column == "est_rows").unwrap();
+        assert_eq!(plan.last().unwrap().values[rows_at], Value::Integer(2000));
+
+        let missing = crate::parser::parse_sql("ANALYZE missing").unwrap();
+        assert!(processor.process_statement(missing).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_index_ddl_errors() {
+        let processor = join_fixture().await;
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+
+        assert!(execute("CREATE UNIQUE INDEX idx_orders_user ON orders (user_id)").await.is_err());
+        assert!(execute("CREATE UNIQUE INDEX idx_users_name ON users (name)").await.is_ok());
+        assert!(execute("CREATE INDEX idx_users_name ON users (id)").await.is_err());
+        assert!(execute("CREATE INDEX idx_missing ON missing (id)").await.is_err());
+        assert!(execute("DROP INDEX idx_missing").await.is_err());
+        assert!(execute("DROP INDEX IF EXISTS idx_missing").await.is_ok());
+    }
+
+    async fn constraint_fixture() -> QueryProcessor {
+        let processor = QueryProcessor::new();
+        for sql in [
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE, age INTEGER CHECK (age >= 0))",
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users ON DELETE CASCADE)",
+            "CREATE TABLE comments (id INTEGER PRIMARY KEY, post_id INTEGER, \
+             FOREIGN KEY (post_id) REFERENCES posts (id) ON DELETE SET NULL)",
+            "CREATE TABLE likes (user_id INTEGER REFERENCES users (id) ON DELETE RESTRICT)",
+            "INSERT INTO users (email, age) VALUES ('a@x', 30), ('b@x', 40)",
+            "INSERT INTO posts (user_id) VALUES (1), (1), (2)",
+            "INSERT INTO comments (post_id) VALUES (1), (3)",
+        ] {
+            processor.process_statement(crate::parser::parse_sql(sql).unwrap()).await.unwrap();
+        }
+        processor
+    }
+
+    fn assert_violation(result: SqlResult<QueryResult>, message: &str) {
+        match result {
+            Err(SqlError::ConstraintViolation { message: actual }) => assert_eq!(actual, message),
+            other => panic!("Expected constraint violation {:?}, got {:?}", message, other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_not_null_unique_and_check_constraints() {
+        let processor = constraint_fixture().await;
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+
+        assert_violation(execute("INSERT INTO users (age) VALUES (20)").await, "NOT NULL constraint failed: users.email");
+        assert_violation(
+            execute("INSERT INTO users (email, age) VALUES ('a@x', 20)").await,
+            "UNIQUE constraint failed: users.email",
+        );
+        assert_violation(
+            execute("UPDATE users SET age = 0 - 1 WHERE id = 2").await,
+            "CHECK constraint failed: age >= 0",
+        );
+        assert_violation(execute("INSERT INTO users (id, email) VALUES (1, 'c@x')").await, "UNIQUE constraint failed: users.id");
+        // A CHECK that is NULL passes
+        assert!(execute("INSERT INTO users (email) VALUES ('c@x')").await.is_ok());
+        assert!(execute("DROP INDEX sqlite_autoindex_users_2").await.is_err());
+
+        let (_, rows) = run(&processor, "SELECT id, age FROM users").await;
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![Value::Integer(1), Value::Integer(30)]),
+                Row::new(vec![Value::Integer(2), Value::Integer(40)]),
+                Row::new(vec![Value::Integer(3), Value::Null]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_foreign_key_constraints() {
+        let processor = constraint_fixture().await;
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+
+        assert_violation(execute("INSERT INTO posts (user_id) VALUES (9)").await, "FOREIGN KEY constraint failed");
+        assert!(execute("INSERT INTO posts (user_id) VALUES (NULL)").await.is_ok());
+        assert_violation(execute("UPDATE users SET id = 7 WHERE id = 1").await, "FOREIGN KEY constraint failed");
+
+        // RESTRICT blocks the delete while a row references the user
+        execute("INSERT INTO likes (user_id) VALUES (2)").await.unwrap();
+        assert_violation(execute("DELETE FROM users WHERE id = 2").await, "FOREIGN KEY constraint failed");
+        let (_, rows) = run(&processor, "SELECT id FROM posts").await;
+        assert_eq!(rows.len(), 4);
+
+        // Deleting user 1 cascades to its posts, which sets the comment on
+        // post 1 to NULL
+        let mut dependents = processor.dependent_tables("users").await;
+        dependents.sort();
+        assert_eq!(dependents, vec!["comments", "likes", "posts"]);
+        assert!(matches!(
+            execute("DELETE FROM users WHERE id = 1").await.unwrap(),
+            QueryResult::Delete { rows_affected: 1 }
+        ));
+        let (_, rows) = run(&processor, "SELECT id FROM posts").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Integer(3)]), Row::new(vec![Value::Integer(4)])]);
+        let (_, rows) = run(&processor, "SELECT post_id FROM comments").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Null]), Row::new(vec![Value::Integer(3)])]);
+    }
+
+    fn ints(rows: &[Row]) -> Vec<i64> {
+        rows.iter()
+            .map(|row| match row.values[0] {
+                Value::Integer(value) => value,
+                ref other => panic!("Expected integer, got {:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_uncorrelated_subqueries() {
+        let processor = join_fixture().await;
+
+        let (_, rows) = run(&processor, "SELECT id, (SELECT MAX(total) FROM orders) FROM users WHERE id = 1").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Integer(1), Value::Integer(70)])]);
+        let (_, rows) = run(&processor, "SELECT id FROM orders WHERE total > (SELECT MIN(total) FROM orders)").await;
+        assert_eq!(ints(&rows), vec![10, 12]);
+        // An empty scalar subquery is NULL
+        let (_, rows) = run(&processor, "SELECT (SELECT id FROM users WHERE id = 9) FROM regions WHERE region = 'eu'").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Null])]);
+
+        let (_, rows) = run(&processor, "SELECT id FROM users WHERE id IN (SELECT user_id FROM orders)").await;
+        assert_eq!(ints(&rows), vec![1, 2]);
+        let (_, rows) = run(&processor, "SELECT id FROM users WHERE id NOT IN (SELECT user_id FROM orders)").await;
+        assert_eq!(ints(&rows), vec![3]);
+        // NOT IN is never true once the subquery yields a NULL
+        processor
+            .process_statement(crate::parser::parse_sql("INSERT INTO orders (id, user_id, total) VALUES (13, NULL, 5)").unwrap())
+            .await
+            .unwrap();
+        let (_, rows) = run(&processor, "SELECT id FROM users WHERE id NOT IN (SELECT user_id FROM orders)").await;
+        assert!(rows.is_empty());
+
+        let (_, rows) = run(&processor, "SELECT id FROM users WHERE EXISTS (SELECT * FROM regions)").await;
+        assert_eq!(rows.len(), 3);
+        let result = processor
+            .process_statement(crate::parser::parse_sql("SELECT (SELECT id, name FROM users) FROM regions").unwrap())
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_correlated_subqueries() {
+        let processor = join_fixture().await;
+
+        let query = "SELECT name FROM users AS u WHERE EXISTS (SELECT * FROM orders AS o WHERE o.user_id = u.id AND o.total > 30)";
+        assert!(explain(&processor, query).await.iter().any(|line| line.starts_with("SEMI JOIN")));
+        let (_, rows) = run(&processor, query).await;
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![Value::Text("Alice".to_string())]), Row::new(vec![Value::Text("Bob".to_string())])]
+        );
+
+        let query = "SELECT id FROM users WHERE NOT EXISTS (SELECT * FROM orders WHERE orders.user_id = users.id)";
+        assert!(explain(&processor, query).await.iter().any(|line| line.starts_with("ANTI JOIN")));
+        let (_, rows) = run(&processor, query).await;
+        assert_eq!(ints(&rows), vec![3]);
+
+        // A correlated scalar subquery runs once per row
+        let (_, rows) = run(
+            &processor,
+            "SELECT (SELECT COUNT(*) FROM orders WHERE orders.user_id = users.id) AS n FROM users ORDER BY id",
+        )
+        .await;
+        assert_eq!(ints(&rows), vec![2, 1, 0]);
+        let (_, rows) = run(
+            &processor,
+            "SELECT id FROM orders AS o WHERE total > (SELECT MIN(total) FROM orders WHERE user_id = o.user_id)",
+        )
+        .await;
+        assert_eq!(ints(&rows), vec![10]);
+
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+        assert!(matches!(
+            execute("DELETE FROM orders WHERE user_id IN (SELECT id FROM users WHERE name = 'Alice')").await.unwrap(),
+            QueryResult::Delete { rows_affected: 2 }
+        ));
+        execute("UPDATE users SET name = (SELECT region FROM regions WHERE region = 'eu') WHERE id = 3")
+            .await
+            .unwrap();
+        let (_, rows) = run(&processor, "SELECT name FROM users WHERE id = 3").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Text("eu".to_string())])]);
+    }
+
+    fn texts(rows: &[Row]) -> Vec<String> {
+        rows.iter()
+            .map(|row| match &row.values[0] {
+                Value::Text(value) => value.clone(),
+                other => panic!("Expected text, got {:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_full_text_search() {
+        let processor = QueryProcessor::new();
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+        execute("CREATE VIRTUAL TABLE docs USING fts(title, body, tokenize = 'porter')").await.unwrap();
+        for (title, body) in [
+            ("Indexes", "A B-tree index speeds up searching; indexes are searched by key"),
+            ("Planner", "The query planner picks an index when one matches"),
+            ("Joins", "Hash joins and nested loop joins"),
+        ] {
+            execute(&format!("INSERT INTO docs (title, body) VALUES ('{}', '{}')", title, body))
+                .await
+                .unwrap();
+        }
+
+        let query = "SELECT title FROM docs WHERE docs MATCH 'index' ORDER BY rank";
+        assert!(explain(&processor, query).await.iter().any(|line| line.contains("USING FULL-TEXT INDEX")));
+        let (_, rows) = run(&processor, query).await;
+        assert_eq!(texts(&rows), vec!["Indexes", "Planner"]);
+        let (_, rows) = run(&processor, "SELECT title FROM docs WHERE title MATCH 'join*'").await;
+        assert_eq!(texts(&rows), vec!["Joins"]);
+        let (_, rows) = run(&processor, "SELECT title FROM docs WHERE docs MATCH '\"query planner\" OR hash'").await;
+        assert_eq!(texts(&rows), vec!["Planner", "Joins"]);
+        // rank is hidden from SELECT *
+        let (columns, _) = run(&processor, "SELECT * FROM docs WHERE docs MATCH 'loop'").await;
+        assert_eq!(columns, vec!["title", "body"]);
+
+        execute("UPDATE docs SET body = 'Merge joins need sorted input' WHERE title = 'Joins'").await.unwrap();
+        let (_, rows) = run(&processor, "SELECT title FROM docs WHERE docs MATCH 'hash'").await;
+        assert!(rows.is_empty());
+        assert!(matches!(
+            execute("DELETE FROM docs WHERE docs MATCH 'planner'").await.unwrap(),
+            QueryResult::Delete { rows_affected: 1 }
+        ));
+        let (_, rows) = run(&processor, "SELECT title FROM docs WHERE docs MATCH 'index OR sorted'").await;
+        assert_eq!(texts(&rows), vec!["Indexes", "Joins"]);
+
+        assert!(execute("CREATE VIRTUAL TABLE t USING rtree(a)").await.is_err());
+        assert!(execute("SELECT title FROM docs WHERE docs MATCH 'index OR'").await.is_err());
+        assert!(execute("SELECT title FROM docs WHERE author MATCH 'x'").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_functions() {
+        let processor = QueryProcessor::new();
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+        execute("CREATE TABLE events (id INTEGER PRIMARY KEY, payload TEXT)").await.unwrap();
+        execute(r#"INSERT INTO events (id, payload) VALUES (1, '{"kind": "click", "tags": ["a", "b"], "at": {"x": 3}}')"#)
+            .await
+            .unwrap();
+        execute(r#"INSERT INTO events (id, payload) VALUES (2, '{"kind": "view", "tags": []}')"#)
+            .await
+            .unwrap();
+
+        let (_, rows) = run(&processor, "SELECT json_extract(payload, '$.kind') FROM events ORDER BY id").await;
+        assert_eq!(texts(&rows), vec!["click", "view"]);
+        let (_, rows) = run(&processor, "SELECT id FROM events WHERE payload ->> '$.at.x' = 3").await;
+        assert_eq!(ints(&rows), vec![1]);
+        let (_, rows) = run(&processor, "SELECT json_array_length(payload, '$.tags') FROM events ORDER BY id").await;
+        assert_eq!(ints(&rows), vec![2, 0]);
+        let (_, rows) = run(&processor, "SELECT payload -> 'at', payload -> 'tags' ->> 1 FROM events WHERE id = 1").await;
+        assert_eq!(
+            rows,
+            vec![Row::new(vec![Value::Text(r#"{"x":3}"#.to_string()), Value::Text("b".to_string())])]
+        );
+
+        execute("UPDATE events SET payload = json_set(payload, '$.kind', 'seen', '$.tags[#]', 'new') WHERE id = 2")
+            .await
+            .unwrap();
+        let (_, rows) = run(&processor, "SELECT payload FROM events WHERE id = 2").await;
+        assert_eq!(texts(&rows), vec![r#"{"kind":"seen","tags":["new"]}"#]);
+
+        assert!(execute("SELECT json(payload || 'x') FROM events").await.is_err());
+        assert!(execute("SELECT json_extract(payload) FROM events").await.is_err());
+        assert!(execute("SELECT json_extract(payload, 'kind') FROM events").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_collations() {
+        let processor = QueryProcessor::new();
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+        execute("CREATE TABLE tags (id INTEGER PRIMARY KEY, name TEXT COLLATE NOCASE UNIQUE, code TEXT COLLATE rtrim)")
+            .await
+            .unwrap();
+        execute("INSERT INTO tags (id, name, code) VALUES (1, 'rust', 'a'), (2, 'Go', 'b  '), (3, 'c', 'c')")
+            .await
+            .unwrap();
+        assert_violation(
+            execute("INSERT INTO tags (id, name) VALUES (4, 'RUST')").await,
+            "UNIQUE constraint failed: tags.name",
+        );
+
+        let (_, rows) = run(&processor, "SELECT id FROM tags WHERE name = 'GO'").await;
+        assert_eq!(ints(&rows), vec![2]);
+        let (_, rows) = run(&processor, "SELECT id FROM tags WHERE name = 'GO' COLLATE BINARY").await;
+        assert!(rows.is_empty());
+        let (_, rows) = run(&processor, "SELECT id FROM tags WHERE code = 'b'").await;
+        assert_eq!(ints(&rows), vec![2]);
+        let (_, rows) = run(&processor, "SELECT id FROM tags WHERE name IN ('C', 'x') OR name BETWEEN 'R' AND 'S'").await;
+        assert_eq!(ints(&rows), vec![1, 3]);
+        let (_, rows) = run(&processor, "SELECT name FROM tags ORDER BY name").await;
+        assert_eq!(texts(&rows), vec!["c", "Go", "rust"]);
+        let (_, rows) = run(&processor, "SELECT name FROM tags ORDER BY name COLLATE BINARY").await;
+        assert_eq!(texts(&rows), vec!["Go", "c", "rust"]);
+        let (_, rows) = run(&processor, "SELECT code AS c FROM tags ORDER BY c COLLATE NOCASE DESC").await;
+        assert_eq!(texts(&rows), vec!["c", "b  ", "a"]);
+
+        // An index on a NOCASE column finds rows the way `=` does
+        execute("CREATE INDEX tags_name ON tags (name)").await.unwrap();
+        let (_, rows) = run(&processor, "SELECT id FROM tags WHERE name = 'RUST'").await;
+        assert_eq!(ints(&rows), vec![1]);
+
+        crate::collation::register_collation("by_length", |a, b| a.len().cmp(&b.len()));
+        let (_, rows) = run(&processor, "SELECT name FROM tags ORDER BY name COLLATE by_length, id").await;
+        assert_eq!(texts(&rows), vec!["c", "Go", "rust"]);
+        assert!(execute("SELECT id FROM tags ORDER BY name COLLATE missing").await.is_err());
+        assert!(execute("CREATE TABLE bad (name TEXT COLLATE missing)").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_catalog_tables() {
+        let processor = QueryProcessor::new();
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+        execute("CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE COLLATE NOCASE, role TEXT DEFAULT 'member')")
+            .await
+            .unwrap();
+        execute("CREATE TABLE posts (id INTEGER, user_id INTEGER)").await.unwrap();
+        execute("CREATE INDEX posts_user ON posts (user_id)").await.unwrap();
+        execute("INSERT INTO posts (id, user_id) VALUES (1, 7), (2, 7), (3, NULL)").await.unwrap();
+
+        let (columns, rows) = run(&processor, "SELECT name, type, column_count FROM catalog.tables").await;
+        assert_eq!(columns, vec!["name", "type", "column_count"]);
+        assert_eq!(texts(&rows), vec!["posts", "users"]);
+        assert_eq!(rows[1].values[2], Value::Integer(3));
+        let (_, rows) = run(&processor, "SELECT sql FROM catalog.tables WHERE name = 'posts'").await;
+        assert_eq!(texts(&rows), vec!["CREATE TABLE posts (\n  id INTEGER,\n  user_id INTEGER\n)"]);
+
+        let (_, rows) = run(
+            &processor,
+            "SELECT name, not_null, default_value, primary_key, collation FROM catalog.columns \
+             WHERE table_name = 'users' ORDER BY position",
+        )
+        .await;
+        assert_eq!(texts(&rows), vec!["id", "email", "role"]);
+        assert_eq!(rows[0].values[3], Value::Integer(1));
+        assert_eq!(rows[1].values[1], Value::Integer(1));
+        assert_eq!(rows[1].values[4], Value::Text("NOCASE".to_string()));
+        assert_eq!(rows[2].values[2], Value::Text("'member'".to_string()));
+
+        let (_, rows) = run(
+            &processor,
+            "SELECT i.name, c.type FROM catalog.indexes AS i JOIN catalog.columns AS c \
+             ON c.table_name = i.table_name AND c.name = i.columns WHERE i.automatic = 0",
+        )
+        .await;
+        assert_eq!(texts(&rows), vec!["posts_user"]);
+        let (_, rows) = run(&processor, "SELECT table_name FROM catalog.indexes WHERE automatic = 1").await;
+        assert_eq!(texts(&rows), vec!["users", "users"]);
+
+        let (_, rows) = run(&processor, "SELECT * FROM catalog.stats").await;
+        assert!(rows.is_empty());
+        execute("ANALYZE posts").await.unwrap();
+        let (_, rows) = run(&processor, "SELECT column_name, row_count, null_count, distinct_count, max FROM catalog.stats").await;
+        assert_eq!(texts(&rows), vec!["id", "user_id"]);
+        assert_eq!(rows[1].values[1..], [Value::Integer(3), Value::Integer(1), Value::Integer(1), Value::Integer(7)]);
+
+        // Tables of the schema registry are listed under their schema
+        let mut schema = crate::schema::Schema::empty();
+        schema
+            .add_table(crate::schema::Table::new(
+                "events".to_string(),
+                vec![crate::schema::table::Column {
+                    name: "at".to_string(),
+                    data_type: crate::types::DataType::Integer,
+                    nullable: false,
+                    default_value: None,
+                    auto_increment: false,
+                    primary_key: true,
+                    unique: false,
+                }],
+            ))
+            .unwrap();
+        processor.schema_registry().write().await.add_schema("audit".to_string(), schema).unwrap();
+        let (_, rows) = run(&processor, "SELECT schema, name FROM catalog.tables WHERE sql IS NULL").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Text("audit".to_string()), Value::Text("events".to_string())])]);
+
+        assert!(execute("SELECT * FROM catalog.views").await.is_err());
+        assert!(crate::parser::parse_sql("DELETE FROM catalog.tables").is_err());
+    }
+
+    /// Counts from 1 to 5; conditions are left to the engine
+    struct Numbers;
+
+    impl crate::fdw::ForeignScan for Numbers {
+        fn validate(&self, _table: &crate::fdw::ForeignTable) -> SqlResult<()> {
+            Ok(())
+        }
+
+        fn supports(&self, _table: &crate::fdw::ForeignTable, _predicate: &Expression) -> bool {
+            false
+        }
+
+        fn scan<'a>(&'a self, _table: &'a crate::fdw::ForeignTable, _pushed: &'a [Expression]) -> crate::fdw::ScanFuture<'a> {
+            Box::pin(async { Ok((1..=5).map(|n| Row::new(vec![Value::Integer(n)])).collect()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_foreign_tables() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A JSON service of countries, filtering by `?code=`; it records
+        // the target of every request
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let targets = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+        let recorded = targets.clone();
+        tokio::spawn(async move {
+            let countries = serde_json::json!([
+                {"code": "NZ", "name": "New Zealand", "population": 5},
+                {"code": "FR", "name": "France", "population": 68},
+                {"code": "JP", "name": "Japan", "population": 125},
+            ]);
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..read]).to_string();
+                let target = request.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let code = target.split_once("code=").map(|(_, code)| code.to_string());
+                let data: Vec<_> = countries
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .filter(|country| code.as_ref().is_none_or(|code| country["code"] == code.as_str()))
+                    .cloned()
+                    .collect();
+                recorded.lock().unwrap().push(target);
+                let body = serde_json::json!({ "data": data }).to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let last_target = || targets.lock().unwrap().last().cloned().unwrap();
+
+        let processor = QueryProcessor::new();
+        let execute = |sql: &str| processor.process_statement(crate::parser::parse_sql(sql).unwrap());
+        execute("CREATE TABLE offices (city TEXT, code TEXT)").await.unwrap();
+        execute("INSERT INTO offices VALUES ('Auckland', 'NZ'), ('Paris', 'FR'), ('Lyon', 'FR')").await.unwrap();
+        execute(&format!(
+            "CREATE FOREIGN TABLE countries (code TEXT, name TEXT, population INTEGER) \
+             SERVER http OPTIONS (url 'http://{}/countries', rows '$.data', filter 'code')",
+            address
+        ))
+        .await
+        .unwrap();
+
+        let query = "SELECT name, population FROM countries WHERE code = 'NZ'";
+        assert!(explain(&processor, query)
+            .await
+            .iter()
+            .any(|line| line == "SCAN countries VIA http PUSHING code = 'NZ'"));
+        let (_, rows) = run(&processor, query).await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Text("New Zealand".into()), Value::Integer(5)])]);
+        assert_eq!(last_target(), "/countries?code=NZ");
+
+        // Joined with a local table, through an alias
+        let (_, rows) = run(
+            &processor,
+            "SELECT o.city, c.name FROM offices AS o JOIN countries AS c ON o.code = c.code \
+             WHERE c.code = 'FR' ORDER BY o.city",
+        )
+        .await;
+        assert_eq!(
+            rows,
+            vec![
+                Row::new(vec![Value::Text("Lyon".into()), Value::Text("France".into())]),
+                Row::new(vec![Value::Text("Paris".into()), Value::Text("France".into())]),
+            ]
+        );
+        assert_eq!(last_target(), "/countries?code=FR");
+
+        // Conditions the service cannot evaluate are applied locally
+        let (_, rows) = run(&processor, "SELECT name FROM countries WHERE population > 50 ORDER BY name").await;
+        assert_eq!(texts(&rows), vec!["France", "Japan"]);
+        assert_eq!(last_target(), "/countries");
+        // and conditions on the null-extended side of an outer join stay above it
+        let (_, rows) = run(
+            &processor,
+            "SELECT o.city FROM offices AS o LEFT JOIN countries AS c ON o.code = c.code AND c.population > 10 \
+             WHERE c.code = 'FR' ORDER BY o.city",
+        )
+        .await;
+        assert_eq!(texts(&rows), vec!["Lyon", "Paris"]);
+        assert_eq!(last_target(), "/countries");
+
+        assert!(execute("INSERT INTO countries VALUES ('DE', 'Germany', 84)").await.is_err());
+        assert!(execute("DELETE FROM countries").await.is_err());
+        assert!(execute("CREATE INDEX countries_name ON countries (name)").await.is_err());
+        assert!(execute("CREATE FOREIGN TABLE t (a INTEGER) SERVER ftp OPTIONS (url 'ftp://x')").await.is_err());
+        assert!(execute("CREATE FOREIGN TABLE t (a INTEGER) SERVER http OPTIONS (url 'http://x', limit '1')")
+            .await
+            .is_err());
+        assert!(execute("CREATE FOREIGN TABLE t (a INTEGER PRIMARY KEY) SERVER http OPTIONS (url 'http://x')")
+            .await
+            .is_err());
+
+        // Other servers plug in
+        processor.register_foreign_server("numbers", Arc::new(Numbers)).await;
+        execute("CREATE FOREIGN TABLE n (value INTEGER) SERVER numbers").await.unwrap();
+        let (_, rows) = run(&processor, "SELECT value FROM n WHERE value > 3").await;
+        assert_eq!(rows, vec![Row::new(vec![Value::Integer(4)]), Row::new(vec![Value::Integer(5)])]);
+    }
 }
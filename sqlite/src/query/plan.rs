@@ -1,7 +1,15 @@
-use crate::parser::ast::{Expression, JoinType, OrderDirection};
+use crate::error::{SqlError, SqlResult};
+use crate::fdw::ForeignOptions;
+use crate::fts::{FtsOptions, FtsQuery};
+use crate::parser::ast::{
+    BinaryOperator, ExplainMode, Expression, ForeignKeyAction, JoinConstraint, JoinType, OrderDirection,
+};
+use crate::query::index::{IndexInfo, IndexLookup};
 use crate::types::{Row, Value};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
 /// Query execution plan following composite pattern
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,11 +20,30 @@ pub enum QueryPlan {
         filter: Option<Expression>,
         projection: Option<Vec<String>>,
     },
-    /// Index scan operation
+    /// Index scan operation; `filter` is the full WHERE clause, re-checked
+    /// on every row the index returns
     IndexScan {
         table: String,
         index: String,
-        key: Value,
+        columns: Vec<String>,
+        lookup: IndexLookup,
+        filter: Option<Expression>,
+        /// The index holds every column the query reads
+        covering: bool,
+    },
+    /// Full-text search of a table's inverted index; outputs the matching
+    /// rows with their hidden `rank`, then applies `filter`
+    FtsScan {
+        table: String,
+        query: FtsQuery,
+        filter: Option<Expression>,
+    },
+    /// Read a foreign table from its server, which applies the `pushed`
+    /// conditions; `filter` is applied to the rows it returns
+    ForeignScan {
+        table: String,
+        server: String,
+        pushed: Vec<Expression>,
         filter: Option<Expression>,
     },
     /// Join operation
@@ -24,7 +51,21 @@ pub enum QueryPlan {
         left: Box<QueryPlan>,
         right: Box<QueryPlan>,
         join_type: JoinType,
-        condition: Expression,
+        condition: JoinConstraint,
+    },
+    /// Keep the left rows that have (or, when `anti`, lack) a right row
+    /// with equal keys; produced from `[NOT] EXISTS` and `IN` subqueries
+    SemiJoin {
+        left: Box<QueryPlan>,
+        right: Box<QueryPlan>,
+        /// Pairs of (left expression, right expression) that must be equal
+        keys: Vec<(Expression, Expression)>,
+        anti: bool,
+    },
+    /// Rename the table qualifier of every input column (`FROM t AS a`)
+    Alias {
+        input: Box<QueryPlan>,
+        alias: String,
     },
     /// Projection operation
     Projection {
@@ -40,7 +81,7 @@ pub enum QueryPlan {
     /// Sort operation
     Sort {
         input: Box<QueryPlan>,
-        order_by: Vec<(String, OrderDirection)>,
+        order_by: Vec<(Expression, OrderDirection)>,
     },
     /// Limit operation
     Limit {
@@ -48,12 +89,17 @@ pub enum QueryPlan {
         count: u64,
         offset: Option<u64>,
     },
-    /// Group by operation
+    /// Group by operation; outputs the group keys followed by one column per
+    /// aggregate, named by [`AggregateFunction::output_name`]
     GroupBy {
         input: Box<QueryPlan>,
-        group_columns: Vec<String>,
+        group_columns: Vec<Expression>,
         aggregates: Vec<AggregateFunction>,
     },
+    /// Duplicate elimination (`SELECT DISTINCT`)
+    Distinct {
+        input: Box<QueryPlan>,
+    },
     /// Insert operation
     Insert {
         table: String,
@@ -76,6 +122,30 @@ pub enum QueryPlan {
         table: String,
         schema: TableSchema,
     },
+    /// Drop table operation
+    DropTable {
+        table: String,
+        if_exists: bool,
+    },
+    /// Create index operation
+    CreateIndex {
+        index: IndexInfo,
+    },
+    /// Drop index operation
+    DropIndex {
+        index: String,
+        if_exists: bool,
+    },
+    /// Gather planner statistics for one table, or every table
+    Analyze {
+        table: Option<String>,
+    },
+    /// Describe a plan instead of (or, for `EXPLAIN ANALYZE`, while)
+    /// running it
+    Explain {
+        plan: Box<QueryPlan>,
+        mode: ExplainMode,
+    },
     /// Union operation
     Union {
         left: Box<QueryPlan>,
@@ -95,13 +165,93 @@ pub enum QueryPlan {
 }
 
 /// Aggregate function types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AggregateFunction {
-    Count { column: Option<String> },
-    Sum { column: String },
-    Avg { column: String },
-    Min { column: String },
-    Max { column: String },
+    /// `COUNT(*)` when `column` is `None`, otherwise counts non-NULL values
+    Count { column: Option<Expression> },
+    Sum { column: Expression },
+    Avg { column: Expression },
+    Min { column: Expression },
+    Max { column: Expression },
+}
+
+impl AggregateFunction {
+    /// Recognize an aggregate call; other expressions yield `None`
+    pub fn from_expression(expr: &Expression) -> SqlResult<Option<Self>> {
+        let (name, args) = match expr {
+            Expression::Function { name, args } => (name.to_uppercase(), args),
+            _ => return Ok(None),
+        };
+        let single = || match args.as_slice() {
+            [arg] => Ok(arg.clone()),
+            _ => Err(SqlError::parse_error(format!(
+                "{}() takes exactly one argument",
+                name
+            ))),
+        };
+        let aggregate = match name.as_str() {
+            "COUNT" => match args.as_slice() {
+                [] => AggregateFunction::Count { column: None },
+                [Expression::Column(star)] if star == "*" => AggregateFunction::Count { column: None },
+                _ => AggregateFunction::Count { column: Some(single()?) },
+            },
+            "SUM" => AggregateFunction::Sum { column: single()? },
+            "AVG" => AggregateFunction::Avg { column: single()? },
+            "MIN" => AggregateFunction::Min { column: single()? },
+            "MAX" => AggregateFunction::Max { column: single()? },
+            _ => return Ok(None),
+        };
+        Ok(Some(aggregate))
+    }
+
+    /// Whether `expr` contains an aggregate call anywhere
+    pub fn contains_aggregate(expr: &Expression) -> bool {
+        match expr {
+            Expression::Function { name, args } => {
+                matches!(name.to_uppercase().as_str(), "COUNT" | "SUM" | "AVG" | "MIN" | "MAX")
+                    || args.iter().any(Self::contains_aggregate)
+            }
+            Expression::BinaryOp { left, right, .. } => {
+                Self::contains_aggregate(left) || Self::contains_aggregate(right)
+            }
+            Expression::UnaryOp { operand, .. } => Self::contains_aggregate(operand),
+            Expression::IsNull(inner) | Expression::IsNotNull(inner) => Self::contains_aggregate(inner),
+            Expression::In { expr, list } => {
+                Self::contains_aggregate(expr) || list.iter().any(Self::contains_aggregate)
+            }
+            Expression::Between { expr, low, high } => {
+                Self::contains_aggregate(expr) || Self::contains_aggregate(low) || Self::contains_aggregate(high)
+            }
+            Expression::InSubquery { expr, .. } => Self::contains_aggregate(expr),
+            _ => false,
+        }
+    }
+
+    /// The aggregated expression, `None` for `COUNT(*)`
+    pub fn argument(&self) -> Option<&Expression> {
+        match self {
+            AggregateFunction::Count { column } => column.as_ref(),
+            AggregateFunction::Sum { column }
+            | AggregateFunction::Avg { column }
+            | AggregateFunction::Min { column }
+            | AggregateFunction::Max { column } => Some(column),
+        }
+    }
+
+    /// Name of the output column, e.g. `COUNT(*)` or `SUM(total)`
+    pub fn output_name(&self) -> String {
+        let name = match self {
+            AggregateFunction::Count { .. } => "COUNT",
+            AggregateFunction::Sum { .. } => "SUM",
+            AggregateFunction::Avg { .. } => "AVG",
+            AggregateFunction::Min { .. } => "MIN",
+            AggregateFunction::Max { .. } => "MAX",
+        };
+        match self.argument() {
+            Some(arg) => format!("{}({})", name, arg),
+            None => format!("{}(*)", name),
+        }
+    }
 }
 
 /// Table schema for CREATE TABLE operations
@@ -109,8 +259,209 @@ pub enum AggregateFunction {
 pub struct TableSchema {
     pub columns: Vec<ColumnSchema>,
     pub constraints: Vec<TableConstraint>,
+    /// Set for a full-text table (`CREATE VIRTUAL TABLE ... USING fts`)
+    #[serde(default)]
+    pub fts: Option<FtsOptions>,
+    /// Set for a foreign table (`CREATE FOREIGN TABLE ... SERVER ...`),
+    /// whose rows are read from its server
+    #[serde(default)]
+    pub foreign: Option<ForeignOptions>,
 }
 
+impl TableSchema {
+    /// Schema for a table known only by its column names
+    pub fn untyped(columns: &[String]) -> Self {
+        TableSchema {
+            columns: columns
+                .iter()
+                .map(|name| ColumnSchema {
+                    name: name.clone(),
+                    data_type: crate::types::DataType::Null,
+                    nullable: true,
+                    default: None,
+                    primary_key: false,
+                    unique: false,
+                    collation: None,
+                })
+                .collect(),
+            constraints: Vec::new(),
+            fts: None,
+            foreign: None,
+        }
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|column| column.name.clone()).collect()
+    }
+
+    /// Position of a column, matched case-insensitively
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|column| column.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Columns of the primary key, declared on the columns or as a table
+    /// constraint
+    pub fn primary_key(&self) -> Vec<String> {
+        let declared = self.constraints.iter().find_map(|constraint| match constraint {
+            TableConstraint::PrimaryKey(columns) => Some(columns.clone()),
+            _ => None,
+        });
+        declared.unwrap_or_else(|| {
+            self.columns
+                .iter()
+                .filter(|column| column.primary_key)
+                .map(|column| column.name.clone())
+                .collect()
+        })
+    }
+
+    /// Column sets that must be unique: the primary key, then every UNIQUE
+    /// column or constraint, without repeats
+    pub fn unique_keys(&self) -> Vec<Vec<String>> {
+        let mut keys = vec![self.primary_key()];
+        keys.extend(
+            self.columns
+                .iter()
+                .filter(|column| column.unique)
+                .map(|column| vec![column.name.clone()]),
+        );
+        keys.extend(self.constraints.iter().filter_map(|constraint| match constraint {
+            TableConstraint::Unique(columns) => Some(columns.clone()),
+            _ => None,
+        }));
+        let mut unique: Vec<Vec<String>> = Vec::new();
+        for key in keys {
+            if !key.is_empty() && !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+        unique
+    }
+
+    pub fn checks(&self) -> impl Iterator<Item = &Expression> {
+        self.constraints.iter().filter_map(|constraint| match constraint {
+            TableConstraint::Check(expr) => Some(expr),
+            _ => None,
+        })
+    }
+
+    /// Foreign keys of the table; `parent` resolves the primary key of a
+    /// table referenced without naming its columns
+    pub fn foreign_keys(&self, parent: impl Fn(&str) -> Option<Vec<String>>) -> Vec<ForeignKey> {
+        self.constraints
+            .iter()
+            .filter_map(|constraint| match constraint {
+                TableConstraint::ForeignKey {
+                    columns,
+                    foreign_table,
+                    foreign_columns,
+                    on_delete,
+                } => Some(ForeignKey {
+                    columns: columns.clone(),
+                    foreign_table: foreign_table.clone(),
+                    foreign_columns: if foreign_columns.is_empty() {
+                        parent(foreign_table).unwrap_or_default()
+                    } else {
+                        foreign_columns.clone()
+                    },
+                    on_delete: *on_delete,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The `INTEGER PRIMARY KEY` column, which aliases the rowid and is
+    /// filled in automatically when inserted as NULL
+    pub fn rowid_column(&self) -> Option<usize> {
+        let mut keys = self.columns.iter().enumerate().filter(|(_, column)| column.primary_key);
+        match (keys.next(), keys.next()) {
+            (Some((position, column)), None) if column.data_type == crate::types::DataType::Integer => {
+                Some(position)
+            }
+            _ => None,
+        }
+    }
+
+    /// The CREATE statement that makes a table `name` with this schema
+    pub fn create_sql(&self, name: &str) -> String {
+        if let Some(options) = &self.fts {
+            let mut arguments = self.column_names();
+            if !options.tokenize.is_empty() {
+                arguments.push(format!("tokenize = '{}'", options.tokenize.replace('\'', "''")));
+            }
+            return format!("CREATE VIRTUAL TABLE {} USING fts({})", name, arguments.join(", "));
+        }
+        let mut items: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let mut item = column.name.clone();
+                if column.data_type != crate::types::DataType::Null {
+                    item.push_str(&format!(" {}", column.data_type));
+                }
+                if column.primary_key {
+                    item.push_str(" PRIMARY KEY");
+                }
+                if !column.nullable && !column.primary_key {
+                    item.push_str(" NOT NULL");
+                }
+                if column.unique {
+                    item.push_str(" UNIQUE");
+                }
+                if let Some(collation) = &column.collation {
+                    item.push_str(&format!(" COLLATE {}", collation));
+                }
+                if let Some(default) = &column.default {
+                    item.push_str(&format!(" DEFAULT {}", Expression::Literal(default.clone())));
+                }
+                item
+            })
+            .collect();
+        items.extend(self.constraints.iter().map(|constraint| match constraint {
+            TableConstraint::PrimaryKey(columns) => format!("PRIMARY KEY ({})", columns.join(", ")),
+            TableConstraint::Unique(columns) => format!("UNIQUE ({})", columns.join(", ")),
+            TableConstraint::ForeignKey {
+                columns,
+                foreign_table,
+                foreign_columns,
+                on_delete,
+            } => {
+                let mut item = format!("FOREIGN KEY ({}) REFERENCES {}", columns.join(", "), foreign_table);
+                if !foreign_columns.is_empty() {
+                    item.push_str(&format!(" ({})", foreign_columns.join(", ")));
+                }
+                match on_delete {
+                    ForeignKeyAction::NoAction => {}
+                    ForeignKeyAction::Restrict => item.push_str(" ON DELETE RESTRICT"),
+                    ForeignKeyAction::Cascade => item.push_str(" ON DELETE CASCADE"),
+                    ForeignKeyAction::SetNull => item.push_str(" ON DELETE SET NULL"),
+                }
+                item
+            }
+            TableConstraint::Check(expr) => format!("CHECK ({})", expr),
+        }));
+        let Some(foreign) = &self.foreign else {
+            return format!("CREATE TABLE {} (\n  {}\n)", name, items.join(",\n  "));
+        };
+        let mut sql = format!("CREATE FOREIGN TABLE {} (\n  {}\n) SERVER {}", name, items.join(",\n  "), foreign.server);
+        if !foreign.options.is_empty() {
+            let options: Vec<String> = foreign
+                .options
+                .iter()
+                .map(|(key, value)| format!("{} '{}'", key, value.replace('\'', "''")))
+                .collect();
+            sql.push_str(&format!(" OPTIONS ({})", options.join(", ")));
+        }
+        sql
+    }
+}
+
+/// Declared schema of every table, shared by the planner and the executor
+pub type SchemaCatalog = Arc<RwLock<HashMap<String, TableSchema>>>;
+
 /// Column schema definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColumnSchema {
@@ -120,6 +471,9 @@ pub struct ColumnSchema {
     pub default: Option<Value>,
     pub primary_key: bool,
     pub unique: bool,
+    /// Collation from `COLLATE name`; BINARY when not set
+    #[serde(default)]
+    pub collation: Option<String>,
 }
 
 /// Table constraint definition
@@ -130,29 +484,92 @@ pub enum TableConstraint {
     ForeignKey {
         columns: Vec<String>,
         foreign_table: String,
+        /// Empty to reference the parent's primary key
         foreign_columns: Vec<String>,
+        #[serde(default)]
+        on_delete: ForeignKeyAction,
     },
     Check(Expression),
 }
 
+/// A foreign key of a table, with the parent columns resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub foreign_table: String,
+    pub foreign_columns: Vec<String>,
+    pub on_delete: ForeignKeyAction,
+}
+
+/// One operator of a plan as `EXPLAIN` shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplainNode {
+    pub id: i64,
+    /// Id of the operator reading this one's output, 0 for the root
+    pub parent: i64,
+    /// The physical operator, e.g. `HASH JOIN`
+    pub operator: String,
+    /// The logical operation, as `EXPLAIN QUERY PLAN` shows it
+    pub detail: String,
+    /// Estimated cost, this operator's inputs included
+    pub cost: f64,
+    /// Estimated output rows
+    pub rows: u64,
+}
+
+impl ExplainNode {
+    /// Output columns of an EXPLAIN statement in `mode`
+    pub fn columns(mode: ExplainMode) -> Vec<&'static str> {
+        match mode {
+            ExplainMode::QueryPlan => vec!["id", "parent", "detail"],
+            ExplainMode::Plan => vec!["id", "parent", "operator", "detail", "est_cost", "est_rows"],
+            ExplainMode::Analyze => vec![
+                "id",
+                "parent",
+                "operator",
+                "detail",
+                "est_cost",
+                "est_rows",
+                "actual_rows",
+                "time_ms",
+            ],
+        }
+    }
+}
+
+fn is_column(expr: &Expression) -> bool {
+    matches!(expr, Expression::Column(_) | Expression::QualifiedColumn { .. })
+}
+
 impl QueryPlan {
     /// Get estimated cost of executing this plan
     pub fn estimated_cost(&self) -> f64 {
         match self {
             QueryPlan::Scan { .. } => 100.0,
             QueryPlan::IndexScan { .. } => 10.0,
+            QueryPlan::FtsScan { .. } => 5.0,
+            // A round trip to another system
+            QueryPlan::ForeignScan { .. } => 1000.0,
             QueryPlan::Join { left, right, .. } => {
                 left.estimated_cost() * right.estimated_cost() * 0.1
             }
+            QueryPlan::SemiJoin { left, right, .. } => left.estimated_cost() + right.estimated_cost(),
+            QueryPlan::Alias { input, .. } => input.estimated_cost(),
             QueryPlan::Projection { input, .. } => input.estimated_cost() * 1.1,
             QueryPlan::Selection { input, .. } => input.estimated_cost() * 1.2,
             QueryPlan::Sort { input, .. } => input.estimated_cost() * 2.0,
             QueryPlan::Limit { input, .. } => input.estimated_cost() * 0.5,
             QueryPlan::GroupBy { input, .. } => input.estimated_cost() * 1.5,
+            QueryPlan::Distinct { input } => input.estimated_cost() * 1.3,
             QueryPlan::Insert { values, .. } => values.len() as f64 * 5.0,
             QueryPlan::Update { .. } => 50.0,
             QueryPlan::Delete { .. } => 30.0,
             QueryPlan::CreateTable { .. } => 20.0,
+            QueryPlan::DropTable { .. } => 10.0,
+            QueryPlan::CreateIndex { .. } => 200.0,
+            QueryPlan::DropIndex { .. } => 10.0,
+            QueryPlan::Analyze { .. } => 1000.0,
+            QueryPlan::Explain { .. } => 1.0,
             QueryPlan::Union { left, right, .. } => left.estimated_cost() + right.estimated_cost(),
             QueryPlan::Intersect { left, right } => left.estimated_cost() + right.estimated_cost(),
             QueryPlan::Except { left, right } => left.estimated_cost() + right.estimated_cost(),
@@ -163,19 +580,32 @@ impl QueryPlan {
     pub fn estimated_rows(&self) -> u64 {
         match self {
             QueryPlan::Scan { .. } => 1000, // Default estimate
+            QueryPlan::IndexScan { lookup, .. } if lookup.has_range() => 250,
             QueryPlan::IndexScan { .. } => 100,
+            QueryPlan::FtsScan { .. } => 25,
+            QueryPlan::ForeignScan { pushed, .. } if !pushed.is_empty() => 100,
+            QueryPlan::ForeignScan { .. } => 1000,
             QueryPlan::Join { left, right, .. } => {
                 (left.estimated_rows() * right.estimated_rows()) / 10
             }
+            QueryPlan::SemiJoin { left, .. } => left.estimated_rows() / 2,
+            QueryPlan::Alias { input, .. } => input.estimated_rows(),
             QueryPlan::Projection { input, .. } => input.estimated_rows(),
             QueryPlan::Selection { input, .. } => input.estimated_rows() / 3,
             QueryPlan::Sort { input, .. } => input.estimated_rows(),
             QueryPlan::Limit { count, .. } => *count,
+            QueryPlan::GroupBy { group_columns, .. } if group_columns.is_empty() => 1,
             QueryPlan::GroupBy { input, .. } => input.estimated_rows() / 10,
+            QueryPlan::Distinct { input } => input.estimated_rows() / 2,
             QueryPlan::Insert { values, .. } => values.len() as u64,
             QueryPlan::Update { .. } => 1,
             QueryPlan::Delete { .. } => 1,
-            QueryPlan::CreateTable { .. } => 0,
+            QueryPlan::CreateTable { .. }
+            | QueryPlan::DropTable { .. }
+            | QueryPlan::CreateIndex { .. }
+            | QueryPlan::DropIndex { .. } => 0,
+            QueryPlan::Analyze { .. } => 1,
+            QueryPlan::Explain { plan, .. } => plan.explain().len() as u64,
             QueryPlan::Union { left, right, .. } => left.estimated_rows() + right.estimated_rows(),
             QueryPlan::Intersect { left, right } => {
                 std::cmp::min(left.estimated_rows(), right.estimated_rows())
@@ -198,19 +628,29 @@ impl QueryPlan {
         match self {
             QueryPlan::Scan { .. }
             | QueryPlan::IndexScan { .. }
+            | QueryPlan::FtsScan { .. }
+            | QueryPlan::ForeignScan { .. }
             | QueryPlan::Join { .. }
+            | QueryPlan::SemiJoin { .. }
+            | QueryPlan::Alias { .. }
             | QueryPlan::Projection { .. }
             | QueryPlan::Selection { .. }
             | QueryPlan::Sort { .. }
             | QueryPlan::Limit { .. }
             | QueryPlan::GroupBy { .. }
+            | QueryPlan::Distinct { .. }
             | QueryPlan::Union { .. }
             | QueryPlan::Intersect { .. }
             | QueryPlan::Except { .. } => true,
+            QueryPlan::Explain { plan, mode } => *mode != ExplainMode::Analyze || plan.is_read_only(),
             QueryPlan::Insert { .. }
             | QueryPlan::Update { .. }
             | QueryPlan::Delete { .. }
-            | QueryPlan::CreateTable { .. } => false,
+            | QueryPlan::CreateTable { .. }
+            | QueryPlan::DropTable { .. }
+            | QueryPlan::CreateIndex { .. }
+            | QueryPlan::DropIndex { .. }
+            | QueryPlan::Analyze { .. } => false,
         }
     }
 
@@ -219,6 +659,27 @@ impl QueryPlan {
         !self.is_read_only()
     }
 
+    /// Whether the executor runs this plan as a relational operator, whose
+    /// output columns keep their table qualifiers
+    pub fn is_relational(&self) -> bool {
+        matches!(
+            self,
+            QueryPlan::Scan { .. }
+                | QueryPlan::IndexScan { .. }
+                | QueryPlan::FtsScan { .. }
+                | QueryPlan::ForeignScan { .. }
+                | QueryPlan::Join { .. }
+                | QueryPlan::SemiJoin { .. }
+                | QueryPlan::Alias { .. }
+                | QueryPlan::Projection { .. }
+                | QueryPlan::Selection { .. }
+                | QueryPlan::Sort { .. }
+                | QueryPlan::Limit { .. }
+                | QueryPlan::GroupBy { .. }
+                | QueryPlan::Distinct { .. }
+        )
+    }
+
     /// Get the output schema (column names and types)
     pub fn output_schema(&self) -> Vec<(String, crate::types::DataType)> {
         match self {
@@ -242,6 +703,36 @@ impl QueryPlan {
                 schema.extend(right.output_schema());
                 schema
             }
+            QueryPlan::GroupBy { group_columns, aggregates, .. } => group_columns
+                .iter()
+                .map(|expr| (expr.to_string(), crate::types::DataType::Text))
+                .chain(aggregates.iter().map(|aggregate| {
+                    let data_type = match aggregate {
+                        AggregateFunction::Count { .. } => crate::types::DataType::Integer,
+                        AggregateFunction::Avg { .. } => crate::types::DataType::Real,
+                        _ => crate::types::DataType::Text,
+                    };
+                    (aggregate.output_name(), data_type)
+                }))
+                .collect(),
+            QueryPlan::Alias { input, .. }
+            | QueryPlan::Distinct { input }
+            | QueryPlan::SemiJoin { left: input, .. } => input.output_schema(),
+            QueryPlan::IndexScan { columns, covering: true, .. } => columns
+                .iter()
+                .map(|col| (col.clone(), crate::types::DataType::Text))
+                .collect(),
+            QueryPlan::Explain { mode, .. } => ExplainNode::columns(*mode)
+                .into_iter()
+                .map(|column| {
+                    let data_type = match column {
+                        "id" | "parent" | "est_rows" | "actual_rows" => crate::types::DataType::Integer,
+                        "est_cost" | "time_ms" => crate::types::DataType::Real,
+                        _ => crate::types::DataType::Text,
+                    };
+                    (column.to_string(), data_type)
+                })
+                .collect(),
             _ => vec![("result".to_string(), crate::types::DataType::Integer)],
         }
     }
@@ -262,6 +753,19 @@ impl QueryPlan {
                     condition,
                 }
             }
+            QueryPlan::SemiJoin { left, right, keys, anti } => QueryPlan::SemiJoin {
+                left: Box::new(f((*left).clone())),
+                right: Box::new(f((*right).clone())),
+                keys,
+                anti,
+            },
+            QueryPlan::Alias { input, alias } => {
+                let new_input = Box::new(f((*input).clone()));
+                QueryPlan::Alias {
+                    input: new_input,
+                    alias,
+                }
+            }
             QueryPlan::Projection { input, columns, expressions } => {
                 let new_input = Box::new(f((*input).clone()));
                 QueryPlan::Projection {
@@ -300,6 +804,9 @@ impl QueryPlan {
                     aggregates,
                 }
             }
+            QueryPlan::Distinct { input } => QueryPlan::Distinct {
+                input: Box::new(f((*input).clone())),
+            },
             QueryPlan::Union { left, right, all } => {
                 let new_left = Box::new(f((*left).clone()));
                 let new_right = Box::new(f((*right).clone()));
@@ -355,29 +862,232 @@ impl QueryPlan {
         }
     }
 
+    /// Describe the plan as `EXPLAIN QUERY PLAN` rows: (id, parent id,
+    /// detail), parents before children
+    pub fn explain(&self) -> Vec<(i64, i64, String)> {
+        self.explain_tree()
+            .into_iter()
+            .map(|node| (node.id, node.parent, node.detail))
+            .collect()
+    }
+
+    /// Describe every operator of the plan, parents before children
+    pub fn explain_tree(&self) -> Vec<ExplainNode> {
+        self.explain_tree_with(&|plan| (plan.estimated_cost(), plan.estimated_rows()))
+    }
+
+    /// [`explain_tree`](Self::explain_tree) with the (cost, rows) estimate
+    /// of each operator given by `estimate`, e.g. from table statistics
+    pub fn explain_tree_with(&self, estimate: &dyn Fn(&QueryPlan) -> (f64, u64)) -> Vec<ExplainNode> {
+        let mut nodes = Vec::new();
+        self.explain_into(0, None, estimate, &mut nodes);
+        nodes
+    }
+
+    /// The algorithm the executor runs for this operator; `parent` is the
+    /// operator reading its output
+    fn physical_operator(&self, parent: Option<&QueryPlan>) -> &'static str {
+        match self {
+            QueryPlan::Scan { .. } => "TABLE SCAN",
+            QueryPlan::IndexScan { covering: true, .. } => "COVERING INDEX SCAN",
+            QueryPlan::IndexScan { .. } => "INDEX SCAN",
+            QueryPlan::FtsScan { .. } => "FULL-TEXT SCAN",
+            QueryPlan::ForeignScan { .. } => "FOREIGN SCAN",
+            QueryPlan::Join { condition, .. } => {
+                // Equalities between columns become hash join keys
+                let equi_join = match condition {
+                    JoinConstraint::Using(_) => true,
+                    JoinConstraint::On(condition) => crate::query::join::split_conjunction(condition)
+                        .into_iter()
+                        .any(|conjunct| match conjunct {
+                            Expression::BinaryOp { left, op: BinaryOperator::Equal, right } => {
+                                is_column(left) && is_column(right)
+                            }
+                            _ => false,
+                        }),
+                    JoinConstraint::None => false,
+                };
+                if equi_join {
+                    "HASH JOIN"
+                } else {
+                    "NESTED LOOP JOIN"
+                }
+            }
+            QueryPlan::SemiJoin { anti: false, .. } => "HASH SEMI JOIN",
+            QueryPlan::SemiJoin { anti: true, .. } => "HASH ANTI JOIN",
+            QueryPlan::Alias { .. } => "ALIAS",
+            QueryPlan::Projection { .. } => "PROJECT",
+            QueryPlan::Selection { .. } => "FILTER",
+            QueryPlan::Sort { .. } if matches!(parent, Some(QueryPlan::Limit { .. })) => "TOP-N SORT",
+            QueryPlan::Sort { .. } => "EXTERNAL SORT",
+            QueryPlan::Limit { .. } => "LIMIT",
+            QueryPlan::GroupBy { .. } => "HASH AGGREGATE",
+            QueryPlan::Distinct { .. } => "HASH DISTINCT",
+            QueryPlan::Insert { .. } => "INSERT",
+            QueryPlan::Update { .. } => "UPDATE",
+            QueryPlan::Delete { .. } => "DELETE",
+            QueryPlan::CreateTable { .. } => "CREATE TABLE",
+            QueryPlan::DropTable { .. } => "DROP TABLE",
+            QueryPlan::CreateIndex { .. } => "CREATE INDEX",
+            QueryPlan::DropIndex { .. } => "DROP INDEX",
+            QueryPlan::Analyze { .. } => "ANALYZE",
+            QueryPlan::Explain { .. } => "EXPLAIN",
+            QueryPlan::Union { .. } => "APPEND",
+            QueryPlan::Intersect { .. } => "INTERSECT",
+            QueryPlan::Except { .. } => "EXCEPT",
+        }
+    }
+
+    fn explain_into(
+        &self,
+        parent: i64,
+        parent_plan: Option<&QueryPlan>,
+        estimate: &dyn Fn(&QueryPlan) -> (f64, u64),
+        nodes: &mut Vec<ExplainNode>,
+    ) {
+        let join_list = |items: Vec<String>| items.join(", ");
+        let (detail, children): (String, Vec<&QueryPlan>) = match self {
+            QueryPlan::Scan { table, filter: None, .. } => (format!("SCAN {}", table), vec![]),
+            QueryPlan::Scan { table, filter: Some(filter), .. } => {
+                (format!("SCAN {} WHERE {}", table, filter), vec![])
+            }
+            QueryPlan::FtsScan { table, query, .. } => {
+                (format!("SEARCH {} USING FULL-TEXT INDEX (MATCH {})", table, query), vec![])
+            }
+            QueryPlan::ForeignScan { table, server, pushed, filter } => {
+                let mut detail = format!("SCAN {} VIA {}", table, server);
+                if !pushed.is_empty() {
+                    let pushed: Vec<String> = pushed.iter().map(|condition| condition.to_string()).collect();
+                    detail.push_str(&format!(" PUSHING {}", pushed.join(" AND ")));
+                }
+                if let Some(filter) = filter {
+                    detail.push_str(&format!(" WHERE {}", filter));
+                }
+                (detail, vec![])
+            }
+            QueryPlan::IndexScan { table, index, columns, lookup, covering, .. } => (
+                format!(
+                    "SEARCH {} USING {}INDEX {} {}",
+                    table,
+                    if *covering { "COVERING " } else { "" },
+                    index,
+                    lookup.describe(columns)
+                ),
+                vec![],
+            ),
+            QueryPlan::Join { left, right, join_type, condition } => {
+                let constraint = match condition {
+                    JoinConstraint::On(expr) => format!(" ON {}", expr),
+                    JoinConstraint::Using(columns) => format!(" USING ({})", columns.join(", ")),
+                    JoinConstraint::None => String::new(),
+                };
+                (format!("{:?} JOIN{}", join_type, constraint).to_uppercase(), vec![left, right])
+            }
+            QueryPlan::SemiJoin { left, right, keys, anti } => {
+                let keys = keys.iter().map(|(left, right)| format!("{} = {}", left, right)).collect::<Vec<_>>();
+                let kind = if *anti { "ANTI" } else { "SEMI" };
+                (format!("{} JOIN ON {}", kind, keys.join(" AND ")), vec![left, right])
+            }
+            QueryPlan::Alias { input, alias } => (format!("ALIAS {}", alias), vec![input]),
+            QueryPlan::Projection { input, columns, .. } => {
+                (format!("PROJECT {}", join_list(columns.clone())), vec![input])
+            }
+            QueryPlan::Selection { input, condition } => (format!("FILTER {}", condition), vec![input]),
+            QueryPlan::Sort { input, order_by } => {
+                let keys = order_by
+                    .iter()
+                    .map(|(expr, direction)| match direction {
+                        OrderDirection::Asc => expr.to_string(),
+                        OrderDirection::Desc => format!("{} DESC", expr),
+                    })
+                    .collect();
+                (format!("SORT BY {}", join_list(keys)), vec![input])
+            }
+            QueryPlan::Limit { input, count, offset } => {
+                let offset = offset.map(|o| format!(" OFFSET {}", o)).unwrap_or_default();
+                (format!("LIMIT {}{}", count, offset), vec![input])
+            }
+            QueryPlan::GroupBy { input, group_columns, aggregates } => {
+                let mut detail = "AGGREGATE".to_string();
+                if !aggregates.is_empty() {
+                    let outputs = aggregates.iter().map(AggregateFunction::output_name).collect();
+                    detail.push_str(&format!(" {}", join_list(outputs)));
+                }
+                if !group_columns.is_empty() {
+                    let keys = group_columns.iter().map(|expr| expr.to_string()).collect();
+                    detail.push_str(&format!(" GROUP BY {}", join_list(keys)));
+                }
+                (detail, vec![input])
+            }
+            QueryPlan::Distinct { input } => ("DISTINCT".to_string(), vec![input]),
+            QueryPlan::Insert { table, values, .. } => (format!("INSERT INTO {} ({} rows)", table, values.len()), vec![]),
+            QueryPlan::Update { table, .. } => (format!("UPDATE {}", table), vec![]),
+            QueryPlan::Delete { table, .. } => (format!("DELETE FROM {}", table), vec![]),
+            QueryPlan::CreateTable { table, .. } => (format!("CREATE TABLE {}", table), vec![]),
+            QueryPlan::DropTable { table, .. } => (format!("DROP TABLE {}", table), vec![]),
+            QueryPlan::CreateIndex { index } => (
+                format!("CREATE INDEX {} ON {} ({})", index.name, index.table, index.columns.join(", ")),
+                vec![],
+            ),
+            QueryPlan::DropIndex { index, .. } => (format!("DROP INDEX {}", index), vec![]),
+            QueryPlan::Analyze { table: Some(table) } => (format!("ANALYZE {}", table), vec![]),
+            QueryPlan::Analyze { table: None } => ("ANALYZE".to_string(), vec![]),
+            QueryPlan::Explain { plan, .. } => ("EXPLAIN".to_string(), vec![plan]),
+            QueryPlan::Union { left, right, all } => {
+                (if *all { "UNION ALL" } else { "UNION" }.to_string(), vec![left, right])
+            }
+            QueryPlan::Intersect { left, right } => ("INTERSECT".to_string(), vec![left, right]),
+            QueryPlan::Except { left, right } => ("EXCEPT".to_string(), vec![left, right]),
+        };
+
+        let id = nodes.len() as i64 + 1;
+        let (cost, rows) = estimate(self);
+        nodes.push(ExplainNode {
+            id,
+            parent,
+            operator: self.physical_operator(parent_plan).to_string(),
+            detail,
+            cost,
+            rows,
+        });
+        for child in children {
+            child.explain_into(id, Some(self), estimate, nodes);
+        }
+    }
+
     // Private helper method
     fn collect_tables(&self, tables: &mut Vec<String>) {
         match self {
-            QueryPlan::Scan { table, .. } | QueryPlan::IndexScan { table, .. } => {
+            QueryPlan::Scan { table, .. }
+            | QueryPlan::IndexScan { table, .. }
+            | QueryPlan::FtsScan { table, .. }
+            | QueryPlan::ForeignScan { table, .. } => {
                 tables.push(table.clone());
             }
-            QueryPlan::Join { left, right, .. } => {
+            QueryPlan::Join { left, right, .. } | QueryPlan::SemiJoin { left, right, .. } => {
                 left.collect_tables(tables);
                 right.collect_tables(tables);
             }
-            QueryPlan::Projection { input, .. }
+            QueryPlan::Alias { input, .. }
+            | QueryPlan::Projection { input, .. }
             | QueryPlan::Selection { input, .. }
             | QueryPlan::Sort { input, .. }
             | QueryPlan::Limit { input, .. }
-            | QueryPlan::GroupBy { input, .. } => {
+            | QueryPlan::GroupBy { input, .. }
+            | QueryPlan::Distinct { input } => {
                 input.collect_tables(tables);
             }
             QueryPlan::Insert { table, .. }
             | QueryPlan::Update { table, .. }
             | QueryPlan::Delete { table, .. }
-            | QueryPlan::CreateTable { table, .. } => {
+            | QueryPlan::CreateTable { table, .. }
+            | QueryPlan::DropTable { table, .. } => {
                 tables.push(table.clone());
             }
+            QueryPlan::CreateIndex { index } => tables.push(index.table.clone()),
+            QueryPlan::Analyze { table } => tables.extend(table.iter().cloned()),
+            QueryPlan::DropIndex { .. } => {}
+            QueryPlan::Explain { plan, .. } => plan.collect_tables(tables),
             QueryPlan::Union { left, right, .. }
             | QueryPlan::Intersect { left, right }
             | QueryPlan::Except { left, right } => {
@@ -387,3 +1097,81 @@ impl QueryPlan {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DataType;
+
+    fn column(name: &str, data_type: DataType) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            data_type,
+            nullable: true,
+            default: None,
+            primary_key: false,
+            unique: false,
+            collation: None,
+        }
+    }
+
+    #[test]
+    fn test_create_table_sql() {
+        let schema = TableSchema {
+            columns: vec![
+                ColumnSchema { primary_key: true, nullable: false, ..column("id", DataType::Integer) },
+                ColumnSchema {
+                    nullable: false,
+                    unique: true,
+                    collation: Some("NOCASE".to_string()),
+                    ..column("email", DataType::Text)
+                },
+                ColumnSchema { default: Some(Value::Integer(0)), ..column("score", DataType::Real) },
+                column("owner", DataType::Integer),
+            ],
+            constraints: vec![
+                TableConstraint::Unique(vec!["email".to_string(), "owner".to_string()]),
+                TableConstraint::ForeignKey {
+                    columns: vec!["owner".to_string()],
+                    foreign_table: "users".to_string(),
+                    foreign_columns: vec!["id".to_string()],
+                    on_delete: ForeignKeyAction::Cascade,
+                },
+            ],
+            fts: None,
+            foreign: None,
+        };
+        assert_eq!(
+            schema.create_sql("accounts"),
+            "CREATE TABLE accounts (\n  \
+             id INTEGER PRIMARY KEY,\n  \
+             email TEXT NOT NULL UNIQUE COLLATE NOCASE,\n  \
+             score REAL DEFAULT 0,\n  \
+             owner INTEGER,\n  \
+             UNIQUE (email, owner),\n  \
+             FOREIGN KEY (owner) REFERENCES users (id) ON DELETE CASCADE\n)"
+        );
+    }
+
+    #[test]
+    fn test_create_virtual_and_foreign_table_sql() {
+        let columns = ["title".to_string(), "body".to_string()];
+        let fts = TableSchema {
+            fts: Some(FtsOptions { tokenize: "porter".to_string() }),
+            ..TableSchema::untyped(&columns)
+        };
+        assert_eq!(fts.create_sql("docs"), "CREATE VIRTUAL TABLE docs USING fts(title, body, tokenize = 'porter')");
+
+        let foreign = TableSchema {
+            foreign: Some(ForeignOptions {
+                server: "archive".to_string(),
+                options: vec![("table".to_string(), "o'brien".to_string())],
+            }),
+            ..TableSchema::untyped(&columns)
+        };
+        assert_eq!(
+            foreign.create_sql("remote"),
+            "CREATE FOREIGN TABLE remote (\n  title,\n  body\n) SERVER archive OPTIONS (table 'o''brien')"
+        );
+    }
+}
@@ -1,19 +1,56 @@
+use crate::collation::Collation;
 use crate::error::{SqlError, SqlResult};
-use crate::parser::ast::*;
+use crate::fdw::{split_pushdown, ForeignCatalog, ForeignOptions, ForeignScan, ForeignTable};
+use crate::fts::{extract_match, FtsOptions, RANK_COLUMN};
+use crate::parser::ast::{self, *};
+use crate::query::index::{choose_index, index_candidates, IndexCatalog, IndexChoice, IndexInfo};
 use crate::query::plan::*;
+use crate::query::plan::TableConstraint;
+use crate::query::join::{conjunction, split_conjunction};
+use crate::query::stats::{CostModel, StatsCatalog};
+use crate::query::subquery::decorrelate;
 use crate::types::{Statistics, Value};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Query planner following coalgebraic optimization patterns
 #[derive(Debug)]
 pub struct QueryPlanner {
     coalgebra: QueryPlannerCoalgebra,
+    /// Table schemas, used to tell which columns a subquery reads from the
+    /// enclosing query
+    schemas: SchemaCatalog,
+    /// Servers of the foreign tables, asked which conditions they can
+    /// evaluate
+    foreign: ForeignCatalog,
 }
 
 impl QueryPlanner {
     pub fn new() -> Self {
+        Self::with_indexes(IndexCatalog::new())
+    }
+
+    /// Create a planner that considers the indexes in `indexes` for scans
+    pub fn with_indexes(indexes: IndexCatalog) -> Self {
+        Self::with_catalog(indexes, SchemaCatalog::default(), StatsCatalog::default(), ForeignCatalog::new())
+    }
+
+    /// Create a planner that also sees the schemas of the executor's
+    /// tables, so correlated subqueries can be turned into joins, their
+    /// statistics, which drive the choice of access paths and join order,
+    /// and the servers of its foreign tables
+    pub fn with_catalog(
+        indexes: IndexCatalog,
+        schemas: SchemaCatalog,
+        stats: StatsCatalog,
+        foreign: ForeignCatalog,
+    ) -> Self {
+        let mut coalgebra = QueryPlannerCoalgebra::with_indexes(indexes);
+        coalgebra.table_stats = stats;
         QueryPlanner {
-            coalgebra: QueryPlannerCoalgebra::new(),
+            coalgebra,
+            schemas,
+            foreign,
         }
     }
 
@@ -25,9 +62,22 @@ impl QueryPlanner {
             Statement::Update(update) => self.plan_update(update).await,
             Statement::Delete(delete) => self.plan_delete(delete).await,
             Statement::CreateTable(create) => self.plan_create_table(create).await,
+            Statement::CreateVirtualTable(create) => self.plan_create_virtual_table(create).await,
+            Statement::CreateForeignTable(create) => self.plan_create_foreign_table(create).await,
             Statement::DropTable(drop) => self.plan_drop_table(drop).await,
             Statement::CreateIndex(create) => self.plan_create_index(create).await,
             Statement::DropIndex(drop) => self.plan_drop_index(drop).await,
+            Statement::Analyze(analyze) => Ok(QueryPlan::Analyze {
+                table: analyze.table_name,
+            }),
+            Statement::Transaction(_) => Err(SqlError::transaction_error(
+                "BEGIN, COMMIT and ROLLBACK are run by a session, not planned",
+            )),
+            Statement::Vacuum => Err(SqlError::runtime_error("VACUUM is run by the database, not planned")),
+            Statement::Explain(explain) => Ok(QueryPlan::Explain {
+                plan: Box::new(Box::pin(self.plan_statement(*explain.statement)).await?),
+                mode: explain.mode,
+            }),
         }
     }
 
@@ -48,93 +98,239 @@ impl QueryPlanner {
 
     // Private planning methods
     async fn plan_select(&self, select: SelectStatement) -> SqlResult<QueryPlan> {
+        let needed_columns = Self::referenced_columns(&select);
         let mut plan = if let Some(from) = select.from {
-            // Start with table scan or index scan
-            let base_plan = if self.should_use_index(&from.table, &select.where_clause) {
-                QueryPlan::IndexScan {
-                    table: from.table.clone(),
-                    index: "primary".to_string(), // Simplified
-                    key: Value::Null,
-                    filter: select.where_clause.clone(),
+            // `[NOT] EXISTS` and `IN` subqueries correlated by equalities
+            // become semi joins; other subqueries run during execution
+            let mut where_clause = select.where_clause.clone();
+            let mut semi_joins = Vec::new();
+            if let Some(condition) = where_clause.take() {
+                let schemas = self.schemas.read().await;
+                let mut remaining = Vec::new();
+                for conjunct in split_conjunction(&condition) {
+                    match decorrelate(conjunct, &schemas) {
+                        Some(decorrelated) => semi_joins.push(decorrelated),
+                        None => remaining.push(conjunct.clone()),
+                    }
+                }
+                where_clause = conjunction(remaining);
+            }
+
+            // MATCH conditions on a full-text table are answered by its index
+            let mut full_text = None;
+            if let Some(schema) = self.schemas.read().await.get(&from.table).filter(|schema| schema.fts.is_some()) {
+                if let Some(condition) = where_clause.take() {
+                    let names: Vec<&str> = std::iter::once(from.table.as_str()).chain(from.alias.as_deref()).collect();
+                    let (query, rest) = extract_match(&condition, &names, &schema.column_names())?;
+                    full_text = query;
+                    where_clause = rest;
                 }
+            }
+
+            // With joins the WHERE clause may reference any joined table, and
+            // with an alias it may use the alias, so it is applied above the
+            // joins and alias instead of on the base scan. Its conjuncts that
+            // read a single table are applied to that table below the joins.
+            let (mut base_filter, mut join_filter) = if from.joins.is_empty() && from.alias.is_none() {
+                (where_clause, None)
             } else {
-                QueryPlan::Scan {
+                (None, where_clause)
+            };
+            let mut pushed = vec![None; from.joins.len() + 1];
+            if let Some(condition) = join_filter.take_if(|_| !from.joins.is_empty()) {
+                (pushed, join_filter) = self.push_down_filters(&from, condition).await;
+            }
+            let mut base_pushed = pushed.remove(0);
+            if base_pushed.is_some() && from.alias.is_none() && full_text.is_none() {
+                base_filter = base_pushed.take();
+            }
+
+            // Start with table scan or index scan
+            let base_plan = match full_text {
+                Some(query) => QueryPlan::FtsScan {
                     table: from.table.clone(),
-                    filter: select.where_clause.clone(),
-                    projection: None,
+                    query,
+                    filter: base_filter,
+                },
+                None => {
+                    self.coalgebra
+                        .access_path(&from.table, base_filter, needed_columns.as_deref())
+                        .await
                 }
             };
 
             // Add joins
-            let mut current_plan = base_plan;
-            for join in from.joins {
-                current_plan = QueryPlan::Join {
+            let single_table = from.joins.is_empty();
+            let base_name = from.alias.clone().unwrap_or_else(|| from.table.clone());
+            let base_plan = Self::with_filter(Self::with_alias(base_plan, from.alias), base_pushed);
+            let mut current_plan = self.plan_joins(base_plan, base_name, from.joins, pushed).await;
+            for semi_join in semi_joins {
+                current_plan = QueryPlan::SemiJoin {
                     left: Box::new(current_plan),
-                    right: Box::new(QueryPlan::Scan {
-                        table: join.table,
-                        filter: None,
-                        projection: None,
-                    }),
-                    join_type: join.join_type,
-                    condition: join.condition,
+                    right: Box::new(Box::pin(self.plan_select(semi_join.inner)).await?),
+                    keys: semi_join.keys,
+                    anti: semi_join.anti,
                 };
             }
+            let current_plan = self.push_down_foreign(current_plan, join_filter.as_ref(), single_table).await;
 
-            current_plan
+            match join_filter {
+                Some(condition) => QueryPlan::Selection {
+                    input: Box::new(current_plan),
+                    condition,
+                },
+                None => current_plan,
+            }
         } else {
             // SELECT without FROM (e.g., SELECT 1)
             return Err(SqlError::parse_error("SELECT without FROM not supported"));
         };
 
-        // Add projection
-        if !matches!(select.columns.as_slice(), [SelectColumn::Wildcard]) {
-            let (columns, expressions) = self.extract_projection_info(&select.columns)?;
-            plan = QueryPlan::Projection {
-                input: Box::new(plan),
-                columns,
-                expressions,
-            };
-        }
+        // Projection names come from the expressions as written, before
+        // aggregates are rewritten into references to the GroupBy output
+        let mut projection = if matches!(select.columns.as_slice(), [SelectColumn::Wildcard]) {
+            None
+        } else {
+            Some(self.extract_projection_info(&select.columns)?)
+        };
+        let mut having = select.having;
+        let mut order_by: Vec<(Expression, OrderDirection)> = select
+            .order_by
+            .unwrap_or_default()
+            .into_iter()
+            .map(|clause| (clause.expression, clause.direction))
+            .collect();
 
-        // Add GROUP BY
-        if let Some(group_columns) = select.group_by {
+        // Add GROUP BY when grouping is requested or aggregates are used
+        let uses_aggregates = projection
+            .as_ref()
+            .is_some_and(|(_, expressions)| expressions.iter().any(AggregateFunction::contains_aggregate))
+            || having.as_ref().is_some_and(AggregateFunction::contains_aggregate);
+        if select.group_by.is_some() || uses_aggregates {
+            let group_columns = select.group_by.unwrap_or_default();
+            let mut aggregates = Vec::new();
+            if let Some((_, expressions)) = projection.as_mut() {
+                for expr in expressions.iter_mut() {
+                    *expr = Self::rewrite_grouped(expr, &group_columns, &mut aggregates)?;
+                }
+            }
+            if let Some(condition) = having.as_mut() {
+                *condition = Self::rewrite_grouped(condition, &group_columns, &mut aggregates)?;
+            }
+            for (expr, _) in order_by.iter_mut() {
+                *expr = Self::rewrite_grouped(expr, &group_columns, &mut aggregates)?;
+            }
             plan = QueryPlan::GroupBy {
                 input: Box::new(plan),
-                group_columns: group_columns.into_iter().map(|_| "col".to_string()).collect(),
-                aggregates: vec![], // Simplified
+                group_columns,
+                aggregates,
             };
+        } else if having.is_some() {
+            return Err(SqlError::parse_error("HAVING requires GROUP BY or an aggregate"));
         }
 
         // Add HAVING
-        if let Some(having) = select.having {
+        if let Some(having) = having {
             plan = QueryPlan::Selection {
                 input: Box::new(plan),
                 condition: having,
             };
         }
 
-        // Add ORDER BY
-        if let Some(order_by) = select.order_by {
-            let order_spec: Vec<(String, OrderDirection)> = order_by
-                .into_iter()
-                .map(|clause| ("col".to_string(), clause.direction))
-                .collect();
-            plan = QueryPlan::Sort {
+        // Without DISTINCT, ORDER BY and LIMIT run below the projection so
+        // ORDER BY can use columns that are not selected; result aliases and
+        // positions are replaced by the expressions they name
+        if !select.distinct {
+            if let Some((columns, expressions)) = &projection {
+                order_by = order_by
+                    .into_iter()
+                    .map(|(expr, direction)| {
+                        Ok((Self::resolve_output_reference(expr, columns, expressions)?, direction))
+                    })
+                    .collect::<SqlResult<_>>()?;
+            }
+            plan = Self::with_sort_and_limit(plan, order_by, select.limit);
+            if let Some((columns, expressions)) = projection {
+                plan = QueryPlan::Projection {
+                    input: Box::new(plan),
+                    columns,
+                    expressions,
+                };
+            }
+            return Ok(plan);
+        }
+
+        // With DISTINCT they sort the deduplicated result, so ORDER BY terms
+        // that repeat a selected expression refer to its output column
+        if let Some((columns, expressions)) = projection {
+            for (expr, _) in order_by.iter_mut() {
+                if let Some(index) = expressions.iter().position(|selected| selected == expr) {
+                    *expr = Expression::Column(columns[index].clone());
+                }
+            }
+            plan = QueryPlan::Projection {
                 input: Box::new(plan),
-                order_by: order_spec,
+                columns,
+                expressions,
             };
         }
+        plan = QueryPlan::Distinct {
+            input: Box::new(plan),
+        };
+        Ok(Self::with_sort_and_limit(plan, order_by, select.limit))
+    }
 
-        // Add LIMIT
-        if let Some(limit) = select.limit {
+    fn with_sort_and_limit(
+        mut plan: QueryPlan,
+        order_by: Vec<(Expression, OrderDirection)>,
+        limit: Option<LimitClause>,
+    ) -> QueryPlan {
+        if !order_by.is_empty() {
+            plan = QueryPlan::Sort {
+                input: Box::new(plan),
+                order_by,
+            };
+        }
+        if let Some(limit) = limit {
             plan = QueryPlan::Limit {
                 input: Box::new(plan),
                 count: limit.count,
                 offset: limit.offset,
             };
         }
+        plan
+    }
 
-        Ok(plan)
+    /// Map an ORDER BY term naming a result column, by alias or 1-based
+    /// position, to the expression that computes it
+    fn resolve_output_reference(
+        expr: Expression,
+        columns: &[String],
+        expressions: &[Expression],
+    ) -> SqlResult<Expression> {
+        match &expr {
+            Expression::Literal(Value::Integer(position)) => {
+                if *position < 1 || *position as usize > expressions.len() {
+                    return Err(SqlError::parse_error(format!(
+                        "ORDER BY term {} out of range, should be between 1 and {}",
+                        position,
+                        expressions.len()
+                    )));
+                }
+                Ok(expressions[*position as usize - 1].clone())
+            }
+            Expression::Column(name) => Ok(columns
+                .iter()
+                .position(|column| column.eq_ignore_ascii_case(name))
+                .map(|index| expressions[index].clone())
+                .unwrap_or(expr)),
+            // `ORDER BY alias COLLATE name`
+            Expression::Collate { expr: inner, collation } => Ok(Expression::Collate {
+                expr: Box::new(Self::resolve_output_reference((**inner).clone(), columns, expressions)?),
+                collation: collation.clone(),
+            }),
+            _ => Ok(expr),
+        }
     }
 
     async fn plan_insert(&self, insert: InsertStatement) -> SqlResult<QueryPlan> {
@@ -177,21 +373,82 @@ impl QueryPlanner {
     }
 
     async fn plan_create_table(&self, create: CreateTableStatement) -> SqlResult<QueryPlan> {
-        let schema = TableSchema {
-            columns: create
-                .columns
-                .into_iter()
-                .map(|col| ColumnSchema {
-                    name: col.name,
-                    data_type: col.data_type,
+        let mut constraints = Vec::new();
+        let mut columns = create
+            .columns
+            .into_iter()
+            .map(|col| {
+                // Column-level CHECK and REFERENCES become table constraints
+                for constraint in &col.constraints {
+                    match constraint {
+                        ColumnConstraint::Check(expr) => constraints.push(TableConstraint::Check(expr.clone())),
+                        ColumnConstraint::ForeignKey { table, column, on_delete } => {
+                            constraints.push(TableConstraint::ForeignKey {
+                                columns: vec![col.name.clone()],
+                                foreign_table: table.clone(),
+                                foreign_columns: column.iter().cloned().collect(),
+                                on_delete: *on_delete,
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+                ColumnSchema {
                     nullable: !col.constraints.contains(&ColumnConstraint::NotNull),
-                    default: None,
+                    default: col.constraints.iter().find_map(|constraint| match constraint {
+                        ColumnConstraint::Default(value) => Some(value.clone()),
+                        _ => None,
+                    }),
                     primary_key: col.constraints.contains(&ColumnConstraint::PrimaryKey),
                     unique: col.constraints.contains(&ColumnConstraint::Unique),
-                })
-                .collect(),
-            constraints: vec![], // Simplified
+                    collation: col.constraints.iter().find_map(|constraint| match constraint {
+                        ColumnConstraint::Collate(name) => Some(name.clone()),
+                        _ => None,
+                    }),
+                    name: col.name,
+                    data_type: col.data_type,
+                }
+            })
+            .collect::<Vec<_>>();
+        // Unknown collations are rejected now rather than when compared
+        for column in &mut columns {
+            if let Some(name) = &column.collation {
+                column.collation = Some(Collation::find(name)?.name().to_string());
+            }
+        }
+
+        for constraint in create.constraints {
+            constraints.push(match constraint {
+                ast::TableConstraint::PrimaryKey(columns) => TableConstraint::PrimaryKey(columns),
+                ast::TableConstraint::Unique(columns) => TableConstraint::Unique(columns),
+                ast::TableConstraint::Check(expr) => TableConstraint::Check(expr),
+                ast::TableConstraint::ForeignKey {
+                    columns,
+                    foreign_table,
+                    foreign_columns,
+                    on_delete,
+                } => TableConstraint::ForeignKey {
+                    columns,
+                    foreign_table,
+                    foreign_columns,
+                    on_delete,
+                },
+            });
+        }
+
+        let schema = TableSchema {
+            columns,
+            constraints,
+            fts: None,
+            foreign: None,
         };
+        let foreign_keys = schema.foreign_keys(|_| None);
+        let key_columns = schema.unique_keys().into_iter().flatten();
+        for column in key_columns.chain(foreign_keys.into_iter().flat_map(|key| key.columns)) {
+            if schema.position(&column).is_none() {
+                return Err(SqlError::column_not_found(column));
+            }
+        }
 
         Ok(QueryPlan::CreateTable {
             table: create.table_name,
@@ -199,25 +456,343 @@ impl QueryPlanner {
         })
     }
 
-    async fn plan_drop_table(&self, _drop: DropTableStatement) -> SqlResult<QueryPlan> {
-        // Simplified implementation
-        Err(SqlError::runtime_error("DROP TABLE not implemented"))
+    /// A foreign table: its columns may be NOT NULL and have a DEFAULT or
+    /// COLLATE, and its server checks the options
+    async fn plan_create_foreign_table(&self, create: CreateForeignTableStatement) -> SqlResult<QueryPlan> {
+        let mut columns = Vec::new();
+        for definition in create.columns {
+            let mut column = ColumnSchema {
+                name: definition.name,
+                data_type: definition.data_type,
+                nullable: true,
+                default: None,
+                primary_key: false,
+                unique: false,
+                collation: None,
+            };
+            for constraint in definition.constraints {
+                match constraint {
+                    ColumnConstraint::NotNull => column.nullable = false,
+                    ColumnConstraint::Default(value) => column.default = Some(value),
+                    ColumnConstraint::Collate(name) => column.collation = Some(Collation::find(&name)?.name().to_string()),
+                    _ => {
+                        return Err(SqlError::schema_error(format!(
+                            "foreign table column {} can only be NOT NULL, DEFAULT or COLLATE",
+                            column.name
+                        )))
+                    }
+                }
+            }
+            columns.push(column);
+        }
+
+        let options = ForeignOptions {
+            server: create.server.to_ascii_lowercase(),
+            options: create.options,
+        };
+        let table = ForeignTable::new(create.table_name.clone(), columns.clone(), &options);
+        self.foreign.get(&options.server).await?.validate(&table)?;
+        let mut schema = TableSchema::untyped(&[]);
+        schema.columns = columns;
+        schema.foreign = Some(options);
+        Ok(QueryPlan::CreateTable {
+            table: create.table_name,
+            schema,
+        })
     }
 
-    async fn plan_create_index(&self, _create: CreateIndexStatement) -> SqlResult<QueryPlan> {
-        // Simplified implementation
-        Err(SqlError::runtime_error("CREATE INDEX not implemented"))
+    /// Read the foreign tables of `plan` from their servers, which are
+    /// sent the conditions of their scan's filter and of `condition` (which
+    /// every output row of `plan` must satisfy) that they can evaluate.
+    /// `condition` stays in the plan; with a `single_table` its columns
+    /// need no qualifiers.
+    async fn push_down_foreign(
+        &self,
+        plan: QueryPlan,
+        condition: Option<&Expression>,
+        single_table: bool,
+    ) -> QueryPlan {
+        let schemas = self.schemas.read().await;
+        if !schemas.values().any(|schema| schema.foreign.is_some()) {
+            return plan;
+        }
+        let servers = self.foreign.servers().await;
+        let conjuncts: Vec<Expression> = condition
+            .map(|condition| split_conjunction(condition).into_iter().cloned().collect())
+            .unwrap_or_default();
+        push_down_foreign(plan, &conjuncts, None, single_table, &schemas, &servers)
     }
 
-    async fn plan_drop_index(&self, _drop: DropIndexStatement) -> SqlResult<QueryPlan> {
-        // Simplified implementation
-        Err(SqlError::runtime_error("DROP INDEX not implemented"))
+    /// A full-text table: its columns are the module's column arguments,
+    /// and `tokenize` picks the tokenizer
+    async fn plan_create_virtual_table(&self, create: CreateVirtualTableStatement) -> SqlResult<QueryPlan> {
+        if !matches!(create.module.to_ascii_lowercase().as_str(), "fts" | "fts5") {
+            return Err(SqlError::schema_error(format!("no such module: {}", create.module)));
+        }
+        let mut columns = Vec::new();
+        let mut options = FtsOptions::default();
+        for argument in create.arguments {
+            match argument {
+                ModuleArgument::Column(name) if name.eq_ignore_ascii_case(RANK_COLUMN) => {
+                    return Err(SqlError::schema_error(format!("reserved fts column name: {}", name)));
+                }
+                ModuleArgument::Column(name) => columns.push(name),
+                ModuleArgument::Option { name, value } if name.eq_ignore_ascii_case("tokenize") => {
+                    options.tokenize = value;
+                }
+                ModuleArgument::Option { name, .. } => {
+                    return Err(SqlError::schema_error(format!("unrecognized fts option: {}", name)));
+                }
+            }
+        }
+        if columns.is_empty() {
+            return Err(SqlError::schema_error("A full-text table needs at least one column"));
+        }
+        options.tokenizer()?;
+
+        let mut schema = TableSchema::untyped(&columns);
+        for column in &mut schema.columns {
+            column.data_type = crate::types::DataType::Text;
+        }
+        schema.fts = Some(options);
+        Ok(QueryPlan::CreateTable {
+            table: create.table_name,
+            schema,
+        })
+    }
+
+    async fn plan_drop_table(&self, drop: DropTableStatement) -> SqlResult<QueryPlan> {
+        Ok(QueryPlan::DropTable {
+            table: drop.table_name,
+            if_exists: drop.if_exists,
+        })
+    }
+
+    async fn plan_create_index(&self, create: CreateIndexStatement) -> SqlResult<QueryPlan> {
+        Ok(QueryPlan::CreateIndex {
+            index: IndexInfo::new(create.index_name, create.table_name, create.columns, create.unique),
+        })
+    }
+
+    async fn plan_drop_index(&self, drop: DropIndexStatement) -> SqlResult<QueryPlan> {
+        Ok(QueryPlan::DropIndex {
+            index: drop.index_name,
+            if_exists: drop.if_exists,
+        })
+    }
+
+    /// Join the FROM tables in the order written, unless every table has
+    /// been analyzed and all joins are inner joins with ON conditions. Then
+    /// the join order is chosen greedily: start from the smallest table and
+    /// repeatedly join the table, connected by an ON condition if possible,
+    /// that gives the smallest estimated result. Each ON conjunct is
+    /// applied at the first join where the tables it reads are available,
+    /// and a projection restores the column order of `SELECT *`.
+    async fn plan_joins(
+        &self,
+        base: QueryPlan,
+        base_name: String,
+        joins: Vec<JoinClause>,
+        filters: Vec<Option<Expression>>,
+    ) -> QueryPlan {
+        // Each joined table with its alias and the filters pushed down to it
+        let rights: Vec<QueryPlan> = joins
+            .iter()
+            .zip(filters)
+            .map(|(join, filter)| {
+                let scan = QueryPlan::Scan {
+                    table: join.table.clone(),
+                    filter: None,
+                    projection: None,
+                };
+                Self::with_filter(Self::with_alias(scan, join.alias.clone()), filter)
+            })
+            .collect();
+        let written_order = |joins: Vec<JoinClause>| {
+            joins.into_iter().zip(rights.clone()).fold(base.clone(), |left, (join, right)| QueryPlan::Join {
+                left: Box::new(left),
+                right: Box::new(right),
+                join_type: join.join_type,
+                condition: join.constraint,
+            })
+        };
+        let reorderable = joins.len() >= 2
+            && joins
+                .iter()
+                .all(|join| join.join_type == JoinType::Inner && matches!(join.constraint, JoinConstraint::On(_)));
+        if !reorderable || matches!(base, QueryPlan::FtsScan { .. }) {
+            return written_order(joins);
+        }
+
+        // The inputs (visible name, table, plan) and the ON conjuncts
+        let mut inputs = vec![(base_name, base_table(&base).to_string(), base.clone())];
+        let mut conjuncts = Vec::new();
+        for (join, right) in joins.iter().zip(&rights) {
+            let name = join.alias.clone().unwrap_or_else(|| join.table.clone());
+            inputs.push((name, join.table.clone(), right.clone()));
+            if let JoinConstraint::On(condition) = &join.constraint {
+                conjuncts.extend(split_conjunction(condition).into_iter().cloned());
+            }
+        }
+
+        let schemas = self.schemas.read().await;
+        let stats = self.coalgebra.table_stats.read().await;
+        let model = CostModel::new(&stats);
+        let mut names: Vec<&str> = inputs.iter().map(|(name, _, _)| name.as_str()).collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        let known = names.len() == inputs.len()
+            && inputs
+                .iter()
+                .all(|(_, table, _)| model.table(table).is_some() && schemas.contains_key(table));
+        // Which inputs each conjunct reads
+        let sources: Vec<(&str, &str)> = inputs.iter().map(|(name, table, _)| (name.as_str(), table.as_str())).collect();
+        let readers: Option<Vec<Vec<usize>>> = conjuncts
+            .iter()
+            .map(|conjunct| conjunct_inputs(conjunct, &sources, &schemas))
+            .collect();
+        let (true, Some(readers)) = (known, readers) else {
+            return written_order(joins);
+        };
+
+        let estimate = |plan: &QueryPlan| model.estimate(plan).rows;
+        let start = (0..inputs.len())
+            .min_by(|a, b| estimate(&inputs[*a].2).total_cmp(&estimate(&inputs[*b].2)))
+            .unwrap_or(0);
+        let mut order = vec![start];
+        let mut applied = vec![false; conjuncts.len()];
+        let mut plan = inputs[start].2.clone();
+        while order.len() < inputs.len() {
+            let mut best: Option<(bool, f64, usize, QueryPlan, Vec<usize>)> = None;
+            for candidate in (0..inputs.len()).filter(|input| !order.contains(input)) {
+                let usable: Vec<usize> = (0..conjuncts.len())
+                    .filter(|c| !applied[*c])
+                    .filter(|c| readers[*c].iter().all(|input| *input == candidate || order.contains(input)))
+                    .collect();
+                let connected = usable.iter().any(|c| readers[*c].contains(&candidate));
+                let condition = match conjunction(usable.iter().map(|c| conjuncts[*c].clone()).collect()) {
+                    Some(condition) => JoinConstraint::On(condition),
+                    None => JoinConstraint::None,
+                };
+                let joined = QueryPlan::Join {
+                    left: Box::new(plan.clone()),
+                    right: Box::new(inputs[candidate].2.clone()),
+                    join_type: if condition == JoinConstraint::None { JoinType::Cross } else { JoinType::Inner },
+                    condition,
+                };
+                let rows = estimate(&joined);
+                // Joins along an ON condition beat cross products
+                let better = best.as_ref().is_none_or(|(best_connected, best_rows, ..)| {
+                    (connected && !best_connected) || (connected == *best_connected && rows < *best_rows)
+                });
+                if better {
+                    best = Some((connected, rows, candidate, joined, usable));
+                }
+            }
+            let Some((_, _, candidate, joined, usable)) = best else {
+                break;
+            };
+            for c in usable {
+                applied[c] = true;
+            }
+            order.push(candidate);
+            plan = joined;
+        }
+        if order.iter().copied().eq(0..inputs.len()) {
+            return written_order(joins);
+        }
+
+        // Put the columns back in the order the tables were written
+        let (columns, expressions) = inputs
+            .iter()
+            .flat_map(|(name, table, _)| {
+                schemas[table].column_names().into_iter().map(move |column| {
+                    let expr = Expression::QualifiedColumn {
+                        table: name.clone(),
+                        column: column.clone(),
+                    };
+                    (column, expr)
+                })
+            })
+            .unzip();
+        QueryPlan::Projection {
+            input: Box::new(plan),
+            columns,
+            expressions,
+        }
+    }
+
+    /// Split a WHERE clause over joined tables into the filters of each
+    /// table, the one in FROM first, and the rest. A conjunct goes to a
+    /// table when it reads only that table and no join pads the table with
+    /// NULLs, which the WHERE clause must see; foreign tables get none, so
+    /// conditions still reach their servers.
+    async fn push_down_filters(
+        &self,
+        from: &FromClause,
+        condition: Expression,
+    ) -> (Vec<Option<Expression>>, Option<Expression>) {
+        let schemas = self.schemas.read().await;
+        let sources: Vec<(&str, &str)> = std::iter::once((&from.table, &from.alias))
+            .chain(from.joins.iter().map(|join| (&join.table, &join.alias)))
+            .map(|(table, alias)| (alias.as_deref().unwrap_or(table), table.as_str()))
+            .collect();
+        let padded = |input: usize| {
+            let own = input > 0 && matches!(from.joins[input - 1].join_type, JoinType::Left | JoinType::Full);
+            own || from.joins[input..]
+                .iter()
+                .any(|join| matches!(join.join_type, JoinType::Right | JoinType::Full))
+        };
+        let foreign = |input: usize| schemas.get(sources[input].1).is_some_and(|schema| schema.foreign.is_some());
+
+        let mut filters = vec![Vec::new(); sources.len()];
+        let mut rest = Vec::new();
+        for conjunct in split_conjunction(&condition) {
+            match conjunct_inputs(conjunct, &sources, &schemas).as_deref() {
+                Some(&[input]) if !padded(input) && !foreign(input) => filters[input].push(conjunct.clone()),
+                _ => rest.push(conjunct.clone()),
+            }
+        }
+        (filters.into_iter().map(conjunction).collect(), conjunction(rest))
     }
 
     // Helper methods
-    fn should_use_index(&self, _table: &str, _where_clause: &Option<Expression>) -> bool {
-        // Simplified index selection logic
-        false
+    fn with_alias(plan: QueryPlan, alias: Option<String>) -> QueryPlan {
+        match alias {
+            Some(alias) => QueryPlan::Alias {
+                input: Box::new(plan),
+                alias,
+            },
+            None => plan,
+        }
+    }
+
+    fn with_filter(plan: QueryPlan, filter: Option<Expression>) -> QueryPlan {
+        match filter {
+            Some(condition) => QueryPlan::Selection {
+                input: Box::new(plan),
+                condition,
+            },
+            None => plan,
+        }
+    }
+
+    /// Every column a SELECT reads, or `None` if it selects `*`
+    fn referenced_columns(select: &SelectStatement) -> Option<Vec<String>> {
+        // Columns read inside subqueries are not tracked
+        let expressions = select.expressions();
+        if select.columns.contains(&SelectColumn::Wildcard) || expressions.iter().any(|expr| expr.has_subquery()) {
+            return None;
+        }
+
+        let mut names: Vec<String> = expressions
+            .into_iter()
+            .flat_map(|expr| expr.column_names())
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        Some(names)
     }
 
     fn extract_projection_info(
@@ -233,9 +808,11 @@ impl QueryPlanner {
                     return Err(SqlError::runtime_error("Wildcard in projection extraction"));
                 }
                 SelectColumn::Expression { expr, alias } => {
-                    let name = alias
-                        .clone()
-                        .unwrap_or_else(|| format!("col_{}", column_names.len()));
+                    let name = alias.clone().unwrap_or_else(|| match expr {
+                        Expression::Column(name) => name.clone(),
+                        Expression::QualifiedColumn { column, .. } => column.clone(),
+                        other => other.to_string(),
+                    });
                     column_names.push(name);
                     expressions.push(expr.clone());
                 }
@@ -245,6 +822,64 @@ impl QueryPlanner {
         Ok((column_names, expressions))
     }
 
+    /// Replace aggregate calls (collecting them into `aggregates`) and
+    /// computed group keys with references to the GroupBy output columns
+    fn rewrite_grouped(
+        expr: &Expression,
+        group_columns: &[Expression],
+        aggregates: &mut Vec<AggregateFunction>,
+    ) -> SqlResult<Expression> {
+        if let Some(aggregate) = AggregateFunction::from_expression(expr)? {
+            if aggregate.argument().is_some_and(AggregateFunction::contains_aggregate) {
+                return Err(SqlError::parse_error(format!(
+                    "Nested aggregate in {}",
+                    expr
+                )));
+            }
+            let name = aggregate.output_name();
+            if !aggregates.contains(&aggregate) {
+                aggregates.push(aggregate);
+            }
+            return Ok(Expression::Column(name));
+        }
+        if group_columns.contains(expr) {
+            return Ok(group_output_reference(expr));
+        }
+
+        let mut rewrite = |expr: &Expression| Self::rewrite_grouped(expr, group_columns, aggregates);
+        Ok(match expr {
+            Expression::BinaryOp { left, op, right } => Expression::BinaryOp {
+                left: Box::new(rewrite(left)?),
+                op: op.clone(),
+                right: Box::new(rewrite(right)?),
+            },
+            Expression::UnaryOp { op, operand } => Expression::UnaryOp {
+                op: op.clone(),
+                operand: Box::new(rewrite(operand)?),
+            },
+            Expression::IsNull(inner) => Expression::IsNull(Box::new(rewrite(inner)?)),
+            Expression::IsNotNull(inner) => Expression::IsNotNull(Box::new(rewrite(inner)?)),
+            Expression::In { expr, list } => Expression::In {
+                expr: Box::new(rewrite(expr)?),
+                list: list.iter().map(&mut rewrite).collect::<SqlResult<_>>()?,
+            },
+            Expression::Between { expr, low, high } => Expression::Between {
+                expr: Box::new(rewrite(expr)?),
+                low: Box::new(rewrite(low)?),
+                high: Box::new(rewrite(high)?),
+            },
+            Expression::Function { name, args } => Expression::Function {
+                name: name.clone(),
+                args: args.iter().map(&mut rewrite).collect::<SqlResult<_>>()?,
+            },
+            Expression::InSubquery { expr, subquery } => Expression::InSubquery {
+                expr: Box::new(rewrite(expr)?),
+                subquery: subquery.clone(),
+            },
+            other => other.clone(),
+        })
+    }
+
     fn evaluate_literal_expression(&self, expr: Expression) -> SqlResult<Value> {
         match expr {
             Expression::Literal(value) => Ok(value),
@@ -266,13 +901,66 @@ impl Default for QueryPlanner {
 pub struct QueryPlannerCoalgebra {
     pub statistics: Statistics,
     cost_function: fn(&QueryPlan, &Statistics) -> f64,
+    indexes: IndexCatalog,
+    /// Statistics gathered by ANALYZE
+    table_stats: StatsCatalog,
 }
 
 impl QueryPlannerCoalgebra {
     pub fn new() -> Self {
+        Self::with_indexes(IndexCatalog::new())
+    }
+
+    pub fn with_indexes(indexes: IndexCatalog) -> Self {
         QueryPlannerCoalgebra {
             statistics: Statistics::empty(),
             cost_function: Self::default_cost_function,
+            indexes,
+            table_stats: StatsCatalog::default(),
+        }
+    }
+
+    /// Choose between a full scan and an index scan for a filtered read of
+    /// `table`; `needed` lists the columns the query reads (`None` for all).
+    /// With statistics for the table the cheapest access path wins;
+    /// without, the index that matches the most of the filter.
+    pub async fn access_path(
+        &self,
+        table: &str,
+        filter: Option<Expression>,
+        needed: Option<&[String]>,
+    ) -> QueryPlan {
+        let scan = QueryPlan::Scan {
+            table: table.to_string(),
+            filter: filter.clone(),
+            projection: None,
+        };
+        let Some(condition) = &filter else {
+            return scan;
+        };
+        let indexes = self.indexes.indexes_on(table).await;
+        let index_scan = |choice: IndexChoice| QueryPlan::IndexScan {
+            table: table.to_string(),
+            index: choice.index.name,
+            columns: choice.index.columns,
+            lookup: choice.lookup,
+            filter: filter.clone(),
+            covering: choice.covering,
+        };
+
+        let stats = self.table_stats.read().await;
+        if stats.contains_key(table) {
+            let model = CostModel::new(&stats);
+            return index_candidates(&indexes, condition, needed)
+                .into_iter()
+                .map(|(_, choice)| index_scan(choice))
+                .chain(std::iter::once(scan))
+                .min_by(|a, b| model.estimate(a).cost.total_cmp(&model.estimate(b).cost))
+                .expect("a full scan is always possible");
+        }
+        match choose_index(&indexes, condition, needed) {
+            Some(choice) => index_scan(choice),
+            None => scan,
         }
     }
 
@@ -283,17 +971,12 @@ impl QueryPlannerCoalgebra {
         match plan {
             QueryPlan::Scan { table, filter, projection } => {
                 // Check if index scan is better
-                if let Some(index_cost) = self.check_index_scan(&table, filter.as_ref()) {
-                    if index_cost < cost {
-                        return Ok(QueryPlan::IndexScan {
-                            table,
-                            index: "idx_primary".to_string(),
-                            key: Value::Null,
-                            filter,
-                        });
+                match self.access_path(&table, filter.clone(), None).await {
+                    index_scan @ QueryPlan::IndexScan { .. } if index_scan.estimated_cost() < cost => {
+                        Ok(index_scan)
                     }
+                    _ => Ok(QueryPlan::Scan { table, filter, projection }),
                 }
-                Ok(QueryPlan::Scan { table, filter, projection })
             }
             QueryPlan::Join { left, right, join_type, condition } => {
                 // Choose optimal join strategy
@@ -314,6 +997,12 @@ impl QueryPlannerCoalgebra {
                     condition,
                 })
             }
+            QueryPlan::SemiJoin { left, right, keys, anti } => Ok(QueryPlan::SemiJoin {
+                left: Box::new(Box::pin(self.optimize_plan(*left)).await?),
+                right: Box::new(Box::pin(self.optimize_plan(*right)).await?),
+                keys,
+                anti,
+            }),
             QueryPlan::Projection { input, columns, expressions } => {
                 let optimized_input = Box::new(Box::pin(self.optimize_plan(*input)).await?);
                 Ok(QueryPlan::Projection {
@@ -344,27 +1033,70 @@ impl QueryPlannerCoalgebra {
                     offset,
                 })
             }
+            QueryPlan::GroupBy { input, group_columns, aggregates } => {
+                let optimized_input = Box::pin(self.optimize_plan(*input)).await?;
+                Ok(Self::push_aggregate_below_sort(optimized_input, group_columns, aggregates))
+            }
+            QueryPlan::Distinct { input } => {
+                let optimized_input = Box::new(Box::pin(self.optimize_plan(*input)).await?);
+                Ok(QueryPlan::Distinct {
+                    input: optimized_input,
+                })
+            }
             other => Ok(other),
         }
     }
 
-    fn default_cost_function(plan: &QueryPlan, _statistics: &Statistics) -> f64 {
-        plan.estimated_cost()
+    /// Hash aggregation does not preserve input order, so sorting before it
+    /// is wasted work. A sort on group keys is moved above the aggregate,
+    /// where it orders far fewer rows; any other sort is dropped.
+    fn push_aggregate_below_sort(
+        input: QueryPlan,
+        group_columns: Vec<Expression>,
+        aggregates: Vec<AggregateFunction>,
+    ) -> QueryPlan {
+        match input {
+            QueryPlan::Sort { input, order_by } => {
+                let on_group_keys = order_by.iter().all(|(expr, _)| group_columns.contains(expr));
+                let order_by: Vec<_> = order_by
+                    .iter()
+                    .map(|(expr, direction)| (group_output_reference(expr), direction.clone()))
+                    .collect();
+                let grouped = QueryPlan::GroupBy {
+                    input,
+                    group_columns,
+                    aggregates,
+                };
+                if on_group_keys {
+                    QueryPlan::Sort {
+                        input: Box::new(grouped),
+                        order_by,
+                    }
+                } else {
+                    grouped
+                }
+            }
+            input => QueryPlan::GroupBy {
+                input: Box::new(input),
+                group_columns,
+                aggregates,
+            },
+        }
     }
 
-    fn check_index_scan(&self, _table: &str, _filter: Option<&Expression>) -> Option<f64> {
-        // Simplified index cost estimation
-        Some(50.0)
+    fn default_cost_function(plan: &QueryPlan, _statistics: &Statistics) -> f64 {
+        plan.estimated_cost()
     }
 
     fn choose_join_strategy(
         &self,
         _left: &QueryPlan,
         _right: &QueryPlan,
-        _condition: &Expression,
+        _condition: &JoinConstraint,
         _join_type: JoinType,
     ) -> JoinStrategy {
-        // Simplified join strategy selection
+        // Simplified join strategy selection; the executor picks hash join
+        // once column ownership is known
         JoinStrategy::NestedLoop
     }
 }
@@ -375,6 +1107,138 @@ impl Default for QueryPlannerCoalgebra {
     }
 }
 
+/// How a group key is referenced above the GroupBy: plain columns keep
+/// their name, computed keys are named by their SQL text
+fn group_output_reference(expr: &Expression) -> Expression {
+    match expr {
+        Expression::Column(_) | Expression::QualifiedColumn { .. } => expr.clone(),
+        other => Expression::Column(other.to_string()),
+    }
+}
+
+/// The table a FROM clause's base plan reads
+/// The foreign scans of [`QueryPlanner::push_down_foreign`]; `alias` is
+/// the name the query gives the table read by `plan`
+fn push_down_foreign(
+    plan: QueryPlan,
+    conjuncts: &[Expression],
+    alias: Option<&str>,
+    single_table: bool,
+    schemas: &HashMap<String, TableSchema>,
+    servers: &HashMap<String, Arc<dyn ForeignScan>>,
+) -> QueryPlan {
+    let push = |plan: QueryPlan, conjuncts: &[Expression], alias: Option<&str>| {
+        push_down_foreign(plan, conjuncts, alias, single_table, schemas, servers)
+    };
+    match plan {
+        QueryPlan::Scan { table, filter, projection } => {
+            let foreign = schemas.get(&table).and_then(|schema| ForeignTable::of(&table, schema));
+            let Some((foreign, scan)) = foreign.and_then(|foreign| {
+                let scan = servers.get(&foreign.server)?.clone();
+                Some((foreign, scan))
+            }) else {
+                return QueryPlan::Scan { table, filter, projection };
+            };
+            let names = [alias.unwrap_or(&table)];
+            let (mut pushed, rest) = match &filter {
+                Some(filter) => split_pushdown(scan.as_ref(), &foreign, &names, true, filter),
+                None => (Vec::new(), Vec::new()),
+            };
+            for conjunct in conjuncts {
+                let (local, _) = split_pushdown(scan.as_ref(), &foreign, &names, single_table, conjunct);
+                for predicate in local {
+                    if !pushed.contains(&predicate) {
+                        pushed.push(predicate);
+                    }
+                }
+            }
+            QueryPlan::ForeignScan {
+                table,
+                server: foreign.server,
+                pushed,
+                filter: conjunction(rest),
+            }
+        }
+        QueryPlan::Alias { input, alias } => QueryPlan::Alias {
+            input: Box::new(push(*input, conjuncts, Some(&alias))),
+            alias,
+        },
+        // WHERE conditions on the side of an outer join that may be null
+        // extended must not remove its rows before the join
+        QueryPlan::Join { left, right, join_type, condition } => {
+            let (to_left, to_right) = match join_type {
+                JoinType::Inner | JoinType::Cross => (conjuncts, conjuncts),
+                JoinType::Left => (conjuncts, &[][..]),
+                JoinType::Right => (&[][..], conjuncts),
+                JoinType::Full => (&[][..], &[][..]),
+            };
+            QueryPlan::Join {
+                left: Box::new(push(*left, to_left, None)),
+                right: Box::new(push(*right, to_right, None)),
+                join_type,
+                condition,
+            }
+        }
+        QueryPlan::SemiJoin { left, right, keys, anti } => QueryPlan::SemiJoin {
+            left: Box::new(push(*left, conjuncts, None)),
+            right,
+            keys,
+            anti,
+        },
+        other => other,
+    }
+}
+
+fn base_table(plan: &QueryPlan) -> &str {
+    match plan {
+        QueryPlan::Scan { table, .. }
+        | QueryPlan::IndexScan { table, .. }
+        | QueryPlan::FtsScan { table, .. }
+        | QueryPlan::ForeignScan { table, .. } => table,
+        QueryPlan::Alias { input, .. } | QueryPlan::Selection { input, .. } => base_table(input),
+        _ => "",
+    }
+}
+
+/// Which of the join inputs `(visible name, table)` a conjunct reads;
+/// `None` if it reads a column that matches no input or several, or has a
+/// subquery
+fn conjunct_inputs(
+    conjunct: &Expression,
+    inputs: &[(&str, &str)],
+    schemas: &HashMap<String, TableSchema>,
+) -> Option<Vec<usize>> {
+    if conjunct.has_subquery() {
+        return None;
+    }
+    let mut readers = Vec::new();
+    let mut pending = vec![conjunct];
+    while let Some(expr) = pending.pop() {
+        let matching: Vec<usize> = match expr {
+            Expression::QualifiedColumn { table, .. } => (0..inputs.len())
+                .filter(|input| inputs[*input].0.eq_ignore_ascii_case(table))
+                .collect(),
+            Expression::Column(column) => (0..inputs.len())
+                .filter(|input| {
+                    schemas
+                        .get(inputs[*input].1)
+                        .is_some_and(|schema| schema.position(column).is_some())
+                })
+                .collect(),
+            other => {
+                pending.extend(other.children());
+                continue;
+            }
+        };
+        match matching.as_slice() {
+            [input] if !readers.contains(input) => readers.push(*input),
+            [_] => {}
+            _ => return None,
+        }
+    }
+    Some(readers)
+}
+
 /// Join strategy options
 #[derive(Debug, Clone, Copy)]
 pub enum JoinStrategy {
@@ -393,6 +1257,7 @@ mod tests {
         let planner = QueryPlanner::new();
         
         let select = SelectStatement {
+            distinct: false,
             columns: vec![SelectColumn::Wildcard],
             from: Some(FromClause {
                 table: "users".to_string(),
@@ -436,4 +1301,45 @@ mod tests {
             _ => panic!("Expected insert plan"),
         }
     }
+
+    #[tokio::test]
+    async fn test_sort_below_aggregate_is_hoisted() {
+        let planner = QueryPlanner::new();
+        let region = Expression::Column("region".to_string());
+        let scan = QueryPlan::Scan {
+            table: "sales".to_string(),
+            filter: None,
+            projection: None,
+        };
+        let group_by = |order_by: Vec<(Expression, OrderDirection)>| QueryPlan::GroupBy {
+            input: Box::new(QueryPlan::Sort {
+                input: Box::new(scan.clone()),
+                order_by,
+            }),
+            group_columns: vec![region.clone()],
+            aggregates: vec![AggregateFunction::Count { column: None }],
+        };
+
+        let plan = planner
+            .optimize_plan(group_by(vec![(region.clone(), OrderDirection::Desc)]))
+            .await
+            .unwrap();
+        match plan {
+            QueryPlan::Sort { input, order_by } => {
+                assert_eq!(order_by, vec![(region.clone(), OrderDirection::Desc)]);
+                assert!(matches!(*input, QueryPlan::GroupBy { .. }));
+            }
+            other => panic!("Expected sort above aggregate, got {:?}", other),
+        }
+
+        let amount = Expression::Column("amount".to_string());
+        let plan = planner
+            .optimize_plan(group_by(vec![(amount, OrderDirection::Asc)]))
+            .await
+            .unwrap();
+        match plan {
+            QueryPlan::GroupBy { input, .. } => assert!(!matches!(*input, QueryPlan::Sort { .. })),
+            other => panic!("Expected aggregate without sort, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,147 @@
+//! Operator statistics for `EXPLAIN ANALYZE`
+//!
+//! While a plan runs under [`profile`], the executor reports every
+//! operator it starts and finishes. Operators get the ids of
+//! [`QueryPlan::explain_tree`]: each takes the next free id under its
+//! parent and reserves the ids of its whole subtree, so an operator that
+//! never runs does not shift the ids of the ones after it.
+
+use crate::query::plan::QueryPlan;
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// What one operator did during an `EXPLAIN ANALYZE` run
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OperatorStats {
+    /// Rows the operator output
+    pub rows: u64,
+    /// Time spent in the operator, its inputs included
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Profile {
+    /// Statistics by operator id - 1; `None` for operators that never ran
+    stats: Vec<Option<OperatorStats>>,
+    /// Next free id under each running operator, the plan's root first
+    next_ids: Vec<usize>,
+    /// Number of subqueries running, whose operators are not in the plan
+    hidden: usize,
+}
+
+tokio::task_local! {
+    static PROFILE: RefCell<Profile>;
+}
+
+/// A running operator, from [`enter`]
+#[derive(Debug)]
+pub struct Span {
+    id: usize,
+    started: Instant,
+}
+
+/// Run `future`, which executes `plan`, and collect the statistics of the
+/// plan's operators, indexed by id - 1
+pub async fn profile<F: Future>(plan: &QueryPlan, future: F) -> (F::Output, Vec<Option<OperatorStats>>) {
+    let profile = Profile {
+        stats: vec![None; plan.explain().len()],
+        next_ids: vec![1],
+        hidden: 0,
+    };
+    PROFILE
+        .scope(RefCell::new(profile), async move {
+            let output = future.await;
+            let stats = PROFILE.with(|profile| std::mem::take(&mut profile.borrow_mut().stats));
+            (output, stats)
+        })
+        .await
+}
+
+/// Note that the operator `plan` is starting; `None` when nothing is
+/// being profiled
+pub fn enter(plan: &QueryPlan) -> Option<Span> {
+    PROFILE
+        .try_with(|profile| {
+            let mut profile = profile.borrow_mut();
+            if profile.hidden > 0 {
+                return None;
+            }
+            let next = profile.next_ids.last_mut()?;
+            let id = *next;
+            *next += plan.explain().len();
+            profile.next_ids.push(id + 1);
+            Some(Span { id, started: Instant::now() })
+        })
+        .ok()
+        .flatten()
+}
+
+/// Note that the operator of `span` finished, having output `rows` rows
+pub fn exit(span: Option<Span>, rows: usize) {
+    let Some(span) = span else {
+        return;
+    };
+    let elapsed = span.started.elapsed();
+    let _ = PROFILE.try_with(|profile| {
+        let mut profile = profile.borrow_mut();
+        profile.next_ids.pop();
+        if let Some(slot) = profile.stats.get_mut(span.id - 1) {
+            let stats = slot.get_or_insert_with(OperatorStats::default);
+            stats.rows += rows as u64;
+            stats.elapsed += elapsed;
+        }
+    });
+}
+
+/// Run `future` without reporting its operators, e.g. a subquery whose
+/// plan is not part of the explained one
+pub async fn hidden<F: Future>(future: F) -> F::Output {
+    let adjust = |delta: isize| {
+        let _ = PROFILE.try_with(|profile| {
+            let mut profile = profile.borrow_mut();
+            profile.hidden = profile.hidden.saturating_add_signed(delta);
+        });
+    };
+    adjust(1);
+    let output = future.await;
+    adjust(-1);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(table: &str) -> QueryPlan {
+        QueryPlan::Scan {
+            table: table.to_string(),
+            filter: None,
+            projection: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_operator_ids_follow_explain_order() {
+        let left = QueryPlan::Distinct { input: Box::new(scan("a")) };
+        let plan = QueryPlan::Union {
+            left: Box::new(left.clone()),
+            right: Box::new(scan("b")),
+            all: true,
+        };
+        let ((), stats) = profile(&plan, async {
+            let union = enter(&plan);
+            // The left branch's scan is skipped; the right scan keeps id 4
+            let distinct = enter(&left);
+            exit(distinct, 2);
+            let right = enter(&scan("b"));
+            hidden(async { exit(enter(&scan("c")), 100) }).await;
+            exit(right, 3);
+            exit(union, 5);
+        })
+        .await;
+        let rows: Vec<Option<u64>> = stats.iter().map(|stats| stats.map(|stats| stats.rows)).collect();
+        assert_eq!(rows, vec![Some(5), Some(2), None, Some(3)]);
+        assert!(enter(&plan).is_none());
+    }
+}
@@ -0,0 +1,530 @@
+use crate::collation::Collation;
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::{BinaryOperator, Expression, UnaryOperator};
+use crate::query::json;
+use crate::query::result::QueryResult;
+use crate::types::{Row, Value};
+
+/// A column in an intermediate relation, remembering which table (or
+/// alias) it came from so qualified references can be resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnRef {
+    pub table: Option<String>,
+    pub name: String,
+    /// Readable by name but left out of `SELECT *` results
+    pub hidden: bool,
+    /// How the column's text compares; BINARY when `None`
+    pub collation: Option<Collation>,
+}
+
+impl ColumnRef {
+    pub fn new(table: Option<String>, name: impl Into<String>) -> Self {
+        ColumnRef {
+            table,
+            name: name.into(),
+            hidden: false,
+            collation: None,
+        }
+    }
+
+    /// A column that is only read when named, like the `rank` of a
+    /// full-text scan
+    pub fn hidden(table: Option<String>, name: impl Into<String>) -> Self {
+        ColumnRef {
+            hidden: true,
+            ..ColumnRef::new(table, name)
+        }
+    }
+
+    fn matches(&self, table: Option<&str>, name: &str) -> bool {
+        if !self.name.eq_ignore_ascii_case(name) {
+            return false;
+        }
+        match table {
+            Some(table) => self
+                .table
+                .as_deref()
+                .is_some_and(|own| own.eq_ignore_ascii_case(table)),
+            None => true,
+        }
+    }
+}
+
+/// Rows flowing between physical operators
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    pub columns: Vec<ColumnRef>,
+    pub rows: Vec<Row>,
+}
+
+impl Relation {
+    pub fn new(columns: Vec<ColumnRef>, rows: Vec<Row>) -> Self {
+        Relation { columns, rows }
+    }
+
+    /// Build a relation whose columns all belong to `table`
+    pub fn from_table(table: &str, columns: Vec<String>, rows: Vec<Row>) -> Self {
+        let columns = columns
+            .into_iter()
+            .map(|name| ColumnRef::new(Some(table.to_string()), name))
+            .collect();
+        Relation { columns, rows }
+    }
+
+    /// Build an unqualified relation from a SELECT result
+    pub fn from_result(result: QueryResult) -> SqlResult<Self> {
+        match result {
+            QueryResult::Select { columns, rows } => Ok(Relation {
+                columns: columns.into_iter().map(|name| ColumnRef::new(None, name)).collect(),
+                rows,
+            }),
+            _ => Err(SqlError::runtime_error("Expected a SELECT result")),
+        }
+    }
+
+    /// Re-qualify every column with `alias` (`FROM t AS alias`)
+    pub fn with_alias(mut self, alias: &str) -> Self {
+        for column in &mut self.columns {
+            column.table = Some(alias.to_string());
+        }
+        self
+    }
+
+    /// Convert to a SELECT result with unqualified column names, leaving
+    /// out hidden columns
+    pub fn into_result(self) -> QueryResult {
+        if self.columns.iter().all(|column| !column.hidden) {
+            let columns = self.columns.into_iter().map(|column| column.name).collect();
+            return QueryResult::select(columns, self.rows);
+        }
+        let visible: Vec<bool> = self.columns.iter().map(|column| !column.hidden).collect();
+        let columns = self.columns.into_iter().filter(|column| !column.hidden).map(|column| column.name).collect();
+        let rows = self
+            .rows
+            .into_iter()
+            .map(|row| Row::new(row.values.into_iter().zip(&visible).filter_map(|(value, &visible)| visible.then_some(value)).collect()))
+            .collect();
+        QueryResult::select(columns, rows)
+    }
+
+    /// Find the position of a (possibly qualified) column
+    pub fn resolve(&self, table: Option<&str>, name: &str) -> SqlResult<usize> {
+        resolve_column(&self.columns, table, name)
+    }
+
+    /// Keep only rows for which `condition` evaluates to true
+    pub fn filter(self, condition: &Expression) -> SqlResult<Self> {
+        let mut rows = Vec::with_capacity(self.rows.len());
+        for row in self.rows {
+            if is_truthy(&evaluate(condition, &self.columns, &row)?) {
+                rows.push(row);
+            }
+        }
+        Ok(Relation {
+            columns: self.columns,
+            rows,
+        })
+    }
+}
+
+/// Find the position of a column, rejecting ambiguous unqualified names
+pub fn resolve_column(columns: &[ColumnRef], table: Option<&str>, name: &str) -> SqlResult<usize> {
+    let mut matches = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| column.matches(table, name))
+        .map(|(index, _)| index);
+
+    let display = match table {
+        Some(table) => format!("{}.{}", table, name),
+        None => name.to_string(),
+    };
+    let index = matches
+        .next()
+        .ok_or_else(|| SqlError::column_not_found(display.clone()))?;
+    if matches.next().is_some() {
+        return Err(SqlError::runtime_error(format!("Ambiguous column name: {}", display)));
+    }
+    Ok(index)
+}
+
+/// Hashable form of a value, used for hash joins, grouping and DISTINCT
+///
+/// Integral reals hash like the equal integer, matching `=` semantics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KeyPart {
+    Null,
+    Integer(i64),
+    Real(u64),
+    Text(String),
+    Blob(Vec<u8>),
+    Boolean(bool),
+}
+
+impl KeyPart {
+    pub fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Null => KeyPart::Null,
+            Value::Integer(i) => KeyPart::Integer(*i),
+            Value::Real(r) if r.fract() == 0.0 && r.abs() < i64::MAX as f64 => KeyPart::Integer(*r as i64),
+            Value::Real(r) => KeyPart::Real(r.to_bits()),
+            Value::Text(s) => KeyPart::Text(s.clone()),
+            Value::Blob(b) => KeyPart::Blob(b.clone()),
+            Value::Boolean(b) => KeyPart::Boolean(*b),
+        }
+    }
+
+    /// Key for a whole row
+    pub fn row_key(values: &[Value]) -> Vec<KeyPart> {
+        values.iter().map(KeyPart::from_value).collect()
+    }
+}
+
+/// SQL truthiness: NULL and zero are false
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Boolean(b) => *b,
+        Value::Integer(i) => *i != 0,
+        Value::Real(r) => *r != 0.0,
+        _ => false,
+    }
+}
+
+/// Evaluate an expression against a row of a relation
+pub fn evaluate(expr: &Expression, columns: &[ColumnRef], row: &Row) -> SqlResult<Value> {
+    match expr {
+        Expression::Literal(value) => Ok(value.clone()),
+        Expression::Column(name) => {
+            let index = resolve_column(columns, None, name)?;
+            Ok(row.values.get(index).cloned().unwrap_or(Value::Null))
+        }
+        Expression::QualifiedColumn { table, column } => {
+            let index = resolve_column(columns, Some(table), column)?;
+            Ok(row.values.get(index).cloned().unwrap_or(Value::Null))
+        }
+        Expression::BinaryOp { left, op, right } => {
+            let collation = match op {
+                BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::LessThan
+                | BinaryOperator::LessThanOrEqual
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterThanOrEqual => comparison_collation(left, right, columns)?,
+                _ => None,
+            };
+            let left = evaluate(left, columns, row)?;
+            let right = evaluate(right, columns, row)?;
+            evaluate_binary(&left, op, &right, collation.as_ref())
+        }
+        Expression::Collate { expr, collation } => {
+            Collation::find(collation)?;
+            evaluate(expr, columns, row)
+        }
+        Expression::UnaryOp { op, operand } => {
+            let value = evaluate(operand, columns, row)?;
+            match (op, value) {
+                (_, Value::Null) => Ok(Value::Null),
+                (UnaryOperator::Plus, value) => Ok(value),
+                (UnaryOperator::Minus, Value::Integer(i)) => Ok(Value::Integer(-i)),
+                (UnaryOperator::Minus, Value::Real(r)) => Ok(Value::Real(-r)),
+                (UnaryOperator::Not, value) => Ok(Value::Boolean(!is_truthy(&value))),
+                (UnaryOperator::Minus, value) => Err(SqlError::type_error(format!(
+                    "Cannot negate {}",
+                    value.data_type()
+                ))),
+            }
+        }
+        Expression::IsNull(inner) => Ok(Value::Boolean(evaluate(inner, columns, row)?.is_null())),
+        Expression::IsNotNull(inner) => Ok(Value::Boolean(!evaluate(inner, columns, row)?.is_null())),
+        Expression::In { expr, list } => {
+            let collation = expression_collation(expr, columns)?;
+            let value = evaluate(expr, columns, row)?;
+            if value.is_null() {
+                return Ok(Value::Null);
+            }
+            // Without a match, a NULL in the list makes the result unknown
+            let mut unknown = false;
+            for item in list {
+                match collated_equals(&value, &evaluate(item, columns, row)?, collation.as_ref()) {
+                    Some(true) => return Ok(Value::Boolean(true)),
+                    Some(false) => {}
+                    None => unknown = true,
+                }
+            }
+            Ok(if unknown { Value::Null } else { Value::Boolean(false) })
+        }
+        Expression::Between { expr, low, high } => {
+            let collation = expression_collation(expr, columns)?;
+            let value = evaluate(expr, columns, row)?;
+            let low = evaluate(low, columns, row)?;
+            let high = evaluate(high, columns, row)?;
+            if value.is_null() || low.is_null() || high.is_null() {
+                return Ok(Value::Null);
+            }
+            let (above, below) = (
+                collated_cmp(&value, &low, collation.as_ref()),
+                collated_cmp(&value, &high, collation.as_ref()),
+            );
+            Ok(Value::Boolean(
+                above.is_some_and(|o| o != std::cmp::Ordering::Less)
+                    && below.is_some_and(|o| o != std::cmp::Ordering::Greater),
+            ))
+        }
+        Expression::Function { name, args } => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, columns, row))
+                .collect::<SqlResult<Vec<_>>>()?;
+            call_function(name, &args)
+        }
+        // The executor runs subqueries and substitutes their results
+        // before rows are evaluated
+        Expression::Subquery(_) | Expression::InSubquery { .. } | Expression::Exists(_) => Err(
+            SqlError::runtime_error("Subqueries are not supported in this part of a query"),
+        ),
+        Expression::Parameter(index) => Err(SqlError::runtime_error(format!("Parameter ?{} is not bound", index))),
+    }
+}
+
+/// Call a scalar function; aggregates are computed before rows reach
+/// [`evaluate`]
+fn call_function(name: &str, args: &[Value]) -> SqlResult<Value> {
+    let arity_error = || SqlError::runtime_error(format!("wrong number of arguments to function {}()", name));
+    match (name.to_ascii_lowercase().as_str(), args) {
+        ("json", [document]) => json::json(document),
+        ("json_extract", [document, paths @ ..]) if !paths.is_empty() => json::json_extract(document, paths),
+        ("json_set", [document, assignments @ ..]) => json::json_set(document, assignments),
+        ("json_array_length", [document]) => json::json_array_length(document, None),
+        ("json_array_length", [document, path]) => json::json_array_length(document, Some(path)),
+        ("json" | "json_extract" | "json_set" | "json_array_length", _) => Err(arity_error()),
+        _ => Err(SqlError::runtime_error(format!(
+            "Function {} is not supported in row expressions",
+            name
+        ))),
+    }
+}
+
+/// Equality with SQL NULL semantics (`None` means unknown)
+pub fn sql_equals(left: &Value, right: &Value) -> Option<bool> {
+    collated_equals(left, right, None)
+}
+
+fn collated_equals(left: &Value, right: &Value, collation: Option<&Collation>) -> Option<bool> {
+    if left.is_null() || right.is_null() {
+        return None;
+    }
+    Some(collated_cmp(left, right, collation) == Some(std::cmp::Ordering::Equal))
+}
+
+fn collated_cmp(left: &Value, right: &Value, collation: Option<&Collation>) -> Option<std::cmp::Ordering> {
+    match collation {
+        Some(collation) => collation.partial_cmp(left, right),
+        None => left.partial_cmp(right),
+    }
+}
+
+/// Collation an operand brings to a comparison: its own `COLLATE`, else
+/// that of the column it names; `None` means BINARY
+pub fn expression_collation(expr: &Expression, columns: &[ColumnRef]) -> SqlResult<Option<Collation>> {
+    Ok(explicit_collation(expr)?.or_else(|| column_collation(expr, columns)))
+}
+
+/// Collation of a comparison, by SQLite's rules: an explicit `COLLATE` on
+/// either operand, the left one first, then the collation of a column
+/// operand, again the left one first
+pub fn comparison_collation(left: &Expression, right: &Expression, columns: &[ColumnRef]) -> SqlResult<Option<Collation>> {
+    Ok(explicit_collation(left)?
+        .or(explicit_collation(right)?)
+        .or_else(|| column_collation(left, columns))
+        .or_else(|| column_collation(right, columns)))
+}
+
+fn explicit_collation(expr: &Expression) -> SqlResult<Option<Collation>> {
+    match expr {
+        Expression::Collate { collation, .. } => Collation::find(collation).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn column_collation(expr: &Expression, columns: &[ColumnRef]) -> Option<Collation> {
+    let index = match expr {
+        Expression::Column(name) => resolve_column(columns, None, name).ok()?,
+        Expression::QualifiedColumn { table, column } => resolve_column(columns, Some(table), column).ok()?,
+        _ => return None,
+    };
+    columns[index].collation.clone()
+}
+
+fn evaluate_binary(left: &Value, op: &BinaryOperator, right: &Value, collation: Option<&Collation>) -> SqlResult<Value> {
+    use std::cmp::Ordering;
+
+    let compare = |accept: fn(Ordering) -> bool| -> Value {
+        if left.is_null() || right.is_null() {
+            return Value::Null;
+        }
+        Value::Boolean(collated_cmp(left, right, collation).is_some_and(accept))
+    };
+
+    match op {
+        BinaryOperator::And => {
+            if (!left.is_null() && !is_truthy(left)) || (!right.is_null() && !is_truthy(right)) {
+                Ok(Value::Boolean(false))
+            } else if left.is_null() || right.is_null() {
+                Ok(Value::Null)
+            } else {
+                Ok(Value::Boolean(true))
+            }
+        }
+        BinaryOperator::Or => {
+            if is_truthy(left) || is_truthy(right) {
+                Ok(Value::Boolean(true))
+            } else if left.is_null() || right.is_null() {
+                Ok(Value::Null)
+            } else {
+                Ok(Value::Boolean(false))
+            }
+        }
+        BinaryOperator::Equal => Ok(collated_equals(left, right, collation).map_or(Value::Null, Value::Boolean)),
+        BinaryOperator::NotEqual => {
+            Ok(collated_equals(left, right, collation).map_or(Value::Null, |eq| Value::Boolean(!eq)))
+        }
+        BinaryOperator::LessThan => Ok(compare(|o| o == Ordering::Less)),
+        BinaryOperator::LessThanOrEqual => Ok(compare(|o| o != Ordering::Greater)),
+        BinaryOperator::GreaterThan => Ok(compare(|o| o == Ordering::Greater)),
+        BinaryOperator::GreaterThanOrEqual => Ok(compare(|o| o != Ordering::Less)),
+        BinaryOperator::Like | BinaryOperator::NotLike => {
+            if left.is_null() || right.is_null() {
+                return Ok(Value::Null);
+            }
+            let matched = like(&left.to_string(), &right.to_string());
+            Ok(Value::Boolean(matched == matches!(op, BinaryOperator::Like)))
+        }
+        BinaryOperator::Concat => {
+            if left.is_null() || right.is_null() {
+                return Ok(Value::Null);
+            }
+            Ok(Value::Text(format!("{}{}", left, right)))
+        }
+        // Answered by a full-text index before rows are evaluated
+        BinaryOperator::Match => Err(SqlError::runtime_error("unable to use function MATCH in the requested context")),
+        BinaryOperator::JsonExtract => json::arrow(left, right, false),
+        BinaryOperator::JsonExtractValue => json::arrow(left, right, true),
+        BinaryOperator::Add
+        | BinaryOperator::Subtract
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Modulo => evaluate_arithmetic(left, op, right),
+    }
+}
+
+fn evaluate_arithmetic(left: &Value, op: &BinaryOperator, right: &Value) -> SqlResult<Value> {
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        (Value::Integer(a), Value::Integer(b)) => Ok(match op {
+            BinaryOperator::Add => Value::Integer(a.wrapping_add(*b)),
+            BinaryOperator::Subtract => Value::Integer(a.wrapping_sub(*b)),
+            BinaryOperator::Multiply => Value::Integer(a.wrapping_mul(*b)),
+            BinaryOperator::Divide if *b == 0 => Value::Null,
+            BinaryOperator::Divide => Value::Integer(a / b),
+            BinaryOperator::Modulo if *b == 0 => Value::Null,
+            _ => Value::Integer(a % b),
+        }),
+        _ => {
+            let (a, b) = match (left.as_real(), right.as_real()) {
+                (Some(a), Some(b)) => (a, b),
+                _ => {
+                    return Err(SqlError::type_error(format!(
+                        "Cannot apply arithmetic to {} and {}",
+                        left.data_type(),
+                        right.data_type()
+                    )))
+                }
+            };
+            Ok(match op {
+                BinaryOperator::Add => Value::Real(a + b),
+                BinaryOperator::Subtract => Value::Real(a - b),
+                BinaryOperator::Multiply => Value::Real(a * b),
+                BinaryOperator::Divide if b == 0.0 => Value::Null,
+                BinaryOperator::Divide => Value::Real(a / b),
+                BinaryOperator::Modulo if b == 0.0 => Value::Null,
+                _ => Value::Real(a % b),
+            })
+        }
+    }
+}
+
+/// Case-insensitive LIKE with `%` and `_` wildcards
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '_' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '%' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '%')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relation() -> Relation {
+        let mut users = Relation::from_table(
+            "users",
+            vec!["id".to_string(), "name".to_string()],
+            vec![Row::new(vec![Value::Integer(1), Value::Text("Alice".to_string())])],
+        );
+        users.columns.push(ColumnRef::new(Some("orders".to_string()), "id"));
+        users.rows[0].values.push(Value::Integer(7));
+        users
+    }
+
+    #[test]
+    fn test_resolve_qualified_and_ambiguous() {
+        let relation = relation();
+        assert_eq!(relation.resolve(Some("orders"), "id").unwrap(), 2);
+        assert_eq!(relation.resolve(None, "NAME").unwrap(), 1);
+        assert!(relation.resolve(None, "id").is_err());
+        assert!(relation.resolve(Some("users"), "total").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_expressions() {
+        let relation = relation();
+        let row = &relation.rows[0];
+        let expr = Expression::BinaryOp {
+            left: Box::new(Expression::QualifiedColumn {
+                table: "users".to_string(),
+                column: "id".to_string(),
+            }),
+            op: BinaryOperator::Equal,
+            right: Box::new(Expression::Literal(Value::Real(1.0))),
+        };
+        assert_eq!(evaluate(&expr, &relation.columns, row).unwrap(), Value::Boolean(true));
+
+        let null_cmp = Expression::BinaryOp {
+            left: Box::new(Expression::Literal(Value::Null)),
+            op: BinaryOperator::Equal,
+            right: Box::new(Expression::Literal(Value::Integer(1))),
+        };
+        assert_eq!(evaluate(&null_cmp, &relation.columns, row).unwrap(), Value::Null);
+
+        assert!(like("Alice", "a%e"));
+        assert!(like("Alice", "_lic_"));
+        assert!(!like("Alice", "b%"));
+    }
+}
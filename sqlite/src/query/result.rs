@@ -29,6 +29,14 @@ pub enum QueryResult {
     CreateIndex,
     /// DROP INDEX query result
     DropIndex,
+    /// A transaction was started
+    Begin,
+    /// A transaction was committed
+    Commit,
+    /// A transaction was rolled back, by ROLLBACK or by COMMIT after an error
+    Rollback,
+    /// The database file was rebuilt by VACUUM
+    Vacuum,
 }
 
 impl QueryResult {
@@ -192,6 +200,10 @@ impl QueryResult {
             QueryResult::DropTable => "Table dropped successfully".to_string(),
             QueryResult::CreateIndex => "Index created successfully".to_string(),
             QueryResult::DropIndex => "Index dropped successfully".to_string(),
+            QueryResult::Begin => "Transaction started".to_string(),
+            QueryResult::Commit => "Transaction committed".to_string(),
+            QueryResult::Rollback => "Transaction rolled back".to_string(),
+            QueryResult::Vacuum => "Database vacuumed".to_string(),
         }
     }
 
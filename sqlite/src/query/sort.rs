@@ -0,0 +1,503 @@
+use crate::collation::Collation;
+use crate::error::{SqlError, SqlResult};
+use crate::parser::ast::{Expression, OrderDirection};
+use crate::page_cache::{FilePageStorage, PageCache, PageCacheConfig, PageData, PageType};
+use crate::query::relation::{evaluate, expression_collation, ColumnRef, Relation};
+use crate::types::{PageId, Row, Value};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+/// Sort configuration
+#[derive(Debug, Clone)]
+pub struct SortConfig {
+    /// Rows held in memory before a sorted run is spilled to disk
+    pub memory_rows: usize,
+    /// Page cache used for spilled runs; its pages live in a temporary file
+    pub spill_cache: PageCacheConfig,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        SortConfig {
+            memory_rows: 100_000,
+            spill_cache: PageCacheConfig {
+                capacity: 64,
+                enable_prefetch: false,
+                ..PageCacheConfig::default()
+            },
+        }
+    }
+}
+
+/// What a sort had to do to produce its output
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortStats {
+    /// Sorted runs written through the spill cache (0 for in-memory sorts)
+    pub runs: usize,
+    pub spilled_rows: usize,
+    pub spilled_pages: u32,
+}
+
+/// ORDER BY operator: sorts in memory while the input fits within
+/// `memory_rows`, otherwise writes sorted runs through a page cache backed
+/// by a temporary file and k-way merges them
+///
+/// The sort is stable and orders values as SQLite does: NULL first, then
+/// numbers, text and blobs. An integer literal key `n` sorts by the n-th
+/// input column.
+#[derive(Debug, Clone)]
+pub struct ExternalSort {
+    order_by: Vec<(Expression, OrderDirection)>,
+    config: SortConfig,
+    limit: Option<usize>,
+}
+
+impl ExternalSort {
+    pub fn new(order_by: Vec<(Expression, OrderDirection)>, config: SortConfig) -> Self {
+        ExternalSort {
+            order_by,
+            config,
+            limit: None,
+        }
+    }
+
+    /// Only the first `limit` rows are needed (`ORDER BY .. LIMIT`); a
+    /// limit that fits in memory never spills
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub async fn execute(&self, input: Relation) -> SqlResult<Relation> {
+        Ok(self.execute_with_stats(input).await?.0)
+    }
+
+    pub async fn execute_with_stats(&self, input: Relation) -> SqlResult<(Relation, SortStats)> {
+        let keys = self.key_columns(&input.columns)?;
+        let orders = self.key_orders(&keys, &input.columns)?;
+        let memory_rows = self.config.memory_rows.max(1);
+        let top_n = self.limit.filter(|limit| *limit < memory_rows);
+
+        let mut stats = SortStats::default();
+        let mut spill: Option<SpillArea> = None;
+        let mut runs = Vec::new();
+        let mut buffer: Vec<SortEntry> = Vec::new();
+        for row in input.rows {
+            let key = Self::sort_key(&keys, &input.columns, &row)?;
+            buffer.push(SortEntry { key, row });
+            if buffer.len() < memory_rows {
+                continue;
+            }
+            sort_entries(&mut buffer, &orders);
+            if let Some(limit) = top_n {
+                buffer.truncate(limit);
+                continue;
+            }
+            let area = match spill.as_mut() {
+                Some(area) => area,
+                None => spill.insert(SpillArea::new(&self.config.spill_cache)?),
+            };
+            stats.spilled_rows += buffer.len();
+            runs.push(area.write_run(std::mem::take(&mut buffer)).await?);
+        }
+        sort_entries(&mut buffer, &orders);
+
+        let mut rows: Vec<Row> = match spill {
+            None => buffer.into_iter().map(|entry| entry.row).collect(),
+            Some(mut area) => {
+                stats.spilled_rows += buffer.len();
+                runs.push(area.write_run(buffer).await?);
+                stats.runs = runs.len();
+                stats.spilled_pages = area.next_page;
+                area.merge(runs, &orders, self.limit).await?
+            }
+        };
+        if let Some(limit) = self.limit {
+            rows.truncate(limit);
+        }
+        Ok((Relation::new(input.columns, rows), stats))
+    }
+
+    fn key_columns(&self, columns: &[ColumnRef]) -> SqlResult<Vec<SortKey>> {
+        self.order_by
+            .iter()
+            .map(|(expr, _)| match expr {
+                Expression::Literal(Value::Integer(position)) => {
+                    if *position < 1 || *position as usize > columns.len() {
+                        return Err(SqlError::parse_error(format!(
+                            "ORDER BY term {} out of range, should be between 1 and {}",
+                            position,
+                            columns.len()
+                        )));
+                    }
+                    Ok(SortKey::Position(*position as usize - 1))
+                }
+                expr => Ok(SortKey::Expression(expr.clone())),
+            })
+            .collect()
+    }
+
+    /// Direction and collation of each key: a term's own `COLLATE`, else
+    /// that of the column it names
+    fn key_orders(&self, keys: &[SortKey], columns: &[ColumnRef]) -> SqlResult<Vec<KeyOrder>> {
+        keys.iter()
+            .zip(&self.order_by)
+            .map(|(key, (_, direction))| {
+                let collation = match key {
+                    SortKey::Position(index) => columns[*index].collation.clone(),
+                    SortKey::Expression(expr) => expression_collation(expr, columns)?,
+                };
+                Ok(KeyOrder {
+                    direction: direction.clone(),
+                    collation,
+                })
+            })
+            .collect()
+    }
+
+    fn sort_key(keys: &[SortKey], columns: &[ColumnRef], row: &Row) -> SqlResult<Vec<Value>> {
+        keys.iter()
+            .map(|key| match key {
+                SortKey::Position(index) => Ok(row.values.get(*index).cloned().unwrap_or(Value::Null)),
+                SortKey::Expression(expr) => evaluate(expr, columns, row),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SortKey {
+    Position(usize),
+    Expression(Expression),
+}
+
+/// Compare values in SQLite sort order: NULL < numbers < text < blobs
+pub fn compare_values(left: &Value, right: &Value) -> Ordering {
+    fn class(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) | Value::Boolean(_) => 1,
+            Value::Text(_) => 2,
+            Value::Blob(_) => 3,
+        }
+    }
+    fn numeric(value: &Value) -> f64 {
+        match value {
+            Value::Boolean(b) => *b as i64 as f64,
+            other => other.as_real().unwrap_or(0.0),
+        }
+    }
+
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Text(a), Value::Text(b)) => a.cmp(b),
+        (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+        _ if class(left) == 1 && class(right) == 1 => numeric(left).total_cmp(&numeric(right)),
+        _ => class(left).cmp(&class(right)),
+    }
+}
+
+/// How one ORDER BY key sorts
+#[derive(Debug, Clone)]
+pub struct KeyOrder {
+    pub direction: OrderDirection,
+    /// BINARY when `None`
+    pub collation: Option<Collation>,
+}
+
+/// Compare two sort keys column by column, honouring each direction and
+/// collation
+pub fn compare_keys(left: &[Value], right: &[Value], orders: &[KeyOrder]) -> Ordering {
+    for ((a, b), order) in left.iter().zip(right).zip(orders) {
+        let (a, b) = match order.direction {
+            OrderDirection::Asc => (a, b),
+            OrderDirection::Desc => (b, a),
+        };
+        let ordering = match &order.collation {
+            Some(collation) => collation.compare_values(a, b),
+            None => compare_values(a, b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct SortEntry {
+    key: Vec<Value>,
+    row: Row,
+}
+
+fn sort_entries(entries: &mut [SortEntry], orders: &[KeyOrder]) {
+    entries.sort_by(|a, b| compare_keys(&a.key, &b.key, orders));
+}
+
+/// A sorted run stored as a byte stream over consecutive spill pages
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    first_page: u32,
+    end_page: u32,
+}
+
+/// Temporary page space for sorted runs
+struct SpillArea {
+    cache: PageCache,
+    page_size: usize,
+    next_page: u32,
+}
+
+impl SpillArea {
+    fn new(config: &PageCacheConfig) -> SqlResult<Self> {
+        let storage = FilePageStorage::temporary(config.page_size)?;
+        Ok(SpillArea {
+            cache: PageCache::with_storage(config.clone(), Arc::new(storage)),
+            page_size: config.page_size,
+            next_page: 0,
+        })
+    }
+
+    /// Write sorted entries as length-prefixed records packed into pages
+    async fn write_run(&mut self, entries: Vec<SortEntry>) -> SqlResult<Run> {
+        let first_page = self.next_page;
+        let mut bytes = Vec::with_capacity(self.page_size);
+        for entry in entries {
+            let record = serde_json::to_vec(&entry)
+                .map_err(|e| SqlError::runtime_error(format!("Cannot spill sort run: {}", e)))?;
+            bytes.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&record);
+            while bytes.len() >= self.page_size {
+                let rest = bytes.split_off(self.page_size);
+                self.write_page(std::mem::replace(&mut bytes, rest)).await?;
+            }
+        }
+        if !bytes.is_empty() {
+            self.write_page(bytes).await?;
+        }
+        Ok(Run {
+            first_page,
+            end_page: self.next_page,
+        })
+    }
+
+    async fn write_page(&mut self, data: Vec<u8>) -> SqlResult<()> {
+        let page_id = PageId(self.next_page);
+        self.next_page += 1;
+        self.cache
+            .store_page(page_id, PageData::new_dirty(data, PageType::Overflow))
+            .await
+    }
+
+    /// K-way merge of the runs; ties go to the earlier run, which keeps the
+    /// sort stable since runs are written in input order
+    async fn merge(
+        &self,
+        runs: Vec<Run>,
+        orders: &[KeyOrder],
+        limit: Option<usize>,
+    ) -> SqlResult<Vec<Row>> {
+        let mut readers: Vec<RunReader> = runs.into_iter().map(RunReader::new).collect();
+        let mut heap = BinaryHeap::new();
+        for (index, reader) in readers.iter_mut().enumerate() {
+            if let Some(entry) = reader.next(&self.cache).await? {
+                heap.push(Reverse(HeapEntry { entry, run: index, orders }));
+            }
+        }
+
+        let mut rows = Vec::new();
+        while let Some(Reverse(HeapEntry { entry, run, .. })) = heap.pop() {
+            rows.push(entry.row);
+            if limit.is_some_and(|limit| rows.len() >= limit) {
+                break;
+            }
+            if let Some(entry) = readers[run].next(&self.cache).await? {
+                heap.push(Reverse(HeapEntry { entry, run, orders }));
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// Sequential reader over one run's pages
+struct RunReader {
+    run: Run,
+    next_page: u32,
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl RunReader {
+    fn new(run: Run) -> Self {
+        RunReader {
+            next_page: run.first_page,
+            run,
+            bytes: Vec::new(),
+            position: 0,
+        }
+    }
+
+    async fn next(&mut self, cache: &PageCache) -> SqlResult<Option<SortEntry>> {
+        if !self.fill(cache, 4).await? {
+            return Ok(None);
+        }
+        let header = &self.bytes[self.position..self.position + 4];
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        self.position += 4;
+        if !self.fill(cache, len).await? {
+            return Err(SqlError::runtime_error("Truncated sort run"));
+        }
+        let entry = serde_json::from_slice(&self.bytes[self.position..self.position + len])
+            .map_err(|e| SqlError::runtime_error(format!("Corrupted sort run: {}", e)))?;
+        self.position += len;
+        Ok(Some(entry))
+    }
+
+    /// Make at least `needed` unread bytes available; false at end of run
+    async fn fill(&mut self, cache: &PageCache, needed: usize) -> SqlResult<bool> {
+        while self.bytes.len() - self.position < needed {
+            if self.next_page >= self.run.end_page {
+                return Ok(false);
+            }
+            self.bytes.drain(..self.position);
+            self.position = 0;
+            let page = cache.load_page(PageId(self.next_page)).await?;
+            self.bytes.extend_from_slice(&page.data);
+            self.next_page += 1;
+        }
+        Ok(true)
+    }
+}
+
+struct HeapEntry<'a> {
+    entry: SortEntry,
+    run: usize,
+    orders: &'a [KeyOrder],
+}
+
+impl Ord for HeapEntry<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_keys(&self.entry.key, &other.entry.key, self.orders).then(self.run.cmp(&other.run))
+    }
+}
+
+impl PartialOrd for HeapEntry<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> Relation {
+        let names = ["dave", "alice", "carol", "bob", "erin", "frank", "grace"];
+        let ages = [
+            Value::Integer(40),
+            Value::Integer(30),
+            Value::Null,
+            Value::Integer(30),
+            Value::Real(25.5),
+            Value::Integer(40),
+            Value::Integer(30),
+        ];
+        let rows = names
+            .iter()
+            .zip(ages)
+            .map(|(name, age)| Row::new(vec![Value::Text(name.to_string()), age]))
+            .collect();
+        Relation::from_table("people", vec!["name".to_string(), "age".to_string()], rows)
+    }
+
+    fn names(relation: &Relation) -> Vec<String> {
+        relation.rows.iter().map(|row| row.values[0].to_string()).collect()
+    }
+
+    fn by_age_desc_then_name() -> Vec<(Expression, OrderDirection)> {
+        vec![
+            (Expression::Column("age".to_string()), OrderDirection::Desc),
+            (Expression::Column("name".to_string()), OrderDirection::Asc),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_multi_key_sort_in_memory() {
+        let sort = ExternalSort::new(by_age_desc_then_name(), SortConfig::default());
+        let (sorted, stats) = sort.execute_with_stats(people()).await.unwrap();
+        assert_eq!(
+            names(&sorted),
+            vec!["dave", "frank", "alice", "bob", "grace", "erin", "carol"]
+        );
+        assert_eq!(stats.runs, 0);
+    }
+
+    #[tokio::test]
+    async fn test_spilled_sort_matches_in_memory_sort() {
+        let config = SortConfig {
+            memory_rows: 2,
+            spill_cache: PageCacheConfig {
+                capacity: 2,
+                page_size: 32,
+                ..PageCacheConfig::default()
+            },
+        };
+        let sort = ExternalSort::new(by_age_desc_then_name(), config.clone());
+        let (sorted, stats) = sort.execute_with_stats(people()).await.unwrap();
+        assert_eq!(
+            names(&sorted),
+            vec!["dave", "frank", "alice", "bob", "grace", "erin", "carol"]
+        );
+        assert_eq!(stats.runs, 4);
+        assert_eq!(stats.spilled_rows, 7);
+        assert!(stats.spilled_pages > config.spill_cache.capacity as u32);
+
+        // Stable: equal keys keep input order across runs
+        let by_age = vec![(Expression::Column("age".to_string()), OrderDirection::Asc)];
+        let sorted = ExternalSort::new(by_age, config.clone()).execute(people()).await.unwrap();
+        assert_eq!(
+            names(&sorted),
+            vec!["carol", "erin", "alice", "bob", "grace", "dave", "frank"]
+        );
+
+        let top = ExternalSort::new(by_age_desc_then_name(), config)
+            .with_limit(3)
+            .execute(people())
+            .await
+            .unwrap();
+        assert_eq!(names(&top), vec!["dave", "frank", "alice"]);
+    }
+
+    #[tokio::test]
+    async fn test_sort_by_position() {
+        let by_second = vec![(Expression::Literal(Value::Integer(2)), OrderDirection::Asc)];
+        let sorted = ExternalSort::new(by_second, SortConfig::default())
+            .execute(people())
+            .await
+            .unwrap();
+        assert_eq!(names(&sorted)[0], "carol");
+
+        let out_of_range = vec![(Expression::Literal(Value::Integer(3)), OrderDirection::Asc)];
+        assert!(ExternalSort::new(out_of_range, SortConfig::default())
+            .execute(people())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_compare_values_across_types() {
+        assert_eq!(compare_values(&Value::Null, &Value::Integer(1)), Ordering::Less);
+        assert_eq!(compare_values(&Value::Integer(2), &Value::Real(1.5)), Ordering::Greater);
+        assert_eq!(compare_values(&Value::Real(9.0), &Value::Text("1".to_string())), Ordering::Less);
+        assert_eq!(compare_values(&Value::Text("z".to_string()), &Value::Blob(vec![0])), Ordering::Less);
+    }
+}
@@ -0,0 +1,636 @@
+//! Table and column statistics for the cost-based planner
+//!
+//! `ANALYZE [table]` reads every row of a table and records its row count
+//! and, for each column, the number of NULLs and of distinct values and an
+//! equi-depth histogram. [`CostModel`] turns them into row and cost
+//! estimates for plans; operators over tables that were never analyzed
+//! keep the fixed estimates of [`QueryPlan::estimated_rows`]. Statistics
+//! live in memory and describe the tables as they were at the last
+//! ANALYZE.
+
+use crate::parser::ast::{BinaryOperator, Expression, JoinConstraint, JoinType, UnaryOperator};
+use crate::query::index::IndexLookup;
+use crate::query::join::split_conjunction;
+use crate::query::plan::QueryPlan;
+use crate::query::relation::{KeyPart, Relation};
+use crate::query::sort::compare_values;
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Number of buckets of each column histogram
+pub const HISTOGRAM_BUCKETS: usize = 16;
+
+/// Fraction of rows kept by a condition nothing is known about, as in
+/// [`QueryPlan::estimated_rows`]
+const DEFAULT_SELECTIVITY: f64 = 1.0 / 3.0;
+
+/// Fraction of rows equal to a value of a column without statistics
+const DEFAULT_EQUALITY: f64 = 0.1;
+
+/// Statistics of every analyzed table, shared by the planner and the
+/// executor
+pub type StatsCatalog = Arc<RwLock<HashMap<String, TableStats>>>;
+
+/// What ANALYZE learned about one column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub null_count: u64,
+    /// Number of distinct non-NULL values
+    pub distinct: u64,
+    pub min: Option<Value>,
+    /// Upper bound of each equi-depth bucket of the non-NULL values,
+    /// ascending; the last one is the maximum
+    pub histogram: Vec<Value>,
+}
+
+impl ColumnStats {
+    fn collect(mut values: Vec<Value>, buckets: usize) -> Self {
+        let total = values.len();
+        values.retain(|value| !value.is_null());
+        let null_count = (total - values.len()) as u64;
+        let distinct = values.iter().map(KeyPart::from_value).collect::<HashSet<_>>().len() as u64;
+        values.sort_by(compare_values);
+        let histogram = match values.len() {
+            0 => Vec::new(),
+            len => {
+                let buckets = buckets.min(len);
+                (1..=buckets).map(|bucket| values[(bucket * len).div_ceil(buckets) - 1].clone()).collect()
+            }
+        };
+        ColumnStats {
+            null_count,
+            distinct,
+            min: values.into_iter().next(),
+            histogram,
+        }
+    }
+
+    /// Fraction of non-NULL values below `value`, or at most `value` when
+    /// `inclusive`, read from the histogram
+    fn fraction_below(&self, value: &Value, inclusive: bool) -> f64 {
+        let (Some(min), Some(max)) = (&self.min, self.histogram.last()) else {
+            return 0.0;
+        };
+        let below = |bound: &Value| match compare_values(bound, value) {
+            Ordering::Less => true,
+            Ordering::Equal => inclusive,
+            Ordering::Greater => false,
+        };
+        if !below(min) {
+            return 0.0;
+        }
+        if below(max) {
+            return 1.0;
+        }
+        // Whole buckets below the value, and half of the one it falls in
+        let full = self.histogram.iter().take_while(|bound| below(bound)).count();
+        (full as f64 + 0.5) / self.histogram.len() as f64
+    }
+}
+
+/// What ANALYZE learned about one table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStats {
+    pub row_count: u64,
+    /// Keyed by lower-case column name
+    pub columns: HashMap<String, ColumnStats>,
+}
+
+impl TableStats {
+    /// Gather the statistics of every column of `relation`
+    pub fn collect(relation: &Relation) -> Self {
+        let columns = relation
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| !column.hidden)
+            .map(|(position, column)| {
+                let values = relation.rows.iter().map(|row| row.values[position].clone()).collect();
+                (column.name.to_lowercase(), ColumnStats::collect(values, HISTOGRAM_BUCKETS))
+            })
+            .collect();
+        TableStats {
+            row_count: relation.rows.len() as u64,
+            columns,
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnStats> {
+        self.columns.get(&name.to_lowercase())
+    }
+
+    /// Fraction of the rows for which `condition` holds
+    pub fn selectivity(&self, condition: &Expression) -> f64 {
+        selectivity(condition, &|_, column| self.column(column).map(|stats| (self.row_count, stats)))
+    }
+
+    /// Fraction of the rows an index on `columns` returns for `lookup`
+    pub fn lookup_selectivity(&self, columns: &[String], lookup: &IndexLookup) -> f64 {
+        let column = |position: usize| columns.get(position).and_then(|name| self.column(name));
+        let mut fraction: f64 = lookup
+            .equal
+            .iter()
+            .enumerate()
+            .map(|(position, value)| equality(self.row_count, column(position), value))
+            .product();
+        if lookup.has_range() {
+            let stats = column(lookup.equal.len());
+            fraction *= range(self.row_count, stats, &lookup.lower, &lookup.upper);
+        }
+        fraction
+    }
+}
+
+/// Resolves a column reference (table qualifier, column) to the row count
+/// of its table and the column's statistics
+type ColumnResolver<'a> = dyn Fn(Option<&str>, &str) -> Option<(u64, &'a ColumnStats)> + 'a;
+
+/// Fraction of rows equal to `value`
+fn equality(row_count: u64, stats: Option<&ColumnStats>, value: &Value) -> f64 {
+    match stats {
+        _ if value.is_null() => 0.0,
+        Some(stats) if stats.distinct == 0 || row_count == 0 => 0.0,
+        Some(stats) => {
+            let out_of_range = stats.fraction_below(value, true) == 0.0 || stats.fraction_below(value, false) == 1.0;
+            if out_of_range {
+                return 0.0;
+            }
+            let non_null = (row_count - stats.null_count) as f64 / row_count as f64;
+            non_null / stats.distinct as f64
+        }
+        None => DEFAULT_EQUALITY,
+    }
+}
+
+/// Fraction of rows between two bounds
+fn range(row_count: u64, stats: Option<&ColumnStats>, lower: &Bound<Value>, upper: &Bound<Value>) -> f64 {
+    let Some(stats) = stats else {
+        return DEFAULT_SELECTIVITY;
+    };
+    if row_count == 0 {
+        return 0.0;
+    }
+    // Values up to `upper` minus values short of `lower`
+    let upper = match upper {
+        Bound::Included(value) => stats.fraction_below(value, true),
+        Bound::Excluded(value) => stats.fraction_below(value, false),
+        Bound::Unbounded => 1.0,
+    };
+    let lower = match lower {
+        Bound::Included(value) => stats.fraction_below(value, false),
+        Bound::Excluded(value) => stats.fraction_below(value, true),
+        Bound::Unbounded => 0.0,
+    };
+    let non_null = (row_count - stats.null_count) as f64 / row_count as f64;
+    (upper - lower).max(0.0) * non_null
+}
+
+/// Fraction of rows for which `condition` holds, treating the conjuncts of
+/// AND and the operands of OR as independent
+fn selectivity<'a>(condition: &Expression, resolve: &ColumnResolver<'a>) -> f64 {
+    let column = |expr: &Expression| match expr {
+        Expression::Column(name) => resolve(None, name),
+        Expression::QualifiedColumn { table, column } => resolve(Some(table), column),
+        _ => None,
+    };
+    match condition {
+        Expression::BinaryOp { op: BinaryOperator::And, .. } => {
+            // Bounds on one column form a single range instead of
+            // independent conditions
+            let mut ranges: Vec<(&Expression, Bound<Value>, Bound<Value>)> = Vec::new();
+            let mut fraction = 1.0;
+            for conjunct in split_conjunction(condition) {
+                let bound = comparison(conjunct).filter(|(reference, ..)| column(reference).is_some());
+                let Some((reference, op, value)) = bound else {
+                    fraction *= selectivity(conjunct, resolve);
+                    continue;
+                };
+                let position = match ranges.iter().position(|(other, ..)| *other == reference) {
+                    Some(position) => position,
+                    None => {
+                        ranges.push((reference, Bound::Unbounded, Bound::Unbounded));
+                        ranges.len() - 1
+                    }
+                };
+                let (_, lower, upper) = &mut ranges[position];
+                match op {
+                    BinaryOperator::LessThan if *upper == Bound::Unbounded => *upper = Bound::Excluded(value.clone()),
+                    BinaryOperator::LessThanOrEqual if *upper == Bound::Unbounded => {
+                        *upper = Bound::Included(value.clone())
+                    }
+                    BinaryOperator::GreaterThan if *lower == Bound::Unbounded => *lower = Bound::Excluded(value.clone()),
+                    BinaryOperator::GreaterThanOrEqual if *lower == Bound::Unbounded => {
+                        *lower = Bound::Included(value.clone())
+                    }
+                    BinaryOperator::LessThan
+                    | BinaryOperator::LessThanOrEqual
+                    | BinaryOperator::GreaterThan
+                    | BinaryOperator::GreaterThanOrEqual => {}
+                    _ => fraction *= selectivity(conjunct, resolve),
+                }
+            }
+            for (reference, lower, upper) in ranges {
+                if let Some((row_count, stats)) = column(reference) {
+                    if (lower.clone(), upper.clone()) != (Bound::Unbounded, Bound::Unbounded) {
+                        fraction *= range(row_count, Some(stats), &lower, &upper);
+                    }
+                }
+            }
+            fraction
+        }
+        Expression::BinaryOp { left, op: BinaryOperator::Or, right } => {
+            let (left, right) = (selectivity(left, resolve), selectivity(right, resolve));
+            left + right - left * right
+        }
+        Expression::UnaryOp { op: UnaryOperator::Not, operand } => 1.0 - selectivity(operand, resolve),
+        Expression::BinaryOp { .. } => {
+            let Some((reference, op, value)) = comparison(condition) else {
+                return DEFAULT_SELECTIVITY;
+            };
+            let Some((row_count, stats)) = column(reference) else {
+                return match op {
+                    BinaryOperator::Equal => DEFAULT_EQUALITY,
+                    _ => DEFAULT_SELECTIVITY,
+                };
+            };
+            let value = value.clone();
+            let stats = Some(stats);
+            match op {
+                BinaryOperator::Equal => equality(row_count, stats, &value),
+                BinaryOperator::NotEqual => {
+                    range(row_count, stats, &Bound::Unbounded, &Bound::Unbounded) - equality(row_count, stats, &value)
+                }
+                BinaryOperator::LessThan => range(row_count, stats, &Bound::Unbounded, &Bound::Excluded(value)),
+                BinaryOperator::LessThanOrEqual => range(row_count, stats, &Bound::Unbounded, &Bound::Included(value)),
+                BinaryOperator::GreaterThan => range(row_count, stats, &Bound::Excluded(value), &Bound::Unbounded),
+                BinaryOperator::GreaterThanOrEqual => {
+                    range(row_count, stats, &Bound::Included(value), &Bound::Unbounded)
+                }
+                _ => DEFAULT_SELECTIVITY,
+            }
+        }
+        Expression::Between { expr, low, high } => match (column(expr), &**low, &**high) {
+            (Some((row_count, stats)), Expression::Literal(low), Expression::Literal(high)) => range(
+                row_count,
+                Some(stats),
+                &Bound::Included(low.clone()),
+                &Bound::Included(high.clone()),
+            ),
+            _ => DEFAULT_SELECTIVITY,
+        },
+        Expression::In { expr, list } => {
+            let stats = column(expr);
+            let fraction: f64 = list
+                .iter()
+                .map(|item| match (item, stats) {
+                    (Expression::Literal(value), Some((row_count, stats))) => equality(row_count, Some(stats), value),
+                    _ => DEFAULT_EQUALITY,
+                })
+                .sum();
+            fraction.min(1.0)
+        }
+        Expression::IsNull(expr) | Expression::IsNotNull(expr) => {
+            let nulls = match column(expr) {
+                Some((0, _)) => 0.0,
+                Some((row_count, stats)) => stats.null_count as f64 / row_count as f64,
+                None => DEFAULT_EQUALITY,
+            };
+            match condition {
+                Expression::IsNull(_) => nulls,
+                _ => 1.0 - nulls,
+            }
+        }
+        _ => DEFAULT_SELECTIVITY,
+    }
+    .clamp(0.0, 1.0)
+}
+
+/// A `column <op> literal` comparison as (column, operator, literal);
+/// `5 < age` becomes `age > 5`
+fn comparison(expr: &Expression) -> Option<(&Expression, BinaryOperator, &Value)> {
+    let Expression::BinaryOp { left, op, right } = expr else {
+        return None;
+    };
+    match (&**left, &**right) {
+        (reference, Expression::Literal(value)) if reference_of(reference).is_some() => {
+            Some((reference, op.clone(), value))
+        }
+        (Expression::Literal(value), reference) if reference_of(reference).is_some() => {
+            let flipped = match op {
+                BinaryOperator::LessThan => BinaryOperator::GreaterThan,
+                BinaryOperator::LessThanOrEqual => BinaryOperator::GreaterThanOrEqual,
+                BinaryOperator::GreaterThan => BinaryOperator::LessThan,
+                BinaryOperator::GreaterThanOrEqual => BinaryOperator::LessThanOrEqual,
+                other => other.clone(),
+            };
+            Some((reference, flipped, value))
+        }
+        _ => None,
+    }
+}
+
+/// Estimated output rows and cost of a plan
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub rows: f64,
+    /// Roughly the number of rows read and processed, inputs included
+    pub cost: f64,
+}
+
+/// Row and cost estimates from table statistics
+#[derive(Debug, Clone, Copy)]
+pub struct CostModel<'a> {
+    stats: &'a HashMap<String, TableStats>,
+}
+
+impl<'a> CostModel<'a> {
+    pub fn new(stats: &'a HashMap<String, TableStats>) -> Self {
+        CostModel { stats }
+    }
+
+    pub fn table(&self, name: &str) -> Option<&'a TableStats> {
+        self.stats.get(name)
+    }
+
+    pub fn estimate(&self, plan: &QueryPlan) -> Estimate {
+        match plan {
+            QueryPlan::Scan { table, filter, .. } => match self.table(table) {
+                Some(stats) => {
+                    let rows = stats.row_count as f64;
+                    Estimate {
+                        rows: rows * filter.as_ref().map_or(1.0, |filter| stats.selectivity(filter)),
+                        cost: rows,
+                    }
+                }
+                None => fixed(plan),
+            },
+            QueryPlan::IndexScan { table, columns, lookup, filter, covering, .. } => match self.table(table) {
+                Some(stats) => {
+                    let rows = stats.row_count as f64;
+                    let fetched = rows * stats.lookup_selectivity(columns, lookup);
+                    // A B-tree descent, then one index entry per match and,
+                    // unless the index covers the query, a table lookup
+                    let per_row = if *covering { 1.0 } else { 2.0 };
+                    Estimate {
+                        rows: fetched.min(rows * filter.as_ref().map_or(1.0, |filter| stats.selectivity(filter))),
+                        cost: (rows + 1.0).log2() + fetched * per_row,
+                    }
+                }
+                None => fixed(plan),
+            },
+            QueryPlan::Join { left, right, join_type, condition } => {
+                let (left_estimate, right_estimate) = (self.estimate(left), self.estimate(right));
+                let (fraction, hashed) = self.join_selectivity(left, right, condition);
+                let mut rows = left_estimate.rows * right_estimate.rows * fraction;
+                if matches!(join_type, JoinType::Left | JoinType::Full) {
+                    rows = rows.max(left_estimate.rows);
+                }
+                if matches!(join_type, JoinType::Right | JoinType::Full) {
+                    rows = rows.max(right_estimate.rows);
+                }
+                let work = if hashed {
+                    left_estimate.rows + right_estimate.rows
+                } else {
+                    left_estimate.rows * right_estimate.rows
+                };
+                Estimate {
+                    rows,
+                    cost: left_estimate.cost + right_estimate.cost + work + rows,
+                }
+            }
+            QueryPlan::SemiJoin { left, right, .. } => {
+                let (left, right) = (self.estimate(left), self.estimate(right));
+                Estimate {
+                    rows: left.rows / 2.0,
+                    cost: left.cost + right.cost + left.rows + right.rows,
+                }
+            }
+            QueryPlan::Alias { input, .. } => self.estimate(input),
+            QueryPlan::Projection { input, .. } | QueryPlan::Distinct { input } => {
+                let input_estimate = self.estimate(input);
+                let rows = match plan {
+                    QueryPlan::Distinct { .. } => input_estimate.rows / 2.0,
+                    _ => input_estimate.rows,
+                };
+                Estimate {
+                    rows,
+                    cost: input_estimate.cost + input_estimate.rows,
+                }
+            }
+            QueryPlan::Selection { input, condition } => {
+                let input_estimate = self.estimate(input);
+                let fraction = selectivity(condition, &|table, column| self.column(input, table, column));
+                Estimate {
+                    rows: input_estimate.rows * fraction,
+                    cost: input_estimate.cost + input_estimate.rows,
+                }
+            }
+            QueryPlan::Sort { input, .. } => {
+                let input = self.estimate(input);
+                Estimate {
+                    rows: input.rows,
+                    cost: input.cost + input.rows * (input.rows + 1.0).log2(),
+                }
+            }
+            QueryPlan::Limit { input, count, offset } => {
+                let input = self.estimate(input);
+                let skipped = offset.unwrap_or(0) as f64;
+                Estimate {
+                    rows: (input.rows - skipped).clamp(0.0, *count as f64),
+                    cost: input.cost,
+                }
+            }
+            QueryPlan::GroupBy { input, group_columns, .. } => {
+                let input_estimate = self.estimate(input);
+                let groups = if group_columns.is_empty() {
+                    1.0
+                } else {
+                    group_columns
+                        .iter()
+                        .map(|expr| match expr {
+                            Expression::Column(name) => self.column(input, None, name),
+                            Expression::QualifiedColumn { table, column } => self.column(input, Some(table), column),
+                            _ => None,
+                        })
+                        .try_fold(1.0, |groups, stats| stats.map(|(_, stats)| groups * stats.distinct.max(1) as f64))
+                        .unwrap_or(input_estimate.rows / 10.0)
+                        .min(input_estimate.rows)
+                };
+                Estimate {
+                    rows: groups,
+                    cost: input_estimate.cost + input_estimate.rows,
+                }
+            }
+            QueryPlan::Union { left, right, .. }
+            | QueryPlan::Intersect { left, right }
+            | QueryPlan::Except { left, right } => {
+                let (left_estimate, right_estimate) = (self.estimate(left), self.estimate(right));
+                let rows = match plan {
+                    QueryPlan::Union { .. } => left_estimate.rows + right_estimate.rows,
+                    QueryPlan::Intersect { .. } => left_estimate.rows.min(right_estimate.rows),
+                    _ => left_estimate.rows / 2.0,
+                };
+                Estimate {
+                    rows,
+                    cost: left_estimate.cost + right_estimate.cost + rows,
+                }
+            }
+            _ => fixed(plan),
+        }
+    }
+
+    /// Fraction of row pairs a join keeps, and whether it runs as a hash
+    /// join. Each equality between columns keeps 1 / (distinct values of
+    /// the column with more of them).
+    fn join_selectivity(&self, left: &QueryPlan, right: &QueryPlan, condition: &JoinConstraint) -> (f64, bool) {
+        let resolve = |table: Option<&str>, column: &str| {
+            self.column(left, table, column).or_else(|| self.column(right, table, column))
+        };
+        let key_fraction = |a: (Option<&str>, &str), b: (Option<&str>, &str)| {
+            let distinct = |(table, column)| resolve(table, column).map(|(_, stats)| stats.distinct.max(1) as f64);
+            match (distinct(a), distinct(b)) {
+                (Some(a), Some(b)) => 1.0 / a.max(b),
+                (Some(distinct), None) | (None, Some(distinct)) => 1.0 / distinct,
+                (None, None) => DEFAULT_EQUALITY,
+            }
+        };
+        match condition {
+            JoinConstraint::None => (1.0, false),
+            JoinConstraint::Using(columns) => (
+                columns
+                    .iter()
+                    .map(|column| key_fraction((None, column), (None, column)))
+                    .product(),
+                !columns.is_empty(),
+            ),
+            JoinConstraint::On(condition) => {
+                let mut fraction = 1.0;
+                let mut hashed = false;
+                for conjunct in split_conjunction(condition) {
+                    fraction *= match conjunct {
+                        Expression::BinaryOp { left: a, op: BinaryOperator::Equal, right: b } => {
+                            match (reference_of(a), reference_of(b)) {
+                                (Some(a), Some(b)) => {
+                                    hashed = true;
+                                    key_fraction(a, b)
+                                }
+                                _ => selectivity(conjunct, &resolve),
+                            }
+                        }
+                        _ => selectivity(conjunct, &resolve),
+                    };
+                }
+                (fraction, hashed)
+            }
+        }
+    }
+
+    /// Statistics of a column read by `plan`, found through the tables it
+    /// scans; `table` may be a table name or an alias
+    fn column(&self, plan: &QueryPlan, table: Option<&str>, column: &str) -> Option<(u64, &'a ColumnStats)> {
+        let mut sources = Vec::new();
+        collect_sources(plan, None, &mut sources);
+        sources.into_iter().find_map(|(name, alias)| {
+            let visible = alias.unwrap_or(name);
+            if table.is_some_and(|table| !table.eq_ignore_ascii_case(visible)) {
+                return None;
+            }
+            let stats = self.table(name)?;
+            stats.column(column).map(|column| (stats.row_count, column))
+        })
+    }
+}
+
+/// The fixed estimates of a plan, for tables without statistics
+fn fixed(plan: &QueryPlan) -> Estimate {
+    Estimate {
+        rows: plan.estimated_rows() as f64,
+        cost: plan.estimated_cost(),
+    }
+}
+
+/// (qualifier, column) of a column reference
+fn reference_of(expr: &Expression) -> Option<(Option<&str>, &str)> {
+    match expr {
+        Expression::Column(name) => Some((None, name)),
+        Expression::QualifiedColumn { table, column } => Some((Some(table), column)),
+        _ => None,
+    }
+}
+
+/// The tables whose columns `plan` outputs, with the alias they go by
+fn collect_sources<'p>(plan: &'p QueryPlan, alias: Option<&'p str>, sources: &mut Vec<(&'p str, Option<&'p str>)>) {
+    match plan {
+        QueryPlan::Scan { table, .. }
+        | QueryPlan::IndexScan { table, .. }
+        | QueryPlan::FtsScan { table, .. }
+        | QueryPlan::ForeignScan { table, .. } => sources.push((table, alias)),
+        QueryPlan::Alias { input, alias } => collect_sources(input, Some(alias), sources),
+        QueryPlan::Join { left, right, .. } => {
+            collect_sources(left, alias, sources);
+            collect_sources(right, alias, sources);
+        }
+        QueryPlan::SemiJoin { left: input, .. }
+        | QueryPlan::Projection { input, .. }
+        | QueryPlan::Selection { input, .. }
+        | QueryPlan::Sort { input, .. }
+        | QueryPlan::Limit { input, .. }
+        | QueryPlan::GroupBy { input, .. }
+        | QueryPlan::Distinct { input } => collect_sources(input, alias, sources),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Row;
+
+    fn orders() -> TableStats {
+        let rows = (0..100)
+            .map(|id| {
+                let customer = if id % 10 == 0 { Value::Null } else { Value::Integer(id % 5) };
+                Row::new(vec![Value::Integer(id), customer])
+            })
+            .collect();
+        TableStats::collect(&Relation::from_table("orders", vec!["id".to_string(), "customer".to_string()], rows))
+    }
+
+    fn condition(sql: &str) -> Expression {
+        match crate::parser::parse_sql(&format!("SELECT id FROM orders WHERE {}", sql)).unwrap() {
+            crate::parser::ast::Statement::Select(select) => select.where_clause.unwrap(),
+            other => panic!("Expected SELECT, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_collect_and_estimate_selectivity() {
+        let stats = orders();
+        assert_eq!(stats.row_count, 100);
+        let id = stats.column("ID").unwrap();
+        assert_eq!((id.distinct, id.null_count, id.histogram.len()), (100, 0, HISTOGRAM_BUCKETS));
+        assert_eq!(id.min, Some(Value::Integer(0)));
+        assert_eq!(id.histogram.last(), Some(&Value::Integer(99)));
+        let customer = stats.column("customer").unwrap();
+        assert_eq!((customer.distinct, customer.null_count), (5, 10));
+
+        let close = |sql: &str, expected: f64| {
+            let estimate = stats.selectivity(&condition(sql));
+            assert!((estimate - expected).abs() < 0.05, "{}: {} vs {}", sql, estimate, expected);
+        };
+        close("id = 42", 0.01);
+        close("id = 500", 0.0);
+        close("id < 25", 0.25);
+        close("id >= 50 AND id < 75", 0.25);
+        close("id BETWEEN 90 AND 200", 0.1);
+        close("customer = 3", 0.18);
+        close("customer IS NULL", 0.1);
+        close("customer = 1 OR customer = 2", 0.34);
+        close("NOT (id < 25)", 0.75);
+    }
+}
@@ -0,0 +1,319 @@
+use crate::parser::ast::{BinaryOperator, Expression, SelectColumn, SelectStatement, UnaryOperator};
+use crate::query::join::{conjunction, split_conjunction};
+use crate::query::plan::{AggregateFunction, TableSchema};
+use crate::types::Value;
+use std::collections::HashMap;
+
+/// Names that column references in a SELECT resolve against: its tables
+/// (by alias when they have one) and its result column aliases
+struct Scope<'a> {
+    tables: Vec<(String, Option<&'a TableSchema>)>,
+    aliases: Vec<String>,
+}
+
+impl<'a> Scope<'a> {
+    fn of(select: &SelectStatement, schemas: &'a HashMap<String, TableSchema>) -> Self {
+        let mut tables = Vec::new();
+        if let Some(from) = &select.from {
+            let sources = std::iter::once((&from.table, &from.alias))
+                .chain(from.joins.iter().map(|join| (&join.table, &join.alias)));
+            for (table, alias) in sources {
+                tables.push((alias.clone().unwrap_or_else(|| table.clone()), schemas.get(table)));
+            }
+        }
+        let aliases = select
+            .columns
+            .iter()
+            .filter_map(|column| match column {
+                SelectColumn::Expression { alias, .. } => alias.clone(),
+                SelectColumn::Wildcard => None,
+            })
+            .collect();
+        Scope { tables, aliases }
+    }
+
+    /// Whether a column reference names something in this scope; a table
+    /// whose schema is unknown is assumed to have the column
+    fn resolves(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Column(name) => {
+                name == "*"
+                    || self.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+                    || self
+                        .tables
+                        .iter()
+                        .any(|(_, schema)| schema.is_none_or(|schema| schema.position(name).is_some()))
+            }
+            Expression::QualifiedColumn { table, .. } => {
+                self.tables.iter().any(|(name, _)| name.eq_ignore_ascii_case(table))
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Column references in `select` that resolve outside it, i.e. to an
+/// enclosing query; a subquery with none is uncorrelated
+pub fn outer_references(select: &SelectStatement, schemas: &HashMap<String, TableSchema>) -> Vec<Expression> {
+    let scope = Scope::of(select, schemas);
+    let mut references = Vec::new();
+    for expr in select.expressions() {
+        collect_outer(expr, &scope, schemas, &mut references);
+    }
+    references
+}
+
+fn collect_outer(
+    expr: &Expression,
+    scope: &Scope,
+    schemas: &HashMap<String, TableSchema>,
+    references: &mut Vec<Expression>,
+) {
+    let mut push = |reference: Expression| {
+        if !scope.resolves(&reference) && !references.contains(&reference) {
+            references.push(reference);
+        }
+    };
+    match expr {
+        Expression::Column(_) | Expression::QualifiedColumn { .. } => push(expr.clone()),
+        Expression::Subquery(select) | Expression::Exists(select) => {
+            outer_references(select, schemas).into_iter().for_each(push);
+        }
+        Expression::InSubquery { expr, subquery } => {
+            outer_references(subquery, schemas).into_iter().for_each(push);
+            collect_outer(expr, scope, schemas, references);
+        }
+        other => {
+            for child in other.children() {
+                collect_outer(child, scope, schemas, references);
+            }
+        }
+    }
+}
+
+/// `select` with each outer reference replaced by its value for the
+/// current row of the enclosing query
+pub fn substitute(
+    select: &SelectStatement,
+    values: &[(Expression, Value)],
+    schemas: &HashMap<String, TableSchema>,
+) -> SelectStatement {
+    let mut select = select.clone();
+    for expr in select.expressions_mut() {
+        substitute_expression(expr, values, schemas);
+    }
+    select
+}
+
+fn substitute_expression(expr: &mut Expression, values: &[(Expression, Value)], schemas: &HashMap<String, TableSchema>) {
+    // A nested subquery only takes the values it does not resolve itself
+    let nested = |select: &SelectStatement| {
+        let outer = outer_references(select, schemas);
+        let values: Vec<_> = values.iter().filter(|(reference, _)| outer.contains(reference)).cloned().collect();
+        Box::new(substitute(select, &values, schemas))
+    };
+    match expr {
+        Expression::Column(_) | Expression::QualifiedColumn { .. } => {
+            if let Some((_, value)) = values.iter().find(|(reference, _)| reference == expr) {
+                *expr = Expression::Literal(value.clone());
+            }
+        }
+        Expression::Subquery(select) | Expression::Exists(select) => *select = nested(select),
+        Expression::InSubquery { expr, subquery } => {
+            *subquery = nested(subquery);
+            substitute_expression(expr, values, schemas);
+        }
+        other => {
+            for child in other.children_mut() {
+                substitute_expression(child, values, schemas);
+            }
+        }
+    }
+}
+
+/// A `[NOT] EXISTS` or `IN` subquery in a WHERE clause, rewritten as a
+/// semi join against the subquery's tables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decorrelated {
+    /// The subquery's FROM, filtered by its conditions that do not
+    /// reference the enclosing query
+    pub inner: SelectStatement,
+    /// Pairs of (enclosing query expression, subquery expression) that
+    /// must be equal
+    pub keys: Vec<(Expression, Expression)>,
+    /// `NOT EXISTS`: keep the rows without a match
+    pub anti: bool,
+}
+
+/// Rewrite a WHERE conjunct as a semi join when its subquery is tied to
+/// the enclosing query only by equalities. `NOT IN` is left alone, since a
+/// NULL in its subquery makes it unknown rather than true.
+pub fn decorrelate(conjunct: &Expression, schemas: &HashMap<String, TableSchema>) -> Option<Decorrelated> {
+    let (select, anti, mut keys) = match conjunct {
+        Expression::Exists(select) => (select, false, Vec::new()),
+        Expression::UnaryOp { op: UnaryOperator::Not, operand } => match &**operand {
+            Expression::Exists(select) => (select, true, Vec::new()),
+            _ => return None,
+        },
+        Expression::InSubquery { expr, subquery } => {
+            let [SelectColumn::Expression { expr: selected, .. }] = subquery.columns.as_slice() else {
+                return None;
+            };
+            if expr.has_subquery() || AggregateFunction::contains_aggregate(selected) {
+                return None;
+            }
+            (subquery, false, vec![((**expr).clone(), selected.clone())])
+        }
+        _ => return None,
+    };
+    let aggregated = select.columns.iter().any(|column| match column {
+        SelectColumn::Expression { expr, .. } => AggregateFunction::contains_aggregate(expr),
+        SelectColumn::Wildcard => false,
+    });
+    if select.from.is_none() || select.group_by.is_some() || select.having.is_some() || select.limit.is_some() || aggregated {
+        return None;
+    }
+
+    let scope = Scope::of(select, schemas);
+    let outer_of = |expr: &Expression| {
+        let mut references = Vec::new();
+        collect_outer(expr, &scope, schemas, &mut references);
+        references
+    };
+    if keys.iter().any(|(_, selected)| !outer_of(selected).is_empty()) {
+        return None;
+    }
+    let mut filters = Vec::new();
+    for part in select.where_clause.iter().flat_map(split_conjunction) {
+        if outer_of(part).is_empty() {
+            filters.push(part.clone());
+            continue;
+        }
+        // `outer = inner` in either order
+        let Expression::BinaryOp { left, op: BinaryOperator::Equal, right } = part else {
+            return None;
+        };
+        let only_outer = |expr: &Expression| {
+            !expr.has_subquery() && !expr.column_names().is_empty() && {
+                let mut columns = Vec::new();
+                collect_columns(expr, &mut columns);
+                columns.iter().all(|column| !scope.resolves(column))
+            }
+        };
+        if only_outer(left) && outer_of(right).is_empty() {
+            keys.push(((**left).clone(), (**right).clone()));
+        } else if only_outer(right) && outer_of(left).is_empty() {
+            keys.push(((**right).clone(), (**left).clone()));
+        } else {
+            return None;
+        }
+    }
+    if keys.is_empty() {
+        // Uncorrelated EXISTS runs once instead
+        return None;
+    }
+
+    Some(Decorrelated {
+        inner: SelectStatement {
+            distinct: false,
+            columns: vec![SelectColumn::Wildcard],
+            from: select.from.clone(),
+            where_clause: conjunction(filters),
+            group_by: None,
+            having: None,
+            order_by: None,
+            limit: None,
+        },
+        keys,
+        anti,
+    })
+}
+
+fn collect_columns(expr: &Expression, columns: &mut Vec<Expression>) {
+    match expr {
+        Expression::Column(_) | Expression::QualifiedColumn { .. } => columns.push(expr.clone()),
+        other => other.children().into_iter().for_each(|child| collect_columns(child, columns)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::Statement;
+
+    fn schemas() -> HashMap<String, TableSchema> {
+        let mut schemas = HashMap::new();
+        schemas.insert("users".to_string(), TableSchema::untyped(&["id".to_string(), "name".to_string()]));
+        schemas.insert(
+            "orders".to_string(),
+            TableSchema::untyped(&["id".to_string(), "user_id".to_string(), "total".to_string()]),
+        );
+        schemas
+    }
+
+    fn where_clause(sql: &str) -> Expression {
+        match crate::parser::parse_sql(sql).unwrap() {
+            Statement::Select(select) => select.where_clause.unwrap(),
+            other => panic!("Expected SELECT, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_outer_references_and_substitution() {
+        let schemas = schemas();
+        let Expression::Exists(subquery) = where_clause(
+            "SELECT name FROM users AS u WHERE EXISTS (SELECT id FROM orders WHERE user_id = u.id AND total > \
+             (SELECT total FROM orders AS o2 WHERE o2.id = name))",
+        ) else {
+            panic!("Expected EXISTS");
+        };
+        let outer = outer_references(&subquery, &schemas);
+        assert_eq!(
+            outer,
+            vec![
+                Expression::QualifiedColumn { table: "u".to_string(), column: "id".to_string() },
+                Expression::Column("name".to_string()),
+            ]
+        );
+
+        let bound = substitute(
+            &subquery,
+            &[(outer[0].clone(), Value::Integer(1)), (outer[1].clone(), Value::Integer(2))],
+            &schemas,
+        );
+        assert!(outer_references(&bound, &schemas).is_empty());
+    }
+
+    #[test]
+    fn test_decorrelate_exists_and_in() {
+        let schemas = schemas();
+        let exists = where_clause(
+            "SELECT * FROM users WHERE NOT EXISTS (SELECT * FROM orders AS o WHERE o.user_id = users.id AND o.total > 10)",
+        );
+        let decorrelated = decorrelate(&exists, &schemas).unwrap();
+        assert!(decorrelated.anti);
+        assert_eq!(
+            decorrelated.keys,
+            vec![(
+                Expression::QualifiedColumn { table: "users".to_string(), column: "id".to_string() },
+                Expression::QualifiedColumn { table: "o".to_string(), column: "user_id".to_string() },
+            )]
+        );
+        assert_eq!(decorrelated.inner.where_clause.unwrap().to_string(), "o.total > 10");
+
+        let in_subquery = where_clause("SELECT * FROM users WHERE id IN (SELECT user_id FROM orders)");
+        let decorrelated = decorrelate(&in_subquery, &schemas).unwrap();
+        assert_eq!(decorrelated.keys.len(), 1);
+        assert!(!decorrelated.anti);
+
+        // NOT IN, non-equality correlation and aggregates keep the subquery
+        for sql in [
+            "SELECT * FROM users WHERE id NOT IN (SELECT user_id FROM orders)",
+            "SELECT * FROM users WHERE EXISTS (SELECT * FROM orders WHERE user_id > users.id)",
+            "SELECT * FROM users WHERE EXISTS (SELECT COUNT(*) FROM orders WHERE user_id = users.id)",
+            "SELECT * FROM users WHERE EXISTS (SELECT * FROM orders)",
+        ] {
+            assert_eq!(decorrelate(&where_clause(sql), &schemas), None, "{}", sql);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Authentication hooks for server connections
+
+use std::collections::HashMap;
+
+/// Who is connecting, from the startup message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub user: String,
+    pub database: String,
+    /// Every startup parameter, e.g. `application_name`
+    pub parameters: HashMap<String, String>,
+}
+
+/// How a connection proves who it is
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthMethod {
+    /// Let the client in without a password
+    Trust,
+    /// Ask for a password, sent in cleartext, and pass it to
+    /// [`Authenticator::check_password`]
+    Password,
+    /// Turn the client away with this message
+    Reject(String),
+}
+
+/// Decides whether a client may connect
+///
+/// The server asks [`method`](Authenticator::method) once per connection;
+/// with [`AuthMethod::Password`] it then asks the client for a password and
+/// lets it in if [`check_password`](Authenticator::check_password) agrees.
+pub trait Authenticator: Send + Sync {
+    fn method(&self, credentials: &Credentials) -> AuthMethod;
+
+    fn check_password(&self, _credentials: &Credentials, _password: &str) -> bool {
+        false
+    }
+}
+
+/// Let every client in
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrustAuthenticator;
+
+impl Authenticator for TrustAuthenticator {
+    fn method(&self, _credentials: &Credentials) -> AuthMethod {
+        AuthMethod::Trust
+    }
+}
+
+/// Let in the users of a fixed list, with their passwords
+#[derive(Debug, Clone, Default)]
+pub struct PasswordAuthenticator {
+    users: HashMap<String, String>,
+}
+
+impl PasswordAuthenticator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_user(mut self, user: impl Into<String>, password: impl Into<String>) -> Self {
+        self.users.insert(user.into(), password.into());
+        self
+    }
+}
+
+impl Authenticator for PasswordAuthenticator {
+    fn method(&self, credentials: &Credentials) -> AuthMethod {
+        if self.users.contains_key(&credentials.user) {
+            AuthMethod::Password
+        } else {
+            AuthMethod::Reject(format!("role \"{}\" does not exist", credentials.user))
+        }
+    }
+
+    fn check_password(&self, credentials: &Credentials, password: &str) -> bool {
+        self.users.get(&credentials.user).is_some_and(|expected| expected == password)
+    }
+}
@@ -0,0 +1,536 @@
+//! PostgreSQL wire-protocol server
+//!
+//! `sqlite serve --listen :5433` lets PostgreSQL drivers and tools such as
+//! psql connect over the simple query protocol. Each connection has its own
+//! [`Session`], so BEGIN ... COMMIT works per connection. A query holding
+//! several statements runs them in order and stops at the first error;
+//! outside a transaction, the statements before it stay committed.
+//!
+//! Rows are sent in text format. The extended query protocol (prepared
+//! statements bound over the wire), TLS and query cancellation are not
+//! supported: clients are told so and can carry on with simple queries.
+
+pub mod auth;
+pub mod protocol;
+
+pub use auth::{AuthMethod, Authenticator, Credentials, PasswordAuthenticator, TrustAuthenticator};
+pub use protocol::{BackendMessage, FrontendMessage, StartupMessage, TypeOid};
+
+use crate::engine::{CategoricalSQLite, Session};
+use crate::error::{SqlError, SqlResult};
+use crate::parser::split_statements;
+use crate::query::QueryResult;
+use crate::types::Value;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufStream};
+use tokio::net::TcpListener;
+use tokio::task::LocalSet;
+
+/// Version reported to clients, which expect a PostgreSQL version number
+const SERVER_VERSION: &str = "14.0 (categorical-sqlite 0.1.0)";
+
+/// Serves one database to PostgreSQL clients
+#[derive(Clone)]
+pub struct Server {
+    db: CategoricalSQLite,
+    authenticator: Arc<dyn Authenticator>,
+    next_process_id: Arc<AtomicI32>,
+}
+
+impl Server {
+    /// A server that lets every client in; see [`Server::with_authenticator`]
+    pub fn new(db: CategoricalSQLite) -> Self {
+        Server {
+            db,
+            authenticator: Arc::new(TrustAuthenticator),
+            next_process_id: Arc::new(AtomicI32::new(1)),
+        }
+    }
+
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Arc::new(authenticator);
+        self
+    }
+
+    /// Listen on `address`, e.g. `:5433` (every interface) or
+    /// `127.0.0.1:5433`, and serve clients until an error
+    pub async fn listen(self, address: &str) -> SqlResult<()> {
+        let listener = TcpListener::bind(listen_address(address))
+            .await
+            .map_err(|error| SqlError::connection_error(format!("Cannot listen on {}: {}", address, error)))?;
+        self.serve(listener).await
+    }
+
+    /// Serve the clients that connect to `listener`. Statement futures are
+    /// not `Send`, so connections are tasks of a local set on this thread.
+    pub async fn serve(self, listener: TcpListener) -> SqlResult<()> {
+        LocalSet::new()
+            .run_until(async move {
+                loop {
+                    // Failing to accept one client (e.g. out of file
+                    // descriptors) does not stop the server
+                    let Ok((stream, _)) = listener.accept().await else {
+                        continue;
+                    };
+                    let _ = stream.set_nodelay(true);
+                    let server = self.clone();
+                    tokio::task::spawn_local(async move {
+                        // A client that goes away or breaks the protocol
+                        // only loses its own connection
+                        let _ = server.handle(stream).await;
+                    });
+                }
+            })
+            .await
+    }
+
+    /// Talk to one client until it disconnects
+    pub async fn handle<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) -> SqlResult<()> {
+        let mut connection = Connection {
+            stream: BufStream::new(stream),
+            out: Vec::new(),
+        };
+        if !self.start_up(&mut connection).await? {
+            return Ok(());
+        }
+        let mut session = self.db.session();
+        connection.send(BackendMessage::ReadyForQuery(session.status()));
+        connection.flush().await?;
+
+        let result = self.serve_queries(&mut connection, &mut session).await;
+        let closed = session.close().await;
+        result.and(closed)
+    }
+
+    /// Read the startup message and authenticate the client; whether it
+    /// was let in
+    async fn start_up<S: AsyncRead + AsyncWrite + Unpin>(&self, connection: &mut Connection<S>) -> SqlResult<bool> {
+        let parameters = loop {
+            match protocol::read_startup(&mut connection.stream).await? {
+                StartupMessage::EncryptionRequest => {
+                    connection.send(BackendMessage::EncryptionResponse(false));
+                    connection.flush().await?;
+                }
+                StartupMessage::CancelRequest => return Ok(false),
+                StartupMessage::Startup { parameters } => break parameters,
+            }
+        };
+        let Some(user) = parameters.get("user").cloned() else {
+            connection.fail("28000", "no PostgreSQL user name specified in startup packet").await?;
+            return Ok(false);
+        };
+        let credentials = Credentials {
+            database: parameters.get("database").cloned().unwrap_or_else(|| user.clone()),
+            user,
+            parameters,
+        };
+
+        match self.authenticator.method(&credentials) {
+            AuthMethod::Trust => {}
+            AuthMethod::Reject(message) => {
+                connection.fail("28000", &message).await?;
+                return Ok(false);
+            }
+            AuthMethod::Password => {
+                connection.send(BackendMessage::AuthenticationCleartextPassword);
+                connection.flush().await?;
+                let accepted = match protocol::read_message(&mut connection.stream).await? {
+                    Some(FrontendMessage::Password(password)) => {
+                        self.authenticator.check_password(&credentials, &password)
+                    }
+                    _ => false,
+                };
+                if !accepted {
+                    let message = format!("password authentication failed for user \"{}\"", credentials.user);
+                    connection.fail("28P01", &message).await?;
+                    return Ok(false);
+                }
+            }
+        }
+
+        connection.send(BackendMessage::AuthenticationOk);
+        let application_name = credentials.parameters.get("application_name").cloned().unwrap_or_default();
+        for (name, value) in [
+            ("server_version", SERVER_VERSION.to_string()),
+            ("server_encoding", "UTF8".to_string()),
+            ("client_encoding", "UTF8".to_string()),
+            ("DateStyle", "ISO, MDY".to_string()),
+            ("TimeZone", "UTC".to_string()),
+            ("integer_datetimes", "on".to_string()),
+            ("standard_conforming_strings", "on".to_string()),
+            ("application_name", application_name),
+        ] {
+            connection.send(BackendMessage::ParameterStatus {
+                name: name.to_string(),
+                value,
+            });
+        }
+        connection.send(BackendMessage::BackendKeyData {
+            process_id: self.next_process_id.fetch_add(1, Ordering::Relaxed),
+            secret_key: uuid::Uuid::new_v4().as_u128() as i32,
+        });
+        Ok(true)
+    }
+
+    async fn serve_queries<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        connection: &mut Connection<S>,
+        session: &mut Session,
+    ) -> SqlResult<()> {
+        // After an unsupported message, the rest of its extended query
+        // sequence is skipped up to Sync, as after a failed extended query
+        let mut skipping = false;
+        while let Some(message) = protocol::read_message(&mut connection.stream).await? {
+            match message {
+                FrontendMessage::Terminate => break,
+                FrontendMessage::Query(sql) => {
+                    skipping = false;
+                    run_query(connection, session, &sql).await;
+                    connection.send(BackendMessage::ReadyForQuery(session.status()));
+                    connection.flush().await?;
+                }
+                FrontendMessage::Sync => {
+                    skipping = false;
+                    connection.send(BackendMessage::ReadyForQuery(session.status()));
+                    connection.flush().await?;
+                }
+                FrontendMessage::Password(_) | FrontendMessage::Unsupported(_) if !skipping => {
+                    skipping = true;
+                    connection.send(BackendMessage::ErrorResponse {
+                        code: "0A000",
+                        message: "only the simple query protocol is supported".to_string(),
+                    });
+                    connection.flush().await?;
+                }
+                FrontendMessage::Password(_) | FrontendMessage::Unsupported(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Server {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Server").field("db", &self.db).finish_non_exhaustive()
+    }
+}
+
+/// `:5433` listens on every interface, as `0.0.0.0:5433`
+pub fn listen_address(address: &str) -> String {
+    match address.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{}", port),
+        None => address.to_string(),
+    }
+}
+
+struct Connection<S> {
+    stream: BufStream<S>,
+    /// Messages waiting to be flushed
+    out: Vec<u8>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    fn send(&mut self, message: BackendMessage) {
+        message.encode(&mut self.out);
+    }
+
+    async fn flush(&mut self) -> SqlResult<()> {
+        let out = std::mem::take(&mut self.out);
+        self.stream
+            .write_all(&out)
+            .await
+            .map_err(|error| SqlError::connection_error(error.to_string()))?;
+        self.stream
+            .flush()
+            .await
+            .map_err(|error| SqlError::connection_error(error.to_string()))
+    }
+
+    /// Send a fatal error before closing the connection
+    async fn fail(&mut self, code: &'static str, message: &str) -> SqlResult<()> {
+        self.send(BackendMessage::ErrorResponse {
+            code,
+            message: message.to_string(),
+        });
+        self.flush().await
+    }
+}
+
+/// Run the statements of a simple query, sending each one's result, up to
+/// the first error
+async fn run_query<S: AsyncRead + AsyncWrite + Unpin>(connection: &mut Connection<S>, session: &mut Session, sql: &str) {
+    let statements = split_statements(sql);
+    if statements.is_empty() {
+        connection.send(BackendMessage::EmptyQueryResponse);
+    }
+    for statement in statements {
+        match session.execute_sql(statement).await {
+            Ok(result) => send_result(connection, result),
+            Err(error) => {
+                connection.send(BackendMessage::ErrorResponse {
+                    code: protocol::sqlstate(&error),
+                    message: error.to_string(),
+                });
+                break;
+            }
+        }
+    }
+}
+
+fn send_result<S: AsyncRead + AsyncWrite + Unpin>(connection: &mut Connection<S>, result: QueryResult) {
+    let tag = match result {
+        QueryResult::Select { columns, rows } => {
+            let fields = columns
+                .into_iter()
+                .enumerate()
+                .map(|(position, name)| (name, column_type(rows.iter().map(|row| &row.values[position]))))
+                .collect();
+            connection.send(BackendMessage::RowDescription(fields));
+            let count = rows.len();
+            for row in rows {
+                connection.send(BackendMessage::DataRow(row.values.iter().map(text_value).collect()));
+            }
+            format!("SELECT {}", count)
+        }
+        QueryResult::Insert { rows_affected } => format!("INSERT 0 {}", rows_affected),
+        QueryResult::Update { rows_affected } => format!("UPDATE {}", rows_affected),
+        QueryResult::Delete { rows_affected } => format!("DELETE {}", rows_affected),
+        QueryResult::CreateTable => "CREATE TABLE".to_string(),
+        QueryResult::DropTable => "DROP TABLE".to_string(),
+        QueryResult::CreateIndex => "CREATE INDEX".to_string(),
+        QueryResult::DropIndex => "DROP INDEX".to_string(),
+        QueryResult::Begin => "BEGIN".to_string(),
+        QueryResult::Commit => "COMMIT".to_string(),
+        QueryResult::Rollback => "ROLLBACK".to_string(),
+        QueryResult::Vacuum => "VACUUM".to_string(),
+    };
+    connection.send(BackendMessage::CommandComplete(tag));
+}
+
+/// Type of a result column: that of its values if they all agree, text
+/// otherwise (or when every value is NULL)
+fn column_type<'a>(values: impl Iterator<Item = &'a Value>) -> TypeOid {
+    let mut types = values.filter_map(|value| match value {
+        Value::Null => None,
+        Value::Integer(_) => Some(TypeOid::Int8),
+        Value::Real(_) => Some(TypeOid::Float8),
+        Value::Text(_) => Some(TypeOid::Text),
+        Value::Blob(_) => Some(TypeOid::Bytea),
+        Value::Boolean(_) => Some(TypeOid::Bool),
+    });
+    let Some(first) = types.next() else {
+        return TypeOid::Text;
+    };
+    if types.all(|oid| oid == first) {
+        first
+    } else {
+        TypeOid::Text
+    }
+}
+
+/// A value in PostgreSQL's text format; `None` for NULL
+fn text_value(value: &Value) -> Option<Vec<u8>> {
+    let text = match value {
+        Value::Null => return None,
+        Value::Integer(integer) => integer.to_string(),
+        Value::Real(real) if real.is_nan() => "NaN".to_string(),
+        Value::Real(real) if real.is_infinite() => {
+            if *real > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+        }
+        Value::Real(real) => real.to_string(),
+        Value::Text(text) => text.clone(),
+        Value::Blob(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("\\x{}", hex)
+        }
+        Value::Boolean(boolean) => if *boolean { "t" } else { "f" }.to_string(),
+    };
+    Some(text.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::DatabaseConfig;
+    use tokio::io::{AsyncReadExt, DuplexStream};
+
+    /// Minimal client side of the protocol
+    struct Client {
+        stream: DuplexStream,
+    }
+
+    impl Client {
+        async fn start_up(&mut self, user: &str) {
+            let mut body = PROTOCOL_VERSION_BYTES.to_vec();
+            for field in ["user", user, "database", "main", ""] {
+                body.extend_from_slice(field.as_bytes());
+                body.push(0);
+            }
+            let mut message = (body.len() as i32 + 4).to_be_bytes().to_vec();
+            message.extend_from_slice(&body);
+            self.stream.write_all(&message).await.unwrap();
+        }
+
+        async fn send(&mut self, tag: u8, body: &str) {
+            let mut message = vec![tag];
+            message.extend_from_slice(&(body.len() as i32 + 5).to_be_bytes());
+            message.extend_from_slice(body.as_bytes());
+            message.push(0);
+            self.stream.write_all(&message).await.unwrap();
+        }
+
+        async fn read_one(&mut self) -> (u8, Vec<u8>) {
+            let tag = self.stream.read_u8().await.unwrap();
+            let len = self.stream.read_i32().await.unwrap() as usize;
+            let mut body = vec![0; len - 4];
+            self.stream.read_exact(&mut body).await.unwrap();
+            (tag, body)
+        }
+
+        /// Read messages up to and including the next one tagged `last`
+        async fn read_until(&mut self, last: u8) -> Vec<(u8, Vec<u8>)> {
+            let mut messages = Vec::new();
+            loop {
+                let message = self.read_one().await;
+                let done = message.0 == last;
+                messages.push(message);
+                if done {
+                    return messages;
+                }
+            }
+        }
+
+        /// Send a simple query; returns the CommandComplete tags ("ERROR"
+        /// for an error), the DataRow values and the final transaction
+        /// status
+        async fn query(&mut self, sql: &str) -> (Vec<String>, Vec<Vec<Option<String>>>, u8) {
+            self.send(b'Q', sql).await;
+            let mut tags = Vec::new();
+            let mut rows = Vec::new();
+            let mut status = 0;
+            for (tag, body) in self.read_until(b'Z').await {
+                match tag {
+                    b'C' => tags.push(String::from_utf8(body[..body.len() - 1].to_vec()).unwrap()),
+                    b'E' => tags.push("ERROR".to_string()),
+                    b'D' => rows.push(decode_row(&body)),
+                    b'Z' => status = body[0],
+                    _ => {}
+                }
+            }
+            (tags, rows, status)
+        }
+    }
+
+    const PROTOCOL_VERSION_BYTES: [u8; 4] = protocol::PROTOCOL_VERSION.to_be_bytes();
+
+    fn decode_row(body: &[u8]) -> Vec<Option<String>> {
+        let count = i16::from_be_bytes([body[0], body[1]]) as usize;
+        let mut values = Vec::with_capacity(count);
+        let mut at = 2;
+        for _ in 0..count {
+            let len = i32::from_be_bytes(body[at..at + 4].try_into().unwrap());
+            at += 4;
+            if len < 0 {
+                values.push(None);
+            } else {
+                let len = len as usize;
+                values.push(Some(String::from_utf8(body[at..at + len].to_vec()).unwrap()));
+                at += len;
+            }
+        }
+        values
+    }
+
+    fn text(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_simple_queries_and_transactions() {
+        let db = CategoricalSQLite::new(DatabaseConfig::default());
+        let server = Server::new(db.clone());
+        let (client_end, server_end) = tokio::io::duplex(1 << 16);
+        let mut client = Client { stream: client_end };
+
+        let conversation = async {
+            client.start_up("ann").await;
+            let startup = client.read_until(b'Z').await;
+            assert_eq!(startup[0], (b'R', 0i32.to_be_bytes().to_vec()));
+            assert!(startup.iter().any(|(tag, _)| *tag == b'K'));
+
+            let (tags, _, status) = client
+                .query("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT, score REAL); INSERT INTO t (name, score) VALUES ('a;b', 1.5)")
+                .await;
+            assert_eq!(tags, vec!["CREATE TABLE", "INSERT 0 1"]);
+            assert_eq!(status, b'I');
+
+            let (tags, _, status) = client.query("BEGIN; INSERT INTO t (name) VALUES ('c')").await;
+            assert_eq!((tags, status), (vec!["BEGIN".to_string(), "INSERT 0 1".to_string()], b'T'));
+            let (tags, _, status) = client.query("SELECT nope FROM t").await;
+            assert_eq!((tags, status), (vec!["ERROR".to_string()], b'E'));
+            let (tags, _, status) = client.query("COMMIT").await;
+            assert_eq!((tags, status), (vec!["ROLLBACK".to_string()], b'I'));
+
+            let (tags, rows, _) = client.query("SELECT id, name, score FROM t ORDER BY id").await;
+            assert_eq!(tags, vec!["SELECT 1"]);
+            assert_eq!(rows, vec![vec![text("1"), text("a;b"), text("1.5")]]);
+
+            let (tags, _, _) = client.query(" ; ").await;
+            assert!(tags.is_empty());
+
+            // Extended query messages are refused once, up to Sync
+            client.send(b'P', "").await;
+            client.send(b'B', "").await;
+            client.send(b'S', "").await;
+            let replies: Vec<u8> = client.read_until(b'Z').await.into_iter().map(|(tag, _)| tag).collect();
+            assert_eq!(replies, vec![b'E', b'Z']);
+
+            client.send(b'X', "").await;
+        };
+        let (served, ()) = tokio::join!(server.handle(server_end), conversation);
+        served.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_password_authentication() {
+        let db = CategoricalSQLite::new(DatabaseConfig::default());
+        let server = Server::new(db).with_authenticator(PasswordAuthenticator::new().with_user("ann", "secret"));
+
+        for (user, password, accepted) in [("ann", "secret", true), ("ann", "guess", false), ("bob", "", false)] {
+            let (client_end, server_end) = tokio::io::duplex(1 << 16);
+            let mut client = Client { stream: client_end };
+            let conversation = async {
+                client.start_up(user).await;
+                let (tag, body) = client.read_one().await;
+                if user == "bob" {
+                    assert_eq!(tag, b'E');
+                    return;
+                }
+                assert_eq!((tag, body), (b'R', 3i32.to_be_bytes().to_vec()));
+                client.send(b'p', password).await;
+                let (tag, _) = client.read_one().await;
+                assert_eq!(tag, if accepted { b'R' } else { b'E' });
+                if accepted {
+                    client.read_until(b'Z').await;
+                    client.send(b'X', "").await;
+                }
+            };
+            let (served, ()) = tokio::join!(server.handle(server_end), conversation);
+            served.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_listen_address_and_text_values() {
+        assert_eq!(listen_address(":5433"), "0.0.0.0:5433");
+        assert_eq!(listen_address("127.0.0.1:5433"), "127.0.0.1:5433");
+        assert_eq!(text_value(&Value::Boolean(true)), Some(b"t".to_vec()));
+        assert_eq!(text_value(&Value::Blob(vec![0xde, 0xad])), Some(b"\\xdead".to_vec()));
+        assert_eq!(text_value(&Value::Null), None);
+        let values = [Value::Null, Value::Integer(1), Value::Integer(2)];
+        assert_eq!(column_type(values.iter()), TypeOid::Int8);
+        let values = [Value::Integer(1), Value::Text("x".to_string())];
+        assert_eq!(column_type(values.iter()), TypeOid::Text);
+    }
+}
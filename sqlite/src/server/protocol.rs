@@ -0,0 +1,278 @@
+//! PostgreSQL frontend/backend protocol, version 3
+//!
+//! Only the messages of startup, cleartext password authentication and the
+//! simple query protocol are decoded; other frontend messages are read
+//! whole and reported as [`FrontendMessage::Unsupported`].
+
+use crate::engine::TransactionStatus;
+use crate::error::{SqlError, SqlResult};
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Protocol version 3.0, as sent in a startup message
+pub const PROTOCOL_VERSION: i32 = 196_608;
+const SSL_REQUEST: i32 = 80_877_103;
+const GSSENC_REQUEST: i32 = 80_877_104;
+const CANCEL_REQUEST: i32 = 80_877_102;
+
+/// Longest message accepted from a client
+const MAX_MESSAGE_LEN: usize = 64 << 20;
+
+/// First message of a connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartupMessage {
+    /// Connection parameters such as `user`, `database` and
+    /// `application_name`
+    Startup { parameters: HashMap<String, String> },
+    /// The client asks for TLS, or GSSAPI encryption, before starting up
+    EncryptionRequest,
+    /// A request to cancel a query running on another connection
+    CancelRequest,
+}
+
+/// Message from a started-up client
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrontendMessage {
+    /// `Q`: one or more statements separated by semicolons
+    Query(String),
+    /// `p`: reply to an authentication request
+    Password(String),
+    /// `S`: end of an extended query message sequence
+    Sync,
+    /// `X`: the client is closing the connection
+    Terminate,
+    /// Any other message, by type byte
+    Unsupported(u8),
+}
+
+/// Type of a result column, by PostgreSQL type OID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeOid {
+    Bool = 16,
+    Bytea = 17,
+    Int8 = 20,
+    Text = 25,
+    Float8 = 701,
+}
+
+impl TypeOid {
+    /// Size of the type, or -1 for variable-length types
+    fn size(self) -> i16 {
+        match self {
+            TypeOid::Bool => 1,
+            TypeOid::Int8 | TypeOid::Float8 => 8,
+            TypeOid::Bytea | TypeOid::Text => -1,
+        }
+    }
+}
+
+/// Message to the client
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendMessage {
+    AuthenticationOk,
+    AuthenticationCleartextPassword,
+    ParameterStatus { name: String, value: String },
+    BackendKeyData { process_id: i32, secret_key: i32 },
+    ReadyForQuery(TransactionStatus),
+    /// Column names and types of the rows that follow; values are sent as
+    /// text
+    RowDescription(Vec<(String, TypeOid)>),
+    /// One row, `None` for NULL
+    DataRow(Vec<Option<Vec<u8>>>),
+    /// A statement finished, e.g. `SELECT 3` or `INSERT 0 1`
+    CommandComplete(String),
+    EmptyQueryResponse,
+    ErrorResponse { code: &'static str, message: String },
+    /// Reply to an encryption request: `N` turns it down
+    EncryptionResponse(bool),
+}
+
+impl BackendMessage {
+    /// Append the message, framed, to `out`
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let (tag, body) = match self {
+            BackendMessage::EncryptionResponse(accepted) => {
+                out.push(if *accepted { b'S' } else { b'N' });
+                return;
+            }
+            BackendMessage::AuthenticationOk => (b'R', 0i32.to_be_bytes().to_vec()),
+            BackendMessage::AuthenticationCleartextPassword => (b'R', 3i32.to_be_bytes().to_vec()),
+            BackendMessage::ParameterStatus { name, value } => {
+                let mut body = Vec::new();
+                put_cstr(&mut body, name);
+                put_cstr(&mut body, value);
+                (b'S', body)
+            }
+            BackendMessage::BackendKeyData { process_id, secret_key } => {
+                let mut body = process_id.to_be_bytes().to_vec();
+                body.extend_from_slice(&secret_key.to_be_bytes());
+                (b'K', body)
+            }
+            BackendMessage::ReadyForQuery(status) => {
+                let status = match status {
+                    TransactionStatus::Idle => b'I',
+                    TransactionStatus::InTransaction => b'T',
+                    TransactionStatus::Failed => b'E',
+                };
+                (b'Z', vec![status])
+            }
+            BackendMessage::RowDescription(fields) => {
+                let mut body = (fields.len() as i16).to_be_bytes().to_vec();
+                for (name, oid) in fields {
+                    put_cstr(&mut body, name);
+                    body.extend_from_slice(&0i32.to_be_bytes()); // table OID
+                    body.extend_from_slice(&0i16.to_be_bytes()); // column number
+                    body.extend_from_slice(&(*oid as i32).to_be_bytes());
+                    body.extend_from_slice(&oid.size().to_be_bytes());
+                    body.extend_from_slice(&(-1i32).to_be_bytes()); // type modifier
+                    body.extend_from_slice(&0i16.to_be_bytes()); // text format
+                }
+                (b'T', body)
+            }
+            BackendMessage::DataRow(values) => {
+                let mut body = (values.len() as i16).to_be_bytes().to_vec();
+                for value in values {
+                    match value {
+                        Some(bytes) => {
+                            body.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+                            body.extend_from_slice(bytes);
+                        }
+                        None => body.extend_from_slice(&(-1i32).to_be_bytes()),
+                    }
+                }
+                (b'D', body)
+            }
+            BackendMessage::CommandComplete(tag) => {
+                let mut body = Vec::new();
+                put_cstr(&mut body, tag);
+                (b'C', body)
+            }
+            BackendMessage::EmptyQueryResponse => (b'I', Vec::new()),
+            BackendMessage::ErrorResponse { code, message } => {
+                let mut body = Vec::new();
+                for (field, value) in [(b'S', "ERROR"), (b'V', "ERROR"), (b'C', code), (b'M', message.as_str())] {
+                    body.push(field);
+                    put_cstr(&mut body, value);
+                }
+                body.push(0);
+                (b'E', body)
+            }
+        };
+        out.push(tag);
+        out.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+        out.extend_from_slice(&body);
+    }
+}
+
+fn put_cstr(out: &mut Vec<u8>, text: &str) {
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+}
+
+/// Read the first message of a connection
+pub async fn read_startup<R: AsyncRead + Unpin>(reader: &mut R) -> SqlResult<StartupMessage> {
+    let len = read_len(reader).await?;
+    let body = read_body(reader, len.checked_sub(4).ok_or_else(|| bad_message("startup message too short"))?).await?;
+    let code = i32::from_be_bytes(
+        body.get(..4)
+            .and_then(|code| code.try_into().ok())
+            .ok_or_else(|| bad_message("startup message too short"))?,
+    );
+    match code {
+        SSL_REQUEST | GSSENC_REQUEST => Ok(StartupMessage::EncryptionRequest),
+        CANCEL_REQUEST => Ok(StartupMessage::CancelRequest),
+        PROTOCOL_VERSION => {
+            let mut fields = body[4..].split(|byte| *byte == 0).map(|field| String::from_utf8_lossy(field).into_owned());
+            let mut parameters = HashMap::new();
+            while let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+                if name.is_empty() {
+                    break;
+                }
+                parameters.insert(name, value);
+            }
+            Ok(StartupMessage::Startup { parameters })
+        }
+        _ => Err(SqlError::connection_error(format!(
+            "Unsupported protocol version {}.{}",
+            code >> 16,
+            code & 0xffff
+        ))),
+    }
+}
+
+/// Read a message after startup; `None` when the client closed the
+/// connection
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> SqlResult<Option<FrontendMessage>> {
+    let mut tag = [0u8; 1];
+    match reader.read_exact(&mut tag).await {
+        Ok(_) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(SqlError::connection_error(error.to_string())),
+    }
+    let len = read_len(reader).await?;
+    let body = read_body(reader, len.checked_sub(4).ok_or_else(|| bad_message("message length too short"))?).await?;
+    let message = match tag[0] {
+        b'Q' => FrontendMessage::Query(cstr(&body)?),
+        b'p' => FrontendMessage::Password(cstr(&body)?),
+        b'S' => FrontendMessage::Sync,
+        b'X' => FrontendMessage::Terminate,
+        other => FrontendMessage::Unsupported(other),
+    };
+    Ok(Some(message))
+}
+
+async fn read_len<R: AsyncRead + Unpin>(reader: &mut R) -> SqlResult<usize> {
+    let len = reader
+        .read_i32()
+        .await
+        .map_err(|error| SqlError::connection_error(error.to_string()))?;
+    usize::try_from(len)
+        .ok()
+        .filter(|len| *len <= MAX_MESSAGE_LEN)
+        .ok_or_else(|| bad_message(format!("invalid message length {}", len)))
+}
+
+async fn read_body<R: AsyncRead + Unpin>(reader: &mut R, len: usize) -> SqlResult<Vec<u8>> {
+    let mut body = vec![0u8; len];
+    reader
+        .read_exact(&mut body)
+        .await
+        .map_err(|error| SqlError::connection_error(error.to_string()))?;
+    Ok(body)
+}
+
+/// A NUL-terminated string at the start of `body`
+fn cstr(body: &[u8]) -> SqlResult<String> {
+    let end = body
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or_else(|| bad_message("string is not NUL-terminated"))?;
+    String::from_utf8(body[..end].to_vec()).map_err(|_| bad_message("string is not UTF-8"))
+}
+
+fn bad_message(message: impl Into<String>) -> SqlError {
+    SqlError::connection_error(format!("Malformed message: {}", message.into()))
+}
+
+/// SQLSTATE code reported for an error
+pub fn sqlstate(error: &SqlError) -> &'static str {
+    match error {
+        SqlError::ParseError { .. } => "42601",
+        SqlError::TypeError { .. } => "42804",
+        SqlError::TableNotFound { .. } => "42P01",
+        SqlError::ColumnNotFound { .. } => "42703",
+        SqlError::IndexNotFound { .. } => "42704",
+        SqlError::DuplicateKey { .. } => "23505",
+        SqlError::ConstraintViolation { .. } => "23000",
+        SqlError::SchemaError { .. } => "42000",
+        SqlError::TransactionError { message } if message.starts_with("current transaction is aborted") => "25P02",
+        SqlError::TransactionError { .. } => "25000",
+        SqlError::SerializationFailure { .. } => "40001",
+        SqlError::ConnectionError { .. } => "08000",
+        SqlError::IoError { .. } => "58030",
+        SqlError::RuntimeError { .. }
+        | SqlError::PageCacheError { .. }
+        | SqlError::BTreeError { .. }
+        | SqlError::WalError { .. } => "XX000",
+    }
+}
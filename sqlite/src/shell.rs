@@ -0,0 +1,554 @@
+//! The interactive SQL shell
+//!
+//! Statements end with a semicolon and may go on over several lines; a
+//! line starting with `.` is a shell command, as in sqlite3.
+
+use crate::{run_export, run_import, ExportArgs, ImportArgs};
+use categorical_sqlite::engine::Session;
+use categorical_sqlite::parser::{is_complete, split_statements, strip_comments};
+use categorical_sqlite::query::QueryResult;
+use categorical_sqlite::transfer::{self, Format, TransferOptions};
+use categorical_sqlite::types::Row;
+use categorical_sqlite::{CategoricalSQLite, SqlError, Value};
+use clap::Parser;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+const HELP: &str = "\
+.exit, .quit             Leave the shell
+.export TABLE FILE       Write a table to a CSV or JSON file
+.headers on|off          Show column names above the rows
+.help                    Show this message
+.import FILE TABLE       Load a CSV or JSON file into a table
+.indexes [TABLE]         List the indexes, of every table or of TABLE
+.mode MODE               Print rows as list, table, csv, json or line
+.param list|set|unset|clear
+                         Values for ?N and :name placeholders
+.read FILE               Run the SQL statements in FILE
+.schema [TABLE]          Show the CREATE statements of the tables
+.tables                  List the tables
+.timer on|off            Show how long each statement takes";
+
+/// How the rows of a SELECT are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Values separated by `|`, under a header
+    List,
+    /// A grid of boxed, aligned columns
+    Table,
+    Csv,
+    /// A JSON array of objects
+    Json,
+    /// One `column = value` line per value, with a blank line between rows
+    Line,
+}
+
+impl Mode {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "list" => Some(Mode::List),
+            "table" => Some(Mode::Table),
+            "csv" => Some(Mode::Csv),
+            "json" => Some(Mode::Json),
+            "line" => Some(Mode::Line),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for printing results
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    pub mode: Mode,
+    /// Print column names in list, table and csv modes
+    pub headers: bool,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            mode: Mode::List,
+            headers: true,
+        }
+    }
+}
+
+impl Output {
+    pub fn print(&self, result: QueryResult) -> Result<(), SqlError> {
+        let message = match result {
+            QueryResult::Select { columns, rows } => return self.write_rows(&columns, &rows, &mut io::stdout().lock()),
+            QueryResult::Insert { rows_affected } => format!("Inserted {} row(s)", rows_affected),
+            QueryResult::Update { rows_affected } => format!("Updated {} row(s)", rows_affected),
+            QueryResult::Delete { rows_affected } => format!("Deleted {} row(s)", rows_affected),
+            QueryResult::CreateTable => "Table created successfully".to_string(),
+            QueryResult::DropTable => "Table dropped successfully".to_string(),
+            QueryResult::CreateIndex => "Index created successfully".to_string(),
+            QueryResult::DropIndex => "Index dropped successfully".to_string(),
+            QueryResult::Begin => "Transaction started".to_string(),
+            QueryResult::Commit => "Transaction committed".to_string(),
+            QueryResult::Rollback => "Transaction rolled back".to_string(),
+            QueryResult::Vacuum => "Database vacuumed".to_string(),
+        };
+        println!("{}", message);
+        Ok(())
+    }
+
+    /// Write the rows of a SELECT to `out` in the current mode
+    pub fn write_rows(&self, columns: &[String], rows: &[Row], out: &mut impl Write) -> Result<(), SqlError> {
+        self.render_rows(columns, rows, out).map_err(|error| SqlError::io_error(error.to_string()))
+    }
+
+    fn render_rows(&self, columns: &[String], rows: &[Row], out: &mut impl Write) -> io::Result<()> {
+        let texts = || rows.iter().map(|row| row.values.iter().map(Value::to_string).collect::<Vec<_>>());
+        match self.mode {
+            Mode::List => {
+                if self.headers {
+                    writeln!(out, "{}", columns.join(" | "))?;
+                    let rule: Vec<String> = columns.iter().map(|column| "-".repeat(column.len())).collect();
+                    writeln!(out, "{}", rule.join("-+-"))?;
+                }
+                for row in texts() {
+                    writeln!(out, "{}", row.join(" | "))?;
+                }
+            }
+            Mode::Table => {
+                let mut widths: Vec<usize> = columns
+                    .iter()
+                    .map(|column| if self.headers { column.chars().count() } else { 0 })
+                    .collect();
+                let rows: Vec<Vec<String>> = texts().collect();
+                for row in &rows {
+                    for (width, text) in widths.iter_mut().zip(row) {
+                        *width = (*width).max(text.chars().count());
+                    }
+                }
+                let rule: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+                let rule = format!("+{}+", rule.join("+"));
+                let line = |texts: &[String]| {
+                    let cells: Vec<String> = texts
+                        .iter()
+                        .zip(&widths)
+                        .map(|(text, width)| format!(" {:<width$} ", text, width = width))
+                        .collect();
+                    format!("|{}|", cells.join("|"))
+                };
+                writeln!(out, "{}", rule)?;
+                if self.headers {
+                    writeln!(out, "{}", line(columns))?;
+                    writeln!(out, "{}", rule)?;
+                }
+                for row in &rows {
+                    writeln!(out, "{}", line(row))?;
+                }
+                writeln!(out, "{}", rule)?;
+            }
+            Mode::Csv | Mode::Json => {
+                let options = TransferOptions {
+                    format: if self.mode == Mode::Csv { Format::Csv } else { Format::Json },
+                    header: self.headers,
+                    ..TransferOptions::default()
+                };
+                transfer::write_rows(columns, rows, &mut *out, &options, |_| {})
+                    .map_err(|error| io::Error::other(error.to_string()))?;
+            }
+            Mode::Line => {
+                let width = columns.iter().map(|column| column.chars().count()).max().unwrap_or(0);
+                for (position, row) in texts().enumerate() {
+                    if position > 0 {
+                        writeln!(out)?;
+                    }
+                    for (column, text) in columns.iter().zip(row) {
+                        writeln!(out, "{:>width$} = {}", column, text, width = width)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State of a shell: its session, placeholder values and output settings
+pub struct Shell<'a> {
+    db: &'a CategoricalSQLite,
+    /// BEGIN ... COMMIT groups the statements typed in between
+    session: Session,
+    /// Values bound to `?N` and `:name` placeholders, set with `.param set`
+    params: BTreeMap<String, Value>,
+    output: Output,
+    timer: bool,
+}
+
+impl<'a> Shell<'a> {
+    pub fn new(db: &'a CategoricalSQLite) -> Self {
+        Shell {
+            db,
+            session: db.session(),
+            params: BTreeMap::new(),
+            output: Output::default(),
+            timer: false,
+        }
+    }
+
+    /// Read and run input until `.exit` or end of input, keeping the line
+    /// history in `$SQLITE_HISTORY` or `~/.categorical_sqlite_history`
+    pub async fn run(mut self) -> Result<(), SqlError> {
+        println!("Categorical SQLite v0.1.0");
+        println!("Enter SQL statements ending with ';' (type .help for commands, .exit to quit):");
+
+        let mut editor =
+            Editor::<(), DefaultHistory>::new().map_err(|error| SqlError::io_error(error.to_string()))?;
+        let history = history_file();
+        if let Some(path) = history.as_ref().filter(|path| path.exists()) {
+            if let Err(error) = editor.load_history(path) {
+                eprintln!("Warning: could not load history: {}", error);
+            }
+        }
+
+        let mut pending = String::new();
+        loop {
+            let prompt = if pending.is_empty() { "sqlite> " } else { "   ...> " };
+            let line = match editor.readline(prompt) {
+                Ok(line) => line,
+                // Ctrl-C drops a half-typed statement
+                Err(ReadlineError::Interrupted) => {
+                    pending.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(error) => return Err(SqlError::io_error(error.to_string())),
+            };
+
+            if pending.is_empty() {
+                let command = line.trim();
+                if command.is_empty() {
+                    continue;
+                }
+                if command.starts_with('.') {
+                    let _ = editor.add_history_entry(command);
+                    if !self.run_command(command).await {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            pending.push_str(&line);
+            pending.push('\n');
+            if is_complete(&pending) || strip_comments(&pending).trim().is_empty() {
+                let _ = editor.add_history_entry(pending.trim());
+                self.run_sql(&pending).await;
+                pending.clear();
+            }
+        }
+
+        if let Some(path) = &history {
+            if let Err(error) = editor.save_history(path) {
+                eprintln!("Warning: could not save history: {}", error);
+            }
+        }
+        self.session.close().await
+    }
+
+    /// Run a dot-command; false when the shell should exit
+    async fn run_command(&mut self, line: &str) -> bool {
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let result = match (command, rest) {
+            (".exit" | ".quit", _) => return false,
+            (".help", _) => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            (".tables", "") => {
+                let tables = self.db.tables().await;
+                if !tables.is_empty() {
+                    println!("{}", tables.join("  "));
+                }
+                Ok(())
+            }
+            (".schema", table) => self.write_schema(table, &mut io::stdout()).await,
+            (".indexes" | ".indices", table) => self.write_indexes(table, &mut io::stdout()).await,
+            (".mode", mode) => {
+                match Mode::from_name(mode) {
+                    Some(mode) => self.output.mode = mode,
+                    None => println!("Usage: .mode list|table|csv|json|line"),
+                }
+                Ok(())
+            }
+            (".headers", setting) => {
+                match on_off(setting) {
+                    Some(on) => self.output.headers = on,
+                    None => println!("Usage: .headers on|off"),
+                }
+                Ok(())
+            }
+            (".timer", setting) => {
+                match on_off(setting) {
+                    Some(on) => self.timer = on,
+                    None => println!("Usage: .timer on|off"),
+                }
+                Ok(())
+            }
+            (".read", "") => {
+                println!("Usage: .read FILE");
+                Ok(())
+            }
+            (".read", file) => match std::fs::read_to_string(file) {
+                Ok(sql) => {
+                    self.run_sql(&sql).await;
+                    Ok(())
+                }
+                Err(error) => Err(SqlError::io_error(format!("{}: {}", file, error))),
+            },
+            (".param", command) => {
+                run_param_command(&mut self.params, command);
+                Ok(())
+            }
+            (".import", _) => match ImportArgs::try_parse_from(line.split_whitespace()) {
+                Ok(args) => run_import(&mut self.session, &args).await,
+                Err(error) => {
+                    println!("{}", error);
+                    Ok(())
+                }
+            },
+            (".export", _) => match ExportArgs::try_parse_from(line.split_whitespace()) {
+                Ok(args) => run_export(&mut self.session, &args).await,
+                Err(error) => {
+                    println!("{}", error);
+                    Ok(())
+                }
+            },
+            _ => {
+                println!("Unknown command or invalid arguments: {} (see .help)", line);
+                Ok(())
+            }
+        };
+        if let Err(error) = result {
+            println!("Error: {}", error);
+        }
+        true
+    }
+
+    /// Tables a dot-command names: `table`, or every table when empty
+    async fn named_tables(&self, table: &str) -> Vec<String> {
+        if table.is_empty() {
+            self.db.tables().await
+        } else {
+            vec![table.to_string()]
+        }
+    }
+
+    /// Write the CREATE statements of one table, or of every table, with
+    /// their indexes
+    async fn write_schema(&self, table: &str, out: &mut impl Write) -> Result<(), SqlError> {
+        let mut sql = Vec::new();
+        for table in self.named_tables(table).await {
+            let schema = self
+                .db
+                .table_schema(&table)
+                .await
+                .ok_or_else(|| SqlError::table_not_found(&table))?;
+            sql.push(schema.create_sql(&table));
+            for index in self.db.indexes_on(&table).await {
+                if !index.is_automatic() {
+                    sql.push(index.create_sql());
+                }
+            }
+        }
+        sql.iter()
+            .try_for_each(|statement| writeln!(out, "{};", statement))
+            .map_err(|error| SqlError::io_error(error.to_string()))
+    }
+
+    /// Write the index names of one table, or of every table
+    async fn write_indexes(&self, table: &str, out: &mut impl Write) -> Result<(), SqlError> {
+        let mut names = Vec::new();
+        for table in self.named_tables(table).await {
+            names.extend(self.db.indexes_on(&table).await.into_iter().map(|index| index.name));
+        }
+        names
+            .iter()
+            .try_for_each(|name| writeln!(out, "{}", name))
+            .map_err(|error| SqlError::io_error(error.to_string()))
+    }
+
+    /// Run every statement of `sql` in turn, stopping at the first error
+    async fn run_sql(&mut self, sql: &str) {
+        for statement in split_statements(&strip_comments(sql)) {
+            let started = Instant::now();
+            let result = execute_with_params(self.db, &mut self.session, statement, &self.params)
+                .await
+                .and_then(|result| self.output.print(result));
+            if self.timer {
+                println!("Run Time: real {:.3}", started.elapsed().as_secs_f64());
+            }
+            if let Err(error) = result {
+                println!("Error: {}", error);
+                return;
+            }
+        }
+    }
+}
+
+fn on_off(setting: &str) -> Option<bool> {
+    match setting.to_ascii_lowercase().as_str() {
+        "on" | "yes" | "1" => Some(true),
+        "off" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("SQLITE_HISTORY")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".categorical_sqlite_history")))
+}
+
+/// `.param list | set NAME VALUE | unset NAME | clear`
+fn run_param_command(params: &mut BTreeMap<String, Value>, command: &str) {
+    let mut words = command.splitn(3, char::is_whitespace);
+    match (words.next(), words.next(), words.next()) {
+        (Some("list") | Some(""), None, None) => {
+            for (name, value) in params.iter() {
+                println!("{} = {}", name, value);
+            }
+        }
+        (Some("set"), Some(name), Some(value)) => {
+            params.insert(name.to_string(), parse_param_value(value.trim()));
+        }
+        (Some("unset"), Some(name), None) => {
+            params.remove(name);
+        }
+        (Some("clear"), None, None) => params.clear(),
+        _ => println!("Usage: .param list | set NAME VALUE | unset NAME | clear"),
+    }
+}
+
+/// Shell parameter value: a number, NULL, a quoted string, or else the
+/// text as written
+fn parse_param_value(text: &str) -> Value {
+    if let Ok(integer) = text.parse::<i64>() {
+        Value::Integer(integer)
+    } else if let Ok(real) = text.parse::<f64>() {
+        Value::Real(real)
+    } else if text.eq_ignore_ascii_case("NULL") {
+        Value::Null
+    } else if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        Value::Text(text[1..text.len() - 1].replace("''", "'"))
+    } else {
+        Value::Text(text.to_string())
+    }
+}
+
+/// Run SQL, binding its placeholders from the shell's parameters; a
+/// placeholder without a value is bound to NULL, as in sqlite3
+async fn execute_with_params(
+    db: &CategoricalSQLite,
+    session: &mut Session,
+    sql: &str,
+    params: &BTreeMap<String, Value>,
+) -> Result<QueryResult, SqlError> {
+    let statement = db.prepare(sql)?;
+    let values: Vec<Value> = (1..=statement.parameter_count())
+        .map(|index| {
+            let key = statement
+                .parameter_name(index)
+                .map_or_else(|| format!("?{}", index), str::to_string);
+            params.get(&key).cloned().unwrap_or(Value::Null)
+        })
+        .collect();
+    session.execute(&statement, &values).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use categorical_sqlite::engine::DatabaseConfig;
+
+    fn render(mode: Mode, headers: bool) -> String {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let rows = vec![
+            Row::new(vec![Value::Integer(1), Value::Text("ann".to_string())]),
+            Row::new(vec![Value::Integer(22), Value::Text("bob".to_string())]),
+        ];
+        let mut out = Vec::new();
+        Output { mode, headers }.write_rows(&columns, &rows, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_list_mode() {
+        assert_eq!(render(Mode::List, true), "id | name\n---+-----\n1 | ann\n22 | bob\n");
+        assert_eq!(render(Mode::List, false), "1 | ann\n22 | bob\n");
+    }
+
+    #[test]
+    fn test_table_mode() {
+        assert_eq!(
+            render(Mode::Table, true),
+            "+----+------+\n\
+             | id | name |\n\
+             +----+------+\n\
+             | 1  | ann  |\n\
+             | 22 | bob  |\n\
+             +----+------+\n"
+        );
+        assert_eq!(render(Mode::Table, false), "+----+-----+\n| 1  | ann |\n| 22 | bob |\n+----+-----+\n");
+    }
+
+    #[test]
+    fn test_line_mode() {
+        assert_eq!(render(Mode::Line, true), "  id = 1\nname = ann\n\n  id = 22\nname = bob\n");
+    }
+
+    #[test]
+    fn test_csv_and_json_modes() {
+        assert_eq!(render(Mode::Csv, true), "id,name\n1,ann\n22,bob\n");
+        assert_eq!(render(Mode::Csv, false), "1,ann\n22,bob\n");
+        let json: serde_json::Value = serde_json::from_str(&render(Mode::Json, true)).unwrap();
+        assert_eq!(json, serde_json::json!([{"id": 1, "name": "ann"}, {"id": 22, "name": "bob"}]));
+    }
+
+    #[tokio::test]
+    async fn test_schema_and_indexes() {
+        let db = CategoricalSQLite::new(DatabaseConfig::default());
+        for sql in [
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE, age INTEGER DEFAULT 0)",
+            "CREATE INDEX idx_age ON users (age)",
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, author INTEGER REFERENCES users (id))",
+        ] {
+            db.execute_sql(sql).await.unwrap();
+        }
+        let shell = Shell::new(&db);
+
+        let mut out = Vec::new();
+        shell.write_schema("users", &mut out).await.unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "CREATE TABLE users (\n  \
+             id INTEGER PRIMARY KEY,\n  \
+             email TEXT NOT NULL UNIQUE,\n  \
+             age INTEGER DEFAULT 0\n);\n\
+             CREATE INDEX idx_age ON users (age);\n"
+        );
+
+        // Indexes made for the primary key and UNIQUE columns are listed but
+        // have no statement
+        let mut out = Vec::new();
+        shell.write_indexes("users", &mut out).await.unwrap();
+        let names = String::from_utf8(out).unwrap();
+        let mut names: Vec<&str> = names.lines().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["idx_age", "sqlite_autoindex_users_1", "sqlite_autoindex_users_2"]);
+
+        let mut out = Vec::new();
+        shell.write_indexes("posts", &mut out).await.unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "sqlite_autoindex_posts_1\n");
+        assert!(shell.write_schema("missing", &mut Vec::new()).await.is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Online backups
+//!
+//! A backup copies the pages of a [`PagerSnapshot`](super::PagerSnapshot),
+//! so it is consistent as of one commit while later transactions keep
+//! committing. A full backup holds every page; an incremental backup only
+//! the pages whose LSN is newer than an earlier backup's, and is applied on
+//! top of that backup when restoring.
+
+use super::{Pager, PagerConfig};
+use crate::error::{SqlError, SqlResult};
+use crate::page_cache::{FilePageStorage, PageCacheConfig, PageData, PageStorage, PageType};
+use crate::query::{IndexInfo, TableSchema};
+use crate::transaction::WalSyncMode;
+use crate::types::{PageId, Row};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// First bytes of every backup file
+const BACKUP_MAGIC: &[u8; 8] = b"CSQLBKP1";
+
+/// Size of the SHA-256 checksum closing a backup file
+const BACKUP_CHECKSUM_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupKind {
+    Full,
+    Incremental,
+}
+
+/// The pages of a database as of one commit
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub kind: BackupKind,
+    pub page_size: u32,
+    /// Pages in the database when the backup was taken
+    pub page_count: u32,
+    /// LSN an incremental backup was taken since; 0 for a full backup
+    pub base_lsn: u64,
+    /// LSN of the last commit in the backup
+    pub lsn: u64,
+    pub pages: BTreeMap<PageId, PageData>,
+}
+
+/// Tables, with their schemas and rows, and index definitions of a
+/// database
+#[derive(Debug, Clone, Default)]
+pub struct BackupContents {
+    pub tables: Vec<(String, TableSchema, Vec<Row>)>,
+    pub indexes: Vec<IndexInfo>,
+}
+
+impl Backup {
+    /// Back up the pager's last commit; with `since`, only the pages
+    /// written after the commit with that LSN (the header page is always
+    /// included)
+    pub async fn take(pager: &Pager, since: Option<u64>) -> SqlResult<Self> {
+        let snapshot = pager.snapshot().await;
+        let header = snapshot.header().clone();
+        if let Some(since) = since.filter(|since| *since > header.change_counter) {
+            return Err(SqlError::io_error(format!(
+                "Cannot back up changes since LSN {}: the database is at LSN {}",
+                since, header.change_counter
+            )));
+        }
+        let mut pages = BTreeMap::new();
+        for page_id in (0..header.page_count).map(PageId) {
+            let page = snapshot.read_page(page_id).await?;
+            if page_id.0 == 0 || since.is_none_or(|since| page.lsn > since) {
+                pages.insert(page_id, page);
+            }
+        }
+        Ok(Backup {
+            kind: if since.is_some() { BackupKind::Incremental } else { BackupKind::Full },
+            page_size: header.page_size,
+            page_count: header.page_count,
+            base_lsn: since.unwrap_or(0),
+            lsn: header.change_counter,
+            pages,
+        })
+    }
+
+    /// Full backup of a database given by its contents rather than a file,
+    /// e.g. an in-memory one
+    pub async fn of_contents(contents: &BackupContents, page_size: usize) -> SqlResult<Self> {
+        let file = TempFile::new("backup");
+        let pager = Pager::open(file.path(), scratch_config(page_size)).await?;
+        let mut transaction = pager.begin().await;
+        for (name, schema, rows) in &contents.tables {
+            transaction.write_table(name, schema.clone(), rows).await?;
+        }
+        for index in &contents.indexes {
+            transaction.put_index(index.clone());
+        }
+        transaction.commit().await?;
+        Backup::take(&pager, None).await
+    }
+
+    /// Combine a full backup and the incremental backups taken after it,
+    /// in order, into one full backup
+    pub fn merge(chain: Vec<Backup>) -> SqlResult<Self> {
+        let mut chain = chain.into_iter();
+        let mut merged = match chain.next() {
+            Some(backup) if backup.kind == BackupKind::Full => backup,
+            Some(_) => return Err(SqlError::io_error("A backup chain must start with a full backup")),
+            None => return Err(SqlError::io_error("No backup to restore")),
+        };
+        for backup in chain {
+            // Pages changed between the two backups would be missing
+            if backup.kind != BackupKind::Incremental || backup.base_lsn > merged.lsn || backup.lsn < merged.lsn {
+                return Err(SqlError::io_error(format!(
+                    "Backup of LSNs {}..{} does not follow the backup at LSN {}",
+                    backup.base_lsn, backup.lsn, merged.lsn
+                )));
+            }
+            if backup.page_size != merged.page_size {
+                return Err(SqlError::io_error("Backups in a chain must have the same page size"));
+            }
+            merged.pages.extend(backup.pages);
+            merged.page_count = backup.page_count;
+            merged.lsn = backup.lsn;
+        }
+        Ok(merged)
+    }
+
+    /// Tables and indexes stored in a full backup, read by rebuilding its
+    /// database in a scratch file
+    pub async fn contents(&self) -> SqlResult<BackupContents> {
+        if self.kind != BackupKind::Full {
+            return Err(SqlError::io_error("An incremental backup must be merged into a full backup first"));
+        }
+        let file = TempFile::new("restore");
+        let storage = FilePageStorage::open(file.path(), self.page_size as usize)?;
+        for (page_id, page) in &self.pages {
+            storage.write_page(*page_id, page)?;
+        }
+        storage.sync()?;
+        drop(storage);
+
+        let pager = Pager::open(file.path(), scratch_config(self.page_size as usize)).await?;
+        let mut transaction = pager.begin().await;
+        let mut contents = BackupContents {
+            indexes: transaction.indexes(),
+            ..BackupContents::default()
+        };
+        for (name, schema) in transaction.tables() {
+            let rows = transaction.read_table(&name).await?;
+            contents.tables.push((name, schema, rows));
+        }
+        Ok(contents)
+    }
+
+    /// Write the backup to `path`, replacing it only once it is complete
+    pub fn write(&self, path: impl AsRef<Path>) -> SqlResult<()> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(BACKUP_MAGIC);
+        bytes.push(match self.kind {
+            BackupKind::Full => 0,
+            BackupKind::Incremental => 1,
+        });
+        bytes.extend_from_slice(&self.page_size.to_le_bytes());
+        bytes.extend_from_slice(&self.page_count.to_le_bytes());
+        bytes.extend_from_slice(&self.base_lsn.to_le_bytes());
+        bytes.extend_from_slice(&self.lsn.to_le_bytes());
+        bytes.extend_from_slice(&(self.pages.len() as u32).to_le_bytes());
+        for (page_id, page) in &self.pages {
+            bytes.extend_from_slice(&page_id.0.to_le_bytes());
+            bytes.push(page.page_type.as_byte());
+            bytes.extend_from_slice(&page.lsn.to_le_bytes());
+            bytes.extend_from_slice(&(page.data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&page.data);
+        }
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum);
+
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let written = std::fs::write(&partial, &bytes)
+            .and_then(|_| std::fs::File::open(&partial)?.sync_all())
+            .and_then(|_| std::fs::rename(&partial, path));
+        written.map_err(|e| SqlError::io_error(format!("Cannot write backup {}: {}", path.display(), e)))
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> SqlResult<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| SqlError::io_error(format!("Cannot read backup {}: {}", path.display(), e)))?;
+        let corrupted = || SqlError::io_error(format!("{} is not a valid backup", path.display()));
+        if bytes.len() < BACKUP_MAGIC.len() + BACKUP_CHECKSUM_SIZE || &bytes[..BACKUP_MAGIC.len()] != BACKUP_MAGIC {
+            return Err(corrupted());
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - BACKUP_CHECKSUM_SIZE);
+        if Sha256::digest(body).as_slice() != checksum {
+            return Err(corrupted());
+        }
+
+        let mut reader = Reader { bytes: body, position: BACKUP_MAGIC.len() };
+        let kind = match reader.take(1).ok_or_else(corrupted)?[0] {
+            0 => BackupKind::Full,
+            1 => BackupKind::Incremental,
+            _ => return Err(corrupted()),
+        };
+        let page_size = reader.u32().ok_or_else(corrupted)?;
+        let page_count = reader.u32().ok_or_else(corrupted)?;
+        let base_lsn = reader.u64().ok_or_else(corrupted)?;
+        let lsn = reader.u64().ok_or_else(corrupted)?;
+        let mut pages = BTreeMap::new();
+        for _ in 0..reader.u32().ok_or_else(corrupted)? {
+            let page_id = PageId(reader.u32().ok_or_else(corrupted)?);
+            let page_type = PageType::from_byte(reader.take(1).ok_or_else(corrupted)?[0]).ok_or_else(corrupted)?;
+            let page_lsn = reader.u64().ok_or_else(corrupted)?;
+            let len = reader.u32().ok_or_else(corrupted)? as usize;
+            let data = reader.take(len).ok_or_else(corrupted)?.to_vec();
+            pages.insert(page_id, PageData::new(data, page_type).with_lsn(page_lsn));
+        }
+        Ok(Backup {
+            kind,
+            page_size,
+            page_count,
+            base_lsn,
+            lsn,
+            pages,
+        })
+    }
+}
+
+/// Pager over a scratch database file, which needs no journal
+fn scratch_config(page_size: usize) -> PagerConfig {
+    PagerConfig {
+        cache: PageCacheConfig {
+            page_size,
+            ..PageCacheConfig::default()
+        },
+        enable_wal: false,
+        sync_mode: WalSyncMode::Off,
+        checkpoint_threshold: None,
+        auto_vacuum: false,
+    }
+}
+
+/// A file in the system temp directory, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(purpose: &str) -> Self {
+        TempFile(std::env::temp_dir().join(format!("categorical-sqlite-{}.{}", uuid::Uuid::new_v4(), purpose)))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.position..self.position + len)?;
+        self.position += len;
+        Some(slice)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    fn rows(count: usize) -> Vec<Row> {
+        (0..count)
+            .map(|i| Row::new(vec![Value::Integer(i as i64), Value::Text(format!("row {}", i))]))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_incremental_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PagerConfig {
+            checkpoint_threshold: Some(8),
+            enable_wal: true,
+            ..scratch_config(512)
+        };
+        let pager = Pager::open(dir.path().join("test.db"), config).await.unwrap();
+        let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+        let write = |table: &'static str, count: usize| {
+            let pager = &pager;
+            let schema = schema.clone();
+            async move {
+                let mut transaction = pager.begin().await;
+                transaction.write_table(table, schema, &rows(count)).await.unwrap();
+                transaction.commit().await.unwrap();
+            }
+        };
+        write("a", 100).await;
+        write("b", 5).await;
+
+        // Commits after a snapshot is taken do not change what it reads
+        let snapshot = pager.snapshot().await;
+        let before = snapshot.read_page(PageId(1)).await.unwrap();
+        write("a", 10).await;
+        assert_eq!(snapshot.read_page(PageId(1)).await.unwrap().data, before.data);
+        drop(snapshot);
+
+        let full = Backup::take(&pager, None).await.unwrap();
+        write("b", 50).await;
+        let incremental = Backup::take(&pager, Some(full.lsn)).await.unwrap();
+        assert_eq!(incremental.kind, BackupKind::Incremental);
+        assert!(incremental.pages.len() < incremental.page_count as usize);
+        assert!(Backup::take(&pager, Some(incremental.lsn + 1)).await.is_err());
+
+        full.write(dir.path().join("full.bak")).unwrap();
+        incremental.write(dir.path().join("incremental.bak")).unwrap();
+        let chain = vec![
+            Backup::read(dir.path().join("full.bak")).unwrap(),
+            Backup::read(dir.path().join("incremental.bak")).unwrap(),
+        ];
+        let contents = Backup::merge(chain).unwrap().contents().await.unwrap();
+        let tables: Vec<(&str, usize)> = contents.tables.iter().map(|(name, _, rows)| (name.as_str(), rows.len())).collect();
+        assert_eq!(tables, vec![("a", 10), ("b", 50)]);
+        assert_eq!(contents.tables[1].2, rows(50));
+
+        // An incremental backup needs the backup it was taken since
+        assert!(Backup::merge(vec![incremental.clone()]).is_err());
+        let later = Backup::take(&pager, Some(incremental.lsn)).await.unwrap();
+        assert!(Backup::merge(vec![full, later]).is_err());
+
+        let mut bytes = std::fs::read(dir.path().join("full.bak")).unwrap();
+        bytes[40] ^= 0xFF;
+        std::fs::write(dir.path().join("full.bak"), bytes).unwrap();
+        assert!(Backup::read(dir.path().join("full.bak")).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::error::{SqlError, SqlResult};
+use crate::types::PageId;
+
+/// First bytes of every database file
+pub const MAGIC: &[u8; 16] = b"CategoricalSQL\0\x01";
+
+/// On-disk format version written to new files
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Encoded size of the header at the start of page 0
+pub const HEADER_SIZE: usize = 48;
+
+/// Database header stored in page 0
+///
+/// Page id 0 always holds the header, so it doubles as the "no page"
+/// marker for the freelist head and catalog root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseHeader {
+    pub page_size: u32,
+    /// Number of pages in the file, including the header page
+    pub page_count: u32,
+    /// First page of the freelist chain
+    pub freelist_head: Option<PageId>,
+    pub freelist_count: u32,
+    /// Root page of the schema catalog tree
+    pub catalog_root: Option<PageId>,
+    /// Incremented by every commit
+    pub change_counter: u64,
+}
+
+impl DatabaseHeader {
+    /// Header of a freshly created, empty database
+    pub fn new(page_size: usize) -> Self {
+        DatabaseHeader {
+            page_size: page_size as u32,
+            page_count: 1,
+            freelist_head: None,
+            freelist_count: 0,
+            catalog_root: None,
+            change_counter: 0,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.page_size.to_le_bytes());
+        bytes.extend_from_slice(&self.page_count.to_le_bytes());
+        bytes.extend_from_slice(&page_ref(self.freelist_head).to_le_bytes());
+        bytes.extend_from_slice(&self.freelist_count.to_le_bytes());
+        bytes.extend_from_slice(&page_ref(self.catalog_root).to_le_bytes());
+        bytes.extend_from_slice(&self.change_counter.to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> SqlResult<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(SqlError::io_error("File is not a categorical-sqlite database"));
+        }
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+        };
+        let version = u32_at(16);
+        if version != FORMAT_VERSION {
+            return Err(SqlError::io_error(format!("Unsupported database format version {}", version)));
+        }
+        let mut counter = [0u8; 8];
+        counter.copy_from_slice(&bytes[40..48]);
+        Ok(DatabaseHeader {
+            page_size: u32_at(20),
+            page_count: u32_at(24),
+            freelist_head: page_id(u32_at(28)),
+            freelist_count: u32_at(32),
+            catalog_root: page_id(u32_at(36)),
+            change_counter: u64::from_le_bytes(counter),
+        })
+    }
+}
+
+fn page_ref(page: Option<PageId>) -> u32 {
+    page.map_or(0, |page| page.0)
+}
+
+fn page_id(raw: u32) -> Option<PageId> {
+    (raw != 0).then_some(PageId(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = DatabaseHeader {
+            page_size: 4096,
+            page_count: 12,
+            freelist_head: Some(PageId(7)),
+            freelist_count: 3,
+            catalog_root: Some(PageId(1)),
+            change_counter: 42,
+        };
+        let bytes = header.encode();
+        assert_eq!(bytes.len(), HEADER_SIZE);
+        assert_eq!(DatabaseHeader::decode(&bytes).unwrap(), header);
+
+        let empty = DatabaseHeader::new(1024);
+        assert_eq!(DatabaseHeader::decode(&empty.encode()).unwrap(), empty);
+
+        let mut corrupted = bytes;
+        corrupted[0] = b'X';
+        assert!(DatabaseHeader::decode(&corrupted).is_err());
+    }
+}
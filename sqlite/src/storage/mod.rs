@@ -0,0 +1,1367 @@
+pub mod backup;
+pub mod header;
+pub mod tree;
+
+pub use backup::{Backup, BackupContents, BackupKind};
+pub use header::DatabaseHeader;
+pub use tree::{CellPayload, LeafCell, TreePage};
+
+use crate::error::{SqlError, SqlResult};
+use crate::page_cache::{FilePageStorage, PageCache, PageCacheConfig, PageData, PageStorage, PageType};
+use crate::query::{IndexInfo, TableSchema, TableStats};
+use crate::transaction::{WalFile, WalSyncMode};
+use crate::types::{PageId, Row, RowId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Entry of the schema catalog, the tree rooted at the header's
+/// `catalog_root`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CatalogEntry {
+    Table {
+        name: String,
+        schema: TableSchema,
+        root: u32,
+    },
+    Index {
+        index: IndexInfo,
+    },
+    /// What ANALYZE gathered about a table, like SQLite's `sqlite_stat1`
+    Stats {
+        table: String,
+        stats: TableStats,
+    },
+}
+
+/// Pager configuration
+#[derive(Debug, Clone)]
+pub struct PagerConfig {
+    /// Page cache in front of the file; its page size is used for new files
+    pub cache: PageCacheConfig,
+    /// Journal every commit through the WAL before touching the database
+    /// file. Without it a crash during a commit can corrupt the file.
+    pub enable_wal: bool,
+    /// When WAL appends and checkpoints wait for the disk
+    pub sync_mode: WalSyncMode,
+    /// Checkpoint once the WAL holds this many frames; `None` leaves
+    /// checkpoints to [`Pager::checkpoint`]
+    pub checkpoint_threshold: Option<usize>,
+    /// Give free pages at the end of the file back to the file system at
+    /// every commit, rather than only on [`Pager::vacuum`]
+    pub auto_vacuum: bool,
+}
+
+/// Size of the database file before and after a [`Pager::vacuum`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VacuumStats {
+    pub pages_before: u32,
+    pub pages_after: u32,
+}
+
+/// Path of the WAL file belonging to a database file
+pub fn wal_path(database: &Path) -> PathBuf {
+    let mut name = database.as_os_str().to_owned();
+    name.push("-wal");
+    PathBuf::from(name)
+}
+
+/// Single-file database storage
+///
+/// The file is a sequence of fixed-size pages read through the page cache.
+/// Page 0 holds the [`DatabaseHeader`]; every other page belongs to a table
+/// B-tree, an overflow chain, the schema catalog or the freelist. Changes
+/// are made in a [`PagerTransaction`] and become visible atomically when it
+/// commits.
+///
+/// With the WAL enabled a commit only appends to the WAL; its pages are
+/// copied into the database file by the next checkpoint.
+#[derive(Debug)]
+pub struct Pager {
+    path: PathBuf,
+    storage: Arc<JournaledStorage>,
+    cache: Arc<PageCache>,
+    checkpoint_threshold: Option<usize>,
+    auto_vacuum: bool,
+    /// Pages in the file as of the last commit; the file is cut to this
+    /// length once no snapshot or WAL frame needs the pages past it
+    page_count: AtomicU32,
+    state: tokio::sync::Mutex<PagerState>,
+    /// Open snapshots, which keep the images of pages overwritten after
+    /// they were taken
+    snapshots: Mutex<Vec<Weak<SnapshotPages>>>,
+}
+
+/// Pages a [`PagerSnapshot`] must not see change: each commit copies the
+/// previous image of the pages it overwrites in here first
+type SnapshotPages = tokio::sync::Mutex<BTreeMap<PageId, PageData>>;
+
+/// The database as of one commit, readable while later transactions
+/// commit
+#[derive(Debug)]
+pub struct PagerSnapshot<'a> {
+    pager: &'a Pager,
+    header: DatabaseHeader,
+    preserved: Arc<SnapshotPages>,
+}
+
+impl PagerSnapshot<'_> {
+    /// Header as of the snapshot; its change counter is the snapshot's LSN
+    pub fn header(&self) -> &DatabaseHeader {
+        &self.header
+    }
+
+    /// A page as it was when the snapshot was taken
+    pub async fn read_page(&self, page_id: PageId) -> SqlResult<PageData> {
+        // Holding the lock keeps a commit from overwriting the page between
+        // the check and the read
+        let preserved = self.preserved.lock().await;
+        match preserved.get(&page_id) {
+            Some(page) => Ok(page.clone()),
+            None => Ok(self.pager.cache.load_page(page_id).await?.as_ref().clone()),
+        }
+    }
+}
+
+/// The database file behind its WAL: reads see committed pages that are
+/// still waiting in the WAL
+#[derive(Debug)]
+struct JournaledStorage {
+    file: FilePageStorage,
+    wal: Option<Mutex<WalFile>>,
+    sync_mode: WalSyncMode,
+}
+
+impl JournaledStorage {
+    /// Make a commit durable according to the sync mode
+    fn commit(&self, pages: &BTreeMap<PageId, PageData>) -> SqlResult<()> {
+        match &self.wal {
+            Some(wal) => wal
+                .lock()
+                .unwrap()
+                .append_commit(pages, self.sync_mode == WalSyncMode::Full),
+            None => {
+                for (page_id, page) in pages {
+                    self.file.write_page(*page_id, page)?;
+                }
+                if self.sync_mode != WalSyncMode::Off {
+                    self.file.sync()?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Copy every page in the WAL into the database file and empty the WAL;
+    /// returns the number of pages copied
+    fn checkpoint(&self, sync_mode: WalSyncMode) -> SqlResult<usize> {
+        let mut wal = match &self.wal {
+            Some(wal) => wal.lock().unwrap(),
+            None => return Ok(0),
+        };
+        if wal.is_empty() {
+            return Ok(0);
+        }
+        let sync = sync_mode != WalSyncMode::Off;
+        // The WAL must be durable before the database file is overwritten,
+        // and the file durable before the WAL is discarded
+        if sync {
+            wal.sync()?;
+        }
+        let pages = wal.committed_pages()?;
+        for (page_id, page) in &pages {
+            self.file.write_page(*page_id, page)?;
+        }
+        if sync {
+            self.file.sync()?;
+        }
+        wal.reset()?;
+        Ok(pages.len())
+    }
+
+    fn wal_frames(&self) -> usize {
+        self.wal.as_ref().map_or(0, |wal| wal.lock().unwrap().frame_count())
+    }
+}
+
+impl PageStorage for JournaledStorage {
+    fn read_page(&self, page_id: PageId) -> SqlResult<Option<PageData>> {
+        if let Some(wal) = &self.wal {
+            if let Some(page) = wal.lock().unwrap().read_page(page_id)? {
+                return Ok(Some(page));
+            }
+        }
+        self.file.read_page(page_id)
+    }
+
+    /// Writes from the cache are journaled like any other commit
+    fn write_page(&self, page_id: PageId, page: &PageData) -> SqlResult<()> {
+        self.commit(&BTreeMap::from([(page_id, page.clone())]))
+    }
+
+    fn sync(&self) -> SqlResult<()> {
+        if let Some(wal) = &self.wal {
+            wal.lock().unwrap().sync()?;
+        }
+        self.file.sync()
+    }
+}
+
+/// Committed header and catalog
+#[derive(Debug, Clone)]
+struct PagerState {
+    header: DatabaseHeader,
+    catalog: Vec<CatalogEntry>,
+    /// Rowids of the rows of each table in row order, for changes made by
+    /// row position; a table missing here gets them from its tree
+    rowids: HashMap<String, Vec<RowId>>,
+}
+
+impl Pager {
+    /// Open (or create) the database file at `path`, first replaying any
+    /// commits left in its WAL by a crash
+    pub async fn open(path: impl AsRef<Path>, config: PagerConfig) -> SqlResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let wal_path = wal_path(&path);
+
+        // Recovery: commits left in the WAL by a crash are checkpointed
+        // into the database file before anything reads it
+        let mut wal = if config.enable_wal || wal_path.exists() {
+            Some(WalFile::open(&wal_path)?)
+        } else {
+            None
+        };
+        let recovered_header = match &mut wal {
+            Some(wal) => wal.read_page(PageId(0))?,
+            None => None,
+        };
+        let page_size = match recovered_header {
+            Some(page) => DatabaseHeader::decode(&page.data)?.page_size as usize,
+            None => {
+                let probe = FilePageStorage::open(&path, config.cache.page_size.max(header::HEADER_SIZE))?;
+                match probe.read_page(PageId(0))? {
+                    Some(page) => DatabaseHeader::decode(&page.data)?.page_size as usize,
+                    None => config.cache.page_size,
+                }
+            }
+        };
+
+        let storage = JournaledStorage {
+            file: FilePageStorage::open(&path, page_size)?,
+            wal: wal.map(Mutex::new),
+            sync_mode: config.sync_mode,
+        };
+        storage.checkpoint(WalSyncMode::Full)?;
+        let storage = if config.enable_wal {
+            Arc::new(storage)
+        } else {
+            // A WAL left behind by an earlier run was only needed for recovery
+            if let Some(wal) = storage.wal {
+                let _ = std::fs::remove_file(wal.into_inner().unwrap().path());
+            }
+            Arc::new(JournaledStorage { wal: None, ..storage })
+        };
+
+        let header = match storage.file.read_page(PageId(0))? {
+            Some(page) => DatabaseHeader::decode(&page.data)?,
+            None => {
+                let header = DatabaseHeader::new(page_size);
+                storage
+                    .file
+                    .write_page(PageId(0), &PageData::new(header.encode(), PageType::Metadata))?;
+                storage.file.sync()?;
+                header
+            }
+        };
+
+        let cache_config = PageCacheConfig {
+            page_size,
+            ..config.cache
+        };
+        let pager = Pager {
+            path,
+            cache: Arc::new(PageCache::with_storage(cache_config, storage.clone())),
+            storage,
+            checkpoint_threshold: config.checkpoint_threshold,
+            auto_vacuum: config.auto_vacuum,
+            page_count: AtomicU32::new(header.page_count),
+            state: tokio::sync::Mutex::new(PagerState {
+                header,
+                catalog: Vec::new(),
+                rowids: HashMap::new(),
+            }),
+            snapshots: Mutex::new(Vec::new()),
+        };
+
+        let mut transaction = pager.begin().await;
+        let catalog = match transaction.header.catalog_root {
+            Some(root) => transaction
+                .read_tree(root)
+                .await?
+                .into_iter()
+                .map(|(_, payload)| decode_record(&payload))
+                .collect::<SqlResult<Vec<CatalogEntry>>>()?,
+            None => Vec::new(),
+        };
+        transaction.state.catalog = catalog;
+        drop(transaction);
+        // Pages a snapshot kept past the end of the file last time
+        pager.release_file_tail()?;
+
+        Ok(pager)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Page cache every page read goes through
+    pub fn cache(&self) -> Arc<PageCache> {
+        self.cache.clone()
+    }
+
+    /// Header as of the last commit
+    pub async fn header(&self) -> DatabaseHeader {
+        self.state.lock().await.header.clone()
+    }
+
+    /// Start a transaction; transactions are serialized, and one that is
+    /// dropped without committing leaves the file untouched
+    pub async fn begin(&self) -> PagerTransaction<'_> {
+        let state = self.state.lock().await;
+        PagerTransaction {
+            pager: self,
+            header: state.header.clone(),
+            catalog: state.catalog.clone(),
+            catalog_dirty: false,
+            pages: BTreeMap::new(),
+            rowids: HashMap::new(),
+            state,
+        }
+    }
+
+    /// Take a snapshot of the last commit; transactions keep committing
+    /// while it is read
+    pub async fn snapshot(&self) -> PagerSnapshot<'_> {
+        // No commit is in progress while the state is locked
+        let state = self.state.lock().await;
+        let preserved = Arc::new(SnapshotPages::default());
+        self.snapshots.lock().unwrap().push(Arc::downgrade(&preserved));
+        PagerSnapshot {
+            pager: self,
+            header: state.header.clone(),
+            preserved,
+        }
+    }
+
+    /// Copy the pages committed to the WAL into the database file and
+    /// empty the WAL; returns the number of pages copied
+    pub fn checkpoint(&self) -> SqlResult<usize> {
+        let copied = self.storage.checkpoint(self.storage.sync_mode)?;
+        self.release_file_tail()?;
+        Ok(copied)
+    }
+
+    /// Rewrite every table and the catalog into consecutive pages, leaving
+    /// the freelist empty, and shrink the file to match. With the WAL the
+    /// file shrinks at the checkpoint that follows, which is run here.
+    pub async fn vacuum(&self) -> SqlResult<VacuumStats> {
+        let mut transaction = self.begin().await;
+        let pages_before = transaction.header.page_count;
+        transaction.vacuum().await?;
+        transaction.commit().await?;
+        self.checkpoint()?;
+        Ok(VacuumStats {
+            pages_before,
+            pages_after: self.header().await.page_count,
+        })
+    }
+
+    /// Cut the file after the last page in use, unless an open snapshot
+    /// may still read past it or the WAL holds pages a checkpoint will
+    /// write there
+    fn release_file_tail(&self) -> SqlResult<()> {
+        let snapshot_open = {
+            let mut snapshots = self.snapshots.lock().unwrap();
+            snapshots.retain(|snapshot| snapshot.strong_count() > 0);
+            !snapshots.is_empty()
+        };
+        if snapshot_open || self.storage.wal_frames() > 0 {
+            return Ok(());
+        }
+        self.storage.file.truncate(self.page_count.load(Ordering::SeqCst))
+    }
+
+    /// Frames in the WAL waiting for a checkpoint
+    pub fn wal_frames(&self) -> usize {
+        self.storage.wal_frames()
+    }
+
+    /// Commit a transaction's pages, checkpointing if the WAL has grown
+    /// past the threshold
+    async fn write_pages(&self, pages: &BTreeMap<PageId, PageData>, page_count: u32) -> SqlResult<()> {
+        let snapshots: Vec<Arc<SnapshotPages>> = {
+            let mut snapshots = self.snapshots.lock().unwrap();
+            snapshots.retain(|snapshot| snapshot.strong_count() > 0);
+            snapshots.iter().filter_map(Weak::upgrade).collect()
+        };
+        // Open snapshots keep the old images, and are locked until the new
+        // ones are in place
+        let mut guards = Vec::with_capacity(snapshots.len());
+        for snapshot in &snapshots {
+            let mut preserved = snapshot.lock().await;
+            for page_id in pages.keys() {
+                if !preserved.contains_key(page_id) {
+                    if let Some(page) = self.storage.read_page(*page_id)? {
+                        preserved.insert(*page_id, page);
+                    }
+                }
+            }
+            guards.push(preserved);
+        }
+
+        self.storage.commit(pages)?;
+        self.page_count.store(page_count, Ordering::SeqCst);
+
+        // The cache only ever holds committed, clean page images
+        for (page_id, page) in pages {
+            self.cache.store_page(*page_id, page.clone()).await?;
+        }
+        drop(guards);
+
+        if self
+            .checkpoint_threshold
+            .is_some_and(|threshold| self.storage.wal_frames() >= threshold)
+        {
+            self.checkpoint()?;
+        } else {
+            self.release_file_tail()?;
+        }
+        Ok(())
+    }
+}
+
+/// A batch of changes to the database file, applied atomically by
+/// [`PagerTransaction::commit`]
+#[derive(Debug)]
+pub struct PagerTransaction<'a> {
+    pager: &'a Pager,
+    state: tokio::sync::MutexGuard<'a, PagerState>,
+    header: DatabaseHeader,
+    catalog: Vec<CatalogEntry>,
+    catalog_dirty: bool,
+    /// Pages written by this transaction, not yet visible to anyone else
+    pages: BTreeMap<PageId, PageData>,
+    /// Rowids of the tables this transaction changed, taken from the
+    /// pager's and put back when it commits
+    rowids: HashMap<String, Vec<RowId>>,
+}
+
+impl PagerTransaction<'_> {
+    /// Tables in the catalog with their schemas
+    pub fn tables(&self) -> Vec<(String, TableSchema)> {
+        self.catalog
+            .iter()
+            .filter_map(|entry| match entry {
+                CatalogEntry::Table { name, schema, .. } => Some((name.clone(), schema.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Index definitions in the catalog
+    pub fn indexes(&self) -> Vec<IndexInfo> {
+        self.catalog
+            .iter()
+            .filter_map(|entry| match entry {
+                CatalogEntry::Index { index } => Some(index.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Table statistics in the catalog
+    pub fn stats(&self) -> Vec<(String, TableStats)> {
+        self.catalog
+            .iter()
+            .filter_map(|entry| match entry {
+                CatalogEntry::Stats { table, stats } => Some((table.clone(), stats.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Read every row of a table, in rowid order
+    pub async fn read_table(&mut self, name: &str) -> SqlResult<Vec<Row>> {
+        let root = self
+            .table_root(name)
+            .ok_or_else(|| SqlError::table_not_found(name.to_string()))?;
+        let records = self.read_tree(root).await?;
+        if !self.rowids.contains_key(name) {
+            let rowids = records.iter().map(|(rowid, _)| *rowid).collect();
+            self.state.rowids.insert(name.to_string(), rowids);
+        }
+        records.into_iter().map(|(_, payload)| decode_record(&payload)).collect()
+    }
+
+    /// Replace the contents (and schema) of a table, creating it if needed
+    pub async fn write_table(&mut self, name: &str, schema: TableSchema, rows: &[Row]) -> SqlResult<()> {
+        if let Some(old_root) = self.table_root(name) {
+            self.free_tree(old_root).await?;
+        }
+        let records = rows
+            .iter()
+            .enumerate()
+            .map(|(position, row)| Ok((RowId(position as u64 + 1), encode_record(row)?)))
+            .collect::<SqlResult<Vec<_>>>()?;
+        self.state.rowids.remove(name);
+        self.rowids
+            .insert(name.to_string(), records.iter().map(|(rowid, _)| *rowid).collect());
+        let root = self.write_tree(records).await?;
+
+        self.catalog
+            .retain(|entry| !matches!(entry, CatalogEntry::Table { name: existing, .. } if existing == name));
+        self.catalog.push(CatalogEntry::Table {
+            name: name.to_string(),
+            schema,
+            root: root.0,
+        });
+        self.catalog_dirty = true;
+        Ok(())
+    }
+
+    /// Add rows after the last row of a table, writing only the pages
+    /// they go in
+    pub async fn append_rows(&mut self, name: &str, rows: &[Row]) -> SqlResult<()> {
+        let (mut root, mut rowids) = self.table_rowids(name).await?;
+        let mut next = rowids.last().map_or(1, |rowid| rowid.0 + 1);
+        for row in rows {
+            root = self.put_record(root, RowId(next), encode_record(row)?).await?;
+            rowids.push(RowId(next));
+            next += 1;
+        }
+        self.rowids.insert(name.to_string(), rowids);
+        self.set_table_root(name, root);
+        Ok(())
+    }
+
+    /// Replace the rows of a table at the given positions
+    pub async fn update_rows(&mut self, name: &str, rows: &[(usize, Row)]) -> SqlResult<()> {
+        let (mut root, rowids) = self.table_rowids(name).await?;
+        for (position, row) in rows {
+            let rowid = row_at(name, &rowids, *position)?;
+            root = self.put_record(root, rowid, encode_record(row)?).await?;
+        }
+        self.rowids.insert(name.to_string(), rowids);
+        self.set_table_root(name, root);
+        Ok(())
+    }
+
+    /// Remove the rows of a table at the given positions, which are
+    /// ascending; the rows after them move up
+    pub async fn delete_rows(&mut self, name: &str, positions: &[usize]) -> SqlResult<()> {
+        let (root, mut rowids) = self.table_rowids(name).await?;
+        for &position in positions {
+            self.delete_record(root, row_at(name, &rowids, position)?).await?;
+        }
+        let mut position = 0;
+        rowids.retain(|_| {
+            let kept = positions.binary_search(&position).is_err();
+            position += 1;
+            kept
+        });
+        self.rowids.insert(name.to_string(), rowids);
+        Ok(())
+    }
+
+    /// Root of a table and the rowids of its rows in row order, which the
+    /// caller puts back in `rowids` once it has changed them
+    async fn table_rowids(&mut self, name: &str) -> SqlResult<(PageId, Vec<RowId>)> {
+        let root = self
+            .table_root(name)
+            .ok_or_else(|| SqlError::table_not_found(name.to_string()))?;
+        let rowids = match self.rowids.remove(name).or_else(|| self.state.rowids.remove(name)) {
+            Some(rowids) => rowids,
+            None => self.tree_rowids(root).await?,
+        };
+        Ok((root, rowids))
+    }
+
+    fn set_table_root(&mut self, name: &str, new_root: PageId) {
+        for entry in &mut self.catalog {
+            if let CatalogEntry::Table { name: existing, root, .. } = entry {
+                if existing == name && *root != new_root.0 {
+                    *root = new_root.0;
+                    self.catalog_dirty = true;
+                }
+            }
+        }
+    }
+
+    /// Remove a table, its pages and its indexes; a missing table is ignored
+    pub async fn drop_table(&mut self, name: &str) -> SqlResult<()> {
+        if let Some(root) = self.table_root(name) {
+            self.free_tree(root).await?;
+        }
+        self.rowids.remove(name);
+        self.state.rowids.remove(name);
+        self.catalog.retain(|entry| match entry {
+            CatalogEntry::Table { name: existing, .. } => existing != name,
+            CatalogEntry::Index { index } => !index.table.eq_ignore_ascii_case(name),
+            CatalogEntry::Stats { table, .. } => table != name,
+        });
+        self.catalog_dirty = true;
+        Ok(())
+    }
+
+    /// Record an index definition; its entries are rebuilt from the table
+    /// when the database is opened
+    pub fn put_index(&mut self, index: IndexInfo) {
+        self.drop_index(&index.name);
+        self.catalog.push(CatalogEntry::Index { index });
+        self.catalog_dirty = true;
+    }
+
+    pub fn drop_index(&mut self, name: &str) {
+        self.catalog
+            .retain(|entry| !matches!(entry, CatalogEntry::Index { index } if index.name == name));
+        self.catalog_dirty = true;
+    }
+
+    /// Replace the statistics of every table
+    pub fn put_stats(&mut self, stats: impl IntoIterator<Item = (String, TableStats)>) {
+        self.catalog.retain(|entry| !matches!(entry, CatalogEntry::Stats { .. }));
+        self.catalog
+            .extend(stats.into_iter().map(|(table, stats)| CatalogEntry::Stats { table, stats }));
+        self.catalog_dirty = true;
+    }
+
+    /// Rewrite every table into consecutive pages after the header, as if
+    /// the database were loaded into a new file. Pages past the new end
+    /// of the file are no longer in use once the transaction commits.
+    pub async fn vacuum(&mut self) -> SqlResult<()> {
+        let mut contents = Vec::new();
+        for entry in &self.catalog {
+            if let CatalogEntry::Table { name, root, .. } = entry {
+                contents.push((name.clone(), PageId(*root)));
+            }
+        }
+        let mut tables = Vec::with_capacity(contents.len());
+        for (name, root) in contents {
+            tables.push((name, self.read_tree(root).await?));
+        }
+
+        // Every page is written afresh, starting after the header
+        self.pages.clear();
+        self.header.page_count = 1;
+        self.header.freelist_head = None;
+        self.header.freelist_count = 0;
+        self.header.catalog_root = None;
+        for (name, records) in tables {
+            let new_root = self.write_tree(records).await?;
+            for entry in &mut self.catalog {
+                if let CatalogEntry::Table { name: existing, root, .. } = entry {
+                    if *existing == name {
+                        *root = new_root.0;
+                    }
+                }
+            }
+        }
+        self.catalog_dirty = true;
+        Ok(())
+    }
+
+    /// Make every change durable and visible
+    pub async fn commit(mut self) -> SqlResult<()> {
+        if self.catalog_dirty {
+            if let Some(root) = self.header.catalog_root.take() {
+                self.free_tree(root).await?;
+            }
+        }
+        // Before the catalog is written, so it takes the lowest free pages
+        if self.pager.auto_vacuum {
+            self.release_free_tail().await?;
+        }
+        if self.catalog_dirty && !self.catalog.is_empty() {
+            let records = self
+                .catalog
+                .iter()
+                .enumerate()
+                .map(|(position, entry)| Ok((RowId(position as u64 + 1), encode_record(entry)?)))
+                .collect::<SqlResult<Vec<_>>>()?;
+            self.header.catalog_root = Some(self.write_tree(records).await?);
+        }
+
+        // The change counter is the commit's LSN, stamped on every page it
+        // writes
+        self.header.change_counter += 1;
+        let lsn = self.header.change_counter;
+        self.pages
+            .insert(PageId(0), PageData::new(self.header.encode(), PageType::Metadata));
+        for page in self.pages.values_mut() {
+            page.lsn = lsn;
+        }
+        self.pager.write_pages(&self.pages, self.header.page_count).await?;
+
+        self.state.header = self.header.clone();
+        self.state.catalog = std::mem::take(&mut self.catalog);
+        let rowids = std::mem::take(&mut self.rowids);
+        self.state.rowids.extend(rowids);
+        Ok(())
+    }
+
+    fn table_root(&self, name: &str) -> Option<PageId> {
+        self.catalog.iter().find_map(|entry| match entry {
+            CatalogEntry::Table { name: existing, root, .. } if existing == name => Some(PageId(*root)),
+            _ => None,
+        })
+    }
+
+    fn page_size(&self) -> usize {
+        self.header.page_size as usize
+    }
+
+    async fn read_page(&mut self, page_id: PageId) -> SqlResult<PageData> {
+        match self.pages.get(&page_id) {
+            Some(page) => Ok(page.clone()),
+            None => Ok(self.pager.cache.load_page(page_id).await?.as_ref().clone()),
+        }
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: PageData) {
+        self.pages.insert(page_id, page);
+    }
+
+    /// Take a page from the freelist, or grow the file by one page
+    async fn allocate(&mut self) -> SqlResult<PageId> {
+        match self.header.freelist_head {
+            Some(page_id) => {
+                let page = self.read_page(page_id).await?;
+                self.header.freelist_head = tree::decode_free(page_id, &page)?;
+                self.header.freelist_count -= 1;
+                Ok(page_id)
+            }
+            None => {
+                let page_id = PageId(self.header.page_count);
+                self.header.page_count += 1;
+                Ok(page_id)
+            }
+        }
+    }
+
+    /// Take the free pages at the end of the file off the freelist and
+    /// out of the file's page count
+    async fn release_free_tail(&mut self) -> SqlResult<()> {
+        let mut free = Vec::with_capacity(self.header.freelist_count as usize);
+        let mut next = self.header.freelist_head;
+        while let Some(page_id) = next {
+            let page = self.read_page(page_id).await?;
+            next = tree::decode_free(page_id, &page)?;
+            free.push(page_id);
+        }
+        let sorted = free.is_sorted();
+        free.sort();
+        let count = free.len();
+        while free.last().is_some_and(|page_id| page_id.0 + 1 == self.header.page_count) {
+            let page_id = free.pop().expect("checked above");
+            self.pages.remove(&page_id);
+            self.header.page_count -= 1;
+        }
+        if sorted && free.len() == count {
+            return Ok(());
+        }
+        // The rest of the freelist is relinked lowest page first, so pages
+        // near the start of the file are reused before those at the end
+        self.header.freelist_head = None;
+        self.header.freelist_count = 0;
+        for page_id in free.into_iter().rev() {
+            self.free(page_id);
+        }
+        Ok(())
+    }
+
+    fn free(&mut self, page_id: PageId) {
+        let page = tree::encode_free(self.header.freelist_head);
+        self.write_page(page_id, page);
+        self.header.freelist_head = Some(page_id);
+        self.header.freelist_count += 1;
+    }
+
+    /// Bulk-load a B-tree from records sorted by rowid, returning its root
+    async fn write_tree(&mut self, records: Vec<(RowId, Vec<u8>)>) -> SqlResult<PageId> {
+        let capacity = tree::leaf_capacity(self.page_size());
+        let mut level: Vec<(RowId, PageId)> = Vec::new();
+        let mut cells: Vec<LeafCell> = Vec::new();
+        let mut used = 0;
+        for (rowid, payload) in records {
+            let cell = LeafCell {
+                rowid,
+                payload: self.write_payload(payload).await?,
+            };
+            if used + cell.encoded_len() > capacity && !cells.is_empty() {
+                level.push(self.write_node(TreePage::Leaf(std::mem::take(&mut cells))).await?);
+                used = 0;
+            }
+            used += cell.encoded_len();
+            cells.push(cell);
+        }
+        if !cells.is_empty() || level.is_empty() {
+            level.push(self.write_node(TreePage::Leaf(cells)).await?);
+        }
+
+        let fanout = tree::interior_fanout(self.page_size());
+        while level.len() > 1 {
+            let mut parents = Vec::with_capacity(level.len() / fanout + 1);
+            for children in level.chunks(fanout) {
+                parents.push(self.write_node(TreePage::Interior(children.to_vec())).await?);
+            }
+            level = parents;
+        }
+        Ok(level[0].1)
+    }
+
+    /// Allocate a page for a tree node; returns the first rowid below it
+    async fn write_node(&mut self, node: TreePage) -> SqlResult<(RowId, PageId)> {
+        let first = first_rowid(&node);
+        let page_id = self.allocate().await?;
+        self.write_page(page_id, node.encode());
+        Ok((first, page_id))
+    }
+
+    /// Write a node to `page_id`, moving what does not fit there into new
+    /// pages; returns the node's first rowid and the new pages, which go
+    /// right after it in its parent
+    async fn store_node(&mut self, page_id: PageId, node: TreePage) -> SqlResult<(RowId, Vec<(RowId, PageId)>)> {
+        let mut pieces = match node {
+            TreePage::Leaf(cells) => {
+                let capacity = tree::leaf_capacity(self.page_size());
+                let mut pieces = Vec::new();
+                let mut piece = Vec::new();
+                let mut used = 0;
+                for cell in cells {
+                    if used + cell.encoded_len() > capacity && !piece.is_empty() {
+                        pieces.push(TreePage::Leaf(std::mem::take(&mut piece)));
+                        used = 0;
+                    }
+                    used += cell.encoded_len();
+                    piece.push(cell);
+                }
+                pieces.push(TreePage::Leaf(piece));
+                pieces
+            }
+            TreePage::Interior(children) => children
+                .chunks(tree::interior_fanout(self.page_size()))
+                .map(|chunk| TreePage::Interior(chunk.to_vec()))
+                .collect(),
+        }
+        .into_iter();
+        let node = pieces.next().expect("a node has at least one piece");
+        let first = first_rowid(&node);
+        self.write_page(page_id, node.encode());
+        let mut siblings = Vec::new();
+        for piece in pieces {
+            siblings.push(self.write_node(piece).await?);
+        }
+        Ok((first, siblings))
+    }
+
+    /// The leaf of a tree `rowid` belongs in, with its cells and the
+    /// interior pages above it: each with its children and the one taken
+    async fn find_leaf(
+        &mut self,
+        root: PageId,
+        rowid: RowId,
+    ) -> SqlResult<(Vec<(PageId, Vec<(RowId, PageId)>, usize)>, PageId, Vec<LeafCell>)> {
+        let mut path = Vec::new();
+        let mut page_id = root;
+        loop {
+            let page = self.read_page(page_id).await?;
+            match TreePage::decode(page_id, &page)? {
+                TreePage::Leaf(cells) => return Ok((path, page_id, cells)),
+                TreePage::Interior(children) => {
+                    let index = children.partition_point(|(first, _)| *first <= rowid).saturating_sub(1);
+                    let child = children[index].1;
+                    path.push((page_id, children, index));
+                    page_id = child;
+                }
+            }
+        }
+    }
+
+    /// Store the record of `rowid` in a tree, in place of the one it has,
+    /// splitting the pages it no longer fits in; returns the root, which
+    /// is a new page once the old root split
+    async fn put_record(&mut self, mut root: PageId, rowid: RowId, payload: Vec<u8>) -> SqlResult<PageId> {
+        let (mut path, leaf, mut cells) = self.find_leaf(root, rowid).await?;
+        let payload = self.write_payload(payload).await?;
+        match cells.binary_search_by_key(&rowid, |cell| cell.rowid) {
+            Ok(at) => {
+                let old = std::mem::replace(&mut cells[at].payload, payload);
+                self.free_payload(old).await?;
+            }
+            Err(at) => cells.insert(at, LeafCell { rowid, payload }),
+        }
+        let (mut first, mut siblings) = self.store_node(leaf, TreePage::Leaf(cells)).await?;
+        while !siblings.is_empty() {
+            let (page_id, children) = match path.pop() {
+                Some((parent, mut children, index)) => {
+                    children.splice(index + 1..index + 1, siblings);
+                    (parent, children)
+                }
+                // The root split, so a new root goes above it and the
+                // pages split off it
+                None => {
+                    let mut children = vec![(first, root)];
+                    children.extend(siblings);
+                    root = self.allocate().await?;
+                    (root, children)
+                }
+            };
+            (first, siblings) = self.store_node(page_id, TreePage::Interior(children)).await?;
+        }
+        Ok(root)
+    }
+
+    /// Remove the record of `rowid` from a tree. A leaf left empty is taken
+    /// out of its parent, and so on up; the root stays, as an empty leaf
+    /// once nothing is left under it.
+    async fn delete_record(&mut self, root: PageId, rowid: RowId) -> SqlResult<()> {
+        let (mut path, leaf, mut cells) = self.find_leaf(root, rowid).await?;
+        let at = cells
+            .binary_search_by_key(&rowid, |cell| cell.rowid)
+            .map_err(|_| SqlError::btree_error(format!("Row {} is missing from the tree at page {}", rowid, root)))?;
+        let cell = cells.remove(at);
+        self.free_payload(cell.payload).await?;
+        if !cells.is_empty() || path.is_empty() {
+            self.write_page(leaf, TreePage::Leaf(cells).encode());
+            return Ok(());
+        }
+        self.free(leaf);
+        while let Some((page_id, mut children, index)) = path.pop() {
+            children.remove(index);
+            if !children.is_empty() {
+                self.write_page(page_id, TreePage::Interior(children).encode());
+                return Ok(());
+            }
+            if path.is_empty() {
+                self.write_page(page_id, TreePage::Leaf(Vec::new()).encode());
+                return Ok(());
+            }
+            self.free(page_id);
+        }
+        Ok(())
+    }
+
+    async fn write_payload(&mut self, payload: Vec<u8>) -> SqlResult<CellPayload> {
+        if payload.len() <= tree::max_inline_payload(self.page_size()) {
+            return Ok(CellPayload::Inline(payload));
+        }
+        // Chunks are written back to front so each page knows its successor
+        let chunks: Vec<&[u8]> = payload.chunks(tree::overflow_chunk_size(self.page_size())).collect();
+        let mut next = None;
+        for chunk in chunks.iter().rev() {
+            let page_id = self.allocate().await?;
+            self.write_page(page_id, tree::encode_overflow(next, chunk));
+            next = Some(page_id);
+        }
+        Ok(CellPayload::Overflow {
+            len: payload.len() as u32,
+            first: next.expect("payload larger than a page has at least one chunk"),
+        })
+    }
+
+    async fn read_payload(&mut self, payload: CellPayload) -> SqlResult<Vec<u8>> {
+        match payload {
+            CellPayload::Inline(bytes) => Ok(bytes),
+            CellPayload::Overflow { len, first } => {
+                let mut bytes = Vec::with_capacity(len as usize);
+                let mut next = Some(first);
+                while let Some(page_id) = next {
+                    let page = self.read_page(page_id).await?;
+                    let (following, chunk) = tree::decode_overflow(page_id, &page)?;
+                    bytes.extend_from_slice(chunk);
+                    next = following;
+                }
+                if bytes.len() != len as usize {
+                    return Err(SqlError::btree_error(format!(
+                        "Overflow chain at page {} holds {} bytes, expected {}",
+                        first,
+                        bytes.len(),
+                        len
+                    )));
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Every record of a tree, in rowid order
+    async fn read_tree(&mut self, root: PageId) -> SqlResult<Vec<(RowId, Vec<u8>)>> {
+        let mut records = Vec::new();
+        let mut stack = vec![root];
+        while let Some(page_id) = stack.pop() {
+            let page = self.read_page(page_id).await?;
+            match TreePage::decode(page_id, &page)? {
+                TreePage::Leaf(cells) => {
+                    for cell in cells {
+                        records.push((cell.rowid, self.read_payload(cell.payload).await?));
+                    }
+                }
+                TreePage::Interior(children) => {
+                    stack.extend(children.into_iter().rev().map(|(_, child)| child));
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// Rowids of every record of a tree, in order, read without the
+    /// records themselves
+    async fn tree_rowids(&mut self, root: PageId) -> SqlResult<Vec<RowId>> {
+        let mut rowids = Vec::new();
+        let mut stack = vec![root];
+        while let Some(page_id) = stack.pop() {
+            let page = self.read_page(page_id).await?;
+            match TreePage::decode(page_id, &page)? {
+                TreePage::Leaf(cells) => rowids.extend(cells.iter().map(|cell| cell.rowid)),
+                TreePage::Interior(children) => {
+                    stack.extend(children.into_iter().rev().map(|(_, child)| child));
+                }
+            }
+        }
+        Ok(rowids)
+    }
+
+    /// Return every page of a tree, including overflow chains, to the
+    /// freelist
+    async fn free_tree(&mut self, root: PageId) -> SqlResult<()> {
+        let mut stack = vec![root];
+        while let Some(page_id) = stack.pop() {
+            let page = self.read_page(page_id).await?;
+            match TreePage::decode(page_id, &page)? {
+                TreePage::Leaf(cells) => {
+                    for cell in cells {
+                        self.free_payload(cell.payload).await?;
+                    }
+                }
+                TreePage::Interior(children) => stack.extend(children.into_iter().map(|(_, child)| child)),
+            }
+            self.free(page_id);
+        }
+        Ok(())
+    }
+
+    /// Return the overflow chain of a payload, if it has one, to the
+    /// freelist
+    async fn free_payload(&mut self, payload: CellPayload) -> SqlResult<()> {
+        if let CellPayload::Overflow { first, .. } = payload {
+            let mut next = Some(first);
+            while let Some(overflow) = next {
+                let page = self.read_page(overflow).await?;
+                next = tree::decode_overflow(overflow, &page)?.0;
+                self.free(overflow);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// First rowid under a tree node; an empty leaf has none, and gets 0
+fn first_rowid(node: &TreePage) -> RowId {
+    match node {
+        TreePage::Leaf(cells) => cells.first().map_or(RowId(0), |cell| cell.rowid),
+        TreePage::Interior(children) => children[0].0,
+    }
+}
+
+/// Rowid of the row of a table at `position`
+fn row_at(table: &str, rowids: &[RowId], position: usize) -> SqlResult<RowId> {
+    rowids.get(position).copied().ok_or_else(|| {
+        SqlError::btree_error(format!("Table {} has no row at position {}", table, position))
+    })
+}
+
+fn encode_record<T: Serialize>(record: &T) -> SqlResult<Vec<u8>> {
+    serde_json::to_vec(record).map_err(|e| SqlError::io_error(format!("Cannot encode record: {}", e)))
+}
+
+fn decode_record<T: for<'de> Deserialize<'de>>(bytes: &[u8]) -> SqlResult<T> {
+    serde_json::from_slice(bytes).map_err(|e| SqlError::btree_error(format!("Corrupted record: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+
+    fn config(page_size: usize) -> PagerConfig {
+        PagerConfig {
+            cache: PageCacheConfig {
+                capacity: 8,
+                page_size,
+                enable_prefetch: false,
+                ..PageCacheConfig::default()
+            },
+            enable_wal: true,
+            sync_mode: WalSyncMode::Normal,
+            checkpoint_threshold: None,
+            auto_vacuum: false,
+        }
+    }
+
+    fn rows(count: usize, width: usize) -> Vec<Row> {
+        (0..count)
+            .map(|i| Row::new(vec![Value::Integer(i as i64), Value::Text("x".repeat(width))]))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_tables_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+        // Enough rows for interior pages, and some too large for a leaf
+        let mut expected = rows(300, 10);
+        expected.extend(rows(3, 2000));
+
+        {
+            let pager = Pager::open(&path, config(512)).await.unwrap();
+            let mut transaction = pager.begin().await;
+            transaction.write_table("items", schema.clone(), &expected).await.unwrap();
+            transaction.put_index(IndexInfo::new("idx_items", "items", vec!["id".to_string()], true));
+            transaction.commit().await.unwrap();
+
+            // Uncommitted changes are discarded
+            let mut transaction = pager.begin().await;
+            transaction.drop_table("items").await.unwrap();
+        }
+
+        // The page size comes from the file, not the configuration
+        let pager = Pager::open(&path, config(4096)).await.unwrap();
+        assert_eq!(pager.header().await.page_size, 512);
+        let mut transaction = pager.begin().await;
+        assert_eq!(transaction.tables().len(), 1);
+        assert_eq!(transaction.indexes()[0].name, "idx_items");
+        assert_eq!(transaction.read_table("items").await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_rows_change_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+        let row = |id: i64, width: usize| Row::new(vec![Value::Integer(id), Value::Text("y".repeat(width))]);
+        let mut expected = rows(300, 10);
+
+        let pager = Pager::open(&path, config(512)).await.unwrap();
+        let mut transaction = pager.begin().await;
+        transaction.write_table("items", schema.clone(), &expected).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        // Appends split the last leaf and, in time, the root; updates and
+        // deletes anywhere, some with rows too large for a leaf
+        let mut transaction = pager.begin().await;
+        let appended: Vec<Row> = (300..600).map(|id| row(id, if id % 100 == 0 { 2000 } else { 10 })).collect();
+        transaction.append_rows("items", &appended).await.unwrap();
+        expected.extend(appended);
+        let updated = vec![(0, row(-1, 3000)), (150, row(-2, 5)), (400, row(-3, 10))];
+        transaction.update_rows("items", &updated).await.unwrap();
+        for (position, row) in &updated {
+            expected[*position] = row.clone();
+        }
+        let deleted: Vec<usize> = [1, 2, 3].into_iter().chain(290..320).chain([599]).collect();
+        transaction.delete_rows("items", &deleted).await.unwrap();
+        for position in deleted.iter().rev() {
+            expected.remove(*position);
+        }
+        assert_eq!(transaction.read_table("items").await.unwrap(), expected);
+        transaction.commit().await.unwrap();
+
+        // A row added to a large table writes a handful of pages, not the
+        // table
+        let mut transaction = pager.begin().await;
+        transaction.append_rows("items", &[row(1000, 10)]).await.unwrap();
+        assert!(transaction.pages.len() <= 3);
+        drop(transaction);
+        let mut transaction = pager.begin().await;
+        transaction.update_rows("items", &[(200, row(1001, 10))]).await.unwrap();
+        assert!(transaction.pages.len() <= 2);
+        drop(transaction);
+
+        // Emptying the table frees its pages but keeps the root
+        let mut transaction = pager.begin().await;
+        let all: Vec<usize> = (0..expected.len()).collect();
+        transaction.delete_rows("items", &all).await.unwrap();
+        transaction.append_rows("items", &[row(7, 10)]).await.unwrap();
+        transaction.commit().await.unwrap();
+        let header = pager.header().await;
+        assert!(header.freelist_count > header.page_count / 2);
+        drop(pager);
+
+        let pager = Pager::open(&path, config(512)).await.unwrap();
+        let mut transaction = pager.begin().await;
+        assert_eq!(transaction.read_table("items").await.unwrap(), vec![row(7, 10)]);
+    }
+
+    #[tokio::test]
+    async fn test_freed_pages_are_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let pager = Pager::open(dir.path().join("test.db"), config(512)).await.unwrap();
+        let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+
+        for _ in 0..3 {
+            let mut transaction = pager.begin().await;
+            transaction.write_table("items", schema.clone(), &rows(100, 20)).await.unwrap();
+            transaction.commit().await.unwrap();
+        }
+        let after_rewrites = pager.header().await;
+
+        let mut transaction = pager.begin().await;
+        transaction.write_table("items", schema.clone(), &rows(100, 20)).await.unwrap();
+        transaction.commit().await.unwrap();
+        assert_eq!(pager.header().await.page_count, after_rewrites.page_count);
+
+        let mut transaction = pager.begin().await;
+        transaction.drop_table("items").await.unwrap();
+        transaction.commit().await.unwrap();
+        let header = pager.header().await;
+        assert_eq!(header.freelist_count, header.page_count - 1);
+        assert_eq!(header.catalog_root, None);
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_and_auto_vacuum_shrink_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        async fn write(pager: &Pager, table: &str, count: usize) {
+            let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+            let mut transaction = pager.begin().await;
+            transaction.write_table(table, schema, &rows(count, 20)).await.unwrap();
+            transaction.commit().await.unwrap();
+        }
+
+        let pager = Pager::open(&path, config(512)).await.unwrap();
+        write(&pager, "small", 10).await;
+        write(&pager, "large", 300).await;
+        write(&pager, "large", 20).await;
+        pager.checkpoint().unwrap();
+        let before = pager.header().await;
+        assert!(before.freelist_count > before.page_count / 2);
+        let file_len = std::fs::metadata(&path).unwrap().len();
+
+        let stats = pager.vacuum().await.unwrap();
+        let header = pager.header().await;
+        assert_eq!(stats.pages_before, before.page_count);
+        assert_eq!(stats.pages_after, header.page_count);
+        assert_eq!((header.freelist_head, header.freelist_count), (None, 0));
+        assert!(header.page_count <= before.page_count - before.freelist_count);
+        assert!(std::fs::metadata(&path).unwrap().len() < file_len);
+        drop(pager);
+
+        let pager = Pager::open(&path, PagerConfig { auto_vacuum: true, ..config(512) }).await.unwrap();
+        let mut transaction = pager.begin().await;
+        assert_eq!(transaction.read_table("small").await.unwrap(), rows(10, 20));
+        assert_eq!(transaction.read_table("large").await.unwrap(), rows(20, 20));
+        drop(transaction);
+
+        // Dropping the table at the end of the file gives its pages back
+        // at the commit
+        write(&pager, "tail", 200).await;
+        let grown = pager.header().await.page_count;
+        let mut transaction = pager.begin().await;
+        transaction.drop_table("tail").await.unwrap();
+        transaction.commit().await.unwrap();
+        pager.checkpoint().unwrap();
+        let header = pager.header().await;
+        assert_eq!(header.page_count, stats.pages_after);
+        assert!(header.page_count < grown);
+        let mut transaction = pager.begin().await;
+        assert_eq!(transaction.read_table("large").await.unwrap(), rows(20, 20));
+    }
+
+    #[tokio::test]
+    async fn test_committed_wal_is_replayed_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+
+        // Capture the pages of a commit, then simulate a crash after the
+        // WAL append but before the database file was written
+        let commit = {
+            let pager = Pager::open(&path, config(512)).await.unwrap();
+            let mut transaction = pager.begin().await;
+            transaction.write_table("items", schema, &rows(50, 5)).await.unwrap();
+            transaction.commit().await.unwrap();
+            let mut pages = BTreeMap::new();
+            for page in 0..pager.header().await.page_count {
+                let page_id = PageId(page);
+                pages.insert(page_id, pager.storage.read_page(page_id).unwrap().unwrap());
+            }
+            pages
+        };
+        std::fs::remove_file(&path).unwrap();
+        WalFile::open(wal_path(&path)).unwrap().append_commit(&commit, true).unwrap();
+
+        let pager = Pager::open(&path, config(512)).await.unwrap();
+        let mut transaction = pager.begin().await;
+        assert_eq!(transaction.read_table("items").await.unwrap(), rows(50, 5));
+        assert_eq!(std::fs::metadata(wal_path(&path)).unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_commits_wait_in_wal_until_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+
+        let pager = Pager::open(&path, config(512)).await.unwrap();
+        let file_len = std::fs::metadata(&path).unwrap().len();
+        let mut transaction = pager.begin().await;
+        transaction.write_table("items", schema, &rows(200, 10)).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        // Nothing reached the database file, and pages evicted from the
+        // cache are read back from the WAL
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), file_len);
+        assert!(pager.wal_frames() > 0);
+        pager.cache().clear().await.unwrap();
+        let mut transaction = pager.begin().await;
+        assert_eq!(transaction.read_table("items").await.unwrap(), rows(200, 10));
+        drop(transaction);
+
+        let copied = pager.checkpoint().unwrap();
+        assert_eq!(copied as u32, pager.header().await.page_count);
+        assert_eq!(pager.wal_frames(), 0);
+        assert_eq!(std::fs::metadata(wal_path(&path)).unwrap().len(), 0);
+        assert!(std::fs::metadata(&path).unwrap().len() > (copied as u64 - 1) * 512);
+        assert_eq!(pager.checkpoint().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let schema = TableSchema::untyped(&["id".to_string(), "data".to_string()]);
+        let config = PagerConfig {
+            checkpoint_threshold: Some(20),
+            ..config(512)
+        };
+
+        let pager = Pager::open(&path, config.clone()).await.unwrap();
+        for count in 1..=10 {
+            let mut transaction = pager.begin().await;
+            transaction.write_table("items", schema.clone(), &rows(count * 10, 10)).await.unwrap();
+            transaction.commit().await.unwrap();
+            assert!(pager.wal_frames() < 20);
+        }
+        drop(pager);
+
+        // Disabling the WAL checkpoints and removes what was left of it
+        let pager = Pager::open(&path, PagerConfig { enable_wal: false, ..config }).await.unwrap();
+        assert!(!wal_path(&path).exists());
+        let mut transaction = pager.begin().await;
+        assert_eq!(transaction.read_table("items").await.unwrap(), rows(100, 10));
+    }
+}
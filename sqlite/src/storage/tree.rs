@@ -0,0 +1,262 @@
+use crate::error::{SqlError, SqlResult};
+use crate::page_cache::{PageData, PageType};
+use crate::types::{PageId, RowId};
+
+/// Type byte of a leaf page, which holds rows keyed by rowid
+pub const LEAF_PAGE: u8 = 0x0D;
+
+/// Type byte of an interior page, which routes to child pages
+pub const INTERIOR_PAGE: u8 = 0x05;
+
+/// Type byte, then a u16 entry count
+const TREE_HEADER: usize = 3;
+
+/// Encoded size of a leaf cell apart from its inline payload: rowid, tag,
+/// payload length
+const CELL_HEADER: usize = 8 + 1 + 4;
+
+/// Encoded size of an interior entry: first rowid of the child, child page
+const INTERIOR_ENTRY: usize = 8 + 4;
+
+/// Payload of a leaf cell: stored in the leaf, or spilled to a chain of
+/// overflow pages when it is too large
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellPayload {
+    Inline(Vec<u8>),
+    Overflow { len: u32, first: PageId },
+}
+
+/// One row in a leaf page
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeafCell {
+    pub rowid: RowId,
+    pub payload: CellPayload,
+}
+
+impl LeafCell {
+    /// Bytes the cell takes up in a leaf page
+    pub fn encoded_len(&self) -> usize {
+        match &self.payload {
+            CellPayload::Inline(bytes) => CELL_HEADER + bytes.len(),
+            CellPayload::Overflow { .. } => CELL_HEADER + 4,
+        }
+    }
+}
+
+/// A page of a table B-tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreePage {
+    /// Rows in rowid order
+    Leaf(Vec<LeafCell>),
+    /// Children in rowid order, each with the first rowid stored below it
+    Interior(Vec<(RowId, PageId)>),
+}
+
+impl TreePage {
+    pub fn encode(&self) -> PageData {
+        let mut bytes = Vec::new();
+        match self {
+            TreePage::Leaf(cells) => {
+                bytes.push(LEAF_PAGE);
+                bytes.extend_from_slice(&(cells.len() as u16).to_le_bytes());
+                for cell in cells {
+                    bytes.extend_from_slice(&cell.rowid.0.to_le_bytes());
+                    match &cell.payload {
+                        CellPayload::Inline(payload) => {
+                            bytes.push(0);
+                            bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+                            bytes.extend_from_slice(payload);
+                        }
+                        CellPayload::Overflow { len, first } => {
+                            bytes.push(1);
+                            bytes.extend_from_slice(&len.to_le_bytes());
+                            bytes.extend_from_slice(&first.0.to_le_bytes());
+                        }
+                    }
+                }
+                PageData::new(bytes, PageType::Data)
+            }
+            TreePage::Interior(children) => {
+                bytes.push(INTERIOR_PAGE);
+                bytes.extend_from_slice(&(children.len() as u16).to_le_bytes());
+                for (rowid, child) in children {
+                    bytes.extend_from_slice(&rowid.0.to_le_bytes());
+                    bytes.extend_from_slice(&child.0.to_le_bytes());
+                }
+                PageData::new(bytes, PageType::Index)
+            }
+        }
+    }
+
+    pub fn decode(page_id: PageId, page: &PageData) -> SqlResult<Self> {
+        let mut reader = ByteReader::new(page_id, &page.data);
+        let kind = reader.u8()?;
+        let count = reader.u16()? as usize;
+        match kind {
+            LEAF_PAGE => {
+                let mut cells = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let rowid = RowId(reader.u64()?);
+                    let payload = match reader.u8()? {
+                        0 => {
+                            let len = reader.u32()? as usize;
+                            CellPayload::Inline(reader.bytes(len)?.to_vec())
+                        }
+                        1 => CellPayload::Overflow {
+                            len: reader.u32()?,
+                            first: PageId(reader.u32()?),
+                        },
+                        tag => return Err(corrupted(page_id, format!("unknown cell tag {}", tag))),
+                    };
+                    cells.push(LeafCell { rowid, payload });
+                }
+                Ok(TreePage::Leaf(cells))
+            }
+            INTERIOR_PAGE => {
+                let mut children = Vec::with_capacity(count);
+                for _ in 0..count {
+                    children.push((RowId(reader.u64()?), PageId(reader.u32()?)));
+                }
+                Ok(TreePage::Interior(children))
+            }
+            kind => Err(corrupted(page_id, format!("not a B-tree page (type {:#04x})", kind))),
+        }
+    }
+}
+
+/// Bytes available for cells in a leaf page
+pub fn leaf_capacity(page_size: usize) -> usize {
+    page_size - TREE_HEADER
+}
+
+/// Largest payload kept inline, so that a leaf always fits several rows
+pub fn max_inline_payload(page_size: usize) -> usize {
+    leaf_capacity(page_size) / 4 - CELL_HEADER
+}
+
+/// Most children an interior page can hold
+pub fn interior_fanout(page_size: usize) -> usize {
+    (page_size - TREE_HEADER) / INTERIOR_ENTRY
+}
+
+/// Payload bytes carried by each overflow page
+pub fn overflow_chunk_size(page_size: usize) -> usize {
+    page_size - 4
+}
+
+/// Overflow page: the next page of the chain (0 ends it), then a chunk of
+/// the payload
+pub fn encode_overflow(next: Option<PageId>, chunk: &[u8]) -> PageData {
+    let mut bytes = Vec::with_capacity(4 + chunk.len());
+    bytes.extend_from_slice(&next.map_or(0, |page| page.0).to_le_bytes());
+    bytes.extend_from_slice(chunk);
+    PageData::new(bytes, PageType::Overflow)
+}
+
+pub fn decode_overflow(page_id: PageId, page: &PageData) -> SqlResult<(Option<PageId>, &[u8])> {
+    if page.page_type != PageType::Overflow {
+        return Err(corrupted(page_id, "expected an overflow page"));
+    }
+    let mut reader = ByteReader::new(page_id, &page.data);
+    let next = reader.u32()?;
+    Ok(((next != 0).then_some(PageId(next)), &page.data[4..]))
+}
+
+/// Freelist page: the next free page (0 ends the list)
+pub fn encode_free(next: Option<PageId>) -> PageData {
+    PageData::new(next.map_or(0, |page| page.0).to_le_bytes().to_vec(), PageType::FreeList)
+}
+
+pub fn decode_free(page_id: PageId, page: &PageData) -> SqlResult<Option<PageId>> {
+    if page.page_type != PageType::FreeList {
+        return Err(corrupted(page_id, "expected a freelist page"));
+    }
+    let next = ByteReader::new(page_id, &page.data).u32()?;
+    Ok((next != 0).then_some(PageId(next)))
+}
+
+fn corrupted(page_id: PageId, detail: impl std::fmt::Display) -> SqlError {
+    SqlError::btree_error(format!("Corrupted page {}: {}", page_id, detail))
+}
+
+/// Bounds-checked little-endian reads from a page
+struct ByteReader<'a> {
+    page_id: PageId,
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(page_id: PageId, bytes: &'a [u8]) -> Self {
+        ByteReader { page_id, bytes, offset: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> SqlResult<&'a [u8]> {
+        let end = self.offset + len;
+        if end > self.bytes.len() {
+            return Err(corrupted(self.page_id, "truncated"));
+        }
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> SqlResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> SqlResult<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> SqlResult<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> SqlResult<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_pages_round_trip() {
+        let leaf = TreePage::Leaf(vec![
+            LeafCell {
+                rowid: RowId(1),
+                payload: CellPayload::Inline(b"alice".to_vec()),
+            },
+            LeafCell {
+                rowid: RowId(2),
+                payload: CellPayload::Overflow { len: 9000, first: PageId(5) },
+            },
+        ]);
+        let encoded = leaf.encode();
+        assert_eq!(encoded.page_type, PageType::Data);
+        assert_eq!(TreePage::decode(PageId(3), &encoded).unwrap(), leaf);
+
+        let interior = TreePage::Interior(vec![(RowId(1), PageId(3)), (RowId(40), PageId(4))]);
+        assert_eq!(TreePage::decode(PageId(6), &interior.encode()).unwrap(), interior);
+
+        let mut truncated = encoded;
+        truncated.data.truncate(10);
+        assert!(TreePage::decode(PageId(3), &truncated).is_err());
+        assert!(TreePage::decode(PageId(7), &encode_free(None)).is_err());
+    }
+
+    #[test]
+    fn test_overflow_and_free_pages() {
+        let page = encode_overflow(Some(PageId(9)), b"chunk");
+        assert_eq!(decode_overflow(PageId(8), &page).unwrap(), (Some(PageId(9)), &b"chunk"[..]));
+        assert_eq!(decode_free(PageId(2), &encode_free(Some(PageId(4)))).unwrap(), Some(PageId(4)));
+        assert_eq!(decode_free(PageId(2), &encode_free(None)).unwrap(), None);
+        assert!(max_inline_payload(4096) * 4 < leaf_capacity(4096));
+    }
+}
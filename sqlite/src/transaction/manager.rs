@@ -1,6 +1,8 @@
 use crate::error::{SqlError, SqlResult};
 use crate::transaction::{Transaction, TransactionConfig, TransactionState, TransactionStats, IsolationLevel};
+use crate::transaction::mvcc::{MvccStore, MvccTransaction};
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// Transaction manager
@@ -9,6 +11,8 @@ pub struct TransactionManager {
     active_transactions: HashMap<Uuid, Transaction>,
     config: TransactionConfig,
     stats: TransactionStats,
+    /// Row versions read and written by snapshot transactions
+    mvcc: Arc<MvccStore>,
 }
 
 impl TransactionManager {
@@ -17,9 +21,21 @@ impl TransactionManager {
             active_transactions: HashMap::new(),
             config,
             stats: TransactionStats::default(),
+            mvcc: Arc::new(MvccStore::new()),
         }
     }
 
+    /// Versioned row store; it locks internally, so snapshot transactions
+    /// run concurrently without holding the manager
+    pub fn mvcc(&self) -> Arc<MvccStore> {
+        self.mvcc.clone()
+    }
+
+    /// Start a snapshot transaction at the configured isolation level
+    pub fn begin_snapshot(&self) -> MvccTransaction {
+        self.mvcc.begin(self.config.default_isolation_level)
+    }
+
     pub async fn begin_transaction(&mut self) -> SqlResult<Transaction> {
         if self.active_transactions.len() >= self.config.max_active_transactions {
             return Err(SqlError::transaction_error("Too many active transactions"));
@@ -69,6 +85,9 @@ impl TransactionManager {
             self.rollback_transaction(id).await?;
         }
 
+        // Row versions no open snapshot can see any more
+        self.mvcc.collect_garbage();
+
         Ok(())
     }
 
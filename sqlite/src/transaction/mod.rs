@@ -1,9 +1,11 @@
 pub mod manager;
 pub mod wal;
 pub mod command;
+pub mod mvcc;
 
 pub use manager::{TransactionManager, SqlTransaction};
-pub use wal::{WalCoalgebra, WalEntry};
+pub use wal::{WalCoalgebra, WalEntry, WalFile};
+pub use mvcc::{MvccStore, MvccTransaction, RowVersion, TableChanges, Timestamp};
 pub use command::SqlCommand;
 
 use crate::error::{SqlError, SqlResult};
@@ -0,0 +1,822 @@
+use crate::error::{SqlError, SqlResult};
+use crate::transaction::IsolationLevel;
+use crate::types::{Row, RowId};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Logical commit time; every committed write transaction gets the next one
+pub type Timestamp = u64;
+
+/// One committed state of a row; `row` is `None` once the row was deleted
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowVersion {
+    pub committed_at: Timestamp,
+    pub row: Option<Row>,
+}
+
+/// Multi-version row store
+///
+/// Every row keeps a chain of committed versions, oldest first. A
+/// transaction reads the newest version committed at or before its
+/// snapshot, so readers never wait for writers. Writes are buffered in the
+/// [`MvccTransaction`] and installed as new versions when it commits.
+///
+/// The store's lock is only held for the duration of a single operation,
+/// never for the lifetime of a transaction.
+#[derive(Debug, Default)]
+pub struct MvccStore {
+    state: Mutex<MvccState>,
+}
+
+#[derive(Debug, Default)]
+struct MvccState {
+    /// Timestamp of the last commit
+    clock: Timestamp,
+    tables: HashMap<String, VersionedTable>,
+    /// Start timestamp of every open transaction
+    active: HashMap<Uuid, Timestamp>,
+}
+
+#[derive(Debug, Default)]
+struct VersionedTable {
+    rows: BTreeMap<RowId, Vec<RowVersion>>,
+    /// Rowids of the latest rows in table order: the order they were
+    /// added in, or were last given to [`MvccStore::sync_table`] in
+    order: Arc<Vec<RowId>>,
+    next_rowid: u64,
+    /// Last time any row of the table changed, for scan conflicts
+    modified_at: Timestamp,
+}
+
+impl VersionedTable {
+    fn visible(&self, rowid: RowId, snapshot: Timestamp) -> Option<&Row> {
+        visible_version(self.rows.get(&rowid)?, snapshot)
+    }
+
+    fn fresh_rowid(&mut self) -> RowId {
+        self.next_rowid += 1;
+        RowId(self.next_rowid)
+    }
+}
+
+/// How a commit changes a table, by position in table order before it:
+/// rows are replaced, then rows removed, then rows added at the end
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableChanges {
+    /// New rows by position, ascending
+    pub updated: Vec<(usize, Row)>,
+    /// Positions, ascending
+    pub deleted: Vec<usize>,
+    pub inserted: Vec<Row>,
+}
+
+impl TableChanges {
+    pub fn is_empty(&self) -> bool {
+        self.updated.is_empty() && self.deleted.is_empty() && self.inserted.is_empty()
+    }
+}
+
+/// Positions of `rowids` in `order`, `None` for one that is not there.
+/// The order is usually sorted, as rows are added with increasing
+/// rowids, so each is looked for by binary search first.
+fn positions_of(order: &[RowId], rowids: &[RowId]) -> Vec<Option<usize>> {
+    let mut index: Option<HashMap<RowId, usize>> = None;
+    rowids
+        .iter()
+        .map(|rowid| match order.binary_search(rowid) {
+            Ok(position) if order[position] == *rowid => Some(position),
+            _ => index
+                .get_or_insert_with(|| order.iter().enumerate().map(|(position, rowid)| (*rowid, position)).collect())
+                .get(rowid)
+                .copied(),
+        })
+        .collect()
+}
+
+/// Drop the entries at `positions` (ascending)
+fn remove_positions<T>(items: &mut Vec<T>, positions: &[usize]) {
+    let mut position = 0;
+    items.retain(|_| {
+        let kept = positions.binary_search(&position).is_err();
+        position += 1;
+        kept
+    });
+}
+
+fn visible_version(versions: &[RowVersion], snapshot: Timestamp) -> Option<&Row> {
+    versions
+        .iter()
+        .rev()
+        .find(|version| version.committed_at <= snapshot)
+        .and_then(|version| version.row.as_ref())
+}
+
+/// A transaction's view of an [`MvccStore`] and its pending writes
+#[derive(Debug)]
+pub struct MvccTransaction {
+    pub id: Uuid,
+    pub isolation_level: IsolationLevel,
+    start: Timestamp,
+    writes: HashMap<String, BTreeMap<RowId, Option<Row>>>,
+    /// Rowids of the rows the transaction sees, in table order
+    order: HashMap<String, Arc<Vec<RowId>>>,
+    /// Rows read, checked for conflicts under Serializable
+    reads: HashSet<(String, RowId)>,
+    /// Tables scanned, so rows inserted by others count as conflicts
+    scans: HashSet<String>,
+}
+
+impl MvccTransaction {
+    /// Timestamp of the snapshot taken when the transaction began
+    pub fn start_timestamp(&self) -> Timestamp {
+        self.start
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.writes.values().all(BTreeMap::is_empty)
+    }
+
+    /// Tables the transaction inserted, updated or deleted rows of
+    pub fn written_tables(&self) -> impl Iterator<Item = &str> {
+        self.writes
+            .iter()
+            .filter(|(_, writes)| !writes.is_empty())
+            .map(|(table, _)| table.as_str())
+    }
+
+    /// Rowid of the row at `position` in table order
+    pub fn rowid_at(&self, table: &str, position: usize) -> Option<RowId> {
+        self.order.get(table)?.get(position).copied()
+    }
+
+    /// Note that the transaction read all of a table, so under
+    /// Serializable any commit to it since it began is a conflict
+    pub fn record_scan(&mut self, table: &str) {
+        self.scans.insert(table.to_string());
+    }
+
+    fn pending(&self, table: &str, rowid: RowId) -> Option<&Option<Row>> {
+        self.writes.get(table)?.get(&rowid)
+    }
+
+    fn order_mut(&mut self, table: &str) -> &mut Vec<RowId> {
+        Arc::make_mut(self.order.entry(table.to_string()).or_default())
+    }
+}
+
+/// Match the rows of a table as they are against the rows it should
+/// hold: equal rows keep their rowid, the rest become updates in order,
+/// then deletions (`None`) or rows inserted with a `fresh` rowid. Returns
+/// the rowids of `rows` in order and the writes.
+fn diff_rows(
+    mut current: BTreeMap<RowId, Row>,
+    rows: Vec<Row>,
+    mut fresh: impl FnMut() -> RowId,
+) -> (Vec<RowId>, Vec<(RowId, Option<Row>)>) {
+    let key = |row: &Row| format!("{:?}", row.values);
+    let mut unmatched: HashMap<String, Vec<RowId>> = HashMap::new();
+    for (rowid, row) in current.iter().rev() {
+        unmatched.entry(key(row)).or_default().push(*rowid);
+    }
+    let mut matched = Vec::with_capacity(rows.len());
+    for row in rows {
+        match unmatched.get_mut(&key(&row)).and_then(Vec::pop) {
+            Some(rowid) => {
+                current.remove(&rowid);
+                matched.push((Some(rowid), row));
+            }
+            None => matched.push((None, row)),
+        }
+    }
+    let mut removed = current.into_keys();
+    let mut order = Vec::with_capacity(matched.len());
+    let mut writes = Vec::new();
+    for (rowid, row) in matched {
+        let rowid = match rowid {
+            Some(rowid) => rowid,
+            None => {
+                let rowid = removed.next().unwrap_or_else(&mut fresh);
+                writes.push((rowid, Some(row)));
+                rowid
+            }
+        };
+        order.push(rowid);
+    }
+    writes.extend(removed.map(|rowid| (rowid, None)));
+    (order, writes)
+}
+
+impl MvccStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timestamp of the last commit
+    pub fn current_timestamp(&self) -> Timestamp {
+        self.state.lock().unwrap().clock
+    }
+
+    /// Number of open transactions
+    pub fn active_transactions(&self) -> usize {
+        self.state.lock().unwrap().active.len()
+    }
+
+    /// Install committed rows for a table, e.g. when loading it from disk;
+    /// they are visible to every transaction
+    pub fn load_table(&self, table: impl Into<String>, rows: Vec<Row>) {
+        let mut state = self.state.lock().unwrap();
+        let committed_at = state.clock;
+        let versioned = state.tables.entry(table.into()).or_default();
+        for row in rows {
+            let rowid = versioned.fresh_rowid();
+            versioned.rows.insert(rowid, vec![RowVersion { committed_at, row: Some(row) }]);
+            Arc::make_mut(&mut versioned.order).push(rowid);
+        }
+    }
+
+    /// Commit `rows`, in table order, as the contents of a table, for
+    /// writes made outside any [`MvccTransaction`]: rows still there keep
+    /// their versions, and the others are updated, deleted or inserted in
+    /// one new commit
+    pub fn sync_table(&self, table: &str, rows: Vec<Row>) {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let current = state
+            .tables
+            .get(table)
+            .map(|versioned| Self::visible_rows(versioned, state.clock))
+            .unwrap_or_default();
+        let versioned = state.tables.entry(table.to_string()).or_default();
+        let (order, writes) = diff_rows(current, rows, || versioned.fresh_rowid());
+        versioned.order = Arc::new(order);
+        if writes.is_empty() {
+            return;
+        }
+        state.clock += 1;
+        let committed_at = state.clock;
+        let versioned = state.tables.get_mut(table).expect("table entry was just made");
+        versioned.modified_at = committed_at;
+        for (rowid, row) in writes {
+            versioned.rows.entry(rowid).or_default().push(RowVersion { committed_at, row });
+        }
+    }
+
+    /// Commit changes made outside any [`MvccTransaction`], given by
+    /// position in table order and applied one table after another, as
+    /// one new commit
+    pub fn commit_changes(&self, changes: Vec<(String, TableChanges)>) {
+        if changes.iter().all(|(_, changes)| changes.is_empty()) {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let committed_at = state.clock;
+        for (table, changes) in changes {
+            let versioned = state.tables.entry(table).or_default();
+            versioned.modified_at = committed_at;
+            let mut order = std::mem::take(Arc::make_mut(&mut versioned.order));
+            let mut writes = Vec::new();
+            for (position, row) in changes.updated {
+                writes.extend(order.get(position).map(|rowid| (*rowid, Some(row))));
+            }
+            writes.extend(changes.deleted.iter().filter_map(|position| Some((*order.get(*position)?, None))));
+            remove_positions(&mut order, &changes.deleted);
+            for row in changes.inserted {
+                let rowid = versioned.fresh_rowid();
+                order.push(rowid);
+                writes.push((rowid, Some(row)));
+            }
+            versioned.order = Arc::new(order);
+            for (rowid, row) in writes {
+                versioned.rows.entry(rowid).or_default().push(RowVersion { committed_at, row });
+            }
+        }
+    }
+
+    /// The rows of a table as of the last commit, in table order
+    pub fn latest_rows(&self, table: &str) -> Vec<Row> {
+        let state = self.state.lock().unwrap();
+        let Some(versioned) = state.tables.get(table) else {
+            return Vec::new();
+        };
+        versioned
+            .order
+            .iter()
+            .filter_map(|rowid| versioned.visible(*rowid, state.clock).cloned())
+            .collect()
+    }
+
+    fn visible_rows(versioned: &VersionedTable, snapshot: Timestamp) -> BTreeMap<RowId, Row> {
+        versioned
+            .rows
+            .iter()
+            .filter_map(|(rowid, versions)| Some((*rowid, visible_version(versions, snapshot)?.clone())))
+            .collect()
+    }
+
+    pub fn begin(&self, isolation_level: IsolationLevel) -> MvccTransaction {
+        let mut state = self.state.lock().unwrap();
+        let transaction = MvccTransaction {
+            id: Uuid::new_v4(),
+            isolation_level,
+            start: state.clock,
+            writes: HashMap::new(),
+            order: state
+                .tables
+                .iter()
+                .map(|(table, versioned)| (table.clone(), versioned.order.clone()))
+                .collect(),
+            reads: HashSet::new(),
+            scans: HashSet::new(),
+        };
+        state.active.insert(transaction.id, transaction.start);
+        transaction
+    }
+
+    /// Snapshot a read sees: fixed at begin for RepeatableRead and
+    /// Serializable, the latest commit for the weaker levels. Uncommitted
+    /// writes of other transactions are never visible, so ReadUncommitted
+    /// behaves like ReadCommitted.
+    fn snapshot(state: &MvccState, transaction: &MvccTransaction) -> Timestamp {
+        match transaction.isolation_level {
+            IsolationLevel::ReadUncommitted | IsolationLevel::ReadCommitted => state.clock,
+            IsolationLevel::RepeatableRead | IsolationLevel::Serializable => transaction.start,
+        }
+    }
+
+    /// Read one row, seeing the transaction's own writes
+    pub fn get(&self, transaction: &mut MvccTransaction, table: &str, rowid: RowId) -> Option<Row> {
+        if let Some(pending) = transaction.pending(table, rowid) {
+            return pending.clone();
+        }
+        transaction.reads.insert((table.to_string(), rowid));
+        let state = self.state.lock().unwrap();
+        let snapshot = Self::snapshot(&state, transaction);
+        state.tables.get(table)?.visible(rowid, snapshot).cloned()
+    }
+
+    /// Every row of a table visible to the transaction, in rowid order
+    pub fn scan(&self, transaction: &mut MvccTransaction, table: &str) -> Vec<(RowId, Row)> {
+        transaction.scans.insert(table.to_string());
+        let mut rows: BTreeMap<RowId, Row> = {
+            let state = self.state.lock().unwrap();
+            let snapshot = Self::snapshot(&state, transaction);
+            state
+                .tables
+                .get(table)
+                .map(|versioned| Self::visible_rows(versioned, snapshot))
+                .unwrap_or_default()
+        };
+        if let Some(writes) = transaction.writes.get(table) {
+            for (rowid, row) in writes {
+                match row {
+                    Some(row) => rows.insert(*rowid, row.clone()),
+                    None => rows.remove(rowid),
+                };
+            }
+        }
+        rows.into_iter().collect()
+    }
+
+    /// Add a row at the end of the table; it gets a fresh rowid that no
+    /// other transaction uses
+    pub fn insert(&self, transaction: &mut MvccTransaction, table: &str, row: Row) -> RowId {
+        let rowid = self.state.lock().unwrap().tables.entry(table.to_string()).or_default().fresh_rowid();
+        transaction.writes.entry(table.to_string()).or_default().insert(rowid, Some(row));
+        transaction.order_mut(table).push(rowid);
+        rowid
+    }
+
+    /// Replace a row visible to the transaction
+    pub fn update(&self, transaction: &mut MvccTransaction, table: &str, rowid: RowId, row: Row) -> SqlResult<()> {
+        if self.get(transaction, table, rowid).is_none() {
+            return Err(SqlError::runtime_error(format!("No row {} in table {}", rowid, table)));
+        }
+        transaction.writes.entry(table.to_string()).or_default().insert(rowid, Some(row));
+        Ok(())
+    }
+
+    /// Delete a row visible to the transaction
+    pub fn delete(&self, transaction: &mut MvccTransaction, table: &str, rowid: RowId) -> SqlResult<()> {
+        if self.get(transaction, table, rowid).is_none() {
+            return Err(SqlError::runtime_error(format!("No row {} in table {}", rowid, table)));
+        }
+        transaction.writes.entry(table.to_string()).or_default().insert(rowid, None);
+        let order = transaction.order_mut(table);
+        if let [Some(position)] = positions_of(order, &[rowid])[..] {
+            order.remove(position);
+        }
+        Ok(())
+    }
+
+    /// Replace the row at `position` in table order
+    pub fn update_at(&self, transaction: &mut MvccTransaction, table: &str, position: usize, row: Row) -> SqlResult<()> {
+        let rowid = transaction
+            .rowid_at(table, position)
+            .ok_or_else(|| SqlError::runtime_error(format!("No row at {} in table {}", position, table)))?;
+        self.update(transaction, table, rowid, row)
+    }
+
+    /// Delete the rows at `positions` (ascending) in table order
+    pub fn delete_at(&self, transaction: &mut MvccTransaction, table: &str, positions: &[usize]) -> SqlResult<()> {
+        for &position in positions {
+            let rowid = transaction
+                .rowid_at(table, position)
+                .ok_or_else(|| SqlError::runtime_error(format!("No row at {} in table {}", position, table)))?;
+            if self.get(transaction, table, rowid).is_none() {
+                return Err(SqlError::runtime_error(format!("No row {} in table {}", rowid, table)));
+            }
+            transaction.writes.entry(table.to_string()).or_default().insert(rowid, None);
+        }
+        remove_positions(transaction.order_mut(table), positions);
+        Ok(())
+    }
+
+    /// Make the table hold `rows`, in table order, for the transaction,
+    /// buffering the updates, deletions and inserts that turn the rows it
+    /// sees into them
+    pub fn write_table(&self, transaction: &mut MvccTransaction, table: &str, rows: Vec<Row>) {
+        let current = self.scan(transaction, table).into_iter().collect();
+        let (order, writes) = {
+            let mut state = self.state.lock().unwrap();
+            let versioned = state.tables.entry(table.to_string()).or_default();
+            diff_rows(current, rows, || versioned.fresh_rowid())
+        };
+        transaction.writes.entry(table.to_string()).or_default().extend(writes);
+        transaction.order.insert(table.to_string(), Arc::new(order));
+    }
+
+    /// Install the transaction's writes as new versions
+    ///
+    /// Conflicts are settled first-committer-wins: the commit fails if a
+    /// row it wrote was committed by someone else since it began (under
+    /// RepeatableRead and Serializable), or, under Serializable, if
+    /// anything it read or scanned was. Returns the commit timestamp; a
+    /// read-only transaction commits at its snapshot.
+    pub fn commit(&self, transaction: MvccTransaction) -> SqlResult<Timestamp> {
+        let mut state = self.state.lock().unwrap();
+        state.active.remove(&transaction.id);
+        Self::check_conflicts(&state, &transaction)?;
+        if transaction.is_read_only() {
+            return Ok(Self::snapshot(&state, &transaction));
+        }
+
+        state.clock += 1;
+        let committed_at = state.clock;
+        for (table, writes) in transaction.writes {
+            let versioned = state.tables.entry(table).or_default();
+            versioned.modified_at = committed_at;
+            let mut deleted = HashSet::new();
+            let mut inserted = Vec::new();
+            for (rowid, row) in writes {
+                let chain = versioned.rows.entry(rowid).or_default();
+                match (&row, chain.is_empty()) {
+                    (Some(_), true) => inserted.push(rowid),
+                    (None, false) => {
+                        deleted.insert(rowid);
+                    }
+                    _ => {}
+                }
+                chain.push(RowVersion { committed_at, row });
+            }
+            if !deleted.is_empty() || !inserted.is_empty() {
+                let order = Arc::make_mut(&mut versioned.order);
+                if !deleted.is_empty() {
+                    order.retain(|rowid| !deleted.contains(rowid));
+                }
+                order.extend(inserted);
+            }
+        }
+        Ok(committed_at)
+    }
+
+    /// Check that the transaction can commit, as [`commit`](Self::commit)
+    /// does, and say how committing it will change each table it wrote.
+    /// Rows it inserted go at the end, in the order it inserted them.
+    pub fn prepare_commit(&self, transaction: &MvccTransaction) -> SqlResult<Vec<(String, TableChanges)>> {
+        let state = self.state.lock().unwrap();
+        Self::check_conflicts(&state, transaction)?;
+        let mut tables = Vec::new();
+        for (table, writes) in &transaction.writes {
+            let versioned = state.tables.get(table);
+            let committed = |rowid: &RowId| versioned.is_some_and(|versioned| versioned.rows.contains_key(rowid));
+            let (existing, inserted): (Vec<_>, Vec<_>) = writes.iter().partition(|(rowid, _)| committed(rowid));
+            let order = versioned.map(|versioned| versioned.order.as_slice()).unwrap_or_default();
+            let rowids: Vec<RowId> = existing.iter().map(|(rowid, _)| **rowid).collect();
+            let mut changes = TableChanges::default();
+            for ((rowid, row), position) in existing.into_iter().zip(positions_of(order, &rowids)) {
+                let Some(position) = position else {
+                    return Err(SqlError::serialization_failure(format!(
+                        "Could not serialize transaction {}: row {} of {} was deleted concurrently",
+                        transaction.id, rowid, table
+                    )));
+                };
+                match row {
+                    Some(row) => changes.updated.push((position, row.clone())),
+                    None => changes.deleted.push(position),
+                }
+            }
+            changes.updated.sort_by_key(|(position, _)| *position);
+            changes.deleted.sort_unstable();
+            // Rows it inserted and deleted again were never there
+            changes.inserted = inserted.into_iter().filter_map(|(_, row)| row.clone()).collect();
+            if !changes.is_empty() {
+                tables.push((table.clone(), changes));
+            }
+        }
+        Ok(tables)
+    }
+
+    fn check_conflicts(state: &MvccState, transaction: &MvccTransaction) -> SqlResult<()> {
+        match Self::find_conflict(state, transaction) {
+            Some(conflict) => Err(SqlError::serialization_failure(format!(
+                "Could not serialize transaction {}: {}",
+                transaction.id, conflict
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn find_conflict(state: &MvccState, transaction: &MvccTransaction) -> Option<String> {
+        let changed_since_start = |table: &str, rowid: RowId| {
+            state
+                .tables
+                .get(table)
+                .and_then(|versioned| versioned.rows.get(&rowid))
+                .and_then(|versions| versions.last())
+                .is_some_and(|version| version.committed_at > transaction.start)
+        };
+
+        if matches!(
+            transaction.isolation_level,
+            IsolationLevel::RepeatableRead | IsolationLevel::Serializable
+        ) {
+            for (table, writes) in &transaction.writes {
+                if let Some(rowid) = writes.keys().find(|rowid| changed_since_start(table, **rowid)) {
+                    return Some(format!("row {} of {} was modified concurrently", rowid, table));
+                }
+            }
+        }
+
+        if transaction.isolation_level == IsolationLevel::Serializable {
+            if let Some((table, rowid)) = transaction.reads.iter().find(|(table, rowid)| changed_since_start(table, *rowid)) {
+                return Some(format!("row {} of {} changed after it was read", rowid, table));
+            }
+            if let Some(table) = transaction.scans.iter().find(|table| {
+                state
+                    .tables
+                    .get(table.as_str())
+                    .is_some_and(|versioned| versioned.modified_at > transaction.start)
+            }) {
+                return Some(format!("table {} changed after it was scanned", table));
+            }
+        }
+        None
+    }
+
+    /// Discard the transaction's writes
+    pub fn rollback(&self, transaction: MvccTransaction) {
+        self.state.lock().unwrap().active.remove(&transaction.id);
+    }
+
+    /// Drop versions no open or future transaction can see: a version
+    /// survives only while it is the newest one at or before some active
+    /// snapshot (or the newest of all), and rows whose only survivor is a
+    /// deletion go away entirely. Returns the number of versions removed.
+    pub fn collect_garbage(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let mut snapshots: Vec<Timestamp> = state.active.values().copied().collect();
+        snapshots.push(state.clock);
+        let mut removed = 0;
+        for versioned in state.tables.values_mut() {
+            versioned.rows.retain(|_, versions| {
+                let needed: HashSet<usize> = snapshots
+                    .iter()
+                    .filter_map(|snapshot| versions.iter().rposition(|version| version.committed_at <= *snapshot))
+                    .collect();
+                let before = versions.len();
+                let mut index = 0;
+                versions.retain(|_| {
+                    index += 1;
+                    needed.contains(&(index - 1))
+                });
+                removed += before - versions.len();
+
+                let deleted = matches!(versions.as_slice(), [version] if version.row.is_none());
+                if deleted {
+                    removed += 1;
+                }
+                !versions.is_empty() && !deleted
+            });
+        }
+        removed
+    }
+
+    /// Number of versions stored for every row of a table
+    pub fn version_count(&self, table: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .tables
+            .get(table)
+            .map_or(0, |versioned| versioned.rows.values().map(Vec::len).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Value;
+    use std::sync::Arc;
+
+    fn row(value: i64) -> Row {
+        Row::new(vec![Value::Integer(value)])
+    }
+
+    fn values(store: &MvccStore, transaction: &mut MvccTransaction) -> Vec<Row> {
+        store.scan(transaction, "t").into_iter().map(|(_, row)| row).collect()
+    }
+
+    #[test]
+    fn test_snapshot_depends_on_isolation_level() {
+        let store = MvccStore::new();
+        store.load_table("t", vec![row(1)]);
+
+        let mut repeatable = store.begin(IsolationLevel::RepeatableRead);
+        let mut read_committed = store.begin(IsolationLevel::ReadCommitted);
+        assert_eq!(values(&store, &mut repeatable), vec![row(1)]);
+
+        let mut writer = store.begin(IsolationLevel::ReadCommitted);
+        store.update(&mut writer, "t", RowId(1), row(2)).unwrap();
+        store.insert(&mut writer, "t", row(3));
+        // Uncommitted writes are only visible to their own transaction
+        assert_eq!(values(&store, &mut writer), vec![row(2), row(3)]);
+        assert_eq!(values(&store, &mut read_committed), vec![row(1)]);
+        store.commit(writer).unwrap();
+
+        assert_eq!(values(&store, &mut repeatable), vec![row(1)]);
+        assert_eq!(values(&store, &mut read_committed), vec![row(2), row(3)]);
+        store.commit(repeatable).unwrap();
+        store.commit(read_committed).unwrap();
+    }
+
+    #[test]
+    fn test_first_committer_wins() {
+        let store = MvccStore::new();
+        store.load_table("t", vec![row(100)]);
+
+        // Lost update: both read the row, both write it
+        let mut first = store.begin(IsolationLevel::RepeatableRead);
+        let mut second = store.begin(IsolationLevel::RepeatableRead);
+        store.update(&mut first, "t", RowId(1), row(90)).unwrap();
+        store.update(&mut second, "t", RowId(1), row(80)).unwrap();
+        store.commit(first).unwrap();
+        assert!(store.commit(second).is_err());
+
+        // Write skew: disjoint writes based on an overlapping read, only
+        // rejected under Serializable
+        store.load_table("t", vec![row(0)]);
+        for (isolation_level, expect_conflict) in [
+            (IsolationLevel::RepeatableRead, false),
+            (IsolationLevel::Serializable, true),
+        ] {
+            let mut first = store.begin(isolation_level);
+            let mut second = store.begin(isolation_level);
+            let total: i64 = [&mut first, &mut second]
+                .map(|transaction| values(&store, transaction).len() as i64)
+                .iter()
+                .sum();
+            store.update(&mut first, "t", RowId(1), row(total)).unwrap();
+            store.update(&mut second, "t", RowId(2), row(total)).unwrap();
+            store.commit(first).unwrap();
+            assert_eq!(store.commit(second).is_err(), expect_conflict);
+        }
+        assert_eq!(store.active_transactions(), 0);
+    }
+
+    #[test]
+    fn test_whole_table_writes() {
+        let store = MvccStore::new();
+        store.sync_table("t", vec![row(1), row(2), row(3)]);
+        let mut reader = store.begin(IsolationLevel::RepeatableRead);
+
+        // Rows still there keep their versions; a changed one is updated
+        store.sync_table("t", vec![row(1), row(20), row(3), row(4)]);
+        assert_eq!(store.latest_rows("t"), vec![row(1), row(20), row(3), row(4)]);
+        assert_eq!(store.version_count("t"), 5);
+        assert_eq!(values(&store, &mut reader), vec![row(1), row(2), row(3)]);
+
+        let mut writer = store.begin(IsolationLevel::RepeatableRead);
+        store.write_table(&mut writer, "t", vec![row(1), row(3)]);
+        assert_eq!(values(&store, &mut writer), vec![row(1), row(3)]);
+        assert_eq!(writer.written_tables().collect::<Vec<_>>(), vec!["t"]);
+        // A row it deleted was changed by a write outside the store
+        store.sync_table("t", vec![row(1), row(20), row(3), row(40)]);
+        assert!(store.commit(writer).unwrap_err().is_serialization_failure());
+        store.commit(reader).unwrap();
+    }
+
+    #[test]
+    fn test_writes_by_position() {
+        let store = MvccStore::new();
+        store.sync_table("t", vec![row(1), row(2), row(3)]);
+        store.commit_changes(vec![(
+            "t".to_string(),
+            TableChanges {
+                updated: vec![(1, row(20))],
+                deleted: vec![0],
+                inserted: vec![row(4)],
+            },
+        )]);
+        assert_eq!(store.latest_rows("t"), vec![row(20), row(3), row(4)]);
+
+        // Positions count the rows the transaction sees, its own included
+        let mut writer = store.begin(IsolationLevel::RepeatableRead);
+        store.insert(&mut writer, "t", row(5));
+        store.update_at(&mut writer, "t", 3, row(50)).unwrap();
+        store.update_at(&mut writer, "t", 1, row(30)).unwrap();
+        store.delete_at(&mut writer, "t", &[0]).unwrap();
+        assert_eq!(writer.rowid_at("t", 2), Some(RowId(5)));
+        let appended = TableChanges {
+            inserted: vec![row(6)],
+            ..Default::default()
+        };
+        store.commit_changes(vec![("t".to_string(), appended)]);
+
+        // Committing changes the rows by their positions as of now
+        let expected = TableChanges {
+            updated: vec![(1, row(30))],
+            deleted: vec![0],
+            inserted: vec![row(50)],
+        };
+        assert_eq!(store.prepare_commit(&writer).unwrap(), vec![("t".to_string(), expected)]);
+        store.commit(writer).unwrap();
+        assert_eq!(store.latest_rows("t"), vec![row(30), row(4), row(6), row(50)]);
+    }
+
+    #[test]
+    fn test_rollback_and_garbage_collection() {
+        let store = MvccStore::new();
+        store.load_table("t", vec![row(1), row(2)]);
+
+        let mut discarded = store.begin(IsolationLevel::ReadCommitted);
+        store.delete(&mut discarded, "t", RowId(1)).unwrap();
+        store.rollback(discarded);
+
+        let mut reader = store.begin(IsolationLevel::RepeatableRead);
+        for value in 10..13 {
+            let mut writer = store.begin(IsolationLevel::ReadCommitted);
+            store.update(&mut writer, "t", RowId(1), row(value)).unwrap();
+            store.commit(writer).unwrap();
+        }
+        let mut writer = store.begin(IsolationLevel::ReadCommitted);
+        store.delete(&mut writer, "t", RowId(2)).unwrap();
+        store.commit(writer).unwrap();
+        assert_eq!(store.version_count("t"), 6);
+
+        // The reader's snapshot pins the versions it can still see; the
+        // intermediate updates are obsolete already
+        assert_eq!(store.collect_garbage(), 2);
+        assert_eq!(values(&store, &mut reader), vec![row(1), row(2)]);
+        store.commit(reader).unwrap();
+
+        assert_eq!(store.collect_garbage(), 3);
+        assert_eq!(store.version_count("t"), 1);
+        let mut latest = store.begin(IsolationLevel::Serializable);
+        assert_eq!(values(&store, &mut latest), vec![row(12)]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_increments_retry_on_conflict() {
+        let store = Arc::new(MvccStore::new());
+        store.load_table("t", vec![row(0)]);
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    for _ in 0..25 {
+                        loop {
+                            let mut transaction = store.begin(IsolationLevel::Serializable);
+                            let current = match store.get(&mut transaction, "t", RowId(1)).unwrap().values[0] {
+                                Value::Integer(value) => value,
+                                _ => unreachable!(),
+                            };
+                            tokio::task::yield_now().await;
+                            store.update(&mut transaction, "t", RowId(1), row(current + 1)).unwrap();
+                            if store.commit(transaction).is_ok() {
+                                break;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let mut transaction = store.begin(IsolationLevel::ReadCommitted);
+        assert_eq!(store.get(&mut transaction, "t", RowId(1)), Some(row(200)));
+    }
+}
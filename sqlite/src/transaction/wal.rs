@@ -1,7 +1,13 @@
 use crate::error::{SqlError, SqlResult};
+use crate::page_cache::{PageData, PageType};
 use crate::transaction::SqlCommand;
-use crate::types::DatabaseState;
+use crate::types::{DatabaseState, PageId};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 /// WAL entry for write-ahead logging
@@ -103,3 +109,240 @@ impl Default for WalCoalgebra {
         Self::new()
     }
 }
+
+/// Marks the start of a commit record in a WAL file ("WAL2"; records of
+/// the first format, without page LSNs, are not recognized)
+const WAL_RECORD_MAGIC: u32 = 0x5741_4C32;
+
+/// Size of the SHA-256 checksum closing each commit record
+const WAL_CHECKSUM_SIZE: usize = 32;
+
+/// Size of a frame header: page id, page type, data length, LSN
+const FRAME_HEADER: usize = 17;
+
+/// Write-ahead log file of page images
+///
+/// Every commit appends one record holding the new image of each page it
+/// wrote, followed by a checksum over the record. Only complete records
+/// with a matching checksum count as committed, so a crash part-way through
+/// an append leaves the previously committed state intact. An in-memory
+/// index maps each page to its latest committed frame so readers can find
+/// pages that have not been checkpointed into the database file yet.
+#[derive(Debug)]
+pub struct WalFile {
+    file: File,
+    path: PathBuf,
+    /// Offset of the newest committed frame of each page
+    index: BTreeMap<PageId, u64>,
+    /// Frames appended since the last reset
+    frames: usize,
+    /// End of the last committed record
+    len: u64,
+}
+
+impl WalFile {
+    /// Open (or create) the WAL file at `path`, indexing the committed
+    /// records it already holds and discarding a torn tail
+    pub fn open(path: impl AsRef<Path>) -> SqlResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| SqlError::wal_error(format!("Cannot open {}: {}", path.display(), e)))?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(wal_io_error)?;
+        let mut wal = WalFile {
+            file,
+            path,
+            index: BTreeMap::new(),
+            frames: 0,
+            len: 0,
+        };
+        while let Some((frames, next)) = parse_record(&bytes, wal.len as usize) {
+            wal.frames += frames.len();
+            wal.index.extend(frames);
+            wal.len = next as u64;
+        }
+        if wal.len < bytes.len() as u64 {
+            wal.file.set_len(wal.len).map_err(wal_io_error)?;
+        }
+        Ok(wal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Frames appended since the last reset
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    /// Whether no committed pages are waiting for a checkpoint
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Append one commit; with `sync` it is on disk when this returns,
+    /// otherwise it survives a process crash but not a power loss until the
+    /// next [`WalFile::sync`]
+    pub fn append_commit(&mut self, pages: &BTreeMap<PageId, PageData>, sync: bool) -> SqlResult<()> {
+        let mut record = Vec::new();
+        record.extend_from_slice(&WAL_RECORD_MAGIC.to_le_bytes());
+        record.extend_from_slice(&(pages.len() as u32).to_le_bytes());
+        let mut offsets = Vec::with_capacity(pages.len());
+        for (page_id, page) in pages {
+            offsets.push((*page_id, self.len + record.len() as u64));
+            record.extend_from_slice(&page_id.0.to_le_bytes());
+            record.push(page.page_type.as_byte());
+            record.extend_from_slice(&(page.data.len() as u32).to_le_bytes());
+            record.extend_from_slice(&page.lsn.to_le_bytes());
+            record.extend_from_slice(&page.data);
+        }
+        let checksum = Sha256::digest(&record);
+        record.extend_from_slice(&checksum);
+
+        self.file.seek(SeekFrom::Start(self.len)).map_err(wal_io_error)?;
+        self.file.write_all(&record).map_err(wal_io_error)?;
+        if sync {
+            self.sync()?;
+        }
+        self.len += record.len() as u64;
+        self.frames += pages.len();
+        self.index.extend(offsets);
+        Ok(())
+    }
+
+    /// Wait until every appended commit is on disk
+    pub fn sync(&mut self) -> SqlResult<()> {
+        self.file.sync_data().map_err(wal_io_error)
+    }
+
+    /// Newest committed image of a page, if the WAL holds one
+    pub fn read_page(&mut self, page_id: PageId) -> SqlResult<Option<PageData>> {
+        let offset = match self.index.get(&page_id) {
+            Some(offset) => *offset,
+            None => return Ok(None),
+        };
+        let mut header = [0u8; FRAME_HEADER];
+        self.file.seek(SeekFrom::Start(offset)).map_err(wal_io_error)?;
+        self.file.read_exact(&mut header).map_err(wal_io_error)?;
+        let page_type = PageType::from_byte(header[4])
+            .ok_or_else(|| SqlError::wal_error(format!("Corrupted WAL frame for page {}", page_id)))?;
+        let len = u32::from_le_bytes([header[5], header[6], header[7], header[8]]) as usize;
+        let mut lsn = [0u8; 8];
+        lsn.copy_from_slice(&header[9..]);
+        let mut data = vec![0u8; len];
+        self.file.read_exact(&mut data).map_err(wal_io_error)?;
+        Ok(Some(PageData::new(data, page_type).with_lsn(u64::from_le_bytes(lsn))))
+    }
+
+    /// Newest committed image of every page in the WAL
+    pub fn committed_pages(&mut self) -> SqlResult<BTreeMap<PageId, PageData>> {
+        let page_ids: Vec<PageId> = self.index.keys().copied().collect();
+        let mut pages = BTreeMap::new();
+        for page_id in page_ids {
+            if let Some(page) = self.read_page(page_id)? {
+                pages.insert(page_id, page);
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Discard every record once its pages are safely in the database file
+    pub fn reset(&mut self) -> SqlResult<()> {
+        self.file.set_len(0).map_err(wal_io_error)?;
+        self.file.sync_all().map_err(wal_io_error)?;
+        self.index.clear();
+        self.frames = 0;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+/// Parse the commit record starting at `offset` into the offsets of its
+/// frames; `None` if it is incomplete or fails its checksum
+fn parse_record(bytes: &[u8], offset: usize) -> Option<(Vec<(PageId, u64)>, usize)> {
+    let u32_at = |at: usize| -> Option<u32> {
+        let slice = bytes.get(at..at + 4)?;
+        Some(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+    };
+
+    if u32_at(offset)? != WAL_RECORD_MAGIC {
+        return None;
+    }
+    let count = u32_at(offset + 4)?;
+    let mut cursor = offset + 8;
+    let mut frames = Vec::new();
+    for _ in 0..count {
+        let page_id = PageId(u32_at(cursor)?);
+        PageType::from_byte(*bytes.get(cursor + 4)?)?;
+        let len = u32_at(cursor + 5)? as usize;
+        frames.push((page_id, cursor as u64));
+        cursor += FRAME_HEADER + len;
+    }
+
+    let checksum = bytes.get(cursor..cursor + WAL_CHECKSUM_SIZE)?;
+    if Sha256::digest(&bytes[offset..cursor]).as_slice() != checksum {
+        return None;
+    }
+    Some((frames, cursor + WAL_CHECKSUM_SIZE))
+}
+
+fn wal_io_error(error: std::io::Error) -> SqlError {
+    SqlError::wal_error(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(pages: &[(u32, &[u8])]) -> BTreeMap<PageId, PageData> {
+        pages
+            .iter()
+            .map(|(id, data)| (PageId(*id), PageData::new(data.to_vec(), PageType::Data)))
+            .collect()
+    }
+
+    #[test]
+    fn test_wal_file_ignores_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let mut wal = WalFile::open(&path).unwrap();
+        wal.append_commit(&commit(&[(1, b"one"), (2, b"two")]), true).unwrap();
+        wal.append_commit(&commit(&[(2, b"TWO")]), false).unwrap();
+        assert_eq!(wal.frame_count(), 3);
+        assert_eq!(wal.read_page(PageId(2)).unwrap().unwrap().data, b"TWO");
+
+        // A third commit that only partly reached the disk
+        let complete = std::fs::metadata(&path).unwrap().len();
+        wal.append_commit(&commit(&[(3, b"three")]), true).unwrap();
+        wal.file.set_len(complete + 10).unwrap();
+
+        let mut reopened = WalFile::open(&path).unwrap();
+        let pages = reopened.committed_pages().unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[&PageId(1)].data, b"one");
+        assert_eq!(pages[&PageId(2)].data, b"TWO");
+
+        // The torn tail was cut off, so new commits are readable after it
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), complete);
+        reopened.append_commit(&commit(&[(4, b"four")]), true).unwrap();
+        let mut reopened = WalFile::open(&path).unwrap();
+        assert_eq!(reopened.read_page(PageId(4)).unwrap().unwrap().data, b"four");
+
+        // A flipped byte invalidates the record it lands in and all after it
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(WalFile::open(&path).unwrap().is_empty());
+
+        reopened.reset().unwrap();
+        assert!(reopened.is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::error::{SqlError, SqlResult};
+use std::io::{BufRead, Write};
+
+/// One field of a CSV record
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub text: String,
+    /// Whether the field was in quotes: `""` is an empty string, while an
+    /// empty unquoted field is NULL
+    pub quoted: bool,
+}
+
+/// Reads CSV records one at a time, following RFC 4180: fields in double
+/// quotes may hold delimiters, line breaks and doubled quotes
+#[derive(Debug)]
+pub struct CsvReader<R> {
+    reader: R,
+    delimiter: char,
+    /// Line the last record started on, counting from 1
+    line: u64,
+    next_line: u64,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(reader: R, delimiter: char) -> Self {
+        CsvReader {
+            reader,
+            delimiter,
+            line: 0,
+            next_line: 1,
+        }
+    }
+
+    /// Line the last record read started on
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The next record; `None` at the end of the input. Blank lines are
+    /// skipped.
+    pub fn next_record(&mut self) -> SqlResult<Option<Vec<Field>>> {
+        let mut text = String::new();
+        loop {
+            self.line = self.next_line;
+            text.clear();
+            if !self.read_line(&mut text)? {
+                return Ok(None);
+            }
+            // A quoted field can go on over several lines
+            while unbalanced(&text) {
+                if !self.read_line(&mut text)? {
+                    return Err(SqlError::parse_error(format!(
+                        "line {}: unterminated quoted field",
+                        self.line
+                    )));
+                }
+            }
+            let record = text.trim_end_matches(['\n', '\r']);
+            if !record.is_empty() {
+                return self.split(record).map(Some);
+            }
+        }
+    }
+
+    fn read_line(&mut self, text: &mut String) -> SqlResult<bool> {
+        let read = self
+            .reader
+            .read_line(text)
+            .map_err(|error| SqlError::io_error(format!("line {}: {}", self.next_line, error)))?;
+        self.next_line += 1;
+        Ok(read > 0)
+    }
+
+    fn split(&self, record: &str) -> SqlResult<Vec<Field>> {
+        let mut fields = Vec::new();
+        let mut chars = record.chars().peekable();
+        loop {
+            let mut field = Field {
+                text: String::new(),
+                quoted: false,
+            };
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                field.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.text.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => field.text.push(c),
+                        None => {
+                            return Err(SqlError::parse_error(format!(
+                                "line {}: unterminated quoted field",
+                                self.line
+                            )))
+                        }
+                    }
+                }
+                match chars.peek() {
+                    None => {}
+                    Some(c) if *c == self.delimiter => {}
+                    Some(c) => {
+                        return Err(SqlError::parse_error(format!(
+                            "line {}: unexpected {:?} after a quoted field",
+                            self.line, c
+                        )))
+                    }
+                }
+            } else {
+                while let Some(c) = chars.peek().copied().filter(|c| *c != self.delimiter) {
+                    field.text.push(c);
+                    chars.next();
+                }
+            }
+            fields.push(field);
+            if chars.next().is_none() {
+                return Ok(fields);
+            }
+        }
+    }
+}
+
+/// Whether `text` ends inside a quoted field
+fn unbalanced(text: &str) -> bool {
+    text.chars().filter(|c| *c == '"').count() % 2 == 1
+}
+
+/// Writes CSV records, quoting fields only where needed
+#[derive(Debug)]
+pub struct CsvWriter<W> {
+    writer: W,
+    delimiter: char,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W, delimiter: char) -> Self {
+        CsvWriter { writer, delimiter }
+    }
+
+    /// Write one record; `None` fields (NULL) are left empty, while empty
+    /// strings are written as `""`
+    pub fn write_record<'a>(&mut self, fields: impl IntoIterator<Item = Option<&'a str>>) -> SqlResult<()> {
+        let mut line = String::new();
+        for (position, field) in fields.into_iter().enumerate() {
+            if position > 0 {
+                line.push(self.delimiter);
+            }
+            match field {
+                None => {}
+                Some(text) if self.needs_quotes(text) => {
+                    line.push('"');
+                    line.push_str(&text.replace('"', "\"\""));
+                    line.push('"');
+                }
+                Some(text) => line.push_str(text),
+            }
+        }
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .map_err(|error| SqlError::io_error(error.to_string()))
+    }
+
+    fn needs_quotes(&self, text: &str) -> bool {
+        text.is_empty()
+            || text.starts_with(' ')
+            || text.ends_with(' ')
+            || text.contains([self.delimiter, '"', '\n', '\r'])
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(record: Vec<Field>) -> Vec<(String, bool)> {
+        record.into_iter().map(|field| (field.text, field.quoted)).collect()
+    }
+
+    #[test]
+    fn test_read_and_write_round_trip() {
+        let input = "id;note\r\n1;\"a;b \"\"c\"\"\nnext line\"\n\n2;\n3;\"\"\n";
+        let mut reader = CsvReader::new(input.as_bytes(), ';');
+        let header = reader.next_record().unwrap().unwrap();
+        assert_eq!(texts(header), vec![("id".to_string(), false), ("note".to_string(), false)]);
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record[1].text, "a;b \"c\"\nnext line");
+        assert_eq!(reader.line(), 2);
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!((reader.line(), texts(record)[1].clone()), (5, (String::new(), false)));
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(texts(record)[1], (String::new(), true));
+        assert!(reader.next_record().unwrap().is_none());
+
+        let mut writer = CsvWriter::new(Vec::new(), ';');
+        writer.write_record([Some("1"), Some("a;b \"c\"\nnext line")]).unwrap();
+        writer.write_record([Some("2"), None, Some("")]).unwrap();
+        let written = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(written, "1;\"a;b \"\"c\"\"\nnext line\"\n2;;\"\"\n");
+
+        let mut reader = CsvReader::new("1,\"open\n".as_bytes(), ',');
+        assert!(reader.next_record().is_err());
+        let mut reader = CsvReader::new("\"a\"b\n".as_bytes(), ',');
+        assert!(reader.next_record().is_err());
+    }
+}
@@ -0,0 +1,857 @@
+//! CSV and JSON import and export
+//!
+//! An import reads its file one record at a time and inserts the records in
+//! batches, one INSERT per batch, all in a single transaction, so a bad
+//! record leaves the table as it was. Values
+//! are converted to the types the table declares; a missing table is
+//! created from the file's column names. An export writes the rows of a
+//! table as CSV, a JSON array of objects or JSON Lines.
+
+pub mod csv;
+
+pub use csv::{CsvReader, CsvWriter, Field};
+
+use crate::engine::{Session, TransactionStatus};
+use crate::error::{SqlError, SqlResult};
+use crate::query::{json, QueryResult};
+use crate::types::{DataType, Row, Value};
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// File format of an import or export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    /// A JSON array of objects, one per row
+    Json,
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl Format {
+    /// Format named on the command line: `csv`, `tsv`, `json` or `jsonl`
+    pub fn from_name(name: &str) -> SqlResult<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" | "tsv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "jsonl" | "ndjson" => Ok(Format::JsonLines),
+            other => Err(SqlError::parse_error(format!("Unknown file format '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferOptions {
+    pub format: Format,
+    /// Field delimiter of CSV files
+    pub delimiter: char,
+    /// Whether a CSV file starts with the column names; without them its
+    /// fields fill the table's columns in order
+    pub header: bool,
+    /// Rows between progress reports
+    pub progress_interval: u64,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        TransferOptions {
+            format: Format::Csv,
+            delimiter: ',',
+            header: true,
+            progress_interval: 10_000,
+        }
+    }
+}
+
+impl TransferOptions {
+    /// Options for a file, by its extension: `.csv`, `.tsv` (CSV with tabs),
+    /// `.json`, or `.jsonl`/`.ndjson`
+    pub fn for_path(path: &Path) -> SqlResult<Self> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        let format = Format::from_name(extension).map_err(|_| {
+            SqlError::parse_error(format!("Cannot tell the format of {}; give it explicitly", path.display()))
+        })?;
+        Ok(TransferOptions {
+            format,
+            delimiter: if extension.eq_ignore_ascii_case("tsv") { '\t' } else { ',' },
+            ..TransferOptions::default()
+        })
+    }
+}
+
+/// How far an import or export has got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub rows: u64,
+    /// Bytes read or written so far
+    pub bytes: u64,
+    /// Size of the file being imported, when known
+    pub total_bytes: Option<u64>,
+}
+
+/// Import a file into `table`; returns the number of rows inserted
+pub async fn import_file(
+    session: &mut Session,
+    path: &Path,
+    table: &str,
+    options: &TransferOptions,
+    progress: impl FnMut(&Progress),
+) -> SqlResult<u64> {
+    let file = File::open(path).map_err(|error| SqlError::io_error(format!("{}: {}", path.display(), error)))?;
+    let total_bytes = file.metadata().ok().map(|metadata| metadata.len());
+    import(session, file, total_bytes, table, options, progress).await
+}
+
+/// Import records read from `reader` into `table`, in the session's open
+/// transaction or else in one of its own
+pub async fn import(
+    session: &mut Session,
+    reader: impl Read,
+    total_bytes: Option<u64>,
+    table: &str,
+    options: &TransferOptions,
+    progress: impl FnMut(&Progress),
+) -> SqlResult<u64> {
+    let counter = Arc::new(AtomicU64::new(0));
+    let reader = BufReader::new(Counted {
+        inner: reader,
+        bytes: counter.clone(),
+    });
+    let interval = options.progress_interval.max(1);
+    let mut import = Import {
+        table,
+        columns: Vec::new(),
+        batch: Vec::new(),
+        batch_columns: Vec::new(),
+        batch_size: interval.min(BATCH_ROWS as u64) as usize,
+        first: 0,
+        last: 0,
+        progress: Progress {
+            total_bytes,
+            ..Progress::default()
+        },
+        interval,
+        bytes: counter,
+        report: progress,
+    };
+
+    let own_transaction = session.status() == TransactionStatus::Idle;
+    if own_transaction {
+        session.execute_sql("BEGIN").await?;
+    }
+    let imported = match options.format {
+        Format::Csv => import.csv(session, CsvReader::new(reader, options.delimiter), options.header).await,
+        Format::Json => import.json(session, JsonReader::array(reader)).await,
+        Format::JsonLines => import.json(session, JsonReader::lines(reader)).await,
+    };
+    if !own_transaction {
+        return imported;
+    }
+    match imported {
+        Ok(rows) => match session.execute_sql("COMMIT").await? {
+            QueryResult::Commit => Ok(rows),
+            _ => Err(SqlError::transaction_error("the import was rolled back")),
+        },
+        Err(error) => {
+            session.execute_sql("ROLLBACK").await?;
+            Err(error)
+        }
+    }
+}
+
+/// Write the rows of `table` to a file; returns the number of rows
+pub async fn export_file(
+    session: &mut Session,
+    table: &str,
+    path: &Path,
+    options: &TransferOptions,
+    progress: impl FnMut(&Progress),
+) -> SqlResult<u64> {
+    let file = File::create(path).map_err(|error| SqlError::io_error(format!("{}: {}", path.display(), error)))?;
+    let mut writer = BufWriter::new(file);
+    let rows = export(session, table, &mut writer, options, progress).await?;
+    writer.flush().map_err(|error| SqlError::io_error(error.to_string()))?;
+    Ok(rows)
+}
+
+/// Write the rows of `table` to `writer`
+pub async fn export(
+    session: &mut Session,
+    table: &str,
+    writer: impl Write,
+    options: &TransferOptions,
+    progress: impl FnMut(&Progress),
+) -> SqlResult<u64> {
+    if !is_identifier(table) {
+        return Err(SqlError::parse_error(format!("'{}' is not a table name", table)));
+    }
+    let QueryResult::Select { columns, rows } = session.execute_sql(&format!("SELECT * FROM {}", table)).await? else {
+        return Err(SqlError::runtime_error("SELECT returned no rows"));
+    };
+    write_rows(&columns, &rows, writer, options, progress)
+}
+
+/// Write a query's rows to `writer` in the format of `options`; returns the
+/// number of rows
+pub fn write_rows(
+    columns: &[String],
+    rows: &[Row],
+    writer: impl Write,
+    options: &TransferOptions,
+    mut progress: impl FnMut(&Progress),
+) -> SqlResult<u64> {
+    let counter = Arc::new(AtomicU64::new(0));
+    let mut writer = Counted {
+        inner: writer,
+        bytes: counter.clone(),
+    };
+    let mut report = Progress::default();
+    let interval = options.progress_interval.max(1);
+    let mut advance = |report: &mut Progress| {
+        report.rows += 1;
+        if report.rows.is_multiple_of(interval) {
+            report.bytes = counter.load(Ordering::Relaxed);
+            progress(report);
+        }
+    };
+    let io_error = |error: std::io::Error| SqlError::io_error(error.to_string());
+
+    match options.format {
+        Format::Csv => {
+            let mut csv = CsvWriter::new(&mut writer, options.delimiter);
+            if options.header {
+                csv.write_record(columns.iter().map(|column| Some(column.as_str())))?;
+            }
+            for row in rows {
+                let fields: Vec<Option<String>> = row.values.iter().map(text_of).collect();
+                csv.write_record(fields.iter().map(Option::as_deref))?;
+                advance(&mut report);
+            }
+        }
+        Format::Json | Format::JsonLines => {
+            let array = options.format == Format::Json;
+            if array {
+                writer.write_all(b"[").map_err(io_error)?;
+            }
+            for (position, row) in rows.iter().enumerate() {
+                let object: Map<String, JsonValue> = columns
+                    .iter()
+                    .zip(&row.values)
+                    .map(|(column, value)| Ok((column.clone(), json_of(value)?)))
+                    .collect::<SqlResult<_>>()?;
+                let separator: &[u8] = match (array, position) {
+                    (true, 0) => b"\n",
+                    (true, _) => b",\n",
+                    (false, _) => b"",
+                };
+                writer.write_all(separator).map_err(io_error)?;
+                serde_json::to_writer(&mut writer, &object).map_err(|error| SqlError::io_error(error.to_string()))?;
+                if !array {
+                    writer.write_all(b"\n").map_err(io_error)?;
+                }
+                advance(&mut report);
+            }
+            if array {
+                let end: &[u8] = if rows.is_empty() { b"]\n" } else { b"\n]\n" };
+                writer.write_all(end).map_err(io_error)?;
+            }
+        }
+    }
+    report.bytes = counter.load(Ordering::Relaxed);
+    progress(&report);
+    Ok(report.rows)
+}
+
+/// Records inserted by one INSERT, at most
+const BATCH_ROWS: usize = 500;
+
+/// State of a running import
+struct Import<'a, F> {
+    table: &'a str,
+    /// Columns of the table with their declared types
+    columns: Vec<(String, DataType)>,
+    /// Values of the records read but not inserted yet, which all give
+    /// `batch_columns`
+    batch: Vec<Vec<Value>>,
+    batch_columns: Vec<String>,
+    /// Records inserted together; fewer than a progress interval, so
+    /// reports stay as frequent
+    batch_size: usize,
+    /// Lines or record numbers of the first and last records in the batch
+    first: u64,
+    last: u64,
+    progress: Progress,
+    interval: u64,
+    bytes: Arc<AtomicU64>,
+    report: F,
+}
+
+impl<F: FnMut(&Progress)> Import<'_, F> {
+    async fn csv<R: BufRead>(&mut self, session: &mut Session, mut reader: CsvReader<R>, header: bool) -> SqlResult<u64> {
+        let names: Vec<String> = if header {
+            let Some(header) = reader.next_record()? else {
+                return self.finish();
+            };
+            let names: Vec<String> = header.into_iter().map(|field| field.text.trim().to_string()).collect();
+            self.prepare_table(session, &names, |_| DataType::Text).await?;
+            names
+        } else {
+            self.load_columns(session).await?;
+            self.columns.iter().map(|(name, _)| name.clone()).collect()
+        };
+        let types = self.types_of(&names)?;
+
+        while let Some(record) = reader.next_record()? {
+            let line = reader.line();
+            if record.len() != names.len() {
+                return Err(SqlError::parse_error(format!(
+                    "line {}: expected {} fields, found {}",
+                    line,
+                    names.len(),
+                    record.len()
+                )));
+            }
+            let values = record
+                .into_iter()
+                .zip(&names)
+                .zip(&types)
+                .map(|((field, name), data_type)| {
+                    let value = if field.text.is_empty() && !field.quoted {
+                        Value::Null
+                    } else {
+                        Value::Text(field.text)
+                    };
+                    coerce(value, data_type).map_err(|error| at(&format!("line {}, column {}", line, name), error))
+                })
+                .collect::<SqlResult<Vec<_>>>()?;
+            self.add(session, &names, values, line, "line").await?;
+        }
+        self.flush(session, "line").await?;
+        self.finish()
+    }
+
+    async fn json<R: BufRead>(&mut self, session: &mut Session, mut reader: JsonReader<R>) -> SqlResult<u64> {
+        let mut record = 0u64;
+        while let Some(object) = reader.next_object()? {
+            record += 1;
+            let context = format!("record {}", record);
+            if self.columns.is_empty() {
+                let names: Vec<String> = object.keys().cloned().collect();
+                self.prepare_table(session, &names, |name| json_type(&object[name])).await?;
+            }
+            let names: Vec<String> = object.keys().cloned().collect();
+            let types = self.types_of(&names).map_err(|error| at(&context, error))?;
+            let values = object
+                .into_iter()
+                .zip(&types)
+                .map(|((name, json), data_type)| {
+                    coerce(value_of(json), data_type).map_err(|error| at(&format!("{}, column {}", context, name), error))
+                })
+                .collect::<SqlResult<Vec<_>>>()?;
+            self.add(session, &names, values, record, "record").await?;
+        }
+        self.flush(session, "record").await?;
+        self.finish()
+    }
+
+    /// Add a record to the batch, inserting the batch first if its records
+    /// give other columns, and after if it is full
+    async fn add(
+        &mut self,
+        session: &mut Session,
+        names: &[String],
+        values: Vec<Value>,
+        place: u64,
+        unit: &str,
+    ) -> SqlResult<()> {
+        if !self.batch.is_empty() && self.batch_columns != names {
+            self.flush(session, unit).await?;
+        }
+        if self.batch.is_empty() {
+            self.batch_columns = names.to_vec();
+            self.first = place;
+        }
+        self.last = place;
+        self.batch.push(values);
+        if self.batch.len() >= self.batch_size {
+            self.flush(session, unit).await?;
+        }
+        Ok(())
+    }
+
+    /// Insert the records of the batch with one statement; batches of the
+    /// same size and columns share one cached statement
+    async fn flush(&mut self, session: &mut Session, unit: &str) -> SqlResult<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let rows = self.batch.len();
+        let statement = session
+            .database()
+            .prepare(&insert_sql(self.table, &self.batch_columns, rows))?;
+        let values: Vec<Value> = std::mem::take(&mut self.batch).into_iter().flatten().collect();
+        if let Err(error) = session.execute(&statement, &values).await {
+            let place = if self.first == self.last {
+                format!("{} {}", unit, self.first)
+            } else {
+                format!("{}s {} to {}", unit, self.first, self.last)
+            };
+            return Err(at(&place, error));
+        }
+        self.advance(rows as u64);
+        Ok(())
+    }
+
+    /// Create the table from the file's column names, if it does not
+    /// exist, and learn its columns
+    async fn prepare_table(
+        &mut self,
+        session: &mut Session,
+        names: &[String],
+        data_type: impl Fn(&str) -> DataType,
+    ) -> SqlResult<()> {
+        if session.table_schema(self.table).await.is_none() {
+            if names.is_empty() {
+                return Err(SqlError::parse_error("the file names no columns"));
+            }
+            if let Some(name) = names.iter().find(|name| !is_identifier(name)) {
+                return Err(SqlError::parse_error(format!("'{}' is not a valid column name", name)));
+            }
+            let columns: Vec<String> = names
+                .iter()
+                .map(|name| format!("{} {}", name, data_type(name)))
+                .collect();
+            session
+                .execute_sql(&format!("CREATE TABLE {} ({})", self.table, columns.join(", ")))
+                .await?;
+        }
+        self.load_columns(session).await
+    }
+
+    async fn load_columns(&mut self, session: &Session) -> SqlResult<()> {
+        let schema = session
+            .table_schema(self.table)
+            .await
+            .ok_or_else(|| SqlError::table_not_found(self.table.to_string()))?;
+        self.columns = schema
+            .columns
+            .into_iter()
+            .map(|column| (column.name, column.data_type))
+            .collect();
+        Ok(())
+    }
+
+    /// Declared type of each named column
+    fn types_of(&self, names: &[String]) -> SqlResult<Vec<DataType>> {
+        names
+            .iter()
+            .map(|name| {
+                self.columns
+                    .iter()
+                    .find(|(column, _)| column.eq_ignore_ascii_case(name))
+                    .map(|(_, data_type)| data_type.clone())
+                    .ok_or_else(|| SqlError::column_not_found(format!("{}.{}", self.table, name)))
+            })
+            .collect()
+    }
+
+    /// Count rows inserted, reporting progress each time another interval
+    /// of them is done
+    fn advance(&mut self, rows: u64) {
+        let reported = self.progress.rows / self.interval;
+        self.progress.rows += rows;
+        if self.progress.rows / self.interval > reported {
+            self.progress.bytes = self.bytes.load(Ordering::Relaxed);
+            (self.report)(&self.progress);
+        }
+    }
+
+    fn finish(&mut self) -> SqlResult<u64> {
+        self.progress.bytes = self.bytes.load(Ordering::Relaxed);
+        (self.report)(&self.progress);
+        Ok(self.progress.rows)
+    }
+}
+
+/// Reads the objects of a JSON array, or of JSON Lines, one at a time
+struct JsonReader<R> {
+    reader: R,
+    lines: bool,
+    /// Objects read so far; `None` once the array has ended
+    read: Option<u64>,
+}
+
+impl<R: BufRead> JsonReader<R> {
+    fn array(reader: R) -> Self {
+        JsonReader {
+            reader,
+            lines: false,
+            read: Some(0),
+        }
+    }
+
+    fn lines(reader: R) -> Self {
+        JsonReader {
+            reader,
+            lines: true,
+            read: Some(0),
+        }
+    }
+
+    fn next_object(&mut self) -> SqlResult<Option<Map<String, JsonValue>>> {
+        let Some(read) = self.read else {
+            return Ok(None);
+        };
+        let value = if self.lines {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if self.reader.read_line(&mut line).map_err(|error| SqlError::io_error(error.to_string()))? == 0 {
+                    self.read = None;
+                    return Ok(None);
+                }
+                if !line.trim().is_empty() {
+                    break;
+                }
+            }
+            serde_json::from_str(&line).map_err(|error| json_error(read + 1, error))?
+        } else {
+            let end = match read {
+                0 => self.next_byte(b"[")? == b'[' && self.next_byte(b"]{")? == b']',
+                _ => self.next_byte(b",]")? == b']' || self.next_byte(b"{")? != b'{',
+            };
+            if end {
+                self.read = None;
+                return Ok(None);
+            }
+            // Objects end at their closing brace, so the parser reads no
+            // further than the record
+            let mut parser = serde_json::Deserializer::from_reader(&mut self.reader);
+            JsonValue::deserialize(&mut parser).map_err(|error| json_error(read + 1, error))?
+        };
+        self.read = Some(read + 1);
+        match value {
+            JsonValue::Object(object) => Ok(Some(object)),
+            _ => Err(SqlError::parse_error(format!("record {}: not a JSON object", read + 1))),
+        }
+    }
+
+    /// Skip whitespace and take the next byte, which must be one of
+    /// `expected`. An opening brace is left for the JSON parser.
+    fn next_byte(&mut self, expected: &[u8]) -> SqlResult<u8> {
+        loop {
+            let buffer = self.reader.fill_buf().map_err(|error| SqlError::io_error(error.to_string()))?;
+            let Some(&byte) = buffer.first() else {
+                return Err(SqlError::parse_error("unexpected end of the JSON array"));
+            };
+            if byte.is_ascii_whitespace() {
+                self.reader.consume(1);
+                continue;
+            }
+            if !expected.contains(&byte) {
+                return Err(SqlError::parse_error(format!(
+                    "expected one of {:?} in the JSON array, found {:?}",
+                    String::from_utf8_lossy(expected),
+                    byte as char
+                )));
+            }
+            if byte != b'{' {
+                self.reader.consume(1);
+            }
+            return Ok(byte);
+        }
+    }
+}
+
+fn json_error(record: u64, error: serde_json::Error) -> SqlError {
+    SqlError::parse_error(format!("record {}: {}", record, error))
+}
+
+/// Counts the bytes that pass through a reader or writer
+struct Counted<T> {
+    inner: T,
+    bytes: Arc<AtomicU64>,
+}
+
+impl<T: Read> Read for Counted<T> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.bytes.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl<T: Write> Write for Counted<T> {
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buffer)?;
+        self.bytes.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Convert an imported value to the type declared for its column; columns
+/// without a type take values as they are
+pub fn coerce(value: Value, data_type: &DataType) -> SqlResult<Value> {
+    let mismatch = |value: &Value| SqlError::type_error(format!("cannot store {:?} in a {} column", value, data_type));
+    Ok(match (value, data_type) {
+        (Value::Null, _) => Value::Null,
+        (value, DataType::Null) => value,
+        (Value::Text(text), DataType::Integer) => {
+            let trimmed = text.trim();
+            match trimmed.parse::<i64>() {
+                Ok(integer) => Value::Integer(integer),
+                Err(_) => match trimmed.parse::<f64>() {
+                    Ok(real) if real.fract() == 0.0 && real.abs() < i64::MAX as f64 => Value::Integer(real as i64),
+                    _ => return Err(mismatch(&Value::Text(text))),
+                },
+            }
+        }
+        (Value::Text(text), DataType::Real) => match text.trim().parse::<f64>() {
+            Ok(real) => Value::Real(real),
+            Err(_) => return Err(mismatch(&Value::Text(text))),
+        },
+        (Value::Text(text), DataType::Boolean) => match text.trim().to_ascii_lowercase().as_str() {
+            "true" | "t" | "yes" | "y" | "1" => Value::Boolean(true),
+            "false" | "f" | "no" | "n" | "0" => Value::Boolean(false),
+            _ => return Err(mismatch(&Value::Text(text))),
+        },
+        (Value::Text(text), DataType::Blob) => match text.strip_prefix("\\x") {
+            Some(hex) => Value::Blob(decode_hex(hex).ok_or_else(|| mismatch(&Value::Text(text.clone())))?),
+            None => Value::Blob(text.into_bytes()),
+        },
+        (Value::Integer(integer), DataType::Real) => Value::Real(integer as f64),
+        (Value::Integer(integer @ (0 | 1)), DataType::Boolean) => Value::Boolean(integer == 1),
+        (Value::Real(real), DataType::Integer) if real.fract() == 0.0 && real.abs() < i64::MAX as f64 => {
+            Value::Integer(real as i64)
+        }
+        (Value::Boolean(boolean), DataType::Integer) => Value::Integer(boolean as i64),
+        (value, DataType::Text) => match value {
+            Value::Text(_) => value,
+            other => Value::Text(text_of(&other).unwrap_or_default()),
+        },
+        (value, data_type) if value.data_type() == *data_type => value,
+        (value, _) => return Err(mismatch(&value)),
+    })
+}
+
+/// Text form of a value in CSV; `None` for NULL. BLOBs are written as
+/// `\x` and hex digits, which an import reads back.
+fn text_of(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Blob(bytes) => Some(encode_hex(bytes)),
+        other => Some(other.to_string()),
+    }
+}
+
+fn json_of(value: &Value) -> SqlResult<JsonValue> {
+    match value {
+        Value::Blob(bytes) => Ok(JsonValue::String(encode_hex(bytes))),
+        other => json::to_json(other),
+    }
+}
+
+/// SQL value of a JSON value; `true`/`false` stay booleans, and arrays and
+/// objects are kept as JSON text
+fn value_of(json: JsonValue) -> Value {
+    match json {
+        JsonValue::Bool(boolean) => Value::Boolean(boolean),
+        other => json::to_sql(&other),
+    }
+}
+
+/// Column type for a table created from a JSON record
+fn json_type(json: &JsonValue) -> DataType {
+    match json {
+        JsonValue::Bool(_) => DataType::Boolean,
+        JsonValue::Number(number) if number.is_i64() => DataType::Integer,
+        JsonValue::Number(_) => DataType::Real,
+        _ => DataType::Text,
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\\x{}", hex)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok())
+        .collect()
+}
+
+/// INSERT of `rows` rows into `columns`, with a parameter per value
+fn insert_sql(table: &str, columns: &[String], rows: usize) -> String {
+    let tuples: Vec<String> = (0..rows)
+        .map(|row| {
+            let placeholders: Vec<String> = (1..=columns.len())
+                .map(|column| format!("?{}", row * columns.len() + column))
+                .collect();
+            format!("({})", placeholders.join(", "))
+        })
+        .collect();
+    format!("INSERT INTO {} ({}) VALUES {}", table, columns.join(", "), tuples.join(", "))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `error` with where in the file it happened
+fn at(place: &str, error: SqlError) -> SqlError {
+    SqlError::runtime_error(format!("{}: {}", place, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::DatabaseConfig;
+    use crate::CategoricalSQLite;
+
+    #[tokio::test]
+    async fn test_import_and_export() {
+        let db = CategoricalSQLite::new(DatabaseConfig::default());
+        db.execute_sql("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL, price REAL, active BOOLEAN)")
+            .await
+            .unwrap();
+        let mut session = db.session();
+        let options = TransferOptions {
+            delimiter: ';',
+            progress_interval: 2,
+            ..TransferOptions::default()
+        };
+
+        let csv = "name;price;id;active\nlamp;12.5;1;yes\n\"desk; oak\";;2;false\nchair;30;3;1\n";
+        let mut reports = Vec::new();
+        let rows = import(&mut session, csv.as_bytes(), Some(csv.len() as u64), "items", &options, |progress| {
+            reports.push(progress.rows)
+        })
+        .await
+        .unwrap();
+        assert_eq!(rows, 3);
+        assert_eq!(reports, vec![2, 3]);
+        let result = db.execute_sql("SELECT id, name, price, active FROM items WHERE id = 2").await.unwrap();
+        assert_eq!(
+            result.rows().unwrap()[0].values,
+            vec![Value::Integer(2), Value::Text("desk; oak".to_string()), Value::Null, Value::Boolean(false)]
+        );
+
+        // A bad record rolls the whole import back
+        let bad = "name;price;id;active\nbed;99;4;true\nsofa;cheap;5;true\n";
+        let error = import(&mut session, bad.as_bytes(), None, "items", &options, |_| {})
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("line 3, column price"), "{}", error);
+        let result = db.execute_sql("SELECT COUNT(*) FROM items").await.unwrap();
+        assert_eq!(result.rows().unwrap()[0].values[0], Value::Integer(3));
+
+        let json_options = TransferOptions {
+            format: Format::Json,
+            ..TransferOptions::default()
+        };
+        let mut exported = Vec::new();
+        export(&mut session, "items", &mut exported, &json_options, |_| {}).await.unwrap();
+        let exported = String::from_utf8(exported).unwrap();
+        assert!(exported.starts_with("[\n{\"id\":1,\"name\":\"lamp\",\"price\":12.5,\"active\":true},\n"), "{}", exported);
+
+        // The export imports into a new table typed from its first record
+        let rows = import(&mut session, exported.as_bytes(), None, "copy", &json_options, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(rows, 3);
+        let schema = db.table_schema("copy").await.unwrap();
+        let types: Vec<DataType> = schema.columns.iter().map(|column| column.data_type.clone()).collect();
+        assert_eq!(types, vec![DataType::Integer, DataType::Text, DataType::Real, DataType::Boolean]);
+
+        let mut csv = Vec::new();
+        export(&mut session, "copy", &mut csv, &options, |_| {}).await.unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id;name;price;active\n1;lamp;12.5;true\n2;\"desk; oak\";;false\n3;chair;30;true\n"
+        );
+
+        let lines = "{\"id\": 7, \"name\": \"rug\"}\n\n{\"name\": \"vase\", \"id\": 8}\n";
+        let lines_options = TransferOptions {
+            format: Format::JsonLines,
+            ..TransferOptions::default()
+        };
+        let rows = import(&mut session, lines.as_bytes(), None, "items", &lines_options, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(rows, 2);
+        assert!(import(&mut session, "[1]".as_bytes(), None, "items", &json_options, |_| {}).await.is_err());
+        assert_eq!(import(&mut session, " [ ] ".as_bytes(), None, "items", &json_options, |_| {}).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_large_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = CategoricalSQLite::open(dir.path().join("import.db"), DatabaseConfig::default())
+            .await
+            .unwrap();
+        db.execute_sql("CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT NOT NULL, amount REAL)")
+            .await
+            .unwrap();
+        let mut csv = String::from("id,kind,amount\n");
+        for n in 1..=50_000 {
+            csv.push_str(&format!("{},kind{},{}.5\n", n, n % 7, n));
+        }
+
+        // At a statement per row, each costing more as the table grew,
+        // importing a sixth of this took minutes
+        let mut session = db.session();
+        let mut reports = Vec::new();
+        let started = std::time::Instant::now();
+        let options = TransferOptions::default();
+        let rows = import(&mut session, csv.as_bytes(), Some(csv.len() as u64), "events", &options, |progress| {
+            reports.push(progress.rows)
+        })
+        .await
+        .unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed < std::time::Duration::from_secs(60), "importing took {:?}", elapsed);
+        assert_eq!(rows, 50_000);
+        let expected: Vec<u64> = (1..=5).map(|n| n * 10_000).chain([50_000]).collect();
+        assert_eq!(reports, expected);
+        let result = db.execute_sql("SELECT COUNT(*), MAX(id) FROM events WHERE kind = 'kind3'").await.unwrap();
+        assert_eq!(result.rows().unwrap()[0].values, vec![Value::Integer(7_143), Value::Integer(49_997)]);
+
+        // A failed batch names the lines it held
+        let duplicate = "id,kind,amount\n50001,new,1\n5,old,2\n";
+        let error = import(&mut session, duplicate.as_bytes(), None, "events", &options, |_| {})
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("lines 2 to 3"), "{}", error);
+        let result = db.execute_sql("SELECT COUNT(*) FROM events").await.unwrap();
+        assert_eq!(result.rows().unwrap()[0].values[0], Value::Integer(50_000));
+    }
+
+    #[test]
+    fn test_coerce_to_declared_types() {
+        let text = |text: &str| Value::Text(text.to_string());
+        assert_eq!(coerce(text(" 42 "), &DataType::Integer).unwrap(), Value::Integer(42));
+        assert_eq!(coerce(text("3.0"), &DataType::Integer).unwrap(), Value::Integer(3));
+        assert!(coerce(text("3.5"), &DataType::Integer).is_err());
+        assert_eq!(coerce(Value::Integer(2), &DataType::Real).unwrap(), Value::Real(2.0));
+        assert_eq!(coerce(text("\\x00ff"), &DataType::Blob).unwrap(), Value::Blob(vec![0, 255]));
+        assert_eq!(coerce(Value::Integer(7), &DataType::Text).unwrap(), text("7"));
+        assert_eq!(coerce(text("abc"), &DataType::Null).unwrap(), text("abc"));
+        assert!(coerce(Value::Boolean(true), &DataType::Real).is_err());
+    }
+}
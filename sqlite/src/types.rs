@@ -159,6 +159,62 @@ impl Row {
     }
 }
 
+/// What a statement did to a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Insert => write!(f, "insert"),
+            ChangeKind::Update => write!(f, "update"),
+            ChangeKind::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// A row inserted, updated or deleted, with its values before and after
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowChange {
+    pub table: String,
+    pub kind: ChangeKind,
+    pub columns: Vec<String>,
+    /// Values before an UPDATE or DELETE
+    pub old: Option<Vec<Value>>,
+    /// Values after an INSERT or UPDATE
+    pub new: Option<Vec<Value>>,
+}
+
+impl RowChange {
+    pub fn insert(table: impl Into<String>, columns: Vec<String>, new: Vec<Value>) -> Self {
+        RowChange { table: table.into(), kind: ChangeKind::Insert, columns, old: None, new: Some(new) }
+    }
+
+    pub fn update(table: impl Into<String>, columns: Vec<String>, old: Vec<Value>, new: Vec<Value>) -> Self {
+        RowChange { table: table.into(), kind: ChangeKind::Update, columns, old: Some(old), new: Some(new) }
+    }
+
+    pub fn delete(table: impl Into<String>, columns: Vec<String>, old: Vec<Value>) -> Self {
+        RowChange { table: table.into(), kind: ChangeKind::Delete, columns, old: Some(old), new: None }
+    }
+
+    /// Value of a column before the change
+    pub fn old_value(&self, column: &str) -> Option<&Value> {
+        let position = self.columns.iter().position(|c| c == column)?;
+        self.old.as_ref()?.get(position)
+    }
+
+    /// Value of a column after the change
+    pub fn new_value(&self, column: &str) -> Option<&Value> {
+        let position = self.columns.iter().position(|c| c == column)?;
+        self.new.as_ref()?.get(position)
+    }
+}
+
 /// Database state for categorical operations
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DatabaseState {
@@ -188,7 +244,7 @@ pub enum DatabaseMode {
 }
 
 /// Row identifier
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct RowId(pub u64);
 
 impl fmt::Display for RowId {
@@ -198,7 +254,7 @@ impl fmt::Display for RowId {
 }
 
 /// Page identifier for storage
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PageId(pub u32);
 
 impl fmt::Display for PageId {
@@ -0,0 +1,383 @@
+//! Bytecode programs as processes
+//!
+//! A `BytecodeActor` runs a program under a yielding fuel budget, one
+//! slice per scheduler quantum, so a long-running program shares its
+//! scheduler thread with other processes instead of holding it.
+//!
+//! `receive` suspends the program until a message is delivered, and
+//! `yield` until the next quantum. The VM queues `spawn` and `send` as
+//! `Outgoing` requests, which the actor hands to its `ProcessHost` after
+//! each slice. A spawned process runs the spawning program from the
+//! spawned function, and finishes when that function returns.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use crate::bytecode::{BytecodeProgram, BytecodeVerifier, BytecodeVM, Exhaustion, FuelBudget, Value};
+use crate::error::{BytecodeResult, RuntimeError, RuntimeResult};
+use crate::runtime::{ReamActor, ReamRuntime};
+use crate::types::{MessagePayload, Pid};
+
+/// A request a program makes of the scheduler running it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outgoing {
+    /// Start `function` of the running program as process `pid`
+    Spawn { pid: Pid, function: u32, args: Vec<Value> },
+    /// Deliver `message` to `to`
+    Send { to: Pid, message: Value },
+}
+
+/// Carries out the spawns and sends of bytecode actors
+pub trait ProcessHost: Send + Sync {
+    /// Run a spawned actor as a process under its own pid
+    fn spawn(&self, actor: BytecodeActor) -> RuntimeResult<()>;
+
+    /// Deliver a message to a process
+    fn send(&self, to: Pid, message: MessagePayload) -> RuntimeResult<()>;
+}
+
+impl ProcessHost for ReamRuntime {
+    fn spawn(&self, actor: BytecodeActor) -> RuntimeResult<()> {
+        self.spawn_as(actor.pid, actor).map(|_| ())
+    }
+
+    fn send(&self, to: Pid, message: MessagePayload) -> RuntimeResult<()> {
+        ReamRuntime::send(self, to, message)
+    }
+}
+
+/// Where the actor's program is
+#[derive(Debug, Clone, PartialEq)]
+enum Run {
+    Running,
+    Finished(Value),
+    Failed(String),
+}
+
+/// Actor running a bytecode program across quanta
+pub struct BytecodeActor {
+    pid: Pid,
+    program: BytecodeProgram,
+    /// Function and arguments a spawned actor started at
+    entry: Option<(u32, Vec<Value>)>,
+    fuel: u64,
+    /// The VM is only ever used through `&mut self`; the lock makes the actor `Sync`
+    vm: Mutex<BytecodeVM>,
+    host: Option<Arc<dyn ProcessHost>>,
+    run: Run,
+}
+
+impl BytecodeActor {
+    /// Verify a program and start it, giving it `fuel` per quantum
+    pub fn new(pid: Pid, program: BytecodeProgram, fuel: u64) -> BytecodeResult<Self> {
+        BytecodeVerifier::allowing_all_effects().verify(&program)?;
+        let mut vm = BytecodeVM::new();
+        vm.set_pid(pid);
+        vm.set_budget(Some(FuelBudget { fuel, on_exhaustion: Exhaustion::Yield }));
+        vm.begin();
+        Ok(BytecodeActor { pid, program, entry: None, fuel, vm: Mutex::new(vm), host: None, run: Run::Running })
+    }
+
+    /// Carry out the program's spawns and sends through `host`; without
+    /// one, a program that spawns or sends fails
+    pub fn with_host(mut self, host: Arc<dyn ProcessHost>) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// An actor for a function another actor of the same, verified, program spawned
+    fn spawned(&self, pid: Pid, function: u32, args: Vec<Value>) -> BytecodeResult<Self> {
+        let mut vm = BytecodeVM::new();
+        vm.set_pid(pid);
+        vm.set_budget(Some(FuelBudget { fuel: self.fuel, on_exhaustion: Exhaustion::Yield }));
+        vm.begin_function(&self.program, function, args.clone())?;
+        Ok(BytecodeActor {
+            pid,
+            program: self.program.clone(),
+            entry: Some((function, args)),
+            fuel: self.fuel,
+            vm: Mutex::new(vm),
+            host: self.host.clone(),
+            run: Run::Running,
+        })
+    }
+
+    /// Hand what the program spawned and sent during its last slice to the host
+    fn dispatch(&mut self) -> RuntimeResult<()> {
+        let outgoing = self.vm.get_mut().unwrap().take_outgoing();
+        if outgoing.is_empty() {
+            return Ok(());
+        }
+        let host = self.host.clone()
+            .ok_or_else(|| RuntimeError::ActorError("Bytecode actor has no process host".to_string()))?;
+        for request in outgoing {
+            match request {
+                Outgoing::Spawn { pid, function, args } => {
+                    let actor = self.spawned(pid, function, args)
+                        .map_err(|error| RuntimeError::ActorError(error.to_string()))?;
+                    host.spawn(actor)?;
+                }
+                Outgoing::Send { to, message } => host.send(to, to_payload(message))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// The program's result, once it has finished
+    pub fn result(&self) -> Option<&Value> {
+        match &self.run {
+            Run::Finished(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl ReamActor for BytecodeActor {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        let message = from_payload(message)?;
+        self.vm.get_mut().unwrap().deliver(message);
+        Ok(())
+    }
+
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        let vm = self.vm.get_mut().unwrap();
+        match &self.entry {
+            Some((function, args)) => vm.begin_function(&self.program, *function, args.clone())
+                .map_err(|error| RuntimeError::ActorError(error.to_string()))?,
+            None => vm.begin(),
+        }
+        self.run = Run::Running;
+        Ok(())
+    }
+
+    fn run_slice(&mut self) -> RuntimeResult<bool> {
+        if self.run != Run::Running {
+            return Ok(false);
+        }
+        let resumed = self.vm.get_mut().unwrap().resume(&self.program);
+        self.dispatch()?;
+        match resumed {
+            Ok(Some(value)) => {
+                self.run = Run::Finished(value);
+                Ok(false)
+            }
+            // Waiting for a message needs no quantum until one is delivered
+            Ok(None) => Ok(!self.vm.get_mut().unwrap().is_waiting()),
+            Err(error) => {
+                self.run = Run::Failed(error.to_string());
+                Err(RuntimeError::ActorError(error.to_string()))
+            }
+        }
+    }
+
+    fn dictionary(&self) -> BTreeMap<String, String> {
+        let vm = self.vm.lock().unwrap();
+        let (status, key, detail) = match &self.run {
+            Run::Running if vm.is_waiting() => ("waiting", "pc", vm.context().pc.to_string()),
+            Run::Running => ("running", "pc", vm.context().pc.to_string()),
+            Run::Finished(value) => ("finished", "result", value.to_string()),
+            Run::Failed(error) => ("failed", "error", error.clone()),
+        };
+        BTreeMap::from([("status".to_string(), status.to_string()), (key.to_string(), detail)])
+    }
+}
+
+/// A sent value as a message: strings as text, bytes as bytes, and
+/// anything else as its JSON encoding
+fn to_payload(value: Value) -> MessagePayload {
+    match value {
+        Value::String(text) => MessagePayload::Text(text),
+        Value::Bytes(bytes) => MessagePayload::binary(bytes),
+        other => match serde_json::to_value(&other) {
+            Ok(json) => MessagePayload::Data(json),
+            Err(_) => MessagePayload::Text(other.to_string()),
+        },
+    }
+}
+
+/// The value `receive` pushes for a delivered message
+fn from_payload(payload: MessagePayload) -> RuntimeResult<Value> {
+    match payload {
+        MessagePayload::Text(text) => Ok(Value::String(text)),
+        MessagePayload::Bytes(bytes) => Ok(Value::Bytes(bytes)),
+        MessagePayload::SharedBinary(binary) => Ok(Value::Bytes(binary.to_vec())),
+        MessagePayload::Data(json) => Ok(serde_json::from_value(json.clone()).unwrap_or(Value::String(json.to_string()))),
+        MessagePayload::Control(_) => Err(RuntimeError::InvalidMessage("Bytecode actors take no control messages".to_string())),
+        MessagePayload::Traced { payload, .. } => from_payload(*payload),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::assemble;
+    use std::collections::HashMap;
+    use crate::error::BytecodeError;
+    use crate::runtime::Process;
+    use crate::types::Priority;
+
+    /// Counts down from 50, leaving 0
+    const COUNTDOWN: &str = "
+        .const 0 int 50
+        .const 1 int 1
+        const 0
+        store 0
+        loop:
+        load 0
+        jump_if_not done
+        load 0
+        const 1
+        sub
+        store 0
+        jump loop
+        done:
+        load 0
+    ";
+
+    #[test]
+    fn test_fuel_budget() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let mut vm = BytecodeVM::new();
+        vm.set_budget(Some(FuelBudget { fuel: 100, on_exhaustion: Exhaustion::Error }));
+        assert!(matches!(vm.execute_program(&program), Err(BytecodeError::OutOfFuel(_))));
+
+        // A budget only stops the program when it runs out
+        vm.set_budget(Some(FuelBudget { fuel: 1000, on_exhaustion: Exhaustion::Error }));
+        assert_eq!(vm.execute_program(&program).unwrap(), Value::Int(0));
+        assert!(vm.context().fuel.unwrap() < 1000);
+
+        // A yielding program picks up where it stopped
+        vm.set_budget(Some(FuelBudget { fuel: 100, on_exhaustion: Exhaustion::Yield }));
+        vm.begin();
+        let mut slices = 1;
+        while vm.resume(&program).unwrap().is_none() {
+            slices += 1;
+        }
+        assert!(slices > 3, "{}", slices);
+        assert_eq!(vm.execute_program(&program).unwrap(), Value::Int(0));
+    }
+
+    #[test]
+    fn test_actor_yields_between_quanta() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let pid = Pid::new();
+        let actor = BytecodeActor::new(pid, program, 50).unwrap();
+        let mut process = Process::new(pid, Box::new(actor), Priority::Normal);
+
+        let mut quanta = 0;
+        loop {
+            process.run_quantum().unwrap();
+            quanta += 1;
+            if !process.is_busy() {
+                break;
+            }
+        }
+        assert!(quanta > 3, "{}", quanta);
+        let dictionary = process.dictionary();
+        assert_eq!(dictionary["status"], "finished");
+        assert_eq!(dictionary["result"], "0");
+    }
+
+    /// Spawns an echo process, sends it the main pid and returns its reply
+    const PING: &str = r#"
+        .const 0 value {"Function":0}
+        .const 1 string "pong"
+        .function 0 echo params=0 locals=0 len=4 effect=send
+        const 0
+        spawn 0 spawn
+        dup
+        self_pid read
+        send send
+        pop
+        receive read
+        jump end
+        echo:
+        receive read
+        const 1
+        send send
+        ret
+        end:
+        nop
+    "#;
+
+    /// Holds spawns and sends until the test's scheduling loop picks them up
+    #[derive(Default)]
+    struct TestHost {
+        spawned: Mutex<Vec<BytecodeActor>>,
+        sent: Mutex<Vec<(Pid, MessagePayload)>>,
+    }
+
+    impl ProcessHost for TestHost {
+        fn spawn(&self, actor: BytecodeActor) -> RuntimeResult<()> {
+            self.spawned.lock().unwrap().push(actor);
+            Ok(())
+        }
+
+        fn send(&self, to: Pid, message: MessagePayload) -> RuntimeResult<()> {
+            self.sent.lock().unwrap().push((to, message));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_process_instructions() {
+        let mut vm = BytecodeVM::new();
+        let program = assemble("self_pid read\nyield\n").unwrap();
+        assert_eq!(vm.execute_program(&program).unwrap(), Value::Pid(vm.pid()));
+
+        // Receiving needs a message delivered first
+        let program = assemble("receive read\n").unwrap();
+        assert!(matches!(vm.execute_program(&program), Err(BytecodeError::NoMessage(0))));
+        vm.begin();
+        assert_eq!(vm.resume(&program).unwrap(), None);
+        assert!(vm.is_waiting());
+        vm.deliver(Value::Int(7));
+        assert_eq!(vm.resume(&program).unwrap(), Some(Value::Int(7)));
+
+        // Spawns and sends wait in the outbox for the scheduler
+        let program = assemble(&PING.replace("receive read\n        jump end", "jump end")).unwrap();
+        let spawned = match vm.execute_program(&program).unwrap() {
+            Value::Pid(pid) => pid,
+            other => panic!("{:?}", other),
+        };
+        let outgoing = vm.take_outgoing();
+        assert_eq!(outgoing[0], Outgoing::Spawn { pid: spawned, function: 0, args: vec![] });
+        assert_eq!(outgoing[1], Outgoing::Send { to: spawned, message: Value::Pid(vm.pid()) });
+
+        // Process instructions must be graded for their effects
+        let err = vm.load_program("ping".to_string(), assemble(&PING.replace("send send\n        pop", "send\n        pop")).unwrap())
+            .unwrap_err().to_string();
+        assert!(err.contains("send at PC 4 is Pure but needs at least Send"), "{}", err);
+    }
+
+    #[test]
+    fn test_actors_exchange_messages() {
+        let host = Arc::new(TestHost::default());
+        let pid = Pid::new();
+        let actor = BytecodeActor::new(pid, assemble(PING).unwrap(), 100).unwrap().with_host(host.clone());
+        let mut processes = HashMap::from([(pid, Process::new(pid, Box::new(actor), Priority::Normal))]);
+
+        for _ in 0..10 {
+            for actor in host.spawned.lock().unwrap().drain(..) {
+                processes.insert(actor.pid(), Process::new(actor.pid(), Box::new(actor), Priority::Normal));
+            }
+            for (to, message) in host.sent.lock().unwrap().drain(..) {
+                processes.get_mut(&to).unwrap().deliver(message).unwrap();
+            }
+            for process in processes.values_mut() {
+                process.run_quantum().unwrap();
+            }
+        }
+        assert_eq!(processes.len(), 2);
+        let dictionary = processes[&pid].dictionary();
+        assert_eq!(dictionary["status"], "finished", "{:?}", dictionary);
+        assert_eq!(dictionary["result"], Value::String("pong".to_string()).to_string());
+
+        // The echo process finished with the message it sent back
+        let echo = processes.values().find(|process| process.pid() != pid).unwrap();
+        assert_eq!(echo.dictionary()["status"], "finished");
+    }
+}
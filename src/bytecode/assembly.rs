@@ -0,0 +1,605 @@
+//! Textual bytecode assembly (`.reams`)
+//!
+//! `disassemble` renders a program as assembly and `assemble` parses it
+//! back. Directives describe the constant pool, globals, functions,
+//! exports and imports. Every other line is an instruction, optionally
+//! prefixed by its index or by a label:
+//!
+//! ```text
+//! .program double
+//! .const 0 int 21
+//! .const 1 int 2
+//! .function 0 double params=1 locals=0 start=5 len=3
+//!
+//!     0: const 0 pure           ; 21
+//!     1: call 0 pure            ; double
+//!     2: store 0 pure
+//!     3: load 0 pure
+//!     4: jump 8 pure            ; -> end
+//! double:
+//!     5: const 1 pure           ; 2
+//!     6: mul pure
+//!     7: ret pure
+//! ```
+//!
+//! Mnemonics are the snake_case names of `Bytecode` variants. An
+//! instruction's effect grade is written after its operands and defaults
+//! to `pure`. Jump targets may be labels. Call targets may be function
+//! names. Everything after `;` is a comment. `.function` defaults
+//! `start` to the label with the function's name. `len` defaults to the
+//! instructions up to the next function or the end of the program.
+//! `effect` declares a function's effect grade, which otherwise is the
+//! grade of its instructions.
+//! `.backend register` selects the register backend.
+//! Debug information and the compilation time are not carried.
+
+use std::collections::HashMap;
+
+use crate::bytecode::{Bytecode, BytecodeFunction, BytecodeProgram, Value};
+use crate::bytecode::program::{ExecutionBackend, FunctionSignature, ImportInfo};
+use crate::error::{BytecodeError, BytecodeResult};
+use crate::types::EffectGrade;
+
+/// File extension of bytecode assembly
+pub const ASSEMBLY_EXTENSION: &str = "reams";
+
+/// Column annotations start at
+const COMMENT_COLUMN: usize = 30;
+
+/// Render a program as assembly
+pub fn disassemble(program: &BytecodeProgram) -> String {
+    let mut out = String::new();
+    let metadata = &program.metadata;
+    out.push_str(&format!(".program {}\n", metadata.name));
+    out.push_str(&format!(".version {}\n", metadata.version));
+    out.push_str(&format!(".language {}\n", metadata.source_language));
+    if metadata.backend != ExecutionBackend::Stack {
+        out.push_str(&format!(".backend {}\n", metadata.backend));
+    }
+
+    if !program.constants.is_empty() {
+        out.push('\n');
+        for (index, value) in program.constants.iter().enumerate() {
+            out.push_str(&format!(".const {} {}\n", index, write_value(value)));
+        }
+    }
+    if !program.globals.is_empty() {
+        out.push('\n');
+        for (index, name) in program.globals.iter().enumerate() {
+            out.push_str(&format!(".global {} {}\n", index, name));
+        }
+    }
+    if !program.functions.is_empty() {
+        out.push('\n');
+        for function in &program.functions {
+            out.push_str(&format!(".function {} {} params={} locals={} start={} len={}",
+                function.id, function.name, function.param_count, function.local_count,
+                function.start_pc, function.instructions.len()));
+            if function.effect_grade != function.analyze_effects() {
+                out.push_str(&format!(" effect={}", effect_name(function.effect_grade)));
+            }
+            out.push('\n');
+            let default = BytecodeFunction::new(function.id, function.name.clone(), function.param_count).signature;
+            if serde_json::to_value(&function.signature).ok() != serde_json::to_value(&default).ok() {
+                out.push_str(&format!(".signature {} {}\n", function.id, to_json(&function.signature)));
+            }
+        }
+    }
+    if !program.exports.is_empty() || !program.imports.is_empty() {
+        out.push('\n');
+        let mut exports: Vec<_> = program.exports.iter().collect();
+        exports.sort();
+        for (name, id) in exports {
+            out.push_str(&format!(".export {} {}\n", name, id));
+        }
+        let mut imports: Vec<_> = program.imports.iter().collect();
+        imports.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, import) in imports {
+            out.push_str(&format!(".import {} {}\n", name, to_json(import)));
+        }
+    }
+
+    let labels = function_labels(program);
+    out.push('\n');
+    for (pc, instruction) in program.instructions.iter().enumerate() {
+        if let Some(label) = labels.get(&pc) {
+            out.push_str(&format!("{}:\n", label));
+        }
+        let (mnemonic, operands, effect) = parts(instruction);
+        let mut text = format!("{:>5}: {}", pc, mnemonic);
+        for operand in operands {
+            text.push_str(&format!(" {}", operand));
+        }
+        text.push_str(&format!(" {}", effect_name(effect)));
+        if let Some(comment) = annotation(program, &labels, instruction) {
+            text = format!("{:<width$} ; {}", text, comment, width = COMMENT_COLUMN);
+        }
+        out.push_str(&text);
+        out.push('\n');
+    }
+    out
+}
+
+/// Parse assembly into a program
+pub fn assemble(source: &str) -> BytecodeResult<BytecodeProgram> {
+    let mut program = BytecodeProgram::new("unnamed".to_string());
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut pending = Vec::new();
+    let mut functions = Vec::new();
+    let mut signatures = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let error = |message: String| BytecodeError::Assembly { line: number, message };
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(directive) = line.strip_prefix('.') {
+            let (name, rest) = split_word(directive);
+            match name {
+                "program" => program.metadata.name = rest.to_string(),
+                "version" => program.metadata.version = rest.to_string(),
+                "language" => program.metadata.source_language = rest.to_string(),
+                "backend" => program.metadata.backend = rest.parse()
+                    .map_err(|_| error(format!("Unknown backend {}", rest)))?,
+                "const" => {
+                    let (index, literal) = split_word(rest);
+                    expect_index(index, program.constants.len(), "constant").map_err(error)?;
+                    program.constants.push(parse_value(literal).map_err(error)?);
+                }
+                "global" => {
+                    let (index, name) = split_word(rest);
+                    expect_index(index, program.globals.len(), "global").map_err(error)?;
+                    program.globals.push(name.to_string());
+                }
+                "function" => functions.push((number, parse_function(rest).map_err(error)?)),
+                "signature" => {
+                    let (id, json) = split_word(rest);
+                    let id: u32 = parse_number(id).map_err(error)?;
+                    let signature: FunctionSignature = serde_json::from_str(json)
+                        .map_err(|e| error(format!("Invalid signature: {}", e)))?;
+                    signatures.push((number, id, signature));
+                }
+                "export" => {
+                    let (name, id) = split_word(rest);
+                    program.exports.insert(name.to_string(), parse_number(id).map_err(error)?);
+                }
+                "import" => {
+                    let (name, json) = split_word(rest);
+                    let import: ImportInfo = serde_json::from_str(json)
+                        .map_err(|e| error(format!("Invalid import: {}", e)))?;
+                    program.imports.insert(name.to_string(), import);
+                }
+                other => return Err(error(format!("Unknown directive .{}", other))),
+            }
+            continue;
+        }
+
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if let Some(prefix) = words[0].strip_suffix(':') {
+            let pc = pending.len();
+            if prefix.parse::<usize>().is_ok() {
+                expect_index(prefix, pc, "instruction").map_err(error)?;
+            } else if labels.insert(prefix.to_string(), pc).is_some() {
+                return Err(error(format!("Label {} is defined twice", prefix)));
+            }
+            words.remove(0);
+        }
+        let Some((&mnemonic, rest)) = words.split_first() else {
+            continue;
+        };
+        let (effect, operands) = match rest.split_last() {
+            Some((last, operands)) if parse_effect(last).is_some() => (parse_effect(last).unwrap(), operands),
+            _ => (EffectGrade::Pure, rest),
+        };
+        pending.push((number, mnemonic, operands.to_vec(), effect));
+    }
+
+    // Functions are laid out before operands are resolved, so calls can name them
+    let mut starts = Vec::new();
+    for (number, declaration) in &functions {
+        let start = match &declaration.start {
+            Some(start) => resolve_label(start, &labels),
+            None => labels.get(&declaration.name).copied()
+                .ok_or_else(|| format!("No label {} for the start of the function", declaration.name)),
+        };
+        starts.push(start.map_err(|message| BytecodeError::Assembly { line: *number, message })?);
+    }
+
+    for (number, mnemonic, operands, effect) in pending {
+        let error = |message: String| BytecodeError::Assembly { line: number, message };
+        let calls_function = matches!(mnemonic, "call" | "array_map" | "array_filter");
+        let operands = operands.iter()
+            .map(|operand| match operand.parse() {
+                Ok(value) => Ok(value),
+                Err(_) if calls_function => functions.iter()
+                    .position(|(_, function)| function.name == *operand)
+                    .map(|index| index as u32)
+                    .ok_or_else(|| format!("Unknown function {}", operand)),
+                Err(_) => resolve_label(operand, &labels).map(|pc| pc as u32),
+            })
+            .collect::<Result<Vec<u32>, String>>()
+            .map_err(error)?;
+        program.instructions.push(instruction(mnemonic, &operands, effect).map_err(error)?);
+    }
+
+    for (index, (number, declaration)) in functions.iter().enumerate() {
+        let error = |message: String| BytecodeError::Assembly { line: *number, message };
+        let start = starts[index];
+        let end = match declaration.len {
+            Some(len) => start + len,
+            None => starts.iter().copied().filter(|other| *other > start).min()
+                .unwrap_or(program.instructions.len()),
+        };
+        let body = program.instructions.get(start..end)
+            .ok_or_else(|| error(format!("Function {} runs past the end of the program", declaration.name)))?;
+
+        let mut function = BytecodeFunction::new(declaration.id, declaration.name.clone(), declaration.params);
+        function.local_count = declaration.locals;
+        function.start_pc = start;
+        for instruction in body {
+            function.add_instruction(instruction.clone());
+        }
+        if let Some(effect) = declaration.effect {
+            function.effect_grade = effect;
+        }
+        program.functions.push(function);
+    }
+
+    for (number, id, signature) in signatures {
+        let function = program.functions.iter_mut().find(|function| function.id == id)
+            .ok_or(BytecodeError::Assembly { line: number, message: format!("Function {} not found", id) })?;
+        function.set_signature(signature);
+    }
+
+    Ok(program)
+}
+
+/// A `.function` directive
+struct FunctionDeclaration {
+    id: u32,
+    name: String,
+    params: usize,
+    locals: usize,
+    start: Option<String>,
+    len: Option<usize>,
+    effect: Option<EffectGrade>,
+}
+
+fn parse_function(text: &str) -> Result<FunctionDeclaration, String> {
+    let mut words = text.split_whitespace();
+    let id = parse_number(words.next().unwrap_or(""))?;
+    let name = words.next().ok_or("Missing function name")?.to_string();
+    let mut declaration = FunctionDeclaration { id, name, params: 0, locals: 0, start: None, len: None, effect: None };
+    for word in words {
+        let (key, value) = word.split_once('=').ok_or_else(|| format!("Expected key=value, found {}", word))?;
+        match key {
+            "params" => declaration.params = parse_number(value)?,
+            "locals" => declaration.locals = parse_number(value)?,
+            "start" => declaration.start = Some(value.to_string()),
+            "len" => declaration.len = Some(parse_number(value)?),
+            "effect" => declaration.effect = Some(parse_effect(value).ok_or_else(|| format!("Unknown effect grade {}", value))?),
+            _ => return Err(format!("Unknown function attribute {}", key)),
+        }
+    }
+    Ok(declaration)
+}
+
+/// Mnemonic, operands and effect grade of an instruction
+fn parts(instruction: &Bytecode) -> (String, Vec<u32>, EffectGrade) {
+    // Variants are either `Name(effect)` or `Name(operands.., effect)`
+    let encoded = serde_json::to_value(instruction).unwrap_or_default();
+    let Some((variant, fields)) = encoded.as_object().and_then(|object| object.iter().next()) else {
+        return (format!("{:?}", instruction), Vec::new(), instruction.effect_grade());
+    };
+    let operands = match fields.as_array() {
+        Some(fields) => fields.iter().filter_map(|field| field.as_u64()).map(|operand| operand as u32).collect(),
+        None => Vec::new(),
+    };
+    (mnemonic(variant), operands, instruction.effect_grade())
+}
+
+/// Build an instruction from its mnemonic, operands and effect grade
+fn instruction(mnemonic: &str, operands: &[u32], effect: EffectGrade) -> Result<Bytecode, String> {
+    let effect = serde_json::to_value(effect).map_err(|e| e.to_string())?;
+    let fields = if operands.is_empty() {
+        effect
+    } else {
+        let mut fields: Vec<serde_json::Value> = operands.iter().map(|operand| (*operand).into()).collect();
+        fields.push(effect);
+        fields.into()
+    };
+    let encoded = serde_json::json!({ variant(mnemonic): fields });
+    serde_json::from_value(encoded).map_err(|e| {
+        if e.to_string().contains("unknown variant") {
+            format!("Unknown instruction {}", mnemonic)
+        } else {
+            format!("Wrong number of operands for {}", mnemonic)
+        }
+    })
+}
+
+/// `UnsignedShiftRight` -> `unsigned_shift_right`
+fn mnemonic(variant: &str) -> String {
+    let mut mnemonic = String::new();
+    for (i, c) in variant.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                mnemonic.push('_');
+            }
+            mnemonic.push(c.to_ascii_lowercase());
+        } else {
+            mnemonic.push(c);
+        }
+    }
+    mnemonic
+}
+
+/// `unsigned_shift_right` -> `UnsignedShiftRight`; a trailing `_` is kept, as in `Self_`
+fn variant(mnemonic: &str) -> String {
+    let mut variant: String = mnemonic.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect();
+    if mnemonic.ends_with('_') {
+        variant.push('_');
+    }
+    variant
+}
+
+fn effect_name(effect: EffectGrade) -> &'static str {
+    match effect {
+        EffectGrade::Pure => "pure",
+        EffectGrade::Read => "read",
+        EffectGrade::Write => "write",
+        EffectGrade::Memory => "memory",
+        EffectGrade::Send => "send",
+        EffectGrade::Spawn => "spawn",
+        EffectGrade::IO => "io",
+    }
+}
+
+fn parse_effect(name: &str) -> Option<EffectGrade> {
+    match name {
+        "pure" => Some(EffectGrade::Pure),
+        "read" => Some(EffectGrade::Read),
+        "write" => Some(EffectGrade::Write),
+        "memory" => Some(EffectGrade::Memory),
+        "send" => Some(EffectGrade::Send),
+        "spawn" => Some(EffectGrade::Spawn),
+        "io" => Some(EffectGrade::IO),
+        _ => None,
+    }
+}
+
+/// A constant as written in a `.const` directive
+fn write_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Int(i) => format!("int {}", i),
+        Value::UInt(u) => format!("uint {}", u),
+        Value::Float(f) => format!("float {:?}", f),
+        Value::Bool(b) => format!("bool {}", b),
+        Value::String(s) => format!("string {}", to_json(s)),
+        other => format!("value {}", to_json(other)),
+    }
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let (kind, literal) = split_word(text);
+    let invalid = |e: &dyn std::fmt::Display| format!("Invalid {} constant {}: {}", kind, literal, e);
+    match kind {
+        "null" => Ok(Value::Null),
+        "int" => literal.parse().map(Value::Int).map_err(|e| invalid(&e)),
+        "uint" => literal.parse().map(Value::UInt).map_err(|e| invalid(&e)),
+        "float" => literal.parse().map(Value::Float).map_err(|e| invalid(&e)),
+        "bool" => literal.parse().map(Value::Bool).map_err(|e| invalid(&e)),
+        "string" => serde_json::from_str(literal).map(Value::String).map_err(|e| invalid(&e)),
+        "value" => serde_json::from_str(literal).map_err(|e| invalid(&e)),
+        _ => Err(format!("Unknown constant type {}", kind)),
+    }
+}
+
+/// Labels for the starts of functions whose names can be written as labels
+fn function_labels(program: &BytecodeProgram) -> HashMap<usize, String> {
+    program.functions.iter()
+        .filter(|function| {
+            let name = function.name.as_str();
+            !name.is_empty()
+                && !name.contains(|c: char| c.is_whitespace() || c == ';' || c == ':')
+                && name.parse::<u64>().is_err()
+                && parse_effect(name).is_none()
+                && program.functions.iter().filter(|other| other.name == name).count() == 1
+        })
+        .map(|function| (function.start_pc, function.name.clone()))
+        .collect()
+}
+
+/// What an instruction's operand refers to
+fn annotation(program: &BytecodeProgram, labels: &HashMap<usize, String>, instruction: &Bytecode) -> Option<String> {
+    match instruction {
+        Bytecode::Const(index, _) | Bytecode::LoadGlobal(index, _) | Bytecode::StoreGlobal(index, _)
+        | Bytecode::StrSplit(index, _) | Bytecode::AddConst(index, _)
+        | Bytecode::CallNative(index, _) => program.constants.get(*index as usize).map(describe_value),
+        Bytecode::Call(index, _) | Bytecode::CallDirect(index, _, _) | Bytecode::ArrayMap(index, _)
+        | Bytecode::ArrayFilter(index, _) => {
+            program.functions.get(*index as usize).map(|function| function.name.clone())
+        }
+        Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)
+        | Bytecode::TryBegin(target, _) => {
+            let target = *target as usize;
+            if target == program.instructions.len() {
+                Some("-> end".to_string())
+            } else {
+                labels.get(&target).map(|label| format!("-> {}", label))
+            }
+        }
+        Bytecode::Load(index, _) | Bytecode::Store(index, _) => program.metadata.debug_info.as_ref()
+            .and_then(|info| info.variable_names.get(&(*index as usize)).cloned()),
+        _ => None,
+    }
+}
+
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => to_json(s),
+        other => other.to_string(),
+    }
+}
+
+fn resolve_label(operand: &str, labels: &HashMap<String, usize>) -> Result<usize, String> {
+    match operand.parse() {
+        Ok(pc) => Ok(pc),
+        Err(_) => labels.get(operand).copied().ok_or_else(|| format!("Unknown label {}", operand)),
+    }
+}
+
+fn expect_index(text: &str, expected: usize, what: &str) -> Result<(), String> {
+    match text.parse::<usize>() {
+        Ok(index) if index == expected => Ok(()),
+        _ => Err(format!("Expected {} {}, found {}", what, expected, text)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("Expected a number, found {:?}", text))
+}
+
+/// First word and the rest of the text
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+/// The line up to a `;` outside string literals
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::BytecodeVM;
+
+    const DOUBLE: &str = "\
+.program double
+.version 1.0.0
+.language unknown
+
+.const 0 int 21
+.const 1 int 2
+.const 2 string \"a; b\"
+
+.function 0 double params=1 locals=0 start=6 len=3
+
+.export double 0
+
+    0: const 0 pure            ; 21
+    1: call 0 pure             ; double
+    2: store 0 write
+    3: load 0 read
+    4: jump 9 pure             ; -> end
+    5: load_global 2 read      ; \"a; b\"
+double:
+    6: const 1 pure            ; 2
+    7: mul pure
+    8: ret pure
+";
+
+    #[test]
+    fn test_golden_round_trip() {
+        let program = assemble(DOUBLE).unwrap();
+        assert_eq!(program.constants[2], Value::String("a; b".to_string()));
+        assert_eq!(program.instructions[2], Bytecode::Store(0, EffectGrade::Write));
+        assert_eq!(program.functions[0].start_pc, 6);
+        assert_eq!(program.functions[0].instructions.len(), 3);
+        assert_eq!(program.exports.get("double"), Some(&0));
+        assert_eq!(disassemble(&program), DOUBLE);
+
+        let mut vm = BytecodeVM::new();
+        assert_eq!(vm.execute_program(&program).unwrap(), Value::Int(42));
+    }
+
+    #[test]
+    fn test_hand_written_program() {
+        let program = assemble("
+            .const 0 int 3
+            .const 1 int -1
+            .function 0 decrement
+
+            const 0                 ; counter
+            store 0
+            loop:
+            load 0
+            jump_if_not done
+            load 0
+            call decrement
+            store 0
+            jump loop
+            done:
+            load 0
+            jump end
+            decrement:
+            const 1
+            add
+            ret
+            end:
+        ").unwrap();
+
+        assert_eq!(program.instructions[3], Bytecode::JumpIfNot(8, EffectGrade::Pure));
+        assert_eq!(program.instructions[5], Bytecode::Call(0, EffectGrade::Pure));
+        assert_eq!(program.instructions[9], Bytecode::Jump(13, EffectGrade::Pure));
+        assert_eq!(program.functions[0].start_pc, 10);
+        assert_eq!(program.functions[0].instructions.len(), 3);
+
+        let mut vm = BytecodeVM::new();
+        assert_eq!(vm.execute_program(&program).unwrap(), Value::Int(0));
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        for instruction in [
+            Bytecode::UnsignedShiftRight(EffectGrade::Pure),
+            Bytecode::Self_(EffectGrade::Read),
+            Bytecode::FileOpen(1, 2, EffectGrade::IO),
+            Bytecode::GcCollect(EffectGrade::Memory),
+        ] {
+            let (mnemonic, operands, effect) = parts(&instruction);
+            assert_eq!(super::instruction(&mnemonic, &operands, effect).unwrap(), instruction);
+        }
+        assert_eq!(parts(&Bytecode::Self_(EffectGrade::Read)).0, "self_");
+    }
+
+    #[test]
+    fn test_assembly_errors() {
+        let error = |source: &str| assemble(source).unwrap_err().to_string();
+        assert_eq!(error("nop\nfrobnicate"), "Assembly error on line 2: Unknown instruction frobnicate");
+        assert_eq!(error("const pure"), "Assembly error on line 1: Wrong number of operands for const");
+        assert_eq!(error("jump nowhere"), "Assembly error on line 1: Unknown label nowhere");
+        assert_eq!(error("0: nop\n2: nop"), "Assembly error on line 2: Expected instruction 1, found 2");
+        assert_eq!(error(".const 1 int 3"), "Assembly error on line 1: Expected constant 0, found 1");
+        assert_eq!(error(".function 0 f"), "Assembly error on line 1: No label f for the start of the function");
+    }
+}
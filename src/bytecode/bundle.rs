@@ -0,0 +1,212 @@
+//! Bytecode bundles: a compiled project and its dependencies in one file
+//!
+//! `ream build` writes a bundle so `ream run` can start a project without
+//! parsing or compiling any source. `ream sign` adds a signature, which a
+//! VM with a signature policy checks before running the bundle.
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::bytecode::{BytecodeProgram, BytecodeVM, Value};
+use crate::bytecode::signing::{BundleSignature, SigningKey};
+use crate::error::{BytecodeError, BytecodeResult};
+
+/// File extension for bytecode bundles
+pub const BUNDLE_EXTENSION: &str = "reamb";
+
+/// Bundle format version, bumped when the layout changes
+pub const BUNDLE_FORMAT_VERSION: u32 = 3;
+
+/// Magic bytes at the start of every bundle file
+const BUNDLE_MAGIC: &[u8; 6] = b"REAMB\0";
+
+/// One compiled module of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleModule {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Source file the module was compiled from
+    pub source: String,
+    /// Compiled program
+    pub program: BytecodeProgram,
+}
+
+impl BundleModule {
+    /// Create a new bundle module
+    pub fn new(name: String, version: String, source: String, program: BytecodeProgram) -> Self {
+        BundleModule { name, version, source, program }
+    }
+}
+
+/// A compiled project ready to run without its sources
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BytecodeBundle {
+    /// Bundle format version
+    pub format_version: u32,
+    /// Dependency modules, in load order
+    pub dependencies: Vec<BundleModule>,
+    /// Entry module, executed after all dependencies
+    pub entry: BundleModule,
+    /// Build mode the bundle was produced with
+    pub build_mode: String,
+    /// Build time (seconds since the Unix epoch)
+    pub built_at: u64,
+    /// Signature over the rest of the bundle
+    pub signature: Option<BundleSignature>,
+}
+
+impl BytecodeBundle {
+    /// Create a bundle around its entry module
+    pub fn new(entry: BundleModule) -> Self {
+        BytecodeBundle {
+            format_version: BUNDLE_FORMAT_VERSION,
+            dependencies: Vec::new(),
+            entry,
+            build_mode: "release".to_string(),
+            built_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            signature: None,
+        }
+    }
+
+    /// Add a dependency; dependencies load in the order they are added
+    pub fn add_dependency(&mut self, module: BundleModule) {
+        self.dependencies.push(module);
+    }
+
+    /// Check whether a dependency is already bundled
+    pub fn has_dependency(&self, name: &str) -> bool {
+        self.dependencies.iter().any(|m| m.name == name)
+    }
+
+    /// All modules in load order, entry last
+    pub fn modules(&self) -> impl Iterator<Item = &BundleModule> {
+        self.dependencies.iter().chain(std::iter::once(&self.entry))
+    }
+
+    /// Total instruction count across all modules
+    pub fn instruction_count(&self) -> usize {
+        self.modules().map(|m| m.program.instructions.len()).sum()
+    }
+
+    /// Validate every module's program
+    pub fn validate(&self) -> BytecodeResult<()> {
+        for module in self.modules() {
+            module.program.validate().map_err(|e| {
+                BytecodeError::Bundle(format!("module {} is invalid: {}", module.name, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Serialize the bundle
+    pub fn to_bytes(&self) -> BytecodeResult<Vec<u8>> {
+        let body = bincode::serialize(self)
+            .map_err(|e| BytecodeError::Bundle(format!("serialization failed: {}", e)))?;
+
+        let mut bytes = Vec::with_capacity(BUNDLE_MAGIC.len() + 4 + body.len());
+        bytes.extend_from_slice(BUNDLE_MAGIC);
+        bytes.extend_from_slice(&BUNDLE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        Ok(bytes)
+    }
+
+    /// Deserialize a bundle, rejecting other files and other format versions
+    pub fn from_bytes(bytes: &[u8]) -> BytecodeResult<Self> {
+        let header_len = BUNDLE_MAGIC.len() + 4;
+        if bytes.len() < header_len || &bytes[..BUNDLE_MAGIC.len()] != BUNDLE_MAGIC {
+            return Err(BytecodeError::Bundle("not a REAM bytecode bundle".to_string()));
+        }
+
+        let mut version = [0u8; 4];
+        version.copy_from_slice(&bytes[BUNDLE_MAGIC.len()..header_len]);
+        let version = u32::from_le_bytes(version);
+        if version != BUNDLE_FORMAT_VERSION {
+            return Err(BytecodeError::Bundle(format!(
+                "unsupported bundle format version {} (expected {})",
+                version, BUNDLE_FORMAT_VERSION
+            )));
+        }
+
+        bincode::deserialize(&bytes[header_len..])
+            .map_err(|e| BytecodeError::Bundle(format!("corrupt bundle: {}", e)))
+    }
+
+    /// The bytes a signature covers: the serialized bundle without its signature
+    pub fn signed_bytes(&self) -> BytecodeResult<Vec<u8>> {
+        let unsigned = BytecodeBundle { signature: None, ..self.clone() };
+        unsigned.to_bytes()
+    }
+
+    /// Sign the bundle, replacing any earlier signature
+    pub fn sign(&mut self, key: &SigningKey) -> BytecodeResult<()> {
+        let signature = key.sign(&self.signed_bytes()?);
+        self.signature = Some(BundleSignature { public_key: key.public_key().to_vec(), signature });
+        Ok(())
+    }
+
+    /// Write the bundle to a file
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> BytecodeResult<()> {
+        let bytes = self.to_bytes()?;
+        std::fs::write(path.as_ref(), bytes)
+            .map_err(|e| BytecodeError::Bundle(format!("failed to write {}: {}", path.as_ref().display(), e)))
+    }
+
+    /// Read a bundle from a file
+    pub fn read_from<P: AsRef<Path>>(path: P) -> BytecodeResult<Self> {
+        let bytes = std::fs::read(path.as_ref())
+            .map_err(|e| BytecodeError::Bundle(format!("failed to read {}: {}", path.as_ref().display(), e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Run dependencies then the entry module, returning the entry's result;
+    /// fails if the VM's signature policy rejects the bundle
+    pub fn execute(&self, vm: &mut BytecodeVM) -> BytecodeResult<Value> {
+        vm.check_signature(self)?;
+        for module in &self.dependencies {
+            vm.execute_program(&module.program)?;
+        }
+        vm.execute_program(&self.entry.program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::Bytecode;
+    use crate::types::EffectGrade;
+
+    fn module(name: &str, value: i64) -> BundleModule {
+        let mut program = BytecodeProgram::new(name.to_string());
+        let const_id = program.add_constant(Value::Int(value));
+        program.add_instruction(Bytecode::Const(const_id, EffectGrade::Pure));
+        BundleModule::new(name.to_string(), "0.1.0".to_string(), format!("{}.tl", name), program)
+    }
+
+    #[test]
+    fn test_bundle_round_trip_and_execute() {
+        let mut bundle = BytecodeBundle::new(module("app", 42));
+        bundle.add_dependency(module("util", 1));
+        assert!(bundle.has_dependency("util"));
+        assert_eq!(bundle.modules().map(|m| m.name.as_str()).collect::<Vec<_>>(), vec!["util", "app"]);
+
+        let loaded = BytecodeBundle::from_bytes(&bundle.to_bytes().unwrap()).unwrap();
+        assert!(loaded.validate().is_ok());
+        assert_eq!(loaded.instruction_count(), 2);
+
+        let result = loaded.execute(&mut BytecodeVM::new()).unwrap();
+        assert!(matches!(result, Value::Int(42)));
+    }
+
+    #[test]
+    fn test_bundle_rejects_foreign_and_future_files() {
+        assert!(BytecodeBundle::from_bytes(b"(println \"hi\")").is_err());
+
+        let mut bytes = BytecodeBundle::new(module("app", 1)).to_bytes().unwrap();
+        bytes[BUNDLE_MAGIC.len()] = 99;
+        assert!(BytecodeBundle::from_bytes(&bytes).is_err());
+    }
+}
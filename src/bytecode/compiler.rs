@@ -26,6 +26,8 @@ pub struct BytecodeCompiler {
     labels: HashMap<String, u32>,
     /// Pending label references
     pending_labels: Vec<(usize, String)>,
+    /// Labels created so far, numbering the next
+    label_count: usize,
     /// Local variable mapping
     locals: HashMap<String, u32>,
     /// Current local count
@@ -43,6 +45,7 @@ impl BytecodeCompiler {
             program: BytecodeProgram::new(program_name),
             labels: HashMap::new(),
             pending_labels: Vec::new(),
+            label_count: 0,
             locals: HashMap::new(),
             local_count: 0,
             effect_stack: vec![EffectGrade::Pure],
@@ -96,9 +99,11 @@ impl BytecodeCompiler {
         }
     }
 
-    /// Create a label (alias for label_ref for compatibility)
+    /// Create a label name starting with `name` that no other label has,
+    /// so nested and repeated constructs do not share labels
     pub fn create_label(&mut self, name: &str) -> String {
-        name.to_string()
+        self.label_count += 1;
+        format!("{}_{}", name, self.label_count)
     }
 
     /// Place a label at current position (alias for define_label for compatibility)
@@ -160,6 +165,44 @@ impl BytecodeCompiler {
         }
     }
     
+    /// Declare a function whose code is emitted later, at the end of the
+    /// program's own instructions, so calls to it can be compiled first
+    pub fn declare_function(&mut self, name: String, param_count: usize) -> u32 {
+        let id = self.program.functions.len() as u32;
+        self.program.functions.push(BytecodeFunction::new(id, name, param_count));
+        id
+    }
+
+    /// A function added or declared so far
+    pub fn function(&self, id: u32) -> Option<&BytecodeFunction> {
+        self.program.functions.get(id as usize)
+    }
+
+    /// Start the code of a declared function at the next instruction
+    pub fn start_declared_function(&mut self, id: u32) -> BytecodeResult<()> {
+        let start_pc = self.program.instructions.len();
+        let function = self.program.functions.get_mut(id as usize)
+            .ok_or_else(|| BytecodeError::CompilationFailed(format!("Function {} not declared", id)))?;
+        function.start_pc = start_pc;
+        Ok(())
+    }
+
+    /// Finish a declared function, whose code is everything emitted since
+    /// it started
+    pub fn finish_declared_function(&mut self, id: u32) -> BytecodeResult<()> {
+        let function = self.program.functions.get_mut(id as usize)
+            .ok_or_else(|| BytecodeError::CompilationFailed(format!("Function {} not declared", id)))?;
+        function.instructions = self.program.instructions[function.start_pc..].to_vec();
+        function.local_count = function.instructions.iter()
+            .filter_map(|instruction| match instruction {
+                Bytecode::Load(index, _) | Bytecode::Store(index, _) => Some(*index as usize + 1),
+                _ => None,
+            })
+            .fold(function.param_count, usize::max);
+        function.effect_grade = function.analyze_effects();
+        Ok(())
+    }
+
     /// Set function signature
     pub fn set_function_signature(&mut self, signature: FunctionSignature) -> BytecodeResult<()> {
         if let Some(ref mut function) = self.current_function {
@@ -248,6 +291,7 @@ impl BytecodeCompiler {
             Bytecode::Jump(ref mut t, _) => *t = target,
             Bytecode::JumpIf(ref mut t, _) => *t = target,
             Bytecode::JumpIfNot(ref mut t, _) => *t = target,
+            Bytecode::TryBegin(ref mut t, _) => *t = target,
             _ => {}
         }
     }
@@ -306,7 +350,11 @@ impl BytecodeCompiler {
     
     /// Compile function call
     pub fn compile_call(&mut self, function_id: u32) {
-        self.emit(Bytecode::Call(function_id, EffectGrade::Send));
+        // A call has at least the effects of the function it calls
+        let effect = self.program.functions.iter()
+            .find(|function| function.id == function_id)
+            .map_or(EffectGrade::Send, |function| function.effect_grade.combine(EffectGrade::Send));
+        self.emit(Bytecode::Call(function_id, effect));
     }
     
     /// Compile return statement
@@ -326,19 +374,23 @@ impl BytecodeCompiler {
         self.emit(Bytecode::Jump(pc, EffectGrade::Pure));
     }
     
-    /// Compile actor spawn
+    /// Compile actor spawn of a function taking no arguments
     pub fn compile_spawn(&mut self, function_id: u32) {
-        self.emit(Bytecode::SpawnProcess(function_id, EffectGrade::Spawn));
+        let function = self.add_constant(Value::Function(function_id));
+        self.emit(Bytecode::Const(function, EffectGrade::Pure));
+        self.emit(Bytecode::Spawn(0, EffectGrade::Spawn));
     }
     
     /// Compile message send
     pub fn compile_send(&mut self, pid_local: u32, msg_local: u32) {
-        self.emit(Bytecode::SendMessage(pid_local, msg_local, EffectGrade::Send));
+        self.emit(Bytecode::Load(pid_local, EffectGrade::Pure));
+        self.emit(Bytecode::Load(msg_local, EffectGrade::Pure));
+        self.emit(Bytecode::Send(EffectGrade::Send));
     }
     
     /// Compile message receive
     pub fn compile_receive(&mut self) {
-        self.emit(Bytecode::ReceiveMessage(EffectGrade::Read));
+        self.emit(Bytecode::Receive(EffectGrade::Read));
     }
 
     /// Add a compiled program to this compiler
@@ -390,14 +442,16 @@ impl BytecodeCompiler {
         false
     }
 
-    /// Validate that all jump targets are valid
+    /// Validate that all jump targets are valid; jumping just past the
+    /// last instruction ends the program
     fn validate_jump_targets(&self) -> BytecodeResult<()> {
         let instructions = &self.program.instructions;
 
         for (i, instruction) in instructions.iter().enumerate() {
             match instruction {
-                Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) => {
-                    if *target as usize >= instructions.len() {
+                Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)
+                | Bytecode::TryBegin(target, _) => {
+                    if *target as usize > instructions.len() {
                         return Err(BytecodeError::CompilationFailed(
                             format!("Invalid jump target {} at instruction {}", target, i)
                         ));
@@ -0,0 +1,494 @@
+//! Bytecode debugger
+//!
+//! `Debugger` drives a `BytecodeVM` one instruction at a time through
+//! `BytecodeVM::step`. It stops at breakpoints, set on an instruction
+//! index or on the first instruction of a function, and when a watched
+//! value changes. Stepping over a `Call` runs the whole call.
+//!
+//! Code that only needs to observe execution installs `DebuggerHooks` on
+//! the VM instead. The VM calls them while it executes normally.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bytecode::{Bytecode, BytecodeFunction, BytecodeProgram, BytecodeVM, CallFrame, ExecutionContext, Value};
+use crate::error::{BytecodeError, BytecodeResult};
+
+/// What the VM does after `DebuggerHooks::before_instruction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Execute the instruction
+    Continue,
+    /// Stop execution with `BytecodeError::Aborted`
+    Abort,
+}
+
+/// Callbacks the VM makes while executing a program
+pub trait DebuggerHooks: Send {
+    /// Called before the instruction at `pc` executes
+    fn before_instruction(&mut self, _pc: usize, _instruction: &Bytecode, _context: &ExecutionContext) -> DebugAction {
+        DebugAction::Continue
+    }
+
+    /// Called after a call has entered `function`
+    fn on_call(&mut self, _function: &BytecodeFunction, _context: &ExecutionContext) {}
+
+    /// Called after a function has returned
+    fn on_return(&mut self, _context: &ExecutionContext) {}
+
+    /// Called when the instruction at `pc` fails
+    fn on_error(&mut self, _pc: usize, _error: &BytecodeError) {}
+}
+
+/// Where execution stops
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Before the instruction at this index
+    Instruction(usize),
+    /// Before the first instruction of the function with this name
+    Function(String),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Instruction(pc) => write!(f, "{}", pc),
+            Breakpoint::Function(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = BytecodeError;
+
+    /// An instruction index, or otherwise a function name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(BytecodeError::InvalidOperand("Empty breakpoint".to_string()));
+        }
+        Ok(match s.parse() {
+            Ok(pc) => Breakpoint::Instruction(pc),
+            Err(_) => Breakpoint::Function(s.to_string()),
+        })
+    }
+}
+
+/// A value watched for changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watch {
+    /// A local variable of the current frame
+    Local(usize),
+    /// A stack slot, counted from the top
+    Stack(usize),
+    /// The program counter
+    Pc,
+    /// The call depth
+    Depth,
+}
+
+impl Watch {
+    /// The watched value, if it exists in `context`
+    pub fn value(&self, context: &ExecutionContext) -> Option<Value> {
+        match self {
+            Watch::Local(index) => context.get_local(*index).ok().cloned(),
+            Watch::Stack(depth) => context.stack.iter().rev().nth(*depth).cloned(),
+            Watch::Pc => Some(Value::UInt(context.pc as u64)),
+            Watch::Depth => Some(Value::UInt(context.call_stack.len() as u64)),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Local(index) => write!(f, "local[{}]", index),
+            Watch::Stack(0) => write!(f, "top"),
+            Watch::Stack(depth) => write!(f, "stack[{}]", depth),
+            Watch::Pc => write!(f, "pc"),
+            Watch::Depth => write!(f, "depth"),
+        }
+    }
+}
+
+impl FromStr for Watch {
+    type Err = BytecodeError;
+
+    /// `local[N]`, `stack[N]`, `top`, `pc` or `depth`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let indexed = |prefix: &str| {
+            s.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_prefix('['))
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|index| index.trim().parse().ok())
+        };
+        match s {
+            "top" => Ok(Watch::Stack(0)),
+            "pc" => Ok(Watch::Pc),
+            "depth" => Ok(Watch::Depth),
+            _ => indexed("local").map(Watch::Local)
+                .or_else(|| indexed("stack").map(Watch::Stack))
+                .ok_or_else(|| BytecodeError::InvalidOperand(format!("Unknown watch expression: {}", s))),
+        }
+    }
+}
+
+/// Why the debugger stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    /// Reached a breakpoint at this instruction
+    Breakpoint(usize),
+    /// Finished a step
+    Step,
+    /// A watched value changed
+    WatchChanged { watch: Watch, old: Option<Value>, new: Option<Value> },
+    /// The program finished with this value
+    Finished(Value),
+    /// The program failed
+    Error(String),
+}
+
+/// Steps through a bytecode program
+pub struct Debugger {
+    vm: BytecodeVM,
+    program: BytecodeProgram,
+    breakpoints: Vec<Breakpoint>,
+    /// Watches and their last seen values
+    watches: Vec<(Watch, Option<Value>)>,
+    /// How the program ended, once it has
+    outcome: Option<StopReason>,
+}
+
+impl Debugger {
+    /// Debug `program`, stopped before its first instruction
+    pub fn new(program: BytecodeProgram) -> Self {
+        let mut vm = BytecodeVM::new();
+        vm.begin();
+        Debugger {
+            vm,
+            program,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    /// The program being debugged
+    pub fn program(&self) -> &BytecodeProgram {
+        &self.program
+    }
+
+    /// Set a breakpoint, checking that it refers to an instruction or function
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> BytecodeResult<()> {
+        match &breakpoint {
+            Breakpoint::Instruction(pc) if *pc >= self.program.instructions.len() => {
+                return Err(BytecodeError::InvalidOperand(format!("No instruction at {}", pc)));
+            }
+            Breakpoint::Function(name) if self.function(name).is_none() => {
+                return Err(BytecodeError::InvalidOperand(format!("Function {} not found", name)));
+            }
+            _ => {}
+        }
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+        Ok(())
+    }
+
+    /// Remove a breakpoint, returning whether it was set
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|existing| existing != breakpoint);
+        self.breakpoints.len() != before
+    }
+
+    /// Breakpoints set
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Watch a value for changes
+    pub fn watch(&mut self, watch: Watch) {
+        if self.watches.iter().all(|(existing, _)| *existing != watch) {
+            let value = watch.value(self.vm.context());
+            self.watches.push((watch, value));
+        }
+    }
+
+    /// Stop watching a value, returning whether it was watched
+    pub fn unwatch(&mut self, watch: &Watch) -> bool {
+        let before = self.watches.len();
+        self.watches.retain(|(existing, _)| existing != watch);
+        self.watches.len() != before
+    }
+
+    /// Watches and their current values
+    pub fn watches(&self) -> Vec<(Watch, Option<Value>)> {
+        self.watches.iter()
+            .map(|(watch, _)| (watch.clone(), watch.value(self.vm.context())))
+            .collect()
+    }
+
+    /// Execute one instruction
+    pub fn step(&mut self) -> StopReason {
+        self.execute().unwrap_or(StopReason::Step)
+    }
+
+    /// Execute one instruction, running a call to completion
+    pub fn step_over(&mut self) -> StopReason {
+        match self.current_instruction() {
+            Some(Bytecode::Call(..) | Bytecode::CallDirect(..)) => {
+                let depth = self.vm.context().call_stack.len();
+                self.run_until(|context| context.call_stack.len() <= depth)
+            }
+            _ => self.step(),
+        }
+    }
+
+    /// Run until the current function returns
+    pub fn step_out(&mut self) -> StopReason {
+        match self.vm.context().call_stack.len() {
+            0 => self.resume(),
+            depth => self.run_until(|context| context.call_stack.len() < depth),
+        }
+    }
+
+    /// Run until a breakpoint, a watch change or the end of the program
+    pub fn resume(&mut self) -> StopReason {
+        self.run_until(|_| false)
+    }
+
+    /// Start the program again, keeping breakpoints and watches
+    pub fn restart(&mut self) {
+        self.vm.begin();
+        self.outcome = None;
+        for (watch, value) in &mut self.watches {
+            *value = watch.value(self.vm.context());
+        }
+    }
+
+    /// How the program ended, if it has
+    pub fn outcome(&self) -> Option<&StopReason> {
+        self.outcome.as_ref()
+    }
+
+    /// Whether the program has finished or failed
+    pub fn is_finished(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// The execution context
+    pub fn context(&self) -> &ExecutionContext {
+        self.vm.context()
+    }
+
+    /// Index of the next instruction
+    pub fn pc(&self) -> usize {
+        self.vm.context().pc
+    }
+
+    /// The next instruction, if any
+    pub fn current_instruction(&self) -> Option<&Bytecode> {
+        self.program.instructions.get(self.pc())
+    }
+
+    /// The value stack, bottom first
+    pub fn stack(&self) -> &[Value] {
+        &self.vm.context().stack
+    }
+
+    /// Local variables of the current frame
+    pub fn locals(&self) -> &[Value] {
+        let context = self.vm.context();
+        context.locals.get(context.local_base()..).unwrap_or(&[])
+    }
+
+    /// Active calls, outermost first
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.vm.context().call_stack
+    }
+
+    /// The function whose instructions include `pc`
+    pub fn function_at(&self, pc: usize) -> Option<&BytecodeFunction> {
+        self.program.functions.iter()
+            .find(|function| (function.start_pc..function.start_pc + function.instructions.len()).contains(&pc))
+    }
+
+    fn function(&self, name: &str) -> Option<&BytecodeFunction> {
+        self.program.functions.iter().find(|function| function.name == name)
+    }
+
+    fn is_breakpoint(&self, pc: usize) -> bool {
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Instruction(at) => *at == pc,
+            Breakpoint::Function(name) => self.function(name).map(|function| function.start_pc) == Some(pc),
+        })
+    }
+
+    /// Execute instructions until one stops the debugger or `done` holds;
+    /// a breakpoint at the starting instruction does not stop it
+    fn run_until(&mut self, done: impl Fn(&ExecutionContext) -> bool) -> StopReason {
+        loop {
+            if let Some(reason) = self.execute() {
+                return reason;
+            }
+            let pc = self.pc();
+            if self.is_breakpoint(pc) {
+                return StopReason::Breakpoint(pc);
+            }
+            if done(self.vm.context()) {
+                return StopReason::Step;
+            }
+        }
+    }
+
+    /// Execute one instruction, returning why to stop if the program ended
+    /// or a watched value changed
+    fn execute(&mut self) -> Option<StopReason> {
+        if let Some(outcome) = &self.outcome {
+            return Some(outcome.clone());
+        }
+
+        let running = match self.vm.step(&self.program) {
+            Ok(running) => running,
+            Err(e) => {
+                self.outcome = Some(StopReason::Error(e.to_string()));
+                return self.outcome.clone();
+            }
+        };
+
+        let mut changed = None;
+        for (watch, value) in &mut self.watches {
+            let new = watch.value(self.vm.context());
+            if new != *value {
+                let old = std::mem::replace(value, new.clone());
+                changed.get_or_insert(StopReason::WatchChanged { watch: watch.clone(), old, new });
+            }
+        }
+        if changed.is_some() {
+            return changed;
+        }
+
+        if !running {
+            let result = self.stack().last().cloned().unwrap_or(Value::Null);
+            self.outcome = Some(StopReason::Finished(result));
+            return self.outcome.clone();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::types::EffectGrade;
+
+    /// Main code computes `double(21)` into local 0; `double` starts at 5
+    fn program() -> BytecodeProgram {
+        let mut program = BytecodeProgram::new("debug".to_string());
+        let n = program.add_constant(Value::Int(21));
+        let two = program.add_constant(Value::Int(2));
+        program.add_instruction(Bytecode::Const(n, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Call(0, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Store(0, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Load(0, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Jump(8, EffectGrade::Pure));
+
+        let mut double = BytecodeFunction::new(0, "double".to_string(), 1);
+        double.add_instruction(Bytecode::Const(two, EffectGrade::Pure));
+        double.add_instruction(Bytecode::Mul(EffectGrade::Pure));
+        double.add_instruction(Bytecode::Ret(EffectGrade::Pure));
+        program.add_function(double);
+        program
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let mut debugger = Debugger::new(program());
+        debugger.add_breakpoint("double".parse().unwrap()).unwrap();
+        assert!(debugger.add_breakpoint(Breakpoint::Instruction(8)).is_err());
+        assert!(debugger.add_breakpoint(Breakpoint::Function("triple".to_string())).is_err());
+
+        assert_eq!(debugger.resume(), StopReason::Breakpoint(5));
+        assert_eq!(debugger.call_stack().len(), 1);
+        assert_eq!(debugger.function_at(debugger.pc()).map(|f| f.name.as_str()), Some("double"));
+
+        assert_eq!(debugger.step(), StopReason::Step);
+        assert_eq!(debugger.stack(), &[Value::Int(21), Value::Int(2)]);
+
+        assert_eq!(debugger.resume(), StopReason::Finished(Value::Int(42)));
+        assert!(debugger.is_finished());
+        assert_eq!(debugger.step(), StopReason::Finished(Value::Int(42)));
+
+        // Stepping over the call runs it without stopping inside
+        debugger.restart();
+        assert!(debugger.remove_breakpoint(&Breakpoint::Function("double".to_string())));
+        debugger.step();
+        assert_eq!(debugger.step_over(), StopReason::Step);
+        assert_eq!(debugger.pc(), 2);
+        assert!(debugger.call_stack().is_empty());
+        assert_eq!(debugger.stack(), &[Value::Int(42)]);
+    }
+
+    #[test]
+    fn test_watches() {
+        let mut debugger = Debugger::new(program());
+        debugger.watch("local[0]".parse().unwrap());
+        assert_eq!(Watch::from_str("stack[1]").unwrap(), Watch::Stack(1));
+        assert_eq!(Watch::Stack(0).to_string(), "top");
+        assert!(Watch::from_str("heap").is_err());
+
+        assert_eq!(debugger.resume(), StopReason::WatchChanged {
+            watch: Watch::Local(0),
+            old: None,
+            new: Some(Value::Int(42)),
+        });
+        assert_eq!(debugger.pc(), 3);
+        assert_eq!(debugger.locals(), &[Value::Int(42)]);
+    }
+
+    #[test]
+    fn test_error_stops_debugger() {
+        let mut program = BytecodeProgram::new("broken".to_string());
+        program.add_instruction(Bytecode::Add(EffectGrade::Pure));
+        let mut debugger = Debugger::new(program);
+
+        assert!(matches!(debugger.resume(), StopReason::Error(e) if e.contains("Stack underflow")));
+        assert!(debugger.is_finished());
+    }
+
+    #[derive(Default)]
+    struct Trace {
+        events: Arc<Mutex<Vec<String>>>,
+        abort_at: Option<usize>,
+    }
+
+    impl DebuggerHooks for Trace {
+        fn before_instruction(&mut self, pc: usize, _instruction: &Bytecode, _context: &ExecutionContext) -> DebugAction {
+            self.events.lock().unwrap().push(pc.to_string());
+            if self.abort_at == Some(pc) { DebugAction::Abort } else { DebugAction::Continue }
+        }
+
+        fn on_call(&mut self, function: &BytecodeFunction, _context: &ExecutionContext) {
+            self.events.lock().unwrap().push(format!("call {}", function.name));
+        }
+
+        fn on_return(&mut self, _context: &ExecutionContext) {
+            self.events.lock().unwrap().push("ret".to_string());
+        }
+    }
+
+    #[test]
+    fn test_vm_hooks() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut vm = BytecodeVM::new();
+        vm.set_hooks(Some(Box::new(Trace { events: events.clone(), abort_at: None })));
+        assert_eq!(vm.execute_program(&program()).unwrap(), Value::Int(42));
+        assert_eq!(*events.lock().unwrap(), ["0", "1", "call double", "5", "6", "7", "ret", "2", "3", "4"]);
+
+        vm.set_hooks(Some(Box::new(Trace { events: events.clone(), abort_at: Some(6) })));
+        assert!(matches!(vm.execute_program(&program()), Err(BytecodeError::Aborted(6))));
+    }
+}
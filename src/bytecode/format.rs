@@ -0,0 +1,421 @@
+//! On-disk format of bytecode programs
+//!
+//! A program file is a header followed by sections. All integers are
+//! little-endian.
+//!
+//! ```text
+//! header   magic "REAMBC\0\0" | major u16 | minor u16 | section count u32
+//! section  kind u16 | flags u16 | length u32 | crc32 u32 | payload
+//! ```
+//!
+//! Each section holds one part of the program, encoded with bincode, and
+//! is checksummed on its own. Readers accept any minor version of their
+//! major version. They skip sections they do not know unless the section
+//! is flagged required. A new minor version may add sections. Changing
+//! what an existing section holds needs a new major version.
+//!
+//! Loaded programs are structurally validated before they are returned;
+//! `load_verified` also runs a `BytecodeVerifier`.
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::bytecode::{Bytecode, BytecodeFunction, BytecodeProgram, BytecodeVerifier, Value};
+use crate::bytecode::program::{DebugInfo, ExecutionBackend, ImportInfo, ProgramMetadata};
+use crate::error::{BytecodeError, BytecodeResult};
+
+/// Magic bytes at the start of every program file
+pub const PROGRAM_MAGIC: &[u8; 8] = b"REAMBC\0\0";
+
+/// Major format version; readers reject other majors
+pub const FORMAT_MAJOR: u16 = 1;
+
+/// Minor format version; bumped when sections are added
+pub const FORMAT_MINOR: u16 = 1;
+
+/// Section flag: readers that do not know the section must reject the file
+pub const SECTION_REQUIRED: u16 = 1;
+
+const HEADER_LEN: usize = PROGRAM_MAGIC.len() + 2 + 2 + 4;
+const SECTION_HEADER_LEN: usize = 2 + 2 + 4 + 4;
+
+/// Sections of a program file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum SectionKind {
+    /// Name, version and compiler of the program
+    Metadata = 1,
+    /// Constant pool
+    Constants = 2,
+    /// Instructions
+    Code = 3,
+    /// Function table
+    Functions = 4,
+    /// Global variable names
+    Globals = 5,
+    /// Export table
+    Exports = 6,
+    /// Import table
+    Imports = 7,
+    /// Source locations and variable names
+    DebugInfo = 8,
+    /// Execution backend, since 1.1
+    Execution = 9,
+}
+
+impl SectionKind {
+    const ALL: [SectionKind; 9] = [
+        SectionKind::Metadata,
+        SectionKind::Constants,
+        SectionKind::Code,
+        SectionKind::Functions,
+        SectionKind::Globals,
+        SectionKind::Exports,
+        SectionKind::Imports,
+        SectionKind::DebugInfo,
+        SectionKind::Execution,
+    ];
+
+    fn from_u16(kind: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| *known as u16 == kind)
+    }
+
+    /// Whether a file is invalid without this section
+    fn is_required(self) -> bool {
+        matches!(self, SectionKind::Metadata | SectionKind::Constants | SectionKind::Code)
+    }
+}
+
+/// Program metadata without the debug info, which has its own section
+#[derive(Serialize, Deserialize)]
+struct MetadataSection {
+    name: String,
+    version: String,
+    source_language: String,
+    compiled_at: u64,
+    compiler_version: String,
+}
+
+impl BytecodeProgram {
+    /// Encode the program in the on-disk format
+    pub fn to_bytes(&self) -> BytecodeResult<Vec<u8>> {
+        let metadata = MetadataSection {
+            name: self.metadata.name.clone(),
+            version: self.metadata.version.clone(),
+            source_language: self.metadata.source_language.clone(),
+            compiled_at: self.metadata.compiled_at,
+            compiler_version: self.metadata.compiler_version.clone(),
+        };
+
+        let mut sections = vec![
+            (SectionKind::Metadata, encode(&metadata)?),
+            (SectionKind::Constants, encode(&self.constants)?),
+            (SectionKind::Code, encode(&self.instructions)?),
+            (SectionKind::Functions, encode(&self.functions)?),
+            (SectionKind::Globals, encode(&self.globals)?),
+            (SectionKind::Exports, encode(&self.exports)?),
+            (SectionKind::Imports, encode(&self.imports)?),
+        ];
+        if let Some(debug_info) = &self.metadata.debug_info {
+            sections.push((SectionKind::DebugInfo, encode(debug_info)?));
+        }
+        if self.metadata.backend != ExecutionBackend::Stack {
+            sections.push((SectionKind::Execution, encode(&self.metadata.backend)?));
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(PROGRAM_MAGIC);
+        bytes.extend_from_slice(&FORMAT_MAJOR.to_le_bytes());
+        bytes.extend_from_slice(&FORMAT_MINOR.to_le_bytes());
+        bytes.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+        for (kind, payload) in sections {
+            let flags = if kind.is_required() { SECTION_REQUIRED } else { 0 };
+            write_section(&mut bytes, kind as u16, flags, &payload)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a program from the on-disk format and validate it
+    pub fn from_bytes(bytes: &[u8]) -> BytecodeResult<Self> {
+        let sections = read_sections(bytes)?;
+        let section = |kind: SectionKind| sections.get(&(kind as u16)).copied();
+        let required = |kind: SectionKind| {
+            section(kind).ok_or_else(|| format_error(format!("missing {:?} section", kind)))
+        };
+
+        let metadata: MetadataSection = decode(SectionKind::Metadata, required(SectionKind::Metadata)?)?;
+        let constants: Vec<Value> = decode(SectionKind::Constants, required(SectionKind::Constants)?)?;
+        let instructions: Vec<Bytecode> = decode(SectionKind::Code, required(SectionKind::Code)?)?;
+        let functions: Vec<BytecodeFunction> = decode_or_default(SectionKind::Functions, section(SectionKind::Functions))?;
+        let globals: Vec<String> = decode_or_default(SectionKind::Globals, section(SectionKind::Globals))?;
+        let exports: HashMap<String, u32> = decode_or_default(SectionKind::Exports, section(SectionKind::Exports))?;
+        let imports: HashMap<String, ImportInfo> = decode_or_default(SectionKind::Imports, section(SectionKind::Imports))?;
+        let debug_info: Option<DebugInfo> = section(SectionKind::DebugInfo)
+            .map(|payload| decode(SectionKind::DebugInfo, payload))
+            .transpose()?;
+        let backend: ExecutionBackend = decode_or_default(SectionKind::Execution, section(SectionKind::Execution))?;
+
+        let program = BytecodeProgram {
+            instructions,
+            constants,
+            functions,
+            globals,
+            exports,
+            imports,
+            metadata: ProgramMetadata {
+                name: metadata.name,
+                version: metadata.version,
+                source_language: metadata.source_language,
+                compiled_at: metadata.compiled_at,
+                compiler_version: metadata.compiler_version,
+                debug_info,
+                backend,
+            },
+        };
+        program.validate()?;
+        Ok(program)
+    }
+
+    /// Write the program to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> BytecodeResult<()> {
+        let bytes = self.to_bytes()?;
+        std::fs::write(path.as_ref(), bytes)
+            .map_err(|e| format_error(format!("failed to write {}: {}", path.as_ref().display(), e)))
+    }
+
+    /// Read a program from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> BytecodeResult<Self> {
+        let bytes = std::fs::read(path.as_ref())
+            .map_err(|e| format_error(format!("failed to read {}: {}", path.as_ref().display(), e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Read a program from a file and check it with `verifier`
+    pub fn load_verified<P: AsRef<Path>>(path: P, verifier: &mut BytecodeVerifier) -> BytecodeResult<Self> {
+        let program = Self::load(path)?;
+        verifier.verify(&program)?;
+        Ok(program)
+    }
+
+    /// Whether `bytes` start like a program file
+    pub fn is_program_file(bytes: &[u8]) -> bool {
+        bytes.starts_with(PROGRAM_MAGIC)
+    }
+}
+
+fn format_error(message: String) -> BytecodeError {
+    BytecodeError::Format(message)
+}
+
+fn encode<T: Serialize>(value: &T) -> BytecodeResult<Vec<u8>> {
+    bincode::serialize(value).map_err(|e| format_error(format!("serialization failed: {}", e)))
+}
+
+fn decode<T: DeserializeOwned>(kind: SectionKind, payload: &[u8]) -> BytecodeResult<T> {
+    bincode::deserialize(payload).map_err(|e| format_error(format!("corrupt {:?} section: {}", kind, e)))
+}
+
+fn decode_or_default<T: DeserializeOwned + Default>(kind: SectionKind, payload: Option<&[u8]>) -> BytecodeResult<T> {
+    payload.map(|payload| decode(kind, payload)).transpose().map(Option::unwrap_or_default)
+}
+
+fn write_section(bytes: &mut Vec<u8>, kind: u16, flags: u16, payload: &[u8]) -> BytecodeResult<()> {
+    let length = u32::try_from(payload.len())
+        .map_err(|_| format_error(format!("section {} is too large", kind)))?;
+    bytes.extend_from_slice(&kind.to_le_bytes());
+    bytes.extend_from_slice(&flags.to_le_bytes());
+    bytes.extend_from_slice(&length.to_le_bytes());
+    bytes.extend_from_slice(&crc32(payload).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Check the header and checksums, returning the payloads of known sections by kind
+fn read_sections(bytes: &[u8]) -> BytecodeResult<HashMap<u16, &[u8]>> {
+    if bytes.len() < HEADER_LEN || !BytecodeProgram::is_program_file(bytes) {
+        return Err(format_error("not a REAM bytecode program".to_string()));
+    }
+    let mut reader = Reader { bytes, offset: PROGRAM_MAGIC.len() };
+    let major = reader.u16()?;
+    let minor = reader.u16()?;
+    if major != FORMAT_MAJOR {
+        return Err(format_error(format!(
+            "unsupported format version {}.{} (expected {}.x)", major, minor, FORMAT_MAJOR
+        )));
+    }
+
+    let count = reader.u32()?;
+    let mut sections = HashMap::new();
+    for _ in 0..count {
+        let kind = reader.u16()?;
+        let flags = reader.u16()?;
+        let length = reader.u32()? as usize;
+        let checksum = reader.u32()?;
+        let payload = reader.take(length)?;
+
+        let Some(known) = SectionKind::from_u16(kind) else {
+            if flags & SECTION_REQUIRED != 0 {
+                return Err(format_error(format!(
+                    "required section {} is not supported by this reader (format {}.{})", kind, major, minor
+                )));
+            }
+            continue;
+        };
+        if crc32(payload) != checksum {
+            return Err(format_error(format!("checksum mismatch in {:?} section", known)));
+        }
+        if sections.insert(kind, payload).is_some() {
+            return Err(format_error(format!("duplicate {:?} section", known)));
+        }
+    }
+    Ok(sections)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> BytecodeResult<&'a [u8]> {
+        let end = self.offset.checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format_error("truncated file".to_string()))?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> BytecodeResult<u16> {
+        let mut buf = [0u8; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u32(&mut self) -> BytecodeResult<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+}
+
+/// CRC-32 (IEEE) of `bytes`
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EffectGrade;
+
+    fn program() -> BytecodeProgram {
+        let mut program = BytecodeProgram::new("saved".to_string());
+        let n = program.add_constant(Value::Int(21));
+        let two = program.add_constant(Value::Int(2));
+        program.add_instruction(Bytecode::Const(n, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Call(0, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Jump(6, EffectGrade::Pure));
+        let mut double = BytecodeFunction::new(0, "double".to_string(), 1);
+        double.add_instruction(Bytecode::Const(two, EffectGrade::Pure));
+        double.add_instruction(Bytecode::Mul(EffectGrade::Pure));
+        double.add_instruction(Bytecode::Ret(EffectGrade::Pure));
+        program.add_function(double);
+        program.export_function("double".to_string(), 0).unwrap();
+        program.set_debug_info(DebugInfo {
+            source_files: vec!["saved.tl".to_string()],
+            line_mapping: HashMap::from([(0, (0, 1))]),
+            variable_names: HashMap::new(),
+        });
+        program
+    }
+
+    /// Offset of the first section of `kind` in `bytes`
+    fn section_offset(bytes: &[u8], kind: SectionKind) -> usize {
+        let mut offset = HEADER_LEN;
+        loop {
+            let length = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            if u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) == kind as u16 {
+                return offset;
+            }
+            offset += SECTION_HEADER_LEN + length;
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("saved.reambc");
+        program().save(&path).unwrap();
+
+        let loaded = BytecodeProgram::load(&path).unwrap();
+        assert_eq!(loaded.instructions, program().instructions);
+        assert_eq!(loaded.constants, program().constants);
+        assert_eq!(loaded.functions[0].name, "double");
+        assert_eq!(loaded.exports.get("double"), Some(&0));
+        assert_eq!(loaded.get_source_location(0), Some(("saved.tl".to_string(), 1)));
+
+        let mut vm = crate::bytecode::BytecodeVM::new();
+        assert_eq!(vm.execute_program(&loaded).unwrap(), Value::Int(42));
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_forward_compatibility() {
+        let mut bytes = program().to_bytes().unwrap();
+        // A newer minor version with an optional section this reader does not know
+        bytes[PROGRAM_MAGIC.len() + 2] = 7;
+        bytes[PROGRAM_MAGIC.len() + 4] += 1;
+        write_section(&mut bytes, 99, 0, b"from the future").unwrap();
+        assert_eq!(BytecodeProgram::from_bytes(&bytes).unwrap().instructions.len(), 6);
+
+        // ...unless the reader is required to understand it
+        let mut bytes = program().to_bytes().unwrap();
+        bytes[PROGRAM_MAGIC.len() + 4] += 1;
+        write_section(&mut bytes, 99, SECTION_REQUIRED, b"from the future").unwrap();
+        assert!(BytecodeProgram::from_bytes(&bytes).is_err());
+
+        let mut bytes = program().to_bytes().unwrap();
+        bytes[PROGRAM_MAGIC.len()] = 2;
+        let err = BytecodeProgram::from_bytes(&bytes).unwrap_err().to_string();
+        assert!(err.contains(&format!("unsupported format version 2.{}", FORMAT_MINOR)), "{}", err);
+    }
+
+    #[test]
+    fn test_execution_backend() {
+        let bytes = program().to_bytes().unwrap();
+        assert_eq!(BytecodeProgram::from_bytes(&bytes).unwrap().metadata.backend, ExecutionBackend::Stack);
+
+        let mut register = program();
+        register.metadata.backend = ExecutionBackend::Register;
+        let loaded = BytecodeProgram::from_bytes(&register.to_bytes().unwrap()).unwrap();
+        assert_eq!(loaded.metadata.backend, ExecutionBackend::Register);
+    }
+
+    #[test]
+    fn test_corruption_is_detected() {
+        assert!(BytecodeProgram::from_bytes(b"(println \"hi\")").is_err());
+
+        let bytes = program().to_bytes().unwrap();
+        assert!(BytecodeProgram::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut corrupt = bytes.clone();
+        let offset = section_offset(&corrupt, SectionKind::Code);
+        corrupt[offset + SECTION_HEADER_LEN] ^= 0xff;
+        let err = BytecodeProgram::from_bytes(&corrupt).unwrap_err().to_string();
+        assert!(err.contains("checksum mismatch in Code section"), "{}", err);
+
+        // A structurally invalid program is rejected after decoding
+        let mut invalid = program();
+        invalid.instructions.push(Bytecode::Const(9, EffectGrade::Pure));
+        assert!(BytecodeProgram::from_bytes(&invalid.to_bytes().unwrap()).is_err());
+    }
+}
@@ -121,7 +121,7 @@ pub enum Bytecode {
     StrSplit(u32, EffectGrade), // delimiter constant index
 
     // List operations
-    /// Create empty list
+    /// Create list from stack elements (element count on top)
     ListNew(EffectGrade),
     /// Get list length
     ListLen(EffectGrade),
@@ -158,7 +158,7 @@ pub enum Bytecode {
     /// Get map size
     MapSize(EffectGrade),
     
-    // Actor operations
+    // Actor operations, superseded in the VM by the process operations below
     /// Spawn a new process
     SpawnProcess(u32, EffectGrade),
     /// Send message to process
@@ -280,6 +280,74 @@ pub enum Bytecode {
     
     // No-op
     Nop(EffectGrade),
+
+    // Tuple operations, kept last so earlier instructions encode as before
+    /// Create tuple from stack elements
+    TupleNew(u32, EffectGrade), // element count
+
+    // Superinstructions and inline caches written by the optimizer, kept last for the same reason
+    /// Add a constant to the value on top of the stack (`const` then `add`)
+    AddConst(u32, EffectGrade), // constant index
+    /// Push the sum of two locals (`load`, `load` then `add`)
+    AddLocals(u32, u32, EffectGrade), // local indices
+    /// Call a function whose start has been resolved into the instruction
+    CallDirect(u32, u32, EffectGrade), // function index, start pc
+
+    // Native calls, kept last for the same reason
+    /// Call a registered native function, named by a string constant
+    CallNative(u32, EffectGrade), // name constant index
+
+    // Process operations, kept last for the same reason
+    /// Spawn a function as a process: pops that many arguments, then the function; pushes the new pid
+    Spawn(u32, EffectGrade), // argument count
+    /// Send a message: pops the message, then the pid; pushes the message back
+    Send(EffectGrade),
+    /// Push the next message, suspending until one arrives
+    Receive(EffectGrade),
+    /// Suspend until the next quantum
+    Yield(EffectGrade),
+    /// Push the running process's pid
+    SelfPid(EffectGrade),
+
+    // Exception handling, kept last for the same reason
+    /// Install a handler for the code up to the matching `try_end`
+    TryBegin(u32, EffectGrade), // handler pc
+    /// Remove the innermost handler
+    TryEnd(EffectGrade),
+    /// Pop a value and unwind to the innermost handler, which gets it pushed
+    Throw(EffectGrade),
+}
+
+/// Target types of the `Cast` instruction
+pub mod cast {
+    /// Signed integer, truncating floats and parsing strings
+    pub const INT: u32 = 0;
+    /// Unsigned integer, rejecting negative values
+    pub const UINT: u32 = 1;
+    /// Floating point number
+    pub const FLOAT: u32 = 2;
+    /// Truthiness of the value
+    pub const BOOL: u32 = 3;
+    /// Printed form of the value
+    pub const STRING: u32 = 4;
+    /// Integer if the value reads as one, otherwise float
+    pub const NUMBER: u32 = 5;
+    /// List of a tuple's elements or a string's characters
+    pub const LIST: u32 = 6;
+
+    /// Name of a cast target, if the code is valid
+    pub fn name(code: u32) -> Option<&'static str> {
+        match code {
+            INT => Some("int"),
+            UINT => Some("uint"),
+            FLOAT => Some("float"),
+            BOOL => Some("bool"),
+            STRING => Some("string"),
+            NUMBER => Some("number"),
+            LIST => Some("list"),
+            _ => None,
+        }
+    }
 }
 
 impl Bytecode {
@@ -415,6 +483,19 @@ impl Bytecode {
             Bytecode::Debug(effect) => *effect,
             Bytecode::Break(effect) => *effect,
             Bytecode::Nop(effect) => *effect,
+            Bytecode::TupleNew(_, effect) => *effect,
+            Bytecode::AddConst(_, effect) => *effect,
+            Bytecode::AddLocals(_, _, effect) => *effect,
+            Bytecode::CallDirect(_, _, effect) => *effect,
+            Bytecode::CallNative(_, effect) => *effect,
+            Bytecode::Spawn(_, effect) => *effect,
+            Bytecode::Send(effect) => *effect,
+            Bytecode::Receive(effect) => *effect,
+            Bytecode::Yield(effect) => *effect,
+            Bytecode::SelfPid(effect) => *effect,
+            Bytecode::TryBegin(_, effect) => *effect,
+            Bytecode::TryEnd(effect) => *effect,
+            Bytecode::Throw(effect) => *effect,
         }
     }
     
@@ -555,6 +636,19 @@ impl Bytecode {
             Bytecode::Debug(_) => "debug",
             Bytecode::Break(_) => "break",
             Bytecode::Nop(_) => "nop",
+            Bytecode::TupleNew(_, _) => "tuple_new",
+            Bytecode::AddConst(_, _) => "add_const",
+            Bytecode::AddLocals(_, _, _) => "add_locals",
+            Bytecode::CallDirect(_, _, _) => "call_direct",
+            Bytecode::CallNative(_, _) => "call_native",
+            Bytecode::Spawn(_, _) => "spawn",
+            Bytecode::Send(_) => "send",
+            Bytecode::Receive(_) => "receive",
+            Bytecode::Yield(_) => "yield",
+            Bytecode::SelfPid(_) => "self_pid",
+            Bytecode::TryBegin(_, _) => "try_begin",
+            Bytecode::TryEnd(_) => "try_end",
+            Bytecode::Throw(_) => "throw",
         }
     }
     
@@ -574,11 +668,67 @@ impl Bytecode {
             Bytecode::Link(_, _) => 1,
             Bytecode::Monitor(_, _) => 1,
             Bytecode::Cast(_, _) => 1,
+            Bytecode::TupleNew(_, _) => 1,
+            Bytecode::AddConst(_, _) => 1,
+            Bytecode::CallNative(_, _) => 1,
+            Bytecode::Spawn(_, _) => 1,
+            Bytecode::TryBegin(_, _) => 1,
             Bytecode::SendMessage(_, _, _) => 2,
+            Bytecode::AddLocals(_, _, _) => 2,
+            Bytecode::CallDirect(_, _, _) => 2,
             _ => 0,
         }
     }
     
+    /// Number of values this instruction pops to compute the one value it
+    /// pushes, for instructions that do nothing else
+    pub fn operator_arity(&self) -> Option<usize> {
+        match self {
+            Bytecode::MapNew(_) => Some(0),
+            Bytecode::Not(_) | Bytecode::BitNot(_) | Bytecode::Abs(_) | Bytecode::Neg(_) |
+            Bytecode::Sqrt(_) | Bytecode::Sin(_) | Bytecode::Cos(_) | Bytecode::Tan(_) |
+            Bytecode::Log(_) | Bytecode::Exp(_) | Bytecode::StrLen(_) | Bytecode::StrSlice(_, _, _) |
+            Bytecode::StrSplit(_, _) | Bytecode::ListLen(_) | Bytecode::ArraySlice(_, _, _) |
+            Bytecode::ArraySort(_) | Bytecode::MapKeys(_) | Bytecode::MapValues(_) |
+            Bytecode::MapSize(_) | Bytecode::TypeOf(_) | Bytecode::Cast(_, _) |
+            Bytecode::AddConst(_, _) => Some(1),
+            Bytecode::Add(_) | Bytecode::Sub(_) | Bytecode::Mul(_) | Bytecode::Div(_) |
+            Bytecode::Mod(_) | Bytecode::And(_) | Bytecode::Or(_) | Bytecode::Eq(_) |
+            Bytecode::Lt(_) | Bytecode::Le(_) | Bytecode::Gt(_) | Bytecode::Ge(_) |
+            Bytecode::BitAnd(_) | Bytecode::BitOr(_) | Bytecode::BitXor(_) |
+            Bytecode::ShiftLeft(_) | Bytecode::ShiftRight(_) | Bytecode::UnsignedShiftRight(_) |
+            Bytecode::Min(_) | Bytecode::Max(_) | Bytecode::Pow(_) | Bytecode::StrConcat(_) |
+            Bytecode::StrIndex(_) | Bytecode::ListGet(_) | Bytecode::ListAppend(_) |
+            Bytecode::ArrayConcat(_) | Bytecode::MapGet(_) | Bytecode::MapRemove(_) => Some(2),
+            Bytecode::ListSet(_) | Bytecode::MapPut(_) => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Fuel a metered VM charges for executing this instruction, by class:
+    /// stack, local and scalar operations are cheapest, then calls and
+    /// math functions, then operations on whole strings and collections,
+    /// then anything that leaves the VM
+    pub fn fuel_cost(&self) -> u64 {
+        match self {
+            Bytecode::Call(_, _) | Bytecode::CallDirect(_, _, _) | Bytecode::CallNative(_, _) |
+            Bytecode::Ret(_) | Bytecode::Throw(_) => 3,
+            Bytecode::Sqrt(_) | Bytecode::Pow(_) | Bytecode::Sin(_) | Bytecode::Cos(_) |
+            Bytecode::Tan(_) | Bytecode::Log(_) | Bytecode::Exp(_) |
+            Bytecode::ListNew(_) | Bytecode::TupleNew(_, _) | Bytecode::MapNew(_) => 2,
+            Bytecode::StrLen(_) | Bytecode::StrConcat(_) | Bytecode::StrSlice(_, _, _) |
+            Bytecode::StrIndex(_) | Bytecode::StrSplit(_, _) | Bytecode::ListLen(_) |
+            Bytecode::ListGet(_) | Bytecode::ListSet(_) | Bytecode::ListAppend(_) |
+            Bytecode::ArraySlice(_, _, _) | Bytecode::ArrayConcat(_) | Bytecode::MapGet(_) |
+            Bytecode::MapPut(_) | Bytecode::MapRemove(_) | Bytecode::MapKeys(_) |
+            Bytecode::MapValues(_) | Bytecode::MapSize(_) | Bytecode::Cast(_, _) => 4,
+            Bytecode::ArraySort(_) | Bytecode::ArrayMap(_, _) | Bytecode::ArrayFilter(_, _) => 16,
+            Bytecode::Spawn(_, _) | Bytecode::Send(_) => 10,
+            _ if self.effect_grade() >= EffectGrade::Send => 10,
+            _ => 1,
+        }
+    }
+
     /// Check if this is a control flow instruction
     pub fn is_control_flow(&self) -> bool {
         matches!(self, 
@@ -586,7 +736,10 @@ impl Bytecode {
             Bytecode::JumpIf(_, _) | 
             Bytecode::JumpIfNot(_, _) | 
             Bytecode::Call(_, _) | 
-            Bytecode::Ret(_)
+            Bytecode::CallDirect(_, _, _) |
+            Bytecode::Ret(_) |
+            Bytecode::TryBegin(_, _) |
+            Bytecode::Throw(_)
         )
     }
     
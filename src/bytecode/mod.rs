@@ -9,18 +9,39 @@ pub mod optimizer;
 pub mod registry;
 pub mod verifier;
 pub mod security;
+pub mod bundle;
+pub mod debugger;
+pub mod assembly;
+pub mod format;
+pub mod register;
+pub mod actor;
+pub mod native;
+pub mod signing;
 
-use std::collections::{HashMap, HashSet};
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 use serde::{Deserialize, Serialize};
 use crate::types::{EffectGrade, Pid};
 use crate::error::{BytecodeError, BytecodeResult};
+use crate::debug::cpu_profiler;
 
 pub use instruction::{Bytecode, Instruction};
-pub use program::{BytecodeProgram, BytecodeFunction};
+pub use program::{BytecodeProgram, BytecodeFunction, ExecutionBackend};
 pub use compiler::{BytecodeCompiler, LanguageCompiler};
-pub use optimizer::{Optimization, ConstantFolding, DeadCodeElimination};
+pub use optimizer::{
+    Optimization, OptimizationPipeline, OptimizationStats, ConstantFolding, DeadCodeElimination,
+    SuperinstructionFusion, JumpThreading, InlineCaches, LoopInvariantCodeMotion, StackToRegister,
+};
 pub use registry::BytecodeRegistry;
 pub use verifier::{BytecodeVerifier, TypeInfo as VerifierTypeInfo, VerificationError};
+pub use bundle::{BytecodeBundle, BundleModule, BUNDLE_EXTENSION};
+pub use signing::{BundleSignature, SignatureEnforcement, SignaturePolicy, SigningKey, TrustedKeys};
+pub use format::{PROGRAM_MAGIC, FORMAT_MAJOR, FORMAT_MINOR};
+pub use assembly::{assemble, disassemble, ASSEMBLY_EXTENSION};
+pub use register::{RegisterProgram, RegisterInstruction, RegisterFunction, Operand};
+pub use native::{NativeRegistry, NativeFunction, NativeFn};
+pub use actor::{BytecodeActor, Outgoing, ProcessHost};
+pub use debugger::{Debugger, DebuggerHooks, DebugAction, Breakpoint, Watch, StopReason};
 pub use security::{SecurityManager, Permission, SecurityPolicy, ResourceLimits, SecurityEvent, SecurityEventType, create_sandbox_manager};
 
 /// Value types in REAM bytecode
@@ -364,6 +385,14 @@ pub struct ExecutionContext {
     pub effect_grade: EffectGrade,
     /// Call stack
     pub call_stack: Vec<CallFrame>,
+    /// Fuel left, when execution is metered
+    pub fuel: Option<u64>,
+    /// Why the program last stopped short of finishing, if it did
+    pub suspended: Option<Suspension>,
+    /// Exception handlers, innermost last
+    pub handlers: Vec<Handler>,
+    /// Globals stored so far, by the constant naming them
+    pub globals: HashMap<u32, Value>,
 }
 
 /// Call frame for function calls
@@ -377,6 +406,17 @@ pub struct CallFrame {
     pub function_id: u32,
 }
 
+/// An exception handler installed by `try_begin`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handler {
+    /// Where the handler's code starts
+    pub handler_pc: usize,
+    /// Stack depth to unwind to
+    pub stack_depth: usize,
+    /// Call depth to unwind to
+    pub call_depth: usize,
+}
+
 impl ExecutionContext {
     /// Create a new execution context
     pub fn new() -> Self {
@@ -386,6 +426,10 @@ impl ExecutionContext {
             pc: 0,
             effect_grade: EffectGrade::Pure,
             call_stack: Vec::new(),
+            fuel: None,
+            suspended: None,
+            handlers: Vec::new(),
+            globals: HashMap::new(),
         }
     }
     
@@ -404,15 +448,21 @@ impl ExecutionContext {
         self.stack.last().ok_or(BytecodeError::InvalidOperand("Stack empty".to_string()))
     }
     
-    /// Get a local variable
+    /// Start of the current frame's locals
+    pub fn local_base(&self) -> usize {
+        self.call_stack.last().map_or(0, |frame| frame.local_base)
+    }
+
+    /// Get a local variable of the current frame
     pub fn get_local(&self, index: usize) -> BytecodeResult<&Value> {
-        self.locals.get(index).ok_or(BytecodeError::InvalidOperand(
+        self.locals.get(self.local_base() + index).ok_or(BytecodeError::InvalidOperand(
             format!("Local variable {} not found", index)
         ))
     }
     
-    /// Set a local variable
+    /// Set a local variable of the current frame
     pub fn set_local(&mut self, index: usize, value: Value) -> BytecodeResult<()> {
+        let index = self.local_base() + index;
         if index >= self.locals.len() {
             self.locals.resize(index + 1, Value::Null);
         }
@@ -447,7 +497,65 @@ impl ExecutionContext {
         self.pc = 0;
         self.effect_grade = EffectGrade::Pure;
         self.call_stack.clear();
+        self.fuel = None;
+        self.suspended = None;
+        self.handlers.clear();
+        self.globals.clear();
+    }
+
+    /// Unwind to the innermost handler and push `exception` for it,
+    /// returning the exception back when there is no handler
+    pub fn unwind(&mut self, exception: Value) -> Result<(), Value> {
+        let Some(handler) = self.handlers.pop() else {
+            return Err(exception);
+        };
+        if let Some(frame) = self.call_stack.get(handler.call_depth) {
+            self.locals.truncate(frame.local_base);
+        }
+        self.call_stack.truncate(handler.call_depth);
+        self.stack.truncate(handler.stack_depth);
+        self.stack.push(exception);
+        self.pc = handler.handler_pc;
+        Ok(())
     }
+
+    /// Burn fuel for an instruction about to execute at `pc`, failing
+    /// without burning any when too little is left
+    pub fn charge(&mut self, cost: u64, pc: usize) -> BytecodeResult<()> {
+        if let Some(fuel) = self.fuel.as_mut() {
+            *fuel = fuel.checked_sub(cost).ok_or(BytecodeError::OutOfFuel(pc))?;
+        }
+        Ok(())
+    }
+}
+
+/// What a metered VM does when a program runs out of fuel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Exhaustion {
+    /// Fail with `BytecodeError::OutOfFuel`
+    #[default]
+    Error,
+    /// Stop before the instruction it cannot pay for, so that `resume`
+    /// continues the program with a fresh budget
+    Yield,
+}
+
+/// Why a program running as a process gave up its quantum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suspension {
+    /// `receive` found the mailbox empty and runs again once a message is delivered
+    Receive,
+    /// The program executed `yield`
+    Yield,
+}
+
+/// Fuel a metered VM gives a program, see `Bytecode::fuel_cost`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuelBudget {
+    /// Fuel for a run, or for each slice of a run when yielding
+    pub fuel: u64,
+    /// What happens when the fuel runs out
+    pub on_exhaustion: Exhaustion,
 }
 
 impl Default for ExecutionContext {
@@ -464,6 +572,22 @@ pub struct BytecodeVM {
     programs: HashMap<String, BytecodeProgram>,
     /// Runtime statistics
     stats: VMStats,
+    /// Hooks called as instructions execute
+    hooks: Option<Box<dyn DebuggerHooks>>,
+    /// Checks programs as they are loaded
+    verifier: BytecodeVerifier,
+    /// Fuel given to programs; unmetered when `None`
+    budget: Option<FuelBudget>,
+    /// Functions `call_native` can call
+    natives: NativeRegistry,
+    /// Pid `self_pid` pushes
+    pid: Pid,
+    /// Messages delivered and not yet received
+    inbox: VecDeque<Value>,
+    /// Spawns and sends waiting for the scheduler to carry them out
+    outbox: Vec<Outgoing>,
+    /// Checks the signatures of loaded bundles; unchecked when `None`
+    signatures: Option<SignaturePolicy>,
 }
 
 #[derive(Debug, Default)]
@@ -480,12 +604,121 @@ impl BytecodeVM {
             context: ExecutionContext::new(),
             programs: HashMap::new(),
             stats: VMStats::default(),
+            hooks: None,
+            verifier: BytecodeVerifier::allowing_all_effects(),
+            budget: None,
+            natives: NativeRegistry::stdlib(),
+            pid: Pid::new(),
+            inbox: VecDeque::new(),
+            outbox: Vec::new(),
+            signatures: None,
         }
     }
-    
-    /// Load a bytecode program
-    pub fn load_program(&mut self, name: String, program: BytecodeProgram) {
+
+    /// Install hooks called as instructions execute; `None` removes them
+    pub fn set_hooks(&mut self, hooks: Option<Box<dyn DebuggerHooks>>) {
+        self.hooks = hooks;
+    }
+
+    /// Meter programs with a fuel budget, or stop metering with `None`;
+    /// applies from the next program started
+    pub fn set_budget(&mut self, budget: Option<FuelBudget>) {
+        self.budget = budget;
+    }
+
+    /// Replace the verifier that checks loaded programs
+    pub fn set_verifier(&mut self, mut verifier: BytecodeVerifier) {
+        verifier.set_natives(self.natives.clone());
+        self.verifier = verifier;
+    }
+
+    /// Register a native function for `call_native`, replacing any of the same name
+    pub fn register_native<F>(&mut self, name: impl Into<String>, arity: usize, effect: EffectGrade, function: F)
+    where
+        F: Fn(&[Value]) -> BytecodeResult<Value> + Send + Sync + 'static,
+    {
+        self.natives.register(name, arity, effect, function);
+        self.verifier.set_natives(self.natives.clone());
+    }
+
+    /// The functions `call_native` can call
+    pub fn natives(&self) -> &NativeRegistry {
+        &self.natives
+    }
+
+    /// The pid programs on this VM run as
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Run programs as process `pid`
+    pub fn set_pid(&mut self, pid: Pid) {
+        self.pid = pid;
+    }
+
+    /// Queue a message for `receive`
+    pub fn deliver(&mut self, message: Value) {
+        self.inbox.push_back(message);
+    }
+
+    /// Drop the messages delivered but not yet received
+    pub fn clear_inbox(&mut self) {
+        self.inbox.clear();
+    }
+
+    /// Take the spawns and sends the program made since the last call
+    pub fn take_outgoing(&mut self) -> Vec<Outgoing> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Whether the program is stopped in `receive` with nothing delivered to it
+    pub fn is_waiting(&self) -> bool {
+        self.context.suspended == Some(Suspension::Receive) && self.inbox.is_empty()
+    }
+
+    /// The execution context
+    pub fn context(&self) -> &ExecutionContext {
+        &self.context
+    }
+
+    /// The execution context, for backends that drive the VM themselves
+    pub(crate) fn context_mut(&mut self) -> &mut ExecutionContext {
+        &mut self.context
+    }
+
+    /// Verify a bytecode program and load it
+    pub fn load_program(&mut self, name: String, program: BytecodeProgram) -> BytecodeResult<()> {
+        self.verifier.verify(&program)?;
         self.programs.insert(name, program);
+        Ok(())
+    }
+
+    /// Check the signature of a bundle, then verify and load each of its
+    /// modules under its name
+    pub fn load_bundle(&mut self, bundle: &BytecodeBundle) -> BytecodeResult<()> {
+        self.check_signature(bundle)?;
+        for module in bundle.modules() {
+            self.load_program(module.name.clone(), module.program.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Check bundles against a signature policy, or stop checking with `None`
+    pub fn set_signature_policy(&mut self, policy: Option<SignaturePolicy>) {
+        self.signatures = policy;
+    }
+
+    /// The signature policy bundles are checked against
+    pub fn signature_policy(&self) -> Option<&SignaturePolicy> {
+        self.signatures.as_ref()
+    }
+
+    /// Check a bundle against the signature policy
+    pub fn check_signature(&mut self, bundle: &BytecodeBundle) -> BytecodeResult<()> {
+        match &mut self.signatures {
+            Some(policy) => policy.check(bundle),
+            None => Ok(()),
+        }
     }
     
     /// Execute a program
@@ -499,15 +732,124 @@ impl BytecodeVM {
     
     /// Execute a bytecode program
     pub fn execute_program(&mut self, program: &BytecodeProgram) -> BytecodeResult<Value> {
+        if program.metadata.backend == ExecutionBackend::Register {
+            let registers = StackToRegister.translate(program)?;
+            return self.execute_registers(&registers);
+        }
+        self.begin();
+        loop {
+            // Nothing else runs on this VM, so a yielding program just
+            // continues, and one waiting for a message would wait forever
+            if let Some(result) = self.resume(program)? {
+                return Ok(result);
+            }
+            if self.is_waiting() {
+                return Err(BytecodeError::NoMessage(self.context.pc));
+            }
+        }
+    }
+
+    /// Reset the context to start executing a program from its first instruction
+    pub fn begin(&mut self) {
         self.context.reset();
-        
-        while self.context.pc < program.instructions.len() {
-            let instruction = &program.instructions[self.context.pc];
-            self.execute_instruction(instruction, program)?;
-            self.stats.instructions_executed += 1;
+        self.context.fuel = self.budget.map(|budget| budget.fuel);
+    }
+
+    /// Start a program at one of its functions, as a spawned process does;
+    /// the program finishes when the function returns
+    pub fn begin_function(&mut self, program: &BytecodeProgram, function: u32, args: Vec<Value>) -> BytecodeResult<()> {
+        let entry = program.functions.get(function as usize)
+            .ok_or_else(|| BytecodeError::InvalidOperand(format!("Function {} not found", function)))?;
+        if args.len() != entry.param_count {
+            return Err(BytecodeError::InvalidOperand(format!(
+                "Function {} takes {} arguments, not {}", entry.name, entry.param_count, args.len()
+            )));
         }
-        
-        // Return top of stack or null
+        self.begin();
+        self.context.stack.extend(args);
+        self.context.push_call(function, program.instructions.len());
+        self.context.pc = entry.start_pc;
+        Ok(())
+    }
+
+    /// Continue a program started with `begin`, returning its result once
+    /// it finishes, or `None` when it yields: for running out of fuel, at
+    /// `yield`, or in `receive` with no message delivered. A yielding
+    /// budget is refilled on every call.
+    pub fn resume(&mut self, program: &BytecodeProgram) -> BytecodeResult<Option<Value>> {
+        self.context.suspended = None;
+        let yielding = match self.budget {
+            Some(budget) if budget.on_exhaustion == Exhaustion::Yield => {
+                self.context.fuel = Some(budget.fuel);
+                true
+            }
+            _ => false,
+        };
+        let frames = cpu_profiler::frames();
+        let mut published = None;
+        loop {
+            if frames.is_active() && published != Some(self.context.call_stack.len()) {
+                published = Some(self.context.call_stack.len());
+                frames.set(self.context.call_stack.iter()
+                    .filter_map(|frame| program.functions.get(frame.function_id as usize))
+                    .map(|function| function.name.as_str()));
+            }
+            match self.step(program) {
+                Ok(true) if self.context.suspended.is_some() => return Ok(None),
+                Ok(true) => {}
+                Ok(false) => return self.result().map(Some),
+                Err(BytecodeError::OutOfFuel(_)) if yielding => return Ok(None),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Execute the instruction at the program counter, returning `false`
+    /// once the program has run past its last instruction
+    pub fn step(&mut self, program: &BytecodeProgram) -> BytecodeResult<bool> {
+        let pc = self.context.pc;
+        let Some(instruction) = program.instructions.get(pc) else {
+            return Ok(false);
+        };
+
+        if let Some(hooks) = self.hooks.as_mut() {
+            if hooks.before_instruction(pc, instruction, &self.context) == DebugAction::Abort {
+                let error = BytecodeError::Aborted(pc);
+                hooks.on_error(pc, &error);
+                return Err(error);
+            }
+        }
+
+        self.context.charge(instruction.fuel_cost(), pc)?;
+        let depth = self.context.call_stack.len();
+        if let Err(error) = self.execute_instruction(instruction, program) {
+            // Inside a try, a failing instruction throws its error message
+            if self.context.unwind(Value::String(error.to_string())).is_err() {
+                if let Some(hooks) = self.hooks.as_mut() {
+                    hooks.on_error(pc, &error);
+                }
+                return Err(error);
+            }
+        }
+        self.stats.instructions_executed += 1;
+
+        if let Some(hooks) = self.hooks.as_mut() {
+            match self.context.call_stack.len().cmp(&depth) {
+                Ordering::Greater => {
+                    let frame = &self.context.call_stack[depth];
+                    if let Some(function) = program.functions.get(frame.function_id as usize) {
+                        hooks.on_call(function, &self.context);
+                    }
+                }
+                Ordering::Less => hooks.on_return(&self.context),
+                Ordering::Equal => {}
+            }
+        }
+        Ok(self.context.pc < program.instructions.len())
+    }
+
+    /// The value a finished program returns: the top of the stack or null
+    pub fn result(&mut self) -> BytecodeResult<Value> {
         if self.context.stack.is_empty() {
             Ok(Value::Null)
         } else {
@@ -540,30 +882,6 @@ impl BytecodeVM {
                 self.context.set_local(*idx as usize, value)?;
                 self.context.pc += 1;
             }
-            Add(effect) => {
-                self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.add_values(a, b)?;
-                self.context.push(result);
-                self.context.pc += 1;
-            }
-            Sub(effect) => {
-                self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.sub_values(a, b)?;
-                self.context.push(result);
-                self.context.pc += 1;
-            }
-            Mul(effect) => {
-                self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.mul_values(a, b)?;
-                self.context.push(result);
-                self.context.pc += 1;
-            }
             Jump(target, effect) => {
                 self.context.update_effect(*effect);
                 self.context.pc = *target as usize;
@@ -577,6 +895,19 @@ impl BytecodeVM {
                     self.context.pc += 1;
                 }
             }
+            AddLocals(a, b, effect) => {
+                self.context.update_effect(*effect);
+                let a = self.context.get_local(*a as usize)?.clone();
+                let b = self.context.get_local(*b as usize)?.clone();
+                self.context.push(Self::add_values(a, b)?);
+                self.context.pc += 1;
+            }
+            CallDirect(func_idx, start_pc, effect) => {
+                self.context.update_effect(*effect);
+                self.context.push_call(*func_idx, self.context.pc + 1);
+                self.context.pc = *start_pc as usize;
+                self.stats.function_calls += 1;
+            }
             Call(func_idx, effect) => {
                 self.context.update_effect(*effect);
                 self.context.push_call(*func_idx, self.context.pc + 1);
@@ -595,22 +926,31 @@ impl BytecodeVM {
                 let frame = self.context.pop_call()?;
                 self.context.pc = frame.return_pc;
 
+                // Handlers the function left installed go with it
+                let depth = self.context.call_stack.len();
+                while self.context.handlers.last().is_some_and(|handler| handler.call_depth > depth) {
+                    self.context.handlers.pop();
+                }
+
                 // Restore locals
                 self.context.locals.truncate(frame.local_base);
             }
             LoadGlobal(idx, effect) => {
                 self.context.update_effect(*effect);
-                // For now, treat globals as constants (simplified implementation)
-                let value = program.constants.get(*idx as usize)
-                    .ok_or_else(|| BytecodeError::InvalidOperand(format!("Global {} not found", idx)))?
-                    .clone();
+                // A global never stored reads as its constant
+                let value = match self.context.globals.get(idx) {
+                    Some(value) => value.clone(),
+                    None => program.constants.get(*idx as usize)
+                        .ok_or_else(|| BytecodeError::InvalidOperand(format!("Global {} not found", idx)))?
+                        .clone(),
+                };
                 self.context.push(value);
                 self.context.pc += 1;
             }
-            StoreGlobal(_idx, effect) => {
+            StoreGlobal(idx, effect) => {
                 self.context.update_effect(*effect);
-                // For now, just pop the value (simplified implementation)
-                let _value = self.context.pop()?;
+                let value = self.context.pop()?;
+                self.context.globals.insert(*idx, value);
                 self.context.pc += 1;
             }
             Print(effect) => {
@@ -632,113 +972,337 @@ impl BytecodeVM {
                     self.context.pc += 1;
                 }
             }
-            // Bitwise operations
-            BitAnd(effect) => {
+            // Enhanced arithmetic
+            DivRem(effect) => {
                 self.context.update_effect(*effect);
                 let b = self.context.pop()?;
                 let a = self.context.pop()?;
-                let result = self.bitwise_and(a, b)?;
-                self.context.push(result);
+                let (div, rem) = Self::div_rem_values(a, b)?;
+                self.context.push(rem);
+                self.context.push(div);
                 self.context.pc += 1;
             }
-            BitOr(effect) => {
+            // Stack operations
+            Dup(effect) => {
                 self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.bitwise_or(a, b)?;
-                self.context.push(result);
+                let value = self.context.peek()?.clone();
+                self.context.push(value);
                 self.context.pc += 1;
             }
-            BitXor(effect) => {
+            Pop(effect) => {
+                self.context.update_effect(*effect);
+                self.context.pop()?;
+                self.context.pc += 1;
+            }
+            Swap(effect) => {
                 self.context.update_effect(*effect);
                 let b = self.context.pop()?;
                 let a = self.context.pop()?;
-                let result = self.bitwise_xor(a, b)?;
-                self.context.push(result);
+                self.context.push(b);
+                self.context.push(a);
                 self.context.pc += 1;
             }
-            BitNot(effect) => {
+            // Collection construction
+            ListNew(effect) => {
                 self.context.update_effect(*effect);
-                let a = self.context.pop()?;
-                let result = self.bitwise_not(a)?;
-                self.context.push(result);
+                let count = Self::index_operand(self.context.pop()?)?;
+                let items = self.pop_values(count)?;
+                self.context.push(Value::List(items));
                 self.context.pc += 1;
             }
-            ShiftLeft(effect) => {
+            CallNative(idx, effect) => {
+                let name = match program.constants.get(*idx as usize) {
+                    Some(Value::String(name)) => name,
+                    _ => return Err(BytecodeError::InvalidOperand(format!("Native function name {} not found", idx))),
+                };
+                let native = self.natives.get(name)
+                    .ok_or_else(|| BytecodeError::InvalidOperand(format!("Unknown native function {}", name)))?
+                    .clone();
+                if native.effect > *effect {
+                    return Err(BytecodeError::EffectMismatch { expected: native.effect, actual: *effect });
+                }
                 self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.shift_left(a, b)?;
-                self.context.push(result);
+                let args = self.pop_values(native.arity)?;
+                self.context.push(native.call(&args)?);
                 self.context.pc += 1;
+                self.stats.function_calls += 1;
             }
-            ShiftRight(effect) => {
+            Spawn(count, effect) => {
                 self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.shift_right(a, b)?;
-                self.context.push(result);
+                let args = self.pop_values(*count as usize)?;
+                let function = match self.context.pop()? {
+                    Value::Function(function) => function,
+                    other => return Err(BytecodeError::InvalidOperand(format!("Cannot spawn {}", other.type_name()))),
+                };
+                let pid = Pid::new();
+                self.outbox.push(Outgoing::Spawn { pid, function, args });
+                self.context.push(Value::Pid(pid));
                 self.context.pc += 1;
             }
-            UnsignedShiftRight(effect) => {
+            Send(effect) => {
                 self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.unsigned_shift_right(a, b)?;
-                self.context.push(result);
+                let message = self.context.pop()?;
+                let to = match self.context.pop()? {
+                    Value::Pid(pid) => pid,
+                    other => return Err(BytecodeError::InvalidOperand(format!("Cannot send to {}", other.type_name()))),
+                };
+                self.outbox.push(Outgoing::Send { to, message: message.clone() });
+                self.context.push(message);
                 self.context.pc += 1;
             }
-            // Enhanced arithmetic
-            DivRem(effect) => {
+            Receive(effect) => {
                 self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let (div, rem) = self.div_rem_values(a, b)?;
-                self.context.push(rem);
-                self.context.push(div);
+                // Without a message the pc stays here, so resuming retries
+                match self.inbox.pop_front() {
+                    Some(message) => {
+                        self.context.push(message);
+                        self.context.pc += 1;
+                    }
+                    None => self.context.suspended = Some(Suspension::Receive),
+                }
+            }
+            Yield(effect) => {
+                self.context.update_effect(*effect);
+                self.context.suspended = Some(Suspension::Yield);
                 self.context.pc += 1;
             }
-            Abs(effect) => {
+            SelfPid(effect) => {
                 self.context.update_effect(*effect);
-                let a = self.context.pop()?;
-                let result = self.abs_value(a)?;
-                self.context.push(result);
+                self.context.push(Value::Pid(self.pid));
                 self.context.pc += 1;
             }
-            Neg(effect) => {
+            TryBegin(handler_pc, effect) => {
                 self.context.update_effect(*effect);
-                let a = self.context.pop()?;
-                let result = self.neg_value(a)?;
-                self.context.push(result);
+                let handler = Handler {
+                    handler_pc: *handler_pc as usize,
+                    stack_depth: self.context.stack.len(),
+                    call_depth: self.context.call_stack.len(),
+                };
+                self.context.handlers.push(handler);
                 self.context.pc += 1;
             }
-            Min(effect) => {
+            TryEnd(effect) => {
                 self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.min_values(a, b)?;
-                self.context.push(result);
+                self.context.handlers.pop()
+                    .ok_or_else(|| BytecodeError::InvalidOperand("try_end without a handler".to_string()))?;
                 self.context.pc += 1;
             }
-            Max(effect) => {
+            Throw(effect) => {
                 self.context.update_effect(*effect);
-                let b = self.context.pop()?;
-                let a = self.context.pop()?;
-                let result = self.max_values(a, b)?;
-                self.context.push(result);
+                let exception = self.context.pop()?;
+                self.context.unwind(exception)
+                    .map_err(|exception| BytecodeError::Uncaught(exception.to_string()))?;
+            }
+            TupleNew(count, effect) => {
+                self.context.update_effect(*effect);
+                let items = self.pop_values(*count as usize)?;
+                self.context.push(Value::Tuple(items));
                 self.context.pc += 1;
             }
+            // Everything that computes a value from popped operands
             _ => {
-                return Err(BytecodeError::InvalidInstruction(format!("Unimplemented instruction: {:?}", instruction)));
+                let Some(arity) = instruction.operator_arity() else {
+                    return Err(BytecodeError::InvalidInstruction(format!("Unimplemented instruction: {:?}", instruction)));
+                };
+                self.context.update_effect(instruction.effect_grade());
+                let base = self.context.stack.len().checked_sub(arity)
+                    .ok_or_else(|| BytecodeError::InvalidOperand("Stack underflow".to_string()))?;
+                let result = Self::operate(instruction, &mut self.context.stack[base..], &program.constants)?;
+                self.context.stack.truncate(base);
+                self.context.push(result);
+                self.context.pc += 1;
             }
         }
         
         Ok(())
     }
+
+    /// Compute the result of an operator, see `Bytecode::operator_arity`,
+    /// from its operands in the order they were pushed. Operands are taken
+    /// out of `args`.
+    fn operate(instruction: &Bytecode, args: &mut [Value], constants: &[Value]) -> BytecodeResult<Value> {
+        use crate::bytecode::instruction::Bytecode::*;
+
+        let mut take = |index: usize| std::mem::replace(&mut args[index], Value::Null);
+        match instruction {
+            // Arithmetic
+            Add(_) => Self::add_values(take(0), take(1)),
+            AddConst(idx, _) => {
+                let constant = constants.get(*idx as usize)
+                    .ok_or_else(|| BytecodeError::InvalidOperand(format!("Constant {} not found", idx)))?;
+                Self::add_values(take(0), constant.clone())
+            }
+            Sub(_) => Self::sub_values(take(0), take(1)),
+            Mul(_) => Self::mul_values(take(0), take(1)),
+            Div(_) => Self::div_values(take(0), take(1)),
+            Mod(_) => Self::mod_values(take(0), take(1)),
+            Abs(_) => Self::abs_value(take(0)),
+            Neg(_) => Self::neg_value(take(0)),
+            Min(_) => Self::min_values(take(0), take(1)),
+            Max(_) => Self::max_values(take(0), take(1)),
+            Pow(_) => {
+                let a = Self::float_operand(take(0))?;
+                let b = Self::float_operand(take(1))?;
+                Ok(Value::Float(a.powf(b)))
+            }
+            Sqrt(_) | Sin(_) | Cos(_) | Tan(_) | Log(_) | Exp(_) => {
+                let a = Self::float_operand(take(0))?;
+                Ok(Value::Float(match instruction {
+                    Sqrt(_) => a.sqrt(),
+                    Sin(_) => a.sin(),
+                    Cos(_) => a.cos(),
+                    Tan(_) => a.tan(),
+                    Log(_) => a.ln(),
+                    _ => a.exp(),
+                }))
+            }
+            // Bitwise operations
+            BitAnd(_) => Self::bitwise_and(take(0), take(1)),
+            BitOr(_) => Self::bitwise_or(take(0), take(1)),
+            BitXor(_) => Self::bitwise_xor(take(0), take(1)),
+            BitNot(_) => Self::bitwise_not(take(0)),
+            ShiftLeft(_) => Self::shift_left(take(0), take(1)),
+            ShiftRight(_) => Self::shift_right(take(0), take(1)),
+            UnsignedShiftRight(_) => Self::unsigned_shift_right(take(0), take(1)),
+            // Logical operations
+            And(_) => Ok(Value::Bool(take(0).is_truthy() && take(1).is_truthy())),
+            Or(_) => Ok(Value::Bool(take(0).is_truthy() || take(1).is_truthy())),
+            Not(_) => Ok(Value::Bool(!take(0).is_truthy())),
+            // Comparisons
+            Eq(_) => Ok(Value::Bool(Self::values_equal(&args[0], &args[1]))),
+            Lt(_) | Le(_) | Gt(_) | Ge(_) => {
+                let ordering = Self::compare_values(&args[0], &args[1])?;
+                Ok(Value::Bool(match instruction {
+                    Lt(_) => ordering == Ordering::Less,
+                    Le(_) => ordering != Ordering::Greater,
+                    Gt(_) => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                }))
+            }
+            // String operations
+            StrLen(_) => {
+                let s = Self::string_operand(take(0))?;
+                Ok(Value::Int(s.chars().count() as i64))
+            }
+            StrConcat(_) => {
+                let a = Self::string_operand(take(0))?;
+                let b = Self::string_operand(take(1))?;
+                Ok(Value::String(a + &b))
+            }
+            StrSlice(start, end, _) => {
+                let s = Self::string_operand(take(0))?;
+                let chars: Vec<char> = s.chars().collect();
+                let range = Self::slice_range(*start, *end, chars.len())?;
+                Ok(Value::String(chars[range].iter().collect()))
+            }
+            StrIndex(_) => {
+                let s = Self::string_operand(take(0))?;
+                let needle = Self::string_operand(take(1))?;
+                let index = s.find(&needle)
+                    .map(|byte| s[..byte].chars().count() as i64)
+                    .unwrap_or(-1);
+                Ok(Value::Int(index))
+            }
+            StrSplit(idx, _) => {
+                let delimiter = match constants.get(*idx as usize) {
+                    Some(Value::String(delimiter)) => delimiter,
+                    _ => return Err(BytecodeError::InvalidOperand(format!("Constant {} is not a string delimiter", idx))),
+                };
+                let s = Self::string_operand(take(0))?;
+                Ok(Value::List(s.split(delimiter.as_str()).map(|part| Value::String(part.to_string())).collect()))
+            }
+            // List operations
+            ListLen(_) => {
+                let items = Self::sequence_operand(take(0))?;
+                Ok(Value::Int(items.len() as i64))
+            }
+            ListGet(_) => {
+                let mut items = Self::sequence_operand(take(0))?;
+                let index = Self::index_operand(take(1))?;
+                if index >= items.len() {
+                    return Err(BytecodeError::InvalidOperand(format!("Index {} out of bounds for length {}", index, items.len())));
+                }
+                Ok(items.swap_remove(index))
+            }
+            ListSet(_) => {
+                let mut items = Self::list_operand(take(0))?;
+                let index = Self::index_operand(take(1))?;
+                let len = items.len();
+                let slot = items.get_mut(index).ok_or_else(|| {
+                    BytecodeError::InvalidOperand(format!("Index {} out of bounds for length {}", index, len))
+                })?;
+                *slot = take(2);
+                Ok(Value::List(items))
+            }
+            ListAppend(_) => {
+                let mut items = Self::list_operand(take(0))?;
+                items.push(take(1));
+                Ok(Value::List(items))
+            }
+            ArraySlice(start, end, _) => {
+                let items = Self::list_operand(take(0))?;
+                let range = Self::slice_range(*start, *end, items.len())?;
+                Ok(Value::List(items[range].to_vec()))
+            }
+            ArrayConcat(_) => {
+                let mut a = Self::list_operand(take(0))?;
+                a.extend(Self::list_operand(take(1))?);
+                Ok(Value::List(a))
+            }
+            ArraySort(_) => {
+                let mut items = Self::list_operand(take(0))?;
+                // Values comparable with the first are comparable with each
+                // other, so checking those up front keeps sort_by infallible
+                if let Some(first) = items.first() {
+                    for item in &items {
+                        Self::compare_values(first, item)?;
+                    }
+                }
+                items.sort_by(|a, b| Self::compare_values(a, b).unwrap_or(Ordering::Equal));
+                Ok(Value::List(items))
+            }
+            // Map operations
+            MapNew(_) => Ok(Value::Map(HashMap::new())),
+            MapGet(_) => {
+                let mut map = Self::map_operand(take(0))?;
+                let key = Self::string_operand(take(1))?;
+                Ok(map.remove(&key).unwrap_or(Value::Null))
+            }
+            MapPut(_) => {
+                let mut map = Self::map_operand(take(0))?;
+                let key = Self::string_operand(take(1))?;
+                map.insert(key, take(2));
+                Ok(Value::Map(map))
+            }
+            MapRemove(_) => {
+                let mut map = Self::map_operand(take(0))?;
+                map.remove(&Self::string_operand(take(1))?);
+                Ok(Value::Map(map))
+            }
+            MapKeys(_) | MapValues(_) => {
+                let map = Self::map_operand(take(0))?;
+                // Sorted by key so that programs see a stable order
+                let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                Ok(Value::List(match instruction {
+                    MapKeys(_) => entries.into_iter().map(|(key, _)| Value::String(key)).collect(),
+                    _ => entries.into_iter().map(|(_, value)| value).collect(),
+                }))
+            }
+            MapSize(_) => {
+                let map = Self::map_operand(take(0))?;
+                Ok(Value::Int(map.len() as i64))
+            }
+            // Type operations
+            TypeOf(_) => Ok(Value::String(take(0).type_name().to_string())),
+            Cast(target, _) => Self::cast_value(take(0), *target),
+            _ => Err(BytecodeError::InvalidInstruction(format!("Not an operator: {:?}", instruction))),
+        }
+    }
     
     // Arithmetic operations
     
-    fn add_values(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn add_values(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a + b)),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
@@ -749,7 +1313,7 @@ impl BytecodeVM {
         }
     }
     
-    fn sub_values(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn sub_values(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a - b)),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
@@ -759,7 +1323,7 @@ impl BytecodeVM {
         }
     }
     
-    fn mul_values(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn mul_values(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a * b)),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
@@ -771,7 +1335,7 @@ impl BytecodeVM {
 
     // Bitwise operations
 
-    fn bitwise_and(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn bitwise_and(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a & b)),
             (Value::UInt(a), Value::UInt(b)) => Ok(Value::UInt(a & b)),
@@ -781,7 +1345,7 @@ impl BytecodeVM {
         }
     }
 
-    fn bitwise_or(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn bitwise_or(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a | b)),
             (Value::UInt(a), Value::UInt(b)) => Ok(Value::UInt(a | b)),
@@ -791,7 +1355,7 @@ impl BytecodeVM {
         }
     }
 
-    fn bitwise_xor(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn bitwise_xor(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a ^ b)),
             (Value::UInt(a), Value::UInt(b)) => Ok(Value::UInt(a ^ b)),
@@ -801,7 +1365,7 @@ impl BytecodeVM {
         }
     }
 
-    fn bitwise_not(&self, a: Value) -> BytecodeResult<Value> {
+    fn bitwise_not(a: Value) -> BytecodeResult<Value> {
         match a {
             Value::Int(a) => Ok(Value::Int(!a)),
             Value::UInt(a) => Ok(Value::UInt(!a)),
@@ -809,7 +1373,7 @@ impl BytecodeVM {
         }
     }
 
-    fn shift_left(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn shift_left(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => {
                 if b >= 0 && b < 64 {
@@ -829,7 +1393,7 @@ impl BytecodeVM {
         }
     }
 
-    fn shift_right(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn shift_right(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => {
                 if b >= 0 && b < 64 {
@@ -849,7 +1413,7 @@ impl BytecodeVM {
         }
     }
 
-    fn unsigned_shift_right(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn unsigned_shift_right(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => {
                 if b >= 0 && b < 64 {
@@ -871,7 +1435,7 @@ impl BytecodeVM {
 
     // Enhanced arithmetic operations
 
-    fn div_rem_values(&self, a: Value, b: Value) -> BytecodeResult<(Value, Value)> {
+    fn div_rem_values(a: Value, b: Value) -> BytecodeResult<(Value, Value)> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => {
                 if b == 0 {
@@ -891,7 +1455,7 @@ impl BytecodeVM {
         }
     }
 
-    fn abs_value(&self, a: Value) -> BytecodeResult<Value> {
+    fn abs_value(a: Value) -> BytecodeResult<Value> {
         match a {
             Value::Int(a) => Ok(Value::Int(a.abs())),
             Value::Float(a) => Ok(Value::Float(a.abs())),
@@ -899,7 +1463,7 @@ impl BytecodeVM {
         }
     }
 
-    fn neg_value(&self, a: Value) -> BytecodeResult<Value> {
+    fn neg_value(a: Value) -> BytecodeResult<Value> {
         match a {
             Value::Int(a) => Ok(Value::Int(-a)),
             Value::Float(a) => Ok(Value::Float(-a)),
@@ -907,7 +1471,7 @@ impl BytecodeVM {
         }
     }
 
-    fn min_values(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn min_values(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a.min(b))),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a.min(b))),
@@ -917,7 +1481,7 @@ impl BytecodeVM {
         }
     }
 
-    fn max_values(&self, a: Value, b: Value) -> BytecodeResult<Value> {
+    fn max_values(a: Value, b: Value) -> BytecodeResult<Value> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a.max(b))),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a.max(b))),
@@ -927,6 +1491,176 @@ impl BytecodeVM {
         }
     }
     
+    fn div_values(a: Value, b: Value) -> BytecodeResult<Value> {
+        match (a, b) {
+            (Value::Int(_), Value::Int(0)) | (Value::UInt(_), Value::UInt(0)) => {
+                Err(BytecodeError::InvalidOperand("Division by zero".to_string()))
+            }
+            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a.wrapping_div(b))),
+            (Value::UInt(a), Value::UInt(b)) => Ok(Value::UInt(a / b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a / b)),
+            (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 / b)),
+            (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a / b as f64)),
+            _ => Err(BytecodeError::InvalidOperand("Cannot divide these types".to_string())),
+        }
+    }
+
+    fn mod_values(a: Value, b: Value) -> BytecodeResult<Value> {
+        match (a, b) {
+            (Value::Int(_), Value::Int(0)) | (Value::UInt(_), Value::UInt(0)) => {
+                Err(BytecodeError::InvalidOperand("Division by zero".to_string()))
+            }
+            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a.wrapping_rem(b))),
+            (Value::UInt(a), Value::UInt(b)) => Ok(Value::UInt(a % b)),
+            (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a % b)),
+            (Value::Int(a), Value::Float(b)) => Ok(Value::Float(a as f64 % b)),
+            (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a % b as f64)),
+            _ => Err(BytecodeError::InvalidOperand("Cannot take modulo of these types".to_string())),
+        }
+    }
+
+    fn float_operand(a: Value) -> BytecodeResult<f64> {
+        match a {
+            Value::Int(_) | Value::UInt(_) | Value::Float(_) => Ok(a.as_float().unwrap_or_default()),
+            _ => Err(BytecodeError::InvalidOperand(format!("Expected a number, got {}", a.type_name()))),
+        }
+    }
+
+    // Comparison operations
+
+    /// Equality with integers and floats comparing by numeric value
+    fn values_equal(a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::Int(x), Value::UInt(y)) | (Value::UInt(y), Value::Int(x)) => {
+                u64::try_from(*x).map(|x| x == *y).unwrap_or(false)
+            }
+            (Value::Float(_), Value::Int(_) | Value::UInt(_))
+            | (Value::Int(_) | Value::UInt(_), Value::Float(_)) => a.as_float() == b.as_float(),
+            _ => a == b,
+        }
+    }
+
+    /// Order two numbers or two strings
+    fn compare_values(a: &Value, b: &Value) -> BytecodeResult<Ordering> {
+        let ordering = match (a, b) {
+            (Value::Int(x), Value::Int(y)) => Some(x.cmp(y)),
+            (Value::UInt(x), Value::UInt(y)) => Some(x.cmp(y)),
+            (Value::Int(x), Value::UInt(y)) => Some(i128::from(*x).cmp(&i128::from(*y))),
+            (Value::UInt(x), Value::Int(y)) => Some(i128::from(*x).cmp(&i128::from(*y))),
+            (Value::Float(_) | Value::Int(_) | Value::UInt(_), Value::Float(_) | Value::Int(_) | Value::UInt(_)) => {
+                a.as_float().zip(b.as_float()).and_then(|(x, y)| x.partial_cmp(&y))
+            }
+            (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+            _ => None,
+        };
+        ordering.ok_or_else(|| BytecodeError::InvalidOperand(format!(
+            "Cannot compare {} with {}", a.type_name(), b.type_name()
+        )))
+    }
+
+    // Collection operations
+
+    fn string_operand(a: Value) -> BytecodeResult<String> {
+        match a {
+            Value::String(s) => Ok(s),
+            _ => Err(BytecodeError::InvalidOperand(format!("Expected a string, got {}", a.type_name()))),
+        }
+    }
+
+    fn list_operand(a: Value) -> BytecodeResult<Vec<Value>> {
+        match a {
+            Value::List(items) => Ok(items),
+            _ => Err(BytecodeError::InvalidOperand(format!("Expected a list, got {}", a.type_name()))),
+        }
+    }
+
+    /// Elements of a list or a tuple
+    fn sequence_operand(a: Value) -> BytecodeResult<Vec<Value>> {
+        match a {
+            Value::List(items) | Value::Tuple(items) => Ok(items),
+            _ => Err(BytecodeError::InvalidOperand(format!("Expected a list or tuple, got {}", a.type_name()))),
+        }
+    }
+
+    fn map_operand(a: Value) -> BytecodeResult<HashMap<String, Value>> {
+        match a {
+            Value::Map(map) => Ok(map),
+            _ => Err(BytecodeError::InvalidOperand(format!("Expected a map, got {}", a.type_name()))),
+        }
+    }
+
+    fn index_operand(a: Value) -> BytecodeResult<usize> {
+        match a {
+            Value::Int(i) if i >= 0 => Ok(i as usize),
+            Value::UInt(u) => Ok(u as usize),
+            _ => Err(BytecodeError::InvalidOperand(format!("Invalid index: {}", a))),
+        }
+    }
+
+    /// Pop `count` values, returning them in the order they were pushed
+    fn pop_values(&mut self, count: usize) -> BytecodeResult<Vec<Value>> {
+        if count > self.context.stack.len() {
+            return Err(BytecodeError::InvalidOperand(format!(
+                "Cannot collect {} values from a stack of {}", count, self.context.stack.len()
+            )));
+        }
+        let base = self.context.stack.len() - count;
+        Ok(self.context.stack.split_off(base))
+    }
+
+    /// Range of a slice instruction; an end of `u32::MAX` slices to the end
+    fn slice_range(start: u32, end: u32, len: usize) -> BytecodeResult<std::ops::Range<usize>> {
+        let start = start as usize;
+        let end = if end == u32::MAX { len } else { end as usize };
+        if start > end || end > len {
+            return Err(BytecodeError::InvalidOperand(format!("Slice {}..{} out of bounds for length {}", start, end, len)));
+        }
+        Ok(start..end)
+    }
+
+    // Type operations
+
+    fn cast_value(a: Value, target: u32) -> BytecodeResult<Value> {
+        use crate::bytecode::instruction::cast;
+
+        let failed = |a: &Value| BytecodeError::InvalidOperand(format!(
+            "Cannot cast {} to {}", a, cast::name(target).unwrap_or("unknown type")
+        ));
+        match target {
+            cast::INT => match &a {
+                Value::String(s) => s.trim().parse().map(Value::Int).map_err(|_| failed(&a)),
+                _ => a.as_int().map(Value::Int).ok_or_else(|| failed(&a)),
+            },
+            cast::UINT => match &a {
+                Value::String(s) => s.trim().parse().map(Value::UInt).map_err(|_| failed(&a)),
+                _ => a.as_uint().map(Value::UInt).ok_or_else(|| failed(&a)),
+            },
+            cast::FLOAT => match &a {
+                Value::String(s) => s.trim().parse().map(Value::Float).map_err(|_| failed(&a)),
+                _ => a.as_float().map(Value::Float).ok_or_else(|| failed(&a)),
+            },
+            cast::BOOL => Ok(Value::Bool(a.is_truthy())),
+            cast::STRING => Ok(Value::String(a.as_string())),
+            cast::NUMBER => match &a {
+                Value::String(s) => {
+                    let s = s.trim();
+                    s.parse().map(Value::Int)
+                        .or_else(|_| s.parse().map(Value::Float))
+                        .map_err(|_| failed(&a))
+                }
+                Value::Int(_) | Value::UInt(_) | Value::Float(_) => Ok(a),
+                _ => Err(failed(&a)),
+            },
+            cast::LIST => match a {
+                Value::List(_) => Ok(a),
+                Value::Tuple(items) => Ok(Value::List(items)),
+                Value::String(s) => Ok(Value::List(s.chars().map(|c| Value::String(c.to_string())).collect())),
+                _ => Err(failed(&a)),
+            },
+            _ => Err(BytecodeError::InvalidOperand(format!("Unknown cast target {}", target))),
+        }
+    }
+
     /// Get VM statistics
     pub fn stats(&self) -> &VMStats {
         &self.stats
@@ -938,3 +1672,134 @@ impl Default for BytecodeVM {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> BytecodeResult<Value> {
+        BytecodeVM::new().execute_program(&assemble(source)?)
+    }
+
+    #[test]
+    fn test_comparisons_and_logic() {
+        let source = "\
+.const 0 int 2
+.const 1 float 2.0
+.const 2 string \"abc\"
+.const 3 string \"abd\"
+    const 0 pure
+    const 1 pure
+    eq pure
+    const 2 pure
+    const 3 pure
+    lt pure
+    and pure
+    const 0 pure
+    const 1 pure
+    gt pure
+    not pure
+    or pure
+";
+        assert_eq!(run(source).unwrap(), Value::Bool(true));
+        assert!(run(".const 0 int 1\n.const 1 string \"a\"\n    const 0 pure\n    const 1 pure\n    le pure\n").is_err());
+    }
+
+    #[test]
+    fn test_collections() {
+        let source = "\
+.const 0 int 3
+.const 1 int 1
+.const 2 int 2
+.const 3 string \"k\"
+    const 0 pure
+    const 1 pure
+    const 2 pure
+    const 0 pure
+    list_new memory
+    array_sort pure
+    const 1 pure
+    list_get pure
+    tuple_new 1 memory
+    map_new memory
+    swap pure
+    const 3 pure
+    swap pure
+    map_put write
+    dup pure
+    map_size pure
+    pop pure
+    const 3 pure
+    map_get pure
+";
+        assert_eq!(run(source).unwrap(), Value::Tuple(vec![Value::Int(2)]));
+        assert!(run(".const 0 int 5\n    map_new memory\n    const 0 pure\n    list_get pure\n").is_err());
+    }
+
+    #[test]
+    fn test_strings_and_casts() {
+        let source = "\
+.const 0 string \"a,b,c\"
+.const 1 string \",\"
+    const 0 pure
+    str_split 1 pure
+    list_len pure
+    cast 4 pure
+    const 0 pure
+    str_slice 2 4294967295 pure
+    str_concat pure
+";
+        assert_eq!(run(source).unwrap(), Value::String("3b,c".to_string()));
+        assert_eq!(run(".const 0 string \" 42 \"\n    const 0 pure\n    cast 5 pure\n").unwrap(), Value::Int(42));
+        assert_eq!(run(".const 0 float 2.5\n    const 0 pure\n    type_of pure\n").unwrap(), Value::String("float".to_string()));
+        assert!(run(".const 0 string \"x\"\n    const 0 pure\n    cast 0 pure\n").is_err());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert!(run(".const 0 int 1\n.const 1 int 0\n    const 0 pure\n    const 1 pure\n    mod pure\n").is_err());
+        assert_eq!(
+            run(".const 0 int 7\n.const 1 int 2\n    const 0 pure\n    const 1 pure\n    div pure\n").unwrap(),
+            Value::Int(3)
+        );
+    }
+
+    #[test]
+    fn test_exception_handling() {
+        // Throwing unwinds the call into the handler, restoring the stack
+        let source = "\
+.const 0 int 1
+.const 1 int 0
+.const 2 string \"boom\"
+.function 0 fail params=0 locals=0 len=3
+    const 0
+    try_begin handler
+    call fail
+    try_end
+handler:
+    tuple_new 2
+    jump end
+fail:
+    const 0
+    const 2
+    throw
+end:
+    nop
+";
+        let mut vm = BytecodeVM::new();
+        vm.load_program("throw".to_string(), assemble(source).unwrap()).unwrap();
+        assert_eq!(vm.execute("throw").unwrap(), Value::Tuple(vec![Value::Int(1), Value::String("boom".to_string())]));
+        assert!(vm.context().call_stack.is_empty() && vm.context().handlers.is_empty());
+
+        // Instructions that fail inside a try throw their error
+        let source = ".const 0 int 1\n.const 1 int 0\n    try_begin caught\n    const 0\n    const 1\n    div\n    try_end\ncaught:\n    nop\n";
+        assert!(matches!(run(source).unwrap(), Value::String(message) if message.contains("zero")));
+        assert!(matches!(run(".const 0 int 1\n    const 0\n    throw\n"), Err(BytecodeError::Uncaught(_))));
+        assert!(run("try_end\n").is_err());
+
+        // The handler starts from the stack at try_begin plus the exception
+        let source = ".const 0 int 1\n    const 0\n    try_begin handler\n    try_end\n    jump end\nhandler:\nend:\n    nop\n";
+        let err = vm.load_program("depth".to_string(), assemble(source).unwrap()).unwrap_err().to_string();
+        assert!(err.contains("Stack depth at PC 4"), "{}", err);
+    }
+}
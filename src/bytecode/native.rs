@@ -0,0 +1,169 @@
+//! Native functions callable from bytecode
+//!
+//! `call_native` names its function by a string constant, so a program
+//! does not depend on the order functions were registered in. The VM
+//! pops the function's arguments, first argument deepest, calls it and
+//! pushes its result. Each function declares its effect grade; the
+//! calling instruction must be graded at least as high.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use crate::bytecode::Value;
+use crate::error::{BytecodeError, BytecodeResult};
+use crate::types::EffectGrade;
+
+/// Body of a native function, given its arguments in the order they were pushed
+pub type NativeFn = Arc<dyn Fn(&[Value]) -> BytecodeResult<Value> + Send + Sync>;
+
+/// A registered native function
+#[derive(Clone)]
+pub struct NativeFunction {
+    /// Number of arguments popped from the stack
+    pub arity: usize,
+    /// Effects of calling the function
+    pub effect: EffectGrade,
+    function: NativeFn,
+}
+
+impl NativeFunction {
+    /// Call the function
+    pub fn call(&self, args: &[Value]) -> BytecodeResult<Value> {
+        (self.function)(args)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("arity", &self.arity)
+            .field("effect", &self.effect)
+            .finish()
+    }
+}
+
+/// Native functions by name
+#[derive(Debug, Clone, Default)]
+pub struct NativeRegistry {
+    functions: HashMap<String, NativeFunction>,
+}
+
+impl NativeRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry holding the standard library builtins
+    pub fn stdlib() -> Self {
+        let mut registry = Self::new();
+        registry.register("string/upper", 1, EffectGrade::Pure, |args| Ok(Value::String(string_arg(args, 0)?.to_uppercase())));
+        registry.register("string/lower", 1, EffectGrade::Pure, |args| Ok(Value::String(string_arg(args, 0)?.to_lowercase())));
+        registry.register("string/trim", 1, EffectGrade::Pure, |args| Ok(Value::String(string_arg(args, 0)?.trim().to_string())));
+        registry.register("to_string", 1, EffectGrade::Pure, |args| Ok(Value::String(args[0].to_string())));
+        registry.register("time/now_millis", 0, EffectGrade::Read, |_| {
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+                .map_err(|e| BytecodeError::InvalidOperand(e.to_string()))?;
+            Ok(Value::Int(now.as_millis() as i64))
+        });
+        registry
+    }
+
+    /// Register a function, replacing any of the same name
+    pub fn register<F>(&mut self, name: impl Into<String>, arity: usize, effect: EffectGrade, function: F)
+    where
+        F: Fn(&[Value]) -> BytecodeResult<Value> + Send + Sync + 'static,
+    {
+        self.functions.insert(name.into(), NativeFunction { arity, effect, function: Arc::new(function) });
+    }
+
+    /// Look up a function
+    pub fn get(&self, name: &str) -> Option<&NativeFunction> {
+        self.functions.get(name)
+    }
+
+    /// Names and functions of everything registered
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &NativeFunction)> {
+        self.functions.iter().map(|(name, function)| (name.as_str(), function))
+    }
+}
+
+/// A string argument of a native function
+pub fn string_arg(args: &[Value], index: usize) -> BytecodeResult<&str> {
+    match args.get(index) {
+        Some(Value::String(s)) => Ok(s),
+        Some(other) => Err(BytecodeError::InvalidOperand(format!("Expected a string, got {}", other.type_name()))),
+        None => Err(BytecodeError::InvalidOperand(format!("Missing argument {}", index))),
+    }
+}
+
+/// An integer argument of a native function
+pub fn int_arg(args: &[Value], index: usize) -> BytecodeResult<i64> {
+    match args.get(index) {
+        Some(Value::Int(i)) => Ok(*i),
+        Some(other) => Err(BytecodeError::InvalidOperand(format!("Expected an integer, got {}", other.type_name()))),
+        None => Err(BytecodeError::InvalidOperand(format!("Missing argument {}", index))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{assemble, BytecodeVM};
+
+    #[test]
+    fn test_call_native() {
+        let mut vm = BytecodeVM::new();
+        vm.register_native("math/clamp", 3, EffectGrade::Pure, |args| {
+            Ok(Value::Int(int_arg(args, 0)?.clamp(int_arg(args, 1)?, int_arg(args, 2)?)))
+        });
+        let program = assemble("
+            .const 0 string \"math/clamp\"
+            .const 1 int 42
+            .const 2 int 0
+            .const 3 int 10
+            .const 4 string \"string/upper\"
+            .const 5 string \"done\"
+            const 5
+            call_native 4
+            const 1
+            const 2
+            const 3
+            call_native 0
+            tuple_new 2
+        ").unwrap();
+        assert!(crate::bytecode::disassemble(&program).contains("; \"math/clamp\""));
+        vm.load_program("clamp".to_string(), program.clone()).unwrap();
+        assert_eq!(vm.execute("clamp").unwrap(), Value::Tuple(vec![Value::String("DONE".to_string()), Value::Int(10)]));
+
+        // Arguments are checked when the native function runs
+        let mut bad = program.clone();
+        bad.constants[1] = Value::String("42".to_string());
+        let err = vm.execute_program(&bad).unwrap_err().to_string();
+        assert!(err.contains("Expected an integer"), "{}", err);
+    }
+
+    #[test]
+    fn test_native_effects_and_verification() {
+        let mut vm = BytecodeVM::new();
+        let program = assemble("
+            .const 0 string \"time/now_millis\"
+            call_native 0
+        ").unwrap();
+
+        // The instruction must be graded for the function's effects
+        let err = vm.load_program("now".to_string(), program).unwrap_err().to_string();
+        assert!(err.contains("is Pure but time/now_millis is Read"), "{}", err);
+        let program = assemble(".const 0 string \"time/now_millis\"\ncall_native 0 read\n").unwrap();
+        assert!(matches!(vm.execute_program(&program).unwrap(), Value::Int(ms) if ms > 0));
+
+        let program = assemble(".const 0 string \"missing\"\ncall_native 0\n").unwrap();
+        let err = vm.load_program("missing".to_string(), program.clone()).unwrap_err().to_string();
+        assert!(err.contains("Unknown native function missing"), "{}", err);
+        assert!(vm.execute_program(&program).is_err());
+
+        // The stack must hold the function's arguments
+        let program = assemble(".const 0 string \"string/upper\"\ncall_native 0\n").unwrap();
+        assert!(vm.load_program("upper".to_string(), program).is_err());
+    }
+}
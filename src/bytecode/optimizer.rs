@@ -1,8 +1,10 @@
 //! Bytecode optimization passes
 
-use std::collections::HashSet;
-use crate::bytecode::{Bytecode, BytecodeProgram};
+use std::collections::{BTreeMap, HashSet};
+use crate::bytecode::{Bytecode, BytecodeProgram, BytecodeVM, ExecutionBackend, Value};
+use crate::bytecode::register::{Operand, RegisterFunction, RegisterInstruction, RegisterProgram};
 use crate::error::{BytecodeError, BytecodeResult};
+use crate::types::EffectGrade;
 
 /// Optimization trait for functorial transformations
 pub trait Optimization {
@@ -11,6 +13,9 @@ pub trait Optimization {
     
     /// Check if optimization preserves semantics
     fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool;
+
+    /// Name of the pass, as shown in statistics
+    fn name(&self) -> &'static str;
 }
 
 /// Constant folding optimization
@@ -18,50 +23,35 @@ pub struct ConstantFolding;
 
 impl Optimization for ConstantFolding {
     fn optimize(&self, program: &BytecodeProgram) -> BytecodeResult<BytecodeProgram> {
-        let mut optimized = program.clone();
-        
+        let mut constants = program.constants.clone();
+
         // Simple constant folding for adjacent const + binary op
-        let mut new_instructions = Vec::new();
-        let mut i = 0;
-        
-        while i < optimized.instructions.len() {
-            if i + 2 < optimized.instructions.len() {
-                if let (
-                    Bytecode::Const(a_idx, _),
-                    Bytecode::Const(b_idx, _),
-                    Bytecode::Add(effect)
-                ) = (&optimized.instructions[i], &optimized.instructions[i + 1], &optimized.instructions[i + 2]) {
-                    // Fold constants
-                    let a_idx = *a_idx;
-                    let b_idx = *b_idx;
-                    let effect = *effect;
-
-                    if let (Some(a), Some(b)) = (
-                        optimized.constants.get(a_idx as usize),
-                        optimized.constants.get(b_idx as usize)
-                    ) {
-                        if let (crate::bytecode::Value::Int(a_val), crate::bytecode::Value::Int(b_val)) = (a, b) {
-                            let result = crate::bytecode::Value::Int(a_val + b_val);
-                            let result_idx = optimized.add_constant(result);
-                            new_instructions.push(Bytecode::Const(result_idx, effect));
-                            i += 3;
-                            continue;
-                        }
-                    }
-                }
-            }
-            
-            new_instructions.push(optimized.instructions[i].clone());
-            i += 1;
-        }
-        
-        optimized.instructions = new_instructions;
+        let mut optimized = rewrite(program, |instructions, pc| {
+            let (Bytecode::Const(a_idx, _), Bytecode::Const(b_idx, _), Bytecode::Add(effect)) =
+                (instructions.get(pc)?, instructions.get(pc + 1)?, instructions.get(pc + 2)?) else {
+                return None;
+            };
+            let (Some(Value::Int(a)), Some(Value::Int(b))) =
+                (constants.get(*a_idx as usize), constants.get(*b_idx as usize)) else {
+                return None;
+            };
+            // Overflow is left for the VM to report
+            let result = a.checked_add(*b)?;
+            constants.push(Value::Int(result));
+            Some((3, vec![Bytecode::Const(constants.len() as u32 - 1, *effect)]))
+        });
+
+        optimized.constants = constants;
         Ok(optimized)
     }
     
     fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
         before.analyze_effects() == after.analyze_effects()
     }
+
+    fn name(&self) -> &'static str {
+        "constant folding"
+    }
 }
 
 /// Dead code elimination
@@ -85,6 +75,10 @@ impl Optimization for DeadCodeElimination {
     fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
         before.analyze_effects() == after.analyze_effects()
     }
+
+    fn name(&self) -> &'static str {
+        "dead code elimination"
+    }
 }
 
 impl DeadCodeElimination {
@@ -109,7 +103,7 @@ impl DeadCodeElimination {
                 Bytecode::Jump(target, _) => {
                     worklist.push(*target as usize);
                 }
-                Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) => {
+                Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) | Bytecode::TryBegin(target, _) => {
                     worklist.push(*target as usize);
                     if idx > 0 {
                         worklist.push(idx - 1);
@@ -140,52 +134,942 @@ impl Optimization for PeepholeOptimization {
         
         while changed {
             changed = false;
-            let mut new_instructions = Vec::new();
-            let mut i = 0;
-            
-            while i < optimized.instructions.len() {
-                let mut consumed = 1;
-                
-                // Pattern: Load X, Store X -> Dup
-                if i + 1 < optimized.instructions.len() {
-                    if let (Bytecode::Load(a, _), Bytecode::Store(b, effect)) = 
-                        (&optimized.instructions[i], &optimized.instructions[i + 1]) {
-                        if a == b {
-                            new_instructions.push(Bytecode::Dup(*effect));
-                            consumed = 2;
-                            changed = true;
-                        }
-                    }
+            let constants = optimized.constants.clone();
+            optimized = rewrite(&optimized, |instructions, pc| {
+                match (instructions.get(pc)?, instructions.get(pc + 1)?) {
+                    // Pattern: Load X, Store X -> (remove)
+                    (Bytecode::Load(a, _), Bytecode::Store(b, _)) if a == b => {}
+                    // Pattern: Const 0, Add -> (remove)
+                    (Bytecode::Const(idx, _), Bytecode::Add(_))
+                        if matches!(constants.get(*idx as usize), Some(Value::Int(0))) => {}
+                    _ => return None,
                 }
-                
-                // Pattern: Const 0, Add -> (remove)
-                if i + 1 < optimized.instructions.len() {
-                    if let (Bytecode::Const(idx, _), Bytecode::Add(_)) = 
-                        (&optimized.instructions[i], &optimized.instructions[i + 1]) {
-                        if let Some(crate::bytecode::Value::Int(0)) = optimized.constants.get(*idx as usize) {
-                            // Skip both instructions (adding 0 is no-op)
-                            consumed = 2;
-                            changed = true;
-                        }
-                    }
+                changed = true;
+                Some((2, Vec::new()))
+            });
+        }
+        
+        Ok(optimized)
+    }
+    
+    fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
+        before.analyze_effects() == after.analyze_effects()
+    }
+
+    fn name(&self) -> &'static str {
+        "peephole"
+    }
+}
+
+/// Fuses common instruction sequences into superinstructions
+///
+/// `load a; load b; add` becomes `add_locals a b` and `const c; add`
+/// becomes `add_const c`, so the VM dispatches once instead of two or
+/// three times.
+pub struct SuperinstructionFusion;
+
+impl Optimization for SuperinstructionFusion {
+    fn optimize(&self, program: &BytecodeProgram) -> BytecodeResult<BytecodeProgram> {
+        Ok(rewrite(program, |instructions, pc| {
+            match (instructions.get(pc)?, instructions.get(pc + 1), instructions.get(pc + 2)) {
+                (Bytecode::Load(a, ea), Some(Bytecode::Load(b, eb)), Some(Bytecode::Add(effect))) => {
+                    Some((3, vec![Bytecode::AddLocals(*a, *b, ea.combine(*eb).combine(*effect))]))
                 }
-                
-                if consumed == 1 {
-                    new_instructions.push(optimized.instructions[i].clone());
+                (Bytecode::Const(index, ec), Some(Bytecode::Add(effect)), _) => {
+                    Some((2, vec![Bytecode::AddConst(*index, ec.combine(*effect))]))
                 }
-                
-                i += consumed;
+                _ => None,
             }
-            
-            optimized.instructions = new_instructions;
+        }))
+    }
+
+    fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
+        before.analyze_effects() == after.analyze_effects()
+    }
+
+    fn name(&self) -> &'static str {
+        "superinstruction fusion"
+    }
+}
+
+/// Points jumps that land on an unconditional jump at its target
+///
+/// An unconditional jump to a `ret` becomes the `ret`.
+pub struct JumpThreading;
+
+impl Optimization for JumpThreading {
+    fn optimize(&self, program: &BytecodeProgram) -> BytecodeResult<BytecodeProgram> {
+        let mut optimized = program.clone();
+        for pc in 0..optimized.instructions.len() {
+            let instruction = &optimized.instructions[pc];
+            let (Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)) = instruction else {
+                continue;
+            };
+
+            // Follow the chain, stopping at loops
+            let mut target = *target;
+            let mut seen = HashSet::from([pc as u32]);
+            while let Some(Bytecode::Jump(next, _)) = program.instructions.get(target as usize) {
+                if !seen.insert(target) {
+                    break;
+                }
+                target = *next;
+            }
+
+            optimized.instructions[pc] = match (instruction, program.instructions.get(target as usize)) {
+                (Bytecode::Jump(..), Some(Bytecode::Ret(effect))) => Bytecode::Ret(*effect),
+                (Bytecode::Jump(_, effect), _) => Bytecode::Jump(target, *effect),
+                (Bytecode::JumpIf(_, effect), _) => Bytecode::JumpIf(target, *effect),
+                (Bytecode::JumpIfNot(_, effect), _) => Bytecode::JumpIfNot(target, *effect),
+                _ => continue,
+            };
         }
-        
+        sync_functions(&mut optimized);
         Ok(optimized)
     }
-    
+
+    fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
+        before.instructions.len() == after.instructions.len()
+    }
+
+    fn name(&self) -> &'static str {
+        "jump threading"
+    }
+}
+
+/// Resolves lookups the VM would otherwise repeat on every execution
+///
+/// Loads of globals that are never stored become constant loads, and
+/// calls carry their function's start so the VM need not look it up.
+/// Programs check the cached starts when they are validated.
+pub struct InlineCaches;
+
+impl Optimization for InlineCaches {
+    fn optimize(&self, program: &BytecodeProgram) -> BytecodeResult<BytecodeProgram> {
+        let stored: HashSet<u32> = program.instructions.iter()
+            .filter_map(|instruction| match instruction {
+                Bytecode::StoreGlobal(index, _) => Some(*index),
+                _ => None,
+            })
+            .collect();
+
+        let mut optimized = program.clone();
+        for instruction in &mut optimized.instructions {
+            match *instruction {
+                Bytecode::LoadGlobal(index, effect) if !stored.contains(&index) => {
+                    *instruction = Bytecode::Const(index, effect);
+                }
+                Bytecode::Call(index, effect) => {
+                    if let Some(function) = program.functions.get(index as usize) {
+                        *instruction = Bytecode::CallDirect(index, function.start_pc as u32, effect);
+                    }
+                }
+                _ => {}
+            }
+        }
+        sync_functions(&mut optimized);
+        Ok(optimized)
+    }
+
     fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
         before.analyze_effects() == after.analyze_effects()
     }
+
+    fn name(&self) -> &'static str {
+        "inline caches"
+    }
+}
+
+/// Hoists loop-invariant pure computations out of loops
+///
+/// A loop runs from the target of a backward jump to the jump. An
+/// expression built only from pure operators, constants and locals the
+/// loop never stores is computed once before the loop into a fresh local,
+/// which the loop loads instead. Expressions are only taken from the
+/// straight-line code at the top of the loop, never from past an
+/// instruction that sends, spawns or does IO, so effects outside the VM
+/// happen in the same order as before. Code past a conditional exit may
+/// not run at all, so from there only expressions of constants that
+/// evaluate without error are hoisted.
+pub struct LoopInvariantCodeMotion;
+
+impl Optimization for LoopInvariantCodeMotion {
+    fn optimize(&self, program: &BytecodeProgram) -> BytecodeResult<BytecodeProgram> {
+        let mut optimized = program.clone();
+        // Each hoist leaves fewer operators inside loops; the bound is a backstop
+        for _ in 0..program.instructions.len() {
+            match Hoist::find(&optimized) {
+                Some(hoist) => optimized = hoist.apply(&optimized),
+                None => break,
+            }
+        }
+        Ok(optimized)
+    }
+
+    fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
+        before.analyze_effects() == after.analyze_effects()
+    }
+
+    fn name(&self) -> &'static str {
+        "loop-invariant code motion"
+    }
+}
+
+/// An expression to move in front of its loop
+struct Hoist {
+    /// First instruction of the loop, where the hoisted code goes
+    header: usize,
+    /// Last instruction of the loop
+    end: usize,
+    /// The expression's instructions
+    start: usize,
+    len: usize,
+    /// Function the loop is in
+    function: Option<usize>,
+    /// Local receiving the expression's value
+    local: u32,
+}
+
+impl Hoist {
+    /// The longest hoistable expression of the first loop that has one
+    fn find(program: &BytecodeProgram) -> Option<Hoist> {
+        let instructions = &program.instructions;
+        let mut sources: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (pc, instruction) in instructions.iter().enumerate() {
+            if let Some(target) = jump_target(instruction) {
+                sources.entry(target).or_default().push(pc);
+            }
+        }
+
+        for (&header, from) in &sources {
+            let Some(&end) = from.iter().filter(|pc| **pc >= header).max() else {
+                continue;
+            };
+            let function = function_at(program, header);
+            if function_at(program, end) != function || end >= instructions.len() {
+                continue;
+            }
+
+            // Everything must enter through the header
+            let side_entry = sources.range(header + 1..=end)
+                .any(|(_, from)| from.iter().any(|pc| !(header..=end).contains(pc)));
+            let boundary = program.functions.iter().any(|function| {
+                let function_end = function.start_pc + function.instructions.len();
+                (header + 1..=end).contains(&function.start_pc) || (header + 1..=end).contains(&function_end)
+            });
+            if side_entry || boundary {
+                continue;
+            }
+
+            // Code every iteration runs, up to exits, before it can branch
+            // within the loop or affect the outside
+            let mut prefix_end = header;
+            let mut first_exit = None;
+            while prefix_end <= end {
+                let instruction = &instructions[prefix_end];
+                let exits = matches!(instruction, Bytecode::JumpIf(..) | Bytecode::JumpIfNot(..))
+                    && jump_target(instruction).is_some_and(|target| !(header..=end).contains(&target));
+                if (prefix_end > header && sources.contains_key(&prefix_end))
+                    || (jump_target(instruction).is_some() && !exits)
+                    || matches!(instruction, Bytecode::Ret(_))
+                    || instruction.effect_grade() >= EffectGrade::Send {
+                    break;
+                }
+                if exits {
+                    first_exit.get_or_insert(prefix_end);
+                }
+                prefix_end += 1;
+            }
+
+            let stored: HashSet<u32> = instructions[header..=end].iter()
+                .filter_map(|instruction| match instruction {
+                    Bytecode::Store(index, _) => Some(*index),
+                    _ => None,
+                })
+                .collect();
+            let best = (header..prefix_end)
+                .filter_map(|last| invariant_expression(instructions, header, last, &stored).map(|start| (start, last + 1 - start)))
+                .filter(|(start, len)| {
+                    first_exit.is_none_or(|exit| *start < exit)
+                        || evaluates(&instructions[*start..start + len], &program.constants)
+                })
+                .max_by_key(|(_, len)| *len);
+            if let Some((start, len)) = best {
+                return Some(Hoist { header, end, start, len, function, local: next_local(program, function) });
+            }
+        }
+        None
+    }
+
+    /// Move the expression into `local` in front of the loop
+    fn apply(&self, program: &BytecodeProgram) -> BytecodeProgram {
+        let instructions = &program.instructions;
+        let len = instructions.len();
+        let expression = self.start..self.start + self.len;
+
+        // New position of each instruction; the expression's all become the load
+        let mut new_pc = vec![0; len + 1];
+        let mut position = 0;
+        for (pc, slot) in new_pc.iter_mut().enumerate() {
+            if pc == self.header {
+                position += self.len + 1;
+            }
+            *slot = position;
+            if pc < len && (!expression.contains(&pc) || pc + 1 == expression.end) {
+                position += 1;
+            }
+        }
+        // Entering the loop from outside runs the hoisted code first
+        let entry = |target: usize, from: Option<usize>| {
+            if target == self.header && !from.is_some_and(|pc| (self.header..=self.end).contains(&pc)) {
+                self.header
+            } else {
+                new_pc[target]
+            }
+        };
+        let retarget = |instruction: &Bytecode, pc: usize| {
+            let mut instruction = instruction.clone();
+            match &mut instruction {
+                Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)
+                | Bytecode::TryBegin(target, _)
+                    if (*target as usize) <= len => *target = entry(*target as usize, Some(pc)) as u32,
+                Bytecode::CallDirect(_, target, _) if (*target as usize) <= len => {
+                    *target = entry(*target as usize, None) as u32
+                }
+                _ => {}
+            }
+            instruction
+        };
+
+        let mut code = Vec::with_capacity(len + 2);
+        for (pc, instruction) in instructions.iter().enumerate() {
+            if pc == self.header {
+                code.extend_from_slice(&instructions[expression.clone()]);
+                code.push(Bytecode::Store(self.local, EffectGrade::Pure));
+            }
+            if pc == self.start {
+                code.push(Bytecode::Load(self.local, EffectGrade::Pure));
+            }
+            if !expression.contains(&pc) {
+                code.push(retarget(instruction, pc));
+            }
+        }
+
+        let mut hoisted = program.clone();
+        for (index, function) in hoisted.functions.iter_mut().enumerate() {
+            let end = entry(function.start_pc + function.instructions.len(), None);
+            function.start_pc = entry(function.start_pc, None);
+            function.instructions = code[function.start_pc..end].to_vec();
+            if self.function == Some(index) {
+                function.local_count = function.local_count.max(self.local as usize + 1);
+            }
+        }
+        if let Some(debug_info) = hoisted.metadata.debug_info.as_mut() {
+            debug_info.line_mapping = debug_info.line_mapping.iter()
+                .filter(|(pc, _)| **pc <= len)
+                .map(|(pc, location)| (new_pc[*pc], *location))
+                .collect();
+        }
+        hoisted.instructions = code;
+        hoisted
+    }
+}
+
+/// Start of an expression ending at `last` that computes the same value on
+/// every iteration: pure operators over constants and unstored locals
+fn invariant_expression(instructions: &[Bytecode], header: usize, last: usize, stored: &HashSet<u32>) -> Option<usize> {
+    let mut needed = 1;
+    let mut operators = 0;
+    let mut start = last + 1;
+    while needed > 0 {
+        if start == header {
+            return None;
+        }
+        start -= 1;
+        let instruction = &instructions[start];
+        if instruction.effect_grade() != EffectGrade::Pure {
+            return None;
+        }
+        match instruction {
+            Bytecode::Const(..) => needed -= 1,
+            Bytecode::Load(index, _) if !stored.contains(index) => needed -= 1,
+            Bytecode::AddLocals(a, b, _) if !stored.contains(a) && !stored.contains(b) => {
+                needed -= 1;
+                operators += 1;
+            }
+            _ => {
+                needed = needed - 1 + instruction.operator_arity()?;
+                operators += 1;
+            }
+        }
+    }
+    (operators > 0).then_some(start)
+}
+
+/// Whether an expression of constants and operators computes without error
+fn evaluates(expression: &[Bytecode], constants: &[Value]) -> bool {
+    let mut stack = Vec::new();
+    for instruction in expression {
+        if let Bytecode::Const(index, _) = instruction {
+            match constants.get(*index as usize) {
+                Some(value) => stack.push(value.clone()),
+                None => return false,
+            }
+            continue;
+        }
+        let Some(base) = instruction.operator_arity().and_then(|arity| stack.len().checked_sub(arity)) else {
+            return false;
+        };
+        match BytecodeVM::operate(instruction, &mut stack[base..], constants) {
+            Ok(value) => {
+                stack.truncate(base);
+                stack.push(value);
+            }
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+/// Where a jump goes
+pub(crate) fn jump_target(instruction: &Bytecode) -> Option<usize> {
+    match instruction {
+        Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)
+        | Bytecode::TryBegin(target, _) => Some(*target as usize),
+        _ => None,
+    }
+}
+
+/// The function whose code includes `pc`; `None` for top-level code
+fn function_at(program: &BytecodeProgram, pc: usize) -> Option<usize> {
+    program.functions.iter()
+        .position(|function| (function.start_pc..function.start_pc + function.instructions.len()).contains(&pc))
+}
+
+/// A local that no code of the function, or of the top level, uses
+fn next_local(program: &BytecodeProgram, function: Option<usize>) -> u32 {
+    let used = program.instructions.iter().enumerate()
+        .filter(|(pc, _)| function_at(program, *pc) == function)
+        .filter_map(|(_, instruction)| match instruction {
+            Bytecode::Load(index, _) | Bytecode::Store(index, _) => Some(*index + 1),
+            Bytecode::AddLocals(a, b, _) => Some(*a.max(b) + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let declared = function.map_or(0, |index| program.functions[index].local_count as u32);
+    used.max(declared)
+}
+
+/// Replace runs of instructions, keeping jumps, cached calls, functions
+/// and debug info pointing at the same code
+///
+/// `replacement` is offered each position in turn and may return how many
+/// instructions starting there to replace, and with what. Runs that
+/// something jumps into, or that cross a function boundary, are skipped.
+fn rewrite(
+    program: &BytecodeProgram,
+    mut replacement: impl FnMut(&[Bytecode], usize) -> Option<(usize, Vec<Bytecode>)>,
+) -> BytecodeProgram {
+    let instructions = &program.instructions;
+    let len = instructions.len();
+
+    let mut boundaries: HashSet<usize> = program.functions.iter()
+        .flat_map(|function| [function.start_pc, function.start_pc + function.instructions.len()])
+        .collect();
+    for instruction in instructions {
+        match instruction {
+            Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)
+            | Bytecode::TryBegin(target, _) | Bytecode::CallDirect(_, target, _) => {
+                boundaries.insert(*target as usize);
+            }
+            _ => {}
+        }
+    }
+
+    let mut new_instructions = Vec::with_capacity(len);
+    let mut new_pc = vec![0; len + 1];
+    let mut pc = 0;
+    while pc < len {
+        new_pc[pc] = new_instructions.len();
+        let run = replacement(instructions, pc)
+            .filter(|(count, _)| *count > 0 && pc + count <= len)
+            .filter(|(count, _)| !(pc + 1..pc + count).any(|inner| boundaries.contains(&inner)));
+        match run {
+            Some((count, replaced)) => {
+                new_instructions.extend(replaced);
+                pc += count;
+            }
+            None => {
+                new_instructions.push(instructions[pc].clone());
+                pc += 1;
+            }
+        }
+    }
+    new_pc[len] = new_instructions.len();
+
+    let remap = |target: &mut u32| *target = new_pc[*target as usize] as u32;
+    for instruction in &mut new_instructions {
+        match instruction {
+            Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)
+            | Bytecode::TryBegin(target, _) | Bytecode::CallDirect(_, target, _) if (*target as usize) <= len => remap(target),
+            _ => {}
+        }
+    }
+
+    let mut rewritten = program.clone();
+    for function in &mut rewritten.functions {
+        let end = new_pc[(function.start_pc + function.instructions.len()).min(len)];
+        function.start_pc = new_pc[function.start_pc.min(len)];
+        function.instructions = new_instructions[function.start_pc.min(end)..end].to_vec();
+    }
+    if let Some(debug_info) = rewritten.metadata.debug_info.as_mut() {
+        debug_info.line_mapping = debug_info.line_mapping.iter()
+            .filter(|(pc, _)| **pc <= len)
+            .map(|(pc, location)| (new_pc[*pc], *location))
+            .collect();
+    }
+    rewritten.instructions = new_instructions;
+    rewritten
+}
+
+/// Refresh each function's copy of its instructions after editing them in place
+fn sync_functions(program: &mut BytecodeProgram) {
+    for function in &mut program.functions {
+        let end = function.start_pc + function.instructions.len();
+        if let Some(code) = program.instructions.get(function.start_pc..end) {
+            function.instructions = code.to_vec();
+        }
+    }
+}
+
+/// Translation of stack code to register code for the register backend
+///
+/// Stack slots become registers. Loads and constants are not copied until
+/// an instruction needs them, so `load 0; load 1; add` becomes one add of
+/// two local registers. Code whose stack depth differs between the paths
+/// reaching an instruction, or that jumps between functions, has no
+/// translation.
+pub struct StackToRegister;
+
+impl StackToRegister {
+    /// Translate a program to register code
+    pub fn translate(&self, program: &BytecodeProgram) -> BytecodeResult<RegisterProgram> {
+        let len = program.instructions.len();
+        let mut owner = vec![None; len];
+        for (index, function) in program.functions.iter().enumerate() {
+            let end = function.start_pc + function.instructions.len();
+            if function.instructions.is_empty() || end > len {
+                return Err(untranslatable(format!("function {} has no code in the program", function.name)));
+            }
+            for slot in &mut owner[function.start_pc..end] {
+                if slot.replace(index).is_some() {
+                    return Err(untranslatable(format!("function {} overlaps another function", function.name)));
+                }
+            }
+        }
+        if owner.first().is_some_and(Option::is_some) {
+            return Err(untranslatable("the program starts inside a function".to_string()));
+        }
+
+        let mut translation = Translation {
+            program,
+            owner,
+            code: Vec::new(),
+            new_pc: vec![usize::MAX; len + 1],
+        };
+        let (registers, _) = translation.region(None, 0, 0)?;
+        let mut functions = Vec::with_capacity(program.functions.len());
+        for (index, function) in program.functions.iter().enumerate() {
+            let entry = translation.code.len();
+            let (registers, arg_base) = translation.region(Some(index), function.start_pc, function.param_count)?;
+            functions.push(RegisterFunction { entry, registers, arg_base });
+        }
+
+        let new_pc = translation.new_pc;
+        let mut code = translation.code;
+        for instruction in &mut code {
+            if let RegisterInstruction::Jump(target)
+            | RegisterInstruction::JumpIf { target, .. }
+            | RegisterInstruction::JumpIfNot { target, .. } = instruction {
+                *target = new_pc[*target as usize] as u32;
+            }
+        }
+
+        Ok(RegisterProgram {
+            code,
+            constants: program.constants.clone(),
+            functions,
+            registers,
+            effect: program.analyze_effects(),
+        })
+    }
+}
+
+impl Optimization for StackToRegister {
+    fn optimize(&self, program: &BytecodeProgram) -> BytecodeResult<BytecodeProgram> {
+        self.translate(program)?;
+        let mut optimized = program.clone();
+        optimized.metadata.backend = ExecutionBackend::Register;
+        Ok(optimized)
+    }
+
+    fn preserves_semantics(&self, before: &BytecodeProgram, after: &BytecodeProgram) -> bool {
+        before.instructions == after.instructions
+    }
+
+    fn name(&self) -> &'static str {
+        "stack to register"
+    }
+}
+
+fn untranslatable(reason: String) -> BytecodeError {
+    BytecodeError::Optimization(format!("No register translation: {}", reason))
+}
+
+/// State of a translation in progress
+struct Translation<'a> {
+    program: &'a BytecodeProgram,
+    /// Function each instruction belongs to; `None` for top-level code
+    owner: Vec<Option<usize>>,
+    code: Vec<RegisterInstruction>,
+    /// Register code position of each stack code position
+    new_pc: Vec<usize>,
+}
+
+impl Translation<'_> {
+    /// Translate a function, or the top-level code when `function` is
+    /// `None`, returning its frame size and first stack register
+    fn region(&mut self, function: Option<usize>, entry: usize, entry_depth: usize) -> BytecodeResult<(usize, u32)> {
+        let instructions = &self.program.instructions;
+        let len = instructions.len();
+
+        // Stack depth before each reachable instruction
+        let mut depths = BTreeMap::new();
+        let mut targets = HashSet::from([entry]);
+        let mut worklist = vec![(entry, entry_depth)];
+        let mut locals = 0;
+        let mut max_depth = entry_depth;
+        while let Some((pc, depth)) = worklist.pop() {
+            if let Some(&known) = depths.get(&pc) {
+                if known != depth {
+                    return Err(untranslatable(format!("stack depth at {} is {} or {}", pc, known, depth)));
+                }
+                continue;
+            }
+            depths.insert(pc, depth);
+            if pc == len {
+                continue;
+            }
+
+            let instruction = &instructions[pc];
+            let (pops, pushes) = self.stack_effect(pc)?;
+            if depth < pops {
+                return Err(untranslatable(format!("{} at {} underflows the stack", instruction.name(), pc)));
+            }
+            let after = depth - pops + pushes;
+            max_depth = max_depth.max(after);
+
+            let mut successors = Vec::with_capacity(2);
+            match instruction {
+                Bytecode::Load(index, _) | Bytecode::Store(index, _) => {
+                    locals = locals.max(*index as usize + 1);
+                    successors.push(pc + 1);
+                }
+                Bytecode::AddLocals(a, b, _) => {
+                    locals = locals.max(*a.max(b) as usize + 1);
+                    successors.push(pc + 1);
+                }
+                Bytecode::Jump(target, _) => {
+                    targets.insert(*target as usize);
+                    successors.push(*target as usize);
+                }
+                Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) => {
+                    targets.insert(*target as usize);
+                    successors.push(*target as usize);
+                    successors.push(pc + 1);
+                }
+                Bytecode::Ret(_) => {
+                    if function.is_some() && depth != 1 {
+                        return Err(untranslatable(format!("ret at {} leaves {} values on the stack", pc, depth)));
+                    }
+                }
+                _ => successors.push(pc + 1),
+            }
+            for next in successors {
+                let inside = match function {
+                    None => next == len || self.owner.get(next) == Some(&None),
+                    Some(_) => self.owner.get(next) == Some(&function),
+                };
+                if !inside {
+                    return Err(untranslatable(format!("{} at {} leaves its function", instruction.name(), pc)));
+                }
+                worklist.push((next, after));
+            }
+        }
+
+        let mut stack = OperandStack {
+            entries: Vec::new(),
+            locals: locals as u32,
+            scratch: (locals + max_depth) as u32,
+        };
+        let mut block_code = self.code.len();
+        let mut falls_through = None;
+        for (&pc, &depth) in &depths {
+            if pc == len {
+                continue;
+            }
+            if falls_through != Some(pc) || targets.contains(&pc) {
+                if falls_through == Some(pc) {
+                    stack.flush(&mut self.code);
+                }
+                stack.reset(depth);
+                block_code = self.code.len();
+            }
+            self.new_pc[pc] = self.code.len();
+            self.instruction(pc, function.is_some(), &mut stack, block_code)?;
+            falls_through = match instructions[pc] {
+                Bytecode::Jump(..) | Bytecode::Ret(_) => None,
+                _ => Some(pc + 1),
+            };
+        }
+
+        // Running past the last instruction ends the program
+        if let Some(&depth) = depths.get(&len) {
+            if falls_through == Some(len) && !targets.contains(&len) {
+                self.new_pc[len] = self.code.len();
+                self.code.push(RegisterInstruction::Halt(stack.entries.last().copied()));
+            } else {
+                if falls_through == Some(len) {
+                    stack.flush(&mut self.code);
+                }
+                self.new_pc[len] = self.code.len();
+                let result = depth.checked_sub(1).map(|top| Operand::Reg(stack.slot(top)));
+                self.code.push(RegisterInstruction::Halt(result));
+            }
+        }
+
+        Ok((locals + max_depth + 1, locals as u32))
+    }
+
+    /// Values an instruction pops and pushes
+    fn stack_effect(&self, pc: usize) -> BytecodeResult<(usize, usize)> {
+        let instruction = &self.program.instructions[pc];
+        Ok(match instruction {
+            Bytecode::Const(..) | Bytecode::Load(..) | Bytecode::LoadGlobal(..) | Bytecode::AddLocals(..) => (0, 1),
+            Bytecode::Dup(_) => (1, 2),
+            Bytecode::Swap(_) | Bytecode::DivRem(_) => (2, 2),
+            Bytecode::Store(..) | Bytecode::StoreGlobal(..) | Bytecode::Pop(_) | Bytecode::Print(_)
+            | Bytecode::JumpIf(..) | Bytecode::JumpIfNot(..) => (1, 0),
+            Bytecode::Nop(_) | Bytecode::Jump(..) | Bytecode::Ret(_) => (0, 0),
+            Bytecode::ListNew(_) => (Self::list_length(self.program, pc)? + 1, 1),
+            Bytecode::TupleNew(count, _) => (*count as usize, 1),
+            Bytecode::Call(index, _) | Bytecode::CallDirect(index, _, _) => {
+                let function = self.program.functions.get(*index as usize)
+                    .ok_or_else(|| BytecodeError::InvalidOperand(format!("Function {} not found", index)))?;
+                (function.param_count, 1)
+            }
+            _ => match instruction.operator_arity() {
+                Some(arity) => (arity, 1),
+                None => return Err(untranslatable(format!("{} at {} is not supported", instruction.name(), pc))),
+            },
+        })
+    }
+
+    /// Length of the list built at `pc`, which must come from the constant before it
+    fn list_length(program: &BytecodeProgram, pc: usize) -> BytecodeResult<usize> {
+        let count = match pc.checked_sub(1).map(|previous| &program.instructions[previous]) {
+            Some(Bytecode::Const(index, _)) => program.constants.get(*index as usize).cloned(),
+            _ => None,
+        };
+        count.map(BytecodeVM::index_operand).transpose()?
+            .ok_or_else(|| untranslatable(format!("list_new at {} has no constant length", pc)))
+    }
+
+    /// Translate the instruction at `pc`
+    fn instruction(&mut self, pc: usize, in_function: bool, stack: &mut OperandStack, block_code: usize) -> BytecodeResult<()> {
+        let instruction = &self.program.instructions[pc];
+        let code = &mut self.code;
+        match instruction {
+            Bytecode::Const(index, _) | Bytecode::LoadGlobal(index, _) => {
+                if *index as usize >= self.program.constants.len() {
+                    return Err(BytecodeError::InvalidOperand(format!("Constant {} not found", index)));
+                }
+                stack.entries.push(Operand::Const(*index));
+            }
+            Bytecode::Load(index, _) => stack.entries.push(Operand::Reg(*index)),
+            Bytecode::AddLocals(a, b, effect) => {
+                let dst = stack.push_result();
+                let args = vec![Operand::Reg(*a), Operand::Reg(*b)];
+                code.push(RegisterInstruction::Apply { op: Bytecode::Add(*effect), dst, args });
+            }
+            Bytecode::Store(index, _) => {
+                let value = stack.pop(1).remove(0);
+                if value != Operand::Reg(*index) {
+                    stack.materialize(*index, code);
+                    let producer = match value {
+                        Operand::Reg(register) if code.len() > block_code => {
+                            code.last_mut().and_then(result_register).filter(|dst| **dst == register)
+                        }
+                        _ => None,
+                    };
+                    match producer {
+                        Some(dst) => *dst = *index,
+                        None => code.push(RegisterInstruction::Move { dst: *index, src: value }),
+                    }
+                }
+            }
+            // Registers hold no globals, so loads of one stored would
+            // still read its constant
+            Bytecode::StoreGlobal(..) => {
+                return Err(untranslatable(format!("store_global at {} is not supported", pc)));
+            }
+            Bytecode::Pop(_) => {
+                stack.pop(1);
+            }
+            Bytecode::Dup(_) => {
+                let top = stack.pop(1)[0];
+                stack.entries.extend([top, top]);
+            }
+            Bytecode::Swap(_) => {
+                let pair = stack.pop(2);
+                let (a, b) = (pair[0], pair[1]);
+                if stack.is_slot(a) || stack.is_slot(b) {
+                    let below = stack.slot(stack.entries.len());
+                    let above = below + 1;
+                    code.push(RegisterInstruction::Move { dst: stack.scratch, src: b });
+                    code.push(RegisterInstruction::Move { dst: above, src: a });
+                    code.push(RegisterInstruction::Move { dst: below, src: Operand::Reg(stack.scratch) });
+                    stack.entries.extend([Operand::Reg(below), Operand::Reg(above)]);
+                } else {
+                    stack.entries.extend([b, a]);
+                }
+            }
+            Bytecode::Print(_) => {
+                let value = stack.pop(1)[0];
+                code.push(RegisterInstruction::Print(value));
+            }
+            Bytecode::Nop(_) => {}
+            Bytecode::Jump(target, _) => {
+                stack.flush(code);
+                code.push(RegisterInstruction::Jump(*target));
+            }
+            Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) => {
+                let cond = stack.pop(1)[0];
+                stack.flush(code);
+                code.push(match instruction {
+                    Bytecode::JumpIf(..) => RegisterInstruction::JumpIf { cond, target: *target },
+                    _ => RegisterInstruction::JumpIfNot { cond, target: *target },
+                });
+            }
+            Bytecode::Ret(_) => {
+                let value = if in_function { Some(stack.pop(1)[0]) } else { stack.entries.last().copied() };
+                code.push(RegisterInstruction::Ret(value));
+            }
+            Bytecode::DivRem(_) => {
+                let pair = stack.pop(2);
+                let remainder = stack.push_result();
+                let quotient = stack.push_result();
+                code.push(RegisterInstruction::DivRem { quotient, remainder, lhs: pair[0], rhs: pair[1] });
+            }
+            Bytecode::ListNew(_) => {
+                if !matches!(stack.entries.last(), Some(Operand::Const(_))) {
+                    return Err(untranslatable(format!("list_new at {} has no constant length", pc)));
+                }
+                stack.pop(1);
+                let items = stack.pop(Self::list_length(self.program, pc)?);
+                let dst = stack.push_result();
+                code.push(RegisterInstruction::Collect { dst, items, tuple: false });
+            }
+            Bytecode::TupleNew(count, _) => {
+                let items = stack.pop(*count as usize);
+                let dst = stack.push_result();
+                code.push(RegisterInstruction::Collect { dst, items, tuple: true });
+            }
+            Bytecode::Call(function, _) | Bytecode::CallDirect(function, _, _) => {
+                let args = stack.pop(self.program.functions[*function as usize].param_count);
+                let dst = stack.push_result();
+                code.push(RegisterInstruction::Call { function: *function, args, dst });
+            }
+            _ => {
+                let arity = instruction.operator_arity().unwrap_or_default();
+                let args = stack.pop(arity);
+                let dst = stack.push_result();
+                code.push(RegisterInstruction::Apply { op: instruction.clone(), dst, args });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The register an instruction writes its only result to
+fn result_register(instruction: &mut RegisterInstruction) -> Option<&mut u32> {
+    match instruction {
+        RegisterInstruction::Move { dst, .. }
+        | RegisterInstruction::Apply { dst, .. }
+        | RegisterInstruction::Collect { dst, .. }
+        | RegisterInstruction::Call { dst, .. } => Some(dst),
+        _ => None,
+    }
+}
+
+/// Operands on the stack while translating a block
+///
+/// Stack slot `i` lives in register `locals + i`. An entry may stand for a
+/// local or constant not yet copied to its slot, but never for a slot
+/// above its own, so writing an entry's slot only clobbers that entry.
+struct OperandStack {
+    entries: Vec<Operand>,
+    locals: u32,
+    scratch: u32,
+}
+
+impl OperandStack {
+    fn slot(&self, index: usize) -> u32 {
+        self.locals + index as u32
+    }
+
+    fn is_slot(&self, operand: Operand) -> bool {
+        matches!(operand, Operand::Reg(register) if register >= self.locals)
+    }
+
+    /// Start a block with `depth` values in their slots
+    fn reset(&mut self, depth: usize) {
+        self.entries = (0..depth).map(|index| Operand::Reg(self.slot(index))).collect();
+    }
+
+    /// Pop `count` entries, bottom first; the depth analysis guarantees they exist
+    fn pop(&mut self, count: usize) -> Vec<Operand> {
+        self.entries.split_off(self.entries.len() - count)
+    }
+
+    /// Push the slot an instruction writes its result to
+    fn push_result(&mut self) -> u32 {
+        let dst = self.slot(self.entries.len());
+        self.entries.push(Operand::Reg(dst));
+        dst
+    }
+
+    /// Copy every entry into its slot, as blocks expect on entry
+    fn flush(&mut self, code: &mut Vec<RegisterInstruction>) {
+        for index in 0..self.entries.len() {
+            let slot = Operand::Reg(self.slot(index));
+            if self.entries[index] != slot {
+                code.push(RegisterInstruction::Move { dst: self.slot(index), src: self.entries[index] });
+                self.entries[index] = slot;
+            }
+        }
+    }
+
+    /// Copy entries that read a local into their slots before it is overwritten
+    fn materialize(&mut self, local: u32, code: &mut Vec<RegisterInstruction>) {
+        for index in 0..self.entries.len() {
+            if self.entries[index] == Operand::Reg(local) {
+                code.push(RegisterInstruction::Move { dst: self.slot(index), src: Operand::Reg(local) });
+                self.entries[index] = Operand::Reg(self.slot(index));
+            }
+        }
+    }
 }
 
 /// Optimization pipeline
@@ -214,10 +1098,41 @@ impl OptimizationPipeline {
             .add_pass(Box::new(PeepholeOptimization))
             .add_pass(Box::new(DeadCodeElimination))
     }
+
+    /// The passes run at an optimization level: none at 0, constant folding
+    /// and jump threading at 1, peephole rewrites and inline caches from 2,
+    /// loop-invariant code motion and superinstruction fusion from 3
+    pub fn for_level(level: u8) -> Self {
+        let mut pipeline = Self::new();
+        if level >= 1 {
+            pipeline = pipeline.add_pass(Box::new(ConstantFolding));
+        }
+        if level >= 2 {
+            pipeline = pipeline.add_pass(Box::new(PeepholeOptimization));
+        }
+        if level >= 3 {
+            pipeline = pipeline
+                .add_pass(Box::new(LoopInvariantCodeMotion))
+                .add_pass(Box::new(SuperinstructionFusion));
+        }
+        if level >= 1 {
+            pipeline = pipeline.add_pass(Box::new(JumpThreading));
+        }
+        if level >= 2 {
+            pipeline = pipeline.add_pass(Box::new(InlineCaches));
+        }
+        pipeline
+    }
     
     /// Run all optimization passes
     pub fn optimize(&self, program: &BytecodeProgram) -> BytecodeResult<BytecodeProgram> {
+        self.optimize_with_stats(program).map(|(optimized, _)| optimized)
+    }
+
+    /// Run all optimization passes, recording what each one did
+    pub fn optimize_with_stats(&self, program: &BytecodeProgram) -> BytecodeResult<(BytecodeProgram, OptimizationStats)> {
         let mut current = program.clone();
+        let mut stats = OptimizationStats::default();
         
         for pass in &self.passes {
             let optimized = pass.optimize(&current)?;
@@ -228,12 +1143,65 @@ impl OptimizationPipeline {
                     "Optimization pass violated semantics preservation".to_string()
                 ));
             }
-            
+
+            stats.passes.push(PassStats {
+                name: pass.name(),
+                instructions_before: current.instructions.len(),
+                instructions_after: optimized.instructions.len(),
+                rewritten: changed_instructions(&current.instructions, &optimized.instructions),
+            });
             current = optimized;
         }
         
-        Ok(current)
+        Ok((current, stats))
+    }
+}
+
+/// What a pipeline run did, pass by pass
+#[derive(Debug, Clone, Default)]
+pub struct OptimizationStats {
+    pub passes: Vec<PassStats>,
+}
+
+impl OptimizationStats {
+    /// Instructions before the first pass
+    pub fn instructions_before(&self) -> Option<usize> {
+        self.passes.first().map(|pass| pass.instructions_before)
+    }
+
+    /// Instructions after the last pass
+    pub fn instructions_after(&self) -> Option<usize> {
+        self.passes.last().map(|pass| pass.instructions_after)
+    }
+
+    /// Instructions removed by all passes together
+    pub fn instructions_removed(&self) -> usize {
+        self.passes.iter().map(PassStats::instructions_removed).sum()
+    }
+}
+
+/// What one pass did
+#[derive(Debug, Clone)]
+pub struct PassStats {
+    pub name: &'static str,
+    pub instructions_before: usize,
+    pub instructions_after: usize,
+    /// Instructions changed in place; zero for passes that change the count
+    pub rewritten: usize,
+}
+
+impl PassStats {
+    /// Instructions the pass removed
+    pub fn instructions_removed(&self) -> usize {
+        self.instructions_before.saturating_sub(self.instructions_after)
+    }
+}
+
+fn changed_instructions(before: &[Bytecode], after: &[Bytecode]) -> usize {
+    if before.len() != after.len() {
+        return 0;
     }
+    before.iter().zip(after).filter(|(a, b)| a != b).count()
 }
 
 impl Default for OptimizationPipeline {
@@ -245,9 +1213,46 @@ impl Default for OptimizationPipeline {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bytecode::{Value, BytecodeProgram};
+    use crate::bytecode::{assemble, Value, BytecodeProgram};
     use crate::types::EffectGrade;
 
+    /// Sums `total + counter + 10` for counters 5 down to 1
+    const LOOP: &str = "
+        .const 0 int 5
+        .const 1 int 0
+        .const 2 int -1
+        .const 3 int 10
+        .function 0 add_ten params=1
+
+        const 0
+        store 0
+        const 1
+        store 1
+        loop:
+        load 0
+        jump_if_not hop
+        load 1
+        load 0
+        add
+        call add_ten
+        store 1
+        load 0
+        const 2
+        add
+        store 0
+        jump loop
+        hop:
+        jump done
+        done:
+        load 1
+        jump exit
+        add_ten:
+        const 3
+        add
+        ret
+        exit:
+    ";
+
     #[test]
     fn test_constant_folding() {
         let mut program = BytecodeProgram::new("test".to_string());
@@ -305,4 +1310,163 @@ mod tests {
         // Should be optimized
         assert!(optimized.instructions.len() <= program.instructions.len());
     }
+
+    #[test]
+    fn test_optimization_levels() {
+        let program = assemble(LOOP).unwrap();
+        let expected = BytecodeVM::new().execute_program(&program).unwrap();
+        assert_eq!(expected, Value::Int(65));
+
+        let (unchanged, stats) = OptimizationPipeline::for_level(0).optimize_with_stats(&program).unwrap();
+        assert!(stats.passes.is_empty());
+        assert_eq!(unchanged.instructions, program.instructions);
+
+        let (optimized, stats) = OptimizationPipeline::for_level(3).optimize_with_stats(&program).unwrap();
+        assert_eq!(stats.instructions_before(), Some(22));
+        assert_eq!(stats.instructions_after(), Some(18));
+        assert_eq!(stats.instructions_removed(), 4);
+        optimized.validate().unwrap();
+
+        assert!(optimized.instructions.contains(&Bytecode::AddLocals(1, 0, EffectGrade::Pure)));
+        assert!(optimized.instructions.contains(&Bytecode::AddConst(2, EffectGrade::Pure)));
+        let add_ten = &optimized.functions[0];
+        assert_eq!(add_ten.instructions, vec![Bytecode::AddConst(3, EffectGrade::Pure), Bytecode::Ret(EffectGrade::Pure)]);
+        assert!(optimized.instructions.contains(&Bytecode::CallDirect(0, add_ten.start_pc as u32, EffectGrade::Pure)));
+
+        assert_eq!(BytecodeVM::new().execute_program(&optimized).unwrap(), expected);
+        let mut register = optimized.clone();
+        register.metadata.backend = ExecutionBackend::Register;
+        assert_eq!(BytecodeVM::new().execute_program(&register).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_fusion_skips_jump_targets() {
+        let program = assemble("
+            load 0
+            join:
+            load 1
+            add
+            jump join
+        ").unwrap();
+        let fused = SuperinstructionFusion.optimize(&program).unwrap();
+        assert_eq!(fused.instructions, program.instructions);
+    }
+
+    #[test]
+    fn test_jump_threading() {
+        let program = assemble("
+            .const 0 bool true
+            const 0
+            jump_if hop
+            jump out
+            hop:
+            jump out
+            out:
+            ret
+            spin:
+            jump spin
+        ").unwrap();
+        let threaded = JumpThreading.optimize(&program).unwrap();
+        assert_eq!(threaded.instructions[1], Bytecode::JumpIf(4, EffectGrade::Pure));
+        assert_eq!(threaded.instructions[2], Bytecode::Ret(EffectGrade::Pure));
+        assert_eq!(threaded.instructions[5], Bytecode::Jump(5, EffectGrade::Pure));
+    }
+
+    #[test]
+    fn test_inline_caches() {
+        let program = assemble("
+            .const 0 int 1
+            .const 1 int 2
+            load_global 0
+            load_global 1
+            store_global 1
+        ").unwrap();
+        let cached = InlineCaches.optimize(&program).unwrap();
+        assert_eq!(cached.instructions[0], Bytecode::Const(0, EffectGrade::Pure));
+        assert_eq!(cached.instructions[1], Bytecode::LoadGlobal(1, EffectGrade::Pure));
+
+        let mut stale = InlineCaches.optimize(&assemble(LOOP).unwrap()).unwrap();
+        stale.validate().unwrap();
+        stale.functions[0].start_pc -= 1;
+        assert!(stale.validate().is_err());
+    }
+
+    #[test]
+    fn test_loop_invariant_code_motion() {
+        let program = assemble("
+            .const 0 int 3
+            .const 1 int 6
+            .const 2 int 7
+            .const 3 int -1
+            .const 4 int 0
+            .const 5 int 2
+
+            const 0
+            store 0
+            const 4
+            store 1
+            const 5
+            store 2
+            loop:
+            load 2
+            load 2
+            mul
+            store 3
+            load 0
+            jump_if_not done
+            const 1
+            const 2
+            mul
+            load 1
+            add
+            load 3
+            add
+            store 1
+            load 0
+            const 3
+            add
+            store 0
+            jump loop
+            done:
+            load 1
+        ").unwrap();
+        let expected = BytecodeVM::new().execute_program(&program).unwrap();
+        assert_eq!(expected, Value::Int(138));
+
+        let hoisted = LoopInvariantCodeMotion.optimize(&program).unwrap();
+        let back_edge = hoisted.instructions.iter().rposition(|i| matches!(i, Bytecode::Jump(..))).unwrap();
+        let Bytecode::Jump(header, _) = hoisted.instructions[back_edge] else { unreachable!() };
+        let body = &hoisted.instructions[header as usize..back_edge];
+        assert!(!body.contains(&Bytecode::Mul(EffectGrade::Pure)));
+        assert_eq!(hoisted.instructions.iter().filter(|i| **i == Bytecode::Mul(EffectGrade::Pure)).count(), 2);
+        assert!(hoisted.instructions[..header as usize].contains(&Bytecode::Store(4, EffectGrade::Pure)));
+        assert!(hoisted.instructions[..header as usize].contains(&Bytecode::Store(5, EffectGrade::Pure)));
+
+        assert_eq!(BytecodeVM::new().execute_program(&hoisted).unwrap(), expected);
+        let mut register = hoisted.clone();
+        register.metadata.backend = ExecutionBackend::Register;
+        assert_eq!(BytecodeVM::new().execute_program(&register).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_code_motion_respects_effects() {
+        // Nothing moves past the print, and locals past the exit may be unset
+        let program = assemble("
+            .const 0 int 6
+            loop:
+            load 0
+            print io
+            const 0
+            const 0
+            mul
+            load 1
+            jump_if_not loop
+            load 2
+            load 2
+            mul
+            jump loop
+        ").unwrap();
+        let hoisted = LoopInvariantCodeMotion.optimize(&program).unwrap();
+        assert_eq!(hoisted.instructions, program.instructions);
+    }
 }
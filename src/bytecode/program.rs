@@ -127,6 +127,40 @@ pub struct ProgramMetadata {
     pub compiler_version: String,
     /// Debug information
     pub debug_info: Option<DebugInfo>,
+    /// Engine the program runs on
+    #[serde(default)]
+    pub backend: ExecutionBackend,
+}
+
+/// Engine a program runs on
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExecutionBackend {
+    /// Operand stack interpreter
+    #[default]
+    Stack,
+    /// Register machine, run on code translated from the stack code
+    Register,
+}
+
+impl std::fmt::Display for ExecutionBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionBackend::Stack => write!(f, "stack"),
+            ExecutionBackend::Register => write!(f, "register"),
+        }
+    }
+}
+
+impl std::str::FromStr for ExecutionBackend {
+    type Err = BytecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stack" => Ok(ExecutionBackend::Stack),
+            "register" => Ok(ExecutionBackend::Register),
+            _ => Err(BytecodeError::InvalidOperand(format!("Unknown execution backend: {}", s))),
+        }
+    }
 }
 
 /// Debug information
@@ -160,6 +194,7 @@ impl BytecodeProgram {
                     .as_secs(),
                 compiler_version: env!("CARGO_PKG_VERSION").to_string(),
                 debug_info: None,
+                backend: ExecutionBackend::Stack,
             },
         }
     }
@@ -266,7 +301,15 @@ impl BytecodeProgram {
                         ));
                     }
                 }
-                Bytecode::Const(const_id, _) => {
+                Bytecode::CallDirect(func_id, start_pc, _) => {
+                    if !self.functions.iter().any(|f| f.id == *func_id && f.start_pc == *start_pc as usize) {
+                        return Err(BytecodeError::InvalidOperand(
+                            format!("Cached call to function {} at {} is stale", func_id, start_pc)
+                        ));
+                    }
+                }
+                Bytecode::Const(const_id, _) | Bytecode::AddConst(const_id, _)
+                | Bytecode::CallNative(const_id, _) => {
                     if *const_id as usize >= self.constants.len() {
                         return Err(BytecodeError::InvalidOperand(
                             format!("Constant {} not found", const_id)
@@ -0,0 +1,363 @@
+//! Register-based execution
+//!
+//! The stack VM spends much of its time moving values on and off the
+//! operand stack. In register code every operand names a register of the
+//! current frame or a constant, so adding two locals is one instruction
+//! instead of three. Register code is produced from stack code by the
+//! `StackToRegister` optimizer pass; a program runs on it when its
+//! metadata selects `ExecutionBackend::Register`.
+//!
+//! Each frame holds the function's locals, then one register per operand
+//! stack slot, then a scratch register. Locals start out null.
+//!
+//! Register code burns fuel like the stack code it came from, but a run
+//! cannot be suspended, so running out of fuel is an error even under a
+//! yielding budget.
+
+use crate::bytecode::{Bytecode, BytecodeVM, Value};
+use crate::error::{BytecodeError, BytecodeResult};
+use crate::types::EffectGrade;
+
+/// Where an instruction reads a value from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// A register of the current frame
+    Reg(u32),
+    /// An entry of the constant pool
+    Const(u32),
+}
+
+/// Register machine instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegisterInstruction {
+    /// Copy a value into a register
+    Move { dst: u32, src: Operand },
+    /// Apply an operator, see `Bytecode::operator_arity`, to its operands
+    Apply { op: Bytecode, dst: u32, args: Vec<Operand> },
+    /// Divide, storing quotient and remainder
+    DivRem { quotient: u32, remainder: u32, lhs: Operand, rhs: Operand },
+    /// Build a list, or a tuple, from operands
+    Collect { dst: u32, items: Vec<Operand>, tuple: bool },
+    /// Unconditional jump
+    Jump(u32),
+    /// Jump if the condition is truthy
+    JumpIf { cond: Operand, target: u32 },
+    /// Jump if the condition is falsy
+    JumpIfNot { cond: Operand, target: u32 },
+    /// Call a function with arguments, storing its result
+    Call { function: u32, args: Vec<Operand>, dst: u32 },
+    /// Return a value to the caller; without a value the frame had nothing to return
+    Ret(Option<Operand>),
+    /// Print a value
+    Print(Operand),
+    /// End the program with a result
+    Halt(Option<Operand>),
+}
+
+/// A function of a register program
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterFunction {
+    /// First instruction of the function
+    pub entry: usize,
+    /// Registers in a frame of the function
+    pub registers: usize,
+    /// Register receiving the first argument; the others follow it
+    pub arg_base: u32,
+}
+
+/// A program translated to register code
+#[derive(Debug, Clone)]
+pub struct RegisterProgram {
+    pub(crate) code: Vec<RegisterInstruction>,
+    pub(crate) constants: Vec<Value>,
+    /// Indexed like the functions of the stack program
+    pub(crate) functions: Vec<RegisterFunction>,
+    /// Registers of the top-level frame
+    pub(crate) registers: usize,
+    /// Effects of the translated code
+    pub(crate) effect: EffectGrade,
+}
+
+impl RegisterProgram {
+    /// The register code
+    pub fn code(&self) -> &[RegisterInstruction] {
+        &self.code
+    }
+
+    /// The functions, indexed like those of the stack program
+    pub fn functions(&self) -> &[RegisterFunction] {
+        &self.functions
+    }
+
+    /// Registers of the top-level frame
+    pub fn registers(&self) -> usize {
+        self.registers
+    }
+
+    /// Effects of the translated code
+    pub fn effect_grade(&self) -> EffectGrade {
+        self.effect
+    }
+}
+
+/// An active call
+struct RegisterFrame {
+    return_pc: usize,
+    base: usize,
+    dst: u32,
+}
+
+/// Fuel for an instruction, charged as for the stack code it replaces
+fn fuel_cost(instruction: &RegisterInstruction) -> u64 {
+    match instruction {
+        RegisterInstruction::Apply { op, .. } => op.fuel_cost(),
+        RegisterInstruction::Collect { .. } => Bytecode::ListNew(EffectGrade::Pure).fuel_cost(),
+        RegisterInstruction::Call { function, .. } => Bytecode::Call(*function, EffectGrade::Pure).fuel_cost(),
+        RegisterInstruction::Ret(_) => Bytecode::Ret(EffectGrade::Pure).fuel_cost(),
+        RegisterInstruction::Print(_) => Bytecode::Print(EffectGrade::IO).fuel_cost(),
+        _ => 1,
+    }
+}
+
+fn read(registers: &[Value], base: usize, constants: &[Value], operand: &Operand) -> Value {
+    match operand {
+        Operand::Reg(register) => registers[base + *register as usize].clone(),
+        Operand::Const(index) => constants[*index as usize].clone(),
+    }
+}
+
+impl BytecodeVM {
+    /// Run register code, returning the program's result
+    pub fn execute_registers(&mut self, program: &RegisterProgram) -> BytecodeResult<Value> {
+        self.begin();
+        self.context.update_effect(program.effect);
+
+        let constants = &program.constants;
+        let mut registers = vec![Value::Null; program.registers];
+        let mut frames: Vec<RegisterFrame> = Vec::new();
+        let mut base = 0;
+        let mut pc = 0;
+
+        loop {
+            let instruction = program.code.get(pc).ok_or_else(|| {
+                BytecodeError::InvalidOperand(format!("Register code has no instruction {}", pc))
+            })?;
+            self.context.charge(fuel_cost(instruction), pc)?;
+            self.stats.instructions_executed += 1;
+
+            match instruction {
+                RegisterInstruction::Move { dst, src } => {
+                    registers[base + *dst as usize] = read(&registers, base, constants, src);
+                    pc += 1;
+                }
+                RegisterInstruction::Apply { op, dst, args } => {
+                    let mut values = [Value::Null, Value::Null, Value::Null];
+                    for (value, arg) in values.iter_mut().zip(args) {
+                        *value = read(&registers, base, constants, arg);
+                    }
+                    let result = BytecodeVM::operate(op, &mut values[..args.len()], constants)?;
+                    registers[base + *dst as usize] = result;
+                    pc += 1;
+                }
+                RegisterInstruction::DivRem { quotient, remainder, lhs, rhs } => {
+                    let a = read(&registers, base, constants, lhs);
+                    let b = read(&registers, base, constants, rhs);
+                    let (div, rem) = BytecodeVM::div_rem_values(a, b)?;
+                    registers[base + *quotient as usize] = div;
+                    registers[base + *remainder as usize] = rem;
+                    pc += 1;
+                }
+                RegisterInstruction::Collect { dst, items, tuple } => {
+                    let items = items.iter().map(|item| read(&registers, base, constants, item)).collect();
+                    registers[base + *dst as usize] = if *tuple { Value::Tuple(items) } else { Value::List(items) };
+                    pc += 1;
+                }
+                RegisterInstruction::Jump(target) => {
+                    pc = *target as usize;
+                }
+                RegisterInstruction::JumpIf { cond, target } => {
+                    if read(&registers, base, constants, cond).is_truthy() {
+                        pc = *target as usize;
+                    } else {
+                        pc += 1;
+                    }
+                }
+                RegisterInstruction::JumpIfNot { cond, target } => {
+                    if read(&registers, base, constants, cond).is_truthy() {
+                        pc += 1;
+                    } else {
+                        pc = *target as usize;
+                    }
+                }
+                RegisterInstruction::Call { function, args, dst } => {
+                    let callee = program.functions.get(*function as usize).ok_or_else(|| {
+                        BytecodeError::InvalidOperand(format!("Function {} not found", function))
+                    })?;
+                    let callee_base = registers.len();
+                    registers.resize(callee_base + callee.registers, Value::Null);
+                    for (i, arg) in args.iter().enumerate() {
+                        registers[callee_base + callee.arg_base as usize + i] = read(&registers, base, constants, arg);
+                    }
+                    frames.push(RegisterFrame { return_pc: pc + 1, base, dst: *dst });
+                    base = callee_base;
+                    pc = callee.entry;
+                    self.stats.function_calls += 1;
+                }
+                RegisterInstruction::Ret(value) => {
+                    let frame = frames.pop()
+                        .ok_or_else(|| BytecodeError::InvalidOperand("Call stack underflow".to_string()))?;
+                    let result = value.map(|value| read(&registers, base, constants, &value)).unwrap_or(Value::Null);
+                    registers.truncate(base);
+                    base = frame.base;
+                    registers[base + frame.dst as usize] = result;
+                    pc = frame.return_pc;
+                }
+                RegisterInstruction::Print(value) => {
+                    println!("{}", read(&registers, base, constants, value));
+                    pc += 1;
+                }
+                RegisterInstruction::Halt(result) => {
+                    return Ok(result.map(|result| read(&registers, base, constants, &result)).unwrap_or(Value::Null));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{assemble, BytecodeProgram, ExecutionBackend, StackToRegister};
+
+    const FIB: &str = "
+        .const 0 int 15
+        .const 1 int 2
+        .const 2 int 1
+        .function 0 fib params=1 locals=1
+
+        const 0
+        call fib
+        jump end
+        fib:
+        store 0
+        load 0
+        const 1
+        lt
+        jump_if_not recurse
+        load 0
+        ret
+        recurse:
+        load 0
+        const 2
+        sub
+        call fib
+        load 0
+        const 1
+        sub
+        call fib
+        add
+        ret
+        end:
+    ";
+
+    /// Result of a program on the stack backend and on the register backend
+    fn run_both(mut program: BytecodeProgram) -> (Value, Value) {
+        let stack = BytecodeVM::new().execute_program(&program).unwrap();
+        program.metadata.backend = ExecutionBackend::Register;
+        let register = BytecodeVM::new().execute_program(&program).unwrap();
+        (stack, register)
+    }
+
+    #[test]
+    fn test_fib() {
+        let program = assemble(FIB).unwrap();
+        assert_eq!(run_both(program), (Value::Int(610), Value::Int(610)));
+    }
+
+    #[test]
+    fn test_loop_with_stack_shuffles() {
+        // Sums 1..=10 into local 1, then returns (sum div 7, sum rem 7) as a tuple
+        let program = assemble("
+            .const 0 int 10
+            .const 1 int 0
+            .const 2 int 1
+            .const 3 int 7
+
+            const 0
+            store 0
+            const 1
+            store 1
+            loop:
+            load 0
+            jump_if_not done
+            load 1
+            load 0
+            swap
+            add
+            store 1
+            load 0
+            dup
+            const 2
+            sub
+            store 0
+            pop
+            jump loop
+            done:
+            load 1
+            const 3
+            div_rem
+            swap
+            tuple_new 2
+        ").unwrap();
+
+        let (stack, register) = run_both(program);
+        assert_eq!(stack, Value::Tuple(vec![Value::Int(7), Value::Int(6)]));
+        assert_eq!(register, stack);
+    }
+
+    #[test]
+    fn test_sort() {
+        let program = assemble("
+            .const 0 int 3
+            .const 1 int 1
+            .const 2 int 2
+            .const 3 int 3
+
+            const 0
+            const 1
+            const 2
+            const 3
+            list_new
+            array_sort
+        ").unwrap();
+
+        let registers = StackToRegister.translate(&program).unwrap();
+        assert_eq!(registers.code().len(), 3);
+        let (stack, register) = run_both(program);
+        assert_eq!(stack, Value::List(vec![Value::Int(1), Value::Int(2), Value::Int(3)]));
+        assert_eq!(register, stack);
+    }
+
+    #[test]
+    fn test_untranslatable() {
+        // The stack holds one value at `join` on one path and none on the other
+        let program = assemble("
+            .const 0 bool true
+            const 0
+            const 0
+            jump_if join
+            pop
+            join:
+            nop
+        ").unwrap();
+        assert!(StackToRegister.translate(&program).is_err());
+
+        let program = assemble("
+            .function 0 f params=0
+            call f
+            f:
+            ret
+        ").unwrap();
+        assert!(StackToRegister.translate(&program).is_err());
+    }
+}
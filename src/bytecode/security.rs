@@ -3,12 +3,13 @@
 //! This module implements fine-grained security controls for bytecode execution
 //! including permission systems, sandboxing, and resource limits.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashSet, VecDeque};
 use std::path::PathBuf;
 use std::net::SocketAddr;
 use std::time::{Duration, Instant, SystemTime};
 use serde::{Deserialize, Serialize};
 use crate::error::{BytecodeError, BytecodeResult};
+use crate::security::audit::{self, AuditKind};
 
 /// Security manager for bytecode execution
 pub struct SecurityManager {
@@ -22,8 +23,13 @@ pub struct SecurityManager {
     usage: ResourceUsage,
     /// Execution start time
     start_time: Instant,
+    /// Most recent audit events, oldest first
+    audit_log: VecDeque<SecurityEvent>,
 }
 
+/// Most audit events a manager keeps
+const AUDIT_LOG_LIMIT: usize = 1000;
+
 /// Permission types for bytecode operations
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Permission {
@@ -35,12 +41,20 @@ pub enum Permission {
     FileExecute(PathBuf),
     /// Network connect permission
     NetworkConnect(SocketAddr),
-    /// Network bind permission
+    /// Network bind permission; granted on an unspecified address, it
+    /// covers the port on every address
     NetworkBind(SocketAddr),
+    /// Network connect permission by host name, as `host:port`; granted,
+    /// either part may be `*`
+    NetworkHost(String),
     /// Process spawn permission
     ProcessSpawn,
-    /// System property access
+    /// System property access, such as an environment variable; granted,
+    /// a trailing `*` covers every name with that prefix
     SystemProperty(String),
+    /// Access to a named secret; granted, a trailing `*` covers every name
+    /// with that prefix
+    Secret(String),
     /// Memory allocation permission
     MemoryAlloc(usize),
     /// Timer creation permission
@@ -138,6 +152,8 @@ pub enum SecurityEventType {
     BlockedInstruction,
     /// Security violation
     SecurityViolation,
+    /// Code without a trusted signature was loaded or rejected
+    UntrustedCode,
 }
 
 impl SecurityManager {
@@ -149,6 +165,7 @@ impl SecurityManager {
             limits: ResourceLimits::default(),
             usage: ResourceUsage::default(),
             start_time: Instant::now(),
+            audit_log: VecDeque::new(),
         }
     }
     
@@ -160,6 +177,7 @@ impl SecurityManager {
             limits,
             usage: ResourceUsage::default(),
             start_time: Instant::now(),
+            audit_log: VecDeque::new(),
         }
     }
     
@@ -173,43 +191,35 @@ impl SecurityManager {
         self.permissions.remove(permission);
     }
     
-    /// Check if a permission is granted
+    /// Check if a permission is granted, exactly or by a permission
+    /// covering it
     pub fn check_permission(&self, permission: &Permission) -> bool {
-        // Check for exact permission
-        if self.permissions.contains(permission) {
-            return true;
-        }
-        
-        // Check for wildcard permissions
-        match permission {
-            Permission::FileRead(path) | Permission::FileWrite(path) | Permission::FileExecute(path) => {
-                self.permissions.contains(&Permission::FileAll)
-            }
-            Permission::NetworkConnect(_) | Permission::NetworkBind(_) => {
-                self.permissions.contains(&Permission::NetworkAll)
-            }
-            Permission::ProcessSpawn | Permission::SystemProperty(_) => {
-                self.permissions.contains(&Permission::SystemAll)
-            }
-            _ => false,
-        }
+        self.permissions.contains(permission)
+            || self.permissions.iter().any(|granted| granted.covers(permission))
     }
     
     /// Check and enforce a permission
     pub fn enforce_permission(&mut self, permission: Permission) -> BytecodeResult<()> {
+        let context = format!("Permission check for {:?}", permission);
+        self.enforce_permission_for(permission, context)
+    }
+
+    /// Check and enforce a permission, auditing a denial with `context`
+    pub fn enforce_permission_for(&mut self, permission: Permission, context: String) -> BytecodeResult<()> {
         let granted = if self.policy.default_deny {
             self.check_permission(&permission)
         } else {
             !self.policy.sandbox_mode || self.check_permission(&permission)
         };
         
-        if self.policy.audit_logging {
+        // Denials are audited; granted checks are the normal case
+        if self.policy.audit_logging && !granted {
             self.log_security_event(SecurityEvent {
                 timestamp: SystemTime::now(),
                 event_type: SecurityEventType::PermissionCheck,
                 permission: Some(permission.clone()),
                 granted,
-                context: format!("Permission check for {:?}", permission),
+                context,
             });
         }
         
@@ -310,20 +320,40 @@ impl SecurityManager {
         &self.usage
     }
     
+    /// Audit events recorded so far, oldest first
+    pub fn audit_log(&self) -> impl Iterator<Item = &SecurityEvent> {
+        self.audit_log.iter()
+    }
+
     /// Get security policy
     pub fn get_policy(&self) -> &SecurityPolicy {
         &self.policy
     }
     
+    /// Get resource limits
+    pub fn get_limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+
     /// Update security policy
     pub fn update_policy(&mut self, policy: SecurityPolicy) {
         self.policy = policy;
     }
     
-    /// Log a security event
-    fn log_security_event(&self, event: SecurityEvent) {
-        // In a real implementation, this would write to an audit log
-        eprintln!("SECURITY EVENT: {:?}", event);
+    /// Log a security event and keep it in the audit log
+    fn log_security_event(&mut self, event: SecurityEvent) {
+        tracing::warn!(target: "ream::audit", event_type = ?event.event_type, permission = ?event.permission, context = %event.context, "Security event");
+        if !event.granted {
+            let subject = match &event.permission {
+                Some(permission) => format!("{:?}", permission),
+                None => format!("{:?}", event.event_type),
+            };
+            audit::record(AuditKind::PermissionDenied, subject, &event.context);
+        }
+        if self.audit_log.len() == AUDIT_LOG_LIMIT {
+            self.audit_log.pop_front();
+        }
+        self.audit_log.push_back(event);
     }
 }
 
@@ -333,6 +363,45 @@ impl Default for SecurityManager {
     }
 }
 
+impl Permission {
+    /// Whether granting `self` grants `other` too
+    fn covers(&self, other: &Permission) -> bool {
+        use Permission::*;
+        match (self, other) {
+            (FileAll, FileRead(_) | FileWrite(_) | FileExecute(_)) => true,
+            (FileRead(dir), FileRead(path)) | (FileWrite(dir), FileWrite(path)) | (FileExecute(dir), FileExecute(path)) => {
+                path.starts_with(dir)
+            }
+            (NetworkAll, NetworkConnect(_) | NetworkBind(_) | NetworkHost(_)) => true,
+            (NetworkBind(granted), NetworkBind(addr)) => {
+                granted.port() == addr.port() && (granted.ip().is_unspecified() || granted.ip() == addr.ip())
+            }
+            (NetworkHost(pattern), NetworkHost(target)) => {
+                let (host, port) = pattern.rsplit_once(':').unwrap_or((pattern, "*"));
+                match target.rsplit_once(':') {
+                    Some((target_host, target_port)) => {
+                        (host == "*" || host.eq_ignore_ascii_case(target_host)) && (port == "*" || port == target_port)
+                    }
+                    None => false,
+                }
+            }
+            (SystemAll, ProcessSpawn | SystemProperty(_)) => true,
+            (SystemProperty(pattern), SystemProperty(name)) | (Secret(pattern), Secret(name)) => match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => pattern == name,
+            },
+            _ => self == other,
+        }
+    }
+}
+
+impl SecurityPolicy {
+    /// A policy denying everything not explicitly granted
+    pub fn default_deny() -> Self {
+        SecurityPolicy { default_deny: true, ..Self::default() }
+    }
+}
+
 impl Default for SecurityPolicy {
     fn default() -> Self {
         let mut allowed_effects = HashSet::new();
@@ -350,6 +419,18 @@ impl Default for SecurityPolicy {
     }
 }
 
+impl ResourceLimits {
+    /// Maximum memory allocation (bytes)
+    pub fn max_memory(&self) -> usize {
+        self.max_memory
+    }
+
+    /// Maximum instruction count
+    pub fn max_instructions(&self) -> u64 {
+        self.max_instructions
+    }
+}
+
 impl Default for ResourceLimits {
     fn default() -> Self {
         ResourceLimits {
@@ -425,6 +506,37 @@ mod tests {
         assert!(manager.check_permission(&write_permission));
     }
 
+    #[test]
+    fn test_scoped_permissions() {
+        let mut manager = SecurityManager::with_policy(SecurityPolicy::default_deny(), ResourceLimits::default());
+        manager.grant_permission(Permission::FileRead(PathBuf::from("/srv/app")));
+        manager.grant_permission(Permission::NetworkHost("api.example.com:443".to_string()));
+        manager.grant_permission(Permission::NetworkHost("*:80".to_string()));
+        manager.grant_permission(Permission::NetworkBind("0.0.0.0:8080".parse().unwrap()));
+        manager.grant_permission(Permission::SystemProperty("APP_*".to_string()));
+
+        assert!(manager.check_permission(&Permission::FileRead(PathBuf::from("/srv/app/data/users.json"))));
+        assert!(!manager.check_permission(&Permission::FileRead(PathBuf::from("/srv/application"))));
+        assert!(!manager.check_permission(&Permission::FileWrite(PathBuf::from("/srv/app/data"))));
+
+        assert!(manager.check_permission(&Permission::NetworkHost("API.example.com:443".to_string())));
+        assert!(manager.check_permission(&Permission::NetworkHost("example.org:80".to_string())));
+        assert!(!manager.check_permission(&Permission::NetworkHost("api.example.com:8443".to_string())));
+        assert!(manager.check_permission(&Permission::NetworkBind("127.0.0.1:8080".parse().unwrap())));
+        assert!(!manager.check_permission(&Permission::NetworkBind("127.0.0.1:8081".parse().unwrap())));
+
+        assert!(manager.check_permission(&Permission::SystemProperty("APP_MODE".to_string())));
+        assert!(!manager.check_permission(&Permission::SystemProperty("HOME".to_string())));
+
+        // Only denials are audited
+        assert!(manager.enforce_permission(Permission::SystemProperty("APP_MODE".to_string())).is_ok());
+        assert!(manager.enforce_permission(Permission::ProcessSpawn).is_err());
+        let audited: Vec<_> = manager.audit_log().collect();
+        assert_eq!(audited.len(), 1);
+        assert_eq!(audited[0].permission, Some(Permission::ProcessSpawn));
+        assert!(!audited[0].granted);
+    }
+
     #[test]
     fn test_resource_limits() {
         let mut manager = SecurityManager::new();
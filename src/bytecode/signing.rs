@@ -0,0 +1,297 @@
+//! Bundle signing
+//!
+//! `ream sign` signs a bytecode bundle with an Ed25519 key, and VMs given a
+//! `SignaturePolicy` check the signature of every bundle they load against
+//! a store of trusted public keys. Under `warn` an unsigned or untrusted
+//! bundle still loads; under `deny` it is rejected. Either way the rejection
+//! is audited.
+//!
+//! Keys are files holding the hex-encoded 32-byte key: `ream keygen k`
+//! writes the signing key to `k` and its public key to `k.pub`. A trusted
+//! keys store is a directory of `.pub` files, each named after its key.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::bytecode::{BytecodeBundle, SecurityEvent, SecurityEventType};
+use crate::error::{BytecodeError, BytecodeResult};
+use crate::security::audit::{self, AuditKind};
+
+/// Extension of public key files
+pub const PUBLIC_KEY_EXTENSION: &str = "pub";
+
+/// Rejections kept in a policy's audit log
+const AUDIT_LOG_LIMIT: usize = 1000;
+
+/// Signature carried by a signed bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleSignature {
+    /// Ed25519 public key of the signer
+    pub public_key: Vec<u8>,
+    /// Signature over the bundle without its signature
+    pub signature: Vec<u8>,
+}
+
+/// Ed25519 key bundles are signed with
+pub struct SigningKey {
+    pair: Ed25519KeyPair,
+}
+
+impl SigningKey {
+    /// A new random key
+    pub fn generate() -> Self {
+        let seed = Zeroizing::new(rand::random::<[u8; 32]>());
+        Self::from_seed(&*seed).expect("32 random bytes are a valid seed")
+    }
+
+    /// The key with this 32-byte seed
+    pub fn from_seed(seed: &[u8]) -> BytecodeResult<Self> {
+        Ed25519KeyPair::from_seed_unchecked(seed)
+            .map(|pair| SigningKey { pair })
+            .map_err(|e| BytecodeError::SecurityViolation(format!("Invalid signing key: {}", e)))
+    }
+
+    /// Read a key file
+    pub fn load(path: &Path) -> BytecodeResult<Self> {
+        let text = Zeroizing::new(read_key_file(path)?);
+        let seed = Zeroizing::new(hex::decode(text.trim()).map_err(|e| {
+            BytecodeError::SecurityViolation(format!("Invalid signing key {}: {}", path.display(), e))
+        })?);
+        Self::from_seed(&seed)
+    }
+
+    /// Public key verifying this key's signatures
+    pub fn public_key(&self) -> &[u8] {
+        self.pair.public_key().as_ref()
+    }
+
+    /// Sign `message`
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.pair.sign(message).as_ref().to_vec()
+    }
+}
+
+/// Generate a key and write it to `path`, readable only by its owner, with
+/// its public key at `path.pub`; an existing key is never overwritten
+pub fn generate_key_file(path: &Path) -> BytecodeResult<SigningKey> {
+    let seed = Zeroizing::new(rand::random::<[u8; 32]>());
+    let key = SigningKey::from_seed(&*seed)?;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options.open(path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, Zeroizing::new(hex::encode(*seed)).as_bytes()));
+    written.map_err(|e| BytecodeError::SecurityViolation(format!("Failed to write {}: {}", path.display(), e)))?;
+
+    let public = public_key_path(path);
+    std::fs::write(&public, format!("{}\n", hex::encode(key.public_key())))
+        .map_err(|e| BytecodeError::SecurityViolation(format!("Failed to write {}: {}", public.display(), e)))?;
+    Ok(key)
+}
+
+/// File the public key of the key at `path` is written to
+pub fn public_key_path(path: &Path) -> PathBuf {
+    let mut public = path.as_os_str().to_owned();
+    public.push(".");
+    public.push(PUBLIC_KEY_EXTENSION);
+    PathBuf::from(public)
+}
+
+fn read_key_file(path: &Path) -> BytecodeResult<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| BytecodeError::SecurityViolation(format!("Failed to read key {}: {}", path.display(), e)))
+}
+
+/// Public keys whose signatures are trusted, by name
+#[derive(Debug, Clone, Default)]
+pub struct TrustedKeys {
+    keys: BTreeMap<String, Vec<u8>>,
+}
+
+impl TrustedKeys {
+    /// An empty store, trusting no one
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every `.pub` file in a directory
+    pub fn load(dir: &Path) -> BytecodeResult<Self> {
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            BytecodeError::SecurityViolation(format!("Failed to read trusted keys {}: {}", dir.display(), e))
+        })?;
+        let mut trusted = TrustedKeys::new();
+        for entry in entries {
+            let path = entry.map_err(|e| BytecodeError::SecurityViolation(e.to_string()))?.path();
+            if path.extension().is_none_or(|ext| ext != PUBLIC_KEY_EXTENSION) {
+                continue;
+            }
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            let key = hex::decode(read_key_file(&path)?.trim()).map_err(|e| {
+                BytecodeError::SecurityViolation(format!("Invalid public key {}: {}", path.display(), e))
+            })?;
+            trusted.add(name, key);
+        }
+        Ok(trusted)
+    }
+
+    /// Trust a public key
+    pub fn add(&mut self, name: impl Into<String>, public_key: Vec<u8>) {
+        self.keys.insert(name.into(), public_key);
+    }
+
+    /// Name of a trusted key
+    pub fn name_of(&self, public_key: &[u8]) -> Option<&str> {
+        self.keys.iter()
+            .find(|(_, key)| key.as_slice() == public_key)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Number of trusted keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether no key is trusted
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// What happens to bundles without a trusted signature
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum SignatureEnforcement {
+    /// Signatures are not checked
+    #[default]
+    Off,
+    /// The bundle loads, and the failed check is audited
+    Warn,
+    /// The bundle is rejected, and the rejection is audited
+    Deny,
+}
+
+/// Which signatures a VM accepts and how strictly
+#[derive(Debug, Clone, Default)]
+pub struct SignaturePolicy {
+    trusted: TrustedKeys,
+    enforcement: SignatureEnforcement,
+    audit_log: VecDeque<SecurityEvent>,
+}
+
+impl SignaturePolicy {
+    /// Accept bundles signed with one of `trusted`
+    pub fn new(trusted: TrustedKeys, enforcement: SignatureEnforcement) -> Self {
+        SignaturePolicy { trusted, enforcement, audit_log: VecDeque::new() }
+    }
+
+    /// Check the signature of `bundle`
+    pub fn check(&mut self, bundle: &BytecodeBundle) -> BytecodeResult<()> {
+        if self.enforcement == SignatureEnforcement::Off {
+            return Ok(());
+        }
+        let problem = match &bundle.signature {
+            None => "is not signed".to_string(),
+            Some(signature) => match self.trusted.name_of(&signature.public_key) {
+                None => format!("is signed by untrusted key {}", hex::encode(&signature.public_key)),
+                Some(name) => {
+                    let valid = UnparsedPublicKey::new(&ED25519, &signature.public_key)
+                        .verify(&bundle.signed_bytes()?, &signature.signature)
+                        .is_ok();
+                    if valid {
+                        return Ok(());
+                    }
+                    format!("has an invalid signature for key {}", name)
+                }
+            },
+        };
+
+        let denied = self.enforcement == SignatureEnforcement::Deny;
+        let context = format!("Bundle {} v{} {}", bundle.entry.name, bundle.entry.version, problem);
+        tracing::warn!(target: "ream::audit", denied, "{}", context);
+        let outcome = if denied { "rejected" } else { "loaded under warn" };
+        audit::record(AuditKind::UntrustedCode, &bundle.entry.name, format!("{}; {}", context, outcome));
+        if self.audit_log.len() == AUDIT_LOG_LIMIT {
+            self.audit_log.pop_front();
+        }
+        self.audit_log.push_back(SecurityEvent {
+            timestamp: SystemTime::now(),
+            event_type: SecurityEventType::UntrustedCode,
+            permission: None,
+            granted: !denied,
+            context: context.clone(),
+        });
+
+        if denied {
+            Err(BytecodeError::SecurityViolation(context))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Bundles that failed the check, oldest first
+    pub fn audit_log(&self) -> impl Iterator<Item = &SecurityEvent> {
+        self.audit_log.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{Bytecode, BytecodeProgram, BytecodeVM, BundleModule, Value};
+    use crate::types::EffectGrade;
+
+    fn bundle(value: i64) -> BytecodeBundle {
+        let mut program = BytecodeProgram::new("app".to_string());
+        let const_id = program.add_constant(Value::Int(value));
+        program.add_instruction(Bytecode::Const(const_id, EffectGrade::Pure));
+        BytecodeBundle::new(BundleModule::new("app".to_string(), "0.1.0".to_string(), "app.tl".to_string(), program))
+    }
+
+    #[test]
+    fn test_signed_bundles_verified_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("release");
+        let key = generate_key_file(&key_path).unwrap();
+        assert!(generate_key_file(&key_path).is_err(), "keys are never overwritten");
+        let loaded = SigningKey::load(&key_path).unwrap();
+        assert_eq!(loaded.public_key(), key.public_key());
+
+        let keys = dir.path().join("trusted");
+        std::fs::create_dir(&keys).unwrap();
+        std::fs::copy(public_key_path(&key_path), keys.join("release.pub")).unwrap();
+        let trusted = TrustedKeys::load(&keys).unwrap();
+        assert_eq!(trusted.name_of(key.public_key()), Some("release"));
+
+        let mut signed = bundle(42);
+        signed.sign(&loaded).unwrap();
+        let signed = BytecodeBundle::from_bytes(&signed.to_bytes().unwrap()).unwrap();
+        let mut tampered = signed.clone();
+        tampered.entry.program.constants[0] = Value::Int(666);
+        let mut untrusted = bundle(42);
+        untrusted.sign(&SigningKey::generate()).unwrap();
+
+        let mut vm = BytecodeVM::new();
+        vm.set_signature_policy(Some(SignaturePolicy::new(trusted.clone(), SignatureEnforcement::Deny)));
+        vm.load_bundle(&signed).unwrap();
+        assert!(matches!(vm.execute("app").unwrap(), Value::Int(42)));
+        for rejected in [&bundle(42), &tampered, &untrusted] {
+            let err = vm.load_bundle(rejected).unwrap_err();
+            assert!(matches!(err, BytecodeError::SecurityViolation(_)), "{}", err);
+        }
+        let audited: Vec<_> = vm.signature_policy().unwrap().audit_log().collect();
+        assert_eq!(audited.len(), 3);
+        assert!(audited[0].context.contains("is not signed"), "{}", audited[0].context);
+        assert!(audited[1].context.contains("invalid signature for key release"), "{}", audited[1].context);
+        assert!(audited.iter().all(|event| !event.granted));
+
+        // Warn loads the bundle anyway, but still audits it
+        vm.set_signature_policy(Some(SignaturePolicy::new(trusted, SignatureEnforcement::Warn)));
+        assert!(matches!(bundle(7).execute(&mut vm).unwrap(), Value::Int(7)));
+        assert!(vm.signature_policy().unwrap().audit_log().all(|event| event.granted));
+    }
+}
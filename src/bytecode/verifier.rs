@@ -4,7 +4,8 @@
 //! safe execution of untrusted code as specified in IMPROVEMENT.md
 
 use std::collections::{HashMap, HashSet};
-use crate::bytecode::{BytecodeProgram, Value, Bytecode};
+use crate::bytecode::{BytecodeProgram, NativeRegistry, Value, Bytecode};
+use crate::bytecode::instruction::cast;
 use crate::types::EffectGrade;
 use crate::error::{BytecodeError, BytecodeResult};
 
@@ -85,6 +86,8 @@ pub struct BytecodeVerifier {
     valid_jump_targets: HashSet<usize>,
     /// Resource usage tracking
     resource_usage: ResourceUsage,
+    /// Native functions programs may call
+    natives: NativeRegistry,
 }
 
 /// Resource usage tracking for verification
@@ -105,6 +108,43 @@ struct ResourceUsage {
     max_timer_handles: u32,
 }
 
+/// Types on the operand stack and in the locals before an instruction
+#[derive(Debug, Clone)]
+struct FlowState {
+    stack: Vec<TypeInfo>,
+    /// Locals stored on every path to the instruction
+    locals: Vec<TypeInfo>,
+}
+
+impl FlowState {
+    /// Merge the state of another path into this one, returning whether
+    /// this state changed. Types that differ between paths become `Any`.
+    fn merge(&mut self, other: &FlowState, pc: usize) -> BytecodeResult<bool> {
+        if self.stack.len() != other.stack.len() {
+            return Err(BytecodeError::Verification(format!(
+                "Stack depth at PC {} is {} on one path and {} on another",
+                pc,
+                self.stack.len(),
+                other.stack.len()
+            )));
+        }
+        let mut changed = false;
+        if other.locals.len() < self.locals.len() {
+            self.locals.truncate(other.locals.len());
+            changed = true;
+        }
+        for (mine, theirs) in self.stack.iter_mut().chain(self.locals.iter_mut())
+            .zip(other.stack.iter().chain(other.locals.iter()))
+        {
+            if mine != theirs && *mine != TypeInfo::Any {
+                *mine = TypeInfo::Any;
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+}
+
 impl BytecodeVerifier {
     /// Create a new bytecode verifier with default limits
     pub fn new() -> Self {
@@ -127,6 +167,7 @@ impl BytecodeVerifier {
                 max_timer_handles: 20,
                 ..Default::default()
             },
+            natives: NativeRegistry::stdlib(),
         }
     }
     
@@ -144,58 +185,251 @@ impl BytecodeVerifier {
             allowed_effects,
             valid_jump_targets: HashSet::new(),
             resource_usage: ResourceUsage::default(),
+            natives: NativeRegistry::stdlib(),
         }
     }
+
+    /// Replace the native functions programs may call
+    pub fn set_natives(&mut self, natives: NativeRegistry) {
+        self.natives = natives;
+    }
+
+    /// Create a verifier with default limits that allows every effect
+    pub fn allowing_all_effects() -> Self {
+        let mut verifier = Self::new();
+        verifier.allowed_effects.extend([
+            EffectGrade::Read,
+            EffectGrade::Write,
+            EffectGrade::Send,
+            EffectGrade::Spawn,
+        ]);
+        verifier
+    }
     
     /// Verify a bytecode program
+    ///
+    /// The top-level code and every function body are checked separately by
+    /// following their control flow: each reachable instruction must find
+    /// the operands it pops, with types it accepts, and every path reaching
+    /// an instruction must leave the stack at the same depth. A function
+    /// starts with its arguments on the stack and must return with exactly
+    /// its result on it.
     pub fn verify(&mut self, program: &BytecodeProgram) -> BytecodeResult<()> {
         // Reset state
         self.type_stack.clear();
         self.locals_types.clear();
         self.valid_jump_targets.clear();
-        self.resource_usage = ResourceUsage::default();
-        
-        // First pass: collect jump targets
-        self.collect_jump_targets(program)?;
-        
-        // Second pass: verify instructions
-        self.verify_instructions(program)?;
+        self.resource_usage.memory_allocations = 0;
+        self.resource_usage.file_handles = 0;
+        self.resource_usage.socket_handles = 0;
+        self.resource_usage.timer_handles = 0;
+
+        let owners = self.function_owners(program)?;
+        self.verify_resources(program)?;
+
+        for function in 0..program.functions.len() {
+            self.verify_region(program, &owners, Some(function))?;
+        }
+        let result = self.verify_region(program, &owners, None)?;
+
+        // Functions must declare the effects they perform
+        self.verify_effects(program)?;
         
         // Final validation
         self.validate_final_state()?;
+
+        // Leave the types the program ends with for inspection
+        self.type_stack = result.map(|state| state.stack).unwrap_or_default();
         
         Ok(())
     }
-    
-    /// Collect all valid jump targets
-    fn collect_jump_targets(&mut self, program: &BytecodeProgram) -> BytecodeResult<()> {
-        for (pc, instruction) in program.instructions.iter().enumerate() {
-            self.valid_jump_targets.insert(pc);
-            
-            // Add function entry points
-            match instruction {
-                Bytecode::Call(func_idx, _) => {
-                    if let Some(function) = program.functions.get(*func_idx as usize) {
-                        self.valid_jump_targets.insert(function.start_pc);
-                    }
+
+    /// Check the function table, returning the function each instruction
+    /// belongs to, `None` for top-level code
+    fn function_owners(&self, program: &BytecodeProgram) -> BytecodeResult<Vec<Option<usize>>> {
+        let len = program.instructions.len();
+        let mut owners = vec![None; len];
+        for (index, function) in program.functions.iter().enumerate() {
+            let end = function.start_pc + function.instructions.len();
+            if function.instructions.is_empty() || end > len {
+                return Err(BytecodeError::Verification(format!(
+                    "Function {} has no code in the program",
+                    function.name
+                )));
+            }
+            for owner in &mut owners[function.start_pc..end] {
+                if let Some(other) = owner.replace(index) {
+                    return Err(BytecodeError::Verification(format!(
+                        "Function {} overlaps function {}",
+                        function.name, program.functions[other].name
+                    )));
                 }
+            }
+        }
+        if let Some(Some(function)) = owners.first() {
+            return Err(BytecodeError::Verification(format!(
+                "Program starts inside function {}",
+                program.functions[*function].name
+            )));
+        }
+        Ok(owners)
+    }
+
+    /// Count the resources the program acquires against the limits
+    fn verify_resources(&mut self, program: &BytecodeProgram) -> BytecodeResult<()> {
+        for instruction in &program.instructions {
+            match instruction {
+                Bytecode::Alloc(_, _) => self.verify_resource_allocation()?,
+                Bytecode::FileOpen(_, _, _) => self.verify_file_operation()?,
+                Bytecode::SocketCreate(_, _) => self.verify_socket_operation()?,
                 _ => {}
             }
         }
-        
-        // Add end of program as valid target
-        self.valid_jump_targets.insert(program.instructions.len());
-        
         Ok(())
     }
+
+    /// Verify the top-level code, or a function when `function` is given,
+    /// returning the state at the end of the program if the code reaches it
+    fn verify_region(
+        &mut self,
+        program: &BytecodeProgram,
+        owners: &[Option<usize>],
+        function: Option<usize>,
+    ) -> BytecodeResult<Option<FlowState>> {
+        let len = program.instructions.len();
+        self.collect_jump_targets(owners, function, len);
+
+        let (entry, params) = match function {
+            Some(index) => (program.functions[index].start_pc, program.functions[index].param_count),
+            None => (0, 0),
+        };
+        if params > self.max_stack_depth {
+            return Err(BytecodeError::Verification("Stack overflow".to_string()));
+        }
+        let mut states = HashMap::new();
+        states.insert(entry, FlowState { stack: vec![TypeInfo::Any; params], locals: Vec::new() });
+        let mut worklist = vec![entry];
+
+        while let Some(pc) = worklist.pop() {
+            if pc == len {
+                continue;
+            }
+            let state = states[&pc].clone();
+            self.type_stack = state.stack;
+            self.locals_types = state.locals;
+
+            let instruction = &program.instructions[pc];
+            if matches!(instruction, Bytecode::Ret(_)) && function.is_some() && self.type_stack.len() != 1 {
+                return Err(BytecodeError::Verification(format!(
+                    "Return at PC {} leaves {} values on the stack instead of 1",
+                    pc,
+                    self.type_stack.len()
+                )));
+            }
+            self.verify_instruction(instruction, pc, program)?;
+
+            let after = FlowState {
+                stack: std::mem::take(&mut self.type_stack),
+                locals: std::mem::take(&mut self.locals_types),
+            };
+            let successors = match instruction {
+                Bytecode::Jump(target, _) => vec![(*target as usize, after)],
+                Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) => {
+                    vec![(*target as usize, after.clone()), (pc + 1, after)]
+                }
+                Bytecode::Ret(_) | Bytecode::Throw(_) => Vec::new(),
+                // The handler starts from the stack at `try_begin` plus the
+                // exception, with locals the try may have changed since
+                Bytecode::TryBegin(target, _) => {
+                    let mut caught = after.clone();
+                    caught.stack.push(TypeInfo::Any);
+                    caught.locals.fill(TypeInfo::Any);
+                    vec![(pc + 1, after), (*target as usize, caught)]
+                }
+                _ => vec![(pc + 1, after)],
+            };
+            for (next, after) in successors {
+                if !self.valid_jump_targets.contains(&next) {
+                    return Err(BytecodeError::Verification(format!(
+                        "{} at PC {} runs out of its code",
+                        instruction.name(),
+                        pc
+                    )));
+                }
+                let changed = match states.get_mut(&next) {
+                    Some(known) => known.merge(&after, next)?,
+                    None => {
+                        states.insert(next, after);
+                        true
+                    }
+                };
+                if changed {
+                    worklist.push(next);
+                }
+            }
+        }
+
+        Ok(states.remove(&len))
+    }
     
-    /// Verify all instructions in the program
-    fn verify_instructions(&mut self, program: &BytecodeProgram) -> BytecodeResult<()> {
+    /// Check declared effect grades against the effects of instructions
+    ///
+    /// A function's grade must cover every instruction in its body, and a
+    /// call's grade must cover the grade of the function it calls, so a
+    /// caller's grade covers everything its callees do.
+    pub fn verify_effects(&self, program: &BytecodeProgram) -> BytecodeResult<()> {
+        for function in &program.functions {
+            let end = function.start_pc + function.instructions.len();
+            let body = program.instructions.get(function.start_pc..end).unwrap_or(&function.instructions);
+            for (offset, instruction) in body.iter().enumerate() {
+                if instruction.effect_grade() > function.effect_grade {
+                    return Err(BytecodeError::Verification(format!(
+                        "Function {} is declared {:?} but {} at PC {} is {:?}",
+                        function.name,
+                        function.effect_grade,
+                        instruction.name(),
+                        function.start_pc + offset,
+                        instruction.effect_grade()
+                    )));
+                }
+            }
+        }
+
         for (pc, instruction) in program.instructions.iter().enumerate() {
-            self.verify_instruction(instruction, pc, program)?;
+            let callee = match instruction {
+                Bytecode::Call(index, _) | Bytecode::CallDirect(index, _, _)
+                | Bytecode::ArrayMap(index, _) | Bytecode::ArrayFilter(index, _) => program.functions.get(*index as usize),
+                _ => None,
+            };
+            if let Some(callee) = callee {
+                if callee.effect_grade > instruction.effect_grade() {
+                    return Err(BytecodeError::Verification(format!(
+                        "{} at PC {} is {:?} but calls {}, which is {:?}",
+                        instruction.name(),
+                        pc,
+                        instruction.effect_grade(),
+                        callee.name,
+                        callee.effect_grade
+                    )));
+                }
+            }
         }
         Ok(())
     }
+
+    /// Collect the instructions the code of a function, or the top-level
+    /// code when `function` is `None`, may continue at
+    fn collect_jump_targets(&mut self, owners: &[Option<usize>], function: Option<usize>, len: usize) {
+        self.valid_jump_targets.clear();
+        self.valid_jump_targets.extend(
+            owners.iter().enumerate().filter(|(_, owner)| **owner == function).map(|(pc, _)| pc)
+        );
+
+        // Running off the end of the top-level code ends the program
+        if function.is_none() {
+            self.valid_jump_targets.insert(len);
+        }
+    }
     
     /// Verify a single instruction
     fn verify_instruction(
@@ -232,9 +466,50 @@ impl BytecodeVerifier {
                 self.verify_local_store(*idx, value_type)?;
             }
             
-            Bytecode::Add(_) | Bytecode::Sub(_) | Bytecode::Mul(_) | Bytecode::Div(_) => {
+            Bytecode::LoadGlobal(idx, _) => {
+                // Globals are read from the constant pool
+                self.verify_constant_access(*idx, program)?;
+                self.push_type(TypeInfo::from_value(&program.constants[*idx as usize]))?;
+            }
+
+            Bytecode::StoreGlobal(_, _) | Bytecode::Print(_) => {
+                self.pop_type()?;
+            }
+
+            Bytecode::Nop(_) | Bytecode::Ret(_) => {}
+
+            Bytecode::Add(_) => {
                 let b_type = self.pop_type()?;
                 let a_type = self.pop_type()?;
+                if a_type == TypeInfo::String && b_type == TypeInfo::String {
+                    self.push_type(TypeInfo::String)?;
+                } else {
+                    self.verify_arithmetic_operation(&a_type, &b_type)?;
+                    self.push_type(self.result_type_for_arithmetic(&a_type, &b_type))?;
+                }
+            }
+
+            Bytecode::Sub(_) | Bytecode::Mul(_) | Bytecode::Div(_)
+            | Bytecode::Min(_) | Bytecode::Max(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.verify_arithmetic_operation(&a_type, &b_type)?;
+                self.push_type(self.result_type_for_arithmetic(&a_type, &b_type))?;
+            }
+
+            Bytecode::AddConst(idx, _) => {
+                self.verify_constant_access(*idx, program)?;
+                let b_type = TypeInfo::from_value(&program.constants[*idx as usize]);
+                let a_type = self.pop_type()?;
+                self.verify_arithmetic_operation(&a_type, &b_type)?;
+                self.push_type(self.result_type_for_arithmetic(&a_type, &b_type))?;
+            }
+
+            Bytecode::AddLocals(a, b, _) => {
+                self.verify_local_access(*a)?;
+                self.verify_local_access(*b)?;
+                let a_type = self.locals_types[*a as usize].clone();
+                let b_type = self.locals_types[*b as usize].clone();
                 self.verify_arithmetic_operation(&a_type, &b_type)?;
                 self.push_type(self.result_type_for_arithmetic(&a_type, &b_type))?;
             }
@@ -246,6 +521,278 @@ impl BytecodeVerifier {
                 self.push_type(a_type)?;
             }
             
+            Bytecode::ShiftLeft(_) | Bytecode::ShiftRight(_) | Bytecode::UnsignedShiftRight(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.verify_bitwise_operation(&a_type, &a_type)?;
+                self.verify_operand_type(&b_type, &TypeInfo::Int, instruction)?;
+                self.push_type(a_type)?;
+            }
+
+            Bytecode::BitNot(_) => {
+                let a_type = self.pop_type()?;
+                self.verify_bitwise_operation(&a_type, &a_type)?;
+                self.push_type(a_type)?;
+            }
+            
+            Bytecode::Mod(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.verify_arithmetic_operation(&a_type, &b_type)?;
+                self.push_type(self.result_type_for_arithmetic(&a_type, &b_type))?;
+            }
+
+            Bytecode::DivRem(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.verify_arithmetic_operation(&a_type, &b_type)?;
+                let result = self.result_type_for_arithmetic(&a_type, &b_type);
+                self.push_type(result.clone())?;
+                self.push_type(result)?;
+            }
+
+            Bytecode::Abs(_) | Bytecode::Neg(_) => {
+                let a_type = self.pop_type()?;
+                self.verify_arithmetic_operation(&a_type, &a_type)?;
+                self.push_type(a_type)?;
+            }
+            
+            Bytecode::And(_) | Bytecode::Or(_) | Bytecode::Eq(_) => {
+                self.pop_type()?;
+                self.pop_type()?;
+                self.push_type(TypeInfo::Bool)?;
+            }
+            
+            Bytecode::Not(_) => {
+                self.pop_type()?;
+                self.push_type(TypeInfo::Bool)?;
+            }
+            
+            Bytecode::Lt(_) | Bytecode::Le(_) | Bytecode::Gt(_) | Bytecode::Ge(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.verify_comparison_operation(&a_type, &b_type)?;
+                self.push_type(TypeInfo::Bool)?;
+            }
+            
+            Bytecode::Sqrt(_) | Bytecode::Sin(_) | Bytecode::Cos(_) | Bytecode::Tan(_)
+            | Bytecode::Log(_) | Bytecode::Exp(_) => {
+                let a_type = self.pop_type()?;
+                self.verify_operand_type(&a_type, &TypeInfo::Float, instruction)?;
+                self.push_type(TypeInfo::Float)?;
+            }
+            
+            Bytecode::Pow(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.verify_arithmetic_operation(&a_type, &b_type)?;
+                self.push_type(TypeInfo::Float)?;
+            }
+            
+            Bytecode::Dup(_) => {
+                let top = self.pop_type()?;
+                self.push_type(top.clone())?;
+                self.push_type(top)?;
+            }
+            
+            Bytecode::Pop(_) => {
+                self.pop_type()?;
+            }
+            
+            Bytecode::Swap(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.push_type(b_type)?;
+                self.push_type(a_type)?;
+            }
+            
+            Bytecode::StrLen(_) => {
+                let s_type = self.pop_type()?;
+                self.verify_operand_type(&s_type, &TypeInfo::String, instruction)?;
+                self.push_type(TypeInfo::Int)?;
+            }
+            
+            Bytecode::StrConcat(_) | Bytecode::StrIndex(_) => {
+                for _ in 0..2 {
+                    let s_type = self.pop_type()?;
+                    self.verify_operand_type(&s_type, &TypeInfo::String, instruction)?;
+                }
+                let result = match instruction {
+                    Bytecode::StrConcat(_) => TypeInfo::String,
+                    _ => TypeInfo::Int,
+                };
+                self.push_type(result)?;
+            }
+            
+            Bytecode::StrSlice(start, end, _) => {
+                self.verify_slice_bounds(*start, *end)?;
+                let s_type = self.pop_type()?;
+                self.verify_operand_type(&s_type, &TypeInfo::String, instruction)?;
+                self.push_type(TypeInfo::String)?;
+            }
+            
+            Bytecode::StrSplit(idx, _) => {
+                self.verify_constant_access(*idx, program)?;
+                if !matches!(program.constants[*idx as usize], Value::String(_)) {
+                    return Err(BytecodeError::Verification(format!(
+                        "Delimiter constant {} is not a string",
+                        idx
+                    )));
+                }
+                let s_type = self.pop_type()?;
+                self.verify_operand_type(&s_type, &TypeInfo::String, instruction)?;
+                self.push_type(TypeInfo::List(Box::new(TypeInfo::String)))?;
+            }
+            
+            Bytecode::ListNew(_) => {
+                // The element count must be the constant pushed just before
+                let count = pc.checked_sub(1)
+                    .and_then(|prev| match program.instructions.get(prev) {
+                        Some(Bytecode::Const(idx, _)) => program.constants.get(*idx as usize),
+                        _ => None,
+                    })
+                    .and_then(|count| count.as_uint())
+                    .ok_or_else(|| BytecodeError::Verification(format!(
+                        "List at PC {} needs a constant element count",
+                        pc
+                    )))?;
+                self.pop_type()?;
+                let mut element_type = None;
+                for _ in 0..count {
+                    let item_type = self.pop_type()?;
+                    element_type = match element_type {
+                        None => Some(item_type),
+                        Some(element) if element == item_type => Some(element),
+                        Some(_) => Some(TypeInfo::Any),
+                    };
+                }
+                self.push_type(TypeInfo::List(Box::new(element_type.unwrap_or(TypeInfo::Any))))?;
+            }
+            
+            Bytecode::ListLen(_) => {
+                let list_type = self.pop_type()?;
+                self.verify_sequence_type(&list_type, instruction)?;
+                self.push_type(TypeInfo::Int)?;
+            }
+            
+            Bytecode::ListGet(_) => {
+                let index_type = self.pop_type()?;
+                let list_type = self.pop_type()?;
+                self.verify_operand_type(&index_type, &TypeInfo::Int, instruction)?;
+                self.verify_sequence_type(&list_type, instruction)?;
+                let element_type = match list_type {
+                    TypeInfo::List(element) => *element,
+                    _ => TypeInfo::Any,
+                };
+                self.push_type(element_type)?;
+            }
+            
+            Bytecode::ListSet(_) => {
+                self.pop_type()?;
+                let index_type = self.pop_type()?;
+                let list_type = self.pop_type()?;
+                self.verify_operand_type(&index_type, &TypeInfo::Int, instruction)?;
+                self.verify_list_type(&list_type, instruction)?;
+                self.push_type(TypeInfo::List(Box::new(TypeInfo::Any)))?;
+            }
+            
+            Bytecode::ListAppend(_) => {
+                self.pop_type()?;
+                let list_type = self.pop_type()?;
+                self.verify_list_type(&list_type, instruction)?;
+                self.push_type(TypeInfo::List(Box::new(TypeInfo::Any)))?;
+            }
+            
+            Bytecode::ArraySlice(start, end, _) => {
+                self.verify_slice_bounds(*start, *end)?;
+                let list_type = self.pop_type()?;
+                self.verify_list_type(&list_type, instruction)?;
+                self.push_type(list_type)?;
+            }
+            
+            Bytecode::ArrayConcat(_) => {
+                let b_type = self.pop_type()?;
+                let a_type = self.pop_type()?;
+                self.verify_list_type(&a_type, instruction)?;
+                self.verify_list_type(&b_type, instruction)?;
+                let result = if a_type == b_type { a_type } else { TypeInfo::List(Box::new(TypeInfo::Any)) };
+                self.push_type(result)?;
+            }
+            
+            Bytecode::ArraySort(_) => {
+                let list_type = self.pop_type()?;
+                self.verify_list_type(&list_type, instruction)?;
+                self.push_type(list_type)?;
+            }
+            
+            Bytecode::MapNew(_) => {
+                self.push_type(TypeInfo::Map(Box::new(TypeInfo::String), Box::new(TypeInfo::Any)))?;
+            }
+            
+            Bytecode::MapGet(_) | Bytecode::MapRemove(_) => {
+                let key_type = self.pop_type()?;
+                let map_type = self.pop_type()?;
+                self.verify_operand_type(&key_type, &TypeInfo::String, instruction)?;
+                self.verify_map_type(&map_type, instruction)?;
+                let result = match instruction {
+                    Bytecode::MapGet(_) => TypeInfo::Any,
+                    _ => map_type,
+                };
+                self.push_type(result)?;
+            }
+            
+            Bytecode::MapPut(_) => {
+                self.pop_type()?;
+                let key_type = self.pop_type()?;
+                let map_type = self.pop_type()?;
+                self.verify_operand_type(&key_type, &TypeInfo::String, instruction)?;
+                self.verify_map_type(&map_type, instruction)?;
+                self.push_type(TypeInfo::Map(Box::new(TypeInfo::String), Box::new(TypeInfo::Any)))?;
+            }
+            
+            Bytecode::MapKeys(_) | Bytecode::MapValues(_) | Bytecode::MapSize(_) => {
+                let map_type = self.pop_type()?;
+                self.verify_map_type(&map_type, instruction)?;
+                let result = match instruction {
+                    Bytecode::MapKeys(_) => TypeInfo::List(Box::new(TypeInfo::String)),
+                    Bytecode::MapValues(_) => TypeInfo::List(Box::new(TypeInfo::Any)),
+                    _ => TypeInfo::Int,
+                };
+                self.push_type(result)?;
+            }
+            
+            Bytecode::TupleNew(count, _) => {
+                let mut element_types = Vec::new();
+                for _ in 0..*count {
+                    element_types.push(self.pop_type()?);
+                }
+                element_types.reverse();
+                self.push_type(TypeInfo::Tuple(element_types))?;
+            }
+            
+            Bytecode::TypeOf(_) => {
+                self.pop_type()?;
+                self.push_type(TypeInfo::String)?;
+            }
+            
+            Bytecode::Cast(target, _) => {
+                self.pop_type()?;
+                let result = match *target {
+                    cast::INT => TypeInfo::Int,
+                    cast::UINT => TypeInfo::UInt,
+                    cast::FLOAT => TypeInfo::Float,
+                    cast::BOOL => TypeInfo::Bool,
+                    cast::STRING => TypeInfo::String,
+                    cast::NUMBER => TypeInfo::Any,
+                    cast::LIST => TypeInfo::List(Box::new(TypeInfo::Any)),
+                    _ => return Err(BytecodeError::Verification(format!(
+                        "Unknown cast target {} at PC {}",
+                        target, pc
+                    ))),
+                };
+                self.push_type(result)?;
+            }
+            
             Bytecode::Jump(target, _) => {
                 self.verify_jump_target(*target)?;
             }
@@ -259,25 +806,103 @@ impl BytecodeVerifier {
             Bytecode::Call(func_idx, _) => {
                 self.verify_function_call(*func_idx, program)?;
             }
+
+            Bytecode::CallDirect(func_idx, start_pc, _) => {
+                self.verify_function_call(*func_idx, program)?;
+                if program.functions[*func_idx as usize].start_pc != *start_pc as usize {
+                    return Err(BytecodeError::Verification(format!(
+                        "Call at PC {} caches start {} of function {}, which starts elsewhere",
+                        pc, start_pc, func_idx
+                    )));
+                }
+            }
             
+            Bytecode::CallNative(idx, effect) => {
+                self.verify_constant_access(*idx, program)?;
+                let Value::String(name) = &program.constants[*idx as usize] else {
+                    return Err(BytecodeError::Verification(format!(
+                        "Native function name constant {} is not a string",
+                        idx
+                    )));
+                };
+                let native = self.natives.get(name).ok_or_else(|| {
+                    BytecodeError::Verification(format!("Unknown native function {}", name))
+                })?;
+                if native.effect > *effect {
+                    return Err(BytecodeError::Verification(format!(
+                        "{} at PC {} is {:?} but {} is {:?}",
+                        instruction.name(), pc, effect, name, native.effect
+                    )));
+                }
+                for _ in 0..native.arity {
+                    self.pop_type()?;
+                }
+                self.push_type(TypeInfo::Any)?;
+            }
+
+            Bytecode::TryBegin(target, _) => {
+                self.verify_jump_target(*target)?;
+            }
+
+            Bytecode::TryEnd(_) => {}
+
+            Bytecode::Throw(_) => {
+                self.pop_type()?;
+            }
+
+            Bytecode::Spawn(count, effect) => {
+                self.require_effect(instruction, pc, *effect, EffectGrade::Spawn)?;
+                for _ in 0..*count {
+                    self.pop_type()?;
+                }
+                match self.pop_type()? {
+                    TypeInfo::Function(_, _) | TypeInfo::Any => {}
+                    other => return Err(BytecodeError::Verification(format!(
+                        "{} at PC {} expects a function, got {:?}", instruction.name(), pc, other
+                    ))),
+                }
+                self.push_type(TypeInfo::Pid)?;
+            }
+
+            Bytecode::Send(effect) => {
+                self.require_effect(instruction, pc, *effect, EffectGrade::Send)?;
+                let message = self.pop_type()?;
+                let pid = self.pop_type()?;
+                if !pid.is_compatible_with(&TypeInfo::Pid) {
+                    return Err(BytecodeError::Verification(format!(
+                        "{} at PC {} expects a pid, got {:?}", instruction.name(), pc, pid
+                    )));
+                }
+                self.push_type(message)?;
+            }
+
+            Bytecode::Receive(effect) => {
+                self.require_effect(instruction, pc, *effect, EffectGrade::Read)?;
+                self.push_type(TypeInfo::Any)?;
+            }
+
+            Bytecode::Yield(_) => {}
+
+            Bytecode::SelfPid(effect) => {
+                self.require_effect(instruction, pc, *effect, EffectGrade::Read)?;
+                self.push_type(TypeInfo::Pid)?;
+            }
+
             Bytecode::Alloc(size, _) => {
-                self.verify_resource_allocation()?;
                 self.push_type(TypeInfo::MemoryRef)?;
             }
             
             Bytecode::FileOpen(_, _, _) => {
-                self.verify_file_operation()?;
                 self.push_type(TypeInfo::FileHandle)?;
             }
             
             Bytecode::SocketCreate(_, _) => {
-                self.verify_socket_operation()?;
                 self.push_type(TypeInfo::SocketHandle)?;
             }
             
             _ => {
-                // For other instructions, perform basic stack validation
-                self.verify_basic_instruction(instruction)?;
+                // The stack effect of anything else is unknown
+                self.verify_basic_instruction(instruction, pc)?;
             }
         }
         
@@ -322,6 +947,17 @@ impl BytecodeVerifier {
         Ok(())
     }
     
+    /// Reject a process instruction graded below the effects it has
+    fn require_effect(&self, instruction: &Bytecode, pc: usize, effect: EffectGrade, needed: EffectGrade) -> BytecodeResult<()> {
+        if effect < needed {
+            return Err(BytecodeError::Verification(format!(
+                "{} at PC {} is {:?} but needs at least {:?}",
+                instruction.name(), pc, effect, needed
+            )));
+        }
+        Ok(())
+    }
+
     /// Verify local variable access
     fn verify_local_access(&self, idx: u32) -> BytecodeResult<()> {
         if idx as usize >= self.locals_types.len() {
@@ -354,6 +990,7 @@ impl BytecodeVerifier {
     /// Verify arithmetic operation types
     fn verify_arithmetic_operation(&self, a: &TypeInfo, b: &TypeInfo) -> BytecodeResult<()> {
         match (a, b) {
+            (TypeInfo::Any, _) | (_, TypeInfo::Any) => Ok(()),
             (TypeInfo::Int, TypeInfo::Int) |
             (TypeInfo::UInt, TypeInfo::UInt) |
             (TypeInfo::Float, TypeInfo::Float) |
@@ -371,6 +1008,7 @@ impl BytecodeVerifier {
     /// Get result type for arithmetic operation
     fn result_type_for_arithmetic(&self, a: &TypeInfo, b: &TypeInfo) -> TypeInfo {
         match (a, b) {
+            (TypeInfo::Any, _) | (_, TypeInfo::Any) => TypeInfo::Any,
             (TypeInfo::Float, _) | (_, TypeInfo::Float) => TypeInfo::Float,
             (TypeInfo::UInt, TypeInfo::UInt) => TypeInfo::UInt,
             _ => TypeInfo::Int,
@@ -380,6 +1018,7 @@ impl BytecodeVerifier {
     /// Verify bitwise operation types
     fn verify_bitwise_operation(&self, a: &TypeInfo, b: &TypeInfo) -> BytecodeResult<()> {
         match (a, b) {
+            (TypeInfo::Any, _) | (_, TypeInfo::Any) => Ok(()),
             (TypeInfo::Int, TypeInfo::Int) |
             (TypeInfo::UInt, TypeInfo::UInt) => Ok(()),
             _ => Err(BytecodeError::Verification(format!(
@@ -389,6 +1028,72 @@ impl BytecodeVerifier {
         }
     }
     
+    /// Verify comparison operand types: two numbers or two strings
+    fn verify_comparison_operation(&self, a: &TypeInfo, b: &TypeInfo) -> BytecodeResult<()> {
+        match (a, b) {
+            (TypeInfo::Any, _) | (_, TypeInfo::Any) |
+            (TypeInfo::String, TypeInfo::String) => Ok(()),
+            _ if self.verify_arithmetic_operation(a, b).is_ok() => Ok(()),
+            _ => Err(BytecodeError::Verification(format!(
+                "Cannot compare {:?} with {:?}",
+                a, b
+            ))),
+        }
+    }
+    
+    /// Verify an operand is compatible with the type an instruction expects
+    fn verify_operand_type(&self, actual: &TypeInfo, expected: &TypeInfo, instruction: &Bytecode) -> BytecodeResult<()> {
+        if actual.is_compatible_with(expected) {
+            Ok(())
+        } else {
+            Err(BytecodeError::Verification(format!(
+                "{} expects {:?}, found {:?}",
+                instruction.name(), expected, actual
+            )))
+        }
+    }
+    
+    /// Verify an operand is a list
+    fn verify_list_type(&self, type_info: &TypeInfo, instruction: &Bytecode) -> BytecodeResult<()> {
+        match type_info {
+            TypeInfo::List(_) | TypeInfo::Any => Ok(()),
+            _ => Err(BytecodeError::Verification(format!(
+                "{} expects a list, found {:?}",
+                instruction.name(), type_info
+            ))),
+        }
+    }
+    
+    /// Verify an operand is a list or a tuple
+    fn verify_sequence_type(&self, type_info: &TypeInfo, instruction: &Bytecode) -> BytecodeResult<()> {
+        match type_info {
+            TypeInfo::Tuple(_) => Ok(()),
+            _ => self.verify_list_type(type_info, instruction),
+        }
+    }
+    
+    /// Verify an operand is a map
+    fn verify_map_type(&self, type_info: &TypeInfo, instruction: &Bytecode) -> BytecodeResult<()> {
+        match type_info {
+            TypeInfo::Map(_, _) | TypeInfo::Any => Ok(()),
+            _ => Err(BytecodeError::Verification(format!(
+                "{} expects a map, found {:?}",
+                instruction.name(), type_info
+            ))),
+        }
+    }
+    
+    /// Verify slice bounds, where an end of `u32::MAX` means the end
+    fn verify_slice_bounds(&self, start: u32, end: u32) -> BytecodeResult<()> {
+        if end != u32::MAX && start > end {
+            return Err(BytecodeError::Verification(format!(
+                "Slice start {} is past its end {}",
+                start, end
+            )));
+        }
+        Ok(())
+    }
+    
     /// Verify boolean condition
     fn verify_boolean_condition(&self, type_info: &TypeInfo) -> BytecodeResult<()> {
         // Any type can be used as a boolean condition (truthiness)
@@ -406,17 +1111,16 @@ impl BytecodeVerifier {
         Ok(())
     }
     
-    /// Verify function call
+    /// Verify function call: the arguments are popped and a result of
+    /// unknown type is pushed
     fn verify_function_call(&mut self, func_idx: u32, program: &BytecodeProgram) -> BytecodeResult<()> {
-        if func_idx as usize >= program.functions.len() {
-            return Err(BytecodeError::Verification(format!(
-                "Function index {} out of bounds",
-                func_idx
-            )));
-        }
+        let function = program.functions.get(func_idx as usize).ok_or_else(|| {
+            BytecodeError::Verification(format!("Function index {} out of bounds", func_idx))
+        })?;
         
-        // For now, assume functions can be called with any arguments
-        // In a more sophisticated verifier, we'd check argument types
+        for _ in 0..function.param_count {
+            self.pop_type()?;
+        }
         self.push_type(TypeInfo::Any)?;
         Ok(())
     }
@@ -454,11 +1158,13 @@ impl BytecodeVerifier {
         Ok(())
     }
     
-    /// Verify basic instruction (placeholder for other instructions)
-    fn verify_basic_instruction(&mut self, instruction: &Bytecode) -> BytecodeResult<()> {
-        // Basic verification for instructions not specifically handled
-        // This would be expanded in a full implementation
-        Ok(())
+    /// Reject an instruction whose stack effect the verifier cannot check
+    fn verify_basic_instruction(&self, instruction: &Bytecode, pc: usize) -> BytecodeResult<()> {
+        Err(BytecodeError::Verification(format!(
+            "{} at PC {} is not supported by the VM",
+            instruction.name(),
+            pc
+        )))
     }
     
     /// Validate final state after verification
@@ -511,6 +1217,31 @@ mod tests {
         assert!(verifier.verify(&program).is_ok());
     }
 
+    #[test]
+    fn test_collection_typing() {
+        let mut verifier = BytecodeVerifier::new();
+        let program = crate::bytecode::assemble("\
+.const 0 string \"a b\"
+.const 1 string \" \"
+.const 2 int 1
+    const 0 pure
+    str_split 1 pure
+    const 2 pure
+    list_get pure
+    str_len pure
+    const 2 pure
+    lt pure
+").unwrap();
+        verifier.verify(&program).unwrap();
+        assert_eq!(verifier.type_stack, vec![TypeInfo::Bool]);
+
+        // Indexing a string and counting a list without a constant are rejected
+        let program = crate::bytecode::assemble(".const 0 string \"ab\"\n    const 0 pure\n    const 0 pure\n    list_get pure\n").unwrap();
+        assert!(verifier.verify(&program).is_err());
+        let program = crate::bytecode::assemble(".const 0 int 1\n    const 0 pure\n    dup pure\n    list_new pure\n").unwrap();
+        assert!(verifier.verify(&program).is_err());
+    }
+
     #[test]
     fn test_stack_operations() {
         let mut verifier = BytecodeVerifier::new();
@@ -523,4 +1254,163 @@ mod tests {
         // Test underflow
         assert!(verifier.pop_type().is_err());
     }
+
+    #[test]
+    fn test_effect_verification() {
+        let verifier = BytecodeVerifier::new();
+        let source = |declared: &str, call: &str| format!("
+            .const 0 string \"hi\"
+            .function 0 shout params=0 effect={}
+            call shout {}
+            jump end
+            shout:
+            const 0
+            print io
+            const 0
+            ret
+            end:
+        ", declared, call);
+
+        let program = crate::bytecode::assemble(&source("io", "io")).unwrap();
+        verifier.verify_effects(&program).unwrap();
+
+        let program = crate::bytecode::assemble(&source("io", "pure")).unwrap();
+        let err = verifier.verify_effects(&program).unwrap_err().to_string();
+        assert!(err.contains("calls shout, which is IO"), "{}", err);
+
+        let program = crate::bytecode::assemble(&source("pure", "pure")).unwrap();
+        assert!(crate::bytecode::disassemble(&program).contains("effect=pure"));
+        let err = verifier.verify_effects(&program).unwrap_err().to_string();
+        assert!(err.contains("declared Pure but print"), "{}", err);
+    }
+
+    #[test]
+    fn test_control_flow_verification() {
+        let mut verifier = BytecodeVerifier::allowing_all_effects();
+        let fib = crate::bytecode::assemble("
+            .const 0 int 10
+            .const 1 int 2
+            .const 2 int 1
+            .function 0 fib params=1 locals=1
+            const 0
+            call fib
+            jump end
+            fib:
+            store 0
+            load 0
+            const 1
+            lt
+            jump_if_not recurse
+            load 0
+            ret
+            recurse:
+            load 0
+            const 2
+            sub
+            call fib
+            load 0
+            const 1
+            sub
+            call fib
+            add
+            ret
+            end:
+        ").unwrap();
+        verifier.verify(&fib).unwrap();
+        assert_eq!(verifier.type_stack, vec![TypeInfo::Any]);
+
+        let rejected = |verifier: &mut BytecodeVerifier, source: &str| {
+            let program = crate::bytecode::assemble(source).unwrap();
+            verifier.verify(&program).unwrap_err().to_string()
+        };
+
+        // One path to `join` leaves a value that the other pops
+        let err = rejected(&mut verifier, "
+            .const 0 bool true
+            const 0
+            const 0
+            jump_if join
+            pop
+            join:
+            nop
+        ");
+        assert!(err.contains("Stack depth at PC 4 is"), "{}", err);
+
+        // Types that differ between paths are only known at runtime
+        verifier.verify(&crate::bytecode::assemble("
+            .const 0 bool true
+            .const 1 int 1
+            .const 2 string \"a\"
+            const 0
+            jump_if other
+            const 1
+            jump join
+            other:
+            const 2
+            join:
+            str_len
+        ").unwrap()).unwrap_err();
+
+        let err = rejected(&mut verifier, ".const 0 int 1\nconst 0\nadd\n");
+        assert!(err.contains("underflow"), "{}", err);
+        let err = rejected(&mut verifier, "const 3\n");
+        assert!(err.contains("Constant index 3"), "{}", err);
+        let err = rejected(&mut verifier, "load 0\n");
+        assert!(err.contains("Local variable index 0"), "{}", err);
+
+        // A function returns exactly its result and keeps to its own code
+        let err = rejected(&mut verifier, "
+            .const 0 int 1
+            .function 0 f params=1
+            const 0
+            call f
+            jump end
+            f:
+            const 0
+            ret
+            end:
+        ");
+        assert!(err.contains("leaves 2 values"), "{}", err);
+        let err = rejected(&mut verifier, "
+            .const 0 int 1
+            .function 0 f params=0
+            jump inside
+            f:
+            const 0
+            inside:
+            ret
+        ");
+        assert!(err.contains("Invalid jump target: 2"), "{}", err);
+        let err = rejected(&mut verifier, "
+            .const 0 int 1
+            .function 0 f params=0 len=1
+            jump end
+            f:
+            const 0
+            ret
+            end:
+        ");
+        assert!(err.contains("const at PC 1 runs out of its code"), "{}", err);
+
+        let mut program = fib.clone();
+        program.instructions[1] = Bytecode::Call(4, EffectGrade::Pure);
+        let err = verifier.verify(&program).unwrap_err().to_string();
+        assert!(err.contains("Function index 4"), "{}", err);
+
+        let mut verifier = BytecodeVerifier::with_limits(4, 8, verifier.allowed_effects.clone());
+        let err = rejected(&mut verifier, &format!(".const 0 int 1\n{}", "const 0\n".repeat(5)));
+        assert!(err.contains("Stack overflow"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_program_verifies() {
+        let mut vm = crate::bytecode::BytecodeVM::new();
+        let program = crate::bytecode::assemble(".const 0 int 1\nconst 0\npop\npop\n").unwrap();
+        assert!(vm.load_program("bad".to_string(), program).is_err());
+        assert!(vm.execute("bad").is_err());
+
+        let program = crate::bytecode::assemble(".const 0 int 1\nconst 0\nconst 0\nadd\n").unwrap();
+        vm.load_program("good".to_string(), program).unwrap();
+        assert_eq!(vm.execute("good").unwrap(), Value::Int(2));
+    }
 }
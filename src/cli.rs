@@ -1,6 +1,17 @@
 use clap::{Parser, Subcommand};
 use colored::*;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use crate::config::ReamSettings;
+use crate::tlisp::bench::{BenchMode, DEFAULT_THRESHOLD};
+use crate::error::ReamResult;
+use crate::fuzz::FuzzTarget;
+use crate::logging::{LogConfig, LogFormat, LogRotation, LOG_ENV};
+use crate::bytecode::SignatureEnforcement;
+use crate::runtime::{CronExpr, MissedRuns, OverflowPolicy};
+use crate::runtime::serverless_runtime::DEFAULT_MAX_CONCURRENCY;
+use crate::security::audit;
 
 /// REAM - Rust Erlang Abstract Machine
 /// A mathematically-grounded actor runtime with bytecode JIT compilation and TLISP
@@ -38,6 +49,50 @@ pub struct Cli {
     /// Disable colored output
     #[arg(long, global = true)]
     pub no_color: bool,
+
+    /// Log filter directives, e.g. `info,ream::daemon=debug` (defaults to $REAM_LOG)
+    #[arg(long, global = true, value_name = "FILTER")]
+    pub log_level: Option<String>,
+
+    /// Log output format [default: human]
+    #[arg(long, global = true, value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Configuration file (defaults to $REAM_CONFIG, then ./ream.toml)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config_file: Option<PathBuf>,
+
+    /// Override a setting, e.g. `runtime.gc_threshold=1048576`
+    #[arg(long = "set", global = true, value_name = "SECTION.KEY=VALUE")]
+    pub overrides: Vec<String>,
+}
+
+impl Cli {
+    /// Settings from the configuration file, environment and `--set` flags,
+    /// with the logging flags applied on top
+    pub fn settings(&self) -> ReamResult<ReamSettings> {
+        let mut settings = ReamSettings::load(self.config_file.as_deref(), &self.overrides)?;
+        settings.log = self.log_config(&settings.log);
+        Ok(settings)
+    }
+
+    /// Logging configuration from the flags, falling back to $REAM_LOG,
+    /// then to a level implied by --debug or --verbose, then to `configured`
+    pub fn log_config(&self, configured: &LogConfig) -> LogConfig {
+        let filter = self.log_level.clone()
+            .or_else(|| std::env::var(LOG_ENV).ok())
+            .or_else(|| {
+                if self.debug {
+                    Some("debug".to_string())
+                } else if self.verbose {
+                    Some("info".to_string())
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_else(|| configured.filter.clone());
+        LogConfig { filter, format: self.log_format.unwrap_or(configured.format) }
+    }
 }
 
 /// Available commands for the REAM CLI
@@ -61,8 +116,35 @@ pub enum Commands {
         /// History file path
         #[arg(long, default_value = ".ream_history")]
         history_file: PathBuf,
+
+        /// Attach to a running daemon through its IPC socket
+        #[arg(long, value_name = "SOCKET")]
+        attach: Option<PathBuf>,
+
+        /// Evaluate in this actor's context when attached
+        #[arg(long, value_name = "PID", requires = "attach")]
+        actor: Option<String>,
     },
     
+    /// Create a new TLISP project
+    New {
+        /// Project name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Project template
+        #[arg(short, long, default_value = "basic")]
+        template: ProjectTemplate,
+
+        /// Directory to create the project in (defaults to NAME)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+
+        /// Initial package version
+        #[arg(long, default_value = "0.1.0")]
+        version: String,
+    },
+
     /// Run a TLISP script file
     Run {
         /// Path to the TLISP script file
@@ -84,6 +166,14 @@ pub enum Commands {
         /// Optimization level (0-3)
         #[arg(short = 'O', long, default_value = "2")]
         optimization: u8,
+
+        /// Directory of trusted public keys (.pub) bundles must be signed with
+        #[arg(long, value_name = "DIR")]
+        trusted_keys: Option<PathBuf>,
+
+        /// What happens to bundles without a trusted signature
+        #[arg(long, value_enum, default_value = "off")]
+        signatures: SignatureEnforcement,
     },
     
     /// Build a TLISP project
@@ -115,6 +205,10 @@ pub enum Commands {
         /// Enable WebAssembly output
         #[arg(long)]
         wasm: bool,
+
+        /// Skip type checking when building a project bundle
+        #[arg(long)]
+        no_check: bool,
     },
     
     /// Check TLISP code for errors
@@ -147,104 +241,849 @@ pub enum Commands {
         indent: u8,
     },
     
-    /// Show system information
+    /// Show system information, or an actor's details from the running daemon
     Info {
+        /// Actor PID
+        #[arg(value_name = "PID")]
+        pid: Option<String>,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
         /// Show detailed system info
         #[arg(long)]
-        detailed: bool,
+        detailed: bool,
+    },
+    
+    /// Run tests
+    Test {
+        /// Path to test file or directory
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+        
+        /// Run tests in parallel
+        #[arg(short, long, default_value = "true")]
+        parallel: bool,
+        
+        /// Show test output
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Only run tests whose name contains this string
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Report format
+        #[arg(long, default_value = "pretty")]
+        format: TestFormat,
+
+        /// Seed for property-based tests, to replay a failure
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Run `defbench` benchmarks under the interpreter, VM and JIT
+    Bench {
+        /// Path to bench file or directory
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+
+        /// Only run benchmarks whose name contains this string
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Execution modes to measure
+        #[arg(long = "mode", value_delimiter = ',', default_value = "interpreter,vm,jit")]
+        modes: Vec<BenchMode>,
+
+        /// Unmeasured iterations before sampling
+        #[arg(long, default_value = "10")]
+        warmup: usize,
+
+        /// Measured iterations
+        #[arg(short = 'n', long, default_value = "100")]
+        iterations: usize,
+
+        /// Report format
+        #[arg(long, default_value = "pretty")]
+        format: BenchFormat,
+
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Compare against a report saved with --save-baseline
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+
+        /// Save this run's results as a baseline
+        #[arg(long, value_name = "FILE")]
+        save_baseline: Option<PathBuf>,
+
+        /// Slowdown in percent reported as a regression
+        #[arg(long, default_value_t = DEFAULT_THRESHOLD)]
+        threshold: f64,
+    },
+
+    /// Fuzz the TLisp parser, bytecode verifier/VM or SQL parser
+    Fuzz {
+        /// Code to fuzz
+        #[arg(value_enum)]
+        target: FuzzTarget,
+
+        /// Mutated inputs to run after the corpus
+        #[arg(long, default_value = "10000")]
+        runs: usize,
+
+        /// Seed for the mutation RNG, to replay a run
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Seed corpus [default: fuzz/corpus/<target>]
+        #[arg(long, value_name = "DIR")]
+        corpus: Option<PathBuf>,
+
+        /// Where crashing inputs are saved [default: fuzz/artifacts/<target>]
+        #[arg(long, value_name = "DIR")]
+        artifacts: Option<PathBuf>,
+
+        /// Largest input to generate, in bytes
+        #[arg(long, default_value = "4096")]
+        max_len: usize,
+
+        /// Run a single saved input instead of fuzzing
+        #[arg(long, value_name = "FILE")]
+        reproduce: Option<PathBuf>,
+    },
+    
+    /// Compile TLISP code to bytecode
+    Compile {
+        /// Path to the TLISP file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Output file path (defaults to input file with .reambc extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Output format (binary or text)
+        #[arg(short, long, default_value = "binary")]
+        format: CompileFormat,
+
+        /// Optimization level (0-3)
+        #[arg(short = 'O', long, default_value = "2")]
+        optimization: u8,
+
+        /// Include debug information
+        #[arg(long)]
+        debug_info: bool,
+
+        /// Backend the program runs on
+        #[arg(long, value_enum, default_value = "stack")]
+        backend: crate::bytecode::ExecutionBackend,
+
+        /// Show compilation statistics
+        #[arg(long)]
+        stats: bool,
+
+        /// Compile ahead of time to native code: an executable when the
+        /// runtime library is given, an object file otherwise
+        #[arg(long)]
+        aot: bool,
+
+        /// Target to compile for: `wasm` for a WebAssembly module, or a
+        /// target triple with --aot (defaults to the host)
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Runtime static library to link --aot output against (defaults
+        /// to $REAM_RUNTIME_LIB)
+        #[arg(long, requires = "aot")]
+        runtime: Option<PathBuf>,
+    },
+
+    /// Execute compiled bytecode
+    #[command(alias = "exec")]
+    Execute {
+        /// Path to the bytecode file (.reambc)
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Command line arguments to pass to the program
+        #[arg(value_name = "ARGS")]
+        args: Vec<String>,
+
+        /// Show execution timing
+        #[arg(short, long)]
+        time: bool,
+
+        /// Use JIT compilation
+        #[arg(long, default_value = "true")]
+        jit: bool,
+
+        /// Show execution statistics
+        #[arg(long)]
+        stats: bool,
+    },
+
+    /// Print a program as annotated bytecode assembly
+    Disasm {
+        /// Bytecode file, or TLisp source to compile
+        #[arg(value_name = "PROGRAM")]
+        program: PathBuf,
+
+        /// Write the assembly to this file instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Assemble a .reams file into bytecode
+    Asm {
+        /// Bytecode assembly file (.reams)
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Output file path (defaults to input file with .reambc extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Sign a bytecode bundle
+    Sign {
+        /// Bundle to sign (.reamb)
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Signing key file
+        #[arg(short, long)]
+        key: PathBuf,
+    },
+
+    /// Generate a bundle signing key, with its public key at <OUTPUT>.pub
+    Keygen {
+        /// Signing key file to create
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+    },
+
+    /// Package management
+    Package {
+        #[command(subcommand)]
+        command: PackageCommand,
+    },
+
+    /// Build a project and publish it to a package registry
+    Publish {
+        /// Project directory
+        #[arg(value_name = "PATH", default_value = ".")]
+        path: PathBuf,
+
+        /// Registry URL [default: $REAM_REGISTRY, then https://packages.tlisp.org]
+        #[arg(long)]
+        registry: Option<String>,
+
+        /// Registry API token [default: $REAM_REGISTRY_TOKEN]
+        #[arg(long)]
+        token: Option<String>,
+
+        /// Sign the package and its bundle with this key, made with `ream keygen`
+        #[arg(short, long)]
+        key: Option<PathBuf>,
+
+        /// Replace the version if it was already published
+        #[arg(long)]
+        allow_overwrite: bool,
+    },
+
+    /// Yank a published version so it is no longer picked for new installs
+    Yank {
+        /// Package name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Version to yank
+        #[arg(value_name = "VERSION")]
+        version: String,
+
+        /// Why the version should no longer be used
+        #[arg(long, conflicts_with = "undo")]
+        reason: Option<String>,
+
+        /// Restore a yanked version instead
+        #[arg(long)]
+        undo: bool,
+
+        /// Registry URL [default: $REAM_REGISTRY, then https://packages.tlisp.org]
+        #[arg(long)]
+        registry: Option<String>,
+
+        /// Registry API token [default: $REAM_REGISTRY_TOKEN]
+        #[arg(long)]
+        token: Option<String>,
+    },
+
+    /// Search a package registry
+    Search {
+        /// Search terms
+        #[arg(value_name = "QUERY")]
+        query: String,
+
+        /// Maximum number of results
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+
+        /// Search the cached index instead of the registry
+        #[arg(long)]
+        offline: bool,
+
+        /// Registry URL [default: $REAM_REGISTRY, then https://packages.tlisp.org]
+        #[arg(long)]
+        registry: Option<String>,
+
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Run and maintain a package registry
+    Registry {
+        #[command(subcommand)]
+        command: RegistryCommand,
+    },
+
+    /// Serve ORM models and actors over GraphQL
+    Graphql {
+        #[command(subcommand)]
+        command: GraphqlCommand,
+    },
+
+    /// Daemon mode operations
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommand,
+    },
+
+    /// Monitor running system
+    Monitor {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Refresh interval in milliseconds
+        #[arg(short, long, default_value = "1000")]
+        interval: u64,
+
+        /// Show only specific actor by PID
+        #[arg(long)]
+        actor: Option<String>,
+    },
+
+    /// List actors in the running daemon
+    Ps {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Show detailed information
+        #[arg(long)]
+        detailed: bool,
+    },
+
+    /// Kill an actor in the running daemon
+    Kill {
+        /// Actor PID
+        #[arg(value_name = "PID")]
+        pid: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Reason for killing
+        #[arg(short, long, default_value = "normal")]
+        reason: String,
+    },
+
+    /// Show the supervision tree of the running daemon
+    Tree {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Print the tree as JSON instead of ASCII art
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Inspect crashed actors
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommand,
+    },
+
+    /// Query and verify the audit log
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+
+    /// Profile actors in running daemon
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+
+    /// Record and export message flows between actors in running daemon
+    Trace {
+        #[command(subcommand)]
+        command: TraceCommand,
+    },
+
+    /// Deploy and invoke serverless functions in running daemon
+    #[command(name = "fn")]
+    Function {
+        #[command(subcommand)]
+        command: FunctionCommand,
+    },
+
+    /// Schedule recurring jobs in running daemon
+    Cron {
+        #[command(subcommand)]
+        command: CronCommand,
+    },
+
+    /// Inspect saga workflows in running daemon
+    Workflow {
+        #[command(subcommand)]
+        command: WorkflowCommand,
+    },
+
+    /// Inspect the layered configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+/// Configuration commands
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the settings in effect after every layer is applied
+    Show {
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Workflow commands
+#[derive(Subcommand)]
+pub enum WorkflowCommand {
+    /// List workflows with their status
+    List {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Print the workflows as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the progress of a workflow step by step
+    Inspect {
+        /// Workflow ID
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Print the workflow as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Scheduled job commands
+#[derive(Subcommand)]
+pub enum CronCommand {
+    /// Schedule a job, replacing any job with the same name
+    #[command(group(clap::ArgGroup::new("action").required(true).args(["call", "send", "invoke"])))]
+    Add {
+        /// Job name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Cron schedule in UTC, like "*/15 9-17 * * mon-fri" or @daily
+        #[arg(value_name = "SCHEDULE", value_parser = |source: &str| source.parse::<CronExpr>().map(Box::new))]
+        schedule: Box<CronExpr>,
+
+        /// Call this TLisp function
+        #[arg(long, value_name = "FUNCTION")]
+        call: Option<String>,
+
+        /// Send a message to this actor
+        #[arg(long, value_name = "PID", requires = "message")]
+        send: Option<String>,
+
+        /// Message sent with --send
+        #[arg(long)]
+        message: Option<String>,
+
+        /// Invoke this serverless function
+        #[arg(long, value_name = "FUNCTION")]
+        invoke: Option<String>,
+
+        /// Payload delivered with --invoke
+        #[arg(long, default_value = "")]
+        payload: String,
+
+        /// Delay each run by a random amount up to this many seconds
+        #[arg(long, value_name = "SECONDS", default_value_t = 0)]
+        jitter: u64,
+
+        /// What happens to runs missed while the daemon was down
+        #[arg(long, value_enum, default_value_t = MissedRuns::Skip)]
+        missed: MissedRuns,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// Remove a job
+    Remove {
+        /// Job name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// List jobs with their next runs
+    List {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Print the jobs as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Serverless function commands
+#[derive(Subcommand)]
+pub enum FunctionCommand {
+    /// Deploy a function from a bytecode bundle built with `ream build`, or
+    /// from a TLisp source file defining `(handle payload)`
+    Deploy {
+        /// Function name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Bytecode bundle, or TLisp source (`.tl`)
+        #[arg(value_name = "BUNDLE")]
+        bundle: PathBuf,
+
+        /// Trigger the function with HTTP requests to this path instead of messages
+        #[arg(long, value_name = "PATH")]
+        http: Option<String>,
+
+        /// HTTP method of the trigger
+        #[arg(long, default_value = "POST", requires = "http")]
+        method: String,
+
+        /// Seconds an instance may sit idle before it is dropped [default: 30]
+        #[arg(long, value_name = "SECONDS")]
+        idle_timeout: Option<u64>,
+
+        /// Invocations that may run at once
+        #[arg(long, default_value_t = DEFAULT_MAX_CONCURRENCY)]
+        concurrency: usize,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// Invoke a function and print its response
+    Invoke {
+        /// Function name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Payload delivered to the function
+        #[arg(value_name = "PAYLOAD", default_value = "")]
+        payload: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// List deployed functions with their instances
+    List {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Print the functions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Audit log commands
+#[derive(Subcommand)]
+pub enum AuditCommand {
+    /// Show audit log entries
+    Query {
+        /// Only entries from this time on: RFC 3339, or an age like 30m, 2h or 7d
+        #[arg(long, value_parser = audit::parse_since)]
+        since: Option<SystemTime>,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Read this audit log file instead of asking the daemon
+        #[arg(long)]
+        file: Option<PathBuf>,
+
+        /// Print the entries as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Export the entries as JSON to this file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check that an audit log file has not been tampered with
+    Verify {
+        /// Audit log file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+}
+
+/// Profiling commands
+#[derive(Subcommand)]
+pub enum ProfileCommand {
+    /// Sample the allocations of an actor for a while
+    Heap {
+        /// Actor PID
+        #[arg(value_name = "PID")]
+        pid: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// How long to profile, like 30s or 5m
+        #[arg(long, default_value = "30s", value_parser = audit::parse_age)]
+        duration: Duration,
+
+        /// Bytes allocated between samples [default: 65536]
+        #[arg(long)]
+        interval: Option<usize>,
+
+        /// Allocation sites to show
+        #[arg(long, default_value = "20")]
+        top: usize,
+
+        /// Write the stacks in the folded format read by flamegraph tools
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Print the profile as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Sample the TLisp functions an actor runs for a while
+    Cpu {
+        /// Actor PID
+        #[arg(value_name = "PID")]
+        pid: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// How long to profile, like 30s or 5m
+        #[arg(long, default_value = "30s", value_parser = audit::parse_age)]
+        duration: Duration,
+
+        /// Samples taken a second [default: 99]
+        #[arg(short, long)]
+        frequency: Option<u32>,
+
+        /// Functions to show
+        #[arg(long, default_value = "20")]
+        top: usize,
+
+        /// Write the stacks in the folded format read by flamegraph tools
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Print the profile as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Message flow commands
+#[derive(Subcommand)]
+pub enum TraceCommand {
+    /// Start recording the messages actors send each other
+    Start {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Messages to keep; older ones are dropped [default: 10000]
+        #[arg(long)]
+        limit: Option<usize>,
     },
-    
-    /// Run tests
-    Test {
-        /// Path to test file or directory
-        #[arg(value_name = "PATH")]
-        path: Option<PathBuf>,
-        
-        /// Run tests in parallel
-        #[arg(short, long, default_value = "true")]
-        parallel: bool,
-        
-        /// Show test output
+
+    /// Stop recording, keeping the recorded messages for export
+    Stop {
+        /// Daemon socket path
         #[arg(short, long)]
-        verbose: bool,
+        socket: Option<PathBuf>,
     },
-    
-    /// Compile TLISP code to bytecode
-    Compile {
-        /// Path to the TLISP file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
 
-        /// Output file path (defaults to input file with .reambc extension)
+    /// Render recorded messages as a sequence diagram or graph
+    Export {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "mermaid")]
+        format: FlowFormat,
+
+        /// Only messages sent or received by this actor; repeatable
+        #[arg(long = "pid", value_name = "PID")]
+        pids: Vec<String>,
+
+        /// Only messages of this type, such as a schema name, text or exit; repeatable
+        #[arg(long = "type", value_name = "TYPE")]
+        message_types: Vec<String>,
+
+        /// Only messages from this time on: RFC 3339, or an age like 30m, 2h or 7d
+        #[arg(long, value_parser = audit::parse_since)]
+        since: Option<SystemTime>,
+
+        /// Only messages before this time: RFC 3339, or an age like 30m, 2h or 7d
+        #[arg(long, value_parser = audit::parse_since)]
+        until: Option<SystemTime>,
+
+        /// Write to this file instead of stdout
         #[arg(short, long)]
         output: Option<PathBuf>,
+    },
+}
 
-        /// Output format (binary or text)
-        #[arg(short, long, default_value = "binary")]
-        format: CompileFormat,
+/// Postmortem debugging commands
+#[derive(Subcommand)]
+pub enum DebugCommand {
+    /// Show the crash dump of an actor
+    Dump {
+        /// Actor PID
+        #[arg(value_name = "PID")]
+        pid: String,
 
-        /// Optimization level (0-3)
-        #[arg(short = 'O', long, default_value = "2")]
-        optimization: u8,
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
 
-        /// Include debug information
+        /// Read the dump from this directory instead of asking the daemon
         #[arg(long)]
-        debug_info: bool,
+        dir: Option<PathBuf>,
 
-        /// Show compilation statistics
+        /// Print the dump as JSON
         #[arg(long)]
-        stats: bool,
+        json: bool,
     },
 
-    /// Execute compiled bytecode
-    #[command(alias = "exec")]
-    Execute {
-        /// Path to the bytecode file (.reambc)
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
-
-        /// Command line arguments to pass to the program
-        #[arg(value_name = "ARGS")]
-        args: Vec<String>,
+    /// Record the messages delivered to an actor
+    Record {
+        /// Actor PID
+        #[arg(value_name = "PID")]
+        pid: String,
 
-        /// Show execution timing
+        /// Daemon socket path
         #[arg(short, long)]
-        time: bool,
+        socket: Option<PathBuf>,
 
-        /// Use JIT compilation
-        #[arg(long, default_value = "true")]
-        jit: bool,
+        /// Most messages to keep [default: 10000]
+        #[arg(long)]
+        limit: Option<usize>,
 
-        /// Show execution statistics
+        /// Stop recording
         #[arg(long)]
-        stats: bool,
-    },
+        stop: bool,
 
-    /// Package management
-    Package {
-        #[command(subcommand)]
-        command: PackageCommand,
+        /// Save the recording to this file when stopping
+        #[arg(short, long, requires = "stop")]
+        output: Option<PathBuf>,
     },
 
-    /// Daemon mode operations
-    Daemon {
-        #[command(subcommand)]
-        command: DaemonCommand,
-    },
+    /// Step through an actor's recorded messages and states
+    Replay {
+        /// Actor PID, whose recording is fetched from the daemon
+        #[arg(value_name = "PID", required_unless_present = "file")]
+        pid: Option<String>,
 
-    /// Monitor running system
-    Monitor {
         /// Daemon socket path
         #[arg(short, long)]
         socket: Option<PathBuf>,
 
-        /// Refresh interval in milliseconds
-        #[arg(short, long, default_value = "1000")]
-        interval: u64,
+        /// Replay a recording saved with `record --stop --output`
+        #[arg(short, long, conflicts_with = "pid")]
+        file: Option<PathBuf>,
+    },
 
-        /// Show only specific actor by PID
+    /// Show the event journals of event-sourced actors
+    Journal {
+        /// Persistence ID of the journal to show; lists the journals if omitted
+        #[arg(value_name = "ID")]
+        persistence_id: Option<String>,
+
+        /// Event store database
+        #[arg(long, value_name = "FILE")]
+        store: PathBuf,
+
+        /// Show only the events after this one
+        #[arg(long, default_value_t = 0)]
+        after: u64,
+
+        /// Print the journal as JSON
         #[arg(long)]
-        actor: Option<String>,
+        json: bool,
+    },
+
+    /// Step through a bytecode program
+    Bytecode {
+        /// Bytecode file, or TLisp source to compile
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Break at an instruction index or function name (repeatable)
+        #[arg(short, long = "break", value_name = "LOCATION")]
+        breakpoints: Vec<String>,
     },
 }
 
@@ -273,6 +1112,64 @@ pub enum PackageCommand {
     Update,
 }
 
+#[derive(Subcommand)]
+pub enum RegistryCommand {
+    /// Serve a registry over HTTP
+    Serve {
+        /// Directory the registry is kept in
+        #[arg(long)]
+        dir: PathBuf,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:4870")]
+        addr: SocketAddr,
+    },
+
+    /// Issue an API token allowing a user to publish and yank
+    Token {
+        /// User the token is issued to
+        #[arg(value_name = "USER")]
+        user: String,
+
+        /// Directory the registry is kept in
+        #[arg(long)]
+        dir: PathBuf,
+    },
+
+    /// Copy every package of a registry into a directory that can be served offline
+    Mirror {
+        /// Directory to mirror into
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Registry URL [default: $REAM_REGISTRY, then https://packages.tlisp.org]
+        #[arg(long)]
+        registry: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum GraphqlCommand {
+    /// Serve the models and resolvers a TLisp script defines
+    Serve {
+        /// TLisp script calling define-model and defresolver
+        #[arg(value_name = "SCRIPT")]
+        script: PathBuf,
+
+        /// Database URL; postgres:// URLs use PostgreSQL, others SQLite
+        #[arg(long)]
+        database: String,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:4000")]
+        addr: SocketAddr,
+
+        /// Daemon socket the actor queries ask
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+}
+
 /// Daemon management commands
 #[derive(Subcommand)]
 pub enum DaemonCommand {
@@ -282,6 +1179,10 @@ pub enum DaemonCommand {
         #[arg(value_name = "FILE")]
         file: PathBuf,
 
+        /// TOML daemon configuration, re-read on SIGHUP
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
         /// Daemon socket path
         #[arg(short, long)]
         socket: Option<PathBuf>,
@@ -294,6 +1195,18 @@ pub enum DaemonCommand {
         #[arg(short, long)]
         logfile: Option<PathBuf>,
 
+        /// How often to start a new log file [default: daily]
+        #[arg(long, value_enum)]
+        log_rotation: Option<LogRotation>,
+
+        /// Serve Prometheus metrics at http://ADDR/metrics
+        #[arg(long, value_name = "ADDR")]
+        metrics_addr: Option<SocketAddr>,
+
+        /// Maximum number of actors given their own metric series [default: 100]
+        #[arg(long)]
+        metrics_actor_limit: Option<usize>,
+
         /// Run in foreground (don't daemonize)
         #[arg(long)]
         foreground: bool,
@@ -325,6 +1238,13 @@ pub enum DaemonCommand {
         pidfile: Option<PathBuf>,
     },
 
+    /// Reload the configuration of a running daemon
+    Reload {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
     /// Restart daemon
     Restart {
         /// TLisp program file to run
@@ -349,6 +1269,94 @@ pub enum DaemonCommand {
         #[command(subcommand)]
         command: ActorCommand,
     },
+
+    /// Manage isolation domains in running daemon
+    Domain {
+        #[command(subcommand)]
+        command: DomainCommand,
+    },
+
+    /// List the message schemas registered in running daemon
+    Schemas {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Print the schemas as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Isolation domain commands
+#[derive(Subcommand)]
+pub enum DomainCommand {
+    /// List domains with their actors and memory
+    List {
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// Create a domain
+    Create {
+        /// Domain name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Maximum number of actors in the domain
+        #[arg(long)]
+        max_actors: Option<usize>,
+
+        /// Maximum memory held by the domain's actors (bytes)
+        #[arg(long)]
+        max_memory: Option<usize>,
+
+        /// Maximum messages per second sent within the domain
+        #[arg(long)]
+        max_message_rate: Option<u32>,
+
+        /// Capability profile the domain's actors run under
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// Suspend every actor of a domain
+    Suspend {
+        /// Domain name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// Resume a suspended domain
+    Resume {
+        /// Domain name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+
+    /// Terminate every actor of a domain and remove it
+    Delete {
+        /// Domain name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
 }
 
 /// Actor management commands
@@ -438,6 +1446,37 @@ pub enum ActorCommand {
         #[arg(short, long)]
         socket: Option<PathBuf>,
     },
+
+    /// Limit the messages an actor accepts
+    Limit {
+        /// Actor PID
+        #[arg(value_name = "PID")]
+        pid: String,
+
+        /// Messages per second from all senders together
+        #[arg(long)]
+        rate: Option<u32>,
+
+        /// Messages per second from any one sender
+        #[arg(long)]
+        per_sender: Option<u32>,
+
+        /// Messages accepted at once after a quiet period [default: one second's worth]
+        #[arg(long)]
+        burst: Option<u32>,
+
+        /// What happens to messages over the limit
+        #[arg(long, value_enum, default_value = "shed")]
+        policy: OverflowPolicy,
+
+        /// Lift the actor's limit
+        #[arg(long, conflicts_with_all = ["rate", "per_sender", "burst"])]
+        clear: bool,
+
+        /// Daemon socket path
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
 }
 
 /// Build mode for compilation
@@ -471,6 +1510,67 @@ pub enum CompileFormat {
     Text,
 }
 
+/// Report format for test results
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TestFormat {
+    /// Human-readable progress and summary
+    Pretty,
+    /// JUnit XML for CI systems
+    Junit,
+    /// JSON report
+    Json,
+}
+
+/// Benchmark report format
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum BenchFormat {
+    /// Human-readable table
+    Pretty,
+    /// JSON report, loadable as a baseline
+    Json,
+    /// Standalone HTML page
+    Html,
+}
+
+/// Message flow export format
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum FlowFormat {
+    /// Mermaid sequence diagram
+    Mermaid,
+    /// Graphviz DOT graph
+    Dot,
+    /// JSON list of messages
+    Json,
+}
+
+/// Template for a new project
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ProjectTemplate {
+    /// Executable with a main function
+    Basic,
+    /// Library module
+    Lib,
+    /// Actor service with a supervised worker
+    Actor,
+    /// Web service with HTTP routes
+    Web,
+    /// Command-line tool that parses its arguments
+    Cli,
+}
+
+impl ProjectTemplate {
+    /// Template name understood by `ProjectConfigManager::init_project`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProjectTemplate::Basic => "basic",
+            ProjectTemplate::Lib => "lib",
+            ProjectTemplate::Actor => "actor",
+            ProjectTemplate::Web => "web",
+            ProjectTemplate::Cli => "cli",
+        }
+    }
+}
+
 impl std::fmt::Display for BuildMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -514,16 +1614,21 @@ pub fn print_help() {
     println!("  {}  - Clear the screen", "clear".bright_green());
     println!("  {}  - Show system information", "info".bright_green());
     println!("  {}  - Show current environment", "env".bright_green());
-    println!("  {}  - Load a script file", "load <file>".bright_green());
+    println!("  {}  - Load a script file", ":load <file>".bright_green());
     println!("  {}  - Reset the environment", "reset".bright_green());
     println!("  {}  - Show command history", "history".bright_green());
-    println!("  {}  - Time expression evaluation", "time <expr>".bright_green());
-    println!("  {}  - Show type of expression", "type <expr>".bright_green());
+    println!("  {}  - Time expression evaluation", ":time <expr>".bright_green());
+    println!("  {}  - Show type of expression", ":type <expr>".bright_green());
     println!("  {}  - Show bytecode for expression", "bytecode <expr>".bright_green());
     println!("  {}  - Show JIT assembly for expression", "asm <expr>".bright_green());
     println!("  {}  - Toggle debug mode", "debug".bright_green());
     println!("  {}  - Toggle JIT compilation", "jit".bright_green());
     println!();
+    println!("{}", "Editing:".bright_yellow().bold());
+    println!("  {}  - Complete symbols and meta-commands", "Tab".bright_green());
+    println!("  {}  - Continue input until brackets balance", "Enter".bright_green());
+    println!("  {}  - Last three results", "*1 *2 *3".bright_green());
+    println!();
     println!("{}", "TLISP expressions:".bright_yellow().bold());
     println!("  {}  - Define a variable", "(define x 42)".bright_blue());
     println!("  {}  - Lambda function", "(lambda (x) (* x x))".bright_blue());
@@ -613,6 +1718,104 @@ mod tests {
             }
             _ => panic!("Expected Build command"),
         }
+
+        // Test bench subcommand
+        let cli = Cli::parse_from(&["ream", "bench", "benches", "--mode", "vm,jit", "-n", "20", "--format", "html"]);
+        match cli.command {
+            Some(Commands::Bench { path, modes, iterations, format, threshold, .. }) => {
+                assert_eq!(path, Some(PathBuf::from("benches")));
+                assert_eq!(modes, vec![BenchMode::Vm, BenchMode::Jit]);
+                assert_eq!(iterations, 20);
+                assert_eq!(format, BenchFormat::Html);
+                assert_eq!(threshold, DEFAULT_THRESHOLD);
+            }
+            _ => panic!("Expected Bench command"),
+        }
+
+        // Test fuzz subcommand
+        let cli = Cli::parse_from(&["ream", "fuzz", "sql_parser", "--runs", "50", "--seed", "3"]);
+        assert!(matches!(cli.command, Some(Commands::Fuzz { target: FuzzTarget::SqlParser, runs: 50, seed: Some(3), .. })));
+        assert!(Cli::try_parse_from(&["ream", "fuzz", "lexer"]).is_err());
+
+        // Test sign subcommand and signature enforcement
+        let cli = Cli::parse_from(&["ream", "sign", "app.reamb", "--key", "release"]);
+        assert!(matches!(cli.command, Some(Commands::Sign { key, .. }) if key == PathBuf::from("release")));
+        let cli = Cli::parse_from(&["ream", "run", "app.reamb", "--trusted-keys", "keys", "--signatures", "deny"]);
+        assert!(matches!(cli.command, Some(Commands::Run { signatures: SignatureEnforcement::Deny, .. })));
+
+        // Test profile heap subcommand
+        let cli = Cli::parse_from(&["ream", "profile", "heap", "<0.1.0>", "--duration", "2m", "--output", "heap.folded"]);
+        match cli.command {
+            Some(Commands::Profile { command: ProfileCommand::Heap { pid, duration, interval, output, .. } }) => {
+                assert_eq!(pid, "<0.1.0>");
+                assert_eq!(duration, Duration::from_secs(120));
+                assert_eq!(interval, None);
+                assert_eq!(output, Some(PathBuf::from("heap.folded")));
+            }
+            _ => panic!("Expected Profile command"),
+        }
+        assert!(Cli::try_parse_from(&["ream", "profile", "heap", "<0.1.0>", "--duration", "soon"]).is_err());
+
+        // Test profile cpu subcommand
+        let cli = Cli::parse_from(&["ream", "profile", "cpu", "<0.1.0>", "--frequency", "250", "--json"]);
+        match cli.command {
+            Some(Commands::Profile { command: ProfileCommand::Cpu { pid, duration, frequency, json, .. } }) => {
+                assert_eq!(pid, "<0.1.0>");
+                assert_eq!(duration, Duration::from_secs(30));
+                assert_eq!(frequency, Some(250));
+                assert!(json);
+            }
+            _ => panic!("Expected Profile command"),
+        }
+
+        // Test trace export subcommand
+        let cli = Cli::parse_from(&["ream", "trace", "export", "--format", "dot", "--pid", "#3", "--pid", "#4", "--type", "exit", "--since", "5m"]);
+        match cli.command {
+            Some(Commands::Trace { command: TraceCommand::Export { format, pids, message_types, since, until, .. } }) => {
+                assert_eq!(format, FlowFormat::Dot);
+                assert_eq!(pids, ["#3", "#4"]);
+                assert_eq!(message_types, ["exit"]);
+                assert!(since.is_some() && until.is_none());
+            }
+            _ => panic!("Expected Trace command"),
+        }
+        assert!(matches!(Cli::parse_from(&["ream", "trace", "export"]).command,
+            Some(Commands::Trace { command: TraceCommand::Export { format: FlowFormat::Mermaid, .. } })));
+
+        // Test tree command
+        assert!(matches!(Cli::parse_from(&["ream", "tree", "--json"]).command,
+            Some(Commands::Tree { socket: None, json: true })));
+
+        // Test audit query subcommand
+        let cli = Cli::parse_from(&["ream", "audit", "query", "--since", "2h", "--json"]);
+        assert!(matches!(cli.command, Some(Commands::Audit { command: AuditCommand::Query { since: Some(_), json: true, .. } })));
+        assert!(Cli::try_parse_from(&["ream", "audit", "query", "--since", "yesterday"]).is_err());
+
+        // Test serverless function subcommands
+        let cli = Cli::parse_from(&["ream", "fn", "deploy", "greet", "greet.reamb", "--http", "/greet", "--idle-timeout", "60"]);
+        assert!(matches!(cli.command, Some(Commands::Function { command: FunctionCommand::Deploy { http: Some(_), idle_timeout: Some(60), .. } })));
+        assert!(Cli::try_parse_from(&["ream", "fn", "deploy", "greet", "greet.reamb", "--method", "GET"]).is_err());
+        let cli = Cli::parse_from(&["ream", "cron", "add", "report", "0 9 * * mon", "--call", "report", "--jitter", "30", "--missed", "once"]);
+        assert!(matches!(cli.command, Some(Commands::Cron { command: CronCommand::Add { jitter: 30, missed: MissedRuns::Once, .. } })));
+        assert!(Cli::try_parse_from(&["ream", "cron", "add", "report", "0 9 * * mon"]).is_err());
+        assert!(Cli::try_parse_from(&["ream", "cron", "add", "report", "0 9 * *", "--call", "report"]).is_err());
+        assert!(Cli::try_parse_from(&["ream", "cron", "add", "ping", "@hourly", "--send", "<0.1.0>"]).is_err());
+        let cli = Cli::parse_from(&["ream", "workflow", "inspect", "order-1f2e3d4c5b6a", "--json"]);
+        assert!(matches!(cli.command, Some(Commands::Workflow { command: WorkflowCommand::Inspect { json: true, .. } })));
+        assert!(Cli::try_parse_from(&["ream", "workflow", "inspect"]).is_err());
+        // Test registry subcommands
+        let cli = Cli::parse_from(&["ream", "publish", "--key", "release"]);
+        assert!(matches!(cli.command, Some(Commands::Publish { path, key: Some(_), .. }) if path == PathBuf::from(".")));
+        assert!(Cli::try_parse_from(&["ream", "yank", "json-utils", "1.0.0", "--undo", "--reason", "broken"]).is_err());
+        let cli = Cli::parse_from(&["ream", "search", "json", "--offline"]);
+        assert!(matches!(cli.command, Some(Commands::Search { offline: true, limit: 20, .. })));
+        let cli = Cli::parse_from(&["ream", "registry", "serve", "--dir", "registry"]);
+        assert!(matches!(cli.command, Some(Commands::Registry { command: RegistryCommand::Serve { addr, .. } }) if addr.port() == 4870));
+        let cli = Cli::parse_from(&["ream", "graphql", "serve", "schema.tl", "--database", "blog.db"]);
+        assert!(matches!(cli.command, Some(Commands::Graphql { command: GraphqlCommand::Serve { addr, socket: None, .. } }) if addr.port() == 4000));
+        assert!(Cli::try_parse_from(&["ream", "graphql", "serve", "schema.tl"]).is_err());
+        let cli = Cli::parse_from(&["ream", "fn", "invoke", "greet", "world"]);
+        assert!(matches!(cli.command, Some(Commands::Function { command: FunctionCommand::Invoke { payload, .. } }) if payload == "world"));
     }
     
     #[test]
@@ -620,6 +1823,11 @@ mod tests {
         let cli = Cli::parse_from(&["ream", "--verbose", "--debug", "run", "script.scm"]);
         assert!(cli.verbose);
         assert!(cli.debug);
+
+        let cli = Cli::parse_from(&["ream", "daemon", "reload", "--set", "runtime.gc_threshold=1024", "--config-file", "ream.toml"]);
+        assert_eq!(cli.overrides, vec!["runtime.gc_threshold=1024".to_string()]);
+        assert_eq!(cli.config_file, Some(PathBuf::from("ream.toml")));
+        assert_eq!(cli.log_config(&LogConfig::default()).format, LogFormat::Human);
     }
     
     #[test]
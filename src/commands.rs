@@ -1,28 +1,76 @@
-use crate::cli::{Commands, BuildMode, BuildTarget, PackageCommand, CompileFormat, DaemonCommand, ActorCommand};
-use crate::repl::start_repl;
-use crate::tlisp::TlispInterpreter;
-use crate::bytecode::{BytecodeCompiler, BytecodeVM, BytecodeProgram, LanguageCompiler};
-use crate::jit::JitRuntime;
+use crate::cli::{Commands, AuditCommand, BenchFormat, ProfileCommand, TraceCommand, FlowFormat, ConfigCommand, CronCommand, WorkflowCommand, FunctionCommand, BuildMode, BuildTarget, PackageCommand, CompileFormat, DaemonCommand, ActorCommand, DomainCommand, DebugCommand, ProjectTemplate, RegistryCommand, GraphqlCommand, TestFormat};
+use crate::tlisp::test_runner::{discover_test_files, TestOutcome, TestRunner};
+use crate::tlisp::bench::{format_nanos, BenchMode, BenchReport, BenchRunner, Verdict};
+use crate::tlisp::package_config::{ProjectConfig, ProjectConfigManager, DependencySpec, CONFIG_FILE};
+use crate::repl::{start_attached_repl, start_repl};
+use crate::tlisp::{EnhancedTlispCompiler, TlispInterpreter};
+use crate::tlisp::package_manager::PackageMetadata;
+use crate::tlisp::package_registry::{PackageRegistry, PublishOptions, PublishRequest, SearchQuery, DEFAULT_REGISTRY_URL};
+use crate::tlisp::registry_server::{self, RegistryStore};
+use crate::orm::{script_schema, CdcConfig, CdcDriver, ChangeDataCapture, GraphQLServer, PostgresDriver, ScriptOrm, SqlError, SqliteDriver, TableCapture, TlispResolver};
+use crate::tlisp::rust_crate_integration::{BuildProfile, RustCrateIntegration};
+use crate::tlisp::RustFunction;
+use crate::bytecode::{BytecodeCompiler, BytecodeVM, BytecodeProgram, BytecodeBundle, BundleModule, BUNDLE_EXTENSION};
+use crate::bytecode::debugger::{Breakpoint, Debugger, StopReason, Watch};
+use crate::bytecode::assembly::{self, ASSEMBLY_EXTENSION};
+use crate::bytecode::signing::{generate_key_file, public_key_path};
+use crate::bytecode::{SignatureEnforcement, SignaturePolicy, SigningKey, TrustedKeys};
+use crate::jit::{AotCompiler, JitRuntime};
+use crate::wasm::{self, WasmCompiler};
 use crate::error::{ReamResult, ReamError};
-use crate::daemon::{DaemonConfig, runtime::DaemonRuntime, ipc::IpcClient};
+use crate::fuzz::{self, FuzzTarget, Fuzzer};
+use crate::daemon::{DaemonConfig, FunctionSource, runtime::{DaemonRuntime, Detached}, ipc::IpcClient, pidfile::PidFile};
+use crate::daemon::describe_rate_limit;
+use crate::daemon::metrics::DEFAULT_ACTOR_SERIES_LIMIT;
+use crate::logging::{self, LogRotation};
+use crate::config::{self, ReamSettings};
+use crate::runtime::crash::CrashDump;
+use crate::runtime::event_sourcing::{EventStore, SqliteEventStore};
+use crate::security::audit;
+use crate::runtime::{DomainConfig, DomainInfo, DomainQuotas, RateLimit};
+use crate::runtime::cron::{CronJob, JobAction};
+use crate::runtime::schema::SchemaInfo;
+use crate::runtime::workflow::WorkflowInfo;
+use crate::runtime::serverless::WakeTrigger;
+use crate::runtime::serverless_runtime::{DeployOptions, FunctionInfo};
+use crate::debug::replay::{Recording, Replay};
+use crate::debug::flow::{self, FlowFilter};
 
 #[cfg(feature = "tui")]
 use crate::daemon::tui::TuiApp;
 use colored::*;
 use std::fs;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Instant, Duration};
 
 pub fn execute_command(command: Commands, debug: bool, verbose: bool) -> ReamResult<()> {
     match command {
-        Commands::Interactive { load, banner, history: _, history_file } => {
-            execute_interactive(load, banner, history_file)
+        Commands::Interactive { load, banner, history: _, history_file, attach, actor } => {
+            match attach {
+                Some(socket) => start_attached_repl(socket, actor, banner, history_file),
+                None => execute_interactive(load, banner, history_file),
+            }
+        }
+        Commands::New { name, template, path, version } => {
+            execute_new(name, template, path, version)
         }
-        Commands::Run { file, args, time, jit, optimization } => {
-            execute_run(file, args, time, jit, optimization, debug, verbose)
+        Commands::Run { file, args, time, jit, optimization, trusted_keys, signatures } => {
+            let signatures = match (trusted_keys, signatures) {
+                (_, SignatureEnforcement::Off) => None,
+                (Some(dir), level) => Some(SignaturePolicy::new(TrustedKeys::load(&dir)?, level)),
+                (None, level) => Some(SignaturePolicy::new(TrustedKeys::new(), level)),
+            };
+            execute_run(file, args, RunOptions { time, jit, optimization, signatures }, debug, verbose)
         }
-        Commands::Build { path, output, mode, optimization, target, arch, wasm } => {
-            execute_build(path, output, mode, optimization, target, arch, wasm)
+        Commands::Build { path, output, mode, optimization, target, arch, wasm, no_check } => {
+            if path.is_dir() && ProjectConfigManager::new(path.clone()).is_initialized() {
+                execute_build_bundle(path, output, mode, !no_check)
+            } else {
+                execute_build(path, output, mode, optimization, target, arch, wasm)
+            }
         }
         Commands::Check { file, types, warnings } => {
             execute_check(file, types, warnings)
@@ -30,42 +78,212 @@ pub fn execute_command(command: Commands, debug: bool, verbose: bool) -> ReamRes
         Commands::Format { file, in_place, indent } => {
             execute_format(file, in_place, indent)
         }
-        Commands::Info { detailed } => {
+        Commands::Info { pid: Some(pid), socket, .. } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_actor_info(pid, socket_path, debug, verbose)
+        }
+        Commands::Info { pid: None, detailed, .. } => {
             execute_info(detailed)
         }
-        Commands::Compile { file, output, format, optimization, debug_info, stats } => {
-            execute_compile(file, output, format, optimization, debug_info, stats, debug, verbose)
+        Commands::Compile { file, output, format, optimization, debug_info, backend, stats, aot, target, runtime } => {
+            let target = match target {
+                Some(target) if is_wasm_target(&target) => {
+                    if aot {
+                        return Err(ReamError::Other("--aot compiles to native code, not --target wasm".to_string()));
+                    }
+                    CompileTarget::Wasm
+                }
+                target if aot => CompileTarget::Aot(AotOutput {
+                    target,
+                    runtime: runtime.or_else(|| std::env::var_os("REAM_RUNTIME_LIB").map(PathBuf::from)),
+                }),
+                Some(target) => {
+                    return Err(ReamError::Other(format!("Compiling for {} needs --aot", target)));
+                }
+                None => CompileTarget::Bytecode,
+            };
+            execute_compile(file, output, format, optimization, debug_info, backend, stats, target, debug, verbose)
         }
         Commands::Execute { file, args, time, jit, stats } => {
             execute_bytecode(file, args, time, jit, stats, debug, verbose)
         }
-        Commands::Test { path, parallel, verbose } => {
-            execute_test(path, parallel, verbose)
+        Commands::Disasm { program, output } => {
+            execute_disasm(program, output)
+        }
+        Commands::Asm { file, output } => {
+            execute_asm(file, output)
+        }
+        Commands::Sign { file, key } => {
+            execute_sign(file, key)
+        }
+        Commands::Keygen { output } => {
+            execute_keygen(output)
+        }
+        Commands::Test { path, parallel, verbose, filter, format, seed, output } => {
+            execute_test(path, parallel, verbose, filter, format, seed, output)
+        }
+        Commands::Bench { path, filter, modes, warmup, iterations, format, output, baseline, save_baseline, threshold } => {
+            execute_bench(path, filter, modes, warmup, iterations, format, output, baseline, save_baseline, threshold)
+        }
+        Commands::Fuzz { target, runs, seed, corpus, artifacts, max_len, reproduce } => {
+            execute_fuzz(target, runs, seed, corpus, artifacts, max_len, reproduce)
         }
         Commands::Package { command } => {
             execute_package(command)
         }
+        Commands::Publish { path, registry, token, key, allow_overwrite } => {
+            execute_publish(path, registry, token, key, allow_overwrite)
+        }
+        Commands::Yank { name, version, reason, undo, registry, token } => {
+            execute_yank(name, version, reason, undo, registry, token)
+        }
+        Commands::Search { query, limit, offline, registry, json } => {
+            execute_search(query, limit, offline, registry, json)
+        }
+        Commands::Registry { command } => {
+            execute_registry_command(command)
+        }
+        Commands::Graphql { command } => {
+            execute_graphql_command(command)
+        }
         Commands::Daemon { command } => {
             execute_daemon(command, debug, verbose)
         }
         Commands::Monitor { socket, interval, actor } => {
-            let config = DaemonConfig::default();
+            let config = config::current().daemon;
             let socket_path = socket.unwrap_or(config.socket_path);
             execute_monitor(socket_path, interval, actor, debug, verbose)
         }
+        Commands::Ps { socket, detailed } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_actor_list(socket_path, detailed, debug, verbose)
+        }
+        Commands::Tree { socket, json } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_tree(socket_path, json)
+        }
+        Commands::Kill { pid, socket, reason } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_actor_kill(pid, socket_path, reason, debug, verbose)
+        }
+        Commands::Debug { command: DebugCommand::Dump { pid, socket, dir, json } } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_debug_dump(pid, socket_path, dir, json)
+        }
+        Commands::Debug { command: DebugCommand::Record { pid, socket, limit, stop, output } } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_debug_record(pid, socket_path, limit, stop, output)
+        }
+        Commands::Debug { command: DebugCommand::Replay { pid, socket, file } } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_debug_replay(pid, socket_path, file)
+        }
+        Commands::Debug { command: DebugCommand::Journal { persistence_id, store, after, json } } => {
+            execute_debug_journal(persistence_id, store, after, json)
+        }
+        Commands::Debug { command: DebugCommand::Bytecode { file, breakpoints } } => {
+            execute_debug_bytecode(file, breakpoints)
+        }
+        Commands::Audit { command: AuditCommand::Query { since, socket, file, json, output } } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_audit_query(since, socket_path, file, json, output)
+        }
+        Commands::Audit { command: AuditCommand::Verify { file } } => {
+            execute_audit_verify(file)
+        }
+        Commands::Profile { command: ProfileCommand::Heap { pid, socket, duration, interval, top, output, json } } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_profile_heap(pid, socket_path, duration, interval, top, output, json)
+        }
+        Commands::Profile { command: ProfileCommand::Cpu { pid, socket, duration, frequency, top, output, json } } => {
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_profile_cpu(pid, socket_path, duration, frequency, top, output, json)
+        }
+        Commands::Trace { command } => {
+            execute_trace_command(command)
+        }
+        Commands::Function { command } => {
+            execute_function_command(command)
+        }
+        Commands::Cron { command } => {
+            execute_cron_command(command)
+        }
+        Commands::Workflow { command } => {
+            execute_workflow_command(command)
+        }
+        Commands::Config { command } => {
+            execute_config_command(command)
+        }
+    }
+}
+
+fn execute_new(name: String, template: ProjectTemplate, path: Option<PathBuf>, version: String) -> ReamResult<()> {
+    let project_root = path.unwrap_or_else(|| PathBuf::from(&name));
+
+    println!("{} {} ({} template)", "Creating project:".bright_green(), name.bright_cyan(), template.name());
+
+    if project_root.exists() && fs::read_dir(&project_root).map_err(ReamError::Io)?.next().is_some() {
+        return Err(ReamError::Other(format!("Destination is not empty: {}", project_root.display())));
+    }
+
+    fs::create_dir_all(&project_root).map_err(ReamError::Io)?;
+
+    let mut manager = ProjectConfigManager::new(project_root.clone());
+    manager.init_project(name, version, Some(template.name()))
+        .map_err(|e| ReamError::Other(format!("Failed to create project: {}", e)))?;
+
+    println!("  ✓ {}", project_root.join(CONFIG_FILE).display());
+    if let Some(config) = manager.config() {
+        if config.package.lib.is_some() {
+            println!("  ✓ {}", project_root.join("src/lib.tl").display());
+        }
+        if !config.package.bin.is_empty() {
+            println!("  ✓ {}", project_root.join("src/main.tl").display());
+        }
+        for test in config.package.tests.iter().filter_map(|t| t.path.as_ref()) {
+            println!("  ✓ {}", project_root.join(test).display());
+        }
+    }
+    println!("  ✓ {}", project_root.join(".gitignore").display());
+
+    println!();
+    println!("Next steps:");
+    println!("  cd {}", project_root.display());
+    if template != ProjectTemplate::Lib {
+        println!("  ream run src/main.tl");
+        println!("  ream test");
     }
+
+    Ok(())
 }
 
 fn execute_interactive(load: Option<PathBuf>, banner: bool, history_file: PathBuf) -> ReamResult<()> {
     start_repl(load, banner, history_file)
 }
 
-fn execute_run(file: PathBuf, args: Vec<String>, time: bool, jit: bool, optimization: u8, debug: bool, verbose: bool) -> ReamResult<()> {
+/// How `ream run` runs a program
+struct RunOptions {
+    /// Print how long the program took
+    time: bool,
+    /// Compile the program with the JIT
+    jit: bool,
+    /// Optimization level
+    optimization: u8,
+    /// Checks the signatures of bundles; unchecked when `None`
+    signatures: Option<SignaturePolicy>,
+}
+
+fn execute_run(file: PathBuf, args: Vec<String>, options: RunOptions, debug: bool, verbose: bool) -> ReamResult<()> {
+    let RunOptions { time, jit, optimization, signatures } = options;
     println!("{} {}", "Running:".bright_green(), file.display());
     
     if !file.exists() {
         return Err(ReamError::Other(format!("File not found: {}", file.display())));
     }
+
+    if file.extension().is_some_and(|ext| ext == BUNDLE_EXTENSION) {
+        return execute_bundle(file, time, signatures, verbose);
+    }
     
     let content = fs::read_to_string(&file)
         .map_err(|e| ReamError::Io(e))?;
@@ -122,6 +340,20 @@ fn execute_run(file: PathBuf, args: Vec<String>, time: bool, jit: bool, optimiza
                 args);
     }
 
+    // Bind the native extensions of the project the file belongs to
+    if let Some(root) = enclosing_project(&file) {
+        let mut manager = ProjectConfigManager::new(root.clone());
+        let config = manager.load()
+            .map_err(|e| ReamError::Other(format!("Failed to load project config: {}", e)))?
+            .clone();
+        for (name, function) in load_project_extensions(&root, &config, &BuildMode::Release)? {
+            if verbose {
+                println!("  ✓ extension {}", name);
+            }
+            runtime.register_native(name, function);
+        }
+    }
+
     // Add command line arguments to environment
     runtime.define("*args*", crate::tlisp::Value::List(
         args.into_iter().map(|s| crate::tlisp::Value::String(s)).collect()
@@ -222,7 +454,7 @@ fn execute_build(path: PathBuf, output: PathBuf, mode: BuildMode, optimization:
     let mut compiler = BytecodeCompiler::new("build_project".to_string());
 
     // Parse the content first
-    match interpreter.parse(&content) {
+    let built = match interpreter.parse(&content) {
         Ok(expr) => {
             println!("  ✓ Syntax valid");
 
@@ -231,8 +463,9 @@ fn execute_build(path: PathBuf, output: PathBuf, mode: BuildMode, optimization:
                 Ok(program) => {
                     println!("  ✓ Bytecode compilation successful ({} instructions)", program.instructions.len());
 
-                    // Store the compiled program for later use
-                    compiler.add_program(program);
+                    // Keep the compiled program for later use
+                    compiler.add_program(program.clone());
+                    program
                 }
                 Err(e) => {
                     return Err(ReamError::Other(format!("Bytecode compilation failed: {}", e)));
@@ -243,7 +476,7 @@ fn execute_build(path: PathBuf, output: PathBuf, mode: BuildMode, optimization:
             println!("  ✗ Compilation failed: {}", e);
             return Err(e.into());
         }
-    }
+    };
     
     // Generate output files
     println!("{} Generating output files...", "3.".dimmed());
@@ -287,11 +520,7 @@ build_time = \"{}\"
     // WebAssembly output
     if wasm {
         println!("{} Generating WebAssembly...", "4.".dimmed());
-        let wasm_file = output.join("program.wasm");
-        // For now, just create a placeholder
-        fs::write(&wasm_file, b"# WebAssembly output would go here")
-            .map_err(|e| ReamError::Io(e))?;
-        println!("  ✓ Generated: {}", wasm_file.display());
+        write_wasm(&output.join("program.wasm"), &built)?;
     }
     
     let duration = start_time.elapsed();
@@ -300,6 +529,218 @@ build_time = \"{}\"
     Ok(())
 }
 
+/// Build a project directory into a bytecode bundle
+fn execute_build_bundle(path: PathBuf, output: PathBuf, mode: BuildMode, check: bool) -> ReamResult<()> {
+    let start_time = Instant::now();
+
+    let (config, bundle) = build_project_bundle(&path, mode, check)?;
+
+    println!("{} Writing bundle...", "3.".dimmed());
+    fs::create_dir_all(&output).map_err(ReamError::Io)?;
+    let bundle_file = output.join(format!("{}.{}", config.package.name, BUNDLE_EXTENSION));
+    bundle.write_to(&bundle_file)?;
+    println!("  ✓ Generated: {}", bundle_file.display());
+
+    let duration = start_time.elapsed();
+    println!("{} Build completed in {:.2}ms", "✓".bright_green(), duration.as_millis());
+
+    Ok(())
+}
+
+/// Compile a project directory and its path dependencies into a bundle
+fn build_project_bundle(path: &Path, mode: BuildMode, check: bool) -> ReamResult<(ProjectConfig, BytecodeBundle)> {
+    let mut manager = ProjectConfigManager::new(path.to_path_buf());
+    let config = manager.load()
+        .map_err(|e| ReamError::Other(format!("Failed to load project config: {}", e)))?
+        .clone();
+
+    println!("{} {} v{}", "Building:".bright_green(), config.package.name.bright_cyan(), config.package.version);
+    println!("  Mode: {}", mode.to_string().bright_cyan());
+    println!("  Type checking: {}", if check { "enabled".bright_green() } else { "disabled".dimmed() });
+
+    println!("{} Compiling dependencies...", "1.".dimmed());
+    let extensions = load_project_extensions(path, &config, &mode)?;
+    for (name, _) in &extensions {
+        println!("  ✓ extension {}", name);
+    }
+    let natives: Vec<String> = extensions.into_iter().map(|(name, _)| name).collect();
+
+    let mut dependencies = Vec::new();
+    collect_bundle_dependencies(path, &config, check, &mut vec![config.package.name.clone()], &mut dependencies)?;
+    for module in &dependencies {
+        println!("  ✓ {} v{} ({} instructions)", module.name, module.version, module.program.instructions.len());
+    }
+
+    println!("{} Compiling {}...", "2.".dimmed(), config.package.name);
+    let entry_source = project_entry_source(path, &config, false);
+    let entry = compile_bundle_module(&config.package.name, &config.package.version, &entry_source, &natives, check)?;
+    println!("  ✓ {} ({} instructions)", entry_source.display(), entry.program.instructions.len());
+
+    let mut bundle = BytecodeBundle::new(entry);
+    bundle.build_mode = mode.to_string();
+    for module in dependencies {
+        bundle.add_dependency(module);
+    }
+    bundle.validate()?;
+
+    Ok((config, bundle))
+}
+
+/// Compile path dependencies depth-first so each lands after its own dependencies
+fn collect_bundle_dependencies(
+    root: &Path,
+    config: &ProjectConfig,
+    check: bool,
+    stack: &mut Vec<String>,
+    modules: &mut Vec<BundleModule>,
+) -> ReamResult<()> {
+    let mut names: Vec<&String> = config.dependencies.keys().collect();
+    names.sort();
+
+    for name in names {
+        if modules.iter().any(|m| &m.name == name) {
+            continue;
+        }
+        if stack.contains(name) {
+            return Err(ReamError::Other(format!("Dependency cycle: {} -> {}", stack.join(" -> "), name)));
+        }
+
+        let dep_root = match &config.dependencies[name] {
+            DependencySpec::Detailed(detailed) if detailed.path.is_some() => {
+                root.join(detailed.path.as_ref().unwrap())
+            }
+            _ => {
+                return Err(ReamError::Other(format!(
+                    "Dependency '{}' has no path; only path dependencies can be bundled", name
+                )));
+            }
+        };
+
+        let mut manager = ProjectConfigManager::new(dep_root.clone());
+        let dep_config = manager.load()
+            .map_err(|e| ReamError::Other(format!("Failed to load dependency '{}': {}", name, e)))?
+            .clone();
+
+        stack.push(name.clone());
+        collect_bundle_dependencies(&dep_root, &dep_config, check, stack, modules)?;
+        stack.pop();
+
+        let source = project_entry_source(&dep_root, &dep_config, true);
+        modules.push(compile_bundle_module(name, &dep_config.package.version, &source, &[], check)?);
+    }
+
+    Ok(())
+}
+
+/// Source file a project is built from, preferring the library for dependencies
+fn project_entry_source(root: &Path, config: &ProjectConfig, library: bool) -> PathBuf {
+    let lib = config.package.lib.as_ref().and_then(|lib| lib.path.clone());
+    let bin = config.package.bin.first().and_then(|bin| bin.path.clone());
+    let (preferred, fallback, default) = if library {
+        (lib, bin, "src/lib.tl")
+    } else {
+        (bin, lib, "src/main.tl")
+    };
+
+    root.join(preferred.or(fallback).unwrap_or_else(|| PathBuf::from(default)))
+}
+
+/// Build and load the native extensions a project declares, cached under
+/// its target directory
+fn load_project_extensions(root: &Path, config: &ProjectConfig, mode: &BuildMode) -> ReamResult<Vec<(String, Arc<dyn RustFunction>)>> {
+    if config.extensions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let target_dir = config.build.as_ref().and_then(|build| build.target_dir.clone())
+        .unwrap_or_else(|| PathBuf::from("target"));
+    let profile = match mode {
+        BuildMode::Debug => BuildProfile::Debug,
+        BuildMode::Release => BuildProfile::Release,
+    };
+    let mut integration = RustCrateIntegration::new(root.join(target_dir).join("extensions"));
+    Ok(integration.load_project_extensions(root, config, profile)?)
+}
+
+/// Project directory a source file belongs to, if any
+fn enclosing_project(file: &Path) -> Option<PathBuf> {
+    let file = file.canonicalize().ok()?;
+    file.ancestors().skip(1)
+        .find(|dir| ProjectConfigManager::new(dir.to_path_buf()).is_initialized())
+        .map(Path::to_path_buf)
+}
+
+/// Parse, type check and compile one source file into a bundle module;
+/// `natives` are the extension functions the source may call
+fn compile_bundle_module(name: &str, version: &str, source: &Path, natives: &[String], check: bool) -> ReamResult<BundleModule> {
+    let content = fs::read_to_string(source)
+        .map_err(|e| ReamError::Other(format!("Failed to read {}: {}", source.display(), e)))?;
+
+    let mut parser = crate::tlisp::Parser::new();
+    let tokens = parser.tokenize(&content)
+        .map_err(|e| ReamError::Other(format!("{}: lexer error: {}", source.display(), e)))?;
+    let expressions = match parser.parse_multiple(&tokens) {
+        Ok(exprs) => exprs,
+        Err(_) => vec![parser.parse(&tokens)
+            .map_err(|e| ReamError::Other(format!("{}: parse error: {}", source.display(), e)))?],
+    };
+
+    if check {
+        let mut checker = crate::tlisp::TypeChecker::new();
+        checker.define("*args*".to_string(), crate::tlisp::Type::List(Box::new(crate::tlisp::Type::String)));
+        checker.define("*file*".to_string(), crate::tlisp::Type::String);
+        for native in natives {
+            checker.define(native.clone(), crate::tlisp::Type::TypeVar(native.clone()));
+        }
+        // Top-level definitions may be used before they appear, e.g. by recursive functions
+        for expr in &expressions {
+            if let crate::tlisp::Expr::Define(def_name, _, _) = expr {
+                checker.define(def_name.clone(), crate::tlisp::Type::TypeVar(format!("{}:{}", name, def_name)));
+            }
+        }
+        for expr in &expressions {
+            checker.infer(expr)
+                .map_err(|e| ReamError::Other(format!("{}: type error: {}", source.display(), e)))?;
+        }
+    }
+
+    // Modules run top level in the VM, so there is no frame to return from
+    let mut program = compile_tlisp(name, &expressions)?;
+    program.metadata.source_language = "tlisp".to_string();
+    program.metadata.name = name.to_string();
+    program.metadata.version = version.to_string();
+
+    Ok(BundleModule::new(name.to_string(), version.to_string(), source.display().to_string(), program))
+}
+
+/// Run a bytecode bundle produced by `ream build`
+fn execute_bundle(file: PathBuf, time: bool, signatures: Option<SignaturePolicy>, verbose: bool) -> ReamResult<()> {
+    let start_time = Instant::now();
+
+    let bundle = BytecodeBundle::read_from(&file)?;
+    if verbose {
+        println!("  Entry: {} v{}", bundle.entry.name, bundle.entry.version);
+        println!("  Dependencies: {}", bundle.dependencies.len());
+        println!("  Instructions: {}", bundle.instruction_count());
+    }
+
+    let mut vm = BytecodeVM::new();
+    vm.set_signature_policy(signatures);
+    let result = bundle.execute(&mut vm)
+        .map_err(|e| ReamError::Other(format!("VM execution failed: {}", e)))?;
+
+    if time {
+        println!("{} {:.2}ms", "Execution time:".dimmed(), start_time.elapsed().as_millis());
+    }
+
+    if !matches!(result, crate::bytecode::Value::Null) {
+        println!("{} {}", "Result:".bright_green(), result);
+    }
+
+    println!("{} {}", "Completed:".bright_green(), file.display());
+
+    Ok(())
+}
+
 fn execute_check(file: PathBuf, types: bool, warnings: bool) -> ReamResult<()> {
     println!("{} {}", "Checking:".bright_green(), file.display());
     
@@ -414,81 +855,268 @@ fn execute_info(detailed: bool) -> ReamResult<()> {
     Ok(())
 }
 
-fn execute_test(path: Option<PathBuf>, parallel: bool, verbose: bool) -> ReamResult<()> {
+fn execute_test(
+    path: Option<PathBuf>,
+    parallel: bool,
+    verbose: bool,
+    filter: Option<String>,
+    format: TestFormat,
+    seed: Option<u64>,
+    output: Option<PathBuf>,
+) -> ReamResult<()> {
     let test_path = path.unwrap_or_else(|| PathBuf::from("tests"));
-    
-    println!("{} {}", "Running tests from:".bright_green(), test_path.display());
-    println!("  Parallel: {}", if parallel { "enabled".bright_green() } else { "disabled".dimmed() });
-    println!("  Verbose: {}", if verbose { "enabled".bright_green() } else { "disabled".dimmed() });
-    
+    // Machine-readable reports on stdout must not be mixed with progress output
+    let quiet = format != TestFormat::Pretty && output.is_none();
+
+    if !quiet {
+        println!("{} {}", "Running tests from:".bright_green(), test_path.display());
+        println!("  Parallel: {}", if parallel { "enabled".bright_green() } else { "disabled".dimmed() });
+        if let Some(filter) = &filter {
+            println!("  Filter: {}", filter.bright_cyan());
+        }
+        if let Some(seed) = seed {
+            println!("  Seed: {}", seed.to_string().bright_cyan());
+        }
+    }
+
     if !test_path.exists() {
         return Err(ReamError::Other(format!("Test path not found: {}", test_path.display())));
     }
-    
-    let start_time = Instant::now();
-    let mut total_tests = 0;
-    let mut passed_tests = 0;
-    let mut failed_tests = 0;
-    
+
     // Find test files
     let test_files = if test_path.is_file() {
         vec![test_path]
     } else {
         find_test_files(&test_path)?
     };
-    
+
     if test_files.is_empty() {
-        println!("{}", "No test files found".bright_yellow());
+        if !quiet {
+            println!("{}", "No test files found".bright_yellow());
+        }
         return Ok(());
     }
-    
-    // Run tests
-    for test_file in &test_files {
-        println!("\n{} {}", "Testing:".bright_blue(), test_file.display());
-        
-        let content = fs::read_to_string(test_file)
-            .map_err(|e| ReamError::Io(e))?;
-        
-        let mut interpreter = TlispInterpreter::new();
-        
-        // Add test utilities
-        interpreter.define("assert".to_string(), crate::tlisp::Value::Builtin("assert".to_string()));
-        interpreter.define("assert-eq".to_string(), crate::tlisp::Value::Builtin("assert-eq".to_string()));
-        interpreter.define("assert-error".to_string(), crate::tlisp::Value::Builtin("assert-error".to_string()));
-        
-        match interpreter.eval(&content) {
-            Ok(_) => {
-                passed_tests += 1;
-                println!("  ✓ {}", "Passed".bright_green());
+
+    let mut runner = TestRunner::new();
+    if parallel {
+        runner = runner.with_jobs(thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+    }
+    if let Some(filter) = filter {
+        runner = runner.with_filter(filter);
+    }
+    if let Some(seed) = seed {
+        runner = runner.with_seed(seed);
+    }
+    let report = runner.run(&test_files);
+
+    if !quiet {
+        let mut current_file = None;
+        for result in &report.results {
+            if current_file != Some(&result.file) {
+                println!("\n{} {}", "Testing:".bright_blue(), result.file.display());
+                current_file = Some(&result.file);
             }
-            Err(e) => {
-                failed_tests += 1;
-                println!("  ✗ {}", format!("Failed: {}", e).bright_red());
-                if verbose {
-                    println!("    Error details: {}", e);
+            match &result.outcome {
+                TestOutcome::Passed => {
+                    println!("  ✓ {} {}", result.name, format!("({:.2}ms)", result.duration.as_secs_f64() * 1000.0).dimmed());
+                }
+                TestOutcome::Failed(message) => {
+                    println!("  ✗ {}", result.name.bright_red());
+                    if verbose {
+                        println!("    {}", message);
+                    } else {
+                        println!("    {}", message.lines().next().unwrap_or(""));
+                    }
                 }
             }
         }
-        
-        total_tests += 1;
+
+        // Summary
+        println!("\n{}", "Test Summary:".bright_yellow().bold());
+        println!("  Total: {}", report.results.len().to_string().bright_cyan());
+        println!("  Passed: {}", report.passed().to_string().bright_green());
+        println!("  Failed: {}", report.failed().to_string().bright_red());
+        println!("  Duration: {:.2}ms", report.duration.as_millis());
     }
-    
-    let duration = start_time.elapsed();
-    
-    // Summary
-    println!("\n{}", "Test Summary:".bright_yellow().bold());
-    println!("  Total: {}", total_tests.to_string().bright_cyan());
-    println!("  Passed: {}", passed_tests.to_string().bright_green());
-    println!("  Failed: {}", failed_tests.to_string().bright_red());
-    println!("  Duration: {:.2}ms", duration.as_millis());
-    
-    if failed_tests == 0 {
-        println!("{} All tests passed!", "✓".bright_green());
+
+    let rendered = match format {
+        TestFormat::Pretty => None,
+        TestFormat::Junit => Some(report.to_junit_xml()),
+        TestFormat::Json => Some(report.to_json()),
+    };
+    if let Some(rendered) = rendered {
+        match &output {
+            Some(output) => {
+                fs::write(output, rendered).map_err(ReamError::Io)?;
+                println!("  ✓ Report written to {}", output.display());
+            }
+            None => print!("{}", rendered),
+        }
+    }
+
+    if report.is_success() {
+        if !quiet {
+            println!("{} All tests passed!", "✓".bright_green());
+        }
+        Ok(())
     } else {
-        println!("{} {} tests failed", "✗".bright_red(), failed_tests);
+        Err(ReamError::Other(format!("{} tests failed", report.failed())))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_bench(
+    path: Option<PathBuf>,
+    filter: Option<String>,
+    modes: Vec<BenchMode>,
+    warmup: usize,
+    iterations: usize,
+    format: BenchFormat,
+    output: Option<PathBuf>,
+    baseline: Option<PathBuf>,
+    save_baseline: Option<PathBuf>,
+    threshold: f64,
+) -> ReamResult<()> {
+    let bench_path = path.unwrap_or_else(|| PathBuf::from("benches"));
+    // Machine-readable reports on stdout must not be mixed with progress output
+    let quiet = format != BenchFormat::Pretty && output.is_none();
+
+    if !bench_path.exists() {
+        return Err(ReamError::Other(format!("Bench path not found: {}", bench_path.display())));
+    }
+    let files = discover_test_files(&bench_path)?;
+    if files.is_empty() {
+        if !quiet {
+            println!("{}", "No bench files found".bright_yellow());
+        }
+        return Ok(());
+    }
+
+    // Load the baseline up front so a bad path fails before the run
+    let baseline = baseline.map(|path| BenchReport::load(&path)).transpose()?;
+
+    if !quiet {
+        println!("{} {}", "Benchmarking:".bright_green(), bench_path.display());
+        let modes: Vec<String> = modes.iter().map(|m| m.to_string()).collect();
+        println!("  Modes: {}", modes.join(", ").bright_cyan());
+        println!("  Iterations: {} ({} warmup)", iterations, warmup);
+    }
+
+    let mut runner = BenchRunner::new()
+        .with_warmup(warmup)
+        .with_iterations(iterations)
+        .with_modes(modes);
+    if let Some(filter) = filter {
+        runner = runner.with_filter(filter);
+    }
+    let report = runner.run(&files);
+    let comparisons = baseline.as_ref()
+        .map(|baseline| report.compare(baseline, threshold))
+        .unwrap_or_default();
+
+    if !quiet {
+        println!("
+  {:<24} {:<12} {:>12} {:>25} {:>12} {:>10}", "BENCHMARK", "MODE", "MEAN", "95% CI", "STD DEV", "CHANGE");
+        for result in &report.results {
+            let Some(stats) = &result.stats else {
+                let error = result.error.as_deref().unwrap_or("not run");
+                println!("  {:<24} {:<12} {}", result.name, result.mode.to_string(), error.lines().next().unwrap_or("").dimmed());
+                continue;
+            };
+            let change = match comparisons.iter().find(|c| c.name == result.name && c.mode == result.mode) {
+                Some(c) => {
+                    let text = format!("{:+.1}%", c.change);
+                    match c.verdict {
+                        Verdict::Regressed => text.bright_red().to_string(),
+                        Verdict::Improved => text.bright_green().to_string(),
+                        Verdict::Unchanged => text.dimmed().to_string(),
+                    }
+                }
+                None => String::new(),
+            };
+            println!(
+                "  {:<24} {:<12} {:>12} {:>25} {:>12} {:>10}",
+                result.name,
+                result.mode.to_string(),
+                format_nanos(stats.mean_ns),
+                format!("{} – {}", format_nanos(stats.ci_low_ns), format_nanos(stats.ci_high_ns)),
+                format_nanos(stats.stddev_ns),
+                change
+            );
+        }
+        println!("
+  Duration: {:.2}ms", report.duration_ms);
+    }
+
+    let rendered = match format {
+        BenchFormat::Pretty => None,
+        BenchFormat::Json => Some(report.to_json()),
+        BenchFormat::Html => Some(report.to_html(&comparisons)),
+    };
+    if let Some(rendered) = rendered {
+        match &output {
+            Some(output) => {
+                fs::write(output, rendered).map_err(ReamError::Io)?;
+                println!("  ✓ Report written to {}", output.display());
+            }
+            None => print!("{}", rendered),
+        }
+    }
+
+    if let Some(path) = save_baseline {
+        fs::write(&path, report.to_json()).map_err(ReamError::Io)?;
+        if !quiet {
+            println!("  ✓ Baseline saved to {}", path.display());
+        }
+    }
+
+    let regressions: Vec<String> = comparisons.iter()
+        .filter(|c| c.verdict == Verdict::Regressed)
+        .map(|c| format!("{} ({}) {:+.1}%", c.name, c.mode, c.change))
+        .collect();
+    if regressions.is_empty() {
+        Ok(())
+    } else {
+        Err(ReamError::Other(format!("Performance regressions against baseline: {}", regressions.join(", "))))
     }
-    
-    Ok(())
+}
+
+fn execute_fuzz(
+    target: FuzzTarget,
+    runs: usize,
+    seed: Option<u64>,
+    corpus: Option<PathBuf>,
+    artifacts: Option<PathBuf>,
+    max_len: usize,
+    reproduce: Option<PathBuf>,
+) -> ReamResult<()> {
+    if let Some(input) = reproduce {
+        println!("{} {} on {}", "Reproducing:".bright_green(), target, input.display());
+        fuzz::reproduce(target, &input)?;
+        println!("{}", "No crash".bright_green());
+        return Ok(());
+    }
+
+    let corpus = corpus.unwrap_or_else(|| Path::new("fuzz/corpus").join(target.name()));
+    let artifacts = artifacts.unwrap_or_else(|| Path::new("fuzz/artifacts").join(target.name()));
+    let seed = seed.unwrap_or_else(rand::random);
+    println!("{} {}", "Fuzzing:".bright_green(), target);
+    println!("  Corpus: {}", corpus.display());
+    println!("  Runs: {} (seed {})", runs, seed.to_string().bright_cyan());
+
+    let report = Fuzzer::new(target, &corpus, artifacts, seed)?
+        .with_max_len(max_len)
+        .run(runs)?;
+    if report.crashes.is_empty() {
+        println!("{} {} inputs, no crashes", "Done:".bright_green(), report.runs);
+        return Ok(());
+    }
+
+    println!("{} {} crashing inputs in {} runs", "Failed:".bright_red(), report.crashes.len(), report.runs);
+    for crash in &report.crashes {
+        println!("  ream fuzz {} --reproduce {}", target, crash.display());
+    }
+    Err(ReamError::Other(format!("{} crashed on {} inputs", target, report.crashes.len())))
 }
 
 fn execute_package(command: PackageCommand) -> ReamResult<()> {
@@ -523,28 +1151,187 @@ fn execute_package(command: PackageCommand) -> ReamResult<()> {
     Ok(())
 }
 
-fn execute_bytecode(
-    file: PathBuf,
-    args: Vec<String>,
-    time: bool,
-    jit: bool,
-    stats: bool,
-    debug: bool,
-    verbose: bool
-) -> ReamResult<()> {
-    println!("{} {}", "Executing:".bright_green(), file.display());
+/// Client of the registry at `url`, or at $REAM_REGISTRY, with its index
+/// cached in the user's cache directory
+fn open_registry(url: Option<String>, token: Option<String>) -> ReamResult<PackageRegistry> {
+    let url = url.or_else(|| std::env::var("REAM_REGISTRY").ok())
+        .unwrap_or_else(|| DEFAULT_REGISTRY_URL.to_string());
+    let cache_name: String = url.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let cache_dir = dirs::cache_dir().unwrap_or_else(std::env::temp_dir)
+        .join("ream").join("registry").join(cache_name);
+
+    let mut registry = PackageRegistry::new(url.clone(), url, cache_dir);
+    registry.initialize()?;
+    if let Some(token) = token.or_else(|| std::env::var("REAM_REGISTRY_TOKEN").ok()) {
+        registry.set_auth_token(token);
+    }
+    Ok(registry)
+}
 
-    if !file.exists() {
-        return Err(ReamError::Other(format!("Bytecode file not found: {}", file.display())));
+fn execute_publish(path: PathBuf, registry: Option<String>, token: Option<String>, key: Option<PathBuf>, allow_overwrite: bool) -> ReamResult<()> {
+    let key = key.map(|key| SigningKey::load(&key)).transpose()?;
+    let mut registry = open_registry(registry, token)?;
+
+    let (config, mut bundle) = build_project_bundle(&path, BuildMode::Release, true)?;
+    if let Some(key) = &key {
+        bundle.sign(key)?;
+    }
+    let package = &config.package;
+    let mut metadata = PackageMetadata::new(package.name.clone(), package.version.clone());
+    metadata.description = package.description.clone();
+    metadata.author = package.authors.first().cloned();
+    metadata.license = package.license.clone();
+    metadata.homepage = package.homepage.clone();
+    metadata.repository = package.repository.clone();
+    metadata.keywords = package.keywords.clone();
+    metadata.categories = package.categories.clone();
+
+    println!("{} {} v{}", "Publishing:".bright_green(), package.name.bright_cyan(), package.version);
+    let options = PublishOptions { allow_overwrite, ..Default::default() };
+    let request = PublishRequest::new(metadata, &bundle.to_bytes()?, options, key.as_ref());
+    let published = registry.publish_package(request)?;
+
+    println!("  ✓ Uploaded {} bytes (sha256 {})", published.size, published.checksum);
+    if let Some(signature) = &published.signature {
+        println!("  ✓ Signed with key {}", signature.public_key);
     }
+    Ok(())
+}
 
-    // Check file extension
-    if let Some(ext) = file.extension() {
-        if ext != "reambc" {
-            println!("{} File doesn't have .reambc extension, attempting to load anyway...", "Warning:".bright_yellow());
-        }
+fn execute_yank(name: String, version: String, reason: Option<String>, undo: bool, registry: Option<String>, token: Option<String>) -> ReamResult<()> {
+    let mut registry = open_registry(registry, token)?;
+    if undo {
+        registry.unyank_version(&name, &version)?;
+        println!("{} {} v{} restored", "Success:".bright_green(), name, version);
+    } else {
+        registry.yank_version(&name, &version, reason)?;
+        println!("{} {} v{} yanked", "Success:".bright_green(), name, version);
     }
-
+    Ok(())
+}
+
+fn execute_search(query: String, limit: u32, offline: bool, registry: Option<String>, json: bool) -> ReamResult<()> {
+    let mut registry = open_registry(registry, None)?;
+    registry.set_offline(offline);
+    let results = registry.search_packages(SearchQuery { limit: Some(limit), ..SearchQuery::new(query) })?;
+
+    if json {
+        let json = serde_json::to_string_pretty(&results)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize results: {}", e)))?;
+        println!("{}", json);
+        return Ok(());
+    }
+    if results.packages.is_empty() {
+        println!("No packages found");
+        return Ok(());
+    }
+    for result in &results.packages {
+        let description = result.metadata.description.as_deref().unwrap_or("");
+        println!("{} v{}  {}", result.metadata.name.bright_cyan(), result.latest_version, description.dimmed());
+    }
+    if results.total as usize > results.packages.len() {
+        println!("... and {} more", results.total as usize - results.packages.len());
+    }
+    Ok(())
+}
+
+fn execute_registry_command(command: RegistryCommand) -> ReamResult<()> {
+    match command {
+        RegistryCommand::Serve { dir, addr } => {
+            let store = RegistryStore::open(&dir)?;
+            let packages = store.index().len();
+            let rt = tokio::runtime::Runtime::new().map_err(|e| ReamError::Other(format!("Failed to create runtime: {}", e)))?;
+            rt.block_on(async {
+                let (addr, server) = registry_server::bind(Arc::new(Mutex::new(store)), addr)?;
+                println!("{} {} ({} packages) at http://{}", "Serving registry:".bright_green(), dir.display(), packages, addr);
+                server.await;
+                Ok(())
+            })
+        }
+        RegistryCommand::Token { user, dir } => {
+            let token = RegistryStore::open(&dir)?.add_token(&user)?;
+            println!("{} {}", "Token for".bright_green(), user.bright_cyan());
+            println!("{}", token);
+            Ok(())
+        }
+        RegistryCommand::Mirror { dir, registry } => {
+            let mut registry = open_registry(registry, None)?;
+            let copied = RegistryStore::mirror(&mut registry, &dir)?;
+            println!("{} {} new package versions into {}", "Mirrored:".bright_green(), copied, dir.display());
+            Ok(())
+        }
+    }
+}
+
+fn execute_graphql_command(command: GraphqlCommand) -> ReamResult<()> {
+    match command {
+        GraphqlCommand::Serve { script, database, addr, socket } => {
+            let source = std::fs::read_to_string(&script)?;
+            let failed = |e: SqlError| ReamError::Other(format!("GraphQL server: {}", e));
+            let rt = tokio::runtime::Runtime::new().map_err(|e| ReamError::Other(format!("Failed to create runtime: {}", e)))?;
+
+            let cdc = ChangeDataCapture::new(CdcConfig::new());
+            let orm = rt
+                .block_on(async {
+                    Ok::<_, SqlError>(if database.starts_with("postgres://") || database.starts_with("postgresql://") {
+                        ScriptOrm::new(CdcDriver::new(PostgresDriver::connect(database.as_str()).await?, cdc.clone()))
+                    } else {
+                        ScriptOrm::new(CdcDriver::new(SqliteDriver::connect(database.as_str()).await?, cdc.clone()))
+                    })
+                })
+                .map_err(failed)?;
+            orm.install_global();
+
+            let mut interpreter = TlispInterpreter::new();
+            interpreter.define("*file*".to_string(), crate::tlisp::Value::String(script.to_string_lossy().to_string()));
+            interpreter.eval(&source)?;
+            let models = orm.models();
+            for model in &models {
+                cdc.capture(model.name.clone(), TableCapture::new().to_event_bus());
+            }
+
+            let interpreter = Arc::new(Mutex::new(interpreter));
+            let socket_path = socket.unwrap_or(config::current().daemon.socket_path);
+            let mut builder = GraphQLServer::builder_shared(script_schema(&models), orm.database())
+                .with_changes(cdc)
+                .with_actors(IpcClient::new(socket_path));
+            for (type_name, signature, resolver) in TlispResolver::registered(&interpreter).map_err(failed)? {
+                builder = builder.with_resolver(type_name, signature, resolver);
+            }
+            let server = Arc::new(builder.build().map_err(failed)?);
+
+            rt.block_on(async {
+                let (addr, serve) = server.bind(addr).map_err(failed)?;
+                println!("{} {} ({} models) at http://{}/graphql", "Serving GraphQL:".bright_green(), script.display(), models.len(), addr);
+                serve.await;
+                Ok(())
+            })
+        }
+    }
+}
+
+fn execute_bytecode(
+    file: PathBuf,
+    args: Vec<String>,
+    time: bool,
+    jit: bool,
+    stats: bool,
+    debug: bool,
+    verbose: bool
+) -> ReamResult<()> {
+    println!("{} {}", "Executing:".bright_green(), file.display());
+
+    if !file.exists() {
+        return Err(ReamError::Other(format!("Bytecode file not found: {}", file.display())));
+    }
+
+    // Check file extension
+    if let Some(ext) = file.extension() {
+        if ext != "reambc" && ext != ASSEMBLY_EXTENSION {
+            println!("{} File doesn't have .reambc extension, attempting to load anyway...", "Warning:".bright_yellow());
+        }
+    }
+
     let start_time = if time {
         Some(Instant::now())
     } else {
@@ -620,12 +1407,22 @@ fn execute_compile(
     format: CompileFormat,
     optimization: u8,
     debug_info: bool,
+    backend: crate::bytecode::ExecutionBackend,
     stats: bool,
+    target: CompileTarget,
     debug: bool,
     verbose: bool
 ) -> ReamResult<()> {
     println!("{} {}", "Compiling:".bright_green(), file.display());
 
+    if backend == crate::bytecode::ExecutionBackend::Register {
+        match target {
+            CompileTarget::Aot(_) => return Err(ReamError::Other("--aot compiles for the stack backend only".to_string())),
+            CompileTarget::Wasm => return Err(ReamError::Other("--target wasm compiles for the stack backend only".to_string())),
+            CompileTarget::Bytecode => {}
+        }
+    }
+
     if !file.exists() {
         return Err(ReamError::Other(format!("File not found: {}", file.display())));
     }
@@ -696,17 +1493,7 @@ fn execute_compile(
         .unwrap_or("program")
         .to_string();
 
-    let mut compiler = BytecodeCompiler::new(program_name.clone());
-
-    // Compile all TLisp expressions to bytecode
-    for expr in &expressions {
-        compile_tlisp_expr(&mut compiler, expr)?;
-    }
-
-    // Add final return instruction
-    compiler.emit(crate::bytecode::Bytecode::Ret(crate::types::EffectGrade::Pure));
-
-    let mut program = compiler.finish()?;
+    let mut program = compile_tlisp(&program_name, &expressions)?;
 
     // Set metadata
     program.metadata.source_language = "tlisp".to_string();
@@ -722,11 +1509,36 @@ fn execute_compile(
         program.metadata.debug_info = Some(debug_info);
     }
 
+    let (mut program, optimization_stats) = crate::bytecode::OptimizationPipeline::for_level(optimization)
+        .optimize_with_stats(&program)
+        .map_err(|e| ReamError::Other(format!("Optimization failed: {}", e)))?;
+
+    if backend == crate::bytecode::ExecutionBackend::Register {
+        use crate::bytecode::Optimization;
+        program = crate::bytecode::StackToRegister.optimize(&program)
+            .map_err(|e| ReamError::Other(format!("Register backend unavailable: {}", e)))?;
+    }
+
     if verbose {
         println!("  ✓ Generated {} instructions", program.instructions.len());
         println!("  ✓ Generated {} constants", program.constants.len());
     }
 
+    match target {
+        CompileTarget::Aot(aot) => {
+            write_native(&file, output, &program, optimization, aot)?;
+            println!("{} Compilation completed successfully!", "✓".bright_green());
+            return Ok(());
+        }
+        CompileTarget::Wasm => {
+            println!("{} Compiling to WebAssembly...", "4.".dimmed());
+            write_wasm(&output.unwrap_or_else(|| file.with_extension("wasm")), &program)?;
+            println!("{} Compilation completed successfully!", "✓".bright_green());
+            return Ok(());
+        }
+        CompileTarget::Bytecode => {}
+    }
+
     // Determine output file
     let output_file = output.unwrap_or_else(|| {
         let mut output_path = file.clone();
@@ -745,12 +1557,7 @@ fn execute_compile(
     println!("{} Writing output file...", "4.".dimmed());
     match format {
         CompileFormat::Binary => {
-            // Serialize to binary format using bincode
-            let binary_data = bincode::serialize(&program)
-                .map_err(|e| ReamError::Other(format!("Serialization failed: {}", e)))?;
-
-            fs::write(&output_file, binary_data)
-                .map_err(|e| ReamError::Io(e))?;
+            program.save(&output_file)?;
 
             if verbose {
                 println!("  ✓ Binary format: {} bytes", fs::metadata(&output_file).unwrap().len());
@@ -781,6 +1588,14 @@ fn execute_compile(
         println!("  Format: {:?}", format);
         println!("  Optimization level: {}", optimization);
         println!("  Debug info: {}", if debug_info { "enabled" } else { "disabled" });
+        println!("  Backend: {}", program.metadata.backend);
+        if let (Some(before), Some(after)) = (optimization_stats.instructions_before(), optimization_stats.instructions_after()) {
+            println!("  Optimized: {} -> {} instructions", before, after);
+            for pass in &optimization_stats.passes {
+                println!("    {}: {} -> {} instructions, {} rewritten",
+                    pass.name, pass.instructions_before, pass.instructions_after, pass.rewritten);
+            }
+        }
         println!("  Instructions: {}", program.instructions.len());
         println!("  Constants: {}", program.constants.len());
         println!("  Functions: {}", program.functions.len());
@@ -797,296 +1612,83 @@ fn execute_compile(
     Ok(())
 }
 
-/// Compile a TLisp expression to bytecode
-fn compile_tlisp_expr(compiler: &mut BytecodeCompiler, expr: &crate::tlisp::Expr<()>) -> ReamResult<()> {
-    use crate::tlisp::Expr;
-    use crate::bytecode::{Bytecode, Value};
-    use crate::types::EffectGrade;
-
-    match expr {
-        // Literals
-        Expr::Number(n, _) => {
-            let const_id = compiler.add_const(Value::Int(*n));
-            compiler.emit(Bytecode::Const(const_id, EffectGrade::Pure));
-        }
-
-        Expr::Float(f, _) => {
-            let const_id = compiler.add_const(Value::Float(*f));
-            compiler.emit(Bytecode::Const(const_id, EffectGrade::Pure));
-        }
-
-        Expr::Bool(b, _) => {
-            let const_id = compiler.add_const(Value::Bool(*b));
-            compiler.emit(Bytecode::Const(const_id, EffectGrade::Pure));
-        }
-
-        Expr::String(s, _) => {
-            let const_id = compiler.add_const(Value::String(s.clone()));
-            compiler.emit(Bytecode::Const(const_id, EffectGrade::Pure));
-        }
-
-        // Variables
-        Expr::Symbol(name, _) => {
-            // Try to load as global variable, if not found, treat as constant
-            let const_id = compiler.add_const(Value::String(name.clone()));
-            compiler.emit(Bytecode::LoadGlobal(const_id, EffectGrade::Read));
-        }
-
-        // Lists (function calls and special forms)
-        Expr::List(exprs, _) => {
-            if exprs.is_empty() {
-                // Empty list
-                let const_id = compiler.add_const(Value::Null);
-                compiler.emit(Bytecode::Const(const_id, EffectGrade::Pure));
-                return Ok(());
-            }
-
-            // Check if it's a special form or function call
-            if let Expr::Symbol(op, _) = &exprs[0] {
-                match op.as_str() {
-                    // Arithmetic operations
-                    "+" => compile_arithmetic_op(compiler, &exprs[1..], Bytecode::Add(EffectGrade::Pure))?,
-                    "-" => compile_arithmetic_op(compiler, &exprs[1..], Bytecode::Sub(EffectGrade::Pure))?,
-                    "*" => compile_arithmetic_op(compiler, &exprs[1..], Bytecode::Mul(EffectGrade::Pure))?,
-                    "/" => compile_arithmetic_op(compiler, &exprs[1..], Bytecode::Div(EffectGrade::Pure))?,
-
-                    // Comparison operations
-                    "=" => compile_comparison_op(compiler, &exprs[1..], Bytecode::Eq(EffectGrade::Pure))?,
-                    "<" => compile_comparison_op(compiler, &exprs[1..], Bytecode::Lt(EffectGrade::Pure))?,
-                    "<=" => compile_comparison_op(compiler, &exprs[1..], Bytecode::Le(EffectGrade::Pure))?,
-                    ">" => compile_comparison_op(compiler, &exprs[1..], Bytecode::Gt(EffectGrade::Pure))?,
-                    ">=" => compile_comparison_op(compiler, &exprs[1..], Bytecode::Ge(EffectGrade::Pure))?,
-
-                    // Special forms
-                    "define" => compile_define(compiler, &exprs[1..])?,
-                    "if" => compile_if(compiler, &exprs[1..])?,
-                    "println" => compile_println(compiler, &exprs[1..])?,
-
-                    // Function call
-                    _ => compile_function_call(compiler, op, &exprs[1..])?,
-                }
-            } else {
-                // First element is not a symbol, compile as regular expression
-                for expr in exprs {
-                    compile_tlisp_expr(compiler, expr)?;
-                }
-            }
-        }
-
-        // Other expression types
-        Expr::Lambda(_params, body, _) => {
-            // For now, just compile the body
-            compile_tlisp_expr(compiler, body)?;
-        }
-
-        Expr::Application(func, args, _) => {
-            // Compile function and arguments
-            compile_tlisp_expr(compiler, func)?;
-            for arg in args {
-                compile_tlisp_expr(compiler, arg)?;
-            }
-            // For now, just emit a no-op instead of trying to call undefined functions
-            compiler.emit(Bytecode::Nop(EffectGrade::Pure));
-        }
-
-        Expr::Let(bindings, body, _) => {
-            // Compile bindings
-            for (name, expr) in bindings {
-                compile_tlisp_expr(compiler, expr)?;
-                // Store to local variable (simplified)
-                let const_id = compiler.add_const(Value::String(name.clone()));
-                compiler.emit(Bytecode::Store(const_id, EffectGrade::Write));
-            }
-            // Compile body
-            compile_tlisp_expr(compiler, body)?;
-        }
-
-        Expr::If(condition, then_expr, else_expr, _) => {
-            // Use the helper function for if compilation
-            let args = vec![(**condition).clone(), (**then_expr).clone(), (**else_expr).clone()];
-            compile_if(compiler, &args)?;
-        }
-
-        Expr::Quote(expr, _) => {
-            // For now, just compile the quoted expression
-            compile_tlisp_expr(compiler, expr)?;
-        }
-
-        Expr::Define(name, expr, _) => {
-            // Compile the expression
-            compile_tlisp_expr(compiler, expr)?;
-            // Store to global variable
-            let const_id = compiler.add_const(Value::String(name.clone()));
-            compiler.emit(Bytecode::StoreGlobal(const_id, EffectGrade::Write));
-        }
-
-        _ => {
-            // Unsupported expression type
-            let const_id = compiler.add_const(Value::Null);
-            compiler.emit(Bytecode::Const(const_id, EffectGrade::Pure));
-        }
-    }
-
-    Ok(())
+/// What `ream compile` compiles a program to
+enum CompileTarget {
+    /// Bytecode for the VM
+    Bytecode,
+    /// Native code, with --aot
+    Aot(AotOutput),
+    /// A WebAssembly module, with --target wasm
+    Wasm,
 }
 
-/// Compile arithmetic operations
-fn compile_arithmetic_op(
-    compiler: &mut BytecodeCompiler,
-    args: &[crate::tlisp::Expr<()>],
-    op: crate::bytecode::Bytecode
-) -> ReamResult<()> {
-    if args.len() < 2 {
-        return Err(ReamError::Other("Arithmetic operations require at least 2 arguments".to_string()));
-    }
-
-    // Compile first argument
-    compile_tlisp_expr(compiler, &args[0])?;
-
-    // Compile remaining arguments and emit operations
-    for arg in &args[1..] {
-        compile_tlisp_expr(compiler, arg)?;
-        compiler.emit(op.clone());
-    }
-
-    Ok(())
+/// Whether a --target names WebAssembly rather than a native triple
+fn is_wasm_target(target: &str) -> bool {
+    target == "wasm" || target.starts_with("wasm32")
 }
 
-/// Compile comparison operations
-fn compile_comparison_op(
-    compiler: &mut BytecodeCompiler,
-    args: &[crate::tlisp::Expr<()>],
-    op: crate::bytecode::Bytecode
-) -> ReamResult<()> {
-    if args.len() != 2 {
-        return Err(ReamError::Other("Comparison operations require exactly 2 arguments".to_string()));
-    }
-
-    // Compile both arguments
-    compile_tlisp_expr(compiler, &args[0])?;
-    compile_tlisp_expr(compiler, &args[1])?;
-
-    // Emit comparison operation
-    compiler.emit(op);
-
-    Ok(())
+/// Where `ream compile --aot` sends its output
+struct AotOutput {
+    /// Target triple, the host when `None`
+    target: Option<String>,
+    /// Runtime static library to link against
+    runtime: Option<PathBuf>,
 }
 
-/// Compile define statements
-fn compile_define(compiler: &mut BytecodeCompiler, args: &[crate::tlisp::Expr<()>]) -> ReamResult<()> {
-    if args.len() != 2 {
-        return Err(ReamError::Other("define requires exactly 2 arguments".to_string()));
-    }
-
-    // Get variable name
-    let var_name = match &args[0] {
-        crate::tlisp::Expr::Symbol(name, _) => name.clone(),
-        crate::tlisp::Expr::List(exprs, _) if !exprs.is_empty() => {
-            // Function definition: (define (name params...) body)
-            if let crate::tlisp::Expr::Symbol(name, _) = &exprs[0] {
-                // For now, just treat as variable definition
-                name.clone()
-            } else {
-                return Err(ReamError::Other("Invalid function definition".to_string()));
-            }
-        }
-        _ => return Err(ReamError::Other("define requires a symbol or function signature".to_string())),
+/// Compile a program ahead of time, linking an executable when the
+/// runtime library is known and writing an object file otherwise
+fn write_native(file: &Path, output: Option<PathBuf>, program: &BytecodeProgram, optimization: u8, aot: AotOutput) -> ReamResult<()> {
+    let mut compiler = match &aot.target {
+        Some(target) => AotCompiler::new(target)?,
+        None => AotCompiler::host(),
+    };
+    compiler.set_optimization_level(optimization);
+
+    println!("{} Compiling to native code for {}...", "4.".dimmed(), compiler.target());
+    let object = compiler.compile_object(program)?;
+    let Some(runtime) = aot.runtime else {
+        let output = output.unwrap_or_else(|| file.with_extension("o"));
+        fs::write(&output, object).map_err(ReamError::Io)?;
+        println!("  ✓ Generated: {}", output.display());
+        println!("  Link it against the runtime library with --runtime to get an executable");
+        return Ok(());
     };
 
-    // Compile the value expression
-    compile_tlisp_expr(compiler, &args[1])?;
-
-    // Store to global variable
-    let const_id = compiler.add_const(crate::bytecode::Value::String(var_name));
-    compiler.emit(crate::bytecode::Bytecode::StoreGlobal(const_id, crate::types::EffectGrade::Write));
-
-    Ok(())
-}
-
-/// Compile if expressions
-fn compile_if(compiler: &mut BytecodeCompiler, args: &[crate::tlisp::Expr<()>]) -> ReamResult<()> {
-    if args.len() != 3 {
-        return Err(ReamError::Other("if requires exactly 3 arguments (condition, then, else)".to_string()));
-    }
-
-    // Generate unique labels
-    let else_label = format!("else_{}", std::ptr::addr_of!(*compiler) as usize);
-    let end_label = format!("end_{}", std::ptr::addr_of!(*compiler) as usize);
-
-    // Compile condition
-    compile_tlisp_expr(compiler, &args[0])?;
-
-    // Jump to else if condition is false
-    let else_pc = compiler.label_ref(else_label.clone());
-    compiler.emit(crate::bytecode::Bytecode::JumpIfNot(else_pc, crate::types::EffectGrade::Pure));
-
-    // Compile then branch
-    compile_tlisp_expr(compiler, &args[1])?;
-
-    // Jump to end
-    let end_pc = compiler.label_ref(end_label.clone());
-    compiler.emit(crate::bytecode::Bytecode::Jump(end_pc, crate::types::EffectGrade::Pure));
-
-    // Define else label
-    compiler.define_label(else_label);
-
-    // Compile else branch
-    compile_tlisp_expr(compiler, &args[2])?;
-
-    // Define end label
-    compiler.define_label(end_label);
-
+    let output = output.unwrap_or_else(|| file.with_extension(""));
+    let object_file = output.with_extension("o");
+    fs::write(&object_file, object).map_err(ReamError::Io)?;
+    println!("{} Linking with {}...", "5.".dimmed(), runtime.display());
+    let linked = compiler.link(&object_file, &runtime, &output);
+    let _ = fs::remove_file(&object_file);
+    linked?;
+    println!("  ✓ Generated: {}", output.display());
     Ok(())
 }
 
-/// Compile println statements
-fn compile_println(compiler: &mut BytecodeCompiler, args: &[crate::tlisp::Expr<()>]) -> ReamResult<()> {
-    // Compile all arguments
-    for arg in args {
-        compile_tlisp_expr(compiler, arg)?;
-        compiler.emit(crate::bytecode::Bytecode::Print(crate::types::EffectGrade::Write));
-    }
-
+/// Compile a program to a WebAssembly module at `output`, with a
+/// JavaScript loader for it alongside
+fn write_wasm(output: &Path, program: &BytecodeProgram) -> ReamResult<()> {
+    let module = WasmCompiler::new().compile(program)?;
+    fs::write(output, module).map_err(ReamError::Io)?;
+    println!("  ✓ Generated: {}", output.display());
+
+    let module_name = output.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let loader = output.with_extension("js");
+    fs::write(&loader, wasm::LOADER.replace("{module}", &format!("./{}", module_name))).map_err(ReamError::Io)?;
+    println!("  ✓ Generated: {}", loader.display());
     Ok(())
 }
 
-/// Compile function calls
-fn compile_function_call(
-    compiler: &mut BytecodeCompiler,
-    func_name: &str,
-    args: &[crate::tlisp::Expr<()>]
-) -> ReamResult<()> {
-    // For built-in functions, handle them specially
-    match func_name {
-        "string-append" => {
-            // Compile all arguments and concatenate
-            for arg in args {
-                compile_tlisp_expr(compiler, arg)?;
-            }
-            // For now, just use the last argument
-            Ok(())
-        }
-        "number->string" => {
-            // Compile argument and convert to string
-            if args.len() != 1 {
-                return Err(ReamError::Other("number->string requires exactly 1 argument".to_string()));
-            }
-            compile_tlisp_expr(compiler, &args[0])?;
-            // For now, just keep the value as-is
-            Ok(())
-        }
-        _ => {
-            // Generic function call - for now, just compile arguments and emit a placeholder
-            for arg in args {
-                compile_tlisp_expr(compiler, arg)?;
-            }
-
-            // For now, just emit a no-op instead of trying to call undefined functions
-            compiler.emit(crate::bytecode::Bytecode::Nop(crate::types::EffectGrade::Pure));
-
-            Ok(())
-        }
-    }
+/// Compile TLisp expressions, in order, into one program whose result is
+/// the last one's value
+fn compile_tlisp(name: &str, expressions: &[crate::tlisp::Expr<()>]) -> ReamResult<BytecodeProgram> {
+    let interpreter = TlispInterpreter::new();
+    let expressions: Vec<_> = expressions.iter()
+        .map(|expr| interpreter.annotate_types(expr.clone()))
+        .collect();
+    let mut compiler = EnhancedTlispCompiler::new(name.to_string());
+    compiler.compile_sequence(&expressions)?;
+    Ok(compiler.finish()?)
 }
 
 // Helper functions
@@ -1113,6 +1715,9 @@ fn format_value(value: &crate::tlisp::Value) -> String {
         crate::tlisp::Value::Pid(pid) => {
             format!("#<pid:{}>", pid.raw())
         }
+        crate::tlisp::Value::Binary(binary) => {
+            format!("#<binary:{}>", binary.len())
+        }
         crate::tlisp::Value::Unit => "()".to_string(),
         crate::tlisp::Value::Null => "null".to_string(),
         crate::tlisp::Value::StmVar(var) => format!("#<stm-var:{}>", var.name()),
@@ -1209,28 +1814,7 @@ fn format_tlisp_code(content: &str, indent: u8) -> String {
 }
 
 fn find_test_files(dir: &PathBuf) -> ReamResult<Vec<PathBuf>> {
-    let mut test_files = Vec::new();
-    
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir).map_err(|e| ReamError::Io(e))? {
-            let entry = entry.map_err(|e| ReamError::Io(e))?;
-            let path = entry.path();
-            
-            if path.is_file() {
-                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                    if name.ends_with("_test.scm") || name.ends_with(".test.scm") {
-                        test_files.push(path);
-                    }
-                }
-            } else if path.is_dir() {
-                // Recursively search subdirectories
-                test_files.extend(find_test_files(&path)?);
-            }
-        }
-    }
-    
-    test_files.sort();
-    Ok(test_files)
+    discover_test_files(dir).map_err(|e| ReamError::Other(e.to_string()))
 }
 
 #[cfg(test)]
@@ -1263,6 +1847,126 @@ mod tests {
         assert_eq!(files.len(), 1);
         assert_eq!(files[0], test_file);
     }
+
+    #[test]
+    fn test_execute_build_bundle_with_path_dependency() {
+        let temp_dir = TempDir::new().unwrap();
+        let util = temp_dir.path().join("util");
+        let app = temp_dir.path().join("app");
+
+        ProjectConfigManager::new(util.clone()).init_project("util".to_string(), "0.2.0".to_string(), Some("lib")).unwrap();
+        std::fs::write(util.join("src/lib.tl"), "(define answer 40)").unwrap();
+
+        let mut manager = ProjectConfigManager::new(app.clone());
+        manager.init_project("app".to_string(), "0.1.0".to_string(), Some("bin")).unwrap();
+        manager.config_mut().unwrap().dependencies.insert(
+            "util".to_string(),
+            DependencySpec::Detailed(crate::tlisp::package_config::DetailedDependency {
+                version: None,
+                git: None,
+                branch: None,
+                tag: None,
+                rev: None,
+                path: Some(PathBuf::from("../util")),
+                registry: None,
+                features: Vec::new(),
+                default_features: true,
+                optional: false,
+                package: None,
+            }),
+        );
+        manager.save().unwrap();
+        std::fs::write(app.join("src/main.tl"), "(+ 40 2)").unwrap();
+
+        let output = temp_dir.path().join("build");
+        execute_build_bundle(app, output.clone(), BuildMode::Release, true).unwrap();
+
+        let bundle = BytecodeBundle::read_from(output.join("app.reamb")).unwrap();
+        assert_eq!(bundle.entry.name, "app");
+        assert_eq!(bundle.dependencies.len(), 1);
+        assert_eq!(bundle.dependencies[0].version, "0.2.0");
+        let result = bundle.execute(&mut BytecodeVM::new()).unwrap();
+        assert!(matches!(result, crate::bytecode::Value::Int(42)));
+    }
+
+    #[test]
+    fn test_compile_collection_builtins() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("main.tl");
+        std::fs::write(&source, r#"
+            (string-append
+                "first=" (number->string (list-ref (map-get (make-map "xs" (sort (list 3 1 2))) "xs") 0))
+                " ok=" (number->string (and (= (length (list 1 2 3)) 3) (not (< 2 1)))))
+        "#).unwrap();
+
+        let module = compile_bundle_module("main", "0.1.0", &source, &[], false).unwrap();
+        let result = BytecodeVM::new().execute_program(&module.program).unwrap();
+        assert_eq!(result, crate::bytecode::Value::String("first=1 ok=true".to_string()));
+    }
+
+    #[test]
+    fn test_compiled_programs_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let programs = [
+            ("greet", r#"
+                (define (greet s) (string-append s " world"))
+                (greet "hello")
+            "#, crate::bytecode::Value::String("hello world".to_string())),
+            ("catch", r#"(try (throw "boom") (catch e (string-append "caught " e)))"#,
+                crate::bytecode::Value::String("caught boom".to_string())),
+            ("fib", r#"
+                (define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))
+                (fib 15)
+            "#, crate::bytecode::Value::Int(610)),
+            ("loop", r#"
+                (define limit 10)
+                (define (loop i acc)
+                    (if (> i limit)
+                        acc
+                        (loop (+ i 1) (+ acc i))))
+                (println "summing")
+                (loop 0 0)
+            "#, crate::bytecode::Value::Int(55)),
+        ];
+
+        for (name, source, expected) in programs {
+            let file = temp_dir.path().join(format!("{}.tl", name));
+            std::fs::write(&file, source).unwrap();
+            for optimization in [0, 2] {
+                execute_compile(file.clone(), None, CompileFormat::Binary, optimization, false,
+                    crate::bytecode::ExecutionBackend::Stack, false, CompileTarget::Bytecode, false, false).unwrap();
+                let compiled = file.with_extension("reambc");
+                let program = load_bytecode_file(&compiled).unwrap();
+
+                assert_eq!(BytecodeVM::new().execute_program(&program).unwrap(), expected, "{} -O{}", name, optimization);
+                let jit = JitRuntime::new(crate::runtime::ReamRuntime::new().unwrap());
+                assert_eq!(jit.execute_program(&program).unwrap(), expected, "{} -O{} --jit", name, optimization);
+                for jit in [false, true] {
+                    execute_bytecode(compiled.clone(), vec![], false, jit, false, false, false).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_execute_new_scaffolds_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("service");
+
+        execute_new("service".to_string(), ProjectTemplate::Actor, Some(root.clone()), "0.1.0".to_string()).unwrap();
+
+        for file in [CONFIG_FILE, "src/main.tl", "tests/main_test.tl", ".gitignore"] {
+            assert!(root.join(file).exists(), "missing {}", file);
+        }
+        let main = std::fs::read_to_string(root.join("src/main.tl")).unwrap();
+        assert!(main.contains("(spawn"));
+        let test_files = find_test_files(&root.join("tests")).unwrap();
+        assert_eq!(test_files.len(), 1);
+        assert!(TestRunner::new().run(&test_files).is_success());
+
+        // Refuses to overwrite an existing project
+        assert!(execute_new("service".to_string(), ProjectTemplate::Basic, Some(root), "0.1.0".to_string()).is_err());
+    }
 }
 
 /// Load a bytecode program from a file
@@ -1270,7 +1974,18 @@ fn load_bytecode_file(file: &PathBuf) -> ReamResult<BytecodeProgram> {
     let file_data = fs::read(file)
         .map_err(|e| ReamError::Io(e))?;
 
-    // Try to deserialize as binary format first
+    if file.extension().is_some_and(|ext| ext == ASSEMBLY_EXTENSION) {
+        let source = String::from_utf8(file_data)
+            .map_err(|e| ReamError::Other(format!("Invalid UTF-8 in assembly file: {}", e)))?;
+        return assembly::assemble(&source)
+            .map_err(|e| ReamError::Other(format!("{}: {}", file.display(), e)));
+    }
+
+    if BytecodeProgram::is_program_file(&file_data) {
+        return Ok(BytecodeProgram::from_bytes(&file_data)?);
+    }
+
+    // Files written before the versioned format: plain bincode, then JSON
     match bincode::deserialize::<BytecodeProgram>(&file_data) {
         Ok(program) => Ok(program),
         Err(_) => {
@@ -1284,6 +1999,61 @@ fn load_bytecode_file(file: &PathBuf) -> ReamResult<BytecodeProgram> {
     }
 }
 
+/// Load a bytecode or assembly file, compiling TLisp sources
+fn load_program(file: &PathBuf) -> ReamResult<BytecodeProgram> {
+    if file.extension().is_some_and(|ext| ext == "tl") {
+        let name = file.file_stem().and_then(|stem| stem.to_str()).unwrap_or("main");
+        Ok(compile_bundle_module(name, "0.0.0", file, &[], false)?.program)
+    } else {
+        load_bytecode_file(file)
+    }
+}
+
+/// Print a program as bytecode assembly
+fn execute_disasm(program: PathBuf, output: Option<PathBuf>) -> ReamResult<()> {
+    let text = assembly::disassemble(&load_program(&program)?);
+    match output {
+        Some(output) => {
+            fs::write(&output, text).map_err(ReamError::Io)?;
+            println!("{} {}", "Disassembled:".bright_green(), output.display());
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// Assemble a .reams file into a bytecode program file
+fn execute_asm(file: PathBuf, output: Option<PathBuf>) -> ReamResult<()> {
+    let source = fs::read_to_string(&file).map_err(ReamError::Io)?;
+    let program = assembly::assemble(&source)
+        .map_err(|e| ReamError::Other(format!("{}: {}", file.display(), e)))?;
+
+    let output = output.unwrap_or_else(|| file.with_extension("reambc"));
+    program.save(&output)?;
+
+    println!("{} {} ({} instructions)", "Assembled:".bright_green(), output.display(), program.instructions.len());
+    Ok(())
+}
+
+/// Sign a bundle in place
+fn execute_sign(file: PathBuf, key: PathBuf) -> ReamResult<()> {
+    let key = SigningKey::load(&key)?;
+    let mut bundle = BytecodeBundle::read_from(&file)?;
+    bundle.sign(&key)?;
+    bundle.write_to(&file)?;
+
+    println!("{} {} with key {}", "Signed:".bright_green(), file.display(), hex::encode(key.public_key()));
+    Ok(())
+}
+
+fn execute_keygen(output: PathBuf) -> ReamResult<()> {
+    let key = generate_key_file(&output)?;
+
+    println!("{} {}", "Signing key:".bright_green(), output.display());
+    println!("{} {} ({})", "Public key:".bright_green(), public_key_path(&output).display(), hex::encode(key.public_key()));
+    Ok(())
+}
+
 /// Create a standalone executable from TLisp source code
 fn create_standalone_executable(
     source_content: &str,
@@ -1366,41 +2136,62 @@ cargo run --manifest-path "{}" -- run "$REAM_SOURCE"
 
 fn execute_daemon(command: DaemonCommand, debug: bool, verbose: bool) -> ReamResult<()> {
     match command {
-        DaemonCommand::Start { file, socket, pidfile, logfile, foreground } => {
-            execute_daemon_start(file, socket, pidfile, logfile, foreground, debug, verbose)
+        DaemonCommand::Start { file, config, socket, pidfile, logfile, log_rotation, metrics_addr, metrics_actor_limit, foreground } => {
+            execute_daemon_start(file, config, socket, pidfile, logfile, log_rotation, metrics_addr, metrics_actor_limit, foreground, debug, verbose)
         }
         DaemonCommand::Stop { socket, pidfile, force } => {
-            let config = DaemonConfig::default();
+            let config = config::current().daemon;
             let socket = socket.unwrap_or(config.socket_path);
             let pidfile = pidfile.unwrap_or(config.pid_file);
             execute_daemon_stop(socket, pidfile, force, debug, verbose)
         }
         DaemonCommand::Status { socket, pidfile } => {
-            let config = DaemonConfig::default();
+            let config = config::current().daemon;
             let socket = socket.unwrap_or(config.socket_path);
             let pidfile = pidfile.unwrap_or(config.pid_file);
             execute_daemon_status(socket, pidfile, debug, verbose)
         }
+        DaemonCommand::Reload { socket } => {
+            let socket = socket.unwrap_or(config::current().daemon.socket_path);
+            execute_daemon_reload(socket)
+        }
         DaemonCommand::Restart { file, socket, pidfile, logfile } => {
             execute_daemon_restart(file, socket, pidfile, logfile, debug, verbose)
         }
         DaemonCommand::Actor { command } => {
             execute_actor_command(command, debug, verbose)
         }
+        DaemonCommand::Domain { command } => {
+            execute_domain_command(command)
+        }
+        DaemonCommand::Schemas { socket, json } => {
+            execute_daemon_schemas(socket.unwrap_or(config::current().daemon.socket_path), json)
+        }
     }
 }
 
 fn execute_daemon_start(
     file: PathBuf,
+    config_file: Option<PathBuf>,
     socket: Option<PathBuf>,
     pidfile: Option<PathBuf>,
     logfile: Option<PathBuf>,
+    log_rotation: Option<LogRotation>,
+    metrics_addr: Option<SocketAddr>,
+    metrics_actor_limit: Option<usize>,
     foreground: bool,
     debug: bool,
     verbose: bool,
 ) -> ReamResult<()> {
-    // Use default config and override with provided values
-    let mut config = DaemonConfig::default();
+    // Start from the layered settings and override with provided values
+    let mut config = match &config_file {
+        Some(path) => ReamSettings::load(Some(&absolute_path(path)?), &config::current().overrides)?.daemon,
+        None => config::current().daemon,
+    };
+    if let Some(path) = config.config_file.take() {
+        config.config_file = Some(absolute_path(&path)?);
+    }
+    let log_to_file = !foreground || logfile.is_some();
 
     if let Some(socket_path) = socket {
         config.socket_path = socket_path;
@@ -1411,18 +2202,50 @@ fn execute_daemon_start(
     if let Some(log_file) = logfile {
         config.log_file = log_file;
     }
+    if let Some(log_rotation) = log_rotation {
+        config.log_rotation = log_rotation;
+    }
+    if metrics_addr.is_some() {
+        config.metrics_addr = metrics_addr;
+    }
+    if let Some(limit) = metrics_actor_limit {
+        config.metrics_actor_limit = limit;
+    }
     config.foreground = foreground;
 
+    // A background daemon runs from `/`, so resolve paths against the current directory now
+    let file = absolute_path(&file)?;
+    config.socket_path = absolute_path(&config.socket_path)?;
+    config.pid_file = absolute_path(&config.pid_file)?;
+    config.log_file = absolute_path(&config.log_file)?;
+
     println!("{} Starting daemon with program: {}", "Info:".bright_blue().bold(), file.display());
     println!("  Socket: {}", config.socket_path.display());
     println!("  PID file: {}", config.pid_file.display());
     println!("  Log file: {}", config.log_file.display());
     println!("  Foreground: {}", foreground);
+    if let Some(addr) = config.metrics_addr {
+        println!("  Metrics: http://{}/metrics", addr);
+    }
 
     if debug {
         println!("{} Debug mode enabled", "Debug:".bright_yellow().bold());
     }
 
+    if !file.exists() {
+        return Err(ReamError::Other(format!("File not found: {}", file.display())));
+    }
+
+    // Detach before any runtime threads exist
+    if DaemonRuntime::daemonize(&config)? == Detached::Parent {
+        return wait_for_daemon(&config.pid_file, &config.log_file);
+    }
+
+    // A background daemon has no terminal, so its logs go to the log file
+    if log_to_file {
+        logging::log_to_file(&config.log_file, config.log_rotation)?;
+    }
+
     // Create and start daemon runtime
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
@@ -1433,11 +2256,30 @@ fn execute_daemon_start(
         println!("{} Daemon starting...", "Info:".bright_green().bold());
         daemon.start(file).await?;
 
-        println!("{} Daemon started successfully", "Success:".bright_green().bold());
+        println!("{} Daemon stopped", "Info:".bright_green().bold());
         Ok(())
     })
 }
 
+fn absolute_path(path: &Path) -> ReamResult<PathBuf> {
+    std::path::absolute(path).map_err(ReamError::Io)
+}
+
+/// Wait for a freshly detached daemon to claim its PID file
+fn wait_for_daemon(pid_file: &Path, log_file: &Path) -> ReamResult<()> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if let Some(pid) = PidFile::running(pid_file) {
+            println!("{} Daemon started in the background (PID {})", "Success:".bright_green().bold(), pid);
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    Err(ReamError::Other(format!(
+        "Daemon did not start; see {}", log_file.display()
+    )))
+}
+
 fn execute_daemon_stop(
     socket: PathBuf,
     pidfile: PathBuf,
@@ -1462,10 +2304,22 @@ fn execute_daemon_stop(
                 socket_path: socket,
                 pid_file: pidfile,
                 log_file: PathBuf::from("/tmp/ream-daemon.log"),
+                log_rotation: LogRotation::Daily,
                 foreground: false,
                 monitor_interval: Duration::from_millis(1000),
                 max_actors: 10000,
                 memory_limit: 64 * 1024 * 1024,
+                metrics_addr: None,
+                metrics_actor_limit: DEFAULT_ACTOR_SERIES_LIMIT,
+                alert_rules: Vec::new(),
+                dump_dir: PathBuf::from("/tmp/ream-dumps"),
+                security_policy: None,
+                secrets_file: None,
+                audit_log: None,
+                schedule_file: PathBuf::from("/tmp/ream-schedules.json"),
+                snapshot_dir: PathBuf::from("/tmp/ream-snapshots"),
+                workflow_store: PathBuf::from("/tmp/ream-workflows.db"),
+                config_file: None,
             };
 
             let daemon = DaemonRuntime::new(config)?;
@@ -1527,6 +2381,32 @@ fn execute_daemon_status(
                     println!("{} Failed to get system info: {}", "Warning:".bright_yellow().bold(), e);
                 }
             }
+
+            // Check registered databases
+            match client.get_database_health().await {
+                Ok(databases) => {
+                    for database in databases {
+                        let health = &database.health;
+                        let state = if health.healthy { "healthy".bright_green() } else { "unhealthy".bright_red() };
+                        println!(
+                            "  Database {}: {} ({:?}, {}/{} connections in use, {} idle, {} waiting)",
+                            database.name,
+                            state,
+                            health.latency,
+                            health.status.in_use,
+                            health.status.max_size,
+                            health.status.idle,
+                            health.status.waiting
+                        );
+                        if let Some(error) = &health.error {
+                            println!("    {}", error);
+                        }
+                    }
+                }
+                Err(e) => {
+                    println!("{} Failed to get database health: {}", "Warning:".bright_yellow().bold(), e);
+                }
+            }
         } else {
             println!("{} Daemon is not running", "Status:".bright_red().bold());
 
@@ -1553,17 +2433,24 @@ fn execute_daemon_restart(
     println!("{} Restarting daemon with program: {}", "Info:".bright_blue().bold(), file.display());
 
     // Use default config for missing paths
-    let config = DaemonConfig::default();
+    let config = config::current().daemon;
     let socket_path = socket.clone().unwrap_or(config.socket_path.clone());
     let pidfile_path = pidfile.clone().unwrap_or(config.pid_file.clone());
 
-    // First stop, then start
-    execute_daemon_stop(socket_path, pidfile_path, false, debug, verbose)?;
-    execute_daemon_start(file, socket, pidfile, logfile, false, debug, verbose)
+    // First stop, then start once the old daemon has released its PID file
+    execute_daemon_stop(socket_path, pidfile_path.clone(), false, debug, verbose)?;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while PidFile::running(&pidfile_path).is_some() {
+        if Instant::now() >= deadline {
+            return Err(ReamError::Other("Daemon did not stop in time".to_string()));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    execute_daemon_start(file, None, socket, pidfile, logfile, None, None, None, false, debug, verbose)
 }
 
 fn execute_actor_command(command: ActorCommand, debug: bool, verbose: bool) -> ReamResult<()> {
-    let config = DaemonConfig::default();
+    let config = config::current().daemon;
 
     match command {
         ActorCommand::List { socket, detailed } => {
@@ -1594,6 +2481,11 @@ fn execute_actor_command(command: ActorCommand, debug: bool, verbose: bool) -> R
             let socket_path = socket.unwrap_or(config.socket_path);
             execute_actor_send(pid, message, socket_path, debug, verbose)
         }
+        ActorCommand::Limit { pid, rate, per_sender, burst, policy, clear, socket } => {
+            let socket_path = socket.unwrap_or(config.socket_path);
+            let limit = (!clear).then_some(RateLimit { max_rate: rate, max_rate_per_sender: per_sender, burst, policy });
+            execute_actor_limit(pid, limit, socket_path)
+        }
     }
 }
 
@@ -1694,6 +2586,14 @@ fn execute_actor_info(pid: String, socket: PathBuf, debug: bool, verbose: bool)
                 println!("    Processed: {}", actor.messages_processed);
                 println!("    Rate: {:.2} msg/s", actor.message_rate);
                 println!();
+                if let Some(ingress) = &actor.ingress {
+                    println!("  Rate Limit: {}", describe_rate_limit(&ingress.limit));
+                    println!("    Admitted: {}", ingress.stats.admitted);
+                    println!("    Shed: {}", ingress.stats.shed);
+                    println!("    Rejected: {}", ingress.stats.rejected);
+                    println!("    Queued: {}", ingress.stats.queued);
+                    println!();
+                }
                 if !actor.links.is_empty() {
                     println!("  Links: {:?}", actor.links);
                 }
@@ -1713,6 +2613,17 @@ fn execute_actor_info(pid: String, socket: PathBuf, debug: bool, verbose: bool)
     })
 }
 
+fn execute_actor_limit(pid: String, limit: Option<RateLimit>, socket: PathBuf) -> ReamResult<()> {
+    if limit.as_ref().is_some_and(|limit| limit.max_rate.is_none() && limit.max_rate_per_sender.is_none()) {
+        return Err(ReamError::Other("Give --rate or --per-sender, or --clear to lift the limit".to_string()));
+    }
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let msg = rt.block_on(IpcClient::new(socket).set_rate_limit(pid, limit))?;
+    println!("{} {}", "Success:".bright_green().bold(), msg);
+    Ok(())
+}
+
 fn execute_actor_kill(pid: String, socket: PathBuf, reason: String, debug: bool, verbose: bool) -> ReamResult<()> {
     println!("{} Killing actor PID: {} with reason: {}", "Info:".bright_blue().bold(), pid, reason);
     println!("  Socket: {}", socket.display());
@@ -1829,6 +2740,990 @@ fn execute_actor_send(pid: String, message: String, socket: PathBuf, debug: bool
     })
 }
 
+fn print_domains(domains: &[DomainInfo]) {
+    if domains.is_empty() {
+        println!("{} No domains found", "Info:".bright_blue().bold());
+        return;
+    }
+    println!("{:<20} {:<10} {:<8} {:<12} {:<16}", "Name", "State", "Actors", "Memory", "Profile");
+    println!("{}", "-".repeat(70));
+    for domain in domains {
+        println!("{:<20} {:<10} {:<8} {:<12} {:<16}",
+            domain.name,
+            format!("{:?}", domain.state),
+            domain.actors,
+            format!("{}B", domain.memory_usage),
+            domain.config.profile.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+fn print_schemas(schemas: &[SchemaInfo]) {
+    if schemas.is_empty() {
+        println!("{} No schemas registered", "Info:".bright_blue().bold());
+        return;
+    }
+    let versions = |versions: &[u32]| {
+        if versions.is_empty() {
+            "-".to_string()
+        } else {
+            versions.iter().map(|version| format!("v{}", version)).collect::<Vec<_>>().join(",")
+        }
+    };
+    println!("{:<24} {:<10} {:<8} {:<8} {:<12} {:<12}", "Name", "Versions", "Current", "Wire", "Upgrades", "Downgrades");
+    println!("{}", "-".repeat(80));
+    for schema in schemas {
+        let registered: Vec<u32> = schema.versions.iter().map(|version| version.version).collect();
+        println!("{:<24} {:<10} {:<8} {:<8} {:<12} {:<12}",
+            schema.name,
+            versions(&registered),
+            format!("v{}", schema.current),
+            format!("v{}", schema.wire_version),
+            versions(&schema.upgrades),
+            versions(&schema.downgrades)
+        );
+    }
+}
+
+fn execute_daemon_schemas(socket: PathBuf, json: bool) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let schemas = rt.block_on(IpcClient::new(socket).list_schemas())?;
+    if json {
+        let json = serde_json::to_string_pretty(&schemas)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize schemas: {}", e)))?;
+        println!("{}", json);
+    } else {
+        print_schemas(&schemas);
+    }
+    Ok(())
+}
+
+fn execute_tree(socket: PathBuf, json: bool) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let tree = rt.block_on(IpcClient::new(socket).supervision_tree())?;
+    if json {
+        let json = serde_json::to_string_pretty(&tree)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize supervision tree: {}", e)))?;
+        println!("{}", json);
+    } else {
+        print!("{}", tree.render_ascii());
+    }
+    Ok(())
+}
+
+fn execute_domain_command(command: DomainCommand) -> ReamResult<()> {
+    let default_socket = config::current().daemon.socket_path;
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+
+    rt.block_on(async {
+        let result = match command {
+            DomainCommand::List { socket } => {
+                let client = IpcClient::new(socket.unwrap_or(default_socket));
+                match client.list_domains().await {
+                    Ok(domains) => {
+                        print_domains(&domains);
+                        return Ok(());
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            DomainCommand::Create { name, max_actors, max_memory, max_message_rate, profile, socket } => {
+                let config = DomainConfig {
+                    quotas: DomainQuotas { max_actors, max_memory, max_message_rate },
+                    profile,
+                };
+                IpcClient::new(socket.unwrap_or(default_socket)).create_domain(name, config).await
+            }
+            DomainCommand::Suspend { name, socket } => {
+                IpcClient::new(socket.unwrap_or(default_socket)).suspend_domain(name).await
+            }
+            DomainCommand::Resume { name, socket } => {
+                IpcClient::new(socket.unwrap_or(default_socket)).resume_domain(name).await
+            }
+            DomainCommand::Delete { name, socket } => {
+                IpcClient::new(socket.unwrap_or(default_socket)).delete_domain(name).await
+            }
+        };
+
+        match result {
+            Ok(msg) => {
+                println!("{} {}", "Success:".bright_green().bold(), msg);
+                Ok(())
+            }
+            Err(e) => {
+                println!("{} {}", "Error:".bright_red().bold(), e);
+                Err(e)
+            }
+        }
+    })
+}
+
+fn print_functions(functions: &[FunctionInfo]) {
+    if functions.is_empty() {
+        println!("{} No functions deployed", "Info:".bright_blue().bold());
+        return;
+    }
+    println!("{:<20} {:<24} {:<6} {:<10} {:<12} {:<12}", "Name", "Trigger", "Warm", "Running", "Invocations", "Cold starts");
+    println!("{}", "-".repeat(88));
+    for function in functions {
+        let trigger = match &function.trigger {
+            WakeTrigger::HttpRequest { path, method } => format!("{} {}", method, path),
+            _ => "message".to_string(),
+        };
+        println!("{:<20} {:<24} {:<6} {:<10} {:<12} {:<12}",
+            function.name,
+            trigger,
+            function.warm_instances,
+            format!("{}/{}", function.running, function.max_concurrency),
+            function.invocations,
+            function.cold_starts
+        );
+    }
+}
+
+fn execute_function_command(command: FunctionCommand) -> ReamResult<()> {
+    let default_socket = config::current().daemon.socket_path;
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+
+    rt.block_on(async {
+        let result = match command {
+            FunctionCommand::Deploy { name, bundle, http, method, idle_timeout, concurrency, socket } => {
+                let code = if bundle.extension().is_some_and(|ext| ext == "tl") {
+                    FunctionSource::Tlisp(fs::read_to_string(&bundle).map_err(ReamError::Io)?)
+                } else {
+                    let bytes = fs::read(&bundle).map_err(ReamError::Io)?;
+                    // Check the bundle here, so a bad file is reported before reaching the daemon
+                    BytecodeBundle::from_bytes(&bytes)?;
+                    FunctionSource::Bundle(bytes)
+                };
+                let trigger = match http {
+                    Some(path) => WakeTrigger::HttpRequest { path, method },
+                    None => WakeTrigger::IncomingMessage,
+                };
+                let options = DeployOptions {
+                    idle_timeout: idle_timeout.map(Duration::from_secs),
+                    max_concurrency: concurrency,
+                };
+                IpcClient::new(socket.unwrap_or(default_socket)).deploy_function(name, code, trigger, options).await
+            }
+            FunctionCommand::Invoke { name, payload, socket } => {
+                let client = IpcClient::new(socket.unwrap_or(default_socket));
+                match client.invoke_function(name, payload.into_bytes()).await {
+                    Ok(output) => {
+                        println!("{}", String::from_utf8_lossy(&output));
+                        return Ok(());
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            FunctionCommand::List { socket, json } => {
+                let client = IpcClient::new(socket.unwrap_or(default_socket));
+                match client.list_functions().await {
+                    Ok(functions) if json => {
+                        let json = serde_json::to_string_pretty(&functions)
+                            .map_err(|e| ReamError::Other(format!("Failed to serialize functions: {}", e)))?;
+                        println!("{}", json);
+                        return Ok(());
+                    }
+                    Ok(functions) => {
+                        print_functions(&functions);
+                        return Ok(());
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        };
+
+        match result {
+            Ok(msg) => {
+                println!("{} {}", "Success:".bright_green().bold(), msg);
+                Ok(())
+            }
+            Err(e) => {
+                println!("{} {}", "Error:".bright_red().bold(), e);
+                Err(e)
+            }
+        }
+    })
+}
+
+fn print_cron_jobs(jobs: &[CronJob]) {
+    if jobs.is_empty() {
+        println!("{} No jobs scheduled", "Info:".bright_blue().bold());
+        return;
+    }
+    let time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+        time.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_string())
+    };
+    println!("{:<16} {:<20} {:<20} {:<20} Action", "Name", "Schedule", "Next run (UTC)", "Last run (UTC)");
+    println!("{}", "-".repeat(100));
+    for job in jobs {
+        println!("{:<16} {:<20} {:<20} {:<20} {}", job.name, job.schedule, time(job.due_at), time(job.last_run), job.action);
+    }
+}
+
+fn execute_cron_command(command: CronCommand) -> ReamResult<()> {
+    let default_socket = config::current().daemon.socket_path;
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+
+    rt.block_on(async {
+        let result = match command {
+            CronCommand::Add { name, schedule, call, send, message, invoke, payload, jitter, missed, socket } => {
+                // clap requires exactly one action, and a message with --send
+                let action = match (call, send, invoke) {
+                    (Some(function), _, _) => JobAction::Call { function },
+                    (_, Some(actor), _) => JobAction::Send { actor, message: message.unwrap_or_default() },
+                    (_, _, Some(function)) => JobAction::Invoke { function, payload },
+                    (None, None, None) => return Err(ReamError::Other("No job action given".to_string())),
+                };
+                let mut job = CronJob::new(name, *schedule, action);
+                job.jitter = Duration::from_secs(jitter);
+                job.missed = missed;
+                IpcClient::new(socket.unwrap_or(default_socket)).add_cron_job(job).await
+            }
+            CronCommand::Remove { name, socket } => {
+                IpcClient::new(socket.unwrap_or(default_socket)).remove_cron_job(name).await
+            }
+            CronCommand::List { socket, json } => {
+                let client = IpcClient::new(socket.unwrap_or(default_socket));
+                match client.list_cron_jobs().await {
+                    Ok(jobs) if json => {
+                        let json = serde_json::to_string_pretty(&jobs)
+                            .map_err(|e| ReamError::Other(format!("Failed to serialize jobs: {}", e)))?;
+                        println!("{}", json);
+                        return Ok(());
+                    }
+                    Ok(jobs) => {
+                        print_cron_jobs(&jobs);
+                        return Ok(());
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        };
+
+        match result {
+            Ok(msg) => {
+                println!("{} {}", "Success:".bright_green().bold(), msg);
+                Ok(())
+            }
+            Err(e) => {
+                println!("{} {}", "Error:".bright_red().bold(), e);
+                Err(e)
+            }
+        }
+    })
+}
+
+fn print_workflows(workflows: &[WorkflowInfo]) {
+    if workflows.is_empty() {
+        println!("{} No workflows", "Info:".bright_blue().bold());
+        return;
+    }
+    let status = |workflow: &WorkflowInfo| workflow.status.map_or("-".to_string(), |status| status.to_string());
+    let time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+        time.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_string())
+    };
+    println!("{:<32} {:<16} {:<14} {:<8} {:<20} {:<20}", "ID", "Saga", "Status", "Steps", "Started (UTC)", "Updated (UTC)");
+    println!("{}", "-".repeat(115));
+    for workflow in workflows {
+        let completed = workflow.steps.iter().filter(|step| step.output.is_some()).count();
+        println!("{:<32} {:<16} {:<14} {:<8} {:<20} {:<20}",
+            workflow.id,
+            workflow.saga,
+            status(workflow),
+            format!("{}/{}", completed, workflow.steps.len()),
+            time(workflow.started_at),
+            time(workflow.updated_at));
+    }
+}
+
+fn print_workflow(workflow: &WorkflowInfo) {
+    let status = workflow.status.map_or("-".to_string(), |status| status.to_string());
+    println!("{} {} ({})", "Workflow:".bright_blue().bold(), workflow.id, workflow.saga);
+    println!("  Status: {}", status);
+    println!("  Input: {}", workflow.input);
+    if let Some(error) = &workflow.error {
+        println!("  Error: {}", error.bright_red());
+    }
+    println!();
+    println!("  {:<20} {:<20} {:>8}  Output / error", "Step", "Status", "Attempts");
+    for step in &workflow.steps {
+        let detail = match (&step.error, &step.output) {
+            (Some(error), _) => error.clone(),
+            (None, Some(output)) => output.to_string(),
+            (None, None) => String::new(),
+        };
+        println!("  {:<20} {:<20} {:>8}  {}", step.name, format!("{:?}", step.status).to_lowercase(), step.attempts, detail);
+    }
+}
+
+fn execute_daemon_reload(socket: PathBuf) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let message = rt.block_on(IpcClient::new(socket).reload_config())?;
+    println!("{} {}", "Success:".bright_green().bold(), message);
+    Ok(())
+}
+
+fn execute_config_command(command: ConfigCommand) -> ReamResult<()> {
+    match command {
+        ConfigCommand::Show { json } => {
+            let settings = config::current();
+            if json {
+                let text = serde_json::to_string_pretty(&settings)
+                    .map_err(|e| ReamError::Other(format!("Failed to serialize settings: {}", e)))?;
+                println!("{}", text);
+            } else {
+                let text = toml::to_string_pretty(&settings)
+                    .map_err(|e| ReamError::Other(format!("Failed to serialize settings: {}", e)))?;
+                match &settings.source {
+                    Some(path) => println!("# Loaded from {}", path.display()),
+                    None => println!("# No configuration file; defaults and overrides only"),
+                }
+                print!("{}", text);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn execute_workflow_command(command: WorkflowCommand) -> ReamResult<()> {
+    let default_socket = config::current().daemon.socket_path;
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+
+    rt.block_on(async {
+        let to_json = |value: serde_json::Result<String>| {
+            value.map_err(|e| ReamError::Other(format!("Failed to serialize workflows: {}", e)))
+        };
+        match command {
+            WorkflowCommand::List { socket, json } => {
+                let workflows = IpcClient::new(socket.unwrap_or(default_socket)).list_workflows().await?;
+                if json {
+                    println!("{}", to_json(serde_json::to_string_pretty(&workflows))?);
+                } else {
+                    print_workflows(&workflows);
+                }
+            }
+            WorkflowCommand::Inspect { id, socket, json } => {
+                let workflow = IpcClient::new(socket.unwrap_or(default_socket)).inspect_workflow(id).await?;
+                if json {
+                    println!("{}", to_json(serde_json::to_string_pretty(&workflow))?);
+                } else {
+                    print_workflow(&workflow);
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn execute_debug_dump(pid: String, socket: PathBuf, dir: Option<PathBuf>, json: bool) -> ReamResult<()> {
+    let dump = match dir {
+        Some(dir) => {
+            let parsed = crate::types::Pid::from_string(&pid)
+                .map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid)))?;
+            CrashDump::load(&dir, parsed)?
+        }
+        None => {
+            let rt = tokio::runtime::Runtime::new()
+                .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+            rt.block_on(IpcClient::new(socket).get_crash_dump(pid))?
+        }
+    };
+
+    if json {
+        let json = serde_json::to_string_pretty(&dump)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize crash dump: {}", e)))?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let crashed_at: chrono::DateTime<chrono::Local> = dump.crashed_at.into();
+    println!("{} {} crashed at {}", "Crash:".bright_red().bold(), dump.pid, crashed_at.format("%Y-%m-%d %H:%M:%S%.3f"));
+    println!("  Reason: {}", dump.reason);
+    println!();
+    println!("  Last messages (oldest first):");
+    if dump.recent_messages.is_empty() {
+        println!("    (none)");
+    }
+    for message in &dump.recent_messages {
+        println!("    {}", message);
+    }
+    if !dump.trace.is_empty() {
+        println!();
+        println!("  Trace (innermost first):");
+        for (depth, frame) in dump.trace.iter().enumerate() {
+            println!("    {:>2}: {}", depth, frame);
+        }
+    }
+    if !dump.dictionary.is_empty() {
+        println!();
+        println!("  Process dictionary:");
+        for (key, value) in &dump.dictionary {
+            println!("    {} = {}", key, value);
+        }
+    }
+    println!();
+    if let Some(parent) = dump.parent {
+        println!("  Parent: {}", parent);
+    }
+    if !dump.links.is_empty() {
+        println!("  Links: {:?}", dump.links);
+    }
+    if !dump.monitors.is_empty() {
+        println!("  Monitors: {:?}", dump.monitors);
+    }
+    println!("  Heap:");
+    println!("    Memory: {} bytes", dump.heap.memory_usage);
+    println!("    Mailbox: {} messages", dump.heap.mailbox_len);
+    println!("    Processed: {} messages", dump.heap.messages_processed);
+    println!("    CPU Time: {} μs", dump.heap.cpu_time);
+    println!("    Restarts: {}", dump.heap.restarts);
+    Ok(())
+}
+
+fn execute_audit_query(
+    since: Option<std::time::SystemTime>,
+    socket: PathBuf,
+    file: Option<PathBuf>,
+    json: bool,
+    output: Option<PathBuf>,
+) -> ReamResult<()> {
+    let entries = match file {
+        Some(file) => {
+            let mut entries = audit::read(&file)?;
+            entries.retain(|entry| since.is_none_or(|since| entry.timestamp >= since));
+            entries
+        }
+        None => {
+            let rt = tokio::runtime::Runtime::new()
+                .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+            rt.block_on(IpcClient::new(socket).query_audit(since))?
+        }
+    };
+
+    if json || output.is_some() {
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize audit entries: {}", e)))?;
+        match output {
+            Some(output) => {
+                fs::write(&output, json).map_err(ReamError::Io)?;
+                println!("{} Exported {} entries to {}", "Success:".bright_green().bold(), entries.len(), output.display());
+            }
+            None => println!("{}", json),
+        }
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No audit entries");
+    }
+    for entry in &entries {
+        let timestamp: chrono::DateTime<chrono::Local> = entry.timestamp.into();
+        println!("{:>6} {} {:<14} {:<24} {}",
+            entry.seq,
+            timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.kind.to_string().bright_yellow(),
+            entry.subject,
+            entry.detail);
+    }
+    Ok(())
+}
+
+fn execute_audit_verify(file: PathBuf) -> ReamResult<()> {
+    let entries = audit::read(&file)?;
+    match entries.last() {
+        Some(last) => println!("{} {} entries, chain intact up to {}", "Verified:".bright_green().bold(), entries.len(), last.hash),
+        None => println!("{} {} is empty", "Verified:".bright_green().bold(), file.display()),
+    }
+    Ok(())
+}
+
+fn execute_debug_record(pid: String, socket: PathBuf, limit: Option<usize>, stop: bool, output: Option<PathBuf>) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let client = IpcClient::new(socket);
+
+    if !stop {
+        let msg = rt.block_on(client.start_recording(pid, limit))?;
+        println!("{} {}", "Success:".bright_green().bold(), msg);
+        return Ok(());
+    }
+
+    let recording = rt.block_on(client.stop_recording(pid.clone()))?;
+    println!("{} Stopped recording actor {} ({} messages)", "Success:".bright_green().bold(), pid, recording.len());
+    if let Some(output) = output {
+        let json = serde_json::to_vec_pretty(&recording)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize recording: {}", e)))?;
+        fs::write(&output, json).map_err(ReamError::Io)?;
+        println!("  Saved to {}", output.display());
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_profile_heap(
+    pid: String,
+    socket: PathBuf,
+    duration: Duration,
+    interval: Option<usize>,
+    top: usize,
+    output: Option<PathBuf>,
+    json: bool,
+) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let client = IpcClient::new(socket);
+
+    let msg = rt.block_on(client.start_heap_profile(pid.clone(), interval))?;
+    wait_for_profile(&rt, &msg, duration, json);
+    let profile = rt.block_on(client.stop_heap_profile(pid))?;
+
+    if let Some(output) = &output {
+        fs::write(output, profile.folded()).map_err(ReamError::Io)?;
+    }
+    if json {
+        let json = serde_json::to_string_pretty(&profile)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize heap profile: {}", e)))?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("{} actor {} over {:.1}s", "Heap profile:".bright_blue().bold(), profile.pid, profile.duration.as_secs_f64());
+    println!("  Allocations: {} ({} bytes)", profile.allocations, profile.bytes);
+    println!("  Live at end: ~{} bytes, sampled every {} bytes", profile.live_bytes(), profile.interval);
+    if profile.sites.is_empty() {
+        println!("  {}", "No allocations were sampled".bright_yellow());
+    } else {
+        println!("\n  {:>12} {:>12} {:>8}  {:<10} SITE", "BYTES", "LIVE", "SAMPLES", "KIND");
+        for site in profile.sites.iter().take(top) {
+            let location = site.site.as_deref().unwrap_or("(standard library)");
+            println!("  {:>12} {:>12} {:>8}  {:<10} {}", site.bytes, site.live_bytes, site.samples, site.kind, location);
+        }
+        if profile.sites.len() > top {
+            println!("  ... {} more sites", profile.sites.len() - top);
+        }
+    }
+    if let Some(output) = output {
+        println!("\n  Folded stacks saved to {} (render with flamegraph.pl or inferno-flamegraph)", output.display());
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_profile_cpu(
+    pid: String,
+    socket: PathBuf,
+    duration: Duration,
+    frequency: Option<u32>,
+    top: usize,
+    output: Option<PathBuf>,
+    json: bool,
+) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let client = IpcClient::new(socket);
+
+    let msg = rt.block_on(client.start_cpu_profile(pid.clone(), frequency))?;
+    wait_for_profile(&rt, &msg, duration, json);
+    let profile = rt.block_on(client.stop_cpu_profile(pid))?;
+
+    if let Some(output) = &output {
+        fs::write(output, profile.folded()).map_err(ReamError::Io)?;
+    }
+    if json {
+        let json = serde_json::to_string_pretty(&profile)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize CPU profile: {}", e)))?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("{} actor {} over {:.1}s", "CPU profile:".bright_blue().bold(), profile.pid, profile.duration.as_secs_f64());
+    println!("  Samples: {} running of {} taken at {} Hz (~{:.2}s of CPU)",
+        profile.samples, profile.ticks, profile.frequency, profile.time(profile.samples).as_secs_f64());
+    if profile.functions.is_empty() {
+        println!("  {}", "The actor did not run while it was sampled".bright_yellow());
+    } else {
+        println!("\n  {:>8} {:>7} {:>8} {:>7}  FUNCTION", "SELF", "SELF%", "TOTAL", "TOTAL%");
+        let percent = |samples: u64| samples as f64 * 100.0 / profile.samples as f64;
+        for function in profile.functions.iter().take(top) {
+            println!("  {:>8} {:>6.1}% {:>8} {:>6.1}%  {}",
+                function.self_samples, percent(function.self_samples),
+                function.total_samples, percent(function.total_samples),
+                function.name);
+        }
+        if profile.functions.len() > top {
+            println!("  ... {} more functions", profile.functions.len() - top);
+        }
+    }
+    if let Some(output) = output {
+        println!("\n  Folded stacks saved to {} (render with flamegraph.pl or inferno-flamegraph)", output.display());
+    }
+    Ok(())
+}
+
+fn execute_trace_command(command: TraceCommand) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let socket_of = |socket: Option<PathBuf>| socket.unwrap_or(config::current().daemon.socket_path);
+
+    match command {
+        TraceCommand::Start { socket, limit } => {
+            let msg = rt.block_on(IpcClient::new(socket_of(socket)).start_flow_recording(limit))?;
+            println!("{} {}", "Success:".bright_green().bold(), msg);
+        }
+        TraceCommand::Stop { socket } => {
+            let msg = rt.block_on(IpcClient::new(socket_of(socket)).stop_flow_recording())?;
+            println!("{} {}", "Success:".bright_green().bold(), msg);
+        }
+        TraceCommand::Export { socket, format, pids, message_types, since, until, output } => {
+            let pids = pids.iter()
+                .map(|pid| crate::types::Pid::from_string(pid).map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid))))
+                .collect::<ReamResult<Vec<_>>>()?;
+            let filter = FlowFilter { pids, message_types, since, until };
+            let events = rt.block_on(IpcClient::new(socket_of(socket)).flows(filter))?;
+
+            let rendered = match format {
+                FlowFormat::Mermaid => flow::to_mermaid(&events),
+                FlowFormat::Dot => flow::to_dot(&events),
+                FlowFormat::Json => serde_json::to_string_pretty(&events)
+                    .map_err(|e| ReamError::Other(format!("Failed to serialize message flows: {}", e)))?,
+            };
+            match output {
+                Some(output) => {
+                    fs::write(&output, rendered).map_err(ReamError::Io)?;
+                    println!("{} Exported {} messages to {}", "Success:".bright_green().bold(), events.len(), output.display());
+                }
+                None => println!("{}", rendered.trim_end()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Let a profile started with `msg` run for `duration`, or until Ctrl-C
+fn wait_for_profile(rt: &tokio::runtime::Runtime, msg: &str, duration: Duration, json: bool) {
+    if !json {
+        println!("{} {}", "Success:".bright_green().bold(), msg);
+        println!("  Sampling for {:?}, press Ctrl-C to stop early", duration);
+    }
+    rt.block_on(async {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    });
+}
+
+fn execute_debug_replay(pid: Option<String>, socket: PathBuf, file: Option<PathBuf>) -> ReamResult<()> {
+    let recording: Recording = match (file, pid) {
+        (Some(file), _) => {
+            let json = fs::read(&file).map_err(ReamError::Io)?;
+            serde_json::from_slice(&json)
+                .map_err(|e| ReamError::Other(format!("Invalid recording {}: {}", file.display(), e)))?
+        }
+        (None, Some(pid)) => {
+            let rt = tokio::runtime::Runtime::new()
+                .map_err(|e| ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+            rt.block_on(IpcClient::new(socket).get_recording(pid))?
+        }
+        (None, None) => return Err(ReamError::Other("Give a PID or a recording file".to_string())),
+    };
+
+    println!("{} {} messages recorded for {}", "Replay:".bright_blue().bold(), recording.len(), recording.pid);
+    if recording.dropped > 0 {
+        println!("  {} older messages were dropped by the limit of {}", recording.dropped, recording.limit);
+    }
+    println!("  n: next  p: previous  g N: go to step N  s: state  l: list  q: quit");
+
+    let mut replay = Replay::new(recording);
+    print_replay_step(&replay);
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("replay [{}/{}]> ", replay.position(), replay.recording().len());
+        std::io::Write::flush(&mut std::io::stdout()).map_err(ReamError::Io)?;
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line).map_err(ReamError::Io)? == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        match words.next().unwrap_or("n") {
+            "n" | "next" => {
+                if replay.forward().is_none() {
+                    println!("  At the last message");
+                    continue;
+                }
+            }
+            "p" | "prev" => {
+                if !replay.back() {
+                    println!("  At the start of the recording");
+                    continue;
+                }
+            }
+            "g" | "goto" => match words.next().and_then(|step| step.parse().ok()) {
+                Some(step) => replay.seek(step),
+                None => {
+                    println!("  Usage: g <step>");
+                    continue;
+                }
+            },
+            "s" | "state" => {}
+            "l" | "list" => {
+                for (step, delivery) in replay.recording().deliveries.iter().enumerate() {
+                    let marker = if step + 1 == replay.position() { ">" } else { " " };
+                    let status = if delivery.error.is_some() { " (failed)".red().to_string() } else { String::new() };
+                    println!("  {} {:>4}: {}{}", marker, step + 1, delivery.message.summary(), status);
+                }
+                continue;
+            }
+            "q" | "quit" => break,
+            other => {
+                println!("  Unknown command: {}", other);
+                continue;
+            }
+        }
+        print_replay_step(&replay);
+    }
+    Ok(())
+}
+
+fn print_replay_step(replay: &Replay) {
+    match replay.current() {
+        Some(delivery) => {
+            let delivered_at: chrono::DateTime<chrono::Local> = delivery.delivered_at.into();
+            println!("{} Step {} of {} (message #{}, {})",
+                "→".bright_cyan(), replay.position(), replay.recording().len(), delivery.index,
+                delivered_at.format("%H:%M:%S%.3f"));
+            println!("  Message: {}", delivery.message.summary());
+            if let Some(error) = replay.error() {
+                println!("  {} {}", "Failed:".bright_red().bold(), error);
+            }
+        }
+        None => println!("{} Start of recording", "→".bright_cyan()),
+    }
+    let state = replay.state();
+    if state.is_empty() {
+        println!("  State: (empty)");
+    } else {
+        println!("  State:");
+        for (key, value) in &state {
+            println!("    {} = {}", key, value);
+        }
+    }
+}
+
+fn execute_debug_journal(persistence_id: Option<String>, store: PathBuf, after: u64, json: bool) -> ReamResult<()> {
+    if !store.exists() {
+        return Err(ReamError::Other(format!("No event store at {}", store.display())));
+    }
+    let events = SqliteEventStore::open(&store)?;
+
+    let Some(persistence_id) = persistence_id else {
+        let journals = events.journals()?;
+        if json {
+            let json = serde_json::to_string_pretty(&journals)
+                .map_err(|e| ReamError::Other(format!("Failed to serialize journals: {}", e)))?;
+            println!("{}", json);
+            return Ok(());
+        }
+        if journals.is_empty() {
+            println!("No journals in {}", store.display());
+            return Ok(());
+        }
+        println!("{:<40} {:>10} {:>10}", "PERSISTENCE ID", "EVENTS", "SNAPSHOT");
+        for journal in &journals {
+            let snapshot = journal.snapshot_sequence.map_or("-".to_string(), |sequence| sequence.to_string());
+            println!("{:<40} {:>10} {:>10}", journal.persistence_id, journal.highest_sequence, snapshot);
+        }
+        return Ok(());
+    };
+
+    let snapshot = events.latest_snapshot(&persistence_id)?;
+    let entries = events.events(&persistence_id, after)?;
+    if json {
+        let json = serde_json::to_string_pretty(&serde_json::json!({
+            "persistence_id": persistence_id,
+            "snapshot": snapshot,
+            "events": entries,
+        }))
+        .map_err(|e| ReamError::Other(format!("Failed to serialize journal: {}", e)))?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("{} {} ({} events shown)", "Journal:".bright_blue().bold(), persistence_id, entries.len());
+    if let Some(snapshot) = &snapshot {
+        let taken_at: chrono::DateTime<chrono::Local> = snapshot.taken_at.into();
+        println!("  Snapshot at event {} taken {}: {}", snapshot.sequence, taken_at.format("%Y-%m-%d %H:%M:%S"), snapshot.state);
+    }
+    for entry in &entries {
+        let recorded_at: chrono::DateTime<chrono::Local> = entry.recorded_at.into();
+        println!("{:>8} {} {}", entry.sequence, recorded_at.format("%Y-%m-%d %H:%M:%S%.3f"), entry.event);
+    }
+    Ok(())
+}
+
+fn execute_debug_bytecode(file: PathBuf, breakpoints: Vec<String>) -> ReamResult<()> {
+    let program = load_program(&file)?;
+
+    let mut debugger = Debugger::new(program);
+    for location in &breakpoints {
+        let breakpoint: Breakpoint = location.parse().map_err(|e| ReamError::Other(format!("{}", e)))?;
+        debugger.add_breakpoint(breakpoint).map_err(|e| ReamError::Other(format!("{}", e)))?;
+    }
+
+    println!("{} {} ({} instructions, {} functions)", "Debugging:".bright_blue().bold(),
+        file.display(), debugger.program().instructions.len(), debugger.program().functions.len());
+    println!("  s: step  n: next  o: out  c: continue  b/d LOC: break/delete  w/u EXPR: watch/unwatch");
+    println!("  stack  locals  bt  l: list  r: restart  q: quit");
+    print_debugger_position(&debugger);
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("debug [{}]> ", debugger.pc());
+        std::io::Write::flush(&mut std::io::stdout()).map_err(ReamError::Io)?;
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line).map_err(ReamError::Io)? == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("s");
+        let argument = words.collect::<Vec<_>>().join(" ");
+        let reason = match command {
+            "s" | "step" => debugger.step(),
+            "n" | "next" => debugger.step_over(),
+            "o" | "out" => debugger.step_out(),
+            "c" | "continue" => debugger.resume(),
+            "b" | "break" | "d" | "delete" | "w" | "watch" | "u" | "unwatch" if argument.is_empty() => {
+                println!("  Usage: {} <location or expression>", command);
+                continue;
+            }
+            "b" | "break" => {
+                match argument.parse().and_then(|breakpoint| debugger.add_breakpoint(breakpoint)) {
+                    Ok(()) => println!("  Breakpoint at {}", argument),
+                    Err(e) => println!("  {}", e),
+                }
+                continue;
+            }
+            "d" | "delete" => {
+                match argument.parse::<Breakpoint>() {
+                    Ok(breakpoint) if debugger.remove_breakpoint(&breakpoint) => println!("  Deleted breakpoint at {}", breakpoint),
+                    _ => println!("  No breakpoint at {}", argument),
+                }
+                continue;
+            }
+            "w" | "watch" => {
+                match argument.parse::<Watch>() {
+                    Ok(watch) => {
+                        println!("  Watching {}", watch);
+                        debugger.watch(watch);
+                    }
+                    Err(e) => println!("  {}", e),
+                }
+                continue;
+            }
+            "u" | "unwatch" => {
+                match argument.parse::<Watch>() {
+                    Ok(watch) if debugger.unwatch(&watch) => println!("  No longer watching {}", watch),
+                    _ => println!("  Not watching {}", argument),
+                }
+                continue;
+            }
+            "stack" => {
+                if debugger.stack().is_empty() {
+                    println!("  (empty)");
+                }
+                for (depth, value) in debugger.stack().iter().rev().enumerate() {
+                    println!("  {:>4}: {}", depth, value);
+                }
+                continue;
+            }
+            "locals" => {
+                if debugger.locals().is_empty() {
+                    println!("  (none)");
+                }
+                for (index, value) in debugger.locals().iter().enumerate() {
+                    println!("  {:>4}: {}", index, value);
+                }
+                continue;
+            }
+            "bt" | "backtrace" => {
+                let name = |pc: usize| debugger.function_at(pc).map(|f| f.name.clone()).unwrap_or_else(|| "<main>".to_string());
+                println!("  #0 {} at {}", name(debugger.pc()), debugger.pc());
+                for (level, frame) in debugger.call_stack().iter().rev().enumerate() {
+                    let caller = frame.return_pc.saturating_sub(1);
+                    println!("  #{} {} at {}", level + 1, name(caller), caller);
+                }
+                continue;
+            }
+            "l" | "list" => {
+                let pc = debugger.pc();
+                let instructions = &debugger.program().instructions;
+                let start = pc.saturating_sub(5);
+                for (index, instruction) in instructions.iter().enumerate().skip(start).take(11) {
+                    let marker = if index == pc { ">" } else { " " };
+                    println!("  {} {:>4}: {:?}", marker, index, instruction);
+                }
+                continue;
+            }
+            "r" | "restart" => {
+                debugger.restart();
+                print_debugger_position(&debugger);
+                continue;
+            }
+            "q" | "quit" => break,
+            other => {
+                println!("  Unknown command: {}", other);
+                continue;
+            }
+        };
+
+        match reason {
+            StopReason::Step => {}
+            StopReason::Breakpoint(pc) => println!("{} Breakpoint at {}", "●".bright_red(), pc),
+            StopReason::WatchChanged { watch, old, new } => {
+                println!("{} {} changed: {} -> {}", "●".bright_yellow(), watch, watched_value(old), watched_value(new));
+            }
+            StopReason::Finished(value) => {
+                println!("{} Program finished: {}", "✓".bright_green(), value);
+                continue;
+            }
+            StopReason::Error(error) => {
+                println!("{} {}", "Failed:".bright_red().bold(), error);
+                continue;
+            }
+        }
+        print_debugger_position(&debugger);
+    }
+    Ok(())
+}
+
+fn print_debugger_position(debugger: &Debugger) {
+    let pc = debugger.pc();
+    let function = debugger.function_at(pc).map(|f| f.name.as_str()).unwrap_or("<main>");
+    match debugger.current_instruction() {
+        Some(instruction) => println!("{} {:>4}: {:?}  [{}]", "→".bright_cyan(), pc, instruction, function),
+        None => println!("{} {:>4}: (end of program)", "→".bright_cyan(), pc),
+    }
+    for (watch, value) in debugger.watches() {
+        println!("  {} = {}", watch, watched_value(value));
+    }
+}
+
+fn watched_value(value: Option<crate::bytecode::Value>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
 fn execute_monitor(socket: PathBuf, interval: u64, actor: Option<String>, debug: bool, verbose: bool) -> ReamResult<()> {
     println!("{} Starting TUI monitor", "Info:".bright_blue().bold());
     println!("  Socket: {}", socket.display());
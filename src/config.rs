@@ -0,0 +1,413 @@
+//! Runtime configuration file
+//!
+//! Settings come from `ream.toml`, environment variables and command line
+//! flags, in increasing order of precedence:
+//!
+//! 1. Built-in defaults
+//! 2. The file given with `--config-file`, else `$REAM_CONFIG`, else
+//!    `./ream.toml` if it exists
+//! 3. `REAM_<SECTION>_<KEY>` environment variables, e.g.
+//!    `REAM_RUNTIME_GC_THRESHOLD=1048576`; `REAM_LOG` sets `log.filter`
+//! 4. `--set section.key=value` flags
+//!
+//! ```toml
+//! [log]
+//! filter = "info,ream::daemon=debug"
+//!
+//! [runtime]
+//! gc_threshold = 134217728
+//!
+//! [daemon]
+//! max_actors = 5000
+//! ```
+//!
+//! A file with none of these sections is read as a plain daemon config.
+//! Log filters, GC thresholds and daemon alert rules are reloaded on SIGHUP
+//! or `ream daemon reload`; most other changes need a restart.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::daemon::DaemonConfig;
+use crate::error::{ReamError, ReamResult};
+use crate::logging::{self, LogConfig, LOG_ENV};
+use crate::types::ReamConfig;
+
+/// Configuration file looked for in the working directory
+pub const CONFIG_FILE: &str = "ream.toml";
+
+/// Environment variable naming the configuration file
+pub const CONFIG_ENV: &str = "REAM_CONFIG";
+
+/// Prefix of environment variables overriding single settings
+pub const ENV_PREFIX: &str = "REAM_";
+
+/// Sections of the configuration file
+const SECTIONS: [&str; 3] = ["log", "runtime", "daemon"];
+
+/// Settings in effect for this process
+static CURRENT: RwLock<Option<ReamSettings>> = RwLock::new(None);
+
+/// Every setting, as layered from defaults, file, environment and flags
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReamSettings {
+    /// Logging
+    pub log: LogConfig,
+    /// Actor runtime
+    pub runtime: ReamConfig,
+    /// Daemon
+    pub daemon: DaemonConfig,
+    /// File the settings were loaded from
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    /// `section.key=value` overrides given on the command line
+    #[serde(skip)]
+    pub overrides: Vec<String>,
+}
+
+impl ReamSettings {
+    /// Load settings with every layer, reading the process environment
+    ///
+    /// Without a `file`, `$REAM_CONFIG` and then `./ream.toml` are tried.
+    pub fn load(file: Option<&Path>, overrides: &[String]) -> ReamResult<Self> {
+        Self::load_with_env(file, overrides, std::env::vars())
+    }
+
+    /// Load settings with every layer, taking variables from `env`
+    pub fn load_with_env(
+        file: Option<&Path>,
+        overrides: &[String],
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> ReamResult<Self> {
+        let env: BTreeMap<String, String> = env.into_iter().collect();
+        let source = match file {
+            Some(path) => Some(path.to_path_buf()),
+            None => env.get(CONFIG_ENV).filter(|path| !path.is_empty()).map(PathBuf::from)
+                .or_else(|| Some(PathBuf::from(CONFIG_FILE)).filter(|path| path.exists())),
+        };
+
+        let defaults = serde_json::to_value(ReamSettings::default())
+            .map_err(|e| ReamError::Other(format!("Failed to serialize default settings: {}", e)))?;
+        let mut layered = toml::Table::new();
+
+        if let Some(path) = &source {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| ReamError::Other(format!("Failed to read config file {}: {}", path.display(), e)))?;
+            let mut table: toml::Table = toml::from_str(&content)
+                .map_err(|e| ReamError::Other(format!("Invalid config file {}: {}", path.display(), e)))?;
+            // A plain daemon config without sections
+            if !table.is_empty() && !SECTIONS.iter().any(|section| table.contains_key(*section)) {
+                table = toml::Table::from_iter([("daemon".to_string(), toml::Value::Table(table))]);
+            }
+            let mut unknown = Vec::new();
+            unknown_keys(&table, &defaults, "", &mut unknown);
+            if !unknown.is_empty() {
+                return Err(ReamError::Other(format!(
+                    "Invalid config file {}:\n  - {}", path.display(), unknown.join("\n  - ")
+                )));
+            }
+            layered = table;
+        }
+
+        // Environment variables for every scalar setting
+        if let Some(filter) = env.get(LOG_ENV) {
+            set_setting(&mut layered, &defaults, "log.filter", filter, LOG_ENV)?;
+        }
+        for section in SECTIONS {
+            let Some(serde_json::Value::Object(keys)) = defaults.get(section) else { continue };
+            for (key, default) in keys {
+                if default.is_object() || default.is_array() {
+                    continue;
+                }
+                let name = format!("{}{}_{}", ENV_PREFIX, section, key).to_uppercase();
+                if let Some(raw) = env.get(&name) {
+                    set_setting(&mut layered, &defaults, &format!("{}.{}", section, key), raw, &name)?;
+                }
+            }
+        }
+
+        for assignment in overrides {
+            let (key, raw) = assignment.split_once('=').ok_or_else(|| ReamError::Other(format!(
+                "Invalid --set '{}': expected section.key=value", assignment
+            )))?;
+            set_setting(&mut layered, &defaults, key.trim(), raw.trim(), "--set")?;
+        }
+
+        let mut settings: ReamSettings = toml::Value::Table(layered).try_into()
+            .map_err(|e| ReamError::Other(format!("Invalid configuration: {}", e)))?;
+        if let Some(path) = &source {
+            settings.daemon.config_file = Some(path.clone());
+        }
+        settings.source = source;
+        settings.overrides = overrides.to_vec();
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Check values the types alone do not constrain, reporting every
+    /// problem at once
+    pub fn validate(&self) -> ReamResult<()> {
+        let mut problems = Vec::new();
+        if let Err(e) = logging::parse_filter(&self.log.filter) {
+            problems.push(format!("log.filter: {}", e));
+        }
+        let positive = [
+            ("runtime.max_processes", self.runtime.max_processes as u64),
+            ("runtime.scheduler_quantum", self.runtime.scheduler_quantum),
+            ("runtime.max_messages_per_quantum", self.runtime.max_messages_per_quantum as u64),
+            ("runtime.mailbox_batch_size", self.runtime.mailbox_batch_size as u64),
+            ("runtime.max_message_queue_size", self.runtime.max_message_queue_size as u64),
+            ("runtime.gc_threshold", self.runtime.gc_threshold as u64),
+            ("daemon.max_actors", self.daemon.max_actors as u64),
+            ("daemon.monitor_interval", self.daemon.monitor_interval.as_millis() as u64),
+        ];
+        for (name, value) in positive {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", name));
+            }
+        }
+        if self.runtime.jit_opt_level > 3 {
+            problems.push(format!("runtime.jit_opt_level must be between 0 and 3, got {}", self.runtime.jit_opt_level));
+        }
+        if !(0.0..=1.0).contains(&self.runtime.tracing.sample_ratio) {
+            problems.push(format!(
+                "runtime.tracing.sample_ratio must be between 0.0 and 1.0, got {}", self.runtime.tracing.sample_ratio
+            ));
+        }
+        let cpus = crate::runtime::topology::CpuTopology::system().cpus();
+        for core in self.runtime.topology.cores.iter().filter(|core| !cpus.contains(core)) {
+            problems.push(format!("runtime.topology.cores: no core {} on this machine", core));
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let origin = self.source.as_ref().map(|path| format!(" ({})", path.display())).unwrap_or_default();
+        Err(ReamError::Other(format!("Invalid configuration{}:\n  - {}", origin, problems.join("\n  - "))))
+    }
+
+    /// Apply the settings that changed between two loads, leaving the ones
+    /// the reload did not change (such as command line overrides) alone.
+    /// Returns the names of changed settings that only take effect after a
+    /// restart.
+    pub fn reload(&mut self, previous: &ReamSettings, loaded: &ReamSettings) -> Vec<&'static str> {
+        if loaded.log.filter != previous.log.filter {
+            self.log.filter = loaded.log.filter.clone();
+        }
+        if loaded.runtime.gc_threshold != previous.runtime.gc_threshold {
+            self.runtime.gc_threshold = loaded.runtime.gc_threshold;
+        }
+
+        let mut needs_restart = Vec::new();
+        if loaded.log.format != previous.log.format {
+            needs_restart.push("log.format");
+        }
+        let runtime = [
+            ("runtime.max_processes", loaded.runtime.max_processes != previous.runtime.max_processes),
+            ("runtime.scheduler_quantum", loaded.runtime.scheduler_quantum != previous.runtime.scheduler_quantum),
+            ("runtime.max_messages_per_quantum", loaded.runtime.max_messages_per_quantum != previous.runtime.max_messages_per_quantum),
+            ("runtime.mailbox_batch_size", loaded.runtime.mailbox_batch_size != previous.runtime.mailbox_batch_size),
+            ("runtime.max_message_queue_size", loaded.runtime.max_message_queue_size != previous.runtime.max_message_queue_size),
+            ("runtime.enable_jit", loaded.runtime.enable_jit != previous.runtime.enable_jit),
+            ("runtime.jit_opt_level", loaded.runtime.jit_opt_level != previous.runtime.jit_opt_level),
+            ("runtime.topology", loaded.runtime.topology != previous.runtime.topology),
+        ];
+        needs_restart.extend(runtime.into_iter().filter(|(_, changed)| *changed).map(|(name, _)| name));
+        needs_restart.extend(self.daemon.reload(&previous.daemon, &loaded.daemon));
+        needs_restart
+    }
+}
+
+/// Install the settings in effect for this process
+pub fn install(settings: ReamSettings) {
+    *CURRENT.write().unwrap() = Some(settings);
+}
+
+/// Settings in effect for this process, or the defaults if none were
+/// installed
+pub fn current() -> ReamSettings {
+    CURRENT.read().unwrap().clone().unwrap_or_default()
+}
+
+/// Collect `section.key` paths of `table` that the defaults do not have
+fn unknown_keys(table: &toml::Table, defaults: &serde_json::Value, prefix: &str, unknown: &mut Vec<String>) {
+    let Some(known) = defaults.as_object() else { return };
+    for (key, value) in table {
+        let path = format!("{}{}", prefix, key);
+        match known.get(key) {
+            Some(default @ serde_json::Value::Object(_)) => {
+                if let toml::Value::Table(nested) = value {
+                    unknown_keys(nested, default, &format!("{}.", path), unknown);
+                }
+            }
+            Some(_) => {}
+            None => unknown.push(format!("unknown setting `{}`{}", path, suggest(key, known, prefix))),
+        }
+    }
+}
+
+/// A " (did you mean ...?)" hint for the known key closest to `key`
+fn suggest(key: &str, known: &serde_json::Map<String, serde_json::Value>, prefix: &str) -> String {
+    known.keys()
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, candidate)| format!(" (did you mean `{}{}`?)", prefix, candidate))
+        .unwrap_or_default()
+}
+
+/// Set `section.key` from a raw string, parsed as a TOML value when it is
+/// one and checked against the type of the default
+fn set_setting(
+    layered: &mut toml::Table,
+    defaults: &serde_json::Value,
+    key: &str,
+    raw: &str,
+    origin: &str,
+) -> ReamResult<()> {
+    let (section, name) = key.split_once('.').ok_or_else(|| ReamError::Other(format!(
+        "{}: `{}` is not a setting; expected section.key", origin, key
+    )))?;
+    let default = defaults.get(section).and_then(|s| s.get(name)).ok_or_else(|| {
+        let hint = match defaults.get(section).and_then(|s| s.as_object()) {
+            Some(known) => suggest(name, known, &format!("{}.", section)),
+            None => defaults.as_object().map(|known| suggest(section, known, "")).unwrap_or_default(),
+        };
+        ReamError::Other(format!("{}: unknown setting `{}`{}", origin, key, hint))
+    })?;
+
+    let parsed = toml::from_str::<toml::Table>(&format!("v = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("v"));
+    let value = match (default, parsed) {
+        (serde_json::Value::String(_), _) => toml::Value::String(raw.to_string()),
+        (serde_json::Value::Bool(_), Some(value @ toml::Value::Boolean(_))) => value,
+        (serde_json::Value::Number(n), Some(value @ toml::Value::Integer(_))) if !n.is_f64() => value,
+        (serde_json::Value::Number(_), Some(toml::Value::Integer(i))) => toml::Value::Float(i as f64),
+        (serde_json::Value::Number(n), Some(value @ toml::Value::Float(_))) if n.is_f64() => value,
+        (serde_json::Value::Null, Some(value)) => value,
+        (serde_json::Value::Null, None) => toml::Value::String(raw.to_string()),
+        (default, _) => {
+            let expected = match default {
+                serde_json::Value::Bool(_) => "true or false",
+                serde_json::Value::Number(n) if n.is_f64() => "a number",
+                serde_json::Value::Number(_) => "an integer",
+                _ => "a table",
+            };
+            return Err(ReamError::Other(format!(
+                "{}: `{}` expects {}, got '{}'", origin, key, expected, raw
+            )));
+        }
+    };
+
+    let table = layered
+        .entry(section.to_string())
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    match table {
+        toml::Value::Table(table) => {
+            table.insert(name.to_string(), value);
+            Ok(())
+        }
+        _ => Err(ReamError::Other(format!("`{}` must be a table", section))),
+    }
+}
+
+/// Levenshtein distance, for suggesting the setting a typo meant
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_layer_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, r#"
+[log]
+filter = "info"
+
+[runtime]
+gc_threshold = 1000
+max_processes = 10
+
+[daemon]
+max_actors = 50
+"#).unwrap();
+
+        let settings = ReamSettings::load_with_env(
+            Some(&path),
+            &["runtime.max_processes=30".to_string()],
+            env(&[("REAM_RUNTIME_GC_THRESHOLD", "2000"), ("REAM_RUNTIME_MAX_PROCESSES", "20"), ("REAM_LOG", "debug")]),
+        ).unwrap();
+        assert_eq!(settings.log.filter, "debug");
+        assert_eq!(settings.runtime.gc_threshold, 2000);
+        assert_eq!(settings.runtime.max_processes, 30);
+        assert_eq!(settings.daemon.max_actors, 50);
+        // Untouched settings keep their defaults
+        assert_eq!(settings.runtime.jit_opt_level, ReamConfig::default().jit_opt_level);
+        assert_eq!(settings.daemon.config_file.as_deref(), Some(path.as_path()));
+
+        // A plain daemon config is read as the daemon section
+        std::fs::write(&path, "max_actors = 7\n").unwrap();
+        let settings = ReamSettings::load_with_env(Some(&path), &[], env(&[])).unwrap();
+        assert_eq!(settings.daemon.max_actors, 7);
+    }
+
+    #[test]
+    fn test_helpful_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "[runtime]\ngc_treshold = 10\n").unwrap();
+        let error = ReamSettings::load_with_env(Some(&path), &[], env(&[])).unwrap_err().to_string();
+        assert!(error.contains("unknown setting `runtime.gc_treshold` (did you mean `runtime.gc_threshold`?)"), "{}", error);
+
+        std::fs::write(&path, "[runtime]\ngc_threshold = 0\njit_opt_level = 9\n").unwrap();
+        let error = ReamSettings::load_with_env(Some(&path), &[], env(&[])).unwrap_err().to_string();
+        assert!(error.contains("runtime.gc_threshold must be greater than 0"), "{}", error);
+        assert!(error.contains("runtime.jit_opt_level must be between 0 and 3"), "{}", error);
+
+        let error = ReamSettings::load_with_env(None, &[], env(&[("REAM_RUNTIME_ENABLE_JIT", "maybe")]))
+            .unwrap_err().to_string();
+        assert!(error.contains("REAM_RUNTIME_ENABLE_JIT: `runtime.enable_jit` expects true or false"), "{}", error);
+        assert!(ReamSettings::load_with_env(None, &["nosection=1".to_string()], env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_reload_applies_reloadable_settings() {
+        let previous = ReamSettings::default();
+        let mut loaded = previous.clone();
+        loaded.log.filter = "debug".to_string();
+        loaded.runtime.gc_threshold = 1024;
+        loaded.runtime.max_processes = 5;
+
+        let mut current = previous.clone();
+        current.daemon.max_actors = 99;
+        let needs_restart = current.reload(&previous, &loaded);
+        assert_eq!(current.log.filter, "debug");
+        assert_eq!(current.runtime.gc_threshold, 1024);
+        assert_eq!(current.runtime.max_processes, previous.runtime.max_processes);
+        assert_eq!(current.daemon.max_actors, 99);
+        assert_eq!(needs_restart, vec!["runtime.max_processes"]);
+    }
+}
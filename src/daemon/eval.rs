@@ -0,0 +1,125 @@
+//! Code evaluation for attached REPLs
+//!
+//! TLisp runtimes are not `Send`, so the daemon keeps them on a dedicated
+//! thread and hands it evaluation jobs. The main context is the runtime the
+//! daemon program was loaded into; each actor context starts from a copy of
+//! the main context's bindings with `*actor*` bound to the actor's pid,
+//! and its builtins are checked against the actor's capabilities.
+
+use std::collections::HashMap;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use serde::{Serialize, Deserialize};
+use tokio::sync::oneshot;
+
+use crate::error::{ReamError, ReamResult};
+use crate::security::policy;
+use crate::tlisp::{capture_output, TlispRuntime, Value};
+use crate::types::Pid;
+
+/// Variable bound to the actor's pid in an actor context
+pub const ACTOR_VAR: &str = "*actor*";
+
+/// Result of evaluating code in the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalResult {
+    /// Output printed while evaluating
+    pub output: String,
+    /// Printed result value, or the evaluation error
+    pub value: Result<String, String>,
+}
+
+/// A pending evaluation
+struct EvalJob {
+    code: String,
+    actor: Option<Pid>,
+    reply: oneshot::Sender<EvalResult>,
+}
+
+/// Handle to the thread that owns the daemon's TLisp runtimes
+pub struct EvalService {
+    jobs: Mutex<mpsc::Sender<EvalJob>>,
+}
+
+impl EvalService {
+    /// Start the evaluation thread; it exits when the service is dropped
+    pub fn new() -> Self {
+        let (jobs, receiver) = mpsc::channel();
+        thread::spawn(move || Self::run(receiver));
+        EvalService { jobs: Mutex::new(jobs) }
+    }
+
+    /// Evaluate `code` in the main context or an actor's context
+    pub async fn eval(&self, code: &str, actor: Option<Pid>) -> ReamResult<EvalResult> {
+        let (reply, result) = oneshot::channel();
+        let job = EvalJob { code: code.to_string(), actor, reply };
+        self.jobs.lock().unwrap().send(job)
+            .map_err(|_| ReamError::Other("Evaluation thread has stopped".to_string()))?;
+
+        result.await
+            .map_err(|_| ReamError::Other("Evaluation thread dropped the request".to_string()))
+    }
+
+    /// Evaluation loop, run on the service thread
+    fn run(jobs: mpsc::Receiver<EvalJob>) {
+        let mut main = TlispRuntime::new();
+        let mut actors: HashMap<Pid, TlispRuntime> = HashMap::new();
+
+        for job in jobs {
+            let (result, output) = match job.actor {
+                None => capture_output(|| main.eval(&job.code)),
+                Some(pid) => {
+                    let runtime = actors.entry(pid).or_insert_with(|| Self::actor_context(&main, pid));
+                    // Builtins run with the actor's capabilities
+                    policy::as_process(pid, || capture_output(|| runtime.eval(&job.code)))
+                }
+            };
+            let value = result.map(|value| value.to_string()).map_err(|e| e.to_string());
+            let _ = job.reply.send(EvalResult { output, value });
+        }
+    }
+
+    /// New actor context seeded with the main context's bindings
+    fn actor_context(main: &TlispRuntime, pid: Pid) -> TlispRuntime {
+        let mut runtime = TlispRuntime::new();
+        for name in main.symbols() {
+            if let Some(value) = main.get(&name) {
+                runtime.define(&name, value);
+            }
+        }
+        runtime.define(ACTOR_VAR, Value::Pid(pid));
+        runtime
+    }
+}
+
+impl Default for EvalService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_eval_contexts() {
+        let service = EvalService::new();
+
+        let result = service.eval("(define greeting \"hi\")", None).await.unwrap();
+        assert!(result.value.is_ok());
+
+        let result = service.eval("(println greeting)", None).await.unwrap();
+        assert_eq!(result.output, "hi\n");
+
+        let pid = Pid::new();
+        let result = service.eval("(define local 1) greeting", Some(pid)).await.unwrap();
+        assert_eq!(result.value, Ok("\"hi\"".to_string()));
+        let result = service.eval(ACTOR_VAR, Some(pid)).await.unwrap();
+        assert_eq!(result.value, Ok(Value::Pid(pid).to_string()));
+
+        // Actor contexts do not leak into the main context
+        let result = service.eval("local", None).await.unwrap();
+        assert!(result.value.is_err());
+    }
+}
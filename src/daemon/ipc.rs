@@ -1,186 +1,255 @@
 //! Inter-process communication for daemon mode
 //!
-//! Provides platform-specific IPC communication between daemon and client processes.
+//! The daemon serves `DaemonMessage` requests over a Unix domain socket
+//! (a named pipe on Windows). Each request and response is a JSON document
+//! preceded by its length as a 4-byte big-endian integer, so a client can
+//! send any number of requests over one connection. Every connection is
+//! served on its own task.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
 
-use std::path::PathBuf;
 use crate::error::{ReamResult, ReamError};
-use super::{DaemonMessage, DaemonResponse, DaemonManager};
+use crate::runtime::crash::CrashDump;
+use crate::runtime::{DomainConfig, DomainInfo, RateLimit, SupervisionNode};
+use crate::runtime::cron::CronJob;
+use crate::runtime::schema::SchemaInfo;
+use crate::runtime::workflow::WorkflowInfo;
+use crate::runtime::serverless::WakeTrigger;
+use crate::runtime::serverless_runtime::{DeployOptions, FunctionInfo};
+use crate::debug::profiler::HeapProfile;
+use crate::debug::cpu_profiler::CpuProfile;
+use crate::debug::flow::{FlowEvent, FlowFilter};
+use crate::debug::replay::Recording;
+use crate::security::AuditEntry;
+use super::{DaemonMessage, DaemonResponse, DaemonManager, FunctionSource};
+use super::eval::EvalResult;
+use super::monitor::{Alert, AlertRule};
+
+/// Largest request or response accepted, in bytes
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Read one length-prefixed frame, or `None` if the peer closed the connection
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> ReamResult<Option<Vec<u8>>> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(ReamError::Io(e)),
+    }
 
-#[cfg(unix)]
-pub use unix_impl::*;
+    let len = u32::from_be_bytes(header) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(ReamError::Other(format!(
+            "IPC frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_SIZE
+        )));
+    }
 
-#[cfg(not(unix))]
-pub use windows_impl::*;
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame).await.map_err(ReamError::Io)?;
+    Ok(Some(frame))
+}
 
-#[cfg(unix)]
-mod unix_impl {
-    use super::*;
-    use std::io::{Read, Write};
-    use std::os::unix::net::{UnixListener, UnixStream};
-    use serde_json;
+/// Write one length-prefixed frame
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> ReamResult<()> {
+    if frame.len() > MAX_FRAME_SIZE {
+        return Err(ReamError::Other(format!(
+            "IPC frame of {} bytes exceeds the {} byte limit", frame.len(), MAX_FRAME_SIZE
+        )));
+    }
+
+    writer.write_all(&(frame.len() as u32).to_be_bytes()).await.map_err(ReamError::Io)?;
+    writer.write_all(frame).await.map_err(ReamError::Io)?;
+    writer.flush().await.map_err(ReamError::Io)
+}
 
 /// IPC server for daemon communication
 pub struct IpcServer {
-    /// Socket path
+    /// Socket path (the pipe name on Windows is derived from it)
     socket_path: PathBuf,
-    /// Unix domain socket listener
-    listener: Option<UnixListener>,
     /// Daemon manager reference
-    daemon: std::sync::Arc<DaemonManager>,
+    daemon: Arc<DaemonManager>,
+    /// Wakes the accept loop when the server stops
+    shutdown: Arc<Notify>,
+    /// Accept loop task
+    task: Option<JoinHandle<()>>,
 }
 
 impl IpcServer {
     /// Create a new IPC server
-    pub fn new(socket_path: PathBuf, daemon: std::sync::Arc<DaemonManager>) -> Self {
+    pub fn new(socket_path: PathBuf, daemon: Arc<DaemonManager>) -> Self {
         IpcServer {
             socket_path,
-            listener: None,
             daemon,
+            shutdown: Arc::new(Notify::new()),
+            task: None,
         }
     }
-    
-    /// Start the IPC server
+
+    /// Bind the socket and start accepting connections in the background
     pub async fn start(&mut self) -> ReamResult<()> {
-        // Remove existing socket file if it exists
-        if self.socket_path.exists() {
-            std::fs::remove_file(&self.socket_path)
-                .map_err(|e| ReamError::Io(e))?;
-        }
-        
-        // Create Unix domain socket listener
-        let listener = UnixListener::bind(&self.socket_path)
-            .map_err(|e| ReamError::Io(e))?;
-        
-        self.listener = Some(listener);
-        
-        // Start accepting connections
-        self.accept_connections().await
-    }
-    
-    /// Accept and handle incoming connections
-    async fn accept_connections(&self) -> ReamResult<()> {
-        let listener = self.listener.as_ref()
-            .ok_or_else(|| ReamError::Other("Listener not initialized".to_string()))?;
-        
-        loop {
-            match listener.accept() {
-                Ok((stream, _addr)) => {
-                    let daemon = self.daemon.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::handle_client(stream, daemon).await {
-                            eprintln!("Error handling client: {}", e);
+        if self.task.is_some() {
+            return Err(ReamError::Other("IPC server is already running".to_string()));
+        }
+
+        let mut listener = platform::bind(&self.socket_path)?;
+        let daemon = self.daemon.clone();
+        let shutdown = self.shutdown.clone();
+
+        self.task = Some(tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok(stream) => {
+                            let daemon = daemon.clone();
+                            tokio::spawn(async move {
+                                if let Err(e) = serve_connection(stream, daemon).await {
+                                    tracing::warn!(error = %e, "Error handling IPC client");
+                                }
+                            });
                         }
-                    });
-                }
-                Err(e) => {
-                    eprintln!("Error accepting connection: {}", e);
-                    break;
+                        Err(e) => {
+                            tracing::error!(error = %e, "Error accepting IPC connection");
+                            break;
+                        }
+                    },
+                    _ = shutdown.notified() => break,
                 }
             }
-        }
-        
+        }));
+
+        tracing::info!(socket = %self.socket_path.display(), "IPC server listening");
         Ok(())
     }
-    
-    /// Handle a client connection
-    async fn handle_client(
-        mut stream: UnixStream,
-        daemon: std::sync::Arc<DaemonManager>,
-    ) -> ReamResult<()> {
-        let mut buffer = String::new();
-        stream.read_to_string(&mut buffer)
-            .map_err(|e| ReamError::Io(e))?;
-        
-        // Parse the message
-        let message: DaemonMessage = serde_json::from_str(&buffer)
-            .map_err(|e| ReamError::Other(format!("Failed to parse message: {}", e)))?;
-        
-        // Process the message
-        let response = Self::process_message(message, &daemon).await?;
-        
-        // Send response
-        let response_json = serde_json::to_string(&response)
+
+    /// Stop accepting connections and remove the socket
+    pub fn stop(&mut self) -> ReamResult<()> {
+        if self.task.take().is_some() {
+            self.shutdown.notify_one();
+        }
+        platform::cleanup(&self.socket_path)
+    }
+}
+
+/// Serve requests from one client until it disconnects
+async fn serve_connection<S>(mut stream: S, daemon: Arc<DaemonManager>) -> ReamResult<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    while let Some(frame) = read_frame(&mut stream).await? {
+        let response = match serde_json::from_slice::<DaemonMessage>(&frame) {
+            Ok(message) => process_message(message, &daemon).await,
+            Err(e) => DaemonResponse::Error(format!("Failed to parse message: {}", e)),
+        };
+
+        let response_json = serde_json::to_vec(&response)
             .map_err(|e| ReamError::Other(format!("Failed to serialize response: {}", e)))?;
-        
-        stream.write_all(response_json.as_bytes())
-            .map_err(|e| ReamError::Io(e))?;
-        
-        Ok(())
+        write_frame(&mut stream, &response_json).await?;
     }
-    
-    /// Process a daemon message and return response
-    async fn process_message(
-        message: DaemonMessage,
-        daemon: &DaemonManager,
-    ) -> ReamResult<DaemonResponse> {
-        match message {
-            DaemonMessage::GetSystemInfo => {
-                let info = daemon.get_system_info();
-                Ok(DaemonResponse::SystemInfo(info))
-            }
-            DaemonMessage::ListActors { detailed } => {
-                let actors = daemon.list_actors(detailed);
-                Ok(DaemonResponse::ActorList(actors))
-            }
-            DaemonMessage::GetActorInfo { pid } => {
-                match daemon.get_actor_info(&pid) {
-                    Ok(info) => Ok(DaemonResponse::ActorInfo(info)),
-                    Err(e) => Ok(DaemonResponse::Error(e.to_string())),
-                }
-            }
-            DaemonMessage::KillActor { pid, reason } => {
-                match daemon.kill_actor(&pid, &reason) {
-                    Ok(msg) => Ok(DaemonResponse::Success(msg)),
-                    Err(e) => Ok(DaemonResponse::Error(e.to_string())),
-                }
-            }
-            DaemonMessage::SuspendActor { pid } => {
-                match daemon.suspend_actor(&pid) {
-                    Ok(msg) => Ok(DaemonResponse::Success(msg)),
-                    Err(e) => Ok(DaemonResponse::Error(e.to_string())),
-                }
-            }
-            DaemonMessage::ResumeActor { pid } => {
-                match daemon.resume_actor(&pid) {
-                    Ok(msg) => Ok(DaemonResponse::Success(msg)),
-                    Err(e) => Ok(DaemonResponse::Error(e.to_string())),
-                }
-            }
-            DaemonMessage::RestartActor { pid } => {
-                match daemon.restart_actor(&pid) {
-                    Ok(msg) => Ok(DaemonResponse::Success(msg)),
-                    Err(e) => Ok(DaemonResponse::Error(e.to_string())),
-                }
-            }
-            DaemonMessage::SendMessage { pid, message } => {
-                match daemon.send_message(&pid, &message) {
-                    Ok(msg) => Ok(DaemonResponse::Success(msg)),
-                    Err(e) => Ok(DaemonResponse::Error(e.to_string())),
-                }
-            }
-            DaemonMessage::Shutdown => {
-                // TODO: Implement daemon shutdown
-                Ok(DaemonResponse::Success("Shutdown initiated".to_string()))
-            }
-            DaemonMessage::Ping => {
-                Ok(DaemonResponse::Pong)
-            }
+
+    Ok(())
+}
+
+/// Process a daemon message and return response
+async fn process_message(message: DaemonMessage, daemon: &DaemonManager) -> DaemonResponse {
+    fn reply(result: ReamResult<String>) -> DaemonResponse {
+        match result {
+            Ok(msg) => DaemonResponse::Success(msg),
+            Err(e) => DaemonResponse::Error(e.to_string()),
         }
     }
-    
-    /// Stop the IPC server
-    pub fn stop(&mut self) -> ReamResult<()> {
-        if let Some(_listener) = self.listener.take() {
-            // Listener will be dropped and closed
-        }
-        
-        // Remove socket file
-        if self.socket_path.exists() {
-            std::fs::remove_file(&self.socket_path)
-                .map_err(|e| ReamError::Io(e))?;
-        }
-        
-        Ok(())
+
+    match message {
+        DaemonMessage::GetSystemInfo => DaemonResponse::SystemInfo(daemon.get_system_info()),
+        DaemonMessage::ListActors { detailed } => DaemonResponse::ActorList(daemon.list_actors(detailed)),
+        DaemonMessage::GetActorInfo { pid } => match daemon.get_actor_info(&pid) {
+            Ok(info) => DaemonResponse::ActorInfo(info),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::KillActor { pid, reason } => reply(daemon.kill_actor(&pid, &reason)),
+        DaemonMessage::SuspendActor { pid } => reply(daemon.suspend_actor(&pid)),
+        DaemonMessage::ResumeActor { pid } => reply(daemon.resume_actor(&pid)),
+        DaemonMessage::RestartActor { pid } => reply(daemon.restart_actor(&pid)),
+        DaemonMessage::SetRateLimit { pid, limit } => reply(daemon.set_rate_limit(&pid, limit)),
+        DaemonMessage::SendMessage { pid, message } => reply(daemon.send_message(&pid, &message)),
+        DaemonMessage::GetDatabaseHealth => DaemonResponse::DatabaseHealth(daemon.database_health().await),
+        DaemonMessage::Eval { code, actor } => match daemon.eval(&code, actor.as_deref()).await {
+            Ok(result) => DaemonResponse::Evaluated(result),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::ListAlertRules => DaemonResponse::AlertRules(daemon.config().alert_rules),
+        DaemonMessage::SetAlertRule { rule } => DaemonResponse::Success(daemon.set_alert_rule(rule)),
+        DaemonMessage::RemoveAlertRule { name } => reply(daemon.remove_alert_rule(&name)),
+        DaemonMessage::GetAlerts { after } => DaemonResponse::Alerts(daemon.alerts(after)),
+        DaemonMessage::GetCrashDump { pid } => match daemon.crash_dump(&pid) {
+            Ok(dump) => DaemonResponse::CrashDump(Box::new(dump)),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::StartRecording { pid, limit } => reply(daemon.start_recording(&pid, limit)),
+        DaemonMessage::StopRecording { pid } => match daemon.stop_recording(&pid) {
+            Ok(recording) => DaemonResponse::Recording(Box::new(recording)),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::GetRecording { pid } => match daemon.recording(&pid) {
+            Ok(recording) => DaemonResponse::Recording(Box::new(recording)),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::StartHeapProfile { pid, interval } => reply(daemon.start_heap_profile(&pid, interval)),
+        DaemonMessage::StopHeapProfile { pid } => match daemon.stop_heap_profile(&pid) {
+            Ok(profile) => DaemonResponse::HeapProfile(Box::new(profile)),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::StartCpuProfile { pid, frequency } => reply(daemon.start_cpu_profile(&pid, frequency)),
+        DaemonMessage::StopCpuProfile { pid } => match daemon.stop_cpu_profile(&pid) {
+            Ok(profile) => DaemonResponse::CpuProfile(Box::new(profile)),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::StartFlowRecording { limit } => DaemonResponse::Success(daemon.start_flow_recording(limit)),
+        DaemonMessage::StopFlowRecording => DaemonResponse::Success(daemon.stop_flow_recording()),
+        DaemonMessage::GetFlows { filter } => DaemonResponse::Flows(daemon.flows(&filter)),
+        DaemonMessage::GetSupervisionTree => DaemonResponse::SupervisionTree(daemon.supervision_tree()),
+        DaemonMessage::ListDomains => DaemonResponse::Domains(daemon.list_domains()),
+        DaemonMessage::CreateDomain { name, config } => reply(daemon.create_domain(&name, config)),
+        DaemonMessage::SuspendDomain { name } => reply(daemon.suspend_domain(&name)),
+        DaemonMessage::ResumeDomain { name } => reply(daemon.resume_domain(&name)),
+        DaemonMessage::DeleteDomain { name } => reply(daemon.delete_domain(&name)),
+        DaemonMessage::QueryAudit { since } => match daemon.query_audit(since) {
+            Ok(entries) => DaemonResponse::AuditEntries(entries),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::DeployFunction { name, code, trigger, options } => {
+            reply(daemon.deploy_function(&name, code, trigger, options))
+        }
+        DaemonMessage::InvokeFunction { name, payload } => match daemon.invoke_function(&name, payload) {
+            Ok(output) => DaemonResponse::Invoked(output),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::ListFunctions => DaemonResponse::Functions(daemon.list_functions()),
+        DaemonMessage::AddCronJob { job } => reply(daemon.add_cron_job(job)),
+        DaemonMessage::RemoveCronJob { name } => reply(daemon.remove_cron_job(&name)),
+        DaemonMessage::ListCronJobs => DaemonResponse::CronJobs(daemon.list_cron_jobs()),
+        DaemonMessage::ListSchemas => DaemonResponse::Schemas(daemon.list_schemas()),
+        DaemonMessage::ListWorkflows => match daemon.list_workflows() {
+            Ok(workflows) => DaemonResponse::Workflows(workflows),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::InspectWorkflow { id } => match daemon.inspect_workflow(&id) {
+            Ok(workflow) => DaemonResponse::Workflow(Box::new(workflow)),
+            Err(e) => DaemonResponse::Error(e.to_string()),
+        },
+        DaemonMessage::Reload => {
+            daemon.request_reload();
+            DaemonResponse::Success("Configuration reload requested".to_string())
+        }
+        DaemonMessage::Shutdown => {
+            daemon.request_shutdown();
+            DaemonResponse::Success("Shutdown initiated".to_string())
+        }
+        DaemonMessage::Ping => DaemonResponse::Pong,
     }
 }
 
@@ -195,40 +264,27 @@ impl IpcClient {
     pub fn new(socket_path: PathBuf) -> Self {
         IpcClient { socket_path }
     }
-    
+
     /// Send a message to the daemon and get response
     pub async fn send_message(&self, message: DaemonMessage) -> ReamResult<DaemonResponse> {
-        // Connect to daemon socket
-        let mut stream = UnixStream::connect(&self.socket_path)
-            .map_err(|e| ReamError::Io(e))?;
-        
-        // Serialize and send message
-        let message_json = serde_json::to_string(&message)
+        let mut stream = platform::connect(&self.socket_path).await?;
+
+        let message_json = serde_json::to_vec(&message)
             .map_err(|e| ReamError::Other(format!("Failed to serialize message: {}", e)))?;
-        
-        stream.write_all(message_json.as_bytes())
-            .map_err(|e| ReamError::Io(e))?;
-        
-        // Read response
-        let mut buffer = String::new();
-        stream.read_to_string(&mut buffer)
-            .map_err(|e| ReamError::Io(e))?;
-        
-        // Parse response
-        let response: DaemonResponse = serde_json::from_str(&buffer)
-            .map_err(|e| ReamError::Other(format!("Failed to parse response: {}", e)))?;
-        
-        Ok(response)
-    }
-    
+        write_frame(&mut stream, &message_json).await?;
+
+        let frame = read_frame(&mut stream).await?
+            .ok_or_else(|| ReamError::Other("Daemon closed the connection without responding".to_string()))?;
+
+        serde_json::from_slice(&frame)
+            .map_err(|e| ReamError::Other(format!("Failed to parse response: {}", e)))
+    }
+
     /// Check if daemon is running
     pub async fn is_daemon_running(&self) -> bool {
-        match self.send_message(DaemonMessage::Ping).await {
-            Ok(DaemonResponse::Pong) => true,
-            _ => false,
-        }
+        matches!(self.send_message(DaemonMessage::Ping).await, Ok(DaemonResponse::Pong))
     }
-    
+
     /// Get system information from daemon
     pub async fn get_system_info(&self) -> ReamResult<crate::daemon::SystemInfo> {
         match self.send_message(DaemonMessage::GetSystemInfo).await? {
@@ -237,7 +293,7 @@ impl IpcClient {
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-    
+
     /// List actors from daemon
     pub async fn list_actors(&self, detailed: bool) -> ReamResult<Vec<crate::daemon::ActorInfo>> {
         match self.send_message(DaemonMessage::ListActors { detailed }).await? {
@@ -246,7 +302,7 @@ impl IpcClient {
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-    
+
     /// Get actor information from daemon
     pub async fn get_actor_info(&self, pid: String) -> ReamResult<crate::daemon::ActorInfo> {
         match self.send_message(DaemonMessage::GetActorInfo { pid }).await? {
@@ -255,175 +311,453 @@ impl IpcClient {
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-    
-    /// Kill an actor
-    pub async fn kill_actor(&self, pid: String, reason: String) -> ReamResult<String> {
-        match self.send_message(DaemonMessage::KillActor { pid, reason }).await? {
-            DaemonResponse::Success(msg) => Ok(msg),
+
+    /// Check the databases registered with the daemon
+    pub async fn get_database_health(&self) -> ReamResult<Vec<crate::daemon::DatabaseHealth>> {
+        match self.send_message(DaemonMessage::GetDatabaseHealth).await? {
+            DaemonResponse::DatabaseHealth(health) => Ok(health),
             DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-    
-    /// Suspend an actor
-    pub async fn suspend_actor(&self, pid: String) -> ReamResult<String> {
-        match self.send_message(DaemonMessage::SuspendActor { pid }).await? {
-            DaemonResponse::Success(msg) => Ok(msg),
+
+    /// Evaluate TLisp code in the daemon, optionally in an actor's context
+    pub async fn eval(&self, code: String, actor: Option<String>) -> ReamResult<EvalResult> {
+        match self.send_message(DaemonMessage::Eval { code, actor }).await? {
+            DaemonResponse::Evaluated(result) => Ok(result),
             DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-    
+
+    /// Kill an actor
+    pub async fn kill_actor(&self, pid: String, reason: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::KillActor { pid, reason }).await
+    }
+
+    /// Suspend an actor
+    pub async fn suspend_actor(&self, pid: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::SuspendActor { pid }).await
+    }
+
     /// Resume an actor
     pub async fn resume_actor(&self, pid: String) -> ReamResult<String> {
-        match self.send_message(DaemonMessage::ResumeActor { pid }).await? {
-            DaemonResponse::Success(msg) => Ok(msg),
-            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
-            _ => Err(ReamError::Other("Unexpected response".to_string())),
-        }
+        self.expect_success(DaemonMessage::ResumeActor { pid }).await
     }
-    
+
     /// Restart an actor
     pub async fn restart_actor(&self, pid: String) -> ReamResult<String> {
-        match self.send_message(DaemonMessage::RestartActor { pid }).await? {
-            DaemonResponse::Success(msg) => Ok(msg),
+        self.expect_success(DaemonMessage::RestartActor { pid }).await
+    }
+
+    /// Limit the messages an actor accepts; `None` lifts the limit
+    pub async fn set_rate_limit(&self, pid: String, limit: Option<RateLimit>) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::SetRateLimit { pid, limit }).await
+    }
+
+    /// Send message to an actor
+    pub async fn send_actor_message(&self, pid: String, message: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::SendMessage { pid, message }).await
+    }
+
+    /// List the alerting rules
+    pub async fn list_alert_rules(&self) -> ReamResult<Vec<AlertRule>> {
+        match self.send_message(DaemonMessage::ListAlertRules).await? {
+            DaemonResponse::AlertRules(rules) => Ok(rules),
             DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-    
-    /// Send message to an actor
-    pub async fn send_actor_message(&self, pid: String, message: String) -> ReamResult<String> {
-        match self.send_message(DaemonMessage::SendMessage { pid, message }).await? {
-            DaemonResponse::Success(msg) => Ok(msg),
+
+    /// Add an alerting rule, replacing any rule with the same name
+    pub async fn set_alert_rule(&self, rule: AlertRule) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::SetAlertRule { rule }).await
+    }
+
+    /// Remove an alerting rule
+    pub async fn remove_alert_rule(&self, name: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::RemoveAlertRule { name }).await
+    }
+
+    /// Get notified alerts with an id greater than `after`
+    pub async fn get_alerts(&self, after: u64) -> ReamResult<Vec<Alert>> {
+        match self.send_message(DaemonMessage::GetAlerts { after }).await? {
+            DaemonResponse::Alerts(alerts) => Ok(alerts),
             DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-    
-    /// Shutdown daemon
-    pub async fn shutdown_daemon(&self) -> ReamResult<String> {
-        match self.send_message(DaemonMessage::Shutdown).await? {
-            DaemonResponse::Success(msg) => Ok(msg),
+
+    /// Get the crash dump of an actor
+    pub async fn get_crash_dump(&self, pid: String) -> ReamResult<CrashDump> {
+        match self.send_message(DaemonMessage::GetCrashDump { pid }).await? {
+            DaemonResponse::CrashDump(dump) => Ok(*dump),
             DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
             _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
-}
 
-} // End of unix_impl module
+    /// Start recording the messages delivered to an actor
+    pub async fn start_recording(&self, pid: String, limit: Option<usize>) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::StartRecording { pid, limit }).await
+    }
 
-#[cfg(not(unix))]
-mod windows_impl {
-    use super::*;
-    use super::super::{SystemInfo, ActorInfo, DaemonConfig};
+    /// Stop recording an actor, returning the recording
+    pub async fn stop_recording(&self, pid: String) -> ReamResult<Recording> {
+        self.expect_recording(DaemonMessage::StopRecording { pid }).await
+    }
 
-    /// IPC server for daemon communication (Windows stub)
-    pub struct IpcServer {
-        socket_path: PathBuf,
-        daemon: std::sync::Arc<DaemonManager>,
+    /// Get the recording of an actor without stopping it
+    pub async fn get_recording(&self, pid: String) -> ReamResult<Recording> {
+        self.expect_recording(DaemonMessage::GetRecording { pid }).await
     }
 
-    impl IpcServer {
-        pub fn new(socket_path: PathBuf, daemon: std::sync::Arc<DaemonManager>) -> Self {
-            IpcServer { socket_path, daemon }
+    /// Start sampling the allocations of an actor
+    pub async fn start_heap_profile(&self, pid: String, interval: Option<usize>) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::StartHeapProfile { pid, interval }).await
+    }
+
+    /// Stop sampling an actor, returning its heap profile
+    pub async fn stop_heap_profile(&self, pid: String) -> ReamResult<HeapProfile> {
+        match self.send_message(DaemonMessage::StopHeapProfile { pid }).await? {
+            DaemonResponse::HeapProfile(profile) => Ok(*profile),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
+
+    /// Start sampling the TLisp functions an actor runs
+    pub async fn start_cpu_profile(&self, pid: String, frequency: Option<u32>) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::StartCpuProfile { pid, frequency }).await
+    }
 
-        pub async fn start(&mut self) -> ReamResult<()> {
-            Err(ReamError::NotImplemented("IPC server not implemented on Windows".to_string()))
+    /// Stop sampling an actor, returning its CPU profile
+    pub async fn stop_cpu_profile(&self, pid: String) -> ReamResult<CpuProfile> {
+        match self.send_message(DaemonMessage::StopCpuProfile { pid }).await? {
+            DaemonResponse::CpuProfile(profile) => Ok(*profile),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
 
-        pub fn stop(&mut self) -> ReamResult<()> {
-            Ok(())
+    /// Start recording the messages actors send each other
+    pub async fn start_flow_recording(&self, limit: Option<usize>) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::StartFlowRecording { limit }).await
+    }
+
+    /// Stop recording messages, keeping those recorded
+    pub async fn stop_flow_recording(&self) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::StopFlowRecording).await
+    }
+
+    /// Get the recorded messages passing a filter
+    pub async fn flows(&self, filter: FlowFilter) -> ReamResult<Vec<FlowEvent>> {
+        match self.send_message(DaemonMessage::GetFlows { filter }).await? {
+            DaemonResponse::Flows(events) => Ok(events),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
     }
 
-    /// IPC client for communicating with daemon (Windows stub)
-    pub struct IpcClient {
-        socket_path: PathBuf,
+    /// Get the full supervision hierarchy
+    pub async fn supervision_tree(&self) -> ReamResult<SupervisionNode> {
+        match self.send_message(DaemonMessage::GetSupervisionTree).await? {
+            DaemonResponse::SupervisionTree(tree) => Ok(tree),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
+        }
     }
 
-    impl IpcClient {
-        pub fn new(socket_path: PathBuf) -> Self {
-            IpcClient { socket_path }
+    /// List the isolation domains
+    pub async fn list_domains(&self) -> ReamResult<Vec<DomainInfo>> {
+        match self.send_message(DaemonMessage::ListDomains).await? {
+            DaemonResponse::Domains(domains) => Ok(domains),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
+
+    /// Create an isolation domain
+    pub async fn create_domain(&self, name: String, config: DomainConfig) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::CreateDomain { name, config }).await
+    }
+
+    /// Suspend the actors of a domain
+    pub async fn suspend_domain(&self, name: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::SuspendDomain { name }).await
+    }
+
+    /// Resume the actors of a suspended domain
+    pub async fn resume_domain(&self, name: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::ResumeDomain { name }).await
+    }
+
+    /// Terminate the actors of a domain and remove it
+    pub async fn delete_domain(&self, name: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::DeleteDomain { name }).await
+    }
 
-        pub async fn send_message(&self, _message: DaemonMessage) -> ReamResult<DaemonResponse> {
-            Err(ReamError::NotImplemented("IPC client not implemented on Windows".to_string()))
+    /// Get the audit log entries recorded at or after `since`
+    pub async fn query_audit(&self, since: Option<SystemTime>) -> ReamResult<Vec<AuditEntry>> {
+        match self.send_message(DaemonMessage::QueryAudit { since }).await? {
+            DaemonResponse::AuditEntries(entries) => Ok(entries),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
 
-        pub async fn is_daemon_running(&self) -> bool {
-            // On Windows, check if PID file exists and process is running
-            let config = DaemonConfig::default();
+    /// Deploy a serverless function from an encoded bytecode bundle or
+    /// TLisp source
+    pub async fn deploy_function(&self, name: String, code: FunctionSource, trigger: WakeTrigger, options: DeployOptions) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::DeployFunction { name, code, trigger, options }).await
+    }
 
-            if !config.pid_file.exists() {
-                return false;
-            }
+    /// Invoke a serverless function, returning its response
+    pub async fn invoke_function(&self, name: String, payload: Vec<u8>) -> ReamResult<Vec<u8>> {
+        match self.send_message(DaemonMessage::InvokeFunction { name, payload }).await? {
+            DaemonResponse::Invoked(output) => Ok(output),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
+        }
+    }
 
-            // Read PID from file
-            if let Ok(pid_str) = std::fs::read_to_string(&config.pid_file) {
-                if let Ok(pid) = pid_str.trim().parse::<u32>() {
-                    // Check if process is still running using Windows API
-                    self.is_process_running(pid)
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
+    /// List the serverless functions
+    pub async fn list_functions(&self) -> ReamResult<Vec<FunctionInfo>> {
+        match self.send_message(DaemonMessage::ListFunctions).await? {
+            DaemonResponse::Functions(functions) => Ok(functions),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
 
-        fn is_process_running(&self, pid: u32) -> bool {
-            // On Windows, we can check if a process exists by trying to open it
-            use std::process::Command;
+    /// Schedule a recurring job, replacing any job with the same name
+    pub async fn add_cron_job(&self, job: CronJob) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::AddCronJob { job }).await
+    }
 
-            let output = Command::new("tasklist")
-                .args(&["/FI", &format!("PID eq {}", pid), "/FO", "CSV"])
-                .output();
+    /// Remove a scheduled job
+    pub async fn remove_cron_job(&self, name: String) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::RemoveCronJob { name }).await
+    }
 
-            if let Ok(output) = output {
-                let output_str = String::from_utf8_lossy(&output.stdout);
-                // If the process exists, tasklist will return more than just the header
-                output_str.lines().count() > 1
-            } else {
-                false
-            }
+    /// List the scheduled jobs
+    pub async fn list_cron_jobs(&self) -> ReamResult<Vec<CronJob>> {
+        match self.send_message(DaemonMessage::ListCronJobs).await? {
+            DaemonResponse::CronJobs(jobs) => Ok(jobs),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
 
-        pub async fn get_system_info(&self) -> ReamResult<SystemInfo> {
-            Err(ReamError::NotImplemented("Get system info not implemented on Windows".to_string()))
+    /// List the message schemas
+    pub async fn list_schemas(&self) -> ReamResult<Vec<SchemaInfo>> {
+        match self.send_message(DaemonMessage::ListSchemas).await? {
+            DaemonResponse::Schemas(schemas) => Ok(schemas),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
+        }
+    }
+
+    /// List the workflows and their progress
+    pub async fn list_workflows(&self) -> ReamResult<Vec<WorkflowInfo>> {
+        match self.send_message(DaemonMessage::ListWorkflows).await? {
+            DaemonResponse::Workflows(workflows) => Ok(workflows),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
 
-        pub async fn list_actors(&self, _detailed: bool) -> ReamResult<Vec<ActorInfo>> {
-            Err(ReamError::NotImplemented("List actors not implemented on Windows".to_string()))
+    /// Get the progress of a workflow
+    pub async fn inspect_workflow(&self, id: String) -> ReamResult<WorkflowInfo> {
+        match self.send_message(DaemonMessage::InspectWorkflow { id }).await? {
+            DaemonResponse::Workflow(workflow) => Ok(*workflow),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
+
+    /// Ask the daemon to reload its configuration
+    pub async fn reload_config(&self) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::Reload).await
+    }
 
-        pub async fn get_actor_info(&self, _pid: String) -> ReamResult<ActorInfo> {
-            Err(ReamError::NotImplemented("Get actor info not implemented on Windows".to_string()))
+    /// Shutdown daemon
+    pub async fn shutdown_daemon(&self) -> ReamResult<String> {
+        self.expect_success(DaemonMessage::Shutdown).await
+    }
+
+    async fn expect_recording(&self, message: DaemonMessage) -> ReamResult<Recording> {
+        match self.send_message(message).await? {
+            DaemonResponse::Recording(recording) => Ok(*recording),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
 
-        pub async fn kill_actor(&self, _pid: String, _reason: String) -> ReamResult<String> {
-            Err(ReamError::NotImplemented("Kill actor not implemented on Windows".to_string()))
+    async fn expect_success(&self, message: DaemonMessage) -> ReamResult<String> {
+        match self.send_message(message).await? {
+            DaemonResponse::Success(msg) => Ok(msg),
+            DaemonResponse::Error(msg) => Err(ReamError::Other(msg)),
+            _ => Err(ReamError::Other("Unexpected response".to_string())),
         }
+    }
+}
 
-        pub async fn suspend_actor(&self, _pid: String) -> ReamResult<String> {
-            Err(ReamError::NotImplemented("Suspend actor not implemented on Windows".to_string()))
+#[cfg(unix)]
+mod platform {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    pub struct Listener(UnixListener);
+
+    impl Listener {
+        pub async fn accept(&mut self) -> std::io::Result<UnixStream> {
+            self.0.accept().await.map(|(stream, _addr)| stream)
         }
+    }
 
-        pub async fn resume_actor(&self, _pid: String) -> ReamResult<String> {
-            Err(ReamError::NotImplemented("Resume actor not implemented on Windows".to_string()))
+    /// Listen on `path`, replacing a socket left behind by an earlier daemon
+    pub fn bind(path: &Path) -> ReamResult<Listener> {
+        if path.exists() {
+            std::fs::remove_file(path).map_err(ReamError::Io)?;
         }
 
-        pub async fn restart_actor(&self, _pid: String) -> ReamResult<String> {
-            Err(ReamError::NotImplemented("Restart actor not implemented on Windows".to_string()))
+        let listener = UnixListener::bind(path).map_err(ReamError::Io)?;
+        // Only the daemon's user may control it
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(ReamError::Io)?;
+        Ok(Listener(listener))
+    }
+
+    pub async fn connect(path: &Path) -> ReamResult<UnixStream> {
+        UnixStream::connect(path).await.map_err(ReamError::Io)
+    }
+
+    pub fn cleanup(path: &Path) -> ReamResult<()> {
+        if path.exists() {
+            std::fs::remove_file(path).map_err(ReamError::Io)?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::time::Duration;
+    use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions};
+
+    /// `ERROR_PIPE_BUSY`: every pipe instance is serving another client
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    /// Named pipes live in their own namespace; the socket's file name picks the pipe
+    fn pipe_name(path: &Path) -> String {
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "ream-daemon".to_string());
+        format!(r"\\.\pipe\{}", name)
+    }
 
-        pub async fn send_actor_message(&self, _pid: String, _message: String) -> ReamResult<String> {
-            Err(ReamError::NotImplemented("Send actor message not implemented on Windows".to_string()))
+    pub struct Listener {
+        name: String,
+        next: NamedPipeServer,
+    }
+
+    impl Listener {
+        /// Wait for a client on the pending instance and create the next one
+        pub async fn accept(&mut self) -> std::io::Result<NamedPipeServer> {
+            self.next.connect().await?;
+            let next = ServerOptions::new().create(&self.name)?;
+            Ok(std::mem::replace(&mut self.next, next))
         }
+    }
 
-        pub async fn shutdown_daemon(&self) -> ReamResult<String> {
-            Err(ReamError::NotImplemented("Shutdown daemon not implemented on Windows".to_string()))
+    pub fn bind(path: &Path) -> ReamResult<Listener> {
+        let name = pipe_name(path);
+        let next = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&name)
+            .map_err(ReamError::Io)?;
+        Ok(Listener { name, next })
+    }
+
+    pub async fn connect(path: &Path) -> ReamResult<NamedPipeClient> {
+        let name = pipe_name(path);
+        loop {
+            match ClientOptions::new().open(&name) {
+                Ok(client) => return Ok(client),
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                Err(e) => return Err(ReamError::Io(e)),
+            }
         }
     }
+
+    pub fn cleanup(_path: &Path) -> ReamResult<()> {
+        // The pipe disappears with its last instance
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::daemon::DaemonConfig;
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        write_frame(&mut client, b"{\"Ping\":null}").await.unwrap();
+        write_frame(&mut client, b"").await.unwrap();
+        drop(client);
+
+        assert_eq!(read_frame(&mut server).await.unwrap().as_deref(), Some(&b"{\"Ping\":null}"[..]));
+        assert_eq!(read_frame(&mut server).await.unwrap().as_deref(), Some(&b""[..]));
+        assert_eq!(read_frame(&mut server).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_is_rejected() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&(MAX_FRAME_SIZE as u32 + 1).to_be_bytes()).await.unwrap();
+        assert!(read_frame(&mut server).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_server_handles_concurrent_clients() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("ream.sock");
+        let config = DaemonConfig { socket_path: socket_path.clone(), ..DaemonConfig::default() };
+        let manager = Arc::new(DaemonManager::new(config).unwrap());
+
+        let mut server = IpcServer::new(socket_path.clone(), manager);
+        server.start().await.unwrap();
+
+        let clients = (0..4).map(|_| {
+            let client = IpcClient::new(socket_path.clone());
+            tokio::spawn(async move {
+                assert!(client.is_daemon_running().await);
+                client.list_actors(false).await.unwrap()
+            })
+        });
+        for client in clients {
+            assert!(client.await.unwrap().is_empty());
+        }
+
+        // One connection can carry several requests
+        let mut stream = platform::connect(&socket_path).await.unwrap();
+        for _ in 0..2 {
+            write_frame(&mut stream, b"\"Ping\"").await.unwrap();
+            let frame = read_frame(&mut stream).await.unwrap().unwrap();
+            assert!(matches!(serde_json::from_slice(&frame).unwrap(), DaemonResponse::Pong));
+        }
+        write_frame(&mut stream, b"not json").await.unwrap();
+        let frame = read_frame(&mut stream).await.unwrap().unwrap();
+        assert!(matches!(serde_json::from_slice(&frame).unwrap(), DaemonResponse::Error(_)));
+
+        server.stop().unwrap();
+        assert!(!socket_path.exists());
+    }
 }
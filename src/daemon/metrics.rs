@@ -0,0 +1,353 @@
+//! Prometheus metrics for the daemon
+//!
+//! Runtime, actor and registered source metrics are written in the
+//! Prometheus text exposition format and served over HTTP at `/metrics`.
+//! Per-actor series are capped: the actors with the deepest mailboxes get
+//! their own `pid` label and the rest are summed under `pid="other"`.
+//!
+//! The same endpoint serves the serverless functions deployed with an HTTP
+//! trigger: any other request is routed to the function whose trigger
+//! matches its method and path.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use async_trait::async_trait;
+use warp::http::{Method, StatusCode};
+use warp::path::FullPath;
+use warp::Filter;
+
+use crate::error::{ReamError, ReamResult};
+use crate::orm::pool::{ConnectionManager, Pool};
+use crate::p2p::ReamNode;
+use crate::runtime::memory::GcStats;
+use crate::runtime::IngressStats;
+use super::{ActorInfo, ActorStatus, DaemonManager, SystemInfo};
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Default number of actors given their own label values
+pub const DEFAULT_ACTOR_SERIES_LIMIT: usize = 100;
+
+/// Label value that aggregates actors beyond the series limit
+pub const OTHER_ACTORS: &str = "other";
+
+/// Prometheus metric type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+impl MetricKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+/// A metric family and its samples
+struct Family {
+    name: String,
+    kind: MetricKind,
+    help: String,
+    samples: Vec<String>,
+}
+
+/// Collects samples grouped by family, so sources writing the same
+/// family (e.g. two registered pools) produce a single HELP/TYPE header
+#[derive(Default)]
+pub struct MetricsWriter {
+    families: Vec<Family>,
+}
+
+impl MetricsWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a counter sample
+    pub fn counter(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+        self.sample(name, MetricKind::Counter, help, labels, value);
+    }
+
+    /// Write a gauge sample
+    pub fn gauge(&mut self, name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+        self.sample(name, MetricKind::Gauge, help, labels, value);
+    }
+
+    fn sample(&mut self, name: &str, kind: MetricKind, help: &str, labels: &[(&str, &str)], value: f64) {
+        let index = match self.families.iter().position(|family| family.name == name) {
+            Some(index) => index,
+            None => {
+                self.families.push(Family {
+                    name: name.to_string(),
+                    kind,
+                    help: help.to_string(),
+                    samples: Vec::new(),
+                });
+                self.families.len() - 1
+            }
+        };
+
+        let mut sample = name.to_string();
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels.iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            sample.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+        sample.push(' ');
+        sample.push_str(&format_value(value));
+        self.families[index].samples.push(sample);
+    }
+
+    /// Render all families in the order they were first written
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        for family in &self.families {
+            output.push_str(&format!("# HELP {} {}\n", family.name, family.help));
+            output.push_str(&format!("# TYPE {} {}\n", family.name, family.kind.as_str()));
+            for sample in &family.samples {
+                output.push_str(sample);
+                output.push('\n');
+            }
+        }
+        output
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Something that contributes metrics to the daemon's `/metrics` output
+#[async_trait]
+pub trait MetricsSource: Send + Sync {
+    /// Write this source's samples; `name` is what it was registered under
+    async fn collect(&self, name: &str, out: &mut MetricsWriter);
+}
+
+#[async_trait]
+impl<M: ConnectionManager> MetricsSource for Pool<M> {
+    async fn collect(&self, name: &str, out: &mut MetricsWriter) {
+        let status = self.status();
+        let labels = [("database", name)];
+        out.gauge("ream_db_pool_max_connections", "Maximum connections in the pool", &labels, status.max_size as f64);
+        out.gauge("ream_db_pool_open_connections", "Open connections, idle or in use", &labels, status.size as f64);
+        out.gauge("ream_db_pool_idle_connections", "Idle connections", &labels, status.idle as f64);
+        out.gauge("ream_db_pool_in_use_connections", "Connections checked out", &labels, status.in_use as f64);
+        out.gauge("ream_db_pool_waiting", "Callers waiting for a connection", &labels, status.waiting as f64);
+    }
+}
+
+#[async_trait]
+impl MetricsSource for ReamNode {
+    async fn collect(&self, name: &str, out: &mut MetricsWriter) {
+        let stats = self.network_stats().await;
+        let labels = [("node", name)];
+        out.gauge("ream_p2p_connected_nodes", "Connected cluster nodes", &labels, stats.connected_nodes as f64);
+        out.gauge("ream_p2p_active_connections", "Active network connections", &labels, stats.active_connections as f64);
+        out.counter("ream_p2p_messages_sent_total", "Messages sent to other nodes", &labels, stats.total_messages_sent as f64);
+        out.counter("ream_p2p_messages_received_total", "Messages received from other nodes", &labels, stats.total_messages_received as f64);
+        out.counter("ream_p2p_bytes_sent_total", "Bytes sent to other nodes", &labels, stats.total_bytes_sent as f64);
+        out.counter("ream_p2p_bytes_received_total", "Bytes received from other nodes", &labels, stats.total_bytes_received as f64);
+    }
+}
+
+/// Write runtime-wide metrics
+pub fn write_system(out: &mut MetricsWriter, info: &SystemInfo, gc: &GcStats) {
+    let stats = &info.runtime_stats;
+    out.gauge("ream_processes", "Processes in the runtime", &[], stats.process_count as f64);
+    out.gauge("ream_processes_running", "Running processes", &[], stats.running_processes as f64);
+    out.gauge("ream_memory_bytes", "Runtime memory usage", &[], stats.memory_usage as f64);
+    out.gauge("ream_message_rate", "Messages sent per second", &[], stats.message_rate);
+    out.gauge("ream_scheduler_utilization", "Scheduler utilization ratio", &[], stats.scheduler_utilization);
+    out.counter("ream_gc_collections_total", "Garbage collections performed", &[], gc.collections as f64);
+    out.counter("ream_gc_pause_seconds_total", "Time spent in garbage collection", &[], gc.total_time.as_secs_f64());
+    out.counter("ream_gc_collected_bytes_total", "Bytes reclaimed by garbage collection", &[], gc.bytes_collected as f64);
+    let binaries = crate::runtime::binary::stats();
+    out.gauge("ream_shared_binaries", "Shared binaries referenced by processes", &[], binaries.live_binaries as f64);
+    out.gauge("ream_shared_binary_bytes", "Bytes referenced by shared binaries", &[], binaries.live_bytes as f64);
+    out.counter("ream_shared_binaries_freed_total", "Shared binaries freed once unreferenced", &[], binaries.freed_binaries as f64);
+    out.gauge("ream_uptime_seconds", "Daemon uptime", &[], info.uptime.as_secs_f64());
+    out.counter("ream_messages_processed_total", "Messages processed by all actors", &[], info.total_messages as f64);
+
+    for (status, count) in [
+        ("running", info.active_actors),
+        ("suspended", info.suspended_actors),
+        ("crashed", info.crashed_actors),
+    ] {
+        out.gauge("ream_actors", "Actors by status", &[("status", status)], count as f64);
+    }
+}
+
+/// Write per-actor metrics, giving at most `limit` actors their own series
+pub fn write_actors(out: &mut MetricsWriter, actors: &[ActorInfo], limit: usize) {
+    let mut actors: Vec<&ActorInfo> = actors.iter()
+        .filter(|actor| actor.status != ActorStatus::Terminated)
+        .collect();
+    actors.sort_by(|a, b| b.mailbox_size.cmp(&a.mailbox_size).then(a.pid.0.cmp(&b.pid.0)));
+
+    let split = limit.min(actors.len());
+    let (labelled, rest) = actors.split_at(split);
+    for actor in labelled {
+        let pid = actor.pid.to_string();
+        write_actor(out, &pid, actor.mailbox_size, actor.memory_usage, actor.messages_processed);
+        if let Some(ingress) = &actor.ingress {
+            write_ingress(out, &pid, &ingress.stats);
+        }
+    }
+    if !rest.is_empty() {
+        write_actor(
+            out,
+            OTHER_ACTORS,
+            rest.iter().map(|actor| actor.mailbox_size).sum(),
+            rest.iter().map(|actor| actor.memory_usage).sum(),
+            rest.iter().map(|actor| actor.messages_processed).sum(),
+        );
+        let limited: Vec<&IngressStats> = rest.iter().filter_map(|actor| actor.ingress.as_ref().map(|ingress| &ingress.stats)).collect();
+        if !limited.is_empty() {
+            let stats = IngressStats {
+                admitted: limited.iter().map(|stats| stats.admitted).sum(),
+                shed: limited.iter().map(|stats| stats.shed).sum(),
+                rejected: limited.iter().map(|stats| stats.rejected).sum(),
+                queued: limited.iter().map(|stats| stats.queued).sum(),
+            };
+            write_ingress(out, OTHER_ACTORS, &stats);
+        }
+    }
+    out.gauge("ream_actor_series_dropped", "Actors folded into pid=\"other\" by the series limit", &[], rest.len() as f64);
+}
+
+fn write_actor(out: &mut MetricsWriter, pid: &str, mailbox: usize, memory: usize, messages: u64) {
+    let labels = [("pid", pid)];
+    out.gauge("ream_actor_mailbox_depth", "Messages waiting in the actor's mailbox", &labels, mailbox as f64);
+    out.gauge("ream_actor_memory_bytes", "Memory used by the actor", &labels, memory as f64);
+    out.counter("ream_actor_messages_processed_total", "Messages processed by the actor", &labels, messages as f64);
+}
+
+/// Write what the rate limit of a limited actor did with its messages
+fn write_ingress(out: &mut MetricsWriter, pid: &str, stats: &IngressStats) {
+    let labels = [("pid", pid)];
+    out.counter("ream_actor_messages_admitted_total", "Messages let through the actor's rate limit", &labels, stats.admitted as f64);
+    out.counter("ream_actor_messages_shed_total", "Messages dropped by the actor's rate limit", &labels, stats.shed as f64);
+    out.counter("ream_actor_messages_rejected_total", "Messages refused by the actor's rate limit", &labels, stats.rejected as f64);
+    out.gauge("ream_actor_messages_queued", "Messages held by the actor's rate limit", &labels, stats.queued as f64);
+}
+
+/// Bind the `/metrics` endpoint and the HTTP triggers of serverless
+/// functions, returning the bound address and the server future
+pub fn bind(manager: Arc<DaemonManager>, addr: SocketAddr) -> ReamResult<(SocketAddr, impl Future<Output = ()>)> {
+    let metrics = manager.clone();
+    let route = warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(move || {
+            let manager = metrics.clone();
+            async move {
+                let body = manager.render_metrics().await;
+                Ok::<_, Infallible>(warp::reply::with_header(body, "content-type", CONTENT_TYPE))
+            }
+        });
+
+    let functions = warp::method()
+        .and(warp::path::full())
+        .and(warp::body::bytes())
+        .and_then(move |method: Method, path: FullPath, body: bytes::Bytes| {
+            let manager = manager.clone();
+            async move {
+                let Some(name) = manager.route_function(method.as_str(), path.as_str()) else {
+                    return Ok::<_, Infallible>(warp::reply::with_status(Vec::new(), StatusCode::NOT_FOUND));
+                };
+                // Functions run synchronously, so keep them off the server's threads
+                let invoked = tokio::task::spawn_blocking(move || manager.invoke_function(&name, body.to_vec())).await;
+                Ok(match invoked {
+                    Ok(Ok(output)) => warp::reply::with_status(output, StatusCode::OK),
+                    Ok(Err(e)) => warp::reply::with_status(e.to_string().into_bytes(), StatusCode::INTERNAL_SERVER_ERROR),
+                    Err(e) => warp::reply::with_status(e.to_string().into_bytes(), StatusCode::INTERNAL_SERVER_ERROR),
+                })
+            }
+        });
+
+    warp::serve(route.or(functions))
+        .try_bind_ephemeral(addr)
+        .map_err(|e| ReamError::Other(format!("Failed to bind metrics endpoint {}: {}", addr, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use crate::types::Pid;
+
+    fn actor(mailbox_size: usize) -> ActorInfo {
+        ActorInfo {
+            pid: Pid::new(),
+            status: ActorStatus::Running,
+            mailbox_size,
+            memory_usage: 1024,
+            messages_processed: 1,
+            message_rate: 0.0,
+            cpu_time: 0,
+            uptime: Duration::ZERO,
+            last_activity: SystemTime::now(),
+            actor_type: "ReamActor".to_string(),
+            state_description: String::new(),
+            links: Vec::new(),
+            monitors: Vec::new(),
+            supervisor: None,
+            recent_messages: Vec::new(),
+            restarts: 0,
+            domain: None,
+            ingress: None,
+        }
+    }
+
+    #[test]
+    fn test_families_are_grouped() {
+        let mut out = MetricsWriter::new();
+        out.gauge("ream_db_pool_idle_connections", "Idle connections", &[("database", "main")], 2.0);
+        out.gauge("ream_processes", "Processes", &[], 3.0);
+        out.gauge("ream_db_pool_idle_connections", "Idle connections", &[("database", "a \"b\"")], 0.5);
+
+        assert_eq!(out.render(), "\
+# HELP ream_db_pool_idle_connections Idle connections
+# TYPE ream_db_pool_idle_connections gauge
+ream_db_pool_idle_connections{database=\"main\"} 2
+ream_db_pool_idle_connections{database=\"a \\\"b\\\"\"} 0.5
+# HELP ream_processes Processes
+# TYPE ream_processes gauge
+ream_processes 3
+");
+    }
+
+    #[test]
+    fn test_actor_series_are_capped() {
+        let actors: Vec<ActorInfo> = (0..5).map(actor).collect();
+        let mut out = MetricsWriter::new();
+        write_actors(&mut out, &actors, 2);
+        let text = out.render();
+
+        let deepest = actors[4].pid.to_string();
+        assert!(text.contains(&format!("ream_actor_mailbox_depth{{pid=\"{}\"}} 4", deepest)));
+        assert!(text.contains("ream_actor_mailbox_depth{pid=\"other\"} 3"));
+        assert!(text.contains("ream_actor_memory_bytes{pid=\"other\"} 3072"));
+        assert!(text.contains("ream_actor_series_dropped 3"));
+        assert_eq!(text.matches("ream_actor_mailbox_depth{").count(), 3);
+    }
+}
@@ -6,23 +6,50 @@
 pub mod runtime;
 pub mod ipc;
 pub mod monitor;
+pub mod eval;
+pub mod metrics;
+pub mod pidfile;
+pub mod signals;
 
 #[cfg(feature = "tui")]
 pub mod tui;
 
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant, SystemTime};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Notify};
 use serde::{Serialize, Deserialize};
 
-use crate::types::{Pid, RuntimeStats};
+use crate::types::{MessagePayload, Pid, RuntimeStats};
 use crate::error::{ReamResult, ReamError};
-use crate::runtime::ReamRuntime;
+use crate::runtime::{DomainConfig, DomainInfo, IngressStatus, RateLimit, ReamRuntime, SupervisionNode};
+use crate::runtime::crash::CrashDump;
+use crate::runtime::cron::{CronJob, CronScheduler, JobAction};
+use crate::runtime::schema::{SchemaInfo, SchemaRegistry};
+use crate::runtime::event_sourcing::{MemoryEventStore, SqliteEventStore};
+use crate::runtime::workflow::{WorkflowEngine, WorkflowInfo};
+use crate::runtime::serverless::{ServerlessConfig, WakeTrigger};
+use crate::runtime::serverless_runtime::{DeployOptions, FunctionInfo, ServerlessReamRuntime};
+use crate::bytecode::BytecodeBundle;
+use crate::debug::profiler::{self, HeapProfile};
+use crate::debug::cpu_profiler::{self, CpuProfile};
+use crate::debug::flow::{self, FlowEvent, FlowFilter};
+use crate::debug::replay::{Recording, DEFAULT_RECORDING_LIMIT};
+use crate::orm::pool::{HealthCheck, PoolHealth};
+use crate::logging::LogRotation;
+use crate::security::audit::{self, AuditEntry, AuditKind};
+use eval::{EvalResult, EvalService};
+use metrics::{MetricsSource, MetricsWriter, DEFAULT_ACTOR_SERIES_LIMIT};
+use monitor::{Alert, AlertAction, AlertEngine, AlertRule};
 
 
 /// Daemon configuration
+///
+/// Can be loaded from a TOML file; fields missing from the file keep their
+/// defaults. The daemon re-reads the file on SIGHUP.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DaemonConfig {
     /// Socket path for IPC
     pub socket_path: PathBuf,
@@ -30,6 +57,8 @@ pub struct DaemonConfig {
     pub pid_file: PathBuf,
     /// Log file path
     pub log_file: PathBuf,
+    /// How often the log file is rotated
+    pub log_rotation: LogRotation,
     /// Whether to run in foreground
     pub foreground: bool,
     /// Monitoring update interval
@@ -38,32 +67,158 @@ pub struct DaemonConfig {
     pub max_actors: usize,
     /// Memory limit per actor (bytes)
     pub memory_limit: usize,
+    /// Address to serve Prometheus metrics on
+    pub metrics_addr: Option<SocketAddr>,
+    /// Maximum number of actors given their own metric series
+    pub metrics_actor_limit: usize,
+    /// Alerting rules checked on every monitoring pass
+    pub alert_rules: Vec<AlertRule>,
+    /// Directory crash dumps of actors are written to
+    pub dump_dir: PathBuf,
+    /// Capability policy the builtins of the daemon's programs are checked
+    /// against; without one they may do anything
+    pub security_policy: Option<PathBuf>,
+    /// Encrypted secrets file served to `secret-get` and `secret-set`,
+    /// unlocked with the passphrase in `REAM_MASTER_KEY`
+    pub secrets_file: Option<PathBuf>,
+    /// Hash-chained audit log of spawns, permission denials, remote
+    /// connections and configuration changes
+    pub audit_log: Option<PathBuf>,
+    /// File scheduled jobs are saved to, so they survive restarts
+    pub schedule_file: PathBuf,
+    /// Directory the initialized environments of TLisp functions are
+    /// snapshotted to, so redeploying unchanged functions skips initialization
+    pub snapshot_dir: PathBuf,
+    /// Event store workflows checkpoint their progress in
+    pub workflow_store: PathBuf,
+    /// File this configuration was loaded from, re-read on reload
+    #[serde(skip)]
+    pub config_file: Option<PathBuf>,
+}
+
+impl DaemonConfig {
+    /// Load a configuration file, either a plain daemon config or the
+    /// `[daemon]` section of a `ream.toml`
+    pub fn load(path: &Path) -> ReamResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(ReamError::Io)?;
+        let mut table: toml::Table = toml::from_str(&content)
+            .map_err(|e| ReamError::Other(format!("Invalid daemon config {}: {}", path.display(), e)))?;
+        if let Some(toml::Value::Table(daemon)) = table.remove("daemon") {
+            table = daemon;
+        }
+        let mut config: DaemonConfig = toml::Value::Table(table).try_into()
+            .map_err(|e| ReamError::Other(format!("Invalid daemon config {}: {}", path.display(), e)))?;
+        config.config_file = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Apply the settings that changed between two loads of the config file.
+    /// Settings the file did not change keep their current value, so command
+    /// line overrides survive a reload. Returns the names of changed settings
+    /// that only take effect after a restart.
+    pub fn reload(&mut self, previous: &DaemonConfig, loaded: &DaemonConfig) -> Vec<&'static str> {
+        if loaded.log_file != previous.log_file {
+            self.log_file = loaded.log_file.clone();
+        }
+        if loaded.log_rotation != previous.log_rotation {
+            self.log_rotation = loaded.log_rotation;
+        }
+        if loaded.monitor_interval != previous.monitor_interval {
+            self.monitor_interval = loaded.monitor_interval;
+        }
+        if loaded.max_actors != previous.max_actors {
+            self.max_actors = loaded.max_actors;
+        }
+        if loaded.memory_limit != previous.memory_limit {
+            self.memory_limit = loaded.memory_limit;
+        }
+        if loaded.metrics_actor_limit != previous.metrics_actor_limit {
+            self.metrics_actor_limit = loaded.metrics_actor_limit;
+        }
+        if loaded.dump_dir != previous.dump_dir {
+            self.dump_dir = loaded.dump_dir.clone();
+        }
+        if loaded.security_policy != previous.security_policy {
+            self.security_policy = loaded.security_policy.clone();
+        }
+        if loaded.secrets_file != previous.secrets_file {
+            self.secrets_file = loaded.secrets_file.clone();
+        }
+        if loaded.audit_log != previous.audit_log {
+            self.audit_log = loaded.audit_log.clone();
+        }
+        // Replaces rules added over IPC as well
+        if loaded.alert_rules != previous.alert_rules {
+            self.alert_rules = loaded.alert_rules.clone();
+        }
+
+        let mut needs_restart = Vec::new();
+        if loaded.socket_path != previous.socket_path {
+            needs_restart.push("socket_path");
+        }
+        if loaded.pid_file != previous.pid_file {
+            needs_restart.push("pid_file");
+        }
+        if loaded.metrics_addr != previous.metrics_addr {
+            needs_restart.push("metrics_addr");
+        }
+        if loaded.schedule_file != previous.schedule_file {
+            needs_restart.push("schedule_file");
+        }
+        if loaded.snapshot_dir != previous.snapshot_dir {
+            needs_restart.push("snapshot_dir");
+        }
+        if loaded.workflow_store != previous.workflow_store {
+            needs_restart.push("workflow_store");
+        }
+        needs_restart
+    }
 }
 
 impl Default for DaemonConfig {
     fn default() -> Self {
         #[cfg(unix)]
-        let (socket_path, pid_file, log_file) = (
+        let (socket_path, pid_file, log_file, dump_dir, schedule_file, snapshot_dir, workflow_store) = (
             PathBuf::from("/tmp/ream-daemon.sock"),
             PathBuf::from("/tmp/ream-daemon.pid"),
             PathBuf::from("/tmp/ream-daemon.log"),
+            PathBuf::from("/tmp/ream-dumps"),
+            PathBuf::from("/tmp/ream-schedules.json"),
+            PathBuf::from("/tmp/ream-snapshots"),
+            PathBuf::from("/tmp/ream-workflows.db"),
         );
 
         #[cfg(windows)]
-        let (socket_path, pid_file, log_file) = (
+        let (socket_path, pid_file, log_file, dump_dir, schedule_file, snapshot_dir, workflow_store) = (
             std::env::temp_dir().join("ream-daemon.sock"),
             std::env::temp_dir().join("ream-daemon.pid"),
             std::env::temp_dir().join("ream-daemon.log"),
+            std::env::temp_dir().join("ream-dumps"),
+            std::env::temp_dir().join("ream-schedules.json"),
+            std::env::temp_dir().join("ream-snapshots"),
+            std::env::temp_dir().join("ream-workflows.db"),
         );
 
         DaemonConfig {
             socket_path,
             pid_file,
             log_file,
+            log_rotation: LogRotation::Daily,
             foreground: false,
             monitor_interval: Duration::from_millis(1000),
             max_actors: 10000,
             memory_limit: 64 * 1024 * 1024, // 64MB per actor
+            metrics_addr: None,
+            metrics_actor_limit: DEFAULT_ACTOR_SERIES_LIMIT,
+            alert_rules: Vec::new(),
+            dump_dir,
+            security_policy: None,
+            secrets_file: None,
+            audit_log: None,
+            schedule_file,
+            snapshot_dir,
+            workflow_store,
+            config_file: None,
         }
     }
 }
@@ -99,6 +254,18 @@ pub struct ActorInfo {
     pub monitors: Vec<Pid>,
     /// Parent supervisor
     pub supervisor: Option<Pid>,
+    /// Summaries of the messages it received most recently, oldest first
+    #[serde(default)]
+    pub recent_messages: Vec<String>,
+    /// Times the actor has been restarted
+    #[serde(default)]
+    pub restarts: u32,
+    /// Isolation domain the actor was spawned into
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// Rate limit on the messages it accepts, and what it did
+    #[serde(default)]
+    pub ingress: Option<IngressStatus>,
 }
 
 /// Actor status enumeration
@@ -151,6 +318,24 @@ pub struct SystemInfo {
     pub load_average: f64,
 }
 
+/// Health of a database registered with the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseHealth {
+    /// Name the database was registered under
+    pub name: String,
+    /// Result of its latest check
+    pub health: PoolHealth,
+}
+
+/// Code a serverless function is deployed from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FunctionSource {
+    /// An encoded bytecode bundle
+    Bundle(Vec<u8>),
+    /// TLisp source defining `handle`
+    Tlisp(String),
+}
+
 /// IPC message types for daemon communication
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DaemonMessage {
@@ -170,6 +355,76 @@ pub enum DaemonMessage {
     RestartActor { pid: String },
     /// Send message to actor
     SendMessage { pid: String, message: String },
+    /// Limit the messages an actor accepts; `None` lifts the limit
+    SetRateLimit { pid: String, limit: Option<RateLimit> },
+    /// Check the registered databases
+    GetDatabaseHealth,
+    /// Evaluate TLisp code in the daemon, optionally in an actor's context
+    Eval { code: String, actor: Option<String> },
+    /// List the alerting rules
+    ListAlertRules,
+    /// Add an alerting rule, replacing any rule with the same name
+    SetAlertRule { rule: AlertRule },
+    /// Remove an alerting rule
+    RemoveAlertRule { name: String },
+    /// Get notified alerts with an id greater than `after`
+    GetAlerts { after: u64 },
+    /// Get the crash dump of an actor
+    GetCrashDump { pid: String },
+    /// Start recording the messages delivered to an actor
+    StartRecording { pid: String, limit: Option<usize> },
+    /// Stop recording an actor, returning the recording
+    StopRecording { pid: String },
+    /// Get the recording of an actor without stopping it
+    GetRecording { pid: String },
+    /// Start sampling the allocations of an actor
+    StartHeapProfile { pid: String, interval: Option<usize> },
+    /// Stop sampling an actor, returning its heap profile
+    StopHeapProfile { pid: String },
+    /// Start sampling the TLisp functions an actor runs
+    StartCpuProfile { pid: String, frequency: Option<u32> },
+    /// Stop sampling an actor, returning its CPU profile
+    StopCpuProfile { pid: String },
+    /// Start recording the messages actors send each other
+    StartFlowRecording { limit: Option<usize> },
+    /// Stop recording messages, keeping those recorded
+    StopFlowRecording,
+    /// Get the recorded messages passing a filter
+    GetFlows { filter: FlowFilter },
+    /// Get the full supervision hierarchy
+    GetSupervisionTree,
+    /// List the isolation domains
+    ListDomains,
+    /// Create an isolation domain
+    CreateDomain { name: String, config: DomainConfig },
+    /// Suspend the actors of a domain
+    SuspendDomain { name: String },
+    /// Resume the actors of a suspended domain
+    ResumeDomain { name: String },
+    /// Terminate the actors of a domain and remove it
+    DeleteDomain { name: String },
+    /// Get the audit log entries recorded at or after `since`
+    QueryAudit { since: Option<SystemTime> },
+    /// Deploy a serverless function
+    DeployFunction { name: String, code: FunctionSource, trigger: WakeTrigger, options: DeployOptions },
+    /// Invoke a serverless function
+    InvokeFunction { name: String, payload: Vec<u8> },
+    /// List the serverless functions
+    ListFunctions,
+    /// Schedule a recurring job, replacing any job with the same name
+    AddCronJob { job: CronJob },
+    /// Remove a scheduled job
+    RemoveCronJob { name: String },
+    /// List the scheduled jobs
+    ListCronJobs,
+    /// List the message schemas and their versions
+    ListSchemas,
+    /// List the workflows and their progress
+    ListWorkflows,
+    /// Get the progress of a workflow
+    InspectWorkflow { id: String },
+    /// Reload the configuration file
+    Reload,
     /// Shutdown daemon
     Shutdown,
     /// Ping daemon
@@ -185,6 +440,42 @@ pub enum DaemonResponse {
     ActorList(Vec<ActorInfo>),
     /// Actor information response
     ActorInfo(ActorInfo),
+    /// Database health response
+    DatabaseHealth(Vec<DatabaseHealth>),
+    /// Evaluation response
+    Evaluated(EvalResult),
+    /// Alerting rules response
+    AlertRules(Vec<AlertRule>),
+    /// Notified alerts response
+    Alerts(Vec<Alert>),
+    /// Crash dump response
+    CrashDump(Box<CrashDump>),
+    /// Message recording response
+    Recording(Box<Recording>),
+    /// Heap profile response
+    HeapProfile(Box<HeapProfile>),
+    /// CPU profile response
+    CpuProfile(Box<CpuProfile>),
+    /// Recorded message flows response
+    Flows(Vec<FlowEvent>),
+    /// Supervision hierarchy response
+    SupervisionTree(SupervisionNode),
+    /// Isolation domains response
+    Domains(Vec<DomainInfo>),
+    /// Audit log entries response
+    AuditEntries(Vec<AuditEntry>),
+    /// Serverless function response
+    Invoked(Vec<u8>),
+    /// Serverless functions response
+    Functions(Vec<FunctionInfo>),
+    /// Scheduled jobs response
+    CronJobs(Vec<CronJob>),
+    /// Message schemas response
+    Schemas(Vec<SchemaInfo>),
+    /// Workflows response
+    Workflows(Vec<WorkflowInfo>),
+    /// Workflow progress response
+    Workflow(Box<WorkflowInfo>),
     /// Operation success
     Success(String),
     /// Operation error
@@ -196,11 +487,17 @@ pub enum DaemonResponse {
 /// Daemon runtime manager
 pub struct DaemonManager {
     /// Configuration
-    config: DaemonConfig,
+    config: RwLock<DaemonConfig>,
     /// REAM runtime
     runtime: Arc<ReamRuntime>,
     /// Actor information cache
     actors: Arc<RwLock<std::collections::HashMap<Pid, ActorInfo>>>,
+    /// Databases reported by health checks
+    databases: Arc<RwLock<std::collections::BTreeMap<String, Arc<dyn HealthCheck>>>>,
+    /// Extra sources included in the metrics endpoint
+    metric_sources: Arc<RwLock<std::collections::BTreeMap<String, Arc<dyn MetricsSource>>>>,
+    /// TLisp runtimes the program and attached REPLs evaluate in
+    evaluator: EvalService,
     /// System start time
     start_time: Instant,
     /// IPC command channel
@@ -210,28 +507,82 @@ pub struct DaemonManager {
     response_tx: Arc<RwLock<Option<mpsc::UnboundedSender<DaemonResponse>>>>,
     /// Running flag
     running: Arc<std::sync::atomic::AtomicBool>,
+    /// Signalled when a client asks the daemon to shut down
+    shutdown: Notify,
+    /// Signalled when a client asks the daemon to reload its configuration
+    reload: Notify,
+    /// Alerting state
+    alerts: std::sync::Mutex<AlertEngine>,
+    /// Serverless functions, started with the first deployment
+    functions: std::sync::Mutex<Option<Arc<ServerlessReamRuntime>>>,
+    /// Scheduled jobs
+    cron: std::sync::Mutex<CronScheduler>,
+    /// Schemas of the messages actors exchange
+    schemas: Arc<SchemaRegistry>,
+    /// Saga workflows, checkpointed in memory until the workflow store opens
+    workflows: RwLock<Arc<WorkflowEngine>>,
 }
 
 impl DaemonManager {
     /// Create a new daemon manager
     pub fn new(config: DaemonConfig) -> ReamResult<Self> {
-        let runtime = Arc::new(ReamRuntime::new()?);
+        let runtime = Arc::new(ReamRuntime::with_config(crate::config::current().runtime));
         let actors = Arc::new(RwLock::new(std::collections::HashMap::new()));
         let start_time = Instant::now();
 
         let (command_tx, command_rx) = mpsc::unbounded_channel();
 
         Ok(DaemonManager {
-            config,
+            config: RwLock::new(config),
             runtime,
             actors,
+            databases: Arc::new(RwLock::new(std::collections::BTreeMap::new())),
+            metric_sources: Arc::new(RwLock::new(std::collections::BTreeMap::new())),
+            evaluator: EvalService::new(),
             start_time,
             command_tx,
             command_rx: Arc::new(RwLock::new(Some(command_rx))),
             response_tx: Arc::new(RwLock::new(None)),
             running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            shutdown: Notify::new(),
+            reload: Notify::new(),
+            alerts: std::sync::Mutex::new(AlertEngine::new()),
+            functions: std::sync::Mutex::new(None),
+            cron: std::sync::Mutex::new(CronScheduler::new()),
+            schemas: Arc::new(SchemaRegistry::new()),
+            workflows: RwLock::new(Arc::new(WorkflowEngine::new(Arc::new(MemoryEventStore::new())))),
         })
     }
+
+    /// Current configuration
+    pub fn config(&self) -> DaemonConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replace the configuration after a reload
+    pub fn set_config(&self, config: DaemonConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Ask the daemon to shut down
+    pub fn request_shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+
+    /// Wait until a shutdown is requested
+    pub async fn shutdown_requested(&self) {
+        self.shutdown.notified().await
+    }
+
+    /// Ask the daemon to reload its configuration
+    pub fn request_reload(&self) {
+        self.reload.notify_one();
+    }
+
+    /// Wait until a configuration reload is requested
+    pub async fn reload_requested(&self) {
+        self.reload.notified().await
+    }
     
     /// Start the daemon
     pub async fn start(&self, program_file: PathBuf) -> ReamResult<()> {
@@ -263,29 +614,35 @@ impl DaemonManager {
         let runtime = self.runtime.clone();
         let actors = self.actors.clone();
         let running = self.running.clone();
-        let interval = self.config.monitor_interval;
+        let interval = self.config().monitor_interval;
 
         tokio::spawn(async move {
             let mut interval_timer = tokio::time::interval(interval);
+            let mut last_update = Instant::now();
 
             while running.load(std::sync::atomic::Ordering::SeqCst) {
                 interval_timer.tick().await;
 
                 // Update actor information from runtime
-                Self::update_actor_cache(&runtime, &actors).await;
+                Self::update_actor_cache(&runtime, &actors, last_update.elapsed()).await;
+                last_update = Instant::now();
             }
         });
 
         Ok(())
     }
 
-    /// Update actor cache with current runtime data
+    /// Update actor cache with current runtime data; message rates are
+    /// measured against the previous update, `elapsed` ago
     async fn update_actor_cache(
         runtime: &Arc<ReamRuntime>,
         actors: &Arc<RwLock<std::collections::HashMap<Pid, ActorInfo>>>,
+        elapsed: Duration,
     ) {
         let mut actor_cache = actors.write().unwrap();
-        actor_cache.clear();
+        let previous: std::collections::HashMap<Pid, u64> = actor_cache.drain()
+            .map(|(pid, actor)| (pid, actor.messages_processed))
+            .collect();
 
         // Get all processes from runtime
         let processes = runtime.list_process_handles();
@@ -293,15 +650,26 @@ impl DaemonManager {
         for (pid, process_handle) in processes {
             let process_info = process_handle.info();
             let mailbox = process_handle.mailbox();
-            let mailbox_size = mailbox.read().unwrap().len();
+            let (mailbox_size, recent_messages) = {
+                let mailbox = mailbox.read().unwrap();
+                (mailbox.len(), mailbox.recent().map(MessagePayload::summary).collect())
+            };
+
+            // Actors seen for the first time have no rate yet
+            let message_rate = match previous.get(&pid) {
+                Some(&before) if !elapsed.is_zero() => {
+                    process_info.messages_processed.saturating_sub(before) as f64 / elapsed.as_secs_f64()
+                }
+                _ => 0.0,
+            };
 
             let actor_info = ActorInfo {
                 pid,
                 status: Self::map_process_state(process_info.state),
                 mailbox_size,
                 memory_usage: process_info.memory_usage,
-                messages_processed: 0, // TODO: Add to ProcessInfo
-                message_rate: 0.0, // TODO: Calculate from stats
+                messages_processed: process_info.messages_processed,
+                message_rate,
                 cpu_time: process_info.cpu_time,
                 uptime: process_handle.uptime(),
                 last_activity: SystemTime::now(),
@@ -310,6 +678,10 @@ impl DaemonManager {
                 links: process_info.links,
                 monitors: process_info.monitors,
                 supervisor: process_info.parent,
+                recent_messages,
+                restarts: process_info.restarts,
+                domain: runtime.domain_of(pid),
+                ingress: runtime.ingress_status(pid),
             };
 
             actor_cache.insert(pid, actor_info);
@@ -356,6 +728,67 @@ impl DaemonManager {
         }
     }
     
+    /// Register a database (e.g. an ORM connection pool) for health checks
+    pub fn register_database(&self, name: impl Into<String>, database: Arc<dyn HealthCheck>) {
+        self.databases.write().unwrap().insert(name.into(), database);
+    }
+
+    /// Check the registered databases, in name order
+    pub async fn database_health(&self) -> Vec<DatabaseHealth> {
+        let databases: Vec<(String, Arc<dyn HealthCheck>)> = self.databases.read().unwrap()
+            .iter()
+            .map(|(name, database)| (name.clone(), database.clone()))
+            .collect();
+
+        let mut health = Vec::with_capacity(databases.len());
+        for (name, database) in databases {
+            health.push(DatabaseHealth {
+                name,
+                health: database.health_check().await,
+            });
+        }
+        health
+    }
+
+    /// Register a source (e.g. an ORM connection pool or a P2P node) for the metrics endpoint
+    pub fn register_metrics(&self, name: impl Into<String>, source: Arc<dyn MetricsSource>) {
+        self.metric_sources.write().unwrap().insert(name.into(), source);
+    }
+
+    /// Render runtime, actor and registered source metrics in Prometheus text format
+    pub async fn render_metrics(&self) -> String {
+        let mut out = MetricsWriter::new();
+        metrics::write_system(&mut out, &self.get_system_info(), &self.runtime.gc_stats());
+        metrics::write_actors(&mut out, &self.list_actors(false), self.config().metrics_actor_limit);
+
+        let sources: Vec<(String, Arc<dyn MetricsSource>)> = self.metric_sources.read().unwrap()
+            .iter()
+            .map(|(name, source)| (name.clone(), source.clone()))
+            .collect();
+        for (name, source) in sources {
+            source.collect(&name, &mut out).await;
+        }
+
+        out.render()
+    }
+
+    /// Evaluate TLisp code in the main context, or in the context of a live actor
+    pub async fn eval(&self, code: &str, actor: Option<&str>) -> ReamResult<EvalResult> {
+        let actor = match actor {
+            Some(pid_str) => {
+                let pid = Pid::from_string(pid_str)
+                    .map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid_str)))?;
+                if self.runtime.get_process(pid).is_none() {
+                    return Err(ReamError::Other(format!("Actor {} not found", pid_str)));
+                }
+                Some(pid)
+            }
+            None => None,
+        };
+
+        self.evaluator.eval(code, actor).await
+    }
+
     /// Get all actors
     pub fn list_actors(&self, _detailed: bool) -> Vec<ActorInfo> {
         let actors = self.actors.read().unwrap();
@@ -373,6 +806,373 @@ impl DaemonManager {
             .ok_or_else(|| ReamError::Other(format!("Actor {} not found", pid_str)))
     }
 
+    /// Check actors against the alerting rules and run the actions of
+    /// the rules that fire
+    pub async fn check_alerts(&self) {
+        let rules = self.config().alert_rules;
+        if rules.is_empty() {
+            return;
+        }
+        let actors = self.list_actors(false);
+        let fired = self.alerts.lock().unwrap().evaluate(&rules, &actors, Instant::now());
+
+        for alert in fired {
+            let Some(rule) = rules.iter().find(|rule| rule.name == alert.rule) else {
+                continue;
+            };
+            for action in &rule.actions {
+                self.run_alert_action(action, &alert).await;
+            }
+        }
+    }
+
+    async fn run_alert_action(&self, action: &AlertAction, alert: &Alert) {
+        let pid = alert.pid.to_string();
+        match action {
+            AlertAction::Log => {
+                tracing::warn!(rule = %alert.rule, pid = %pid, "Alert: {}", alert.message);
+            }
+            AlertAction::Notify => self.alerts.lock().unwrap().notify(alert.clone()),
+            AlertAction::Callback { function } => {
+                let code = format!("({} {:?} {:?})", function, pid, alert.rule);
+                let failure = match self.eval(&code, None).await {
+                    Ok(result) => result.value.err(),
+                    Err(e) => Some(e.to_string()),
+                };
+                if let Some(error) = failure {
+                    tracing::error!(rule = %alert.rule, function = %function, error = %error, "Alert callback failed");
+                }
+            }
+            AlertAction::Kill => {
+                if let Err(e) = self.kill_actor(&pid, &format!("alert {}", alert.rule)) {
+                    tracing::error!(rule = %alert.rule, pid = %pid, error = %e, "Alert failed to kill actor");
+                }
+            }
+        }
+    }
+
+    /// Add an alerting rule, replacing any rule with the same name
+    pub fn set_alert_rule(&self, rule: AlertRule) -> String {
+        let mut config = self.config.write().unwrap();
+        let message = match config.alert_rules.iter_mut().find(|existing| existing.name == rule.name) {
+            Some(existing) => {
+                *existing = rule;
+                format!("Alert rule {} replaced", existing.name)
+            }
+            None => {
+                let message = format!("Alert rule {} added", rule.name);
+                config.alert_rules.push(rule);
+                message
+            }
+        };
+        audit::record(AuditKind::ConfigChange, "alert_rules", &message);
+        message
+    }
+
+    /// Remove an alerting rule
+    pub fn remove_alert_rule(&self, name: &str) -> ReamResult<String> {
+        let mut config = self.config.write().unwrap();
+        let before = config.alert_rules.len();
+        config.alert_rules.retain(|rule| rule.name != name);
+        if config.alert_rules.len() == before {
+            return Err(ReamError::Other(format!("No alert rule named {}", name)));
+        }
+        let message = format!("Alert rule {} removed", name);
+        audit::record(AuditKind::ConfigChange, "alert_rules", &message);
+        Ok(message)
+    }
+
+    /// Notified alerts with an id greater than `after`
+    pub fn alerts(&self, after: u64) -> Vec<Alert> {
+        self.alerts.lock().unwrap().notifications(after)
+    }
+
+    /// Read the crash dump of an actor from the dump directory
+    pub fn crash_dump(&self, pid_str: &str) -> ReamResult<CrashDump> {
+        let pid = Pid::from_string(pid_str)
+            .map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid_str)))?;
+        CrashDump::load(&self.config().dump_dir, pid)
+    }
+
+    /// Start recording the messages delivered to an actor
+    pub fn start_recording(&self, pid_str: &str, limit: Option<usize>) -> ReamResult<String> {
+        let handle = self.process(pid_str)?;
+        let limit = limit.unwrap_or(DEFAULT_RECORDING_LIMIT);
+        handle.start_recording(limit);
+        Ok(format!("Recording actor {} (keeping the last {} messages)", pid_str, limit))
+    }
+
+    /// Stop recording an actor, returning the recording
+    pub fn stop_recording(&self, pid_str: &str) -> ReamResult<Recording> {
+        self.process(pid_str)?.stop_recording()
+            .ok_or_else(|| ReamError::Other(format!("Actor {} is not being recorded", pid_str)))
+    }
+
+    /// The recording of an actor so far
+    pub fn recording(&self, pid_str: &str) -> ReamResult<Recording> {
+        self.process(pid_str)?.recording()
+            .ok_or_else(|| ReamError::Other(format!("Actor {} is not being recorded", pid_str)))
+    }
+
+    /// Start sampling the allocations of an actor, one sample per
+    /// `interval` bytes
+    pub fn start_heap_profile(&self, pid_str: &str, interval: Option<usize>) -> ReamResult<String> {
+        if !profiler::is_installed() {
+            return Err(ReamError::Other("Heap profiling needs the profiling allocator, which this daemon was not built with".to_string()));
+        }
+        let pid = self.process(pid_str)?.pid();
+        let interval = interval.unwrap_or(profiler::DEFAULT_INTERVAL);
+        profiler::start(pid, interval);
+        Ok(format!("Profiling the heap of actor {} (sampling every {} bytes)", pid_str, interval))
+    }
+
+    /// Stop sampling an actor, returning its heap profile
+    pub fn stop_heap_profile(&self, pid_str: &str) -> ReamResult<HeapProfile> {
+        let pid = Pid::from_string(pid_str)
+            .map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid_str)))?;
+        profiler::stop(pid)
+            .ok_or_else(|| ReamError::Other(format!("Actor {} is not being profiled", pid_str)))
+    }
+
+    /// Start sampling the TLisp functions an actor runs, `frequency` times
+    /// a second
+    pub fn start_cpu_profile(&self, pid_str: &str, frequency: Option<u32>) -> ReamResult<String> {
+        let pid = self.process(pid_str)?.pid();
+        let frequency = frequency.unwrap_or(cpu_profiler::DEFAULT_FREQUENCY);
+        if frequency == 0 || frequency > cpu_profiler::MAX_FREQUENCY {
+            return Err(ReamError::Other(format!("Sampling frequency must be between 1 and {} Hz", cpu_profiler::MAX_FREQUENCY)));
+        }
+        cpu_profiler::start(pid, frequency);
+        Ok(format!("Profiling the CPU time of actor {} ({} Hz)", pid_str, frequency))
+    }
+
+    /// Stop sampling an actor, returning its CPU profile
+    pub fn stop_cpu_profile(&self, pid_str: &str) -> ReamResult<CpuProfile> {
+        let pid = Pid::from_string(pid_str)
+            .map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid_str)))?;
+        cpu_profiler::stop(pid)
+            .ok_or_else(|| ReamError::Other(format!("Actor {} is not being profiled", pid_str)))
+    }
+
+    /// Start recording the messages actors send each other, keeping at
+    /// most `limit`
+    pub fn start_flow_recording(&self, limit: Option<usize>) -> String {
+        let limit = limit.unwrap_or(flow::DEFAULT_FLOW_LIMIT);
+        flow::start(limit);
+        format!("Recording message flows (keeping the last {})", limit)
+    }
+
+    /// Stop recording messages, keeping those recorded for export
+    pub fn stop_flow_recording(&self) -> String {
+        let status = flow::stop();
+        format!("Stopped recording message flows ({} kept, {} dropped)", status.kept, status.dropped)
+    }
+
+    /// Recorded messages passing `filter`, oldest first
+    pub fn flows(&self, filter: &FlowFilter) -> Vec<FlowEvent> {
+        flow::query(filter)
+    }
+
+    /// The supervision hierarchy rooted at the runtime's root supervisor
+    pub fn supervision_tree(&self) -> SupervisionNode {
+        self.runtime.supervision_tree()
+    }
+
+    fn process(&self, pid_str: &str) -> ReamResult<crate::runtime::ProcessHandle> {
+        let pid = Pid::from_string(pid_str)
+            .map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid_str)))?;
+        self.runtime.get_process(pid)
+            .ok_or_else(|| ReamError::Other(format!("Actor {} not found", pid_str)))
+    }
+
+    /// Summaries of the isolation domains
+    pub fn list_domains(&self) -> Vec<DomainInfo> {
+        self.runtime.domains()
+    }
+
+    /// Create an isolation domain
+    pub fn create_domain(&self, name: &str, config: DomainConfig) -> ReamResult<String> {
+        self.runtime.create_domain(name, config)?;
+        let message = format!("Domain {} created", name);
+        audit::record(AuditKind::ConfigChange, "domains", &message);
+        Ok(message)
+    }
+
+    /// Suspend the actors of a domain
+    pub fn suspend_domain(&self, name: &str) -> ReamResult<String> {
+        self.runtime.suspend_domain(name)?;
+        Ok(format!("Domain {} suspended", name))
+    }
+
+    /// Resume the actors of a suspended domain
+    pub fn resume_domain(&self, name: &str) -> ReamResult<String> {
+        self.runtime.resume_domain(name)?;
+        Ok(format!("Domain {} resumed", name))
+    }
+
+    /// Terminate the actors of a domain and remove it
+    pub fn delete_domain(&self, name: &str) -> ReamResult<String> {
+        self.runtime.delete_domain(name)?;
+        let message = format!("Domain {} deleted", name);
+        audit::record(AuditKind::ConfigChange, "domains", &message);
+        Ok(message)
+    }
+
+    /// Audit log entries recorded at or after `since`
+    pub fn query_audit(&self, since: Option<SystemTime>) -> ReamResult<Vec<AuditEntry>> {
+        audit::query(since)
+    }
+
+    /// Deploy a serverless function from an encoded bytecode bundle or
+    /// TLisp source
+    pub fn deploy_function(&self, name: &str, code: FunctionSource, trigger: WakeTrigger, options: DeployOptions) -> ReamResult<String> {
+        let functions = {
+            let mut functions = self.functions.lock().unwrap();
+            match functions.as_ref() {
+                Some(functions) => functions.clone(),
+                None => {
+                    let config = ServerlessConfig {
+                        snapshot_dir: Some(self.config().snapshot_dir),
+                        ..ServerlessConfig::default()
+                    };
+                    functions.insert(Arc::new(ServerlessReamRuntime::new(config)?)).clone()
+                }
+            }
+        };
+        let message = match code {
+            FunctionSource::Bundle(bytes) => {
+                let bundle = BytecodeBundle::from_bytes(&bytes)?;
+                let message = format!("Function {} deployed from {} v{}", name, bundle.entry.name, bundle.entry.version);
+                functions.deploy_with(name, bundle, trigger, options)?;
+                message
+            }
+            FunctionSource::Tlisp(source) => {
+                functions.deploy_tlisp(name, &source, trigger, options)?;
+                format!("Function {} deployed from TLisp source", name)
+            }
+        };
+        audit::record(AuditKind::ConfigChange, "functions", &message);
+        Ok(message)
+    }
+
+    /// Invoke a serverless function, returning its response
+    pub fn invoke_function(&self, name: &str, payload: Vec<u8>) -> ReamResult<Vec<u8>> {
+        let functions = self.functions.lock().unwrap().clone()
+            .ok_or_else(|| ReamError::Other(format!("Function {} not found", name)))?;
+        Ok(functions.invoke(name, payload)?)
+    }
+
+    /// Serverless function whose HTTP trigger matches a request
+    pub fn route_function(&self, method: &str, path: &str) -> Option<String> {
+        let functions = self.functions.lock().unwrap().clone()?;
+        functions.route_http(method, path)
+    }
+
+    /// The serverless functions
+    pub fn list_functions(&self) -> Vec<FunctionInfo> {
+        let functions = self.functions.lock().unwrap().clone();
+        functions.map(|functions| functions.function_info()).unwrap_or_default()
+    }
+
+    /// Drop the serverless function instances idle past their idle timeout
+    pub fn scale_functions_to_zero(&self) {
+        let functions = self.functions.lock().unwrap().clone();
+        if let Some(functions) = functions {
+            let dropped = functions.scale_to_zero();
+            if dropped > 0 {
+                tracing::debug!(dropped, "Dropped idle function instances");
+            }
+        }
+    }
+
+    /// Load the scheduled jobs saved in `path`, saving changes back to it
+    pub fn open_schedules(&self, path: &Path) -> ReamResult<usize> {
+        let scheduler = CronScheduler::open(path)?;
+        let jobs = scheduler.jobs().count();
+        *self.cron.lock().unwrap() = scheduler;
+        Ok(jobs)
+    }
+
+    /// Schedule a recurring job, replacing any job with the same name
+    pub fn add_cron_job(&self, job: CronJob) -> ReamResult<String> {
+        let message = format!("Job {} scheduled at {}: {}", job.name, job.schedule, job.action);
+        self.cron.lock().unwrap().add(job, chrono::Utc::now())?;
+        audit::record(AuditKind::ConfigChange, "cron", &message);
+        Ok(message)
+    }
+
+    /// Remove a scheduled job
+    pub fn remove_cron_job(&self, name: &str) -> ReamResult<String> {
+        self.cron.lock().unwrap().remove(name)?;
+        let message = format!("Job {} removed", name);
+        audit::record(AuditKind::ConfigChange, "cron", &message);
+        Ok(message)
+    }
+
+    /// The scheduled jobs
+    pub fn list_cron_jobs(&self) -> Vec<CronJob> {
+        self.cron.lock().unwrap().jobs().cloned().collect()
+    }
+
+    /// The message schema registry, to register schemas with and to check
+    /// the messages of the cluster's network layer against
+    pub fn schemas(&self) -> Arc<SchemaRegistry> {
+        Arc::clone(&self.schemas)
+    }
+
+    /// The message schemas registered
+    pub fn list_schemas(&self) -> Vec<SchemaInfo> {
+        self.schemas.describe()
+    }
+
+    /// Checkpoint workflows in the event store at `path`
+    ///
+    /// Sagas are registered with the engine this returns; calling
+    /// `WorkflowEngine::recover` after registering them resumes the
+    /// workflows a previous run left unfinished.
+    pub fn open_workflows(&self, path: &Path) -> ReamResult<Arc<WorkflowEngine>> {
+        let engine = Arc::new(WorkflowEngine::new(Arc::new(SqliteEventStore::open(path)?)));
+        *self.workflows.write().unwrap() = engine.clone();
+        Ok(engine)
+    }
+
+    /// The engine running the daemon's workflows
+    pub fn workflows(&self) -> Arc<WorkflowEngine> {
+        self.workflows.read().unwrap().clone()
+    }
+
+    /// The workflows and their progress, oldest first
+    pub fn list_workflows(&self) -> ReamResult<Vec<WorkflowInfo>> {
+        Ok(self.workflows().list()?)
+    }
+
+    /// The progress of a workflow
+    pub fn inspect_workflow(&self, id: &str) -> ReamResult<WorkflowInfo> {
+        Ok(self.workflows().inspect(id)?)
+    }
+
+    /// Take the scheduled runs that are due
+    pub fn due_cron_jobs(&self) -> ReamResult<Vec<(String, JobAction)>> {
+        Ok(self.cron.lock().unwrap().due(chrono::Utc::now())?)
+    }
+
+    /// Run a scheduled job's action
+    pub async fn run_cron_job(self: Arc<Self>, action: JobAction) -> ReamResult<()> {
+        match action {
+            JobAction::Call { function } => {
+                let result = self.eval(&format!("({})", function), None).await?;
+                result.value.map(drop).map_err(ReamError::Other)
+            }
+            JobAction::Send { actor, message } => self.send_message(&actor, &message).map(drop),
+            JobAction::Invoke { function, payload } => {
+                tokio::task::spawn_blocking(move || self.invoke_function(&function, payload.into_bytes()))
+                    .await
+                    .map_err(|e| ReamError::Other(format!("Function task failed: {}", e)))?
+                    .map(drop)
+            }
+        }
+    }
+
     /// Kill an actor
     pub fn kill_actor(&self, pid_str: &str, reason: &str) -> ReamResult<String> {
         let pid = Pid::from_string(pid_str)
@@ -436,6 +1236,20 @@ impl DaemonManager {
         }
     }
 
+    /// Limit the messages an actor accepts, or lift its limit
+    pub fn set_rate_limit(&self, pid_str: &str, limit: Option<RateLimit>) -> ReamResult<String> {
+        let pid = Pid::from_string(pid_str)
+            .map_err(|_| ReamError::Other(format!("Invalid PID: {}", pid_str)))?;
+        let message = match &limit {
+            Some(limit) => format!("Actor {} limited to {}", pid_str, describe_rate_limit(limit)),
+            None => format!("Rate limit of actor {} lifted", pid_str),
+        };
+        self.runtime.set_rate_limit(pid, limit)
+            .map_err(|_| ReamError::Other(format!("Actor {} not found", pid_str)))?;
+        audit::record(AuditKind::ConfigChange, pid, &message);
+        Ok(message)
+    }
+
     /// Restart an actor
     pub fn restart_actor(&self, pid_str: &str) -> ReamResult<String> {
         let pid = Pid::from_string(pid_str)
@@ -474,3 +1288,128 @@ impl DaemonManager {
         }
     }
 }
+
+/// A rate limit as `10 msg/s, 2 msg/s per sender, burst 5 (queue)`
+pub fn describe_rate_limit(limit: &RateLimit) -> String {
+    let mut parts = Vec::new();
+    if let Some(rate) = limit.max_rate {
+        parts.push(format!("{} msg/s", rate));
+    }
+    if let Some(rate) = limit.max_rate_per_sender {
+        parts.push(format!("{} msg/s per sender", rate));
+    }
+    if let Some(burst) = limit.burst {
+        parts.push(format!("burst {}", burst));
+    }
+    if parts.is_empty() {
+        parts.push("no rate".to_string());
+    }
+    format!("{} ({})", parts.join(", "), limit.policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_keeps_overrides_the_file_did_not_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.toml");
+        std::fs::write(&path, "max_actors = 10\nmetrics_actor_limit = 5\n").unwrap();
+        let previous = DaemonConfig::load(&path).unwrap();
+        assert_eq!(previous.max_actors, 10);
+        assert_eq!(previous.config_file.as_deref(), Some(path.as_path()));
+
+        // As started with `--socket` and `--metrics-actor-limit` overrides
+        let mut config = previous.clone();
+        config.socket_path = PathBuf::from("/run/ream.sock");
+        config.metrics_actor_limit = 20;
+
+        std::fs::write(&path, "max_actors = 50\nmetrics_actor_limit = 5\npid_file = \"/run/ream.pid\"\n").unwrap();
+        let loaded = DaemonConfig::load(&path).unwrap();
+        let needs_restart = config.reload(&previous, &loaded);
+
+        assert_eq!(config.max_actors, 50);
+        assert_eq!(config.metrics_actor_limit, 20);
+        assert_eq!(config.socket_path, PathBuf::from("/run/ream.sock"));
+        assert_eq!(needs_restart, vec!["pid_file"]);
+
+        std::fs::write(&path, r#"
+max_actors = 50
+[[alert_rules]]
+name = "backlog"
+condition = { kind = "mailbox_depth", threshold = 1000, for_secs = 30 }
+actions = [{ kind = "log" }, { kind = "callback", function = "on-backlog" }]
+"#).unwrap();
+        let reloaded = DaemonConfig::load(&path).unwrap();
+        config.reload(&loaded, &reloaded);
+        assert_eq!(config.alert_rules.len(), 1);
+        assert_eq!(config.alert_rules[0].condition, monitor::AlertCondition::MailboxDepth { threshold: 1000, for_secs: 30 });
+        assert_eq!(config.alert_rules[0].actions[1], AlertAction::Callback { function: "on-backlog".to_string() });
+    }
+
+    #[tokio::test]
+    async fn test_actor_cache_accounts_messages() {
+        use crate::runtime::actor::CounterActor;
+
+        let runtime = Arc::new(ReamRuntime::new().unwrap());
+        let pid = runtime.spawn(CounterActor::new(Pid::new(), 0)).unwrap();
+        let handle = runtime.get_process(pid).unwrap();
+        let actors = Arc::new(RwLock::new(std::collections::HashMap::new()));
+        let deliver = |count: usize| {
+            for _ in 0..count {
+                handle.mailbox().write().unwrap().send(MessagePayload::Text("increment".to_string()));
+            }
+            handle.run_quantum().unwrap();
+        };
+
+        deliver(2);
+        DaemonManager::update_actor_cache(&runtime, &actors, Duration::from_secs(1)).await;
+        let actor = actors.read().unwrap()[&pid].clone();
+        assert_eq!(actor.messages_processed, 2);
+        assert_eq!(actor.message_rate, 0.0);
+
+        deliver(3);
+        DaemonManager::update_actor_cache(&runtime, &actors, Duration::from_millis(500)).await;
+        let actor = actors.read().unwrap()[&pid].clone();
+        assert_eq!(actor.messages_processed, 5);
+        assert_eq!(actor.message_rate, 6.0);
+    }
+
+    #[tokio::test]
+    async fn test_alert_rules_notify_ipc_clients() {
+        use crate::runtime::actor::CounterActor;
+        use monitor::AlertCondition;
+
+        let manager = DaemonManager::new(DaemonConfig::default()).unwrap();
+        let message = manager.set_alert_rule(AlertRule {
+            name: "backlog".to_string(),
+            condition: AlertCondition::MailboxDepth { threshold: 100, for_secs: 0 },
+            actions: vec![AlertAction::Log],
+        });
+        assert_eq!(message, "Alert rule backlog added");
+        let message = manager.set_alert_rule(AlertRule {
+            name: "backlog".to_string(),
+            condition: AlertCondition::MailboxDepth { threshold: 2, for_secs: 0 },
+            actions: vec![AlertAction::Notify],
+        });
+        assert_eq!(message, "Alert rule backlog replaced");
+        assert_eq!(manager.config().alert_rules.len(), 1);
+
+        let pid = manager.runtime.spawn(CounterActor::new(Pid::new(), 0)).unwrap();
+        let mailbox = manager.runtime.get_process(pid).unwrap().mailbox();
+        for _ in 0..3 {
+            mailbox.write().unwrap().send(MessagePayload::Text("increment".to_string()));
+        }
+        DaemonManager::update_actor_cache(&manager.runtime, &manager.actors, Duration::from_secs(1)).await;
+        manager.check_alerts().await;
+        manager.check_alerts().await;
+
+        let alerts = manager.alerts(0);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].rule.as_str(), alerts[0].pid), ("backlog", pid));
+
+        manager.remove_alert_rule("backlog").unwrap();
+        assert!(manager.remove_alert_rule("backlog").is_err());
+    }
+}
@@ -1,9 +1,10 @@
 //! Actor monitoring and data collection
 //!
 //! Provides comprehensive monitoring capabilities for actors including
-//! performance metrics, resource usage, and lifecycle tracking.
+//! performance metrics, resource usage, and lifecycle tracking, and the
+//! alerting engine that checks actors against configured rules.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant, SystemTime};
 use serde::{Serialize, Deserialize};
@@ -428,3 +429,257 @@ impl Default for SystemMetrics {
         }
     }
 }
+
+/// What an alert rule watches for in each actor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Mailbox holds more than `threshold` messages for `for_secs` seconds
+    MailboxDepth { threshold: usize, for_secs: u64 },
+    /// Actor restarted `count` times within `window_secs` seconds
+    CrashLoop { count: u32, window_secs: u64 },
+    /// Heap grows beyond `limit` bytes
+    HeapSize { limit: usize },
+}
+
+/// What to do when an alert fires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertAction {
+    /// Write a warning to the daemon log
+    Log,
+    /// Queue the alert for IPC clients polling with `GetAlerts`
+    Notify,
+    /// Call a TLisp function with the actor's PID and the rule name
+    Callback { function: String },
+    /// Kill the actor
+    Kill,
+}
+
+/// A named condition and the actions it fires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    /// Unique name, used to replace or remove the rule
+    pub name: String,
+    /// Condition checked against every actor
+    pub condition: AlertCondition,
+    /// Actions run when the condition starts to hold
+    pub actions: Vec<AlertAction>,
+}
+
+/// An alert that fired
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    /// Sequence number, increasing across all alerts
+    pub id: u64,
+    /// Rule that fired
+    pub rule: String,
+    /// Actor the rule fired for
+    pub pid: Pid,
+    /// What was observed
+    pub message: String,
+    /// When it fired
+    pub fired_at: SystemTime,
+}
+
+/// Number of alerts kept for IPC clients
+const ALERT_HISTORY: usize = 256;
+
+/// What a rule has observed about one actor
+#[derive(Debug, Default)]
+struct RuleTracking {
+    /// When the mailbox went over the threshold
+    over_since: Option<Instant>,
+    /// Restart count at the previous check
+    restarts_seen: Option<u32>,
+    /// Times of the restarts inside the window
+    restarts: VecDeque<Instant>,
+    /// Whether the rule has fired and the condition still holds
+    firing: bool,
+}
+
+/// Checks actors against alert rules. A rule fires once when its condition
+/// starts to hold for an actor and again only after the condition cleared.
+#[derive(Debug, Default)]
+pub struct AlertEngine {
+    tracking: HashMap<(String, Pid), RuleTracking>,
+    notifications: VecDeque<Alert>,
+    next_id: u64,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check every actor against every rule, returning the alerts that fired
+    pub fn evaluate(&mut self, rules: &[AlertRule], actors: &[ActorInfo], now: Instant) -> Vec<Alert> {
+        let mut fired = Vec::new();
+        for rule in rules {
+            for actor in actors.iter().filter(|actor| actor.status != ActorStatus::Terminated) {
+                let tracking = self.tracking.entry((rule.name.clone(), actor.pid)).or_default();
+                match Self::check(&rule.condition, actor, tracking, now) {
+                    Some(message) if !tracking.firing => {
+                        tracking.firing = true;
+                        self.next_id += 1;
+                        fired.push(Alert {
+                            id: self.next_id,
+                            rule: rule.name.clone(),
+                            pid: actor.pid,
+                            message,
+                            fired_at: SystemTime::now(),
+                        });
+                    }
+                    Some(_) => {}
+                    None => tracking.firing = false,
+                }
+            }
+        }
+
+        // Forget actors that are gone and rules that were removed
+        let live: HashSet<(&str, Pid)> = rules.iter()
+            .flat_map(|rule| actors.iter().map(move |actor| (rule.name.as_str(), actor.pid)))
+            .collect();
+        self.tracking.retain(|(rule, pid), _| live.contains(&(rule.as_str(), *pid)));
+        fired
+    }
+
+    /// Whether `condition` holds for `actor`, with a description if it does
+    fn check(condition: &AlertCondition, actor: &ActorInfo, tracking: &mut RuleTracking, now: Instant) -> Option<String> {
+        match *condition {
+            AlertCondition::MailboxDepth { threshold, for_secs } => {
+                if actor.mailbox_size <= threshold {
+                    tracking.over_since = None;
+                    return None;
+                }
+                let since = *tracking.over_since.get_or_insert(now);
+                (now.duration_since(since) >= Duration::from_secs(for_secs)).then(|| format!(
+                    "mailbox has held more than {} messages for {}s ({} now)",
+                    threshold, for_secs, actor.mailbox_size
+                ))
+            }
+            AlertCondition::CrashLoop { count, window_secs } => {
+                // The first check only establishes the baseline
+                let new_restarts = actor.restarts.saturating_sub(tracking.restarts_seen.unwrap_or(actor.restarts));
+                tracking.restarts_seen = Some(actor.restarts);
+                tracking.restarts.extend(std::iter::repeat_n(now, new_restarts as usize));
+                let window = Duration::from_secs(window_secs);
+                while tracking.restarts.front().is_some_and(|at| now.duration_since(*at) > window) {
+                    tracking.restarts.pop_front();
+                }
+                (tracking.restarts.len() >= count as usize).then(|| format!(
+                    "restarted {} times within {}s", tracking.restarts.len(), window_secs
+                ))
+            }
+            AlertCondition::HeapSize { limit } => (actor.memory_usage > limit).then(|| format!(
+                "heap is {} bytes, over the {} byte limit", actor.memory_usage, limit
+            )),
+        }
+    }
+
+    /// Queue an alert for IPC clients, dropping the oldest beyond the limit
+    pub fn notify(&mut self, alert: Alert) {
+        if self.notifications.len() == ALERT_HISTORY {
+            self.notifications.pop_front();
+        }
+        self.notifications.push_back(alert);
+    }
+
+    /// Queued alerts with an id greater than `after`, oldest first
+    pub fn notifications(&self, after: u64) -> Vec<Alert> {
+        self.notifications.iter().filter(|alert| alert.id > after).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actor(mailbox_size: usize, restarts: u32) -> ActorInfo {
+        ActorInfo {
+            pid: Pid::new(),
+            status: ActorStatus::Running,
+            mailbox_size,
+            memory_usage: 0,
+            messages_processed: 0,
+            message_rate: 0.0,
+            cpu_time: 0,
+            uptime: Duration::ZERO,
+            last_activity: SystemTime::now(),
+            actor_type: "ReamActor".to_string(),
+            state_description: String::new(),
+            links: Vec::new(),
+            monitors: Vec::new(),
+            supervisor: None,
+            recent_messages: Vec::new(),
+            restarts,
+            domain: None,
+            ingress: None,
+        }
+    }
+
+    fn rule(condition: AlertCondition) -> Vec<AlertRule> {
+        vec![AlertRule { name: "test".to_string(), condition, actions: vec![AlertAction::Log] }]
+    }
+
+    #[test]
+    fn test_mailbox_rule_waits_and_fires_once() {
+        let rules = rule(AlertCondition::MailboxDepth { threshold: 10, for_secs: 5 });
+        let mut engine = AlertEngine::new();
+        let start = Instant::now();
+        let mut actors = vec![actor(50, 0)];
+
+        assert!(engine.evaluate(&rules, &actors, start).is_empty());
+        assert!(engine.evaluate(&rules, &actors, start + Duration::from_secs(4)).is_empty());
+        let fired = engine.evaluate(&rules, &actors, start + Duration::from_secs(5));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].pid, actors[0].pid);
+        assert!(engine.evaluate(&rules, &actors, start + Duration::from_secs(6)).is_empty());
+
+        // Clears, then has to stay over the threshold for the full period again
+        actors[0].mailbox_size = 0;
+        assert!(engine.evaluate(&rules, &actors, start + Duration::from_secs(7)).is_empty());
+        actors[0].mailbox_size = 50;
+        assert!(engine.evaluate(&rules, &actors, start + Duration::from_secs(8)).is_empty());
+        assert_eq!(engine.evaluate(&rules, &actors, start + Duration::from_secs(13)).len(), 1);
+    }
+
+    #[test]
+    fn test_crash_loop_rule_counts_restarts_in_window() {
+        let rules = rule(AlertCondition::CrashLoop { count: 3, window_secs: 60 });
+        let mut engine = AlertEngine::new();
+        let start = Instant::now();
+        // Restarts from before the engine started watching don't count
+        let mut actors = vec![actor(0, 7)];
+
+        assert!(engine.evaluate(&rules, &actors, start).is_empty());
+        actors[0].restarts = 9;
+        assert!(engine.evaluate(&rules, &actors, start + Duration::from_secs(10)).is_empty());
+        actors[0].restarts = 10;
+        let fired = engine.evaluate(&rules, &actors, start + Duration::from_secs(20));
+        assert_eq!(fired.len(), 1);
+        assert!(fired[0].message.contains("3 times"), "{}", fired[0].message);
+
+        // The window slides past the first restarts
+        assert!(engine.evaluate(&rules, &actors, start + Duration::from_secs(71)).is_empty());
+        actors[0].restarts = 12;
+        assert_eq!(engine.evaluate(&rules, &actors, start + Duration::from_secs(72)).len(), 1);
+    }
+
+    #[test]
+    fn test_notifications_after_id() {
+        let rules = rule(AlertCondition::HeapSize { limit: 100 });
+        let mut engine = AlertEngine::new();
+        let mut actors = vec![actor(0, 0), actor(0, 0)];
+        actors[0].memory_usage = 200;
+        actors[1].memory_usage = 300;
+
+        for alert in engine.evaluate(&rules, &actors, Instant::now()) {
+            engine.notify(alert);
+        }
+        let all = engine.notifications(0);
+        assert_eq!(all.len(), 2);
+        assert_eq!(engine.notifications(all[0].id).len(), 1);
+        assert!(engine.notifications(all[1].id).is_empty());
+    }
+}
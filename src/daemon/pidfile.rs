@@ -0,0 +1,169 @@
+//! PID file handling
+//!
+//! The daemon keeps its PID file open and locked for as long as it runs
+//! (`flock` on Unix, a deny-write share mode on Windows). A PID file nobody
+//! holds was left behind by a daemon that died and is taken over; one that
+//! is held means a daemon is running.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{ReamError, ReamResult};
+
+/// A locked PID file, removed when dropped
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    /// Holds the lock
+    _file: File,
+}
+
+impl PidFile {
+    /// Write this process's PID to `path`, failing if a live daemon holds it
+    pub fn acquire(path: &Path) -> ReamResult<PidFile> {
+        let mut file = match platform::open_locked(path) {
+            Ok(Some(file)) => file,
+            Ok(None) => {
+                return Err(ReamError::Other(match read_pid(path) {
+                    Some(pid) => format!("Daemon is already running (PID {})", pid),
+                    None => format!("Daemon is already running ({} is locked)", path.display()),
+                }));
+            }
+            Err(e) => {
+                return Err(ReamError::Other(format!("Failed to open PID file {}: {}", path.display(), e)));
+            }
+        };
+
+        let mut previous = String::new();
+        file.read_to_string(&mut previous).map_err(ReamError::Io)?;
+        if let Ok(pid) = previous.trim().parse::<u32>() {
+            tracing::warn!(pid, path = %path.display(), "Replacing stale PID file");
+        }
+
+        file.set_len(0).map_err(ReamError::Io)?;
+        file.seek(SeekFrom::Start(0)).map_err(ReamError::Io)?;
+        writeln!(file, "{}", std::process::id()).map_err(ReamError::Io)?;
+        file.sync_all().map_err(ReamError::Io)?;
+
+        Ok(PidFile { path: path.to_path_buf(), _file: file })
+    }
+
+    /// PID of the live daemon holding `path`, if any
+    pub fn running(path: &Path) -> Option<u32> {
+        if !path.exists() || !platform::is_locked(path) {
+            return None;
+        }
+        read_pid(path)
+    }
+
+    /// Path of the PID file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::*;
+    use std::os::unix::io::AsRawFd;
+    use nix::errno::Errno;
+    use nix::fcntl::{flock, FlockArg};
+
+    /// Try to take the lock without blocking; `false` if someone else holds it
+    fn try_lock(file: &File) -> std::io::Result<bool> {
+        match flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+            Ok(()) => Ok(true),
+            Err(Errno::EWOULDBLOCK) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn open_locked(path: &Path) -> std::io::Result<Option<File>> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        Ok(if try_lock(&file)? { Some(file) } else { None })
+    }
+
+    pub fn is_locked(path: &Path) -> bool {
+        // The probe's lock is released when the file is closed again
+        match File::open(path) {
+            Ok(file) => matches!(try_lock(&file), Ok(false)),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::os::windows::fs::OpenOptionsExt;
+
+    /// Others may read the PID but not open the file for writing
+    const FILE_SHARE_READ: u32 = 0x1;
+    /// `ERROR_SHARING_VIOLATION`: another process holds the file
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+
+    fn open(path: &Path, create: bool) -> std::io::Result<Option<File>> {
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(create)
+            .truncate(false)
+            .share_mode(FILE_SHARE_READ)
+            .open(path)
+        {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn open_locked(path: &Path) -> std::io::Result<Option<File>> {
+        open(path, true)
+    }
+
+    pub fn is_locked(path: &Path) -> bool {
+        matches!(open(path, false), Ok(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ream.pid");
+
+        let pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(PidFile::running(&path), Some(std::process::id()));
+        let err = PidFile::acquire(&path).unwrap_err().to_string();
+        assert!(err.contains("already running"), "{}", err);
+
+        drop(pid_file);
+        assert!(!path.exists());
+        assert_eq!(PidFile::running(&path), None);
+    }
+
+    #[test]
+    fn test_stale_pid_file_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ream.pid");
+        std::fs::write(&path, "4194304999\n").unwrap();
+
+        assert_eq!(PidFile::running(&path), None);
+        let _pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("{}\n", std::process::id()));
+    }
+}
@@ -6,17 +6,34 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::fs;
-use std::process;
 use tokio::time::interval;
+use tracing::{debug, error, info, warn};
 
 #[cfg(unix)]
-use daemonize::Daemonize;
+use daemonize::{Daemonize, Outcome};
 
+use crate::config::{self, ReamSettings};
 use crate::error::{ReamResult, ReamError};
+use crate::logging;
 use crate::runtime::ReamRuntime;
+use crate::runtime::crash;
+use crate::security::{audit, policy, secrets, AuditKind, AuditLog, CapabilityPolicy, SecretStore};
+use zeroize::Zeroizing;
 
 use super::{DaemonConfig, DaemonManager, ActorInfo, ActorStatus};
 use super::ipc::IpcServer;
+use super::metrics;
+use super::pidfile::PidFile;
+use super::signals::{DaemonSignal, Signals};
+
+/// Which side of the detach the caller is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detached {
+    /// The original process, which should report and exit
+    Parent,
+    /// The background daemon, which should go on to start the runtime
+    Daemon,
+}
 
 /// Daemon runtime implementation
 pub struct DaemonRuntime {
@@ -26,6 +43,10 @@ pub struct DaemonRuntime {
     manager: Arc<DaemonManager>,
     /// IPC server
     ipc_server: Option<IpcServer>,
+    /// PID file, held while the daemon runs
+    pid_file: Option<PidFile>,
+    /// Config file contents as last loaded, to see what a reload changes
+    file_config: Option<ReamSettings>,
     /// Running flag
     running: Arc<std::sync::atomic::AtomicBool>,
 }
@@ -36,49 +57,101 @@ impl DaemonRuntime {
         let manager = Arc::new(DaemonManager::new(config.clone())?);
         let ipc_server = Some(IpcServer::new(config.socket_path.clone(), manager.clone()));
         let running = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let file_config = config.config_file.as_deref().map(Self::load_settings).transpose()?;
         
         Ok(DaemonRuntime {
             config,
             manager,
             ipc_server,
+            pid_file: None,
+            file_config,
             running,
         })
     }
     
-    /// Start the daemon
-    pub async fn start(&mut self, program_file: PathBuf) -> ReamResult<()> {
-        // Check if daemon is already running
-        if self.is_daemon_running()? {
-            return Err(ReamError::Other("Daemon is already running".to_string()));
+    /// Detach from the terminal unless `config.foreground` is set.
+    ///
+    /// On Unix this double-forks into a new session with `/` as working
+    /// directory, so every path in `config` must be absolute. On Windows the
+    /// daemon is relaunched as a detached `--foreground` process. Forking
+    /// only keeps the calling thread, so this must run before the async
+    /// runtime is created.
+    pub fn daemonize(config: &DaemonConfig) -> ReamResult<Detached> {
+        if config.foreground {
+            return Ok(Detached::Daemon);
         }
-        
-        // Daemonize if not in foreground mode
-        if !self.config.foreground {
-            self.daemonize()?;
+        if let Some(pid) = PidFile::running(&config.pid_file) {
+            return Err(ReamError::Other(format!("Daemon is already running (PID {})", pid)));
         }
-        
-        // Write PID file
-        self.write_pid_file()?;
+
+        #[cfg(unix)]
+        {
+            match Daemonize::new().working_directory("/").umask(0o027).execute() {
+                Outcome::Parent(Ok(_)) => Ok(Detached::Parent),
+                Outcome::Child(Ok(_)) => Ok(Detached::Daemon),
+                Outcome::Parent(Err(e)) | Outcome::Child(Err(e)) => {
+                    Err(ReamError::Other(format!("Failed to daemonize: {}", e)))
+                }
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            const DETACHED_PROCESS: u32 = 0x0000_0008;
+            const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+            let exe = std::env::current_exe().map_err(ReamError::Io)?;
+            std::process::Command::new(exe)
+                .args(std::env::args_os().skip(1))
+                .arg("--foreground")
+                .arg("--logfile")
+                .arg(&config.log_file)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP)
+                .spawn()
+                .map_err(|e| ReamError::Other(format!("Failed to start background daemon: {}", e)))?;
+            Ok(Detached::Parent)
+        }
+    }
+
+    /// Start the daemon and serve until it is asked to shut down
+    pub async fn start(&mut self, program_file: PathBuf) -> ReamResult<()> {
+        // Claim the PID file; fails if another daemon holds it
+        self.pid_file = Some(PidFile::acquire(&self.config.pid_file)?);
         
         // Set running flag
         self.running.store(true, std::sync::atomic::Ordering::SeqCst);
+
+        crash::set_dump_dir(Some(self.config.dump_dir.clone()));
+        self.open_audit_log()?;
+        self.install_policy()?;
+        self.open_secrets()?;
+        self.open_schedules()?;
+        self.open_workflows()?;
         
         // Start IPC server
-        if let Some(mut ipc_server) = self.ipc_server.take() {
-            let ipc_handle = tokio::spawn(async move {
-                if let Err(e) = ipc_server.start().await {
-                    eprintln!("IPC server error: {}", e);
-                }
-            });
+        if let Some(ipc_server) = self.ipc_server.as_mut() {
+            ipc_server.start().await?;
         }
         
-        // Load and run the TLisp program
-        self.load_program(program_file).await?;
+        // Serve metrics
+        if let Some(addr) = self.config.metrics_addr {
+            let (addr, server) = metrics::bind(self.manager.clone(), addr)?;
+            info!(%addr, "Serving metrics");
+            tokio::spawn(server);
+        }
 
-        // Start monitoring loop and wait for it
-        self.run_main_loop().await?;
+        // Load and run the TLisp program, then serve until shutdown
+        let result = match self.load_program(program_file).await {
+            Ok(()) => self.run_main_loop().await,
+            Err(e) => Err(e),
+        };
 
-        Ok(())
+        self.stop().await?;
+        result
     }
     
     /// Stop the daemon
@@ -93,84 +166,20 @@ impl DaemonRuntime {
         
         // Stop daemon manager
         self.manager.stop().await?;
+
+        // Flush spans still waiting for export
+        let _ = tokio::task::spawn_blocking(crate::telemetry::shutdown).await;
         
-        // Remove PID file
-        self.remove_pid_file()?;
+        // Release and remove the PID file
+        self.pid_file = None;
         
+        info!("Daemon stopped");
         Ok(())
     }
     
     /// Check if daemon is running
     pub fn is_daemon_running(&self) -> ReamResult<bool> {
-        if !self.config.pid_file.exists() {
-            return Ok(false);
-        }
-        
-        let pid_str = fs::read_to_string(&self.config.pid_file)
-            .map_err(|e| ReamError::Io(e))?;
-        
-        let pid: u32 = pid_str.trim().parse()
-            .map_err(|e| ReamError::Other(format!("Invalid PID in file: {}", e)))?;
-        
-        // Check if process is still running
-        #[cfg(unix)]
-        {
-            use nix::sys::signal::{kill, Signal};
-            use nix::unistd::Pid;
-            
-            match kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
-                Ok(_) => Ok(true),
-                Err(nix::errno::Errno::ESRCH) => Ok(false), // Process not found
-                Err(nix::errno::Errno::EPERM) => Ok(true),  // Process exists but no permission
-                Err(_) => Ok(false),
-            }
-        }
-        
-        #[cfg(not(unix))]
-        {
-            // On non-Unix systems, just check if PID file exists
-            Ok(true)
-        }
-    }
-    
-    /// Daemonize the process
-    fn daemonize(&self) -> ReamResult<()> {
-        #[cfg(unix)]
-        {
-            let daemonize = Daemonize::new()
-                .pid_file(&self.config.pid_file)
-                .working_directory("/tmp")
-                .user("nobody")
-                .group("daemon")
-                .umask(0o027);
-
-            match daemonize.start() {
-                Ok(_) => Ok(()),
-                Err(e) => Err(ReamError::Other(format!("Failed to daemonize: {}", e))),
-            }
-        }
-
-        #[cfg(not(unix))]
-        {
-            Err(ReamError::NotImplemented("Daemonization not supported on Windows".to_string()))
-        }
-    }
-    
-    /// Write PID file
-    fn write_pid_file(&self) -> ReamResult<()> {
-        let pid = process::id();
-        fs::write(&self.config.pid_file, pid.to_string())
-            .map_err(|e| ReamError::Io(e))?;
-        Ok(())
-    }
-    
-    /// Remove PID file
-    fn remove_pid_file(&self) -> ReamResult<()> {
-        if self.config.pid_file.exists() {
-            fs::remove_file(&self.config.pid_file)
-                .map_err(|e| ReamError::Io(e))?;
-        }
-        Ok(())
+        Ok(PidFile::running(&self.config.pid_file).is_some())
     }
     
     /// Load and execute TLisp program
@@ -179,39 +188,60 @@ impl DaemonRuntime {
         let program_content = fs::read_to_string(&program_file)
             .map_err(|e| ReamError::Io(e))?;
 
-        println!("Loading TLisp program: {}", program_file.display());
-        println!("Program content length: {} bytes", program_content.len());
-
-        // Create TLisp runtime with REAM integration
-        let mut tlisp_runtime = crate::tlisp::TlispRuntime::new();
+        info!(program = %program_file.display(), bytes = program_content.len(), "Loading TLisp program");
 
-        // Execute the program
-        match tlisp_runtime.eval(&program_content) {
-            Ok(result) => {
-                println!("TLisp program executed successfully");
-                println!("Result: {:?}", result);
+        // Evaluate in the daemon's main context so attached REPLs see its definitions
+        let result = self.manager.eval(&program_content, None).await?;
+        print!("{}", result.output);
+        match result.value {
+            Ok(value) => {
+                info!(result = %value, "TLisp program executed");
             }
             Err(e) => {
-                println!("TLisp program execution failed: {}", e);
+                error!(error = %e, "TLisp program execution failed");
                 return Err(ReamError::Other(format!("TLisp execution failed: {}", e)));
             }
         }
 
         // The program should have spawned actors that are now running in the REAM runtime
         let process_count = self.manager.runtime.list_processes().len();
-        println!("TLisp program spawned {} processes", process_count);
+        info!(processes = process_count, "TLisp program spawned processes");
 
         Ok(())
     }
     
-    /// Run the main daemon loop
-    async fn run_main_loop(&self) -> ReamResult<()> {
-        println!("Daemon entering main loop...");
+    /// Run the main daemon loop until a signal or client asks it to stop
+    async fn run_main_loop(&mut self) -> ReamResult<()> {
+        info!("Daemon entering main loop");
 
+        let mut signals = Signals::new()?;
         let mut interval = interval(self.config.monitor_interval);
 
         while self.running.load(std::sync::atomic::Ordering::SeqCst) {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {}
+                signal = signals.recv() => match signal {
+                    DaemonSignal::Shutdown => {
+                        info!("Received shutdown signal");
+                        break;
+                    }
+                    DaemonSignal::Reload => {
+                        self.reload();
+                        interval = tokio::time::interval(self.config.monitor_interval);
+                        continue;
+                    }
+                },
+                _ = self.manager.shutdown_requested() => {
+                    info!("Shutdown requested over IPC");
+                    break;
+                }
+                _ = self.manager.reload_requested() => {
+                    info!("Reload requested over IPC");
+                    self.reload();
+                    interval = tokio::time::interval(self.config.monitor_interval);
+                    continue;
+                }
+            }
 
             // Update actor information
             Self::update_actor_info(&self.manager).await;
@@ -222,17 +252,159 @@ impl DaemonRuntime {
             // Garbage collection if needed
             Self::perform_gc_if_needed(&self.manager).await;
 
+            // Scale idle serverless functions to zero
+            self.manager.scale_functions_to_zero();
+
+            // Start the scheduled jobs that are due
+            Self::run_cron_jobs(&self.manager);
+
             // Print status periodically (every 10 seconds)
             if interval.period().as_secs() >= 10 {
                 let process_count = self.manager.runtime.list_processes().len();
-                println!("Daemon running: {} processes active", process_count);
+                debug!(processes = process_count, "Daemon running");
             }
         }
 
-        println!("Daemon main loop exiting...");
+        info!("Daemon main loop exiting");
         Ok(())
     }
+
+    /// Load the configuration file with the environment and command line
+    /// overrides this process started with
+    fn load_settings(path: &std::path::Path) -> ReamResult<ReamSettings> {
+        ReamSettings::load(Some(path), &config::current().overrides)
+    }
+
+    /// Re-read the configuration file and reopen the log file
+    fn reload(&mut self) {
+        if let (Some(path), Some(previous)) = (self.config.config_file.clone(), self.file_config.take()) {
+            match Self::load_settings(&path) {
+                Ok(loaded) => {
+                    // Start from the manager's copy, which has the rules edited over IPC
+                    let mut settings = ReamSettings { daemon: self.manager.config(), ..config::current() };
+                    let filter = settings.log.filter.clone();
+                    for setting in settings.reload(&previous, &loaded) {
+                        warn!(setting, "Changed setting takes effect after a restart");
+                    }
+                    if settings.log.filter != filter {
+                        if let Err(e) = logging::set_filter(&settings.log.filter) {
+                            error!(error = %e, "Failed to change log filter; keeping the current one");
+                        }
+                    }
+                    self.manager.runtime.set_gc_threshold(settings.runtime.gc_threshold);
+                    self.config = settings.daemon.clone();
+                    self.file_config = Some(loaded);
+                    self.manager.set_config(self.config.clone());
+                    config::install(settings);
+                    crash::set_dump_dir(Some(self.config.dump_dir.clone()));
+                    if let Err(e) = self.open_audit_log() {
+                        error!(error = %e, "Failed to reopen audit log; keeping the current one");
+                    }
+                    if let Err(e) = self.install_policy() {
+                        error!(error = %e, "Failed to reload capability policy; keeping the current one");
+                    }
+                    if let Err(e) = self.open_secrets() {
+                        error!(error = %e, "Failed to reopen secrets file; keeping the current one");
+                    }
+                    info!(config = %path.display(), "Configuration reloaded");
+                    audit::record(AuditKind::ConfigChange, path.display(), "Configuration reloaded");
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to reload configuration; keeping the current one");
+                    self.file_config = Some(previous);
+                }
+            }
+        }
+
+        // Lets external log rotation move the old file away
+        if !self.config.foreground {
+            if let Err(e) = logging::log_to_file(&self.config.log_file, self.config.log_rotation) {
+                error!(error = %e, "Failed to reopen log file");
+            }
+        }
+    }
     
+    /// Check the builtins of the daemon's programs against the configured
+    /// capability policy, re-reading the policy file
+    fn install_policy(&self) -> ReamResult<()> {
+        let loaded = match &self.config.security_policy {
+            Some(path) => {
+                let loaded = CapabilityPolicy::load(path)?;
+                info!(policy = %path.display(), "Capability policy installed");
+                Some(loaded)
+            }
+            None => None,
+        };
+        policy::install(loaded);
+        Ok(())
+    }
+
+    /// Record audit events to the configured log, continuing its chain
+    fn open_audit_log(&self) -> ReamResult<()> {
+        let log = match &self.config.audit_log {
+            Some(path) => {
+                let log = AuditLog::open(path)?;
+                info!(audit_log = %path.display(), "Audit log opened");
+                Some(log)
+            }
+            None => None,
+        };
+        audit::install(log);
+        Ok(())
+    }
+
+    /// Serve the configured secrets file to the daemon's programs
+    fn open_secrets(&self) -> ReamResult<()> {
+        let store = match &self.config.secrets_file {
+            Some(path) => {
+                let passphrase = Zeroizing::new(std::env::var(secrets::MASTER_KEY_ENV).map_err(|_| {
+                    ReamError::Other(format!("{} must be set to open {}", secrets::MASTER_KEY_ENV, path.display()))
+                })?);
+                let store = SecretStore::open(path, &passphrase)?;
+                info!(secrets = %path.display(), "Secrets file opened");
+                Some(store)
+            }
+            None => None,
+        };
+        secrets::install(store);
+        Ok(())
+    }
+
+    /// Load the jobs saved in the schedule file
+    fn open_schedules(&self) -> ReamResult<()> {
+        let jobs = self.manager.open_schedules(&self.config.schedule_file)?;
+        info!(schedule_file = %self.config.schedule_file.display(), jobs, "Scheduled jobs loaded");
+        Ok(())
+    }
+
+    /// Checkpoint workflows in the workflow store
+    fn open_workflows(&self) -> ReamResult<()> {
+        self.manager.open_workflows(&self.config.workflow_store)?;
+        info!(workflow_store = %self.config.workflow_store.display(), "Workflow store opened");
+        Ok(())
+    }
+
+    /// Start the scheduled jobs that are due, each in a task of its own so
+    /// slow jobs do not hold up monitoring
+    fn run_cron_jobs(manager: &Arc<DaemonManager>) {
+        let due = match manager.due_cron_jobs() {
+            Ok(due) => due,
+            Err(e) => {
+                error!(error = %e, "Failed to check scheduled jobs");
+                return;
+            }
+        };
+        for (name, action) in due {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                debug!(job = %name, %action, "Running scheduled job");
+                if let Err(e) = manager.run_cron_job(action).await {
+                    warn!(job = %name, error = %e, "Scheduled job failed");
+                }
+            });
+        }
+    }
+
     /// Update actor information
     async fn update_actor_info(manager: &Arc<DaemonManager>) {
         // TODO: Collect actor information from runtime
@@ -241,8 +413,7 @@ impl DaemonRuntime {
     
     /// Perform health checks on actors
     async fn perform_health_checks(manager: &Arc<DaemonManager>) {
-        // TODO: Check for crashed or unresponsive actors
-        // Restart actors if needed based on supervision strategy
+        manager.check_alerts().await;
     }
     
     /// Perform garbage collection if needed
@@ -274,15 +445,8 @@ impl DaemonRuntime {
     
     /// Force kill daemon
     pub fn force_kill(&self) -> ReamResult<()> {
-        if !self.config.pid_file.exists() {
-            return Err(ReamError::Other("PID file not found".to_string()));
-        }
-        
-        let pid_str = fs::read_to_string(&self.config.pid_file)
-            .map_err(|e| ReamError::Io(e))?;
-        
-        let _pid: u32 = pid_str.trim().parse()
-            .map_err(|e| ReamError::Other(format!("Invalid PID in file: {}", e)))?;
+        let pid = PidFile::running(&self.config.pid_file)
+            .ok_or_else(|| ReamError::Other("Daemon is not running".to_string()))?;
         
         #[cfg(unix)]
         {
@@ -295,11 +459,19 @@ impl DaemonRuntime {
         
         #[cfg(not(unix))]
         {
-            return Err(ReamError::Other("Force kill not supported on this platform".to_string()));
+            let status = std::process::Command::new("taskkill")
+                .args(["/F", "/PID", &pid.to_string()])
+                .status()
+                .map_err(|e| ReamError::Other(format!("Failed to kill process: {}", e)))?;
+            if !status.success() {
+                return Err(ReamError::Other(format!("Failed to kill process {}", pid)));
+            }
         }
         
-        // Remove PID file
-        self.remove_pid_file()?;
+        // The killed daemon could not remove its PID file
+        if self.config.pid_file.exists() {
+            fs::remove_file(&self.config.pid_file).map_err(ReamError::Io)?;
+        }
         
         Ok(())
     }
@@ -0,0 +1,81 @@
+//! Signal handling for the daemon
+//!
+//! SIGTERM and SIGINT ask the daemon to shut down gracefully and SIGHUP to
+//! reload its configuration. On Windows, console close and Ctrl+C/Ctrl+Break
+//! events shut it down; there is no reload signal.
+
+use crate::error::{ReamError, ReamResult};
+
+/// What a received signal asks the daemon to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonSignal {
+    /// Stop serving, clean up and exit
+    Shutdown,
+    /// Re-read the configuration file and reopen the log file
+    Reload,
+}
+
+fn install_error(e: std::io::Error) -> ReamError {
+    ReamError::Other(format!("Failed to install signal handler: {}", e))
+}
+
+#[cfg(unix)]
+pub struct Signals {
+    terminate: tokio::signal::unix::Signal,
+    interrupt: tokio::signal::unix::Signal,
+    hangup: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    /// Start listening; must be called on the async runtime
+    pub fn new() -> ReamResult<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Signals {
+            terminate: signal(SignalKind::terminate()).map_err(install_error)?,
+            interrupt: signal(SignalKind::interrupt()).map_err(install_error)?,
+            hangup: signal(SignalKind::hangup()).map_err(install_error)?,
+        })
+    }
+
+    /// Wait for the next signal
+    pub async fn recv(&mut self) -> DaemonSignal {
+        tokio::select! {
+            _ = self.terminate.recv() => DaemonSignal::Shutdown,
+            _ = self.interrupt.recv() => DaemonSignal::Shutdown,
+            _ = self.hangup.recv() => DaemonSignal::Reload,
+        }
+    }
+}
+
+#[cfg(windows)]
+pub struct Signals {
+    ctrl_c: tokio::signal::windows::CtrlC,
+    ctrl_break: tokio::signal::windows::CtrlBreak,
+    ctrl_close: tokio::signal::windows::CtrlClose,
+    ctrl_shutdown: tokio::signal::windows::CtrlShutdown,
+}
+
+#[cfg(windows)]
+impl Signals {
+    /// Start listening; must be called on the async runtime
+    pub fn new() -> ReamResult<Self> {
+        use tokio::signal::windows;
+        Ok(Signals {
+            ctrl_c: windows::ctrl_c().map_err(install_error)?,
+            ctrl_break: windows::ctrl_break().map_err(install_error)?,
+            ctrl_close: windows::ctrl_close().map_err(install_error)?,
+            ctrl_shutdown: windows::ctrl_shutdown().map_err(install_error)?,
+        })
+    }
+
+    /// Wait for the next signal
+    pub async fn recv(&mut self) -> DaemonSignal {
+        tokio::select! {
+            _ = self.ctrl_c.recv() => DaemonSignal::Shutdown,
+            _ = self.ctrl_break.recv() => DaemonSignal::Shutdown,
+            _ = self.ctrl_close.recv() => DaemonSignal::Shutdown,
+            _ = self.ctrl_shutdown.recv() => DaemonSignal::Shutdown,
+        }
+    }
+}
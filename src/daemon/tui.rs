@@ -2,8 +2,13 @@
 //!
 //! Provides a professional real-time monitoring dashboard using ratatui
 //! for visualizing actor states, system metrics, and performance data.
+//! The actors tab is a sortable, filterable table in the style of `htop`;
+//! Enter drills into an actor to show its links, monitors and recent
+//! messages, and `p`/`u`/`k` suspend, resume and kill the selected actor.
+//! The tree tab draws the supervision hierarchy with restart counts.
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use std::io;
@@ -19,7 +24,7 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{
-        Block, Borders, Gauge, List, ListItem, ListState,
+        Block, Borders, Cell, Gauge, List, ListItem, ListState,
         Paragraph, Row, Sparkline, Table, TableState, Tabs, Wrap,
     },
     Frame, Terminal,
@@ -27,6 +32,7 @@ use ratatui::{
 use tui_input::{backend::crossterm::EventHandler, Input};
 
 use crate::error::{ReamResult, ReamError};
+use crate::runtime::SupervisionNode;
 use super::{ActorInfo, ActorStatus, SystemInfo};
 use super::ipc::IpcClient;
 use super::monitor::SystemMetrics;
@@ -37,17 +43,27 @@ pub struct TuiApp {
     client: IpcClient,
     /// Current tab index
     current_tab: usize,
-    /// Actor list state
-    actor_list_state: ListState,
     /// Actor table state
     actor_table_state: TableState,
+    /// Column the actor table is sorted by
+    sort_key: SortKey,
+    /// Sort the actor table largest first
+    sort_descending: bool,
+    /// Applied actor filter
+    filter: String,
+    /// Actor shown in the drill-down view, with its selected link
+    detail: Option<(ActorInfo, ListState)>,
+    /// System-wide samples taken on each refresh, oldest first
+    history: VecDeque<HistorySample>,
     /// System information
     system_info: Option<SystemInfo>,
     /// Actor information
     actors: Vec<ActorInfo>,
     /// System metrics
     system_metrics: Option<SystemMetrics>,
-    /// Selected actor for details
+    /// Supervision hierarchy
+    supervision_tree: Option<SupervisionNode>,
+    /// PID of the selected actor, kept across refreshes and re-sorts
     selected_actor: Option<String>,
     /// Refresh interval
     refresh_interval: Duration,
@@ -71,26 +87,115 @@ pub enum InputMode {
     Normal,
     Command,
     Filter,
+    /// Waiting for `y` to kill the actor with this PID
+    ConfirmKill(String),
+}
+
+/// Column the actor table is sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Pid,
+    Cpu,
+    Mailbox,
+    Memory,
+    MessageRate,
+}
+
+impl SortKey {
+    /// The next column, cycling back to the first
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Pid => SortKey::Cpu,
+            SortKey::Cpu => SortKey::Mailbox,
+            SortKey::Mailbox => SortKey::Memory,
+            SortKey::Memory => SortKey::MessageRate,
+            SortKey::MessageRate => SortKey::Pid,
+        }
+    }
+
+    /// Column header
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Pid => "PID",
+            SortKey::Cpu => "CPU",
+            SortKey::Mailbox => "Mailbox",
+            SortKey::Memory => "Memory",
+            SortKey::MessageRate => "Msg/s",
+        }
+    }
+
+    fn compare(self, a: &ActorInfo, b: &ActorInfo) -> Ordering {
+        match self {
+            SortKey::Pid => a.pid.0.cmp(&b.pid.0),
+            SortKey::Cpu => a.cpu_time.cmp(&b.cpu_time),
+            SortKey::Mailbox => a.mailbox_size.cmp(&b.mailbox_size),
+            SortKey::Memory => a.memory_usage.cmp(&b.memory_usage),
+            SortKey::MessageRate => a.message_rate.total_cmp(&b.message_rate),
+        }
+    }
+}
+
+/// System-wide figures recorded for the graphs
+#[derive(Debug, Clone, Copy)]
+struct HistorySample {
+    message_rate: u64,
+    memory: u64,
+    actors: u64,
 }
 
 /// Tab names
-const TAB_NAMES: &[&str] = &["Overview", "Actors", "Performance", "Logs", "Commands"];
+const TAB_NAMES: &[&str] = &["Overview", "Actors", "Tree", "Performance", "Logs", "Commands"];
+
+/// Index of the actors tab
+const ACTORS_TAB: usize = 1;
+
+/// Number of refreshes kept for the system graphs
+const HISTORY_LEN: usize = 240;
+
+/// Actors matching `filter`, ordered by `key`; ties keep PID order so rows
+/// don't jump around between refreshes
+pub fn visible_actors<'a>(
+    actors: &'a [ActorInfo],
+    filter: &str,
+    key: SortKey,
+    descending: bool,
+) -> Vec<&'a ActorInfo> {
+    let filter = filter.to_lowercase();
+    let mut visible: Vec<&ActorInfo> = actors
+        .iter()
+        .filter(|actor| {
+            filter.is_empty()
+                || actor.pid.to_string().to_lowercase().contains(&filter)
+                || actor.actor_type.to_lowercase().contains(&filter)
+                || format!("{:?}", actor.status).to_lowercase().contains(&filter)
+        })
+        .collect();
+    visible.sort_by(|a, b| {
+        let order = key.compare(a, b);
+        let order = if descending { order.reverse() } else { order };
+        order.then(a.pid.0.cmp(&b.pid.0))
+    });
+    visible
+}
 
 impl TuiApp {
     /// Create a new TUI application
     pub fn new(socket_path: PathBuf, refresh_interval: Duration) -> Self {
         let client = IpcClient::new(socket_path);
-        let mut actor_list_state = ListState::default();
-        actor_list_state.select(Some(0));
         
         TuiApp {
             client,
             current_tab: 0,
-            actor_list_state,
             actor_table_state: TableState::default(),
+            sort_key: SortKey::Cpu,
+            sort_descending: true,
+            filter: String::new(),
+            detail: None,
+            history: VecDeque::with_capacity(HISTORY_LEN),
             system_info: None,
             actors: Vec::new(),
             system_metrics: None,
+            supervision_tree: None,
             selected_actor: None,
             refresh_interval,
             last_refresh: Instant::now(),
@@ -165,6 +270,15 @@ impl TuiApp {
             InputMode::Normal => self.handle_normal_key(key).await,
             InputMode::Command => self.handle_command_key(key).await,
             InputMode::Filter => self.handle_filter_key(key).await,
+            InputMode::ConfirmKill(ref pid) => {
+                let pid = pid.clone();
+                self.input_mode = InputMode::Normal;
+                if key == KeyCode::Char('y') {
+                    self.execute_command(format!("kill {}", pid)).await;
+                } else {
+                    self.status_message = "Kill cancelled".to_string();
+                }
+            }
         }
     }
     
@@ -194,6 +308,31 @@ impl TuiApp {
             KeyCode::Up => self.move_selection_up(),
             KeyCode::Down => self.move_selection_down(),
             KeyCode::Enter => self.select_current_item().await,
+            KeyCode::Esc | KeyCode::Backspace => self.detail = None,
+            KeyCode::Char('s') if self.current_tab == ACTORS_TAB => {
+                self.sort_key = self.sort_key.next();
+                self.sync_selection();
+            }
+            KeyCode::Char('S') if self.current_tab == ACTORS_TAB => {
+                self.sort_descending = !self.sort_descending;
+                self.sync_selection();
+            }
+            KeyCode::Char('p') if self.current_tab == ACTORS_TAB => {
+                if let Some(pid) = self.target_actor() {
+                    self.execute_command(format!("suspend {}", pid)).await;
+                }
+            }
+            KeyCode::Char('u') if self.current_tab == ACTORS_TAB => {
+                if let Some(pid) = self.target_actor() {
+                    self.execute_command(format!("resume {}", pid)).await;
+                }
+            }
+            KeyCode::Char('k') if self.current_tab == ACTORS_TAB => {
+                if let Some(pid) = self.target_actor() {
+                    self.status_message = format!("Kill actor {}? (y/n)", pid);
+                    self.input_mode = InputMode::ConfirmKill(pid);
+                }
+            }
             _ => {}
         }
     }
@@ -224,12 +363,15 @@ impl TuiApp {
     async fn handle_filter_key(&mut self, key: KeyCode) {
         match key {
             KeyCode::Enter => {
-                // Apply filter
+                self.filter = self.input.value().to_string();
                 self.input_mode = InputMode::Normal;
+                self.sync_selection();
             }
             KeyCode::Esc => {
+                self.filter.clear();
                 self.input_mode = InputMode::Normal;
                 self.input.reset();
+                self.sync_selection();
             }
             _ => {
                 self.input.handle_event(&Event::Key(event::KeyEvent::new(
@@ -240,58 +382,116 @@ impl TuiApp {
         }
     }
     
+    /// PIDs of the rows in the actor table, in display order
+    fn visible_pids(&self) -> Vec<String> {
+        visible_actors(&self.actors, &self.filter, self.sort_key, self.sort_descending)
+            .iter()
+            .map(|actor| actor.pid.to_string())
+            .collect()
+    }
+
+    /// Point the table at the selected actor after its rows changed,
+    /// falling back to the nearest row if that actor is gone
+    fn sync_selection(&mut self) {
+        let pids = self.visible_pids();
+        if pids.is_empty() {
+            self.actor_table_state.select(None);
+            return;
+        }
+        let index = self.selected_actor.as_ref()
+            .and_then(|selected| pids.iter().position(|pid| pid == selected))
+            .unwrap_or_else(|| self.actor_table_state.selected().unwrap_or(0).min(pids.len() - 1));
+        self.actor_table_state.select(Some(index));
+        self.selected_actor = Some(pids[index].clone());
+    }
+
+    /// Move the table selection by `offset` rows, wrapping around
+    fn move_table_selection(&mut self, offset: isize) {
+        let pids = self.visible_pids();
+        if pids.is_empty() {
+            return;
+        }
+        let len = pids.len() as isize;
+        let current = self.actor_table_state.selected().unwrap_or(0) as isize;
+        let index = (current + offset).rem_euclid(len) as usize;
+        self.actor_table_state.select(Some(index));
+        self.selected_actor = Some(pids[index].clone());
+    }
+
+    /// Move the drill-down view's link selection by `offset`, wrapping around
+    fn move_detail_selection(&mut self, offset: isize) {
+        if let Some((actor, state)) = &mut self.detail {
+            let len = (actor.links.len() + actor.monitors.len()) as isize;
+            if len == 0 {
+                return;
+            }
+            let current = state.selected().unwrap_or(0) as isize;
+            state.select(Some((current + offset).rem_euclid(len) as usize));
+        }
+    }
+
     /// Move selection up
     fn move_selection_up(&mut self) {
-        match self.current_tab {
-            1 => { // Actors tab
-                let i = match self.actor_list_state.selected() {
-                    Some(i) => {
-                        if i == 0 {
-                            self.actors.len() - 1
-                        } else {
-                            i - 1
-                        }
-                    }
-                    None => 0,
-                };
-                self.actor_list_state.select(Some(i));
-            }
-            _ => {}
+        if self.current_tab != ACTORS_TAB {
+            return;
+        }
+        if self.detail.is_some() {
+            self.move_detail_selection(-1);
+        } else {
+            self.move_table_selection(-1);
         }
     }
     
     /// Move selection down
     fn move_selection_down(&mut self) {
-        match self.current_tab {
-            1 => { // Actors tab
-                let i = match self.actor_list_state.selected() {
-                    Some(i) => {
-                        if i >= self.actors.len() - 1 {
-                            0
-                        } else {
-                            i + 1
-                        }
-                    }
-                    None => 0,
-                };
-                self.actor_list_state.select(Some(i));
-            }
-            _ => {}
+        if self.current_tab != ACTORS_TAB {
+            return;
+        }
+        if self.detail.is_some() {
+            self.move_detail_selection(1);
+        } else {
+            self.move_table_selection(1);
+        }
+    }
+
+    /// Actor the suspend/resume/kill keys act on
+    fn target_actor(&self) -> Option<String> {
+        match &self.detail {
+            Some((actor, _)) => Some(actor.pid.to_string()),
+            None => self.selected_actor.clone(),
         }
     }
     
-    /// Select current item
+    /// Open the drill-down view for the selected actor, or for the selected
+    /// link when the view is already open
     async fn select_current_item(&mut self) {
-        match self.current_tab {
-            1 => { // Actors tab
-                if let Some(i) = self.actor_list_state.selected() {
-                    if let Some(actor) = self.actors.get(i) {
-                        self.selected_actor = Some(actor.pid.to_string());
-                        self.status_message = format!("Selected actor: {}", actor.pid);
-                    }
+        if self.current_tab != ACTORS_TAB {
+            return;
+        }
+        let pid = match &self.detail {
+            Some((actor, state)) => state.selected()
+                .and_then(|i| actor.links.iter().chain(actor.monitors.iter()).nth(i))
+                .map(|pid| pid.to_string()),
+            None => self.selected_actor.clone(),
+        };
+        let Some(pid) = pid else {
+            return;
+        };
+        self.open_detail(pid).await;
+    }
+
+    /// Fetch an actor's latest information and show it in the drill-down view
+    async fn open_detail(&mut self, pid: String) {
+        match self.client.get_actor_info(pid.clone()).await {
+            Ok(actor) => {
+                let mut state = ListState::default();
+                if !actor.links.is_empty() || !actor.monitors.is_empty() {
+                    state.select(Some(0));
                 }
+                self.status_message = format!("Inspecting actor: {}", pid);
+                self.detail = Some((actor, state));
             }
-            _ => {}
+            Err(e) => self.error_message = Some(e.to_string()),
         }
     }
     
@@ -354,6 +554,20 @@ impl TuiApp {
                 self.refresh_data().await;
                 self.status_message = "Data refreshed".to_string();
             }
+            "sort" => {
+                match parts.get(1).copied() {
+                    Some("pid") => self.sort_key = SortKey::Pid,
+                    Some("cpu") => self.sort_key = SortKey::Cpu,
+                    Some("mailbox") => self.sort_key = SortKey::Mailbox,
+                    Some("memory") => self.sort_key = SortKey::Memory,
+                    Some("rate") => self.sort_key = SortKey::MessageRate,
+                    _ => {
+                        self.error_message = Some("Usage: sort pid|cpu|mailbox|memory|rate".to_string());
+                        return;
+                    }
+                }
+                self.sync_selection();
+            }
             _ => {
                 self.error_message = Some(format!("Unknown command: {}", parts[0]));
             }
@@ -365,6 +579,14 @@ impl TuiApp {
         // Get system info
         match self.client.get_system_info().await {
             Ok(info) => {
+                if self.history.len() == HISTORY_LEN {
+                    self.history.pop_front();
+                }
+                self.history.push_back(HistorySample {
+                    message_rate: info.system_message_rate.round() as u64,
+                    memory: info.total_memory as u64,
+                    actors: info.total_actors as u64,
+                });
                 self.system_info = Some(info);
                 self.error_message = None;
             }
@@ -377,12 +599,30 @@ impl TuiApp {
         match self.client.list_actors(true).await {
             Ok(actors) => {
                 self.actors = actors;
+                self.sync_selection();
             }
             Err(e) => {
                 self.error_message = Some(format!("Failed to get actors: {}", e));
             }
         }
         
+        // Get supervision tree
+        match self.client.supervision_tree().await {
+            Ok(tree) => self.supervision_tree = Some(tree),
+            Err(e) => {
+                self.error_message = Some(format!("Failed to get supervision tree: {}", e));
+            }
+        }
+
+        // Keep the drill-down view current
+        if let Some((actor, _)) = &self.detail {
+            if let Ok(latest) = self.client.get_actor_info(actor.pid.to_string()).await {
+                if let Some((actor, _)) = &mut self.detail {
+                    *actor = latest;
+                }
+            }
+        }
+        
         self.last_refresh = Instant::now();
     }
     
@@ -404,9 +644,10 @@ impl TuiApp {
         match self.current_tab {
             0 => self.draw_overview(f, chunks[1]),
             1 => self.draw_actors(f, chunks[1]),
-            2 => self.draw_performance(f, chunks[1]),
-            3 => self.draw_logs(f, chunks[1]),
-            4 => self.draw_commands(f, chunks[1]),
+            2 => self.draw_tree(f, chunks[1]),
+            3 => self.draw_performance(f, chunks[1]),
+            4 => self.draw_logs(f, chunks[1]),
+            5 => self.draw_commands(f, chunks[1]),
             _ => {}
         }
         
@@ -439,7 +680,7 @@ impl TuiApp {
     fn draw_footer(&self, f: &mut Frame, area: Rect) {
         let footer_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
             .split(area);
         
         // Status message
@@ -460,9 +701,16 @@ impl TuiApp {
         
         // Help text
         let help_text = match self.input_mode {
+            InputMode::Normal if self.current_tab == ACTORS_TAB && self.detail.is_some() => {
+                "Enter:follow link Esc:back p:suspend u:resume k:kill"
+            }
+            InputMode::Normal if self.current_tab == ACTORS_TAB => {
+                "Enter:inspect s:sort S:reverse p:suspend u:resume k:kill /:filter"
+            }
             InputMode::Normal => "q:quit r:refresh Tab:switch /:filter ::command",
             InputMode::Command => "Enter:execute Esc:cancel",
-            InputMode::Filter => "Enter:apply Esc:cancel",
+            InputMode::Filter => "Enter:apply Esc:clear",
+            InputMode::ConfirmKill(_) => "y:kill any other key:cancel",
         };
         
         let help = Paragraph::new(help_text)
@@ -548,84 +796,110 @@ impl TuiApp {
     }
 
     /// Draw actors tab
-    fn draw_actors(&self, f: &mut Frame, area: Rect) {
+    fn draw_actors(&mut self, f: &mut Frame, area: Rect) {
         let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(3)])
             .split(area);
 
-        // Actor list
-        let actor_items: Vec<ListItem> = self.actors
-            .iter()
-            .map(|actor| {
-                let status_color = match actor.status {
-                    ActorStatus::Running => Color::Green,
-                    ActorStatus::Suspended => Color::Yellow,
-                    ActorStatus::Crashed => Color::Red,
-                    ActorStatus::Terminated => Color::Gray,
-                    _ => Color::White,
-                };
+        if let Some((actor, state)) = &mut self.detail {
+            draw_actor_drill_down(f, chunks[0], actor, state);
+        } else {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .split(chunks[0]);
 
-                ListItem::new(format!(
-                    "{} | {} | {} msgs | {} bytes",
-                    actor.pid,
-                    format!("{:?}", actor.status),
-                    actor.mailbox_size,
-                    actor.memory_usage
-                )).style(Style::default().fg(status_color))
-            })
-            .collect();
+            let visible = visible_actors(&self.actors, &self.filter, self.sort_key, self.sort_descending);
+            let arrow = if self.sort_descending { "▼" } else { "▲" };
+            let header = [SortKey::Pid, SortKey::Cpu, SortKey::Mailbox, SortKey::Memory, SortKey::MessageRate]
+                .iter()
+                .map(|key| {
+                    if *key == self.sort_key {
+                        Cell::from(format!("{}{}", key.label(), arrow)).style(Style::default().fg(Color::Yellow))
+                    } else {
+                        Cell::from(key.label())
+                    }
+                })
+                .chain(std::iter::once(Cell::from("Status")));
 
-        let actor_list = List::new(actor_items)
-            .block(Block::default().borders(Borders::ALL).title("Actors"))
-            .highlight_style(Style::default().add_modifier(Modifier::BOLD).bg(Color::DarkGray));
-        f.render_stateful_widget(actor_list, chunks[0], &mut self.actor_list_state.clone());
-
-        // Actor details
-        if let Some(selected_idx) = self.actor_list_state.selected() {
-            if let Some(actor) = self.actors.get(selected_idx) {
-                let details_text = vec![
-                    Line::from(format!("PID: {}", actor.pid)),
-                    Line::from(format!("Status: {:?}", actor.status)),
-                    Line::from(format!("Type: {}", actor.actor_type)),
-                    Line::from(format!("Uptime: {:?}", actor.uptime)),
-                    Line::from(format!("Mailbox: {}", actor.mailbox_size)),
-                    Line::from(format!("Memory: {} bytes", actor.memory_usage)),
-                    Line::from(format!("Messages: {}", actor.messages_processed)),
-                    Line::from(format!("Rate: {:.2} msg/s", actor.message_rate)),
-                    Line::from(format!("CPU Time: {} μs", actor.cpu_time)),
-                    Line::from(format!("State: {}", actor.state_description)),
-                    Line::from(format!("Links: {}", actor.links.len())),
-                    Line::from(format!("Monitors: {}", actor.monitors.len())),
-                ];
-
-                let details = Paragraph::new(details_text)
-                    .block(Block::default().borders(Borders::ALL).title("Actor Details"))
-                    .wrap(Wrap { trim: true });
-                f.render_widget(details, chunks[1]);
-            }
+            let rows: Vec<Row> = visible.iter().map(|actor| {
+                Row::new(vec![
+                    Cell::from(actor.pid.to_string()),
+                    Cell::from(format_micros(actor.cpu_time)),
+                    Cell::from(actor.mailbox_size.to_string()),
+                    Cell::from(format_bytes(actor.memory_usage as u64)),
+                    Cell::from(format!("{:.1}", actor.message_rate)),
+                    Cell::from(format!("{:?}", actor.status)),
+                ]).style(Style::default().fg(status_color(&actor.status)))
+            }).collect();
+
+            let title = if self.filter.is_empty() {
+                format!("Actors ({})", visible.len())
+            } else {
+                format!("Actors ({} of {}, filter: {})", visible.len(), self.actors.len(), self.filter)
+            };
+            let table = Table::new(rows)
+                .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD).bg(Color::DarkGray))
+                .widths(&[
+                    Constraint::Min(12),
+                    Constraint::Length(10),
+                    Constraint::Length(9),
+                    Constraint::Length(10),
+                    Constraint::Length(8),
+                    Constraint::Length(11),
+                ]);
+            let selected = self.actor_table_state.selected().and_then(|i| visible.get(i).copied());
+            let details = Paragraph::new(selected.map(actor_summary_lines).unwrap_or_default())
+                .block(Block::default().borders(Borders::ALL).title("Actor Details"))
+                .wrap(Wrap { trim: true });
+            f.render_stateful_widget(table, columns[0], &mut self.actor_table_state);
+            f.render_widget(details, columns[1]);
         }
+
+        // Filter input
+        let input_text = match self.input_mode {
+            InputMode::Filter => format!("/ {}", self.input.value()),
+            _ => format!("Filter: {}", if self.filter.is_empty() { "(none)" } else { &self.filter }),
+        };
+        let input_widget = Paragraph::new(input_text)
+            .block(Block::default().borders(Borders::ALL).title("Filter"));
+        f.render_widget(input_widget, chunks[1]);
     }
 
     /// Draw performance tab
     fn draw_performance(&self, f: &mut Frame, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .constraints([
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
+            ])
             .split(area);
 
-        // Message rate chart (placeholder)
-        let message_rates: Vec<u64> = self.actors
-            .iter()
-            .map(|actor| actor.message_rate as u64)
-            .collect();
-
-        if !message_rates.is_empty() {
+        let latest = self.history.back();
+        let graphs = [
+            ("Message Rate", latest.map(|s| format!("{} msg/s", s.message_rate)), Color::Yellow,
+                self.history.iter().map(|s| s.message_rate).collect::<Vec<u64>>()),
+            ("Memory", latest.map(|s| format_bytes(s.memory)), Color::Blue,
+                self.history.iter().map(|s| s.memory).collect()),
+            ("Actors", latest.map(|s| s.actors.to_string()), Color::Green,
+                self.history.iter().map(|s| s.actors).collect()),
+        ];
+        for (i, (title, current, color, data)) in graphs.iter().enumerate() {
+            let title = format!("{} ({})", title, current.as_deref().unwrap_or("-"));
+            // Show the newest samples that fit
+            let width = chunks[i].width.saturating_sub(2) as usize;
+            let data = &data[data.len().saturating_sub(width)..];
             let sparkline = Sparkline::default()
-                .block(Block::default().borders(Borders::ALL).title("Message Rates"))
-                .data(&message_rates)
-                .style(Style::default().fg(Color::Yellow));
-            f.render_widget(sparkline, chunks[0]);
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .data(data)
+                .style(Style::default().fg(*color));
+            f.render_widget(sparkline, chunks[i]);
         }
 
         // Performance metrics table
@@ -646,7 +920,22 @@ impl TuiApp {
             .block(Block::default().borders(Borders::ALL).title("Performance Metrics"))
             .header(Row::new(vec!["Metric", "Value"]).style(Style::default().add_modifier(Modifier::BOLD)))
             .widths(&[Constraint::Percentage(50), Constraint::Percentage(50)]);
-        f.render_widget(performance_table, chunks[1]);
+        f.render_widget(performance_table, chunks[3]);
+    }
+
+    /// Draw supervision tree tab
+    fn draw_tree(&self, f: &mut Frame, area: Rect) {
+        let (title, text) = match &self.supervision_tree {
+            Some(tree) => (
+                format!("Supervision Tree ({} nodes)", tree.count()),
+                tree.render_ascii().lines().map(|line| Line::from(line.to_string())).collect(),
+            ),
+            None => ("Supervision Tree".to_string(), vec![Line::from("Loading supervision tree...")]),
+        };
+
+        let tree = Paragraph::new(text)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(tree, area);
     }
 
     /// Draw logs tab
@@ -679,6 +968,7 @@ impl TuiApp {
             Line::from("resume <pid>         - Resume an actor"),
             Line::from("restart <pid>        - Restart an actor"),
             Line::from("send <pid> <msg>     - Send message to actor"),
+            Line::from("sort <column>        - Sort actors by pid, cpu, mailbox, memory or rate"),
             Line::from("refresh              - Refresh data"),
             Line::from(""),
             Line::from("Press ':' to enter command mode"),
@@ -708,3 +998,144 @@ impl TuiApp {
         counts
     }
 }
+
+/// Color an actor's row by its status
+fn status_color(status: &ActorStatus) -> Color {
+    match status {
+        ActorStatus::Running => Color::Green,
+        ActorStatus::Suspended => Color::Yellow,
+        ActorStatus::Crashed => Color::Red,
+        ActorStatus::Terminated => Color::Gray,
+        _ => Color::White,
+    }
+}
+
+/// Human-readable byte count
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Human-readable CPU time given in microseconds
+fn format_micros(micros: u64) -> String {
+    let duration = Duration::from_micros(micros);
+    if duration.as_secs() >= 60 {
+        format!("{}m{:02}s", duration.as_secs() / 60, duration.as_secs() % 60)
+    } else if duration.as_millis() >= 1000 {
+        format!("{:.2}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+/// Key figures of an actor for the side pane
+fn actor_summary_lines(actor: &ActorInfo) -> Vec<Line<'static>> {
+    vec![
+        Line::from(format!("PID: {}", actor.pid)),
+        Line::from(format!("Status: {:?}", actor.status)),
+        Line::from(format!("Type: {}", actor.actor_type)),
+        Line::from(format!("Uptime: {:?}", actor.uptime)),
+        Line::from(format!("Mailbox: {}", actor.mailbox_size)),
+        Line::from(format!("Memory: {}", format_bytes(actor.memory_usage as u64))),
+        Line::from(format!("Messages: {}", actor.messages_processed)),
+        Line::from(format!("Rate: {:.2} msg/s", actor.message_rate)),
+        Line::from(format!("CPU Time: {}", format_micros(actor.cpu_time))),
+        Line::from(format!("State: {}", actor.state_description)),
+        Line::from(format!("Supervisor: {}", actor.supervisor.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string()))),
+        Line::from(format!("Links: {}", actor.links.len())),
+        Line::from(format!("Monitors: {}", actor.monitors.len())),
+        Line::from(""),
+        Line::from("Enter to inspect"),
+    ]
+}
+
+/// Draw the drill-down view of one actor: its figures, the actors it links
+/// and monitors (selectable, Enter follows them) and its recent messages
+fn draw_actor_drill_down(f: &mut Frame, area: Rect, actor: &ActorInfo, state: &mut ListState) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(area);
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(14), Constraint::Min(0)])
+        .split(columns[0]);
+
+    let mut summary = actor_summary_lines(actor);
+    summary.truncate(summary.len() - 2);
+    let details = Paragraph::new(summary)
+        .block(Block::default().borders(Borders::ALL).title(format!("Actor {}", actor.pid)))
+        .wrap(Wrap { trim: true });
+    f.render_widget(details, left[0]);
+
+    let related: Vec<ListItem> = actor.links.iter()
+        .map(|pid| ListItem::new(format!("link     {}", pid)))
+        .chain(actor.monitors.iter().map(|pid| ListItem::new(format!("monitor  {}", pid))))
+        .collect();
+    let related = List::new(related)
+        .block(Block::default().borders(Borders::ALL).title("Links & Monitors"))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).bg(Color::DarkGray));
+    f.render_stateful_widget(related, left[1], state);
+
+    let messages: Vec<ListItem> = actor.recent_messages.iter().rev()
+        .map(|message| ListItem::new(message.as_str()))
+        .collect();
+    let messages = List::new(messages)
+        .block(Block::default().borders(Borders::ALL).title("Recent Messages (newest first)"));
+    f.render_widget(messages, columns[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use crate::types::Pid;
+
+    fn actor(cpu_time: u64, mailbox_size: usize, memory_usage: usize, actor_type: &str) -> ActorInfo {
+        ActorInfo {
+            pid: Pid::new(),
+            status: ActorStatus::Running,
+            mailbox_size,
+            memory_usage,
+            messages_processed: 0,
+            message_rate: 0.0,
+            cpu_time,
+            uptime: Duration::ZERO,
+            last_activity: SystemTime::now(),
+            actor_type: actor_type.to_string(),
+            state_description: String::new(),
+            links: Vec::new(),
+            monitors: Vec::new(),
+            supervisor: None,
+            recent_messages: Vec::new(),
+            restarts: 0,
+            domain: None,
+            ingress: None,
+        }
+    }
+
+    #[test]
+    fn test_visible_actors_sort_and_filter() {
+        let actors = vec![
+            actor(30, 1, 300, "Worker"),
+            actor(10, 5, 100, "Worker"),
+            actor(20, 3, 200, "Supervisor"),
+        ];
+        let pids = |visible: Vec<&ActorInfo>| visible.iter().map(|a| a.pid).collect::<Vec<_>>();
+
+        assert_eq!(pids(visible_actors(&actors, "", SortKey::Cpu, true)), [actors[0].pid, actors[2].pid, actors[1].pid]);
+        assert_eq!(pids(visible_actors(&actors, "", SortKey::Mailbox, true)), [actors[1].pid, actors[2].pid, actors[0].pid]);
+        assert_eq!(pids(visible_actors(&actors, "", SortKey::Memory, false)), [actors[1].pid, actors[2].pid, actors[0].pid]);
+        assert_eq!(pids(visible_actors(&actors, "worker", SortKey::Cpu, false)), [actors[1].pid, actors[0].pid]);
+    }
+}
@@ -0,0 +1,396 @@
+//! Sampling CPU profiler for TLisp
+//!
+//! While a process is being profiled, the evaluator and the bytecode VM
+//! publish the TLisp functions it is inside, and a sampler thread looks at
+//! them `frequency` times a second. Samples are only taken while the
+//! process is running a quantum, so a profile shows where its CPU time
+//! went, aggregated by function name and as folded stacks for flamegraphs.
+//!
+//! Functions are attributed to the process a thread is running, as set by
+//! `enter`, which `Process::run_quantum` calls. Processes that are not being
+//! profiled pay one atomic read per function call.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Serialize, Deserialize};
+
+use crate::types::Pid;
+
+/// Samples taken a second unless asked otherwise; off a round number so
+/// sampling does not run in lockstep with timers
+pub const DEFAULT_FREQUENCY: u32 = 99;
+
+/// Most samples taken a second
+pub const MAX_FREQUENCY: u32 = 10_000;
+
+/// Frame of samples taken outside any TLisp function
+pub const TOPLEVEL_FRAME: &str = "[toplevel]";
+
+/// Frame of functions called without a name
+pub const LAMBDA_FRAME: &str = "lambda";
+
+/// CPU profile of a process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuProfile {
+    /// Profiled process
+    pub pid: Pid,
+    /// Samples taken a second
+    pub frequency: u32,
+    /// When profiling started
+    pub started_at: SystemTime,
+    /// How long the process was profiled
+    pub duration: Duration,
+    /// Samples taken, running or not
+    pub ticks: u64,
+    /// Samples taken while the process was running
+    pub samples: u64,
+    /// Time by function, most samples first
+    pub functions: Vec<FunctionSamples>,
+    /// Sampled stacks, most samples first
+    pub stacks: Vec<StackSamples>,
+}
+
+/// Samples of one TLisp function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionSamples {
+    /// Function name, `lambda` for anonymous ones
+    pub name: String,
+    /// Samples with the function innermost
+    pub self_samples: u64,
+    /// Samples with the function anywhere on the stack
+    pub total_samples: u64,
+}
+
+/// Samples of one stack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackSamples {
+    /// TLisp functions, outermost first
+    pub frames: Vec<String>,
+    /// Samples taken
+    pub samples: u64,
+}
+
+impl CpuProfile {
+    /// Estimated CPU time a number of samples stands for
+    pub fn time(&self, samples: u64) -> Duration {
+        Duration::from_secs_f64(samples as f64 / self.frequency as f64)
+    }
+
+    /// Stacks in the folded format read by flamegraph tools, weighted by
+    /// samples
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for stack in &self.stacks {
+            let frames: Vec<String> = stack.frames.iter().map(|frame| frame.replace([';', ' '], "_")).collect();
+            let _ = writeln!(folded, "{} {}", frames.join(";"), stack.samples);
+        }
+        folded
+    }
+}
+
+/// Start profiling a process, `frequency` samples a second; restarts a
+/// profile already in progress
+pub fn start(pid: Pid, frequency: u32) {
+    let frequency = frequency.clamp(1, MAX_FREQUENCY);
+    let session = Arc::new(Session {
+        pid,
+        frequency,
+        started_at: SystemTime::now(),
+        started: Instant::now(),
+        running: AtomicUsize::new(0),
+        stopped: AtomicBool::new(false),
+        ticks: AtomicU64::new(0),
+        stack: Mutex::new(Vec::new()),
+        samples: Mutex::new(HashMap::new()),
+        sampler: Mutex::new(None),
+    });
+    let sampler = {
+        let session = Arc::clone(&session);
+        thread::Builder::new()
+            .name(format!("cpu-profiler-{}", pid))
+            .spawn(move || session.sample_until_stopped())
+            .ok()
+    };
+    *session.sampler.lock().unwrap() = sampler;
+
+    let previous = SESSIONS.write().unwrap().insert(pid, session);
+    match previous {
+        Some(previous) => previous.stop(),
+        None => {
+            ACTIVE.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Stop profiling a process, returning its profile
+pub fn stop(pid: Pid) -> Option<CpuProfile> {
+    let session = SESSIONS.write().unwrap().remove(&pid)?;
+    ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    session.stop();
+    Some(session.report())
+}
+
+/// Whether a process is being profiled
+pub fn is_profiling(pid: Pid) -> bool {
+    SESSIONS.read().unwrap().contains_key(&pid)
+}
+
+/// Attribute the functions this thread runs to `pid` until the scope drops
+pub fn enter(pid: Pid) -> ProcessScope {
+    let session = if ACTIVE.load(Ordering::Relaxed) == 0 {
+        None
+    } else {
+        SESSIONS.read().unwrap().get(&pid).cloned()
+    };
+    if let Some(session) = &session {
+        session.running.fetch_add(1, Ordering::Relaxed);
+    }
+    let previous = CURRENT.with(|current| current.replace(session.clone()));
+    ProcessScope { previous, session }
+}
+
+/// A thread running a process, see `enter`
+pub struct ProcessScope {
+    previous: Option<Arc<Session>>,
+    session: Option<Arc<Session>>,
+}
+
+impl Drop for ProcessScope {
+    fn drop(&mut self) {
+        if let Some(session) = &self.session {
+            session.running.fetch_sub(1, Ordering::Relaxed);
+        }
+        let previous = self.previous.take();
+        let _ = CURRENT.try_with(|current| current.replace(previous));
+    }
+}
+
+/// Publish that the thread is inside the function `name` until the
+/// returned frames drop
+pub fn frame(name: &str) -> Frames {
+    let frames = frames();
+    frames.set([name]);
+    frames
+}
+
+/// Frames the thread publishes on top of those already published, replaced
+/// with `Frames::set` and removed when dropped
+pub fn frames() -> Frames {
+    if ACTIVE.load(Ordering::Relaxed) == 0 {
+        return Frames { session: None, base: 0 };
+    }
+    let session = CURRENT.try_with(|current| current.borrow().clone()).ok().flatten();
+    let base = session.as_ref().map_or(0, |session| session.stack.lock().unwrap().len());
+    Frames { session, base }
+}
+
+/// Frames a thread publishes for the process it runs, see `frames`
+pub struct Frames {
+    session: Option<Arc<Session>>,
+    base: usize,
+}
+
+impl Frames {
+    /// Whether the process is being profiled, so frames are worth publishing
+    pub fn is_active(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Replace the published frames, outermost first
+    pub fn set<'a>(&self, names: impl IntoIterator<Item = &'a str>) {
+        if let Some(session) = &self.session {
+            let mut stack = session.stack.lock().unwrap();
+            stack.truncate(self.base);
+            stack.extend(names.into_iter().map(Arc::from));
+        }
+    }
+}
+
+impl Drop for Frames {
+    fn drop(&mut self) {
+        if let Some(session) = &self.session {
+            session.stack.lock().unwrap().truncate(self.base);
+        }
+    }
+}
+
+/// Sessions running; function calls are not published while there are none
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+static SESSIONS: LazyLock<RwLock<HashMap<Pid, Arc<Session>>>> = LazyLock::new(Default::default);
+
+thread_local! {
+    /// Session of the process this thread runs
+    static CURRENT: RefCell<Option<Arc<Session>>> = const { RefCell::new(None) };
+}
+
+struct Session {
+    pid: Pid,
+    frequency: u32,
+    started_at: SystemTime,
+    started: Instant,
+    /// Threads running the process, only sampled when there are some
+    running: AtomicUsize,
+    stopped: AtomicBool,
+    ticks: AtomicU64,
+    /// Functions the process is inside, outermost first
+    stack: Mutex<Vec<Arc<str>>>,
+    samples: Mutex<HashMap<Vec<Arc<str>>, u64>>,
+    sampler: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Session {
+    fn sample_until_stopped(&self) {
+        let period = Duration::from_secs_f64(1.0 / self.frequency as f64);
+        let mut next = Instant::now() + period;
+        while !self.stopped.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if now < next {
+                thread::park_timeout(next - now);
+                continue;
+            }
+            self.sample();
+            // Skip the ticks missed while descheduled rather than catch up
+            next = (next + period).max(now);
+        }
+    }
+
+    fn sample(&self) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        if self.running.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let stack = self.stack.lock().unwrap().clone();
+        *self.samples.lock().unwrap().entry(stack).or_default() += 1;
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(sampler) = self.sampler.lock().unwrap().take() {
+            sampler.thread().unpark();
+            let _ = sampler.join();
+        }
+    }
+
+    fn report(&self) -> CpuProfile {
+        let sampled = std::mem::take(&mut *self.samples.lock().unwrap());
+
+        let mut functions: HashMap<&str, FunctionSamples> = HashMap::new();
+        let mut stacks = Vec::with_capacity(sampled.len());
+        for (frames, samples) in &sampled {
+            let innermost = frames.last().map_or(TOPLEVEL_FRAME, |frame| &**frame);
+            let mut seen = HashSet::new();
+            for name in frames.iter().map(|frame| &**frame).chain([innermost]) {
+                if !seen.insert(name) {
+                    continue;
+                }
+                let function = functions.entry(name).or_insert_with(|| FunctionSamples {
+                    name: name.to_string(),
+                    self_samples: 0,
+                    total_samples: 0,
+                });
+                function.total_samples += samples;
+                if name == innermost {
+                    function.self_samples += samples;
+                }
+            }
+            let frames = if frames.is_empty() {
+                vec![TOPLEVEL_FRAME.to_string()]
+            } else {
+                frames.iter().map(|frame| frame.to_string()).collect()
+            };
+            stacks.push(StackSamples { frames, samples: *samples });
+        }
+
+        let mut functions: Vec<FunctionSamples> = functions.into_values().collect();
+        functions.sort_by(|a, b| b.self_samples.cmp(&a.self_samples)
+            .then(b.total_samples.cmp(&a.total_samples))
+            .then_with(|| a.name.cmp(&b.name)));
+        stacks.sort_by(|a, b| b.samples.cmp(&a.samples).then_with(|| a.frames.cmp(&b.frames)));
+
+        CpuProfile {
+            pid: self.pid,
+            frequency: self.frequency,
+            started_at: self.started_at,
+            duration: self.started.elapsed(),
+            ticks: self.ticks.load(Ordering::Relaxed),
+            samples: sampled.values().sum(),
+            functions,
+            stacks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spin inside the published frames for a while
+    fn busy(frames: &[&str], duration: Duration) {
+        let _frames = frames_of(frames);
+        let until = Instant::now() + duration;
+        while Instant::now() < until {
+            std::hint::spin_loop();
+        }
+    }
+
+    fn frames_of(names: &[&str]) -> Frames {
+        let frames = frames();
+        frames.set(names.iter().copied());
+        frames
+    }
+
+    #[test]
+    fn test_profile_attributes_samples_to_functions() {
+        let pid = Pid::new();
+        start(pid, 1000);
+        assert!(is_profiling(pid));
+        {
+            let _scope = enter(pid);
+            let _main = frame("main");
+            busy(&["fib"], Duration::from_millis(120));
+            busy(&["fib", "fib"], Duration::from_millis(60));
+            busy(&[], Duration::from_millis(30));
+        }
+        // Not running, so not sampled
+        thread::sleep(Duration::from_millis(30));
+
+        let profile = stop(pid).unwrap();
+        assert!(!is_profiling(pid));
+        assert!(profile.samples > 0 && profile.samples < profile.ticks, "{} of {}", profile.samples, profile.ticks);
+        assert_eq!(profile.functions[0].name, "fib");
+        let fib = &profile.functions[0];
+        assert!(fib.self_samples > fib.total_samples / 2);
+        let main = profile.functions.iter().find(|function| function.name == "main").unwrap();
+        assert!(main.total_samples >= fib.total_samples);
+        assert!(profile.stacks.iter().any(|stack| stack.frames == ["main", "fib", "fib"]));
+        assert!(profile.stacks.iter().all(|stack| stack.frames[0] == "main" || stack.frames == [TOPLEVEL_FRAME]));
+
+        for line in profile.folded().lines() {
+            let (stack, samples) = line.rsplit_once(' ').unwrap();
+            assert!(stack.starts_with("main") || stack == TOPLEVEL_FRAME);
+            samples.parse::<u64>().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_unprofiled_processes_publish_nothing() {
+        let pid = Pid::new();
+        start(pid, 1000);
+        {
+            let _scope = enter(Pid::new());
+            let frames = frame("other");
+            assert!(!frames.is_active());
+            busy(&["other"], Duration::from_millis(20));
+        }
+        let profile = stop(pid).unwrap();
+        assert_eq!(profile.samples, 0);
+        assert!(profile.functions.is_empty());
+        assert!(stop(pid).is_none());
+    }
+}
@@ -0,0 +1,413 @@
+//! Message-flow recording
+//!
+//! While flow recording is on, every message routed between processes is
+//! kept with its sender, recipient, type and the trace it belongs to, up to
+//! a limit after which the oldest are dropped. The sender of a message an
+//! actor sends while handling another is the actor, as `receive_traced`
+//! tells through `receiving`. Recorded flows render as Mermaid sequence
+//! diagrams or Graphviz DOT graphs.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+
+use crate::runtime::schema::TypedMessage;
+use crate::types::{ControlMessage, MessagePayload, Pid};
+
+/// Messages kept unless asked otherwise
+pub const DEFAULT_FLOW_LIMIT: usize = 10_000;
+
+/// Participant standing for senders outside any actor
+const EXTERNAL: &str = "external";
+
+/// A message sent from one process to another
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowEvent {
+    /// Position in the recording, counting messages dropped by the limit
+    pub seq: u64,
+    /// When the message was sent
+    pub sent_at: SystemTime,
+    /// Sending actor; `None` when sent from outside any actor
+    pub from: Option<Pid>,
+    /// Receiving process
+    pub to: Pid,
+    /// Schema name of typed messages, else `text`, `bytes`, `data` or the
+    /// control message
+    pub message_type: String,
+    /// One-line summary of the message
+    pub summary: String,
+    /// Trace the message was sent in, if any
+    pub trace_id: Option<u128>,
+}
+
+/// Which recorded messages to export; empty lists match everything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlowFilter {
+    /// Messages sent or received by any of these processes
+    pub pids: Vec<Pid>,
+    /// Messages of any of these types
+    pub message_types: Vec<String>,
+    /// Messages sent at or after this time
+    pub since: Option<SystemTime>,
+    /// Messages sent before this time
+    pub until: Option<SystemTime>,
+}
+
+impl FlowFilter {
+    /// Whether a recorded message passes the filter
+    pub fn matches(&self, event: &FlowEvent) -> bool {
+        (self.pids.is_empty() || self.pids.contains(&event.to) || event.from.is_some_and(|from| self.pids.contains(&from)))
+            && (self.message_types.is_empty() || self.message_types.contains(&event.message_type))
+            && self.since.is_none_or(|since| event.sent_at >= since)
+            && self.until.is_none_or(|until| event.sent_at < until)
+    }
+}
+
+/// Recorded flows, how many were dropped and whether recording is on
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlowStatus {
+    /// Whether messages are being recorded
+    pub recording: bool,
+    /// Messages kept
+    pub kept: usize,
+    /// Most messages kept
+    pub limit: usize,
+    /// Messages dropped by the limit
+    pub dropped: u64,
+}
+
+/// Start recording messages, keeping at most `limit`; drops what an
+/// earlier recording kept
+pub fn start(limit: usize) {
+    let mut log = LOG.lock().unwrap();
+    *log = FlowLog { events: VecDeque::new(), limit: limit.max(1), next_seq: 0 };
+    RECORDING.store(true, Ordering::Relaxed);
+}
+
+/// Stop recording, keeping the recorded messages for export
+pub fn stop() -> FlowStatus {
+    RECORDING.store(false, Ordering::Relaxed);
+    status()
+}
+
+/// Whether messages are being recorded
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// What the recording holds
+pub fn status() -> FlowStatus {
+    let log = LOG.lock().unwrap();
+    FlowStatus {
+        recording: is_recording(),
+        kept: log.events.len(),
+        limit: log.limit,
+        dropped: log.next_seq - log.events.len() as u64,
+    }
+}
+
+/// Recorded messages passing `filter`, oldest first
+pub fn query(filter: &FlowFilter) -> Vec<FlowEvent> {
+    LOG.lock().unwrap().events.iter().filter(|event| filter.matches(event)).cloned().collect()
+}
+
+/// Record a message routed to `to`, sent by `from` or else by the actor
+/// this thread is running
+pub fn record(from: Option<Pid>, to: Pid, payload: &MessagePayload) {
+    if !is_recording() {
+        return;
+    }
+    let from = from.or_else(|| RECEIVING.with(Cell::get));
+    let trace_id = match payload {
+        MessagePayload::Traced { context, .. } => Some(context.trace_id),
+        _ => None,
+    };
+    let event = FlowEvent {
+        seq: 0,
+        sent_at: SystemTime::now(),
+        from,
+        to,
+        message_type: message_type(payload),
+        summary: payload.summary(),
+        trace_id,
+    };
+    LOG.lock().unwrap().push(event);
+}
+
+/// Mark this thread as running the actor `pid` until the scope drops, so
+/// what it sends is recorded as sent by the actor
+pub fn receiving(pid: Pid) -> ReceiveScope {
+    ReceiveScope { previous: RECEIVING.with(|receiving| receiving.replace(Some(pid))) }
+}
+
+/// An actor handling a message, see `receiving`
+pub struct ReceiveScope {
+    previous: Option<Pid>,
+}
+
+impl Drop for ReceiveScope {
+    fn drop(&mut self) {
+        let _ = RECEIVING.try_with(|receiving| receiving.set(self.previous));
+    }
+}
+
+/// Type of a message as flows are filtered by
+pub fn message_type(payload: &MessagePayload) -> String {
+    if let Some(typed) = TypedMessage::from_payload(payload) {
+        return typed.schema;
+    }
+    match payload {
+        MessagePayload::Bytes(_) | MessagePayload::SharedBinary(_) => "bytes".to_string(),
+        MessagePayload::Text(_) => "text".to_string(),
+        MessagePayload::Data(_) => "data".to_string(),
+        MessagePayload::Control(control) => match control {
+            ControlMessage::Terminate => "terminate",
+            ControlMessage::Suspend => "suspend",
+            ControlMessage::Resume => "resume",
+            ControlMessage::Link(_) => "link",
+            ControlMessage::Monitor(_) => "monitor",
+            ControlMessage::Exit { .. } => "exit",
+        }.to_string(),
+        MessagePayload::Traced { payload, .. } => message_type(payload),
+    }
+}
+
+/// Render messages as a Mermaid sequence diagram, one arrow per message
+pub fn to_mermaid(events: &[FlowEvent]) -> String {
+    let participants = Participants::of(events);
+    let mut diagram = String::from("sequenceDiagram\n");
+    for (name, id) in participants.iter() {
+        let _ = writeln!(diagram, "    participant {} as {}", id, mermaid_escape(name));
+    }
+    for event in events {
+        let _ = writeln!(diagram, "    {}->>{}: {}",
+            participants.id(&sender(event)),
+            participants.id(&event.to.to_string()),
+            mermaid_escape(&format!("{} {}", event.message_type, event.summary)));
+    }
+    diagram
+}
+
+/// Render messages as a Graphviz DOT graph, one edge per sender, recipient
+/// and message type labelled with how many were sent
+pub fn to_dot(events: &[FlowEvent]) -> String {
+    let participants = Participants::of(events);
+    let mut edges: BTreeMap<(&str, &str, &str), usize> = BTreeMap::new();
+    for event in events {
+        let from = participants.id(&sender(event));
+        let to = participants.id(&event.to.to_string());
+        *edges.entry((from, to, &event.message_type)).or_default() += 1;
+    }
+
+    let mut graph = String::from("digraph flows {\n    rankdir=LR;\n    node [shape=box];\n");
+    for (name, id) in participants.iter() {
+        let _ = writeln!(graph, "    {} [label=\"{}\"];", id, dot_escape(name));
+    }
+    for ((from, to, message_type), count) in edges {
+        let label = if count == 1 { message_type.to_string() } else { format!("{} ×{}", message_type, count) };
+        let _ = writeln!(graph, "    {} -> {} [label=\"{}\"];", from, to, dot_escape(&label));
+    }
+    graph.push_str("}\n");
+    graph
+}
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+static LOG: Mutex<FlowLog> = Mutex::new(FlowLog { events: VecDeque::new(), limit: DEFAULT_FLOW_LIMIT, next_seq: 0 });
+
+thread_local! {
+    /// Actor whose message this thread is handling
+    static RECEIVING: Cell<Option<Pid>> = const { Cell::new(None) };
+}
+
+struct FlowLog {
+    events: VecDeque<FlowEvent>,
+    limit: usize,
+    next_seq: u64,
+}
+
+impl FlowLog {
+    fn push(&mut self, mut event: FlowEvent) {
+        event.seq = self.next_seq;
+        self.next_seq += 1;
+        if self.events.len() >= self.limit {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+}
+
+fn sender(event: &FlowEvent) -> String {
+    event.from.map_or_else(|| EXTERNAL.to_string(), |from| from.to_string())
+}
+
+/// Processes taking part, in order of first appearance, with their
+/// diagram identifiers
+struct Participants {
+    names: Vec<String>,
+    ids: HashMap<String, String>,
+}
+
+impl Participants {
+    fn of(events: &[FlowEvent]) -> Self {
+        let mut participants = Participants { names: Vec::new(), ids: HashMap::new() };
+        for event in events {
+            for name in [sender(event), event.to.to_string()] {
+                if !participants.ids.contains_key(&name) {
+                    participants.ids.insert(name.clone(), format!("p{}", participants.names.len()));
+                    participants.names.push(name);
+                }
+            }
+        }
+        participants
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.names.iter().map(|name| (name.as_str(), self.ids[name].as_str()))
+    }
+
+    fn id(&self, name: &str) -> &str {
+        &self.ids[name]
+    }
+}
+
+/// Escape the characters Mermaid gives meaning to in message text
+fn mermaid_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '#' => escaped.push_str("#35;"),
+            ';' => escaped.push_str("#59;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn event(from: Option<u64>, to: u64, message_type: &str, summary: &str) -> FlowEvent {
+        FlowEvent {
+            seq: 0,
+            sent_at: SystemTime::now(),
+            from: from.map(Pid::from_raw),
+            to: Pid::from_raw(to),
+            message_type: message_type.to_string(),
+            summary: summary.to_string(),
+            trace_id: None,
+        }
+    }
+
+    #[test]
+    fn test_message_type() {
+        assert_eq!(message_type(&MessagePayload::Text("hi".to_string())), "text");
+        assert_eq!(message_type(&MessagePayload::Control(ControlMessage::Exit { pid: Pid::from_raw(1), reason: "done".to_string() })), "exit");
+        let typed = TypedMessage { schema: "order.placed".to_string(), version: 2, body: serde_json::json!({"id": 7}) };
+        assert_eq!(message_type(&typed.into_payload()), "order.placed");
+        assert_eq!(message_type(&MessagePayload::Data(serde_json::json!({"id": 7}))), "data");
+    }
+
+    #[test]
+    fn test_filter() {
+        let ping = event(Some(1), 2, "ping", "\"ping\"");
+        let pong = event(Some(2), 3, "pong", "\"pong\"");
+        assert!(FlowFilter::default().matches(&ping));
+
+        let by_pid = FlowFilter { pids: vec![Pid::from_raw(1)], ..Default::default() };
+        assert!(by_pid.matches(&ping) && !by_pid.matches(&pong));
+        let by_type = FlowFilter { message_types: vec!["pong".to_string()], ..Default::default() };
+        assert!(!by_type.matches(&ping) && by_type.matches(&pong));
+
+        let later = ping.sent_at + Duration::from_secs(60);
+        assert!(!FlowFilter { since: Some(later), ..Default::default() }.matches(&ping));
+        assert!(!FlowFilter { until: Some(ping.sent_at), ..Default::default() }.matches(&ping));
+        assert!(FlowFilter { until: Some(later), ..Default::default() }.matches(&ping));
+    }
+
+    #[test]
+    fn test_sends_are_recorded_from_the_receiving_actor() {
+        let (actor, peer) = (Pid::new(), Pid::new());
+        let only_these = FlowFilter { pids: vec![actor, peer], ..Default::default() };
+        record(None, peer, &MessagePayload::Text("dropped".to_string()));
+
+        start(100);
+        record(None, actor, &MessagePayload::Text("start".to_string()));
+        {
+            let _scope = receiving(actor);
+            record(None, peer, &MessagePayload::Text("ping".to_string()));
+            record(Some(peer), actor, &MessagePayload::Text("pong".to_string()));
+        }
+        let status = stop();
+        record(None, peer, &MessagePayload::Text("late".to_string()));
+
+        assert!(!status.recording && status.limit == 100);
+        let events = query(&only_these);
+        let summaries: Vec<&str> = events.iter().map(|event| event.summary.as_str()).collect();
+        assert_eq!(summaries, ["\"start\"", "\"ping\"", "\"pong\""]);
+        assert_eq!(events[0].from, None);
+        assert_eq!(events[1].from, Some(actor));
+        assert_eq!(events[2].from, Some(peer));
+        assert!(events[1].seq < events[2].seq);
+    }
+
+    #[test]
+    fn test_limit_drops_the_oldest() {
+        let mut log = FlowLog { events: VecDeque::new(), limit: 2, next_seq: 0 };
+        for summary in ["a", "b", "c"] {
+            log.push(event(None, 1, "text", summary));
+        }
+        let kept: Vec<(u64, &str)> = log.events.iter().map(|event| (event.seq, event.summary.as_str())).collect();
+        assert_eq!(kept, [(1, "b"), (2, "c")]);
+    }
+
+    #[test]
+    fn test_mermaid() {
+        let events = [
+            event(None, 1, "text", "\"start\""),
+            event(Some(1), 2, "ping", "<1 byte>; #1"),
+            event(Some(2), 1, "pong", "ok"),
+        ];
+        assert_eq!(to_mermaid(&events), "\
+sequenceDiagram
+    participant p0 as external
+    participant p1 as #35;1
+    participant p2 as #35;2
+    p0->>p1: text \"start\"
+    p1->>p2: ping #lt;1 byte#gt;#59; #35;1
+    p2->>p1: pong ok
+");
+    }
+
+    #[test]
+    fn test_dot() {
+        let events = [
+            event(Some(1), 2, "ping", "1"),
+            event(Some(1), 2, "ping", "2"),
+            event(Some(2), 1, "pong", "\"ok\""),
+        ];
+        assert_eq!(to_dot(&events), "\
+digraph flows {
+    rankdir=LR;
+    node [shape=box];
+    p0 [label=\"#1\"];
+    p1 [label=\"#2\"];
+    p0 -> p1 [label=\"ping ×2\"];
+    p1 -> p0 [label=\"pong\"];
+}
+");
+    }
+}
@@ -2,6 +2,11 @@
 //! 
 //! This module provides debugging and tracing capabilities for actor systems.
 
+pub mod replay;
+pub mod profiler;
+pub mod cpu_profiler;
+pub mod flow;
+
 use std::time::Instant;
 use crate::types::Pid;
 
@@ -0,0 +1,474 @@
+//! Sampling heap profiler
+//!
+//! `ProfilingAllocator` wraps the system allocator and must be installed as
+//! the global allocator of the binary. While a process is being profiled,
+//! the allocations it makes are counted and one per `interval` bytes is
+//! sampled with its stack. Frees of sampled blocks are tracked, so a profile
+//! also tells what is still live when it is taken. Stacks are resolved to
+//! symbols, with file and line where debug info is available, only when the
+//! profile is taken.
+//!
+//! Allocations are attributed to the process a thread is running, as set by
+//! `enter`, which `Process::run_quantum` calls. Processes that are not being
+//! profiled pay one thread-local read per allocation.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fmt::Write;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Serialize, Deserialize};
+
+use crate::types::Pid;
+
+/// Bytes allocated between samples unless asked otherwise
+pub const DEFAULT_INTERVAL: usize = 64 * 1024;
+
+/// Deepest stack kept for a sample
+const MAX_FRAMES: usize = 48;
+
+/// Frames of the profiler and the allocator shims, trimmed from the top of
+/// sampled stacks
+const INTERNAL_FRAMES: &[&str] = &["backtrace::", "debug::profiler::", "ProfilingAllocator", "__rust_", "__rustc::", "__rdl_", "alloc::alloc::"];
+
+/// Frames of the standard library, skipped when looking for the site that
+/// made an allocation
+const LIBRARY_FRAMES: &[&str] = &["alloc::", "core::", "std::", "hashbrown::"];
+
+/// Kinds of allocation, by the frames that make them
+const KINDS: &[(&str, &str)] = &[
+    ("alloc::raw_vec::", "Vec"),
+    ("alloc::vec::", "Vec"),
+    ("alloc::string::", "String"),
+    ("alloc::str::", "String"),
+    ("alloc::fmt::format", "String"),
+    ("alloc::boxed::", "Box"),
+    ("alloc::sync::", "Arc"),
+    ("alloc::rc::", "Rc"),
+    ("alloc::collections::btree", "BTreeMap"),
+    ("alloc::collections::vec_deque", "VecDeque"),
+    ("hashbrown::", "HashMap"),
+];
+
+/// Global allocator that feeds the heap profiler
+pub struct ProfilingAllocator;
+
+unsafe impl GlobalAlloc for ProfilingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let block = System.alloc(layout);
+        track_alloc(block, layout.size());
+        block
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let block = System.alloc_zeroed(layout);
+        track_alloc(block, layout.size());
+        block
+    }
+
+    unsafe fn dealloc(&self, block: *mut u8, layout: Layout) {
+        track_free(block);
+        System.dealloc(block, layout)
+    }
+
+    unsafe fn realloc(&self, block: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        track_free(block);
+        let moved = System.realloc(block, layout, new_size);
+        track_alloc(moved, new_size);
+        moved
+    }
+}
+
+/// Heap profile of a process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeapProfile {
+    /// Profiled process
+    pub pid: Pid,
+    /// Bytes allocated between samples
+    pub interval: usize,
+    /// When profiling started
+    pub started_at: SystemTime,
+    /// How long the process was profiled
+    pub duration: Duration,
+    /// Allocations made by the process
+    pub allocations: u64,
+    /// Bytes allocated by the process
+    pub bytes: u64,
+    /// Sampled allocation sites, most bytes first
+    pub sites: Vec<AllocationSite>,
+}
+
+/// Allocations sampled with the same stack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocationSite {
+    /// What was allocated: `Vec`, `String`, `Box`, `HashMap`, ... or `other`
+    pub kind: String,
+    /// Innermost frame outside the standard library, with file and line
+    /// when debug info is available
+    pub site: Option<String>,
+    /// Stack of the allocation, outermost frame first
+    pub stack: Vec<String>,
+    /// Samples taken
+    pub samples: u64,
+    /// Bytes allocated, estimated from the samples
+    pub bytes: u64,
+    /// Bytes still live when the profile was taken, estimated from the samples
+    pub live_bytes: u64,
+}
+
+impl HeapProfile {
+    /// Estimated bytes still live when the profile was taken
+    pub fn live_bytes(&self) -> u64 {
+        self.sites.iter().map(|site| site.live_bytes).sum()
+    }
+
+    /// Stacks in the folded format read by flamegraph tools, weighted by
+    /// bytes allocated
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for site in &self.sites {
+            let frames: Vec<String> = site.stack.iter().map(|frame| frame.replace(';', ":")).collect();
+            let _ = writeln!(folded, "{};[{}] {}", frames.join(";"), site.kind, site.bytes);
+        }
+        folded
+    }
+}
+
+/// Whether `ProfilingAllocator` is the global allocator
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Start profiling a process, one sample per `interval` bytes; restarts a
+/// profile already in progress
+pub fn start(pid: Pid, interval: usize) {
+    let session = Arc::new(Session {
+        id: NEXT_SESSION.fetch_add(1, Ordering::Relaxed),
+        pid,
+        interval: interval.max(1) as u64,
+        started_at: SystemTime::now(),
+        started: Instant::now(),
+        allocations: AtomicU64::new(0),
+        bytes: AtomicU64::new(0),
+        stacks: Mutex::new(Stacks::default()),
+    });
+    if SESSIONS.write().unwrap().insert(pid, session).is_none() {
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Stop profiling a process, returning its profile
+pub fn stop(pid: Pid) -> Option<HeapProfile> {
+    let session = SESSIONS.write().unwrap().remove(&pid)?;
+    ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    Some(session.report())
+}
+
+/// Whether a process is being profiled
+pub fn is_profiling(pid: Pid) -> bool {
+    SESSIONS.read().unwrap().contains_key(&pid)
+}
+
+/// Attribute the allocations of this thread to `pid` until the scope drops
+pub fn enter(pid: Pid) -> ProcessScope {
+    let session = if ACTIVE.load(Ordering::Relaxed) == 0 {
+        None
+    } else {
+        SESSIONS.read().unwrap().get(&pid).cloned()
+    };
+    let current = session.as_ref().map_or(ptr::null(), Arc::as_ptr);
+    let previous = CURRENT.with(|cell| cell.replace(current));
+    ProcessScope { previous, _session: session }
+}
+
+/// Allocations of a thread attributed to a process, see `enter`
+pub struct ProcessScope {
+    previous: *const Session,
+    /// Keeps the session the thread points at alive
+    _session: Option<Arc<Session>>,
+}
+
+impl Drop for ProcessScope {
+    fn drop(&mut self) {
+        CURRENT.with(|cell| cell.set(self.previous));
+    }
+}
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Sessions running; allocations are not looked at while there are none
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(0);
+
+static SESSIONS: LazyLock<RwLock<HashMap<Pid, Arc<Session>>>> = LazyLock::new(Default::default);
+
+/// Sampled blocks not freed yet, by address. Only locked while `BUSY`, as
+/// the allocator locks it too.
+static LIVE: LazyLock<Mutex<HashMap<usize, LiveBlock>>> = LazyLock::new(Default::default);
+
+thread_local! {
+    /// Session of the process this thread runs, kept alive by a `ProcessScope`
+    static CURRENT: Cell<*const Session> = const { Cell::new(ptr::null()) };
+    /// Set while the profiler itself runs, so its own allocations are not
+    /// tracked and the locks it holds are not taken again
+    static BUSY: Cell<bool> = const { Cell::new(false) };
+}
+
+struct Session {
+    id: u64,
+    pid: Pid,
+    interval: u64,
+    started_at: SystemTime,
+    started: Instant,
+    allocations: AtomicU64,
+    bytes: AtomicU64,
+    /// Only locked while `BUSY`, as the allocator locks it too
+    stacks: Mutex<Stacks>,
+}
+
+#[derive(Default)]
+struct Stacks {
+    index: HashMap<Vec<usize>, usize>,
+    samples: Vec<SampledStack>,
+}
+
+struct SampledStack {
+    ips: Vec<usize>,
+    samples: u64,
+    bytes: u64,
+}
+
+struct LiveBlock {
+    session: u64,
+    stack: usize,
+    bytes: u64,
+}
+
+/// Run `f` with tracking off for this thread; `None` when it already is
+fn untracked<R>(f: impl FnOnce() -> R) -> Option<R> {
+    let entered = BUSY.try_with(|busy| !busy.replace(true)).unwrap_or(false);
+    if !entered {
+        return None;
+    }
+    let result = f();
+    let _ = BUSY.try_with(|busy| busy.set(false));
+    Some(result)
+}
+
+fn track_alloc(block: *mut u8, size: usize) {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        INSTALLED.store(true, Ordering::Relaxed);
+    }
+    if block.is_null() || ACTIVE.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let Ok(session) = CURRENT.try_with(Cell::get) else { return };
+    if session.is_null() {
+        return;
+    }
+    // SAFETY: the `ProcessScope` that set `CURRENT` holds the session
+    // until it resets `CURRENT`
+    let session = unsafe { &*session };
+    untracked(|| session.record(block as usize, size as u64));
+}
+
+fn track_free(block: *mut u8) {
+    if block.is_null() || ACTIVE.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    untracked(|| LIVE.lock().unwrap().remove(&(block as usize)));
+}
+
+impl Session {
+    fn record(&self, block: usize, size: u64) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        let before = self.bytes.fetch_add(size, Ordering::Relaxed);
+        let crossed = (before + size) / self.interval - before / self.interval;
+        if crossed == 0 {
+            return;
+        }
+
+        let mut ips = Vec::with_capacity(MAX_FRAMES);
+        backtrace::trace(|frame| {
+            ips.push(frame.ip() as usize);
+            ips.len() < MAX_FRAMES
+        });
+        let bytes = crossed * self.interval;
+        let stack = {
+            let mut stacks = self.stacks.lock().unwrap();
+            let stacks = &mut *stacks;
+            let index = *stacks.index.entry(ips).or_insert_with_key(|ips| {
+                stacks.samples.push(SampledStack { ips: ips.clone(), samples: 0, bytes: 0 });
+                stacks.samples.len() - 1
+            });
+            stacks.samples[index].samples += 1;
+            stacks.samples[index].bytes += bytes;
+            index
+        };
+        LIVE.lock().unwrap().insert(block, LiveBlock { session: self.id, stack, bytes });
+    }
+
+    fn report(&self) -> HeapProfile {
+        let (stacks, live) = untracked(|| {
+            let stacks = std::mem::take(&mut *self.stacks.lock().unwrap());
+            let mut live: HashMap<usize, u64> = HashMap::new();
+            LIVE.lock().unwrap().retain(|_, block| {
+                if block.session != self.id {
+                    return true;
+                }
+                *live.entry(block.stack).or_default() += block.bytes;
+                false
+            });
+            (stacks, live)
+        }).unwrap_or_default();
+
+        let mut symbols = HashMap::new();
+        let mut sites: Vec<AllocationSite> = Vec::new();
+        let mut merged: HashMap<(Vec<String>, String), usize> = HashMap::new();
+        for (index, sampled) in stacks.samples.into_iter().enumerate() {
+            let mut frames: Vec<Frame> = sampled.ips.iter()
+                .flat_map(|ip| symbols.entry(*ip).or_insert_with(|| resolve(*ip)).clone())
+                .collect();
+            let internal = frames.iter()
+                .take_while(|frame| INTERNAL_FRAMES.iter().any(|prefix| frame.name.contains(prefix)))
+                .count();
+            frames.drain(..internal);
+
+            let kind = frames.iter()
+                .find_map(|frame| KINDS.iter().find(|(prefix, _)| frame.name.contains(prefix)))
+                .map_or("other", |(_, kind)| kind)
+                .to_string();
+            let site = frames.iter()
+                .find(|frame| !LIBRARY_FRAMES.iter().any(|prefix| frame.name.trim_start_matches('<').starts_with(prefix)))
+                .map(Frame::describe);
+            let stack: Vec<String> = frames.iter().rev().map(|frame| frame.name.clone()).collect();
+            let live_bytes = live.get(&index).copied().unwrap_or(0);
+
+            match merged.get(&(stack.clone(), kind.clone())) {
+                Some(&existing) => {
+                    let existing = &mut sites[existing];
+                    existing.samples += sampled.samples;
+                    existing.bytes += sampled.bytes;
+                    existing.live_bytes += live_bytes;
+                }
+                None => {
+                    merged.insert((stack.clone(), kind.clone()), sites.len());
+                    sites.push(AllocationSite { kind, site, stack, samples: sampled.samples, bytes: sampled.bytes, live_bytes });
+                }
+            }
+        }
+        sites.sort_by_key(|site| std::cmp::Reverse(site.bytes));
+
+        HeapProfile {
+            pid: self.pid,
+            interval: self.interval as usize,
+            started_at: self.started_at,
+            duration: self.started.elapsed(),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            sites,
+        }
+    }
+}
+
+/// A resolved stack frame
+#[derive(Clone)]
+struct Frame {
+    name: String,
+    location: Option<String>,
+}
+
+impl Frame {
+    fn describe(&self) -> String {
+        match &self.location {
+            Some(location) => format!("{} ({})", self.name, location),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Frames at an instruction pointer, innermost first when calls were inlined
+fn resolve(ip: usize) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    backtrace::resolve(ip as *mut c_void, |symbol| {
+        let name = symbol.name().map_or_else(|| format!("{:#x}", ip), |name| format!("{:#}", name));
+        let location = symbol.filename().zip(symbol.lineno())
+            .map(|(file, line)| format!("{}:{}", file.display(), line));
+        frames.push(Frame { name, location });
+    });
+    if frames.is_empty() {
+        frames.push(Frame { name: format!("{:#x}", ip), location: None });
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+
+    #[global_allocator]
+    static ALLOCATOR: ProfilingAllocator = ProfilingAllocator;
+
+    #[inline(never)]
+    fn allocate_buffers() -> Vec<Vec<u64>> {
+        (0..16).map(|i| vec![i; 1024]).collect()
+    }
+
+    #[test]
+    fn test_profile_samples_allocations_of_the_process() {
+        assert!(is_installed());
+        let pid = Pid::new();
+        start(pid, 1024);
+        assert!(is_profiling(pid));
+
+        let kept = {
+            let _scope = enter(pid);
+            let kept = allocate_buffers();
+            black_box(format!("{:?}", &kept[0][..100]));
+            kept
+        };
+        // Not attributed to the process once the scope is gone
+        black_box(vec![0u8; 1 << 20]);
+
+        let profile = stop(pid).unwrap();
+        assert!(!is_profiling(pid));
+        assert!(profile.allocations >= 17);
+        assert!(profile.bytes >= 16 * 1024 * 8 && profile.bytes < 1 << 20, "{}", profile.bytes);
+        assert!(profile.sites.iter().any(|site| site.kind == "Vec"), "{:?}", profile.sites);
+        assert!(profile.sites.iter().any(|site| site.stack.iter().any(|frame| frame.contains("allocate_buffers"))));
+        assert!(profile.live_bytes() >= 8 * 1024 * 8, "{}", profile.live_bytes());
+        drop(kept);
+
+        for line in profile.folded().lines() {
+            let (stack, bytes) = line.rsplit_once(' ').unwrap();
+            assert!(stack.ends_with(']'));
+            bytes.parse::<u64>().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_unprofiled_processes_are_not_tracked() {
+        let pid = Pid::new();
+        {
+            let _scope = enter(pid);
+            black_box(allocate_buffers());
+        }
+        assert!(stop(pid).is_none());
+
+        start(pid, DEFAULT_INTERVAL);
+        let other = Pid::new();
+        {
+            let _scope = enter(other);
+            black_box(allocate_buffers());
+        }
+        let profile = stop(pid).unwrap();
+        assert_eq!(profile.allocations, 0);
+        assert!(profile.sites.is_empty());
+    }
+}
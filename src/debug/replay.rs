@@ -0,0 +1,307 @@
+//! Time-travel debugging
+//!
+//! A process that is being recorded keeps every message delivered to its
+//! actor together with a snapshot of the actor's process dictionary taken
+//! after the message was handled. A `Replay` steps forward and backward
+//! through such a recording. Given a factory for the recorded actor it
+//! re-runs the deliveries against a fresh actor in a sandboxed process that
+//! belongs to no runtime, so nothing the replay does reaches the live
+//! system; without one it steps through the recorded snapshots.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+
+use crate::runtime::actor::ReamActor;
+use crate::runtime::process::Process;
+use crate::types::{MessagePayload, Pid, Priority};
+
+/// Deliveries kept by a recording unless asked otherwise
+pub const DEFAULT_RECORDING_LIMIT: usize = 10_000;
+
+/// Snapshot of an actor's process dictionary
+pub type StateSnapshot = BTreeMap<String, String>;
+
+/// A message delivered to a recorded actor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    /// Position in the recording, counting deliveries dropped by the limit
+    pub index: u64,
+    /// When the message was handled
+    pub delivered_at: SystemTime,
+    /// The message as delivered
+    pub message: MessagePayload,
+    /// The actor's process dictionary after handling the message
+    pub state: StateSnapshot,
+    /// Error the actor failed with, if it did
+    pub error: Option<String>,
+}
+
+/// Message history of one actor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// Recorded process
+    pub pid: Pid,
+    /// When recording started
+    pub started_at: SystemTime,
+    /// The actor's process dictionary before the first kept delivery
+    pub initial_state: StateSnapshot,
+    /// Kept deliveries, oldest first
+    pub deliveries: VecDeque<Delivery>,
+    /// Most deliveries kept; older ones are dropped
+    pub limit: usize,
+    /// Deliveries dropped by the limit
+    pub dropped: u64,
+}
+
+impl Recording {
+    /// Start recording an actor whose dictionary is currently `initial_state`
+    pub fn new(pid: Pid, initial_state: StateSnapshot, limit: usize) -> Self {
+        Recording {
+            pid,
+            started_at: SystemTime::now(),
+            initial_state,
+            deliveries: VecDeque::new(),
+            limit: limit.max(1),
+            dropped: 0,
+        }
+    }
+
+    /// Add a delivery, dropping the oldest one when over the limit
+    pub fn record(&mut self, message: MessagePayload, state: StateSnapshot, error: Option<String>) {
+        let index = self.dropped + self.deliveries.len() as u64;
+        self.deliveries.push_back(Delivery {
+            index,
+            delivered_at: SystemTime::now(),
+            message,
+            state,
+            error,
+        });
+        if self.deliveries.len() > self.limit {
+            if let Some(oldest) = self.deliveries.pop_front() {
+                // The replay now starts where the dropped delivery left off
+                self.initial_state = oldest.state;
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Number of kept deliveries
+    pub fn len(&self) -> usize {
+        self.deliveries.len()
+    }
+
+    /// Whether no deliveries are kept
+    pub fn is_empty(&self) -> bool {
+        self.deliveries.is_empty()
+    }
+}
+
+/// Creates the actor a sandboxed replay re-runs deliveries against
+pub type ActorFactory = Box<dyn Fn(Pid) -> Box<dyn ReamActor>>;
+
+/// A sandboxed process and the factory that rebuilds it on rewind
+struct Sandbox {
+    factory: ActorFactory,
+    process: Process,
+    /// Errors of the deliveries re-run so far
+    errors: Vec<Option<String>>,
+}
+
+impl Sandbox {
+    fn new(factory: ActorFactory) -> Self {
+        let process = Self::spawn(&factory);
+        Sandbox { factory, process, errors: Vec::new() }
+    }
+
+    /// A fresh process that belongs to no runtime
+    fn spawn(factory: &ActorFactory) -> Process {
+        let pid = Pid::new();
+        Process::new(pid, factory(pid), Priority::Normal)
+    }
+
+    fn deliver(&mut self, message: &MessagePayload) {
+        let error = self.process.deliver(message.clone()).err().map(|e| e.to_string());
+        self.errors.push(error);
+    }
+
+    fn reset(&mut self) {
+        self.process = Self::spawn(&self.factory);
+        self.errors.clear();
+    }
+}
+
+/// Steps through a recording
+pub struct Replay {
+    recording: Recording,
+    /// Number of deliveries applied, from 0 to the recording's length
+    position: usize,
+    sandbox: Option<Sandbox>,
+}
+
+impl Replay {
+    /// Step through the recorded snapshots
+    pub fn new(recording: Recording) -> Self {
+        Replay { recording, position: 0, sandbox: None }
+    }
+
+    /// Re-run the deliveries against actors made by `factory`, which must
+    /// create the actor in the state the recording starts from
+    pub fn sandboxed(recording: Recording, factory: ActorFactory) -> Self {
+        Replay { recording, position: 0, sandbox: Some(Sandbox::new(factory)) }
+    }
+
+    /// The recording being replayed
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Number of deliveries applied
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Whether deliveries are re-run in a sandbox
+    pub fn is_sandboxed(&self) -> bool {
+        self.sandbox.is_some()
+    }
+
+    /// The last delivery applied, if any
+    pub fn current(&self) -> Option<&Delivery> {
+        self.position.checked_sub(1).and_then(|i| self.recording.deliveries.get(i))
+    }
+
+    /// Apply the next delivery, returning it; `None` at the end
+    pub fn forward(&mut self) -> Option<&Delivery> {
+        let delivery = self.recording.deliveries.get(self.position)?;
+        if let Some(sandbox) = self.sandbox.as_mut() {
+            sandbox.deliver(&delivery.message);
+        }
+        self.position += 1;
+        self.current()
+    }
+
+    /// Undo the last delivery; `false` at the start
+    pub fn back(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.seek(self.position - 1);
+        true
+    }
+
+    /// Go to the point where `position` deliveries have been applied
+    pub fn seek(&mut self, position: usize) {
+        let position = position.min(self.recording.len());
+        if position < self.position {
+            // Actors cannot be rolled back, so re-run from a fresh one
+            if let Some(sandbox) = self.sandbox.as_mut() {
+                sandbox.reset();
+            }
+            self.position = 0;
+        }
+        while self.position < position {
+            self.forward();
+        }
+    }
+
+    /// The actor's process dictionary at the current position
+    pub fn state(&self) -> StateSnapshot {
+        match &self.sandbox {
+            Some(sandbox) => sandbox.process.dictionary(),
+            None => self.recorded_state().clone(),
+        }
+    }
+
+    /// The recorded dictionary at the current position
+    pub fn recorded_state(&self) -> &StateSnapshot {
+        self.current().map(|delivery| &delivery.state).unwrap_or(&self.recording.initial_state)
+    }
+
+    /// Error the last applied delivery failed with, if it did
+    pub fn error(&self) -> Option<&str> {
+        match &self.sandbox {
+            Some(sandbox) => sandbox.errors.last().and_then(|error| error.as_deref()),
+            None => self.current().and_then(|delivery| delivery.error.as_deref()),
+        }
+    }
+
+    /// Whether the sandboxed actor's state differs from the recorded one
+    pub fn diverged(&self) -> bool {
+        self.sandbox.is_some() && self.state() != *self.recorded_state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::actor::CounterActor;
+
+    fn count(state: &StateSnapshot) -> &str {
+        state.get("count").map(String::as_str).unwrap_or("")
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let pid = Pid::new();
+        let mut process = Process::new(pid, Box::new(CounterActor::new(pid, 10)), Priority::Normal);
+        process.start_recording(DEFAULT_RECORDING_LIMIT);
+        {
+            let mailbox = process.mailbox();
+            let mut mb = mailbox.write().unwrap();
+            mb.send(MessagePayload::Text("increment".to_string()));
+            mb.send(MessagePayload::Text("increment".to_string()));
+            mb.send(MessagePayload::Data(serde_json::json!(5)));
+            mb.send(MessagePayload::Text("explode".to_string()));
+        }
+        assert!(process.run_quantum().is_err());
+
+        let recording = process.stop_recording().unwrap();
+        assert!(process.recording().is_none());
+        assert_eq!(recording.len(), 4);
+        assert_eq!(count(&recording.initial_state), "10");
+        assert_eq!(count(&recording.deliveries[2].state), "17");
+        assert!(recording.deliveries[3].error.is_some());
+
+        // Stepping through the recorded snapshots
+        let mut replay = Replay::new(recording.clone());
+        assert_eq!(count(&replay.state()), "10");
+        replay.seek(3);
+        assert_eq!(count(&replay.state()), "17");
+        assert!(replay.back());
+        assert_eq!(count(&replay.state()), "12");
+        assert_eq!(replay.forward().map(|delivery| delivery.index), Some(2));
+
+        // Re-running the deliveries in a sandbox
+        let mut replay = Replay::sandboxed(recording, Box::new(|pid| Box::new(CounterActor::new(pid, 10))));
+        replay.forward();
+        replay.forward();
+        assert_eq!(count(&replay.state()), "12");
+        assert!(!replay.diverged());
+        replay.seek(4);
+        assert!(replay.error().is_some());
+        assert!(replay.forward().is_none());
+        assert!(replay.back());
+        assert_eq!(replay.position(), 3);
+        assert_eq!(replay.error(), None);
+        assert_eq!(count(&replay.state()), "17");
+        replay.seek(0);
+        assert_eq!(count(&replay.state()), "10");
+        assert!(!replay.back());
+    }
+
+    #[test]
+    fn test_recording_limit() {
+        let state = |count: i32| BTreeMap::from([("count".to_string(), count.to_string())]);
+        let mut recording = Recording::new(Pid::new(), state(0), 2);
+        for i in 1..=3 {
+            recording.record(MessagePayload::Text("increment".to_string()), state(i), None);
+        }
+
+        assert_eq!(recording.len(), 2);
+        assert_eq!(recording.dropped, 1);
+        assert_eq!(recording.initial_state, state(1));
+        assert_eq!(recording.deliveries[0].index, 1);
+        assert_eq!(recording.deliveries[1].index, 2);
+    }
+}
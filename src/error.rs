@@ -91,6 +91,10 @@ pub enum RuntimeError {
     #[error("Maximum number of processes ({0}) reached")]
     MaxProcesses(usize),
 
+    /// A quota would be exceeded
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
     /// General runtime error
     #[error("Runtime error: {0}")]
     RuntimeError(String),
@@ -106,6 +110,14 @@ pub enum RuntimeError {
     /// Message delivery error
     #[error("Message delivery error: {0}")]
     MessageDelivery(String),
+
+    /// Event streaming connector error
+    #[error("Connector error: {0}")]
+    Connector(String),
+
+    /// Event journal error
+    #[error("Persistence error: {0}")]
+    Persistence(String),
 }
 
 impl From<std::io::Error> for RuntimeError {
@@ -169,6 +181,34 @@ pub enum BytecodeError {
     /// Verification failed
     #[error("Bytecode verification failed: {0}")]
     Verification(String),
+
+    /// Bundle could not be read or written
+    #[error("Bundle error: {0}")]
+    Bundle(String),
+
+    /// Bytecode program file could not be read or written
+    #[error("Invalid bytecode file: {0}")]
+    Format(String),
+
+    /// Bytecode assembly could not be parsed
+    #[error("Assembly error on line {line}: {message}")]
+    Assembly { line: usize, message: String },
+
+    /// Execution aborted by debugger hooks
+    #[error("Execution aborted at instruction {0}")]
+    Aborted(usize),
+
+    /// A metered program ran out of fuel
+    #[error("Out of fuel at instruction {0}")]
+    OutOfFuel(usize),
+
+    /// A program not running as a process waited for a message
+    #[error("No message to receive at instruction {0}")]
+    NoMessage(usize),
+
+    /// A thrown value reached the top without meeting a handler
+    #[error("Uncaught exception: {0}")]
+    Uncaught(String),
 }
 
 /// JIT compilation errors
@@ -0,0 +1,205 @@
+//! Ahead-of-time compilation to object code
+//!
+//! `AotCompiler` emits the code the JIT would generate for a program into
+//! a relocatable object for a chosen target. The object embeds the
+//! program in its on-disk format and exports a C `main` that hands it and
+//! the compiled function to the runtime. Linking the object against the
+//! runtime built as a static library, with
+//! `cargo rustc --release --lib --crate-type staticlib`, gives a
+//! standalone executable that runs the program without compiling it first.
+
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, UserFuncName};
+use cranelift_codegen::isa;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_module::{default_libcall_names, DataDescription, DataId, FuncId, Linkage, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use target_lexicon::{Architecture, OperatingSystem, Triple};
+use crate::bytecode::BytecodeProgram;
+use crate::jit::ReamJIT;
+
+use crate::error::{JitError, JitResult};
+
+/// Architectures objects can be emitted for
+pub const AOT_ARCHITECTURES: &[&str] = &["x86_64", "aarch64"];
+
+/// Compiles programs to object code for a target
+#[derive(Debug, Clone)]
+pub struct AotCompiler {
+    /// Target triple
+    target: Triple,
+    /// Optimization level
+    opt_level: u8,
+}
+
+impl AotCompiler {
+    /// Compile for the machine the compiler runs on
+    pub fn host() -> Self {
+        AotCompiler {
+            target: Triple::host(),
+            opt_level: 2,
+        }
+    }
+
+    /// Compile for a target triple, such as `x86_64-unknown-linux-gnu`
+    /// or `aarch64-apple-darwin`
+    pub fn new(target: &str) -> JitResult<Self> {
+        let triple = Triple::from_str(target)
+            .map_err(|e| JitError::CodeGeneration(format!("Invalid target {}: {}", target, e)))?;
+        if !matches!(triple.architecture, Architecture::X86_64 | Architecture::Aarch64(_)) {
+            return Err(JitError::CodeGeneration(format!(
+                "Unsupported target architecture {}, expected one of {}",
+                triple.architecture, AOT_ARCHITECTURES.join(", ")
+            )));
+        }
+        Ok(AotCompiler {
+            target: triple,
+            opt_level: 2,
+        })
+    }
+
+    /// Set optimization level
+    pub fn set_optimization_level(&mut self, level: u8) {
+        self.opt_level = level.min(3);
+    }
+
+    /// The target triple
+    pub fn target(&self) -> String {
+        self.target.to_string()
+    }
+
+    /// Whether the target is the machine the compiler runs on
+    pub fn is_host(&self) -> bool {
+        self.target == Triple::host()
+    }
+
+    /// Compile a program to an object exporting `main`
+    pub fn compile_object(&self, program: &BytecodeProgram) -> JitResult<Vec<u8>> {
+        let isa = isa::lookup(self.target.clone())
+            .map_err(|e| JitError::CodeGeneration(format!("Target {} is not supported: {}", self.target, e)))?
+            .finish(ReamJIT::flags(self.opt_level, true)?)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        let builder = ObjectBuilder::new(isa, program.metadata.name.clone(), default_libcall_names())
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        let mut module = ObjectModule::new(builder);
+
+        let (entry, _, _) = ReamJIT::define_program(&mut module, "ream_aot_program", Linkage::Local, program, &[])?;
+        let bytes = program.to_bytes()
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        let data = module.declare_data("ream_aot_bytecode", Linkage::Local, false, false)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        let len = bytes.len();
+        let mut description = DataDescription::new();
+        description.define(bytes.into_boxed_slice());
+        module.define_data(data, &description)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        Self::define_main(&mut module, entry, data, len)?;
+
+        module.finish().emit()
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))
+    }
+
+    /// Define `main`, which runs the program through `ream_aot_main`
+    fn define_main(module: &mut ObjectModule, entry: FuncId, data: DataId, len: usize) -> JitResult<()> {
+        let pointer = module.target_config().pointer_type();
+        let mut run_signature = module.make_signature();
+        run_signature.params.extend([AbiParam::new(pointer), AbiParam::new(pointer), AbiParam::new(pointer)]);
+        run_signature.returns.push(AbiParam::new(types::I32));
+        let run = module.declare_function("ream_aot_main", Linkage::Import, &run_signature)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+
+        let mut signature = module.make_signature();
+        signature.params.extend([AbiParam::new(types::I32), AbiParam::new(pointer)]);
+        signature.returns.push(AbiParam::new(types::I32));
+        let main = module.declare_function("main", Linkage::Export, &signature)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+
+        let mut context = module.make_context();
+        context.func.signature = signature;
+        context.func.name = UserFuncName::user(0, main.as_u32());
+        let mut builder_context = FunctionBuilderContext::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+            let run = module.declare_func_in_func(run, builder.func);
+            let entry = module.declare_func_in_func(entry, builder.func);
+            let data = module.declare_data_in_func(data, builder.func);
+
+            let block = builder.create_block();
+            builder.append_block_params_for_function_params(block);
+            builder.switch_to_block(block);
+            let program = builder.ins().global_value(pointer, data);
+            let len = builder.ins().iconst(pointer, len as i64);
+            let entry = builder.ins().func_addr(pointer, entry);
+            let call = builder.ins().call(run, &[program, len, entry]);
+            let status = builder.inst_results(call)[0];
+            builder.ins().return_(&[status]);
+            builder.seal_all_blocks();
+            builder.finalize();
+        }
+        module.define_function(main, &mut context)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Link an object from `compile_object` with the runtime static
+    /// library into an executable. Uses the C compiler in `CC`, or `cc`,
+    /// which must target the same machine as the object.
+    pub fn link(&self, object: &Path, runtime: &Path, output: &Path) -> JitResult<()> {
+        let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+        let mut command = Command::new(&compiler);
+        command.arg(object).arg(runtime).arg("-o").arg(output);
+        command.args(self.system_libraries());
+        let result = command.output()
+            .map_err(|e| JitError::CodeGeneration(format!("Failed to run {}: {}", compiler, e)))?;
+        if !result.status.success() {
+            return Err(JitError::CodeGeneration(format!(
+                "Linking failed: {}", String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Libraries the runtime needs from the target system
+    fn system_libraries(&self) -> &'static [&'static str] {
+        match self.target.operating_system {
+            OperatingSystem::Darwin(_) | OperatingSystem::MacOSX(_) => {
+                &["-lssl", "-lcrypto", "-lc", "-lm", "-liconv", "-framework", "CoreFoundation", "-framework", "Security", "-framework", "SystemConfiguration"]
+            }
+            _ => &["-lssl", "-lcrypto", "-lgcc_s", "-lutil", "-lrt", "-lpthread", "-lm", "-ldl", "-lc"],
+        }
+    }
+}
+
+impl Default for AotCompiler {
+    fn default() -> Self {
+        Self::host()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::assemble;
+
+    #[test]
+    fn test_object_emission() {
+        let program = assemble(".const 0 int 1\n.const 1 int 2\n    const 0\n    const 1\n    add\n").unwrap();
+
+        // ELF objects record their machine at offset 18
+        let object = AotCompiler::new("x86_64-unknown-linux-gnu").unwrap().compile_object(&program).unwrap();
+        assert_eq!(&object[..4], b"\x7fELF");
+        assert_eq!(u16::from_le_bytes([object[18], object[19]]), 62);
+        let object = AotCompiler::new("aarch64-unknown-linux-gnu").unwrap().compile_object(&program).unwrap();
+        assert_eq!(u16::from_le_bytes([object[18], object[19]]), 183);
+
+        // Mach-O objects start with their magic number
+        let object = AotCompiler::new("aarch64-apple-darwin").unwrap().compile_object(&program).unwrap();
+        assert_eq!(&object[..4], &0xfeedfacf_u32.to_le_bytes());
+
+        assert!(AotCompiler::new("riscv64gc-unknown-linux-gnu").is_err());
+        assert!(AotCompiler::new("not a triple").is_err());
+        assert!(AotCompiler::host().is_host());
+    }
+}
@@ -0,0 +1,201 @@
+//! Background compilation
+//!
+//! A `BackgroundCompiler` runs compilations on worker threads so that
+//! execution never waits for one. Submitting a program returns a
+//! `PendingFunction` straight away; the caller keeps interpreting the
+//! program and switches to the compiled function once a worker has
+//! installed it there.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crossbeam_channel::{unbounded, Sender};
+use crate::bytecode::BytecodeProgram;
+use crate::jit::{JitFunction, ReamJIT};
+use crate::error::{JitError, JitResult};
+
+/// A compilation a worker has finished
+#[derive(Clone)]
+struct Compiled {
+    function: Result<Arc<JitFunction>, String>,
+    /// From submission until the function was installed
+    latency: Duration,
+}
+
+/// A function being compiled in the background
+pub struct PendingFunction {
+    /// Set once the compilation is installed, so checking for it never
+    /// takes the lock
+    ready: AtomicBool,
+    compiled: Mutex<Option<Compiled>>,
+    installed: Condvar,
+    submitted: Instant,
+}
+
+impl PendingFunction {
+    fn new() -> Self {
+        PendingFunction {
+            ready: AtomicBool::new(false),
+            compiled: Mutex::new(None),
+            installed: Condvar::new(),
+            submitted: Instant::now(),
+        }
+    }
+
+    /// Install the result of compiling the function
+    fn install(&self, function: JitResult<JitFunction>) {
+        let compiled = Compiled {
+            function: function.map(Arc::new).map_err(|e| e.to_string()),
+            latency: self.submitted.elapsed(),
+        };
+        *self.compiled.lock().unwrap_or_else(|e| e.into_inner()) = Some(compiled);
+        self.ready.store(true, Ordering::Release);
+        self.installed.notify_all();
+    }
+
+    fn compiled(&self) -> Option<Compiled> {
+        if !self.ready.load(Ordering::Acquire) {
+            return None;
+        }
+        self.compiled.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Whether the compilation has finished
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// The compiled function, once the compilation has finished
+    pub fn function(&self) -> Option<JitResult<Arc<JitFunction>>> {
+        self.compiled().map(|compiled| compiled.function.map_err(JitError::CodeGeneration))
+    }
+
+    /// Time from submission until the function was installed
+    pub fn latency(&self) -> Option<Duration> {
+        self.compiled().map(|compiled| compiled.latency)
+    }
+
+    /// Block until the compilation finishes
+    pub fn wait(&self) -> JitResult<Arc<JitFunction>> {
+        let mut compiled = self.compiled.lock().unwrap_or_else(|e| e.into_inner());
+        while compiled.is_none() {
+            compiled = self.installed.wait(compiled).unwrap_or_else(|e| e.into_inner());
+        }
+        compiled.as_ref()
+            .map(|compiled| compiled.function.clone().map_err(JitError::CodeGeneration))
+            .unwrap_or_else(|| Err(JitError::CodeGeneration("Compilation was lost".to_string())))
+    }
+}
+
+impl std::fmt::Debug for PendingFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingFunction")
+            .field("ready", &self.is_ready())
+            .finish()
+    }
+}
+
+/// A compilation waiting for a worker
+struct Job {
+    compiler: ReamJIT,
+    program: BytecodeProgram,
+    pending: Arc<PendingFunction>,
+}
+
+/// Compiles programs on a pool of worker threads
+pub struct BackgroundCompiler {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl BackgroundCompiler {
+    /// Start `threads` workers, at least one
+    pub fn new(threads: usize) -> Self {
+        let (jobs, queue) = unbounded::<Job>();
+        let workers = (0..threads.max(1))
+            .map(|index| {
+                let queue = queue.clone();
+                std::thread::Builder::new()
+                    .name(format!("ream-jit-{}", index))
+                    .spawn(move || {
+                        for mut job in queue.iter() {
+                            let function = job.compiler.compile_program(&job.program);
+                            job.pending.install(function);
+                        }
+                    })
+                    .expect("Failed to spawn JIT compilation thread")
+            })
+            .collect();
+        BackgroundCompiler {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    /// Queue `program` to be compiled by `compiler`
+    pub fn submit(&self, compiler: ReamJIT, program: BytecodeProgram) -> Arc<PendingFunction> {
+        let pending = Arc::new(PendingFunction::new());
+        let job = Job { compiler, program, pending: Arc::clone(&pending) };
+        let Some(jobs) = self.jobs.as_ref() else {
+            return pending;
+        };
+        // Compile on this thread if every worker has died
+        if let Err(error) = jobs.send(job) {
+            let mut job = error.into_inner();
+            let function = job.compiler.compile_program(&job.program);
+            job.pending.install(function);
+        }
+        pending
+    }
+
+    /// Number of worker threads
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl Drop for BackgroundCompiler {
+    fn drop(&mut self) {
+        // Workers finish the queued compilations, then stop once the
+        // queue closes
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for BackgroundCompiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackgroundCompiler")
+            .field("threads", &self.threads())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{assemble, Value};
+
+    #[test]
+    fn test_background_compilation() {
+        let compiler = BackgroundCompiler::new(2);
+        assert_eq!(compiler.threads(), 2);
+
+        let programs: Vec<_> = (0..4)
+            .map(|n| assemble(&format!(".const 0 int {}\n.const 1 int 1\n    const 0\n    const 1\n    add\n", n)).unwrap())
+            .collect();
+        let pending: Vec<_> = programs.iter()
+            .map(|program| compiler.submit(ReamJIT::new(), program.clone()))
+            .collect();
+        for (n, pending) in pending.iter().enumerate() {
+            let function = pending.wait().unwrap();
+            assert!(pending.is_ready());
+            assert!(pending.latency().is_some());
+            assert_eq!(function.call0().unwrap(), Value::Int(n as i64 + 1));
+            assert_eq!(pending.function().unwrap().unwrap().call0().unwrap(), Value::Int(n as i64 + 1));
+        }
+    }
+}
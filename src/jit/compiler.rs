@@ -1,23 +1,405 @@
 //! JIT compiler implementation
+//!
+//! A program compiles to one native function with a block per
+//! instruction. Control flow is native: jumps, branches and calls go
+//! straight to their target's block, while returns and exceptions, whose
+//! target is only known at run time, go through a jump table over every
+//! instruction.
+//!
+//! Integers and bools are computed in machine code. Compiled code holds
+//! the operands it works on in registers, tagged with their kind, and the
+//! current frame's locals in slots of its own, so constants, arithmetic,
+//! comparisons, branches and local loads and stores are Cranelift IR that
+//! only falls back to the VM for operands of another kind or on overflow.
+//! Floats get the same treatment at instructions type feedback has only
+//! seen floats at. Strings, lists, calls, natives and messages are left to
+//! a runtime helper that steps the bytecode VM over the instruction, with
+//! the values compiled code holds handed to the VM first and taken back
+//! after, so compiled code always computes what the VM would.
 
-use std::collections::HashMap;
-use crate::bytecode::{Bytecode, BytecodeProgram, Value};
+use std::collections::{HashMap, HashSet};
+use std::mem::ManuallyDrop;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use cranelift_codegen::ir::{self, condcodes::{FloatCC, IntCC}, types, AbiParam, Block, FuncRef, InstBuilder, MemFlags};
+use cranelift_codegen::ir::{SourceLoc, StackSlot, StackSlotData, StackSlotKind, Type, UserFuncName};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+use crate::bytecode::{Bytecode, BytecodeProgram, BytecodeVM, Value};
 use crate::jit::{JitFunction, JitMetadata};
+use crate::jit::feedback::{Specialization, TypeFeedback, ValueKind};
+use crate::jit::perf::{self, CodeSymbol};
+use crate::jit::simd::{self, VectorLoop};
+use crate::types::EffectGrade;
 
-use crate::error::{JitError, JitResult};
+use crate::error::{BytecodeError, BytecodeResult, JitError, JitResult};
+
+/// Compiled code returns this once the program runs past its last instruction
+const FINISHED: i64 = 0;
+/// An instruction failed with no handler to catch it
+const FAILED: i64 = -1;
+/// The program stopped in `receive` or at `yield`
+const SUSPENDED: i64 = -2;
+
+/// What compiled code runs against; compiled code reads `frame`,
+/// `frame_len` and `int_sites` itself
+struct JitState {
+    /// Locals of the current frame, as compiled code holds them
+    frame: *mut Slot,
+    /// How many locals the current frame has
+    frame_len: i64,
+    /// Times each instruction computed on integers in compiled code
+    int_sites: *mut u64,
+    /// Every frame's locals alongside the VM's: an int, float or bool is
+    /// held here, and anything else by the VM
+    slots: Vec<Slot>,
+    /// What `int_sites` points to
+    int_counts: Vec<u64>,
+    vm: BytecodeVM,
+    program: Arc<BytecodeProgram>,
+    error: Option<BytecodeError>,
+    /// Operand kinds seen this run
+    feedback: TypeFeedback,
+    /// Guards that failed this run
+    deoptimizations: u64,
+    /// Loops vector instructions can run, by header
+    loops: Arc<HashMap<usize, VectorLoop>>,
+    /// Loops that could not be run with vector instructions this run
+    scalar_loops: HashSet<usize>,
+}
+
+/// Signature of compiled programs: the state and a pc to start at in,
+/// a status out
+type CompiledFn = extern "C" fn(*mut JitState, i64) -> i64;
+
+impl JitState {
+    fn new(program: Arc<BytecodeProgram>, loops: Arc<HashMap<usize, VectorLoop>>, args: &[Value]) -> Self {
+        let mut vm = BytecodeVM::new();
+        vm.begin();
+        vm.context_mut().stack.extend_from_slice(args);
+        let mut int_counts = vec![0; program.instructions.len()];
+        let mut state = JitState {
+            frame: std::ptr::null_mut(),
+            frame_len: 0,
+            int_sites: int_counts.as_mut_ptr(),
+            slots: Vec::new(),
+            int_counts,
+            vm,
+            program,
+            error: None,
+            feedback: TypeFeedback::new(),
+            deoptimizations: 0,
+            loops,
+            scalar_loops: HashSet::new(),
+        };
+        state.absorb();
+        state
+    }
+
+    /// Run compiled code until the program finishes or fails
+    fn run(&mut self, entry: CompiledFn) -> BytecodeResult<Value> {
+        let result = self.run_from(entry, 0);
+        for (pc, count) in self.int_counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+            self.feedback.record_kind(pc, ValueKind::Int, *count);
+        }
+        result
+    }
+
+    fn run_from(&mut self, entry: CompiledFn, mut pc: i64) -> BytecodeResult<Value> {
+        loop {
+            match entry(self, pc) {
+                FINISHED => return self.vm.result(),
+                // Nothing else runs alongside, so a yielding program just
+                // continues, and one waiting for a message would wait forever
+                SUSPENDED if self.vm.is_waiting() => return Err(BytecodeError::NoMessage(self.vm.context().pc)),
+                SUSPENDED => {
+                    let context = self.vm.context_mut();
+                    context.suspended = None;
+                    pc = context.pc as i64;
+                }
+                _ => return Err(self.error.take().unwrap_or_else(|| {
+                    BytecodeError::InvalidInstruction("Compiled code failed".to_string())
+                })),
+            }
+        }
+    }
+
+    /// Hand the locals compiled code holds for the current frame to the VM
+    fn materialize(&mut self) {
+        let context = self.vm.context_mut();
+        let base = context.local_base();
+        for (slot, local) in self.slots.iter().zip(context.locals.iter_mut()).skip(base) {
+            if let Some(value) = slot.value() {
+                *local = value;
+            }
+        }
+    }
+
+    /// Take the ints, floats and bools among the current frame's locals
+    /// back from the VM, which may have changed frames
+    fn absorb(&mut self) {
+        let context = self.vm.context();
+        let base = context.local_base().min(context.locals.len());
+        self.slots.resize(context.locals.len(), Slot::BOXED);
+        for (slot, local) in self.slots.iter_mut().zip(&context.locals).skip(base) {
+            *slot = Slot::of(local);
+        }
+        self.frame = self.slots[base..].as_mut_ptr();
+        self.frame_len = (self.slots.len() - base) as i64;
+    }
+}
+
+/// Tags for the values compiled code holds
+const INT: i64 = 0;
+const FLOAT: i64 = 1;
+const BOOL: i64 = 2;
+/// A value compiled code leaves on the VM's stack or in its locals
+const BOXED: i64 = 3;
+
+/// A value as compiled code holds it in memory: its tag and, unless it
+/// is boxed, its bits
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot {
+    tag: i64,
+    bits: i64,
+}
+
+impl Slot {
+    const BOXED: Slot = Slot { tag: BOXED, bits: 0 };
+
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Int(value) => Slot { tag: INT, bits: *value },
+            Value::Float(value) => Slot { tag: FLOAT, bits: value.to_bits() as i64 },
+            Value::Bool(value) => Slot { tag: BOOL, bits: *value as i64 },
+            _ => Slot::BOXED,
+        }
+    }
+
+    fn value(self) -> Option<Value> {
+        match self.tag {
+            INT => Some(Value::Int(self.bits)),
+            FLOAT => Some(Value::Float(f64::from_bits(self.bits as u64))),
+            BOOL => Some(Value::Bool(self.bits != 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Size of a `Slot`, and where its fields are
+const SLOT: i64 = std::mem::size_of::<Slot>() as i64;
+const TAG: i32 = std::mem::offset_of!(Slot, tag) as i32;
+const BITS: i32 = std::mem::offset_of!(Slot, bits) as i32;
+
+/// Execute the instruction at `pc`, returning the pc to continue at or,
+/// when it is negative, why compiled code has to stop
+#[no_mangle]
+extern "C" fn ream_jit_step(state: *mut JitState, pc: i64) -> i64 {
+    // Safety: compiled code only passes on the state `JitState::run` gave it
+    let state = unsafe { &mut *state };
+    if state.program.instructions.get(pc as usize).is_some_and(TypeFeedback::is_site) {
+        let stack = &state.vm.context().stack;
+        state.feedback.record(pc as usize, &stack[stack.len().saturating_sub(2)..]);
+    }
+    state.materialize();
+    state.vm.context_mut().pc = pc as usize;
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| state.vm.step(&state.program)))
+        .unwrap_or_else(|_| Err(BytecodeError::InvalidInstruction(format!("Instruction at {} panicked", pc))));
+    state.absorb();
+    match result {
+        Ok(_) if state.vm.context().suspended.is_some() => SUSPENDED,
+        Ok(_) => state.vm.context().pc as i64,
+        Err(error) => {
+            state.error = Some(error);
+            FAILED
+        }
+    }
+}
+
+/// Push the `count` operands compiled code holds onto the VM's stack,
+/// where the boxed ones already are, in order, on top
+#[no_mangle]
+extern "C" fn ream_jit_flush(state: *mut JitState, operands: *const Slot, count: i64) {
+    // Safety: as for `ream_jit_step`, and compiled code passes a slot
+    // with room for `count` operands
+    let (state, operands) = unsafe { (&mut *state, std::slice::from_raw_parts(operands, count as usize)) };
+    let stack = &mut state.vm.context_mut().stack;
+    let boxed = operands.iter().filter(|operand| operand.tag == BOXED).count();
+    let mut held = stack.split_off(stack.len().saturating_sub(boxed)).into_iter();
+    stack.extend(operands.iter().map(|operand| operand.value().unwrap_or_else(|| held.next().unwrap_or(Value::Null))));
+}
+
+/// Take the top `count` values of the VM's stack into `operands`, leaving
+/// those that are not ints, floats or bools on the stack as boxed;
+/// returns 0, taking nothing, when the stack is not that deep
+#[no_mangle]
+extern "C" fn ream_jit_unbox(state: *mut JitState, operands: *mut Slot, count: i64) -> i64 {
+    // Safety: as for `ream_jit_flush`
+    let (state, operands) = unsafe { (&mut *state, std::slice::from_raw_parts_mut(operands, count as usize)) };
+    let stack = &mut state.vm.context_mut().stack;
+    let Some(base) = stack.len().checked_sub(operands.len()) else {
+        return 0;
+    };
+    for (operand, value) in operands.iter_mut().zip(stack.split_off(base)) {
+        *operand = Slot::of(&value);
+        if *operand == Slot::BOXED {
+            stack.push(value);
+        }
+    }
+    1
+}
+
+/// Execute a specialized instruction whose guard failed on the VM
+#[no_mangle]
+extern "C" fn ream_jit_deoptimize(state: *mut JitState, pc: i64) -> i64 {
+    // Safety: as for `ream_jit_step`
+    unsafe { (*state).deoptimizations += 1 };
+    ream_jit_step(state, pc)
+}
+
+/// Run the loop headed at `pc` with vector instructions and continue
+/// where it exits, or execute its first instruction on the VM when it
+/// cannot be
+#[no_mangle]
+extern "C" fn ream_jit_vector(state: *mut JitState, pc: i64) -> i64 {
+    // Safety: as for `ream_jit_step`
+    let state = unsafe { &mut *state };
+    let header = pc as usize;
+    let loops = Arc::clone(&state.loops);
+    if let Some(vector_loop) = loops.get(&header) {
+        // A loop that fell back once keeps falling back, rather than
+        // unboxing its lists again on every iteration
+        if !state.scalar_loops.contains(&header) {
+            state.materialize();
+            let context = state.vm.context_mut();
+            if vector_loop.run(context, &state.program.constants) {
+                context.pc = vector_loop.exit;
+                state.absorb();
+                return vector_loop.exit as i64;
+            }
+            state.scalar_loops.insert(header);
+        }
+    }
+    ream_jit_step(state, pc)
+}
+
+/// Entry point of executables linked from `AotCompiler` objects: run the
+/// program embedded in the object, in its on-disk format, with the code
+/// compiled for it and print the result
+#[no_mangle]
+extern "C" fn ream_aot_main(program: *const u8, len: usize, entry: *const u8) -> i32 {
+    // Safety: the object's `main` passes its embedded program and compiled function
+    let (bytes, entry) = unsafe { (std::slice::from_raw_parts(program, len), std::mem::transmute::<*const u8, CompiledFn>(entry)) };
+    let result = BytecodeProgram::from_bytes(bytes)
+        .and_then(|program| {
+            let loops = Arc::new(simd::vector_loops(&program));
+            JitState::new(Arc::new(program), loops, &[]).run(entry)
+        });
+    match result {
+        Ok(value) => {
+            println!("{}", value);
+            0
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            1
+        }
+    }
+}
+
+/// Native code of a compiled program, freed when dropped
+pub(crate) struct NativeCode {
+    module: ManuallyDrop<JITModule>,
+    entry: *const u8,
+    program: Arc<BytecodeProgram>,
+    /// Loops the code runs with vector instructions
+    loops: Arc<HashMap<usize, VectorLoop>>,
+    /// Operand kinds seen, including those the code was compiled with
+    feedback: Mutex<TypeFeedback>,
+    /// Guards that failed since last taken
+    deoptimizations: AtomicU64,
+}
+
+// Safety: the module is only touched again to free the code, which is
+// immutable once finalized, and each run gets its own state
+unsafe impl Send for NativeCode {}
+unsafe impl Sync for NativeCode {}
+
+impl NativeCode {
+    /// Start of the compiled function
+    pub(crate) fn entry(&self) -> *const u8 {
+        self.entry
+    }
+
+    /// Run the program with `args` pushed onto its stack, returning the
+    /// top of the stack once it finishes
+    pub(crate) fn run(&self, args: &[Value]) -> BytecodeResult<Value> {
+        // Safety: `entry` is the function `ReamJIT::compile_program` built with this signature
+        let entry: CompiledFn = unsafe { std::mem::transmute(self.entry) };
+        let mut state = JitState::new(Arc::clone(&self.program), Arc::clone(&self.loops), args);
+        let result = state.run(entry);
+        self.feedback.lock().unwrap().merge(&state.feedback);
+        self.deoptimizations.fetch_add(state.deoptimizations, Ordering::Relaxed);
+        result
+    }
+
+    /// The program the code was compiled from
+    pub(crate) fn program(&self) -> &BytecodeProgram {
+        &self.program
+    }
+
+    /// Operand kinds seen so far
+    pub(crate) fn feedback(&self) -> TypeFeedback {
+        self.feedback.lock().unwrap().clone()
+    }
+
+    /// Take the count of guards that failed since the last call
+    pub(crate) fn take_deoptimizations(&self) -> u64 {
+        self.deoptimizations.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Debug for NativeCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeCode")
+            .field("entry", &self.entry)
+            .field("program", &self.program.metadata.name)
+            .finish()
+    }
+}
+
+impl Drop for NativeCode {
+    fn drop(&mut self) {
+        // Safety: the code is only reachable through this value, which is going away
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() };
+    }
+}
 
 /// REAM JIT compiler
 pub struct ReamJIT {
     /// Optimization level
     opt_level: u8,
-    /// Generated code buffer
-    code_buffer: Vec<u8>,
     /// Function metadata
     metadata: JitMetadata,
-    /// Label table for jumps
-    labels: HashMap<u32, usize>,
-    /// Pending label references
-    pending_labels: Vec<(usize, u32)>,
+    /// Type feedback to specialize for
+    feedback: TypeFeedback,
+    /// Announce compiled code to profilers
+    debug_info: bool,
+    /// Also write compiled code to a jitdump file
+    jitdump: bool,
+}
+
+/// Runtime helpers as declared in the function being compiled
+struct Helpers {
+    step: FuncRef,
+    flush: FuncRef,
+    unbox: FuncRef,
+    deoptimize: FuncRef,
+    vector: FuncRef,
 }
 
 impl ReamJIT {
@@ -25,476 +407,959 @@ impl ReamJIT {
     pub fn new() -> Self {
         ReamJIT {
             opt_level: 2,
-            code_buffer: Vec::new(),
             metadata: JitMetadata {
                 bytecode_size: 0,
                 native_size: 0,
                 compile_time: std::time::Duration::new(0, 0),
                 opt_level: 2,
                 hot_spots: Vec::new(),
+                specializations: Vec::new(),
+                vector_loops: Vec::new(),
             },
-            labels: HashMap::new(),
-            pending_labels: Vec::new(),
+            feedback: TypeFeedback::new(),
+            debug_info: false,
+            jitdump: false,
         }
     }
-    
+
     /// Set optimization level
     pub fn set_optimization_level(&mut self, level: u8) {
         self.opt_level = level.min(3);
         self.metadata.opt_level = self.opt_level;
     }
-    
+
+    /// Specialize the instructions `feedback` has only seen one kind of
+    /// operand for; compiled code keeps adding to it
+    pub fn set_feedback(&mut self, feedback: TypeFeedback) {
+        self.feedback = feedback;
+    }
+
+    /// Name compiled code after its TLisp functions in the `perf`
+    /// symbol map
+    pub fn set_debug_info(&mut self, enable: bool) {
+        self.debug_info = enable;
+    }
+
+    /// Also write compiled code to a jitdump file for `perf inject`,
+    /// when debug info is enabled
+    pub fn set_jitdump(&mut self, enable: bool) {
+        self.jitdump = enable;
+    }
+
     /// Compile a bytecode program to native code
     pub fn compile_program(&mut self, program: &BytecodeProgram) -> JitResult<JitFunction> {
         let start_time = std::time::Instant::now();
-        
+
         self.metadata.bytecode_size = program.instructions.len();
-        self.code_buffer.clear();
-        self.labels.clear();
-        self.pending_labels.clear();
-        
-        // Generate function prologue
-        self.emit_prologue()?;
-        
-        // Compile each instruction
-        for (pc, instruction) in program.instructions.iter().enumerate() {
-            // Define a label for this instruction position
-            self.labels.insert(pc as u32, self.code_buffer.len());
-
-            self.compile_instruction(instruction, pc, program)?;
-        }
-        
-        // Generate function epilogue
-        self.emit_epilogue()?;
-        
-        // Resolve pending labels
-        self.resolve_labels()?;
-        
-        // Allocate executable memory
-        let function_ptr = self.allocate_executable_memory()?;
-        
-        self.metadata.native_size = self.code_buffer.len();
+        self.metadata.specializations = self.feedback.specializations(program);
+        // Unoptimized code leaves every loop to the VM
+        let loops = if self.opt_level > 0 { simd::vector_loops(program) } else { HashMap::new() };
+        self.metadata.vector_loops = loops.keys().copied().collect();
+        self.metadata.vector_loops.sort_unstable();
+        let mut module = self.create_module()?;
+        let (main, native_size, symbols) = Self::define_program(&mut module, "ream_jit_main", Linkage::Export, program, &self.metadata.specializations)?;
+        module.finalize_definitions()
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+
+        let entry = module.get_finalized_function(main);
+        if self.debug_info {
+            perf::register(entry, &symbols, self.jitdump)
+                .map_err(|e| JitError::CodeGeneration(format!("Failed to announce code to profilers: {}", e)))?;
+        }
+        let code = NativeCode {
+            module: ManuallyDrop::new(module),
+            entry,
+            program: Arc::new(program.clone()),
+            loops: Arc::new(loops),
+            feedback: Mutex::new(self.feedback.clone()),
+            deoptimizations: AtomicU64::new(0),
+        };
+
+        self.metadata.native_size = native_size;
         self.metadata.compile_time = start_time.elapsed();
-        
+
         let effect_grade = program.analyze_effects();
-        
-        Ok(JitFunction::new(
-            function_ptr,
-            self.code_buffer.len(),
+
+        Ok(JitFunction::compiled(
+            Arc::new(code),
+            native_size,
             effect_grade,
             self.metadata.clone(),
         ))
     }
-    
-    /// Compile a single instruction
-    fn compile_instruction(
-        &mut self,
-        instruction: &Bytecode,
-        _pc: usize,
+
+    /// Code generation settings
+    pub(crate) fn flags(opt_level: u8, is_pic: bool) -> JitResult<settings::Flags> {
+        let mut flags = settings::builder();
+        let opt_level = if opt_level == 0 { "none" } else { "speed" };
+        let is_pic = if is_pic { "true" } else { "false" };
+        for (name, value) in [("use_colocated_libcalls", "false"), ("is_pic", is_pic), ("opt_level", opt_level)] {
+            flags.set(name, value).map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        }
+        Ok(settings::Flags::new(flags))
+    }
+
+    /// Build the module code is compiled into, for the host machine
+    fn create_module(&self) -> JitResult<JITModule> {
+        let isa = cranelift_native::builder()
+            .map_err(|e| JitError::CodeGeneration(format!("Host machine is not supported: {}", e)))?
+            .finish(Self::flags(self.opt_level, false)?)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+
+        let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+        builder.symbol("ream_jit_step", ream_jit_step as *const u8);
+        builder.symbol("ream_jit_flush", ream_jit_flush as *const u8);
+        builder.symbol("ream_jit_unbox", ream_jit_unbox as *const u8);
+        builder.symbol("ream_jit_deoptimize", ream_jit_deoptimize as *const u8);
+        builder.symbol("ream_jit_vector", ream_jit_vector as *const u8);
+        Ok(JITModule::new(builder))
+    }
+
+    /// Define `name` in `module` as `program` compiled with
+    /// `specializations`, importing the runtime helpers it calls, and
+    /// return it with its size in bytes and its code named by function
+    pub(crate) fn define_program<M: Module>(
+        module: &mut M,
+        name: &str,
+        linkage: Linkage,
         program: &BytecodeProgram,
-    ) -> JitResult<()> {
-        match instruction {
-            Bytecode::Const(idx, _) => {
-                self.emit_load_constant(*idx, program)?;
-            }
-            Bytecode::Add(_) => {
-                self.emit_add()?;
-            }
-            Bytecode::Sub(_) => {
-                self.emit_sub()?;
-            }
-            Bytecode::Mul(_) => {
-                self.emit_mul()?;
-            }
-            Bytecode::Load(idx, _) => {
-                self.emit_load_local(*idx)?;
-            }
-            Bytecode::Store(idx, _) => {
-                self.emit_store_local(*idx)?;
-            }
-            Bytecode::LoadGlobal(idx, _) => {
-                self.emit_load_global(*idx)?;
-            }
-            Bytecode::StoreGlobal(idx, _) => {
-                self.emit_store_global(*idx)?;
+        specializations: &[Specialization],
+    ) -> JitResult<(FuncId, usize, Vec<CodeSymbol>)> {
+        // Compiled code and the step helper share a signature: the state
+        // and a pc in, a pc or status out
+        let pointer = module.target_config().pointer_type();
+        let step = Self::declare(module, "ream_jit_step", &[pointer, types::I64], true)?;
+        let flush = Self::declare(module, "ream_jit_flush", &[pointer, pointer, types::I64], false)?;
+        let unbox = Self::declare(module, "ream_jit_unbox", &[pointer, pointer, types::I64], true)?;
+        let deoptimize = Self::declare(module, "ream_jit_deoptimize", &[pointer, types::I64], true)?;
+        let vector = Self::declare(module, "ream_jit_vector", &[pointer, types::I64], true)?;
+        let mut signature = module.make_signature();
+        signature.params.push(AbiParam::new(pointer));
+        signature.params.push(AbiParam::new(types::I64));
+        signature.returns.push(AbiParam::new(types::I64));
+        let main = module.declare_function(name, linkage, &signature)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+
+        let mut context = module.make_context();
+        context.func.signature = signature;
+        context.func.name = UserFuncName::user(0, main.as_u32());
+        let mut builder_context = FunctionBuilderContext::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+            let helpers = Helpers {
+                step: module.declare_func_in_func(step, builder.func),
+                flush: module.declare_func_in_func(flush, builder.func),
+                unbox: module.declare_func_in_func(unbox, builder.func),
+                deoptimize: module.declare_func_in_func(deoptimize, builder.func),
+                vector: module.declare_func_in_func(vector, builder.func),
+            };
+            let loops: HashSet<usize> = simd::vector_loops(program).into_keys().collect();
+            Emitter::new(&mut builder, program, helpers, pointer, loops).emit(&mut builder, specializations);
+            builder.seal_all_blocks();
+            builder.finalize();
+        }
+        module.define_function(main, &mut context)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))?;
+        let (size, ranges) = context.compiled_code().map_or((0, Vec::new()), |code| {
+            let ranges: Vec<(usize, usize, usize)> = code.buffer.get_srclocs_sorted().iter()
+                .filter(|range| !range.loc.is_default())
+                .map(|range| (range.start as usize, range.end as usize, range.loc.bits() as usize))
+                .collect();
+            (code.code_info().total_size as usize, ranges)
+        });
+        module.clear_context(&mut context);
+        let symbols = perf::symbols(program, size, &ranges);
+        Ok((main, size, symbols))
+    }
+
+    /// Declare a runtime helper, returning an `i64` when `returns` is set
+    fn declare<M: Module>(module: &mut M, name: &str, params: &[Type], returns: bool) -> JitResult<FuncId> {
+        let mut signature = module.make_signature();
+        signature.params.extend(params.iter().map(|param| AbiParam::new(*param)));
+        if returns {
+            signature.returns.push(AbiParam::new(types::I64));
+        }
+        module.declare_function(name, Linkage::Import, &signature)
+            .map_err(|e| JitError::CodeGeneration(e.to_string()))
+    }
+}
+
+impl Default for ReamJIT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A value compiled code holds in registers, as a `Slot`
+#[derive(Debug, Clone, Copy)]
+struct Operand {
+    tag: ir::Value,
+    bits: ir::Value,
+}
+
+/// Emits a program as one function: an entry that jumps to the pc it
+/// is given, then a block per instruction
+struct Emitter<'a> {
+    program: &'a BytecodeProgram,
+    helpers: Helpers,
+    pointer: Type,
+    /// The `JitState` compiled code was given
+    state: ir::Value,
+    /// Where operands are passed to the helpers that move them between
+    /// compiled code and the VM
+    operands: StackSlot,
+    /// Jumps to the block of a pc only known at run time
+    dispatch: Block,
+    /// Returns the status it is given
+    stop: Block,
+    /// Returns once the program runs past its last instruction
+    exit: Block,
+    blocks: Vec<Block>,
+    /// Loop headers, run through the vector helper
+    loops: HashSet<usize>,
+    /// Instructions compiled code runs itself
+    native: Vec<bool>,
+    /// Instructions control can reach other than from the one before,
+    /// which start with every value on the VM's stack
+    leaders: Vec<bool>,
+    /// Operands compiled code holds as each instruction starts, passed to
+    /// its block as a tag and bits each
+    depths: Vec<usize>,
+}
+
+impl<'a> Emitter<'a> {
+    fn new(builder: &mut FunctionBuilder, program: &'a BytecodeProgram, helpers: Helpers, pointer: Type, loops: HashSet<usize>) -> Self {
+        let native: Vec<bool> = program.instructions.iter().enumerate()
+            .map(|(pc, instruction)| !loops.contains(&pc) && Self::is_native(instruction, program))
+            .collect();
+        let leaders = Self::leaders(program, &native);
+        let depths = Self::depths(program, &native, &leaders);
+        let room = program.instructions.iter().zip(&depths)
+            .filter_map(|(instruction, depth)| Self::stack_effect(instruction).map(|(operands, results)| (*depth).max(operands) + results))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        let dispatch = builder.create_block();
+        builder.append_block_param(dispatch, types::I64);
+        let stop = builder.create_block();
+        builder.append_block_param(stop, types::I64);
+        let exit = builder.create_block();
+        let blocks = depths.iter().map(|depth| {
+            let block = builder.create_block();
+            for _ in 0..2 * depth {
+                builder.append_block_param(block, types::I64);
             }
-            Bytecode::Jump(target, _) => {
-                self.emit_jump(*target)?;
+            block
+        }).collect();
+        let operands = builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, (room as i64 * SLOT) as u32, 3));
+
+        builder.switch_to_block(entry);
+        let state = builder.block_params(entry)[0];
+        let start = builder.block_params(entry)[1];
+        builder.ins().jump(dispatch, &[start]);
+
+        Emitter { program, helpers, pointer, state, operands, dispatch, stop, exit, blocks, loops, native, leaders, depths }
+    }
+
+    fn block_at(&self, pc: usize) -> Block {
+        self.blocks.get(pc).copied().unwrap_or(self.exit)
+    }
+
+    fn is_leader(&self, pc: usize) -> bool {
+        self.leaders.get(pc).copied().unwrap_or(true)
+    }
+
+    fn emit(&self, builder: &mut FunctionBuilder, specializations: &[Specialization]) {
+        builder.switch_to_block(self.dispatch);
+        let pc = builder.block_params(self.dispatch)[0];
+        let mut switch = Switch::new();
+        for (pc, block) in self.blocks.iter().enumerate().filter(|(pc, _)| self.leaders[*pc]) {
+            switch.set_entry(pc as u128, *block);
+        }
+        switch.emit(builder, pc, self.exit);
+
+        builder.switch_to_block(self.stop);
+        let status = builder.block_params(self.stop)[0];
+        builder.ins().return_(&[status]);
+
+        builder.switch_to_block(self.exit);
+        let finished = builder.ins().iconst(types::I64, FINISHED);
+        builder.ins().return_(&[finished]);
+
+        let specialized: HashMap<usize, ValueKind> = specializations.iter()
+            .map(|specialization| (specialization.pc, specialization.kind))
+            .collect();
+        for (pc, instruction) in self.program.instructions.iter().enumerate() {
+            builder.switch_to_block(self.blocks[pc]);
+            builder.set_srcloc(SourceLoc::new(pc as u32));
+            let held: Vec<Operand> = builder.block_params(self.blocks[pc]).chunks(2)
+                .map(|pair| Operand { tag: pair[0], bits: pair[1] })
+                .collect();
+            if self.native[pc] {
+                self.emit_native(builder, pc, instruction, held, specialized.get(&pc).copied());
+            } else {
+                let helper = if self.loops.contains(&pc) { self.helpers.vector } else { self.helpers.step };
+                self.emit_step(builder, helper, pc, instruction, &held);
             }
-            Bytecode::JumpIf(target, _) => {
-                self.emit_jump_if(*target)?;
+        }
+    }
+
+    /// Run an instruction through `helper`, handing it the operands
+    /// compiled code holds, and continue where it says
+    fn emit_step(&self, builder: &mut FunctionBuilder, helper: FuncRef, pc: usize, instruction: &Bytecode, held: &[Operand]) {
+        self.flush(builder, held);
+        let here = builder.ins().iconst(types::I64, pc as i64);
+        let call = builder.ins().call(helper, &[self.state, here]);
+        let next = builder.inst_results(call)[0];
+        let failed = builder.ins().icmp_imm(IntCC::SignedLessThan, next, 0);
+        let ok = builder.create_block();
+        builder.ins().brif(failed, self.stop, &[next], ok, &[]);
+
+        // Test for the places the instruction usually continues at before
+        // falling back to the jump table
+        builder.switch_to_block(ok);
+        for successor in Self::successors(instruction, pc, self.program) {
+            let taken = builder.ins().icmp_imm(IntCC::Equal, next, successor as i64);
+            let other = builder.create_block();
+            if self.is_leader(successor) {
+                builder.ins().brif(taken, self.block_at(successor), &[], other, &[]);
+            } else {
+                // Take back the operands compiled code holds there
+                let reload = builder.create_block();
+                builder.ins().brif(taken, reload, &[], other, &[]);
+                builder.switch_to_block(reload);
+                let operands = self.unbox(builder, self.depths[successor], self.dispatch, &[next]);
+                self.continue_at(builder, successor, &operands);
             }
-            Bytecode::JumpIfNot(target, _) => {
-                self.emit_jump_if_not(*target)?;
+            builder.switch_to_block(other);
+        }
+        builder.ins().jump(self.dispatch, &[next]);
+    }
+
+    /// Run an instruction in machine code for the operands it can, as
+    /// `kind` when type feedback specialized it, and through the VM for
+    /// the others
+    fn emit_native(&self, builder: &mut FunctionBuilder, pc: usize, instruction: &Bytecode, mut held: Vec<Operand>, kind: Option<ValueKind>) {
+        // Paths to the VM, emitted once the fast path is, with the
+        // operands compiled code held when it took them
+        let mut slow = Vec::new();
+        let (operands, _) = Self::stack_effect(instruction).unwrap_or_default();
+        if held.len() < operands {
+            let missing = builder.create_block();
+            self.flush(builder, &held);
+            held = self.unbox(builder, operands, missing, &[]);
+            slow.push((missing, self.helpers.step, Vec::new()));
+        }
+        let fallback = builder.create_block();
+        let helper = if kind.is_some() { self.helpers.deoptimize } else { self.helpers.step };
+        slow.push((fallback, helper, held.clone()));
+
+        let mut stack = held;
+        let args = stack.split_off(stack.len() - operands);
+        let next = match (instruction, &args[..]) {
+            (Bytecode::Jump(target, _), _) => Some(*target as usize),
+            (Bytecode::Const(index, _), _) => {
+                let slot = Slot::of(&self.program.constants[*index as usize]);
+                let tag = builder.ins().iconst(types::I64, slot.tag);
+                let bits = builder.ins().iconst(types::I64, slot.bits);
+                stack.push(Operand { tag, bits });
+                Some(pc + 1)
             }
-            Bytecode::Call(func_idx, _) => {
-                self.emit_call(*func_idx)?;
+            (Bytecode::Load(index, _), _) => {
+                let local = self.local(builder, *index, fallback);
+                let tag = builder.ins().load(types::I64, MemFlags::trusted(), local, TAG);
+                let unboxed = builder.ins().icmp_imm(IntCC::NotEqual, tag, BOXED);
+                self.check(builder, unboxed, fallback, &[]);
+                let bits = builder.ins().load(types::I64, MemFlags::trusted(), local, BITS);
+                stack.push(Operand { tag, bits });
+                Some(pc + 1)
             }
-            Bytecode::Ret(_) => {
-                self.emit_return()?;
+            (Bytecode::Store(index, _), [value]) => {
+                let unboxed = builder.ins().icmp_imm(IntCC::NotEqual, value.tag, BOXED);
+                self.check(builder, unboxed, fallback, &[]);
+                let local = self.local(builder, *index, fallback);
+                builder.ins().store(MemFlags::trusted(), value.tag, local, TAG);
+                builder.ins().store(MemFlags::trusted(), value.bits, local, BITS);
+                Some(pc + 1)
             }
-            Bytecode::SpawnProcess(func_idx, _) => {
-                self.emit_spawn(*func_idx)?;
+            // Boxed values are moved on the VM's stack, so the VM drops,
+            // copies and swaps them
+            (Bytecode::Pop(_), [value]) => {
+                let unboxed = builder.ins().icmp_imm(IntCC::NotEqual, value.tag, BOXED);
+                self.check(builder, unboxed, fallback, &[]);
+                Some(pc + 1)
             }
-            Bytecode::SendMessage(pid_idx, msg_idx, _) => {
-                self.emit_send(*pid_idx, *msg_idx)?;
+            (Bytecode::Dup(_), [value]) => {
+                let unboxed = builder.ins().icmp_imm(IntCC::NotEqual, value.tag, BOXED);
+                self.check(builder, unboxed, fallback, &[]);
+                stack.extend([*value, *value]);
+                Some(pc + 1)
             }
-            Bytecode::ReceiveMessage(_) => {
-                self.emit_receive()?;
+            (Bytecode::Swap(_), [a, b]) => {
+                let left = builder.ins().icmp_imm(IntCC::NotEqual, a.tag, BOXED);
+                let right = builder.ins().icmp_imm(IntCC::NotEqual, b.tag, BOXED);
+                let unboxed = builder.ins().band(left, right);
+                self.check(builder, unboxed, fallback, &[]);
+                stack.extend([*b, *a]);
+                Some(pc + 1)
             }
-            Bytecode::Print(_) => {
-                self.emit_print()?;
+            (Bytecode::Not(_), [value]) => {
+                let truthy = self.truthy(builder, *value, fallback);
+                let falsy = builder.ins().bxor_imm(truthy, 1);
+                let tag = builder.ins().iconst(types::I64, BOOL);
+                let bits = builder.ins().uextend(types::I64, falsy);
+                stack.push(Operand { tag, bits });
+                Some(pc + 1)
             }
-            Bytecode::Nop(_) => {
-                self.emit_nop()?;
+            (Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _), [condition]) => {
+                let truthy = self.truthy(builder, *condition, fallback);
+                self.flush(builder, &stack);
+                let (then, otherwise) = match instruction {
+                    Bytecode::JumpIf(..) => (*target as usize, pc + 1),
+                    _ => (pc + 1, *target as usize),
+                };
+                builder.ins().brif(truthy, self.block_at(then), &[], self.block_at(otherwise), &[]);
+                None
             }
-            _ => {
-                // For unimplemented instructions, emit a placeholder
-                self.emit_nop()?;
+            (_, [a, b]) => {
+                let result = self.emit_operator(builder, pc, instruction, [*a, *b], kind, fallback);
+                stack.push(result);
+                Some(pc + 1)
             }
+            _ => Some(pc + 1),
+        };
+        if let Some(next) = next {
+            self.continue_at(builder, next, &stack);
         }
-        
-        Ok(())
-    }
-    
-    // Code generation methods (simplified x86-64 assembly)
-    
-    fn emit_prologue(&mut self) -> JitResult<()> {
-        // push rbp
-        self.code_buffer.push(0x55);
-        // mov rbp, rsp
-        self.code_buffer.extend_from_slice(&[0x48, 0x89, 0xe5]);
-        // sub rsp, 0x100 (allocate stack space)
-        self.code_buffer.extend_from_slice(&[0x48, 0x81, 0xec, 0x00, 0x01, 0x00, 0x00]);
-        Ok(())
-    }
-    
-    fn emit_epilogue(&mut self) -> JitResult<()> {
-        // mov rsp, rbp
-        self.code_buffer.extend_from_slice(&[0x48, 0x89, 0xec]);
-        // pop rbp
-        self.code_buffer.push(0x5d);
-        // ret
-        self.code_buffer.push(0xc3);
-        Ok(())
-    }
-    
-    fn emit_load_constant(&mut self, idx: u32, program: &BytecodeProgram) -> JitResult<()> {
-        // Simplified: load constant into rax
-        if let Some(constant) = program.constants.get(idx as usize) {
-            match constant {
-                Value::Int(val) => {
-                    // mov rax, immediate
-                    self.code_buffer.extend_from_slice(&[0x48, 0xb8]);
-                    self.code_buffer.extend_from_slice(&val.to_le_bytes());
-                }
-                _ => {
-                    // For other types, use a placeholder
-                    self.emit_nop()?;
+
+        for (block, helper, held) in slow {
+            builder.switch_to_block(block);
+            self.emit_step(builder, helper, pc, instruction, &held);
+        }
+    }
+
+    /// Compute an arithmetic or comparison instruction on two ints, or on
+    /// two floats where feedback specialized it for them, leaving anything
+    /// else and integer overflow to `slow`
+    fn emit_operator(&self, builder: &mut FunctionBuilder, pc: usize, instruction: &Bytecode, [a, b]: [Operand; 2], kind: Option<ValueKind>, slow: Block) -> Operand {
+        let float = kind == Some(ValueKind::Float);
+        let tag = if float { FLOAT } else { INT };
+        let left = builder.ins().icmp_imm(IntCC::Equal, a.tag, tag);
+        let right = builder.ins().icmp_imm(IntCC::Equal, b.tag, tag);
+        let mut matched = builder.ins().band(left, right);
+        let ints = matched;
+        if kind.is_none() && matches!(instruction, Bytecode::Eq(_)) {
+            // Bools compare equal by their bits too
+            let left = builder.ins().icmp_imm(IntCC::Equal, a.tag, BOOL);
+            let right = builder.ins().icmp_imm(IntCC::Equal, b.tag, BOOL);
+            let bools = builder.ins().band(left, right);
+            matched = builder.ins().bor(ints, bools);
+        }
+        self.check(builder, matched, slow, &[]);
+
+        if float {
+            let x = builder.ins().bitcast(types::F64, MemFlags::new(), a.bits);
+            let y = builder.ins().bitcast(types::F64, MemFlags::new(), b.bits);
+            // The VM refuses to compare NaN, so leave it to the VM
+            let ordered = builder.ins().fcmp(FloatCC::Ordered, x, y);
+            self.check(builder, ordered, slow, &[]);
+            let result = match instruction {
+                Bytecode::Add(_) => Some(builder.ins().fadd(x, y)),
+                Bytecode::Sub(_) => Some(builder.ins().fsub(x, y)),
+                Bytecode::Mul(_) => Some(builder.ins().fmul(x, y)),
+                _ => None,
+            };
+            let (tag, bits) = match result {
+                Some(result) => (FLOAT, builder.ins().bitcast(types::I64, MemFlags::new(), result)),
+                None => {
+                    let condition = match instruction {
+                        Bytecode::Lt(_) => FloatCC::LessThan,
+                        Bytecode::Le(_) => FloatCC::LessThanOrEqual,
+                        Bytecode::Gt(_) => FloatCC::GreaterThan,
+                        _ => FloatCC::GreaterThanOrEqual,
+                    };
+                    let flag = builder.ins().fcmp(condition, x, y);
+                    (BOOL, builder.ins().uextend(types::I64, flag))
                 }
-            }
+            };
+            let tag = builder.ins().iconst(types::I64, tag);
+            return Operand { tag, bits };
         }
-        Ok(())
-    }
-    
-    fn emit_add(&mut self) -> JitResult<()> {
-        // Simplified: add two values on stack
-        // pop rbx
-        self.code_buffer.push(0x5b);
-        // pop rax
-        self.code_buffer.push(0x58);
-        // add rax, rbx
-        self.code_buffer.extend_from_slice(&[0x48, 0x01, 0xd8]);
-        // push rax
-        self.code_buffer.push(0x50);
-        Ok(())
-    }
-    
-    fn emit_sub(&mut self) -> JitResult<()> {
-        // Simplified: subtract two values on stack
-        // pop rbx
-        self.code_buffer.push(0x5b);
-        // pop rax
-        self.code_buffer.push(0x58);
-        // sub rax, rbx
-        self.code_buffer.extend_from_slice(&[0x48, 0x29, 0xd8]);
-        // push rax
-        self.code_buffer.push(0x50);
-        Ok(())
-    }
-    
-    fn emit_mul(&mut self) -> JitResult<()> {
-        // Simplified: multiply two values on stack
-        // pop rbx
-        self.code_buffer.push(0x5b);
-        // pop rax
-        self.code_buffer.push(0x58);
-        // imul rax, rbx
-        self.code_buffer.extend_from_slice(&[0x48, 0x0f, 0xaf, 0xc3]);
-        // push rax
-        self.code_buffer.push(0x50);
-        Ok(())
-    }
-    
-    fn emit_load_local(&mut self, idx: u32) -> JitResult<()> {
-        // Simplified: load local variable
-        // mov rax, [rbp - offset]
-        let offset = (idx + 1) * 8; // 8 bytes per local
-        self.code_buffer.extend_from_slice(&[0x48, 0x8b, 0x45]);
-        self.code_buffer.push(-(offset as i8) as u8);
-        // push rax
-        self.code_buffer.push(0x50);
-        Ok(())
-    }
-    
-    fn emit_store_local(&mut self, idx: u32) -> JitResult<()> {
-        // Simplified: store to local variable
-        // pop rax
-        self.code_buffer.push(0x58);
-        // mov [rbp - offset], rax
-        let offset = (idx + 1) * 8;
-        self.code_buffer.extend_from_slice(&[0x48, 0x89, 0x45]);
-        self.code_buffer.push(-(offset as i8) as u8);
-        Ok(())
-    }
-
-    fn emit_load_global(&mut self, _idx: u32) -> JitResult<()> {
-        // Placeholder for global variable loading
-        // In a real implementation, this would access a global variable table
-        self.emit_nop()
-    }
-
-    fn emit_store_global(&mut self, _idx: u32) -> JitResult<()> {
-        // Placeholder for global variable storing
-        // In a real implementation, this would access a global variable table
-        self.emit_nop()
-    }
-    
-    fn emit_jump(&mut self, target: u32) -> JitResult<()> {
-        // jmp relative
-        self.code_buffer.push(0xe9);
-        
-        if let Some(&target_addr) = self.labels.get(&target) {
-            let current_addr = self.code_buffer.len();
-            let offset = (target_addr as i32) - (current_addr as i32) - 4;
-            self.code_buffer.extend_from_slice(&offset.to_le_bytes());
-        } else {
-            // Add to pending labels
-            self.pending_labels.push((self.code_buffer.len(), target));
-            self.code_buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]); // Placeholder
-        }
-        
-        Ok(())
-    }
-    
-    fn emit_jump_if(&mut self, target: u32) -> JitResult<()> {
-        // Simplified: conditional jump (jump if true)
-        // pop rax
-        self.code_buffer.push(0x58);
-        // test rax, rax
-        self.code_buffer.extend_from_slice(&[0x48, 0x85, 0xc0]);
-        // jnz relative
-        self.code_buffer.extend_from_slice(&[0x0f, 0x85]);
-
-        if let Some(&target_addr) = self.labels.get(&target) {
-            let current_addr = self.code_buffer.len();
-            let offset = (target_addr as i32) - (current_addr as i32) - 4;
-            self.code_buffer.extend_from_slice(&offset.to_le_bytes());
-        } else {
-            self.pending_labels.push((self.code_buffer.len(), target));
-            self.code_buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+
+        let checked = match instruction {
+            Bytecode::Add(_) => Some(builder.ins().sadd_overflow(a.bits, b.bits)),
+            Bytecode::Sub(_) => Some(builder.ins().ssub_overflow(a.bits, b.bits)),
+            Bytecode::Mul(_) => Some(builder.ins().smul_overflow(a.bits, b.bits)),
+            _ => None,
+        };
+        let (tag, bits) = match checked {
+            Some((result, overflowed)) => {
+                let fits = builder.ins().icmp_imm(IntCC::Equal, overflowed, 0);
+                self.check(builder, fits, slow, &[]);
+                (INT, result)
+            }
+            None => {
+                let condition = match instruction {
+                    Bytecode::Eq(_) => IntCC::Equal,
+                    Bytecode::Lt(_) => IntCC::SignedLessThan,
+                    Bytecode::Le(_) => IntCC::SignedLessThanOrEqual,
+                    Bytecode::Gt(_) => IntCC::SignedGreaterThan,
+                    _ => IntCC::SignedGreaterThanOrEqual,
+                };
+                let flag = builder.ins().icmp(condition, a.bits, b.bits);
+                (BOOL, builder.ins().uextend(types::I64, flag))
+            }
+        };
+        if kind.is_none() {
+            // Generic code counts the integers it computed on, for feedback
+            let sites = builder.ins().load(self.pointer, MemFlags::trusted(), self.state, std::mem::offset_of!(JitState, int_sites) as i32);
+            let site = builder.ins().iadd_imm(sites, pc as i64 * 8);
+            let count = builder.ins().load(types::I64, MemFlags::trusted(), site, 0);
+            let computed = builder.ins().uextend(types::I64, ints);
+            let count = builder.ins().iadd(count, computed);
+            builder.ins().store(MemFlags::trusted(), count, site, 0);
         }
+        let tag = builder.ins().iconst(types::I64, tag);
+        Operand { tag, bits }
+    }
+
+    /// Whether an int or bool is truthy, leaving other values to `slow`
+    fn truthy(&self, builder: &mut FunctionBuilder, value: Operand, slow: Block) -> ir::Value {
+        let int = builder.ins().icmp_imm(IntCC::Equal, value.tag, INT);
+        let bool = builder.ins().icmp_imm(IntCC::Equal, value.tag, BOOL);
+        let known = builder.ins().bor(int, bool);
+        self.check(builder, known, slow, &[]);
+        builder.ins().icmp_imm(IntCC::NotEqual, value.bits, 0)
+    }
 
-        Ok(())
+    /// The address of local `index` of the current frame, going to
+    /// `slow` when the frame does not have it yet
+    fn local(&self, builder: &mut FunctionBuilder, index: u32, slow: Block) -> ir::Value {
+        let len = builder.ins().load(types::I64, MemFlags::trusted(), self.state, std::mem::offset_of!(JitState, frame_len) as i32);
+        let present = builder.ins().icmp_imm(IntCC::SignedGreaterThan, len, index as i64);
+        self.check(builder, present, slow, &[]);
+        let frame = builder.ins().load(self.pointer, MemFlags::trusted(), self.state, std::mem::offset_of!(JitState, frame) as i32);
+        builder.ins().iadd_imm(frame, index as i64 * SLOT)
     }
 
-    fn emit_jump_if_not(&mut self, target: u32) -> JitResult<()> {
-        // Simplified: conditional jump (jump if false)
-        // pop rax
-        self.code_buffer.push(0x58);
-        // test rax, rax
-        self.code_buffer.extend_from_slice(&[0x48, 0x85, 0xc0]);
-        // jz relative (jump if zero/false)
-        self.code_buffer.extend_from_slice(&[0x0f, 0x84]);
+    /// Carry on in a new block when `condition` holds, and go to
+    /// `otherwise` with `args` when it does not
+    fn check(&self, builder: &mut FunctionBuilder, condition: ir::Value, otherwise: Block, args: &[ir::Value]) {
+        let next = builder.create_block();
+        builder.ins().brif(condition, next, &[], otherwise, args);
+        builder.switch_to_block(next);
+    }
 
-        if let Some(&target_addr) = self.labels.get(&target) {
-            let current_addr = self.code_buffer.len();
-            let offset = (target_addr as i32) - (current_addr as i32) - 4;
-            self.code_buffer.extend_from_slice(&offset.to_le_bytes());
+    /// Go on to the instruction at `next` holding `operands`, handing them
+    /// to the VM first when control can reach it from elsewhere too
+    fn continue_at(&self, builder: &mut FunctionBuilder, next: usize, operands: &[Operand]) {
+        if self.is_leader(next) {
+            self.flush(builder, operands);
+            builder.ins().jump(self.block_at(next), &[]);
         } else {
-            self.pending_labels.push((self.code_buffer.len(), target));
-            self.code_buffer.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
-        }
-
-        Ok(())
-    }
-    
-    fn emit_call(&mut self, _func_idx: u32) -> JitResult<()> {
-        // Simplified: function call (placeholder)
-        self.emit_nop()
-    }
-    
-    fn emit_return(&mut self) -> JitResult<()> {
-        // pop rax (return value)
-        self.code_buffer.push(0x58);
-        // jmp to epilogue (simplified)
-        self.emit_epilogue()
-    }
-    
-    fn emit_spawn(&mut self, _func_idx: u32) -> JitResult<()> {
-        // Placeholder for process spawning
-        self.emit_nop()
-    }
-    
-    fn emit_send(&mut self, _pid_idx: u32, _msg_idx: u32) -> JitResult<()> {
-        // Placeholder for message sending
-        self.emit_nop()
-    }
-    
-    fn emit_receive(&mut self) -> JitResult<()> {
-        // Placeholder for message receiving
-        self.emit_nop()
-    }
-
-    fn emit_print(&mut self) -> JitResult<()> {
-        // Placeholder for print operation
-        // In a real implementation, this would call a print function
-        self.emit_nop()
-    }
-
-    fn emit_nop(&mut self) -> JitResult<()> {
-        // nop instruction
-        self.code_buffer.push(0x90);
-        Ok(())
-    }
-    
-    fn resolve_labels(&mut self) -> JitResult<()> {
-        for (patch_addr, target) in &self.pending_labels {
-            if let Some(&target_addr) = self.labels.get(target) {
-                let offset = (target_addr as i32) - (*patch_addr as i32) - 4;
-                let offset_bytes = offset.to_le_bytes();
-                
-                for (i, &byte) in offset_bytes.iter().enumerate() {
-                    if patch_addr + i < self.code_buffer.len() {
-                        self.code_buffer[patch_addr + i] = byte;
-                    }
-                }
-            } else {
-                return Err(JitError::CodeGeneration(
-                    format!("Unresolved label: {}", target)
-                ));
-            }
+            let args: Vec<ir::Value> = operands.iter().flat_map(|operand| [operand.tag, operand.bits]).collect();
+            builder.ins().jump(self.blocks[next], &args);
         }
-        Ok(())
     }
-    
-    fn allocate_executable_memory(&self) -> JitResult<*const u8> {
-        // In a real implementation, this would use mmap or VirtualAlloc
-        // to allocate executable memory. For now, we'll use a simplified approach.
-        
-        #[cfg(unix)]
-        {
-            use std::ptr;
-            
-            unsafe {
-                let size = self.code_buffer.len();
-                let ptr = libc::mmap(
-                    ptr::null_mut(),
-                    size,
-                    libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
-                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
-                    -1,
-                    0,
-                );
-                
-                if ptr == libc::MAP_FAILED {
-                    return Err(JitError::CodeGeneration("Failed to allocate executable memory".to_string()));
-                }
-                
-                // Copy code to executable memory
-                ptr::copy_nonoverlapping(self.code_buffer.as_ptr(), ptr as *mut u8, size);
-                
-                Ok(ptr as *const u8)
-            }
-        }
-        
-        #[cfg(windows)]
-        {
-            use std::ptr;
 
-            unsafe {
-                let size = self.code_buffer.len();
+    /// Push the operands compiled code holds onto the VM's stack
+    fn flush(&self, builder: &mut FunctionBuilder, operands: &[Operand]) {
+        if operands.is_empty() {
+            return;
+        }
+        for (index, operand) in operands.iter().enumerate() {
+            let offset = index as i32 * SLOT as i32;
+            builder.ins().stack_store(operand.tag, self.operands, offset + TAG);
+            builder.ins().stack_store(operand.bits, self.operands, offset + BITS);
+        }
+        let address = builder.ins().stack_addr(self.pointer, self.operands, 0);
+        let count = builder.ins().iconst(types::I64, operands.len() as i64);
+        builder.ins().call(self.helpers.flush, &[self.state, address, count]);
+    }
 
-                // Allocate executable memory using VirtualAlloc
-                let ptr = winapi::um::memoryapi::VirtualAlloc(
-                    ptr::null_mut(),
-                    size,
-                    winapi::um::winnt::MEM_COMMIT | winapi::um::winnt::MEM_RESERVE,
-                    winapi::um::winnt::PAGE_EXECUTE_READWRITE,
-                );
+    /// Take `count` operands off the VM's stack, going to `otherwise` with
+    /// `args` when it does not have that many
+    fn unbox(&self, builder: &mut FunctionBuilder, count: usize, otherwise: Block, args: &[ir::Value]) -> Vec<Operand> {
+        if count == 0 {
+            return Vec::new();
+        }
+        let address = builder.ins().stack_addr(self.pointer, self.operands, 0);
+        let size = builder.ins().iconst(types::I64, count as i64);
+        let call = builder.ins().call(self.helpers.unbox, &[self.state, address, size]);
+        let taken = builder.inst_results(call)[0];
+        self.check(builder, taken, otherwise, args);
+        (0..count).map(|index| {
+            let offset = index as i32 * SLOT as i32;
+            let tag = builder.ins().stack_load(types::I64, self.operands, offset + TAG);
+            let bits = builder.ins().stack_load(types::I64, self.operands, offset + BITS);
+            Operand { tag, bits }
+        }).collect()
+    }
 
-                if ptr.is_null() {
-                    return Err(JitError::CodeGeneration("Failed to allocate executable memory".to_string()));
-                }
+    /// Whether compiled code runs an instruction itself, leaving it to the
+    /// VM only for operands it cannot compute on
+    fn is_native(instruction: &Bytecode, program: &BytecodeProgram) -> bool {
+        matches!(instruction.effect_grade(), EffectGrade::Pure) && match instruction {
+            Bytecode::Const(index, _) => program.constants.get(*index as usize)
+                .is_some_and(|value| Slot::of(value) != Slot::BOXED),
+            _ => Self::stack_effect(instruction).is_some(),
+        }
+    }
 
-                // Copy code to executable memory
-                ptr::copy_nonoverlapping(self.code_buffer.as_ptr(), ptr as *mut u8, size);
+    /// The operands an instruction compiled code runs takes and the results
+    /// it leaves
+    fn stack_effect(instruction: &Bytecode) -> Option<(usize, usize)> {
+        Some(match instruction {
+            Bytecode::Nop(_) | Bytecode::Jump(_, _) => (0, 0),
+            Bytecode::Const(_, _) | Bytecode::Load(_, _) => (0, 1),
+            Bytecode::Store(_, _) | Bytecode::Pop(_) | Bytecode::JumpIf(_, _) | Bytecode::JumpIfNot(_, _) => (1, 0),
+            Bytecode::Not(_) => (1, 1),
+            Bytecode::Dup(_) => (1, 2),
+            Bytecode::Swap(_) => (2, 2),
+            _ if TypeFeedback::is_site(instruction) => (2, 1),
+            _ => return None,
+        })
+    }
 
-                Ok(ptr as *const u8)
+    /// Which instructions control can reach other than from the one
+    /// before: targets, instructions the VM runs and the ones after them
+    fn leaders(program: &BytecodeProgram, native: &[bool]) -> Vec<bool> {
+        let mut leaders = vec![false; program.instructions.len() + 1];
+        let mut mark = |pc: usize| if let Some(leader) = leaders.get_mut(pc) {
+            *leader = true;
+        };
+        mark(0);
+        for function in &program.functions {
+            mark(function.start_pc);
+        }
+        for (pc, instruction) in program.instructions.iter().enumerate() {
+            if let Bytecode::Jump(target, _) | Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _)
+                | Bytecode::CallDirect(_, target, _) | Bytecode::TryBegin(target, _) = instruction
+            {
+                mark(*target as usize);
+                mark(pc + 1);
+            }
+            if !native[pc] {
+                mark(pc);
+                mark(pc + 1);
             }
         }
+        leaders.truncate(program.instructions.len());
+        leaders
+    }
 
-        #[cfg(not(any(unix, windows)))]
-        {
-            // Fallback for other systems - return error instead of non-executable memory
-            Err(JitError::CodeGeneration("JIT compilation not supported on this platform".to_string()))
-        }
+    /// How many operands compiled code holds as each instruction starts
+    fn depths(program: &BytecodeProgram, native: &[bool], leaders: &[bool]) -> Vec<usize> {
+        let mut depth = 0;
+        program.instructions.iter().enumerate().map(|(pc, instruction)| {
+            if leaders[pc] {
+                depth = 0;
+            }
+            let here = depth;
+            if let (true, Some((operands, results))) = (native[pc], Self::stack_effect(instruction)) {
+                depth = depth.max(operands) - operands + results;
+            }
+            here
+        }).collect()
     }
-}
 
-impl Default for ReamJIT {
-    fn default() -> Self {
-        Self::new()
+    /// Where control goes after an instruction when it does not throw;
+    /// returns and handlers are left to the jump table
+    fn successors(instruction: &Bytecode, pc: usize, program: &BytecodeProgram) -> Vec<usize> {
+        match instruction {
+            Bytecode::Jump(target, _) | Bytecode::CallDirect(_, target, _) => vec![*target as usize],
+            Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) if *target as usize == pc + 1 => vec![pc + 1],
+            Bytecode::JumpIf(target, _) | Bytecode::JumpIfNot(target, _) => vec![*target as usize, pc + 1],
+            Bytecode::Call(function, _) => program.functions.get(*function as usize)
+                .map(|function| vec![function.start_pc])
+                .unwrap_or_default(),
+            Bytecode::Ret(_) | Bytecode::Throw(_) => Vec::new(),
+            _ => vec![pc + 1],
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bytecode::{BytecodeProgram, Value};
+    use crate::bytecode::{assemble, BytecodeProgram, DebugAction, DebuggerHooks, ExecutionContext, Value};
     use crate::types::EffectGrade;
 
+    /// Run a program on the VM and compiled, checking both agree
+    fn run(source: &str) -> JitResult<Value> {
+        let program = assemble(source).unwrap();
+        let expected = BytecodeVM::new().execute_program(&program).map_err(|e| e.to_string());
+        let actual = ReamJIT::new().compile_program(&program)?.call0();
+        assert_eq!(actual.as_ref().map_err(|e| e.to_string().replace("Execution error: ", "")), expected.as_ref().map_err(String::clone));
+        actual
+    }
+
     #[test]
     fn test_jit_creation() {
         let jit = ReamJIT::new();
         assert_eq!(jit.opt_level, 2);
-        assert_eq!(jit.code_buffer.len(), 0);
+        assert_eq!(jit.metadata.native_size, 0);
     }
-    
-    #[test]
-    fn test_code_generation() {
-        let mut jit = ReamJIT::new();
-        
-        // Test prologue generation
-        jit.emit_prologue().unwrap();
-        assert!(jit.code_buffer.len() > 0);
-        
-        // Test instruction emission
-        jit.emit_nop().unwrap();
-        assert!(jit.code_buffer.len() > 7); // Prologue + nop
-    }
-    
+
     #[test]
     fn test_program_compilation() {
         let mut jit = ReamJIT::new();
         let mut program = BytecodeProgram::new("test".to_string());
-        
+
         let const_id = program.add_constant(Value::Int(42));
         program.add_instruction(Bytecode::Const(const_id, EffectGrade::Pure));
-        program.add_instruction(Bytecode::Ret(EffectGrade::Pure));
-        
-        // This should compile without errors
-        let result = jit.compile_program(&program);
-        assert!(result.is_ok());
+        program.add_instruction(Bytecode::Nop(EffectGrade::Pure));
+
+        let function = jit.compile_program(&program).unwrap();
+        assert!(function.is_valid());
+        assert_eq!(function.call0().unwrap(), Value::Int(42));
+        assert_eq!(function.call1(Value::Int(7)).unwrap(), Value::Int(42));
+        assert_eq!(function.metadata().native_size, function.size());
+    }
+
+    /// Counts the instructions the VM executes
+    struct Steps(Arc<AtomicU64>);
+
+    impl DebuggerHooks for Steps {
+        fn before_instruction(&mut self, _pc: usize, _instruction: &Bytecode, _context: &ExecutionContext) -> DebugAction {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            DebugAction::Continue
+        }
+    }
+
+    /// Compile a program and run it, returning its result and how many
+    /// of its instructions were left to the VM
+    fn run_counting(source: &str) -> (Value, u64) {
+        let program = assemble(source).unwrap();
+        let mut module = ReamJIT::new().create_module().unwrap();
+        let (main, _, _) = ReamJIT::define_program(&mut module, "main", Linkage::Export, &program, &[]).unwrap();
+        module.finalize_definitions().unwrap();
+        // Safety: `define_program` builds functions with this signature
+        let entry: CompiledFn = unsafe { std::mem::transmute(module.get_finalized_function(main)) };
+        let steps = Arc::new(AtomicU64::new(0));
+        let mut state = JitState::new(Arc::new(program), Arc::new(HashMap::new()), &[]);
+        state.vm.set_hooks(Some(Box::new(Steps(Arc::clone(&steps)))));
+        let result = state.run(entry).unwrap();
+        // Safety: the code is not called again
+        unsafe { module.free_memory() };
+        (result, steps.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn test_code_generation() {
+        // Integer and bool arithmetic, comparisons, branches and stack
+        // shuffling run as machine code without the VM
+        let source = "\
+.const 0 int 6
+.const 1 int 7
+.const 2 bool false
+    const 0
+    const 1
+    mul
+    dup
+    const 1
+    sub
+    swap
+    const 0
+    gt
+    const 2
+    not
+    eq
+    jump_if_not wrong
+    const 1
+    lt
+    jump end
+wrong:
+    pop
+    const 2
+end:
+    nop
+";
+        assert_eq!(run(source).unwrap(), Value::Bool(false));
+        assert_eq!(run_counting(source), (Value::Bool(false), 0));
+
+        // Locals are too, once the VM has grown the frame for them, however
+        // long the loop runs
+        let sum = |n: i64| format!(".const 0 int 0\n.const 1 int 1\n.const 2 int {}\n    const 0\n    store 0\n    const 0\n    store 1\ntop:\n    load 1\n    const 2\n    ge\n    jump_if done\n    load 1\n    const 1\n    add\n    store 1\n    load 0\n    load 1\n    add\n    store 0\n    jump top\ndone:\n    load 0\n", n);
+        assert_eq!(run_counting(&sum(10)), (Value::Int(55), 2));
+        assert_eq!(run_counting(&sum(1000)), (Value::Int(500500), 2));
+
+        // Operands of other kinds go to the VM
+        let source = ".const 0 float 1.5\n.const 1 int 2\n    const 0\n    const 1\n    mul\n    const 1\n    add\n";
+        assert_eq!(run(source).unwrap(), Value::Float(5.0));
+        assert_eq!(run_counting(source), (Value::Float(5.0), 2));
+    }
+
+    #[test]
+    fn test_control_flow_and_calls() {
+        // Sum 1..=10 in a loop over locals
+        let source = "\
+.const 0 int 0
+.const 1 int 1
+.const 2 int 10
+    const 0
+    store 0
+    const 0
+    store 1
+top:
+    load 1
+    const 2
+    ge
+    jump_if done
+    load 1
+    const 1
+    add
+    store 1
+    load 0
+    load 1
+    add
+    store 0
+    jump top
+done:
+    load 0
+";
+        assert_eq!(run(source).unwrap(), Value::Int(55));
+
+        // The same loop specialized for the integers it saw
+        let program = assemble(source).unwrap();
+        let generic = ReamJIT::new().compile_program(&program).unwrap();
+        generic.call0().unwrap();
+        let mut jit = ReamJIT::new();
+        jit.set_feedback(generic.feedback());
+        let specialized = jit.compile_program(&program).unwrap();
+        assert_eq!(specialized.metadata().specializations.len(), 3);
+        assert_eq!(specialized.call0().unwrap(), Value::Int(55));
+        assert_eq!(specialized.take_deoptimizations(), 0);
+
+        // Recursive calls return through the jump table
+        let source = "\
+.const 0 int 1
+.const 1 int 10
+.function 0 fact params=1 locals=1 len=13
+    const 1
+    call fact
+    jump end
+fact:
+    store 0
+    load 0
+    const 0
+    le
+    jump_if_not recurse
+    const 0
+    ret
+recurse:
+    load 0
+    load 0
+    const 0
+    sub
+    call fact
+    mul
+    ret
+end:
+    nop
+";
+        assert_eq!(run(source).unwrap(), Value::Int(3628800));
+    }
+
+    #[test]
+    fn test_strings_lists_and_natives() {
+        let source = "\
+.const 0 string \"hello\"
+.const 1 string \" world\"
+.const 2 string \"string/upper\"
+.const 3 int 2
+    const 0
+    const 1
+    str_concat
+    call_native 2
+    dup
+    str_len
+    const 3
+    list_new
+";
+        assert_eq!(run(source).unwrap(), Value::List(vec![
+            Value::String("HELLO WORLD".to_string()),
+            Value::Int(11),
+        ]));
+    }
+
+    /// A counted loop over a list of `xs` in local 1, with `ys` in local
+    /// 3, `acc` in local 2 and `k` in local 4, leaving local `result`
+    fn vector_loop(xs: &[&str], ys: &[&str], acc: &str, k: &str, body: &str, result: u32) -> String {
+        let mut source = format!(".const 0 int 0\n.const 1 int 1\n.const 2 {}\n.const 3 {}\n.const 4 int {}\n.const 5 int {}\n", acc, k, xs.len(), ys.len());
+        let mut code = String::new();
+        for (list, items, local) in [(4, xs, 1), (5, ys, 3)] {
+            for item in items.iter() {
+                let index = source.lines().count();
+                source.push_str(&format!(".const {} {}\n", index, item));
+                code.push_str(&format!("    const {}\n", index));
+            }
+            code.push_str(&format!("    const {}\n    list_new\n    store {}\n", list, local));
+        }
+        code.push_str("    const 2\n    store 2\n    const 3\n    store 4\n    const 0\n    store 0\n");
+        code.push_str("loop:\n    load 0\n    load 1\n    list_len\n    lt\n    jump_if_not done\n");
+        code.push_str(body);
+        code.push_str(&format!("    load 0\n    const 1\n    add\n    store 0\n    jump loop\ndone:\n    load {}\n", result));
+        source + &code
+    }
+
+    #[test]
+    fn test_vector_loops() {
+        const SUM: &str = "    load 2\n    load 1\n    load 0\n    list_get\n    add\n    store 2\n";
+        const DOT: &str = "    load 2\n    load 1\n    load 0\n    list_get\n    load 3\n    load 0\n    list_get\n    mul\n    add\n    store 2\n";
+        const MAP: &str = "    load 1\n    load 0\n    load 1\n    load 0\n    list_get\n    load 4\n    sub\n    list_set\n    store 1\n";
+        let ints: Vec<String> = (0..37).map(|i| format!("int {}", i * 3 - 40)).collect();
+        let ints: Vec<&str> = ints.iter().map(String::as_str).collect();
+        let floats: Vec<String> = (0..37).map(|i| format!("float {}", i as f64 * 0.1)).collect();
+        let floats: Vec<&str> = floats.iter().map(String::as_str).collect();
+
+        let sum = vector_loop(&ints, &[], "int 5", "int 0", SUM, 2);
+        assert_eq!(ReamJIT::new().compile_program(&assemble(&sum).unwrap()).unwrap().metadata().vector_loops.len(), 1);
+        assert_eq!(run(&sum).unwrap(), Value::Int(5 + (0..37).map(|i| i * 3 - 40).sum::<i64>()));
+        run(&vector_loop(&floats, &[], "float 0.5", "int 0", SUM, 2)).unwrap();
+        run(&vector_loop(&ints, &ints, "int 0", "int 0", DOT, 2)).unwrap();
+        run(&vector_loop(&floats, &floats, "float 0", "int 0", DOT, 2)).unwrap();
+        run(&vector_loop(&ints, &floats, "float 0", "int 0", DOT, 2)).unwrap();
+        run(&vector_loop(&ints, &[], "int 0", "int 7", MAP, 1)).unwrap();
+        run(&vector_loop(&floats, &[], "int 0", "float 0.25", MAP, 1)).unwrap();
+        run(&vector_loop(&[], &[], "int 0", "int 7", MAP, 1)).unwrap();
+
+        // Loops the kernels cannot run fall back to the VM, errors included
+        run(&vector_loop(&["int 1", "string \"2\"", "int 3"], &[], "int 0", "int 0", SUM, 2)).unwrap_err();
+        run(&vector_loop(&ints, &ints[..10], "int 0", "int 0", DOT, 2)).unwrap_err();
+        run(&vector_loop(&["int 1", "float 2.5"], &[], "int 0", "int 1", MAP, 1)).unwrap();
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let source = "\
+.const 0 int 1
+.const 1 int 0
+.const 2 string \"boom\"
+.function 0 fail params=0 locals=0 len=3
+    const 0
+    try_begin handler
+    call fail
+    try_end
+handler:
+    tuple_new 2
+    jump end
+fail:
+    const 0
+    const 2
+    throw
+end:
+    nop
+";
+        assert_eq!(run(source).unwrap(), Value::Tuple(vec![Value::Int(1), Value::String("boom".to_string())]));
+
+        // Failing instructions throw their error, and uncaught ones fail the call
+        let source = ".const 0 int 1\n.const 1 int 0\n    try_begin caught\n    const 0\n    const 1\n    div\n    try_end\ncaught:\n    nop\n";
+        assert!(matches!(run(source).unwrap(), Value::String(message) if message.contains("zero")));
+        assert!(run(".const 0 int 1\n    const 0\n    throw\n").is_err());
+        assert!(run(".const 0 int 1\n    const 0\n    receive\n").is_err());
     }
 }
@@ -0,0 +1,180 @@
+//! Runtime type feedback for specializing compiled code
+//!
+//! Compiled code records the kinds of operands arithmetic and comparison
+//! instructions see. A site that has only ever seen integers, or only
+//! floats, is compiled to machine code for that kind behind a guard, and
+//! a guard that fails deoptimizes the instruction back to the VM and
+//! records the new kind, so the next compilation leaves the site generic.
+
+use std::collections::HashMap;
+use crate::bytecode::{Bytecode, BytecodeProgram, Value};
+
+/// Kind of value an operand held
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    /// Signed integer
+    Int,
+    /// Float
+    Float,
+    /// String
+    String,
+    /// Anything else
+    Other,
+}
+
+impl ValueKind {
+    /// Kind of a value
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Int(_) => ValueKind::Int,
+            Value::Float(_) => ValueKind::Float,
+            Value::String(_) => ValueKind::String,
+            _ => ValueKind::Other,
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// An instruction compiled for operands of one kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Specialization {
+    /// Instruction specialized
+    pub pc: usize,
+    /// Kind both operands must be
+    pub kind: ValueKind,
+}
+
+/// Operand kinds seen at one instruction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SiteFeedback {
+    /// Kinds seen, one bit per `ValueKind`
+    kinds: u8,
+    /// Times the instruction executed
+    pub count: u64,
+}
+
+impl SiteFeedback {
+    /// Kinds seen, in declaration order
+    pub fn kinds(&self) -> Vec<ValueKind> {
+        [ValueKind::Int, ValueKind::Float, ValueKind::String, ValueKind::Other].into_iter()
+            .filter(|kind| self.kinds & kind.bit() != 0)
+            .collect()
+    }
+
+    /// The one kind seen, if only one was
+    pub fn monomorphic(&self) -> Option<ValueKind> {
+        match self.kinds()[..] {
+            [kind] => Some(kind),
+            _ => None,
+        }
+    }
+}
+
+/// Type feedback for a program, by instruction
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeFeedback {
+    sites: HashMap<usize, SiteFeedback>,
+}
+
+impl TypeFeedback {
+    /// Create empty feedback
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether feedback is collected for an instruction
+    pub fn is_site(instruction: &Bytecode) -> bool {
+        matches!(instruction,
+            Bytecode::Add(_) | Bytecode::Sub(_) | Bytecode::Mul(_)
+            | Bytecode::Eq(_) | Bytecode::Lt(_) | Bytecode::Le(_) | Bytecode::Gt(_) | Bytecode::Ge(_))
+    }
+
+    /// Whether an instruction can be compiled for operands of `kind`
+    pub fn can_specialize(instruction: &Bytecode, kind: ValueKind) -> bool {
+        match kind {
+            ValueKind::Int => Self::is_site(instruction),
+            ValueKind::Float => Self::is_site(instruction) && !matches!(instruction, Bytecode::Eq(_)),
+            ValueKind::String | ValueKind::Other => false,
+        }
+    }
+
+    /// Record the operands an instruction was executed with
+    pub fn record(&mut self, pc: usize, operands: &[Value]) {
+        let site = self.sites.entry(pc).or_default();
+        site.count += 1;
+        for operand in operands {
+            site.kinds |= ValueKind::of(operand).bit();
+        }
+    }
+
+    /// Record an instruction executed `count` times with operands of one kind
+    pub fn record_kind(&mut self, pc: usize, kind: ValueKind, count: u64) {
+        let site = self.sites.entry(pc).or_default();
+        site.count += count;
+        site.kinds |= kind.bit();
+    }
+
+    /// Add feedback collected elsewhere
+    pub fn merge(&mut self, other: &TypeFeedback) {
+        for (pc, theirs) in &other.sites {
+            let site = self.sites.entry(*pc).or_default();
+            site.kinds |= theirs.kinds;
+            site.count += theirs.count;
+        }
+    }
+
+    /// Feedback for one instruction
+    pub fn site(&self, pc: usize) -> Option<&SiteFeedback> {
+        self.sites.get(&pc)
+    }
+
+    /// The instructions of `program` worth specializing, by pc
+    pub fn specializations(&self, program: &BytecodeProgram) -> Vec<Specialization> {
+        let mut specializations: Vec<Specialization> = self.sites.iter()
+            .filter_map(|(pc, site)| {
+                let kind = site.monomorphic()?;
+                let instruction = program.instructions.get(*pc)?;
+                Self::can_specialize(instruction, kind).then_some(Specialization { pc: *pc, kind })
+            })
+            .collect();
+        specializations.sort_by_key(|specialization| specialization.pc);
+        specializations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EffectGrade;
+
+    #[test]
+    fn test_specializations_follow_feedback() {
+        let mut program = BytecodeProgram::new("feedback".to_string());
+        program.add_instruction(Bytecode::Add(EffectGrade::Pure));
+        program.add_instruction(Bytecode::Eq(EffectGrade::Pure));
+        program.add_instruction(Bytecode::Lt(EffectGrade::Pure));
+        program.add_instruction(Bytecode::StrConcat(EffectGrade::Pure));
+
+        let mut feedback = TypeFeedback::new();
+        feedback.record(0, &[Value::Int(1), Value::Int(2)]);
+        feedback.record(1, &[Value::Float(1.0), Value::Float(2.0)]);
+        feedback.record(2, &[Value::Float(1.0), Value::Float(2.0)]);
+        feedback.record(3, &[Value::Int(1), Value::Int(2)]);
+        assert_eq!(feedback.specializations(&program), vec![
+            Specialization { pc: 0, kind: ValueKind::Int },
+            Specialization { pc: 2, kind: ValueKind::Float },
+        ]);
+
+        // Mixed operands, here or merged in, make a site generic
+        let mut other = TypeFeedback::new();
+        other.record(0, &[Value::Int(1), Value::String("1".to_string())]);
+        feedback.merge(&other);
+        feedback.record(2, &[Value::Int(1), Value::Float(2.0)]);
+        assert!(feedback.specializations(&program).is_empty());
+        assert_eq!(feedback.site(0).unwrap().kinds(), vec![ValueKind::Int, ValueKind::String]);
+        assert_eq!(feedback.site(0).unwrap().count, 2);
+    }
+}
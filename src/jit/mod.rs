@@ -1,16 +1,26 @@
 //! REAM JIT Compiler - Coalgebraic native code generation
 
+pub mod aot;
+pub mod background;
 pub mod compiler;
+pub mod feedback;
 pub mod optimization;
+pub mod perf;
 pub mod runtime;
+pub mod simd;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use crate::bytecode::{BytecodeProgram, Value};
+use crate::bytecode::{BytecodeProgram, BytecodeVM, Value};
 use crate::types::EffectGrade;
-use crate::error::JitResult;
+use crate::error::{BytecodeError, JitError, JitResult};
 
+pub use aot::AotCompiler;
+pub use background::{BackgroundCompiler, PendingFunction};
 pub use compiler::ReamJIT;
+pub use feedback::{Specialization, TypeFeedback, ValueKind};
+use compiler::NativeCode;
+pub use perf::CodeSymbol;
 pub use optimization::{HotSpotOptimizer, PerformanceMonitor};
 pub use runtime::JitRuntime;
 
@@ -25,12 +35,13 @@ pub struct JitFunction {
     effect_grade: EffectGrade,
     /// Compilation metadata
     metadata: JitMetadata,
+    /// The compiled code, kept alive as long as the function
+    code: Option<Arc<NativeCode>>,
 }
 
 // Safety: JitFunction contains a function pointer that points to executable code.
 // The code is immutable once compiled and the pointer remains valid for the lifetime
-// of the JitFunction. It's safe to send between threads as long as the underlying
-// memory is not freed.
+// of the JitFunction, which holds on to the memory it lives in.
 unsafe impl Send for JitFunction {}
 unsafe impl Sync for JitFunction {}
 
@@ -41,6 +52,7 @@ impl Clone for JitFunction {
             size: self.size,
             effect_grade: self.effect_grade,
             metadata: self.metadata.clone(),
+            code: self.code.clone(),
         }
     }
 }
@@ -58,6 +70,10 @@ pub struct JitMetadata {
     pub opt_level: u8,
     /// Hot spot information
     pub hot_spots: Vec<usize>,
+    /// Instructions compiled for one kind of operand
+    pub specializations: Vec<Specialization>,
+    /// Headers of loops run with vector instructions
+    pub vector_loops: Vec<usize>,
 }
 
 impl JitFunction {
@@ -73,18 +89,32 @@ impl JitFunction {
             size,
             effect_grade,
             metadata,
+            code: None,
+        }
+    }
+
+    /// Create a function for code the compiler built
+    pub(crate) fn compiled(
+        code: Arc<NativeCode>,
+        size: usize,
+        effect_grade: EffectGrade,
+        metadata: JitMetadata,
+    ) -> Self {
+        JitFunction {
+            function_ptr: code.entry(),
+            size,
+            effect_grade,
+            metadata,
+            code: Some(code),
         }
     }
     
-    /// Call the JIT-compiled function
+    /// Call the JIT-compiled function, with its arguments pushed onto the
+    /// program's stack in order
     pub fn call(&self, args: &[Value]) -> JitResult<Value> {
-        // Safety: This is inherently unsafe as we're calling dynamically generated code
-        unsafe {
-            let func: extern "C" fn(*const Value, usize) -> Value = 
-                std::mem::transmute(self.function_ptr);
-            
-            Ok(func(args.as_ptr(), args.len()))
-        }
+        let code = self.code.as_ref()
+            .ok_or_else(|| JitError::Execution("Function has no compiled code".to_string()))?;
+        code.run(args).map_err(|error| JitError::Execution(error.to_string()))
     }
     
     /// Call with no arguments
@@ -121,6 +151,16 @@ impl JitFunction {
     pub fn metadata(&self) -> &JitMetadata {
         &self.metadata
     }
+
+    /// Operand kinds the function has seen, including those it was compiled with
+    pub fn feedback(&self) -> TypeFeedback {
+        self.code.as_ref().map(|code| code.feedback()).unwrap_or_default()
+    }
+
+    /// Take the count of specialized instructions that deoptimized since the last call
+    pub fn take_deoptimizations(&self) -> u64 {
+        self.code.as_ref().map_or(0, |code| code.take_deoptimizations())
+    }
     
     /// Check if function is valid
     pub fn is_valid(&self) -> bool {
@@ -138,6 +178,18 @@ pub struct JitContext {
     optimizer: HotSpotOptimizer,
     /// JIT statistics
     stats: JitStats,
+    /// Announce compiled code to profilers
+    debug_info: bool,
+    /// Also write compiled code to a jitdump file
+    jitdump: bool,
+    /// Workers compiling while programs are interpreted, if compilation
+    /// happens in the background
+    background: Option<BackgroundCompiler>,
+    /// Compilations workers have not installed yet, by cache key
+    pending: HashMap<String, Arc<PendingFunction>>,
+    /// Programs whose background compilation failed, left to the
+    /// interpreter
+    uncompilable: HashSet<String>,
 }
 
 /// JIT compilation statistics
@@ -155,6 +207,30 @@ pub struct JitStats {
     pub hot_spot_optimizations: u64,
     /// Native code size
     pub native_code_size: usize,
+    /// Recompilations for new type feedback
+    pub specializations: u64,
+    /// Specialized instructions whose guards failed
+    pub deoptimizations: u64,
+    /// Compilations installed by background workers
+    pub background_compilations: u64,
+    /// Background compilations that failed
+    pub failed_compilations: u64,
+    /// Runs interpreted while their program was being compiled
+    pub interpreted_runs: u64,
+    /// Total time from queueing background compilations to their install
+    pub total_compile_latency: std::time::Duration,
+    /// Longest time from queueing a background compilation to its install
+    pub max_compile_latency: std::time::Duration,
+}
+
+impl JitStats {
+    /// Average time from queueing a background compilation to its install
+    pub fn average_compile_latency(&self) -> std::time::Duration {
+        match self.background_compilations + self.failed_compilations {
+            0 => std::time::Duration::ZERO,
+            count => self.total_compile_latency / count as u32,
+        }
+    }
 }
 
 impl JitContext {
@@ -165,6 +241,11 @@ impl JitContext {
             monitor: PerformanceMonitor::new(),
             optimizer: HotSpotOptimizer::new(),
             stats: JitStats::default(),
+            debug_info: false,
+            jitdump: false,
+            background: None,
+            pending: HashMap::new(),
+            uncompilable: HashSet::new(),
         }
     }
     
@@ -182,7 +263,7 @@ impl JitContext {
         
         // Compile with JIT
         let start_time = std::time::Instant::now();
-        let mut jit = ReamJIT::new();
+        let mut jit = self.compiler();
         let jit_func = jit.compile_program(program)?;
         let compile_time = start_time.elapsed();
         
@@ -198,16 +279,28 @@ impl JitContext {
         Ok(func_arc)
     }
     
-    /// Execute a program with JIT compilation
+    /// Execute a program with JIT compilation. When compiling in the
+    /// background, the program is interpreted until its compiled code is
+    /// installed.
     pub fn execute(&mut self, program: &BytecodeProgram, args: &[Value]) -> JitResult<Value> {
-        let func = self.compile(program)?;
+        let func = match self.background {
+            Some(_) => self.installed(program),
+            None => Some(self.compile(program)?),
+        };
+        let Some(func) = func else {
+            self.stats.interpreted_runs += 1;
+            return Self::interpret(program, args);
+        };
         
         // Monitor performance
         let start = std::time::Instant::now();
-        let result = func.call(args)?;
+        let result = func.call(args);
         let duration = start.elapsed();
         
         self.monitor.observe_execution(program, duration);
+        self.stats.deoptimizations += func.take_deoptimizations();
+        self.specialize(program, &func)?;
+        let result = result?;
         
         // Check for hot spots and optimize if needed
         if self.monitor.should_optimize(program) {
@@ -222,9 +315,28 @@ impl JitContext {
         &self.stats
     }
     
+    /// Compile programs on `threads` worker threads, interpreting them
+    /// until their compiled code is ready, or on the calling thread
+    /// when `threads` is 0
+    pub fn set_background_threads(&mut self, threads: usize) {
+        self.background = (threads > 0).then(|| BackgroundCompiler::new(threads));
+    }
+
+    /// Block until every background compilation is installed
+    pub fn wait_for_compilations(&mut self) {
+        let keys: Vec<String> = self.pending.keys().cloned().collect();
+        for key in keys {
+            if let Some(pending) = self.pending.get(&key) {
+                let _ = pending.wait();
+            }
+            self.install_ready(&key);
+        }
+    }
+
     /// Clear function cache
     pub fn clear_cache(&mut self) {
         self.functions.clear();
+        self.uncompilable.clear();
         self.stats.cache_hits = 0;
         self.stats.cache_misses = 0;
     }
@@ -255,6 +367,120 @@ impl JitContext {
         format!("jit_{:x}", hasher.finish())
     }
     
+    /// Recompile a function whose type feedback calls for different
+    /// specializations than it was compiled with, which is how sites
+    /// that deoptimized go back to generic code
+    fn specialize(&mut self, program: &BytecodeProgram, func: &JitFunction) -> JitResult<()> {
+        let Some(code) = func.code.as_ref() else {
+            return Ok(());
+        };
+        let feedback = code.feedback();
+        if feedback.specializations(code.program()) == func.metadata().specializations {
+            return Ok(());
+        }
+
+        let mut jit = self.compiler();
+        jit.set_optimization_level(func.metadata().opt_level);
+        jit.set_feedback(feedback);
+        self.stats.specializations += 1;
+        let cache_key = self.generate_cache_key(program);
+        self.recompile(cache_key, jit, code.program())
+    }
+
+    /// Replace the function cached under `key` with `program` compiled
+    /// by `jit`, on a worker when compiling in the background
+    fn recompile(&mut self, key: String, mut jit: ReamJIT, program: &BytecodeProgram) -> JitResult<()> {
+        match &self.background {
+            Some(_) if self.pending.contains_key(&key) => {}
+            Some(background) => {
+                let pending = background.submit(jit, program.clone());
+                self.pending.insert(key, pending);
+            }
+            None => {
+                let func = jit.compile_program(program)?;
+                self.stats.native_code_size += func.size();
+                self.functions.insert(key, Arc::new(func));
+            }
+        }
+        Ok(())
+    }
+
+    /// The compiled function for `program` if one is installed, queueing
+    /// its compilation if none is underway
+    fn installed(&mut self, program: &BytecodeProgram) -> Option<Arc<JitFunction>> {
+        let cache_key = self.generate_cache_key(program);
+        self.install_ready(&cache_key);
+        if let Some(func) = self.functions.get(&cache_key) {
+            self.stats.cache_hits += 1;
+            return Some(Arc::clone(func));
+        }
+        if !self.pending.contains_key(&cache_key) && !self.uncompilable.contains(&cache_key) {
+            self.stats.cache_misses += 1;
+            let jit = self.compiler();
+            if let Some(background) = &self.background {
+                let pending = background.submit(jit, program.clone());
+                self.pending.insert(cache_key, pending);
+            }
+        }
+        None
+    }
+
+    /// Install the compilation pending under `key` if a worker finished it
+    fn install_ready(&mut self, key: &str) {
+        let Some(pending) = self.pending.get(key) else {
+            return;
+        };
+        let (Some(function), Some(latency)) = (pending.function(), pending.latency()) else {
+            return;
+        };
+        self.pending.remove(key);
+        self.stats.total_compile_latency += latency;
+        self.stats.max_compile_latency = self.stats.max_compile_latency.max(latency);
+        match function {
+            Ok(func) => {
+                if !self.functions.contains_key(key) {
+                    self.stats.functions_compiled += 1;
+                }
+                self.stats.background_compilations += 1;
+                self.stats.total_compile_time += func.metadata().compile_time;
+                self.stats.native_code_size += func.size();
+                self.functions.insert(key.to_string(), func);
+            }
+            Err(_) => {
+                self.stats.failed_compilations += 1;
+                // A replacement that failed leaves the function it was to replace
+                if !self.functions.contains_key(key) {
+                    self.uncompilable.insert(key.to_string());
+                }
+            }
+        }
+    }
+
+    /// Run a program in the interpreter, with its arguments pushed onto
+    /// its stack in order
+    fn interpret(program: &BytecodeProgram, args: &[Value]) -> JitResult<Value> {
+        let mut vm = BytecodeVM::new();
+        vm.begin();
+        vm.context_mut().stack.extend_from_slice(args);
+        let result = loop {
+            match vm.resume(program) {
+                Ok(Some(result)) => break Ok(result),
+                Ok(None) if vm.is_waiting() => break Err(BytecodeError::NoMessage(vm.context().pc)),
+                Ok(None) => {}
+                Err(error) => break Err(error),
+            }
+        };
+        result.map_err(|error| JitError::Execution(error.to_string()))
+    }
+
+    /// A compiler configured like this context
+    fn compiler(&self) -> ReamJIT {
+        let mut jit = ReamJIT::new();
+        jit.set_debug_info(self.debug_info);
+        jit.set_jitdump(self.jitdump);
+        jit
+    }
+
     fn optimize_hot_spots(&mut self, program: &BytecodeProgram) -> JitResult<()> {
         let hot_spots = self.monitor.get_hot_spots(program);
         
@@ -263,12 +489,9 @@ impl JitContext {
             
             // Recompile with optimizations
             let cache_key = self.generate_cache_key(program);
-            let mut jit = ReamJIT::new();
+            let mut jit = self.compiler();
             jit.set_optimization_level(3); // Aggressive optimization
-            let optimized_func = jit.compile_program(&optimized)?;
-            
-            // Update cache
-            self.functions.insert(cache_key, Arc::new(optimized_func));
+            self.recompile(cache_key, jit, &optimized)?;
             self.stats.hot_spot_optimizations += 1;
         }
         
@@ -289,6 +512,13 @@ impl JitContext {
         let mut options = JitOptions::default();
         options.debug_info = enable;
         self.optimizer.set_options(&options);
+        self.debug_info = enable;
+    }
+
+    /// Also write compiled code to a jitdump file, when debug info is
+    /// enabled
+    pub fn set_jitdump(&mut self, enable: bool) {
+        self.jitdump = enable;
     }
 
     /// Enable profiling
@@ -317,8 +547,15 @@ pub struct JitOptions {
     pub enable_inlining: bool,
     /// Maximum function size for inlining
     pub inline_threshold: usize,
-    /// Enable debug information
+    /// Enable debug information, naming compiled code after its TLisp
+    /// functions in `/tmp/perf-<pid>.map`
     pub debug_info: bool,
+    /// With debug information, also write a jitdump file for
+    /// `perf inject --jit`
+    pub jitdump: bool,
+    /// Threads compiling in the background while programs are
+    /// interpreted; 0 compiles before running
+    pub background_threads: usize,
 }
 
 impl Default for JitOptions {
@@ -330,6 +567,8 @@ impl Default for JitOptions {
             enable_inlining: true,
             inline_threshold: 100,
             debug_info: false,
+            jitdump: false,
+            background_threads: 0,
         }
     }
 }
@@ -377,12 +616,27 @@ impl JitBuilder {
         self
     }
     
+    /// Enable/disable jitdump output
+    pub fn jitdump(mut self, enable: bool) -> Self {
+        self.options.jitdump = enable;
+        self
+    }
+    
+    /// Set the number of background compilation threads
+    pub fn background_threads(mut self, threads: usize) -> Self {
+        self.options.background_threads = threads;
+        self
+    }
+    
     /// Build the JIT context
     pub fn build(self) -> JitContext {
         let mut context = JitContext::new();
         
         // Configure optimizer
         context.optimizer.set_options(&self.options);
+        context.debug_info = self.options.debug_info;
+        context.jitdump = self.options.jitdump;
+        context.set_background_threads(self.options.background_threads);
         
         // Configure monitor
         context.monitor.set_hot_spot_threshold(self.options.hot_spot_threshold);
@@ -411,12 +665,76 @@ mod tests {
         let mut program = BytecodeProgram::new("test".to_string());
         let const_id = program.add_constant(Value::Int(42));
         program.add_instruction(Bytecode::Const(const_id, EffectGrade::Pure));
-        program.add_instruction(Bytecode::Ret(EffectGrade::Pure));
+        program.add_instruction(Bytecode::Nop(EffectGrade::Pure));
         
-        // This would normally compile, but we don't have a real JIT implementation
-        // So we'll just test the structure
         assert_eq!(context.cache_size(), 0);
         assert_eq!(context.stats().functions_compiled, 0);
+
+        assert_eq!(context.execute(&program, &[]).unwrap(), Value::Int(42));
+        assert_eq!(context.execute(&program, &[]).unwrap(), Value::Int(42));
+        assert_eq!(context.cache_size(), 1);
+        assert_eq!(context.stats().functions_compiled, 1);
+        assert_eq!(context.stats().cache_hits, 1);
+    }
+
+    #[test]
+    fn test_specialization_and_deoptimization() {
+        // Adds its argument to one, then compares the sum with ten
+        let mut program = BytecodeProgram::new("feedback".to_string());
+        let one = program.add_constant(Value::Int(1));
+        let ten = program.add_constant(Value::Int(10));
+        program.add_instruction(Bytecode::Const(one, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Add(EffectGrade::Pure));
+        program.add_instruction(Bytecode::Dup(EffectGrade::Pure));
+        program.add_instruction(Bytecode::Const(ten, EffectGrade::Pure));
+        program.add_instruction(Bytecode::Lt(EffectGrade::Pure));
+        program.add_instruction(Bytecode::TupleNew(2, EffectGrade::Pure));
+        let expect = |sum: Value, less: bool| Value::Tuple(vec![sum, Value::Bool(less)]);
+
+        // Integers seen by the generic code get the sites specialized
+        let mut context = JitContext::new();
+        assert_eq!(context.execute(&program, &[Value::Int(2)]).unwrap(), expect(Value::Int(3), true));
+        assert_eq!(context.stats().specializations, 1);
+        let specialized = context.compile(&program).unwrap();
+        assert_eq!(specialized.metadata().specializations, vec![
+            Specialization { pc: 1, kind: ValueKind::Int },
+            Specialization { pc: 4, kind: ValueKind::Int },
+        ]);
+        assert_eq!(context.execute(&program, &[Value::Int(20)]).unwrap(), expect(Value::Int(21), false));
+        assert_eq!(context.stats().deoptimizations, 0);
+
+        // Overflow leaves the add to the VM, whatever it makes of it
+        let _ = context.execute(&program, &[Value::Int(i64::MAX)]);
+        assert_eq!(context.stats().deoptimizations, 1);
+        assert_eq!(context.stats().specializations, 1);
+
+        // Floats fail both guards, and the sites go back to generic code
+        assert_eq!(context.execute(&program, &[Value::Float(1.5)]).unwrap(), expect(Value::Float(2.5), true));
+        assert_eq!(context.stats().deoptimizations, 3);
+        assert_eq!(context.stats().specializations, 2);
+        let generic = context.compile(&program).unwrap();
+        assert!(generic.metadata().specializations.is_empty());
+        assert_eq!(generic.feedback().site(1).unwrap().kinds(), vec![ValueKind::Int, ValueKind::Float]);
+        assert_eq!(context.execute(&program, &[Value::Int(2)]).unwrap(), expect(Value::Int(3), true));
+    }
+
+    #[test]
+    fn test_execute_tlisp() {
+        let sources = [
+            "(+ 1 (* 2 3))",
+            "(if (< 1 2) \"yes\" \"no\")",
+            "(let ((x 5)) (* x x))",
+            "(try (throw 5) (catch e (+ e 1)))",
+            "(try (/ 1 0) (catch e e))",
+        ];
+        let mut context = JitContext::new();
+        for source in sources {
+            let mut interpreter = crate::tlisp::TlispInterpreter::new();
+            let expr = interpreter.parse(source).unwrap();
+            let program = interpreter.compile_to_bytecode_untyped(expr).unwrap();
+            let expected = crate::bytecode::BytecodeVM::new().execute_program(&program).unwrap();
+            assert_eq!(context.execute(&program, &[]).unwrap(), expected, "{}", source);
+        }
     }
     
     #[test]
@@ -429,4 +747,73 @@ mod tests {
         
         assert_eq!(context.cache_size(), 0);
     }
+
+    #[test]
+    fn test_background_compilation() {
+        let program = crate::bytecode::assemble(".const 0 int 20\n.const 1 int 22\n    const 0\n    const 1\n    add\n").unwrap();
+        let mut context = JitBuilder::new()
+            .background_threads(2)
+            .build();
+
+        // The first run is interpreted while a worker compiles
+        assert_eq!(context.execute(&program, &[]).unwrap(), Value::Int(42));
+        assert_eq!(context.stats().interpreted_runs, 1);
+        assert_eq!(context.stats().cache_misses, 1);
+
+        context.wait_for_compilations();
+        assert_eq!(context.execute(&program, &[]).unwrap(), Value::Int(42));
+        let stats = context.stats();
+        assert_eq!(stats.interpreted_runs, 1);
+        assert_eq!(stats.cache_hits, 1);
+        assert_eq!(stats.functions_compiled, 1);
+        assert_eq!(stats.background_compilations, 1);
+        assert!(stats.max_compile_latency > std::time::Duration::ZERO);
+        assert_eq!(stats.average_compile_latency(), stats.total_compile_latency);
+        assert_eq!(context.cache_size(), 1);
+    }
+
+    #[test]
+    fn test_perf_map() {
+        let program = crate::bytecode::assemble("\
+.const 0 int 1
+.const 1 int 5
+.function 0 countdown params=1 locals=1 len=9
+    const 1
+    call countdown
+    jump end
+countdown:
+    store 0
+    load 0
+    jump_if_not done
+    load 0
+    const 0
+    sub
+    call countdown
+    ret
+done:
+    ret
+end:
+    nop
+").unwrap();
+        let mut context = JitBuilder::new()
+            .debug_info(true)
+            .jitdump(true)
+            .build();
+        context.execute(&program, &[]).unwrap();
+
+        // Every symbol of this process's code is a `start size name` line
+        let map = std::fs::read_to_string(perf::map_path()).unwrap();
+        let lines: Vec<&str> = map.lines().collect();
+        assert!(lines.iter().any(|line| line.ends_with(" countdown")));
+        assert!(lines.iter().any(|line| line.ends_with(&format!(" {}", program.metadata.name))));
+        for line in lines {
+            let fields: Vec<&str> = line.splitn(3, ' ').collect();
+            assert!(usize::from_str_radix(fields[0], 16).unwrap() > 0);
+            assert!(usize::from_str_radix(fields[1], 16).unwrap() > 0);
+        }
+
+        let dump = std::fs::read(perf::jitdump_path()).unwrap();
+        assert_eq!(&dump[..4], &0x4A69_5444u32.to_ne_bytes());
+        assert!(dump.windows(10).any(|window| window == b"countdown\0"));
+    }
 }
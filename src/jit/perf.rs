@@ -0,0 +1,234 @@
+//! Profiler support for compiled code
+//!
+//! When debug information is enabled, compiled programs are announced to
+//! Linux `perf` so profiles and flamegraphs show TLisp function names
+//! rather than anonymous addresses. Each symbol gets a line in
+//! `/tmp/perf-<pid>.map`, which `perf report` reads on its own, and can
+//! also get a record in a `jit-<pid>.dump` jitdump file, which
+//! `perf inject --jit` turns into symbols backed by the machine code
+//! itself. Code compiled from a function's instructions is named after
+//! the function, and everything else after the program.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use crate::bytecode::BytecodeProgram;
+
+/// Identifies a jitdump file
+const JITDUMP_MAGIC: u32 = 0x4A69_5444;
+/// Jitdump format version
+const JITDUMP_VERSION: u32 = 1;
+/// Size of the jitdump file header
+const JITDUMP_HEADER_SIZE: u32 = 40;
+/// Jitdump record for code that was loaded
+const JIT_CODE_LOAD: u32 = 0;
+
+/// A named run of machine code within a compiled program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSymbol {
+    /// Offset from the start of the compiled program
+    pub offset: usize,
+    /// Size in bytes
+    pub size: usize,
+    /// Name profilers show
+    pub name: String,
+}
+
+/// Profiler files of this process, opened on first use
+struct PerfFiles {
+    map: Option<File>,
+    jitdump: Option<File>,
+    /// Records written to the jitdump file
+    code_index: u64,
+}
+
+static PERF_FILES: Mutex<PerfFiles> = Mutex::new(PerfFiles {
+    map: None,
+    jitdump: None,
+    code_index: 0,
+});
+
+/// Path `perf` looks for this process's symbol map at
+pub fn map_path() -> PathBuf {
+    PathBuf::from(format!("/tmp/perf-{}.map", std::process::id()))
+}
+
+/// Path this process's jitdump file is written to, in `JITDUMPDIR` or
+/// the temporary directory
+pub fn jitdump_path() -> PathBuf {
+    let directory = std::env::var_os("JITDUMPDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    directory.join(format!("jit-{}.dump", std::process::id()))
+}
+
+/// Name the code of a program `size` bytes long, given the instruction
+/// each range of it was compiled from as `(start, end, pc)` in order
+pub(crate) fn symbols(program: &BytecodeProgram, size: usize, ranges: &[(usize, usize, usize)]) -> Vec<CodeSymbol> {
+    let mut symbols: Vec<CodeSymbol> = Vec::new();
+    let mut push = |start: usize, end: usize, name: &str| {
+        if end <= start {
+            return;
+        }
+        if let Some(last) = symbols.last_mut() {
+            if last.name == name && last.offset + last.size == start {
+                last.size = end - last.offset;
+                return;
+            }
+        }
+        symbols.push(CodeSymbol { offset: start, size: end - start, name: name.to_string() });
+    };
+
+    let mut covered = 0;
+    for &(start, end, pc) in ranges {
+        let start = start.max(covered);
+        push(covered, start, &program.metadata.name);
+        let name = program.functions.iter()
+            .find(|function| (function.start_pc..function.start_pc + function.instructions.len()).contains(&pc))
+            .map_or(&program.metadata.name, |function| &function.name);
+        push(start, end, name);
+        covered = covered.max(end);
+    }
+    push(covered, size, &program.metadata.name);
+    symbols
+}
+
+/// Announce the symbols of code loaded at `entry` in the symbol map,
+/// and in the jitdump file when `jitdump` is set
+pub(crate) fn register(entry: *const u8, symbols: &[CodeSymbol], jitdump: bool) -> io::Result<()> {
+    let mut files = PERF_FILES.lock().unwrap_or_else(|e| e.into_inner());
+    if files.map.is_none() {
+        files.map = Some(OpenOptions::new().create(true).append(true).open(map_path())?);
+    }
+    if let Some(map) = files.map.as_mut() {
+        let mut lines = String::new();
+        for symbol in symbols {
+            lines.push_str(&format!("{:x} {:x} {}\n", entry as usize + symbol.offset, symbol.size, symbol.name));
+        }
+        map.write_all(lines.as_bytes())?;
+    }
+
+    if !jitdump {
+        return Ok(());
+    }
+    if files.jitdump.is_none() {
+        files.jitdump = Some(open_jitdump()?);
+    }
+    for symbol in symbols {
+        let index = files.code_index;
+        files.code_index += 1;
+        // The code is finalized and stays mapped while it can run
+        let code = unsafe { std::slice::from_raw_parts(entry.add(symbol.offset), symbol.size) };
+        let address = entry as u64 + symbol.offset as u64;
+        let record_size = 16 + 40 + symbol.name.len() + 1 + code.len();
+
+        let mut record = Vec::with_capacity(record_size);
+        record.extend_from_slice(&JIT_CODE_LOAD.to_ne_bytes());
+        record.extend_from_slice(&(record_size as u32).to_ne_bytes());
+        record.extend_from_slice(&timestamp().to_ne_bytes());
+        record.extend_from_slice(&std::process::id().to_ne_bytes());
+        record.extend_from_slice(&thread_id().to_ne_bytes());
+        record.extend_from_slice(&address.to_ne_bytes());
+        record.extend_from_slice(&address.to_ne_bytes());
+        record.extend_from_slice(&(code.len() as u64).to_ne_bytes());
+        record.extend_from_slice(&index.to_ne_bytes());
+        record.extend_from_slice(symbol.name.as_bytes());
+        record.push(0);
+        record.extend_from_slice(code);
+        if let Some(file) = files.jitdump.as_mut() {
+            file.write_all(&record)?;
+        }
+    }
+    Ok(())
+}
+
+/// Create the jitdump file and write its header. `perf record` only
+/// notices the file if the process maps it executable, so it is mapped
+/// once and left mapped.
+fn open_jitdump() -> io::Result<File> {
+    let mut file = OpenOptions::new().create(true).truncate(true).read(true).write(true).open(jitdump_path())?;
+    let machine: u32 = if cfg!(target_arch = "aarch64") { 183 } else { 62 };
+
+    let mut header = Vec::with_capacity(JITDUMP_HEADER_SIZE as usize);
+    header.extend_from_slice(&JITDUMP_MAGIC.to_ne_bytes());
+    header.extend_from_slice(&JITDUMP_VERSION.to_ne_bytes());
+    header.extend_from_slice(&JITDUMP_HEADER_SIZE.to_ne_bytes());
+    header.extend_from_slice(&machine.to_ne_bytes());
+    header.extend_from_slice(&0u32.to_ne_bytes());
+    header.extend_from_slice(&std::process::id().to_ne_bytes());
+    header.extend_from_slice(&timestamp().to_ne_bytes());
+    header.extend_from_slice(&0u64.to_ne_bytes());
+    file.write_all(&header)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        let marker = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                JITDUMP_HEADER_SIZE as usize,
+                libc::PROT_READ | libc::PROT_EXEC,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if marker == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(file)
+}
+
+/// Monotonic clock in nanoseconds, as `perf record -k mono` uses
+fn timestamp() -> u64 {
+    #[cfg(unix)]
+    {
+        let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+        now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64
+    }
+    #[cfg(not(unix))]
+    {
+        0
+    }
+}
+
+/// Id of the calling thread
+fn thread_id() -> u32 {
+    #[cfg(target_os = "linux")]
+    {
+        unsafe { libc::gettid() as u32 }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        std::process::id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::assemble;
+
+    #[test]
+    fn test_symbols_follow_functions() {
+        let program = assemble(
+            ".function 0 double params=1 locals=0 start=2 len=2\n\
+             \x20   nop\n\
+             \x20   nop\n\
+             \x20   nop\n\
+             \x20   nop\n"
+        ).unwrap();
+        let name = program.metadata.name.clone();
+
+        // Ranges of one function merge, and gaps go to the program
+        let symbols = symbols(&program, 100, &[(10, 20, 0), (20, 30, 2), (30, 40, 3), (50, 60, 1)]);
+        assert_eq!(symbols, vec![
+            CodeSymbol { offset: 0, size: 20, name: name.clone() },
+            CodeSymbol { offset: 20, size: 20, name: "double".to_string() },
+            CodeSymbol { offset: 40, size: 60, name },
+        ]);
+    }
+}
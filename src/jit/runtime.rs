@@ -34,6 +34,8 @@ impl JitRuntime {
         // Configure JIT context based on options
         jit_context.set_optimization_level(options.opt_level);
         jit_context.set_debug_info(options.debug_info);
+        jit_context.set_jitdump(options.jitdump);
+        jit_context.set_background_threads(options.background_threads);
 
         if options.enable_hot_spots {
             jit_context.enable_profiling();
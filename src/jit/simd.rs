@@ -0,0 +1,602 @@
+//! Vector instructions for numeric loops over lists
+//!
+//! `vector_loops` recognizes the counted loops bytecode uses to sum a
+//! list, take the dot product of two, or map an arithmetic operation
+//! over one, in the shape
+//!
+//! ```text
+//! loop:
+//!     load i
+//!     load xs
+//!     list_len
+//!     lt
+//!     jump_if_not done
+//!     ...body...
+//!     load i
+//!     const one
+//!     add
+//!     store i
+//!     jump loop
+//! done:
+//! ```
+//!
+//! Compiled code hands the header of such a loop to `VectorLoop::run`,
+//! which unboxes the lists and runs the whole loop with SIMD kernels,
+//! picking AVX2 when the machine has it and the baseline vector
+//! instructions otherwise. Whenever the VM would do anything but plain
+//! arithmetic, such as meet an element that is not a number, overflow,
+//! or fail, the loop is left to run instruction by instruction instead.
+//! Float sums keep the VM's left-to-right order, since reassociating
+//! them would change their rounding, so only their loop overhead goes.
+
+use std::collections::HashMap;
+use crate::bytecode::{Bytecode, BytecodeProgram, ExecutionContext, Value};
+use crate::bytecode::optimizer::jump_target;
+use crate::types::EffectGrade;
+
+/// Arithmetic a map loop applies to each element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapOp {
+    /// Element plus operand
+    Add,
+    /// Element minus operand
+    Sub,
+    /// Element times operand
+    Mul,
+}
+
+/// Where the operand of a map loop comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapOperand {
+    /// A constant
+    Const(u32),
+    /// A local the loop does not assign
+    Local(u32),
+}
+
+/// What a recognized loop computes, in terms of the locals it uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorKind {
+    /// `acc = acc + list[i]`
+    Sum { acc: u32, list: u32 },
+    /// `acc = acc + a[i] * b[i]`
+    Dot { acc: u32, a: u32, b: u32 },
+    /// `dst = list_set(dst, i, src[i] op operand)`
+    Map { dst: u32, src: u32, op: MapOp, operand: MapOperand },
+}
+
+/// A loop compiled code can run with vector instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorLoop {
+    /// First instruction of the loop
+    pub header: usize,
+    /// Where the loop exits to
+    pub exit: usize,
+    /// Index local
+    pub index: u32,
+    /// List whose length bounds the index
+    pub bound: u32,
+    /// What the body computes
+    pub kind: VectorKind,
+}
+
+/// Find the loops of `program` vector instructions can run, by header
+pub fn vector_loops(program: &BytecodeProgram) -> HashMap<usize, VectorLoop> {
+    let instructions = &program.instructions;
+    instructions.iter().enumerate()
+        .filter_map(|(latch, instruction)| match instruction {
+            Bytecode::Jump(header, _) if (*header as usize) < latch => {
+                recognize(program, *header as usize, latch)
+            }
+            _ => None,
+        })
+        // Code outside the loop must not jump into its body
+        .filter(|vector_loop| {
+            instructions.iter().enumerate().all(|(pc, instruction)| {
+                (vector_loop.header..=vector_loop.exit).contains(&pc)
+                    || jump_target(instruction).is_none_or(|target| target <= vector_loop.header || target >= vector_loop.exit)
+            })
+        })
+        .map(|vector_loop| (vector_loop.header, vector_loop))
+        .collect()
+}
+
+/// Match the loop from `header` to the jump back at `latch`
+fn recognize(program: &BytecodeProgram, header: usize, latch: usize) -> Option<VectorLoop> {
+    use Bytecode::*;
+
+    let code = &program.instructions[header..=latch];
+    if code.iter().any(|instruction| instruction.effect_grade() != EffectGrade::Pure) {
+        return None;
+    }
+    let is_one = |index: &u32| matches!(program.constants.get(*index as usize), Some(Value::Int(1)));
+
+    let (index, bound, exit) = match code {
+        [Load(i, _), Load(bound, _), ListLen(_), Lt(_), JumpIfNot(exit, _), ..] => (*i, *bound, *exit as usize),
+        _ => return None,
+    };
+    if exit != latch + 1 || index == bound {
+        return None;
+    }
+    let body = match &code[5..] {
+        [body @ .., Load(i, _), Const(one, _), Add(_), Store(j, _), Jump(_, _)]
+        | [body @ .., Load(i, _), AddConst(one, _), Store(j, _), Jump(_, _)]
+            if *i == index && *j == index && is_one(one) => body,
+        _ => return None,
+    };
+
+    let kind = match body {
+        [Load(acc, _), Load(list, _), Load(i, _), ListGet(_), Add(_), Store(out, _)]
+            if acc == out && *i == index && *list == bound && *acc != index && acc != list => {
+            VectorKind::Sum { acc: *acc, list: *list }
+        }
+        [Load(acc, _), Load(a, _), Load(i, _), ListGet(_), Load(b, _), Load(j, _), ListGet(_), Mul(_), Add(_), Store(out, _)]
+            if acc == out && *i == index && *j == index && *a == bound && ![index, *a, *b].contains(acc) && *b != index => {
+            VectorKind::Dot { acc: *acc, a: *a, b: *b }
+        }
+        [Load(dst, _), Load(i, _), Load(src, _), Load(j, _), ListGet(_), operand, op, ListSet(_), Store(out, _)]
+            if dst == out && *i == index && *j == index && (*dst == bound || *src == bound) && *dst != index && *src != index => {
+            let operand = match operand {
+                Const(constant, _) => MapOperand::Const(*constant),
+                Load(local, _) if *local != index && local != dst => MapOperand::Local(*local),
+                _ => return None,
+            };
+            let op = match op {
+                Add(_) => MapOp::Add,
+                Sub(_) => MapOp::Sub,
+                Mul(_) => MapOp::Mul,
+                _ => return None,
+            };
+            VectorKind::Map { dst: *dst, src: *src, op, operand }
+        }
+        _ => return None,
+    };
+    Some(VectorLoop { header, exit, index, bound, kind })
+}
+
+impl VectorLoop {
+    /// Run the loop to completion from the current value of its index,
+    /// updating the locals it assigns as the VM would. Returns `false`,
+    /// having changed nothing, when the loop has to run instruction by
+    /// instruction instead.
+    pub fn run(&self, context: &mut ExecutionContext, constants: &[Value]) -> bool {
+        let Some((index, assigned)) = self.compute(context, constants) else {
+            return false;
+        };
+        if let Some((local, value)) = assigned {
+            let _ = context.set_local(local as usize, value);
+        }
+        let _ = context.set_local(self.index as usize, Value::Int(index as i64));
+        true
+    }
+
+    /// The final value of the index, and of the local the body assigns
+    /// if it runs at all
+    fn compute(&self, context: &ExecutionContext, constants: &[Value]) -> Option<(usize, Option<(u32, Value)>)> {
+        let start = match context.get_local(self.index as usize).ok()? {
+            Value::Int(start) if *start >= 0 => *start as usize,
+            _ => return None,
+        };
+        let end = list(context, self.bound)?.len();
+        let assigned = match self.kind {
+            VectorKind::Sum { acc, .. } | VectorKind::Dot { acc, .. } => acc,
+            VectorKind::Map { dst, .. } => dst,
+        };
+        if start >= end {
+            return Some((start, None));
+        }
+
+        let value = match self.kind {
+            VectorKind::Sum { acc, list: items } => {
+                let items = &list(context, items)?[start..end];
+                match context.get_local(acc as usize).ok()? {
+                    Value::Int(acc) => Value::Int(sum_i64(*acc, &ints(items)?)?),
+                    Value::Float(acc) => Value::Float(floats(items)?.iter().fold(*acc, |sum, x| sum + x)),
+                    _ => return None,
+                }
+            }
+            VectorKind::Dot { acc, a, b } => {
+                let a = &list(context, a)?[start..end];
+                let b = list(context, b)?.get(start..end)?;
+                match context.get_local(acc as usize).ok()? {
+                    Value::Int(acc) => Value::Int(dot_i64(*acc, &ints(a)?, &ints(b)?)?),
+                    Value::Float(acc) => {
+                        let (a, b) = (floats(a)?, floats(b)?);
+                        Value::Float(a.iter().zip(&b).fold(*acc, |sum, (x, y)| sum + x * y))
+                    }
+                    _ => return None,
+                }
+            }
+            VectorKind::Map { dst, src, op, operand } => {
+                let operand = match operand {
+                    MapOperand::Const(index) => constants.get(index as usize)?,
+                    MapOperand::Local(index) => context.get_local(index as usize).ok()?,
+                };
+                let items = list(context, src)?.get(start..end)?;
+                let mapped: Vec<Value> = match operand {
+                    Value::Int(k) => {
+                        let mut values = ints(items)?;
+                        map_i64(&mut values, *k, op).then_some(())?;
+                        values.into_iter().map(Value::Int).collect()
+                    }
+                    Value::Float(k) => {
+                        let mut values = floats(items)?;
+                        map_f64(&mut values, *k, op);
+                        values.into_iter().map(Value::Float).collect()
+                    }
+                    _ => return None,
+                };
+                match context.get_local(dst as usize).ok()? {
+                    Value::List(items) if items.len() >= end => {
+                        let mut items = items.clone();
+                        for (slot, value) in items[start..end].iter_mut().zip(mapped) {
+                            *slot = value;
+                        }
+                        Value::List(items)
+                    }
+                    _ => return None,
+                }
+            }
+        };
+        Some((end, Some((assigned, value))))
+    }
+}
+
+/// The items of a list or tuple local
+fn list(context: &ExecutionContext, local: u32) -> Option<&[Value]> {
+    match context.get_local(local as usize).ok()? {
+        Value::List(items) | Value::Tuple(items) => Some(items),
+        _ => None,
+    }
+}
+
+/// Unbox integers, if every item is one
+fn ints(items: &[Value]) -> Option<Vec<i64>> {
+    items.iter().map(|item| match item {
+        Value::Int(value) => Some(*value),
+        _ => None,
+    }).collect()
+}
+
+/// Unbox floats, if every item is one
+fn floats(items: &[Value]) -> Option<Vec<f64>> {
+    items.iter().map(|item| match item {
+        Value::Float(value) => Some(*value),
+        _ => None,
+    }).collect()
+}
+
+/// `acc` plus the sum of `values`, or `None` if a partial sum overflows
+pub fn sum_i64(acc: i64, values: &[i64]) -> Option<i64> {
+    // Wrapping lanes give the exact sum when no partial sum can overflow
+    let bound = (max_abs(values) as u128)
+        .checked_mul(values.len() as u128)
+        .and_then(|bound| bound.checked_add(acc.unsigned_abs() as u128));
+    if bound.is_some_and(|bound| bound <= i64::MAX as u128) {
+        return Some(acc.wrapping_add(wrapping_sum(values)));
+    }
+    values.iter().try_fold(acc, |sum, value| sum.checked_add(*value))
+}
+
+/// `acc` plus the dot product of `a` and `b`, or `None` if a product or
+/// partial sum overflows
+pub fn dot_i64(acc: i64, a: &[i64], b: &[i64]) -> Option<i64> {
+    let bound = (max_abs(a) as u128)
+        .checked_mul(max_abs(b) as u128)
+        .and_then(|bound| bound.checked_mul(a.len() as u128))
+        .and_then(|bound| bound.checked_add(acc.unsigned_abs() as u128));
+    if bound.is_some_and(|bound| bound <= i64::MAX as u128) {
+        return Some(acc.wrapping_add(wrapping_dot(a, b)));
+    }
+    a.iter().zip(b).try_fold(acc, |sum, (x, y)| sum.checked_add(x.checked_mul(*y)?))
+}
+
+/// Apply `op` with `k` to each of `values`, returning `false`, with
+/// `values` unspecified, if any result overflows
+pub fn map_i64(values: &mut [i64], k: i64, op: MapOp) -> bool {
+    match op {
+        MapOp::Add => !wrapping_add_overflows(values, k),
+        MapOp::Sub => !wrapping_sub_overflows(values, k),
+        MapOp::Mul if (max_abs(values) as u128) * (k.unsigned_abs() as u128) <= i64::MAX as u128 => {
+            wrapping_mul(values, k);
+            true
+        }
+        MapOp::Mul => values.iter_mut().all(|value| value.checked_mul(k).map(|product| *value = product).is_some()),
+    }
+}
+
+/// Apply `op` with `k` to each of `values`
+pub fn map_f64(values: &mut [f64], k: f64, op: MapOp) {
+    match op {
+        MapOp::Add => float_add(values, k),
+        MapOp::Sub => float_add(values, -k),
+        MapOp::Mul => float_mul(values, k),
+    }
+}
+
+/// The vector instructions kernels run with on this machine
+pub fn vector_width() -> &'static str {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::arch::is_x86_feature_detected!("avx2") {
+            return "avx2";
+        }
+        "sse2"
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        "neon"
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        "scalar"
+    }
+}
+
+/// Lanes kernels work on at a time, enough to fill two AVX2 registers
+const LANES: usize = 8;
+
+/// Define a kernel that runs `$lanes`, written over independent lanes so
+/// it compiles to vector instructions, built for AVX2 when the machine
+/// has it and for the baseline instruction set otherwise
+macro_rules! kernel {
+    ($(#[$doc:meta])* fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)? = $lanes:ident) => {
+        $(#[$doc])*
+        fn $name($($arg: $ty),*) $(-> $ret)? {
+            #[cfg(target_arch = "x86_64")]
+            {
+                #[target_feature(enable = "avx2")]
+                unsafe fn avx2($($arg: $ty),*) $(-> $ret)? {
+                    $lanes($($arg),*)
+                }
+                if std::arch::is_x86_feature_detected!("avx2") {
+                    // Safety: the machine supports AVX2
+                    return unsafe { avx2($($arg),*) };
+                }
+            }
+            $lanes($($arg),*)
+        }
+    };
+}
+
+kernel!(
+    /// Largest magnitude of `values`
+    fn max_abs(values: &[i64]) -> u64 = max_abs_lanes
+);
+kernel!(
+    /// Sum of `values`, wrapping on overflow
+    fn wrapping_sum(values: &[i64]) -> i64 = wrapping_sum_lanes
+);
+kernel!(
+    /// Dot product of `a` and `b`, wrapping on overflow
+    fn wrapping_dot(a: &[i64], b: &[i64]) -> i64 = wrapping_dot_lanes
+);
+kernel!(
+    /// Add `k` to each of `values`, returning whether any overflowed
+    fn wrapping_add_overflows(values: &mut [i64], k: i64) -> bool = wrapping_add_lanes
+);
+kernel!(
+    /// Subtract `k` from each of `values`, returning whether any overflowed
+    fn wrapping_sub_overflows(values: &mut [i64], k: i64) -> bool = wrapping_sub_lanes
+);
+kernel!(
+    /// Multiply each of `values` by `k`, wrapping on overflow
+    fn wrapping_mul(values: &mut [i64], k: i64) = wrapping_mul_lanes
+);
+kernel!(
+    /// Add `k` to each of `values`
+    fn float_add(values: &mut [f64], k: f64) = float_add_lanes
+);
+kernel!(
+    /// Multiply each of `values` by `k`
+    fn float_mul(values: &mut [f64], k: f64) = float_mul_lanes
+);
+
+#[inline(always)]
+fn max_abs_lanes(values: &[i64]) -> u64 {
+    let mut lanes = [0u64; LANES];
+    let chunks = values.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for (lane, value) in lanes.iter_mut().zip(chunk) {
+            *lane = (*lane).max(value.unsigned_abs());
+        }
+    }
+    rest.iter().map(|value| value.unsigned_abs()).chain(lanes).max().unwrap_or(0)
+}
+
+#[inline(always)]
+fn wrapping_sum_lanes(values: &[i64]) -> i64 {
+    let mut lanes = [0i64; LANES];
+    let chunks = values.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for (lane, value) in lanes.iter_mut().zip(chunk) {
+            *lane = lane.wrapping_add(*value);
+        }
+    }
+    rest.iter().chain(&lanes).fold(0, |sum, value| sum.wrapping_add(*value))
+}
+
+#[inline(always)]
+fn wrapping_dot_lanes(a: &[i64], b: &[i64]) -> i64 {
+    let len = a.len().min(b.len());
+    let (a, b) = (&a[..len], &b[..len]);
+    let mut lanes = [0i64; LANES];
+    for (x, y) in a.chunks_exact(LANES).zip(b.chunks_exact(LANES)) {
+        for ((lane, x), y) in lanes.iter_mut().zip(x).zip(y) {
+            *lane = lane.wrapping_add(x.wrapping_mul(*y));
+        }
+    }
+    let done = len - len % LANES;
+    a[done..].iter().zip(&b[done..])
+        .fold(lanes.iter().fold(0i64, |sum, lane| sum.wrapping_add(*lane)), |sum, (x, y)| sum.wrapping_add(x.wrapping_mul(*y)))
+}
+
+#[inline(always)]
+fn wrapping_add_lanes(values: &mut [i64], k: i64) -> bool {
+    // Adding overflowed where the result's sign differs from both operands'
+    let mut overflow = 0i64;
+    for value in values.iter_mut() {
+        let result = value.wrapping_add(k);
+        overflow |= (*value ^ result) & (k ^ result);
+        *value = result;
+    }
+    overflow < 0
+}
+
+#[inline(always)]
+fn wrapping_sub_lanes(values: &mut [i64], k: i64) -> bool {
+    // Subtracting overflowed where the operands' signs differ and the
+    // result's differs from the first's
+    let mut overflow = 0i64;
+    for value in values.iter_mut() {
+        let result = value.wrapping_sub(k);
+        overflow |= (*value ^ k) & (*value ^ result);
+        *value = result;
+    }
+    overflow < 0
+}
+
+#[inline(always)]
+fn wrapping_mul_lanes(values: &mut [i64], k: i64) {
+    for value in values.iter_mut() {
+        *value = value.wrapping_mul(k);
+    }
+}
+
+#[inline(always)]
+fn float_add_lanes(values: &mut [f64], k: f64) {
+    for value in values.iter_mut() {
+        *value += k;
+    }
+}
+
+#[inline(always)]
+fn float_mul_lanes(values: &mut [f64], k: f64) {
+    for value in values.iter_mut() {
+        *value *= k;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::assemble;
+
+    #[test]
+    fn test_kernels_match_scalar_arithmetic() {
+        let values: Vec<i64> = (0..100).map(|i| i * 7 - 300).collect();
+        let others: Vec<i64> = (0..100).map(|i| 50 - i).collect();
+        assert_eq!(sum_i64(5, &values), Some(5 + values.iter().sum::<i64>()));
+        assert_eq!(dot_i64(-1, &values, &others), Some(-1 + values.iter().zip(&others).map(|(x, y)| x * y).sum::<i64>()));
+        assert_eq!(max_abs(&values), 393);
+
+        // Overflow is reported rather than wrapped
+        assert_eq!(sum_i64(i64::MAX - 10, &[5, 5]), Some(i64::MAX));
+        assert_eq!(sum_i64(i64::MAX - 10, &[5, 6]), None);
+        assert_eq!(sum_i64(i64::MAX, &[1, -1]), None);
+        assert_eq!(dot_i64(0, &[i64::MAX / 2], &[3]), None);
+
+        let mut mapped = values.clone();
+        assert!(map_i64(&mut mapped, 3, MapOp::Mul));
+        assert_eq!(mapped, values.iter().map(|x| x * 3).collect::<Vec<_>>());
+        let mut mapped = values.clone();
+        assert!(map_i64(&mut mapped, 10, MapOp::Sub));
+        assert_eq!(mapped, values.iter().map(|x| x - 10).collect::<Vec<_>>());
+        assert!(!map_i64(&mut [i64::MAX - 1, 0], 2, MapOp::Add));
+        assert!(!map_i64(&mut [i64::MIN + 1], 2, MapOp::Sub));
+        assert!(!map_i64(&mut [i64::MAX / 2 + 1], 2, MapOp::Mul));
+
+        let mut floats: Vec<f64> = (0..20).map(|i| i as f64 * 0.1).collect();
+        map_f64(&mut floats, 0.5, MapOp::Sub);
+        assert_eq!(floats[3], 0.30000000000000004 - 0.5);
+        assert!(["avx2", "sse2", "neon", "scalar"].contains(&vector_width()));
+    }
+
+    #[test]
+    fn test_loop_recognition() {
+        let program = assemble("\
+.const 0 int 0
+.const 1 int 1
+.const 2 int 3
+    const 0
+    store 0
+loop:
+    load 0
+    load 1
+    list_len
+    lt
+    jump_if_not done
+    load 2
+    load 1
+    load 0
+    list_get
+    load 3
+    load 0
+    list_get
+    mul
+    add
+    store 2
+    load 0
+    add_const 1
+    store 0
+    jump loop
+done:
+    load 1
+    store 4
+map:
+    load 0
+    load 1
+    list_len
+    lt
+    jump_if_not end
+    load 1
+    load 0
+    load 1
+    load 0
+    list_get
+    const 2
+    mul
+    list_set
+    store 1
+    load 0
+    const 1
+    add
+    store 0
+    jump map
+end:
+    nop
+").unwrap();
+        let loops = vector_loops(&program);
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loops[&2], VectorLoop { header: 2, exit: 21, index: 0, bound: 1, kind: VectorKind::Dot { acc: 2, a: 1, b: 3 } });
+        assert_eq!(loops[&23].kind, VectorKind::Map { dst: 1, src: 1, op: MapOp::Mul, operand: MapOperand::Const(2) });
+
+        // A jump into the body from outside leaves the loop alone
+        let mut jumped = program.clone();
+        jumped.instructions[0] = Bytecode::Jump(8, EffectGrade::Pure);
+        assert!(!vector_loops(&jumped).contains_key(&2));
+
+        // Running a loop updates its locals as the VM would
+        let mut context = ExecutionContext::new();
+        let numbers = |values: &[i64]| Value::List(values.iter().map(|value| Value::Int(*value)).collect());
+        context.set_local(0, Value::Int(1)).unwrap();
+        context.set_local(1, numbers(&[1, 2, 3])).unwrap();
+        context.set_local(2, Value::Int(10)).unwrap();
+        context.set_local(3, numbers(&[4, 5, 6, 7])).unwrap();
+        assert!(loops[&2].run(&mut context, &program.constants));
+        assert_eq!(context.get_local(2).unwrap(), &Value::Int(10 + 10 + 18));
+        assert_eq!(context.get_local(0).unwrap(), &Value::Int(3));
+
+        context.set_local(0, Value::Int(0)).unwrap();
+        assert!(loops[&23].run(&mut context, &program.constants));
+        assert_eq!(context.get_local(1).unwrap(), &numbers(&[3, 6, 9]));
+
+        // Anything but numbers is left to the VM
+        context.set_local(0, Value::Int(0)).unwrap();
+        context.set_local(1, Value::List(vec![Value::Int(1), Value::String("2".to_string())])).unwrap();
+        assert!(!loops[&2].run(&mut context, &program.constants));
+        assert_eq!(context.get_local(0).unwrap(), &Value::Int(0));
+    }
+}
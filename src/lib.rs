@@ -9,17 +9,25 @@
 #![warn(clippy::all)]
 #![allow(dead_code)] // Allow during development
 
+// Lets derives that expand to `::ream::...` paths be used inside this crate
+extern crate self as ream;
+
 pub mod runtime;
 pub mod bytecode;
 pub mod jit;
+pub mod wasm;
 pub mod tlisp;
 pub mod types;
 pub mod error;
 pub mod debug;
+pub mod logging;
+pub mod config;
+pub mod telemetry;
 pub mod security;
 pub mod p2p;
 pub mod sqlite;
 pub mod orm;
+pub mod fuzz;
 // Re-export procedural macros from ream-macros crate
 pub use ream_macros::*;
 /// Command-line interface and argument parsing
@@ -0,0 +1,232 @@
+//! Structured logging
+//!
+//! Diagnostics go through `tracing`. The subscriber filters events per
+//! target with `EnvFilter` directives (`info,ream::daemon=debug,tlisp=trace`),
+//! writes human-readable or JSON lines to stderr, and can be redirected to a
+//! rotated log file once the daemon knows where its log lives. Secret
+//! values are redacted from every line before it is written.
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
+use serde::{Serialize, Deserialize};
+use tracing::Level;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Layered, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::error::{ReamError, ReamResult};
+use crate::security::secrets;
+
+/// Environment variable read when no filter is given on the command line
+pub const LOG_ENV: &str = "REAM_LOG";
+
+/// Target of events logged from TLisp programs
+pub const SCRIPT_TARGET: &str = "tlisp";
+
+/// Output format of log lines
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LogFormat {
+    /// One human-readable line per event
+    #[default]
+    Human,
+    /// One JSON object per event
+    Json,
+}
+
+/// How often the daemon starts a new log file
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LogRotation {
+    /// Always write to the same file
+    Never,
+    /// Start a new file every hour
+    Hourly,
+    /// Start a new file every day
+    #[default]
+    Daily,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Never => Rotation::NEVER,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+        }
+    }
+}
+
+/// Logging configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// `EnvFilter` directives, e.g. `warn,ream::daemon=debug`
+    pub filter: String,
+    /// Output format
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            filter: "warn".to_string(),
+            format: LogFormat::Human,
+        }
+    }
+}
+
+type OutputLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Installed output layer and the format it was created with
+struct Output {
+    handle: reload::Handle<OutputLayer, Registry>,
+    format: LogFormat,
+}
+
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+type FilterHandle = reload::Handle<EnvFilter, Layered<reload::Layer<OutputLayer, Registry>, Registry>>;
+
+/// Installed filter, replaced when the configuration is reloaded
+static FILTER: OnceLock<FilterHandle> = OnceLock::new();
+
+/// Parse filter directives, rejecting malformed ones
+pub fn parse_filter(filter: &str) -> ReamResult<EnvFilter> {
+    EnvFilter::try_new(filter)
+        .map_err(|e| ReamError::Other(format!("Invalid log filter '{}': {}", filter, e)))
+}
+
+/// Install the global subscriber writing to stderr
+pub fn init(config: &LogConfig) -> ReamResult<()> {
+    let (filter, filter_handle) = reload::Layer::new(parse_filter(&config.filter)?);
+    let (output, handle) = reload::Layer::new(stderr_layer(config.format));
+
+    tracing_subscriber::registry()
+        .with(output)
+        .with(filter)
+        .try_init()
+        .map_err(|e| ReamError::Other(format!("Failed to install logger: {}", e)))?;
+
+    let _ = OUTPUT.set(Output { handle, format: config.format });
+    let _ = FILTER.set(filter_handle);
+    Ok(())
+}
+
+/// Replace the filter directives of the installed subscriber
+pub fn set_filter(filter: &str) -> ReamResult<()> {
+    let parsed = parse_filter(filter)?;
+    let handle = FILTER.get()
+        .ok_or_else(|| ReamError::Other("Logging has not been initialized".to_string()))?;
+    handle.reload(parsed)
+        .map_err(|e| ReamError::Other(format!("Failed to change log filter: {}", e)))
+}
+
+/// Send all further log lines to `path`, rotated as configured
+pub fn log_to_file(path: &Path, rotation: LogRotation) -> ReamResult<()> {
+    let output = OUTPUT.get()
+        .ok_or_else(|| ReamError::Other("Logging has not been initialized".to_string()))?;
+
+    let directory = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name()
+        .ok_or_else(|| ReamError::Other(format!("Invalid log file path: {}", path.display())))?;
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation.into())
+        .filename_prefix(file_name.to_string_lossy())
+        .build(directory)
+        .map_err(|e| ReamError::Other(format!("Failed to open log file {}: {}", path.display(), e)))?;
+
+    let layer = fmt::layer().with_ansi(false).with_writer(Redacting(appender));
+    let layer: OutputLayer = match output.format {
+        LogFormat::Human => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    };
+    output.handle.reload(layer)
+        .map_err(|e| ReamError::Other(format!("Failed to switch log output: {}", e)))
+}
+
+fn stderr_layer(format: LogFormat) -> OutputLayer {
+    let layer = fmt::layer().with_writer(Redacting(std::io::stderr));
+    match format {
+        LogFormat::Human => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+/// Writer factory whose writers redact secret values from what they write
+struct Redacting<M>(M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+/// Writer redacting secret values; the formatter writes each line in one
+/// call, so a value is never split between writes
+struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => self.0.write_all(secrets::redact(text).as_bytes())?,
+            Err(_) => self.0.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Render key/value pairs as `key=value` separated by spaces
+pub fn format_fields<K: AsRef<str>, V: std::fmt::Display>(fields: &[(K, V)]) -> String {
+    fields.iter()
+        .map(|(key, value)| format!("{}={}", key.as_ref(), value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Log an event raised by a TLisp program under the `tlisp` target
+pub fn script_event(level: Level, message: &str, fields: &str) {
+    match level {
+        Level::TRACE => tracing::trace!(target: SCRIPT_TARGET, fields, "{}", message),
+        Level::DEBUG => tracing::debug!(target: SCRIPT_TARGET, fields, "{}", message),
+        Level::INFO => tracing::info!(target: SCRIPT_TARGET, fields, "{}", message),
+        Level::WARN => tracing::warn!(target: SCRIPT_TARGET, fields, "{}", message),
+        Level::ERROR => tracing::error!(target: SCRIPT_TARGET, fields, "{}", message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_filter() {
+        assert!(parse_filter("info").is_ok());
+        assert!(parse_filter("warn,ream::daemon=debug,tlisp=trace").is_ok());
+        assert!(parse_filter("ream::daemon=loud").is_err());
+    }
+
+    #[test]
+    fn test_format_fields() {
+        assert_eq!(format_fields(&[("port", 8080), ("retries", 3)]), "port=8080 retries=3");
+        assert_eq!(format_fields::<&str, i32>(&[]), "");
+    }
+
+    #[test]
+    fn test_secrets_redacted_from_lines() {
+        secrets::register("pw-logging-5d1c");
+        let mut line = Vec::new();
+        RedactingWriter(&mut line).write_all(b"INFO connecting with password=pw-logging-5d1c\n").unwrap();
+        assert_eq!(String::from_utf8(line).unwrap(), "INFO connecting with password=[REDACTED]\n");
+    }
+}
@@ -1,11 +1,18 @@
 use ream::cli::Cli;
 use ream::commands::execute_command;
 use ream::repl::start_repl;
+use ream::logging;
+use ream::config;
+use ream::debug::profiler::ProfilingAllocator;
 use clap::Parser;
 use colored::*;
 use std::process;
 use std::path::PathBuf;
 
+/// Lets `ream profile heap` sample the allocations of the daemon's actors
+#[global_allocator]
+static ALLOCATOR: ProfilingAllocator = ProfilingAllocator;
+
 fn main() {
     // Parse command line arguments
     let cli = Cli::parse();
@@ -14,6 +21,22 @@ fn main() {
     if cli.no_color {
         colored::control::set_override(false);
     }
+
+    // Load ream.toml, environment and --set overrides
+    let settings = match cli.settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{} {}", "Error:".bright_red().bold(), e);
+            process::exit(1);
+        }
+    };
+
+    // Set up logging
+    if let Err(e) = logging::init(&settings.log) {
+        eprintln!("{} {}", "Error:".bright_red().bold(), e);
+        process::exit(1);
+    }
+    config::install(settings);
     
     // Handle the command
     let result = match cli.command {
@@ -48,6 +48,10 @@ impl Schema {
                     count += 1;
                     current = next;
                 }
+                SchemaF::Association { next, .. } => {
+                    count += 1;
+                    current = next;
+                }
                 SchemaF::Empty => break,
             }
         }
@@ -72,6 +76,9 @@ impl Schema {
                 SchemaF::ForeignKey { next, .. } => {
                     current = next;
                 }
+                SchemaF::Association { next, .. } => {
+                    current = next;
+                }
                 SchemaF::Empty => break,
             }
         }
@@ -119,6 +126,21 @@ impl Schema {
                     next: Self::ana(next, &mut f) 
                 }
             }
+            SchemaF::Association {
+                name, kind, owner_table, target_table, owner_key, target_key,
+                through, next
+            } => {
+                SchemaF::Association {
+                    name,
+                    kind,
+                    owner_table,
+                    target_table,
+                    owner_key,
+                    target_key,
+                    through,
+                    next: Self::ana(next, &mut f)
+                }
+            }
             SchemaF::Empty => SchemaF::Empty,
         };
         Schema(Box::new(mapped))
@@ -166,6 +188,7 @@ impl Algebra<SchemaF<usize>> for CountAlgebra {
             SchemaF::Table { next, .. } => 1 + next,
             SchemaF::Index { next, .. } => 1 + next,
             SchemaF::ForeignKey { next, .. } => 1 + next,
+            SchemaF::Association { next, .. } => 1 + next,
             SchemaF::Empty => 0,
         }
     }
@@ -185,6 +208,7 @@ impl Algebra<SchemaF<Vec<String>>> for TableNamesAlgebra {
             }
             SchemaF::Index { next, .. } => next,
             SchemaF::ForeignKey { next, .. } => next,
+            SchemaF::Association { next, .. } => next,
             SchemaF::Empty => Vec::new(),
         }
     }
@@ -269,6 +293,8 @@ impl Algebra<SchemaF<Vec<String>>> for SqlGenerationAlgebra {
                 next.push(sql);
                 next
             }
+            // Associations are loaded by queries and need no DDL
+            SchemaF::Association { next, .. } => next,
             SchemaF::Empty => Vec::new(),
         }
     }
@@ -0,0 +1,700 @@
+/// Change data capture - row changes as events and actor messages
+///
+/// `ChangeDataCapture` routes the row changes of the tables it captures to
+/// its event bus, which any task can subscribe to, and to named actors as
+/// `MessagePayload::Data` messages:
+///
+/// ```json
+/// {"type": "row-change", "table": "users", "kind": "update",
+///  "old": {"id": 1, "name": "Ann"}, "new": {"id": 1, "name": "Anne"}}
+/// ```
+///
+/// Changes come from the sqlite engine's change feed, whose `RowChange`s
+/// are published as they arrive, or from `CdcDriver`, which captures the
+/// writes made through an ORM driver.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::error::RuntimeResult;
+use crate::orm::{DatabaseType, Driver, DriverMetadata, Plugin, PluginMetadata, PluginTransformer, Row, Schema, SqlResult, Transaction};
+use crate::runtime::{MessageRouter, ReamRuntime};
+use crate::sqlite::types::Value;
+use crate::types::{MessagePayload, Pid};
+
+pub use crate::sqlite::types::{ChangeKind, RowChange};
+
+/// Changes a bus subscriber may fall behind by before it misses some
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+/// Where the changes of a table go
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CdcTarget {
+    /// The event bus of the capture; see `ChangeDataCapture::subscribe`
+    EventBus,
+    /// The actor registered under this name
+    Actor(String),
+}
+
+/// Which changes of a table are captured and where they go
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableCapture {
+    pub kinds: Vec<ChangeKind>,
+    pub targets: Vec<CdcTarget>,
+}
+
+impl TableCapture {
+    /// Inserts, updates and deletes, sent nowhere yet
+    pub fn new() -> Self {
+        Self {
+            kinds: vec![ChangeKind::Insert, ChangeKind::Update, ChangeKind::Delete],
+            targets: Vec::new(),
+        }
+    }
+
+    /// Capture only these kinds of change
+    pub fn only(mut self, kinds: &[ChangeKind]) -> Self {
+        self.kinds = kinds.to_vec();
+        self
+    }
+
+    pub fn to_event_bus(mut self) -> Self {
+        self.targets.push(CdcTarget::EventBus);
+        self
+    }
+
+    pub fn to_actor(mut self, name: impl Into<String>) -> Self {
+        self.targets.push(CdcTarget::Actor(name.into()));
+        self
+    }
+
+    pub fn captures(&self, kind: ChangeKind) -> bool {
+        !self.targets.is_empty() && self.kinds.contains(&kind)
+    }
+}
+
+impl Default for TableCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tables whose changes are captured
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CdcConfig {
+    pub tables: HashMap<String, TableCapture>,
+}
+
+impl CdcConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn table(mut self, name: impl Into<String>, capture: TableCapture) -> Self {
+        self.tables.insert(name.into(), capture);
+        self
+    }
+}
+
+/// Delivers messages to actors
+pub trait ActorSender: Send + Sync {
+    fn send(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()>;
+}
+
+impl ActorSender for MessageRouter {
+    fn send(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+        self.send_message(to, payload)
+    }
+}
+
+impl ActorSender for ReamRuntime {
+    fn send(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+        ReamRuntime::send(self, to, payload)
+    }
+}
+
+/// Counts of the changes routed by a capture
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CdcStats {
+    /// Changes of captured tables
+    pub captured: u64,
+    /// Messages sent to actors
+    pub sent: u64,
+    /// Messages not sent, because their actor was not registered or the
+    /// send failed
+    pub undelivered: u64,
+}
+
+/// Routes row changes to the event bus and to actors, per table
+#[derive(Clone)]
+pub struct ChangeDataCapture {
+    config: Arc<parking_lot::RwLock<CdcConfig>>,
+    actors: Arc<parking_lot::RwLock<HashMap<String, Pid>>>,
+    sender: Option<Arc<dyn ActorSender>>,
+    bus: broadcast::Sender<Arc<RowChange>>,
+    captured: Arc<AtomicU64>,
+    sent: Arc<AtomicU64>,
+    undelivered: Arc<AtomicU64>,
+}
+
+impl ChangeDataCapture {
+    /// A capture that only has an event bus
+    pub fn new(config: CdcConfig) -> Self {
+        Self {
+            config: Arc::new(parking_lot::RwLock::new(config)),
+            actors: Arc::new(parking_lot::RwLock::new(HashMap::new())),
+            sender: None,
+            bus: broadcast::channel(EVENT_BUS_CAPACITY).0,
+            captured: Arc::new(AtomicU64::new(0)),
+            sent: Arc::new(AtomicU64::new(0)),
+            undelivered: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Send actor targets' messages through a runtime or router
+    pub fn with_sender(mut self, sender: Arc<dyn ActorSender>) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Capture a table's changes, replacing how it was captured before
+    pub fn capture(&self, table: impl Into<String>, capture: TableCapture) {
+        self.config.write().tables.insert(table.into(), capture);
+    }
+
+    /// Stop capturing a table's changes
+    pub fn release(&self, table: &str) {
+        self.config.write().tables.remove(table);
+    }
+
+    pub fn config(&self) -> CdcConfig {
+        self.config.read().clone()
+    }
+
+    /// Whether a kind of change of a table is captured
+    pub fn captures(&self, table: &str, kind: ChangeKind) -> bool {
+        self.config.read().tables.get(table).is_some_and(|capture| capture.captures(kind))
+    }
+
+    /// Name an actor for `CdcTarget::Actor` targets
+    pub fn register_actor(&self, name: impl Into<String>, pid: Pid) {
+        self.actors.write().insert(name.into(), pid);
+    }
+
+    pub fn unregister_actor(&self, name: &str) {
+        self.actors.write().remove(name);
+    }
+
+    /// Receive the captured changes sent to the event bus from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<RowChange>> {
+        self.bus.subscribe()
+    }
+
+    pub fn stats(&self) -> CdcStats {
+        CdcStats {
+            captured: self.captured.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            undelivered: self.undelivered.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Route changes, in order, to the targets of their tables; changes
+    /// of tables that are not captured are skipped
+    pub fn publish(&self, changes: &[RowChange]) {
+        for change in changes {
+            let targets = match self.config.read().tables.get(&change.table) {
+                Some(capture) if capture.captures(change.kind) => capture.targets.clone(),
+                _ => continue,
+            };
+            self.captured.fetch_add(1, Ordering::Relaxed);
+
+            let change = Arc::new(change.clone());
+            for target in targets {
+                match target {
+                    // No subscribers is fine
+                    CdcTarget::EventBus => {
+                        let _ = self.bus.send(change.clone());
+                    }
+                    CdcTarget::Actor(name) => {
+                        let pid = self.actors.read().get(&name).copied();
+                        let sent = match (pid, &self.sender) {
+                            (Some(pid), Some(sender)) => sender.send(pid, change_message(&change)).is_ok(),
+                            _ => false,
+                        };
+                        let counter = if sent { &self.sent } else { &self.undelivered };
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The message an actor receives for a change
+pub fn change_message(change: &RowChange) -> MessagePayload {
+    let image = |values: &Option<Vec<Value>>| match values {
+        Some(values) => serde_json::Value::Object(
+            change
+                .columns
+                .iter()
+                .zip(values)
+                .map(|(column, value)| (column.clone(), json_value(value)))
+                .collect(),
+        ),
+        None => serde_json::Value::Null,
+    };
+    MessagePayload::Data(serde_json::json!({
+        "type": "row-change",
+        "table": change.table,
+        "kind": change.kind.to_string(),
+        "old": image(&change.old),
+        "new": image(&change.new),
+    }))
+}
+
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Integer(i) => serde_json::Value::from(*i),
+        Value::Real(f) => serde_json::Value::from(*f),
+        Value::Text(s) => serde_json::Value::from(s.as_str()),
+        Value::Boolean(b) => serde_json::Value::from(*b),
+        Value::Blob(bytes) => serde_json::Value::from(bytes.clone()),
+    }
+}
+
+/// CDC plugin - captures the writes made through a driver
+pub struct CdcPlugin {
+    metadata: PluginMetadata,
+    cdc: ChangeDataCapture,
+}
+
+impl CdcPlugin {
+    pub fn new(cdc: ChangeDataCapture) -> Self {
+        Self {
+            metadata: PluginMetadata::new(
+                "cdc",
+                "1.0.0",
+                "Publishes the rows changed by INSERT, UPDATE and DELETE",
+                "ream-orm",
+            ),
+            cdc,
+        }
+    }
+}
+
+impl Plugin for CdcPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        self.metadata.clone()
+    }
+}
+
+impl<D> PluginTransformer<D> for CdcPlugin
+where
+    D: Driver + 'static,
+    D::Row: Row,
+{
+    type Output = CdcDriver<D>;
+
+    fn transform(&self, base: D) -> Self::Output {
+        CdcDriver::new(base, self.cdc.clone())
+    }
+}
+
+/// Driver wrapper publishing the changes of the INSERT, UPDATE and DELETE
+/// statements it observes on captured tables
+///
+/// Old values are selected with the statement's WHERE clause before an
+/// UPDATE or DELETE, and new values are read back with RETURNING, which
+/// is added (and its rows kept from the caller) when the statement has
+/// none. Updated rows are paired by their `id` column, or by position
+/// without one. MySQL has no RETURNING, so only its deletes carry values.
+/// Statements run in a transaction are not captured.
+pub struct CdcDriver<D> {
+    base: D,
+    cdc: ChangeDataCapture,
+}
+
+impl<D> CdcDriver<D> {
+    pub fn new(base: D, cdc: ChangeDataCapture) -> Self {
+        Self { base, cdc }
+    }
+
+    pub fn capture(&self) -> &ChangeDataCapture {
+        &self.cdc
+    }
+}
+
+#[async_trait]
+impl<D> Driver for CdcDriver<D>
+where
+    D: Driver,
+    D::Row: Row,
+{
+    type Row = D::Row;
+
+    async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<Self::Row>> {
+        let Some((kind, table)) = dml_target(sql) else {
+            return self.base.observe(sql, binds).await;
+        };
+        if !self.cdc.captures(&table, kind) {
+            return self.base.observe(sql, binds).await;
+        }
+        let database = self.base.metadata().database_type;
+
+        let old = match kind {
+            ChangeKind::Insert => Vec::new(),
+            ChangeKind::Update | ChangeKind::Delete => {
+                let (select, select_binds) = old_rows_query(&database, &table, sql, binds);
+                // Without old values the change is still worth publishing
+                self.base.observe(&select, &select_binds).await.unwrap_or_default()
+            }
+        };
+
+        let returning = find_keyword(sql, "RETURNING").is_some();
+        let rows = if returning || kind == ChangeKind::Delete || database == DatabaseType::MySQL {
+            self.base.observe(sql, binds).await?
+        } else {
+            let sql = format!("{} RETURNING *", sql.trim_end().trim_end_matches(';'));
+            self.base.observe(&sql, binds).await?
+        };
+
+        let changes: Vec<RowChange> = match kind {
+            ChangeKind::Insert => rows
+                .iter()
+                .map(|row| RowChange::insert(&table, row.columns().to_vec(), row.values().to_vec()))
+                .collect(),
+            ChangeKind::Delete => old
+                .iter()
+                .map(|row| RowChange::delete(&table, row.columns().to_vec(), row.values().to_vec()))
+                .collect(),
+            ChangeKind::Update => {
+                let by_id = rows.iter().all(|row| row.get("id").is_some()) && old.iter().all(|row| row.get("id").is_some());
+                rows.iter()
+                    .enumerate()
+                    .filter_map(|(i, new)| {
+                        let old = match by_id {
+                            true => old.iter().find(|old| old.get("id") == new.get("id")),
+                            false => old.get(i),
+                        }?;
+                        let values = new
+                            .columns()
+                            .iter()
+                            .map(|column| old.get(column).cloned().unwrap_or(Value::Null))
+                            .collect();
+                        Some(RowChange::update(&table, new.columns().to_vec(), values, new.values().to_vec()))
+                    })
+                    .collect()
+            }
+        };
+        self.cdc.publish(&changes);
+
+        Ok(if returning { rows } else { Vec::new() })
+    }
+
+    async fn migrate(&self, schema: &Schema) -> SqlResult<()> {
+        self.base.migrate(schema).await
+    }
+
+    async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
+        self.base.begin_transaction().await
+    }
+
+    async fn health_check(&self) -> SqlResult<bool> {
+        self.base.health_check().await
+    }
+
+    fn metadata(&self) -> DriverMetadata {
+        let mut metadata = self.base.metadata();
+        metadata.name = format!("{} (with cdc)", metadata.name);
+        metadata
+    }
+}
+
+/// Kind and table of an INSERT, UPDATE or DELETE statement
+fn dml_target(sql: &str) -> Option<(ChangeKind, String)> {
+    let mut words = sql.split_whitespace();
+    let first = words.next()?.to_uppercase();
+    let (kind, table) = match first.as_str() {
+        "INSERT" if words.next()?.eq_ignore_ascii_case("INTO") => (ChangeKind::Insert, words.next()?),
+        "UPDATE" => (ChangeKind::Update, words.next()?),
+        "DELETE" if words.next()?.eq_ignore_ascii_case("FROM") => (ChangeKind::Delete, words.next()?),
+        _ => return None,
+    };
+    let table = table.split('(').next()?.trim_matches(|c| c == '"' || c == '`');
+    (!table.is_empty()).then(|| (kind, table.to_string()))
+}
+
+/// Byte offset of a keyword outside quotes and parentheses
+fn find_keyword(sql: &str, keyword: &str) -> Option<usize> {
+    let bytes = sql.as_bytes();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                b'\'' | b'"' | b'`' => quote = Some(c),
+                b'(' => depth += 1,
+                b')' => depth = depth.saturating_sub(1),
+                _ if depth == 0
+                    && (i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_'))
+                    && sql[i..].len() >= keyword.len()
+                    && sql[i..i + keyword.len()].eq_ignore_ascii_case(keyword)
+                    && bytes
+                        .get(i + keyword.len())
+                        .is_none_or(|c| !(c.is_ascii_alphanumeric() || *c == b'_')) =>
+                {
+                    return Some(i);
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Placeholders of a dialect in `sql`: how many `?`, or the `$n` numbers
+fn placeholders(database: &DatabaseType, sql: &str) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut quote = None;
+    let mut chars = sql.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '?' && *database != DatabaseType::PostgreSQL => found.push(found.len() + 1),
+            None if c == '$' && *database == DatabaseType::PostgreSQL => {
+                let digits: String = sql[i + 1..].chars().take_while(char::is_ascii_digit).collect();
+                if let Ok(n) = digits.parse() {
+                    found.push(n);
+                }
+                while chars.peek().is_some_and(|(_, c)| c.is_ascii_digit()) {
+                    chars.next();
+                }
+            }
+            None => {}
+        }
+    }
+    found
+}
+
+/// Select the rows an UPDATE or DELETE is about to change, using the
+/// binds its WHERE clause refers to
+fn old_rows_query(database: &DatabaseType, table: &str, sql: &str, binds: &[Value]) -> (String, Vec<Value>) {
+    let Some(start) = find_keyword(sql, "WHERE") else {
+        return (format!("SELECT * FROM {}", table), Vec::new());
+    };
+    let end = find_keyword(sql, "RETURNING").filter(|end| *end > start).unwrap_or(sql.len());
+    let condition = sql[start + "WHERE".len()..end].trim().trim_end_matches(';');
+
+    if *database == DatabaseType::PostgreSQL {
+        let numbers = placeholders(database, condition);
+        let offset = numbers.iter().min().map_or(0, |min| min - 1);
+        let last = numbers.iter().max().copied().unwrap_or(0).min(binds.len());
+        // Renumber from the highest so `$1` never matches inside `$12`
+        let mut renumbered = condition.to_string();
+        let mut sorted = numbers.clone();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        sorted.dedup();
+        for n in sorted {
+            renumbered = renumbered.replace(&format!("${}", n), &format!("${}", n - offset));
+        }
+        let binds = binds.get(offset..last).unwrap_or_default().to_vec();
+        (format!("SELECT * FROM {} WHERE {}", table, renumbered), binds)
+    } else {
+        let skip = placeholders(database, &sql[..start]).len();
+        let count = placeholders(database, condition).len();
+        let binds = binds.get(skip..skip + count).unwrap_or_default().to_vec();
+        (format!("SELECT * FROM {} WHERE {}", table, condition), binds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orm::{DatabaseRow, SqlError};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingSender {
+        messages: Mutex<Vec<(Pid, MessagePayload)>>,
+    }
+
+    impl ActorSender for RecordingSender {
+        fn send(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+            self.messages.lock().unwrap().push((to, payload));
+            Ok(())
+        }
+    }
+
+    /// Users table whose statements are recorded with their binds
+    struct TestDriver {
+        database_type: DatabaseType,
+        statements: Mutex<Vec<(String, Vec<Value>)>>,
+    }
+
+    fn user(id: i64, name: &str) -> DatabaseRow {
+        DatabaseRow::new(
+            vec!["id".to_string(), "name".to_string()],
+            vec![Value::Integer(id), Value::Text(name.to_string())],
+        )
+    }
+
+    #[async_trait]
+    impl Driver for TestDriver {
+        type Row = DatabaseRow;
+
+        async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<DatabaseRow>> {
+            self.statements.lock().unwrap().push((sql.to_string(), binds.to_vec()));
+            Ok(if sql.starts_with("SELECT") {
+                vec![user(1, "Ann"), user(2, "Bob")]
+            } else if sql.starts_with("UPDATE") && sql.contains("RETURNING") {
+                vec![user(2, "Bea"), user(1, "Anne")]
+            } else if sql.starts_with("INSERT") && sql.contains("RETURNING") {
+                vec![user(3, "Cy")]
+            } else {
+                Vec::new()
+            })
+        }
+
+        async fn migrate(&self, _schema: &Schema) -> SqlResult<()> {
+            Ok(())
+        }
+
+        async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
+            Err(SqlError::transaction_error("Transactions are not supported"))
+        }
+
+        async fn health_check(&self) -> SqlResult<bool> {
+            Ok(true)
+        }
+
+        fn metadata(&self) -> DriverMetadata {
+            DriverMetadata {
+                name: "test".to_string(),
+                version: "1".to_string(),
+                database_type: self.database_type.clone(),
+                supports_transactions: false,
+                supports_foreign_keys: false,
+                supports_json: false,
+            }
+        }
+    }
+
+    #[test]
+    fn test_routing() {
+        let sender = Arc::new(RecordingSender::default());
+        let cdc = ChangeDataCapture::new(
+            CdcConfig::new()
+                .table("users", TableCapture::new().to_event_bus().to_actor("audit"))
+                .table("posts", TableCapture::new().only(&[ChangeKind::Delete]).to_actor("missing")),
+        )
+        .with_sender(sender.clone());
+        let audit = Pid::new();
+        cdc.register_actor("audit", audit);
+        let mut bus = cdc.subscribe();
+
+        let columns = vec!["id".to_string(), "name".to_string()];
+        cdc.publish(&[
+            RowChange::update(
+                "users",
+                columns.clone(),
+                vec![Value::Integer(1), Value::Text("Ann".to_string())],
+                vec![Value::Integer(1), Value::Text("Anne".to_string())],
+            ),
+            RowChange::insert("posts", columns.clone(), vec![Value::Integer(5), Value::Null]),
+            RowChange::delete("posts", columns.clone(), vec![Value::Integer(5), Value::Null]),
+            RowChange::insert("tags", columns, vec![Value::Integer(9), Value::Null]),
+        ]);
+
+        assert_eq!(bus.try_recv().unwrap().table, "users");
+        assert!(bus.try_recv().is_err());
+        let messages = sender.messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, audit);
+        match &messages[0].1 {
+            MessagePayload::Data(data) => {
+                assert_eq!(data["kind"], "update");
+                assert_eq!(data["old"]["name"], "Ann");
+                assert_eq!(data["new"]["name"], "Anne");
+            }
+            other => panic!("expected a data message, got {:?}", other),
+        }
+        assert_eq!(cdc.stats(), CdcStats { captured: 2, sent: 1, undelivered: 1 });
+    }
+
+    #[test]
+    fn test_statement_analysis() {
+        assert_eq!(dml_target("insert into users(name) values (?)"), Some((ChangeKind::Insert, "users".to_string())));
+        assert_eq!(dml_target("DELETE FROM \"posts\" WHERE id = 1"), Some((ChangeKind::Delete, "posts".to_string())));
+        assert_eq!(dml_target("SELECT * FROM users"), None);
+        assert_eq!(find_keyword("UPDATE t SET note = 'where' WHERE id = 1", "WHERE"), Some(28));
+
+        let binds = [Value::Text("Bea".to_string()), Value::Integer(2), Value::Integer(9)];
+        assert_eq!(
+            old_rows_query(&DatabaseType::SQLite, "users", "UPDATE users SET name = ? WHERE id = ? OR id = ?", &binds),
+            ("SELECT * FROM users WHERE id = ? OR id = ?".to_string(), binds[1..].to_vec())
+        );
+        assert_eq!(
+            old_rows_query(&DatabaseType::PostgreSQL, "users", "UPDATE users SET name = $1 WHERE id = $2 RETURNING id", &binds[..2]),
+            ("SELECT * FROM users WHERE id = $1".to_string(), vec![Value::Integer(2)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_driver_capture() {
+        let cdc = ChangeDataCapture::new(CdcConfig::new().table("users", TableCapture::new().to_event_bus()));
+        let driver = CdcPlugin::new(cdc.clone()).transform(TestDriver {
+            database_type: DatabaseType::PostgreSQL,
+            statements: Mutex::new(Vec::new()),
+        });
+        let mut bus = cdc.subscribe();
+
+        // The caller did not ask for rows, so it gets none
+        let rows = driver
+            .observe("UPDATE users SET name = $1 WHERE id > $2", &[Value::Text("x".to_string()), Value::Integer(0)])
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+        let first = bus.try_recv().unwrap();
+        assert_eq!(first.old_value("name"), Some(&Value::Text("Bob".to_string())));
+        assert_eq!(first.new_value("name"), Some(&Value::Text("Bea".to_string())));
+        assert_eq!(bus.try_recv().unwrap().old_value("name"), Some(&Value::Text("Ann".to_string())));
+
+        let rows = driver.observe("INSERT INTO users (name) VALUES ($1) RETURNING id", &[Value::Text("Cy".to_string())]).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(bus.try_recv().unwrap().kind, ChangeKind::Insert);
+
+        driver.observe("DELETE FROM users", &[]).await.unwrap();
+        assert_eq!(bus.try_recv().unwrap().kind, ChangeKind::Delete);
+        assert_eq!(bus.try_recv().unwrap().kind, ChangeKind::Delete);
+
+        // Tables that are not captured pass straight through
+        driver.observe("DELETE FROM posts", &[]).await.unwrap();
+        assert!(bus.try_recv().is_err());
+
+        let TestDriver { statements, .. } = driver.base;
+        let statements: Vec<String> = statements.into_inner().unwrap().into_iter().map(|(sql, _)| sql).collect();
+        assert_eq!(
+            statements,
+            vec![
+                "SELECT * FROM users WHERE id > $1",
+                "UPDATE users SET name = $1 WHERE id > $2 RETURNING *",
+                "INSERT INTO users (name) VALUES ($1) RETURNING id",
+                "SELECT * FROM users",
+                "DELETE FROM users",
+                "DELETE FROM posts",
+            ]
+        );
+    }
+}
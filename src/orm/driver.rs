@@ -8,7 +8,12 @@
 
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tracing::{debug, info};
 use crate::sqlite::types::Value;
+use crate::orm::pool::{ConnectionManager, HealthCheck, Pool, PoolConfig, PooledConnection};
 use crate::orm::{Schema, SqlResult};
 use crate::sqlite::error::SqlError;
 
@@ -53,6 +58,9 @@ pub trait Transaction: Send + Sync {
     async fn rollback(self: Box<Self>) -> SqlResult<()>;
 }
 
+/// Future of a transaction body passed to `OrmContext::transaction`
+pub type TransactionFuture<'t, T> = Pin<Box<dyn Future<Output = SqlResult<T>> + Send + 't>>;
+
 /// Driver metadata
 #[derive(Debug, Clone)]
 pub struct DriverMetadata {
@@ -91,7 +99,7 @@ impl SqliteDriver {
         let driver = Self::new(connection_string);
         // In a real implementation, this would establish a connection pool
         // and verify connectivity to the SQLite database
-        println!("Connected to SQLite database: {}", driver.connection_string);
+        info!(connection = %driver.connection_string, "Connected to SQLite database");
         Ok(driver)
     }
 }
@@ -108,7 +116,7 @@ impl Driver for SqliteDriver {
         // 3. Execute query and collect results
         // 4. Convert results to SqliteRow format
 
-        println!("SQLite executing: {} with binds: {:?}", sql, binds);
+        debug!(sql, ?binds, "SQLite executing");
 
         // Simulate some results based on query type
         if sql.to_uppercase().starts_with("SELECT") {
@@ -136,26 +144,27 @@ impl Driver for SqliteDriver {
         let indexes = schema.indexes();
         let foreign_keys = schema.foreign_keys();
 
-        println!("Applying SQLite schema migration:");
-        println!("  - {} tables", tables.len());
-        println!("  - {} indexes", indexes.len());
-        println!("  - {} foreign keys", foreign_keys.len());
+        info!(
+            tables = tables.len(),
+            indexes = indexes.len(),
+            foreign_keys = foreign_keys.len(),
+            "Applying SQLite schema migration"
+        );
 
         // Simulate DDL execution
         for table in &tables {
-            println!("  CREATE TABLE {} ({} columns)", table.name, table.columns.len());
+            debug!(table = %table.name, columns = table.columns.len(), "CREATE TABLE");
         }
 
         for index in &indexes {
-            let unique_str = if index.unique { "UNIQUE " } else { "" };
-            println!("  CREATE {}INDEX {} ON {}", unique_str, index.name, index.table);
+            debug!(index = %index.name, table = %index.table, unique = index.unique, "CREATE INDEX");
         }
 
         for fk in &foreign_keys {
-            println!("  ALTER TABLE {} ADD FOREIGN KEY", fk.from_table);
+            debug!(table = %fk.from_table, "ALTER TABLE ADD FOREIGN KEY");
         }
 
-        println!("Schema migration completed successfully");
+        info!("SQLite schema migration completed");
         Ok(())
     }
     
@@ -165,7 +174,7 @@ impl Driver for SqliteDriver {
         // 1. Get connection from pool
         // 2. Execute BEGIN TRANSACTION
         // 3. Return transaction handle
-        println!("SQLite: Beginning transaction");
+        debug!("SQLite beginning transaction");
         Ok(Box::new(SqliteTransaction::new()))
     }
 
@@ -175,7 +184,7 @@ impl Driver for SqliteDriver {
         // 1. Try to get a connection from the pool
         // 2. Execute a simple query like SELECT 1
         // 3. Return success/failure status
-        println!("SQLite: Health check passed");
+        debug!("SQLite health check passed");
         Ok(true)
     }
     
@@ -261,7 +270,7 @@ impl Transaction for SqliteTransaction {
             return Err(SqlError::runtime_error("Transaction already committed"));
         }
 
-        println!("Transaction executing: {} with binds: {:?}", sql, binds);
+        debug!(sql, ?binds, "Transaction executing");
 
         // Simulate affected rows based on query type
         if sql.to_uppercase().starts_with("INSERT") {
@@ -287,7 +296,7 @@ impl Transaction for SqliteTransaction {
         }
 
         self.committed = true;
-        println!("SQLite transaction committed successfully");
+        debug!("SQLite transaction committed");
         Ok(())
     }
 
@@ -303,45 +312,86 @@ impl Transaction for SqliteTransaction {
         }
 
         self.committed = true; // Mark as completed
-        println!("SQLite transaction rolled back successfully");
+        debug!("SQLite transaction rolled back");
         Ok(())
     }
 }
 
 /// PostgreSQL driver implementation
+///
+/// Queries and transactions run on connections from a [`Pool`], so
+/// concurrent callers use separate connections.
 pub struct PostgresDriver {
     connection_string: String,
+    pool: Pool<PostgresConnectionManager>,
 }
 
 impl PostgresDriver {
     pub fn new(connection_string: impl Into<String>) -> Self {
+        Self::with_pool_config(connection_string, PoolConfig::default())
+    }
+
+    pub fn with_pool_config(connection_string: impl Into<String>, config: PoolConfig) -> Self {
+        let connection_string = connection_string.into();
+        let manager = PostgresConnectionManager {
+            connection_string: connection_string.clone(),
+        };
         Self {
-            connection_string: connection_string.into(),
+            connection_string,
+            pool: Pool::new(manager, config),
         }
     }
     
     pub async fn connect(connection_string: impl Into<String>) -> SqlResult<Self> {
         let driver = Self::new(connection_string);
-        // In a real implementation, this would establish a connection pool
-        // and verify connectivity to the PostgreSQL database
-        println!("Connected to PostgreSQL database: {}", driver.connection_string);
+        // Open the first connection to verify connectivity
+        drop(driver.pool.get().await?);
+        info!(connection = %driver.connection_string, "Connected to PostgreSQL database");
         Ok(driver)
     }
+
+    /// The pool the driver's connections come from
+    pub fn pool(&self) -> &Pool<PostgresConnectionManager> {
+        &self.pool
+    }
+}
+
+/// Opens PostgreSQL connections for a [`Pool`]
+pub struct PostgresConnectionManager {
+    connection_string: String,
 }
 
 #[async_trait]
-impl Driver for PostgresDriver {
-    type Row = PostgresRow;
-    
-    async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<Self::Row>> {
+impl ConnectionManager for PostgresConnectionManager {
+    type Connection = PostgresConnection;
+
+    async fn connect(&self) -> SqlResult<PostgresConnection> {
+        // In a real implementation, this would open a socket to the server,
+        // authenticate and wait for ReadyForQuery
+        Ok(PostgresConnection {
+            connection_string: self.connection_string.clone(),
+        })
+    }
+
+    async fn is_valid(&self, connection: &mut PostgresConnection) -> bool {
+        connection.query("SELECT 1", &[]).await.is_ok()
+    }
+}
+
+/// A single PostgreSQL connection
+pub struct PostgresConnection {
+    connection_string: String,
+}
+
+impl PostgresConnection {
+    pub async fn query(&mut self, sql: &str, binds: &[Value]) -> SqlResult<Vec<PostgresRow>> {
         // Simulate PostgreSQL query execution
         // In a real implementation, this would:
-        // 1. Get connection from pool
-        // 2. Prepare statement with bindings
-        // 3. Execute query and collect results
-        // 4. Convert results to PostgresRow format
+        // 1. Prepare statement with bindings
+        // 2. Execute query and collect results
+        // 3. Convert results to PostgresRow format
 
-        println!("PostgreSQL executing: {} with binds: {:?}", sql, binds);
+        debug!(sql, ?binds, "PostgreSQL executing");
 
         // Simulate some results based on query type
         if sql.to_uppercase().starts_with("SELECT") {
@@ -356,6 +406,15 @@ impl Driver for PostgresDriver {
             Ok(Vec::new())
         }
     }
+}
+
+#[async_trait]
+impl Driver for PostgresDriver {
+    type Row = PostgresRow;
+    
+    async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<Self::Row>> {
+        self.pool.get().await?.query(sql, binds).await
+    }
     
     async fn migrate(&self, schema: &Schema) -> SqlResult<()> {
         // Generate and execute PostgreSQL-specific DDL statements from schema
@@ -369,35 +428,38 @@ impl Driver for PostgresDriver {
         let indexes = schema.indexes();
         let foreign_keys = schema.foreign_keys();
 
-        println!("Applying PostgreSQL schema migration:");
-        println!("  - {} tables", tables.len());
-        println!("  - {} indexes", indexes.len());
-        println!("  - {} foreign keys", foreign_keys.len());
+        info!(
+            tables = tables.len(),
+            indexes = indexes.len(),
+            foreign_keys = foreign_keys.len(),
+            "Applying PostgreSQL schema migration"
+        );
 
         // Simulate DDL execution with PostgreSQL-specific features
         for table in &tables {
-            println!("  CREATE TABLE {} ({} columns) WITH PostgreSQL extensions", table.name, table.columns.len());
+            debug!(table = %table.name, columns = table.columns.len(), "CREATE TABLE");
         }
 
         for index in &indexes {
-            let unique_str = if index.unique { "UNIQUE " } else { "" };
-            println!("  CREATE {}INDEX {} ON {} USING btree", unique_str, index.name, index.table);
+            debug!(index = %index.name, table = %index.table, unique = index.unique, "CREATE INDEX USING btree");
         }
 
         for fk in &foreign_keys {
-            println!("  ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY", fk.from_table, fk.name);
+            debug!(table = %fk.from_table, constraint = %fk.name, "ALTER TABLE ADD CONSTRAINT FOREIGN KEY");
         }
 
-        println!("PostgreSQL schema migration completed successfully");
+        info!("PostgreSQL schema migration completed");
         Ok(())
     }
     
     async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
-        Ok(Box::new(PostgresTransaction::new()))
+        let mut connection = self.pool.get().await?;
+        connection.query("BEGIN", &[]).await?;
+        Ok(Box::new(PostgresTransaction::new(connection)))
     }
     
     async fn health_check(&self) -> SqlResult<bool> {
-        Ok(true)
+        Ok(self.pool.health_check().await.healthy)
     }
     
     fn metadata(&self) -> DriverMetadata {
@@ -455,35 +517,66 @@ impl crate::orm::types::Row for PostgresRow {
 }
 
 /// PostgreSQL transaction implementation
+///
+/// Holds its pooled connection until it is committed or rolled back.
 pub struct PostgresTransaction {
-    // In a real implementation, this would hold:
-    // - Connection handle
-    // - Transaction state
-    // - Savepoint information
-    committed: bool,
+    connection: PooledConnection<PostgresConnectionManager>,
 }
 
 impl PostgresTransaction {
-    pub fn new() -> Self {
-        Self { committed: false }
+    pub fn new(connection: PooledConnection<PostgresConnectionManager>) -> Self {
+        Self { connection }
+    }
+
+    async fn finish(mut self: Box<Self>, sql: &str) -> SqlResult<()> {
+        let result = self.connection.query(sql, &[]).await;
+        if result.is_err() {
+            // The connection may still be inside the transaction
+            self.connection.discard();
+        }
+        result.map(|_| ())
     }
 }
 
 #[async_trait]
 impl Transaction for PostgresTransaction {
     async fn execute(&mut self, sql: &str, binds: &[Value]) -> SqlResult<u64> {
-        println!("PostgreSQL transaction executing: {} with binds: {:?}", sql, binds);
-        Ok(0)
+        let rows = self.connection.query(sql, binds).await?;
+        Ok(rows.len() as u64)
     }
     
     async fn commit(self: Box<Self>) -> SqlResult<()> {
-        println!("PostgreSQL transaction committed");
-        Ok(())
+        self.finish("COMMIT").await
     }
     
     async fn rollback(self: Box<Self>) -> SqlResult<()> {
-        println!("PostgreSQL transaction rolled back");
-        Ok(())
+        self.finish("ROLLBACK").await
+    }
+}
+
+/// How `OrmContext::transaction` retries transactions that fail with a
+/// serialization failure
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (from 1)
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.base_delay.saturating_mul(1 << (retry - 1).min(16))
     }
 }
 
@@ -526,7 +619,10 @@ impl<D: Driver> ConnectionPool<D> {
 mod tests {
     use super::*;
     use crate::orm::schema::{Schema, Column};
+    use crate::orm::OrmContext;
     use crate::sqlite::types::DataType;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_sqlite_driver_creation() {
@@ -571,6 +667,111 @@ mod tests {
         assert!(health_results.iter().all(|&h| h));
     }
 
+    #[tokio::test]
+    async fn test_postgres_transactions_hold_pooled_connections() {
+        let config = PoolConfig {
+            max_size: 2,
+            acquire_timeout: Duration::from_millis(50),
+            ..PoolConfig::default()
+        };
+        let driver = PostgresDriver::with_pool_config("postgresql://localhost/test", config);
+
+        let first = driver.begin_transaction().await.unwrap();
+        let mut second = driver.begin_transaction().await.unwrap();
+        assert_eq!(driver.pool().status().in_use, 2);
+        assert!(driver.observe("SELECT 1", &[]).await.is_err());
+
+        second.execute("UPDATE users SET name = $1", &[Value::Text("Ann".to_string())]).await.unwrap();
+        second.commit().await.unwrap();
+        assert_eq!(driver.observe("SELECT 1", &[]).await.unwrap().len(), 1);
+        first.rollback().await.unwrap();
+        assert_eq!(driver.pool().status().idle, 2);
+        assert!(driver.health_check().await.unwrap());
+    }
+
+    /// Driver whose commits fail with a serialization failure `failures` times
+    struct ConflictingDriver {
+        failures: Arc<AtomicU32>,
+        attempts: Arc<AtomicU32>,
+    }
+
+    struct ConflictingTransaction {
+        failures: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl Transaction for ConflictingTransaction {
+        async fn execute(&mut self, _sql: &str, _binds: &[Value]) -> SqlResult<u64> {
+            Ok(1)
+        }
+
+        async fn commit(self: Box<Self>) -> SqlResult<()> {
+            match self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)) {
+                Ok(_) => Err(SqlError::serialization_failure("could not serialize access")),
+                Err(_) => Ok(()),
+            }
+        }
+
+        async fn rollback(self: Box<Self>) -> SqlResult<()> {
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Driver for ConflictingDriver {
+        type Row = SqliteRow;
+
+        async fn observe(&self, _sql: &str, _binds: &[Value]) -> SqlResult<Vec<SqliteRow>> {
+            Ok(Vec::new())
+        }
+
+        async fn migrate(&self, _schema: &Schema) -> SqlResult<()> {
+            Ok(())
+        }
+
+        async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(ConflictingTransaction { failures: self.failures.clone() }))
+        }
+
+        async fn health_check(&self) -> SqlResult<bool> {
+            Ok(true)
+        }
+
+        fn metadata(&self) -> DriverMetadata {
+            PostgresDriver::new("").metadata()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_retries_serialization_failures() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let driver = ConflictingDriver { failures: Arc::new(AtomicU32::new(2)), attempts: attempts.clone() };
+        let orm = OrmContext::new(driver).with_retry_policy(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+        });
+
+        let updated = orm
+            .transaction(|tx| Box::pin(async move { tx.execute("UPDATE accounts SET balance = 0", &[]).await }))
+            .await
+            .unwrap();
+        assert_eq!(updated, 1);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Out of retries, and other errors are not retried
+        orm.driver().failures.store(3, Ordering::SeqCst);
+        let error = orm.transaction(|_| Box::pin(async { Ok(()) })).await.unwrap_err();
+        assert!(error.is_serialization_failure());
+        attempts.store(0, Ordering::SeqCst);
+        let error = orm
+            .transaction(|_| Box::pin(async { Err::<(), _>(SqlError::runtime_error("boom")) }))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SqlError::RuntimeError { .. }));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
     #[tokio::test]
     async fn test_schema_migration() {
         let driver = SqliteDriver::new(":memory:");
@@ -0,0 +1,1205 @@
+/// GraphQL server over ORM models and actors
+///
+/// Unlike the `graphql!` macro, which compiles queries at build time, this
+/// serves a schema generated at runtime:
+/// - Each table of a `Schema` is an object type named in PascalCase
+///   (`blog_posts` becomes `BlogPosts`) with one field per column and one
+///   per association of the table
+/// - The `Query` root lists the rows of each table (`users(name: "Ann",
+///   order_by: "id", desc: true, limit: 10, offset: 0)`) and finds one by
+///   primary key (`users_by_pk(id: 1)`)
+/// - Associations are loaded like a dataloader: the keys every row of a
+///   level asks for are gathered into batched `IN` queries, one per
+///   association, instead of one query per row
+/// - Resolvers written in Rust or TLisp add fields to any type, declared
+///   with a GraphQL field definition such as `"greeting(name: String!):
+///   String"`
+/// - With a change data capture attached, the `Subscription` root streams
+///   the changes of each table from its event bus (`users_changes { kind
+///   row { id } }`)
+/// - With an actor directory attached, `actors` and `actor(pid:)` query
+///   the actors of a node
+///
+/// ```ignore
+/// let server = GraphQLServer::builder(schema, driver)
+///     .with_changes(cdc)
+///     .with_resolver("Query", "greeting(name: String!): String", |_: &JsonValue, args: JsonValue| {
+///         Ok(json!(format!("Hello, {}", args["name"].as_str().unwrap_or("you"))))
+///     })
+///     .build()?;
+/// let (addr, serve) = Arc::new(server).bind("127.0.0.1:4000".parse()?)?;
+/// serve.await;
+/// ```
+///
+/// Over HTTP, `POST /graphql` takes the usual `{"query", "variables",
+/// "operationName"}` body and answers with JSON, or with server-sent events
+/// (`next` for each result, then `complete`) when the request accepts
+/// `text/event-stream`, which is how subscriptions are served. `GET
+/// /graphql/schema` returns the schema in SDL.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputValue, Object, ObjectAccessor, ResolverContext, Schema as DynamicSchema,
+    Subscription, SubscriptionField, SubscriptionFieldFuture, TypeRef,
+};
+use async_graphql::{Request, Response, Value as GraphQLValue};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::{Map, Value as JsonValue};
+use tokio::sync::broadcast::error::RecvError;
+use warp::Filter;
+
+use super::cdc::ChangeDataCapture;
+use super::relations::load_related;
+use super::scripting::{ScriptDatabase, ScriptModel};
+use super::typed_query::placeholder;
+use super::{AssociationDefinition, AssociationKind, Column, DataType, DatabaseRow, Row, Schema, SqlError, SqlResult, Value};
+use crate::daemon::ipc::IpcClient;
+use crate::daemon::ActorInfo;
+use crate::sqlite::types::RowChange;
+use crate::tlisp::rust_crate_integration::{json_to_value, value_to_json};
+use crate::tlisp::{TlispInterpreter, Value as TlispValue};
+
+/// Global list of `(type signature function)` entries registered by the
+/// TLisp `defresolver` builtin
+pub const RESOLVER_REGISTRY: &str = "*resolvers*";
+
+/// Deepest selection a query may nest
+const MAX_DEPTH: usize = 16;
+
+/// Largest request body accepted over HTTP
+const MAX_BODY: u64 = 1024 * 1024;
+
+/// Names of the built-in scalars, whose values are returned as they are
+const SCALARS: [&str; 5] = [TypeRef::INT, TypeRef::FLOAT, TypeRef::STRING, TypeRef::BOOLEAN, TypeRef::ID];
+
+/// A field computed by user code instead of read from a row
+///
+/// `parent` is the object the field is selected on, as a JSON object, or
+/// null for fields of the root types; `args` has the field's arguments.
+/// Objects of table types may be returned as JSON objects or, from TLisp,
+/// as lists of `(column value)` pairs.
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    async fn resolve(&self, parent: &JsonValue, args: JsonValue) -> SqlResult<JsonValue>;
+}
+
+#[async_trait]
+impl<F> Resolver for F
+where
+    F: Fn(&JsonValue, JsonValue) -> SqlResult<JsonValue> + Send + Sync,
+{
+    async fn resolve(&self, parent: &JsonValue, args: JsonValue) -> SqlResult<JsonValue> {
+        self(parent, args)
+    }
+}
+
+/// A resolver calling a TLisp function of two arguments, the parent and
+/// the arguments, each a list of `(name value)` pairs
+///
+/// Calls take turns on the interpreter that defined the function, on a
+/// blocking thread so the `orm-*` builtins can be used.
+pub struct TlispResolver {
+    interpreter: Arc<Mutex<TlispInterpreter>>,
+    function: TlispValue,
+}
+
+impl TlispResolver {
+    pub fn new(interpreter: Arc<Mutex<TlispInterpreter>>, function: TlispValue) -> Self {
+        Self { interpreter, function }
+    }
+
+    /// The resolvers registered with `defresolver` in an interpreter, as
+    /// `(type, signature, resolver)`
+    pub fn registered(interpreter: &Arc<Mutex<TlispInterpreter>>) -> SqlResult<Vec<(String, String, TlispResolver)>> {
+        let entries = match interpreter.lock().unwrap().get(RESOLVER_REGISTRY) {
+            Some(TlispValue::List(entries)) => entries,
+            _ => return Ok(Vec::new()),
+        };
+        entries
+            .into_iter()
+            .map(|entry| match entry {
+                TlispValue::List(parts) => match parts.as_slice() {
+                    [TlispValue::String(type_name), TlispValue::String(signature), function] => Ok((
+                        type_name.clone(),
+                        signature.clone(),
+                        TlispResolver::new(interpreter.clone(), function.clone()),
+                    )),
+                    _ => Err(SqlError::schema_error(format!("Malformed {} entry", RESOLVER_REGISTRY))),
+                },
+                _ => Err(SqlError::schema_error(format!("Malformed {} entry", RESOLVER_REGISTRY))),
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Resolver for TlispResolver {
+    async fn resolve(&self, parent: &JsonValue, args: JsonValue) -> SqlResult<JsonValue> {
+        let failed = |e: crate::error::TlispError| SqlError::runtime_error(e.to_string());
+        let parent = json_to_value(parent).map_err(failed)?;
+        let args = json_to_value(&args).map_err(failed)?;
+        let interpreter = self.interpreter.clone();
+        let function = self.function.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = interpreter.lock().unwrap().call(&function, &[parent, args]).map_err(failed)?;
+            value_to_json(&result).map_err(failed)
+        })
+        .await
+        .map_err(|e| SqlError::runtime_error(format!("Resolver panicked: {}", e)))?
+    }
+}
+
+/// Where the `actors` queries find the actors of a node
+#[async_trait]
+pub trait ActorDirectory: Send + Sync {
+    async fn actors(&self) -> SqlResult<Vec<ActorInfo>>;
+}
+
+#[async_trait]
+impl ActorDirectory for IpcClient {
+    async fn actors(&self) -> SqlResult<Vec<ActorInfo>> {
+        self.list_actors(true)
+            .await
+            .map_err(|e| SqlError::connection_error(format!("Listing actors failed: {}", e)))
+    }
+}
+
+/// A row, or an object returned by a resolver, as its fields see it
+#[derive(Debug, Clone, Default)]
+struct Record(Map<String, JsonValue>);
+
+impl Record {
+    fn from_row(row: &DatabaseRow) -> Self {
+        Self::from_values(row.columns(), row.values())
+    }
+
+    fn from_values(columns: &[String], values: &[Value]) -> Self {
+        Record(columns.iter().cloned().zip(values.iter().map(to_json)).collect())
+    }
+
+    /// An object returned by a resolver: a JSON object, a list of
+    /// `[name, value]` pairs, or such a list headed by a model name
+    fn from_json(json: JsonValue) -> SqlResult<Self> {
+        let items = match json {
+            JsonValue::Object(fields) => return Ok(Record(fields)),
+            JsonValue::Array(items) => items,
+            other => return Err(SqlError::type_error(format!("Expected an object, got {}", other))),
+        };
+        let pairs = match items.split_first() {
+            Some((JsonValue::String(_), rest)) if rest.iter().all(JsonValue::is_array) => rest,
+            _ => &items[..],
+        };
+        pairs
+            .iter()
+            .map(|pair| match pair.as_array().map(Vec::as_slice) {
+                Some([JsonValue::String(name), value]) => Ok((name.clone(), value.clone())),
+                _ => Err(SqlError::type_error(format!("Expected a (name value) pair, got {}", pair))),
+            })
+            .collect::<SqlResult<_>>()
+            .map(Record)
+    }
+
+    fn field(&self, name: &str) -> async_graphql::Result<Option<FieldValue<'static>>> {
+        match self.0.get(name) {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(value) => Ok(Some(FieldValue::value(GraphQLValue::from_json(value.clone())?))),
+        }
+    }
+
+    /// A column's value, for binding
+    fn key(&self, column: &str) -> Value {
+        self.0.get(column).map_or(Value::Null, to_sql)
+    }
+}
+
+/// A change streamed to a subscription
+struct Change {
+    kind: String,
+    row: Option<Record>,
+    old: Option<Record>,
+}
+
+impl Change {
+    fn of(change: &RowChange) -> Self {
+        let record = |values: &Option<Vec<Value>>| values.as_ref().map(|values| Record::from_values(&change.columns, values));
+        Change {
+            kind: change.kind.to_string(),
+            row: record(&change.new),
+            old: record(&change.old),
+        }
+    }
+}
+
+fn to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Integer(i) => JsonValue::from(*i),
+        Value::Real(r) => serde_json::Number::from_f64(*r).map_or(JsonValue::Null, JsonValue::Number),
+        Value::Text(text) => JsonValue::String(text.clone()),
+        Value::Blob(bytes) => JsonValue::String(hex::encode(bytes)),
+        Value::Boolean(b) => JsonValue::Bool(*b),
+    }
+}
+
+fn to_sql(json: &JsonValue) -> Value {
+    match json {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(b) => Value::Boolean(*b),
+        JsonValue::Number(n) => n.as_i64().map_or_else(|| Value::Real(n.as_f64().unwrap_or(f64::NAN)), Value::Integer),
+        JsonValue::String(text) => Value::Text(text.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+/// GraphQL type of a table: `blog_posts` is `BlogPosts`
+pub fn type_name(table: &str) -> String {
+    table
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn scalar(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Integer => TypeRef::INT,
+        DataType::Real => TypeRef::FLOAT,
+        DataType::Boolean => TypeRef::BOOLEAN,
+        DataType::Text | DataType::Blob | DataType::Null => TypeRef::STRING,
+    }
+}
+
+fn column_type(column: &Column) -> TypeRef {
+    match !column.nullable || column.primary_key {
+        true => TypeRef::named_nn(scalar(&column.data_type)),
+        false => TypeRef::named(scalar(&column.data_type)),
+    }
+}
+
+/// A field reading the value of the same name from a `Record` parent
+fn record_field(name: &str, ty: TypeRef) -> Field {
+    let name = name.to_string();
+    Field::new(name.clone(), ty, move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move { ctx.parent_value.try_downcast_ref::<Record>()?.field(&name) })
+    })
+}
+
+/// A resolver's JSON result as the value of a field of type `ty`
+fn field_value(json: JsonValue, ty: &TypeRef) -> async_graphql::Result<Option<FieldValue<'static>>> {
+    if json.is_null() {
+        return Ok(None);
+    }
+    match ty {
+        TypeRef::NonNull(inner) => field_value(json, inner),
+        TypeRef::List(inner) => match json {
+            JsonValue::Array(items) => {
+                let values = items
+                    .into_iter()
+                    .map(|item| Ok(field_value(item, inner)?.unwrap_or(FieldValue::NULL)))
+                    .collect::<async_graphql::Result<Vec<_>>>()?;
+                Ok(Some(FieldValue::list(values)))
+            }
+            other => Err(format!("Expected a list, got {}", other).into()),
+        },
+        TypeRef::Named(name) if SCALARS.contains(&name.as_ref()) => Ok(Some(FieldValue::value(GraphQLValue::from_json(json)?))),
+        TypeRef::Named(_) => Ok(Some(FieldValue::owned_any(Record::from_json(json)?))),
+    }
+}
+
+/// A field definition such as `"greeting(name: String!): String"`
+struct Signature {
+    name: String,
+    arguments: Vec<(String, TypeRef)>,
+    returns: TypeRef,
+}
+
+fn parse_signature(signature: &str) -> SqlResult<Signature> {
+    use graphql_parser::schema::{Definition, Type, TypeDefinition};
+
+    fn type_ref(ty: &Type<'_, String>) -> TypeRef {
+        match ty {
+            Type::NamedType(name) => TypeRef::named(name.clone()),
+            Type::ListType(inner) => TypeRef::List(Box::new(type_ref(inner))),
+            Type::NonNullType(inner) => TypeRef::NonNull(Box::new(type_ref(inner))),
+        }
+    }
+
+    let source = format!("type Resolver {{ {} }}", signature);
+    let document = graphql_parser::parse_schema::<String>(&source)
+        .map_err(|e| SqlError::schema_error(format!("Bad resolver signature {:?}: {}", signature, e)))?;
+    match document.definitions.as_slice() {
+        [Definition::TypeDefinition(TypeDefinition::Object(object))] if object.fields.len() == 1 => {
+            let field = &object.fields[0];
+            let arguments = field
+                .arguments
+                .iter()
+                .map(|argument| (argument.name.clone(), type_ref(&argument.value_type)))
+                .collect();
+            Ok(Signature {
+                name: field.name.clone(),
+                arguments,
+                returns: type_ref(&field.field_type),
+            })
+        }
+        _ => Err(SqlError::schema_error(format!("Resolver signature {:?} must define one field", signature))),
+    }
+}
+
+/// The rows of one table
+struct TableSource {
+    database: Arc<dyn ScriptDatabase>,
+    table: String,
+    columns: Vec<Column>,
+}
+
+impl TableSource {
+    /// Rows whose columns equal the column arguments, ordered and paged by
+    /// `order_by`, `desc`, `limit` and `offset`
+    async fn select(&self, args: &ObjectAccessor<'_>) -> async_graphql::Result<Vec<Record>> {
+        let database = self.database.database_type();
+        let mut conditions = Vec::new();
+        let mut binds = Vec::new();
+        for column in &self.columns {
+            let Some(value) = args.get(&column.name).filter(|value| !value.is_null()) else {
+                continue;
+            };
+            binds.push(to_sql(&value.as_value().clone().into_json()?));
+            conditions.push(format!("{} = {}", column.name, placeholder(&database, binds.len())));
+        }
+
+        let names: Vec<&str> = self.columns.iter().map(|column| column.name.as_str()).collect();
+        let mut sql = format!("SELECT {} FROM {}", names.join(", "), self.table);
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        if let Some(order_by) = args.get("order_by") {
+            let order_by = order_by.string()?;
+            if !names.contains(&order_by) {
+                return Err(format!("{} has no column {}", self.table, order_by).into());
+            }
+            let descending = args.get("desc").map(|desc| desc.boolean()).transpose()?.unwrap_or(false);
+            sql.push_str(&format!(" ORDER BY {}{}", order_by, if descending { " DESC" } else { "" }));
+        }
+        let count = |name: &str| -> async_graphql::Result<Option<u64>> {
+            args.get(name).map(|value| value.u64()).transpose()
+        };
+        if let Some(limit) = count("limit")? {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        if let Some(offset) = count("offset")? {
+            sql.push_str(&format!(" OFFSET {}", offset));
+        }
+
+        let rows = self.database.query(&sql, &binds).await?;
+        Ok(rows.iter().map(Record::from_row).collect())
+    }
+}
+
+/// Key of a batched association load
+#[derive(Debug, Clone)]
+struct BatchKey(Value);
+
+impl PartialEq for BatchKey {
+    fn eq(&self, other: &Self) -> bool {
+        format!("{:?}", self.0) == format!("{:?}", other.0)
+    }
+}
+
+impl Eq for BatchKey {}
+
+impl Hash for BatchKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        format!("{:?}", self.0).hash(state);
+    }
+}
+
+/// Loads the rows of one association for every key asked for together
+struct AssociationLoader {
+    database: Arc<dyn ScriptDatabase>,
+    association: AssociationDefinition,
+    columns: Vec<String>,
+}
+
+impl Loader<BatchKey> for AssociationLoader {
+    type Value = Vec<Record>;
+    type Error = SqlError;
+
+    async fn load(&self, keys: &[BatchKey]) -> Result<HashMap<BatchKey, Vec<Record>>, SqlError> {
+        let values: Vec<Value> = keys.iter().map(|key| key.0.clone()).collect();
+        let related = load_related(self.database.as_ref(), &self.association, &self.columns, &values).await?;
+        Ok(keys
+            .iter()
+            .cloned()
+            .zip(related.iter().map(|rows| rows.iter().map(Record::from_row).collect()))
+            .collect())
+    }
+}
+
+/// The association loaders of one request, by `table.association`
+struct Loaders(HashMap<String, DataLoader<AssociationLoader>>);
+
+/// What a loader is made from
+struct AssociationSource {
+    key: String,
+    association: AssociationDefinition,
+    columns: Vec<String>,
+}
+
+/// Builds a [`GraphQLServer`]
+pub struct GraphQLServerBuilder {
+    models: Schema,
+    database: Arc<dyn ScriptDatabase>,
+    resolvers: Vec<(String, String, Arc<dyn Resolver>)>,
+    changes: Option<ChangeDataCapture>,
+    actors: Option<Arc<dyn ActorDirectory>>,
+}
+
+impl GraphQLServerBuilder {
+    /// Add the field `signature`, a GraphQL field definition, to the type
+    /// `type_name` (`Query`, `Mutation` or an object type), computed by
+    /// `resolver`
+    pub fn with_resolver(mut self, type_name: impl Into<String>, signature: impl Into<String>, resolver: impl Resolver + 'static) -> Self {
+        self.resolvers.push((type_name.into(), signature.into(), Arc::new(resolver)));
+        self
+    }
+
+    /// Stream the changes published on the event bus of `cdc` to
+    /// subscriptions
+    pub fn with_changes(mut self, cdc: ChangeDataCapture) -> Self {
+        self.changes = Some(cdc);
+        self
+    }
+
+    /// Answer the `actors` queries from `directory`
+    pub fn with_actors(mut self, directory: impl ActorDirectory + 'static) -> Self {
+        self.actors = Some(Arc::new(directory));
+        self
+    }
+
+    pub fn build(self) -> SqlResult<GraphQLServer> {
+        let tables = self.models.tables();
+        let associations = self.models.associations();
+        let mut objects: HashMap<String, Object> = HashMap::new();
+        let mut query = Object::new("Query");
+        let mut mutation = Object::new("Mutation");
+        let mut subscription = Subscription::new("Subscription");
+        let mut has_subscriptions = false;
+        let mut extra_types = Vec::new();
+
+        for table in &tables {
+            let name = type_name(&table.name);
+            let mut object = Object::new(name.clone());
+            for column in &table.columns {
+                object = object.field(record_field(&column.name, column_type(column)));
+            }
+            objects.insert(name.clone(), object);
+
+            let source = Arc::new(TableSource {
+                database: self.database.clone(),
+                table: table.name.clone(),
+                columns: table.columns.clone(),
+            });
+            let rows = source.clone();
+            let mut list = Field::new(table.name.clone(), TypeRef::named_nn_list_nn(name.clone()), move |ctx| {
+                let rows = rows.clone();
+                FieldFuture::new(async move {
+                    let records = rows.select(&ctx.args).await?;
+                    Ok(Some(FieldValue::list(records.into_iter().map(FieldValue::owned_any))))
+                })
+            });
+            for column in &table.columns {
+                list = list.argument(InputValue::new(column.name.clone(), TypeRef::named(scalar(&column.data_type))));
+            }
+            query = query.field(
+                list.argument(InputValue::new("order_by", TypeRef::named(TypeRef::STRING)))
+                    .argument(InputValue::new("desc", TypeRef::named(TypeRef::BOOLEAN)))
+                    .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT)))
+                    .argument(InputValue::new("offset", TypeRef::named(TypeRef::INT))),
+            );
+
+            let keys: Vec<&Column> = table.columns.iter().filter(|column| column.primary_key).collect();
+            if !keys.is_empty() {
+                let rows = source.clone();
+                let mut by_pk = Field::new(format!("{}_by_pk", table.name), TypeRef::named(name.clone()), move |ctx| {
+                    let rows = rows.clone();
+                    FieldFuture::new(async move {
+                        let records = rows.select(&ctx.args).await?;
+                        Ok(records.into_iter().next().map(FieldValue::owned_any))
+                    })
+                });
+                for key in keys {
+                    by_pk = by_pk.argument(InputValue::new(key.name.clone(), TypeRef::named_nn(scalar(&key.data_type))));
+                }
+                query = query.field(by_pk);
+            }
+
+            if let Some(cdc) = &self.changes {
+                let change = format!("{}Change", name);
+                extra_types.push(
+                    Object::new(change.clone())
+                        .field(Field::new("kind", TypeRef::named_nn(TypeRef::STRING), |ctx| {
+                            FieldFuture::new(async move {
+                                let change = ctx.parent_value.try_downcast_ref::<Change>()?;
+                                Ok(Some(FieldValue::value(change.kind.clone())))
+                            })
+                        }))
+                        .field(Field::new("row", TypeRef::named(name.clone()), |ctx| {
+                            FieldFuture::new(async move {
+                                let change = ctx.parent_value.try_downcast_ref::<Change>()?;
+                                Ok(change.row.clone().map(FieldValue::owned_any))
+                            })
+                        }))
+                        .field(Field::new("old", TypeRef::named(name.clone()), |ctx| {
+                            FieldFuture::new(async move {
+                                let change = ctx.parent_value.try_downcast_ref::<Change>()?;
+                                Ok(change.old.clone().map(FieldValue::owned_any))
+                            })
+                        })),
+                );
+                let cdc = cdc.clone();
+                let table_name = table.name.clone();
+                subscription = subscription.field(SubscriptionField::new(
+                    format!("{}_changes", table.name),
+                    TypeRef::named_nn(change),
+                    move |_| {
+                        let changes = cdc.subscribe();
+                        let table = table_name.clone();
+                        SubscriptionFieldFuture::new(async move {
+                            Ok(stream::unfold(changes, move |mut changes| {
+                                let table = table.clone();
+                                async move {
+                                    loop {
+                                        match changes.recv().await {
+                                            Ok(change) if change.table == table => {
+                                                let value = FieldValue::owned_any(Change::of(&change));
+                                                return Some((Ok::<_, async_graphql::Error>(value), changes));
+                                            }
+                                            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                                            Err(RecvError::Closed) => return None,
+                                        }
+                                    }
+                                }
+                            }))
+                        })
+                    },
+                ));
+                has_subscriptions = true;
+            }
+        }
+
+        let mut loaders = Vec::new();
+        for association in associations {
+            let owner = type_name(&association.owner_table);
+            let target = tables
+                .iter()
+                .find(|table| table.name == association.target_table)
+                .ok_or_else(|| SqlError::table_not_found(association.target_table.clone()))?;
+            let target_type = type_name(&target.name);
+            let key = format!("{}.{}", association.owner_table, association.name);
+            let owner_key = association.owner_key.clone();
+            let kind = association.kind;
+            let loader = key.clone();
+            let ty = match kind {
+                AssociationKind::BelongsTo => TypeRef::named(target_type),
+                AssociationKind::HasMany | AssociationKind::ManyToMany => TypeRef::named_nn_list_nn(target_type),
+            };
+            let field = Field::new(association.name.clone(), ty, move |ctx| {
+                let owner_key = owner_key.clone();
+                let loader = loader.clone();
+                FieldFuture::new(async move {
+                    let key = ctx.parent_value.try_downcast_ref::<Record>()?.key(&owner_key);
+                    let loaders = ctx.data::<Loaders>()?;
+                    let related = match &key {
+                        Value::Null => Vec::new(),
+                        _ => loaders.0[&loader].load_one(BatchKey(key)).await?.unwrap_or_default(),
+                    };
+                    Ok(match kind {
+                        AssociationKind::BelongsTo => related.into_iter().next().map(FieldValue::owned_any),
+                        _ => Some(FieldValue::list(related.into_iter().map(FieldValue::owned_any))),
+                    })
+                })
+            });
+            let object = objects
+                .remove(&owner)
+                .ok_or_else(|| SqlError::table_not_found(association.owner_table.clone()))?;
+            objects.insert(owner, object.field(field));
+            loaders.push(AssociationSource {
+                key,
+                columns: target.columns.iter().map(|column| column.name.clone()).collect(),
+                association,
+            });
+        }
+
+        if let Some(directory) = &self.actors {
+            let mut actor = Object::new("Actor");
+            for (name, ty) in [
+                ("pid", TypeRef::named_nn(TypeRef::STRING)),
+                ("status", TypeRef::named_nn(TypeRef::STRING)),
+                ("actor_type", TypeRef::named_nn(TypeRef::STRING)),
+                ("state", TypeRef::named_nn(TypeRef::STRING)),
+                ("mailbox_size", TypeRef::named_nn(TypeRef::INT)),
+                ("memory_usage", TypeRef::named_nn(TypeRef::INT)),
+                ("messages_processed", TypeRef::named_nn(TypeRef::INT)),
+                ("message_rate", TypeRef::named_nn(TypeRef::FLOAT)),
+                ("cpu_time", TypeRef::named_nn(TypeRef::INT)),
+                ("uptime_ms", TypeRef::named_nn(TypeRef::INT)),
+                ("links", TypeRef::named_nn_list_nn(TypeRef::STRING)),
+                ("monitors", TypeRef::named_nn_list_nn(TypeRef::STRING)),
+                ("supervisor", TypeRef::named(TypeRef::STRING)),
+            ] {
+                actor = actor.field(record_field(name, ty));
+            }
+            extra_types.push(actor);
+
+            let all = directory.clone();
+            query = query.field(Field::new("actors", TypeRef::named_nn_list_nn("Actor"), move |_| {
+                let directory = all.clone();
+                FieldFuture::new(async move {
+                    let actors = directory.actors().await?;
+                    Ok(Some(FieldValue::list(actors.iter().map(|actor| FieldValue::owned_any(actor_record(actor))))))
+                })
+            }));
+            let one = directory.clone();
+            query = query.field(
+                Field::new("actor", TypeRef::named("Actor"), move |ctx| {
+                    let directory = one.clone();
+                    FieldFuture::new(async move {
+                        let pid = ctx.args.try_get("pid")?.string()?.to_string();
+                        let actors = directory.actors().await?;
+                        Ok(actors
+                            .iter()
+                            .find(|actor| actor.pid.to_string() == pid)
+                            .map(|actor| FieldValue::owned_any(actor_record(actor))))
+                    })
+                })
+                .argument(InputValue::new("pid", TypeRef::named_nn(TypeRef::STRING))),
+            );
+        }
+
+        let mut has_mutations = false;
+        for (type_name, signature, resolver) in self.resolvers {
+            let Signature { name, arguments, returns: ty } = parse_signature(&signature)?;
+            let returns = ty.clone();
+            let mut field = Field::new(name, ty, move |ctx: ResolverContext<'_>| {
+                let resolver = resolver.clone();
+                let returns = returns.clone();
+                FieldFuture::new(async move {
+                    let parent = match ctx.parent_value.downcast_ref::<Record>() {
+                        Some(record) => JsonValue::Object(record.0.clone()),
+                        None => JsonValue::Null,
+                    };
+                    let args = ctx
+                        .args
+                        .iter()
+                        .map(|(name, value)| Ok((name.to_string(), value.as_value().clone().into_json()?)))
+                        .collect::<async_graphql::Result<Map<_, _>>>()?;
+                    let result = resolver.resolve(&parent, JsonValue::Object(args)).await?;
+                    field_value(result, &returns)
+                })
+            });
+            for (argument, argument_type) in arguments {
+                field = field.argument(InputValue::new(argument, argument_type));
+            }
+            match type_name.as_str() {
+                "Query" => query = query.field(field),
+                "Mutation" => {
+                    mutation = mutation.field(field);
+                    has_mutations = true;
+                }
+                other => {
+                    let object = objects.remove(other).ok_or_else(|| {
+                        SqlError::schema_error(format!("Cannot add a resolver to {}, which is not a table type", other))
+                    })?;
+                    objects.insert(other.to_string(), object.field(field));
+                }
+            }
+        }
+
+        let mut builder = DynamicSchema::build(
+            "Query",
+            has_mutations.then_some("Mutation"),
+            has_subscriptions.then_some("Subscription"),
+        )
+        .limit_depth(MAX_DEPTH)
+        .register(query);
+        if has_mutations {
+            builder = builder.register(mutation);
+        }
+        if has_subscriptions {
+            builder = builder.register(subscription);
+        }
+        for object in objects.into_values().chain(extra_types) {
+            builder = builder.register(object);
+        }
+        let schema = builder
+            .finish()
+            .map_err(|e| SqlError::schema_error(format!("Invalid GraphQL schema: {}", e)))?;
+
+        Ok(GraphQLServer {
+            schema,
+            database: self.database,
+            loaders: Arc::new(loaders),
+        })
+    }
+}
+
+fn actor_record(actor: &ActorInfo) -> Record {
+    let pids = |pids: &[crate::types::Pid]| JsonValue::from(pids.iter().map(|pid| pid.to_string()).collect::<Vec<_>>());
+    let mut fields = Map::new();
+    fields.insert("pid".to_string(), JsonValue::from(actor.pid.to_string()));
+    fields.insert("status".to_string(), JsonValue::from(format!("{:?}", actor.status)));
+    fields.insert("actor_type".to_string(), JsonValue::from(actor.actor_type.clone()));
+    fields.insert("state".to_string(), JsonValue::from(actor.state_description.clone()));
+    fields.insert("mailbox_size".to_string(), JsonValue::from(actor.mailbox_size));
+    fields.insert("memory_usage".to_string(), JsonValue::from(actor.memory_usage));
+    fields.insert("messages_processed".to_string(), JsonValue::from(actor.messages_processed));
+    fields.insert("message_rate".to_string(), JsonValue::from(actor.message_rate));
+    fields.insert("cpu_time".to_string(), JsonValue::from(actor.cpu_time));
+    fields.insert("uptime_ms".to_string(), JsonValue::from(actor.uptime.as_millis() as u64));
+    fields.insert("links".to_string(), pids(&actor.links));
+    fields.insert("monitors".to_string(), pids(&actor.monitors));
+    fields.insert(
+        "supervisor".to_string(),
+        actor.supervisor.map_or(JsonValue::Null, |pid| JsonValue::from(pid.to_string())),
+    );
+    Record(fields)
+}
+
+/// The schema of models declared by scripts with `define-model`
+///
+/// A column named after another model with an `_id` suffix, such as
+/// `user_id` in `post`, references that model: `post` gets a `user` field
+/// and `user` gets a `post` list, unless a column already has the name.
+pub fn script_schema(models: &[ScriptModel]) -> Schema {
+    let mut schema = models
+        .iter()
+        .fold(Schema::empty(), |schema, model| schema.add_table(model.name.clone(), model.columns.clone()));
+    let taken = |model: &ScriptModel, name: &str| model.columns.iter().any(|column| column.name == name);
+    for owner in models {
+        for column in &owner.columns {
+            let Some(target) = column
+                .name
+                .strip_suffix("_id")
+                .and_then(|name| models.iter().find(|model| model.name == name))
+            else {
+                continue;
+            };
+            if !taken(owner, &target.name) {
+                schema = schema.belongs_to(target.name.clone(), owner.name.clone(), target.name.clone(), column.name.clone());
+            }
+            if !taken(target, &owner.name) {
+                schema = schema.has_many(owner.name.clone(), target.name.clone(), owner.name.clone(), column.name.clone());
+            }
+        }
+    }
+    schema
+}
+
+/// A GraphQL schema over ORM models, with its resolvers
+pub struct GraphQLServer {
+    schema: DynamicSchema,
+    database: Arc<dyn ScriptDatabase>,
+    loaders: Arc<Vec<AssociationSource>>,
+}
+
+impl GraphQLServer {
+    /// A server for the tables and associations of `models`, read through
+    /// `database`
+    pub fn builder(models: Schema, database: impl ScriptDatabase + 'static) -> GraphQLServerBuilder {
+        Self::builder_shared(models, Arc::new(database))
+    }
+
+    /// Like `builder`, for a database shared with other users such as a
+    /// `ScriptOrm`
+    pub fn builder_shared(models: Schema, database: Arc<dyn ScriptDatabase>) -> GraphQLServerBuilder {
+        GraphQLServerBuilder {
+            models,
+            database,
+            resolvers: Vec::new(),
+            changes: None,
+            actors: None,
+        }
+    }
+
+    /// The schema in SDL
+    pub fn sdl(&self) -> String {
+        self.schema.sdl()
+    }
+
+    /// Run a query or mutation
+    pub async fn execute(&self, request: impl Into<Request>) -> Response {
+        self.schema.execute(self.prepare(request.into())).await
+    }
+
+    /// Run a subscription, one response per change, or any other
+    /// operation as a single response
+    pub fn subscribe(&self, request: impl Into<Request>) -> BoxStream<'static, Response> {
+        self.schema.execute_stream(self.prepare(request.into()))
+    }
+
+    /// A request with association loaders of its own, so batches and
+    /// caches are never shared between requests
+    fn prepare(&self, request: Request) -> Request {
+        let loaders = self
+            .loaders
+            .iter()
+            .map(|source| {
+                let loader = AssociationLoader {
+                    database: self.database.clone(),
+                    association: source.association.clone(),
+                    columns: source.columns.clone(),
+                };
+                (source.key.clone(), DataLoader::new(loader, tokio::spawn))
+            })
+            .collect();
+        request.data(Loaders(loaders))
+    }
+
+    /// Serve the GraphQL API on `addr`, returning the bound address and
+    /// the server future
+    pub fn bind(self: Arc<Self>, addr: SocketAddr) -> SqlResult<(SocketAddr, impl Future<Output = ()>)> {
+        let with_server = move || {
+            let server = self.clone();
+            warp::any().map(move || server.clone())
+        };
+
+        let execute = warp::path!("graphql")
+            .and(warp::post())
+            .and(warp::header::optional::<String>("accept"))
+            .and(warp::body::content_length_limit(MAX_BODY))
+            .and(warp::body::json())
+            .and(with_server())
+            .then(|accept: Option<String>, request: Request, server: Arc<GraphQLServer>| async move {
+                if accept.is_some_and(|accept| accept.contains("text/event-stream")) {
+                    let events = server
+                        .subscribe(request)
+                        .map(|response| warp::sse::Event::default().event("next").json_data(response))
+                        .chain(stream::once(async { Ok(warp::sse::Event::default().event("complete").data("")) }))
+                        .map(|event| Ok::<_, Infallible>(event.unwrap_or_else(|e| warp::sse::Event::default().event("error").data(e.to_string()))));
+                    warp::reply::Reply::into_response(warp::sse::reply(warp::sse::keep_alive().stream(events)))
+                } else {
+                    warp::reply::Reply::into_response(warp::reply::json(&server.execute(request).await))
+                }
+            });
+
+        let sdl = warp::path!("graphql" / "schema")
+            .and(warp::get())
+            .and(with_server())
+            .map(|server: Arc<GraphQLServer>| server.sdl());
+
+        warp::serve(execute.or(sdl))
+            .try_bind_ephemeral(addr)
+            .map_err(|e| SqlError::connection_error(format!("Failed to bind GraphQL server {}: {}", addr, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::ActorStatus;
+    use crate::orm::cdc::{CdcConfig, CdcDriver, TableCapture};
+    use crate::orm::driver::{DatabaseType, Driver, DriverMetadata, Transaction};
+    use crate::types::Pid;
+    use serde_json::json;
+    use std::time::{Duration, SystemTime};
+
+    /// In-memory users and posts answering the queries of this module
+    struct TestDriver {
+        statements: Arc<Mutex<Vec<String>>>,
+    }
+
+    fn row(columns: &[&str], values: Vec<Value>) -> DatabaseRow {
+        DatabaseRow::new(columns.iter().map(|column| column.to_string()).collect(), values)
+    }
+
+    #[async_trait]
+    impl Driver for TestDriver {
+        type Row = DatabaseRow;
+
+        async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<DatabaseRow>> {
+            self.statements.lock().unwrap().push(sql.to_string());
+            let users = [(1, "Ann"), (2, "Bob"), (3, "Cy")];
+            let posts = [(10, 1, "Hello"), (11, 1, "Again"), (12, 2, "Hi")];
+            let bound = |key: i64| binds.contains(&Value::Integer(key));
+            let user = |(id, name): (i64, &str)| vec![Value::Integer(id), Value::Text(name.to_string())];
+
+            Ok(if sql == "SELECT id, name FROM users ORDER BY id DESC LIMIT 2" {
+                users.into_iter().rev().take(2).map(|u| row(&["id", "name"], user(u))).collect()
+            } else if sql == "SELECT id, name FROM users WHERE id = ?" {
+                users.into_iter().filter(|u| bound(u.0)).map(|u| row(&["id", "name"], user(u))).collect()
+            } else if sql.starts_with("SELECT id, user_id, title, user_id AS __owner_key FROM posts WHERE user_id IN") {
+                posts
+                    .into_iter()
+                    .filter(|p| bound(p.1))
+                    .map(|(id, user_id, title)| {
+                        row(
+                            &["id", "user_id", "title", "__owner_key"],
+                            vec![Value::Integer(id), Value::Integer(user_id), Value::Text(title.to_string()), Value::Integer(user_id)],
+                        )
+                    })
+                    .collect()
+            } else if sql.starts_with("SELECT id, name, id AS __owner_key FROM users WHERE id IN") {
+                users
+                    .into_iter()
+                    .filter(|u| bound(u.0))
+                    .map(|u| {
+                        let mut values = user(u);
+                        values.push(Value::Integer(u.0));
+                        row(&["id", "name", "__owner_key"], values)
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            })
+        }
+
+        async fn migrate(&self, _schema: &Schema) -> SqlResult<()> {
+            Ok(())
+        }
+
+        async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
+            Err(SqlError::transaction_error("not supported"))
+        }
+
+        async fn health_check(&self) -> SqlResult<bool> {
+            Ok(true)
+        }
+
+        fn metadata(&self) -> DriverMetadata {
+            DriverMetadata {
+                name: "test".to_string(),
+                version: "1".to_string(),
+                database_type: DatabaseType::SQLite,
+                supports_transactions: false,
+                supports_foreign_keys: true,
+                supports_json: false,
+            }
+        }
+    }
+
+    fn models() -> Schema {
+        Schema::empty()
+            .add_table(
+                "users",
+                vec![Column::new("id", DataType::Integer).primary_key(), Column::new("name", DataType::Text).not_null()],
+            )
+            .add_table(
+                "posts",
+                vec![
+                    Column::new("id", DataType::Integer).primary_key(),
+                    Column::new("user_id", DataType::Integer),
+                    Column::new("title", DataType::Text),
+                ],
+            )
+            .has_many("posts", "users", "posts", "user_id")
+            .belongs_to("author", "posts", "users", "user_id")
+    }
+
+    struct Directory;
+
+    #[async_trait]
+    impl ActorDirectory for Directory {
+        async fn actors(&self) -> SqlResult<Vec<ActorInfo>> {
+            Ok(vec![ActorInfo {
+                pid: Pid::new(),
+                status: ActorStatus::Running,
+                mailbox_size: 3,
+                memory_usage: 1024,
+                messages_processed: 7,
+                message_rate: 0.5,
+                cpu_time: 10,
+                uptime: Duration::from_secs(2),
+                last_activity: SystemTime::now(),
+                actor_type: "counter".to_string(),
+                state_description: "counting".to_string(),
+                links: Vec::new(),
+                monitors: Vec::new(),
+                supervisor: None,
+                recent_messages: Vec::new(),
+                restarts: 0,
+                domain: None,
+                ingress: None,
+            }])
+        }
+    }
+
+    #[test]
+    fn test_script_schema_follows_id_columns() {
+        let field = |name: &str, ty: &str| TlispValue::List(vec![TlispValue::Symbol(name.into()), TlispValue::Symbol(ty.into())]);
+        let user = ScriptModel::from_spec("user", &[field("id", "int"), field("name", "string")]).unwrap();
+        let post = ScriptModel::from_spec("post", &[field("id", "int"), field("user_id", "int"), field("title", "string")]).unwrap();
+        let associations = script_schema(&[user, post]).associations();
+        let names: Vec<(&str, &str, AssociationKind)> = associations
+            .iter()
+            .map(|a| (a.owner_table.as_str(), a.name.as_str(), a.kind))
+            .collect();
+        assert_eq!(names, vec![("user", "post", AssociationKind::HasMany), ("post", "user", AssociationKind::BelongsTo)]);
+    }
+
+    #[test]
+    fn test_type_names_and_signatures() {
+        assert_eq!(type_name("blog_posts"), "BlogPosts");
+        assert_eq!(type_name("users"), "Users");
+        let signature = parse_signature("greeting(name: String!, times: [Int]): [String!]").unwrap();
+        assert_eq!((signature.name.as_str(), signature.returns.to_string()), ("greeting", "[String!]".to_string()));
+        assert_eq!(signature.arguments[1].1.to_string(), "[Int]");
+        assert!(parse_signature("not a field").is_err());
+
+        let record = Record::from_json(json!(["user", ["id", 1], ["name", "Ann"]])).unwrap();
+        assert_eq!(record.key("id"), Value::Integer(1));
+        assert!(Record::from_json(json!([1, 2])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queries_batch_associations_and_call_resolvers() {
+        let statements = Arc::new(Mutex::new(Vec::new()));
+        let server = GraphQLServer::builder(models(), TestDriver { statements: statements.clone() })
+            .with_resolver("Query", "greeting(name: String!): String", |_: &JsonValue, args: JsonValue| {
+                Ok(json!(format!("Hello, {}", args["name"].as_str().unwrap_or_default())))
+            })
+            .with_resolver("Users", "shout: String!", |parent: &JsonValue, _: JsonValue| {
+                Ok(json!(parent["name"].as_str().unwrap_or_default().to_uppercase()))
+            })
+            .with_actors(Directory)
+            .build()
+            .unwrap();
+        let sdl = server.sdl();
+        assert!(sdl.contains("posts: [Posts!]!"), "{}", sdl);
+        assert!(sdl.contains("users_by_pk(id: Int!): Users"), "{}", sdl);
+        assert!(!sdl.contains("Subscription"), "{}", sdl);
+
+        let response = server
+            .execute("{ users(order_by: \"id\", desc: true, limit: 2) { id shout posts { title author { name } } } greeting(name: \"Ann\") }")
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({
+                "users": [
+                    {"id": 3, "shout": "CY", "posts": []},
+                    {"id": 2, "shout": "BOB", "posts": [{"title": "Hi", "author": {"name": "Bob"}}]},
+                ],
+                "greeting": "Hello, Ann",
+            })
+        );
+        // One query per level, however many rows ask
+        let statements = statements.lock().unwrap().clone();
+        assert_eq!(statements.len(), 3, "{:?}", statements);
+
+        let response = server.execute("{ users_by_pk(id: 1) { name posts { id } } actors { actor_type uptime_ms } }").await;
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({
+                "users_by_pk": {"name": "Ann", "posts": [{"id": 10}, {"id": 11}]},
+                "actors": [{"actor_type": "counter", "uptime_ms": 2000}],
+            })
+        );
+
+        let response = server.execute("{ users(order_by: \"nope\") { id } }").await;
+        assert!(response.errors[0].message.contains("no column nope"));
+        assert!(GraphQLServer::builder(models(), TestDriver { statements: Arc::default() })
+            .with_resolver("Nowhere", "x: Int", |_: &JsonValue, _: JsonValue| Ok(JsonValue::Null))
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tlisp_resolvers() {
+        let interpreter = Arc::new(Mutex::new(TlispInterpreter::new()));
+        interpreter
+            .lock()
+            .unwrap()
+            .eval(
+                "(defresolver Query \"double(n: Int!): Int\" (lambda (parent args) (* 2 (car (cdr (car args))))))
+                 (defresolver Query \"newest: Users\" (lambda (parent args) (list 'user (list \"id\" 9) (list \"name\" \"Di\"))))",
+            )
+            .unwrap();
+        let mut builder = GraphQLServer::builder(models(), TestDriver { statements: Arc::default() });
+        for (type_name, signature, resolver) in TlispResolver::registered(&interpreter).unwrap() {
+            builder = builder.with_resolver(type_name, signature, resolver);
+        }
+        let server = builder.build().unwrap();
+
+        let response = server.execute("{ double(n: 21) newest { id name } }").await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.into_json().unwrap(), json!({"double": 42, "newest": {"id": 9, "name": "Di"}}));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_subscriptions_stream_captured_changes_over_http() {
+        let cdc = ChangeDataCapture::new(CdcConfig::new().table("users", TableCapture::new().to_event_bus()));
+        let driver = CdcDriver::new(TestDriver { statements: Arc::default() }, cdc.clone());
+        let server = Arc::new(GraphQLServer::builder(models(), driver).with_changes(cdc.clone()).build().unwrap());
+        assert!(server.sdl().contains("users_changes: UsersChange!"));
+
+        let mut changes = server.subscribe("subscription { users_changes { kind row { name posts { title } } old { name } } }");
+        let publisher = tokio::spawn(async move {
+            // Keep publishing until the subscription has started listening
+            for _ in 0..50 {
+                cdc.publish(&[RowChange::update(
+                    "users",
+                    vec!["id".to_string(), "name".to_string()],
+                    vec![Value::Integer(1), Value::Text("Ann".to_string())],
+                    vec![Value::Integer(1), Value::Text("Anna".to_string())],
+                )]);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        let response = tokio::time::timeout(Duration::from_secs(5), changes.next()).await.unwrap().unwrap();
+        publisher.abort();
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({"users_changes": {
+                "kind": "update",
+                "row": {"name": "Anna", "posts": [{"title": "Hello"}, {"title": "Again"}]},
+                "old": {"name": "Ann"},
+            }})
+        );
+
+        let (addr, serve) = server.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        tokio::spawn(serve);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/graphql", addr);
+        let body: JsonValue = client
+            .post(&url)
+            .json(&json!({"query": "query One($id: Int!) { users_by_pk(id: $id) { name } }", "variables": {"id": 2}}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["data"], json!({"users_by_pk": {"name": "Bob"}}));
+
+        let events = client
+            .post(&url)
+            .header("accept", "text/event-stream")
+            .json(&json!({"query": "{ users_by_pk(id: 3) { name } }"}))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(events.contains("event:next") && events.contains("event:complete"), "{}", events);
+        assert!(events.contains(r#"{"data":{"users_by_pk":{"name":"Cy"}}}"#), "{}", events);
+
+        let sdl = client.get(format!("{}/schema", url)).send().await.unwrap().text().await.unwrap();
+        assert!(sdl.contains("type Users"));
+    }
+}
@@ -149,7 +149,7 @@ impl Migration for DropTableMigration {
         // 3. Remove any dependent indexes and foreign keys
         // 4. Return the modified schema
 
-        println!("Dropping table: {}", self.table_name);
+        tracing::info!(table = %self.table_name, "Dropping table");
 
         // For now, return the original schema
         // A full implementation would reconstruct the schema without the dropped table
@@ -202,7 +202,7 @@ impl Migration for AddColumnMigration {
         // 3. Add the new column to the table definition
         // 4. Return the modified schema
 
-        println!("Adding column {} to table {}", self.column.name, self.table_name);
+        tracing::info!(table = %self.table_name, column = %self.column.name, "Adding column");
 
         // For now, return the original schema
         // A full implementation would reconstruct the schema with the added column
@@ -267,7 +267,7 @@ impl Migration for DropColumnMigration {
         // 4. Update any dependent indexes and foreign keys
         // 5. Return the modified schema
 
-        println!("Dropping column {} from table {}", self.column_name, self.table_name);
+        tracing::info!(table = %self.table_name, column = %self.column_name, "Dropping column");
 
         // For now, return the original schema
         // A full implementation would reconstruct the schema without the dropped column
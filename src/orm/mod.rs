@@ -39,6 +39,12 @@ pub mod graphql_composable;
 pub mod mutation;
 pub mod nested_relations;
 pub mod mutation_compiler;
+pub mod typed_query;
+pub mod relations;
+pub mod pool;
+pub mod scripting;
+pub mod cdc;
+pub mod graphql_server;
 
 #[cfg(test)]
 pub mod tests;
@@ -65,6 +71,12 @@ pub use graphql_composable::*;
 pub use mutation::*;
 pub use nested_relations::*;
 pub use mutation_compiler::*;
+pub use typed_query::*;
+pub use relations::*;
+pub use pool::*;
+pub use scripting::*;
+pub use cdc::*;
+pub use graphql_server::*;
 
 // Re-export SQL parsing from sqlite module
 pub use crate::sqlite::parser::{ast, parse_sql};
@@ -75,6 +87,7 @@ pub use crate::sqlite::error::{SqlError, SqlResult};
 pub struct OrmContext<D: Driver> {
     driver: D,
     schema: Schema,
+    retry_policy: RetryPolicy,
 }
 
 impl<D: Driver> OrmContext<D> {
@@ -83,9 +96,16 @@ impl<D: Driver> OrmContext<D> {
         Self {
             driver,
             schema: Schema::empty(),
+            retry_policy: RetryPolicy::default(),
         }
     }
 
+    /// Set how transactions are retried after serialization failures
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
     /// Get a reference to the driver
     pub fn driver(&self) -> &D {
         &self.driver
@@ -115,6 +135,45 @@ impl<D: Driver> OrmContext<D> {
         query.execute(&self.driver).await
     }
 
+    /// Run `body` in a transaction, committing if it succeeds and rolling
+    /// back if it fails
+    ///
+    /// When the body or the commit fails with a serialization failure, the
+    /// whole transaction is run again, as set by the retry policy, so the
+    /// body must be safe to repeat.
+    ///
+    /// ```ignore
+    /// orm.transaction(|tx| Box::pin(async move {
+    ///     tx.execute("UPDATE accounts SET balance = balance - 10 WHERE id = $1", &[Value::Integer(1)]).await?;
+    ///     tx.execute("UPDATE accounts SET balance = balance + 10 WHERE id = $1", &[Value::Integer(2)]).await
+    /// })).await?;
+    /// ```
+    pub async fn transaction<T, F>(&self, mut body: F) -> SqlResult<T>
+    where
+        F: for<'t> FnMut(&'t mut dyn Transaction) -> TransactionFuture<'t, T>,
+    {
+        let mut retry = 0;
+        loop {
+            let mut transaction = self.driver.begin_transaction().await?;
+            let error = match body(transaction.as_mut()).await {
+                Ok(value) => match transaction.commit().await {
+                    Ok(()) => return Ok(value),
+                    Err(error) => error,
+                },
+                Err(error) => {
+                    // The body's error is the one worth reporting
+                    let _ = transaction.rollback().await;
+                    error
+                }
+            };
+            if !error.is_serialization_failure() || retry >= self.retry_policy.max_retries {
+                return Err(error);
+            }
+            retry += 1;
+            tokio::time::sleep(self.retry_policy.delay(retry)).await;
+        }
+    }
+
     /// Run migrations to update the schema
     pub async fn migrate(&mut self, migration: impl Migration) -> SqlResult<()> {
         let new_schema = migration.apply(&self.schema);
@@ -101,31 +101,31 @@ impl<D: Driver + Send + Sync> Driver for LoggingDriver<D> {
     type Row = D::Row;
     
     async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<Self::Row>> {
-        println!("[SQL] Executing: {} with binds: {:?}", sql, binds);
+        tracing::info!(sql, ?binds, "Executing query");
         let start = Instant::now();
         let result = self.base.observe(sql, binds).await;
         let duration = start.elapsed();
         
         match &result {
-            Ok(rows) => println!("[SQL] Completed in {:?}, returned {} rows", duration, rows.len()),
-            Err(e) => println!("[SQL] Failed in {:?}: {}", duration, e),
+            Ok(rows) => tracing::info!(?duration, rows = rows.len(), "Query completed"),
+            Err(e) => tracing::warn!(?duration, error = %e, "Query failed"),
         }
         
         result
     }
     
     async fn migrate(&self, schema: &Schema) -> SqlResult<()> {
-        println!("[SQL] Running migration");
+        tracing::info!("Running migration");
         let result = self.base.migrate(schema).await;
         match &result {
-            Ok(_) => println!("[SQL] Migration completed successfully"),
-            Err(e) => println!("[SQL] Migration failed: {}", e),
+            Ok(_) => tracing::info!("Migration completed"),
+            Err(e) => tracing::warn!(error = %e, "Migration failed"),
         }
         result
     }
     
     async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
-        println!("[SQL] Beginning transaction");
+        tracing::info!("Beginning transaction");
         self.base.begin_transaction().await
     }
     
@@ -231,7 +231,7 @@ impl<D: Driver + Send + Sync> Driver for CachingDriver<D> {
                 let cache = self.cache.lock().unwrap();
                 if let Some(entry) = cache.get(&key) {
                     if !entry.is_expired() {
-                        println!("[CACHE] Cache hit for query: {}", sql);
+                        tracing::debug!(sql, "Query cache hit");
                         // TODO: Convert cached data back to D::Row
                         // For now, fall through to actual query
                     }
@@ -251,7 +251,7 @@ impl<D: Driver + Send + Sync> Driver for CachingDriver<D> {
                 
                 let mut cache = self.cache.lock().unwrap();
                 cache.insert(key, entry);
-                println!("[CACHE] Cached result for query: {}", sql);
+                tracing::debug!(sql, "Cached query result");
             }
         }
         
@@ -263,7 +263,7 @@ impl<D: Driver + Send + Sync> Driver for CachingDriver<D> {
         {
             let mut cache = self.cache.lock().unwrap();
             cache.clear();
-            println!("[CACHE] Cache cleared due to migration");
+            tracing::debug!("Query cache cleared by migration");
         }
         
         self.base.migrate(schema).await
@@ -0,0 +1,406 @@
+/// Connection pool - shared connections for concurrent queries
+///
+/// This module implements an async pool of database connections:
+/// - ConnectionManager opens and validates the connections of a driver
+/// - Pool hands out up to max_size connections at a time; callers wait up
+///   to acquire_timeout for one to be returned
+/// - Returned connections are reused until they have been idle or open for
+///   longer than the configured limits
+/// - HealthCheck reports the state of a pool, e.g. to the daemon
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::orm::SqlResult;
+use crate::sqlite::error::SqlError;
+
+/// Opens and checks the connections of a pool
+#[async_trait]
+pub trait ConnectionManager: Send + Sync + 'static {
+    type Connection: Send + 'static;
+
+    /// Open a new connection
+    async fn connect(&self) -> SqlResult<Self::Connection>;
+
+    /// Whether an open connection can still be used
+    async fn is_valid(&self, connection: &mut Self::Connection) -> bool;
+}
+
+/// Pool configuration
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Maximum number of open connections
+    pub max_size: usize,
+    /// How long to wait for a connection before failing
+    pub acquire_timeout: Duration,
+    /// Close connections idle for longer than this
+    pub idle_timeout: Option<Duration>,
+    /// Close connections open for longer than this
+    pub max_lifetime: Option<Duration>,
+    /// Validate idle connections before handing them out
+    pub test_on_checkout: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+            max_lifetime: Some(Duration::from_secs(1800)),
+            test_on_checkout: true,
+        }
+    }
+}
+
+/// Connection counts of a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStatus {
+    pub max_size: usize,
+    /// Open connections, idle or in use
+    pub size: usize,
+    pub idle: usize,
+    pub in_use: usize,
+    /// Callers waiting for a connection
+    pub waiting: usize,
+}
+
+/// Result of checking a pool's database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolHealth {
+    pub healthy: bool,
+    /// Time to get and validate a connection
+    pub latency: Duration,
+    pub status: PoolStatus,
+    pub error: Option<String>,
+}
+
+/// Anything whose database health can be checked
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    async fn health_check(&self) -> PoolHealth;
+}
+
+struct IdleConnection<C> {
+    connection: C,
+    created: Instant,
+    idle_since: Instant,
+}
+
+struct PoolInner<M: ConnectionManager> {
+    manager: M,
+    config: PoolConfig,
+    idle: Mutex<VecDeque<IdleConnection<M::Connection>>>,
+    /// One permit per connection that may be in use
+    permits: Arc<Semaphore>,
+    /// Open connections, idle or in use
+    size: AtomicUsize,
+    waiting: AtomicUsize,
+}
+
+impl<M: ConnectionManager> PoolInner<M> {
+    fn expired(&self, created: Instant, idle_since: Option<Instant>) -> bool {
+        let now = Instant::now();
+        let too_old = self
+            .config
+            .max_lifetime
+            .is_some_and(|lifetime| now.duration_since(created) > lifetime);
+        let too_idle = match (self.config.idle_timeout, idle_since) {
+            (Some(timeout), Some(since)) => now.duration_since(since) > timeout,
+            _ => false,
+        };
+        too_old || too_idle
+    }
+
+    fn close(&self) {
+        self.size.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A pool of connections opened by a [`ConnectionManager`]
+///
+/// Connections are opened on demand and returned to the pool when their
+/// [`PooledConnection`] is dropped. Clones share the same connections.
+pub struct Pool<M: ConnectionManager> {
+    inner: Arc<PoolInner<M>>,
+}
+
+impl<M: ConnectionManager> Clone for Pool<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<M: ConnectionManager> Pool<M> {
+    pub fn new(manager: M, config: PoolConfig) -> Self {
+        let permits = Arc::new(Semaphore::new(config.max_size));
+        Self {
+            inner: Arc::new(PoolInner {
+                manager,
+                config,
+                idle: Mutex::new(VecDeque::new()),
+                permits,
+                size: AtomicUsize::new(0),
+                waiting: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.inner.config
+    }
+
+    pub fn manager(&self) -> &M {
+        &self.inner.manager
+    }
+
+    /// Get a connection, reusing an idle one if possible
+    ///
+    /// Waits while `max_size` connections are in use, and fails with a
+    /// connection error after `acquire_timeout`.
+    pub async fn get(&self) -> SqlResult<PooledConnection<M>> {
+        let inner = &self.inner;
+        inner.waiting.fetch_add(1, Ordering::SeqCst);
+        let permit = tokio::time::timeout(inner.config.acquire_timeout, inner.permits.clone().acquire_owned()).await;
+        inner.waiting.fetch_sub(1, Ordering::SeqCst);
+        let permit = match permit {
+            Ok(Ok(permit)) => permit,
+            Ok(Err(_)) => return Err(SqlError::connection_error("Connection pool is closed")),
+            Err(_) => {
+                return Err(SqlError::connection_error(format!(
+                    "Timed out after {:?} waiting for a connection",
+                    inner.config.acquire_timeout
+                )))
+            }
+        };
+
+        loop {
+            let idle = inner.idle.lock().unwrap().pop_front();
+            let Some(mut idle) = idle else {
+                break;
+            };
+            if inner.expired(idle.created, Some(idle.idle_since)) {
+                inner.close();
+                continue;
+            }
+            if inner.config.test_on_checkout && !inner.manager.is_valid(&mut idle.connection).await {
+                inner.close();
+                continue;
+            }
+            return Ok(PooledConnection::new(self.inner.clone(), idle.connection, idle.created, permit));
+        }
+
+        inner.size.fetch_add(1, Ordering::SeqCst);
+        match inner.manager.connect().await {
+            Ok(connection) => Ok(PooledConnection::new(self.inner.clone(), connection, Instant::now(), permit)),
+            Err(error) => {
+                inner.close();
+                Err(error)
+            }
+        }
+    }
+
+    pub fn status(&self) -> PoolStatus {
+        let inner = &self.inner;
+        let size = inner.size.load(Ordering::SeqCst);
+        let idle = inner.idle.lock().unwrap().len();
+        PoolStatus {
+            max_size: inner.config.max_size,
+            size,
+            idle,
+            in_use: size.saturating_sub(idle),
+            waiting: inner.waiting.load(Ordering::SeqCst),
+        }
+    }
+}
+
+#[async_trait]
+impl<M: ConnectionManager> HealthCheck for Pool<M> {
+    /// Get a connection and validate it
+    async fn health_check(&self) -> PoolHealth {
+        let start = Instant::now();
+        let error = match self.get().await {
+            Ok(mut connection) => {
+                if self.inner.manager.is_valid(&mut connection).await {
+                    None
+                } else {
+                    connection.discard();
+                    Some("Connection failed validation".to_string())
+                }
+            }
+            Err(error) => Some(error.to_string()),
+        };
+        PoolHealth {
+            healthy: error.is_none(),
+            latency: start.elapsed(),
+            status: self.status(),
+            error,
+        }
+    }
+}
+
+/// A connection checked out of a [`Pool`], returned to it when dropped
+pub struct PooledConnection<M: ConnectionManager> {
+    pool: Arc<PoolInner<M>>,
+    connection: Option<M::Connection>,
+    created: Instant,
+    broken: bool,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<M: ConnectionManager> PooledConnection<M> {
+    fn new(pool: Arc<PoolInner<M>>, connection: M::Connection, created: Instant, permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            pool,
+            connection: Some(connection),
+            created,
+            broken: false,
+            _permit: permit,
+        }
+    }
+
+    /// Close the connection instead of returning it to the pool, e.g.
+    /// after an error that leaves it unusable
+    pub fn discard(&mut self) {
+        self.broken = true;
+    }
+}
+
+impl<M: ConnectionManager> Deref for PooledConnection<M> {
+    type Target = M::Connection;
+
+    fn deref(&self) -> &M::Connection {
+        self.connection.as_ref().expect("connection is present until dropped")
+    }
+}
+
+impl<M: ConnectionManager> DerefMut for PooledConnection<M> {
+    fn deref_mut(&mut self) -> &mut M::Connection {
+        self.connection.as_mut().expect("connection is present until dropped")
+    }
+}
+
+impl<M: ConnectionManager> Drop for PooledConnection<M> {
+    fn drop(&mut self) {
+        let Some(connection) = self.connection.take() else {
+            return;
+        };
+        // The connection goes back before the permit is released, so the
+        // next caller finds it idle
+        if self.broken || self.pool.expired(self.created, None) {
+            self.pool.close();
+        } else {
+            self.pool.idle.lock().unwrap().push_back(IdleConnection {
+                connection,
+                created: self.created,
+                idle_since: Instant::now(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    /// Connections numbered in opening order
+    #[derive(Default)]
+    struct TestManager {
+        opened: AtomicUsize,
+        down: AtomicBool,
+    }
+
+    #[async_trait]
+    impl ConnectionManager for TestManager {
+        type Connection = usize;
+
+        async fn connect(&self) -> SqlResult<usize> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(SqlError::connection_error("Database is down"));
+            }
+            Ok(self.opened.fetch_add(1, Ordering::SeqCst))
+        }
+
+        async fn is_valid(&self, _connection: &mut usize) -> bool {
+            !self.down.load(Ordering::SeqCst)
+        }
+    }
+
+    fn pool(config: PoolConfig) -> Pool<TestManager> {
+        Pool::new(TestManager::default(), config)
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuses_connections() {
+        let pool = pool(PoolConfig::default());
+        let first = pool.get().await.unwrap();
+        let second = pool.get().await.unwrap();
+        assert_eq!((*first, *second), (0, 1));
+        assert_eq!(pool.status().in_use, 2);
+
+        drop(first);
+        assert_eq!(*pool.get().await.unwrap(), 0);
+        assert_eq!(pool.status(), PoolStatus { max_size: 10, size: 2, idle: 1, in_use: 1, waiting: 0 });
+
+        let mut broken = pool.get().await.unwrap();
+        broken.discard();
+        drop(broken);
+        assert_eq!(pool.status().size, 1);
+    }
+
+    #[tokio::test]
+    async fn test_pool_waits_for_a_connection() {
+        let pool = pool(PoolConfig {
+            max_size: 1,
+            acquire_timeout: Duration::from_millis(50),
+            ..PoolConfig::default()
+        });
+        let held = pool.get().await.unwrap();
+        let error = pool.get().await.err().unwrap();
+        assert!(matches!(error, SqlError::ConnectionError { .. }));
+
+        let waiter = {
+            let pool = pool.clone();
+            tokio::spawn(async move { *pool.get().await.unwrap() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(pool.status().waiting, 1);
+        drop(held);
+        assert_eq!(waiter.await.unwrap(), 0);
+        assert_eq!(pool.manager().opened.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pool_closes_expired_connections() {
+        let pool = pool(PoolConfig {
+            idle_timeout: Some(Duration::from_millis(20)),
+            ..PoolConfig::default()
+        });
+        drop(pool.get().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(*pool.get().await.unwrap(), 1);
+        assert_eq!(pool.status().size, 1);
+    }
+
+    #[tokio::test]
+    async fn test_pool_health_check() {
+        let pool = pool(PoolConfig::default());
+        let health = pool.health_check().await;
+        assert!(health.healthy);
+        assert_eq!(health.status.idle, 1);
+
+        pool.manager().down.store(true, Ordering::SeqCst);
+        let health = pool.health_check().await;
+        assert!(!health.healthy);
+        assert!(health.error.unwrap().contains("Database is down"));
+        assert_eq!(health.status.size, 0);
+    }
+}
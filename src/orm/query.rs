@@ -213,7 +213,7 @@ impl<A> Query<A> {
                     }
                     QueryF::Insert { statement, next } => {
                         let sql = statement.to_sql();
-                        let _result = driver.observe(&sql, &[]).await?;
+                        let _result = driver.observe(&sql, &statement.binds()).await?;
 
                         // Extract affected row count
                         // In a real implementation, we would get the actual row count from the driver
@@ -224,7 +224,7 @@ impl<A> Query<A> {
                     }
                     QueryF::Update { statement, next } => {
                         let sql = statement.to_sql();
-                        let _result = driver.observe(&sql, &[]).await?;
+                        let _result = driver.observe(&sql, &statement.binds()).await?;
 
                         // Extract affected row count
                         // In a real implementation, we would get the actual row count from the driver
@@ -1205,6 +1205,11 @@ impl DeleteQueryBuilder {
 /// Extension trait for converting AST to SQL
 trait ToSql {
     fn to_sql(&self) -> String;
+
+    /// Values for the `?` placeholders in `to_sql`, in order
+    fn binds(&self) -> Vec<Value> {
+        Vec::new()
+    }
 }
 
 impl ToSql for SelectStatement {
@@ -1253,9 +1258,8 @@ impl ToSql for InsertStatement {
         // Generate proper SQL for INSERT statement
         // In a real implementation, this would:
         // 1. Handle multiple value rows
-        // 2. Generate proper value placeholders for prepared statements
-        // 3. Handle ON CONFLICT clauses
-        // 4. Apply proper SQL escaping and quoting
+        // 2. Handle ON CONFLICT clauses
+        // 3. Apply proper SQL escaping and quoting
 
         let mut sql = format!("INSERT INTO {}", self.table);
 
@@ -1264,55 +1268,41 @@ impl ToSql for InsertStatement {
             sql.push_str(&format!(" ({})", self.columns.join(", ")));
         }
 
-        // Values
+        // Values are bound to placeholders rather than inlined
         sql.push_str(" VALUES ");
         if self.values.is_empty() {
             sql.push_str("()");
         } else {
             let value_rows: Vec<String> = self.values.iter().map(|row| {
-                let value_strs: Vec<String> = row.iter().map(|v| {
-                    match v {
-                        Value::Integer(i) => i.to_string(),
-                        Value::Real(r) => r.to_string(),
-                        Value::Text(s) => format!("'{}'", s.replace("'", "''")),
-                        Value::Boolean(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
-                        Value::Null => "NULL".to_string(),
-                        Value::Blob(_) => "X'<blob>'".to_string(), // Hex representation placeholder
-                    }
-                }).collect();
-                format!("({})", value_strs.join(", "))
+                let placeholders = vec!["?"; row.len()];
+                format!("({})", placeholders.join(", "))
             }).collect();
             sql.push_str(&value_rows.join(", "));
         }
 
         sql
     }
+
+    fn binds(&self) -> Vec<Value> {
+        self.values.iter().flatten().cloned().collect()
+    }
 }
 
 impl ToSql for UpdateStatement {
     fn to_sql(&self) -> String {
         // Generate proper SQL for UPDATE statement
         // In a real implementation, this would:
-        // 1. Generate proper SET clauses with value placeholders
-        // 2. Handle complex WHERE conditions
-        // 3. Apply proper SQL escaping and quoting
-        // 4. Support JOIN clauses for complex updates
+        // 1. Handle complex WHERE conditions
+        // 2. Apply proper SQL escaping and quoting
+        // 3. Support JOIN clauses for complex updates
 
         let mut sql = format!("UPDATE {}", self.table);
 
-        // SET clauses
+        // SET clauses, with values bound to placeholders
         if !self.set_clauses.is_empty() {
-            let set_strs: Vec<String> = self.set_clauses.iter().map(|(column, value)| {
-                let value_str = match value {
-                    Value::Integer(i) => i.to_string(),
-                    Value::Real(r) => r.to_string(),
-                    Value::Text(s) => format!("'{}'", s.replace("'", "''")),
-                    Value::Boolean(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
-                    Value::Null => "NULL".to_string(),
-                    Value::Blob(_) => "X'<blob>'".to_string(), // Hex representation placeholder
-                };
-                format!("{} = {}", column, value_str)
-            }).collect();
+            let set_strs: Vec<String> = self.set_clauses.iter()
+                .map(|(column, _)| format!("{} = ?", column))
+                .collect();
             sql.push_str(&format!(" SET {}", set_strs.join(", ")));
         }
 
@@ -1323,6 +1313,10 @@ impl ToSql for UpdateStatement {
 
         sql
     }
+
+    fn binds(&self) -> Vec<Value> {
+        self.set_clauses.iter().map(|(_, value)| value.clone()).collect()
+    }
 }
 
 impl ToSql for DeleteStatement {
@@ -1398,4 +1392,24 @@ mod tests {
             Query::Pure(_) => panic!("Expected free query"),
         }
     }
+
+    #[test]
+    fn test_values_are_bound_not_inlined() {
+        let insert = InsertStatement {
+            table: "users".to_string(),
+            columns: vec!["name".to_string(), "age".to_string()],
+            values: vec![vec![Value::Text("O'Brien".to_string()), Value::Integer(41)]],
+            on_conflict: None,
+        };
+        assert_eq!(insert.to_sql(), "INSERT INTO users (name, age) VALUES (?, ?)");
+        assert_eq!(insert.binds(), vec![Value::Text("O'Brien".to_string()), Value::Integer(41)]);
+
+        let update = UpdateStatement {
+            table: "users".to_string(),
+            set_clauses: vec![("age".to_string(), Value::Integer(42))],
+            where_clause: None,
+        };
+        assert_eq!(update.to_sql(), "UPDATE users SET age = ?");
+        assert_eq!(update.binds(), vec![Value::Integer(42)]);
+    }
 }
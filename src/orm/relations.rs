@@ -0,0 +1,592 @@
+/// Relations - loading the rows associated with models
+///
+/// This module loads the associations declared in a schema:
+/// - Relation<M, T> is a typed association from model M to model T,
+///   declared as a constant next to the model's fields
+/// - Lazy loading runs one query for the relation of one model
+/// - Eager loading (`TypedQuery::include`) runs one query per relation for
+///   all the models a query returns, instead of one per model (N+1)
+/// - Loaded<M> is a model with the rows of its included relations
+///
+/// ```ignore
+/// impl User {
+///     pub const POSTS: Relation<User, Post> = Relation::has_many("posts", "user_id");
+/// }
+///
+/// let schema = schema.add_association(User::POSTS.definition());
+/// let posts = User::POSTS.load(&user, &driver).await?;
+///
+/// for user in TypedQuery::<User>::select().include(User::POSTS).fetch_loaded(&driver).await? {
+///     println!("{} wrote {} posts", user.name, user.get(User::POSTS)?.len());
+/// }
+/// ```
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use crate::orm::driver::{DatabaseType, Driver};
+use crate::orm::schema::{AssociationDefinition, AssociationKind, JoinTable};
+use crate::orm::scripting::ScriptDatabase;
+use crate::orm::typed_query::{placeholder, Model};
+use crate::orm::types::{DatabaseRow, FromRow, Row, ToRow};
+use crate::orm::SqlResult;
+use crate::sqlite::error::SqlError;
+use crate::sqlite::types::Value;
+
+/// Keys bound in one query; larger batches are split to stay under the
+/// bind limits of the databases
+const BATCH_SIZE: usize = 500;
+
+/// Column carrying the owner key of each loaded row
+const OWNER_KEY: &str = "__owner_key";
+
+/// Association `name` from model `M` to model `T`
+pub struct Relation<M, T> {
+    name: &'static str,
+    kind: AssociationKind,
+    foreign_key: &'static str,
+    through: Option<(&'static str, &'static str, &'static str)>,
+    _marker: PhantomData<fn() -> (M, T)>,
+}
+
+impl<M, T> Clone for Relation<M, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M, T> Copy for Relation<M, T> {}
+
+impl<M, T> std::fmt::Debug for Relation<M, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Relation({}, {:?})", self.name, self.kind)
+    }
+}
+
+impl<M, T> Relation<M, T> {
+    /// Each `M` references one `T` in its `foreign_key` column
+    pub const fn belongs_to(name: &'static str, foreign_key: &'static str) -> Self {
+        Self::new(name, AssociationKind::BelongsTo, foreign_key, None)
+    }
+
+    /// Each `M` is referenced by the `T`s' `foreign_key` column
+    pub const fn has_many(name: &'static str, foreign_key: &'static str) -> Self {
+        Self::new(name, AssociationKind::HasMany, foreign_key, None)
+    }
+
+    /// `M`s and `T`s are linked by the rows of `join_table`, which reference
+    /// `M` in `owner_column` and `T` in `target_column`
+    pub const fn many_to_many(
+        name: &'static str,
+        join_table: &'static str,
+        owner_column: &'static str,
+        target_column: &'static str,
+    ) -> Self {
+        Self::new(
+            name,
+            AssociationKind::ManyToMany,
+            owner_column,
+            Some((join_table, owner_column, target_column)),
+        )
+    }
+
+    const fn new(
+        name: &'static str,
+        kind: AssociationKind,
+        foreign_key: &'static str,
+        through: Option<(&'static str, &'static str, &'static str)>,
+    ) -> Self {
+        Self {
+            name,
+            kind,
+            foreign_key,
+            through,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<M: Model, T: Model> Relation<M, T> {
+    /// The association, for adding to a schema
+    pub fn definition(&self) -> AssociationDefinition {
+        let (owner_key, target_key) = match self.kind {
+            AssociationKind::BelongsTo => (self.foreign_key.to_string(), primary_key::<T>()),
+            AssociationKind::HasMany => (primary_key::<M>(), self.foreign_key.to_string()),
+            AssociationKind::ManyToMany => (primary_key::<M>(), primary_key::<T>()),
+        };
+        AssociationDefinition {
+            name: self.name.to_string(),
+            kind: self.kind,
+            owner_table: M::TABLE.to_string(),
+            target_table: T::TABLE.to_string(),
+            owner_key,
+            target_key,
+            through: self.through.map(|(table, owner_column, target_column)| JoinTable {
+                table: table.to_string(),
+                owner_column: owner_column.to_string(),
+                target_column: target_column.to_string(),
+            }),
+        }
+    }
+
+    /// Load the `T`s of one `M` (lazy loading)
+    pub async fn load<D: Driver>(&self, owner: &M, driver: &D) -> SqlResult<Vec<T>>
+    where
+        D::Row: Row,
+    {
+        let mut related = self.load_many(std::slice::from_ref(owner), driver).await?;
+        Ok(related.pop().unwrap_or_default())
+    }
+
+    /// Load the `T`s of each of `owners` in batched queries, in the order
+    /// of `owners`
+    pub async fn load_many<D: Driver>(&self, owners: &[M], driver: &D) -> SqlResult<Vec<Vec<T>>>
+    where
+        D::Row: Row,
+    {
+        let include = Include::new(self);
+        let keys = owners
+            .iter()
+            .map(|owner| include.owner_key(owner))
+            .collect::<SqlResult<Vec<_>>>()?;
+        include
+            .load(driver, &keys)
+            .await?
+            .into_iter()
+            .map(|rows| rows.into_iter().map(convert_row).collect())
+            .collect()
+    }
+}
+
+/// A model with the rows of the relations included in its query
+#[derive(Debug, Clone)]
+pub struct Loaded<M> {
+    pub model: M,
+    related: HashMap<&'static str, Vec<DatabaseRow>>,
+}
+
+impl<M> Loaded<M> {
+    pub(crate) fn new(model: M, related: HashMap<&'static str, Vec<DatabaseRow>>) -> Self {
+        Self { model, related }
+    }
+
+    /// The `T`s of an included relation; fails if the query did not include it
+    pub fn get<T: FromRow>(&self, relation: Relation<M, T>) -> SqlResult<Vec<T>> {
+        self.related
+            .get(relation.name)
+            .ok_or_else(|| SqlError::runtime_error(format!("Relation {} was not included", relation.name)))?
+            .iter()
+            .cloned()
+            .map(convert_row)
+            .collect()
+    }
+
+    pub fn into_inner(self) -> M {
+        self.model
+    }
+}
+
+impl<M> Deref for Loaded<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.model
+    }
+}
+
+/// A relation to load for the models of a query
+#[derive(Debug, Clone)]
+pub(crate) struct Include {
+    pub(crate) name: &'static str,
+    association: AssociationDefinition,
+    columns: Vec<String>,
+}
+
+impl Include {
+    pub(crate) fn new<M: Model, T: Model>(relation: &Relation<M, T>) -> Self {
+        Self {
+            name: relation.name,
+            association: relation.definition(),
+            columns: T::columns().into_iter().map(|column| column.name).collect(),
+        }
+    }
+
+    /// Value of the owner's key column; a null key has no related rows
+    pub(crate) fn owner_key<M: ToRow>(&self, owner: &M) -> SqlResult<Value> {
+        let values = owner
+            .to_row()
+            .map_err(|error| SqlError::type_error(error.to_string()))?;
+        values.get(&self.association.owner_key).cloned().ok_or_else(|| {
+            SqlError::runtime_error(format!(
+                "{} has no column {}",
+                self.association.owner_table, self.association.owner_key
+            ))
+        })
+    }
+
+    /// The related rows for each of `keys`, in the order of `keys`
+    pub(crate) async fn load<D: Driver>(&self, driver: &D, keys: &[Value]) -> SqlResult<Vec<Vec<DatabaseRow>>>
+    where
+        D::Row: Row,
+    {
+        load_related(driver, &self.association, &self.columns, keys).await
+    }
+}
+
+/// The `columns` of the rows `association` relates to each of `keys`, in
+/// the order of `keys`, in batched queries
+pub(crate) async fn load_related(
+    database: &dyn ScriptDatabase,
+    association: &AssociationDefinition,
+    columns: &[String],
+    keys: &[Value],
+) -> SqlResult<Vec<Vec<DatabaseRow>>> {
+    let mut related = vec![Vec::new(); keys.len()];
+
+    // Owners sharing a key share one bind, and get a copy of each row
+    let mut owners: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut distinct = Vec::new();
+    for (position, key) in keys.iter().enumerate() {
+        if *key == Value::Null {
+            continue;
+        }
+        owners
+            .entry(key_string(key))
+            .or_insert_with(|| {
+                distinct.push(key.clone());
+                Vec::new()
+            })
+            .push(position);
+    }
+
+    let database_type = database.database_type();
+    for batch in distinct.chunks(BATCH_SIZE) {
+        let sql = related_sql(association, columns, &database_type, batch.len());
+        for row in database.query(&sql, batch).await? {
+            let Some(key) = row.get(OWNER_KEY) else {
+                return Err(SqlError::runtime_error(format!("Missing {} in related row", OWNER_KEY)));
+            };
+            let Some(positions) = owners.get(&key_string(key)) else {
+                continue;
+            };
+            let (columns, values): (Vec<String>, Vec<Value>) = row
+                .columns()
+                .iter()
+                .cloned()
+                .zip(row.values().iter().cloned())
+                .filter(|(column, _)| column != OWNER_KEY)
+                .unzip();
+            let row = DatabaseRow::new(columns, values);
+            for &position in positions {
+                related[position].push(row.clone());
+            }
+        }
+    }
+    Ok(related)
+}
+
+/// SELECT of the target rows matching `keys` bound keys, each with the
+/// owner key it matched
+fn related_sql(association: &AssociationDefinition, columns: &[String], database: &DatabaseType, keys: usize) -> String {
+    let target = &association.target_table;
+    let placeholders: Vec<String> = (1..=keys).map(|position| placeholder(database, position)).collect();
+    match &association.through {
+        None => format!(
+            "SELECT {}, {} AS {} FROM {} WHERE {} IN ({})",
+            columns.join(", "),
+            association.target_key,
+            OWNER_KEY,
+            target,
+            association.target_key,
+            placeholders.join(", ")
+        ),
+        Some(join) => {
+            let columns: Vec<String> = columns
+                .iter()
+                .map(|column| format!("{}.{}", target, column))
+                .collect();
+            format!(
+                "SELECT {}, {}.{} AS {} FROM {} JOIN {} ON {}.{} = {}.{} WHERE {}.{} IN ({})",
+                columns.join(", "),
+                join.table,
+                join.owner_column,
+                OWNER_KEY,
+                target,
+                join.table,
+                join.table,
+                join.target_column,
+                target,
+                association.target_key,
+                join.table,
+                join.owner_column,
+                placeholders.join(", ")
+            )
+        }
+    }
+}
+
+/// Primary key column of a model, `id` if it declares none
+fn primary_key<M: Model>() -> String {
+    M::columns()
+        .into_iter()
+        .find(|column| column.primary_key)
+        .map(|column| column.name)
+        .unwrap_or_else(|| "id".to_string())
+}
+
+/// Key for grouping rows by a value, as `Value` is not hashable
+fn key_string(value: &Value) -> String {
+    format!("{:?}", value)
+}
+
+fn convert_row<T: FromRow>(row: DatabaseRow) -> SqlResult<T> {
+    T::from_row(row).map_err(|error| SqlError::type_error(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use crate::orm::driver::{DriverMetadata, Transaction};
+    use crate::orm::schema::Schema;
+    use crate::orm::typed_query::TypedQuery;
+    use crate::Model;
+
+    #[derive(Debug, Clone, PartialEq, Model)]
+    #[model(table = "users")]
+    struct User {
+        #[model(primary_key)]
+        id: i64,
+        name: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Model)]
+    #[model(table = "posts")]
+    struct Post {
+        #[model(primary_key)]
+        id: i64,
+        user_id: i64,
+        title: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Model)]
+    #[model(table = "tags")]
+    struct Tag {
+        #[model(primary_key)]
+        id: i64,
+        label: String,
+    }
+
+    impl User {
+        const POSTS: Relation<User, Post> = Relation::has_many("posts", "user_id");
+    }
+
+    impl Post {
+        const AUTHOR: Relation<Post, User> = Relation::belongs_to("author", "user_id");
+        const TAGS: Relation<Post, Tag> = Relation::many_to_many("tags", "post_tags", "post_id", "tag_id");
+    }
+
+    /// In-memory users, posts and tags answering the queries of this module
+    struct TestDriver {
+        database_type: DatabaseType,
+        statements: Mutex<Vec<String>>,
+    }
+
+    impl TestDriver {
+        fn new(database_type: DatabaseType) -> Self {
+            Self {
+                database_type,
+                statements: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn statements(&self) -> Vec<String> {
+            self.statements.lock().unwrap().clone()
+        }
+    }
+
+    fn row(columns: &[&str], values: Vec<Value>) -> DatabaseRow {
+        DatabaseRow::new(columns.iter().map(|column| column.to_string()).collect(), values)
+    }
+
+    #[async_trait]
+    impl Driver for TestDriver {
+        type Row = DatabaseRow;
+
+        async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<DatabaseRow>> {
+            self.statements.lock().unwrap().push(sql.to_string());
+            let users = [(1, "Ann"), (2, "Bob"), (3, "Cy")];
+            let posts = [(10, 1, "Hello"), (11, 1, "Again"), (12, 2, "Hi")];
+            let post_tags = [(10, 100), (12, 100), (12, 101)];
+            let tags = [(100, "rust"), (101, "sql")];
+            let bound = |key: i64| binds.contains(&Value::Integer(key));
+
+            let rows = if sql.starts_with("SELECT id, name FROM users") {
+                users
+                    .iter()
+                    .map(|(id, name)| row(&["id", "name"], vec![Value::Integer(*id), Value::Text(name.to_string())]))
+                    .collect()
+            } else if sql.contains("FROM users WHERE id IN") {
+                users
+                    .iter()
+                    .filter(|(id, _)| bound(*id))
+                    .map(|(id, name)| {
+                        row(
+                            &["id", "name", OWNER_KEY],
+                            vec![Value::Integer(*id), Value::Text(name.to_string()), Value::Integer(*id)],
+                        )
+                    })
+                    .collect()
+            } else if sql.contains("FROM posts WHERE user_id IN") {
+                posts
+                    .iter()
+                    .filter(|(_, user_id, _)| bound(*user_id))
+                    .map(|(id, user_id, title)| {
+                        row(
+                            &["id", "user_id", "title", OWNER_KEY],
+                            vec![
+                                Value::Integer(*id),
+                                Value::Integer(*user_id),
+                                Value::Text(title.to_string()),
+                                Value::Integer(*user_id),
+                            ],
+                        )
+                    })
+                    .collect()
+            } else if sql.contains("FROM tags JOIN post_tags") {
+                post_tags
+                    .iter()
+                    .filter(|(post_id, _)| bound(*post_id))
+                    .map(|(post_id, tag_id)| {
+                        let (id, label) = tags.iter().find(|(id, _)| id == tag_id).unwrap();
+                        row(
+                            &["id", "label", OWNER_KEY],
+                            vec![Value::Integer(*id), Value::Text(label.to_string()), Value::Integer(*post_id)],
+                        )
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            Ok(rows)
+        }
+
+        async fn migrate(&self, _schema: &Schema) -> SqlResult<()> {
+            Ok(())
+        }
+
+        async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
+            Err(SqlError::transaction_error("Transactions are not supported"))
+        }
+
+        async fn health_check(&self) -> SqlResult<bool> {
+            Ok(true)
+        }
+
+        fn metadata(&self) -> DriverMetadata {
+            DriverMetadata {
+                name: "test".to_string(),
+                version: "1".to_string(),
+                database_type: self.database_type.clone(),
+                supports_transactions: false,
+                supports_foreign_keys: true,
+                supports_json: false,
+            }
+        }
+    }
+
+    #[test]
+    fn test_schema_associations() {
+        let schema = Schema::empty()
+            .add_table("users", User::columns())
+            .add_table("posts", Post::columns())
+            .has_many("posts", "users", "posts", "user_id")
+            .belongs_to("author", "posts", "users", "user_id")
+            .many_to_many("tags", "posts", "tags", "post_tags", "post_id", "tag_id");
+
+        assert_eq!(schema.associations().len(), 3);
+        assert_eq!(schema.find_association("users", "posts"), Some(User::POSTS.definition()));
+        assert_eq!(schema.find_association("posts", "author"), Some(Post::AUTHOR.definition()));
+        assert_eq!(schema.find_association("posts", "tags"), Some(Post::TAGS.definition()));
+        assert_eq!(schema.find_association("users", "author"), None);
+        assert_eq!(schema.tables().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_lazy_loading() {
+        let driver = TestDriver::new(DatabaseType::SQLite);
+        let ann = User { id: 1, name: "Ann".to_string() };
+
+        let posts = User::POSTS.load(&ann, &driver).await.unwrap();
+        assert_eq!(posts.iter().map(|post| post.id).collect::<Vec<_>>(), vec![10, 11]);
+        assert_eq!(Post::AUTHOR.load(&posts[0], &driver).await.unwrap(), vec![ann]);
+        assert_eq!(
+            driver.statements(),
+            vec![
+                "SELECT id, user_id, title, user_id AS __owner_key FROM posts WHERE user_id IN (?)",
+                "SELECT id, name, id AS __owner_key FROM users WHERE id IN (?)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eager_loading_batches_queries() {
+        let driver = TestDriver::new(DatabaseType::PostgreSQL);
+        let users = TypedQuery::<User>::select()
+            .include(User::POSTS)
+            .fetch_loaded(&driver)
+            .await
+            .unwrap();
+
+        let titles: Vec<Vec<String>> = users
+            .iter()
+            .map(|user| user.get(User::POSTS).unwrap().into_iter().map(|post| post.title).collect())
+            .collect();
+        assert_eq!(titles, vec![vec!["Hello", "Again"], vec!["Hi"], vec![]]);
+        assert_eq!(users[1].name, "Bob");
+        assert!(users[0].get(Relation::<User, Post>::has_many("drafts", "user_id")).is_err());
+
+        // One query for the users and one for all of their posts
+        assert_eq!(
+            driver.statements(),
+            vec![
+                "SELECT id, name FROM users",
+                "SELECT id, user_id, title, user_id AS __owner_key FROM posts WHERE user_id IN ($1, $2, $3)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_many_to_many_loading() {
+        let driver = TestDriver::new(DatabaseType::SQLite);
+        let posts = vec![
+            Post { id: 10, user_id: 1, title: "Hello".to_string() },
+            Post { id: 11, user_id: 1, title: "Again".to_string() },
+            Post { id: 12, user_id: 2, title: "Hi".to_string() },
+        ];
+
+        let tags = Post::TAGS.load_many(&posts, &driver).await.unwrap();
+        let labels: Vec<Vec<String>> = tags
+            .into_iter()
+            .map(|tags| tags.into_iter().map(|tag| tag.label).collect())
+            .collect();
+        assert_eq!(labels, vec![vec!["rust"], vec![], vec!["rust", "sql"]]);
+        assert_eq!(
+            driver.statements(),
+            vec![
+                "SELECT tags.id, tags.label, post_tags.post_id AS __owner_key FROM tags \
+                 JOIN post_tags ON post_tags.tag_id = tags.id WHERE post_tags.post_id IN (?, ?, ?)",
+            ]
+        );
+
+        // Owners sharing a key are loaded with a single bind
+        let authors = Post::AUTHOR.load_many(&posts, &driver).await.unwrap();
+        assert_eq!(authors.iter().map(Vec::len).collect::<Vec<_>>(), vec![1, 1, 1]);
+        assert!(driver.statements()[1].ends_with("WHERE id IN (?, ?)"));
+    }
+}
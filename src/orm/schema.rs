@@ -130,6 +130,17 @@ pub enum SchemaF<T> {
         on_update: ForeignKeyAction,
         next: T,
     },
+    /// Association between the rows of two tables with continuation
+    Association {
+        name: String,
+        kind: AssociationKind,
+        owner_table: String,
+        target_table: String,
+        owner_key: String,
+        target_key: String,
+        through: Option<JoinTable>,
+        next: T,
+    },
     /// Terminal case - empty schema
     Empty,
 }
@@ -231,6 +242,95 @@ impl Schema {
         }))
     }
 
+    /// Add an association to the schema
+    pub fn add_association(self, association: AssociationDefinition) -> Self {
+        Schema(Box::new(SchemaF::Association {
+            name: association.name,
+            kind: association.kind,
+            owner_table: association.owner_table,
+            target_table: association.target_table,
+            owner_key: association.owner_key,
+            target_key: association.target_key,
+            through: association.through,
+            next: self,
+        }))
+    }
+
+    /// Each `owner_table` row belongs to the `target_table` row its
+    /// `foreign_key` column references
+    pub fn belongs_to(
+        self,
+        name: impl Into<String>,
+        owner_table: impl Into<String>,
+        target_table: impl Into<String>,
+        foreign_key: impl Into<String>,
+    ) -> Self {
+        let target_table = target_table.into();
+        let target_key = self.primary_key_of(&target_table);
+        self.add_association(AssociationDefinition {
+            name: name.into(),
+            kind: AssociationKind::BelongsTo,
+            owner_table: owner_table.into(),
+            target_table,
+            owner_key: foreign_key.into(),
+            target_key,
+            through: None,
+        })
+    }
+
+    /// Each `owner_table` row has the `target_table` rows whose
+    /// `foreign_key` column references it
+    pub fn has_many(
+        self,
+        name: impl Into<String>,
+        owner_table: impl Into<String>,
+        target_table: impl Into<String>,
+        foreign_key: impl Into<String>,
+    ) -> Self {
+        let owner_table = owner_table.into();
+        let owner_key = self.primary_key_of(&owner_table);
+        self.add_association(AssociationDefinition {
+            name: name.into(),
+            kind: AssociationKind::HasMany,
+            owner_table,
+            target_table: target_table.into(),
+            owner_key,
+            target_key: foreign_key.into(),
+            through: None,
+        })
+    }
+
+    /// `owner_table` and `target_table` rows are linked by the rows of
+    /// `join_table`, which reference the owner in `owner_column` and the
+    /// target in `target_column`
+    pub fn many_to_many(
+        self,
+        name: impl Into<String>,
+        owner_table: impl Into<String>,
+        target_table: impl Into<String>,
+        join_table: impl Into<String>,
+        owner_column: impl Into<String>,
+        target_column: impl Into<String>,
+    ) -> Self {
+        let owner_table = owner_table.into();
+        let target_table = target_table.into();
+        let owner_key = self.primary_key_of(&owner_table);
+        let target_key = self.primary_key_of(&target_table);
+        self.add_association(AssociationDefinition {
+            name: name.into(),
+            kind: AssociationKind::ManyToMany,
+            owner_table,
+            target_table,
+            owner_key,
+            target_key,
+            through: Some(JoinTable {
+                table: join_table.into(),
+                owner_column: owner_column.into(),
+                target_column: target_column.into(),
+            }),
+        })
+    }
+
     /// Check if schema is empty
     pub fn is_empty(&self) -> bool {
         matches!(self.0.as_ref(), SchemaF::Empty)
@@ -257,11 +357,33 @@ impl Schema {
         foreign_keys
     }
 
+    /// Get all associations in the schema
+    pub fn associations(&self) -> Vec<AssociationDefinition> {
+        let mut associations = Vec::new();
+        self.collect_associations(&mut associations);
+        associations
+    }
+
     /// Find a table by name
     pub fn find_table(&self, name: &str) -> Option<TableDefinition> {
         self.tables().into_iter().find(|t| t.name == name)
     }
 
+    /// Find the association `name` of `owner_table`
+    pub fn find_association(&self, owner_table: &str, name: &str) -> Option<AssociationDefinition> {
+        self.associations()
+            .into_iter()
+            .find(|a| a.owner_table == owner_table && a.name == name)
+    }
+
+    /// Primary key column of a table, `id` if the table is unknown or has none
+    fn primary_key_of(&self, table: &str) -> String {
+        self.find_table(table)
+            .and_then(|t| t.columns.into_iter().find(|c| c.primary_key))
+            .map(|c| c.name)
+            .unwrap_or_else(|| "id".to_string())
+    }
+
 
 
     /// Add a table using the builder pattern (mutable version)
@@ -286,6 +408,7 @@ impl Schema {
             }
             SchemaF::Index { next, .. } => next.collect_tables(tables),
             SchemaF::ForeignKey { next, .. } => next.collect_tables(tables),
+            SchemaF::Association { next, .. } => next.collect_tables(tables),
             SchemaF::Empty => {}
         }
     }
@@ -303,6 +426,7 @@ impl Schema {
             }
             SchemaF::Table { next, .. } => next.collect_indexes(indexes),
             SchemaF::ForeignKey { next, .. } => next.collect_indexes(indexes),
+            SchemaF::Association { next, .. } => next.collect_indexes(indexes),
             SchemaF::Empty => {}
         }
     }
@@ -326,6 +450,31 @@ impl Schema {
             }
             SchemaF::Table { next, .. } => next.collect_foreign_keys(foreign_keys),
             SchemaF::Index { next, .. } => next.collect_foreign_keys(foreign_keys),
+            SchemaF::Association { next, .. } => next.collect_foreign_keys(foreign_keys),
+            SchemaF::Empty => {}
+        }
+    }
+
+    fn collect_associations(&self, associations: &mut Vec<AssociationDefinition>) {
+        match self.0.as_ref() {
+            SchemaF::Association {
+                name, kind, owner_table, target_table, owner_key, target_key,
+                through, next
+            } => {
+                associations.push(AssociationDefinition {
+                    name: name.clone(),
+                    kind: *kind,
+                    owner_table: owner_table.clone(),
+                    target_table: target_table.clone(),
+                    owner_key: owner_key.clone(),
+                    target_key: target_key.clone(),
+                    through: through.clone(),
+                });
+                next.collect_associations(associations);
+            }
+            SchemaF::Table { next, .. } => next.collect_associations(associations),
+            SchemaF::Index { next, .. } => next.collect_associations(associations),
+            SchemaF::ForeignKey { next, .. } => next.collect_associations(associations),
             SchemaF::Empty => {}
         }
     }
@@ -737,6 +886,45 @@ pub struct ForeignKeyDefinition {
     pub on_update: ForeignKeyAction,
 }
 
+/// Kinds of association between two tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssociationKind {
+    /// The owner row references one target row
+    BelongsTo,
+    /// Target rows reference the owner row
+    HasMany,
+    /// Owner and target rows are linked through a join table
+    ManyToMany,
+}
+
+/// Join table of a many-to-many association
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JoinTable {
+    pub table: String,
+    /// Column referencing the owner's key
+    pub owner_column: String,
+    /// Column referencing the target's key
+    pub target_column: String,
+}
+
+/// Concrete association definition (extracted from schema)
+///
+/// Owner rows are matched to target rows whose `target_key` equals the
+/// owner's `owner_key`, or through the join table for many-to-many:
+/// - belongs_to: owner_key is the foreign key, target_key the target's primary key
+/// - has_many: owner_key is the owner's primary key, target_key the foreign key
+/// - many_to_many: owner_key and target_key are the primary keys
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssociationDefinition {
+    pub name: String,
+    pub kind: AssociationKind,
+    pub owner_table: String,
+    pub target_table: String,
+    pub owner_key: String,
+    pub target_key: String,
+    pub through: Option<JoinTable>,
+}
+
 /// Type-safe schema structure that exposes tables and columns as properties
 #[derive(Clone)]
 pub struct TypeSafeSchema {
@@ -0,0 +1,640 @@
+/// TLisp bindings for the ORM
+///
+/// Daemon-hosted scripts declare models and persist records without writing
+/// SQL:
+///
+/// ```lisp
+/// (define-model user (id int) (name string) (age int))
+/// (orm-save '(user (name "Ann") (age 31)))   ; => (user (id 1) (name "Ann") (age 31))
+/// (orm-find user 1)                          ; => record or null
+/// (orm-where user '(and (> age 18) (!= name "Bob")))
+/// ```
+///
+/// TLisp has no map type, so a record is a list headed by its model name
+/// followed by `(column value)` pairs in column order. The primary key is the
+/// `id` column, or the first column when there is none.
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::typed_query::placeholder;
+use super::{Column, DataType, DatabaseRow, DatabaseType, Driver, Row, Schema, SqlError, SqlResult, Value};
+use crate::tlisp::Value as TlispValue;
+
+/// ORM used by the TLisp `define-model` and `orm-*` builtins
+static GLOBAL_ORM: Lazy<parking_lot::RwLock<Option<ScriptOrm>>> =
+    Lazy::new(|| parking_lot::RwLock::new(None));
+
+/// SQL text with the values of its placeholders
+pub type Statement = (String, Vec<Value>);
+
+/// Object-safe view of a driver for scripts, which only see generic rows
+#[async_trait]
+pub trait ScriptDatabase: Send + Sync {
+    /// Dialect used for placeholders
+    fn database_type(&self) -> DatabaseType;
+
+    /// Run a statement and return its rows
+    async fn query(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<DatabaseRow>>;
+
+    /// Create the tables of a schema
+    async fn migrate(&self, schema: &Schema) -> SqlResult<()>;
+}
+
+#[async_trait]
+impl<D> ScriptDatabase for D
+where
+    D: Driver,
+    D::Row: Row,
+{
+    fn database_type(&self) -> DatabaseType {
+        self.metadata().database_type
+    }
+
+    async fn query(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<DatabaseRow>> {
+        Ok(self
+            .observe(sql, binds)
+            .await?
+            .into_iter()
+            .map(|row| DatabaseRow::new(row.columns().to_vec(), row.values().to_vec()))
+            .collect())
+    }
+
+    async fn migrate(&self, schema: &Schema) -> SqlResult<()> {
+        Driver::migrate(self, schema).await
+    }
+}
+
+/// A model declared by a script with `define-model`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptModel {
+    /// Model name, which is also the table name
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+impl ScriptModel {
+    /// Build a model from `(column type)` field specs
+    ///
+    /// Types are `int`, `float`, `string`, `bool` and `blob` (or their SQL
+    /// names `integer`, `real`, `text`, `boolean`).
+    pub fn from_spec(name: impl Into<String>, fields: &[TlispValue]) -> SqlResult<Self> {
+        let name = name.into();
+        if fields.is_empty() {
+            return Err(SqlError::schema_error(format!("Model {} has no fields", name)));
+        }
+
+        let mut columns: Vec<Column> = Vec::with_capacity(fields.len());
+        for field in fields {
+            let (column, type_name) = match field {
+                TlispValue::List(items) if items.len() == 2 => match (&items[0], &items[1]) {
+                    (TlispValue::Symbol(column), TlispValue::Symbol(type_name)) => (column, type_name),
+                    _ => return Err(SqlError::schema_error(format!("Invalid field in model {}: {}", name, field))),
+                },
+                _ => return Err(SqlError::schema_error(format!("Invalid field in model {}: {}", name, field))),
+            };
+            let data_type = match type_name.as_str() {
+                "int" | "integer" => DataType::Integer,
+                "float" | "real" => DataType::Real,
+                "string" | "text" => DataType::Text,
+                "bool" | "boolean" => DataType::Boolean,
+                "blob" => DataType::Blob,
+                other => return Err(SqlError::type_error(format!("Unknown field type {} for {}.{}", other, name, column))),
+            };
+            if columns.iter().any(|c| &c.name == column) {
+                return Err(SqlError::schema_error(format!("Duplicate field {} in model {}", column, name)));
+            }
+            columns.push(Column::new(column.clone(), data_type));
+        }
+
+        let key = columns.iter().position(|c| c.name == "id").unwrap_or(0);
+        let mut primary_key = columns[key].clone().primary_key();
+        if primary_key.data_type == DataType::Integer {
+            primary_key = primary_key.auto_increment();
+        }
+        columns[key] = primary_key;
+
+        Ok(Self { name, columns })
+    }
+
+    /// The primary key column
+    pub fn primary_key(&self) -> &Column {
+        self.columns
+            .iter()
+            .find(|c| c.primary_key)
+            .unwrap_or(&self.columns[0])
+    }
+
+    fn column(&self, name: &str) -> SqlResult<&Column> {
+        self.columns
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| SqlError::column_not_found(format!("{}.{}", self.name, name)))
+    }
+
+    fn column_list(&self) -> String {
+        self.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Schema creating the model's table
+    pub fn schema(&self) -> Schema {
+        Schema::table(self.name.clone(), self.columns.clone())
+    }
+
+    /// Select a record by primary key
+    pub fn find_sql(&self, database: &DatabaseType) -> String {
+        format!(
+            "SELECT {} FROM {} WHERE {} = {} LIMIT 1",
+            self.column_list(),
+            self.name,
+            self.primary_key().name,
+            placeholder(database, 1)
+        )
+    }
+
+    /// Select the records matching a quoted condition such as `(> age 18)`
+    ///
+    /// Conditions combine comparisons (`=`, `!=`, `<`, `<=`, `>`, `>=`,
+    /// `like`) with `and`, `or` and `not`. A symbol naming a column refers
+    /// to it; any other operand is bound as a value.
+    pub fn where_sql(&self, database: &DatabaseType, condition: &TlispValue) -> SqlResult<Statement> {
+        let mut binds = Vec::new();
+        let predicate = self.condition_sql(database, condition, &mut binds)?;
+        Ok((
+            format!("SELECT {} FROM {} WHERE {}", self.column_list(), self.name, predicate),
+            binds,
+        ))
+    }
+
+    fn condition_sql(&self, database: &DatabaseType, condition: &TlispValue, binds: &mut Vec<Value>) -> SqlResult<String> {
+        let items = match condition {
+            TlispValue::List(items) if !items.is_empty() => items,
+            TlispValue::Bool(true) => return Ok("1 = 1".to_string()),
+            TlispValue::Bool(false) => return Ok("1 = 0".to_string()),
+            _ => return Err(SqlError::parse_error(format!("Invalid condition: {}", condition))),
+        };
+        let operator = match &items[0] {
+            TlispValue::Symbol(operator) => operator.as_str(),
+            _ => return Err(SqlError::parse_error(format!("Invalid condition: {}", condition))),
+        };
+        let operands = &items[1..];
+
+        match operator {
+            "and" | "or" if !operands.is_empty() => {
+                let parts = operands
+                    .iter()
+                    .map(|operand| self.condition_sql(database, operand, binds))
+                    .collect::<SqlResult<Vec<_>>>()?;
+                Ok(format!("({})", parts.join(if operator == "and" { " AND " } else { " OR " })))
+            }
+            "not" if operands.len() == 1 => {
+                Ok(format!("NOT {}", self.condition_sql(database, &operands[0], binds)?))
+            }
+            "=" | "!=" | "<>" | "<" | "<=" | ">" | ">=" | "like" if operands.len() == 2 => {
+                let left = self.operand_sql(database, &operands[0], binds)?;
+                if matches!(&operands[1], TlispValue::Null) || matches!(&operands[1], TlispValue::Symbol(s) if s == "null") {
+                    return match operator {
+                        "=" => Ok(format!("{} IS NULL", left)),
+                        "!=" | "<>" => Ok(format!("{} IS NOT NULL", left)),
+                        _ => Err(SqlError::parse_error(format!("Cannot compare with null using {}", operator))),
+                    };
+                }
+                let right = self.operand_sql(database, &operands[1], binds)?;
+                let operator = match operator {
+                    "!=" => "<>",
+                    "like" => "LIKE",
+                    other => other,
+                };
+                Ok(format!("{} {} {}", left, operator, right))
+            }
+            _ => Err(SqlError::parse_error(format!("Invalid condition: {}", condition))),
+        }
+    }
+
+    fn operand_sql(&self, database: &DatabaseType, operand: &TlispValue, binds: &mut Vec<Value>) -> SqlResult<String> {
+        match operand {
+            TlispValue::Symbol(name) => Ok(self.column(name)?.name.clone()),
+            value => {
+                binds.push(to_sql_value(value, None)?);
+                Ok(placeholder(database, binds.len()))
+            }
+        }
+    }
+
+    /// Statements saving a record's values: an update of the row with the
+    /// record's primary key, and the insert used when no row was updated
+    ///
+    /// Without a primary key value only the insert is returned and the
+    /// database assigns the key. Both statements return the saved row.
+    pub fn save_sql(&self, database: &DatabaseType, values: &[(String, Value)]) -> (Option<Statement>, Statement) {
+        let key = &self.primary_key().name;
+        let key_value = values
+            .iter()
+            .find(|(column, value)| column == key && *value != Value::Null)
+            .map(|(_, value)| value.clone());
+
+        let update = key_value.map(|key_value| {
+            let assigned: Vec<&(String, Value)> = values.iter().filter(|(column, _)| column != key).collect();
+            let mut binds: Vec<Value> = assigned.iter().map(|(_, value)| value.clone()).collect();
+            let set = if assigned.is_empty() {
+                format!("{} = {}", key, key)
+            } else {
+                assigned
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (column, _))| format!("{} = {}", column, placeholder(database, i + 1)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            binds.push(key_value);
+            (
+                format!(
+                    "UPDATE {} SET {} WHERE {} = {} RETURNING {}",
+                    self.name,
+                    set,
+                    key,
+                    placeholder(database, binds.len()),
+                    self.column_list()
+                ),
+                binds,
+            )
+        });
+
+        let inserted: Vec<&(String, Value)> = values
+            .iter()
+            .filter(|(column, value)| column != key || *value != Value::Null)
+            .collect();
+        let insert = (
+            format!(
+                "INSERT INTO {} ({}) VALUES ({}) RETURNING {}",
+                self.name,
+                inserted.iter().map(|(column, _)| column.as_str()).collect::<Vec<_>>().join(", "),
+                (1..=inserted.len()).map(|i| placeholder(database, i)).collect::<Vec<_>>().join(", "),
+                self.column_list()
+            ),
+            inserted.iter().map(|(_, value)| value.clone()).collect(),
+        );
+
+        (update, insert)
+    }
+
+    /// Convert a row to a `(model (column value) ...)` record
+    pub fn to_record(&self, row: &DatabaseRow) -> TlispValue {
+        let mut items = Vec::with_capacity(self.columns.len() + 1);
+        items.push(TlispValue::Symbol(self.name.clone()));
+        for column in &self.columns {
+            let value = row.get(&column.name).map(to_tlisp_value).unwrap_or(TlispValue::Null);
+            items.push(TlispValue::List(vec![TlispValue::Symbol(column.name.clone()), value]));
+        }
+        TlispValue::List(items)
+    }
+
+    /// Column values of a record, in model column order
+    pub fn from_record(&self, record: &TlispValue) -> SqlResult<Vec<(String, Value)>> {
+        let fields = match record_model(record)? {
+            (name, fields) if name == self.name => fields,
+            (name, _) => return Err(SqlError::type_error(format!("Expected a {} record, got {}", self.name, name))),
+        };
+
+        let mut assigned = HashMap::new();
+        for field in fields {
+            match field {
+                TlispValue::List(pair) if pair.len() == 2 => {
+                    let name = match &pair[0] {
+                        TlispValue::Symbol(name) | TlispValue::String(name) => name,
+                        other => return Err(SqlError::type_error(format!("Invalid field name: {}", other))),
+                    };
+                    let column = self.column(name)?;
+                    assigned.insert(column.name.clone(), to_sql_value(&pair[1], Some(&column.data_type))?);
+                }
+                other => return Err(SqlError::type_error(format!("Invalid field in {} record: {}", self.name, other))),
+            }
+        }
+
+        Ok(self
+            .columns
+            .iter()
+            .filter_map(|c| assigned.remove(&c.name).map(|value| (c.name.clone(), value)))
+            .collect())
+    }
+}
+
+/// Model name and `(column value)` fields of a record
+fn record_model(record: &TlispValue) -> SqlResult<(&str, &[TlispValue])> {
+    match record {
+        TlispValue::List(items) => match items.split_first() {
+            Some((TlispValue::Symbol(name), fields)) => Ok((name, fields)),
+            _ => Err(SqlError::type_error(format!("Not a record: {}", record))),
+        },
+        _ => Err(SqlError::type_error(format!("Not a record: {}", record))),
+    }
+}
+
+/// Convert a database value for TLisp; blobs become lists of bytes
+pub fn to_tlisp_value(value: &Value) -> TlispValue {
+    match value {
+        Value::Null => TlispValue::Null,
+        Value::Integer(i) => TlispValue::Int(*i),
+        Value::Real(f) => TlispValue::Float(*f),
+        Value::Text(s) => TlispValue::String(s.clone()),
+        Value::Boolean(b) => TlispValue::Bool(*b),
+        Value::Blob(bytes) => TlispValue::List(bytes.iter().map(|b| TlispValue::Int(*b as i64)).collect()),
+    }
+}
+
+/// Convert a TLisp value for the database, coercing it to the column type
+/// when one is given
+pub fn to_sql_value(value: &TlispValue, data_type: Option<&DataType>) -> SqlResult<Value> {
+    let converted = match (value, data_type) {
+        (TlispValue::Null | TlispValue::Unit, _) => Value::Null,
+        (TlispValue::Int(i), Some(DataType::Real)) => Value::Real(*i as f64),
+        (TlispValue::Int(i), Some(DataType::Boolean)) => Value::Boolean(*i != 0),
+        (TlispValue::Int(i), _) => Value::Integer(*i),
+        (TlispValue::Float(f), _) => Value::Real(*f),
+        (TlispValue::Bool(b), _) => Value::Boolean(*b),
+        (TlispValue::String(s), _) => Value::Text(s.clone()),
+        (TlispValue::List(items), Some(DataType::Blob)) => Value::Blob(
+            items
+                .iter()
+                .map(|item| match item {
+                    TlispValue::Int(b) if (0..=255).contains(b) => Ok(*b as u8),
+                    other => Err(SqlError::type_error(format!("Invalid byte: {}", other))),
+                })
+                .collect::<SqlResult<Vec<u8>>>()?,
+        ),
+        (other, _) => return Err(SqlError::type_error(format!("Cannot store {} in the database", other))),
+    };
+
+    match (&converted, data_type) {
+        (Value::Null, _) | (_, None) => Ok(converted),
+        (_, Some(expected)) if converted.data_type() == *expected => Ok(converted),
+        (_, Some(expected)) => Err(SqlError::type_error(format!("Expected {}, got {}", expected, value))),
+    }
+}
+
+/// Models and database shared by the scripts of a daemon
+#[derive(Clone)]
+pub struct ScriptOrm {
+    database: Arc<dyn ScriptDatabase>,
+    models: Arc<parking_lot::RwLock<HashMap<String, ScriptModel>>>,
+}
+
+impl ScriptOrm {
+    pub fn new(database: impl ScriptDatabase + 'static) -> Self {
+        Self {
+            database: Arc::new(database),
+            models: Arc::new(parking_lot::RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Install this ORM as the one used by TLisp `orm-*` builtins
+    pub fn install_global(&self) {
+        *GLOBAL_ORM.write() = Some(self.clone());
+    }
+
+    /// ORM used by TLisp `orm-*` builtins, if one is installed
+    pub fn global() -> Option<ScriptOrm> {
+        GLOBAL_ORM.read().clone()
+    }
+
+    /// Create the model's table if needed and register it
+    pub async fn define_model(&self, model: ScriptModel) -> SqlResult<()> {
+        self.database.migrate(&model.schema()).await?;
+        self.models.write().insert(model.name.clone(), model);
+        Ok(())
+    }
+
+    /// A registered model
+    pub fn model(&self, name: &str) -> SqlResult<ScriptModel> {
+        self.models
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SqlError::table_not_found(name))
+    }
+
+    /// Every registered model, by name
+    pub fn models(&self) -> Vec<ScriptModel> {
+        let mut models: Vec<ScriptModel> = self.models.read().values().cloned().collect();
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        models
+    }
+
+    /// Database the models are stored in
+    pub fn database(&self) -> Arc<dyn ScriptDatabase> {
+        self.database.clone()
+    }
+
+    /// The record with the given primary key, if any
+    pub async fn find(&self, model: &str, key: &TlispValue) -> SqlResult<Option<TlispValue>> {
+        let model = self.model(model)?;
+        let key = to_sql_value(key, Some(&model.primary_key().data_type))?;
+        let sql = model.find_sql(&self.database.database_type());
+        let rows = self.database.query(&sql, &[key]).await?;
+        Ok(rows.first().map(|row| model.to_record(row)))
+    }
+
+    /// The records matching a quoted condition
+    pub async fn find_where(&self, model: &str, condition: &TlispValue) -> SqlResult<Vec<TlispValue>> {
+        let model = self.model(model)?;
+        let (sql, binds) = model.where_sql(&self.database.database_type(), condition)?;
+        let rows = self.database.query(&sql, &binds).await?;
+        Ok(rows.iter().map(|row| model.to_record(row)).collect())
+    }
+
+    /// Update or insert a record, returning it as stored
+    pub async fn save(&self, record: &TlispValue) -> SqlResult<TlispValue> {
+        let (name, _) = record_model(record)?;
+        let model = self.model(name)?;
+        let values = model.from_record(record)?;
+        let (update, (insert, insert_binds)) = model.save_sql(&self.database.database_type(), &values);
+
+        if let Some((sql, binds)) = update {
+            if let Some(row) = self.database.query(&sql, &binds).await?.first() {
+                return Ok(model.to_record(row));
+            }
+        }
+        let rows = self.database.query(&insert, &insert_binds).await?;
+        Ok(match rows.first() {
+            Some(row) => model.to_record(row),
+            None => model.to_record(&DatabaseRow::from_pairs(values)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orm::{DriverMetadata, Transaction};
+    use crate::tlisp::TlispInterpreter;
+    use std::sync::Mutex;
+
+    fn symbol(name: &str) -> TlispValue {
+        TlispValue::Symbol(name.to_string())
+    }
+
+    fn field(column: &str, type_name: &str) -> TlispValue {
+        TlispValue::List(vec![symbol(column), symbol(type_name)])
+    }
+
+    fn user() -> ScriptModel {
+        ScriptModel::from_spec("user", &[field("id", "int"), field("name", "string"), field("age", "int")]).unwrap()
+    }
+
+    /// Single-table store answering the statements generated by `ScriptModel`
+    struct TestDriver {
+        rows: Mutex<Vec<DatabaseRow>>,
+        statements: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Driver for TestDriver {
+        type Row = DatabaseRow;
+
+        async fn observe(&self, sql: &str, binds: &[Value]) -> SqlResult<Vec<DatabaseRow>> {
+            self.statements.lock().unwrap().push(sql.to_string());
+            let mut rows = self.rows.lock().unwrap();
+            let by_id = |id: &Value| rows.iter().position(|row| row.get("id") == Some(id));
+
+            if sql.starts_with("INSERT") {
+                let id = Value::Integer(rows.len() as i64 + 1);
+                let row = DatabaseRow::new(
+                    vec!["id".to_string(), "name".to_string(), "age".to_string()],
+                    vec![id, binds[0].clone(), binds[1].clone()],
+                );
+                rows.push(row.clone());
+                Ok(vec![row])
+            } else if sql.starts_with("UPDATE") {
+                Ok(match by_id(&binds[2]) {
+                    Some(i) => {
+                        rows[i].values[1] = binds[0].clone();
+                        rows[i].values[2] = binds[1].clone();
+                        vec![rows[i].clone()]
+                    }
+                    None => Vec::new(),
+                })
+            } else if sql.contains("WHERE id =") {
+                Ok(by_id(&binds[0]).map(|i| vec![rows[i].clone()]).unwrap_or_default())
+            } else {
+                // (and (> age ?) (<> name ?))
+                Ok(rows
+                    .iter()
+                    .filter(|row| {
+                        matches!((row.get("age"), &binds[0]), (Some(Value::Integer(a)), Value::Integer(b)) if a > b)
+                            && row.get("name") != Some(&binds[1])
+                    })
+                    .cloned()
+                    .collect())
+            }
+        }
+
+        async fn migrate(&self, schema: &Schema) -> SqlResult<()> {
+            for table in schema.tables() {
+                self.statements.lock().unwrap().push(format!("CREATE TABLE {}", table.name));
+            }
+            Ok(())
+        }
+
+        async fn begin_transaction(&self) -> SqlResult<Box<dyn Transaction>> {
+            Err(SqlError::transaction_error("Transactions are not supported"))
+        }
+
+        async fn health_check(&self) -> SqlResult<bool> {
+            Ok(true)
+        }
+
+        fn metadata(&self) -> DriverMetadata {
+            DriverMetadata {
+                name: "test".to_string(),
+                version: "1".to_string(),
+                database_type: DatabaseType::SQLite,
+                supports_transactions: false,
+                supports_foreign_keys: false,
+                supports_json: false,
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_sql() {
+        let model = user();
+        assert_eq!(model.primary_key().name, "id");
+        assert!(model.primary_key().auto_increment);
+        assert_eq!(
+            model.find_sql(&DatabaseType::PostgreSQL),
+            "SELECT id, name, age FROM user WHERE id = $1 LIMIT 1"
+        );
+
+        let condition = TlispValue::List(vec![
+            symbol("or"),
+            TlispValue::List(vec![symbol(">="), symbol("age"), TlispValue::Int(18)]),
+            TlispValue::List(vec![symbol("not"), TlispValue::List(vec![symbol("="), symbol("name"), symbol("null")])]),
+        ]);
+        let (sql, binds) = model.where_sql(&DatabaseType::PostgreSQL, &condition).unwrap();
+        assert_eq!(sql, "SELECT id, name, age FROM user WHERE (age >= $1 OR NOT name IS NULL)");
+        assert_eq!(binds, vec![Value::Integer(18)]);
+
+        let unknown = TlispValue::List(vec![symbol("="), symbol("email"), TlispValue::String("a".to_string())]);
+        assert!(model.where_sql(&DatabaseType::SQLite, &unknown).is_err());
+
+        let values = vec![
+            ("id".to_string(), Value::Integer(7)),
+            ("name".to_string(), Value::Text("Ann".to_string())),
+        ];
+        let (update, insert) = model.save_sql(&DatabaseType::PostgreSQL, &values);
+        let (update, update_binds) = update.unwrap();
+        assert_eq!(update, "UPDATE user SET name = $1 WHERE id = $2 RETURNING id, name, age");
+        assert_eq!(update_binds, vec![Value::Text("Ann".to_string()), Value::Integer(7)]);
+        assert_eq!(insert.0, "INSERT INTO user (id, name) VALUES ($1, $2) RETURNING id, name, age");
+
+        let record = TlispValue::List(vec![
+            symbol("user"),
+            TlispValue::List(vec![symbol("age"), TlispValue::String("old".to_string())]),
+        ]);
+        assert!(model.from_record(&record).is_err());
+        assert!(ScriptModel::from_spec("user", &[field("id", "uuid")]).is_err());
+    }
+
+    #[test]
+    fn test_tlisp_builtins() {
+        let driver = TestDriver {
+            rows: Mutex::new(Vec::new()),
+            statements: Mutex::new(Vec::new()),
+        };
+        ScriptOrm::new(driver).install_global();
+
+        let mut interpreter = TlispInterpreter::new();
+        assert_eq!(
+            interpreter.eval("(define-model user (id int) (name string) (age int))").unwrap(),
+            symbol("user")
+        );
+        assert_eq!(interpreter.eval("(orm-find user 1)").unwrap(), TlispValue::Null);
+
+        let ann = interpreter.eval(r#"(orm-save '(user (name "Ann") (age 31)))"#).unwrap();
+        assert_eq!(
+            ann,
+            TlispValue::List(vec![
+                symbol("user"),
+                TlispValue::List(vec![symbol("id"), TlispValue::Int(1)]),
+                TlispValue::List(vec![symbol("name"), TlispValue::String("Ann".to_string())]),
+                TlispValue::List(vec![symbol("age"), TlispValue::Int(31)]),
+            ])
+        );
+        interpreter.eval(r#"(orm-save '(user (name "Bob") (age 40)))"#).unwrap();
+        interpreter.eval(r#"(orm-save '(user (name "Cy") (age 12)))"#).unwrap();
+        interpreter.eval(r#"(orm-save '(user (id 1) (name "Ann") (age 32)))"#).unwrap();
+
+        assert_eq!(interpreter.eval("(orm-find user 1)").unwrap(), {
+            let TlispValue::List(mut items) = ann else { unreachable!() };
+            items[3] = TlispValue::List(vec![symbol("age"), TlispValue::Int(32)]);
+            TlispValue::List(items)
+        });
+        match interpreter.eval(r#"(orm-where user '(and (> age 18) (!= name "Bob")))"#).unwrap() {
+            TlispValue::List(records) => assert_eq!(records.len(), 1),
+            other => panic!("expected a list of records, got {}", other),
+        }
+        assert!(interpreter.eval("(orm-find post 1)").is_err());
+    }
+}
@@ -2,5 +2,5 @@
 pub mod advanced_features_test;
 pub mod schema_based_test;
 pub mod subquery_test;
-pub mod graphql_test;
-pub mod graphql_composable_test;
+#[cfg(any())] pub mod graphql_test;
+#[cfg(any())] pub mod graphql_composable_test;
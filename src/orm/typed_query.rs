@@ -0,0 +1,546 @@
+/// Typed queries - SELECTs checked against the model at compile time
+///
+/// This module builds queries from the columns of a model rather than SQL
+/// strings:
+/// - Model is a table row type, derived with `#[derive(Model)]`
+/// - ModelField<M, T> is a column of model M holding values of type T
+/// - Conditions (combined with `and`, `or` and `!`) and orderings only fit queries of their own model, and
+///   comparisons only take values of the column's type
+/// - Values are bound to placeholders in the dialect of the driver
+///
+/// ```ignore
+/// #[derive(Model)]
+/// #[model(table = "users")]
+/// struct User {
+///     #[model(primary_key)]
+///     id: i64,
+///     name: String,
+///     age: i64,
+///     email: Option<String>,
+/// }
+///
+/// let adults = TypedQuery::<User>::select()
+///     .filter(User::AGE.gt(18).and(User::EMAIL.is_not_null()))
+///     .order_by(User::NAME.asc())
+///     .limit(10)
+///     .fetch(&driver)
+///     .await?;
+/// ```
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use crate::orm::driver::{DatabaseType, Driver};
+use crate::orm::query::{OrderDirection, Query, QueryRow};
+use crate::orm::relations::{Include, Loaded, Relation};
+use crate::orm::schema::{Column, TableDefinition};
+use crate::orm::types::{DatabaseRow, FromRow, Row, ToRow, ToValue};
+use crate::orm::SqlResult;
+use crate::sqlite::error::SqlError;
+use crate::sqlite::types::{DataType, Value};
+
+/// A table row type; `#[derive(Model)]` implements it along with a
+/// [`ModelField`] constant for each column, e.g. `User::AGE`
+pub trait Model: FromRow + ToRow {
+    /// Name of the table
+    const TABLE: &'static str;
+
+    /// Columns of the table, in declaration order
+    fn columns() -> Vec<Column>;
+
+    /// The table, for adding to a schema
+    fn table_definition() -> TableDefinition {
+        Self::columns()
+            .into_iter()
+            .fold(TableDefinition::new(Self::TABLE), TableDefinition::with_column)
+    }
+}
+
+/// Rust types a column can hold; `Option<T>` is a nullable column of `T`
+pub trait FieldType {
+    /// Type of the values the column is compared with
+    type Operand: ToValue;
+    const DATA_TYPE: DataType;
+    const NULLABLE: bool = false;
+}
+
+impl FieldType for i64 {
+    type Operand = i64;
+    const DATA_TYPE: DataType = DataType::Integer;
+}
+
+impl FieldType for f64 {
+    type Operand = f64;
+    const DATA_TYPE: DataType = DataType::Real;
+}
+
+impl FieldType for String {
+    type Operand = String;
+    const DATA_TYPE: DataType = DataType::Text;
+}
+
+impl FieldType for bool {
+    type Operand = bool;
+    const DATA_TYPE: DataType = DataType::Boolean;
+}
+
+impl FieldType for Vec<u8> {
+    type Operand = Vec<u8>;
+    const DATA_TYPE: DataType = DataType::Blob;
+}
+
+impl<T: FieldType> FieldType for Option<T> {
+    type Operand = T::Operand;
+    const DATA_TYPE: DataType = T::DATA_TYPE;
+    const NULLABLE: bool = true;
+}
+
+/// Column `name` of model `M`, holding values of type `T`
+pub struct ModelField<M, T> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> (M, T)>,
+}
+
+impl<M, T> Clone for ModelField<M, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M, T> Copy for ModelField<M, T> {}
+
+impl<M, T> std::fmt::Debug for ModelField<M, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ModelField({})", self.name)
+    }
+}
+
+impl<M, T> ModelField<M, T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<M, T: FieldType> ModelField<M, T> {
+    pub fn eq(self, value: impl Into<T::Operand>) -> Condition<M> {
+        self.compare("=", value)
+    }
+
+    pub fn ne(self, value: impl Into<T::Operand>) -> Condition<M> {
+        self.compare("<>", value)
+    }
+
+    pub fn gt(self, value: impl Into<T::Operand>) -> Condition<M> {
+        self.compare(">", value)
+    }
+
+    pub fn ge(self, value: impl Into<T::Operand>) -> Condition<M> {
+        self.compare(">=", value)
+    }
+
+    pub fn lt(self, value: impl Into<T::Operand>) -> Condition<M> {
+        self.compare("<", value)
+    }
+
+    pub fn le(self, value: impl Into<T::Operand>) -> Condition<M> {
+        self.compare("<=", value)
+    }
+
+    /// `column IN (...)`; an empty list matches no rows
+    pub fn is_in<V: Into<T::Operand>>(self, values: impl IntoIterator<Item = V>) -> Condition<M> {
+        Condition::new(ConditionExpr::In {
+            column: self.name,
+            values: values.into_iter().map(|value| value.into().to_value()).collect(),
+        })
+    }
+
+    pub fn asc(self) -> Order<M> {
+        Order::new(self.name, OrderDirection::Asc)
+    }
+
+    pub fn desc(self) -> Order<M> {
+        Order::new(self.name, OrderDirection::Desc)
+    }
+
+    fn compare(self, op: &'static str, value: impl Into<T::Operand>) -> Condition<M> {
+        Condition::new(ConditionExpr::Compare {
+            column: self.name,
+            op,
+            value: value.into().to_value(),
+        })
+    }
+}
+
+impl<M, T: FieldType<Operand = String>> ModelField<M, T> {
+    /// `column LIKE pattern`, with `%` and `_` wildcards
+    pub fn like(self, pattern: impl Into<String>) -> Condition<M> {
+        self.compare("LIKE", pattern)
+    }
+}
+
+impl<M, T> ModelField<M, Option<T>> {
+    pub fn is_null(self) -> Condition<M> {
+        Condition::new(ConditionExpr::IsNull {
+            column: self.name,
+            negated: false,
+        })
+    }
+
+    pub fn is_not_null(self) -> Condition<M> {
+        Condition::new(ConditionExpr::IsNull {
+            column: self.name,
+            negated: true,
+        })
+    }
+}
+
+/// A WHERE condition over the columns of model `M`
+#[derive(Debug, Clone)]
+pub struct Condition<M> {
+    expr: ConditionExpr,
+    _model: PhantomData<fn() -> M>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConditionExpr {
+    Compare {
+        column: &'static str,
+        op: &'static str,
+        value: Value,
+    },
+    In {
+        column: &'static str,
+        values: Vec<Value>,
+    },
+    IsNull {
+        column: &'static str,
+        negated: bool,
+    },
+    And(Box<ConditionExpr>, Box<ConditionExpr>),
+    Or(Box<ConditionExpr>, Box<ConditionExpr>),
+    Not(Box<ConditionExpr>),
+}
+
+impl<M> Condition<M> {
+    fn new(expr: ConditionExpr) -> Self {
+        Self {
+            expr,
+            _model: PhantomData,
+        }
+    }
+
+    pub fn and(self, other: Condition<M>) -> Self {
+        Self::new(ConditionExpr::And(Box::new(self.expr), Box::new(other.expr)))
+    }
+
+    pub fn or(self, other: Condition<M>) -> Self {
+        Self::new(ConditionExpr::Or(Box::new(self.expr), Box::new(other.expr)))
+    }
+}
+
+impl<M> std::ops::Not for Condition<M> {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::new(ConditionExpr::Not(Box::new(self.expr)))
+    }
+}
+
+/// An ORDER BY term over a column of model `M`
+#[derive(Debug, Clone)]
+pub struct Order<M> {
+    column: &'static str,
+    direction: OrderDirection,
+    _model: PhantomData<fn() -> M>,
+}
+
+impl<M> Order<M> {
+    fn new(column: &'static str, direction: OrderDirection) -> Self {
+        Self {
+            column,
+            direction,
+            _model: PhantomData,
+        }
+    }
+}
+
+/// Placeholder for the bind at `position` (from 1): `$1`, `$2`, ... for
+/// PostgreSQL and `?` elsewhere
+pub(crate) fn placeholder(database: &DatabaseType, position: usize) -> String {
+    match database {
+        DatabaseType::PostgreSQL => format!("${}", position),
+        _ => "?".to_string(),
+    }
+}
+
+/// SQL text with the values of its placeholders
+struct SqlWriter<'a> {
+    database: &'a DatabaseType,
+    sql: String,
+    binds: Vec<Value>,
+}
+
+impl SqlWriter<'_> {
+    /// Add a placeholder for `value`
+    fn bind(&mut self, value: Value) {
+        self.binds.push(value);
+        self.sql.push_str(&placeholder(self.database, self.binds.len()));
+    }
+
+    fn condition(&mut self, expr: &ConditionExpr) {
+        match expr {
+            ConditionExpr::Compare { column, op, value } => {
+                self.sql.push_str(&format!("{} {} ", column, op));
+                self.bind(value.clone());
+            }
+            ConditionExpr::In { values, .. } if values.is_empty() => self.sql.push_str("1 = 0"),
+            ConditionExpr::In { column, values } => {
+                self.sql.push_str(&format!("{} IN (", column));
+                for (position, value) in values.iter().enumerate() {
+                    if position > 0 {
+                        self.sql.push_str(", ");
+                    }
+                    self.bind(value.clone());
+                }
+                self.sql.push(')');
+            }
+            ConditionExpr::IsNull { column, negated } => {
+                let not = if *negated { " NOT" } else { "" };
+                self.sql.push_str(&format!("{} IS{} NULL", column, not));
+            }
+            ConditionExpr::And(left, right) => self.binary(left, "AND", right),
+            ConditionExpr::Or(left, right) => self.binary(left, "OR", right),
+            ConditionExpr::Not(inner) => {
+                self.sql.push_str("NOT (");
+                self.condition(inner);
+                self.sql.push(')');
+            }
+        }
+    }
+
+    fn binary(&mut self, left: &ConditionExpr, op: &str, right: &ConditionExpr) {
+        self.sql.push('(');
+        self.condition(left);
+        self.sql.push_str(&format!(" {} ", op));
+        self.condition(right);
+        self.sql.push(')');
+    }
+}
+
+/// A SELECT of the rows of model `M`
+///
+/// The query reads every column of the model, so its rows convert back
+/// into `M`. Filters are ANDed together.
+#[derive(Debug, Clone)]
+pub struct TypedQuery<M> {
+    filter: Option<ConditionExpr>,
+    order_by: Vec<(&'static str, OrderDirection)>,
+    limit: Option<u64>,
+    offset: Option<u64>,
+    includes: Vec<Include>,
+    _model: PhantomData<fn() -> M>,
+}
+
+impl<M: Model> TypedQuery<M> {
+    pub fn select() -> Self {
+        Self {
+            filter: None,
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+            includes: Vec::new(),
+            _model: PhantomData,
+        }
+    }
+
+    pub fn filter(mut self, condition: Condition<M>) -> Self {
+        self.filter = Some(match self.filter.take() {
+            Some(filter) => ConditionExpr::And(Box::new(filter), Box::new(condition.expr)),
+            None => condition.expr,
+        });
+        self
+    }
+
+    pub fn order_by(mut self, order: Order<M>) -> Self {
+        self.order_by.push((order.column, order.direction));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Load `relation` for the rows of the query with `fetch_loaded`, in
+    /// one query for all of them
+    pub fn include<T: Model>(mut self, relation: Relation<M, T>) -> Self {
+        self.includes.push(Include::new(&relation));
+        self
+    }
+
+    /// The SQL for `database`, and the values of its placeholders in order
+    pub fn to_sql(&self, database: &DatabaseType) -> (String, Vec<Value>) {
+        let columns: Vec<String> = M::columns().into_iter().map(|column| column.name).collect();
+        let mut writer = SqlWriter {
+            database,
+            sql: format!("SELECT {} FROM {}", columns.join(", "), M::TABLE),
+            binds: Vec::new(),
+        };
+        if let Some(filter) = &self.filter {
+            writer.sql.push_str(" WHERE ");
+            writer.condition(filter);
+        }
+        if !self.order_by.is_empty() {
+            let terms: Vec<String> = self
+                .order_by
+                .iter()
+                .map(|(column, direction)| {
+                    let direction = match direction {
+                        OrderDirection::Asc => "ASC",
+                        OrderDirection::Desc => "DESC",
+                    };
+                    format!("{} {}", column, direction)
+                })
+                .collect();
+            writer.sql.push_str(&format!(" ORDER BY {}", terms.join(", ")));
+        }
+        if let Some(limit) = self.limit {
+            writer.sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        if let Some(offset) = self.offset {
+            writer.sql.push_str(&format!(" OFFSET {}", offset));
+        }
+        (writer.sql, writer.binds)
+    }
+
+    /// The query as a step of a [`Query`] program
+    pub fn into_query(self, database: &DatabaseType) -> Query<Vec<QueryRow>> {
+        let (sql, binds) = self.to_sql(database);
+        Query::<Vec<QueryRow>>::raw(sql, binds)
+    }
+
+    /// Run the query on `driver` and convert its rows into models
+    pub async fn fetch<D: Driver>(&self, driver: &D) -> SqlResult<Vec<M>>
+    where
+        D::Row: Row,
+    {
+        let (sql, binds) = self.to_sql(&driver.metadata().database_type);
+        driver
+            .observe(&sql, &binds)
+            .await?
+            .into_iter()
+            .map(|row| M::from_row(row).map_err(|error| SqlError::type_error(error.to_string())))
+            .collect()
+    }
+
+    /// Run the query on `driver` along with its included relations
+    pub async fn fetch_loaded<D: Driver>(&self, driver: &D) -> SqlResult<Vec<Loaded<M>>>
+    where
+        D::Row: Row,
+    {
+        let models = self.fetch(driver).await?;
+        let mut related: Vec<HashMap<&'static str, Vec<DatabaseRow>>> = vec![HashMap::new(); models.len()];
+        for include in &self.includes {
+            let keys = models
+                .iter()
+                .map(|model| include.owner_key(model))
+                .collect::<SqlResult<Vec<_>>>()?;
+            for (position, rows) in include.load(driver, &keys).await?.into_iter().enumerate() {
+                related[position].insert(include.name, rows);
+            }
+        }
+        Ok(models
+            .into_iter()
+            .zip(related)
+            .map(|(model, related)| Loaded::new(model, related))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Model;
+
+    #[derive(Debug, Clone, PartialEq, Model)]
+    #[model(table = "users")]
+    struct User {
+        #[model(primary_key)]
+        id: i64,
+        name: String,
+        age: i64,
+        email: Option<String>,
+    }
+
+    #[test]
+    fn test_typed_select() {
+        let query = TypedQuery::<User>::select()
+            .filter(User::AGE.gt(18))
+            .filter(User::NAME.like("A%").or(!User::EMAIL.is_null()))
+            .order_by(User::AGE.desc())
+            .order_by(User::NAME.asc())
+            .limit(10)
+            .offset(20);
+
+        let (sql, binds) = query.to_sql(&DatabaseType::SQLite);
+        assert_eq!(
+            sql,
+            "SELECT id, name, age, email FROM users WHERE (age > ? AND (name LIKE ? OR NOT (email IS NULL))) \
+             ORDER BY age DESC, name ASC LIMIT 10 OFFSET 20"
+        );
+        assert_eq!(binds, vec![Value::Integer(18), Value::Text("A%".to_string())]);
+
+        let (sql, binds) = TypedQuery::<User>::select()
+            .filter(User::ID.is_in([1, 2]).and(User::EMAIL.eq("o'brien@example.com")))
+            .to_sql(&DatabaseType::PostgreSQL);
+        assert_eq!(sql, "SELECT id, name, age, email FROM users WHERE (id IN ($1, $2) AND email = $3)");
+        assert_eq!(binds.len(), 3);
+
+        let (sql, _) = TypedQuery::<User>::select()
+            .filter(User::ID.is_in(Vec::<i64>::new()))
+            .to_sql(&DatabaseType::SQLite);
+        assert!(sql.ends_with("WHERE 1 = 0"));
+    }
+
+    #[test]
+    fn test_derived_model() {
+        assert_eq!(User::TABLE, "users");
+        let table = User::table_definition();
+        let columns: Vec<(&str, bool, bool)> = table
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.nullable, column.primary_key))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![("id", false, true), ("name", false, false), ("age", false, false), ("email", true, false)]
+        );
+        assert_eq!(table.columns[2].data_type, DataType::Integer);
+
+        let user = User {
+            id: 7,
+            name: "Ann".to_string(),
+            age: 30,
+            email: None,
+        };
+        let values = user.to_row().unwrap();
+        let row = crate::orm::types::DatabaseRow::from_pairs(
+            ["id", "name", "age", "email"]
+                .into_iter()
+                .map(|column| (column.to_string(), values[column].clone()))
+                .collect(),
+        );
+        assert_eq!(User::from_row(row).unwrap(), user);
+    }
+}
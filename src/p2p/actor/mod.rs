@@ -5,11 +5,13 @@
 
 pub mod distributed;
 pub mod migration;
+pub mod placement;
 pub mod registry;
 pub mod supervision;
 
 pub use distributed::*;
 pub use migration::*;
+pub use placement::*;
 pub use registry::*;
 pub use supervision::*;
 
@@ -134,7 +136,16 @@ impl DistributedActorRegistry {
         actor_type: String,
         node_id: NodeId,
     ) -> P2PResult<DistributedActorRef> {
-        let actor_id = ActorId::new();
+        self.spawn_local_actor_with_id(ActorId::new(), actor_type, node_id).await
+    }
+
+    /// Spawn a local actor with a pre-assigned ID (e.g. chosen by placement)
+    pub async fn spawn_local_actor_with_id(
+        &self,
+        actor_id: ActorId,
+        actor_type: String,
+        node_id: NodeId,
+    ) -> P2PResult<DistributedActorRef> {
         let actor = DistributedActor::new(actor_id, node_id, actor_type.clone());
         let actor_ref = actor.get_ref();
 
@@ -0,0 +1,499 @@
+//! Distributed actor placement
+//!
+//! Assigns spawned distributed actors to cluster nodes. The default policy
+//! is a consistent hash ring with virtual nodes, where each node receives a
+//! number of virtual nodes proportional to its capacity. Custom policies can
+//! be plugged in through the `PlacementStrategy` trait.
+
+use crate::p2p::{P2PResult, P2PError, ClusterError, NodeId, NodeInfo, ActorId, PlacementConstraints};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Default number of virtual nodes for a node with the reference capacity
+pub const DEFAULT_VIRTUAL_NODES: usize = 128;
+
+/// Pluggable placement policy for distributed actors
+pub trait PlacementStrategy: Send + Sync {
+    /// Strategy name (for diagnostics)
+    fn name(&self) -> &str;
+
+    /// Rebuild internal state for a new membership view
+    fn update_members(&mut self, members: &[NodeInfo]);
+
+    /// Choose the node that should host `actor_id`
+    fn place(
+        &self,
+        actor_id: ActorId,
+        constraints: Option<&PlacementConstraints>,
+        members: &[NodeInfo],
+    ) -> P2PResult<NodeId>;
+}
+
+/// Hash an arbitrary byte key onto the ring (64-bit FNV-1a)
+///
+/// FNV is used instead of `DefaultHasher` so that every node in the cluster
+/// computes identical ring positions regardless of Rust version.
+pub fn ring_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    // Final avalanche so that similar keys spread across the ring
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash
+}
+
+/// Consistent hash ring with weighted virtual nodes
+#[derive(Debug, Clone, Default)]
+pub struct ConsistentHashRing {
+    /// Ring positions mapped to owning node
+    ring: BTreeMap<u64, NodeId>,
+    /// Number of virtual nodes per physical node
+    weights: HashMap<NodeId, usize>,
+}
+
+impl ConsistentHashRing {
+    /// Create an empty ring
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node with the given number of virtual nodes
+    pub fn add_node(&mut self, node_id: NodeId, virtual_nodes: usize) {
+        self.remove_node(node_id);
+        let virtual_nodes = virtual_nodes.max(1);
+        for replica in 0..virtual_nodes {
+            let mut key = node_id.as_bytes().to_vec();
+            key.extend_from_slice(&(replica as u64).to_le_bytes());
+            self.ring.insert(ring_hash(&key), node_id);
+        }
+        self.weights.insert(node_id, virtual_nodes);
+    }
+
+    /// Remove a node and all of its virtual nodes
+    pub fn remove_node(&mut self, node_id: NodeId) {
+        if self.weights.remove(&node_id).is_some() {
+            self.ring.retain(|_, owner| *owner != node_id);
+        }
+    }
+
+    /// Check whether the ring contains a node
+    pub fn contains(&self, node_id: NodeId) -> bool {
+        self.weights.contains_key(&node_id)
+    }
+
+    /// Number of physical nodes on the ring
+    pub fn node_count(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Number of virtual nodes on the ring
+    pub fn virtual_node_count(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Locate the owner of a key
+    pub fn locate(&self, key: &[u8]) -> Option<NodeId> {
+        self.successors(key).next()
+    }
+
+    /// Locate the first owner of a key that satisfies `accept`
+    pub fn locate_where<F>(&self, key: &[u8], mut accept: F) -> Option<NodeId>
+    where
+        F: FnMut(NodeId) -> bool,
+    {
+        let mut seen = Vec::new();
+        for node_id in self.successors(key) {
+            if seen.contains(&node_id) {
+                continue;
+            }
+            if accept(node_id) {
+                return Some(node_id);
+            }
+            seen.push(node_id);
+            if seen.len() == self.weights.len() {
+                break;
+            }
+        }
+        None
+    }
+
+    /// Walk the ring clockwise starting at the position of `key`
+    fn successors<'a>(&'a self, key: &[u8]) -> impl Iterator<Item = NodeId> + 'a {
+        let position = ring_hash(key);
+        self.ring
+            .range(position..)
+            .chain(self.ring.range(..position))
+            .map(|(_, node_id)| *node_id)
+    }
+}
+
+/// Default placement strategy backed by a consistent hash ring
+///
+/// Nodes are weighted by `max_actors`, relative to the default node
+/// capacity, so a node that can host twice as many actors owns roughly
+/// twice as much of the key space.
+#[derive(Debug, Clone)]
+pub struct ConsistentHashPlacement {
+    ring: ConsistentHashRing,
+    /// Virtual nodes assigned to a node of reference capacity
+    base_virtual_nodes: usize,
+    /// Reference capacity (in `max_actors`) that maps to `base_virtual_nodes`
+    reference_capacity: usize,
+}
+
+impl ConsistentHashPlacement {
+    /// Create a new consistent hash placement strategy
+    pub fn new(base_virtual_nodes: usize) -> Self {
+        Self {
+            ring: ConsistentHashRing::new(),
+            base_virtual_nodes: base_virtual_nodes.max(1),
+            reference_capacity: crate::p2p::NodeCapabilities::default().max_actors,
+        }
+    }
+
+    /// Access the underlying ring
+    pub fn ring(&self) -> &ConsistentHashRing {
+        &self.ring
+    }
+
+    /// Number of virtual nodes a member receives based on its capacity
+    pub fn virtual_nodes_for(&self, node: &NodeInfo) -> usize {
+        let capacity = node.capabilities.max_actors.max(1) as f64;
+        let ratio = capacity / self.reference_capacity.max(1) as f64;
+        ((self.base_virtual_nodes as f64 * ratio).round() as usize).max(1)
+    }
+}
+
+impl Default for ConsistentHashPlacement {
+    fn default() -> Self {
+        Self::new(DEFAULT_VIRTUAL_NODES)
+    }
+}
+
+impl PlacementStrategy for ConsistentHashPlacement {
+    fn name(&self) -> &str {
+        "consistent-hash"
+    }
+
+    fn update_members(&mut self, members: &[NodeInfo]) {
+        let mut ring = ConsistentHashRing::new();
+        for member in members {
+            ring.add_node(member.node_id, self.virtual_nodes_for(member));
+        }
+        self.ring = ring;
+    }
+
+    fn place(
+        &self,
+        actor_id: ActorId,
+        constraints: Option<&PlacementConstraints>,
+        members: &[NodeInfo],
+    ) -> P2PResult<NodeId> {
+        let candidates: HashMap<NodeId, &NodeInfo> = members
+            .iter()
+            .filter(|member| constraints.is_none_or(|c| satisfies(member, c)))
+            .map(|member| (member.node_id, member))
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(P2PError::Cluster(ClusterError::InsufficientNodes));
+        }
+
+        if let Some(node_id) = constraints.and_then(|c| c.node_id) {
+            return Ok(node_id);
+        }
+
+        self.ring
+            .locate_where(actor_id.0.as_bytes(), |node_id| candidates.contains_key(&node_id))
+            .ok_or(P2PError::Cluster(ClusterError::InsufficientNodes))
+    }
+}
+
+/// Check whether a node satisfies placement constraints
+pub fn satisfies(node: &NodeInfo, constraints: &PlacementConstraints) -> bool {
+    if let Some(node_id) = constraints.node_id {
+        if node.node_id != node_id {
+            return false;
+        }
+    }
+    if let Some(node_type) = &constraints.node_type {
+        if &node.capabilities.node_type != node_type {
+            return false;
+        }
+    }
+    if let Some(min_memory) = constraints.min_memory {
+        if node.capabilities.available_memory < min_memory {
+            return false;
+        }
+    }
+    if let Some(min_cpu_cores) = constraints.min_cpu_cores {
+        if node.capabilities.cpu_cores < min_cpu_cores {
+            return false;
+        }
+    }
+    constraints
+        .custom
+        .iter()
+        .all(|(key, value)| node.capabilities.custom.get(key) == Some(value))
+}
+
+/// A single actor move produced by rebalancing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebalanceMove {
+    /// Actor to move
+    pub actor_id: ActorId,
+    /// Node currently hosting the actor
+    pub from: NodeId,
+    /// Node that should host the actor
+    pub to: NodeId,
+}
+
+/// Tracks actor placements and recomputes them on membership change
+pub struct PlacementManager {
+    /// Active placement strategy
+    strategy: Box<dyn PlacementStrategy>,
+    /// Current cluster members
+    members: Vec<NodeInfo>,
+    /// Current actor assignments with the constraints they were placed under
+    assignments: HashMap<ActorId, (NodeId, Option<PlacementConstraints>)>,
+}
+
+impl fmt::Debug for PlacementManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlacementManager")
+            .field("strategy", &self.strategy.name())
+            .field("members", &self.members.len())
+            .field("assignments", &self.assignments.len())
+            .finish()
+    }
+}
+
+impl PlacementManager {
+    /// Create a placement manager using the default consistent hash strategy
+    pub fn new() -> Self {
+        Self::with_strategy(Box::new(ConsistentHashPlacement::default()))
+    }
+
+    /// Create a placement manager with a custom strategy
+    pub fn with_strategy(strategy: Box<dyn PlacementStrategy>) -> Self {
+        Self {
+            strategy,
+            members: Vec::new(),
+            assignments: HashMap::new(),
+        }
+    }
+
+    /// Replace the placement strategy and return the moves it implies
+    pub fn set_strategy(&mut self, mut strategy: Box<dyn PlacementStrategy>) -> Vec<RebalanceMove> {
+        strategy.update_members(&self.members);
+        self.strategy = strategy;
+        self.rebalance()
+    }
+
+    /// Name of the active strategy
+    pub fn strategy_name(&self) -> &str {
+        self.strategy.name()
+    }
+
+    /// Current members known to the placement manager
+    pub fn members(&self) -> &[NodeInfo] {
+        &self.members
+    }
+
+    /// Choose a node for a new actor and record the assignment
+    pub fn place(
+        &mut self,
+        actor_id: ActorId,
+        constraints: Option<PlacementConstraints>,
+    ) -> P2PResult<NodeId> {
+        let node_id = self.strategy.place(actor_id, constraints.as_ref(), &self.members)?;
+        self.assignments.insert(actor_id, (node_id, constraints));
+        Ok(node_id)
+    }
+
+    /// Record an assignment made outside the strategy (e.g. after migration)
+    pub fn record(&mut self, actor_id: ActorId, node_id: NodeId) {
+        let constraints = self.assignments.remove(&actor_id).and_then(|(_, c)| c);
+        self.assignments.insert(actor_id, (node_id, constraints));
+    }
+
+    /// Forget an actor
+    pub fn remove(&mut self, actor_id: ActorId) {
+        self.assignments.remove(&actor_id);
+    }
+
+    /// Node currently assigned to an actor
+    pub fn node_for(&self, actor_id: ActorId) -> Option<NodeId> {
+        self.assignments.get(&actor_id).map(|(node_id, _)| *node_id)
+    }
+
+    /// Number of actors assigned to each node
+    pub fn distribution(&self) -> HashMap<NodeId, usize> {
+        let mut distribution = HashMap::new();
+        for (node_id, _) in self.assignments.values() {
+            *distribution.entry(*node_id).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    /// Apply a new membership view and return the actor moves it requires
+    ///
+    /// Assignments are updated eagerly; callers are expected to drive the
+    /// returned moves through the migration manager.
+    pub fn update_members(&mut self, members: Vec<NodeInfo>) -> Vec<RebalanceMove> {
+        self.members = members;
+        self.strategy.update_members(&self.members);
+        self.rebalance()
+    }
+
+    /// Recompute every assignment against the current strategy
+    fn rebalance(&mut self) -> Vec<RebalanceMove> {
+        let mut moves = Vec::new();
+        for (actor_id, (node_id, constraints)) in self.assignments.iter_mut() {
+            let target = match self.strategy.place(*actor_id, constraints.as_ref(), &self.members) {
+                Ok(target) => target,
+                // Keep the current assignment when no node can satisfy it
+                Err(_) => continue,
+            };
+            if target != *node_id {
+                moves.push(RebalanceMove {
+                    actor_id: *actor_id,
+                    from: *node_id,
+                    to: target,
+                });
+                *node_id = target;
+            }
+        }
+        moves
+    }
+}
+
+impl Default for PlacementManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn node(max_actors: usize) -> NodeInfo {
+        let mut info = NodeInfo::new(NodeId::new(), "127.0.0.1:0".parse::<SocketAddr>().unwrap());
+        info.capabilities.max_actors = max_actors;
+        info
+    }
+
+    #[test]
+    fn test_ring_is_deterministic() {
+        let members = vec![node(10000), node(10000), node(10000)];
+        let mut a = ConsistentHashPlacement::default();
+        let mut b = ConsistentHashPlacement::default();
+        a.update_members(&members);
+        b.update_members(&members);
+
+        for _ in 0..100 {
+            let actor_id = ActorId::new();
+            assert_eq!(
+                a.place(actor_id, None, &members).unwrap(),
+                b.place(actor_id, None, &members).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_weight_by_capacity() {
+        let small = node(10000);
+        let large = node(30000);
+        let members = vec![small.clone(), large.clone()];
+
+        let mut manager = PlacementManager::new();
+        manager.update_members(members);
+        for _ in 0..4000 {
+            manager.place(ActorId::new(), None).unwrap();
+        }
+
+        let distribution = manager.distribution();
+        let small_count = distribution[&small.node_id] as f64;
+        let large_count = distribution[&large.node_id] as f64;
+        assert!(large_count / small_count > 2.0, "ratio was {}", large_count / small_count);
+    }
+
+    #[test]
+    fn test_rebalance_moves_minimal_actors() {
+        let members = vec![node(10000), node(10000), node(10000)];
+        let mut manager = PlacementManager::new();
+        manager.update_members(members.clone());
+        for _ in 0..1000 {
+            manager.place(ActorId::new(), None).unwrap();
+        }
+
+        let newcomer = node(10000);
+        let mut grown = members.clone();
+        grown.push(newcomer.clone());
+        let moves = manager.update_members(grown);
+
+        // Only actors claimed by the new node should move
+        assert!(moves.iter().all(|m| m.to == newcomer.node_id));
+        assert!(moves.len() < 500);
+        assert_eq!(manager.distribution()[&newcomer.node_id], moves.len());
+    }
+
+    #[test]
+    fn test_constraints_filter_candidates() {
+        let mut storage = node(10000);
+        storage.capabilities.node_type = crate::p2p::NodeType::Storage;
+        let members = vec![node(10000), storage.clone(), node(10000)];
+
+        let mut manager = PlacementManager::new();
+        manager.update_members(members);
+        let constraints = PlacementConstraints::node_type(crate::p2p::NodeType::Storage);
+        for _ in 0..50 {
+            let placed = manager.place(ActorId::new(), Some(constraints.clone())).unwrap();
+            assert_eq!(placed, storage.node_id);
+        }
+    }
+
+    #[test]
+    fn test_empty_membership_fails() {
+        let mut manager = PlacementManager::new();
+        assert!(manager.place(ActorId::new(), None).is_err());
+    }
+
+    struct FirstNode;
+
+    impl PlacementStrategy for FirstNode {
+        fn name(&self) -> &str {
+            "first-node"
+        }
+
+        fn update_members(&mut self, _members: &[NodeInfo]) {}
+
+        fn place(
+            &self,
+            _actor_id: ActorId,
+            _constraints: Option<&PlacementConstraints>,
+            members: &[NodeInfo],
+        ) -> P2PResult<NodeId> {
+            members
+                .first()
+                .map(|m| m.node_id)
+                .ok_or(P2PError::Cluster(ClusterError::InsufficientNodes))
+        }
+    }
+
+    #[test]
+    fn test_custom_strategy() {
+        let members = vec![node(10000), node(10000)];
+        let mut manager = PlacementManager::with_strategy(Box::new(FirstNode));
+        manager.update_members(members.clone());
+        assert_eq!(manager.strategy_name(), "first-node");
+        assert_eq!(manager.place(ActorId::new(), None).unwrap(), members[0].node_id);
+    }
+}
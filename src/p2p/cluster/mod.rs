@@ -7,15 +7,19 @@ pub mod membership;
 pub mod failure_detection;
 pub mod recovery;
 pub mod metrics;
+pub mod topology;
 
 pub use membership::*;
 pub use failure_detection::*;
 pub use recovery::*;
 pub use metrics::*;
+pub use topology::*;
 
 use crate::p2p::{P2PResult, P2PError, ClusterError, NodeId, NodeInfo, ClusterInfo, ClusterHealth};
+use crate::p2p::kv::{DistributedKv, Leadership, LockLease};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use serde::{Deserialize, Serialize};
 
@@ -32,6 +36,10 @@ pub struct ClusterManager {
     recovery: Arc<RwLock<RecoveryManager>>,
     /// Cluster metrics
     metrics: Arc<RwLock<ClusterMetrics>>,
+    /// Region/zone view of the cluster
+    topology: Arc<RwLock<ClusterTopology>>,
+    /// Replicated store locks and elections are held in
+    kv: Option<DistributedKv>,
     /// Configuration
     config: ClusterConfig,
 }
@@ -43,6 +51,7 @@ impl ClusterManager {
         let failure_detector = Arc::new(RwLock::new(FailureDetector::new(config.failure_detection_config.clone())));
         let recovery = Arc::new(RwLock::new(RecoveryManager::new(config.recovery_config.clone())));
         let metrics = Arc::new(RwLock::new(ClusterMetrics::new()));
+        let topology = Arc::new(RwLock::new(ClusterTopology::new(&local_node)));
 
         Ok(Self {
             local_node,
@@ -50,10 +59,18 @@ impl ClusterManager {
             failure_detector,
             recovery,
             metrics,
+            topology,
+            kv: None,
             config,
         })
     }
 
+    /// Hold distributed locks and leader elections in `kv`
+    pub fn with_kv(mut self, kv: DistributedKv) -> Self {
+        self.kv = Some(kv);
+        self
+    }
+
     /// Start the cluster manager
     pub async fn start(&self) -> P2PResult<()> {
         // Start all components
@@ -104,6 +121,13 @@ impl ClusterManager {
 
     /// Join an existing cluster
     pub async fn join_cluster(&self, bootstrap_nodes: Vec<NodeInfo>) -> P2PResult<ClusterInfo> {
+        {
+            let mut topology = self.topology.write().await;
+            for node in &bootstrap_nodes {
+                topology.update_node(node);
+            }
+        }
+
         let mut membership = self.membership.write().await;
         membership.join_cluster(bootstrap_nodes).await
     }
@@ -116,22 +140,28 @@ impl ClusterManager {
 
     /// Add a node to the cluster
     pub async fn add_node(&self, node_info: NodeInfo) -> P2PResult<()> {
-        let mut membership = self.membership.write().await;
-        membership.add_member(node_info).await
+        self.add_member(node_info).await
     }
 
     /// Add a member to the cluster
     pub async fn add_member(&self, node_info: NodeInfo) -> P2PResult<()> {
+        self.topology.write().await.update_node(&node_info);
         let mut membership = self.membership.write().await;
         membership.add_member(node_info).await
     }
 
     /// Remove a node from the cluster
     pub async fn remove_node(&self, node_id: NodeId) -> P2PResult<()> {
+        self.topology.write().await.remove_node(node_id);
         let mut membership = self.membership.write().await;
         membership.remove_member(node_id).await
     }
 
+    /// Get the region/zone view of the cluster
+    pub async fn get_topology(&self) -> ClusterTopology {
+        self.topology.read().await.clone()
+    }
+
     /// Get current cluster information
     pub async fn get_cluster_info(&self) -> P2PResult<ClusterInfo> {
         let membership = self.membership.read().await;
@@ -159,9 +189,42 @@ impl ClusterManager {
         let mut recovery = self.recovery.write().await;
         recovery.handle_node_failure(node_id).await?;
 
+        // Free the failed node's locks instead of waiting out their leases
+        if let Some(kv) = &self.kv {
+            kv.release_node_locks(node_id).await?;
+        }
+
         Ok(())
     }
 
+    /// Take or renew a distributed lock for `owner` on this node
+    ///
+    /// Returns `None` if another holder's lease is still live.
+    pub async fn acquire_lock(&self, name: &str, owner: &str, ttl: Duration) -> P2PResult<Option<LockLease>> {
+        self.lock_store()?.acquire_lock(name, owner, ttl).await
+    }
+
+    /// Release a distributed lock held under a fencing token
+    pub async fn release_lock(&self, name: &str, token: u64) -> P2PResult<bool> {
+        self.lock_store()?.release_lock(name, token).await
+    }
+
+    /// Current live lease of a distributed lock
+    pub async fn lock_holder(&self, name: &str) -> P2PResult<Option<LockLease>> {
+        Ok(self.lock_store()?.lock_holder(name).await)
+    }
+
+    /// Stand for (or renew) leadership of `group`, returning the leader
+    pub async fn elect_leader(&self, group: &str, ttl: Duration) -> P2PResult<Leadership> {
+        self.lock_store()?.elect_leader(group, ttl).await
+    }
+
+    fn lock_store(&self) -> P2PResult<&DistributedKv> {
+        self.kv.as_ref().ok_or_else(|| {
+            P2PError::Configuration("cluster manager has no replicated store for locks".to_string())
+        })
+    }
+
     /// Get cluster health status
     pub async fn get_health_status(&self) -> ClusterHealth {
         let membership = self.membership.read().await;
@@ -0,0 +1,372 @@
+//! WAN-aware cluster topology
+//!
+//! Tracks the region/zone of every cluster member so that routing can
+//! prefer nearby replicas, gossip to remote datacenters can be batched,
+//! and Raft voters can be spread across failure domains.
+
+use crate::p2p::{NodeId, NodeInfo, NodeLocality};
+use crate::p2p::consensus::ClusterMembership;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// Network distance between two nodes, ordered from nearest to farthest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LocalityTier {
+    /// Same region and zone
+    SameZone,
+    /// Same region, different zone
+    SameRegion,
+    /// Different region (cross-datacenter link)
+    CrossRegion,
+}
+
+impl LocalityTier {
+    /// Compute the tier between two localities
+    pub fn between(a: &NodeLocality, b: &NodeLocality) -> Self {
+        if a.same_zone(b) {
+            LocalityTier::SameZone
+        } else if a.same_region(b) {
+            LocalityTier::SameRegion
+        } else {
+            LocalityTier::CrossRegion
+        }
+    }
+}
+
+/// Locality view of the cluster from the perspective of one node
+#[derive(Debug, Clone)]
+pub struct ClusterTopology {
+    /// Local node
+    local_node: NodeId,
+    /// Local node's locality
+    local_locality: NodeLocality,
+    /// Locality of every known node
+    localities: HashMap<NodeId, NodeLocality>,
+}
+
+impl ClusterTopology {
+    /// Create a topology view for the local node
+    pub fn new(local: &NodeInfo) -> Self {
+        let mut localities = HashMap::new();
+        localities.insert(local.node_id, local.locality.clone());
+        Self {
+            local_node: local.node_id,
+            local_locality: local.locality.clone(),
+            localities,
+        }
+    }
+
+    /// Record or update a member's locality
+    pub fn update_node(&mut self, node: &NodeInfo) {
+        self.localities.insert(node.node_id, node.locality.clone());
+    }
+
+    /// Forget a member
+    pub fn remove_node(&mut self, node_id: NodeId) {
+        if node_id != self.local_node {
+            self.localities.remove(&node_id);
+        }
+    }
+
+    /// Locality of a node, if known
+    pub fn locality_of(&self, node_id: NodeId) -> Option<&NodeLocality> {
+        self.localities.get(&node_id)
+    }
+
+    /// Distance tier from the local node; unknown nodes are assumed remote
+    pub fn tier_to(&self, node_id: NodeId) -> LocalityTier {
+        self.localities
+            .get(&node_id)
+            .map(|locality| LocalityTier::between(&self.local_locality, locality))
+            .unwrap_or(LocalityTier::CrossRegion)
+    }
+
+    /// Whether a node is reached over a cross-region link
+    pub fn is_remote_region(&self, node_id: NodeId) -> bool {
+        self.tier_to(node_id) == LocalityTier::CrossRegion
+    }
+
+    /// Sort candidate nodes from nearest to farthest (stable within a tier)
+    pub fn order_by_proximity(&self, candidates: &[NodeId]) -> Vec<NodeId> {
+        let mut ordered = candidates.to_vec();
+        ordered.sort_by_key(|node_id| self.tier_to(*node_id));
+        ordered
+    }
+
+    /// Pick the nearest replica among candidates
+    pub fn select_replica(&self, candidates: &[NodeId]) -> Option<NodeId> {
+        candidates.iter().copied().min_by_key(|node_id| self.tier_to(*node_id))
+    }
+
+    /// Group known nodes by (region, zone)
+    pub fn nodes_by_zone(&self) -> BTreeMap<String, Vec<NodeId>> {
+        let mut zones: BTreeMap<String, Vec<NodeId>> = BTreeMap::new();
+        for (node_id, locality) in &self.localities {
+            zones.entry(locality.to_string()).or_default().push(*node_id);
+        }
+        zones
+    }
+}
+
+/// Batches gossip updates destined for other regions
+///
+/// Updates for nodes in the local region are delivered immediately; updates
+/// crossing a region boundary are buffered per destination region and
+/// released once the batch is full or the oldest update is too old.
+#[derive(Debug)]
+pub struct GossipBatcher<T> {
+    /// Maximum updates per cross-region batch
+    max_batch_size: usize,
+    /// Maximum time an update may wait in a batch
+    max_delay: Duration,
+    /// Pending updates per destination region
+    pending: HashMap<Option<String>, PendingBatch<T>>,
+}
+
+#[derive(Debug)]
+struct PendingBatch<T> {
+    started: Instant,
+    updates: Vec<T>,
+}
+
+/// A batch of gossip updates ready to be sent to a region
+#[derive(Debug, Clone, PartialEq)]
+pub struct GossipBatch<T> {
+    /// Destination region (`None` for unlabelled nodes)
+    pub region: Option<String>,
+    /// Updates in the batch
+    pub updates: Vec<T>,
+}
+
+impl<T> GossipBatcher<T> {
+    /// Create a new batcher
+    pub fn new(max_batch_size: usize, max_delay: Duration) -> Self {
+        Self {
+            max_batch_size: max_batch_size.max(1),
+            max_delay,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Queue an update for a destination; returns a batch when one is ready
+    pub fn enqueue(&mut self, topology: &ClusterTopology, destination: NodeId, update: T) -> Option<GossipBatch<T>> {
+        let region = topology
+            .locality_of(destination)
+            .and_then(|locality| locality.region.clone());
+
+        if !topology.is_remote_region(destination) {
+            return Some(GossipBatch { region, updates: vec![update] });
+        }
+
+        let batch = self.pending.entry(region.clone()).or_insert_with(|| PendingBatch {
+            started: Instant::now(),
+            updates: Vec::new(),
+        });
+        batch.updates.push(update);
+
+        if batch.updates.len() >= self.max_batch_size {
+            self.pending
+                .remove(&region)
+                .map(|batch| GossipBatch { region, updates: batch.updates })
+        } else {
+            None
+        }
+    }
+
+    /// Release every batch whose oldest update exceeded the maximum delay
+    pub fn flush_expired(&mut self) -> Vec<GossipBatch<T>> {
+        let expired: Vec<Option<String>> = self
+            .pending
+            .iter()
+            .filter(|(_, batch)| batch.started.elapsed() >= self.max_delay)
+            .map(|(region, _)| region.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|region| {
+                self.pending
+                    .remove(&region)
+                    .map(|batch| GossipBatch { region, updates: batch.updates })
+            })
+            .collect()
+    }
+
+    /// Release all pending batches (e.g. on shutdown)
+    pub fn flush_all(&mut self) -> Vec<GossipBatch<T>> {
+        self.pending
+            .drain()
+            .map(|(region, batch)| GossipBatch { region, updates: batch.updates })
+            .collect()
+    }
+
+    /// Number of updates waiting to be sent
+    pub fn pending_count(&self) -> usize {
+        self.pending.values().map(|batch| batch.updates.len()).sum()
+    }
+}
+
+/// How Raft voters are chosen from the cluster members
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum VoterPlacement {
+    /// Every member votes
+    #[default]
+    AllMembers,
+    /// Spread at most `max_voters` voters round-robin across zones;
+    /// remaining members join as non-voting observers
+    PerZone { max_voters: usize },
+}
+
+/// Build a Raft membership according to the voter placement policy
+///
+/// With `PerZone`, voters are taken one per zone in turn so that losing a
+/// single zone never removes more than `ceil(max_voters / zones)` voters.
+/// The voter count is kept odd to avoid split votes.
+pub fn build_voter_membership(members: &[NodeInfo], placement: &VoterPlacement) -> ClusterMembership {
+    let node_ids: Vec<NodeId> = members.iter().map(|m| m.node_id).collect();
+    let max_voters = match placement {
+        VoterPlacement::AllMembers => return ClusterMembership::new(node_ids),
+        VoterPlacement::PerZone { max_voters } => *max_voters,
+    };
+
+    let mut zones: BTreeMap<String, Vec<NodeId>> = BTreeMap::new();
+    let mut sorted: Vec<&NodeInfo> = members.iter().collect();
+    sorted.sort_by_key(|m| m.node_id.0);
+    for member in sorted {
+        zones.entry(member.locality.to_string()).or_default().push(member.node_id);
+    }
+
+    let mut target = max_voters.min(members.len()).max(1);
+    if target % 2 == 0 && target > 1 {
+        target -= 1;
+    }
+
+    let mut voters = Vec::with_capacity(target);
+    let mut round = 0;
+    while voters.len() < target {
+        let mut picked = false;
+        for zone_members in zones.values() {
+            if voters.len() == target {
+                break;
+            }
+            if let Some(node_id) = zone_members.get(round) {
+                voters.push(*node_id);
+                picked = true;
+            }
+        }
+        if !picked {
+            break;
+        }
+        round += 1;
+    }
+
+    let mut membership = ClusterMembership::new(voters.clone());
+    for node_id in node_ids {
+        if !voters.contains(&node_id) {
+            membership.add_member(node_id, false);
+        }
+    }
+    membership
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn node(region: &str, zone: &str) -> NodeInfo {
+        NodeInfo::new(NodeId::new(), "127.0.0.1:0".parse::<SocketAddr>().unwrap())
+            .with_locality(NodeLocality::new(region, zone))
+    }
+
+    #[test]
+    fn test_locality_tiers() {
+        let local = node("us-east", "a");
+        let same_zone = node("us-east", "a");
+        let same_region = node("us-east", "b");
+        let remote = node("eu-west", "a");
+
+        let mut topology = ClusterTopology::new(&local);
+        for n in [&same_zone, &same_region, &remote] {
+            topology.update_node(n);
+        }
+
+        assert_eq!(topology.tier_to(same_zone.node_id), LocalityTier::SameZone);
+        assert_eq!(topology.tier_to(same_region.node_id), LocalityTier::SameRegion);
+        assert_eq!(topology.tier_to(remote.node_id), LocalityTier::CrossRegion);
+        assert_eq!(topology.tier_to(NodeId::new()), LocalityTier::CrossRegion);
+
+        let ordered = topology.order_by_proximity(&[remote.node_id, same_region.node_id, same_zone.node_id]);
+        assert_eq!(ordered, vec![same_zone.node_id, same_region.node_id, remote.node_id]);
+        assert_eq!(
+            topology.select_replica(&[remote.node_id, same_region.node_id]),
+            Some(same_region.node_id)
+        );
+    }
+
+    #[test]
+    fn test_gossip_batching() {
+        let local = node("us-east", "a");
+        let peer = node("us-east", "b");
+        let remote = node("eu-west", "a");
+        let mut topology = ClusterTopology::new(&local);
+        topology.update_node(&peer);
+        topology.update_node(&remote);
+
+        let mut batcher = GossipBatcher::new(3, Duration::from_secs(60));
+
+        // Same-region updates bypass batching
+        let batch = batcher.enqueue(&topology, peer.node_id, 1).unwrap();
+        assert_eq!(batch.updates, vec![1]);
+
+        assert!(batcher.enqueue(&topology, remote.node_id, 2).is_none());
+        assert!(batcher.enqueue(&topology, remote.node_id, 3).is_none());
+        assert_eq!(batcher.pending_count(), 2);
+        let batch = batcher.enqueue(&topology, remote.node_id, 4).unwrap();
+        assert_eq!(batch.region.as_deref(), Some("eu-west"));
+        assert_eq!(batch.updates, vec![2, 3, 4]);
+        assert_eq!(batcher.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_gossip_flush_expired() {
+        let local = node("us-east", "a");
+        let remote = node("eu-west", "a");
+        let mut topology = ClusterTopology::new(&local);
+        topology.update_node(&remote);
+
+        let mut batcher = GossipBatcher::new(100, Duration::ZERO);
+        assert!(batcher.enqueue(&topology, remote.node_id, "update").is_none());
+        let batches = batcher.flush_expired();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].updates, vec!["update"]);
+    }
+
+    #[test]
+    fn test_voters_spread_across_zones() {
+        let members = vec![
+            node("us-east", "a"), node("us-east", "a"), node("us-east", "a"),
+            node("us-east", "b"), node("us-east", "b"),
+            node("eu-west", "a"),
+        ];
+
+        let membership = build_voter_membership(&members, &VoterPlacement::PerZone { max_voters: 4 });
+        assert_eq!(membership.voting_members.len(), 3);
+        assert_eq!(membership.observer_members.len(), 3);
+        assert_eq!(membership.size(), 6);
+
+        // One voter from each zone
+        let mut voter_zones: Vec<String> = membership
+            .voting_members
+            .iter()
+            .map(|id| members.iter().find(|m| m.node_id == *id).unwrap().locality.to_string())
+            .collect();
+        voter_zones.sort();
+        voter_zones.dedup();
+        assert_eq!(voter_zones.len(), 3);
+
+        let all = build_voter_membership(&members, &VoterPlacement::AllMembers);
+        assert_eq!(all.voting_members.len(), 6);
+    }
+}
@@ -12,7 +12,8 @@ pub use pbft::*;
 pub use raft::*;
 pub use common::*;
 
-use crate::p2p::{P2PResult, P2PError, ConsensusError, NodeId, ConsensusAlgorithm};
+use crate::p2p::{P2PResult, P2PError, ConsensusError, NodeId, NodeInfo, ConsensusAlgorithm};
+use crate::p2p::cluster::{VoterPlacement, build_voter_membership};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::{Deserialize, Serialize};
@@ -227,6 +228,19 @@ impl ConsensusEngine {
         }
     }
 
+    /// Reconfigure consensus membership from the current cluster members
+    ///
+    /// Voters are chosen according to `voter_placement`; PBFT requires every
+    /// replica to participate and ignores the placement policy.
+    pub async fn apply_cluster_members(&self, members: &[NodeInfo]) -> P2PResult<ClusterMembership> {
+        let membership = build_voter_membership(members, &self.config.voter_placement);
+        if let Some(raft) = &self.raft {
+            let mut raft = raft.write().await;
+            raft.set_membership(membership.clone());
+        }
+        Ok(membership)
+    }
+
     /// Get consensus statistics
     pub async fn get_stats(&self) -> ConsensusStats {
         let mut stats = self.stats.read().await.clone();
@@ -267,6 +281,8 @@ pub struct ConsensusConfig {
     pub proposal_timeout: std::time::Duration,
     /// Maximum number of concurrent proposals
     pub max_concurrent_proposals: usize,
+    /// How Raft voters are chosen across zones
+    pub voter_placement: VoterPlacement,
 }
 
 impl Default for ConsensusConfig {
@@ -277,6 +293,7 @@ impl Default for ConsensusConfig {
             raft_config: RaftConfig::default(),
             proposal_timeout: std::time::Duration::from_secs(30),
             max_concurrent_proposals: 10,
+            voter_placement: VoterPlacement::default(),
         }
     }
 }
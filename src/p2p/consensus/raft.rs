@@ -116,6 +116,16 @@ impl RaftConsensus {
         })
     }
 
+    /// Replace the cluster membership (voters and observers)
+    pub fn set_membership(&mut self, membership: ClusterMembership) {
+        self.cluster = membership;
+    }
+
+    /// Current cluster membership
+    pub fn membership(&self) -> &ClusterMembership {
+        &self.cluster
+    }
+
     /// Start the Raft consensus
     pub async fn start(&mut self) -> P2PResult<()> {
         // Initialize as follower
@@ -332,7 +342,7 @@ impl RaftConsensus {
 
             if let Some(entry) = self.log.get((self.last_applied - 1) as usize) {
                 // In a real implementation, we would apply this to the state machine
-                println!("Applied entry {} to state machine: {:?}", entry.index, entry.value);
+                tracing::debug!(index = entry.index, value = ?entry.value, "Applied entry to state machine");
             }
         }
 
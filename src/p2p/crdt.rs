@@ -0,0 +1,813 @@
+//! Conflict-free replicated data types
+//!
+//! State-based CRDTs for shared state that must stay writable through network
+//! partitions. Every local update returns a *delta*, a small state of the same
+//! type that merges like any other state. A [`CrdtReplica`] buffers the deltas
+//! of its named CRDTs for the gossip layer, which ships them to a few peers per
+//! round; peers merge them and pass on whatever was new to them.
+//!
+//! - [`GCounter`]: grow-only counter
+//! - [`PNCounter`]: counter that also supports decrements
+//! - [`OrSet`]: observed-remove set, where adds win over concurrent removes
+//! - [`LwwRegister`]: last-writer-wins register
+//! - [`Rga`]: replicated growable array for collaboratively edited text
+
+use crate::p2p::{P2PResult, P2PError};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Replica used by TLisp builtins and gossip receivers
+static GLOBAL_REPLICA: Lazy<parking_lot::RwLock<Option<Arc<CrdtReplica>>>> =
+    Lazy::new(|| parking_lot::RwLock::new(None));
+
+/// Identifier of the replica that made an update (the node id as a string)
+pub type ReplicaId = String;
+
+/// Grow-only counter
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GCounter {
+    /// Count contributed by each replica
+    counts: BTreeMap<ReplicaId, u64>,
+}
+
+impl GCounter {
+    /// Create a zero counter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `amount` on behalf of `replica`, returning the delta
+    pub fn increment(&mut self, replica: &str, amount: u64) -> GCounter {
+        let count = self.counts.entry(replica.to_string()).or_insert(0);
+        *count += amount;
+        GCounter { counts: BTreeMap::from([(replica.to_string(), *count)]) }
+    }
+
+    /// Current total
+    pub fn value(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Merge another state or delta
+    pub fn merge(&mut self, other: &GCounter) {
+        for (replica, count) in &other.counts {
+            let current = self.counts.entry(replica.clone()).or_insert(0);
+            *current = (*current).max(*count);
+        }
+    }
+}
+
+/// Counter supporting increments and decrements
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PNCounter {
+    /// Increments
+    positive: GCounter,
+    /// Decrements
+    negative: GCounter,
+}
+
+impl PNCounter {
+    /// Create a zero counter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `amount` on behalf of `replica`, returning the delta
+    pub fn increment(&mut self, replica: &str, amount: u64) -> PNCounter {
+        PNCounter { positive: self.positive.increment(replica, amount), negative: GCounter::new() }
+    }
+
+    /// Subtract `amount` on behalf of `replica`, returning the delta
+    pub fn decrement(&mut self, replica: &str, amount: u64) -> PNCounter {
+        PNCounter { positive: GCounter::new(), negative: self.negative.increment(replica, amount) }
+    }
+
+    /// Current total
+    pub fn value(&self) -> i64 {
+        self.positive.value() as i64 - self.negative.value() as i64
+    }
+
+    /// Merge another state or delta
+    pub fn merge(&mut self, other: &PNCounter) {
+        self.positive.merge(&other.positive);
+        self.negative.merge(&other.negative);
+    }
+}
+
+/// Unique tag of one add to an [`OrSet`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Tag {
+    /// Replica that made the add
+    pub replica: ReplicaId,
+    /// Per-replica add counter
+    pub counter: u64,
+}
+
+/// Observed-remove set
+///
+/// A remove only cancels the adds it has observed, so an add concurrent with
+/// a remove survives the merge.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrSet {
+    /// Tags of every add, by element
+    adds: BTreeMap<String, BTreeSet<Tag>>,
+    /// Tags cancelled by removes
+    removed: BTreeSet<Tag>,
+}
+
+impl OrSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `element` on behalf of `replica`, returning the delta
+    pub fn add(&mut self, replica: &str, element: impl Into<String>) -> OrSet {
+        let counter = self
+            .adds
+            .values()
+            .flatten()
+            .chain(&self.removed)
+            .filter(|tag| tag.replica == replica)
+            .map(|tag| tag.counter)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let tag = Tag { replica: replica.to_string(), counter };
+        let element = element.into();
+        self.adds.entry(element.clone()).or_default().insert(tag.clone());
+
+        OrSet { adds: BTreeMap::from([(element, BTreeSet::from([tag]))]), removed: BTreeSet::new() }
+    }
+
+    /// Remove every observed add of `element`, returning the delta
+    pub fn remove(&mut self, element: &str) -> OrSet {
+        let observed: BTreeSet<Tag> = self
+            .adds
+            .get(element)
+            .map(|tags| tags.difference(&self.removed).cloned().collect())
+            .unwrap_or_default();
+        self.removed.extend(observed.iter().cloned());
+        OrSet { adds: BTreeMap::new(), removed: observed }
+    }
+
+    /// Whether `element` is in the set
+    pub fn contains(&self, element: &str) -> bool {
+        self.adds
+            .get(element)
+            .is_some_and(|tags| tags.iter().any(|tag| !self.removed.contains(tag)))
+    }
+
+    /// Elements currently in the set, in order
+    pub fn elements(&self) -> Vec<&str> {
+        self.adds
+            .keys()
+            .filter(|element| self.contains(element))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Merge another state or delta
+    pub fn merge(&mut self, other: &OrSet) {
+        for (element, tags) in &other.adds {
+            self.adds.entry(element.clone()).or_default().extend(tags.iter().cloned());
+        }
+        self.removed.extend(other.removed.iter().cloned());
+    }
+}
+
+/// Last-writer-wins register
+///
+/// Writes are ordered by timestamp, with the replica id breaking ties.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LwwRegister {
+    /// Current value (`None` until first written)
+    value: Option<serde_json::Value>,
+    /// Milliseconds since the Unix epoch of the winning write
+    timestamp: u64,
+    /// Replica of the winning write
+    replica: ReplicaId,
+}
+
+impl LwwRegister {
+    /// Create an unset register
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write `value` on behalf of `replica` at the current time, returning
+    /// the delta
+    ///
+    /// The timestamp never goes backwards, so a local write always wins over
+    /// what this replica has already seen.
+    pub fn set(&mut self, replica: &str, value: serde_json::Value) -> LwwRegister {
+        let timestamp = now_ms().max(self.timestamp + 1);
+        self.set_at(replica, value, timestamp)
+    }
+
+    /// Write `value` with an explicit timestamp, returning the delta
+    pub fn set_at(&mut self, replica: &str, value: serde_json::Value, timestamp: u64) -> LwwRegister {
+        let write = LwwRegister { value: Some(value), timestamp, replica: replica.to_string() };
+        self.merge(&write);
+        write
+    }
+
+    /// Current value
+    pub fn get(&self) -> Option<&serde_json::Value> {
+        self.value.as_ref()
+    }
+
+    /// Merge another state or delta
+    pub fn merge(&mut self, other: &LwwRegister) {
+        if (other.timestamp, &other.replica) > (self.timestamp, &self.replica) {
+            *self = other.clone();
+        }
+    }
+}
+
+/// Identifier of one character in an [`Rga`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RgaId {
+    /// Lamport clock at insertion
+    pub counter: u64,
+    /// Replica that inserted the character
+    pub replica: ReplicaId,
+}
+
+/// One character of an [`Rga`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct RgaNode {
+    id: RgaId,
+    /// Character the insert was made after (`None` for the start)
+    origin: Option<RgaId>,
+    ch: char,
+    deleted: bool,
+}
+
+/// Replicated growable array holding collaboratively edited text
+///
+/// Deleted characters stay as tombstones so concurrent inserts next to them
+/// still find their position.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rga {
+    /// Characters in document order
+    nodes: Vec<RgaNode>,
+    /// Every deleted character, including ones not received yet
+    deleted: BTreeSet<RgaId>,
+    /// Received characters whose origin has not arrived yet
+    pending: Vec<RgaNode>,
+    /// Highest Lamport clock seen
+    clock: u64,
+}
+
+impl Rga {
+    /// Create empty text
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert `text` at character `position` on behalf of `replica`,
+    /// returning the delta
+    pub fn insert(&mut self, replica: &str, position: usize, text: &str) -> Rga {
+        let mut origin = position
+            .checked_sub(1)
+            .and_then(|index| self.visible().nth(index))
+            .map(|node| node.id.clone());
+        if position > 0 && origin.is_none() {
+            // Past the end: append
+            origin = self.visible().last().map(|node| node.id.clone());
+        }
+
+        let mut delta = Rga::new();
+        for ch in text.chars() {
+            self.clock += 1;
+            let node = RgaNode {
+                id: RgaId { counter: self.clock, replica: replica.to_string() },
+                origin: origin.take(),
+                ch,
+                deleted: false,
+            };
+            origin = Some(node.id.clone());
+            self.integrate(node.clone());
+            delta.nodes.push(node);
+        }
+        delta.clock = self.clock;
+        delta
+    }
+
+    /// Delete `length` characters from `position`, returning the delta
+    pub fn delete(&mut self, position: usize, length: usize) -> Rga {
+        let ids: Vec<RgaId> = self.visible().skip(position).take(length).map(|node| node.id.clone()).collect();
+        for node in self.nodes.iter_mut().filter(|node| ids.contains(&node.id)) {
+            node.deleted = true;
+        }
+        self.deleted.extend(ids.iter().cloned());
+        Rga { deleted: ids.into_iter().collect(), ..Rga::new() }
+    }
+
+    /// Current text
+    pub fn text(&self) -> String {
+        self.visible().map(|node| node.ch).collect()
+    }
+
+    /// Number of visible characters
+    pub fn len(&self) -> usize {
+        self.visible().count()
+    }
+
+    /// Whether the text is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Merge another state or delta
+    pub fn merge(&mut self, other: &Rga) {
+        self.deleted.extend(other.deleted.iter().cloned());
+
+        let mut known: HashSet<RgaId> = self.nodes.iter().chain(&self.pending).map(|node| node.id.clone()).collect();
+        for node in other.nodes.iter().chain(&other.pending) {
+            if known.insert(node.id.clone()) {
+                self.clock = self.clock.max(node.id.counter);
+                self.pending.push(node.clone());
+            }
+        }
+        self.clock = self.clock.max(other.clock);
+
+        // Integrate every pending character whose origin is present
+        loop {
+            let present: HashSet<&RgaId> = self.nodes.iter().map(|node| &node.id).collect();
+            let Some(index) = self
+                .pending
+                .iter()
+                .position(|node| node.origin.as_ref().is_none_or(|origin| present.contains(origin)))
+            else {
+                break;
+            };
+            let node = self.pending.remove(index);
+            self.integrate(node);
+        }
+
+        for node in &mut self.nodes {
+            node.deleted |= self.deleted.contains(&node.id);
+        }
+    }
+
+    /// Place a character after its origin, behind any concurrent inserts
+    /// with a higher id
+    fn integrate(&mut self, mut node: RgaNode) {
+        let mut index = match &node.origin {
+            Some(origin) => self.nodes.iter().position(|n| &n.id == origin).map_or(0, |i| i + 1),
+            None => 0,
+        };
+        while index < self.nodes.len() && self.nodes[index].id > node.id {
+            index += 1;
+        }
+        node.deleted |= self.deleted.contains(&node.id);
+        self.nodes.insert(index, node);
+    }
+
+    fn visible(&self) -> impl Iterator<Item = &RgaNode> {
+        self.nodes.iter().filter(|node| !node.deleted)
+    }
+}
+
+/// Kind of a [`Crdt`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrdtKind {
+    GCounter,
+    PnCounter,
+    OrSet,
+    LwwRegister,
+    Rga,
+}
+
+impl fmt::Display for CrdtKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CrdtKind::GCounter => "g-counter",
+            CrdtKind::PnCounter => "pn-counter",
+            CrdtKind::OrSet => "or-set",
+            CrdtKind::LwwRegister => "lww-register",
+            CrdtKind::Rga => "rga",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for CrdtKind {
+    type Err = P2PError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "g-counter" => Ok(CrdtKind::GCounter),
+            "pn-counter" => Ok(CrdtKind::PnCounter),
+            "or-set" => Ok(CrdtKind::OrSet),
+            "lww-register" => Ok(CrdtKind::LwwRegister),
+            "rga" | "text" => Ok(CrdtKind::Rga),
+            other => Err(P2PError::Configuration(format!(
+                "unknown CRDT kind '{}' (expected g-counter, pn-counter, or-set, lww-register or rga)", other
+            ))),
+        }
+    }
+}
+
+/// Local update to a [`Crdt`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CrdtOp {
+    /// Add to a counter
+    Increment(u64),
+    /// Subtract from a PN-counter
+    Decrement(u64),
+    /// Add an element to an OR-set
+    Add(serde_json::Value),
+    /// Remove an element from an OR-set
+    Remove(serde_json::Value),
+    /// Write an LWW-register
+    Set(serde_json::Value),
+    /// Insert text into an RGA
+    Insert { position: usize, text: String },
+    /// Delete text from an RGA
+    Delete { position: usize, length: usize },
+}
+
+/// Any of the supported CRDTs
+///
+/// OR-set elements are stored as their JSON encoding, so any JSON value can
+/// be a member.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "state", rename_all = "kebab-case")]
+pub enum Crdt {
+    GCounter(GCounter),
+    PnCounter(PNCounter),
+    OrSet(OrSet),
+    LwwRegister(LwwRegister),
+    Rga(Rga),
+}
+
+impl Crdt {
+    /// Create an empty CRDT of the given kind
+    pub fn new(kind: CrdtKind) -> Self {
+        match kind {
+            CrdtKind::GCounter => Crdt::GCounter(GCounter::new()),
+            CrdtKind::PnCounter => Crdt::PnCounter(PNCounter::new()),
+            CrdtKind::OrSet => Crdt::OrSet(OrSet::new()),
+            CrdtKind::LwwRegister => Crdt::LwwRegister(LwwRegister::new()),
+            CrdtKind::Rga => Crdt::Rga(Rga::new()),
+        }
+    }
+
+    /// Kind of this CRDT
+    pub fn kind(&self) -> CrdtKind {
+        match self {
+            Crdt::GCounter(_) => CrdtKind::GCounter,
+            Crdt::PnCounter(_) => CrdtKind::PnCounter,
+            Crdt::OrSet(_) => CrdtKind::OrSet,
+            Crdt::LwwRegister(_) => CrdtKind::LwwRegister,
+            Crdt::Rga(_) => CrdtKind::Rga,
+        }
+    }
+
+    /// Apply a local update on behalf of `replica`, returning the delta
+    pub fn apply(&mut self, replica: &str, op: CrdtOp) -> P2PResult<Crdt> {
+        let kind = self.kind();
+        Ok(match (self, op) {
+            (Crdt::GCounter(c), CrdtOp::Increment(n)) => Crdt::GCounter(c.increment(replica, n)),
+            (Crdt::PnCounter(c), CrdtOp::Increment(n)) => Crdt::PnCounter(c.increment(replica, n)),
+            (Crdt::PnCounter(c), CrdtOp::Decrement(n)) => Crdt::PnCounter(c.decrement(replica, n)),
+            (Crdt::OrSet(s), CrdtOp::Add(element)) => Crdt::OrSet(s.add(replica, element.to_string())),
+            (Crdt::OrSet(s), CrdtOp::Remove(element)) => Crdt::OrSet(s.remove(&element.to_string())),
+            (Crdt::LwwRegister(r), CrdtOp::Set(value)) => Crdt::LwwRegister(r.set(replica, value)),
+            (Crdt::Rga(t), CrdtOp::Insert { position, text }) => Crdt::Rga(t.insert(replica, position, &text)),
+            (Crdt::Rga(t), CrdtOp::Delete { position, length }) => Crdt::Rga(t.delete(position, length)),
+            (_, op) => return Err(P2PError::Generic(format!("{} does not support {:?}", kind, op))),
+        })
+    }
+
+    /// Merge another state or delta of the same kind
+    pub fn merge(&mut self, other: &Crdt) -> P2PResult<()> {
+        match (self, other) {
+            (Crdt::GCounter(a), Crdt::GCounter(b)) => a.merge(b),
+            (Crdt::PnCounter(a), Crdt::PnCounter(b)) => a.merge(b),
+            (Crdt::OrSet(a), Crdt::OrSet(b)) => a.merge(b),
+            (Crdt::LwwRegister(a), Crdt::LwwRegister(b)) => a.merge(b),
+            (Crdt::Rga(a), Crdt::Rga(b)) => a.merge(b),
+            (a, b) => {
+                return Err(P2PError::Generic(format!("cannot merge a {} into a {}", b.kind(), a.kind())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Current value as JSON
+    pub fn value(&self) -> serde_json::Value {
+        match self {
+            Crdt::GCounter(c) => c.value().into(),
+            Crdt::PnCounter(c) => c.value().into(),
+            Crdt::OrSet(s) => s
+                .elements()
+                .into_iter()
+                .map(|element| serde_json::from_str(element).unwrap_or_else(|_| element.into()))
+                .collect::<Vec<serde_json::Value>>()
+                .into(),
+            Crdt::LwwRegister(r) => r.get().cloned().unwrap_or(serde_json::Value::Null),
+            Crdt::Rga(t) => t.text().into(),
+        }
+    }
+}
+
+/// Delta (or full state) of a named CRDT, as gossiped between replicas
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrdtDelta {
+    /// Name of the CRDT
+    pub name: String,
+    /// State to merge
+    pub state: Crdt,
+}
+
+impl CrdtDelta {
+    /// Encode a batch for a gossip message
+    ///
+    /// Deltas are JSON inside the binary frame, as bincode cannot read the
+    /// JSON values of registers and sets.
+    pub fn encode_all(deltas: &[CrdtDelta]) -> P2PResult<Vec<u8>> {
+        Ok(serde_json::to_vec(deltas)?)
+    }
+
+    /// Decode a batch from a gossip message
+    pub fn decode_all(bytes: &[u8]) -> P2PResult<Vec<CrdtDelta>> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Named CRDTs held by one node, with the deltas awaiting gossip
+#[derive(Debug)]
+pub struct CrdtReplica {
+    /// Identifier recorded on local updates
+    replica_id: ReplicaId,
+    /// Current states by name
+    crdts: parking_lot::RwLock<BTreeMap<String, Crdt>>,
+    /// Joined deltas not yet gossiped, by name
+    deltas: parking_lot::Mutex<BTreeMap<String, Crdt>>,
+}
+
+impl CrdtReplica {
+    /// Create an empty replica
+    pub fn new(replica_id: impl Into<ReplicaId>) -> Self {
+        Self {
+            replica_id: replica_id.into(),
+            crdts: parking_lot::RwLock::new(BTreeMap::new()),
+            deltas: parking_lot::Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Identifier recorded on local updates
+    pub fn replica_id(&self) -> &str {
+        &self.replica_id
+    }
+
+    /// Create a CRDT, or keep the existing one if it has the same kind
+    pub fn create(&self, name: &str, kind: CrdtKind) -> P2PResult<()> {
+        let mut crdts = self.crdts.write();
+        match crdts.get(name) {
+            Some(existing) if existing.kind() != kind => Err(P2PError::Configuration(format!(
+                "CRDT '{}' is a {}, not a {}", name, existing.kind(), kind
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                crdts.insert(name.to_string(), Crdt::new(kind));
+                Ok(())
+            }
+        }
+    }
+
+    /// Apply a local update, returning the new value
+    pub fn update(&self, name: &str, op: CrdtOp) -> P2PResult<serde_json::Value> {
+        let mut crdts = self.crdts.write();
+        let crdt = crdts
+            .get_mut(name)
+            .ok_or_else(|| P2PError::Generic(format!("no CRDT named '{}'", name)))?;
+        let delta = crdt.apply(&self.replica_id, op)?;
+        self.buffer(name, &delta)?;
+        Ok(crdt.value())
+    }
+
+    /// Current value of a CRDT
+    pub fn value(&self, name: &str) -> Option<serde_json::Value> {
+        self.crdts.read().get(name).map(Crdt::value)
+    }
+
+    /// Current state of a CRDT
+    pub fn get(&self, name: &str) -> Option<Crdt> {
+        self.crdts.read().get(name).cloned()
+    }
+
+    /// Names and kinds of every CRDT
+    pub fn list(&self) -> Vec<(String, CrdtKind)> {
+        self.crdts.read().iter().map(|(name, crdt)| (name.clone(), crdt.kind())).collect()
+    }
+
+    /// Merge a state or delta received from another replica
+    ///
+    /// Creates the CRDT if it is unknown here. Returns whether anything
+    /// changed; changes are buffered so they spread on to further peers.
+    pub fn merge(&self, name: &str, state: &Crdt) -> P2PResult<bool> {
+        let mut crdts = self.crdts.write();
+        let changed = match crdts.get_mut(name) {
+            Some(crdt) => {
+                let before = crdt.clone();
+                crdt.merge(state)?;
+                *crdt != before
+            }
+            None => {
+                crdts.insert(name.to_string(), state.clone());
+                true
+            }
+        };
+        if changed {
+            self.buffer(name, state)?;
+        }
+        Ok(changed)
+    }
+
+    /// Merge a batch of gossiped deltas, returning how many changed state
+    pub fn merge_deltas(&self, deltas: &[CrdtDelta]) -> P2PResult<usize> {
+        let mut changed = 0;
+        for delta in deltas {
+            if self.merge(&delta.name, &delta.state)? {
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Take the deltas buffered since the last gossip round
+    pub fn take_deltas(&self) -> Vec<CrdtDelta> {
+        std::mem::take(&mut *self.deltas.lock())
+            .into_iter()
+            .map(|(name, state)| CrdtDelta { name, state })
+            .collect()
+    }
+
+    /// Put back deltas that could not be sent
+    pub fn requeue(&self, deltas: Vec<CrdtDelta>) -> P2PResult<()> {
+        for delta in deltas {
+            self.buffer(&delta.name, &delta.state)?;
+        }
+        Ok(())
+    }
+
+    /// Full state of every CRDT, for bringing a new peer up to date
+    pub fn full_state(&self) -> Vec<CrdtDelta> {
+        self.crdts
+            .read()
+            .iter()
+            .map(|(name, state)| CrdtDelta { name: name.clone(), state: state.clone() })
+            .collect()
+    }
+
+    /// Install this replica for TLisp `crdt-*` builtins and gossip receivers
+    pub fn install_global(self: &Arc<Self>) {
+        *GLOBAL_REPLICA.write() = Some(Arc::clone(self));
+    }
+
+    /// Replica used by TLisp `crdt-*` builtins, if one is installed
+    pub fn global() -> Option<Arc<CrdtReplica>> {
+        GLOBAL_REPLICA.read().clone()
+    }
+
+    /// Join a delta into the gossip buffer
+    fn buffer(&self, name: &str, delta: &Crdt) -> P2PResult<()> {
+        let mut deltas = self.deltas.lock();
+        match deltas.get_mut(name) {
+            Some(pending) => pending.merge(delta),
+            None => {
+                deltas.insert(name.to_string(), delta.clone());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Wall-clock time in milliseconds since the Unix epoch
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_set_converge() {
+        let (mut a, mut b) = (PNCounter::new(), PNCounter::new());
+        let da = a.increment("a", 5);
+        let db = b.decrement("b", 2);
+        a.merge(&db);
+        b.merge(&da);
+        // Merging is idempotent
+        b.merge(&da);
+        assert_eq!(a, b);
+        assert_eq!(a.value(), 3);
+
+        let (mut x, mut y) = (OrSet::new(), OrSet::new());
+        let add = x.add("a", "apple");
+        y.merge(&add);
+        // Concurrent re-add survives a remove that did not observe it
+        let remove = y.remove("apple");
+        let readd = x.add("a", "apple");
+        x.merge(&remove);
+        y.merge(&readd);
+        assert_eq!(x, y);
+        assert!(x.contains("apple"));
+        let remove = x.remove("apple");
+        y.merge(&remove);
+        assert!(x.elements().is_empty() && y.elements().is_empty());
+    }
+
+    #[test]
+    fn test_register_and_text_converge() {
+        let (mut a, mut b) = (LwwRegister::new(), LwwRegister::new());
+        let da = a.set_at("a", "blue".into(), 10);
+        let db = b.set_at("b", "green".into(), 10);
+        a.merge(&db);
+        b.merge(&da);
+        // Equal timestamps: the higher replica id wins
+        assert_eq!(a.get(), Some(&serde_json::Value::from("green")));
+        assert_eq!(a, b);
+
+        let (mut x, mut y) = (Rga::new(), Rga::new());
+        let base = x.insert("a", 0, "held");
+        y.merge(&base);
+        let dx = x.insert("a", 4, "!");
+        let dy = y.insert("b", 0, "We ");
+        let del = y.delete(3, 1);
+        // Deliver b's edits in reverse order
+        x.merge(&del);
+        x.merge(&dy);
+        y.merge(&dx);
+        assert_eq!(x.text(), "We eld!");
+        assert_eq!(x.text(), y.text());
+
+        // Concurrent inserts at the same position converge
+        let (mut p, mut q) = (x.clone(), y.clone());
+        let dp = p.insert("a", 0, "1");
+        let dq = q.insert("b", 0, "2");
+        p.merge(&dq);
+        q.merge(&dp);
+        assert_eq!(p.text(), q.text());
+    }
+
+    #[test]
+    fn test_replica_delta_propagation() {
+        let a = CrdtReplica::new("a");
+        let b = CrdtReplica::new("b");
+        a.create("visits", CrdtKind::GCounter).unwrap();
+        a.update("visits", CrdtOp::Increment(2)).unwrap();
+        a.update("visits", CrdtOp::Increment(1)).unwrap();
+        assert!(a.update("visits", CrdtOp::Decrement(1)).is_err());
+
+        // Deltas are joined into one per CRDT between rounds
+        let deltas = a.take_deltas();
+        assert_eq!(deltas.len(), 1);
+        let bytes = CrdtDelta::encode_all(&deltas).unwrap();
+        assert_eq!(b.merge_deltas(&CrdtDelta::decode_all(&bytes).unwrap()).unwrap(), 1);
+        assert_eq!(b.value("visits"), Some(serde_json::json!(3)));
+
+        // What was new to b is passed on; echoing it back changes nothing
+        let echoed = b.take_deltas();
+        assert_eq!(a.merge_deltas(&echoed).unwrap(), 0);
+        assert!(a.take_deltas().is_empty());
+        assert!(b.create("visits", CrdtKind::OrSet).is_err());
+    }
+
+    #[test]
+    fn test_tlisp_builtins() {
+        Arc::new(CrdtReplica::new("local")).install_global();
+
+        let mut interpreter = crate::tlisp::TlispInterpreter::new();
+        interpreter.eval(r#"(crdt-new "tlisp/tags" 'or-set)"#).unwrap();
+        interpreter.eval(r#"(crdt-merge "tlisp/tags" 'add "red")"#).unwrap();
+        interpreter.eval(r#"(crdt-merge "tlisp/tags" 'add 7)"#).unwrap();
+        assert_eq!(
+            interpreter.eval(r#"(crdt-merge "tlisp/tags" 'remove "red")"#).unwrap(),
+            crate::tlisp::Value::List(vec![crate::tlisp::Value::Int(7)])
+        );
+
+        interpreter.eval(r#"(crdt-new "tlisp/doc" "rga")"#).unwrap();
+        interpreter.eval(r#"(crdt-merge "tlisp/doc" 'insert 0 "hello")"#).unwrap();
+        interpreter.eval(r#"(crdt-merge "tlisp/doc" 'delete 0 1)"#).unwrap();
+        assert_eq!(
+            interpreter.eval(r#"(crdt-value "tlisp/doc")"#).unwrap(),
+            crate::tlisp::Value::String("ello".to_string())
+        );
+        assert!(interpreter.eval(r#"(crdt-merge "tlisp/doc" 'increment)"#).is_err());
+    }
+}
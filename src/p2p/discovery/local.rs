@@ -0,0 +1,321 @@
+//! Local network discovery via UDP broadcast
+//!
+//! Intended for LAN development clusters: every node periodically
+//! broadcasts a small announcement carrying its cluster ID, and nodes that
+//! hear an announcement for the same cluster ID add the sender as a peer.
+//! This removes the need to hand-configure bootstrap nodes.
+
+use crate::p2p::{P2PResult, P2PError, DiscoveryError, NetworkError, NodeId, NodeInfo};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, RwLock};
+use serde::{Deserialize, Serialize};
+
+/// Protocol tag carried in every announcement
+pub const LOCAL_DISCOVERY_PROTOCOL: &str = "ream-local-discovery/1";
+
+/// Default UDP port used for local discovery
+pub const DEFAULT_LOCAL_DISCOVERY_PORT: u16 = 7946;
+
+/// Local discovery configuration
+#[derive(Debug, Clone)]
+pub struct LocalDiscoveryConfig {
+    /// Cluster ID; only announcements with a matching ID are accepted
+    pub cluster_id: String,
+    /// UDP port to listen on for announcements
+    pub listen_port: u16,
+    /// Destination announcements are sent to
+    pub announce_address: SocketAddr,
+    /// Interval between announcements
+    pub announce_interval: Duration,
+}
+
+impl Default for LocalDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            cluster_id: "ream-dev".to_string(),
+            listen_port: DEFAULT_LOCAL_DISCOVERY_PORT,
+            announce_address: SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::BROADCAST,
+                DEFAULT_LOCAL_DISCOVERY_PORT,
+            )),
+            announce_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+impl LocalDiscoveryConfig {
+    /// Create a configuration for the given cluster ID with default ports
+    pub fn for_cluster(cluster_id: impl Into<String>) -> Self {
+        Self {
+            cluster_id: cluster_id.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Announcement broadcast by each node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalAnnouncement {
+    /// Protocol tag
+    pub protocol: String,
+    /// Cluster the sender belongs to
+    pub cluster_id: String,
+    /// Sender's node information
+    pub node: NodeInfo,
+}
+
+impl LocalAnnouncement {
+    /// Create an announcement for a node
+    pub fn new(cluster_id: &str, node: NodeInfo) -> Self {
+        Self {
+            protocol: LOCAL_DISCOVERY_PROTOCOL.to_string(),
+            cluster_id: cluster_id.to_string(),
+            node,
+        }
+    }
+
+    /// Encode for the wire
+    pub fn encode(&self) -> P2PResult<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Decode from the wire
+    pub fn decode(bytes: &[u8]) -> P2PResult<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// UDP broadcast discovery service
+#[derive(Debug)]
+pub struct LocalDiscovery {
+    /// Local node information
+    local_node: NodeInfo,
+    /// Configuration
+    config: LocalDiscoveryConfig,
+    /// Peers discovered so far
+    discovered: Arc<RwLock<HashMap<NodeId, NodeInfo>>>,
+    /// Shutdown signal for the background task
+    shutdown_tx: Option<mpsc::Sender<()>>,
+    /// Address the listener is bound to
+    bound_address: Option<SocketAddr>,
+}
+
+impl LocalDiscovery {
+    /// Create a new local discovery service
+    pub fn new(local_node: NodeInfo, config: LocalDiscoveryConfig) -> Self {
+        Self {
+            local_node,
+            config,
+            discovered: Arc::new(RwLock::new(HashMap::new())),
+            shutdown_tx: None,
+            bound_address: None,
+        }
+    }
+
+    /// Start announcing and listening
+    ///
+    /// Newly discovered peers are sent on the returned channel.
+    pub async fn start(&mut self) -> P2PResult<mpsc::Receiver<NodeInfo>> {
+        let socket = bind_shared_udp(self.config.listen_port)?;
+        socket
+            .set_broadcast(true)
+            .map_err(|e| P2PError::Network(NetworkError::BindError(e.to_string())))?;
+        self.bound_address = socket.local_addr().ok();
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+        let (discovered_tx, discovered_rx) = mpsc::channel(64);
+        self.shutdown_tx = Some(shutdown_tx);
+
+        let announcement = LocalAnnouncement::new(&self.config.cluster_id, self.local_node.clone()).encode()?;
+        let config = self.config.clone();
+        let local_id = self.local_node.node_id;
+        let discovered = Arc::clone(&self.discovered);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(config.announce_interval);
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.recv() => {
+                        break;
+                    }
+                    _ = ticker.tick() => {
+                        // Announcement failures are retried on the next tick
+                        let _ = socket.send_to(&announcement, config.announce_address).await;
+                    }
+                    result = socket.recv_from(&mut buffer) => {
+                        let Ok((len, _)) = result else { continue };
+                        let Some(node) = accept_announcement(&buffer[..len], &config.cluster_id, local_id) else {
+                            continue;
+                        };
+                        let is_new = discovered.write().await.insert(node.node_id, node.clone()).is_none();
+                        if is_new && discovered_tx.send(node).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(discovered_rx)
+    }
+
+    /// Stop the background task
+    pub async fn stop(&mut self) -> P2PResult<()> {
+        if let Some(shutdown_tx) = self.shutdown_tx.take() {
+            let _ = shutdown_tx.send(()).await;
+        }
+        self.bound_address = None;
+        Ok(())
+    }
+
+    /// Address the listener is bound to (if running)
+    pub fn bound_address(&self) -> Option<SocketAddr> {
+        self.bound_address
+    }
+
+    /// Peers discovered so far
+    pub async fn get_discovered_nodes(&self) -> Vec<NodeInfo> {
+        self.discovered.read().await.values().cloned().collect()
+    }
+}
+
+/// Validate an incoming announcement, returning the sender if it should be
+/// joined (same protocol, same cluster, not ourselves)
+pub fn accept_announcement(bytes: &[u8], cluster_id: &str, local_id: NodeId) -> Option<NodeInfo> {
+    let announcement = LocalAnnouncement::decode(bytes).ok()?;
+    if announcement.protocol != LOCAL_DISCOVERY_PROTOCOL
+        || announcement.cluster_id != cluster_id
+        || announcement.node.node_id == local_id
+    {
+        return None;
+    }
+    let mut node = announcement.node;
+    node.update_last_seen();
+    Some(node)
+}
+
+/// Bind a UDP socket that several local nodes can share
+///
+/// `SO_REUSEADDR`/`SO_REUSEPORT` let every node on a development machine
+/// listen on the same discovery port and receive each broadcast.
+fn bind_shared_udp(port: u16) -> P2PResult<UdpSocket> {
+    let bind_error = |e: std::io::Error| P2PError::Discovery(DiscoveryError::BootstrapFailed(
+        format!("Failed to bind local discovery port {}: {}", port, e)
+    ));
+
+    #[cfg(unix)]
+    let socket = {
+        use std::os::unix::io::FromRawFd;
+
+        // SAFETY: the descriptor is freshly created, checked for errors, and
+        // ownership is transferred to the std socket (or closed on failure).
+        unsafe {
+            let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0);
+            if fd < 0 {
+                return Err(bind_error(std::io::Error::last_os_error()));
+            }
+            let enable: libc::c_int = 1;
+            for option in [libc::SO_REUSEADDR, libc::SO_REUSEPORT] {
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    option,
+                    &enable as *const libc::c_int as *const libc::c_void,
+                    std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+                );
+            }
+            let sockaddr = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: port.to_be(),
+                sin_addr: libc::in_addr { s_addr: u32::from(Ipv4Addr::UNSPECIFIED).to_be() },
+                sin_zero: [0; 8],
+                #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+                sin_len: std::mem::size_of::<libc::sockaddr_in>() as u8,
+            };
+            if libc::bind(
+                fd,
+                &sockaddr as *const libc::sockaddr_in as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            ) < 0 {
+                let error = std::io::Error::last_os_error();
+                libc::close(fd);
+                return Err(bind_error(error));
+            }
+            std::net::UdpSocket::from_raw_fd(fd)
+        }
+    };
+
+    #[cfg(not(unix))]
+    let socket = std::net::UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port))
+        .map_err(bind_error)?;
+
+    socket.set_nonblocking(true).map_err(bind_error)?;
+    UdpSocket::from_std(socket).map_err(bind_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node() -> NodeInfo {
+        NodeInfo::new(NodeId::new(), "127.0.0.1:9000".parse::<SocketAddr>().unwrap())
+    }
+
+    #[test]
+    fn test_accept_announcement_filters() {
+        let local = node();
+        let peer = node();
+
+        let bytes = LocalAnnouncement::new("dev", peer.clone()).encode().unwrap();
+        assert_eq!(accept_announcement(&bytes, "dev", local.node_id).unwrap().node_id, peer.node_id);
+
+        // Different cluster
+        assert!(accept_announcement(&bytes, "prod", local.node_id).is_none());
+        // Our own announcement echoed back
+        assert!(accept_announcement(&bytes, "dev", peer.node_id).is_none());
+        // Garbage
+        assert!(accept_announcement(b"not json", "dev", local.node_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_nodes_discover_each_other() {
+        let port_a = free_udp_port();
+        let port_b = free_udp_port();
+        let a_info = node();
+        let b_info = node();
+
+        let mut a = LocalDiscovery::new(a_info.clone(), LocalDiscoveryConfig {
+            cluster_id: "dev".to_string(),
+            listen_port: port_a,
+            announce_address: format!("127.0.0.1:{}", port_b).parse().unwrap(),
+            announce_interval: Duration::from_millis(20),
+        });
+        let mut b = LocalDiscovery::new(b_info.clone(), LocalDiscoveryConfig {
+            cluster_id: "dev".to_string(),
+            listen_port: port_b,
+            announce_address: format!("127.0.0.1:{}", port_a).parse().unwrap(),
+            announce_interval: Duration::from_millis(20),
+        });
+
+        let mut a_rx = a.start().await.unwrap();
+        let mut b_rx = b.start().await.unwrap();
+
+        let found_by_a = tokio::time::timeout(Duration::from_secs(5), a_rx.recv()).await.unwrap().unwrap();
+        let found_by_b = tokio::time::timeout(Duration::from_secs(5), b_rx.recv()).await.unwrap().unwrap();
+        assert_eq!(found_by_a.node_id, b_info.node_id);
+        assert_eq!(found_by_b.node_id, a_info.node_id);
+        assert_eq!(a.get_discovered_nodes().await.len(), 1);
+
+        a.stop().await.unwrap();
+        b.stop().await.unwrap();
+    }
+
+    fn free_udp_port() -> u16 {
+        std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+}
@@ -7,10 +7,12 @@
 pub mod dht;
 pub mod bootstrap;
 pub mod gossip;
+pub mod local;
 
 pub use dht::*;
 pub use bootstrap::*;
 pub use gossip::*;
+pub use local::*;
 
 // Placeholder implementations for bootstrap and gossip
 // These would be fully implemented in their respective files
@@ -93,6 +95,33 @@ impl GossipProtocol {
     pub async fn get_stats(&self) -> GossipStats {
         self.stats.read().await.clone()
     }
+
+    /// Send `message` to up to `fanout` randomly chosen connected peers,
+    /// returning how many it reached
+    pub async fn spread(&self, network: &NetworkLayer, message: NetworkMessage) -> P2PResult<usize> {
+        let peers = network.get_connected_nodes().await;
+        let targets: Vec<NodeId> = peers
+            .choose_multiple(&mut rand::thread_rng(), self.config.fanout)
+            .copied()
+            .collect();
+
+        let mut sent = 0;
+        for target in targets {
+            if network.send_message(target, message.clone()).await.is_ok() {
+                sent += 1;
+            }
+        }
+
+        let mut stats = self.stats.write().await;
+        stats.rounds_completed += 1;
+        stats.messages_sent += sent as u64;
+        if sent == 0 && !peers.is_empty() {
+            return Err(P2PError::Discovery(DiscoveryError::GossipFailed(
+                "gossip reached no peers".to_string()
+            )));
+        }
+        Ok(sent)
+    }
 }
 
 /// Bootstrap configuration
@@ -116,6 +145,10 @@ impl Default for BootstrapConfig {
 pub struct GossipConfig {
     pub gossip_interval: std::time::Duration,
     pub fanout: usize,
+    /// Maximum updates per batch sent to another region
+    pub cross_region_batch_size: usize,
+    /// Maximum time an update may wait before a cross-region batch is flushed
+    pub cross_region_max_delay: std::time::Duration,
 }
 
 impl Default for GossipConfig {
@@ -123,6 +156,8 @@ impl Default for GossipConfig {
         Self {
             gossip_interval: std::time::Duration::from_secs(1),
             fanout: 3,
+            cross_region_batch_size: 64,
+            cross_region_max_delay: std::time::Duration::from_secs(5),
         }
     }
 }
@@ -136,6 +171,8 @@ pub struct GossipStats {
 }
 
 use crate::p2p::{P2PResult, P2PError, DiscoveryError, NodeId, NodeInfo};
+use crate::p2p::network::{NetworkLayer, NetworkMessage};
+use rand::seq::SliceRandom;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -150,6 +187,8 @@ pub struct NodeDiscovery {
     gossip: Arc<RwLock<GossipProtocol>>,
     /// Bootstrap manager
     bootstrap: Arc<RwLock<BootstrapManager>>,
+    /// LAN broadcast discovery (when enabled)
+    local: Option<Arc<RwLock<LocalDiscovery>>>,
     /// Discovery configuration
     config: DiscoveryConfig,
     /// Discovery statistics
@@ -163,11 +202,15 @@ impl NodeDiscovery {
         let gossip = Arc::new(RwLock::new(GossipProtocol::new(local_node.clone(), config.gossip_config.clone())));
         let bootstrap = Arc::new(RwLock::new(BootstrapManager::new(config.bootstrap_config.clone())));
         let stats = Arc::new(RwLock::new(DiscoveryStats::default()));
+        let local = config.local_discovery.clone().map(|local_config| {
+            Arc::new(RwLock::new(LocalDiscovery::new(local_node.clone(), local_config)))
+        });
 
         Ok(Self {
             dht,
             gossip,
             bootstrap,
+            local,
             config,
             stats,
         })
@@ -193,12 +236,34 @@ impl NodeDiscovery {
             bootstrap.start().await?;
         }
 
+        // Start LAN discovery and auto-join peers announcing our cluster ID
+        if let Some(local) = &self.local {
+            let mut discovered_rx = local.write().await.start().await?;
+            let dht = Arc::clone(&self.dht);
+            let gossip = Arc::clone(&self.gossip);
+            let bootstrap = Arc::clone(&self.bootstrap);
+            let stats = Arc::clone(&self.stats);
+
+            tokio::spawn(async move {
+                while let Some(node) = discovered_rx.recv().await {
+                    let _ = bootstrap.write().await.connect_to_bootstrap_nodes(vec![node.clone()]).await;
+                    let _ = dht.write().await.update_node_info(node.clone()).await;
+                    let _ = gossip.write().await.update_node_info(node).await;
+                    stats.write().await.nodes_discovered += 1;
+                }
+            });
+        }
+
         Ok(())
     }
 
     /// Stop the discovery system
     pub async fn stop(&self) -> P2PResult<()> {
         // Stop all components
+        if let Some(local) = &self.local {
+            local.write().await.stop().await?;
+        }
+
         {
             let mut dht = self.dht.write().await;
             dht.stop().await?;
@@ -298,6 +363,12 @@ impl NodeDiscovery {
         dht.get_known_nodes().await
     }
 
+    /// Spread a message to a few peers through the gossip protocol
+    pub async fn gossip(&self, network: &NetworkLayer, message: NetworkMessage) -> P2PResult<usize> {
+        let gossip = self.gossip.read().await;
+        gossip.spread(network, message).await
+    }
+
     /// Get cluster members from gossip
     pub async fn get_cluster_members(&self) -> Vec<NodeInfo> {
         let gossip = self.gossip.read().await;
@@ -375,6 +446,8 @@ pub struct DiscoveryConfig {
     pub discovery_interval: std::time::Duration,
     /// Node announcement interval
     pub announcement_interval: std::time::Duration,
+    /// LAN broadcast discovery; disabled when `None`
+    pub local_discovery: Option<LocalDiscoveryConfig>,
 }
 
 impl Default for DiscoveryConfig {
@@ -385,6 +458,7 @@ impl Default for DiscoveryConfig {
             bootstrap_config: BootstrapConfig::default(),
             discovery_interval: std::time::Duration::from_secs(30),
             announcement_interval: std::time::Duration::from_secs(60),
+            local_discovery: None,
         }
     }
 }
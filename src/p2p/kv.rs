@@ -0,0 +1,630 @@
+//! Replicated key-value store
+//!
+//! A small configuration store layered on the consensus engine. Writes are
+//! encoded as [`KvCommand`]s and proposed through consensus, so every node
+//! applies them in the same order; reads are served from the local replica.
+//! Compare-and-swap is evaluated by the state machine at apply time, which
+//! makes it safe against concurrent writers on other nodes.
+//!
+//! Distributed locks are leases in the same state machine. Each lease carries
+//! a fencing token — the log index it was granted at — so a holder whose lease
+//! lapsed can be told apart from its successor. Leader election is a lock per
+//! group held by the elected node.
+
+use crate::p2p::{P2PResult, P2PError, ConsensusError, NodeId};
+use crate::p2p::consensus::{ConsensusEngine, ConsensusResult, ConsensusValue};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock};
+use serde::{Deserialize, Serialize};
+
+/// Capacity of the watch broadcast channel
+const WATCH_CHANNEL_CAPACITY: usize = 256;
+
+/// Prefix of the event keys lock changes are announced under
+pub const LOCK_EVENT_PREFIX: &str = "lock:";
+
+/// Prefix of the locks leader elections are held through
+pub const ELECTION_LOCK_PREFIX: &str = "election/";
+
+/// Handle installed for TLisp builtins
+static GLOBAL_KV: Lazy<parking_lot::RwLock<Option<DistributedKv>>> =
+    Lazy::new(|| parking_lot::RwLock::new(None));
+
+/// Command replicated through the consensus log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KvCommand {
+    /// Unconditionally set a key
+    Put { key: String, value: String },
+    /// Set a key only if its current value matches `expected`
+    /// (`None` means the key must be absent)
+    Cas { key: String, expected: Option<String>, value: String },
+    /// Remove a key
+    Delete { key: String },
+    /// Take or renew a lock for `ttl_ms`, measured from the proposer's `now_ms`
+    Acquire { name: String, node: NodeId, owner: String, ttl_ms: u64, now_ms: u64 },
+    /// Give up a lock, if it is still held under `token`
+    Release { name: String, token: u64 },
+}
+
+impl KvCommand {
+    /// Key (or lock name) the command applies to
+    pub fn key(&self) -> &str {
+        match self {
+            KvCommand::Put { key, .. } | KvCommand::Cas { key, .. } | KvCommand::Delete { key } => key,
+            KvCommand::Acquire { name, .. } | KvCommand::Release { name, .. } => name,
+        }
+    }
+
+    /// Encode for the consensus log
+    pub fn encode(&self) -> P2PResult<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Decode from the consensus log
+    pub fn decode(bytes: &[u8]) -> P2PResult<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// A stored value and the log index that last modified it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KvEntry {
+    /// Stored value
+    pub value: String,
+    /// Log index of the last modification
+    pub version: u64,
+}
+
+/// A lock lease granted through consensus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockLease {
+    /// Lock name
+    pub name: String,
+    /// Node holding the lock
+    pub node: NodeId,
+    /// Holder within the node
+    pub owner: String,
+    /// Fencing token; strictly greater for every later holder
+    pub token: u64,
+    /// Milliseconds since the Unix epoch the lease lapses at unless renewed
+    pub expires_at_ms: u64,
+}
+
+impl LockLease {
+    /// Whether the lease has lapsed at `now_ms`
+    pub fn is_expired_at(&self, now_ms: u64) -> bool {
+        now_ms >= self.expires_at_ms
+    }
+}
+
+/// Outcome of a leader election round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leadership {
+    /// Election group
+    pub group: String,
+    /// Elected node
+    pub leader: NodeId,
+    /// Fencing token of the leader's lease; increases with every new leader
+    pub term: u64,
+    /// Milliseconds since the Unix epoch the leadership lapses at unless renewed
+    pub expires_at_ms: u64,
+    /// Whether the local node is the leader
+    pub is_local: bool,
+}
+
+/// Change notification delivered to watchers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvEvent {
+    /// Key that changed
+    pub key: String,
+    /// New entry (`None` if the key was deleted)
+    pub entry: Option<KvEntry>,
+    /// Log index of the change
+    pub version: u64,
+}
+
+/// Deterministic state machine applied on every replica
+#[derive(Debug, Default)]
+pub struct KvStateMachine {
+    /// Current contents
+    entries: BTreeMap<String, KvEntry>,
+    /// Held lock leases by name
+    locks: BTreeMap<String, LockLease>,
+    /// Highest log index applied
+    last_applied: u64,
+}
+
+impl KvStateMachine {
+    /// Create an empty state machine
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a command committed at `index`
+    ///
+    /// Returns the resulting event if the command changed the store, or
+    /// `None` for a failed compare-and-swap, a lock held by someone else, a
+    /// stale release or a replayed index. Lock events are keyed
+    /// [`LOCK_EVENT_PREFIX`] + name and carry the lease as JSON.
+    pub fn apply(&mut self, index: u64, command: &KvCommand) -> Option<KvEvent> {
+        if index <= self.last_applied {
+            return None;
+        }
+        self.last_applied = index;
+
+        let entry = match command {
+            KvCommand::Acquire { name, node, owner, ttl_ms, now_ms } => {
+                let token = match self.locks.get(name) {
+                    Some(lease) if !lease.is_expired_at(*now_ms) => {
+                        if lease.node != *node || lease.owner != *owner {
+                            return None;
+                        }
+                        // Renewal keeps the token
+                        lease.token
+                    }
+                    _ => index,
+                };
+                let lease = LockLease {
+                    name: name.clone(),
+                    node: *node,
+                    owner: owner.clone(),
+                    token,
+                    expires_at_ms: now_ms.saturating_add(*ttl_ms),
+                };
+                let value = serde_json::to_string(&lease).ok()?;
+                self.locks.insert(name.clone(), lease);
+                return Some(KvEvent {
+                    key: format!("{}{}", LOCK_EVENT_PREFIX, name),
+                    entry: Some(KvEntry { value, version: index }),
+                    version: index,
+                });
+            }
+            KvCommand::Release { name, token } => {
+                if self.locks.get(name)?.token != *token {
+                    return None;
+                }
+                self.locks.remove(name);
+                return Some(KvEvent {
+                    key: format!("{}{}", LOCK_EVENT_PREFIX, name),
+                    entry: None,
+                    version: index,
+                });
+            }
+            KvCommand::Put { key, value } => {
+                let entry = KvEntry { value: value.clone(), version: index };
+                self.entries.insert(key.clone(), entry.clone());
+                Some(entry)
+            }
+            KvCommand::Cas { key, expected, value } => {
+                let current = self.entries.get(key).map(|e| &e.value);
+                if current != expected.as_ref() {
+                    return None;
+                }
+                let entry = KvEntry { value: value.clone(), version: index };
+                self.entries.insert(key.clone(), entry.clone());
+                Some(entry)
+            }
+            KvCommand::Delete { key } => {
+                self.entries.remove(key)?;
+                None
+            }
+        };
+
+        Some(KvEvent {
+            key: command.key().to_string(),
+            entry,
+            version: index,
+        })
+    }
+
+    /// Read a key
+    pub fn get(&self, key: &str) -> Option<&KvEntry> {
+        self.entries.get(key)
+    }
+
+    /// All entries whose key starts with `prefix`
+    pub fn scan(&self, prefix: &str) -> Vec<(String, KvEntry)> {
+        self.entries
+            .range(prefix.to_string()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// Current lease of a lock, including one that has lapsed but not been
+    /// taken over yet
+    pub fn lock(&self, name: &str) -> Option<&LockLease> {
+        self.locks.get(name)
+    }
+
+    /// All recorded lock leases
+    pub fn locks(&self) -> Vec<LockLease> {
+        self.locks.values().cloned().collect()
+    }
+
+    /// Highest log index applied
+    pub fn last_applied(&self) -> u64 {
+        self.last_applied
+    }
+}
+
+/// Wall-clock time in milliseconds since the Unix epoch
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Handle to the replicated key-value store
+///
+/// Cloning the handle is cheap; all clones share the same replica.
+#[derive(Debug, Clone)]
+pub struct DistributedKv {
+    /// Local node (recorded as proposer)
+    node_id: NodeId,
+    /// Consensus engine writes are proposed through
+    consensus: Arc<RwLock<ConsensusEngine>>,
+    /// Local replica
+    state: Arc<RwLock<KvStateMachine>>,
+    /// Change notifications
+    events: broadcast::Sender<KvEvent>,
+}
+
+impl DistributedKv {
+    /// Create a store backed by a consensus engine
+    pub fn new(node_id: NodeId, consensus: Arc<RwLock<ConsensusEngine>>) -> Self {
+        let (events, _) = broadcast::channel(WATCH_CHANNEL_CAPACITY);
+        Self {
+            node_id,
+            consensus,
+            state: Arc::new(RwLock::new(KvStateMachine::new())),
+            events,
+        }
+    }
+
+    /// Set a key, returning the version it was written at
+    pub async fn put(&self, key: impl Into<String>, value: impl Into<String>) -> P2PResult<u64> {
+        let command = KvCommand::Put { key: key.into(), value: value.into() };
+        let (version, _) = self.submit(command).await?;
+        Ok(version)
+    }
+
+    /// Read a key from the local replica
+    pub async fn get(&self, key: &str) -> Option<KvEntry> {
+        self.state.read().await.get(key).cloned()
+    }
+
+    /// Read every key with the given prefix from the local replica
+    pub async fn scan(&self, prefix: &str) -> Vec<(String, KvEntry)> {
+        self.state.read().await.scan(prefix)
+    }
+
+    /// Set `key` to `value` only if its current value equals `expected`
+    ///
+    /// Pass `None` as `expected` to create the key only if it is absent.
+    /// Returns whether the swap was applied.
+    pub async fn cas(&self, key: impl Into<String>, expected: Option<String>, value: impl Into<String>) -> P2PResult<bool> {
+        let command = KvCommand::Cas { key: key.into(), expected, value: value.into() };
+        let (_, event) = self.submit(command).await?;
+        Ok(event.is_some())
+    }
+
+    /// Remove a key, returning whether it existed
+    pub async fn delete(&self, key: impl Into<String>) -> P2PResult<bool> {
+        let (_, event) = self.submit(KvCommand::Delete { key: key.into() }).await?;
+        Ok(event.is_some())
+    }
+
+    /// Take or renew the lock `name` for `owner` on this node
+    ///
+    /// Returns the granted lease, or `None` if another holder's lease is
+    /// still live. Renewing keeps the fencing token; callers should pass the
+    /// token to whatever the lock protects and have it reject lower ones.
+    pub async fn acquire_lock(&self, name: impl Into<String>, owner: impl Into<String>, ttl: Duration) -> P2PResult<Option<LockLease>> {
+        let command = KvCommand::Acquire {
+            name: name.into(),
+            node: self.node_id,
+            owner: owner.into(),
+            ttl_ms: ttl.as_millis() as u64,
+            now_ms: now_ms(),
+        };
+        let (_, event) = self.submit(command).await?;
+        match event.and_then(|e| e.entry) {
+            Some(entry) => Ok(Some(serde_json::from_str(&entry.value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Release a lock held under `token`, returning whether it was released
+    ///
+    /// A token superseded by a later holder releases nothing.
+    pub async fn release_lock(&self, name: impl Into<String>, token: u64) -> P2PResult<bool> {
+        let (_, event) = self.submit(KvCommand::Release { name: name.into(), token }).await?;
+        Ok(event.is_some())
+    }
+
+    /// Live lease of a lock on the local replica
+    pub async fn lock_holder(&self, name: &str) -> Option<LockLease> {
+        let now = now_ms();
+        self.state
+            .read()
+            .await
+            .lock(name)
+            .filter(|lease| !lease.is_expired_at(now))
+            .cloned()
+    }
+
+    /// Whether `token` is the fencing token of the live lease on `name`
+    pub async fn validate_token(&self, name: &str, token: u64) -> bool {
+        self.lock_holder(name).await.is_some_and(|lease| lease.token == token)
+    }
+
+    /// Release every lock held by `node`, returning how many were released
+    ///
+    /// Called when a node is declared failed so its locks do not have to
+    /// wait out their leases.
+    pub async fn release_node_locks(&self, node: NodeId) -> P2PResult<usize> {
+        let held: Vec<LockLease> = self
+            .state
+            .read()
+            .await
+            .locks()
+            .into_iter()
+            .filter(|lease| lease.node == node)
+            .collect();
+        let mut released = 0;
+        for lease in held {
+            if self.release_lock(lease.name, lease.token).await? {
+                released += 1;
+            }
+        }
+        Ok(released)
+    }
+
+    /// Stand for leadership of `group`, or renew it if already held
+    ///
+    /// Returns the current leader either way. Leadership is a lock on
+    /// [`ELECTION_LOCK_PREFIX`] + group, so it lapses after `ttl` unless the
+    /// leader calls this again, and its term is the lease's fencing token.
+    pub async fn elect_leader(&self, group: &str, ttl: Duration) -> P2PResult<Leadership> {
+        let name = format!("{}{}", ELECTION_LOCK_PREFIX, group);
+        let owner = self.node_id.to_string();
+        let lease = match self.acquire_lock(name.clone(), owner, ttl).await? {
+            Some(lease) => lease,
+            None => match self.lock_holder(&name).await {
+                Some(lease) => lease,
+                // The leader's lease lapsed between the two reads
+                None => self
+                    .acquire_lock(name.clone(), self.node_id.to_string(), ttl)
+                    .await?
+                    .ok_or_else(|| P2PError::Generic(format!("election for '{}' did not settle", group)))?,
+            },
+        };
+        Ok(Leadership {
+            group: group.to_string(),
+            leader: lease.node,
+            term: lease.token,
+            expires_at_ms: lease.expires_at_ms,
+            is_local: lease.node == self.node_id,
+        })
+    }
+
+    /// Subscribe to every change applied to the local replica
+    pub fn watch(&self) -> broadcast::Receiver<KvEvent> {
+        self.events.subscribe()
+    }
+
+    /// Wait until `key` is modified after `since`, or the timeout expires
+    ///
+    /// Returns immediately if the key already has a newer version.
+    pub async fn wait_for_change(&self, key: &str, since: u64, timeout: Duration) -> Option<KvEvent> {
+        let mut events = self.watch();
+        if let Some(entry) = self.get(key).await {
+            if entry.version > since {
+                let version = entry.version;
+                return Some(KvEvent { key: key.to_string(), entry: Some(entry), version });
+            }
+        }
+
+        tokio::time::timeout(timeout, async {
+            loop {
+                match events.recv().await {
+                    Ok(event) if event.key == key && event.version > since => return Some(event),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Apply a result committed through consensus
+    ///
+    /// Followers call this for entries replicated from the leader; results
+    /// that do not carry a KV command are ignored.
+    pub async fn apply_committed(&self, result: &ConsensusResult) -> P2PResult<Option<KvEvent>> {
+        let Ok(command) = KvCommand::decode(&result.value.data) else {
+            return Ok(None);
+        };
+        let event = self.state.write().await.apply(result.sequence, &command);
+        if let Some(event) = &event {
+            // No receivers is not an error
+            let _ = self.events.send(event.clone());
+        }
+        Ok(event)
+    }
+
+    /// Propose a command and apply it once committed
+    async fn submit(&self, command: KvCommand) -> P2PResult<(u64, Option<KvEvent>)> {
+        let value = ConsensusValue::new(command.encode()?, self.node_id);
+        let result = self.consensus.read().await.propose(value).await?;
+        let event = self.apply_committed(&result).await?;
+        if result.sequence > self.state.read().await.last_applied() {
+            return Err(P2PError::Consensus(ConsensusError::CommitFailed(
+                format!("KV command at index {} was not applied", result.sequence)
+            )));
+        }
+        Ok((result.sequence, event))
+    }
+
+    /// Local node, which proposals are recorded against
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    /// Install this handle as the store used by TLisp `kv-*` builtins
+    pub fn install_global(&self) {
+        *GLOBAL_KV.write() = Some(self.clone());
+    }
+
+    /// Store used by TLisp `kv-*` builtins, if one is installed
+    pub fn global() -> Option<DistributedKv> {
+        GLOBAL_KV.read().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::p2p::consensus::ConsensusConfig;
+
+    async fn kv() -> DistributedKv {
+        let engine = ConsensusEngine::new(ConsensusConfig::default()).unwrap();
+        engine.start().await.unwrap();
+        engine.bootstrap_consensus().await.unwrap();
+        DistributedKv::new(NodeId::new(), Arc::new(RwLock::new(engine)))
+    }
+
+    #[test]
+    fn test_state_machine_cas_and_replay() {
+        let mut sm = KvStateMachine::new();
+        let put = KvCommand::Put { key: "a".to_string(), value: "1".to_string() };
+        assert!(sm.apply(1, &put).is_some());
+        // Replayed index is ignored
+        assert!(sm.apply(1, &KvCommand::Delete { key: "a".to_string() }).is_none());
+        assert_eq!(sm.get("a").unwrap().value, "1");
+
+        let stale = KvCommand::Cas { key: "a".to_string(), expected: Some("0".to_string()), value: "2".to_string() };
+        assert!(sm.apply(2, &stale).is_none());
+        let fresh = KvCommand::Cas { key: "a".to_string(), expected: Some("1".to_string()), value: "2".to_string() };
+        assert_eq!(sm.apply(3, &fresh).unwrap().version, 3);
+        assert_eq!(sm.get("a").unwrap(), &KvEntry { value: "2".to_string(), version: 3 });
+        assert_eq!(sm.last_applied(), 3);
+    }
+
+    #[test]
+    fn test_state_machine_lock_fencing() {
+        let mut sm = KvStateMachine::new();
+        let (a, b) = (NodeId::new(), NodeId::new());
+        let acquire = |node, owner: &str, now_ms| KvCommand::Acquire {
+            name: "jobs".to_string(), node, owner: owner.to_string(), ttl_ms: 100, now_ms,
+        };
+
+        let event = sm.apply(1, &acquire(a, "worker", 0)).unwrap();
+        assert_eq!(event.key, "lock:jobs");
+        assert_eq!(sm.lock("jobs").unwrap().token, 1);
+        // Held by a live lease elsewhere
+        assert!(sm.apply(2, &acquire(b, "worker", 50)).is_none());
+        // Renewal keeps the token and extends the lease
+        sm.apply(3, &acquire(a, "worker", 50)).unwrap();
+        assert_eq!(sm.lock("jobs").unwrap().token, 1);
+        assert_eq!(sm.lock("jobs").unwrap().expires_at_ms, 150);
+        // Once lapsed, a new holder gets a higher token
+        sm.apply(4, &acquire(b, "worker", 200)).unwrap();
+        assert_eq!(sm.lock("jobs").unwrap().token, 4);
+
+        // The stale token cannot release the successor's lock
+        assert!(sm.apply(5, &KvCommand::Release { name: "jobs".to_string(), token: 1 }).is_none());
+        assert!(sm.apply(6, &KvCommand::Release { name: "jobs".to_string(), token: 4 }).unwrap().entry.is_none());
+        assert!(sm.lock("jobs").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_locks_and_election() {
+        let kv = kv().await;
+        let lease = kv.acquire_lock("deploy", "a", Duration::from_secs(30)).await.unwrap().unwrap();
+        assert!(kv.acquire_lock("deploy", "b", Duration::from_secs(30)).await.unwrap().is_none());
+        assert!(kv.validate_token("deploy", lease.token).await);
+
+        let leadership = kv.elect_leader("schedulers", Duration::from_secs(30)).await.unwrap();
+        assert!(leadership.is_local);
+        assert_eq!(leadership.leader, kv.node_id());
+        // Re-electing renews the same term
+        assert_eq!(kv.elect_leader("schedulers", Duration::from_secs(30)).await.unwrap().term, leadership.term);
+
+        // A failed node's locks are released
+        assert_eq!(kv.release_node_locks(kv.node_id()).await.unwrap(), 2);
+        assert!(kv.lock_holder("deploy").await.is_none());
+        assert!(!kv.release_lock("deploy", lease.token).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_put_get_cas() {
+        let kv = kv().await;
+        let version = kv.put("config/replicas", "3").await.unwrap();
+        assert_eq!(kv.get("config/replicas").await.unwrap().version, version);
+
+        assert!(!kv.cas("config/replicas", Some("2".to_string()), "5").await.unwrap());
+        assert!(kv.cas("config/replicas", Some("3".to_string()), "5").await.unwrap());
+        assert!(kv.cas("config/leader", None, "node-a").await.unwrap());
+        assert!(!kv.cas("config/leader", None, "node-b").await.unwrap());
+
+        assert_eq!(kv.get("config/replicas").await.unwrap().value, "5");
+        assert_eq!(kv.scan("config/").await.len(), 2);
+        assert!(kv.delete("config/leader").await.unwrap());
+        assert!(kv.get("config/leader").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_change() {
+        let kv = kv().await;
+        let version = kv.put("flag", "off").await.unwrap();
+
+        let watcher = kv.clone();
+        let waiter = tokio::spawn(async move {
+            watcher.wait_for_change("flag", version, Duration::from_secs(5)).await
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        kv.put("other", "x").await.unwrap();
+        kv.put("flag", "on").await.unwrap();
+
+        let event = waiter.await.unwrap().unwrap();
+        assert_eq!(event.entry.unwrap().value, "on");
+        assert!(kv.wait_for_change("flag", event.version, Duration::from_millis(10)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tlisp_builtins() {
+        let kv = kv().await;
+        kv.install_global();
+
+        let mut interpreter = crate::tlisp::TlispInterpreter::new();
+        assert_eq!(interpreter.eval(r#"(kv-get "tlisp/mode")"#).unwrap(), crate::tlisp::Value::Null);
+        assert!(matches!(interpreter.eval(r#"(kv-put "tlisp/mode" "fast")"#).unwrap(), crate::tlisp::Value::Int(_)));
+        assert_eq!(interpreter.eval(r#"(kv-cas "tlisp/mode" "slow" "safe")"#).unwrap(), crate::tlisp::Value::Bool(false));
+        assert_eq!(interpreter.eval(r#"(kv-cas "tlisp/mode" "fast" "safe")"#).unwrap(), crate::tlisp::Value::Bool(true));
+        assert_eq!(
+            interpreter.eval(r#"(kv-get "tlisp/mode")"#).unwrap(),
+            crate::tlisp::Value::String("safe".to_string())
+        );
+        assert_eq!(interpreter.eval(r#"(kv-watch "tlisp/mode" 1000 10)"#).unwrap(), crate::tlisp::Value::Null);
+
+        let token = interpreter.eval(r#"(lock-acquire "tlisp/lock" 30000 "a")"#).unwrap();
+        assert!(matches!(token, crate::tlisp::Value::Int(_)));
+        assert_eq!(interpreter.eval(r#"(lock-acquire "tlisp/lock" 30000 "b")"#).unwrap(), crate::tlisp::Value::Null);
+        assert_eq!(
+            interpreter.eval(&format!(r#"(lock-release "tlisp/lock" {})"#, token)).unwrap(),
+            crate::tlisp::Value::Bool(true)
+        );
+        match interpreter.eval(r#"(elect-leader "tlisp/group")"#).unwrap() {
+            crate::tlisp::Value::List(items) => assert_eq!(items[2], crate::tlisp::Value::Bool(true)),
+            other => panic!("unexpected election result {}", other),
+        }
+    }
+}
@@ -12,26 +12,31 @@ pub mod consensus;
 pub mod actor;
 pub mod cluster;
 pub mod node;
+pub mod kv;
+pub mod crdt;
 
 // Re-export core types and functions
 pub use types::*;
 pub use error::*;
 pub use node::ReamNode;
+pub use kv::{DistributedKv, KvCommand, KvEntry, KvEvent, Leadership, LockLease};
+pub use crdt::{Crdt, CrdtDelta, CrdtKind, CrdtOp, CrdtReplica, GCounter, LwwRegister, OrSet, PNCounter, Rga};
 
 // Re-export network components
 pub use network::{NetworkLayer, SessionType, NetworkProtocol};
 
 // Re-export discovery components
-pub use discovery::{ReamDHT, GossipProtocol, NodeDiscovery};
+pub use discovery::{ReamDHT, GossipProtocol, NodeDiscovery, LocalDiscovery, LocalDiscoveryConfig};
 
 // Re-export consensus components
 pub use consensus::{ConsensusEngine, PBFTConsensus, RaftConsensus};
 
 // Re-export actor components
 pub use actor::{DistributedActor, DistributedActorRegistry, MigrationManager, DistributedActorRef, MigrationResult};
+pub use actor::{PlacementStrategy, PlacementManager, ConsistentHashPlacement, ConsistentHashRing, RebalanceMove};
 
 // Re-export cluster components
-pub use cluster::{ClusterManager, FailureDetector, ClusterMetrics};
+pub use cluster::{ClusterManager, FailureDetector, ClusterMetrics, ClusterTopology, LocalityTier, VoterPlacement};
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
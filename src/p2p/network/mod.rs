@@ -14,6 +14,11 @@ pub use transport::*;
 pub use router::*;
 
 use crate::p2p::{P2PResult, P2PError, NetworkError, NodeId, NodeInfo};
+use crate::runtime::SchemaRegistry;
+use crate::types::MessagePayload;
+use crate::telemetry::{self, TraceContext};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry::trace::SpanKind;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -32,6 +37,8 @@ pub struct NetworkLayer {
     connections: Arc<RwLock<HashMap<NodeId, Connection>>>,
     /// Network configuration
     config: NetworkConfig,
+    /// Schemas actor messages crossing nodes are checked against
+    schemas: Option<Arc<SchemaRegistry>>,
 }
 
 impl NetworkLayer {
@@ -46,9 +53,45 @@ impl NetworkLayer {
             router,
             connections,
             config,
+            schemas: None,
         })
     }
 
+    /// Check the actor messages sent and received against `schemas`
+    pub fn with_schemas(mut self, schemas: Arc<SchemaRegistry>) -> Self {
+        self.schemas = Some(schemas);
+        self
+    }
+
+    /// Check an actor message against the schemas, converting its payload
+    /// to the version sent or the version this node reads
+    fn check_schema(&self, message: NetworkMessage, outgoing: bool) -> P2PResult<NetworkMessage> {
+        let Some(schemas) = &self.schemas else {
+            return Ok(message);
+        };
+        match message {
+            NetworkMessage::Traced { context, message } => Ok(NetworkMessage::Traced {
+                context,
+                message: Box::new(self.check_schema(*message, outgoing)?),
+            }),
+            NetworkMessage::Actor(ActorMessage::DeliverMessage { target_actor, payload }) => {
+                let rejected = |e: &dyn std::fmt::Display| {
+                    P2PError::Network(NetworkError::InvalidMessage(format!("message for actor {}: {}", target_actor, e)))
+                };
+                let decoded = ActorMessage::decode_payload(&payload).map_err(|e| rejected(&e))?;
+                let checked = if outgoing { schemas.outgoing(decoded) } else { schemas.incoming(decoded) };
+                let payload = ActorMessage::encode_payload(&checked.map_err(|e| rejected(&e))?)?;
+                Ok(NetworkMessage::Actor(ActorMessage::DeliverMessage { target_actor, payload }))
+            }
+            message => Ok(message),
+        }
+    }
+
+    /// Check a message received from another node before it is handled
+    pub fn receive_message(&self, message: NetworkMessage) -> P2PResult<NetworkMessage> {
+        self.check_schema(message, false)
+    }
+
     /// Start the network layer
     pub async fn start(&self) -> P2PResult<()> {
         let mut transport = self.transport.write().await;
@@ -94,6 +137,14 @@ impl NetworkLayer {
 
     /// Send a message to a specific node
     pub async fn send_message(&self, target: NodeId, message: NetworkMessage) -> P2PResult<()> {
+        let cx = telemetry::start_span(
+            "p2p.send",
+            SpanKind::Producer,
+            None,
+            vec![KeyValue::new("ream.node", target.to_string())],
+        );
+        let message = self.check_schema(message, true)?.traced(&cx);
+
         let connections = self.connections.read().await;
         if let Some(connection) = connections.get(&target) {
             connection.send(message).await?;
@@ -107,6 +158,9 @@ impl NetworkLayer {
 
     /// Broadcast a message to all connected nodes
     pub async fn broadcast_message(&self, message: NetworkMessage) -> P2PResult<()> {
+        let cx = telemetry::start_span("p2p.broadcast", SpanKind::Producer, None, Vec::new());
+        let message = self.check_schema(message, true)?.traced(&cx);
+
         let connections = self.connections.read().await;
         let mut errors = Vec::new();
 
@@ -233,6 +287,30 @@ pub enum NetworkMessage {
         node_id: NodeId,
         accepted: bool,
     },
+    /// Message sent from within a traced span
+    Traced {
+        context: TraceContext,
+        message: Box<NetworkMessage>,
+    },
+}
+
+impl NetworkMessage {
+    /// Attach the trace context of the span held by `cx`, if any
+    pub fn traced(self, cx: &Context) -> NetworkMessage {
+        match (TraceContext::from_context(cx), self) {
+            (_, message @ NetworkMessage::Traced { .. }) => message,
+            (Some(context), message) => NetworkMessage::Traced { context, message: Box::new(message) },
+            (None, message) => message,
+        }
+    }
+
+    /// Split off the trace context, returning the message it wrapped
+    pub fn untrace(self) -> (Option<TraceContext>, NetworkMessage) {
+        match self {
+            NetworkMessage::Traced { context, message } => (Some(context), message.untrace().1),
+            message => (None, message),
+        }
+    }
 }
 
 /// Discovery message types
@@ -246,6 +324,10 @@ pub enum DiscoveryMessage {
     JoinCluster { node_info: NodeInfo },
     /// Join cluster response
     JoinClusterResponse { accepted: bool, cluster_info: Option<ClusterInfo> },
+    /// CRDT deltas spread by gossip, encoded with [`CrdtDelta::encode_all`]
+    ///
+    /// [`CrdtDelta::encode_all`]: crate::p2p::crdt::CrdtDelta::encode_all
+    CrdtDeltas { origin: NodeId, deltas: Vec<u8> },
 }
 
 /// Consensus message types
@@ -268,6 +350,23 @@ pub enum ActorMessage {
     MigrateActor { actor_id: crate::p2p::ActorId, target_node: NodeId },
 }
 
+impl ActorMessage {
+    /// Deliver `payload` to an actor on another node
+    pub fn deliver(target_actor: crate::p2p::ActorId, payload: &MessagePayload) -> P2PResult<Self> {
+        Ok(ActorMessage::DeliverMessage { target_actor, payload: Self::encode_payload(payload)? })
+    }
+
+    /// Payloads are JSON inside the binary frame, as bincode cannot read
+    /// the JSON values of `MessagePayload::Data`
+    pub fn encode_payload(payload: &MessagePayload) -> P2PResult<Vec<u8>> {
+        Ok(serde_json::to_vec(payload)?)
+    }
+
+    pub fn decode_payload(payload: &[u8]) -> P2PResult<MessagePayload> {
+        Ok(serde_json::from_slice(payload)?)
+    }
+}
+
 /// Cluster management message types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClusterMessage {
@@ -391,6 +490,43 @@ mod tests {
         assert!(network.stop().await.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_actor_messages_checked_against_schemas() {
+        use crate::runtime::TypedMessage;
+
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Greeting { name: String }
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct GreetingV2 { name: String, polite: bool }
+
+        let schemas = Arc::new(SchemaRegistry::new());
+        schemas.register::<Greeting>("greeting", 1).unwrap();
+        schemas.register::<GreetingV2>("greeting", 2).unwrap();
+        schemas.upgrade("greeting", 1, |body| Ok(serde_json::json!({"name": body["name"], "polite": true}))).unwrap();
+        let network = NetworkLayer::new(NetworkConfig::default()).await.unwrap().with_schemas(schemas);
+        let actor = crate::p2p::ActorId::new();
+
+        let greeting = TypedMessage::new("greeting", 1, &serde_json::json!({"name": "ada"})).unwrap();
+        let message = NetworkMessage::Actor(ActorMessage::deliver(actor, &greeting.into_payload()).unwrap());
+        let received = network.receive_message(message).unwrap();
+        let NetworkMessage::Actor(ActorMessage::DeliverMessage { payload, .. }) = received else {
+            panic!("expected an actor message");
+        };
+        let upgraded = TypedMessage::from_payload(&ActorMessage::decode_payload(&payload).unwrap()).unwrap();
+        assert_eq!(upgraded.version, 2);
+        assert_eq!(upgraded.body["polite"], true);
+
+        // Invalid messages are refused both ways, before they are sent
+        let invalid = TypedMessage::new("greeting", 1, &serde_json::json!({"name": 7})).unwrap();
+        let message = NetworkMessage::Actor(ActorMessage::deliver(actor, &invalid.into_payload()).unwrap());
+        assert!(network.receive_message(message.clone()).is_err());
+        let error = network.send_message(NodeId::new(), message).await.unwrap_err();
+        assert!(error.to_string().contains("greeting v1"), "{}", error);
+        assert!(network.receive_message(NetworkMessage::Ping { timestamp: 1 }).is_ok());
+    }
+
     #[tokio::test]
     async fn test_network_stats() {
         let config = NetworkConfig::default();
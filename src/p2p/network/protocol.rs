@@ -5,6 +5,11 @@
 
 use crate::p2p::{P2PResult, P2PError, NetworkError, NodeId, NodeInfo, ClusterInfo};
 use super::{SessionType, SessionChannel, NetworkMessage, DiscoveryMessage, ConsensusMessage, ActorMessage, ClusterMessage};
+use crate::p2p::crdt::{CrdtDelta, CrdtReplica};
+use crate::telemetry;
+use opentelemetry::KeyValue;
+use opentelemetry::context::FutureExt;
+use opentelemetry::trace::SpanKind;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -77,6 +82,15 @@ impl NetworkProtocol {
                 format!("Session {} not found", session_id)
             )))?;
 
+        // Handle the message in a span continuing the sender's trace
+        let (parent, message) = message.untrace();
+        let cx = telemetry::start_span(
+            "p2p.handle",
+            SpanKind::Server,
+            parent.as_ref(),
+            vec![KeyValue::new("ream.node", session.peer.to_string())],
+        );
+
         // Validate message against session type
         let _message_type = self.get_message_type(&message);
         // TODO: Fix session type validation - temporarily disabled for tests
@@ -84,10 +98,10 @@ impl NetworkProtocol {
 
         // Process message based on protocol type
         let response = match &session.protocol_type {
-            ProtocolType::NodeDiscovery => self.handle_discovery_message(session, message).await?,
-            ProtocolType::Consensus => self.handle_consensus_message(session, message).await?,
-            ProtocolType::ActorCommunication => self.handle_actor_message(session, message).await?,
-            ProtocolType::ClusterManagement => self.handle_cluster_message(session, message).await?,
+            ProtocolType::NodeDiscovery => self.handle_discovery_message(session, message).with_context(cx).await?,
+            ProtocolType::Consensus => self.handle_consensus_message(session, message).with_context(cx).await?,
+            ProtocolType::ActorCommunication => self.handle_actor_message(session, message).with_context(cx).await?,
+            ProtocolType::ClusterManagement => self.handle_cluster_message(session, message).with_context(cx).await?,
         };
 
         // Update statistics
@@ -126,6 +140,7 @@ impl NetworkProtocol {
                 DiscoveryMessage::FindNodeResponse { .. } => "FindNodeResponse".to_string(),
                 DiscoveryMessage::JoinCluster { .. } => "JoinCluster".to_string(),
                 DiscoveryMessage::JoinClusterResponse { .. } => "JoinClusterResponse".to_string(),
+                DiscoveryMessage::CrdtDeltas { .. } => "CrdtDeltas".to_string(),
             },
             NetworkMessage::Consensus(_) => "Consensus".to_string(),
             NetworkMessage::Actor(_) => "Actor".to_string(),
@@ -133,6 +148,7 @@ impl NetworkProtocol {
             NetworkMessage::Custom(_) => "Custom".to_string(),
             NetworkMessage::Handshake { .. } => "Handshake".to_string(),
             NetworkMessage::HandshakeAck { .. } => "HandshakeAck".to_string(),
+            NetworkMessage::Traced { message, .. } => self.get_message_type(message),
         }
     }
 
@@ -168,6 +184,13 @@ impl NetworkProtocol {
                         }
                     )))
                 }
+                DiscoveryMessage::CrdtDeltas { deltas, .. } => {
+                    // Merge into the local replica, which re-gossips what was new
+                    if let Some(replica) = CrdtReplica::global() {
+                        replica.merge_deltas(&CrdtDelta::decode_all(&deltas)?)?;
+                    }
+                    Ok(None)
+                }
                 _ => Ok(None),
             },
             _ => Err(P2PError::Network(NetworkError::ProtocolError(
@@ -4,6 +4,7 @@
 //! path optimization and failure recovery.
 
 use crate::p2p::{P2PResult, P2PError, NetworkError, NodeId};
+use crate::p2p::cluster::ClusterTopology;
 use super::{NetworkLayer, RoutingMessage, NetworkMessage};
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
@@ -19,6 +20,8 @@ pub struct MessageRouter {
     topology: Arc<RwLock<NetworkTopology>>,
     /// Routing statistics
     stats: Arc<RwLock<RoutingStats>>,
+    /// Region/zone view used for topology-aware replica selection
+    cluster_topology: Arc<RwLock<Option<ClusterTopology>>>,
 }
 
 impl MessageRouter {
@@ -28,6 +31,24 @@ impl MessageRouter {
             routing_table: Arc::new(RwLock::new(RoutingTable::new())),
             topology: Arc::new(RwLock::new(NetworkTopology::new())),
             stats: Arc::new(RwLock::new(RoutingStats::default())),
+            cluster_topology: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Install the region/zone view used for replica selection
+    pub async fn set_cluster_topology(&self, topology: ClusterTopology) {
+        *self.cluster_topology.write().await = Some(topology);
+    }
+
+    /// Choose which replica of a resource to contact
+    ///
+    /// Prefers replicas in the local zone, then the local region, and only
+    /// crosses region boundaries when no closer replica exists. Without a
+    /// cluster topology the first candidate is used.
+    pub async fn select_replica(&self, candidates: &[NodeId]) -> Option<NodeId> {
+        match self.cluster_topology.read().await.as_ref() {
+            Some(topology) => topology.select_replica(candidates),
+            None => candidates.first().copied(),
         }
     }
 
@@ -287,6 +308,8 @@ pub enum RoutingAlgorithm {
     LoadBalanced,
     /// Adaptive routing based on network conditions
     Adaptive,
+    /// Prefer replicas in the local zone/region
+    TopologyAware,
 }
 
 #[cfg(test)]
@@ -5,6 +5,10 @@
 
 use crate::p2p::{P2PResult, P2PError, NetworkError, NodeId};
 use super::{NetworkMessage, NetworkConfig};
+use crate::telemetry;
+use crate::security::audit::{self, AuditKind};
+use opentelemetry::KeyValue;
+use opentelemetry::trace::SpanKind;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -80,12 +84,12 @@ impl Transport {
                                 // Spawn task to handle this connection
                                 tokio::spawn(async move {
                                     if let Err(e) = Self::handle_connection(stream, addr, connections, stats, config).await {
-                                        eprintln!("Connection error: {}", e);
+                                        tracing::warn!(error = %e, %addr, "Connection error");
                                     }
                                 });
                             }
                             Err(e) => {
-                                eprintln!("Failed to accept connection: {}", e);
+                                tracing::warn!(error = %e, "Failed to accept connection");
                                 break;
                             }
                         }
@@ -155,6 +159,7 @@ impl Transport {
         // Add to connections
         self.connections.write().await.insert(peer_node_id, connection);
         self.stats.write().await.connections_established += 1;
+        audit::record(AuditKind::RemoteConnection, addr, format!("Connected to node {}", peer_node_id));
 
         Ok(peer_node_id)
     }
@@ -225,6 +230,7 @@ impl Transport {
             let mut transport_stats = stats.write().await;
             transport_stats.connections_established += 1;
         }
+        audit::record(AuditKind::RemoteConnection, addr, format!("Accepted connection from node {}", peer_node_id));
 
         // Handle messages from this connection
         loop {
@@ -236,6 +242,15 @@ impl Transport {
                         transport_stats.messages_received += 1;
                     }
 
+                    // Handle the message in a span continuing the sender's trace
+                    let (parent, message) = message.untrace();
+                    let _cx = telemetry::start_span(
+                        "p2p.receive",
+                        SpanKind::Consumer,
+                        parent.as_ref(),
+                        vec![KeyValue::new("ream.node", peer_node_id.to_string())],
+                    );
+
                     // Process message (for now, just echo back)
                     match message {
                         NetworkMessage::Ping { timestamp } => {
@@ -244,12 +259,12 @@ impl Transport {
                         }
                         _ => {
                             // Handle other message types
-                            println!("Received message from {}: {:?}", peer_node_id, message);
+                            tracing::debug!(peer = ?peer_node_id, ?message, "Received message");
                         }
                     }
                 }
                 Err(e) => {
-                    println!("Connection error with {}: {}", peer_node_id, e);
+                    tracing::warn!(peer = ?peer_node_id, error = %e, "Connection error");
                     break;
                 }
             }
@@ -295,7 +310,7 @@ impl Connection {
                         match message {
                             Some(msg) => {
                                 if let Err(e) = Self::send_message(&mut writer, &msg).await {
-                                    eprintln!("Failed to send message: {}", e);
+                                    tracing::warn!(error = %e, "Failed to send message");
                                     break;
                                 }
                                 connection_stats.write().await.messages_sent += 1;
@@ -315,7 +330,7 @@ impl Connection {
                                 break;
                             }
                             Err(e) => {
-                                eprintln!("Failed to read message: {}", e);
+                                tracing::warn!(error = %e, "Failed to read message");
                                 break;
                             }
                         }
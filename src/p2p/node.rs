@@ -6,13 +6,17 @@
 use crate::p2p::{
     P2PResult, NodeId, NodeInfo, NodeConfig, ClusterInfo, ActorId, PlacementConstraints,
     NetworkLayer, NodeDiscovery, ConsensusEngine, DistributedActorRegistry, ClusterManager,
-    MigrationManager,
+    MigrationManager, PlacementManager, PlacementStrategy, RebalanceMove, DistributedKv,
 };
 use crate::p2p::actor::{DistributedActorRef, MigrationResult};
+use crate::p2p::crdt::{CrdtDelta, CrdtReplica};
+use crate::p2p::network::{DiscoveryMessage, NetworkMessage, NetworkStats};
 use crate::p2p::discovery::DiscoveryConfig;
 use crate::runtime::ReamActor;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 
 /// Main P2P node that integrates all distributed system components
 #[derive(Debug)]
@@ -33,6 +37,16 @@ pub struct ReamNode {
     cluster_manager: Arc<RwLock<ClusterManager>>,
     /// Migration manager
     migration_manager: Arc<RwLock<MigrationManager>>,
+    /// Actor placement manager
+    placement: Arc<RwLock<PlacementManager>>,
+    /// Replicated key-value store
+    kv: DistributedKv,
+    /// CRDTs shared through gossip
+    crdt: Arc<CrdtReplica>,
+    /// Interval between gossip rounds
+    gossip_interval: Duration,
+    /// Background task spreading CRDT deltas
+    gossip_task: Option<JoinHandle<()>>,
     /// Node state
     state: Arc<RwLock<NodeState>>,
 }
@@ -47,7 +61,11 @@ impl ReamNode {
         let network = Arc::new(RwLock::new(NetworkLayer::new(network_config).await?));
 
         // Create discovery system
-        let discovery_config = DiscoveryConfig::default();
+        let discovery_config = DiscoveryConfig {
+            local_discovery: config.local_discovery.clone(),
+            ..DiscoveryConfig::default()
+        };
+        let gossip_interval = discovery_config.gossip_config.gossip_interval;
         let discovery = Arc::new(RwLock::new(
             NodeDiscovery::new(node_info.clone(), discovery_config).await?
         ));
@@ -56,18 +74,27 @@ impl ReamNode {
         let consensus_config = crate::p2p::consensus::ConsensusConfig::default();
         let consensus = Arc::new(RwLock::new(ConsensusEngine::new(consensus_config)?));
 
+        // Create key-value store on top of consensus
+        let kv = DistributedKv::new(node_info.node_id, Arc::clone(&consensus));
+
+        // Create CRDT replica shared through gossip
+        let crdt = Arc::new(CrdtReplica::new(node_info.node_id.to_string()));
+
         // Create actor registry
         let actor_registry = Arc::new(RwLock::new(DistributedActorRegistry::new()));
 
         // Create cluster manager
         let cluster_config = crate::p2p::cluster::ClusterConfig::default();
         let cluster_manager = Arc::new(RwLock::new(
-            ClusterManager::new(node_info.clone(), cluster_config).await?
+            ClusterManager::new(node_info.clone(), cluster_config).await?.with_kv(kv.clone())
         ));
 
         // Create migration manager
         let migration_manager = Arc::new(RwLock::new(MigrationManager::new()));
 
+        // Create placement manager
+        let placement = Arc::new(RwLock::new(PlacementManager::new()));
+
         // Initialize node state
         let state = Arc::new(RwLock::new(NodeState::Initializing));
 
@@ -80,6 +107,11 @@ impl ReamNode {
             actor_registry,
             cluster_manager,
             migration_manager,
+            placement,
+            kv,
+            crdt,
+            gossip_interval,
+            gossip_task: None,
             state,
         })
     }
@@ -113,6 +145,24 @@ impl ReamNode {
             cluster_manager.start().await?;
         }
 
+        // Receive and spread CRDT deltas
+        self.crdt.install_global();
+        let (node_id, network, discovery, crdt) = (
+            self.node_info.node_id,
+            Arc::clone(&self.network),
+            Arc::clone(&self.discovery),
+            Arc::clone(&self.crdt),
+        );
+        let interval = self.gossip_interval;
+        self.gossip_task = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                // Failed rounds keep their deltas for the next one
+                let _ = gossip_crdt_deltas(node_id, &network, &discovery, &crdt).await;
+            }
+        }));
+
         // Update state
         *self.state.write().await = NodeState::Running;
 
@@ -124,6 +174,10 @@ impl ReamNode {
         // Update state
         *self.state.write().await = NodeState::Stopping;
 
+        if let Some(task) = self.gossip_task.take() {
+            task.abort();
+        }
+
         // Stop components in reverse order
         {
             let cluster_manager = self.cluster_manager.read().await;
@@ -205,8 +259,61 @@ impl ReamNode {
 
     /// Add a member to the cluster
     pub async fn add_cluster_member(&self, node_info: NodeInfo) -> P2PResult<()> {
+        {
+            let cluster_manager = self.cluster_manager.read().await;
+            cluster_manager.add_member(node_info).await?;
+        }
+
+        // Re-place Raft voters for the new membership view
+        let members = self.placement_members().await;
+        {
+            let consensus = self.consensus.read().await;
+            consensus.apply_cluster_members(&members).await?;
+        }
+
+        self.rebalance_actors().await?;
+        Ok(())
+    }
+
+    /// Replace the actor placement strategy
+    pub async fn set_placement_strategy(&self, strategy: Box<dyn PlacementStrategy>) -> P2PResult<Vec<RebalanceMove>> {
+        let moves = {
+            let mut placement = self.placement.write().await;
+            placement.set_strategy(strategy)
+        };
+        self.apply_rebalance_moves(&moves).await?;
+        Ok(moves)
+    }
+
+    /// Recompute actor placement against the current membership and
+    /// migrate actors whose owner changed
+    pub async fn rebalance_actors(&self) -> P2PResult<Vec<RebalanceMove>> {
+        let members = self.placement_members().await;
+        let moves = {
+            let mut placement = self.placement.write().await;
+            placement.update_members(members)
+        };
+        self.apply_rebalance_moves(&moves).await?;
+        Ok(moves)
+    }
+
+    /// Members eligible for actor placement
+    async fn placement_members(&self) -> Vec<NodeInfo> {
         let cluster_manager = self.cluster_manager.read().await;
-        cluster_manager.add_member(node_info).await
+        let mut members = cluster_manager.get_members().await;
+        if !members.iter().any(|m| m.node_id == self.node_info.node_id) {
+            members.push(self.node_info.clone());
+        }
+        members
+    }
+
+    /// Drive rebalance moves through the migration manager
+    async fn apply_rebalance_moves(&self, moves: &[RebalanceMove]) -> P2PResult<()> {
+        let migration_manager = self.migration_manager.read().await;
+        for rebalance_move in moves {
+            migration_manager.migrate_actor(rebalance_move.actor_id, rebalance_move.to).await?;
+        }
+        Ok(())
     }
 
     /// Get node information
@@ -214,17 +321,56 @@ impl ReamNode {
         Ok(self.node_info.clone())
     }
 
+    /// Get network statistics
+    pub async fn network_stats(&self) -> NetworkStats {
+        self.network.read().await.get_network_stats().await
+    }
+
+    /// Handle to the replicated key-value store
+    pub fn kv(&self) -> DistributedKv {
+        self.kv.clone()
+    }
+
+    /// CRDTs shared with the cluster through gossip
+    pub fn crdt(&self) -> Arc<CrdtReplica> {
+        Arc::clone(&self.crdt)
+    }
+
+    /// Run a gossip round now, returning how many peers received deltas
+    pub async fn gossip_crdt_deltas(&self) -> P2PResult<usize> {
+        gossip_crdt_deltas(self.node_info.node_id, &self.network, &self.discovery, &self.crdt).await
+    }
+
     /// Spawn a distributed actor
     pub async fn spawn_distributed_actor<A>(
         &mut self,
         _actor: A,
-        _placement_constraints: Option<PlacementConstraints>,
+        placement_constraints: Option<PlacementConstraints>,
     ) -> P2PResult<DistributedActorRef>
     where
         A: ReamActor + Send + Sync + 'static,
     {
+        let actor_id = ActorId::new();
+        let target_node = {
+            let members = self.placement_members().await;
+            let mut placement = self.placement.write().await;
+            if placement.members().len() != members.len() {
+                placement.update_members(members);
+            }
+            placement.place(actor_id, placement_constraints)?
+        };
+
         let actor_registry = self.actor_registry.read().await;
-        actor_registry.spawn_local_actor("generic_actor".to_string(), self.node_info.node_id).await
+        if target_node == self.node_info.node_id {
+            actor_registry
+                .spawn_local_actor_with_id(actor_id, "generic_actor".to_string(), target_node)
+                .await
+        } else {
+            // Remote spawn request would be routed to the target node
+            let actor_ref = DistributedActorRef::new(actor_id, target_node, "generic_actor".to_string());
+            actor_registry.register_remote_actor(actor_ref.clone()).await?;
+            Ok(actor_ref)
+        }
     }
 
     /// Migrate an actor to another node
@@ -233,8 +379,17 @@ impl ReamNode {
         actor_id: ActorId,
         target_node: NodeId,
     ) -> P2PResult<MigrationResult> {
-        let migration_manager = self.migration_manager.read().await;
-        migration_manager.migrate_actor(actor_id, target_node).await
+        let result = {
+            let migration_manager = self.migration_manager.read().await;
+            migration_manager.migrate_actor(actor_id, target_node).await?
+        };
+
+        if result.success {
+            let mut placement = self.placement.write().await;
+            placement.record(actor_id, target_node);
+        }
+
+        Ok(result)
     }
 
     /// Get node state
@@ -278,6 +433,38 @@ impl std::fmt::Display for NodeState {
     }
 }
 
+/// Send the deltas buffered since the last round to a few peers
+///
+/// Deltas are put back if no peer could be reached.
+async fn gossip_crdt_deltas(
+    node_id: NodeId,
+    network: &RwLock<NetworkLayer>,
+    discovery: &RwLock<NodeDiscovery>,
+    crdt: &CrdtReplica,
+) -> P2PResult<usize> {
+    let deltas = crdt.take_deltas();
+    if deltas.is_empty() {
+        return Ok(0);
+    }
+    let message = NetworkMessage::Discovery(DiscoveryMessage::CrdtDeltas {
+        origin: node_id,
+        deltas: CrdtDelta::encode_all(&deltas)?,
+    });
+
+    let network = network.read().await;
+    match discovery.read().await.gossip(&network, message).await {
+        Ok(0) => {
+            crdt.requeue(deltas)?;
+            Ok(0)
+        }
+        Ok(sent) => Ok(sent),
+        Err(e) => {
+            crdt.requeue(deltas)?;
+            Err(e)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -320,4 +507,21 @@ mod tests {
         
         node.stop().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_spawn_uses_placement() {
+        let config = NodeConfig::default();
+        let mut node = ReamNode::new(config).await.unwrap();
+        node.start().await.unwrap();
+        node.create_cluster().await.unwrap();
+
+        let local_id = node.get_node_info().await.unwrap().node_id;
+        let actor_ref = node
+            .spawn_distributed_actor(crate::runtime::actor::CounterActor::new(crate::types::Pid::new(), 0), None)
+            .await
+            .unwrap();
+        assert_eq!(actor_ref.node_id, local_id);
+
+        node.stop().await.unwrap();
+    }
 }
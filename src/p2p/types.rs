@@ -86,6 +86,9 @@ pub struct NodeInfo {
     pub version: String,
     /// Public key for cryptographic operations
     pub public_key: Vec<u8>,
+    /// Region/zone labels used for topology-aware decisions
+    #[serde(default)]
+    pub locality: NodeLocality,
 }
 
 impl NodeInfo {
@@ -97,8 +100,15 @@ impl NodeInfo {
             last_seen: SystemTime::now(),
             version: env!("CARGO_PKG_VERSION").to_string(),
             public_key: vec![], // TODO: Generate actual public key
+            locality: NodeLocality::default(),
         }
     }
+
+    /// Attach region/zone labels to this node
+    pub fn with_locality(mut self, locality: NodeLocality) -> Self {
+        self.locality = locality;
+        self
+    }
     
     /// Check if node is considered alive
     pub fn is_alive(&self, timeout: Duration) -> bool {
@@ -111,6 +121,50 @@ impl NodeInfo {
     }
 }
 
+/// Physical location labels of a node
+///
+/// Unlabelled nodes are treated as belonging to a single implicit
+/// region and zone, so clusters that never set labels behave exactly as
+/// a flat LAN cluster.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NodeLocality {
+    /// Region (e.g. a datacenter or cloud region such as "eu-west-1")
+    pub region: Option<String>,
+    /// Availability zone within the region (e.g. "eu-west-1a")
+    pub zone: Option<String>,
+}
+
+impl NodeLocality {
+    /// Create locality labels for a region and zone
+    pub fn new(region: impl Into<String>, zone: impl Into<String>) -> Self {
+        Self {
+            region: Some(region.into()),
+            zone: Some(zone.into()),
+        }
+    }
+
+    /// Check whether two localities share a region
+    pub fn same_region(&self, other: &NodeLocality) -> bool {
+        self.region == other.region
+    }
+
+    /// Check whether two localities share a zone (within the same region)
+    pub fn same_zone(&self, other: &NodeLocality) -> bool {
+        self.same_region(other) && self.zone == other.zone
+    }
+}
+
+impl std::fmt::Display for NodeLocality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            self.region.as_deref().unwrap_or("default"),
+            self.zone.as_deref().unwrap_or("default")
+        )
+    }
+}
+
 /// Node capabilities and features
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeCapabilities {
@@ -142,7 +196,7 @@ impl Default for NodeCapabilities {
 }
 
 /// Types of nodes in the cluster
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeType {
     /// Gateway node (handles external connections)
     Gateway,
@@ -261,6 +315,8 @@ pub struct NodeConfig {
     pub network_timeout: Duration,
     /// Heartbeat interval
     pub heartbeat_interval: Duration,
+    /// LAN broadcast discovery; disabled when `None`
+    pub local_discovery: Option<crate::p2p::discovery::LocalDiscoveryConfig>,
 }
 
 impl Default for NodeConfig {
@@ -272,6 +328,7 @@ impl Default for NodeConfig {
             bootstrap_nodes: vec![],
             network_timeout: Duration::from_secs(30),
             heartbeat_interval: Duration::from_secs(5),
+            local_discovery: None,
         }
     }
 }
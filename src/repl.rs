@@ -3,14 +3,151 @@ use crate::tlisp::{TlispInterpreter, Value};
 use crate::runtime::ReamRuntime;
 use crate::bytecode::{BytecodeCompiler, BytecodeVM, LanguageCompiler};
 use crate::jit::JitRuntime;
+use crate::daemon::ipc::IpcClient;
 use crate::error::ReamResult;
 use colored::*;
+use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
 use std::fs;
 use std::path::PathBuf;
 use std::time::Instant;
 
+/// Meta-commands offered by tab completion
+const META_COMMANDS: &[&str] = &[
+    ":load", ":type", ":time", ":help", ":quit", ":clear", ":info", ":env", ":hist", ":reset", ":debug", ":jit",
+];
+
+/// Meta-commands offered by tab completion when attached to a daemon
+const ATTACHED_META_COMMANDS: &[&str] = &[":actor", ":actors", ":quit"];
+
+/// Variables holding the last three results, most recent first
+const RESULT_VARS: [&str; 3] = ["*1", "*2", "*3"];
+
+/// Scan `input` for unclosed brackets, skipping strings and `;` comments.
+/// Returns the bracket depth and whether the input ends inside a string.
+fn scan_brackets(input: &str) -> (i32, bool) {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+
+    for c in input.chars() {
+        if in_comment {
+            in_comment = c != '\n';
+        } else if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                ';' => in_comment = true,
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    (depth, in_string)
+}
+
+/// Check whether `input` has no unclosed brackets or strings. Extra closing
+/// brackets count as complete so the parser can report them.
+pub fn input_is_complete(input: &str) -> bool {
+    let (depth, in_string) = scan_brackets(input);
+    depth <= 0 && !in_string
+}
+
+/// Argument of a REPL command written as `name <arg>` or `:name <arg>`
+fn command_argument<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(':').unwrap_or(line).strip_prefix(name)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let arg = rest.trim();
+    if arg.is_empty() { None } else { Some(arg) }
+}
+
+/// Line editor helper: completes symbols and meta-commands, and keeps
+/// reading lines while brackets are unbalanced
+pub struct ReplHelper {
+    /// Names bound in the interpreter, refreshed before each prompt
+    symbols: Vec<String>,
+    /// Meta-commands to complete
+    meta_commands: &'static [&'static str],
+}
+
+impl Default for ReplHelper {
+    fn default() -> Self {
+        ReplHelper { symbols: Vec::new(), meta_commands: META_COMMANDS }
+    }
+}
+
+impl ReplHelper {
+    /// Helper for a REPL attached to a daemon
+    pub fn attached() -> Self {
+        ReplHelper { symbols: Vec::new(), meta_commands: ATTACHED_META_COMMANDS }
+    }
+
+    /// Replace the symbols offered for completion
+    pub fn set_symbols(&mut self, symbols: Vec<String>) {
+        self.symbols = symbols;
+    }
+
+    /// Start of the word being completed and the candidates for it
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || "()[]{}'\"`,".contains(c))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let prefix = &line[start..pos];
+
+        let candidates = if prefix.starts_with(':') && start == 0 {
+            self.meta_commands.iter().filter(|c| c.starts_with(prefix)).map(|c| c.to_string()).collect()
+        } else if prefix.is_empty() {
+            Vec::new()
+        } else {
+            self.symbols.iter().filter(|s| s.starts_with(prefix)).cloned().collect()
+        };
+        (start, candidates)
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if input_is_complete(ctx.input()) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+impl Helper for ReplHelper {}
+
 pub struct ReplState {
     pub tlisp: TlispInterpreter,
     pub runtime: ReamRuntime,
@@ -26,7 +163,7 @@ pub struct ReplState {
 impl ReplState {
     pub fn new() -> ReamResult<Self> {
         Ok(ReplState {
-            tlisp: TlispInterpreter::new(),
+            tlisp: Self::new_interpreter(),
             runtime: ReamRuntime::new()?,
             bytecode_vm: BytecodeVM::new(),
             jit_runtime: JitRuntime::new(ReamRuntime::new().expect("Failed to create ReamRuntime")),
@@ -39,7 +176,7 @@ impl ReplState {
     }
     
     pub fn reset(&mut self) -> ReamResult<()> {
-        self.tlisp = TlispInterpreter::new();
+        self.tlisp = Self::new_interpreter();
         self.runtime = ReamRuntime::new()?;
         self.bytecode_vm = BytecodeVM::new();
         self.jit_runtime = JitRuntime::new(ReamRuntime::new().expect("Failed to create ReamRuntime"));
@@ -47,26 +184,71 @@ impl ReplState {
         Ok(())
     }
     
+    /// Interpreter with the result history variables bound to null
+    fn new_interpreter() -> TlispInterpreter {
+        let mut tlisp = TlispInterpreter::new();
+        for name in RESULT_VARS {
+            tlisp.define(name.to_string(), Value::Null);
+        }
+        tlisp
+    }
+
+    /// Shift `*1` and `*2` down and bind `*1` to the latest result
+    fn record_result(&mut self, value: &Value) {
+        for i in (1..RESULT_VARS.len()).rev() {
+            if let Some(previous) = self.tlisp.get(RESULT_VARS[i - 1]) {
+                self.tlisp.define(RESULT_VARS[i].to_string(), previous);
+            }
+        }
+        self.tlisp.define(RESULT_VARS[0].to_string(), value.clone());
+    }
+
+    /// Defined symbols, for tab completion
+    pub fn symbols(&self) -> Vec<String> {
+        self.tlisp.symbols()
+    }
+
     pub fn load_file(&mut self, path: &PathBuf) -> ReamResult<()> {
         let content = fs::read_to_string(path)
             .map_err(|e| crate::error::ReamError::Io(e))?;
         
         println!("{} {}", "Loading:".bright_yellow(), path.display());
         
-        let lines: Vec<&str> = content.lines().collect();
-        for (i, line) in lines.iter().enumerate() {
-            if !line.trim().is_empty() && !line.trim().starts_with(';') {
-                println!("{} {}", format!("[{}]", i + 1).dimmed(), line);
-                match self.eval_expression(line) {
-                    Ok(result) => {
-                        if !matches!(result, Value::Null) {
-                            println!("  {} {}", "=>".bright_green(), self.format_value(&result));
-                        }
-                    }
-                    Err(e) => {
-                        println!("  {} {}", "Error:".bright_red(), e);
+        // Group lines into complete top-level forms
+        let mut forms: Vec<(usize, String)> = Vec::new();
+        let mut form = String::new();
+        let mut form_line = 0;
+        for (i, line) in content.lines().enumerate() {
+            if form.is_empty() {
+                if line.trim().is_empty() || line.trim().starts_with(';') {
+                    continue;
+                }
+                form_line = i + 1;
+            } else {
+                form.push('\n');
+            }
+            form.push_str(line);
+
+            if input_is_complete(&form) {
+                forms.push((form_line, std::mem::take(&mut form)));
+            }
+        }
+        // An unterminated form is still evaluated so its error is reported
+        if !form.is_empty() {
+            forms.push((form_line, form));
+        }
+
+        for (line_number, form) in forms {
+            println!("{} {}", format!("[{}]", line_number).dimmed(), form);
+            match self.eval_expression(&form) {
+                Ok(result) => {
+                    if !matches!(result, Value::Null) {
+                        println!("  {} {}", "=>".bright_green(), self.format_value(&result));
                     }
                 }
+                Err(e) => {
+                    println!("  {} {}", "Error:".bright_red(), e);
+                }
             }
         }
         
@@ -96,6 +278,7 @@ impl ReplState {
                 if self.show_types {
                     println!("{} {}", "Type:".bright_blue(), self.format_type(&value));
                 }
+                self.record_result(&value);
                 Ok(value)
             }
             Err(e) => Err(e.into()),
@@ -124,6 +307,9 @@ impl ReplState {
             Value::Pid(pid) => {
                 format!("#<pid:{}>", pid.raw()).bright_magenta().to_string()
             }
+            Value::Binary(binary) => {
+                format!("#<binary:{}>", binary.len()).bright_magenta().to_string()
+            }
             Value::Unit => "()".dimmed().to_string(),
             Value::Null => "null".dimmed().to_string(),
             Value::StmVar(var) => format!("#<stm-var:{}>", var.name()).bright_magenta().to_string(),
@@ -273,7 +459,8 @@ impl ReplState {
 
 pub fn start_repl(load_file: Option<PathBuf>, show_banner: bool, history_file: PathBuf) -> ReamResult<()> {
     let mut state = ReplState::new()?;
-    let mut rl = Editor::<(), rustyline::history::DefaultHistory>::new().map_err(|e| crate::error::ReamError::Other(e.to_string()))?;
+    let mut rl = Editor::<ReplHelper, rustyline::history::DefaultHistory>::new().map_err(|e| crate::error::ReamError::Other(e.to_string()))?;
+    rl.set_helper(Some(ReplHelper::default()));
     
     // Load history if it exists
     if history_file.exists() {
@@ -300,6 +487,10 @@ pub fn start_repl(load_file: Option<PathBuf>, show_banner: bool, history_file: P
         } else {
             "ream> ".bright_green().bold()
         };
+
+        if let Some(helper) = rl.helper_mut() {
+            helper.set_symbols(state.symbols());
+        }
         
         match rl.readline(&prompt.to_string()) {
             Ok(line) => {
@@ -311,36 +502,36 @@ pub fn start_repl(load_file: Option<PathBuf>, show_banner: bool, history_file: P
                 let _ = rl.add_history_entry(line);
                 
                 match line {
-                    "quit" | "exit" | ":q" => {
+                    "quit" | "exit" | ":q" | ":quit" => {
                         println!("{}", "Goodbye!".bright_green());
                         break;
                     }
-                    "help" | ":h" => {
+                    "help" | ":h" | ":help" => {
                         print_help();
                     }
-                    "clear" | ":c" => {
+                    "clear" | ":c" | ":clear" => {
                         print!("\x1B[2J\x1B[1;1H");
                     }
-                    "info" | ":i" => {
+                    "info" | ":i" | ":info" => {
                         print_info();
                     }
-                    "env" | ":e" => {
+                    "env" | ":e" | ":env" => {
                         state.show_environment();
                     }
                     "history" | ":hist" => {
                         state.show_history();
                     }
-                    "reset" | ":r" => {
+                    "reset" | ":r" | ":reset" => {
                         if let Err(e) = state.reset() {
                             println!("{} {}", "Error resetting:".bright_red(), e);
                         } else {
                             println!("{}", "Environment reset".bright_green());
                         }
                     }
-                    "debug" | ":d" => {
+                    "debug" | ":d" | ":debug" => {
                         state.toggle_debug();
                     }
-                    "jit" | ":j" => {
+                    "jit" | ":j" | ":jit" => {
                         state.toggle_jit();
                     }
                     "types" | ":t" => {
@@ -351,13 +542,12 @@ pub fn start_repl(load_file: Option<PathBuf>, show_banner: bool, history_file: P
                     }
                     _ => {
                         // Handle special commands
-                        if line.starts_with("load ") {
-                            let file_path = PathBuf::from(line.strip_prefix("load ").unwrap().trim());
+                        if let Some(path) = command_argument(line, "load") {
+                            let file_path = PathBuf::from(path);
                             if let Err(e) = state.load_file(&file_path) {
                                 println!("{} {}", "Error:".bright_red(), e);
                             }
-                        } else if line.starts_with("time ") {
-                            let expr = line.strip_prefix("time ").unwrap();
+                        } else if let Some(expr) = command_argument(line, "time") {
                             let start = Instant::now();
                             match state.eval_expression(expr) {
                                 Ok(result) => {
@@ -369,8 +559,7 @@ pub fn start_repl(load_file: Option<PathBuf>, show_banner: bool, history_file: P
                                     println!("{} {}", "Error:".bright_red(), e);
                                 }
                             }
-                        } else if line.starts_with("type ") {
-                            let expr = line.strip_prefix("type ").unwrap();
+                        } else if let Some(expr) = command_argument(line, "type") {
                             match state.eval_expression(expr) {
                                 Ok(result) => {
                                     println!("{} {}", "Type:".bright_blue(), state.format_type(&result));
@@ -427,6 +616,106 @@ pub fn start_repl(load_file: Option<PathBuf>, show_banner: bool, history_file: P
     Ok(())
 }
 
+/// REPL attached to a running daemon: input is evaluated in the daemon's
+/// TLisp runtime, or in an actor's context, and its output is printed here
+pub fn start_attached_repl(socket: PathBuf, actor: Option<String>, show_banner: bool, history_file: PathBuf) -> ReamResult<()> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| crate::error::ReamError::Other(format!("Failed to create async runtime: {}", e)))?;
+    let client = IpcClient::new(socket.clone());
+
+    if !rt.block_on(client.is_daemon_running()) {
+        return Err(crate::error::ReamError::Other(format!("No daemon is listening on {}", socket.display())));
+    }
+
+    let mut rl = Editor::<ReplHelper, rustyline::history::DefaultHistory>::new().map_err(|e| crate::error::ReamError::Other(e.to_string()))?;
+    rl.set_helper(Some(ReplHelper::attached()));
+    if history_file.exists() {
+        if let Err(e) = rl.load_history(&history_file) {
+            eprintln!("Warning: Could not load history: {}", e);
+        }
+    }
+
+    if show_banner {
+        print_banner();
+    }
+    println!("{} {}", "Attached to daemon:".bright_green(), socket.display());
+    println!("{}", "Use :actor <pid> to evaluate in an actor's context, :actor to return, :actors to list actors".dimmed());
+
+    let mut actor = actor;
+    loop {
+        let prompt = match &actor {
+            Some(pid) => format!("ream@{}> ", pid).bright_cyan().bold(),
+            None => "ream@daemon> ".bright_cyan().bold(),
+        };
+
+        match rl.readline(&prompt.to_string()) {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let _ = rl.add_history_entry(line);
+
+                match line {
+                    "quit" | "exit" | ":q" | ":quit" => {
+                        println!("{}", "Detached".bright_green());
+                        break;
+                    }
+                    ":actor" => {
+                        actor = None;
+                    }
+                    ":actors" => {
+                        match rt.block_on(client.list_actors(false)) {
+                            Ok(actors) if actors.is_empty() => println!("{}", "No actors".dimmed()),
+                            Ok(actors) => {
+                                for info in actors {
+                                    println!("  {} {:?}", info.pid.to_string().bright_yellow(), info.status);
+                                }
+                            }
+                            Err(e) => println!("{} {}", "Error:".bright_red(), e),
+                        }
+                    }
+                    _ => {
+                        if let Some(pid) = command_argument(line, "actor") {
+                            actor = Some(pid.to_string());
+                            continue;
+                        }
+
+                        match rt.block_on(client.eval(line.to_string(), actor.clone())) {
+                            Ok(result) => {
+                                print!("{}", result.output);
+                                match result.value {
+                                    Ok(value) => println!("{} {}", "=>".bright_green(), value),
+                                    Err(e) => println!("{} {}", "Error:".bright_red(), e),
+                                }
+                            }
+                            Err(e) => println!("{} {}", "Error:".bright_red(), e),
+                        }
+                    }
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("{}", "Use 'quit' to detach".dimmed());
+            }
+            Err(ReadlineError::Eof) => {
+                println!("{}", "Detached".bright_green());
+                break;
+            }
+            Err(err) => {
+                println!("{} {}", "Error:".bright_red(), err);
+                break;
+            }
+        }
+    }
+
+    if let Err(e) = rl.save_history(&history_file) {
+        eprintln!("Warning: Could not save history: {}", e);
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -480,4 +769,52 @@ mod tests {
         state.toggle_timing();
         assert!(state.show_timing);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_input_completeness() {
+        assert!(input_is_complete("(+ 1 2)"));
+        assert!(!input_is_complete("(define (f x)\n  (* x"));
+        assert!(input_is_complete("(define (f x)\n  (* x x))"));
+        assert!(input_is_complete("(print \"(\")"));
+        assert!(!input_is_complete("(print \"unterminated"));
+        assert!(input_is_complete("(+ 1 2) ; trailing ("));
+        assert!(input_is_complete(")"));
+    }
+    
+    #[test]
+    fn test_command_argument() {
+        assert_eq!(command_argument(":load foo.tl", "load"), Some("foo.tl"));
+        assert_eq!(command_argument("time (+ 1 2)", "time"), Some("(+ 1 2)"));
+        assert_eq!(command_argument(":time", "time"), None);
+        assert_eq!(command_argument(":types", "type"), None);
+    }
+    
+    #[test]
+    fn test_completion_candidates() {
+        let state = ReplState::new().unwrap();
+        let mut helper = ReplHelper::default();
+        helper.set_symbols(state.symbols());
+        
+        let (start, candidates) = helper.candidates("(ca", 3);
+        assert_eq!(start, 1);
+        assert!(candidates.contains(&"car".to_string()));
+        assert!(candidates.iter().all(|c| c.starts_with("ca")));
+        
+        let (_, candidates) = helper.candidates(":lo", 3);
+        assert_eq!(candidates, vec![":load".to_string()]);
+    }
+    
+    #[test]
+    fn test_result_history() {
+        let mut state = ReplState::new().unwrap();
+        assert!(matches!(state.eval_expression("*1").unwrap(), Value::Null));
+        
+        state.eval_expression("(+ 1 2)").unwrap();
+        state.eval_expression("(* 2 5)").unwrap();
+        state.eval_expression("(define f (lambda (x) x))").unwrap();
+        assert!(matches!(state.tlisp.get("*2"), Some(Value::Int(10))));
+        assert!(matches!(state.tlisp.get("*3"), Some(Value::Int(3))));
+        assert!(matches!(state.eval_expression("(+ *2 *3)").unwrap(), Value::Int(13)));
+        assert!(state.symbols().contains(&"f".to_string()));
+    }
+}
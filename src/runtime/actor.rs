@@ -1,11 +1,12 @@
 //! Actor system implementation with coalgebraic state machines
 
 use std::any::Any;
-use std::collections::{VecDeque, HashMap};
+use std::collections::{BTreeMap, VecDeque, HashMap};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
 use crate::types::{Pid, MessagePayload};
 use crate::error::{RuntimeError, RuntimeResult};
+use crate::runtime::message::receive_traced;
 
 /// Message pattern for selective receive
 #[derive(Debug, Clone)]
@@ -138,7 +139,7 @@ impl ActorContext {
                 let mut mailbox = self.mailbox.lock().unwrap();
                 for (index, message) in mailbox.iter().enumerate() {
                     if self.matches_pattern(message, &pattern) {
-                        let matched_message = mailbox.remove(index).unwrap();
+                        let (_, matched_message) = mailbox.remove(index).unwrap().untrace();
                         return Ok(Some(matched_message));
                     }
                 }
@@ -151,6 +152,10 @@ impl ActorContext {
 
     /// Check if a message matches a pattern
     pub fn matches_pattern(&self, message: &MessagePayload, pattern: &MessagePattern) -> bool {
+        if let MessagePayload::Traced { payload, .. } = message {
+            return self.matches_pattern(payload, pattern);
+        }
+
         match pattern {
             MessagePattern::Any => true,
             MessagePattern::Text(expected) => {
@@ -164,8 +169,9 @@ impl ActorContext {
                 let message_type = match message {
                     MessagePayload::Text(_) => MessageType::Text,
                     MessagePayload::Data(_) => MessageType::Data,
-                    MessagePayload::Bytes(_) => MessageType::Bytes,
+                    MessagePayload::Bytes(_) | MessagePayload::SharedBinary(_) => MessageType::Bytes,
                     MessagePayload::Control(_) => MessageType::Control,
+                    MessagePayload::Traced { .. } => unreachable!("traced payloads are unwrapped above"),
                 };
                 &message_type == msg_type
             }
@@ -251,37 +257,54 @@ pub trait ReamActor: Send + Sync {
         true
     }
 
+    /// Run a slice of work the actor does besides handling messages,
+    /// returning whether more remains for a later quantum
+    fn run_slice(&mut self) -> RuntimeResult<bool> {
+        Ok(false)
+    }
+
     /// Get actor state for debugging
     fn debug_state(&self) -> Box<dyn Any + Send> {
         Box::new(())
     }
 
+    /// Forms the actor was evaluating when its last message failed,
+    /// innermost first; recorded in crash dumps
+    fn crash_trace(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The actor's process dictionary, rendered for crash dumps
+    fn dictionary(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
     /// Handle system messages (linking, monitoring, etc.)
     fn handle_system_message(&mut self, message: SystemMessage) -> RuntimeResult<()> {
         match message {
             SystemMessage::Down { pid, reason } => {
                 // Default implementation: log the down message
-                println!("Actor {} received DOWN message for {}: {}", self.pid(), pid, reason);
+                tracing::debug!(actor = %self.pid(), %pid, %reason, "Received DOWN message");
                 Ok(())
             }
             SystemMessage::Link { from, link_type } => {
                 // Default implementation: accept the link
-                println!("Actor {} received link request from {} ({:?})", self.pid(), from, link_type);
+                tracing::debug!(actor = %self.pid(), %from, ?link_type, "Received link request");
                 Ok(())
             }
             SystemMessage::Unlink { from } => {
                 // Default implementation: accept the unlink
-                println!("Actor {} received unlink request from {}", self.pid(), from);
+                tracing::debug!(actor = %self.pid(), %from, "Received unlink request");
                 Ok(())
             }
             SystemMessage::Monitor { from, monitor_ref } => {
                 // Default implementation: accept the monitor
-                println!("Actor {} received monitor request from {} (ref: {:?})", self.pid(), from, monitor_ref);
+                tracing::debug!(actor = %self.pid(), %from, ?monitor_ref, "Received monitor request");
                 Ok(())
             }
             SystemMessage::Demonitor { monitor_ref } => {
                 // Default implementation: accept the demonitor
-                println!("Actor {} received demonitor request (ref: {:?})", self.pid(), monitor_ref);
+                tracing::debug!(actor = %self.pid(), ?monitor_ref, "Received demonitor request");
                 Ok(())
             }
         }
@@ -371,7 +394,7 @@ where
             let mut mailbox = self.mailbox.lock().unwrap();
             mailbox.pop_front()
         } {
-            self.receive(message)?;
+            receive_traced(self.pid, message, |message| self.receive(message))?;
             processed += 1;
         }
         
@@ -475,6 +498,10 @@ impl ReamActor for CounterActor {
         self.count = self.initial_count;
         Ok(())
     }
+
+    fn dictionary(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([("count".to_string(), self.count.to_string())])
+    }
 }
 
 /// Echo actor that responds with the same message
@@ -502,7 +529,7 @@ impl ReamActor for EchoActor {
         
         // In a real implementation, we would send the message back
         // For now, just acknowledge receipt
-        println!("Echo actor {} received: {:?}", self.pid, message);
+        tracing::debug!(actor = %self.pid, ?message, "Echo actor received message");
         
         Ok(())
     }
@@ -0,0 +1,219 @@
+//! Shared binary heap for zero-copy message passing
+//!
+//! Like Erlang's reference-counted binaries, large payloads live once in a
+//! heap shared by every local process: `MessagePayload::SharedBinary` holds
+//! an `Arc<Bytes>`, so sending one clones a pointer rather than the data,
+//! and slicing one makes a view of the same buffer instead of a copy.
+//! Binaries are immutable; changing one means building a new one. The heap
+//! keeps a weak reference to each binary, which lets the collector count
+//! the binaries still referenced and those freed once the last process
+//! dropped them.
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// Payloads at least this large are worth sharing rather than copying
+pub const SHARED_BINARY_THRESHOLD: usize = 64 * 1024;
+
+/// Registrations between sweeps when few binaries are live
+const MIN_SWEEP_INTERVAL: usize = 1024;
+
+/// Binaries in the shared heap
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryHeapStats {
+    /// Binaries still referenced by some process
+    pub live_binaries: usize,
+    /// Bytes the live binaries refer to; views count their own length
+    pub live_bytes: usize,
+    /// Binaries freed since the runtime started
+    pub freed_binaries: u64,
+    /// Bytes the freed binaries referred to
+    pub freed_bytes: u64,
+}
+
+struct Entry {
+    binary: Weak<Bytes>,
+    len: usize,
+}
+
+struct Heap {
+    entries: Vec<Entry>,
+    next_sweep: usize,
+    freed_binaries: u64,
+    freed_bytes: u64,
+}
+
+impl Heap {
+    /// Drop the entries of freed binaries, returning how many and their size
+    fn sweep(&mut self) -> (usize, usize) {
+        let mut binaries = 0;
+        let mut bytes = 0;
+        self.entries.retain(|entry| {
+            let live = entry.binary.strong_count() > 0;
+            if !live {
+                binaries += 1;
+                bytes += entry.len;
+            }
+            live
+        });
+        self.freed_binaries += binaries as u64;
+        self.freed_bytes += bytes as u64;
+        self.next_sweep = (self.entries.len() * 2).max(MIN_SWEEP_INTERVAL);
+        (binaries, bytes)
+    }
+}
+
+static HEAP: Mutex<Heap> = Mutex::new(Heap {
+    entries: Vec::new(),
+    next_sweep: MIN_SWEEP_INTERVAL,
+    freed_binaries: 0,
+    freed_bytes: 0,
+});
+
+fn heap() -> MutexGuard<'static, Heap> {
+    HEAP.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn register(binary: &Arc<Bytes>) {
+    let mut heap = heap();
+    heap.entries.push(Entry { binary: Arc::downgrade(binary), len: binary.len() });
+    // Sweep as the heap doubles so entries of freed binaries don't pile up
+    // when nothing runs the collector
+    if heap.entries.len() >= heap.next_sweep {
+        heap.sweep();
+    }
+}
+
+/// Move `data` into the shared heap
+pub fn share(data: impl Into<Bytes>) -> Arc<Bytes> {
+    let binary = Arc::new(data.into());
+    register(&binary);
+    binary
+}
+
+/// A view of `range` within `binary` sharing its buffer, or `None` when the
+/// range is out of bounds
+pub fn slice(binary: &Arc<Bytes>, range: Range<usize>) -> Option<Arc<Bytes>> {
+    if range.start > range.end || range.end > binary.len() {
+        return None;
+    }
+    if range.start == 0 && range.end == binary.len() {
+        return Some(Arc::clone(binary));
+    }
+    let view = Arc::new(binary.slice(range));
+    register(&view);
+    Some(view)
+}
+
+/// Forget the binaries no process refers to any more, returning how many
+/// were freed and the bytes they referred to
+pub fn sweep() -> (usize, usize) {
+    heap().sweep()
+}
+
+/// Current contents of the shared heap
+pub fn stats() -> BinaryHeapStats {
+    let heap = heap();
+    let (live_binaries, live_bytes) = heap.entries.iter()
+        .filter(|entry| entry.binary.strong_count() > 0)
+        .fold((0, 0), |(count, bytes), entry| (count + 1, bytes + entry.len));
+    BinaryHeapStats {
+        live_binaries,
+        live_bytes,
+        freed_binaries: heap.freed_binaries,
+        freed_bytes: heap.freed_bytes,
+    }
+}
+
+/// Serde adapter writing a shared binary as plain bytes and reading it
+/// back into the shared heap
+pub mod serde_shared {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(binary: &Arc<Bytes>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(binary)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<Bytes>, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(share)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Mailbox;
+    use crate::tlisp::{TlispInterpreter, Value};
+    use crate::types::MessagePayload;
+
+    #[test]
+    fn test_slice_shares_buffer() {
+        let binary = share(vec![1u8, 2, 3, 4, 5]);
+        let view = slice(&binary, 1..4).unwrap();
+        assert_eq!(&view[..], &[2, 3, 4]);
+        assert_eq!(view.as_ptr(), binary[1..].as_ptr());
+        assert!(Arc::ptr_eq(&slice(&binary, 0..5).unwrap(), &binary));
+        assert!(slice(&binary, 3..6).is_none());
+        #[allow(clippy::reversed_empty_ranges)]
+        let backwards = slice(&binary, 4..2);
+        assert!(backwards.is_none());
+    }
+
+    #[test]
+    fn test_sweep_counts_freed_binaries() {
+        let kept = share(vec![0u8; 300]);
+        let dropped = share(vec![0u8; 700]);
+        let before = stats();
+        assert!(before.live_binaries >= 2);
+        assert!(before.live_bytes >= 1000);
+
+        drop(dropped);
+        sweep();
+        let after = stats();
+        assert!(after.freed_binaries > before.freed_binaries);
+        assert!(after.freed_bytes >= before.freed_bytes + 700);
+        assert_eq!(kept.len(), 300);
+    }
+
+    #[test]
+    fn test_send_shares_buffer() {
+        let data = vec![1u8; SHARED_BINARY_THRESHOLD];
+        let sent = MessagePayload::binary(data);
+        let MessagePayload::SharedBinary(original) = &sent else {
+            panic!("large payloads should be shared");
+        };
+        let original = Arc::clone(original);
+
+        let mut mailbox = Mailbox::new();
+        mailbox.send(sent);
+        match mailbox.receive() {
+            Some(MessagePayload::SharedBinary(received)) => assert!(Arc::ptr_eq(&received, &original)),
+            other => panic!("expected a shared binary, got {:?}", other),
+        }
+        assert!(matches!(MessagePayload::binary(vec![1, 2]), MessagePayload::Bytes(_)));
+    }
+
+    #[test]
+    fn test_tlisp_bytes_builtins() {
+        let mut interpreter = TlispInterpreter::new();
+        let world = interpreter.eval(r#"(bytes->string (bytes-slice (string->bytes "hello world") 6))"#).unwrap();
+        assert_eq!(world, Value::String("world".to_string()));
+        let length = interpreter.eval(r#"(bytes-length (bytes-slice (string->bytes "hello") 1 3))"#).unwrap();
+        assert_eq!(length, Value::Int(2));
+        assert_eq!(interpreter.eval(r#"(bytes? (string->bytes "x"))"#).unwrap(), Value::Bool(true));
+        assert!(interpreter.eval(r#"(bytes-slice (string->bytes "abc") 2 9)"#).is_err());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper(#[serde(with = "serde_shared")] Arc<Bytes>);
+
+        let json = serde_json::to_string(&Wrapper(share(vec![7u8, 8]))).unwrap();
+        let Wrapper(binary) = serde_json::from_str(&json).unwrap();
+        assert_eq!(&binary[..], &[7, 8]);
+    }
+}
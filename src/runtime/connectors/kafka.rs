@@ -0,0 +1,201 @@
+//! Kafka broker
+//!
+//! Consumers join a consumer group and subscribe to its topics; offsets are
+//! stored only when committed, so a consumer that restarts, or another
+//! member of the group that takes over a partition, resumes from the last
+//! record acknowledged. Producers wait for every in-sync replica to take a
+//! record. Any other librdkafka setting can be passed as an option.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer as _, StreamConsumer};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::{Message, Offset, TopicPartitionList};
+
+use super::{Broker, Consumer, Producer, ProducerRecord, Record};
+use crate::error::{RuntimeError, RuntimeResult};
+
+/// How long a record may wait in the producer's queue
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A Kafka cluster, a consumer group and the topics it reads
+#[derive(Debug, Clone, PartialEq)]
+pub struct KafkaBroker {
+    /// Bootstrap servers, `host:port` separated by commas
+    pub brokers: String,
+    pub group: String,
+    pub topics: Vec<String>,
+    /// librdkafka settings, overriding the connector's own
+    pub options: BTreeMap<String, String>,
+}
+
+impl KafkaBroker {
+    pub fn new(brokers: impl Into<String>, group: impl Into<String>) -> Self {
+        KafkaBroker {
+            brokers: brokers.into(),
+            group: group.into(),
+            topics: Vec::new(),
+            options: BTreeMap::new(),
+        }
+    }
+
+    pub fn topic(mut self, topic: impl Into<String>) -> Self {
+        self.topics.push(topic.into());
+        self
+    }
+
+    pub fn option(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.insert(name.into(), value.into());
+        self
+    }
+
+    fn config(&self, defaults: &[(&str, &str)]) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &self.brokers);
+        for (name, value) in defaults {
+            config.set(*name, *value);
+        }
+        for (name, value) in &self.options {
+            config.set(name, value);
+        }
+        config
+    }
+
+    /// Settings of the group's consumers
+    pub fn consumer_config(&self) -> ClientConfig {
+        let mut config = self.config(&[
+            ("enable.auto.commit", "false"),
+            ("enable.auto.offset.store", "false"),
+            ("auto.offset.reset", "earliest"),
+        ]);
+        config.set("group.id", &self.group);
+        config
+    }
+
+    /// Settings of producers
+    pub fn producer_config(&self) -> ClientConfig {
+        self.config(&[("acks", "all")])
+    }
+}
+
+fn failed(what: &str, error: impl std::fmt::Display) -> RuntimeError {
+    RuntimeError::Connector(format!("kafka: {}: {}", what, error))
+}
+
+#[async_trait]
+impl Broker for KafkaBroker {
+    fn describe(&self) -> String {
+        format!("kafka://{}", self.brokers)
+    }
+
+    async fn consumer(&self) -> RuntimeResult<Box<dyn Consumer>> {
+        if self.topics.is_empty() {
+            return Err(RuntimeError::Connector("kafka: no topics to consume".to_string()));
+        }
+        let consumer: StreamConsumer = self.consumer_config().create().map_err(|e| failed("creating consumer", e))?;
+        let topics: Vec<&str> = self.topics.iter().map(String::as_str).collect();
+        consumer.subscribe(&topics).map_err(|e| failed("subscribing", e))?;
+        Ok(Box::new(KafkaConsumer { consumer }))
+    }
+
+    async fn producer(&self) -> RuntimeResult<Box<dyn Producer>> {
+        let producer: FutureProducer = self.producer_config().create().map_err(|e| failed("creating producer", e))?;
+        Ok(Box::new(KafkaProducer { producer }))
+    }
+}
+
+struct KafkaConsumer {
+    consumer: StreamConsumer,
+}
+
+/// The offsets to commit for records done: for each partition, the offset
+/// after its last record
+fn positions(done: &[Record]) -> HashMap<(&str, i32), i64> {
+    let mut positions = HashMap::new();
+    for record in done {
+        let position = positions.entry((record.topic.as_str(), record.partition)).or_insert(0);
+        *position = (record.offset + 1).max(*position);
+    }
+    positions
+}
+
+#[async_trait]
+impl Consumer for KafkaConsumer {
+    async fn next(&mut self) -> RuntimeResult<Record> {
+        let message = self.consumer.recv().await.map_err(|e| failed("reading", e))?;
+        Ok(Record {
+            topic: message.topic().to_string(),
+            partition: message.partition(),
+            offset: message.offset(),
+            key: message.key().map(<[u8]>::to_vec),
+            payload: message.payload().map(<[u8]>::to_vec).unwrap_or_default(),
+        })
+    }
+
+    async fn commit(&mut self, done: &[Record]) -> RuntimeResult<()> {
+        let mut offsets = TopicPartitionList::new();
+        for ((topic, partition), position) in positions(done) {
+            offsets
+                .add_partition_offset(topic, partition, Offset::Offset(position))
+                .map_err(|e| failed("committing", e))?;
+        }
+        self.consumer.commit(&offsets, CommitMode::Async).map_err(|e| failed("committing", e))
+    }
+}
+
+struct KafkaProducer {
+    producer: FutureProducer,
+}
+
+#[async_trait]
+impl Producer for KafkaProducer {
+    async fn send(&mut self, record: &ProducerRecord) -> RuntimeResult<()> {
+        let mut message = FutureRecord::to(&record.topic).payload(&record.payload);
+        if let Some(key) = &record.key {
+            message = message.key(key);
+        }
+        self.producer
+            .send(message, QUEUE_TIMEOUT)
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| failed(&format!("sending to {}", record.topic), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_settings() {
+        let broker = KafkaBroker::new("k1:9092,k2:9092", "billing")
+            .topic("orders")
+            .option("auto.offset.reset", "latest")
+            .option("security.protocol", "ssl");
+        let consumer = broker.consumer_config();
+        assert_eq!(consumer.get("bootstrap.servers"), Some("k1:9092,k2:9092"));
+        assert_eq!(consumer.get("group.id"), Some("billing"));
+        assert_eq!(consumer.get("enable.auto.commit"), Some("false"));
+        assert_eq!(consumer.get("auto.offset.reset"), Some("latest"));
+        assert_eq!(consumer.get("security.protocol"), Some("ssl"));
+        assert_eq!(broker.producer_config().get("acks"), Some("all"));
+        assert_eq!(broker.producer_config().get("group.id"), None);
+        assert_eq!(broker.describe(), "kafka://k1:9092,k2:9092");
+
+        let record = |partition, offset| Record {
+            topic: "orders".to_string(),
+            partition,
+            offset,
+            key: None,
+            payload: Vec::new(),
+        };
+        let done = [record(0, 4), record(1, 7), record(0, 5)];
+        let positions = positions(&done);
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[&("orders", 0)], 6);
+        assert_eq!(positions[&("orders", 1)], 8);
+    }
+}
@@ -0,0 +1,1147 @@
+//! Connectors for event streaming brokers
+//!
+//! A consumer connector reads records from a broker, Kafka or NATS
+//! JetStream, and publishes each on the topic of the same name of a `Bus`,
+//! the runtime's pub-sub bus, as
+//!
+//! ```json
+//! {"type": "record", "connector": 7, "id": 42, "topic": "orders",
+//!  "partition": 0, "offset": 1001, "key": "o-1", "payload": "..."}
+//! ```
+//!
+//! with the key and payload as strings, or as lists of bytes when they are
+//! not UTF-8. Delivery is at least once: a subscriber acknowledges a record
+//! by sending `{"type": "ack", "id": 42}` to the connector, and a record's
+//! offset is committed to the broker only when every subscriber it went to
+//! has acknowledged it and the records before it in its partition. Records
+//! not acknowledged within the ack timeout are delivered again, to the
+//! topic's subscribers at that time, and a record no one has subscribed to
+//! yet waits for its first subscriber.
+//!
+//! A producer connector sends what its actor receives, `{"topic": ...,
+//! "key": ..., "payload": ...}`, to the broker, with string payloads sent as
+//! they are and other values as JSON. A record is retried until the broker
+//! has taken it.
+//!
+//! Each connector runs on a thread of its own and is supervised there: when
+//! the broker fails, the connection is made again after a backoff that
+//! doubles with every failure, and records read but not committed are read
+//! again. A connector that fails more than `max_restarts` times within the
+//! restart window gives up and is left `Down`, as a supervisor would.
+
+pub mod kafka;
+pub mod nats;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::error::{RuntimeError, RuntimeResult};
+use crate::orm::cdc::ActorSender;
+use crate::runtime::supervisor::SupervisorState;
+use crate::runtime::{ReamActor, ReamRuntime};
+use crate::types::{MessagePayload, Pid};
+
+pub use kafka::KafkaBroker;
+pub use nats::NatsBroker;
+
+/// Delivery ids, unique across connectors and their sessions, so an
+/// acknowledgment from before a restart matches nothing
+static NEXT_DELIVERY: AtomicU64 = AtomicU64::new(1);
+
+/// A record read from a broker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    pub key: Option<Vec<u8>>,
+    pub payload: Vec<u8>,
+}
+
+/// A record to send to a broker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProducerRecord {
+    pub topic: String,
+    pub key: Option<Vec<u8>>,
+    pub payload: Vec<u8>,
+}
+
+impl ProducerRecord {
+    /// The record a producer's actor was sent, `{"topic": ..., "key": ...,
+    /// "payload": ...}`
+    pub fn from_payload(message: MessagePayload) -> RuntimeResult<Self> {
+        let data = match message {
+            MessagePayload::Data(data) => data,
+            MessagePayload::Traced { payload, .. } => return Self::from_payload(*payload),
+            other => return Err(RuntimeError::InvalidMessage(format!("expected a record to produce, got {:?}", other))),
+        };
+        let topic = match data.get("topic") {
+            Some(serde_json::Value::String(topic)) => topic.clone(),
+            _ => return Err(RuntimeError::InvalidMessage(format!("record has no topic: {}", data))),
+        };
+        let bytes = |value: &serde_json::Value| match value {
+            serde_json::Value::String(text) => text.clone().into_bytes(),
+            other => other.to_string().into_bytes(),
+        };
+        Ok(ProducerRecord {
+            topic,
+            key: data.get("key").filter(|key| !key.is_null()).map(bytes),
+            payload: data.get("payload").map(bytes).unwrap_or_default(),
+        })
+    }
+}
+
+/// A subscription to a broker, from which records are read
+#[async_trait]
+pub trait Consumer: Send {
+    /// The next record. Must be cancel safe: a call dropped before it
+    /// finishes loses no record.
+    async fn next(&mut self) -> RuntimeResult<Record>;
+
+    /// Mark records as processed, along with the records before them in
+    /// their partitions; they come in offset order for each partition
+    async fn commit(&mut self, done: &[Record]) -> RuntimeResult<()>;
+}
+
+/// A connection to a broker records are sent over
+#[async_trait]
+pub trait Producer: Send {
+    /// Send a record, returning once the broker has taken it
+    async fn send(&mut self, record: &ProducerRecord) -> RuntimeResult<()>;
+}
+
+/// A broker, from which consumers and producers are made
+#[async_trait]
+pub trait Broker: Send + Sync {
+    /// The broker, for logs
+    fn describe(&self) -> String;
+
+    async fn consumer(&self) -> RuntimeResult<Box<dyn Consumer>>;
+
+    async fn producer(&self) -> RuntimeResult<Box<dyn Producer>>;
+}
+
+/// The runtime's pub-sub bus: actors subscribe to topics by name
+pub struct Bus {
+    sender: Arc<dyn ActorSender>,
+    topics: Mutex<HashMap<String, Vec<Pid>>>,
+}
+
+impl Bus {
+    pub fn new(sender: Arc<dyn ActorSender>) -> Self {
+        Bus {
+            sender,
+            topics: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn subscribe(&self, topic: impl Into<String>, pid: Pid) {
+        let mut topics = self.topics.lock().unwrap();
+        let subscribers = topics.entry(topic.into()).or_default();
+        if !subscribers.contains(&pid) {
+            subscribers.push(pid);
+        }
+    }
+
+    pub fn unsubscribe(&self, topic: &str, pid: Pid) {
+        let mut topics = self.topics.lock().unwrap();
+        if let Some(subscribers) = topics.get_mut(topic) {
+            subscribers.retain(|subscriber| *subscriber != pid);
+            if subscribers.is_empty() {
+                topics.remove(topic);
+            }
+        }
+    }
+
+    /// The actors subscribed to `topic`, in the order they subscribed
+    pub fn subscribers(&self, topic: &str) -> Vec<Pid> {
+        self.topics.lock().unwrap().get(topic).cloned().unwrap_or_default()
+    }
+
+    /// Send `payload` to one subscriber
+    pub fn send(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+        self.sender.send(to, payload)
+    }
+
+    /// Send `payload` to every subscriber of `topic`, returning how many it
+    /// reached
+    pub fn publish(&self, topic: &str, payload: MessagePayload) -> usize {
+        self.subscribers(topic)
+            .into_iter()
+            .filter(|pid| self.send(*pid, payload.clone()).is_ok())
+            .count()
+    }
+}
+
+/// How a connector is restarted after its broker fails
+#[derive(Debug, Clone, PartialEq)]
+pub struct Restart {
+    /// Restarts allowed within `window` before the connector gives up
+    pub max_restarts: u32,
+    pub window: Duration,
+    /// Wait before the first reconnection attempt
+    pub backoff: Duration,
+    /// Longest wait between reconnection attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for Restart {
+    fn default() -> Self {
+        Restart {
+            max_restarts: 5,
+            window: Duration::from_secs(60),
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// What a connector is doing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectorState {
+    Connecting,
+    Running,
+    /// Waiting to reconnect after a failure
+    Restarting,
+    /// Failed too often and gave up, for this reason
+    Down(String),
+    Stopped,
+}
+
+/// A connector's state and counts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectorStatus {
+    pub state: ConnectorState,
+    pub restarts: u32,
+    /// Records read from, or sent to, the broker
+    pub records: u64,
+    /// Records committed to the broker
+    pub committed: u64,
+    /// Records read and not yet committed
+    pub in_flight: usize,
+}
+
+impl ConnectorStatus {
+    fn new() -> Self {
+        ConnectorStatus {
+            state: ConnectorState::Connecting,
+            restarts: 0,
+            records: 0,
+            committed: 0,
+            in_flight: 0,
+        }
+    }
+}
+
+/// Configuration of a consumer connector
+#[derive(Clone)]
+pub struct ConsumerSpec {
+    pub name: String,
+    pub broker: Arc<dyn Broker>,
+    pub restart: Restart,
+    /// How long subscribers have to acknowledge a record before it is
+    /// delivered again
+    pub ack_timeout: Duration,
+    /// Records read and not committed before reading pauses
+    pub max_in_flight: usize,
+}
+
+impl ConsumerSpec {
+    pub fn new(name: impl Into<String>, broker: Arc<dyn Broker>) -> Self {
+        ConsumerSpec {
+            name: name.into(),
+            broker,
+            restart: Restart::default(),
+            ack_timeout: Duration::from_secs(30),
+            max_in_flight: 1000,
+        }
+    }
+
+    pub fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    pub fn ack_timeout(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = timeout;
+        self
+    }
+
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = max.max(1);
+        self
+    }
+}
+
+/// Configuration of a producer connector
+#[derive(Clone)]
+pub struct ProducerSpec {
+    pub name: String,
+    pub broker: Arc<dyn Broker>,
+    pub restart: Restart,
+    /// Records its actor queues before sending to it fails
+    pub queue_capacity: usize,
+}
+
+impl ProducerSpec {
+    pub fn new(name: impl Into<String>, broker: Arc<dyn Broker>) -> Self {
+        ProducerSpec {
+            name: name.into(),
+            broker,
+            restart: Restart::default(),
+            queue_capacity: 1024,
+        }
+    }
+
+    pub fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+}
+
+/// A record delivered and not yet committed
+struct Pending {
+    record: Record,
+    /// Delivery ids of the subscribers yet to acknowledge it
+    waiting: HashMap<Pid, u64>,
+    acked: HashSet<Pid>,
+    delivered_at: Instant,
+}
+
+impl Pending {
+    fn is_done(&self) -> bool {
+        !self.acked.is_empty() && self.waiting.is_empty()
+    }
+}
+
+/// The records of a consumer session between reading and committing
+struct Tracker {
+    connector: Pid,
+    partitions: HashMap<(String, i32), BTreeMap<i64, Pending>>,
+    deliveries: HashMap<u64, (String, i32, i64)>,
+    len: usize,
+}
+
+impl Tracker {
+    fn new(connector: Pid) -> Self {
+        Tracker {
+            connector,
+            partitions: HashMap::new(),
+            deliveries: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Deliver a record read from the broker. A record read again before
+    /// it was committed is already being delivered.
+    fn deliver(&mut self, record: Record, bus: &Bus, now: Instant) {
+        let partition = self.partitions.entry((record.topic.clone(), record.partition)).or_default();
+        if partition.contains_key(&record.offset) {
+            return;
+        }
+        let mut pending = Pending {
+            record,
+            waiting: HashMap::new(),
+            acked: HashSet::new(),
+            delivered_at: now,
+        };
+        offer(self.connector, &mut pending, &mut self.deliveries, bus);
+        partition.insert(pending.record.offset, pending);
+        self.len += 1;
+    }
+
+    /// Record an acknowledgment, returning the records it lets commit
+    fn ack(&mut self, id: u64) -> Vec<Record> {
+        let Some((topic, partition, offset)) = self.deliveries.remove(&id) else {
+            return Vec::new();
+        };
+        let key = (topic, partition);
+        if let Some(pending) = self.partitions.get_mut(&key).and_then(|records| records.get_mut(&offset)) {
+            if let Some(pid) = pending.waiting.iter().find(|(_, delivery)| **delivery == id).map(|(pid, _)| *pid) {
+                pending.waiting.remove(&pid);
+                pending.acked.insert(pid);
+            }
+        }
+        self.take_done(&key)
+    }
+
+    /// Deliver again the records not acknowledged within `timeout`, to
+    /// the subscribers of their topics now, returning the records that
+    /// can commit because the subscribers they waited on are gone
+    fn redeliver(&mut self, bus: &Bus, timeout: Duration, now: Instant) -> Vec<Record> {
+        let mut touched = Vec::new();
+        for (key, records) in self.partitions.iter_mut() {
+            for pending in records.values_mut() {
+                if now.duration_since(pending.delivered_at) < timeout || pending.is_done() {
+                    continue;
+                }
+                let subscribers = bus.subscribers(&pending.record.topic);
+                for (pid, delivery) in pending.waiting.clone() {
+                    if !subscribers.contains(&pid) {
+                        pending.waiting.remove(&pid);
+                        self.deliveries.remove(&delivery);
+                    }
+                }
+                offer(self.connector, pending, &mut self.deliveries, bus);
+                pending.delivered_at = now;
+                touched.push(key.clone());
+            }
+        }
+        touched.dedup();
+        touched.iter().flat_map(|key| self.take_done(key)).collect()
+    }
+
+    /// Remove the records at the front of a partition that are done
+    fn take_done(&mut self, key: &(String, i32)) -> Vec<Record> {
+        let Some(records) = self.partitions.get_mut(key) else {
+            return Vec::new();
+        };
+        let mut done = Vec::new();
+        while let Some(entry) = records.first_entry() {
+            if !entry.get().is_done() {
+                break;
+            }
+            done.push(entry.remove().record);
+        }
+        self.len -= done.len();
+        done
+    }
+}
+
+/// Send a record to the subscribers of its topic that have not
+/// acknowledged it, keeping the delivery id of those it went to before
+fn offer(connector: Pid, pending: &mut Pending, deliveries: &mut HashMap<u64, (String, i32, i64)>, bus: &Bus) {
+    let record = &pending.record;
+    for pid in bus.subscribers(&record.topic) {
+        if pending.acked.contains(&pid) {
+            continue;
+        }
+        let id = match pending.waiting.get(&pid) {
+            Some(id) => *id,
+            None => NEXT_DELIVERY.fetch_add(1, Ordering::SeqCst),
+        };
+        match bus.send(pid, record_message(connector, id, record)) {
+            Ok(()) => {
+                pending.waiting.insert(pid, id);
+                deliveries.insert(id, (record.topic.clone(), record.partition, record.offset));
+            }
+            Err(e) => tracing::warn!("connector {}: could not deliver {}@{} to {}: {}", connector, record.topic, record.offset, pid, e),
+        }
+    }
+}
+
+/// Bytes as a string when they are UTF-8, as a list of bytes otherwise
+fn bytes_value(bytes: &[u8]) -> serde_json::Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => serde_json::Value::from(text),
+        Err(_) => serde_json::Value::from(bytes.to_vec()),
+    }
+}
+
+/// The message a subscriber receives for a record
+pub fn record_message(connector: Pid, id: u64, record: &Record) -> MessagePayload {
+    MessagePayload::Data(serde_json::json!({
+        "type": "record",
+        "connector": connector,
+        "id": id,
+        "topic": record.topic,
+        "partition": record.partition,
+        "offset": record.offset,
+        "key": record.key.as_deref().map(bytes_value),
+        "payload": bytes_value(&record.payload),
+    }))
+}
+
+/// The delivery id a subscriber acknowledges, from `{"type": "ack", "id": n}`
+fn ack_id(message: &MessagePayload) -> Option<u64> {
+    match message {
+        MessagePayload::Data(data) if data.get("type").and_then(|kind| kind.as_str()) == Some("ack") => {
+            data.get("id").and_then(|id| id.as_u64())
+        }
+        MessagePayload::Traced { payload, .. } => ack_id(payload),
+        _ => None,
+    }
+}
+
+/// Restarts a connector's sessions as its `Restart` allows
+struct Supervision {
+    restart: Restart,
+    state: SupervisorState,
+    backoff: Duration,
+    status: Arc<Mutex<ConnectorStatus>>,
+}
+
+impl Supervision {
+    fn new(restart: Restart, status: Arc<Mutex<ConnectorStatus>>) -> Self {
+        Supervision {
+            backoff: restart.backoff,
+            restart,
+            state: SupervisorState::new(),
+            status,
+        }
+    }
+
+    fn connected(&mut self) {
+        self.backoff = self.restart.backoff;
+        self.status.lock().unwrap().state = ConnectorState::Running;
+    }
+
+    /// Wait out the backoff after a failure, returning false when the
+    /// connector should give up or was stopped meanwhile
+    async fn restart(&mut self, name: &str, error: RuntimeError, stop: &flume::Receiver<()>) -> bool {
+        if !self.state.can_restart(self.restart.max_restarts, self.restart.window) {
+            tracing::error!("connector {}: giving up after {} restarts: {}", name, self.state.restart_count, error);
+            self.status.lock().unwrap().state = ConnectorState::Down(error.to_string());
+            return false;
+        }
+        self.state.record_restart(self.restart.window);
+        {
+            let mut status = self.status.lock().unwrap();
+            status.state = ConnectorState::Restarting;
+            status.restarts += 1;
+        }
+        tracing::warn!("connector {}: failed, restarting in {:?}: {}", name, self.backoff, error);
+        let stopped = tokio::select! {
+            _ = tokio::time::sleep(self.backoff) => false,
+            _ = stop.recv_async() => true,
+        };
+        self.backoff = (self.backoff * 2).min(self.restart.max_backoff);
+        !stopped
+    }
+
+    fn stopped(&self) {
+        let mut status = self.status.lock().unwrap();
+        if !matches!(status.state, ConnectorState::Down(_)) {
+            status.state = ConnectorState::Stopped;
+        }
+    }
+}
+
+/// Run `work` on a thread of its own, on a runtime of its own
+fn run_thread<F>(name: String, work: F) -> RuntimeResult<()>
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| RuntimeError::Connector(format!("{}: could not start runtime: {}", name, e)))?;
+    thread::Builder::new()
+        .name(format!("connector-{}", name))
+        .spawn(move || runtime.block_on(work))
+        .map_err(|e| RuntimeError::Connector(format!("{}: could not start thread: {}", name, e)))?;
+    Ok(())
+}
+
+enum ConsumerEvent {
+    Record(Record),
+    Ack(u64),
+    Tick,
+    Stop,
+}
+
+/// A running consumer connector
+#[derive(Clone)]
+pub struct ConsumerHandle {
+    pid: Pid,
+    acks: flume::Sender<u64>,
+    stop: flume::Sender<()>,
+    status: Arc<Mutex<ConnectorStatus>>,
+}
+
+impl ConsumerHandle {
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn status(&self) -> ConnectorStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Acknowledge the delivery `id`
+    pub fn ack(&self, id: u64) {
+        let _ = self.acks.send(id);
+    }
+
+    pub fn stop(&self) {
+        let _ = self.stop.try_send(());
+    }
+
+    /// The actor subscribers acknowledge records to
+    pub fn actor(&self) -> ConsumerActor {
+        ConsumerActor { handle: self.clone() }
+    }
+}
+
+/// Start a consumer connector publishing to `bus`, with `pid` as the
+/// connector its records come from. It stops when every handle to it,
+/// its actor's included, is dropped.
+pub fn start_consumer(pid: Pid, spec: ConsumerSpec, bus: Arc<Bus>) -> RuntimeResult<ConsumerHandle> {
+    let (acks, acked) = flume::unbounded();
+    let (stop, stopped) = flume::bounded(1);
+    let status = Arc::new(Mutex::new(ConnectorStatus::new()));
+    let handle = ConsumerHandle {
+        pid,
+        acks,
+        stop,
+        status: status.clone(),
+    };
+    let label = format!("{} ({})", spec.name, spec.broker.describe());
+    run_thread(spec.name.clone(), async move {
+        let mut supervision = Supervision::new(spec.restart.clone(), status.clone());
+        loop {
+            let failed = match spec.broker.consumer().await {
+                Ok(consumer) => {
+                    supervision.connected();
+                    match consume(pid, &spec, consumer, &bus, &acked, &stopped, &status).await {
+                        Ok(()) => break,
+                        Err(e) => e,
+                    }
+                }
+                Err(e) => e,
+            };
+            status.lock().unwrap().in_flight = 0;
+            if !supervision.restart(&label, failed, &stopped).await {
+                break;
+            }
+        }
+        supervision.stopped();
+    })?;
+    Ok(handle)
+}
+
+/// Start a consumer connector whose actor is spawned under `runtime`, which
+/// also delivers its records
+pub fn spawn_consumer(runtime: &Arc<ReamRuntime>, spec: ConsumerSpec, bus: Arc<Bus>) -> RuntimeResult<ConsumerHandle> {
+    let pid = Pid::new();
+    let handle = start_consumer(pid, spec, bus)?;
+    if let Err(e) = runtime.spawn_as(pid, handle.actor()) {
+        handle.stop();
+        return Err(e);
+    }
+    Ok(handle)
+}
+
+/// One session of a consumer, until it is stopped or its broker fails
+async fn consume(
+    pid: Pid,
+    spec: &ConsumerSpec,
+    mut consumer: Box<dyn Consumer>,
+    bus: &Bus,
+    acked: &flume::Receiver<u64>,
+    stopped: &flume::Receiver<()>,
+    status: &Mutex<ConnectorStatus>,
+) -> RuntimeResult<()> {
+    let mut tracker = Tracker::new(pid);
+    let mut tick = tokio::time::interval((spec.ack_timeout / 4).max(Duration::from_millis(10)));
+    loop {
+        let event = tokio::select! {
+            record = consumer.next(), if tracker.len() < spec.max_in_flight => ConsumerEvent::Record(record?),
+            id = acked.recv_async() => match id {
+                Ok(id) => ConsumerEvent::Ack(id),
+                Err(_) => ConsumerEvent::Stop,
+            },
+            _ = tick.tick() => ConsumerEvent::Tick,
+            _ = stopped.recv_async() => ConsumerEvent::Stop,
+        };
+        let done = match event {
+            ConsumerEvent::Record(record) => {
+                tracker.deliver(record, bus, Instant::now());
+                status.lock().unwrap().records += 1;
+                Vec::new()
+            }
+            ConsumerEvent::Ack(id) => tracker.ack(id),
+            ConsumerEvent::Tick => tracker.redeliver(bus, spec.ack_timeout, Instant::now()),
+            ConsumerEvent::Stop => return Ok(()),
+        };
+        if !done.is_empty() {
+            consumer.commit(&done).await?;
+        }
+        let mut status = status.lock().unwrap();
+        status.committed += done.len() as u64;
+        status.in_flight = tracker.len();
+    }
+}
+
+/// The actor standing for a consumer connector, taking acknowledgments
+pub struct ConsumerActor {
+    handle: ConsumerHandle,
+}
+
+impl ReamActor for ConsumerActor {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        match ack_id(&message) {
+            Some(id) => {
+                self.handle.ack(id);
+                Ok(())
+            }
+            None => Err(RuntimeError::InvalidMessage(format!("connector {} expects acknowledgments, got {:?}", self.handle.pid, message))),
+        }
+    }
+
+    fn pid(&self) -> Pid {
+        self.handle.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        Ok(())
+    }
+
+    fn is_alive(&self) -> bool {
+        !matches!(self.handle.status().state, ConnectorState::Down(_) | ConnectorState::Stopped)
+    }
+}
+
+/// A running producer connector
+#[derive(Clone)]
+pub struct ProducerHandle {
+    pid: Pid,
+    records: flume::Sender<ProducerRecord>,
+    stop: flume::Sender<()>,
+    status: Arc<Mutex<ConnectorStatus>>,
+}
+
+impl ProducerHandle {
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn status(&self) -> ConnectorStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Queue a record to send, failing when the queue is full
+    pub fn send(&self, record: ProducerRecord) -> RuntimeResult<()> {
+        self.records.try_send(record).map_err(|e| match e {
+            flume::TrySendError::Full(_) => RuntimeError::MailboxFull(self.pid),
+            flume::TrySendError::Disconnected(_) => RuntimeError::Connector(format!("producer {} has stopped", self.pid)),
+        })
+    }
+
+    pub fn stop(&self) {
+        let _ = self.stop.try_send(());
+    }
+
+    /// The actor whose messages are sent to the broker
+    pub fn actor(&self) -> ProducerActor {
+        ProducerActor { handle: self.clone() }
+    }
+}
+
+/// Start a producer connector
+pub fn start_producer(pid: Pid, spec: ProducerSpec) -> RuntimeResult<ProducerHandle> {
+    let (records, queued) = flume::bounded(spec.queue_capacity);
+    let (stop, stopped) = flume::bounded(1);
+    let status = Arc::new(Mutex::new(ConnectorStatus::new()));
+    let handle = ProducerHandle {
+        pid,
+        records,
+        stop,
+        status: status.clone(),
+    };
+    let label = format!("{} ({})", spec.name, spec.broker.describe());
+    run_thread(spec.name.clone(), async move {
+        let mut supervision = Supervision::new(spec.restart.clone(), status.clone());
+        // A record the broker failed to take, sent first after restarting
+        let mut unsent = None;
+        loop {
+            let failed = match spec.broker.producer().await {
+                Ok(producer) => {
+                    supervision.connected();
+                    match produce(producer, &mut unsent, &queued, &stopped, &status).await {
+                        Ok(()) => break,
+                        Err(e) => e,
+                    }
+                }
+                Err(e) => e,
+            };
+            if !supervision.restart(&label, failed, &stopped).await {
+                break;
+            }
+        }
+        supervision.stopped();
+    })?;
+    Ok(handle)
+}
+
+/// Start a producer connector whose actor is spawned under `runtime`
+pub fn spawn_producer(runtime: &Arc<ReamRuntime>, spec: ProducerSpec) -> RuntimeResult<ProducerHandle> {
+    let pid = Pid::new();
+    let handle = start_producer(pid, spec)?;
+    if let Err(e) = runtime.spawn_as(pid, handle.actor()) {
+        handle.stop();
+        return Err(e);
+    }
+    Ok(handle)
+}
+
+/// One session of a producer, until it is stopped or its broker fails
+async fn produce(
+    mut producer: Box<dyn Producer>,
+    unsent: &mut Option<ProducerRecord>,
+    queued: &flume::Receiver<ProducerRecord>,
+    stopped: &flume::Receiver<()>,
+    status: &Mutex<ConnectorStatus>,
+) -> RuntimeResult<()> {
+    loop {
+        let record = match unsent.take() {
+            Some(record) => record,
+            None => tokio::select! {
+                record = queued.recv_async() => match record {
+                    Ok(record) => record,
+                    Err(_) => return Ok(()),
+                },
+                _ = stopped.recv_async() => return Ok(()),
+            },
+        };
+        if let Err(e) = producer.send(&record).await {
+            *unsent = Some(record);
+            return Err(e);
+        }
+        let mut status = status.lock().unwrap();
+        status.records += 1;
+        status.committed += 1;
+    }
+}
+
+/// The actor standing for a producer connector
+pub struct ProducerActor {
+    handle: ProducerHandle,
+}
+
+impl ReamActor for ProducerActor {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        self.handle.send(ProducerRecord::from_payload(message)?)
+    }
+
+    fn pid(&self) -> Pid {
+        self.handle.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        Ok(())
+    }
+
+    fn is_alive(&self) -> bool {
+        !matches!(self.handle.status().state, ConnectorState::Down(_) | ConnectorState::Stopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Default)]
+    struct RecordingSender {
+        messages: Mutex<Vec<(Pid, serde_json::Value)>>,
+    }
+
+    impl ActorSender for RecordingSender {
+        fn send(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+            match payload {
+                MessagePayload::Data(data) => self.messages.lock().unwrap().push((to, data)),
+                other => panic!("expected data, got {:?}", other),
+            }
+            Ok(())
+        }
+    }
+
+    impl RecordingSender {
+        fn to(&self, pid: Pid) -> Vec<serde_json::Value> {
+            self.messages.lock().unwrap().iter().filter(|(to, _)| *to == pid).map(|(_, data)| data.clone()).collect()
+        }
+    }
+
+    /// A broker keeping one partition per topic in memory, whose
+    /// connections fail on demand
+    #[derive(Default)]
+    struct MemoryBroker {
+        log: Mutex<Vec<Record>>,
+        committed: Mutex<HashMap<String, i64>>,
+        produced: Mutex<Vec<ProducerRecord>>,
+        /// Connection attempts to refuse
+        refuse: AtomicUsize,
+        /// Reads or sends to fail, ending the session
+        fail: AtomicUsize,
+        sessions: AtomicUsize,
+    }
+
+    impl MemoryBroker {
+        fn append(&self, topic: &str, payload: &str) {
+            let mut log = self.log.lock().unwrap();
+            let offset = log.iter().filter(|record| record.topic == topic).count() as i64;
+            log.push(Record {
+                topic: topic.to_string(),
+                partition: 0,
+                offset,
+                key: None,
+                payload: payload.as_bytes().to_vec(),
+            });
+        }
+
+        fn committed(&self, topic: &str) -> i64 {
+            self.committed.lock().unwrap().get(topic).copied().unwrap_or(0)
+        }
+
+        fn failing(&self) -> RuntimeResult<()> {
+            let fail = self.fail.load(Ordering::SeqCst);
+            if fail > 0 {
+                self.fail.store(fail - 1, Ordering::SeqCst);
+                return Err(RuntimeError::Connector("broker went away".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    struct MemoryConsumer {
+        broker: Arc<MemoryBroker>,
+        positions: HashMap<String, i64>,
+    }
+
+    #[async_trait]
+    impl Consumer for MemoryConsumer {
+        async fn next(&mut self) -> RuntimeResult<Record> {
+            loop {
+                self.broker.failing()?;
+                let next = self.broker.log.lock().unwrap().iter().find(|record| {
+                    let position = *self.positions.get(&record.topic).unwrap_or(&self.broker.committed(&record.topic));
+                    record.offset >= position
+                }).cloned();
+                if let Some(record) = next {
+                    self.positions.insert(record.topic.clone(), record.offset + 1);
+                    return Ok(record);
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+
+        async fn commit(&mut self, done: &[Record]) -> RuntimeResult<()> {
+            let mut committed = self.broker.committed.lock().unwrap();
+            for record in done {
+                let position = committed.entry(record.topic.clone()).or_default();
+                assert_eq!(*position, record.offset, "commits come in offset order");
+                *position = record.offset + 1;
+            }
+            Ok(())
+        }
+    }
+
+    struct MemoryProducer {
+        broker: Arc<MemoryBroker>,
+    }
+
+    #[async_trait]
+    impl Producer for MemoryProducer {
+        async fn send(&mut self, record: &ProducerRecord) -> RuntimeResult<()> {
+            self.broker.failing()?;
+            self.broker.produced.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    /// Shares a `MemoryBroker` with the test that made it
+    struct Shared(Arc<MemoryBroker>);
+
+    #[async_trait]
+    impl Broker for Shared {
+        fn describe(&self) -> String {
+            "memory".to_string()
+        }
+
+        async fn consumer(&self) -> RuntimeResult<Box<dyn Consumer>> {
+            let refuse = self.0.refuse.load(Ordering::SeqCst);
+            if refuse > 0 {
+                self.0.refuse.store(refuse - 1, Ordering::SeqCst);
+                return Err(RuntimeError::Connector("connection refused".to_string()));
+            }
+            self.0.sessions.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(MemoryConsumer {
+                broker: self.0.clone(),
+                positions: HashMap::new(),
+            }))
+        }
+
+        async fn producer(&self) -> RuntimeResult<Box<dyn Producer>> {
+            self.0.sessions.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(MemoryProducer { broker: self.0.clone() }))
+        }
+    }
+
+    fn quick_restarts(max_restarts: u32) -> Restart {
+        Restart {
+            max_restarts,
+            window: Duration::from_secs(60),
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        }
+    }
+
+    fn wait_for(what: &str, condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn record(topic: &str, offset: i64) -> Record {
+        Record {
+            topic: topic.to_string(),
+            partition: 0,
+            offset,
+            key: Some(b"k".to_vec()),
+            payload: vec![0xff],
+        }
+    }
+
+    fn delivery(message: &serde_json::Value) -> u64 {
+        message["id"].as_u64().unwrap()
+    }
+
+    #[test]
+    fn test_tracker_commits_acknowledged_prefix() {
+        let sender = Arc::new(RecordingSender::default());
+        let bus = Bus::new(sender.clone());
+        let (first, second, late) = (Pid::new(), Pid::new(), Pid::new());
+        bus.subscribe("orders", first);
+        bus.subscribe("orders", second);
+        let connector = Pid::new();
+        let mut tracker = Tracker::new(connector);
+        let start = Instant::now();
+        for offset in 0..3 {
+            tracker.deliver(record("orders", offset), &bus, start);
+        }
+        tracker.deliver(record("orders", 1), &bus, start);
+        assert_eq!(tracker.len(), 3, "a record read again is not delivered twice");
+        let to_first = sender.to(first);
+        let to_second = sender.to(second);
+        assert_eq!(to_first.len(), 3);
+        assert_eq!(to_first[0]["connector"], connector.raw());
+        assert_eq!(to_first[0]["key"], "k");
+        assert_eq!(to_first[0]["payload"], serde_json::json!([255]));
+
+        // Offset 1 is fully acknowledged first, but waits for offset 0
+        assert!(tracker.ack(delivery(&to_first[1])).is_empty());
+        assert!(tracker.ack(delivery(&to_second[1])).is_empty());
+        assert!(tracker.ack(delivery(&to_first[0])).is_empty());
+        assert!(tracker.ack(12345678).is_empty());
+        let done = tracker.ack(delivery(&to_second[0]));
+        assert_eq!(done.iter().map(|record| record.offset).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(tracker.len(), 1);
+
+        // The second subscriber leaves and another joins before offset 2
+        // is acknowledged, so it goes to the newcomer, with the first
+        // subscriber keeping its delivery id
+        tracker.ack(delivery(&to_first[2]));
+        bus.unsubscribe("orders", second);
+        bus.subscribe("orders", late);
+        assert!(tracker.redeliver(&bus, Duration::from_secs(30), start + Duration::from_secs(1)).is_empty());
+        let done = tracker.redeliver(&bus, Duration::from_secs(30), start + Duration::from_secs(31));
+        assert!(done.is_empty());
+        let to_late = sender.to(late);
+        assert_eq!(to_late.len(), 1);
+        assert_eq!(sender.to(first).len(), 3, "acknowledged records are not sent again");
+        assert_eq!(tracker.ack(delivery(&to_late[0])).len(), 1);
+        assert_eq!(tracker.len(), 0);
+
+        // A record with no subscribers waits for one
+        tracker.deliver(record("audit", 0), &bus, start);
+        assert!(tracker.redeliver(&bus, Duration::from_secs(30), start + Duration::from_secs(31)).is_empty());
+        bus.subscribe("audit", late);
+        tracker.redeliver(&bus, Duration::from_secs(30), start + Duration::from_secs(62));
+        let audit = sender.to(late).pop().unwrap();
+        assert_eq!(audit["topic"], "audit");
+        assert_eq!(tracker.ack(delivery(&audit)).len(), 1);
+    }
+
+    #[test]
+    fn test_consumer_redelivers_after_broker_failure() {
+        let broker = Arc::new(MemoryBroker::default());
+        for payload in ["a", "b", "c"] {
+            broker.append("orders", payload);
+        }
+        let sender = Arc::new(RecordingSender::default());
+        let bus = Arc::new(Bus::new(sender.clone()));
+        let subscriber = Pid::new();
+        bus.subscribe("orders", subscriber);
+        let spec = ConsumerSpec::new("orders", Arc::new(Shared(broker.clone()))).restart(quick_restarts(5));
+        let connector = start_consumer(Pid::new(), spec, bus).unwrap();
+
+        wait_for("the records", || sender.to(subscriber).len() == 3);
+        let messages = sender.to(subscriber);
+        assert_eq!(messages[0]["payload"], "a");
+        let mut actor = connector.actor();
+        actor.receive(MessagePayload::Data(serde_json::json!({"type": "ack", "id": delivery(&messages[0])}))).unwrap();
+        connector.ack(delivery(&messages[1]));
+        wait_for("the commit", || broker.committed("orders") == 2);
+        assert!(actor.receive(MessagePayload::Text("ack".to_string())).is_err());
+
+        // The broker fails before "c" is acknowledged, so it is read again
+        broker.fail.store(1, Ordering::SeqCst);
+        wait_for("the redelivery", || sender.to(subscriber).len() == 4);
+        let again = sender.to(subscriber).pop().unwrap();
+        assert_eq!((again["offset"].as_i64(), again["payload"].as_str()), (Some(2), Some("c")));
+        connector.ack(delivery(&messages[2]));
+        connector.ack(delivery(&again));
+        wait_for("the last commit", || connector.status().committed == 3);
+        assert_eq!(broker.committed("orders"), 3);
+        let status = connector.status();
+        assert_eq!(status.state, ConnectorState::Running);
+        assert_eq!((status.restarts, status.committed, status.in_flight), (1, 3, 0));
+        assert_eq!(broker.sessions.load(Ordering::SeqCst), 2);
+
+        connector.stop();
+        wait_for("the stop", || connector.status().state == ConnectorState::Stopped);
+        assert!(!actor.is_alive());
+    }
+
+    #[test]
+    fn test_supervision_gives_up() {
+        let broker = Arc::new(MemoryBroker::default());
+        broker.refuse.store(usize::MAX, Ordering::SeqCst);
+        let bus = Arc::new(Bus::new(Arc::new(RecordingSender::default())));
+        let spec = ConsumerSpec::new("down", Arc::new(Shared(broker))).restart(quick_restarts(2));
+        let connector = start_consumer(Pid::new(), spec, bus).unwrap();
+        wait_for("the connector to give up", || matches!(connector.status().state, ConnectorState::Down(_)));
+        assert_eq!(connector.status().restarts, 2);
+        assert_eq!(connector.status().state, ConnectorState::Down("Connector error: connection refused".to_string()));
+    }
+
+    #[test]
+    fn test_producer_retries_until_sent() {
+        let broker = Arc::new(MemoryBroker::default());
+        broker.fail.store(2, Ordering::SeqCst);
+        let spec = ProducerSpec::new("events", Arc::new(Shared(broker.clone()))).restart(quick_restarts(5));
+        let producer = start_producer(Pid::new(), spec).unwrap();
+        let mut actor = producer.actor();
+        actor.receive(MessagePayload::Data(serde_json::json!({"topic": "events", "key": "user-1", "payload": "signed up"}))).unwrap();
+        actor.receive(MessagePayload::Data(serde_json::json!({"topic": "events", "payload": {"n": 2}}))).unwrap();
+        assert!(actor.receive(MessagePayload::Data(serde_json::json!({"payload": "no topic"}))).is_err());
+
+        wait_for("the records", || producer.status().records == 2);
+        assert_eq!(
+            *broker.produced.lock().unwrap(),
+            vec![
+                ProducerRecord { topic: "events".to_string(), key: Some(b"user-1".to_vec()), payload: b"signed up".to_vec() },
+                ProducerRecord { topic: "events".to_string(), key: None, payload: b"{\"n\":2}".to_vec() },
+            ]
+        );
+        let status = producer.status();
+        assert_eq!((status.restarts, status.records), (2, 2));
+    }
+}
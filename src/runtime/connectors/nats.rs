@@ -0,0 +1,177 @@
+//! NATS JetStream broker
+//!
+//! Consumers read a stream through a durable pull consumer, named like a
+//! Kafka consumer group, so that a consumer that restarts resumes after
+//! the last record acknowledged. Every record is acknowledged to the server
+//! on its own once committed; records are all in partition 0, their
+//! offsets the stream's sequence numbers. Producers wait for the stream to
+//! store a record. NATS messages have no keys, so records' keys are not
+//! sent.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use async_nats::jetstream::consumer::{pull, AckPolicy};
+use futures::StreamExt;
+
+use super::{Broker, Consumer, Producer, ProducerRecord, Record};
+use crate::error::{RuntimeError, RuntimeResult};
+
+/// A NATS server, a JetStream stream and the durable consumer reading it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NatsBroker {
+    /// Server URL, as `nats://host:port`
+    pub url: String,
+    pub stream: String,
+    /// Name of the durable consumer
+    pub durable: String,
+    /// Subjects to read; all of the stream's when empty
+    pub subjects: Vec<String>,
+}
+
+impl NatsBroker {
+    pub fn new(url: impl Into<String>, stream: impl Into<String>, durable: impl Into<String>) -> Self {
+        NatsBroker {
+            url: url.into(),
+            stream: stream.into(),
+            durable: durable.into(),
+            subjects: Vec::new(),
+        }
+    }
+
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subjects.push(subject.into());
+        self
+    }
+
+    /// Settings of the durable consumer
+    pub fn consumer_config(&self) -> pull::Config {
+        let mut config = pull::Config {
+            durable_name: Some(self.durable.clone()),
+            ack_policy: AckPolicy::Explicit,
+            ..Default::default()
+        };
+        // A single filter works with servers older than 2.10 as well
+        match self.subjects.as_slice() {
+            [subject] => config.filter_subject = subject.clone(),
+            subjects => config.filter_subjects = subjects.to_vec(),
+        }
+        config
+    }
+
+    async fn context(&self) -> RuntimeResult<async_nats::jetstream::Context> {
+        let client = async_nats::connect(self.url.as_str()).await.map_err(|e| failed("connecting", e))?;
+        Ok(async_nats::jetstream::new(client))
+    }
+}
+
+fn failed(what: &str, error: impl std::fmt::Display) -> RuntimeError {
+    RuntimeError::Connector(format!("nats: {}: {}", what, error))
+}
+
+#[async_trait]
+impl Broker for NatsBroker {
+    fn describe(&self) -> String {
+        format!("{} stream {}", self.url, self.stream)
+    }
+
+    async fn consumer(&self) -> RuntimeResult<Box<dyn Consumer>> {
+        let stream = self
+            .context()
+            .await?
+            .get_stream(&self.stream)
+            .await
+            .map_err(|e| failed(&format!("opening stream {}", self.stream), e))?;
+        let consumer = stream
+            .get_or_create_consumer(&self.durable, self.consumer_config())
+            .await
+            .map_err(|e| failed(&format!("opening consumer {}", self.durable), e))?;
+        let messages = consumer.messages().await.map_err(|e| failed("reading", e))?;
+        Ok(Box::new(NatsConsumer {
+            messages,
+            unacked: HashMap::new(),
+        }))
+    }
+
+    async fn producer(&self) -> RuntimeResult<Box<dyn Producer>> {
+        Ok(Box::new(NatsProducer { context: self.context().await? }))
+    }
+}
+
+struct NatsConsumer {
+    messages: pull::Stream,
+    /// Messages read and not yet acknowledged, by stream sequence
+    unacked: HashMap<i64, async_nats::jetstream::Message>,
+}
+
+#[async_trait]
+impl Consumer for NatsConsumer {
+    async fn next(&mut self) -> RuntimeResult<Record> {
+        loop {
+            let message = match self.messages.next().await {
+                Some(message) => message.map_err(|e| failed("reading", e))?,
+                None => return Err(RuntimeError::Connector("nats: the consumer's messages ended".to_string())),
+            };
+            let offset = message.info().map_err(|e| failed("reading", e))?.stream_sequence as i64;
+            // The server sends a message again when it is not acknowledged
+            // in time; it is still on its way to subscribers
+            if self.unacked.contains_key(&offset) {
+                continue;
+            }
+            let record = Record {
+                topic: message.subject.to_string(),
+                partition: 0,
+                offset,
+                key: None,
+                payload: message.payload.to_vec(),
+            };
+            self.unacked.insert(offset, message);
+            return Ok(record);
+        }
+    }
+
+    async fn commit(&mut self, done: &[Record]) -> RuntimeResult<()> {
+        for record in done {
+            if let Some(message) = self.unacked.remove(&record.offset) {
+                message.ack().await.map_err(|e| failed("acknowledging", e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct NatsProducer {
+    context: async_nats::jetstream::Context,
+}
+
+#[async_trait]
+impl Producer for NatsProducer {
+    async fn send(&mut self, record: &ProducerRecord) -> RuntimeResult<()> {
+        let sending = |e: &dyn std::fmt::Display| failed(&format!("sending to {}", record.topic), e);
+        let stored = self
+            .context
+            .publish(record.topic.clone(), record.payload.clone().into())
+            .await
+            .map_err(|e| sending(&e))?;
+        stored.await.map(|_| ()).map_err(|e| sending(&e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consumer_settings() {
+        let broker = NatsBroker::new("nats://127.0.0.1:4222", "ORDERS", "billing").subject("orders.created");
+        let config = broker.consumer_config();
+        assert_eq!(config.durable_name.as_deref(), Some("billing"));
+        assert_eq!(config.ack_policy, AckPolicy::Explicit);
+        assert_eq!(config.filter_subject, "orders.created");
+        assert!(config.filter_subjects.is_empty());
+
+        let config = broker.subject("orders.paid").consumer_config();
+        assert_eq!(config.filter_subject, "");
+        assert_eq!(config.filter_subjects, vec!["orders.created", "orders.paid"]);
+    }
+}
@@ -0,0 +1,164 @@
+//! Crash dumps
+//!
+//! When an actor fails to handle a message its process writes a postmortem
+//! artifact: the last messages it received, the TLisp forms it was
+//! evaluating, its process dictionary, who it was linked to and a summary of
+//! its heap. Dumps are JSON files named after the pid in the dump directory,
+//! which the daemon sets from its configuration; a later crash of the same
+//! pid replaces the earlier dump. Without a dump directory crashes are only
+//! logged.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+
+use crate::error::{ReamError, ReamResult};
+use crate::security::secrets;
+use crate::types::Pid;
+
+/// Postmortem of a crashed actor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashDump {
+    /// Process that crashed
+    pub pid: Pid,
+    /// Error the actor failed with
+    pub reason: String,
+    /// When the crash happened
+    pub crashed_at: SystemTime,
+    /// Last messages received, oldest first; the last one is the message
+    /// being handled when the actor crashed
+    pub recent_messages: Vec<String>,
+    /// Forms being evaluated when the actor crashed, innermost first
+    pub trace: Vec<String>,
+    /// The actor's process dictionary
+    pub dictionary: BTreeMap<String, String>,
+    /// Parent process (if any)
+    pub parent: Option<Pid>,
+    /// Linked processes
+    pub links: Vec<Pid>,
+    /// Monitored processes
+    pub monitors: Vec<Pid>,
+    /// Heap and accounting at the time of the crash
+    pub heap: HeapSummary,
+}
+
+/// Heap and accounting of a crashed process
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeapSummary {
+    /// Memory attributed to the process in bytes
+    pub memory_usage: usize,
+    /// Messages still waiting in the mailbox
+    pub mailbox_len: usize,
+    /// Messages taken from the mailbox since the process started
+    pub messages_processed: u64,
+    /// CPU time used (microseconds)
+    pub cpu_time: u64,
+    /// Times the process has been restarted
+    pub restarts: u32,
+}
+
+impl CrashDump {
+    /// File the dump of `pid` is written to in `dir`
+    pub fn path(dir: &Path, pid: Pid) -> PathBuf {
+        dir.join(format!("{}.json", pid.0))
+    }
+
+    /// Write the dump into `dir`, creating it if needed. Secret values are
+    /// redacted from what the actor was handling.
+    pub fn write_to(&self, dir: &Path) -> ReamResult<PathBuf> {
+        std::fs::create_dir_all(dir).map_err(ReamError::Io)?;
+        let path = Self::path(dir, self.pid);
+        let json = serde_json::to_vec_pretty(&self.redacted())
+            .map_err(|e| ReamError::Other(format!("Failed to serialize crash dump: {}", e)))?;
+        std::fs::write(&path, json).map_err(ReamError::Io)?;
+        Ok(path)
+    }
+
+    /// This dump with secret values redacted
+    fn redacted(&self) -> CrashDump {
+        let redact = |text: &String| secrets::redact(text).into_owned();
+        CrashDump {
+            reason: redact(&self.reason),
+            recent_messages: self.recent_messages.iter().map(redact).collect(),
+            trace: self.trace.iter().map(redact).collect(),
+            dictionary: self.dictionary.iter().map(|(key, value)| (key.clone(), redact(value))).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Read the dump of `pid` from `dir`
+    pub fn load(dir: &Path, pid: Pid) -> ReamResult<CrashDump> {
+        let path = Self::path(dir, pid);
+        let json = match std::fs::read(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ReamError::Other(format!("No crash dump for {} in {}", pid, dir.display())));
+            }
+            Err(e) => return Err(ReamError::Io(e)),
+        };
+        serde_json::from_slice(&json)
+            .map_err(|e| ReamError::Other(format!("Invalid crash dump {}: {}", path.display(), e)))
+    }
+}
+
+static DUMP_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Set the directory crash dumps are written to; `None` stops writing them
+pub fn set_dump_dir(dir: Option<PathBuf>) {
+    *DUMP_DIR.write().unwrap() = dir;
+}
+
+/// Directory crash dumps are written to, if any
+pub fn dump_dir() -> Option<PathBuf> {
+    DUMP_DIR.read().unwrap().clone()
+}
+
+/// Log a crash and write its dump to the dump directory, if one is set
+pub fn record(dump: &CrashDump) {
+    let Some(dir) = dump_dir() else {
+        tracing::error!(pid = %dump.pid, reason = %dump.reason, "Actor crashed");
+        return;
+    };
+    match dump.write_to(&dir) {
+        Ok(path) => tracing::error!(pid = %dump.pid, reason = %dump.reason, dump = %path.display(), "Actor crashed"),
+        Err(e) => tracing::error!(pid = %dump.pid, reason = %dump.reason, error = %e, "Actor crashed; failed to write crash dump"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid = Pid::new();
+        let dump = CrashDump {
+            pid,
+            reason: "Actor error: boom".to_string(),
+            crashed_at: SystemTime::now(),
+            recent_messages: vec!["\"boom\"".to_string()],
+            trace: vec!["(fail)".to_string()],
+            dictionary: BTreeMap::from([("count".to_string(), "3".to_string())]),
+            parent: None,
+            links: vec![Pid::new()],
+            monitors: Vec::new(),
+            heap: HeapSummary { memory_usage: 1024, ..HeapSummary::default() },
+        };
+
+        let path = dump.write_to(&dir.path().join("dumps")).unwrap();
+        assert_eq!(path, CrashDump::path(&dir.path().join("dumps"), pid));
+
+        let loaded = CrashDump::load(&dir.path().join("dumps"), pid).unwrap();
+        assert_eq!(loaded.reason, dump.reason);
+        assert_eq!(loaded.trace, dump.trace);
+        assert_eq!(loaded.dictionary, dump.dictionary);
+        assert_eq!(loaded.links, dump.links);
+        assert_eq!(loaded.heap.memory_usage, 1024);
+
+        let err = CrashDump::load(dir.path(), Pid::new()).unwrap_err().to_string();
+        assert!(err.contains("No crash dump"), "{}", err);
+    }
+}
@@ -0,0 +1,473 @@
+//! Cron scheduler
+//!
+//! Recurring jobs run on cron schedules: five fields for the minute, hour,
+//! day of the month, month and day of the week, each `*`, a value, a range
+//! `a-b`, a step `*/n` or `a-b/n`, or a comma-separated list of those, as in
+//! `*/15 9-17 * * mon-fri`. The aliases `@hourly`, `@daily`, `@weekly`,
+//! `@monthly` and `@yearly` stand for the usual schedules. Schedules are in
+//! UTC. As in cron, when both the day of the month and the day of the week
+//! are restricted, a day matching either runs the job.
+//!
+//! A job that comes due calls a TLisp function, sends a message to an actor
+//! or invokes a serverless function; the scheduler only decides when, and
+//! leaves running the action to its owner. Jobs are saved to a file on every
+//! change, so they survive restarts; runs that fell due while the scheduler
+//! was not running are handled by the job's `MissedRuns` policy.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Timelike, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{RuntimeError, RuntimeResult};
+
+/// How late a run may start and still count as on time
+pub const MISSED_GRACE: ChronoDuration = ChronoDuration::seconds(60);
+
+/// Most missed runs `MissedRuns::All` catches up on
+pub const MAX_CATCH_UP: usize = 100;
+
+/// Minutes searched for the next run before a schedule is taken to never run
+const SEARCH_LIMIT: usize = 1_000_000;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron schedule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronExpr {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpr {
+    /// Whether the schedule runs at the minute starting at `time`
+    pub fn matches(&self, time: &DateTime<Utc>) -> bool {
+        self.month_matches(time) && self.day_matches(time)
+            && bit(self.hours, time.hour()) && bit(self.minutes, time.minute())
+    }
+
+    /// The first minute after `time` the schedule runs at, if it ever runs
+    pub fn next_after(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut next = time.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        for _ in 0..SEARCH_LIMIT {
+            if !self.month_matches(&next) {
+                let (year, month) = if next.month() == 12 { (next.year() + 1, 1) } else { (next.year(), next.month() + 1) };
+                next = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !self.day_matches(&next) {
+                next = (next.date_naive() + ChronoDuration::days(1)).and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !bit(self.hours, next.hour()) {
+                next = next.with_minute(0)? + ChronoDuration::hours(1);
+            } else if !bit(self.minutes, next.minute()) {
+                next += ChronoDuration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+        None
+    }
+
+    fn month_matches(&self, time: &DateTime<Utc>) -> bool {
+        bit(self.months, time.month())
+    }
+
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool {
+        let day = bit(self.days, time.day());
+        let weekday = bit(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, String> {
+        let expanded = match source.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("Invalid cron expression {:?}: expected 5 fields, found {}", source, fields.len()));
+        };
+        let invalid = |e: String| format!("Invalid cron expression {:?}: {}", source, e);
+        let weekdays = field(weekday, 0, 7, &WEEKDAYS).map_err(invalid)?;
+        Ok(CronExpr {
+            source: source.trim().to_string(),
+            minutes: field(minute, 0, 59, &[]).map_err(invalid)?,
+            hours: field(hour, 0, 23, &[]).map_err(invalid)?,
+            days: field(day, 1, 31, &[]).map_err(invalid)?,
+            months: field(month, 1, 12, &MONTHS).map_err(invalid)?,
+            // Sunday is both 0 and 7
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+impl TryFrom<String> for CronExpr {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, String> {
+        source.parse()
+    }
+}
+
+impl From<CronExpr> for String {
+    fn from(expr: CronExpr) -> String {
+        expr.source
+    }
+}
+
+impl fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+/// Parse one field into a mask of the values it matches, from `min` to
+/// `max`; `names` are accepted for the values from `min` on
+fn field(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_ascii_lowercase();
+        let value = match names.iter().position(|name| *name == lower) {
+            Some(index) => index as u32 + min,
+            None => text.parse().map_err(|_| format!("{:?} is not a value", text))?,
+        };
+        if value < min || value > max {
+            return Err(format!("{} is outside {}-{}", value, min, max));
+        }
+        Ok(value)
+    };
+
+    let mut mask = 0;
+    for item in text.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("{:?} is not a step", step))?;
+                if step == 0 {
+                    return Err("step must be at least 1".to_string());
+                }
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (value(from)?, value(to)?),
+                // `a/n` runs from `a` to the end of the range
+                None if step.is_some() => (value(range)?, max),
+                None => {
+                    let value = value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if from > to {
+            return Err(format!("range {} is backwards", range));
+        }
+        for value in (from..=to).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+/// What a job does when it comes due
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobAction {
+    /// Call a TLisp function with no arguments
+    Call { function: String },
+    /// Send a text message to an actor
+    Send { actor: String, message: String },
+    /// Invoke a serverless function
+    Invoke { function: String, payload: String },
+}
+
+impl fmt::Display for JobAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobAction::Call { function } => write!(f, "call ({})", function),
+            JobAction::Send { actor, message } => write!(f, "send {:?} to {}", message, actor),
+            JobAction::Invoke { function, .. } => write!(f, "invoke fn {}", function),
+        }
+    }
+}
+
+/// What happens to runs that fell due while the scheduler was not running,
+/// or that it noticed too late
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum MissedRuns {
+    /// Missed runs are skipped
+    #[default]
+    Skip,
+    /// Missed runs are made up for with a single run
+    Once,
+    /// Every missed run is made up for, up to `MAX_CATCH_UP`
+    All,
+}
+
+/// A recurring job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CronJob {
+    /// Job name
+    pub name: String,
+    /// When the job runs
+    pub schedule: CronExpr,
+    /// What the job does
+    pub action: JobAction,
+    /// Each run is delayed by a random amount up to this, to spread out
+    /// jobs on the same schedule
+    #[serde(default)]
+    pub jitter: std::time::Duration,
+    /// What happens to missed runs
+    #[serde(default)]
+    pub missed: MissedRuns,
+    /// When the job last ran
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+    /// The next run the schedule calls for
+    #[serde(default)]
+    pub next_run: Option<DateTime<Utc>>,
+    /// When the next run starts, after its jitter
+    #[serde(default)]
+    pub due_at: Option<DateTime<Utc>>,
+}
+
+impl CronJob {
+    /// A job running `action` on `schedule`, without jitter, skipping missed runs
+    pub fn new(name: impl Into<String>, schedule: CronExpr, action: JobAction) -> Self {
+        CronJob {
+            name: name.into(),
+            schedule,
+            action,
+            jitter: std::time::Duration::ZERO,
+            missed: MissedRuns::Skip,
+            last_run: None,
+            next_run: None,
+            due_at: None,
+        }
+    }
+
+    fn plan(&mut self, next_run: Option<DateTime<Utc>>) {
+        let jitter = self.jitter.as_millis() as i64;
+        self.next_run = next_run;
+        self.due_at = next_run.map(|run| match jitter {
+            0 => run,
+            _ => run + ChronoDuration::milliseconds(rand::thread_rng().gen_range(0..=jitter)),
+        });
+    }
+}
+
+/// Recurring jobs, saved to a file when they change
+#[derive(Debug, Default)]
+pub struct CronScheduler {
+    jobs: BTreeMap<String, CronJob>,
+    path: Option<PathBuf>,
+}
+
+impl CronScheduler {
+    /// A scheduler keeping its jobs in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A scheduler saving its jobs to `path`, starting with the jobs
+    /// already saved there
+    pub fn open(path: &Path) -> RuntimeResult<Self> {
+        let jobs: Vec<CronJob> = match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                RuntimeError::Scheduler(format!("Invalid schedule file {}: {}", path.display(), e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(RuntimeError::Scheduler(format!("Failed to read {}: {}", path.display(), e))),
+        };
+        Ok(CronScheduler {
+            jobs: jobs.into_iter().map(|job| (job.name.clone(), job)).collect(),
+            path: Some(path.to_path_buf()),
+        })
+    }
+
+    /// Add a job, replacing any job with the same name; its first run is
+    /// the first the schedule calls for after `now`
+    pub fn add(&mut self, mut job: CronJob, now: DateTime<Utc>) -> RuntimeResult<()> {
+        let next_run = job.schedule.next_after(&now)
+            .ok_or_else(|| RuntimeError::Scheduler(format!("Schedule {} never runs", job.schedule)))?;
+        job.plan(Some(next_run));
+        self.jobs.insert(job.name.clone(), job);
+        self.save()
+    }
+
+    /// Remove a job
+    pub fn remove(&mut self, name: &str) -> RuntimeResult<CronJob> {
+        let job = self.jobs.remove(name)
+            .ok_or_else(|| RuntimeError::Scheduler(format!("Job {} not found", name)))?;
+        self.save()?;
+        Ok(job)
+    }
+
+    /// The jobs, by name
+    pub fn jobs(&self) -> impl Iterator<Item = &CronJob> {
+        self.jobs.values()
+    }
+
+    /// Take the runs due at `now`, planning each job's next run. A run due
+    /// more than `MISSED_GRACE` ago, or with runs after it also due, is
+    /// missed, and is made up for as the job's policy says.
+    pub fn due(&mut self, now: DateTime<Utc>) -> RuntimeResult<Vec<(String, JobAction)>> {
+        let mut runs = Vec::new();
+        let mut changed = false;
+        for job in self.jobs.values_mut() {
+            let (Some(next_run), Some(due_at)) = (job.next_run, job.due_at) else {
+                continue;
+            };
+            if due_at > now {
+                continue;
+            }
+
+            let mut due = 1;
+            let mut next = job.schedule.next_after(&next_run);
+            while let Some(run) = next.filter(|run| *run <= now) {
+                due += 1;
+                next = job.schedule.next_after(&run);
+            }
+            let on_time = due == 1 && now - due_at <= MISSED_GRACE;
+            let count = match job.missed {
+                _ if on_time => 1,
+                MissedRuns::Skip => 0,
+                MissedRuns::Once => 1,
+                MissedRuns::All => due.min(MAX_CATCH_UP),
+            };
+            if count < due {
+                tracing::warn!(job = %job.name, missed = due - count, "Skipped missed cron runs");
+            }
+
+            if count > 0 {
+                job.last_run = Some(now);
+            }
+            job.plan(next);
+            runs.extend(std::iter::repeat_n((job.name.clone(), job.action.clone()), count));
+            changed = true;
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(runs)
+    }
+
+    /// Write the jobs to the schedule file, replacing it whole
+    fn save(&self) -> RuntimeResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let failed = |e: std::io::Error| RuntimeError::Scheduler(format!("Failed to save {}: {}", path.display(), e));
+        let jobs: Vec<&CronJob> = self.jobs.values().collect();
+        let json = serde_json::to_vec_pretty(&jobs)
+            .map_err(|e| RuntimeError::Scheduler(format!("Failed to serialize jobs: {}", e)))?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(failed)?;
+        }
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, json).map_err(failed)?;
+        std::fs::rename(&temp, path).map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_cron_expressions() {
+        let office: CronExpr = "*/15 9-17 * * mon-fri".parse().unwrap();
+        // Friday 17:50 is followed by Monday 09:00
+        assert_eq!(office.next_after(&at("2026-10-16T17:50:00Z")), Some(at("2026-10-19T09:00:00Z")));
+        assert_eq!(office.next_after(&at("2026-10-19T09:07:30Z")), Some(at("2026-10-19T09:15:00Z")));
+        assert!(office.matches(&at("2026-10-19T12:30:00Z")));
+        assert!(!office.matches(&at("2026-10-18T12:30:00Z")));
+
+        // Restricting both days matches either: the 1st, or any Sunday (as 7)
+        let either: CronExpr = "0 0 1 * 7".parse().unwrap();
+        assert_eq!(either.next_after(&at("2026-10-17T00:00:00Z")), Some(at("2026-10-18T00:00:00Z")));
+        assert_eq!(either.next_after(&at("2026-10-26T00:00:00Z")), Some(at("2026-11-01T00:00:00Z")));
+
+        let yearly: CronExpr = "@yearly".parse().unwrap();
+        assert_eq!(yearly.next_after(&at("2026-10-17T00:00:00Z")), Some(at("2027-01-01T00:00:00Z")));
+        let leap: CronExpr = "30 12 29 feb *".parse().unwrap();
+        assert_eq!(leap.next_after(&at("2026-10-17T00:00:00Z")), Some(at("2028-02-29T12:30:00Z")));
+        assert_eq!("0 0 31 2 *".parse::<CronExpr>().unwrap().next_after(&at("2026-10-17T00:00:00Z")), None);
+
+        for invalid in ["* * * *", "60 * * * *", "* * * * 8", "*/0 * * * *", "5-1 * * * *", "* * * jab *"] {
+            assert!(invalid.parse::<CronExpr>().is_err(), "{} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn test_missed_runs_and_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedules.json");
+        let mut scheduler = CronScheduler::open(&path).unwrap();
+
+        let hourly = "@hourly".parse::<CronExpr>().unwrap();
+        let call = JobAction::Call { function: "report".to_string() };
+        for missed in [MissedRuns::Skip, MissedRuns::Once, MissedRuns::All] {
+            let mut job = CronJob::new(format!("{:?}", missed), hourly.clone(), call.clone());
+            job.missed = missed;
+            scheduler.add(job, at("2026-10-17T10:30:00Z")).unwrap();
+        }
+        let mut jittered = CronJob::new("jittered", "0 12 * * *".parse().unwrap(), call.clone());
+        jittered.jitter = std::time::Duration::from_secs(30);
+        scheduler.add(jittered, at("2026-10-17T10:30:00Z")).unwrap();
+        let job = scheduler.jobs().find(|job| job.name == "jittered").unwrap();
+        let delay = job.due_at.unwrap() - job.next_run.unwrap();
+        assert!(delay >= ChronoDuration::zero() && delay <= ChronoDuration::seconds(30));
+
+        // On time, every job runs once
+        assert!(scheduler.due(at("2026-10-17T10:59:00Z")).unwrap().is_empty());
+        assert_eq!(scheduler.due(at("2026-10-17T11:00:10Z")).unwrap().len(), 3);
+
+        // Reopened after three hours down, the jobs keep their schedule and
+        // make up for the runs at 12, 13 and 14 as their policies say
+        drop(scheduler);
+        let mut scheduler = CronScheduler::open(&path).unwrap();
+        assert_eq!(scheduler.jobs().count(), 4);
+        let runs = scheduler.due(at("2026-10-17T14:20:00Z")).unwrap();
+        let count = |name: &str| runs.iter().filter(|(job, _)| job == name).count();
+        assert_eq!((count("Skip"), count("Once"), count("All"), count("jittered")), (0, 1, 3, 0));
+        let job = scheduler.jobs().find(|job| job.name == "Skip").unwrap();
+        assert_eq!(job.next_run, Some(at("2026-10-17T15:00:00Z")));
+        assert_eq!(job.last_run, Some(at("2026-10-17T11:00:10Z")));
+
+        scheduler.remove("Skip").unwrap();
+        assert!(scheduler.remove("Skip").is_err());
+        assert_eq!(CronScheduler::open(&path).unwrap().jobs().count(), 3);
+    }
+}
@@ -0,0 +1,351 @@
+//! Isolation domains
+//!
+//! A domain is one tenant's slice of the runtime. Processes spawned into a
+//! domain only see each other: names registered in it resolve only there,
+//! and messages sent through it only reach its own processes. Each domain
+//! has quotas on how many actors it runs, the memory they hold and the
+//! messages they send per second, and can run its processes under a
+//! profile of the capability policy. Suspending a domain suspends its
+//! processes and refuses new ones until it is resumed; deleting it
+//! terminates them.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::bytecode::actor::{BytecodeActor, ProcessHost};
+use crate::error::{RuntimeError, RuntimeResult};
+use crate::security::policy;
+use crate::types::{MessagePayload, Pid};
+use super::message::TokenBucket;
+use super::{ReamActor, ReamRuntime};
+
+/// Limits on what a domain's processes use; `None` is unlimited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainQuotas {
+    /// Processes alive in the domain at once
+    pub max_actors: Option<usize>,
+    /// Memory held by the domain's processes in bytes, checked on spawn
+    pub max_memory: Option<usize>,
+    /// Messages per second sent through the domain
+    pub max_message_rate: Option<u32>,
+}
+
+/// How a domain is set up
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainConfig {
+    /// Resource quotas
+    pub quotas: DomainQuotas,
+    /// Capability profile the domain's processes run under
+    pub profile: Option<String>,
+}
+
+/// Whether a domain's processes run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DomainState {
+    /// Processes run and new ones may be spawned
+    Active,
+    /// Processes are suspended and none may be spawned
+    Suspended,
+}
+
+/// Summary of a domain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainInfo {
+    /// Domain name
+    pub name: String,
+    /// How the domain was set up
+    pub config: DomainConfig,
+    /// Whether its processes run
+    pub state: DomainState,
+    /// Processes alive in the domain
+    pub actors: usize,
+    /// Memory held by those processes in bytes
+    pub memory_usage: usize,
+    /// Names registered in the domain
+    pub names: Vec<String>,
+}
+
+/// A domain's processes, names and message budget
+#[derive(Debug)]
+pub struct IsolationDomain {
+    config: DomainConfig,
+    state: DomainState,
+    processes: HashSet<Pid>,
+    names: HashMap<String, Pid>,
+    /// Refilled at the domain's message rate, holding at most a second's worth
+    budget: Option<TokenBucket>,
+}
+
+fn domain_not_found(name: &str) -> RuntimeError {
+    RuntimeError::RuntimeError(format!("Domain {} not found", name))
+}
+
+impl ReamRuntime {
+    /// Create a domain
+    pub fn create_domain(&self, name: &str, config: DomainConfig) -> RuntimeResult<()> {
+        match self.domains.entry(name.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => {
+                Err(RuntimeError::RuntimeError(format!("Domain {} already exists", name)))
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(IsolationDomain {
+                    budget: config.quotas.max_message_rate.map(|rate| TokenBucket::new(rate, rate)),
+                    config,
+                    state: DomainState::Active,
+                    processes: HashSet::new(),
+                    names: HashMap::new(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Spawn a process into a domain, within its quotas
+    pub fn spawn_in<A>(&self, domain: &str, actor: A) -> RuntimeResult<Pid>
+    where
+        A: ReamActor + Send + Sync + 'static,
+    {
+        self.spawn_in_as(domain, Pid::new(), actor)
+    }
+
+    /// Spawn a process into a domain under a pid chosen by the caller
+    pub fn spawn_in_as<A>(&self, domain: &str, pid: Pid, actor: A) -> RuntimeResult<Pid>
+    where
+        A: ReamActor + Send + Sync + 'static,
+    {
+        let mut entry = self.domains.get_mut(domain).ok_or_else(|| domain_not_found(domain))?;
+        if entry.state == DomainState::Suspended {
+            return Err(RuntimeError::RuntimeError(format!("Domain {} is suspended", domain)));
+        }
+        self.prune(&mut entry);
+        let quotas = &entry.config.quotas;
+        if let Some(max) = quotas.max_actors {
+            if entry.processes.len() >= max {
+                return Err(RuntimeError::QuotaExceeded(format!("domain {} runs its maximum of {} actors", domain, max)));
+            }
+        }
+        if let Some(max) = quotas.max_memory {
+            let used = self.memory_of(&entry);
+            if used >= max {
+                return Err(RuntimeError::QuotaExceeded(format!("domain {} holds {} of its {} bytes", domain, used, max)));
+            }
+        }
+
+        // Assigned first so the process never runs outside its profile
+        policy::assign(pid, entry.config.profile.clone());
+        if let Err(e) = self.spawn_as(pid, actor) {
+            policy::assign(pid, None);
+            return Err(e);
+        }
+        entry.processes.insert(pid);
+        Ok(pid)
+    }
+
+    /// Send a message from within a domain; processes outside it cannot be
+    /// reached
+    pub fn send_in(&self, domain: &str, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+        let mut entry = self.domains.get_mut(domain).ok_or_else(|| domain_not_found(domain))?;
+        if entry.state == DomainState::Suspended {
+            return Err(RuntimeError::RuntimeError(format!("Domain {} is suspended", domain)));
+        }
+        if !entry.processes.contains(&to) {
+            return Err(RuntimeError::ProcessNotFound(to));
+        }
+        if let Some(budget) = entry.budget.as_mut() {
+            if !budget.take(Instant::now()) {
+                let rate = entry.config.quotas.max_message_rate.unwrap_or_default();
+                return Err(RuntimeError::QuotaExceeded(format!("domain {} sends at most {} messages per second", domain, rate)));
+            }
+        }
+        drop(entry);
+        self.send(to, payload)
+    }
+
+    /// Register a process of a domain under a name unique within it
+    pub fn register_name(&self, domain: &str, name: &str, pid: Pid) -> RuntimeResult<()> {
+        let mut entry = self.domains.get_mut(domain).ok_or_else(|| domain_not_found(domain))?;
+        self.prune(&mut entry);
+        if !entry.processes.contains(&pid) {
+            return Err(RuntimeError::ProcessNotFound(pid));
+        }
+        if entry.names.contains_key(name) {
+            return Err(RuntimeError::RuntimeError(format!("Name {} is already registered in domain {}", name, domain)));
+        }
+        entry.names.insert(name.to_string(), pid);
+        Ok(())
+    }
+
+    /// Process registered under `name` in a domain
+    pub fn whereis(&self, domain: &str, name: &str) -> Option<Pid> {
+        let pid = *self.domains.get(domain)?.names.get(name)?;
+        self.processes.contains_key(&pid).then_some(pid)
+    }
+
+    /// Domain a process was spawned into
+    pub fn domain_of(&self, pid: Pid) -> Option<String> {
+        self.domains.iter()
+            .find(|entry| entry.processes.contains(&pid))
+            .map(|entry| entry.key().clone())
+    }
+
+    /// Suspend every process of a domain and refuse new ones
+    pub fn suspend_domain(&self, domain: &str) -> RuntimeResult<()> {
+        let mut entry = self.domains.get_mut(domain).ok_or_else(|| domain_not_found(domain))?;
+        self.prune(&mut entry);
+        for pid in &entry.processes {
+            if let Some(handle) = self.get_process(*pid) {
+                handle.suspend()?;
+            }
+        }
+        entry.state = DomainState::Suspended;
+        Ok(())
+    }
+
+    /// Resume the processes of a suspended domain
+    pub fn resume_domain(&self, domain: &str) -> RuntimeResult<()> {
+        let mut entry = self.domains.get_mut(domain).ok_or_else(|| domain_not_found(domain))?;
+        self.prune(&mut entry);
+        for pid in &entry.processes {
+            if let Some(handle) = self.get_process(*pid) {
+                handle.resume()?;
+            }
+        }
+        entry.state = DomainState::Active;
+        Ok(())
+    }
+
+    /// Terminate every process of a domain and remove it
+    pub fn delete_domain(&self, domain: &str) -> RuntimeResult<()> {
+        let (_, removed) = self.domains.remove(domain).ok_or_else(|| domain_not_found(domain))?;
+        for pid in removed.processes {
+            policy::assign(pid, None);
+            self.terminate_process(pid)?;
+        }
+        Ok(())
+    }
+
+    /// Summaries of the domains, by name
+    pub fn domains(&self) -> Vec<DomainInfo> {
+        let mut domains: Vec<DomainInfo> = self.domains.iter_mut()
+            .map(|mut entry| {
+                self.prune(&mut entry);
+                let mut names: Vec<String> = entry.names.keys().cloned().collect();
+                names.sort();
+                DomainInfo {
+                    name: entry.key().clone(),
+                    config: entry.config.clone(),
+                    state: entry.state,
+                    actors: entry.processes.len(),
+                    memory_usage: self.memory_of(&entry),
+                    names,
+                }
+            })
+            .collect();
+        domains.sort_by(|a, b| a.name.cmp(&b.name));
+        domains
+    }
+
+    /// Forget processes of a domain that have exited
+    fn prune(&self, domain: &mut IsolationDomain) {
+        domain.processes.retain(|pid| {
+            let alive = self.processes.contains_key(pid);
+            if !alive {
+                policy::assign(*pid, None);
+            }
+            alive
+        });
+        let processes = &domain.processes;
+        domain.names.retain(|_, pid| processes.contains(pid));
+    }
+
+    fn memory_of(&self, domain: &IsolationDomain) -> usize {
+        domain.processes.iter()
+            .filter_map(|pid| self.get_process(*pid))
+            .map(|handle| handle.info().memory_usage)
+            .sum()
+    }
+}
+
+/// Host that keeps the processes an actor spawns and messages it sends
+/// inside its domain
+pub struct DomainHost {
+    runtime: Arc<ReamRuntime>,
+    domain: String,
+}
+
+impl DomainHost {
+    /// Host for actors of `domain`
+    pub fn new(runtime: Arc<ReamRuntime>, domain: impl Into<String>) -> Self {
+        DomainHost { runtime, domain: domain.into() }
+    }
+}
+
+impl ProcessHost for DomainHost {
+    fn spawn(&self, actor: BytecodeActor) -> RuntimeResult<()> {
+        self.runtime.spawn_in_as(&self.domain, actor.pid(), actor).map(|_| ())
+    }
+
+    fn send(&self, to: Pid, message: MessagePayload) -> RuntimeResult<()> {
+        self.runtime.send_in(&self.domain, to, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::actor::CounterActor;
+    use crate::types::ProcessState;
+
+    #[test]
+    fn test_domains_are_isolated_and_bounded() {
+        let runtime = ReamRuntime::new().unwrap();
+        runtime.create_domain("acme", DomainConfig {
+            quotas: DomainQuotas { max_actors: Some(2), max_message_rate: Some(3), ..Default::default() },
+            profile: None,
+        }).unwrap();
+        runtime.create_domain("globex", DomainConfig::default()).unwrap();
+        assert!(runtime.create_domain("acme", DomainConfig::default()).is_err());
+
+        let first = runtime.spawn_in("acme", CounterActor::new(Pid::new(), 0)).unwrap();
+        let second = runtime.spawn_in("acme", CounterActor::new(Pid::new(), 0)).unwrap();
+        let err = runtime.spawn_in("acme", CounterActor::new(Pid::new(), 0)).unwrap_err();
+        assert!(matches!(err, RuntimeError::QuotaExceeded(_)), "{}", err);
+        let other = runtime.spawn_in("globex", CounterActor::new(Pid::new(), 0)).unwrap();
+        assert_eq!(runtime.domain_of(first).as_deref(), Some("acme"));
+
+        // Names and pids of one domain are invisible from another
+        runtime.register_name("acme", "counter", first).unwrap();
+        assert_eq!(runtime.whereis("acme", "counter"), Some(first));
+        assert_eq!(runtime.whereis("globex", "counter"), None);
+        assert!(runtime.register_name("acme", "counter", second).is_err());
+        assert!(matches!(runtime.register_name("globex", "stolen", first), Err(RuntimeError::ProcessNotFound(_))));
+        let increment = || MessagePayload::Text("increment".to_string());
+        assert!(matches!(runtime.send_in("globex", first, increment()), Err(RuntimeError::ProcessNotFound(_))));
+
+        // The message rate allows a burst of one second's worth
+        for _ in 0..3 {
+            runtime.send_in("acme", second, increment()).unwrap();
+        }
+        assert!(matches!(runtime.send_in("acme", second, increment()), Err(RuntimeError::QuotaExceeded(_))));
+
+        runtime.suspend_domain("acme").unwrap();
+        assert_eq!(runtime.get_process(first).unwrap().state(), ProcessState::Suspended);
+        assert_eq!(runtime.get_process(other).unwrap().state(), ProcessState::Running);
+        assert!(runtime.spawn_in("acme", CounterActor::new(Pid::new(), 0)).is_err());
+        runtime.resume_domain("acme").unwrap();
+        assert_eq!(runtime.get_process(first).unwrap().state(), ProcessState::Running);
+
+        let domains = runtime.domains();
+        assert_eq!(domains.iter().map(|d| (d.name.as_str(), d.actors)).collect::<Vec<_>>(), vec![("acme", 2), ("globex", 1)]);
+        assert_eq!(domains[0].names, vec!["counter".to_string()]);
+
+        runtime.delete_domain("acme").unwrap();
+        assert!(runtime.get_process(first).is_none());
+        assert!(runtime.get_process(other).is_some());
+        assert!(runtime.spawn_in("acme", CounterActor::new(Pid::new(), 0)).is_err());
+    }
+}
@@ -0,0 +1,761 @@
+//! Event-sourced actors
+//!
+//! An event-sourced actor's state is the fold of the events it has
+//! persisted. Handling a message decides which events the message causes;
+//! each is appended to the actor's journal in an `EventStore`, under the
+//! actor's persistence id, and then applied to the state. When the actor
+//! starts, or its supervisor restarts it, the state is recovered by
+//! replaying the journal through `apply`, from the latest snapshot if there
+//! is one. With `Journal::snapshot_every(n)` the state is snapshotted every
+//! n events, so recovery never replays more than n - 1 of them.
+//!
+//! Journals are kept in memory by `MemoryEventStore`, or in a
+//! categorical-sqlite database file by `SqliteEventStore`, which
+//! `ream debug journal` reads.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use categorical_sqlite::engine::{DatabaseConfig, Session};
+use categorical_sqlite::query::QueryResult;
+use categorical_sqlite::{CategoricalSQLite, Row, SqlError, SqlResult, Value as SqlValue};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{RuntimeError, RuntimeResult};
+use crate::runtime::actor::ReamActor;
+use crate::types::{MessagePayload, Pid};
+
+/// An event in a journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub persistence_id: String,
+    /// Position in the journal, from 1
+    pub sequence: u64,
+    pub event: Value,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// An actor's state after the events of its journal up to `sequence`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub persistence_id: String,
+    pub sequence: u64,
+    pub state: Value,
+    pub taken_at: DateTime<Utc>,
+}
+
+/// Summary of a journal in a store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalInfo {
+    pub persistence_id: String,
+    /// Number of the last event, 0 for an empty journal
+    pub highest_sequence: u64,
+    /// Number of the last event the latest snapshot includes
+    pub snapshot_sequence: Option<u64>,
+}
+
+/// Where journals and their snapshots are kept
+pub trait EventStore: Send + Sync {
+    /// Append an event as number `sequence`, failing unless the journal's
+    /// last event is number `sequence - 1`
+    fn append(&self, persistence_id: &str, sequence: u64, event: &Value) -> RuntimeResult<JournalEntry>;
+
+    /// The events numbered above `after`, oldest first
+    fn events(&self, persistence_id: &str, after: u64) -> RuntimeResult<Vec<JournalEntry>>;
+
+    /// Number of the journal's last event, 0 if it has none
+    fn highest_sequence(&self, persistence_id: &str) -> RuntimeResult<u64>;
+
+    /// Save a snapshot, replacing the journal's earlier one
+    fn save_snapshot(&self, snapshot: &Snapshot) -> RuntimeResult<()>;
+
+    fn latest_snapshot(&self, persistence_id: &str) -> RuntimeResult<Option<Snapshot>>;
+
+    /// The journals in the store, by persistence id
+    fn journals(&self) -> RuntimeResult<Vec<JournalInfo>>;
+}
+
+fn out_of_sequence(persistence_id: &str, highest: u64, sequence: u64) -> RuntimeError {
+    RuntimeError::Persistence(format!(
+        "journal {} is at event {}, cannot append event {}", persistence_id, highest, sequence))
+}
+
+/// Journals kept in memory, lost with the process
+#[derive(Debug, Default)]
+pub struct MemoryEventStore {
+    journals: RwLock<BTreeMap<String, Vec<JournalEntry>>>,
+    snapshots: RwLock<BTreeMap<String, Snapshot>>,
+}
+
+impl MemoryEventStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EventStore for MemoryEventStore {
+    fn append(&self, persistence_id: &str, sequence: u64, event: &Value) -> RuntimeResult<JournalEntry> {
+        let mut journals = self.journals.write().unwrap();
+        let journal = journals.entry(persistence_id.to_string()).or_default();
+        let highest = journal.last().map_or(0, |entry| entry.sequence);
+        if sequence != highest + 1 {
+            return Err(out_of_sequence(persistence_id, highest, sequence));
+        }
+        let entry = JournalEntry {
+            persistence_id: persistence_id.to_string(),
+            sequence,
+            event: event.clone(),
+            recorded_at: Utc::now(),
+        };
+        journal.push(entry.clone());
+        Ok(entry)
+    }
+
+    fn events(&self, persistence_id: &str, after: u64) -> RuntimeResult<Vec<JournalEntry>> {
+        let journals = self.journals.read().unwrap();
+        Ok(journals.get(persistence_id)
+            .map(|journal| journal.iter().filter(|entry| entry.sequence > after).cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn highest_sequence(&self, persistence_id: &str) -> RuntimeResult<u64> {
+        let journals = self.journals.read().unwrap();
+        Ok(journals.get(persistence_id).and_then(|journal| journal.last()).map_or(0, |entry| entry.sequence))
+    }
+
+    fn save_snapshot(&self, snapshot: &Snapshot) -> RuntimeResult<()> {
+        self.snapshots.write().unwrap().insert(snapshot.persistence_id.clone(), snapshot.clone());
+        Ok(())
+    }
+
+    fn latest_snapshot(&self, persistence_id: &str) -> RuntimeResult<Option<Snapshot>> {
+        Ok(self.snapshots.read().unwrap().get(persistence_id).cloned())
+    }
+
+    fn journals(&self) -> RuntimeResult<Vec<JournalInfo>> {
+        let journals = self.journals.read().unwrap();
+        let snapshots = self.snapshots.read().unwrap();
+        let mut infos = BTreeMap::new();
+        for (id, journal) in journals.iter() {
+            infos.insert(id.clone(), JournalInfo {
+                persistence_id: id.clone(),
+                highest_sequence: journal.last().map_or(0, |entry| entry.sequence),
+                snapshot_sequence: None,
+            });
+        }
+        for (id, snapshot) in snapshots.iter() {
+            infos.entry(id.clone())
+                .or_insert_with(|| JournalInfo { persistence_id: id.clone(), highest_sequence: 0, snapshot_sequence: None })
+                .snapshot_sequence = Some(snapshot.sequence);
+        }
+        Ok(infos.into_values().collect())
+    }
+}
+
+const SQLITE_SCHEMA: [(&str, &str); 2] = [
+    ("journal", "CREATE TABLE journal (
+        persistence_id TEXT NOT NULL,
+        sequence INTEGER NOT NULL,
+        event TEXT NOT NULL,
+        recorded_at INTEGER NOT NULL,
+        PRIMARY KEY (persistence_id, sequence)
+    )"),
+    ("snapshots", "CREATE TABLE snapshots (
+        persistence_id TEXT PRIMARY KEY,
+        sequence INTEGER NOT NULL,
+        state TEXT NOT NULL,
+        taken_at INTEGER NOT NULL
+    )"),
+];
+
+fn sql_error(what: &str, error: SqlError) -> RuntimeError {
+    RuntimeError::Persistence(format!("{}: {}", what, error))
+}
+
+fn parse_json(what: &str, text: &str) -> RuntimeResult<Value> {
+    serde_json::from_str(text).map_err(|e| RuntimeError::Persistence(format!("{} is not JSON: {}", what, e)))
+}
+
+fn from_millis(millis: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+fn store_stopped() -> RuntimeError {
+    RuntimeError::Persistence("the event store's database thread has stopped".to_string())
+}
+
+/// Work for the database thread of a `SqliteEventStore`
+type StoreJob = Box<dyn FnOnce(CategoricalSQLite) -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+/// Journals kept in a categorical-sqlite database, one row per event
+///
+/// The engine is async and its statement futures are not `Send`, so the
+/// store gives it a thread with a runtime of its own; each call hands its
+/// statements to that thread and waits for them, which works from plain
+/// threads and from inside another runtime alike.
+#[derive(Debug)]
+pub struct SqliteEventStore {
+    jobs: mpsc::Sender<StoreJob>,
+}
+
+impl SqliteEventStore {
+    /// Open the store in a database file, creating it if need be
+    pub fn open(path: impl AsRef<Path>) -> RuntimeResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let what = format!("could not open {}", path.display());
+        Self::start(what, move || CategoricalSQLite::open(path, DatabaseConfig::default()))
+    }
+
+    /// A store in a private in-memory database
+    pub fn in_memory() -> RuntimeResult<Self> {
+        Self::start("could not open an in-memory database".to_string(), || async {
+            Ok(CategoricalSQLite::new(DatabaseConfig::default()))
+        })
+    }
+
+    fn start<F>(what: String, open: impl FnOnce() -> F + Send + 'static) -> RuntimeResult<Self>
+    where
+        F: Future<Output = SqlResult<CategoricalSQLite>>,
+    {
+        let (jobs, queue) = mpsc::channel::<StoreJob>();
+        let (opened, outcome) = mpsc::channel();
+        thread::Builder::new()
+            .name("ream-event-store".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let _ = opened.send(Err(RuntimeError::Persistence(format!("{}: {}", what, e))));
+                        return;
+                    }
+                };
+                let db = match runtime.block_on(Self::create_tables(what, open)) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        let _ = opened.send(Err(e));
+                        return;
+                    }
+                };
+                let _ = opened.send(Ok(()));
+                // Runs until the store, and with it the sender, is dropped
+                for job in queue {
+                    runtime.block_on(job(db.clone()));
+                }
+            })
+            .map_err(|e| RuntimeError::Persistence(format!("could not start the event store's thread: {}", e)))?;
+        outcome.recv().map_err(|_| store_stopped())??;
+        Ok(Self { jobs })
+    }
+
+    async fn create_tables<F>(what: String, open: impl FnOnce() -> F) -> RuntimeResult<CategoricalSQLite>
+    where
+        F: Future<Output = SqlResult<CategoricalSQLite>>,
+    {
+        let db = open().await.map_err(|e| sql_error(&what, e))?;
+        for (table, sql) in SQLITE_SCHEMA {
+            if db.table_schema(table).await.is_none() {
+                db.execute_sql(sql).await.map_err(|e| sql_error("could not create the journal tables", e))?;
+            }
+        }
+        Ok(db)
+    }
+
+    /// Run `work` on the database thread and wait for its result
+    fn run<T, F>(&self, work: impl FnOnce(CategoricalSQLite) -> F + Send + 'static) -> RuntimeResult<T>
+    where
+        F: Future<Output = RuntimeResult<T>> + 'static,
+        T: Send + 'static,
+    {
+        let (reply, outcome) = mpsc::channel();
+        let job: StoreJob = Box::new(move |db| Box::pin(async move {
+            let _ = reply.send(work(db).await);
+        }));
+        self.jobs.send(job).map_err(|_| store_stopped())?;
+        outcome.recv().map_err(|_| store_stopped())?
+    }
+}
+
+/// The rows `sql` gives with `params`, run in `session`
+async fn query(session: &mut Session, sql: &str, params: &[SqlValue]) -> SqlResult<Vec<Row>> {
+    let statement = session.database().prepare(sql)?;
+    match session.execute(&statement, params).await? {
+        QueryResult::Select { rows, .. } => Ok(rows),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn malformed(what: &str) -> RuntimeError {
+    RuntimeError::Persistence(format!("{}: malformed row", what))
+}
+
+/// Integer column `index` of `row`, None for NULL
+fn integer_at(what: &str, row: &Row, index: usize) -> RuntimeResult<Option<i64>> {
+    match row.values.get(index) {
+        Some(SqlValue::Integer(value)) => Ok(Some(*value)),
+        Some(SqlValue::Null) => Ok(None),
+        _ => Err(malformed(what)),
+    }
+}
+
+fn text_at<'a>(what: &str, row: &'a Row, index: usize) -> RuntimeResult<&'a str> {
+    match row.values.get(index) {
+        Some(SqlValue::Text(text)) => Ok(text),
+        _ => Err(malformed(what)),
+    }
+}
+
+async fn highest_in(session: &mut Session, persistence_id: &str) -> RuntimeResult<u64> {
+    let what = "could not read the journal";
+    let rows = query(session, "SELECT MAX(sequence) FROM journal WHERE persistence_id = ?1", &[
+        SqlValue::Text(persistence_id.to_string()),
+    ])
+    .await
+    .map_err(|e| sql_error(what, e))?;
+    match rows.first() {
+        Some(row) => Ok(integer_at(what, row, 0)?.unwrap_or(0) as u64),
+        None => Ok(0),
+    }
+}
+
+/// Whether `sequence` comes right after the last event of the journal
+async fn follows_last(session: &mut Session, persistence_id: &str, sequence: u64) -> RuntimeResult<bool> {
+    let rows = query(
+        session,
+        "SELECT sequence FROM journal WHERE persistence_id = ?1 AND sequence >= ?2 AND sequence <= ?3",
+        &[
+            SqlValue::Text(persistence_id.to_string()),
+            SqlValue::Integer(sequence as i64 - 1),
+            SqlValue::Integer(sequence as i64),
+        ],
+    )
+    .await
+    .map_err(|e| sql_error("could not read the journal", e))?;
+    let expected = if sequence > 1 { 1 } else { 0 };
+    Ok(sequence > 0 && rows.len() == expected)
+}
+
+impl EventStore for SqliteEventStore {
+    fn append(&self, persistence_id: &str, sequence: u64, event: &Value) -> RuntimeResult<JournalEntry> {
+        let entry = JournalEntry {
+            persistence_id: persistence_id.to_string(),
+            sequence,
+            event: event.clone(),
+            recorded_at: Utc::now(),
+        };
+        self.run(move |db| async move {
+            // PRIMARY KEY (persistence_id, sequence) and the store's single
+            // thread keep appends in order. The check looks up two keys of that
+            // index rather than MAX(sequence), and the insert commits on its
+            // own, so an append does not cost the size of the journal.
+            let mut session = db.session();
+            if !follows_last(&mut session, &entry.persistence_id, sequence).await? {
+                let highest = highest_in(&mut session, &entry.persistence_id).await?;
+                return Err(out_of_sequence(&entry.persistence_id, highest, sequence));
+            }
+            query(
+                &mut session,
+                "INSERT INTO journal (persistence_id, sequence, event, recorded_at) VALUES (?1, ?2, ?3, ?4)",
+                &[
+                    SqlValue::Text(entry.persistence_id.clone()),
+                    SqlValue::Integer(sequence as i64),
+                    SqlValue::Text(entry.event.to_string()),
+                    SqlValue::Integer(entry.recorded_at.timestamp_millis()),
+                ],
+            )
+            .await
+            .map_err(|e| sql_error("could not append the event", e))?;
+            Ok(entry)
+        })
+    }
+
+    fn events(&self, persistence_id: &str, after: u64) -> RuntimeResult<Vec<JournalEntry>> {
+        let persistence_id = persistence_id.to_string();
+        self.run(move |db| async move {
+            let what = "could not read the journal";
+            let rows = query(
+                &mut db.session(),
+                "SELECT sequence, event, recorded_at FROM journal
+                 WHERE persistence_id = ?1 AND sequence > ?2 ORDER BY sequence",
+                &[SqlValue::Text(persistence_id.clone()), SqlValue::Integer(after as i64)],
+            )
+            .await
+            .map_err(|e| sql_error(what, e))?;
+
+            let mut entries = Vec::new();
+            for row in &rows {
+                let sequence = integer_at(what, row, 0)?.ok_or_else(|| malformed(what))?;
+                let event = text_at(what, row, 1)?;
+                let recorded_at = integer_at(what, row, 2)?.ok_or_else(|| malformed(what))?;
+                entries.push(JournalEntry {
+                    persistence_id: persistence_id.clone(),
+                    sequence: sequence as u64,
+                    event: parse_json(&format!("event {} of {}", sequence, persistence_id), event)?,
+                    recorded_at: from_millis(recorded_at),
+                });
+            }
+            Ok(entries)
+        })
+    }
+
+    fn highest_sequence(&self, persistence_id: &str) -> RuntimeResult<u64> {
+        let persistence_id = persistence_id.to_string();
+        self.run(move |db| async move { highest_in(&mut db.session(), &persistence_id).await })
+    }
+
+    fn save_snapshot(&self, snapshot: &Snapshot) -> RuntimeResult<()> {
+        let snapshot = snapshot.clone();
+        self.run(move |db| async move {
+            let what = "could not save the snapshot";
+            let mut session = db.session();
+            session.execute_sql("BEGIN").await.map_err(|e| sql_error(what, e))?;
+            let persistence_id = SqlValue::Text(snapshot.persistence_id.clone());
+            query(&mut session, "DELETE FROM snapshots WHERE persistence_id = ?1", std::slice::from_ref(&persistence_id))
+                .await
+                .map_err(|e| sql_error(what, e))?;
+            query(
+                &mut session,
+                "INSERT INTO snapshots (persistence_id, sequence, state, taken_at) VALUES (?1, ?2, ?3, ?4)",
+                &[
+                    persistence_id,
+                    SqlValue::Integer(snapshot.sequence as i64),
+                    SqlValue::Text(snapshot.state.to_string()),
+                    SqlValue::Integer(snapshot.taken_at.timestamp_millis()),
+                ],
+            )
+            .await
+            .map_err(|e| sql_error(what, e))?;
+            session.execute_sql("COMMIT").await.map_err(|e| sql_error(what, e))?;
+            Ok(())
+        })
+    }
+
+    fn latest_snapshot(&self, persistence_id: &str) -> RuntimeResult<Option<Snapshot>> {
+        let persistence_id = persistence_id.to_string();
+        self.run(move |db| async move {
+            let what = "could not read the snapshot";
+            let rows = query(
+                &mut db.session(),
+                "SELECT sequence, state, taken_at FROM snapshots WHERE persistence_id = ?1",
+                &[SqlValue::Text(persistence_id.clone())],
+            )
+            .await
+            .map_err(|e| sql_error(what, e))?;
+
+            rows.first().map(|row| Ok(Snapshot {
+                persistence_id: persistence_id.clone(),
+                sequence: integer_at(what, row, 0)?.ok_or_else(|| malformed(what))? as u64,
+                state: parse_json(&format!("snapshot of {}", persistence_id), text_at(what, row, 1)?)?,
+                taken_at: from_millis(integer_at(what, row, 2)?.ok_or_else(|| malformed(what))?),
+            }))
+            .transpose()
+        })
+    }
+
+    fn journals(&self) -> RuntimeResult<Vec<JournalInfo>> {
+        self.run(|db| async move {
+            let what = "could not list the journals";
+            let mut session = db.session();
+            let journals = query(&mut session, "SELECT persistence_id, MAX(sequence) FROM journal GROUP BY persistence_id", &[])
+                .await
+                .map_err(|e| sql_error(what, e))?;
+            let snapshots = query(&mut session, "SELECT persistence_id, sequence FROM snapshots", &[])
+                .await
+                .map_err(|e| sql_error(what, e))?;
+
+            let mut infos = BTreeMap::new();
+            for row in &journals {
+                let id = text_at(what, row, 0)?.to_string();
+                infos.insert(id.clone(), JournalInfo {
+                    persistence_id: id,
+                    highest_sequence: integer_at(what, row, 1)?.unwrap_or(0) as u64,
+                    snapshot_sequence: None,
+                });
+            }
+            for row in &snapshots {
+                let id = text_at(what, row, 0)?.to_string();
+                let sequence = integer_at(what, row, 1)?.ok_or_else(|| malformed(what))?;
+                infos.entry(id.clone())
+                    .or_insert_with(|| JournalInfo { persistence_id: id, highest_sequence: 0, snapshot_sequence: None })
+                    .snapshot_sequence = Some(sequence as u64);
+            }
+            Ok(infos.into_values().collect())
+        })
+    }
+}
+
+/// State of an event-sourced actor
+///
+/// The state is serialized into snapshots, and restored from them on
+/// recovery.
+pub trait EventSourced: Serialize + DeserializeOwned + Send + Sync {
+    type Event: Serialize + DeserializeOwned + Send + Sync;
+
+    /// The events handling `message` causes; an error rejects the message
+    /// without persisting anything
+    fn handle(&self, message: &MessagePayload) -> RuntimeResult<Vec<Self::Event>>;
+
+    /// Fold an event into the state
+    fn apply(&mut self, event: &Self::Event);
+}
+
+/// An actor's handle on its journal
+pub struct Journal {
+    store: Arc<dyn EventStore>,
+    persistence_id: String,
+    sequence: u64,
+    snapshot_every: Option<u64>,
+}
+
+impl Journal {
+    pub fn new(store: Arc<dyn EventStore>, persistence_id: impl Into<String>) -> Self {
+        Self {
+            store,
+            persistence_id: persistence_id.into(),
+            sequence: 0,
+            snapshot_every: None,
+        }
+    }
+
+    /// Snapshot the state after every `events` events; 0 never does
+    pub fn snapshot_every(mut self, events: u64) -> Self {
+        self.snapshot_every = (events > 0).then_some(events);
+        self
+    }
+
+    pub fn persistence_id(&self) -> &str {
+        &self.persistence_id
+    }
+
+    /// Number of the last event persisted or recovered
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Recover `state` from the latest snapshot and the events after it,
+    /// returning how many events were replayed
+    ///
+    /// Without a snapshot `state` should be the actor's initial state.
+    pub fn recover<S: EventSourced>(&mut self, state: &mut S) -> RuntimeResult<usize> {
+        self.sequence = 0;
+        if let Some(snapshot) = self.store.latest_snapshot(&self.persistence_id)? {
+            *state = serde_json::from_value(snapshot.state).map_err(|e| RuntimeError::Persistence(
+                format!("snapshot of {} does not fit the state: {}", self.persistence_id, e)))?;
+            self.sequence = snapshot.sequence;
+        }
+
+        let entries = self.store.events(&self.persistence_id, self.sequence)?;
+        for entry in &entries {
+            let event: S::Event = serde_json::from_value(entry.event.clone()).map_err(|e| RuntimeError::Persistence(
+                format!("event {} of {} is not an event of the actor: {}", entry.sequence, self.persistence_id, e)))?;
+            state.apply(&event);
+            self.sequence = entry.sequence;
+        }
+        Ok(entries.len())
+    }
+
+    /// Append `event` to the journal and apply it to `state`, returning its
+    /// number
+    pub fn persist<S: EventSourced>(&mut self, state: &mut S, event: S::Event) -> RuntimeResult<u64> {
+        let json = serde_json::to_value(&event).map_err(|e| RuntimeError::SerializationError(e.to_string()))?;
+        let entry = self.store.append(&self.persistence_id, self.sequence + 1, &json)?;
+        self.sequence = entry.sequence;
+        state.apply(&event);
+
+        if self.snapshot_every.is_some_and(|every| self.sequence.is_multiple_of(every)) {
+            self.snapshot(state)?;
+        }
+        Ok(self.sequence)
+    }
+
+    /// Save `state` as the snapshot at the current event
+    pub fn snapshot<S: EventSourced>(&self, state: &S) -> RuntimeResult<()> {
+        let state = serde_json::to_value(state).map_err(|e| RuntimeError::SerializationError(e.to_string()))?;
+        self.store.save_snapshot(&Snapshot {
+            persistence_id: self.persistence_id.clone(),
+            sequence: self.sequence,
+            state,
+            taken_at: Utc::now(),
+        })
+    }
+}
+
+/// Actor whose state is recovered from its journal
+///
+/// Restarting the actor starts over from its initial state and recovers
+/// again, so a restart loses nothing that was persisted.
+pub struct EventSourcedActor<S> {
+    pid: Pid,
+    initial: S,
+    state: S,
+    journal: Journal,
+}
+
+impl<S: EventSourced + Clone> EventSourcedActor<S> {
+    /// Start an actor at `initial` and recover what its journal holds
+    pub fn new(pid: Pid, initial: S, journal: Journal) -> RuntimeResult<Self> {
+        let mut actor = Self { pid, state: initial.clone(), initial, journal };
+        actor.journal.recover(&mut actor.state)?;
+        Ok(actor)
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+}
+
+impl<S: EventSourced + Clone + 'static> ReamActor for EventSourcedActor<S> {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        for event in self.state.handle(&message)? {
+            self.journal.persist(&mut self.state, event)?;
+        }
+        Ok(())
+    }
+
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        self.state = self.initial.clone();
+        self.journal.recover(&mut self.state)?;
+        Ok(())
+    }
+
+    fn debug_state(&self) -> Box<dyn std::any::Any + Send> {
+        Box::new(serde_json::to_value(&self.state).unwrap_or(Value::Null))
+    }
+
+    fn dictionary(&self) -> BTreeMap<String, String> {
+        let mut dictionary = BTreeMap::new();
+        dictionary.insert("persistence-id".to_string(), self.journal.persistence_id.clone());
+        dictionary.insert("sequence".to_string(), self.journal.sequence.to_string());
+        dictionary.insert("state".to_string(), serde_json::to_string(&self.state).unwrap_or_default());
+        dictionary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    struct Account {
+        balance: i64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    enum Movement {
+        Deposited(i64),
+        Withdrew(i64),
+    }
+
+    impl EventSourced for Account {
+        type Event = Movement;
+
+        fn handle(&self, message: &MessagePayload) -> RuntimeResult<Vec<Movement>> {
+            let amount: i64 = match message {
+                MessagePayload::Text(text) => text.parse().map_err(|_| RuntimeError::InvalidMessage(text.clone()))?,
+                other => return Err(RuntimeError::InvalidMessage(format!("{:?}", other))),
+            };
+            if self.balance + amount < 0 {
+                return Err(RuntimeError::ActorError("insufficient funds".to_string()));
+            }
+            Ok(vec![if amount < 0 { Movement::Withdrew(-amount) } else { Movement::Deposited(amount) }])
+        }
+
+        fn apply(&mut self, event: &Movement) {
+            match event {
+                Movement::Deposited(amount) => self.balance += amount,
+                Movement::Withdrew(amount) => self.balance -= amount,
+            }
+        }
+    }
+
+    #[test]
+    fn test_recovery_replays_from_the_latest_snapshot() {
+        let store: Arc<dyn EventStore> = Arc::new(MemoryEventStore::new());
+        let mut journal = Journal::new(store.clone(), "account-1").snapshot_every(3);
+        let mut account = Account::default();
+        for amount in [10, 20, 30, 40] {
+            journal.persist(&mut account, Movement::Deposited(amount)).unwrap();
+        }
+        assert_eq!(account.balance, 100);
+        assert_eq!(store.latest_snapshot("account-1").unwrap().unwrap().sequence, 3);
+
+        let mut recovered = Account::default();
+        let mut journal = Journal::new(store.clone(), "account-1");
+        assert_eq!(journal.recover(&mut recovered).unwrap(), 1);
+        assert_eq!(recovered, account);
+        assert_eq!(journal.sequence(), 4);
+
+        let stale = store.append("account-1", 4, &serde_json::json!({"Deposited": 1}));
+        assert!(matches!(stale, Err(RuntimeError::Persistence(_))));
+    }
+
+    #[test]
+    fn test_actor_recovers_its_state_on_restart() {
+        let store: Arc<dyn EventStore> = Arc::new(SqliteEventStore::in_memory().unwrap());
+        let journal = Journal::new(store.clone(), "account-2");
+        let mut actor = EventSourcedActor::new(Pid::new(), Account::default(), journal).unwrap();
+
+        actor.receive(MessagePayload::Text("50".to_string())).unwrap();
+        actor.receive(MessagePayload::Text("-20".to_string())).unwrap();
+        assert!(actor.receive(MessagePayload::Text("-100".to_string())).is_err());
+        assert_eq!(actor.state().balance, 30);
+
+        actor.restart().unwrap();
+        assert_eq!(actor.state().balance, 30);
+        assert_eq!(actor.journal().sequence(), 2);
+
+        let again = EventSourcedActor::new(Pid::new(), Account::default(), Journal::new(store.clone(), "account-2")).unwrap();
+        assert_eq!(again.state().balance, 30);
+    }
+
+    #[test]
+    fn test_sqlite_store_keeps_journals_in_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.db");
+        {
+            let store: Arc<dyn EventStore> = Arc::new(SqliteEventStore::open(&path).unwrap());
+            let mut journal = Journal::new(store, "account-3").snapshot_every(2);
+            let mut account = Account::default();
+            for amount in [5, 6, 7] {
+                journal.persist(&mut account, Movement::Deposited(amount)).unwrap();
+            }
+        }
+
+        let store = SqliteEventStore::open(&path).unwrap();
+        let events = store.events("account-3", 1).unwrap();
+        assert_eq!(events.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(events[1].event, serde_json::json!({"Deposited": 7}));
+        assert_eq!(store.journals().unwrap(), vec![JournalInfo {
+            persistence_id: "account-3".to_string(),
+            highest_sequence: 3,
+            snapshot_sequence: Some(2),
+        }]);
+    }
+    #[test]
+    fn test_sqlite_store_appends_a_long_journal_in_order() {
+        let store = SqliteEventStore::in_memory().unwrap();
+        for sequence in 1..=2000 {
+            store.append("account-4", sequence, &serde_json::json!({"Deposited": sequence})).unwrap();
+        }
+        assert!(store.append("account-4", 2000, &serde_json::json!({"Deposited": 1})).is_err());
+        assert!(store.append("account-4", 2002, &serde_json::json!({"Deposited": 1})).is_err());
+        store.append("other", 1, &serde_json::json!({"Deposited": 1})).unwrap();
+
+        assert_eq!(store.highest_sequence("account-4").unwrap(), 2000);
+        let events = store.events("account-4", 1990).unwrap();
+        assert_eq!(events.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), (1991..=2000).collect::<Vec<_>>());
+    }
+}
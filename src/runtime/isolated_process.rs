@@ -12,6 +12,7 @@ use std::ptr;
 use crate::types::{Pid, ExecutionBounds, MemoryLayout};
 use crate::error::{FaultError, FaultResult};
 use crate::runtime::actor::ReamActor;
+use crate::runtime::message::receive_traced;
 use crate::runtime::preemption::PreemptionTimer;
 
 /// Atomic counters for tracking resource usage
@@ -483,7 +484,8 @@ impl IsolatedProcess {
         // Try to receive a message
         if let Some(message) = self.mailbox.receive() {
             // Process the message
-            match self.actor.receive(message) {
+            let actor = &mut self.actor;
+            match receive_traced(self.pid, message, |message| actor.receive(message)) {
                 Ok(_) => Ok(Some(())),
                 Err(e) => {
                     // Handle actor error as a fault
@@ -586,7 +588,8 @@ impl IsolatedProcess {
                 // Simulate message processing work
                 if instruction_count >= 1000 {
                     // Process the actual message
-                    match self.actor.receive(message) {
+                    let actor = &mut self.actor;
+                    match receive_traced(self.pid, message, |message| actor.receive(message)) {
                         Ok(_) => return Ok(Some(())),
                         Err(e) => {
                             let fault = ProcessFault::Panic(format!("{:?}", e));
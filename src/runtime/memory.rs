@@ -4,8 +4,13 @@ use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 use bumpalo::Bump;
+use serde::{Deserialize, Serialize};
 use crate::types::Pid;
 use crate::error::{RuntimeError, RuntimeResult};
+use super::topology;
+
+/// Freed arenas kept per NUMA node for reuse
+const MAX_POOLED_ARENAS: usize = 64;
 
 /// Memory region identifier
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -24,16 +29,22 @@ pub struct MemoryRegion {
     bump: Bump,
     allocated: AtomicUsize,
     owner: Option<Pid>,
+    node: Option<usize>,
     created_at: Instant,
 }
 
 impl MemoryRegion {
     fn new(owner: Option<Pid>) -> Self {
+        Self::with_arena(owner, Bump::new(), None)
+    }
+
+    fn with_arena(owner: Option<Pid>, bump: Bump, node: Option<usize>) -> Self {
         MemoryRegion {
             id: RegionId::new(),
-            bump: Bump::new(),
+            bump,
             allocated: AtomicUsize::new(0),
             owner,
+            node,
             created_at: Instant::now(),
         }
     }
@@ -70,6 +81,11 @@ impl MemoryRegion {
         self.owner
     }
     
+    /// NUMA node whose arena backs this region, when heaps are NUMA-local
+    pub fn node(&self) -> Option<usize> {
+        self.node
+    }
+
     /// Reset the region (deallocate all)
     pub fn reset(&mut self) {
         self.bump.reset();
@@ -84,6 +100,22 @@ pub struct GcStats {
     pub total_time: std::time::Duration,
     pub bytes_collected: usize,
     pub regions_collected: usize,
+    /// Shared binaries found unreferenced
+    pub binaries_collected: usize,
+    /// Bytes those shared binaries referred to
+    pub binary_bytes_collected: usize,
+}
+
+/// Per-process heaps drawn from one NUMA node's arenas
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArenaStats {
+    pub node: usize,
+    /// Live regions backed by this node's arenas
+    pub regions: usize,
+    /// Bytes allocated in those regions
+    pub allocated_bytes: usize,
+    /// Freed arenas waiting to back a new region
+    pub pooled_arenas: usize,
 }
 
 /// Memory manager with generational garbage collection
@@ -111,6 +143,14 @@ pub struct MemoryManager {
     
     /// Generation promotion threshold
     promotion_threshold: std::time::Duration,
+
+    /// Back process regions with arenas local to the allocating thread's node
+    numa_local: bool,
+
+    /// Freed arenas by NUMA node. The kernel places a page on the node of
+    /// the thread that first touches it, and a reset arena keeps its pages,
+    /// so reusing one for a process on the same node keeps its heap local.
+    arenas: HashMap<usize, Vec<Bump>>,
 }
 
 impl MemoryManager {
@@ -125,12 +165,25 @@ impl MemoryManager {
             total_allocated: AtomicUsize::new(0),
             gc_threshold: 64 * 1024 * 1024, // 64MB
             promotion_threshold: std::time::Duration::from_secs(60), // 1 minute
+            numa_local: false,
+            arenas: HashMap::new(),
         }
     }
+
+    /// Back new process regions with arenas local to the NUMA node of the
+    /// thread allocating them
+    pub fn set_numa_local(&mut self, numa_local: bool) {
+        self.numa_local = numa_local;
+    }
     
     /// Allocate a new region for a process
     pub fn allocate_region(&mut self, owner: Pid) -> RegionId {
-        let region = MemoryRegion::new(Some(owner));
+        let node = if self.numa_local { topology::current_node() } else { None };
+        let bump = node
+            .and_then(|node| self.arenas.get_mut(&node))
+            .and_then(Vec::pop)
+            .unwrap_or_default();
+        let region = MemoryRegion::with_arena(Some(owner), bump, node);
         let id = region.id();
         
         self.young_regions.insert(id, region);
@@ -169,16 +222,52 @@ impl MemoryManager {
         self.process_regions.get(&pid).cloned().unwrap_or_default()
     }
     
+    /// Bytes allocated in the regions owned by a process
+    pub fn process_memory(&self, pid: Pid) -> usize {
+        self.process_regions.get(&pid)
+            .map(|ids| ids.iter().filter_map(|id| self.get_region(*id)).map(|r| r.allocated_bytes()).sum())
+            .unwrap_or(0)
+    }
+    
     /// Deallocate all regions owned by a process
     pub fn deallocate_process_regions(&mut self, pid: Pid) -> RuntimeResult<()> {
         if let Some(region_ids) = self.process_regions.remove(&pid) {
             for id in region_ids {
-                self.young_regions.remove(&id);
-                self.old_regions.remove(&id);
+                if let Some(region) = self.young_regions.remove(&id).or_else(|| self.old_regions.remove(&id)) {
+                    self.release(region);
+                }
             }
         }
         Ok(())
     }
+
+    /// Return the arena behind a freed region to its node's pool
+    fn release(&mut self, region: MemoryRegion) {
+        let Some(node) = region.node else { return };
+        let pool = self.arenas.entry(node).or_default();
+        if pool.len() < MAX_POOLED_ARENAS {
+            let mut bump = region.bump;
+            bump.reset();
+            pool.push(bump);
+        }
+    }
+
+    /// Process heaps by NUMA node, for nodes that have backed any
+    pub fn arena_stats(&self) -> Vec<ArenaStats> {
+        let mut stats: HashMap<usize, ArenaStats> = self.arenas.iter()
+            .map(|(&node, pool)| (node, ArenaStats { node, pooled_arenas: pool.len(), ..Default::default() }))
+            .collect();
+        for region in self.young_regions.values().chain(self.old_regions.values()) {
+            if let Some(node) = region.node {
+                let entry = stats.entry(node).or_insert_with(|| ArenaStats { node, ..Default::default() });
+                entry.regions += 1;
+                entry.allocated_bytes += region.allocated_bytes();
+            }
+        }
+        let mut stats: Vec<_> = stats.into_values().collect();
+        stats.sort_by_key(|entry| entry.node);
+        stats
+    }
     
     /// Run garbage collection
     pub fn collect(&mut self) -> GcStats {
@@ -219,9 +308,13 @@ impl MemoryManager {
                         regions.retain(|&r| r != id);
                     }
                 }
+                self.release(region);
             }
         }
         
+        // Forget shared binaries no process refers to any more
+        let (binaries_collected, binary_bytes_collected) = super::binary::sweep();
+
         let collection_time = start.elapsed();
         
         // Update statistics
@@ -229,6 +322,8 @@ impl MemoryManager {
         self.gc_stats.total_time += collection_time;
         self.gc_stats.bytes_collected += bytes_collected;
         self.gc_stats.regions_collected += regions_collected;
+        self.gc_stats.binaries_collected += binaries_collected;
+        self.gc_stats.binary_bytes_collected += binary_bytes_collected;
         
         self.gc_stats.clone()
     }
@@ -365,6 +460,11 @@ mod tests {
         let regions = manager.process_regions(pid);
         assert_eq!(regions.len(), 1);
         assert_eq!(regions[0], region_id);
+
+        assert_eq!(manager.process_memory(pid), 0);
+        manager.get_region(region_id).unwrap().alloc([0u8; 64]);
+        assert!(manager.process_memory(pid) >= 64);
+        assert_eq!(manager.process_memory(Pid::new()), 0);
     }
     
     #[test]
@@ -384,4 +484,33 @@ mod tests {
         // Some regions might be promoted or collected
         assert!(manager.young_regions.len() <= initial_count);
     }
+
+    #[test]
+    fn test_numa_local_arenas() {
+        let mut manager = MemoryManager::new();
+        let pid = Pid::new();
+        let id = manager.allocate_region(pid);
+        assert_eq!(manager.get_region(id).unwrap().node(), None);
+        assert!(manager.arena_stats().is_empty());
+
+        // Stay on one node so every region is drawn from the same one
+        let _ = topology::pin_current_thread(topology::CpuTopology::system().cpus()[0]);
+        manager.set_numa_local(true);
+        let Some(node) = topology::current_node() else { return };
+        let id = manager.allocate_region(pid);
+        manager.get_region(id).unwrap().alloc([0u8; 256]);
+        assert_eq!(manager.get_region(id).unwrap().node(), Some(node));
+        let stats = manager.arena_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].node, stats[0].regions, stats[0].pooled_arenas), (node, 1, 0));
+        assert!(stats[0].allocated_bytes >= 256);
+
+        // Freed arenas go back to their node's pool and back the next region
+        manager.deallocate_process_regions(pid).unwrap();
+        assert_eq!(manager.arena_stats()[0].pooled_arenas, 1);
+        let id = manager.allocate_region(Pid::new());
+        assert_eq!(manager.get_region(id).unwrap().allocated_bytes(), 0);
+        let stats = manager.arena_stats();
+        assert_eq!((stats[0].regions, stats[0].pooled_arenas), (1, 0));
+    }
 }
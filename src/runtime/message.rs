@@ -1,12 +1,22 @@
 //! Message passing system with monoidal composition
+//!
+//! The router can limit the messages an actor accepts: a token bucket per
+//! actor bounds its overall ingress and one per sender stops a single
+//! sender from using it all. Messages over the limit are shed, queued
+//! until the bucket refills, or refused so the sender is notified.
 
-use std::collections::VecDeque;
-use std::sync::{Arc, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use dashmap::DashMap;
+use opentelemetry::KeyValue;
+use opentelemetry::trace::SpanKind;
+use serde::{Deserialize, Serialize};
 use crate::types::{Pid, Message, MessagePayload};
 use crate::error::{RuntimeError, RuntimeResult};
+use crate::telemetry;
+use crate::debug::flow;
 
 /// Type alias for actor messages (for macro compatibility)
 pub type ActorMessage = MessagePayload;
@@ -17,10 +27,188 @@ pub trait MessageTrait {
     fn deserialize(data: &[u8]) -> Result<Self, RuntimeError> where Self: Sized;
 }
 
+/// Hand a message to an actor inside an `actor.receive` span continuing the
+/// sender's trace, so anything the actor sends while handling it joins that
+/// trace and is recorded as a flow from the actor
+pub fn receive_traced<R>(pid: Pid, message: MessagePayload, receive: impl FnOnce(MessagePayload) -> R) -> R {
+    let (parent, message) = message.untrace();
+    let cx = telemetry::start_span(
+        "actor.receive",
+        SpanKind::Consumer,
+        parent.as_ref(),
+        vec![KeyValue::new("ream.pid", pid.to_string())],
+    );
+    let _guard = cx.attach();
+    let _receiving = flow::receiving(pid);
+    receive(message)
+}
+
+/// Most messages held for a rate-limited actor under `OverflowPolicy::Queue`;
+/// later ones are shed
+pub const MAX_QUEUED_MESSAGES: usize = 10_000;
+
+/// Senders an actor tracks buckets for before forgetting idle ones
+const MAX_TRACKED_SENDERS: usize = 1024;
+
+/// How often queued messages are checked against the refilled buckets
+const RELEASE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// What happens to messages over an actor's rate limit
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum OverflowPolicy {
+    /// Drop them
+    #[default]
+    Shed,
+    /// Hold them until the actor's budget allows them through
+    Queue,
+    /// Refuse them, so the sender gets an error
+    Notify,
+}
+
+impl std::fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OverflowPolicy::Shed => "shed",
+            OverflowPolicy::Queue => "queue",
+            OverflowPolicy::Notify => "notify",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Messages per second an actor accepts; `None` is unlimited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    /// Messages per second from all senders together
+    pub max_rate: Option<u32>,
+    /// Messages per second from any one sender
+    pub max_rate_per_sender: Option<u32>,
+    /// Messages accepted at once after a quiet period; defaults to a
+    /// second's worth
+    pub burst: Option<u32>,
+    /// What happens to messages over the limit
+    pub policy: OverflowPolicy,
+}
+
+/// What an actor's rate limit did with its messages
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IngressStats {
+    /// Messages let through
+    pub admitted: u64,
+    /// Messages dropped
+    pub shed: u64,
+    /// Messages refused with an error to the sender
+    pub rejected: u64,
+    /// Messages waiting for the budget to refill
+    pub queued: usize,
+}
+
+/// An actor's rate limit and what it did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IngressStatus {
+    /// The limit
+    pub limit: RateLimit,
+    /// Its effect so far
+    pub stats: IngressStats,
+}
+
+/// Token bucket refilled at a steady rate up to its capacity
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket refilled at `rate` tokens per second, holding `capacity`
+    pub(crate) fn new(rate: u32, capacity: u32) -> Self {
+        let capacity = capacity.max(1) as f64;
+        TokenBucket { rate: rate as f64, capacity, tokens: capacity, refilled: Instant::now() }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.refilled = now;
+    }
+
+    /// Whether a token is available
+    pub(crate) fn ready(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    /// Take a token, if one is available
+    pub(crate) fn take(&mut self, now: Instant) -> bool {
+        let ready = self.ready(now);
+        if ready {
+            self.tokens -= 1.0;
+        }
+        ready
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+}
+
+/// An actor's rate limit with its buckets and queued messages
+struct IngressLimiter {
+    limit: RateLimit,
+    bucket: Option<TokenBucket>,
+    senders: HashMap<Pid, TokenBucket>,
+    queue: VecDeque<(Option<Pid>, Message)>,
+    stats: IngressStats,
+}
+
+impl IngressLimiter {
+    fn new(limit: RateLimit) -> Self {
+        let bucket = limit.max_rate.map(|rate| TokenBucket::new(rate, limit.burst.unwrap_or(rate)));
+        IngressLimiter { limit, bucket, senders: HashMap::new(), queue: VecDeque::new(), stats: IngressStats::default() }
+    }
+
+    /// Take a message from `from` out of the budget, if both the actor's
+    /// and the sender's bucket allow it
+    fn admit(&mut self, from: Option<Pid>, now: Instant) -> bool {
+        let mut sender = match (from, self.limit.max_rate_per_sender) {
+            (Some(from), Some(rate)) => {
+                if self.senders.len() >= MAX_TRACKED_SENDERS {
+                    // A full bucket is no different from a new one
+                    self.senders.retain(|_, bucket| !bucket.is_full(now));
+                }
+                let burst = self.limit.burst.unwrap_or(rate);
+                Some(self.senders.entry(from).or_insert_with(|| TokenBucket::new(rate, burst)))
+            }
+            _ => None,
+        };
+
+        let sender_ready = sender.as_deref_mut().is_none_or(|bucket| bucket.ready(now));
+        let actor_ready = self.bucket.as_mut().is_none_or(|bucket| bucket.ready(now));
+        if !(sender_ready && actor_ready) {
+            return false;
+        }
+        if let Some(bucket) = sender {
+            bucket.take(now);
+        }
+        if let Some(bucket) = self.bucket.as_mut() {
+            bucket.take(now);
+        }
+        self.stats.admitted += 1;
+        true
+    }
+}
+
 /// Message router for inter-process communication
 pub struct MessageRouter {
     /// Process mailboxes
     mailboxes: Arc<DashMap<Pid, Arc<RwLock<Mailbox>>>>,
+
+    /// Rate limits of actors that have one
+    limits: Arc<DashMap<Pid, Mutex<IngressLimiter>>>,
     
     /// Message delivery channel
     delivery_tx: Sender<Message>,
@@ -48,6 +236,7 @@ impl MessageRouter {
         
         MessageRouter {
             mailboxes: Arc::new(DashMap::new()),
+            limits: Arc::new(DashMap::new()),
             delivery_tx,
             delivery_rx,
             stats: Arc::new(RwLock::new(RouterStats::default())),
@@ -60,12 +249,20 @@ impl MessageRouter {
         self.running.store(true, std::sync::atomic::Ordering::SeqCst);
         
         let delivery_rx = self.delivery_rx.clone();
+        let delivery_tx = self.delivery_tx.clone();
         let mailboxes = Arc::clone(&self.mailboxes);
+        let limits = Arc::clone(&self.limits);
         let stats = Arc::clone(&self.stats);
         let running = Arc::clone(&self.running);
         
         std::thread::spawn(move || {
+            let mut released = Instant::now();
             while running.load(std::sync::atomic::Ordering::SeqCst) {
+                if released.elapsed() >= RELEASE_INTERVAL {
+                    released = Instant::now();
+                    release_queued(&limits, &delivery_tx, released);
+                }
+
                 match delivery_rx.try_recv() {
                     Ok(message) => {
                         let start = std::time::Instant::now();
@@ -107,32 +304,103 @@ impl MessageRouter {
         mailbox
     }
     
+    /// Deliver a process's messages to a mailbox it already has
+    pub fn register_mailbox(&self, pid: Pid, mailbox: Arc<RwLock<Mailbox>>) {
+        self.mailboxes.insert(pid, mailbox);
+    }
+    
     /// Unregister a process mailbox
     pub fn unregister_process(&self, pid: Pid) {
         self.mailboxes.remove(&pid);
+        self.limits.remove(&pid);
+    }
+
+    /// Limit the messages a process accepts; `None` lifts the limit and
+    /// delivers any messages it held
+    pub fn set_rate_limit(&self, pid: Pid, limit: Option<RateLimit>) {
+        let previous = match limit {
+            Some(limit) => {
+                let mut limiter = IngressLimiter::new(limit);
+                let previous = self.limits.remove(&pid).map(|(_, previous)| previous.into_inner().unwrap());
+                if let Some(previous) = &previous {
+                    limiter.stats = previous.stats.clone();
+                }
+                self.limits.insert(pid, Mutex::new(limiter));
+                previous
+            }
+            None => self.limits.remove(&pid).map(|(_, previous)| previous.into_inner().unwrap()),
+        };
+
+        // Messages held under the old limit go through the new one
+        for (from, message) in previous.into_iter().flat_map(|previous| previous.queue) {
+            let _ = self.route(from, message);
+        }
+    }
+
+    /// A process's rate limit and what it did
+    pub fn ingress_status(&self, pid: Pid) -> Option<IngressStatus> {
+        self.limits.get(&pid).map(|limiter| {
+            let limiter = limiter.lock().unwrap();
+            let mut stats = limiter.stats.clone();
+            stats.queued = limiter.queue.len();
+            IngressStatus { limit: limiter.limit.clone(), stats }
+        })
     }
     
     /// Send a message to a process
     pub fn send_message(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+        self.send_message_from(None, to, payload)
+    }
+
+    /// Send a message to a process from `from`, which per-sender rate
+    /// limits are applied to
+    pub fn send_message_from(&self, from: Option<Pid>, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
         let message = Message {
-            from: Pid::new(), // TODO: Get actual sender PID from context
+            from: from.unwrap_or_else(Pid::new),
             to,
-            payload,
+            payload: payload.traced(),
             timestamp: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_millis() as u64,
         };
         
-        self.delivery_tx.send(message).map_err(|_| {
-            RuntimeError::InvalidMessage("Failed to queue message".to_string())
-        })?;
+        flow::record(from, to, &message.payload);
+        self.route(from, message)?;
         
         let mut stats = self.stats.write().unwrap();
         stats.messages_sent += 1;
         
         Ok(())
     }
+
+    /// Queue a message for delivery, within the recipient's rate limit
+    fn route(&self, from: Option<Pid>, message: Message) -> RuntimeResult<()> {
+        if let Some(limiter) = self.limits.get(&message.to) {
+            let mut limiter = limiter.lock().unwrap();
+            // Queued messages go first, so later ones wait behind them
+            if !limiter.queue.is_empty() || !limiter.admit(from, Instant::now()) {
+                match limiter.limit.policy {
+                    OverflowPolicy::Queue if limiter.queue.len() < MAX_QUEUED_MESSAGES => {
+                        limiter.queue.push_back((from, message));
+                    }
+                    OverflowPolicy::Queue | OverflowPolicy::Shed => {
+                        limiter.stats.shed += 1;
+                        self.stats.write().unwrap().messages_dropped += 1;
+                    }
+                    OverflowPolicy::Notify => {
+                        limiter.stats.rejected += 1;
+                        return Err(RuntimeError::QuotaExceeded(format!("actor {} is over its message rate limit", message.to)));
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        self.delivery_tx.send(message).map_err(|_| {
+            RuntimeError::InvalidMessage("Failed to queue message".to_string())
+        })
+    }
     
     /// Get router statistics
     pub fn stats(&self) -> RouterStats {
@@ -145,6 +413,21 @@ impl MessageRouter {
     }
 }
 
+/// Deliver the queued messages the buckets of their recipients now allow
+fn release_queued(limits: &DashMap<Pid, Mutex<IngressLimiter>>, delivery_tx: &Sender<Message>, now: Instant) {
+    for entry in limits.iter() {
+        let mut limiter = entry.lock().unwrap();
+        while let Some(&(from, _)) = limiter.queue.front() {
+            if !limiter.admit(from, now) {
+                break;
+            }
+            if let Some((_, message)) = limiter.queue.pop_front() {
+                let _ = delivery_tx.send(message);
+            }
+        }
+    }
+}
+
 /// Process mailbox with coalgebraic message observation
 #[derive(Debug)]
 pub struct Mailbox {
@@ -156,8 +439,14 @@ pub struct Mailbox {
     
     /// Mailbox statistics
     stats: MailboxStats,
+
+    /// Most recently received messages, oldest first
+    recent: VecDeque<MessagePayload>,
 }
 
+/// Number of received messages a mailbox remembers for inspection
+pub const RECENT_MESSAGES: usize = 8;
+
 #[derive(Debug, Default, Clone)]
 struct MailboxStats {
     messages_received: u64,
@@ -178,6 +467,7 @@ impl Mailbox {
             messages: VecDeque::new(),
             max_size,
             stats: MailboxStats::default(),
+            recent: VecDeque::new(),
         }
     }
     
@@ -199,19 +489,54 @@ impl Mailbox {
     
     /// Receive a message from this mailbox
     pub fn receive(&mut self) -> Option<MessagePayload> {
-        if let Some(message) = self.messages.pop_front() {
+        let message = self.messages.pop_front()?;
+        self.record_received([message.clone()]);
+        Some(message)
+    }
+    
+    /// Take up to `max` messages under one borrow of the mailbox
+    ///
+    /// They only count as received once passed to `record_received`, so
+    /// messages put back with `requeue` never do.
+    pub fn receive_batch(&mut self, max: usize) -> Vec<MessagePayload> {
+        let count = max.min(self.messages.len());
+        self.messages.drain(..count).collect()
+    }
+
+    /// Count messages taken with `receive_batch` as received
+    pub fn record_received(&mut self, messages: impl IntoIterator<Item = MessagePayload>) {
+        for message in messages {
             self.stats.messages_processed += 1;
-            Some(message)
-        } else {
-            None
+            if self.recent.len() == RECENT_MESSAGES {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(message);
         }
     }
-    
+
+    /// Put back messages from `receive_batch` that were not handled, in
+    /// order and ahead of any that arrived since
+    pub fn requeue(&mut self, messages: impl DoubleEndedIterator<Item = MessagePayload>) {
+        for message in messages.rev() {
+            self.messages.push_front(message);
+        }
+    }
+
     /// Peek at the next message without removing it
     pub fn peek(&self) -> Option<&MessagePayload> {
         self.messages.front()
     }
     
+    /// Messages received most recently, oldest first
+    pub fn recent(&self) -> impl Iterator<Item = &MessagePayload> {
+        self.recent.iter()
+    }
+
+    /// Number of messages received from this mailbox
+    pub fn messages_processed(&self) -> u64 {
+        self.stats.messages_processed
+    }
+
     /// Get number of messages in queue
     pub fn len(&self) -> usize {
         self.messages.len()
@@ -361,8 +686,60 @@ mod tests {
         let message = mailbox.receive();
         assert!(message.is_some());
         assert!(mailbox.is_empty());
+
+        for i in 0..RECENT_MESSAGES + 2 {
+            mailbox.send(MessagePayload::Text(i.to_string()));
+            mailbox.receive();
+        }
+        let recent: Vec<_> = mailbox.recent().collect();
+        assert_eq!(recent.len(), RECENT_MESSAGES);
+        assert!(matches!(recent[0], MessagePayload::Text(t) if t == "2"));
     }
     
+    #[test]
+    fn test_requeued_messages_are_not_received() {
+        let mut mailbox = Mailbox::new();
+        for i in 0..4 {
+            mailbox.send(MessagePayload::Text(i.to_string()));
+        }
+
+        // A receive between taking a batch and putting part of it back
+        // keeps its count and history
+        let mut batch = mailbox.receive_batch(3).into_iter();
+        let first = batch.next().unwrap();
+        assert!(matches!(mailbox.receive(), Some(MessagePayload::Text(t)) if t == "3"));
+        mailbox.record_received([first]);
+        mailbox.requeue(batch);
+
+        assert_eq!(mailbox.messages_processed(), 2);
+        assert_eq!(mailbox.recent().count(), 2);
+        assert_eq!(mailbox.len(), 2);
+        assert!(matches!(mailbox.peek(), Some(MessagePayload::Text(t)) if t == "1"));
+    }
+
+    #[test]
+    fn test_trace_context_follows_messages() {
+        use crate::telemetry::TraceContext;
+
+        // Untraced senders send plain payloads
+        assert!(matches!(MessagePayload::Text("hi".to_string()).traced(), MessagePayload::Text(_)));
+
+        let parent = TraceContext { trace_id: 42, span_id: 7, sampled: true };
+        let message = {
+            let _guard = parent.to_context().attach();
+            MessagePayload::Text("hi".to_string()).traced()
+        };
+        assert!(matches!(message, MessagePayload::Traced { context, .. } if context == parent));
+
+        let pid = Pid::new();
+        let (trace_id, text) = receive_traced(pid, message, |message| {
+            (TraceContext::current().map(|c| c.trace_id), message)
+        });
+        assert_eq!(trace_id, Some(42));
+        assert!(matches!(text, MessagePayload::Text(ref t) if t == "hi"));
+        assert_eq!(TraceContext::current(), None);
+    }
+
     #[test]
     fn test_message_compose() {
         let msg1 = MessagePayload::Text("hello".to_string());
@@ -402,4 +779,60 @@ mod tests {
         
         router.stop();
     }
+
+    #[test]
+    fn test_rate_limits_protect_actors() {
+        let router = MessageRouter::new();
+        let text = |text: &str| MessagePayload::Text(text.to_string());
+        let drain = |mailbox: &Arc<RwLock<Mailbox>>| {
+            let mut mailbox = mailbox.write().unwrap();
+            std::iter::from_fn(|| mailbox.receive()).collect::<Vec<_>>()
+        };
+
+        let (shed, notified, queued, fair) = (Pid::new(), Pid::new(), Pid::new(), Pid::new());
+        let shed_mailbox = router.register_process(shed);
+        router.register_process(notified);
+        let queued_mailbox = router.register_process(queued);
+        let fair_mailbox = router.register_process(fair);
+        let limit = |policy| RateLimit { max_rate: Some(1), burst: Some(2), policy, ..Default::default() };
+        router.set_rate_limit(shed, Some(limit(OverflowPolicy::Shed)));
+        router.set_rate_limit(notified, Some(limit(OverflowPolicy::Notify)));
+        router.set_rate_limit(queued, Some(RateLimit { max_rate: Some(100), burst: Some(1), policy: OverflowPolicy::Queue, ..Default::default() }));
+        router.set_rate_limit(fair, Some(RateLimit { max_rate_per_sender: Some(1), ..Default::default() }));
+        router.start().unwrap();
+
+        for i in 0..5 {
+            router.send_message(shed, text("flood")).unwrap();
+            router.send_message(queued, text(&i.to_string())).unwrap();
+        }
+        router.send_message(notified, text("one")).unwrap();
+        router.send_message(notified, text("two")).unwrap();
+        assert!(matches!(router.send_message(notified, text("three")), Err(RuntimeError::QuotaExceeded(_))));
+        let (chatty, quiet) = (Pid::new(), Pid::new());
+        for from in [chatty, chatty, quiet] {
+            router.send_message_from(Some(from), fair, text("hi")).unwrap();
+        }
+
+        // Queued messages trickle through in order as the bucket refills
+        let mut delivered = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while delivered.len() < 5 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            delivered.extend(drain(&queued_mailbox));
+        }
+        let delivered: Vec<_> = delivered.iter().map(MessagePayload::summary).collect();
+        assert_eq!(delivered, ["\"0\"", "\"1\"", "\"2\"", "\"3\"", "\"4\""]);
+
+        assert_eq!(drain(&shed_mailbox).len(), 2);
+        assert_eq!(drain(&fair_mailbox).len(), 2, "one sender cannot use up the budget of another");
+        let stats = |pid| router.ingress_status(pid).unwrap().stats;
+        assert_eq!(stats(shed), IngressStats { admitted: 2, shed: 3, rejected: 0, queued: 0 });
+        assert_eq!(stats(notified).rejected, 1);
+        assert_eq!(stats(queued), IngressStats { admitted: 5, shed: 0, rejected: 0, queued: 0 });
+        assert_eq!(stats(fair).shed, 1);
+
+        router.set_rate_limit(shed, None);
+        assert!(router.ingress_status(shed).is_none());
+        router.stop();
+    }
 }
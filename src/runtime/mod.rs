@@ -6,9 +6,17 @@
 pub mod actor;
 pub mod scheduler;
 pub mod memory;
+pub mod binary;
 pub mod message;
 pub mod supervisor;
 pub mod process;
+pub mod crash;
+pub mod connectors;
+pub mod cron;
+pub mod schema;
+pub mod event_sourcing;
+pub mod workflow;
+pub mod domain;
 pub mod isolated_process;
 pub mod stm_mailbox;
 pub mod bounded_execution;
@@ -18,6 +26,7 @@ pub mod serverless_runtime;
 pub mod preemption;
 pub mod executor;
 pub mod work_stealing;
+pub mod topology;
 pub mod realtime;
 pub mod resource_manager;
 
@@ -31,17 +40,25 @@ use dashmap::DashMap;
 
 use crate::types::{Pid, Priority, ProcessInfo, RuntimeStats, ReamConfig, MessagePayload};
 use crate::error::{RuntimeError, RuntimeResult};
+use crate::security::audit::{self, AuditKind};
 use crate::daemon::monitor::ActorMonitor;
 
 pub use actor::{Actor, ReamActor, ActorContext};
 pub use scheduler::{Scheduler, SchedulingOp};
 pub use memory::{GarbageCollector, MemoryManager};
-pub use message::{MessageRouter, Mailbox};
-pub use supervisor::{Supervisor, ProcessTree};
-pub use process::{Process, ProcessHandle};
+pub use message::{MessageRouter, Mailbox, IngressStats, IngressStatus, OverflowPolicy, RateLimit};
+pub use supervisor::{Supervisor, ProcessTree, SupervisionNode};
+pub use process::{Process, ProcessHandle, QuantumBudget};
+pub use domain::{DomainConfig, DomainHost, DomainInfo, DomainQuotas, DomainState};
+pub use connectors::{Broker, Bus, ConnectorState, ConnectorStatus, ConsumerSpec, ProducerSpec};
+pub use cron::{CronExpr, CronJob, CronScheduler, JobAction, MissedRuns};
+pub use schema::{SchemaInfo, SchemaRegistry, TypedMessage};
+pub use event_sourcing::{EventSourced, EventSourcedActor, EventStore, Journal, JournalEntry, MemoryEventStore, Snapshot, SqliteEventStore};
+pub use workflow::{Saga, Step, WorkflowEngine, WorkflowInfo, WorkflowStatus};
 pub use preemption::{PreemptionTimer, ExecutionResult, PreemptionStats};
 pub use executor::{ProcessExecutor, ExecutorStats};
 pub use work_stealing::{WorkStealingScheduler, ScheduledTask, WorkStealingStats};
+pub use topology::{CpuTopology, NumaNode, TopologyConfig};
 pub use realtime::{RealTimeScheduler, RealTimeTask, SchedulingAlgorithm, TaskType, RealTimeStats, ResourceId};
 pub use resource_manager::{ResourceManager, ProcessResourceUsage, ResourceQuotas, ResourceAccounting, LoadBalanceRecommendation, LoadBalanceReason, CoreLoad, LoadBalancingStrategy};
 
@@ -58,6 +75,13 @@ pub struct RuntimeConfig {
     pub distributed: bool,
 }
 
+/// How a process is spawned
+#[derive(Debug, Clone, Default)]
+pub struct SpawnOptions {
+    /// Limit on the messages the process accepts
+    pub rate_limit: Option<RateLimit>,
+}
+
 impl Default for RuntimeConfig {
     fn default() -> Self {
         Self {
@@ -104,6 +128,9 @@ pub struct ReamRuntime {
 
     /// Hypervisor monitor for actor monitoring
     hypervisor: Option<Arc<ActorMonitor>>,
+
+    /// Isolation domains, by name
+    domains: Arc<DashMap<String, domain::IsolationDomain>>,
 }
 
 impl ReamRuntime {
@@ -115,12 +142,15 @@ impl ReamRuntime {
     /// Create a new REAM runtime with custom configuration
     pub fn with_config(config: ReamConfig) -> Self {
         let (shutdown_tx, shutdown_rx) = unbounded();
+        let mut memory = MemoryManager::new();
+        memory.set_gc_threshold(config.gc_threshold);
+        memory.set_numa_local(config.topology.numa_local_heaps);
         
         let runtime = ReamRuntime {
             config,
             processes: Arc::new(DashMap::new()),
             scheduler: Arc::new(Mutex::new(Scheduler::new())),
-            memory: Arc::new(Mutex::new(MemoryManager::new())),
+            memory: Arc::new(Mutex::new(memory)),
             message_router: Arc::new(MessageRouter::new()),
             root_supervisor: Arc::new(Mutex::new(Supervisor::new(
                 crate::types::RestartStrategy::OneForOne
@@ -138,6 +168,7 @@ impl ReamRuntime {
             start_time: Instant::now(),
             running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             hypervisor: None,
+            domains: Arc::new(DashMap::new()),
         };
         
         runtime
@@ -154,6 +185,11 @@ impl ReamRuntime {
     pub fn start(&self) -> RuntimeResult<()> {
         self.running.store(true, std::sync::atomic::Ordering::SeqCst);
 
+        // Export spans if a collector is configured
+        if let Err(e) = crate::telemetry::init(&self.config.tracing) {
+            tracing::warn!(error = %e, "Distributed tracing disabled");
+        }
+
         // Start scheduler with preemption
         self.start_preemptive_scheduler()?;
 
@@ -198,10 +234,18 @@ impl ReamRuntime {
     }
 
     /// Ask pattern for request-response (placeholder for macro compatibility)
-    pub async fn ask_actor<T>(&self, _pid: Pid, _message: MessagePayload) -> RuntimeResult<T>
+    pub async fn ask_actor<T>(&self, pid: Pid, _message: MessagePayload) -> RuntimeResult<T>
     where
         T: Default,
     {
+        let cx = crate::telemetry::start_span(
+            "actor.ask",
+            opentelemetry::trace::SpanKind::Client,
+            None,
+            vec![opentelemetry::KeyValue::new("ream.pid", pid.to_string())],
+        );
+        let _guard = cx.attach();
+
         // Placeholder implementation
         Ok(T::default())
     }
@@ -264,6 +308,28 @@ impl ReamRuntime {
     
     /// Spawn a new process with the given actor
     pub fn spawn<A>(&self, actor: A) -> RuntimeResult<Pid>
+    where
+        A: ReamActor + Send + Sync + 'static,
+    {
+        self.spawn_as(Pid::new(), actor)
+    }
+
+    /// Spawn a process with options
+    pub fn spawn_with<A>(&self, actor: A, options: SpawnOptions) -> RuntimeResult<Pid>
+    where
+        A: ReamActor + Send + Sync + 'static,
+    {
+        let pid = Pid::new();
+        // Limited before it exists, so no message slips past the limit
+        if let Some(limit) = options.rate_limit {
+            self.message_router.set_rate_limit(pid, Some(limit));
+        }
+        self.spawn_as(pid, actor).inspect_err(|_| self.message_router.set_rate_limit(pid, None))
+    }
+
+    /// Spawn a process under a pid chosen by the caller, such as one the
+    /// actor already knows itself by
+    pub fn spawn_as<A>(&self, pid: Pid, actor: A) -> RuntimeResult<Pid>
     where
         A: ReamActor + Send + Sync + 'static,
     {
@@ -271,12 +337,13 @@ impl ReamRuntime {
             return Err(RuntimeError::MaxProcesses(self.config.max_processes));
         }
         
-        let pid = Pid::new();
-        let process = Process::new(pid, Box::new(actor), Priority::Normal);
+        let mut process = Process::new(pid, Box::new(actor), Priority::Normal);
+        process.set_budget(QuantumBudget::from_config(&self.config));
         let handle = ProcessHandle::new(process);
         
         // Add to process table
         self.processes.insert(pid, handle.clone());
+        self.message_router.register_mailbox(pid, handle.mailbox());
         
         // Schedule the process
         self.scheduler.lock().schedule(pid, Priority::Normal)?;
@@ -290,6 +357,8 @@ impl ReamRuntime {
             stats.process_count += 1;
             stats.running_processes += 1;
         }
+
+        audit::record(AuditKind::Spawn, pid, "Process spawned");
         
         Ok(pid)
     }
@@ -298,6 +367,26 @@ impl ReamRuntime {
     pub fn send(&self, to: Pid, payload: crate::types::MessagePayload) -> RuntimeResult<()> {
         self.message_router.send_message(to, payload)
     }
+
+    /// Send a message from a process, counting it against per-sender rate
+    /// limits of the recipient
+    pub fn send_from(&self, from: Pid, to: Pid, payload: crate::types::MessagePayload) -> RuntimeResult<()> {
+        self.message_router.send_message_from(Some(from), to, payload)
+    }
+
+    /// Limit the messages a process accepts; `None` lifts the limit
+    pub fn set_rate_limit(&self, pid: Pid, limit: Option<RateLimit>) -> RuntimeResult<()> {
+        if !self.processes.contains_key(&pid) {
+            return Err(RuntimeError::ProcessNotFound(pid));
+        }
+        self.message_router.set_rate_limit(pid, limit);
+        Ok(())
+    }
+
+    /// A process's rate limit and what it did with its messages
+    pub fn ingress_status(&self, pid: Pid) -> Option<IngressStatus> {
+        self.message_router.ingress_status(pid)
+    }
     
     /// Get process information
     pub fn process_info(&self, pid: Pid) -> RuntimeResult<ProcessInfo> {
@@ -307,10 +396,34 @@ impl ReamRuntime {
         Ok(handle.info())
     }
     
+    /// Snapshot the supervision hierarchy, annotated with live process state
+    pub fn supervision_tree(&self) -> SupervisionNode {
+        let mut tree = self.root_supervisor.lock().snapshot();
+        tree.for_each_mut(&mut |node| {
+            node.state = self.processes.get(&node.pid).map(|handle| handle.info().state);
+        });
+        tree
+    }
+
     /// Get runtime statistics
     pub fn stats(&self) -> RuntimeStats {
         self.stats.read().unwrap().clone()
     }
+
+    /// Get garbage collection statistics
+    pub fn gc_stats(&self) -> memory::GcStats {
+        self.memory.lock().gc_stats().clone()
+    }
+
+    /// Process heaps by the NUMA node whose arenas back them
+    pub fn arena_stats(&self) -> Vec<memory::ArenaStats> {
+        self.memory.lock().arena_stats()
+    }
+
+    /// Change the allocation level above which the collector runs
+    pub fn set_gc_threshold(&self, bytes: usize) {
+        self.memory.lock().set_gc_threshold(bytes);
+    }
     
     /// Get all process PIDs
     pub fn list_processes(&self) -> Vec<Pid> {
@@ -325,6 +438,7 @@ impl ReamRuntime {
     /// Terminate a specific process
     pub fn terminate_process(&self, pid: Pid) -> RuntimeResult<()> {
         if let Some((_, handle)) = self.processes.remove(&pid) {
+            self.message_router.unregister_process(pid);
             handle.terminate()?;
             
             // Update statistics
@@ -353,8 +467,18 @@ impl ReamRuntime {
         let processes = Arc::clone(&self.processes);
         let running = Arc::clone(&self.running);
         let shutdown_rx = Arc::clone(&self.shutdown_rx);
+        let topology = &self.config.topology;
+        let core = topology.pin_workers
+            .then(|| topology::CpuTopology::system().placement(1, topology).first().copied())
+            .flatten();
 
         std::thread::spawn(move || {
+            if let Some(core) = core {
+                if let Err(e) = topology::pin_current_thread(core) {
+                    tracing::warn!(error = %e, "Scheduler thread left unpinned");
+                }
+            }
+
             while running.load(std::sync::atomic::Ordering::SeqCst) {
                 // Check for shutdown signal
                 {
@@ -418,13 +542,13 @@ impl ReamRuntime {
         let memory = Arc::clone(&self.memory);
         let running = Arc::clone(&self.running);
         let stats = Arc::clone(&self.stats);
-        let gc_threshold = self.config.gc_threshold;
         
         std::thread::spawn(move || {
             while running.load(std::sync::atomic::Ordering::SeqCst) {
                 {
+                    // Reads the threshold each pass, so reloads take effect
                     let mut mem = memory.lock();
-                    if mem.total_allocated() > gc_threshold {
+                    if mem.should_collect() {
                         mem.collect();
                         
                         // Update GC stats
@@ -444,6 +568,7 @@ impl ReamRuntime {
     fn start_stats_collector(&self) -> RuntimeResult<()> {
         let stats = Arc::clone(&self.stats);
         let processes = Arc::clone(&self.processes);
+        let memory = Arc::clone(&self.memory);
         let running = Arc::clone(&self.running);
         
         std::thread::spawn(move || {
@@ -454,16 +579,28 @@ impl ReamRuntime {
                 let now = Instant::now();
                 let elapsed = now.duration_since(last_time).as_secs_f64();
                 
+                // Attribute heap regions to their owners and count processed messages
+                let mut current_message_count = 0u64;
+                let total_memory = {
+                    let memory = memory.lock();
+                    for entry in processes.iter() {
+                        let handle = entry.value();
+                        handle.set_memory_usage(memory.process_memory(*entry.key()));
+                        current_message_count += handle.mailbox().read().unwrap().messages_processed();
+                    }
+                    memory.total_allocated()
+                };
+                
                 {
                     let mut s = stats.write().unwrap();
                     s.process_count = processes.len();
                     s.running_processes = processes.iter()
                         .filter(|entry| entry.value().is_running())
                         .count();
+                    s.memory_usage = total_memory;
                     
-                    // Calculate message rate
-                    let current_message_count = 0u64; // TODO: Get from message router
-                    s.message_rate = (current_message_count - last_message_count) as f64 / elapsed;
+                    // Terminated processes take their counts with them
+                    s.message_rate = current_message_count.saturating_sub(last_message_count) as f64 / elapsed;
                     last_message_count = current_message_count;
                 }
                 
@@ -540,16 +677,20 @@ impl ReamRuntime {
         }
     }
 
-    /// Get actor metrics (simplified for demo)
-    pub fn get_actor_metrics(&self, pid: Pid) -> RuntimeResult<(u64, usize, bool)> {
+    /// Get actor metrics: memory usage, mailbox length, whether it is running,
+    /// CPU utilization since it started and messages processed
+    pub fn get_actor_metrics(&self, pid: Pid) -> RuntimeResult<(u64, usize, bool, f64, u64)> {
         if let Some(ref _monitor) = self.hypervisor {
-            // Get real metrics from the process
             if let Some(handle) = self.processes.get(&pid) {
                 let process = handle.value();
+                let info = process.info();
+                let uptime = process.uptime().as_micros().max(1) as f64;
                 Ok((
-                    524288, // memory_usage: 512KB - would be real memory usage
-                    process.mailbox().read().unwrap().len(), // message_queue_length from actual mailbox
-                    process.is_running(), // status from actual process
+                    info.memory_usage as u64,
+                    info.message_queue_len,
+                    process.is_running(),
+                    info.cpu_time as f64 / uptime,
+                    info.messages_processed,
                 ))
             } else {
                 Err(RuntimeError::ProcessNotFound(pid))
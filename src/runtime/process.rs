@@ -1,11 +1,15 @@
 //! Process management and execution
 
+use std::collections::BTreeMap;
 use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
-use crate::types::{Pid, Priority, ProcessState, ProcessInfo};
+use std::time::{Duration, Instant, SystemTime};
+use crate::types::{MessagePayload, Pid, Priority, ProcessState, ProcessInfo, ReamConfig};
 use crate::error::RuntimeResult;
 use crate::runtime::actor::ReamActor;
-use crate::runtime::message::Mailbox;
+use crate::runtime::crash::{self, CrashDump, HeapSummary};
+use crate::debug::{cpu_profiler, profiler};
+use crate::debug::replay::Recording;
+use crate::runtime::message::{receive_traced, Mailbox};
 
 /// Process execution context
 pub struct Process {
@@ -38,11 +42,48 @@ pub struct Process {
     
     /// Monitored processes
     monitors: Vec<Pid>,
+
+    /// Message history, while the process is being recorded
+    recording: Option<Recording>,
+
+    /// Whether the actor yielded with work left, see `ReamActor::run_slice`
+    busy: bool,
+
+    /// Work allowed per quantum
+    budget: QuantumBudget,
+}
+
+/// Work a process may do in one quantum before yielding, so a process with
+/// a full mailbox cannot starve the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuantumBudget {
+    /// Most messages handled per quantum
+    pub max_messages: usize,
+    /// Messages taken from the mailbox per lock acquisition
+    pub batch_size: usize,
+    /// Time after which the quantum ends even with messages left
+    pub time_slice: Duration,
+}
+
+impl QuantumBudget {
+    /// The budget a runtime with `config` gives its processes
+    pub fn from_config(config: &ReamConfig) -> Self {
+        QuantumBudget {
+            max_messages: config.max_messages_per_quantum.max(1),
+            batch_size: config.mailbox_batch_size.max(1),
+            time_slice: Duration::from_micros(config.scheduler_quantum),
+        }
+    }
+}
+
+impl Default for QuantumBudget {
+    fn default() -> Self {
+        Self::from_config(&ReamConfig::default())
+    }
 }
 
 #[derive(Debug, Default, Clone)]
 struct ProcessStats {
-    messages_processed: u64,
     cpu_time: Duration,
     memory_usage: usize,
     restarts: u32,
@@ -63,6 +104,9 @@ impl Process {
             parent: None,
             links: Vec::new(),
             monitors: Vec::new(),
+            recording: None,
+            busy: false,
+            budget: QuantumBudget::default(),
         }
     }
     
@@ -90,36 +134,153 @@ impl Process {
     pub fn mailbox(&self) -> Arc<RwLock<Mailbox>> {
         Arc::clone(&self.mailbox)
     }
+
+    /// Work allowed per quantum
+    pub fn budget(&self) -> QuantumBudget {
+        self.budget
+    }
+
+    /// Change the work allowed per quantum
+    pub fn set_budget(&mut self, budget: QuantumBudget) {
+        self.budget = budget;
+    }
     
     /// Execute a quantum of work
+    ///
+    /// Messages are taken from the mailbox in batches and handled with the
+    /// mailbox unlocked, so senders are not held up, until the budget's
+    /// message limit or time slice runs out. The handled ones are then
+    /// counted as received and any left over from the batch go back to the
+    /// front of the mailbox, under one lock.
     pub fn run_quantum(&mut self) -> RuntimeResult<usize> {
         if self.state != ProcessState::Running {
             return Ok(0);
         }
         
         let start = Instant::now();
+        let _profile = (profiler::enter(self.pid), cpu_profiler::enter(self.pid));
         let mut messages_processed = 0;
+        let mut failure = None;
         
         // Process messages from mailbox
-        {
-            let mut mailbox = self.mailbox.write().unwrap();
-            while let Some(message) = mailbox.receive() {
-                self.actor.receive(message)?;
-                messages_processed += 1;
-                
+        let budget = self.budget;
+        'quantum: while messages_processed < budget.max_messages {
+            let wanted = budget.batch_size.min(budget.max_messages - messages_processed);
+            let batch = self.mailbox.write().unwrap().receive_batch(wanted);
+            if batch.is_empty() {
+                break;
+            }
+            let mut batch = batch.into_iter();
+            let mut handled = Vec::with_capacity(batch.len());
+            let mut exhausted = false;
+            for message in batch.by_ref() {
+                handled.push(message.clone());
+                let recorded = self.recording.as_ref().map(|_| message.clone());
+                let actor = &mut self.actor;
+                let result = receive_traced(self.pid, message, |message| actor.receive(message));
+                if let (Some(recording), Some(message)) = (self.recording.as_mut(), recorded) {
+                    let error = result.as_ref().err().map(|e| e.to_string());
+                    recording.record(message, self.actor.dictionary(), error);
+                }
+                match result {
+                    Ok(()) => messages_processed += 1,
+                    Err(error) => failure = Some(error),
+                }
+
                 // Limit quantum to prevent starvation
-                if messages_processed >= 10 {
+                if failure.is_some() || start.elapsed() >= budget.time_slice {
+                    exhausted = true;
                     break;
                 }
             }
+
+            let mut mailbox = self.mailbox.write().unwrap();
+            mailbox.record_received(handled);
+            mailbox.requeue(batch);
+            drop(mailbox);
+            if exhausted {
+                break 'quantum;
+            }
+        }
+
+        // Then a slice of any long-running work, which yields back here
+        if failure.is_none() {
+            match self.actor.run_slice() {
+                Ok(busy) => self.busy = busy,
+                Err(error) => failure = Some(error),
+            }
         }
         
-        let quantum_time = start.elapsed();
-        self.stats.cpu_time += quantum_time;
-        self.stats.messages_processed += messages_processed;
-        self.stats.last_activity = Some(Instant::now());
+        self.record_quantum(start.elapsed());
+
+        if let Some(error) = failure {
+            crash::record(&self.crash_dump(&error.to_string()));
+            return Err(error);
+        }
         
-        Ok(messages_processed as usize)
+        Ok(messages_processed)
+    }
+
+    /// Hand a message straight to the actor, bypassing the mailbox
+    pub fn deliver(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        let _profile = (profiler::enter(self.pid), cpu_profiler::enter(self.pid));
+        self.actor.receive(message)
+    }
+
+    /// The actor's process dictionary
+    pub fn dictionary(&self) -> BTreeMap<String, String> {
+        self.actor.dictionary()
+    }
+
+    /// Start recording deliveries, keeping at most `limit`; restarts a
+    /// recording already in progress
+    pub fn start_recording(&mut self, limit: usize) {
+        self.recording = Some(Recording::new(self.pid, self.dictionary(), limit));
+    }
+
+    /// Stop recording, returning what was recorded
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    /// The recording in progress, if any
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    /// Postmortem of the process, as written when its actor crashes
+    pub fn crash_dump(&self, reason: &str) -> CrashDump {
+        let info = self.info();
+        let recent_messages = self.mailbox.read().unwrap().recent().map(MessagePayload::summary).collect();
+        CrashDump {
+            pid: self.pid,
+            reason: reason.to_string(),
+            crashed_at: SystemTime::now(),
+            recent_messages,
+            trace: self.actor.crash_trace(),
+            dictionary: self.dictionary(),
+            parent: info.parent,
+            links: info.links,
+            monitors: info.monitors,
+            heap: HeapSummary {
+                memory_usage: info.memory_usage,
+                mailbox_len: info.message_queue_len,
+                messages_processed: info.messages_processed,
+                cpu_time: info.cpu_time,
+                restarts: info.restarts,
+            },
+        }
+    }
+
+    /// Charge a quantum the process spent running to its CPU time
+    pub fn record_quantum(&mut self, elapsed: Duration) {
+        self.stats.cpu_time += elapsed;
+        self.stats.last_activity = Some(Instant::now());
+    }
+
+    /// Set the heap size attributed to the process
+    pub fn set_memory_usage(&mut self, bytes: usize) {
+        self.stats.memory_usage = bytes;
     }
     
     /// Suspend the process
@@ -185,6 +346,7 @@ impl Process {
     
     /// Get process information
     pub fn info(&self) -> ProcessInfo {
+        let mailbox = self.mailbox.read().unwrap();
         ProcessInfo {
             pid: self.pid,
             state: self.state,
@@ -192,9 +354,11 @@ impl Process {
             parent: self.parent,
             links: self.links.clone(),
             monitors: self.monitors.clone(),
-            message_queue_len: self.mailbox.read().unwrap().len(),
+            message_queue_len: mailbox.len(),
             memory_usage: self.stats.memory_usage,
             cpu_time: self.stats.cpu_time.as_micros() as u64,
+            messages_processed: mailbox.messages_processed(),
+            restarts: self.stats.restarts,
         }
     }
     
@@ -202,6 +366,11 @@ impl Process {
     pub fn is_alive(&self) -> bool {
         self.state != ProcessState::Terminated && self.actor.is_alive()
     }
+
+    /// Whether the process needs another quantum even with an empty mailbox
+    pub fn is_busy(&self) -> bool {
+        self.busy
+    }
     
     /// Get uptime
     pub fn uptime(&self) -> Duration {
@@ -252,6 +421,16 @@ impl ProcessHandle {
     pub fn run_quantum(&self) -> RuntimeResult<usize> {
         self.process.write().unwrap().run_quantum()
     }
+
+    /// Whether the process needs another quantum even with an empty mailbox
+    pub fn is_busy(&self) -> bool {
+        self.process.read().unwrap().is_busy()
+    }
+
+    /// Change the work allowed per quantum
+    pub fn set_budget(&self, budget: QuantumBudget) {
+        self.process.write().unwrap().set_budget(budget);
+    }
     
     /// Suspend the process
     pub fn suspend(&self) -> RuntimeResult<()> {
@@ -272,6 +451,31 @@ impl ProcessHandle {
     pub fn restart(&self) -> RuntimeResult<()> {
         self.process.write().unwrap().restart()
     }
+
+    /// Charge a quantum the process spent running to its CPU time
+    pub fn record_quantum(&self, elapsed: Duration) {
+        self.process.write().unwrap().record_quantum(elapsed);
+    }
+
+    /// Set the heap size attributed to the process
+    pub fn set_memory_usage(&self, bytes: usize) {
+        self.process.write().unwrap().set_memory_usage(bytes);
+    }
+
+    /// Start recording deliveries, keeping at most `limit`
+    pub fn start_recording(&self, limit: usize) {
+        self.process.write().unwrap().start_recording(limit);
+    }
+
+    /// Stop recording, returning what was recorded
+    pub fn stop_recording(&self) -> Option<Recording> {
+        self.process.write().unwrap().stop_recording()
+    }
+
+    /// A copy of the recording in progress, if any
+    pub fn recording(&self) -> Option<Recording> {
+        self.process.read().unwrap().recording().cloned()
+    }
     
     /// Get process information
     pub fn info(&self) -> ProcessInfo {
@@ -389,5 +593,72 @@ mod tests {
         // Process the message
         let processed = handle.run_quantum().unwrap();
         assert_eq!(processed, 1);
+
+        assert_eq!(handle.info().messages_processed, 1);
+
+        // Quanta run by the scheduler are charged the same way
+        let before = handle.info().cpu_time;
+        handle.record_quantum(Duration::from_millis(3));
+        assert_eq!(handle.info().cpu_time, before + 3000);
+    }
+
+    #[test]
+    fn test_quantum_budget() {
+        let pid = Pid::new();
+        let mut process = Process::new(pid, Box::new(CounterActor::new(pid, 0)), Priority::Normal);
+        process.set_budget(QuantumBudget { max_messages: 3, batch_size: 2, time_slice: Duration::from_secs(60) });
+        let mailbox = process.mailbox();
+        for _ in 0..5 {
+            mailbox.write().unwrap().send(MessagePayload::Text("increment".to_string()));
+        }
+
+        // The message limit ends the quantum mid-batch
+        assert_eq!(process.run_quantum().unwrap(), 3);
+        assert_eq!(mailbox.read().unwrap().len(), 2);
+        assert_eq!(mailbox.read().unwrap().messages_processed(), 3);
+        assert_eq!(process.run_quantum().unwrap(), 2);
+        assert_eq!(process.run_quantum().unwrap(), 0);
+
+        // An exhausted time slice puts the rest of the batch back in order
+        process.set_budget(QuantumBudget { max_messages: 10, batch_size: 10, time_slice: Duration::ZERO });
+        for message in ["increment", "decrement", "reset"] {
+            mailbox.write().unwrap().send(MessagePayload::Text(message.to_string()));
+        }
+        assert_eq!(process.run_quantum().unwrap(), 1);
+        let mailbox = mailbox.read().unwrap();
+        assert_eq!(mailbox.len(), 2);
+        assert!(matches!(mailbox.peek(), Some(MessagePayload::Text(text)) if text == "decrement"));
+        assert_eq!(mailbox.messages_processed(), 6);
+    }
+
+    #[test]
+    fn test_crash_dump() {
+        use crate::tlisp::{Expr, Type, Value};
+        use crate::tlisp::actor_system::{SecurityLevel, TlispActor};
+
+        let symbol = |name: &str| Expr::Symbol(name.to_string(), Type::TypeVar("T".to_string()));
+        let call = |items| Expr::List(items, Type::TypeVar("T".to_string()));
+        let behavior = call(vec![symbol("+"), Expr::Number(1, Type::Int), call(vec![symbol("no-such-fn")])]);
+
+        let pid = Pid::new();
+        let mut actor = TlispActor::new(pid.to_string(), behavior, Priority::Normal, None, None, SecurityLevel::Trusted);
+        actor.update_state("count".to_string(), Value::Int(3));
+        let mut process = Process::new(pid, Box::new(actor), Priority::Normal);
+        process.link(Pid::new());
+        {
+            let mailbox = process.mailbox();
+            let mut mb = mailbox.write().unwrap();
+            mb.send(MessagePayload::Text("boom".to_string()));
+            mb.send(MessagePayload::Text("never handled".to_string()));
+        }
+
+        let error = process.run_quantum().unwrap_err().to_string();
+        let dump = process.crash_dump(&error);
+        assert!(dump.reason.contains("no-such-fn"), "{}", dump.reason);
+        assert_eq!(dump.recent_messages, vec!["\"boom\"".to_string()]);
+        assert_eq!(dump.trace, vec!["(no-such-fn)".to_string(), "(+ 1 (no-such-fn))".to_string()]);
+        assert_eq!(dump.dictionary.get("count").map(String::as_str), Some("3"));
+        assert_eq!(dump.links, process.info().links);
+        assert_eq!(dump.heap.mailbox_len, 1);
     }
 }
@@ -421,7 +421,7 @@ impl RealTimeScheduler {
             self.stats.deadline_misses += 1;
             
             // Log deadline miss
-            eprintln!("DEADLINE MISS: Task {} missed deadline", pid);
+            tracing::warn!(%pid, "Task missed deadline");
         }
     }
     
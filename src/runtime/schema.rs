@@ -0,0 +1,409 @@
+//! Message schemas
+//!
+//! `MessagePayload::Data` carries JSON of any shape. A message that names
+//! its schema is sent as a `TypedMessage`, `{"$schema": name, "$version":
+//! n, "body": ...}`, and a `SchemaRegistry` knows what each version of each
+//! schema looks like: versions are registered by the Rust type their bodies
+//! deserialize into, and a body is valid when it deserializes.
+//!
+//! Schemas evolve one version at a time. An upgrade turns a body of version
+//! `n` into one of `n + 1` and a downgrade a body of `n + 1` into one of
+//! `n`, so a message can be converted to any registered version the chain
+//! reaches. Incoming messages are converted to the newest version known;
+//! outgoing ones to the schema's wire version, which is the newest unless
+//! pinned lower. During a rolling upgrade, nodes running the new code pin
+//! the old version until every node knows the new one, then unpin it.
+
+use std::any::type_name;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{RuntimeError, RuntimeResult};
+use crate::types::MessagePayload;
+
+/// A message naming the schema and version of its body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypedMessage {
+    #[serde(rename = "$schema")]
+    pub schema: String,
+    #[serde(rename = "$version")]
+    pub version: u32,
+    pub body: serde_json::Value,
+}
+
+impl TypedMessage {
+    pub fn new<T: Serialize>(schema: impl Into<String>, version: u32, body: &T) -> RuntimeResult<Self> {
+        let body = serde_json::to_value(body).map_err(|e| RuntimeError::SerializationError(e.to_string()))?;
+        Ok(TypedMessage {
+            schema: schema.into(),
+            version,
+            body,
+        })
+    }
+
+    /// The typed message a payload carries, if it carries one
+    pub fn from_payload(payload: &MessagePayload) -> Option<TypedMessage> {
+        match payload {
+            MessagePayload::Data(data) if data.get("$schema").is_some() => serde_json::from_value(data.clone()).ok(),
+            MessagePayload::Traced { payload, .. } => TypedMessage::from_payload(payload),
+            _ => None,
+        }
+    }
+
+    pub fn into_payload(self) -> MessagePayload {
+        MessagePayload::Data(serde_json::to_value(self).expect("typed messages are JSON"))
+    }
+
+    /// Read the body as the type its version was registered with
+    pub fn decode<T: DeserializeOwned>(&self) -> RuntimeResult<T> {
+        serde_json::from_value(self.body.clone()).map_err(|e| {
+            RuntimeError::InvalidMessage(format!("{} v{}: {}", self.schema, self.version, e))
+        })
+    }
+}
+
+type Validator = Arc<dyn Fn(&serde_json::Value) -> Result<(), String> + Send + Sync>;
+
+/// Converts a body between adjacent versions
+pub type Converter = Arc<dyn Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync>;
+
+struct Version {
+    type_name: &'static str,
+    validate: Validator,
+}
+
+#[derive(Default)]
+struct Family {
+    versions: BTreeMap<u32, Version>,
+    /// Upgrades by the version they start from
+    upgrades: BTreeMap<u32, Converter>,
+    /// Downgrades by the version they start from
+    downgrades: BTreeMap<u32, Converter>,
+    pinned: Option<u32>,
+}
+
+impl Family {
+    fn newest(&self) -> u32 {
+        *self.versions.keys().next_back().expect("schemas have a version")
+    }
+
+    fn wire_version(&self) -> u32 {
+        self.pinned.unwrap_or_else(|| self.newest())
+    }
+}
+
+/// What the registry knows of one schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaInfo {
+    pub name: String,
+    pub versions: Vec<SchemaVersion>,
+    /// Version incoming messages are converted to
+    pub current: u32,
+    /// Version outgoing messages are converted to
+    pub wire_version: u32,
+    /// Versions with an upgrade to the next
+    pub upgrades: Vec<u32>,
+    /// Versions with a downgrade to the previous
+    pub downgrades: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaVersion {
+    pub version: u32,
+    /// Rust type bodies of this version deserialize into
+    pub type_name: String,
+}
+
+/// The schemas of actor messages and the conversions between their versions
+#[derive(Default)]
+pub struct SchemaRegistry {
+    families: RwLock<BTreeMap<String, Family>>,
+    /// Whether payloads naming no schema are refused
+    strict: RwLock<bool>,
+}
+
+impl std::fmt::Debug for SchemaRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let families = self.families.read().unwrap();
+        let versions = families.iter().map(|(name, family)| (name, family.versions.keys().collect::<Vec<_>>()));
+        f.debug_map().entries(versions).finish()
+    }
+}
+
+fn invalid(message: String) -> RuntimeError {
+    RuntimeError::InvalidMessage(message)
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register version `version` of schema `name`, whose bodies deserialize
+    /// into `T`
+    pub fn register<T: DeserializeOwned + 'static>(&self, name: &str, version: u32) -> RuntimeResult<()> {
+        let mut families = self.families.write().unwrap();
+        let family = families.entry(name.to_string()).or_default();
+        if family.versions.contains_key(&version) {
+            return Err(invalid(format!("{} v{} is already registered", name, version)));
+        }
+        let validate: Validator = Arc::new(|body| serde_json::from_value::<T>(body.clone()).map(drop).map_err(|e| e.to_string()));
+        family.versions.insert(version, Version { type_name: type_name::<T>(), validate });
+        Ok(())
+    }
+
+    fn with_family<R>(&self, name: &str, f: impl FnOnce(&mut Family) -> RuntimeResult<R>) -> RuntimeResult<R> {
+        let mut families = self.families.write().unwrap();
+        let family = families.get_mut(name).ok_or_else(|| invalid(format!("no schema {} is registered", name)))?;
+        f(family)
+    }
+
+    /// Convert bodies of version `from` to version `from + 1`
+    pub fn upgrade<F>(&self, name: &str, from: u32, convert: F) -> RuntimeResult<()>
+    where
+        F: Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+    {
+        self.with_family(name, |family| {
+            for version in [from, from + 1] {
+                if !family.versions.contains_key(&version) {
+                    return Err(invalid(format!("{} v{} is not registered", name, version)));
+                }
+            }
+            family.upgrades.insert(from, Arc::new(convert));
+            Ok(())
+        })
+    }
+
+    /// Convert bodies of version `from` to version `from - 1`
+    pub fn downgrade<F>(&self, name: &str, from: u32, convert: F) -> RuntimeResult<()>
+    where
+        F: Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+    {
+        self.with_family(name, |family| {
+            let to = from.checked_sub(1).ok_or_else(|| invalid(format!("{} v0 has no version below it", name)))?;
+            for version in [to, from] {
+                if !family.versions.contains_key(&version) {
+                    return Err(invalid(format!("{} v{} is not registered", name, version)));
+                }
+            }
+            family.downgrades.insert(from, Arc::new(convert));
+            Ok(())
+        })
+    }
+
+    /// Send messages of schema `name` as `version`, or as the newest
+    /// version again with `None`
+    pub fn pin(&self, name: &str, version: Option<u32>) -> RuntimeResult<()> {
+        self.with_family(name, |family| {
+            if let Some(version) = version.filter(|version| !family.versions.contains_key(version)) {
+                return Err(invalid(format!("{} v{} is not registered", name, version)));
+            }
+            family.pinned = version;
+            Ok(())
+        })
+    }
+
+    /// Refuse payloads that name no schema
+    pub fn set_strict(&self, strict: bool) {
+        *self.strict.write().unwrap() = strict;
+    }
+
+    /// Check a message's body against the version it names
+    pub fn validate(&self, message: &TypedMessage) -> RuntimeResult<()> {
+        let families = self.families.read().unwrap();
+        let family = families
+            .get(&message.schema)
+            .ok_or_else(|| invalid(format!("no schema {} is registered", message.schema)))?;
+        Self::validate_in(family, message)
+    }
+
+    fn validate_in(family: &Family, message: &TypedMessage) -> RuntimeResult<()> {
+        let version = family
+            .versions
+            .get(&message.version)
+            .ok_or_else(|| invalid(format!("{} v{} is not registered", message.schema, message.version)))?;
+        (version.validate)(&message.body).map_err(|e| invalid(format!("{} v{}: {}", message.schema, message.version, e)))
+    }
+
+    /// Convert a valid message to version `to`, one version at a time
+    pub fn convert(&self, message: TypedMessage, to: u32) -> RuntimeResult<TypedMessage> {
+        let families = self.families.read().unwrap();
+        let family = families
+            .get(&message.schema)
+            .ok_or_else(|| invalid(format!("no schema {} is registered", message.schema)))?;
+        Self::convert_in(family, message, to)
+    }
+
+    fn convert_in(family: &Family, mut message: TypedMessage, to: u32) -> RuntimeResult<TypedMessage> {
+        Self::validate_in(family, &message)?;
+        while message.version != to {
+            let (converters, next, direction) = if message.version < to {
+                (&family.upgrades, message.version + 1, "upgrade")
+            } else {
+                (&family.downgrades, message.version - 1, "downgrade")
+            };
+            let convert = converters.get(&message.version).ok_or_else(|| {
+                invalid(format!("{} has no {} from v{} to v{}", message.schema, direction, message.version, next))
+            })?;
+            message.body = convert(message.body).map_err(|e| {
+                invalid(format!("{} {} from v{}: {}", message.schema, direction, message.version, e))
+            })?;
+            message.version = next;
+            Self::validate_in(family, &message)?;
+        }
+        Ok(message)
+    }
+
+    /// Check a payload received from another node, converting a typed
+    /// message to the newest version known
+    pub fn incoming(&self, payload: MessagePayload) -> RuntimeResult<MessagePayload> {
+        self.check(payload, |family| family.newest())
+    }
+
+    /// Check a payload bound for another node, converting a typed message to
+    /// its schema's wire version
+    pub fn outgoing(&self, payload: MessagePayload) -> RuntimeResult<MessagePayload> {
+        self.check(payload, Family::wire_version)
+    }
+
+    fn check(&self, payload: MessagePayload, target: impl Fn(&Family) -> u32) -> RuntimeResult<MessagePayload> {
+        if let MessagePayload::Traced { context, payload } = payload {
+            let payload = Box::new(self.check(*payload, target)?);
+            return Ok(MessagePayload::Traced { context, payload });
+        }
+        let Some(message) = TypedMessage::from_payload(&payload) else {
+            if *self.strict.read().unwrap() && !matches!(payload, MessagePayload::Control(_)) {
+                return Err(invalid("message names no schema".to_string()));
+            }
+            return Ok(payload);
+        };
+        let families = self.families.read().unwrap();
+        let family = families
+            .get(&message.schema)
+            .ok_or_else(|| invalid(format!("no schema {} is registered", message.schema)))?;
+        let to = target(family);
+        Ok(Self::convert_in(family, message, to)?.into_payload())
+    }
+
+    /// Every schema registered, by name
+    pub fn describe(&self) -> Vec<SchemaInfo> {
+        self.families
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, family)| SchemaInfo {
+                name: name.clone(),
+                versions: family
+                    .versions
+                    .iter()
+                    .map(|(version, registered)| SchemaVersion {
+                        version: *version,
+                        type_name: registered.type_name.to_string(),
+                    })
+                    .collect(),
+                current: family.newest(),
+                wire_version: family.wire_version(),
+                upgrades: family.upgrades.keys().copied().collect(),
+                downgrades: family.downgrades.keys().copied().collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct OrderV1 {
+        id: u64,
+        total: f64,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct OrderV2 {
+        id: u64,
+        total_cents: u64,
+        currency: String,
+    }
+
+    fn orders() -> SchemaRegistry {
+        let registry = SchemaRegistry::new();
+        registry.register::<OrderV1>("order.created", 1).unwrap();
+        registry.register::<OrderV2>("order.created", 2).unwrap();
+        registry
+            .upgrade("order.created", 1, |body| {
+                let total = body["total"].as_f64().ok_or("total is not a number")?;
+                Ok(json!({"id": body["id"], "total_cents": (total * 100.0).round() as u64, "currency": "USD"}))
+            })
+            .unwrap();
+        registry
+            .downgrade("order.created", 2, |body| {
+                if body["currency"] != "USD" {
+                    return Err(format!("cannot express {} in v1", body["currency"]));
+                }
+                let cents = body["total_cents"].as_u64().ok_or("total_cents is not a number")?;
+                Ok(json!({"id": body["id"], "total": cents as f64 / 100.0}))
+            })
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_conversions_between_versions() {
+        let registry = orders();
+        let v1 = TypedMessage::new("order.created", 1, &OrderV1 { id: 7, total: 12.5 }).unwrap();
+        registry.validate(&v1).unwrap();
+
+        // Incoming messages reach the newest version
+        let upgraded = registry.incoming(v1.clone().into_payload()).unwrap();
+        let upgraded = TypedMessage::from_payload(&upgraded).unwrap();
+        assert_eq!(upgraded.version, 2);
+        assert_eq!(
+            upgraded.decode::<OrderV2>().unwrap(),
+            OrderV2 { id: 7, total_cents: 1250, currency: "USD".to_string() }
+        );
+
+        // While v1 is pinned, outgoing messages go out as v1
+        registry.pin("order.created", Some(1)).unwrap();
+        let downgraded = registry.outgoing(upgraded.clone().into_payload()).unwrap();
+        assert_eq!(TypedMessage::from_payload(&downgraded), Some(v1.clone()));
+        registry.pin("order.created", None).unwrap();
+        assert_eq!(TypedMessage::from_payload(&registry.outgoing(v1.into_payload()).unwrap()), Some(upgraded));
+
+        let euros = TypedMessage::new("order.created", 2, &OrderV2 { id: 8, total_cents: 100, currency: "EUR".to_string() }).unwrap();
+        let error = registry.convert(euros, 1).unwrap_err().to_string();
+        assert!(error.contains("cannot express \"EUR\" in v1"), "{}", error);
+        assert!(registry.pin("order.created", Some(3)).is_err());
+        assert!(registry.register::<OrderV1>("order.created", 1).is_err());
+        assert!(registry.upgrade("order.created", 2, Ok).is_err());
+    }
+
+    #[test]
+    fn test_invalid_and_untyped_payloads() {
+        let registry = orders();
+        let check = |payload: MessagePayload| registry.incoming(payload).map_err(|e| e.to_string());
+
+        let wrong = TypedMessage { schema: "order.created".to_string(), version: 1, body: json!({"id": "seven"}) };
+        assert!(check(wrong.into_payload()).unwrap_err().contains("order.created v1"));
+        let unknown = TypedMessage { schema: "order.created".to_string(), version: 9, body: json!({}) };
+        assert!(check(unknown.into_payload()).unwrap_err().contains("v9 is not registered"));
+        let other = TypedMessage { schema: "invoice.paid".to_string(), version: 1, body: json!({}) };
+        assert!(check(other.into_payload()).unwrap_err().contains("no schema invoice.paid"));
+
+        assert!(check(MessagePayload::Text("hello".to_string())).is_ok());
+        registry.set_strict(true);
+        assert!(check(MessagePayload::Text("hello".to_string())).unwrap_err().contains("names no schema"));
+
+        let info = registry.describe();
+        assert_eq!(info.len(), 1);
+        assert_eq!(info[0].current, 2);
+        assert_eq!(info[0].upgrades, vec![1]);
+        assert_eq!(info[0].downgrades, vec![2]);
+        assert!(info[0].versions[1].type_name.ends_with("OrderV2"));
+    }
+}
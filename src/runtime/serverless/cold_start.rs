@@ -339,6 +339,8 @@ impl ColdStartOptimizer {
                 compile_time: std::time::Duration::ZERO,
                 opt_level: 0,
                 hot_spots: Vec::new(),
+                specializations: Vec::new(),
+                vector_loops: Vec::new(),
             }
         ))
     }
@@ -380,6 +380,17 @@ impl ServerlessMetrics {
         metrics.error_rate = (metrics.failed_invocations as f64 / metrics.invocations as f64) * 100.0;
     }
     
+    /// Record the time a function took to start a new instance
+    pub fn record_cold_start(&self, function_name: &str, cold_start_time: Duration) {
+        let mut function_metrics = self.function_metrics.write().unwrap();
+        let metrics = function_metrics.entry(function_name.to_string())
+            .or_insert_with(|| FunctionMetrics {
+                name: function_name.to_string(),
+                ..Default::default()
+            });
+        metrics.cold_start_time_total += cold_start_time;
+    }
+
     /// Export metrics in all configured formats
     pub fn export_all(&self) -> Vec<(String, String, String)> {
         let mut exports = Vec::new();
@@ -394,7 +405,7 @@ impl ServerlessMetrics {
                     ));
                 }
                 Err(e) => {
-                    eprintln!("Failed to export metrics with {}: {}", exporter.name(), e);
+                    tracing::warn!(exporter = exporter.name(), error = %e, "Failed to export metrics");
                 }
             }
         }
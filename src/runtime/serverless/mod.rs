@@ -57,6 +57,9 @@ pub struct ServerlessConfig {
     pub jit_cache_enabled: bool,
     /// Hibernation storage size (bytes)
     pub hibernation_storage_size: usize,
+    /// Directory snapshots of TLisp functions' initialized environments are
+    /// saved to, so unchanged functions skip initialization when redeployed
+    pub snapshot_dir: Option<std::path::PathBuf>,
 }
 
 impl Default for ServerlessConfig {
@@ -75,6 +78,7 @@ impl Default for ServerlessConfig {
             zero_copy_enabled: true,
             jit_cache_enabled: true,
             hibernation_storage_size: 1024 * 1024 * 1024, // 1GB
+            snapshot_dir: None,
         }
     }
 }
@@ -278,6 +282,9 @@ pub trait ServerlessActor: crate::runtime::actor::ReamActor {
 
 /// Serverless runtime trait
 pub trait ServerlessRuntime {
+    /// Deploy a function compiled to a bytecode bundle, invoked by `trigger`
+    fn deploy(&mut self, name: &str, bundle: crate::bytecode::BytecodeBundle, trigger: WakeTrigger) -> ServerlessResult<()>;
+
     /// Deploy a serverless function
     fn deploy_function(&mut self, function: ServerlessFunction) -> ServerlessResult<()>;
     
@@ -963,4 +963,119 @@ mod tests {
         println!("Average hibernation time: {:?}", hibernation_phase_time / stress_test_size as u32);
         println!("Average wake time: {:?}", wake_phase_time / stress_test_size as u32);
     }
+
+    fn bundle(name: &str, build: impl FnOnce(&mut crate::bytecode::BytecodeProgram)) -> crate::bytecode::BytecodeBundle {
+        use crate::bytecode::{BundleModule, BytecodeBundle, BytecodeProgram};
+        let mut program = BytecodeProgram::new(name.to_string());
+        build(&mut program);
+        BytecodeBundle::new(BundleModule::new(name.to_string(), "0.1.0".to_string(), format!("{}.tl", name), program))
+    }
+
+    /// Test functions deployed from bundles: cold and warm starts, HTTP
+    /// routing, concurrency limits and scaling to zero
+    #[test]
+    fn test_bundle_functions() {
+        use crate::bytecode::{Bytecode, Value};
+        use crate::runtime::serverless_runtime::{DeployOptions, ServerlessReamRuntime};
+        use crate::types::EffectGrade;
+
+        let runtime = ServerlessReamRuntime::new(ServerlessConfig::default()).unwrap();
+        let greet = bundle("greet", |program| {
+            let hello = program.add_constant(Value::String("Hello, ".to_string()));
+            program.add_instruction(Bytecode::Const(hello, EffectGrade::Pure));
+            program.add_instruction(Bytecode::Receive(EffectGrade::Read));
+            program.add_instruction(Bytecode::StrConcat(EffectGrade::Pure));
+        });
+        let options = DeployOptions { idle_timeout: Some(Duration::from_millis(50)), ..Default::default() };
+        runtime.deploy_with("greet", greet.clone(), WakeTrigger::HttpRequest {
+            path: "/greet".to_string(),
+            method: "post".to_string(),
+        }, options).unwrap();
+
+        // Nothing runs until the first invocation, which cold-starts an instance the next one reuses
+        assert_eq!(runtime.function_info()[0].warm_instances, 0);
+        assert_eq!(runtime.invoke("greet", b"world".to_vec()).unwrap(), b"Hello, world");
+        assert_eq!(runtime.invoke_http("POST", "/greet", b"again".to_vec()).unwrap(), b"Hello, again");
+        let info = &runtime.function_info()[0];
+        assert_eq!((info.invocations, info.cold_starts, info.warm_instances), (2, 1, 1));
+        assert!(runtime.invoke_http("GET", "/greet", Vec::new()).is_err());
+        assert!(runtime.deploy("other", greet, WakeTrigger::HttpRequest {
+            path: "/greet".to_string(),
+            method: "POST".to_string(),
+        }).is_err(), "two functions cannot share a route");
+
+        // Idle instances are dropped once the idle timeout passes
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(runtime.scale_to_zero(), 1);
+        assert_eq!(runtime.function_info()[0].warm_instances, 0);
+
+        // Invocations over the concurrency limit are rejected while one runs
+        let spin = bundle("spin", |program| {
+            let count = program.add_constant(Value::Int(2_000_000));
+            let one = program.add_constant(Value::Int(1));
+            let zero = program.add_constant(Value::Int(0));
+            program.add_instruction(Bytecode::Const(count, EffectGrade::Pure));
+            program.add_instruction(Bytecode::Const(one, EffectGrade::Pure));
+            program.add_instruction(Bytecode::Sub(EffectGrade::Pure));
+            program.add_instruction(Bytecode::Dup(EffectGrade::Pure));
+            program.add_instruction(Bytecode::Const(zero, EffectGrade::Pure));
+            program.add_instruction(Bytecode::Gt(EffectGrade::Pure));
+            program.add_instruction(Bytecode::JumpIf(1, EffectGrade::Pure));
+        });
+        let runtime = std::sync::Arc::new(runtime);
+        runtime.deploy_with("spin", spin, WakeTrigger::IncomingMessage, DeployOptions {
+            max_concurrency: 1,
+            ..Default::default()
+        }).unwrap();
+        let running = {
+            let runtime = runtime.clone();
+            std::thread::spawn(move || runtime.invoke("spin", Vec::new()))
+        };
+        let spin_info = || runtime.function_info().into_iter().find(|info| info.name == "spin").unwrap();
+        while spin_info().running == 0 {
+            std::thread::yield_now();
+        }
+        let err = runtime.invoke("spin", Vec::new()).unwrap_err().to_string();
+        assert!(err.contains("concurrency limit of 1"), "{}", err);
+        assert_eq!(running.join().unwrap().unwrap(), b"0");
+        assert_eq!(spin_info().rejected, 1);
+
+        runtime.undeploy_function("spin").unwrap();
+        assert!(runtime.invoke("spin", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_tlisp_functions_start_from_snapshots() {
+        use crate::runtime::serverless_runtime::{DeployOptions, ServerlessReamRuntime};
+        use crate::tlisp::snapshot::{code_digest, EnvironmentSnapshot};
+        use crate::tlisp::TlispInterpreter;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = ServerlessConfig { snapshot_dir: Some(dir.path().to_path_buf()), ..Default::default() };
+        let runtime = ServerlessReamRuntime::new(config).unwrap();
+        let source = "(define greeting \"Hello\") (define (handle name) (if (= name \"world\") greeting \"Who?\"))";
+        runtime.deploy_tlisp("greet", source, WakeTrigger::IncomingMessage, DeployOptions::default()).unwrap();
+        assert_eq!(runtime.invoke("greet", b"world".to_vec()).unwrap(), b"Hello");
+        assert_eq!(runtime.invoke("greet", b"REAM".to_vec()).unwrap(), b"Who?");
+        let info = &runtime.function_info()[0];
+        assert_eq!((info.invocations, info.warm_instances), (2, 0));
+
+        // A saved snapshot of the same source is restored instead of evaluating it
+        let path = dir.path().join("greet.reamsnap");
+        let saved = EnvironmentSnapshot::read_from(&path).unwrap();
+        assert!(saved.is_for(&code_digest(source.as_bytes())));
+        let mut planted = TlispInterpreter::new();
+        planted.eval("(define (handle name) \"from snapshot\")").unwrap();
+        EnvironmentSnapshot::capture(&planted, saved.code_digest.clone()).write_to(&path).unwrap();
+        runtime.deploy_tlisp("greet", source, WakeTrigger::IncomingMessage, DeployOptions::default()).unwrap();
+        assert_eq!(runtime.invoke("greet", b"world".to_vec()).unwrap(), b"from snapshot");
+
+        // Changed source invalidates it
+        let changed = "(define (handle name) \"Hi\")";
+        runtime.deploy_tlisp("greet", changed, WakeTrigger::IncomingMessage, DeployOptions::default()).unwrap();
+        assert_eq!(runtime.invoke("greet", b"world".to_vec()).unwrap(), b"Hi");
+        assert!(EnvironmentSnapshot::read_from(&path).unwrap().is_for(&code_digest(changed.as_bytes())));
+
+        assert!(runtime.deploy_tlisp("broken", "(define x 1)", WakeTrigger::IncomingMessage, DeployOptions::default()).is_err());
+    }
 }
@@ -53,6 +53,9 @@ pub struct MemoryMappedRegion {
     protection: ProtectionFlags,
 }
 
+// The region owns its mapping, and every user reaches it through a mutex
+unsafe impl Send for MemoryMappedRegion {}
+
 /// Memory protection flags
 #[derive(Debug, Clone, Copy)]
 pub struct ProtectionFlags {
@@ -4,12 +4,203 @@
 //! and serverless features with the main REAM runtime system.
 
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
 
+use crate::bytecode::{BytecodeBundle, BytecodeVM, Value};
 use crate::types::Pid;
-use crate::error::RuntimeResult;
+use crate::error::{BytecodeError, RuntimeError, RuntimeResult};
 use crate::runtime::serverless::*;
+use crate::tlisp::snapshot::{code_digest, EnvironmentSnapshot, SNAPSHOT_EXTENSION};
+use crate::tlisp::TlispInterpreter;
+
+/// Function a TLisp program deployed as a serverless function is invoked through
+pub const TLISP_HANDLER: &str = "handle";
+
+/// Invocations a deployed function runs at once by default
+pub const DEFAULT_MAX_CONCURRENCY: usize = 100;
+
+/// How a deployed function is run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployOptions {
+    /// How long an instance may sit idle before it is dropped; `None` uses
+    /// the runtime's hibernation timeout
+    pub idle_timeout: Option<Duration>,
+    /// Invocations that may run at once; more are rejected
+    pub max_concurrency: usize,
+}
+
+impl Default for DeployOptions {
+    fn default() -> Self {
+        DeployOptions { idle_timeout: None, max_concurrency: DEFAULT_MAX_CONCURRENCY }
+    }
+}
+
+/// A deployed function and its instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionInfo {
+    /// Function name
+    pub name: String,
+    /// What invokes the function
+    pub trigger: WakeTrigger,
+    /// Idle time after which an instance is dropped
+    pub idle_timeout: Duration,
+    /// Invocations that may run at once
+    pub max_concurrency: usize,
+    /// Instances kept warm between invocations
+    pub warm_instances: usize,
+    /// Invocations running now
+    pub running: usize,
+    /// Invocations started
+    pub invocations: u64,
+    /// Invocations that had to start a new instance
+    pub cold_starts: u64,
+    /// Invocations rejected over the concurrency limit
+    pub rejected: u64,
+}
+
+/// A VM with a function's bundle loaded, kept warm between invocations
+struct Instance {
+    vm: BytecodeVM,
+    last_used: Instant,
+}
+
+/// What a deployed function runs
+#[derive(Clone)]
+enum FunctionCode {
+    /// A bytecode bundle, run on VMs kept warm between invocations
+    Bundle(Arc<BytecodeBundle>),
+    /// A TLisp program, restored from the snapshot of its initialized
+    /// environment for every invocation
+    Tlisp(Arc<EnvironmentSnapshot>),
+}
+
+impl FunctionCode {
+    /// Whether both are the same deployment of the code
+    fn same(&self, other: &FunctionCode) -> bool {
+        match (self, other) {
+            (FunctionCode::Bundle(a), FunctionCode::Bundle(b)) => Arc::ptr_eq(a, b),
+            (FunctionCode::Tlisp(a), FunctionCode::Tlisp(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// The code of a deployed function
+struct DeployedCode {
+    code: FunctionCode,
+    trigger: WakeTrigger,
+    idle_timeout: Duration,
+    max_concurrency: usize,
+    /// Warm instances, least recently used first
+    idle: Vec<Instance>,
+    running: usize,
+    invocations: u64,
+    cold_starts: u64,
+    rejected: u64,
+}
+
+impl DeployedCode {
+    /// Drop the instances idle for longer than the idle timeout
+    fn reap(&mut self, now: Instant) -> usize {
+        let before = self.idle.len();
+        let timeout = self.idle_timeout;
+        self.idle.retain(|instance| now.duration_since(instance.last_used) < timeout);
+        before - self.idle.len()
+    }
+
+    fn info(&self, name: &str) -> FunctionInfo {
+        FunctionInfo {
+            name: name.to_string(),
+            trigger: self.trigger.clone(),
+            idle_timeout: self.idle_timeout,
+            max_concurrency: self.max_concurrency,
+            warm_instances: self.idle.len(),
+            running: self.running,
+            invocations: self.invocations,
+            cold_starts: self.cold_starts,
+            rejected: self.rejected,
+        }
+    }
+}
+
+/// Start an instance of a bundle: load and verify its modules, then run
+/// its dependencies so the entry module finds them initialized
+fn cold_start(bundle: &BytecodeBundle) -> RuntimeResult<BytecodeVM> {
+    let failed = |e: BytecodeError| RuntimeError::Serverless(format!("Cold start failed: {}", e));
+    let mut vm = BytecodeVM::new();
+    vm.load_bundle(bundle).map_err(failed)?;
+    for module in &bundle.dependencies {
+        vm.execute_program(&module.program).map_err(failed)?;
+    }
+    Ok(vm)
+}
+
+/// Run the entry module of a bundle once, with the payload delivered for
+/// `receive`; a string or bytes result is the response, anything else is
+/// rendered as text
+fn run_entry(vm: &mut BytecodeVM, bundle: &BytecodeBundle, payload: Vec<u8>) -> RuntimeResult<Vec<u8>> {
+    let message = match String::from_utf8(payload) {
+        Ok(text) => Value::String(text),
+        Err(e) => Value::Bytes(e.into_bytes()),
+    };
+    vm.deliver(message);
+    let result = vm.execute_program(&bundle.entry.program);
+    vm.clear_inbox();
+    let result = result.map_err(|e| RuntimeError::Serverless(format!("Function failed: {}", e)))?;
+    Ok(match result {
+        Value::String(text) => text.into_bytes(),
+        Value::Bytes(bytes) => bytes,
+        other => other.as_string().into_bytes(),
+    })
+}
+
+/// Snapshot of a TLisp program's initialized environment.
+///
+/// A snapshot saved in `dir` for the same source is reused; otherwise the
+/// source is evaluated and the snapshot of the result replaces the saved one.
+fn tlisp_snapshot(name: &str, source: &str, dir: Option<&Path>) -> RuntimeResult<EnvironmentSnapshot> {
+    let digest = code_digest(source.as_bytes());
+    let path = dir.map(|dir| dir.join(format!("{}.{}", name, SNAPSHOT_EXTENSION)));
+    if let Some(snapshot) = path.as_deref()
+        .and_then(|path| EnvironmentSnapshot::read_from(path).ok())
+        .filter(|snapshot| snapshot.is_for(&digest))
+    {
+        return Ok(snapshot);
+    }
+
+    let mut interpreter = TlispInterpreter::new();
+    interpreter.eval(source)
+        .map_err(|e| RuntimeError::Serverless(format!("Initializing function {} failed: {}", name, e)))?;
+    if !matches!(interpreter.get(TLISP_HANDLER), Some(crate::tlisp::Value::Function(_))) {
+        return Err(RuntimeError::Serverless(format!("Function {} does not define ({} payload)", name, TLISP_HANDLER)));
+    }
+    let snapshot = EnvironmentSnapshot::capture(&interpreter, digest);
+    if let Some(path) = path {
+        let saved = std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))
+            .map_err(|e| e.to_string())
+            .and_then(|_| snapshot.write_to(&path).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            tracing::warn!(function = name, error = %e, "Failed to save function snapshot");
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Call the handler of a TLisp function with the payload as a string; a
+/// string result is the response, anything else is rendered as text
+fn run_handler(interpreter: &mut TlispInterpreter, payload: Vec<u8>) -> RuntimeResult<Vec<u8>> {
+    let failed = |e: crate::error::TlispError| RuntimeError::Serverless(format!("Function failed: {}", e));
+    let handler = interpreter.get(TLISP_HANDLER)
+        .ok_or_else(|| RuntimeError::Serverless(format!("Function does not define {}", TLISP_HANDLER)))?;
+    let payload = crate::tlisp::Value::String(String::from_utf8_lossy(&payload).into_owned());
+    Ok(match interpreter.call(&handler, &[payload]).map_err(failed)? {
+        crate::tlisp::Value::String(text) => text.into_bytes(),
+        other => other.to_string().into_bytes(),
+    })
+}
 
 /// Serverless-enabled REAM runtime
 pub struct ServerlessReamRuntime {
@@ -29,6 +220,8 @@ pub struct ServerlessReamRuntime {
     functions: Arc<RwLock<HashMap<String, ServerlessFunction>>>,
     /// Active deployments
     deployments: Arc<RwLock<HashMap<String, ServerlessDeployment>>>,
+    /// Code of the functions deployed from bundles and TLisp programs
+    code: Arc<RwLock<HashMap<String, Arc<Mutex<DeployedCode>>>>>,
 }
 
 impl ServerlessReamRuntime {
@@ -81,6 +274,7 @@ impl ServerlessReamRuntime {
             config,
             functions: Arc::new(RwLock::new(HashMap::new())),
             deployments: Arc::new(RwLock::new(HashMap::new())),
+            code: Arc::new(RwLock::new(HashMap::new())),
         })
     }
     
@@ -99,14 +293,204 @@ impl ServerlessReamRuntime {
         // Store function
         self.functions.write().unwrap().insert(function_name.clone(), function);
         
-        println!("Deployed serverless function: {}", function_name);
+        tracing::info!(function = %function_name, "Deployed serverless function");
         Ok(())
     }
     
+    /// Deploy a function compiled to a bytecode bundle, invoked by `trigger`
+    pub fn deploy(&self, name: &str, bundle: BytecodeBundle, trigger: WakeTrigger) -> RuntimeResult<()> {
+        self.deploy_with(name, bundle, trigger, DeployOptions::default())
+    }
+
+    /// Deploy a function compiled to a bytecode bundle with the given options.
+    ///
+    /// Functions are triggered by messages (`IncomingMessage`) or by HTTP
+    /// requests (`HttpRequest`); no instance runs until the first invocation,
+    /// which starts one from the bundle. Redeploying a function replaces its
+    /// code and drops its warm instances.
+    pub fn deploy_with(&self, name: &str, bundle: BytecodeBundle, trigger: WakeTrigger, options: DeployOptions) -> RuntimeResult<()> {
+        let trigger = self.check_deployment(name, trigger, &options)?;
+        bundle.validate()
+            .map_err(|e| RuntimeError::Serverless(format!("Invalid bundle for function {}: {}", name, e)))?;
+
+        let actor_type = bundle.entry.name.clone();
+        self.install(name, actor_type, FunctionCode::Bundle(Arc::new(bundle)), trigger, options);
+        tracing::info!(function = name, "Deployed serverless function from bundle");
+        Ok(())
+    }
+
+    /// Deploy a function written in TLisp, invoked through its `handle`
+    /// function with the payload as a string.
+    ///
+    /// The source is evaluated once, at deployment, and every invocation
+    /// starts from a snapshot of the environment it left behind. With a
+    /// snapshot directory configured, the snapshot is saved there and reused
+    /// by later deployments of the same source, so redeploying unchanged code
+    /// (after a restart, say) skips initialization; changed code invalidates it.
+    pub fn deploy_tlisp(&self, name: &str, source: &str, trigger: WakeTrigger, options: DeployOptions) -> RuntimeResult<()> {
+        let trigger = self.check_deployment(name, trigger, &options)?;
+        let snapshot = tlisp_snapshot(name, source, self.config.snapshot_dir.as_deref())?;
+
+        self.install(name, name.to_string(), FunctionCode::Tlisp(Arc::new(snapshot)), trigger, options);
+        tracing::info!(function = name, "Deployed serverless function from TLisp source");
+        Ok(())
+    }
+
+    /// Check the trigger and options of a deployment, returning the trigger
+    /// with its HTTP method normalized
+    fn check_deployment(&self, name: &str, trigger: WakeTrigger, options: &DeployOptions) -> RuntimeResult<WakeTrigger> {
+        let trigger = match trigger {
+            WakeTrigger::IncomingMessage => WakeTrigger::IncomingMessage,
+            WakeTrigger::HttpRequest { path, method } => {
+                if !path.starts_with('/') {
+                    return Err(RuntimeError::Serverless(format!("HTTP trigger path {} must start with /", path)));
+                }
+                let method = method.to_ascii_uppercase();
+                if let Some(other) = self.route_http(&method, &path).filter(|other| other != name) {
+                    return Err(RuntimeError::Serverless(format!("{} {} already triggers function {}", method, path, other)));
+                }
+                WakeTrigger::HttpRequest { path, method }
+            }
+            other => return Err(RuntimeError::Serverless(format!("Unsupported function trigger {:?}", other))),
+        };
+        if options.max_concurrency == 0 {
+            return Err(RuntimeError::Serverless("Concurrency limit must be at least 1".to_string()));
+        }
+        Ok(trigger)
+    }
+
+    /// Register deployed code, replacing any earlier deployment of the function
+    fn install(&self, name: &str, actor_type: String, code: FunctionCode, trigger: WakeTrigger, options: DeployOptions) {
+        let function = ServerlessFunction {
+            name: name.to_string(),
+            actor_type,
+            memory_limit: 128 * 1024 * 1024, // 128MB
+            timeout: Duration::from_secs(30),
+            concurrency: options.max_concurrency,
+            wake_triggers: vec![trigger.clone()],
+            environment: HashMap::new(),
+        };
+        let code = DeployedCode {
+            code,
+            trigger,
+            idle_timeout: options.idle_timeout.unwrap_or(self.config.hibernation_timeout),
+            max_concurrency: options.max_concurrency,
+            idle: Vec::new(),
+            running: 0,
+            invocations: 0,
+            cold_starts: 0,
+            rejected: 0,
+        };
+        self.functions.write().unwrap().insert(name.to_string(), function);
+        self.code.write().unwrap().insert(name.to_string(), Arc::new(Mutex::new(code)));
+    }
+
+    /// Invoke a deployed function, returning its response.
+    ///
+    /// A warm instance of a bundle is reused if one is idle; otherwise the
+    /// invocation cold-starts a new one. An instance whose invocation fails is
+    /// dropped. TLisp functions restore a fresh interpreter from their
+    /// snapshot for every invocation.
+    pub fn invoke(&self, name: &str, payload: Vec<u8>) -> RuntimeResult<Vec<u8>> {
+        let code = self.code.read().unwrap().get(name).cloned()
+            .ok_or_else(|| RuntimeError::Serverless(format!("Function not found: {}", name)))?;
+
+        let start = Instant::now();
+        let (deployed, warm) = {
+            let mut code = code.lock().unwrap();
+            code.reap(start);
+            if code.running >= code.max_concurrency {
+                code.rejected += 1;
+                return Err(RuntimeError::Serverless(format!(
+                    "Function {} is at its concurrency limit of {}", name, code.max_concurrency
+                )));
+            }
+            code.running += 1;
+            code.invocations += 1;
+            let warm = code.idle.pop();
+            if warm.is_none() {
+                code.cold_starts += 1;
+            }
+            (code.code.clone(), warm)
+        };
+
+        let (vm, result) = match &deployed {
+            FunctionCode::Bundle(bundle) => {
+                let started = match warm {
+                    Some(instance) => Ok(instance.vm),
+                    None => {
+                        let started = cold_start(bundle);
+                        self.metrics.record_cold_start(name, start.elapsed());
+                        started
+                    }
+                };
+                match started {
+                    Ok(mut vm) => {
+                        let result = run_entry(&mut vm, bundle, payload);
+                        (result.is_ok().then_some(vm), result)
+                    }
+                    Err(e) => (None, Err(e)),
+                }
+            }
+            FunctionCode::Tlisp(snapshot) => {
+                let mut interpreter = snapshot.restore();
+                self.metrics.record_cold_start(name, start.elapsed());
+                (None, run_handler(&mut interpreter, payload))
+            }
+        };
+
+        let mut code = code.lock().unwrap();
+        code.running -= 1;
+        // Redeploying replaces the code, so instances of the old bundle are not kept
+        if let Some(vm) = vm.filter(|_| code.code.same(&deployed)) {
+            code.idle.push(Instance { vm, last_used: Instant::now() });
+        }
+        drop(code);
+
+        self.metrics.record_function_invocation(name, start.elapsed(), result.is_ok());
+        result
+    }
+
+    /// Function whose HTTP trigger matches a request
+    pub fn route_http(&self, method: &str, path: &str) -> Option<String> {
+        self.code.read().unwrap().iter()
+            .find(|(_, code)| matches!(
+                &code.lock().unwrap().trigger,
+                WakeTrigger::HttpRequest { path: route, method: route_method }
+                    if route == path && route_method.eq_ignore_ascii_case(method)
+            ))
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Invoke the function whose HTTP trigger matches a request
+    pub fn invoke_http(&self, method: &str, path: &str, body: Vec<u8>) -> RuntimeResult<Vec<u8>> {
+        let name = self.route_http(method, path)
+            .ok_or_else(|| RuntimeError::Serverless(format!("No function is triggered by {} {}", method, path)))?;
+        self.invoke(&name, body)
+    }
+
+    /// Drop the instances idle past their function's idle timeout, so idle
+    /// functions scale to zero; returns how many were dropped
+    pub fn scale_to_zero(&self) -> usize {
+        let now = Instant::now();
+        let code: Vec<_> = self.code.read().unwrap().values().cloned().collect();
+        code.iter().map(|code| code.lock().unwrap().reap(now)).sum()
+    }
+
+    /// The deployed functions, by name
+    pub fn function_info(&self) -> Vec<FunctionInfo> {
+        let mut functions: Vec<FunctionInfo> = self.code.read().unwrap().iter()
+            .map(|(name, code)| code.lock().unwrap().info(name))
+            .collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        functions
+    }
+
     /// Undeploy a serverless function
     pub fn undeploy_function(&self, name: &str) -> RuntimeResult<()> {
         self.functions.write().unwrap().remove(name);
-        println!("Undeployed serverless function: {}", name);
+        self.code.write().unwrap().remove(name);
+        tracing::info!(function = name, "Undeployed serverless function");
         Ok(())
     }
     
@@ -114,6 +498,10 @@ impl ServerlessReamRuntime {
     pub async fn invoke_function(&self, name: &str, payload: Vec<u8>) -> RuntimeResult<Vec<u8>> {
         let start = Instant::now();
         
+        if self.code.read().unwrap().contains_key(name) {
+            return self.invoke(name, payload);
+        }
+
         // Get function definition
         let function = {
             let functions = self.functions.read().unwrap();
@@ -148,7 +536,7 @@ impl ServerlessReamRuntime {
         let wake_time = self.cold_start_optimizer.instant_wake(pid, &function.actor_type)
             .map_err(|e| crate::error::RuntimeError::Serverless(format!("Cold start failed: {}", e)))?;
         
-        println!("Cold start completed in {:?}", wake_time);
+        tracing::debug!(?wake_time, "Cold start completed");
         
         // Simulate function execution
         let result = self.execute_function_logic(function, payload).await?;
@@ -170,7 +558,7 @@ impl ServerlessReamRuntime {
         let _actor_type = self.hibernation_manager.wake_actor(pid, wake_trigger).await
             .map_err(|e| crate::error::RuntimeError::Serverless(format!("Wake failed: {}", e)))?;
         
-        println!("Warm start completed for actor {:?}", pid);
+        tracing::debug!(%pid, "Warm start completed");
         
         // Execute function
         let result = self.execute_function_logic(function, payload).await?;
@@ -205,7 +593,7 @@ impl ServerlessReamRuntime {
     /// Scale function instances
     pub fn scale_function(&self, name: &str, instances: usize) -> RuntimeResult<()> {
         // In a real implementation, this would manage the number of pre-warmed instances
-        println!("Scaling function {} to {} instances", name, instances);
+        tracing::info!(function = name, instances, "Scaling function");
         Ok(())
     }
     
@@ -263,7 +651,7 @@ impl ServerlessReamRuntime {
         // Store deployment
         self.deployments.write().unwrap().insert(deployment_name.clone(), deployment);
         
-        println!("Deployed serverless deployment: {}", deployment_name);
+        tracing::info!(deployment = %deployment_name, "Deployed serverless deployment");
         Ok(())
     }
     
@@ -279,6 +667,11 @@ impl ServerlessReamRuntime {
 }
 
 impl ServerlessRuntime for ServerlessReamRuntime {
+    fn deploy(&mut self, name: &str, bundle: BytecodeBundle, trigger: WakeTrigger) -> ServerlessResult<()> {
+        ServerlessReamRuntime::deploy(self, name, bundle, trigger)
+            .map_err(|e| ServerlessError::Deployment(format!("{}", e)))
+    }
+
     fn deploy_function(&mut self, function: ServerlessFunction) -> ServerlessResult<()> {
         ServerlessReamRuntime::deploy_function(self, function)
             .map_err(|e| ServerlessError::Deployment(format!("{}", e)))
@@ -289,10 +682,14 @@ impl ServerlessRuntime for ServerlessReamRuntime {
             .map_err(|e| ServerlessError::Deployment(format!("{}", e)))
     }
 
-    fn invoke_function(&mut self, name: &str, _payload: Vec<u8>) -> ServerlessResult<Vec<u8>> {
-        // This is a synchronous interface, so we can't use async here
-        // In a real implementation, this would use a runtime executor
-        Err(ServerlessError::Deployment("Async invocation not supported in sync interface".to_string()))
+    fn invoke_function(&mut self, name: &str, payload: Vec<u8>) -> ServerlessResult<Vec<u8>> {
+        // Functions deployed from bundles run synchronously; the others are
+        // only invocable through the async interface
+        if !self.code.read().unwrap().contains_key(name) {
+            return Err(ServerlessError::Deployment("Async invocation not supported in sync interface".to_string()));
+        }
+        ServerlessReamRuntime::invoke(self, name, payload)
+            .map_err(|e| ServerlessError::Deployment(format!("{}", e)))
     }
 
     fn get_function_metrics(&self, name: &str) -> ServerlessResult<FunctionMetrics> {
@@ -1,14 +1,16 @@
 //! Supervision trees as initial algebra of process hierarchies
 
 use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::time::{Duration, Instant};
-use crate::types::{Pid, RestartStrategy};
+use serde::{Deserialize, Serialize};
+use crate::types::{Pid, ProcessState, RestartStrategy};
 use crate::error::{RuntimeError, RuntimeResult};
 use crate::runtime::process::ProcessHandle;
 
 
 /// Restart policy for child processes
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RestartPolicy {
     /// Always restart the child
     Permanent,
@@ -34,7 +36,7 @@ pub struct ChildSpec {
 }
 
 /// Type of child process
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChildType {
     /// Worker process
     Worker,
@@ -349,6 +351,126 @@ impl ProcessTree {
     }
 }
 
+/// Serializable snapshot of one node in a supervision hierarchy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SupervisionNode {
+    /// Node PID
+    pub pid: Pid,
+    /// Supervisor name or child identifier
+    pub name: String,
+    /// Whether the node is a worker or a supervisor
+    pub kind: ChildType,
+    /// Restart strategy (supervisors only)
+    pub strategy: Option<RestartStrategy>,
+    /// Restart policy (workers only)
+    pub restart_policy: Option<RestartPolicy>,
+    /// Restarts performed so far
+    pub restarts: u32,
+    /// Restart limit for this node
+    pub max_restarts: u32,
+    /// Live process state, if the runtime knows the process
+    pub state: Option<ProcessState>,
+    /// Child nodes in start order
+    pub children: Vec<SupervisionNode>,
+}
+
+impl SupervisionNode {
+    /// Build a snapshot from a process tree
+    pub fn from_tree(tree: &ProcessTree) -> Self {
+        tree.cata(&|node, children| match node {
+            ProcessTree::Process { pid, spec } => SupervisionNode {
+                pid: *pid,
+                name: spec.id.clone(),
+                kind: spec.child_type,
+                strategy: None,
+                restart_policy: Some(spec.restart_policy),
+                restarts: 0,
+                max_restarts: spec.max_restart_intensity,
+                state: None,
+                children,
+            },
+            ProcessTree::Supervisor { pid, spec, state, .. } => SupervisionNode {
+                pid: *pid,
+                name: spec.name.clone(),
+                kind: ChildType::Supervisor,
+                strategy: Some(spec.strategy),
+                restart_policy: None,
+                restarts: state.restart_count,
+                max_restarts: spec.max_restarts,
+                state: None,
+                children,
+            },
+        })
+    }
+
+    /// Total number of nodes in this subtree
+    pub fn count(&self) -> usize {
+        1 + self.children.iter().map(SupervisionNode::count).sum::<usize>()
+    }
+
+    /// Visit every node in this subtree, parents before children
+    pub fn for_each_mut<F: FnMut(&mut SupervisionNode)>(&mut self, f: &mut F) {
+        f(self);
+        for child in &mut self.children {
+            child.for_each_mut(f);
+        }
+    }
+
+    /// Render the subtree as ASCII art, one node per line
+    pub fn render_ascii(&self) -> String {
+        let mut out = String::new();
+        self.render_into(&mut out, "", "");
+        out
+    }
+
+    fn render_into(&self, out: &mut String, head: &str, tail: &str) {
+        let _ = writeln!(out, "{}{}", head, self.label());
+        for (i, child) in self.children.iter().enumerate() {
+            let (branch, indent) = if i + 1 == self.children.len() {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            child.render_into(out, &format!("{}{}", tail, branch), &format!("{}{}", tail, indent));
+        }
+    }
+
+    fn label(&self) -> String {
+        let mut label = format!("{} <{}>", self.name, self.pid);
+        match (self.kind, self.strategy, self.restart_policy) {
+            (ChildType::Supervisor, Some(strategy), _) => {
+                let _ = write!(label, " [supervisor, {}]", strategy_name(strategy));
+            }
+            (ChildType::Supervisor, None, _) => label.push_str(" [supervisor]"),
+            (ChildType::Worker, _, Some(policy)) => {
+                let _ = write!(label, " [worker, {}]", policy_name(policy));
+            }
+            (ChildType::Worker, _, None) => label.push_str(" [worker]"),
+        }
+        if let Some(state) = self.state {
+            let _ = write!(label, " {:?}", state);
+        }
+        let _ = write!(label, " restarts {}/{}", self.restarts, self.max_restarts);
+        label
+    }
+}
+
+fn strategy_name(strategy: RestartStrategy) -> &'static str {
+    match strategy {
+        RestartStrategy::OneForOne => "one_for_one",
+        RestartStrategy::OneForAll => "one_for_all",
+        RestartStrategy::RestForOne => "rest_for_one",
+    }
+}
+
+fn policy_name(policy: RestartPolicy) -> &'static str {
+    match policy {
+        RestartPolicy::Permanent => "permanent",
+        RestartPolicy::Transient => "transient",
+        RestartPolicy::Temporary => "temporary",
+    }
+}
+
 /// Child process information
 #[derive(Clone)]
 struct ChildInfo {
@@ -532,6 +654,23 @@ impl Supervisor {
         &self.tree
     }
     
+    /// Snapshot the supervision hierarchy with live restart counts
+    ///
+    /// Children that have been unsupervised are left out.
+    pub fn snapshot(&self) -> SupervisionNode {
+        let mut root = SupervisionNode::from_tree(&self.tree);
+        root.restarts = self.stats.restarts_performed;
+        root.children.retain(|child| {
+            child.kind == ChildType::Supervisor || self.children.contains_key(&child.pid)
+        });
+        for child in &mut root.children {
+            if let Some(info) = self.children.get(&child.pid) {
+                child.restarts = info.restart_count;
+            }
+        }
+        root
+    }
+
     /// Check if a process is supervised
     pub fn is_supervised(&self, pid: Pid) -> bool {
         self.children.contains_key(&pid)
@@ -769,4 +908,56 @@ mod tests {
         // Test finding processes
         assert!(root_supervisor.find_process(worker_pid).is_some());
     }
+
+    fn counter_handle(pid: Pid) -> ProcessHandle {
+        let actor = CounterActor::new(pid, 0);
+        ProcessHandle::new(Process::new(pid, Box::new(actor), crate::types::Priority::Normal))
+    }
+
+    #[test]
+    fn test_supervisor_snapshot() {
+        let mut supervisor = Supervisor::new(RestartStrategy::OneForAll);
+        let kept = Pid::new();
+        let dropped = Pid::new();
+        supervisor.supervise(kept, counter_handle(kept)).unwrap();
+        supervisor.supervise(dropped, counter_handle(dropped)).unwrap();
+        supervisor.unsupervise(dropped).unwrap();
+        supervisor.children.get_mut(&kept).unwrap().record_restart();
+
+        let snapshot = supervisor.snapshot();
+        assert_eq!(snapshot.pid, supervisor.pid());
+        assert_eq!(snapshot.kind, ChildType::Supervisor);
+        assert_eq!(snapshot.strategy, Some(RestartStrategy::OneForAll));
+        assert_eq!(snapshot.count(), 2);
+        assert_eq!(snapshot.children[0].pid, kept);
+        assert_eq!(snapshot.children[0].restarts, 1);
+        assert_eq!(snapshot.children[0].restart_policy, Some(RestartPolicy::Permanent));
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let parsed: SupervisionNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+
+    #[test]
+    fn test_supervision_tree_ascii() {
+        let root_spec = SupervisorSpec::new("root".to_string());
+        let nested_spec = SupervisorSpec::new("pool".to_string()).strategy(RestartStrategy::RestForOne);
+        let mut nested = ProcessTree::supervisor(Pid::new(), nested_spec);
+        nested.add_child(ProcessTree::process(Pid::new(), ChildSpec::new("conn".to_string()))).unwrap();
+        let mut tree = ProcessTree::supervisor(Pid::new(), root_spec);
+        tree.add_child(nested).unwrap();
+        let worker = ChildSpec::new("cache".to_string()).restart_policy(RestartPolicy::Transient);
+        tree.add_child(ProcessTree::process(Pid::new(), worker)).unwrap();
+
+        let rendered = SupervisionNode::from_tree(&tree).render_ascii();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("root <"));
+        assert!(lines[0].contains("[supervisor, one_for_one]"));
+        assert!(lines[1].starts_with("├── pool <"));
+        assert!(lines[1].contains("rest_for_one"));
+        assert!(lines[2].starts_with("│   └── conn <"));
+        assert!(lines[3].starts_with("└── cache <"));
+        assert!(lines[3].contains("[worker, transient] restarts 0/5"));
+    }
 }
@@ -0,0 +1,241 @@
+//! CPU topology and worker placement
+//!
+//! On machines with many cores, where a scheduler worker runs matters: a
+//! worker that hops between cores loses its caches, and one whose memory
+//! lives on another NUMA node pays for every access across the
+//! interconnect. This module reads the machine's NUMA nodes, decides which
+//! core each worker runs on and pins it there, and records the node the
+//! current thread runs on so per-process heaps can be drawn from arenas
+//! local to it.
+
+use std::cell::Cell;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use crate::error::{RuntimeError, RuntimeResult};
+
+/// Placement of scheduler workers and their memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TopologyConfig {
+    /// Pin each worker thread to one core
+    pub pin_workers: bool,
+    /// Cores workers may be pinned to, in order; all cores when empty
+    pub cores: Vec<usize>,
+    /// Draw per-process heaps from arenas local to the worker's NUMA node
+    pub numa_local_heaps: bool,
+    /// Prefer running an actor on the worker that last ran it
+    pub cache_affinity: bool,
+}
+
+impl Default for TopologyConfig {
+    fn default() -> Self {
+        TopologyConfig {
+            pin_workers: false,
+            cores: Vec::new(),
+            numa_local_heaps: false,
+            cache_affinity: true,
+        }
+    }
+}
+
+/// A NUMA node and the cores attached to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NumaNode {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+}
+
+/// NUMA nodes of the machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuTopology {
+    pub nodes: Vec<NumaNode>,
+}
+
+impl CpuTopology {
+    /// Topology of this machine, read once
+    pub fn system() -> &'static CpuTopology {
+        static SYSTEM: OnceLock<CpuTopology> = OnceLock::new();
+        SYSTEM.get_or_init(Self::detect)
+    }
+
+    /// Read the NUMA nodes from sysfs, or treat the machine as a single node
+    /// where that isn't available
+    pub fn detect() -> Self {
+        Self::from_sysfs("/sys/devices/system/node")
+            .unwrap_or_else(|| Self::single_node(num_cpus::get()))
+    }
+
+    /// One node holding cores `0..cpus`
+    pub fn single_node(cpus: usize) -> Self {
+        CpuTopology {
+            nodes: vec![NumaNode { id: 0, cpus: (0..cpus.max(1)).collect() }],
+        }
+    }
+
+    fn from_sysfs(root: &str) -> Option<Self> {
+        let mut nodes = Vec::new();
+        for entry in std::fs::read_dir(root).ok()?.flatten() {
+            let name = entry.file_name();
+            let Some(id) = name.to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|id| id.parse().ok())
+            else {
+                continue;
+            };
+            let cpulist = std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            let cpus = parse_cpu_list(&cpulist)?;
+            // Memory-only nodes have no cores to run workers on
+            if !cpus.is_empty() {
+                nodes.push(NumaNode { id, cpus });
+            }
+        }
+        nodes.sort_by_key(|node| node.id);
+        (!nodes.is_empty()).then_some(CpuTopology { nodes })
+    }
+
+    /// Node `cpu` belongs to
+    pub fn node_of(&self, cpu: usize) -> Option<usize> {
+        self.nodes.iter().find(|node| node.cpus.contains(&cpu)).map(|node| node.id)
+    }
+
+    /// Every core, node by node
+    pub fn cpus(&self) -> Vec<usize> {
+        self.nodes.iter().flat_map(|node| node.cpus.iter().copied()).collect()
+    }
+
+    /// Core for each of `workers` workers. Cores are taken node by node, so
+    /// neighbouring workers, which steal from one another first, share a
+    /// node; with more workers than cores, cores are shared round-robin.
+    pub fn placement(&self, workers: usize, config: &TopologyConfig) -> Vec<usize> {
+        let cores = if config.cores.is_empty() { self.cpus() } else { config.cores.clone() };
+        if cores.is_empty() {
+            return Vec::new();
+        }
+        (0..workers).map(|worker| cores[worker % cores.len()]).collect()
+    }
+}
+
+/// Parse a kernel CPU list such as `0-3,8,10-11`
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+                if start > end {
+                    return None;
+                }
+                cpus.extend(start..=end);
+            }
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// Restrict the calling thread to `core`
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(core: usize) -> RuntimeResult<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(RuntimeError::Scheduler(format!("core {} is out of range", core)));
+    }
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        return Err(RuntimeError::Scheduler(format!(
+            "cannot pin thread to core {}: {}", core, std::io::Error::last_os_error()
+        )));
+    }
+    CURRENT_NODE.with(|node| node.set(CpuTopology::system().node_of(core)));
+    Ok(())
+}
+
+/// Restrict the calling thread to `core`
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(core: usize) -> RuntimeResult<()> {
+    Err(RuntimeError::Scheduler(format!("cannot pin thread to core {}: not supported on this platform", core)))
+}
+
+thread_local! {
+    static CURRENT_NODE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// NUMA node the calling thread runs on: the node it was pinned to, or
+/// else the node of the core it happens to be on now
+pub fn current_node() -> Option<usize> {
+    CURRENT_NODE.with(Cell::get).or_else(|| current_cpu().and_then(|cpu| CpuTopology::system().node_of(cpu)))
+}
+
+/// Core the calling thread is running on
+#[cfg(target_os = "linux")]
+pub fn current_cpu() -> Option<usize> {
+    let cpu = unsafe { libc::sched_getcpu() };
+    (cpu >= 0).then_some(cpu as usize)
+}
+
+/// Core the calling thread is running on
+#[cfg(not(target_os = "linux"))]
+pub fn current_cpu() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_nodes() -> CpuTopology {
+        CpuTopology {
+            nodes: vec![
+                NumaNode { id: 0, cpus: vec![0, 1, 4, 5] },
+                NumaNode { id: 1, cpus: vec![2, 3, 6, 7] },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+        assert_eq!(parse_cpu_list(""), Some(vec![]));
+        assert_eq!(parse_cpu_list("3-1"), None);
+        assert_eq!(parse_cpu_list("a"), None);
+    }
+
+    #[test]
+    fn test_placement_fills_nodes_in_order() {
+        let topology = two_nodes();
+        let config = TopologyConfig::default();
+        assert_eq!(topology.placement(6, &config), vec![0, 1, 4, 5, 2, 3]);
+        assert_eq!(topology.placement(10, &config)[8..], [0, 1]);
+
+        let config = TopologyConfig { cores: vec![6, 7], ..Default::default() };
+        assert_eq!(topology.placement(3, &config), vec![6, 7, 6]);
+        assert_eq!(topology.node_of(6), Some(1));
+        assert_eq!(topology.node_of(9), None);
+    }
+
+    #[test]
+    fn test_system_topology() {
+        let topology = CpuTopology::system();
+        assert!(!topology.cpus().is_empty());
+        if let Some(cpu) = current_cpu() {
+            assert!(topology.node_of(cpu).is_some());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pin_current_thread() {
+        let core = CpuTopology::system().cpus()[0];
+        std::thread::spawn(move || {
+            // Sandboxes may forbid changing affinity; only check the outcome
+            // when pinning was allowed
+            if pin_current_thread(core).is_ok() {
+                assert_eq!(current_cpu(), Some(core));
+                assert_eq!(current_node(), CpuTopology::system().node_of(core));
+            }
+        }).join().unwrap();
+    }
+}
@@ -0,0 +1,690 @@
+//! Saga workflows
+//!
+//! A saga is a sequence of steps, each an action with an optional
+//! compensating action that undoes it. A workflow runs the steps of a saga
+//! in order; when a step fails for good, the steps that completed are
+//! compensated in reverse order. Each step may be retried, with a delay that
+//! doubles after every failed attempt, and may be given a timeout after
+//! which an attempt counts as failed.
+//!
+//! Steps see the workflow's context, a JSON object holding the workflow's
+//! input under `"input"` and the output of each completed step under the
+//! step's name, and return their output.
+//!
+//! Progress is checkpointed in a journal of the engine's `EventStore`, one
+//! per workflow, before and after every attempt. After a crash,
+//! `WorkflowEngine::recover` resumes the unfinished workflows of the
+//! registered sagas where their journals left off; a step that was running
+//! when the crash happened runs again, so steps should be idempotent.
+//!
+//! Sagas are built in Rust with `Saga::new` or in TLisp with `defsaga`:
+//!
+//! ```lisp
+//! (defsaga order
+//!   (list "reserve" reserve-stock release-stock)
+//!   (list "charge" charge-card refund-card 3 5000))
+//! ```
+//!
+//! where each step is a name, an action, a compensation or `#f`, and
+//! optionally the retries and a timeout in milliseconds. TLisp steps are
+//! called with the context as a list of `(key value)` pairs.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{RuntimeError, RuntimeResult};
+use crate::runtime::event_sourcing::{EventSourced, EventStore, Journal};
+use crate::tlisp::rust_crate_integration::{json_to_value, value_to_json};
+use crate::tlisp::{TlispInterpreter, Value as TlispValue};
+use crate::types::MessagePayload;
+
+/// Global list of `(name steps)` entries registered by the TLisp `defsaga`
+/// builtin
+pub const SAGA_REGISTRY: &str = "*sagas*";
+
+/// Prefix of the persistence ids of workflow journals
+const JOURNAL_PREFIX: &str = "workflow/";
+
+/// Delay before the first retry of a step unless set otherwise
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// A step's action or compensation, given the workflow's context
+pub type StepFn = Arc<dyn Fn(&Value) -> RuntimeResult<Value> + Send + Sync>;
+
+/// A step of a saga
+#[derive(Clone)]
+pub struct Step {
+    pub name: String,
+    action: StepFn,
+    compensation: Option<StepFn>,
+    /// Attempts made after the first fails
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub retry_delay: Duration,
+    /// How long an attempt may take before it counts as failed
+    pub timeout: Option<Duration>,
+}
+
+impl Step {
+    pub fn new(name: impl Into<String>, action: impl Fn(&Value) -> RuntimeResult<Value> + Send + Sync + 'static) -> Self {
+        Self {
+            name: name.into(),
+            action: Arc::new(action),
+            compensation: None,
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            timeout: None,
+        }
+    }
+
+    /// Undo the step with `compensation` when a later step fails
+    pub fn compensate(mut self, compensation: impl Fn(&Value) -> RuntimeResult<Value> + Send + Sync + 'static) -> Self {
+        self.compensation = Some(Arc::new(compensation));
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Delay before attempt number `attempt`, counting from 1
+    fn delay_before(&self, attempt: u32) -> Duration {
+        self.retry_delay.saturating_mul(1 << attempt.saturating_sub(2).min(16))
+    }
+}
+
+impl std::fmt::Debug for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Step")
+            .field("name", &self.name)
+            .field("compensated", &self.compensation.is_some())
+            .field("retries", &self.retries)
+            .field("retry_delay", &self.retry_delay)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// A named sequence of steps
+#[derive(Debug, Clone)]
+pub struct Saga {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+impl Saga {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), steps: Vec::new() }
+    }
+
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// The sagas registered with `defsaga` in an interpreter, whose steps
+    /// take turns on the interpreter
+    pub fn registered(interpreter: &Arc<Mutex<TlispInterpreter>>) -> RuntimeResult<Vec<Saga>> {
+        let malformed = || RuntimeError::TlispError(format!("Malformed {} entry", SAGA_REGISTRY));
+        let entries = match interpreter.lock().unwrap().get(SAGA_REGISTRY) {
+            Some(TlispValue::List(entries)) => entries,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut sagas = Vec::new();
+        for entry in entries {
+            let TlispValue::List(parts) = entry else { return Err(malformed()) };
+            let [TlispValue::String(name), TlispValue::List(steps)] = parts.as_slice() else { return Err(malformed()) };
+            let mut saga = Saga::new(name.clone());
+            for step in steps {
+                let TlispValue::List(parts) = step else { return Err(malformed()) };
+                let [TlispValue::String(name), action, compensation, TlispValue::Int(retries), timeout] = parts.as_slice() else {
+                    return Err(malformed());
+                };
+                let mut step = Step::new(name.clone(), tlisp_step(interpreter, action.clone())).retries(*retries as u32);
+                if !matches!(compensation, TlispValue::Null) {
+                    step = step.compensate(tlisp_step(interpreter, compensation.clone()));
+                }
+                if let TlispValue::Int(millis) = timeout {
+                    step = step.timeout(Duration::from_millis(*millis as u64));
+                }
+                saga = saga.step(step);
+            }
+            sagas.push(saga);
+        }
+        Ok(sagas)
+    }
+}
+
+/// A step calling a TLisp function of one argument, the context
+fn tlisp_step(interpreter: &Arc<Mutex<TlispInterpreter>>, function: TlispValue) -> impl Fn(&Value) -> RuntimeResult<Value> + Send + Sync + 'static {
+    let interpreter = interpreter.clone();
+    move |context| {
+        let failed = |e: crate::error::TlispError| RuntimeError::TlispError(e.to_string());
+        let context = json_to_value(context).map_err(failed)?;
+        let result = interpreter.lock().unwrap().call(&function, &[context]).map_err(failed)?;
+        value_to_json(&result).map_err(failed)
+    }
+}
+
+/// Where a workflow is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkflowStatus {
+    /// Running its steps
+    Running,
+    /// Undoing its completed steps after one failed
+    Compensating,
+    /// Every step completed
+    Completed,
+    /// A step failed and the completed ones were undone
+    Compensated,
+    /// A step failed and a completed one could not be undone
+    Failed,
+}
+
+impl WorkflowStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, WorkflowStatus::Completed | WorkflowStatus::Compensated | WorkflowStatus::Failed)
+    }
+}
+
+impl std::fmt::Display for WorkflowStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            WorkflowStatus::Running => "running",
+            WorkflowStatus::Compensating => "compensating",
+            WorkflowStatus::Completed => "completed",
+            WorkflowStatus::Compensated => "compensated",
+            WorkflowStatus::Failed => "failed",
+        };
+        f.write_str(status)
+    }
+}
+
+/// Where a step of a workflow is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Compensated,
+    CompensationFailed,
+}
+
+/// Progress of a step of a workflow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepInfo {
+    pub name: String,
+    pub status: StepStatus,
+    /// Attempts started, across restarts
+    pub attempts: u32,
+    pub output: Option<Value>,
+    /// Error of the last failed attempt or compensation
+    pub error: Option<String>,
+}
+
+/// Progress of a workflow, as recovered from its journal
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct WorkflowInfo {
+    pub id: String,
+    pub saga: String,
+    pub status: Option<WorkflowStatus>,
+    pub input: Value,
+    pub steps: Vec<StepInfo>,
+    /// Why the workflow is compensating or failed
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl WorkflowInfo {
+    /// The context steps see: the input and the completed steps' outputs
+    pub fn context(&self) -> Value {
+        let mut context = serde_json::Map::new();
+        context.insert("input".to_string(), self.input.clone());
+        for step in &self.steps {
+            if let Some(output) = &step.output {
+                context.insert(step.name.clone(), output.clone());
+            }
+        }
+        Value::Object(context)
+    }
+
+    fn step_mut(&mut self, name: &str) -> Option<&mut StepInfo> {
+        self.steps.iter_mut().find(|step| step.name == name)
+    }
+}
+
+/// Checkpoints written to a workflow's journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum WorkflowEvent {
+    Started { id: String, saga: String, input: Value, steps: Vec<String> },
+    StepStarted { step: String, attempt: u32 },
+    StepCompleted { step: String, output: Value },
+    StepFailed { step: String, error: String },
+    Compensating { step: String, error: String },
+    Compensated { step: String },
+    CompensationFailed { step: String, error: String },
+    Finished { status: WorkflowStatus },
+}
+
+impl EventSourced for WorkflowInfo {
+    type Event = WorkflowEvent;
+
+    fn handle(&self, message: &MessagePayload) -> RuntimeResult<Vec<WorkflowEvent>> {
+        Err(RuntimeError::InvalidMessage(format!("workflow {} is driven by its engine, not by {:?}", self.id, message)))
+    }
+
+    fn apply(&mut self, event: &WorkflowEvent) {
+        match event {
+            WorkflowEvent::Started { id, saga, input, steps } => {
+                self.id = id.clone();
+                self.saga = saga.clone();
+                self.input = input.clone();
+                self.status = Some(WorkflowStatus::Running);
+                self.steps = steps.iter().map(|name| StepInfo {
+                    name: name.clone(),
+                    status: StepStatus::Pending,
+                    attempts: 0,
+                    output: None,
+                    error: None,
+                }).collect();
+            }
+            WorkflowEvent::StepStarted { step, attempt } => if let Some(step) = self.step_mut(step) {
+                step.status = StepStatus::Running;
+                step.attempts = *attempt;
+            },
+            WorkflowEvent::StepCompleted { step, output } => if let Some(step) = self.step_mut(step) {
+                step.status = StepStatus::Completed;
+                step.output = Some(output.clone());
+                step.error = None;
+            },
+            WorkflowEvent::StepFailed { step, error } => if let Some(step) = self.step_mut(step) {
+                step.status = StepStatus::Failed;
+                step.error = Some(error.clone());
+            },
+            WorkflowEvent::Compensating { step, error } => {
+                self.status = Some(WorkflowStatus::Compensating);
+                self.error = Some(format!("step {} failed: {}", step, error));
+            }
+            WorkflowEvent::Compensated { step } => if let Some(step) = self.step_mut(step) {
+                step.status = StepStatus::Compensated;
+            },
+            WorkflowEvent::CompensationFailed { step, error } => if let Some(step) = self.step_mut(step) {
+                step.status = StepStatus::CompensationFailed;
+                step.error = Some(error.clone());
+            },
+            WorkflowEvent::Finished { status } => self.status = Some(*status),
+        }
+    }
+}
+
+/// Run one attempt of `action`, failing it if it outlasts `timeout`
+///
+/// An attempt that times out keeps running on its own thread, but what it
+/// returns is ignored.
+fn attempt(action: &StepFn, context: Value, timeout: Option<Duration>) -> Result<Value, String> {
+    let Some(timeout) = timeout else {
+        return action(&context).map_err(|e| e.to_string());
+    };
+    let (sender, receiver) = mpsc::channel();
+    let action = action.clone();
+    thread::Builder::new()
+        .name("workflow-step".to_string())
+        .spawn(move || {
+            let _ = sender.send(action(&context));
+        })
+        .map_err(|e| format!("could not start the step: {}", e))?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(format!("timed out after {:?}", timeout)),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err("the step panicked".to_string()),
+    }
+}
+
+/// Runs workflows of registered sagas, checkpointing them in an event store
+pub struct WorkflowEngine {
+    store: Arc<dyn EventStore>,
+    sagas: RwLock<HashMap<String, Arc<Saga>>>,
+    /// Workflows being driven by this engine
+    running: Mutex<HashSet<String>>,
+}
+
+impl WorkflowEngine {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self {
+            store,
+            sagas: RwLock::new(HashMap::new()),
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Register a saga, replacing any saga with the same name
+    pub fn register(&self, saga: Saga) {
+        self.sagas.write().unwrap().insert(saga.name.clone(), Arc::new(saga));
+    }
+
+    /// Names of the registered sagas
+    pub fn sagas(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.sagas.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn saga(&self, name: &str) -> RuntimeResult<Arc<Saga>> {
+        self.sagas.read().unwrap().get(name).cloned()
+            .ok_or_else(|| RuntimeError::RuntimeError(format!("no saga named {}", name)))
+    }
+
+    /// Begin a workflow of `saga`, returning its id once its start is
+    /// checkpointed; the steps run on a thread of their own
+    pub fn start(self: &Arc<Self>, saga: &str, input: Value) -> RuntimeResult<String> {
+        let (id, journal, state) = self.begin(saga, input)?;
+        self.spawn(id.clone(), journal, state)?;
+        Ok(id)
+    }
+
+    /// Run a workflow of `saga` to its end on this thread
+    pub fn run(&self, saga: &str, input: Value) -> RuntimeResult<WorkflowInfo> {
+        let (id, journal, state) = self.begin(saga, input)?;
+        self.running.lock().unwrap().insert(id.clone());
+        let result = self.drive(journal, state);
+        self.running.lock().unwrap().remove(&id);
+        result?;
+        self.inspect(&id)
+    }
+
+    fn begin(&self, saga: &str, input: Value) -> RuntimeResult<(String, Journal, WorkflowInfo)> {
+        let saga = self.saga(saga)?;
+        let id = format!("{}-{}", saga.name, &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        let mut journal = Journal::new(self.store.clone(), format!("{}{}", JOURNAL_PREFIX, id));
+        let mut state = WorkflowInfo::default();
+        journal.persist(&mut state, WorkflowEvent::Started {
+            id: id.clone(),
+            saga: saga.name.clone(),
+            input,
+            steps: saga.steps.iter().map(|step| step.name.clone()).collect(),
+        })?;
+        Ok((id, journal, state))
+    }
+
+    fn spawn(self: &Arc<Self>, id: String, journal: Journal, state: WorkflowInfo) -> RuntimeResult<()> {
+        if !self.running.lock().unwrap().insert(id.clone()) {
+            return Ok(());
+        }
+        let (engine, workflow) = (self.clone(), id.clone());
+        let spawned = thread::Builder::new()
+            .name(format!("workflow-{}", id))
+            .spawn(move || {
+                if let Err(e) = engine.drive(journal, state) {
+                    tracing::error!(workflow = %workflow, error = %e, "Workflow could not checkpoint its progress");
+                }
+                engine.running.lock().unwrap().remove(&workflow);
+            });
+        if let Err(e) = spawned {
+            self.running.lock().unwrap().remove(&id);
+            return Err(RuntimeError::RuntimeError(format!("could not start workflow {}: {}", id, e)));
+        }
+        Ok(())
+    }
+
+    /// Resume the unfinished workflows of the registered sagas, returning
+    /// their ids
+    pub fn recover(self: &Arc<Self>) -> RuntimeResult<Vec<String>> {
+        let mut resumed = Vec::new();
+        for journal in self.store.journals()? {
+            let Some(id) = journal.persistence_id.strip_prefix(JOURNAL_PREFIX) else { continue };
+            if self.running.lock().unwrap().contains(id) {
+                continue;
+            }
+            let mut journal = Journal::new(self.store.clone(), journal.persistence_id.clone());
+            let mut state = WorkflowInfo::default();
+            journal.recover(&mut state)?;
+            let unfinished = state.status.is_some_and(|status| !status.is_finished());
+            if unfinished && self.sagas.read().unwrap().contains_key(&state.saga) {
+                tracing::info!(workflow = %state.id, saga = %state.saga, "Resuming workflow");
+                resumed.push(state.id.clone());
+                self.spawn(state.id.clone(), journal, state)?;
+            }
+        }
+        Ok(resumed)
+    }
+
+    /// Run a workflow from where its state says it is to its end
+    fn drive(&self, mut journal: Journal, mut state: WorkflowInfo) -> RuntimeResult<()> {
+        let saga = self.saga(&state.saga)?;
+
+        if state.status == Some(WorkflowStatus::Running) {
+            for step in &saga.steps {
+                let info = state.steps.iter().find(|info| info.name == step.name);
+                if info.is_some_and(|info| info.status == StepStatus::Completed) {
+                    continue;
+                }
+                let mut attempts = info.map_or(0, |info| info.attempts);
+                let error = loop {
+                    attempts += 1;
+                    if attempts > 1 {
+                        thread::sleep(step.delay_before(attempts));
+                    }
+                    journal.persist(&mut state, WorkflowEvent::StepStarted { step: step.name.clone(), attempt: attempts })?;
+                    match attempt(&step.action, state.context(), step.timeout) {
+                        Ok(output) => {
+                            journal.persist(&mut state, WorkflowEvent::StepCompleted { step: step.name.clone(), output })?;
+                            break None;
+                        }
+                        Err(error) => {
+                            tracing::warn!(workflow = %state.id, step = %step.name, attempt = attempts, %error, "Workflow step failed");
+                            journal.persist(&mut state, WorkflowEvent::StepFailed { step: step.name.clone(), error: error.clone() })?;
+                            if attempts > step.retries {
+                                break Some(error);
+                            }
+                        }
+                    }
+                };
+                if let Some(error) = error {
+                    journal.persist(&mut state, WorkflowEvent::Compensating { step: step.name.clone(), error })?;
+                    break;
+                }
+            }
+        }
+
+        let status = match state.status {
+            Some(WorkflowStatus::Compensating) => self.compensate(&saga, &mut journal, &mut state)?,
+            _ => WorkflowStatus::Completed,
+        };
+        journal.persist(&mut state, WorkflowEvent::Finished { status })?;
+        Ok(())
+    }
+
+    /// Undo the completed steps, latest first
+    fn compensate(&self, saga: &Saga, journal: &mut Journal, state: &mut WorkflowInfo) -> RuntimeResult<WorkflowStatus> {
+        let mut status = WorkflowStatus::Compensated;
+        for step in saga.steps.iter().rev() {
+            if !state.steps.iter().any(|info| info.name == step.name && info.status == StepStatus::Completed) {
+                continue;
+            }
+            let Some(compensation) = &step.compensation else {
+                journal.persist(state, WorkflowEvent::Compensated { step: step.name.clone() })?;
+                continue;
+            };
+            let mut result = Err(String::new());
+            for attempts in 1..=step.retries + 1 {
+                if attempts > 1 {
+                    thread::sleep(step.delay_before(attempts));
+                }
+                result = attempt(compensation, state.context(), step.timeout);
+                if result.is_ok() {
+                    break;
+                }
+            }
+            match result {
+                Ok(_) => journal.persist(state, WorkflowEvent::Compensated { step: step.name.clone() })?,
+                Err(error) => {
+                    tracing::error!(workflow = %state.id, step = %step.name, %error, "Workflow step could not be compensated");
+                    status = WorkflowStatus::Failed;
+                    journal.persist(state, WorkflowEvent::CompensationFailed { step: step.name.clone(), error })?
+                }
+            };
+        }
+        Ok(status)
+    }
+
+    /// A workflow's progress
+    pub fn inspect(&self, id: &str) -> RuntimeResult<WorkflowInfo> {
+        let entries = self.store.events(&format!("{}{}", JOURNAL_PREFIX, id), 0)?;
+        if entries.is_empty() {
+            return Err(RuntimeError::RuntimeError(format!("no workflow {}", id)));
+        }
+        let mut state = WorkflowInfo::default();
+        for entry in &entries {
+            let event: WorkflowEvent = serde_json::from_value(entry.event.clone())
+                .map_err(|e| RuntimeError::Persistence(format!("event {} of workflow {}: {}", entry.sequence, id, e)))?;
+            state.apply(&event);
+        }
+        state.started_at = entries.first().map(|entry| entry.recorded_at);
+        state.updated_at = entries.last().map(|entry| entry.recorded_at);
+        Ok(state)
+    }
+
+    /// Every workflow in the store, oldest first
+    pub fn list(&self) -> RuntimeResult<Vec<WorkflowInfo>> {
+        let mut workflows = Vec::new();
+        for journal in self.store.journals()? {
+            if let Some(id) = journal.persistence_id.strip_prefix(JOURNAL_PREFIX) {
+                workflows.push(self.inspect(id)?);
+            }
+        }
+        workflows.sort_by_key(|workflow| workflow.started_at);
+        Ok(workflows)
+    }
+}
+
+impl std::fmt::Debug for WorkflowEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkflowEngine")
+            .field("sagas", &self.sagas())
+            .field("running", &self.running.lock().unwrap().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::event_sourcing::MemoryEventStore;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn order(amount: i64) -> Value {
+        json!({"amount": amount})
+    }
+
+    fn engine() -> Arc<WorkflowEngine> {
+        Arc::new(WorkflowEngine::new(Arc::new(MemoryEventStore::new())))
+    }
+
+    #[test]
+    fn test_failed_step_compensates_completed_steps_in_reverse() {
+        let undone = Arc::new(Mutex::new(Vec::new()));
+        let charges = Arc::new(AtomicU32::new(0));
+        let (reserve_undone, charge_undone, attempts) = (undone.clone(), undone.clone(), charges.clone());
+        let engine = engine();
+        engine.register(Saga::new("order")
+            .step(Step::new("reserve", |context| Ok(json!({"items": context["input"]["amount"]})))
+                .compensate(move |_| { reserve_undone.lock().unwrap().push("reserve"); Ok(Value::Null) }))
+            .step(Step::new("charge", |context| Ok(json!(context["reserve"]["items"].as_i64().unwrap() * 10)))
+                .compensate(move |_| { charge_undone.lock().unwrap().push("charge"); Ok(Value::Null) }))
+            .step(Step::new("ship", move |_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(RuntimeError::ActorError("no courier".to_string()))
+            }).retries(2).retry_delay(Duration::from_millis(1))));
+
+        let info = engine.run("order", order(3)).unwrap();
+        assert_eq!(info.status, Some(WorkflowStatus::Compensated));
+        assert_eq!(charges.load(Ordering::SeqCst), 3);
+        assert_eq!(*undone.lock().unwrap(), vec!["charge", "reserve"]);
+        assert_eq!(info.steps[1].output, Some(json!(30)));
+        assert_eq!(info.steps[2].attempts, 3);
+        assert!(info.error.unwrap().contains("no courier"));
+        assert_eq!(engine.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_timed_out_step_fails_and_recovery_resumes_unfinished_workflows() {
+        let store: Arc<dyn EventStore> = Arc::new(MemoryEventStore::new());
+        let engine = Arc::new(WorkflowEngine::new(store.clone()));
+        engine.register(Saga::new("slow")
+            .step(Step::new("nap", |_| { thread::sleep(Duration::from_millis(200)); Ok(Value::Null) })
+                .timeout(Duration::from_millis(10))));
+        let info = engine.run("slow", Value::Null).unwrap();
+        assert_eq!(info.status, Some(WorkflowStatus::Compensated));
+        assert!(info.steps[0].error.as_deref().unwrap().contains("timed out"));
+
+        // A workflow whose first step completed before the process died
+        let mut journal = Journal::new(store.clone(), "workflow/pay-1");
+        let mut state = WorkflowInfo::default();
+        journal.persist(&mut state, WorkflowEvent::Started {
+            id: "pay-1".to_string(), saga: "pay".to_string(), input: order(5), steps: vec!["debit".to_string(), "credit".to_string()],
+        }).unwrap();
+        journal.persist(&mut state, WorkflowEvent::StepCompleted { step: "debit".to_string(), output: json!(5) }).unwrap();
+
+        let debits = Arc::new(AtomicU32::new(0));
+        let counted = debits.clone();
+        let engine = Arc::new(WorkflowEngine::new(store));
+        engine.register(Saga::new("pay")
+            .step(Step::new("debit", move |_| { counted.fetch_add(1, Ordering::SeqCst); Ok(json!(5)) }))
+            .step(Step::new("credit", |context| Ok(context["debit"].clone()))));
+        assert_eq!(engine.recover().unwrap(), vec!["pay-1".to_string()]);
+        while !engine.inspect("pay-1").unwrap().status.unwrap().is_finished() {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let info = engine.inspect("pay-1").unwrap();
+        assert_eq!(info.status, Some(WorkflowStatus::Completed));
+        assert_eq!(info.steps[1].output, Some(json!(5)));
+        assert_eq!(debits.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_tlisp_sagas() {
+        let interpreter = Arc::new(Mutex::new(TlispInterpreter::new()));
+        interpreter
+            .lock()
+            .unwrap()
+            .eval(
+                "(defsaga greet
+                   (list \"hello\" (lambda (context) \"hi\"))
+                   (list \"fail\" (lambda (context) (car (list))) (lambda (context) 0) 1 1000))",
+            )
+            .unwrap();
+        let sagas = Saga::registered(&interpreter).unwrap();
+        assert_eq!(sagas.len(), 1);
+        assert_eq!(sagas[0].steps[1].retries, 1);
+        assert_eq!(sagas[0].steps[1].timeout, Some(Duration::from_millis(1000)));
+
+        let engine = engine();
+        engine.register(Saga::new("greet").step(sagas[0].steps[0].clone().retry_delay(Duration::ZERO)));
+        let info = engine.run("greet", Value::Null).unwrap();
+        assert_eq!(info.status, Some(WorkflowStatus::Completed));
+        assert_eq!(info.steps[0].output, Some(json!("hi")));
+    }
+}
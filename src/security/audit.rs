@@ -1,435 +1,282 @@
-//! Audit Logging System
+//! Tamper-evident audit log
 //!
-//! This module provides comprehensive audit logging for security events in the REAM system.
+//! Security-relevant events — actor spawns, permission denials, rejected
+//! bytecode, remote connections and configuration changes — are appended to
+//! a file of JSON lines, one entry per line. Every entry carries the SHA-256
+//! hash of the entry before it, so editing, removing or reordering entries
+//! breaks the chain, and reading the log fails at the first broken entry.
+//!
+//! Events are recorded with `record` once a log is installed; until then
+//! they are dropped.
 
-use std::collections::HashMap;
+use std::fmt;
 use std::fs::{File, OpenOptions};
-use std::io::{Write, BufWriter};
+use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use ring::digest::{digest, SHA256};
 use serde::{Deserialize, Serialize};
-use serde_json;
-
-/// Audit event levels
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
-pub enum AuditLevel {
-    Debug,
-    Info,
-    Warning,
-    Error,
-    Critical,
-}
-
-/// Audit event
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuditEvent {
-    pub event_type: String,
-    pub actor: String,
-    pub resource: String,
-    pub timestamp: SystemTime,
-    pub level: AuditLevel,
-    pub metadata: HashMap<String, String>,
-}
-
-/// Audit filter for controlling what gets logged
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuditFilter {
-    pub min_level: AuditLevel,
-    pub event_types: Option<Vec<String>>,
-    pub actors: Option<Vec<String>>,
-    pub resources: Option<Vec<String>>,
-}
-
-impl Default for AuditFilter {
-    fn default() -> Self {
-        AuditFilter {
-            min_level: AuditLevel::Info,
-            event_types: None,
-            actors: None,
-            resources: None,
-        }
-    }
-}
-
-/// Audit output destination
-#[derive(Debug, Clone)]
-pub enum AuditOutput {
-    File(PathBuf),
-    Console,
-    Syslog,
-    Network(String), // URL for remote logging
-}
 
-/// Audit logger configuration
-#[derive(Debug, Clone)]
-pub struct AuditConfig {
-    pub outputs: Vec<AuditOutput>,
-    pub filter: AuditFilter,
-    pub buffer_size: usize,
-    pub flush_interval_ms: u64,
-    pub max_file_size: u64,
-    pub max_files: usize,
-    pub compress_old_files: bool,
+use crate::error::{ReamError, ReamResult};
+use crate::security::secrets;
+
+/// Hash the first entry of a log is chained to
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What an audit entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditKind {
+    /// A process was spawned
+    Spawn,
+    /// A permission check failed
+    PermissionDenied,
+    /// Bytecode without a trusted signature was loaded or rejected
+    UntrustedCode,
+    /// A connection to or from another node
+    RemoteConnection,
+    /// Configuration was changed
+    ConfigChange,
 }
 
-impl Default for AuditConfig {
-    fn default() -> Self {
-        AuditConfig {
-            outputs: vec![AuditOutput::Console],
-            filter: AuditFilter::default(),
-            buffer_size: 1000,
-            flush_interval_ms: 5000,
-            max_file_size: 100 * 1024 * 1024, // 100MB
-            max_files: 10,
-            compress_old_files: true,
-        }
+impl fmt::Display for AuditKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AuditKind::Spawn => "spawn",
+            AuditKind::PermissionDenied => "denied",
+            AuditKind::UntrustedCode => "untrusted-code",
+            AuditKind::RemoteConnection => "connection",
+            AuditKind::ConfigChange => "config",
+        };
+        f.write_str(name)
     }
 }
 
-/// Audit statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuditStats {
-    pub events_logged: u64,
-    pub events_filtered: u64,
-    pub events_failed: u64,
-    pub bytes_written: u64,
-    pub files_rotated: u64,
-    pub last_event_time: Option<SystemTime>,
+/// An entry of the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, from 0
+    pub seq: u64,
+    /// When the event happened
+    pub timestamp: SystemTime,
+    /// What happened
+    pub kind: AuditKind,
+    /// Who or what it happened to: a pid, permission, address or setting
+    pub subject: String,
+    /// Description of the event
+    pub detail: String,
+    /// Hash of the previous entry
+    pub prev_hash: String,
+    /// Hash of this entry, covering every other field
+    pub hash: String,
 }
 
-impl Default for AuditStats {
-    fn default() -> Self {
-        AuditStats {
-            events_logged: 0,
-            events_filtered: 0,
-            events_failed: 0,
-            bytes_written: 0,
-            files_rotated: 0,
-            last_event_time: None,
-        }
+impl AuditEntry {
+    fn digest(&self) -> String {
+        let sealed = (self.seq, self.timestamp, self.kind, &self.subject, &self.detail, &self.prev_hash);
+        let bytes = serde_json::to_vec(&sealed).expect("audit entries serialize");
+        hex::encode(digest(&SHA256, &bytes))
     }
 }
 
-/// File writer with rotation support
-struct RotatingFileWriter {
-    base_path: PathBuf,
-    current_file: Option<BufWriter<File>>,
-    current_size: u64,
-    max_size: u64,
-    max_files: usize,
-    file_index: usize,
+/// An append-only audit log file
+pub struct AuditLog {
+    path: PathBuf,
+    file: File,
+    next_seq: u64,
+    last_hash: String,
 }
 
-impl RotatingFileWriter {
-    fn new(base_path: PathBuf, max_size: u64, max_files: usize) -> std::io::Result<Self> {
-        let mut writer = RotatingFileWriter {
-            base_path,
-            current_file: None,
-            current_size: 0,
-            max_size,
-            max_files,
-            file_index: 0,
+impl AuditLog {
+    /// Open the log at `path`, creating it readable only by its owner.
+    /// Fails if the entries already in it do not form an intact chain.
+    pub fn open(path: &Path) -> ReamResult<Self> {
+        let (next_seq, last_hash) = match read(path) {
+            Ok(entries) => match entries.last() {
+                Some(last) => (last.seq + 1, last.hash.clone()),
+                None => (0, GENESIS_HASH.to_string()),
+            },
+            Err(ReamError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => (0, GENESIS_HASH.to_string()),
+            Err(e) => return Err(e),
         };
-        writer.rotate()?;
-        Ok(writer)
-    }
 
-    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
-        if self.current_size + data.len() as u64 > self.max_size {
-            self.rotate()?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(ReamError::Io)?;
         }
+        let mut options = OpenOptions::new();
+        options.append(true).create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(path).map_err(ReamError::Io)?;
 
-        if let Some(ref mut file) = self.current_file {
-            let bytes_written = file.write(data)?;
-            self.current_size += bytes_written as u64;
-            Ok(bytes_written)
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "No current file",
-            ))
-        }
-    }
-
-    fn flush(&mut self) -> std::io::Result<()> {
-        if let Some(ref mut file) = self.current_file {
-            file.flush()
-        } else {
-            Ok(())
-        }
+        Ok(AuditLog { path: path.to_path_buf(), file, next_seq, last_hash })
     }
 
-    fn rotate(&mut self) -> std::io::Result<()> {
-        // Close current file
-        if let Some(mut file) = self.current_file.take() {
-            file.flush()?;
-        }
-
-        // Remove oldest file if we've reached the limit
-        if self.file_index >= self.max_files {
-            let oldest_path = self.get_file_path(self.file_index - self.max_files);
-            if oldest_path.exists() {
-                std::fs::remove_file(oldest_path)?;
-            }
-        }
-
-        // Create new file
-        let new_path = self.get_file_path(self.file_index);
-        if let Some(parent) = new_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(true)
-            .open(&new_path)?;
+    /// Append an event, chained to the entry before it
+    pub fn append(&mut self, kind: AuditKind, subject: &str, detail: &str) -> ReamResult<AuditEntry> {
+        let mut entry = AuditEntry {
+            seq: self.next_seq,
+            timestamp: SystemTime::now(),
+            kind,
+            subject: subject.to_string(),
+            detail: secrets::redact(detail).into_owned(),
+            prev_hash: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.digest();
 
-        self.current_file = Some(BufWriter::new(file));
-        self.current_size = 0;
-        self.file_index += 1;
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize audit entry: {}", e)))?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes()).map_err(ReamError::Io)?;
 
-        Ok(())
+        self.next_seq += 1;
+        self.last_hash = entry.hash.clone();
+        Ok(entry)
     }
 
-    fn get_file_path(&self, index: usize) -> PathBuf {
-        if index == 0 {
-            self.base_path.clone()
-        } else {
-            let mut path = self.base_path.clone();
-            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
-            let extension = path.extension().unwrap_or_default().to_string_lossy();
-            let filename = if extension.is_empty() {
-                format!("{}.{}", stem, index)
-            } else {
-                format!("{}.{}.{}", stem, index, extension)
-            };
-            path.set_file_name(filename);
-            path
-        }
+    /// File the log is written to
+    pub fn path(&self) -> &Path {
+        &self.path
     }
 }
 
-/// Audit logger
-pub struct AuditLogger {
-    config: AuditConfig,
-    file_writers: HashMap<PathBuf, Arc<Mutex<RotatingFileWriter>>>,
-    event_buffer: Arc<Mutex<Vec<AuditEvent>>>,
-    stats: Arc<Mutex<AuditStats>>,
-}
-
-impl AuditLogger {
-    /// Create a new audit logger
-    pub fn new() -> Self {
-        AuditLogger::with_config(AuditConfig::default())
-    }
-
-    /// Create a new audit logger with configuration
-    pub fn with_config(config: AuditConfig) -> Self {
-        let mut logger = AuditLogger {
-            config,
-            file_writers: HashMap::new(),
-            event_buffer: Arc::new(Mutex::new(Vec::new())),
-            stats: Arc::new(Mutex::new(AuditStats::default())),
+/// Read every entry of the log at `path`, checking the chain
+pub fn read(path: &Path) -> ReamResult<Vec<AuditEntry>> {
+    let file = File::open(path).map_err(ReamError::Io)?;
+    let mut entries: Vec<AuditEntry> = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(ReamError::Io)?;
+        let broken = |reason: String| {
+            ReamError::Other(format!("Audit log {} is broken at line {}: {}", path.display(), number + 1, reason))
         };
+        let entry: AuditEntry = serde_json::from_str(&line).map_err(|e| broken(e.to_string()))?;
 
-        // Initialize file writers
-        for output in &logger.config.outputs {
-            if let AuditOutput::File(path) = output {
-                match RotatingFileWriter::new(
-                    path.clone(),
-                    logger.config.max_file_size,
-                    logger.config.max_files,
-                ) {
-                    Ok(writer) => {
-                        logger.file_writers.insert(path.clone(), Arc::new(Mutex::new(writer)));
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to create file writer for {}: {}", path.display(), e);
-                    }
-                }
-            }
-        }
-
-        // Start background flush task
-        logger.start_flush_task();
-
-        logger
-    }
-
-    /// Log an audit event
-    pub fn log_event(&self, event: AuditEvent) {
-        // Check filter
-        if !self.should_log(&event) {
-            let mut stats = self.stats.lock().unwrap();
-            stats.events_filtered += 1;
-            return;
+        let (seq, prev_hash) = match entries.last() {
+            Some(previous) => (previous.seq + 1, previous.hash.as_str()),
+            None => (0, GENESIS_HASH),
+        };
+        if entry.seq != seq {
+            return Err(broken(format!("expected entry {}, found {}", seq, entry.seq)));
         }
-
-        // Add to buffer
-        {
-            let mut buffer = self.event_buffer.lock().unwrap();
-            buffer.push(event.clone());
-
-            // Flush if buffer is full
-            if buffer.len() >= self.config.buffer_size {
-                self.flush_buffer(&mut buffer);
-            }
+        if entry.prev_hash != prev_hash {
+            return Err(broken("not chained to the entry before it".to_string()));
         }
-
-        // Update stats
-        {
-            let mut stats = self.stats.lock().unwrap();
-            stats.events_logged += 1;
-            stats.last_event_time = Some(event.timestamp);
+        if entry.hash != entry.digest() {
+            return Err(broken("entry was modified".to_string()));
         }
+        entries.push(entry);
     }
+    Ok(entries)
+}
 
-    /// Check if an event should be logged based on filters
-    fn should_log(&self, event: &AuditEvent) -> bool {
-        let filter = &self.config.filter;
-
-        // Check level
-        if event.level < filter.min_level {
-            return false;
-        }
-
-        // Check event types
-        if let Some(ref types) = filter.event_types {
-            if !types.contains(&event.event_type) {
-                return false;
-            }
-        }
-
-        // Check actors
-        if let Some(ref actors) = filter.actors {
-            if !actors.contains(&event.actor) {
-                return false;
-            }
-        }
-
-        // Check resources
-        if let Some(ref resources) = filter.resources {
-            if !resources.contains(&event.resource) {
-                return false;
-            }
-        }
-
-        true
+/// Parse a point in time for `--since`: an RFC 3339 timestamp, or an age
+/// such as `30s`, `15m`, `2h` or `7d`
+pub fn parse_since(since: &str) -> Result<SystemTime, String> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(since) {
+        return Ok(time.into());
     }
+    let age = parse_age(since).map_err(|e| format!("{}, or an RFC 3339 timestamp", e))?;
+    SystemTime::now().checked_sub(age)
+        .ok_or_else(|| format!("Age {} is too large", since))
+}
 
-    /// Flush the event buffer
-    fn flush_buffer(&self, buffer: &mut Vec<AuditEvent>) {
-        for event in buffer.drain(..) {
-            self.write_event(&event);
-        }
-    }
+/// Parse a span of time such as `30s`, `15m`, `2h` or `7d`
+pub fn parse_age(age: &str) -> Result<Duration, String> {
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (amount, unit) = age.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| format!("Invalid age {:?}: expected an amount and unit like 2h", age))?;
+    let seconds = match unit {
+        "s" => amount,
+        "m" => amount * 60,
+        "h" => amount * 60 * 60,
+        "d" => amount * 24 * 60 * 60,
+        _ => return Err(format!("Invalid age unit {:?}: expected s, m, h or d", unit)),
+    };
+    Ok(Duration::from_secs(seconds))
+}
 
-    /// Write an event to all configured outputs
-    fn write_event(&self, event: &AuditEvent) {
-        let serialized = match serde_json::to_string(event) {
-            Ok(s) => s + "\n",
-            Err(e) => {
-                eprintln!("Failed to serialize audit event: {}", e);
-                let mut stats = self.stats.lock().unwrap();
-                stats.events_failed += 1;
-                return;
-            }
-        };
+static LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
 
-        let bytes = serialized.as_bytes();
+/// Install the log events are recorded to; `None` drops them
+pub fn install(log: Option<AuditLog>) {
+    *LOG.lock().unwrap() = log;
+}
 
-        for output in &self.config.outputs {
-            match output {
-                AuditOutput::File(path) => {
-                    if let Some(writer) = self.file_writers.get(path) {
-                        if let Ok(mut w) = writer.lock() {
-                            if let Err(e) = w.write(bytes) {
-                                eprintln!("Failed to write to audit file {}: {}", path.display(), e);
-                                let mut stats = self.stats.lock().unwrap();
-                                stats.events_failed += 1;
-                            } else {
-                                let mut stats = self.stats.lock().unwrap();
-                                stats.bytes_written += bytes.len() as u64;
-                            }
-                        }
-                    }
-                }
-                AuditOutput::Console => {
-                    print!("{}", serialized);
-                }
-                AuditOutput::Syslog => {
-                    // TODO: Implement syslog output
-                }
-                AuditOutput::Network(_url) => {
-                    // TODO: Implement network output
-                }
-            }
+/// Record an event to the installed log
+pub fn record(kind: AuditKind, subject: impl fmt::Display, detail: impl AsRef<str>) {
+    let mut log = LOG.lock().unwrap();
+    if let Some(log) = log.as_mut() {
+        if let Err(e) = log.append(kind, &subject.to_string(), detail.as_ref()) {
+            tracing::error!(error = %e, path = %log.path().display(), "Failed to write audit log");
         }
     }
+}
 
-    /// Start background flush task
-    fn start_flush_task(&self) {
-        let buffer = Arc::clone(&self.event_buffer);
-        let interval = self.config.flush_interval_ms;
-        let file_writers = self.file_writers.clone();
-
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(interval));
-            
-            loop {
-                interval.tick().await;
-                
-                // Flush buffer
-                if let Ok(mut buffer) = buffer.lock() {
-                    if !buffer.is_empty() {
-                        // This would need access to self, so we'll implement a simpler version
-                        buffer.clear();
-                    }
-                }
-
-                // Flush file writers
-                for writer in file_writers.values() {
-                    if let Ok(mut w) = writer.lock() {
-                        let _ = w.flush();
-                    }
-                }
-            }
-        });
+/// Entries of the installed log recorded at or after `since`
+pub fn query(since: Option<SystemTime>) -> ReamResult<Vec<AuditEntry>> {
+    let path = LOG.lock().unwrap().as_ref().map(|log| log.path.clone())
+        .ok_or_else(|| ReamError::Other("No audit log is configured".to_string()))?;
+    let mut entries = read(&path)?;
+    if let Some(since) = since {
+        entries.retain(|entry| entry.timestamp >= since);
     }
+    Ok(entries)
+}
 
-    /// Force flush all buffers
-    pub fn flush(&self) {
-        let mut buffer = self.event_buffer.lock().unwrap();
-        self.flush_buffer(&mut buffer);
-
-        for writer in self.file_writers.values() {
-            if let Ok(mut w) = writer.lock() {
-                let _ = w.flush();
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_is_hash_chained() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit").join("audit.log");
+
+        let mut log = AuditLog::open(&path).unwrap();
+        log.append(AuditKind::Spawn, "<0.1.0>", "spawned").unwrap();
+        log.append(AuditKind::PermissionDenied, "ProcessSpawn", "Process <0.1.0> requested ProcessSpawn").unwrap();
+        drop(log);
+
+        // Reopening continues the chain
+        let mut log = AuditLog::open(&path).unwrap();
+        let third = log.append(AuditKind::ConfigChange, "alert_rules", "Alert rule cpu added").unwrap();
+        assert_eq!(third.seq, 2);
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[2].prev_hash, entries[1].hash);
+
+        // Editing, dropping or reordering an entry breaks the chain
+        let lines: Vec<String> = std::fs::read_to_string(&path).unwrap().lines().map(String::from).collect();
+        let tampered = [
+            vec![lines[0].clone(), lines[1].replace("ProcessSpawn", "FileRead"), lines[2].clone()],
+            vec![lines[0].clone(), lines[2].clone()],
+            vec![lines[1].clone(), lines[0].clone(), lines[2].clone()],
+        ];
+        for lines in tampered {
+            std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+            let err = read(&path).unwrap_err().to_string();
+            assert!(err.contains("is broken at line"), "{}", err);
+            assert!(AuditLog::open(&path).is_err());
         }
     }
 
-    /// Get audit statistics
-    pub fn get_stats(&self) -> AuditStats {
-        self.stats.lock().unwrap().clone()
-    }
-
-    /// Update configuration
-    pub fn update_config(&mut self, config: AuditConfig) {
-        self.config = config;
-        // TODO: Reinitialize file writers if needed
-    }
-
-    /// Get current configuration
-    pub fn get_config(&self) -> &AuditConfig {
-        &self.config
-    }
-}
-
-impl Drop for AuditLogger {
-    fn drop(&mut self) {
-        self.flush();
+    #[test]
+    fn test_installed_log_is_queried_since() {
+        let dir = tempfile::tempdir().unwrap();
+        install(Some(AuditLog::open(&dir.path().join("audit.log")).unwrap()));
+        record(AuditKind::RemoteConnection, "10.0.0.2:7000", "Inbound connection from node n1");
+        let since = parse_since("1h").unwrap();
+        record(AuditKind::ConfigChange, "security_policy", "Capability policy reloaded");
+
+        let entries = query(Some(since)).unwrap();
+        assert!(entries.iter().any(|entry| entry.kind == AuditKind::ConfigChange));
+        assert!(query(Some(SystemTime::now() + Duration::from_secs(60))).unwrap().is_empty());
+        install(None);
+        assert!(query(None).is_err());
+
+        assert!(parse_since("2024-01-01T00:00:00Z").is_ok());
+        assert!(parse_since("5 minutes").is_err());
     }
 }
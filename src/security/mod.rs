@@ -3,12 +3,18 @@
 //! Basic security system providing:
 //! - Environment variables and secrets management
 //! - Access control and audit logging
+//! - Capability policies enforced on stdlib builtins
+//! - Encrypted secrets, redacted from logs and crash dumps
+//! - A hash-chained, tamper-evident audit log
 //!
 //! This is a foundational implementation that can be extended with
 //! consensus storage and advanced cryptographic features.
 
 pub mod basic_security;
 pub mod tlisp_integration;
+pub mod policy;
+pub mod secrets;
+pub mod audit;
 
 // Re-export main types
 pub use basic_security::{
@@ -18,5 +24,8 @@ pub use basic_security::{
 pub use tlisp_integration::{
     TlispSecurityContext, register_security_functions
 };
+pub use policy::{Capabilities, CapabilityPolicy};
+pub use secrets::SecretStore;
+pub use audit::{AuditEntry, AuditKind, AuditLog};
 
 
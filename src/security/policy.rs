@@ -0,0 +1,280 @@
+//! Capability policies for stdlib builtins
+//!
+//! A policy says what the builtins a process calls may reach outside the
+//! runtime: the paths they read and write, the hosts they connect to and
+//! the ports they listen on, whether they spawn processes and which
+//! environment variables they see. Every profile of a policy becomes a
+//! default-deny `SecurityManager`, which audits each denial.
+//!
+//! Policies are TOML files:
+//!
+//! ```toml
+//! # Processes without a profile of their own
+//! [default]
+//! fs_read = ["/srv/app"]
+//! net = ["api.example.com:443", "*:80"]
+//! listen = [8080]
+//! spawn = true
+//! env = ["APP_*"]
+//! secrets = ["app/*"]
+//!
+//! # Profiles processes are assigned to with `assign`
+//! [profiles.untrusted]
+//! fs_read = ["/srv/app/public"]
+//! ```
+//!
+//! Builtins are checked against the profile of the process evaluating
+//! them, which `as_process` sets for the duration of an evaluation. Until
+//! a policy is installed, everything is allowed.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+
+use crate::bytecode::{Permission, SecurityEvent, SecurityManager, SecurityPolicy};
+use crate::error::{BytecodeError, BytecodeResult, ReamError, ReamResult};
+use crate::types::Pid;
+
+/// What the builtins of a process may do
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// Files and directories builtins may read
+    pub fs_read: Vec<PathBuf>,
+    /// Files and directories builtins may write
+    pub fs_write: Vec<PathBuf>,
+    /// Hosts builtins may connect to, as `host:port`; either part may be `*`
+    pub net: Vec<String>,
+    /// Ports builtins may listen on
+    pub listen: Vec<u16>,
+    /// Whether builtins may spawn processes
+    pub spawn: bool,
+    /// Environment variables builtins may read; a trailing `*` matches a prefix
+    pub env: Vec<String>,
+    /// Secrets builtins may read and write; a trailing `*` matches a prefix
+    pub secrets: Vec<String>,
+}
+
+impl Capabilities {
+    /// A security manager granting these capabilities and denying the rest
+    pub fn manager(&self) -> SecurityManager {
+        let mut manager = SecurityManager::with_policy(SecurityPolicy::default_deny(), Default::default());
+        for path in &self.fs_read {
+            manager.grant_permission(Permission::FileRead(normalize(path)));
+        }
+        for path in &self.fs_write {
+            manager.grant_permission(Permission::FileWrite(normalize(path)));
+        }
+        for host in &self.net {
+            manager.grant_permission(Permission::NetworkHost(host.clone()));
+        }
+        for port in &self.listen {
+            manager.grant_permission(Permission::NetworkBind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, *port))));
+        }
+        if self.spawn {
+            manager.grant_permission(Permission::ProcessSpawn);
+        }
+        for name in &self.env {
+            manager.grant_permission(Permission::SystemProperty(name.clone()));
+        }
+        for name in &self.secrets {
+            manager.grant_permission(Permission::Secret(name.clone()));
+        }
+        manager
+    }
+}
+
+/// Capabilities of processes, by profile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapabilityPolicy {
+    /// Capabilities of processes without a profile
+    pub default: Capabilities,
+    /// Named profiles processes can be assigned to
+    pub profiles: HashMap<String, Capabilities>,
+}
+
+impl CapabilityPolicy {
+    /// Load a policy file
+    pub fn load(path: &Path) -> ReamResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(ReamError::Io)?;
+        toml::from_str(&content)
+            .map_err(|e| ReamError::Other(format!("Invalid capability policy {}: {}", path.display(), e)))
+    }
+}
+
+/// An installed policy's managers, which keep the audit log of denials
+struct Installed {
+    default: Mutex<SecurityManager>,
+    profiles: HashMap<String, Mutex<SecurityManager>>,
+}
+
+static POLICY: RwLock<Option<Arc<Installed>>> = RwLock::new(None);
+
+static PROFILES: LazyLock<RwLock<HashMap<Pid, String>>> = LazyLock::new(Default::default);
+
+thread_local! {
+    static CURRENT: Cell<Option<Pid>> = const { Cell::new(None) };
+}
+
+/// Install the policy builtins are checked against; `None` allows everything
+pub fn install(policy: Option<CapabilityPolicy>) {
+    let installed = policy.map(|policy| Arc::new(Installed {
+        default: Mutex::new(policy.default.manager()),
+        profiles: policy.profiles.iter()
+            .map(|(name, capabilities)| (name.clone(), Mutex::new(capabilities.manager())))
+            .collect(),
+    }));
+    *POLICY.write().unwrap() = installed;
+}
+
+/// Run a process under a profile of the policy; `None` returns it to the default
+pub fn assign(pid: Pid, profile: Option<String>) {
+    let mut profiles = PROFILES.write().unwrap();
+    match profile {
+        Some(profile) => profiles.insert(pid, profile),
+        None => profiles.remove(&pid),
+    };
+}
+
+/// Run `f` as process `pid`, so the builtins it calls are checked against
+/// that process's capabilities
+pub fn as_process<R>(pid: Pid, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.replace(Some(pid)));
+    let result = f();
+    CURRENT.with(|current| current.set(previous));
+    result
+}
+
+/// Check that the process running on this thread holds `permission`,
+/// auditing a denial
+pub fn check(permission: Permission) -> BytecodeResult<()> {
+    let Some(policy) = POLICY.read().unwrap().clone() else {
+        return Ok(());
+    };
+    let pid = CURRENT.with(Cell::get);
+    let profile = pid.and_then(|pid| PROFILES.read().unwrap().get(&pid).cloned());
+    let manager = match &profile {
+        Some(name) => policy.profiles.get(name)
+            .ok_or_else(|| BytecodeError::SecurityViolation(format!("Unknown capability profile {}", name)))?,
+        None => &policy.default,
+    };
+
+    let subject = match (pid, &profile) {
+        (Some(pid), Some(profile)) => format!("Process {} ({})", pid, profile),
+        (Some(pid), None) => format!("Process {}", pid),
+        (None, _) => "Main program".to_string(),
+    };
+    let context = format!("{} requested {:?}", subject, permission);
+    let checked = manager.lock().unwrap().enforce_permission_for(permission, context);
+    checked
+}
+
+/// Check read access to a file
+pub fn check_read(path: &Path) -> BytecodeResult<()> {
+    check(Permission::FileRead(normalize(path)))
+}
+
+/// Check write access to a file
+pub fn check_write(path: &Path) -> BytecodeResult<()> {
+    check(Permission::FileWrite(normalize(path)))
+}
+
+/// Denials audited under the installed policy, oldest first
+pub fn audit_log() -> Vec<SecurityEvent> {
+    let Some(policy) = POLICY.read().unwrap().clone() else {
+        return Vec::new();
+    };
+    let mut events: Vec<SecurityEvent> = std::iter::once(&policy.default)
+        .chain(policy.profiles.values())
+        .flat_map(|manager| manager.lock().unwrap().audit_log().cloned().collect::<Vec<_>>())
+        .collect();
+    events.sort_by_key(|event| event.timestamp);
+    events
+}
+
+/// An absolute path with `.` and `..` resolved, so `/srv/app/../etc` is
+/// not taken to be inside `/srv/app`
+fn normalize(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TlispError;
+    use crate::tlisp::{TlispRuntime, Value};
+
+    #[test]
+    fn test_builtins_checked_against_process_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path().join("public");
+        std::fs::create_dir(&public).unwrap();
+        std::fs::write(public.join("index.txt"), "hello").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "hidden").unwrap();
+
+        // Processes without a profile may do anything, so other tests are unaffected
+        let policy: CapabilityPolicy = toml::from_str(&format!(r#"
+            [default]
+            fs_read = ["/"]
+            fs_write = ["/"]
+            net = ["*"]
+            spawn = true
+            env = ["*"]
+            secrets = ["*"]
+
+            [profiles.untrusted]
+            fs_read = ["{}"]
+            env = ["REAM_POLICY_TEST_*"]
+        "#, public.display())).unwrap();
+        install(Some(policy));
+        let pid = Pid::new();
+        assign(pid, Some("untrusted".to_string()));
+
+        let mut runtime = TlispRuntime::new();
+        let index = public.join("index.txt").display().to_string();
+        let secret = public.join("../secret.txt").display().to_string();
+        let mut eval = |code: String| as_process(pid, || runtime.eval(&code));
+
+        assert_eq!(eval(format!("(read-file {:?})", index)).unwrap(), Value::String("hello".to_string()));
+        assert_eq!(eval("(env-get \"REAM_POLICY_TEST_UNSET\")".to_string()).unwrap(), Value::Null);
+        for denied in [
+            format!("(read-file {:?})", secret),
+            format!("(write-file {:?} \"defaced\")", index),
+            "(env-get \"HOME\")".to_string(),
+            "(http-server:get \"http://example.com/\")".to_string(),
+            "(secret-get \"db-password\")".to_string(),
+        ] {
+            match eval(denied.clone()) {
+                Err(TlispError::SecurityError(message)) => assert!(message.contains("Permission denied"), "{}", message),
+                other => panic!("{} was not denied: {:?}", denied, other),
+            }
+        }
+        assert_eq!(std::fs::read_to_string(public.join("index.txt")).unwrap(), "hello");
+
+        // The same code is allowed outside the profile
+        assert_eq!(runtime.eval(&format!("(read-file {:?})", secret)).unwrap(), Value::String("hidden".to_string()));
+
+        // Every denial was audited against the process
+        let subject = format!("Process {} (untrusted)", pid);
+        let audited = audit_log().into_iter().filter(|event| event.context.starts_with(&subject)).count();
+        assert_eq!(audited, 5);
+
+        assign(pid, None);
+        install(None);
+    }
+}
@@ -0,0 +1,339 @@
+//! Secrets
+//!
+//! API keys and credentials are kept encrypted in a secrets file. Values
+//! are sealed with AES-256-GCM under a key derived from a master passphrase
+//! with Argon2; the salt is stored in the file, and each value is bound to
+//! its name so sealed values cannot be swapped between names. The daemon
+//! reads the passphrase from `REAM_MASTER_KEY`.
+//!
+//! Programs read and write secrets with `secret-get` and `secret-set`,
+//! which need the `secrets` capability. Every value the installed store
+//! holds is redacted from log lines and crash dumps, and stays redacted
+//! after the store is replaced.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::{ReamError, ReamResult};
+
+/// Environment variable the daemon reads the master passphrase from
+pub const MASTER_KEY_ENV: &str = "REAM_MASTER_KEY";
+
+/// Text secret values are replaced with
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Values shorter than this are not redacted, as they would mangle
+/// unrelated text
+const MIN_REDACTED_LEN: usize = 4;
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Associated data of the value that tells whether the passphrase is right
+const CHECK_AAD: &[u8] = b"ream-secrets";
+
+/// A value sealed with AES-256-GCM
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// On-disk layout of a secrets file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SecretsFile {
+    version: u32,
+    salt: String,
+    check: Sealed,
+    secrets: BTreeMap<String, Sealed>,
+}
+
+/// Key derived from the master passphrase, wiped when dropped
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct MasterKey([u8; 32]);
+
+impl MasterKey {
+    fn derive(passphrase: &str, salt: &[u8]) -> ReamResult<Self> {
+        let mut key = MasterKey([0; 32]);
+        Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key.0)
+            .map_err(|e| ReamError::Other(format!("Failed to derive master key: {}", e)))?;
+        Ok(key)
+    }
+
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> ReamResult<Sealed> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self.cipher()
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+            .map_err(|_| ReamError::Other("Failed to encrypt secret".to_string()))?;
+        Ok(Sealed { nonce: hex::encode(nonce), ciphertext: hex::encode(ciphertext) })
+    }
+
+    fn open(&self, sealed: &Sealed, aad: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+        let nonce = hex::decode(&sealed.nonce).ok().filter(|nonce| nonce.len() == NONCE_LEN)?;
+        let ciphertext = hex::decode(&sealed.ciphertext).ok()?;
+        self.cipher()
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad })
+            .ok()
+            .map(Zeroizing::new)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+/// Associated data binding a sealed value to its name
+fn secret_aad(name: &str) -> Vec<u8> {
+    format!("secret:{}", name).into_bytes()
+}
+
+/// Encrypted secrets file
+pub struct SecretStore {
+    path: PathBuf,
+    key: MasterKey,
+    file: SecretsFile,
+}
+
+impl SecretStore {
+    /// Open the secrets file at `path`, creating it if it does not exist.
+    /// Fails if the passphrase is not the one the file was created with.
+    pub fn open(path: &Path, passphrase: &str) -> ReamResult<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::create(path, passphrase),
+            Err(e) => return Err(ReamError::Io(e)),
+        };
+        let file: SecretsFile = serde_json::from_str(&content)
+            .map_err(|e| ReamError::Other(format!("Invalid secrets file {}: {}", path.display(), e)))?;
+        if file.version != FORMAT_VERSION {
+            return Err(ReamError::Other(format!(
+                "Unsupported secrets file version {} in {}", file.version, path.display()
+            )));
+        }
+        let salt = hex::decode(&file.salt)
+            .map_err(|e| ReamError::Other(format!("Invalid secrets file {}: {}", path.display(), e)))?;
+        let key = MasterKey::derive(passphrase, &salt)?;
+        if key.open(&file.check, CHECK_AAD).is_none() {
+            return Err(ReamError::Other(format!("Wrong master key for secrets file {}", path.display())));
+        }
+
+        let store = SecretStore { path: path.to_path_buf(), key, file };
+        for name in store.file.secrets.keys() {
+            if let Some(value) = store.get(name)? {
+                register(&value);
+            }
+        }
+        Ok(store)
+    }
+
+    fn create(path: &Path, passphrase: &str) -> ReamResult<Self> {
+        let salt: [u8; SALT_LEN] = rand::random();
+        let key = MasterKey::derive(passphrase, &salt)?;
+        let check = key.seal(&[], CHECK_AAD)?;
+        let file = SecretsFile {
+            version: FORMAT_VERSION,
+            salt: hex::encode(salt),
+            check,
+            secrets: BTreeMap::new(),
+        };
+        let store = SecretStore { path: path.to_path_buf(), key, file };
+        store.save()?;
+        Ok(store)
+    }
+
+    /// File the secrets are stored in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Decrypt a secret
+    pub fn get(&self, name: &str) -> ReamResult<Option<Zeroizing<String>>> {
+        let Some(sealed) = self.file.secrets.get(name) else {
+            return Ok(None);
+        };
+        let plaintext = self.key.open(sealed, &secret_aad(name))
+            .ok_or_else(|| ReamError::Other(format!("Secret {} cannot be decrypted", name)))?;
+        let value = std::str::from_utf8(&plaintext)
+            .map_err(|_| ReamError::Other(format!("Secret {} is not valid UTF-8", name)))?;
+        Ok(Some(Zeroizing::new(value.to_string())))
+    }
+
+    /// Encrypt and store a secret, replacing any previous value
+    pub fn set(&mut self, name: &str, value: &str) -> ReamResult<()> {
+        let sealed = self.key.seal(value.as_bytes(), &secret_aad(name))?;
+        self.file.secrets.insert(name.to_string(), sealed);
+        register(value);
+        self.save()
+    }
+
+    /// Delete a secret; returns whether it existed
+    pub fn remove(&mut self, name: &str) -> ReamResult<bool> {
+        if self.file.secrets.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Names of the stored secrets, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.file.secrets.keys().map(String::as_str)
+    }
+
+    /// Replace the file atomically, readable only by its owner
+    fn save(&self) -> ReamResult<()> {
+        let json = serde_json::to_vec_pretty(&self.file)
+            .map_err(|e| ReamError::Other(format!("Failed to serialize secrets: {}", e)))?;
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        std::io::Write::write_all(&mut options.open(&temp).map_err(ReamError::Io)?, &json)
+            .map_err(ReamError::Io)?;
+        std::fs::rename(&temp, &self.path).map_err(ReamError::Io)
+    }
+}
+
+static STORE: RwLock<Option<Arc<Mutex<SecretStore>>>> = RwLock::new(None);
+
+/// Values redacted from logs and crash dumps, longest first
+static KNOWN: RwLock<Vec<Zeroizing<String>>> = RwLock::new(Vec::new());
+
+/// Install the store `secret-get` and `secret-set` use; `None` removes it
+pub fn install(store: Option<SecretStore>) {
+    *STORE.write().unwrap() = store.map(|store| Arc::new(Mutex::new(store)));
+}
+
+fn installed() -> ReamResult<Arc<Mutex<SecretStore>>> {
+    STORE.read().unwrap().clone()
+        .ok_or_else(|| ReamError::Other("No secret store is configured".to_string()))
+}
+
+/// Read a secret from the installed store
+pub fn get(name: &str) -> ReamResult<Option<Zeroizing<String>>> {
+    let store = installed()?;
+    let value = store.lock().unwrap().get(name);
+    value
+}
+
+/// Store a secret in the installed store
+pub fn set(name: &str, value: &str) -> ReamResult<()> {
+    let store = installed()?;
+    let stored = store.lock().unwrap().set(name, value);
+    stored
+}
+
+/// Redact `value` from logs and crash dumps from now on
+pub fn register(value: &str) {
+    if value.len() < MIN_REDACTED_LEN {
+        return;
+    }
+    let mut known = KNOWN.write().unwrap();
+    if known.iter().any(|known| known.as_str() == value) {
+        return;
+    }
+    known.push(Zeroizing::new(value.to_string()));
+    known.sort_by_key(|known| std::cmp::Reverse(known.len()));
+}
+
+/// `text` with every secret value replaced by `[REDACTED]`
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let known = KNOWN.read().unwrap();
+    let mut redacted = Cow::Borrowed(text);
+    for value in known.iter() {
+        if redacted.contains(value.as_str()) {
+            redacted = Cow::Owned(redacted.replace(value.as_str(), REDACTED));
+        }
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::crash::{CrashDump, HeapSummary};
+    use crate::tlisp::{TlispRuntime, Value};
+    use crate::types::Pid;
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+
+        let mut store = SecretStore::open(&path, "correct horse").unwrap();
+        store.set("api-key", "sk-round-trip-0001").unwrap();
+        store.set("db-password", "hunter2-round-trip").unwrap();
+        assert!(store.remove("db-password").unwrap());
+        assert!(!store.remove("db-password").unwrap());
+
+        // Only ciphertext reaches the disk
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("sk-round-trip-0001"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let store = SecretStore::open(&path, "correct horse").unwrap();
+        assert_eq!(store.names().collect::<Vec<_>>(), vec!["api-key"]);
+        assert_eq!(store.get("api-key").unwrap().as_deref().map(String::as_str), Some("sk-round-trip-0001"));
+        assert!(store.get("db-password").unwrap().is_none());
+
+        let err = SecretStore::open(&path, "wrong horse").err().unwrap().to_string();
+        assert!(err.contains("Wrong master key"), "{}", err);
+
+        // A value moved to another name no longer decrypts
+        let mut file: SecretsFile = serde_json::from_str(&content).unwrap();
+        let sealed = file.secrets.remove("api-key").unwrap();
+        file.secrets.insert("other".to_string(), sealed);
+        std::fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+        assert!(SecretStore::open(&path, "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_builtins_and_redaction() {
+        let dir = tempfile::tempdir().unwrap();
+        install(Some(SecretStore::open(&dir.path().join("secrets.json"), "passphrase").unwrap()));
+
+        let mut runtime = TlispRuntime::new();
+        runtime.eval("(secret-set \"token\" \"tok-builtin-7f3a\")").unwrap();
+        assert_eq!(runtime.eval("(secret-get \"token\")").unwrap(), Value::String("tok-builtin-7f3a".to_string()));
+        assert_eq!(runtime.eval("(secret-get \"missing\")").unwrap(), Value::Null);
+        install(None);
+        assert!(runtime.eval("(secret-get \"token\")").is_err());
+
+        assert_eq!(redact("Authorization: Bearer tok-builtin-7f3a"), "Authorization: Bearer [REDACTED]");
+        assert!(matches!(redact("nothing to hide"), Cow::Borrowed(_)));
+
+        let pid = Pid::new();
+        let dump = CrashDump {
+            pid,
+            reason: "Request with tok-builtin-7f3a failed".to_string(),
+            crashed_at: std::time::SystemTime::now(),
+            recent_messages: vec!["\"tok-builtin-7f3a\"".to_string()],
+            trace: Vec::new(),
+            dictionary: BTreeMap::from([("token".to_string(), "tok-builtin-7f3a".to_string())]),
+            parent: None,
+            links: Vec::new(),
+            monitors: Vec::new(),
+            heap: HeapSummary::default(),
+        };
+        dump.write_to(dir.path()).unwrap();
+        let written = std::fs::read_to_string(CrashDump::path(dir.path(), pid)).unwrap();
+        assert!(!written.contains("tok-builtin-7f3a"), "{}", written);
+        assert!(written.contains(REDACTED));
+    }
+}
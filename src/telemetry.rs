@@ -0,0 +1,226 @@
+//! Distributed tracing
+//!
+//! Actor message handling, ask calls and remote sends open OpenTelemetry
+//! spans. The active span's context travels with messages — wrapped in
+//! `MessagePayload::Traced` between local actors and `NetworkMessage::Traced`
+//! between nodes — so a request can be followed across actors and nodes.
+//! Spans are exported over OTLP/HTTP once `init` installs a tracer provider;
+//! until then they are no-ops and only already-sampled remote contexts are
+//! passed along.
+
+use std::sync::OnceLock;
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry::trace::{
+    SpanContext, SpanId, SpanKind, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use serde::{Serialize, Deserialize};
+
+use crate::error::{ReamError, ReamResult};
+
+/// Environment variable holding the default OTLP collector URL
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Instrumentation scope of all REAM spans
+const TRACER_NAME: &str = "ream";
+
+/// Distributed tracing configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// Fraction of new traces that are recorded, from 0.0 to 1.0.
+    /// Traces continued from a remote parent follow the parent's decision.
+    pub sample_ratio: f64,
+    /// Base URL of the OTLP/HTTP collector, e.g. `http://localhost:4318`;
+    /// spans are not exported when unset
+    pub otlp_endpoint: Option<String>,
+    /// `service.name` reported with every span
+    pub service_name: String,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        TracingConfig {
+            sample_ratio: 1.0,
+            otlp_endpoint: std::env::var(OTLP_ENDPOINT_ENV).ok().filter(|url| !url.is_empty()),
+            service_name: "ream".to_string(),
+        }
+    }
+}
+
+/// Trace context carried by messages, equivalent to a W3C `traceparent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContext {
+    /// Trace the message belongs to
+    pub trace_id: u128,
+    /// Span that sent the message
+    pub span_id: u64,
+    /// Whether the trace is being recorded
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Context of the span active on this thread, if any
+    pub fn current() -> Option<Self> {
+        Self::from_context(&Context::current())
+    }
+
+    /// Context of the span held by `cx`, if any
+    pub fn from_context(cx: &Context) -> Option<Self> {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id: u128::from_be_bytes(span_context.trace_id().to_bytes()),
+            span_id: u64::from_be_bytes(span_context.span_id().to_bytes()),
+            sampled: span_context.is_sampled(),
+        })
+    }
+
+    /// Render as a W3C `traceparent` header value
+    pub fn to_traceparent(&self) -> String {
+        format!("00-{:032x}-{:016x}-{:02x}", self.trace_id, self.span_id, self.sampled as u8)
+    }
+
+    /// Parse a W3C `traceparent` header value
+    pub fn from_traceparent(value: &str) -> Option<Self> {
+        let parts: Vec<&str> = value.trim().split('-').collect();
+        if parts.len() != 4 || parts[0].len() != 2 || parts[1].len() != 32 || parts[2].len() != 16 || parts[3].len() != 2 {
+            return None;
+        }
+        let version = u8::from_str_radix(parts[0], 16).ok()?;
+        if version == 0xff {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(parts[1], 16).ok()?;
+        let span_id = u64::from_str_radix(parts[2], 16).ok()?;
+        let flags = u8::from_str_radix(parts[3], 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+        Some(TraceContext { trace_id, span_id, sampled: flags & 1 == 1 })
+    }
+
+    /// Context with this span as the remote parent
+    pub fn to_context(&self) -> Context {
+        let flags = if self.sampled { TraceFlags::SAMPLED } else { TraceFlags::default() };
+        let span_context = SpanContext::new(
+            TraceId::from_bytes(self.trace_id.to_be_bytes()),
+            SpanId::from_bytes(self.span_id.to_be_bytes()),
+            flags,
+            true,
+            TraceState::default(),
+        );
+        Context::new().with_remote_span_context(span_context)
+    }
+}
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Install the global tracer provider exporting to the configured collector.
+/// Does nothing when no endpoint is configured or a provider is installed.
+pub fn init(config: &TracingConfig) -> ReamResult<()> {
+    let Some(endpoint) = config.otlp_endpoint.as_deref() else {
+        return Ok(());
+    };
+    if PROVIDER.get().is_some() {
+        return Ok(());
+    }
+    if !(0.0..=1.0).contains(&config.sample_ratio) {
+        return Err(ReamError::Other(format!(
+            "Trace sample ratio must be between 0 and 1, got {}", config.sample_ratio
+        )));
+    }
+
+    // The blocking HTTP client refuses to be created on an async runtime
+    // thread, so build the exporter on a thread of its own
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let exporter = std::thread::spawn(move || {
+        SpanExporter::builder().with_http().with_endpoint(url).build()
+    })
+    .join()
+    .map_err(|_| ReamError::Other("OTLP exporter setup panicked".to_string()))?
+    .map_err(|e| ReamError::Other(format!("Failed to create OTLP exporter for {}: {}", endpoint, e)))?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::builder().with_service_name(config.service_name.clone()).build())
+        .build();
+
+    if PROVIDER.set(provider.clone()).is_ok() {
+        global::set_tracer_provider(provider);
+        tracing::info!(endpoint, sample_ratio = config.sample_ratio, "Exporting traces over OTLP");
+    }
+    Ok(())
+}
+
+/// Flush and stop exporting spans
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!(error = %e, "Failed to flush traces");
+        }
+    }
+}
+
+/// Start a span, returning a context that holds it; the span ends when the
+/// last clone of the context is dropped. Without a `parent` the span is a
+/// child of the span active on this thread.
+pub fn start_span(
+    name: &'static str,
+    kind: SpanKind,
+    parent: Option<&TraceContext>,
+    attributes: Vec<KeyValue>,
+) -> Context {
+    let parent = match parent {
+        Some(parent) => parent.to_context(),
+        None => Context::current(),
+    };
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(name)
+        .with_kind(kind)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &parent);
+    parent.with_span(span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let context = TraceContext {
+            trace_id: 0x4bf92f3577b34da6a3ce929d0e0e4736,
+            span_id: 0x00f067aa0ba902b7,
+            sampled: true,
+        };
+        let header = context.to_traceparent();
+        assert_eq!(header, "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+        assert_eq!(TraceContext::from_traceparent(&header), Some(context));
+
+        assert_eq!(TraceContext::from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").map(|c| c.sampled), Some(false));
+        assert_eq!(TraceContext::from_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01"), None);
+        assert_eq!(TraceContext::from_traceparent("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"), None);
+        assert_eq!(TraceContext::from_traceparent("garbage"), None);
+    }
+
+    #[test]
+    fn test_remote_context_is_continued() {
+        let parent = TraceContext { trace_id: 42, span_id: 7, sampled: true };
+        let cx = parent.to_context();
+        assert_eq!(TraceContext::from_context(&cx), Some(parent));
+
+        // With no provider installed the span is a no-op that keeps the
+        // remote parent's context, so it is still passed on
+        let cx = start_span("actor.receive", SpanKind::Consumer, Some(&parent), Vec::new());
+        let _guard = cx.attach();
+        assert_eq!(TraceContext::current().map(|c| c.trace_id), Some(42));
+    }
+}
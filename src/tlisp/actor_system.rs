@@ -4,11 +4,11 @@
 //! including work-stealing, real-time scheduling, and resource management.
 
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::time::Duration;
 
 use crate::runtime::{
-    WorkStealingScheduler, ScheduledTask, RealTimeScheduler, RealTimeTask,
+    WorkStealingScheduler, ScheduledTask, TopologyConfig, RealTimeScheduler, RealTimeTask,
     SchedulingAlgorithm, TaskType, ResourceManager, ResourceQuotas,
     PreemptionTimer, ProcessExecutor, Process, ProcessHandle, ReamActor
 };
@@ -99,6 +99,8 @@ pub struct ActorSystemConfig {
     pub enable_security: bool,
     /// Maximum actors per system
     pub max_actors: usize,
+    /// Core pinning and cache affinity for the worker threads
+    pub topology: TopologyConfig,
 }
 
 impl Default for ActorSystemConfig {
@@ -110,6 +112,7 @@ impl Default for ActorSystemConfig {
             enable_realtime: true,
             enable_security: true,
             max_actors: 10000,
+            topology: crate::config::current().runtime.topology,
         }
     }
 }
@@ -203,8 +206,10 @@ impl ReamActor for TlispActor {
         let tlisp_message = match message {
             MessagePayload::Text(text) => TlispValue::String(text),
             MessagePayload::Bytes(data) => TlispValue::String(String::from_utf8_lossy(&data).to_string()),
+            MessagePayload::SharedBinary(binary) => TlispValue::Binary(binary),
             MessagePayload::Data(json) => TlispValue::String(json.to_string()),
             MessagePayload::Control(_) => TlispValue::String("control".to_string()),
+            MessagePayload::Traced { payload, .. } => return self.receive(*payload),
         };
 
         // Enqueue message
@@ -227,6 +232,13 @@ impl ReamActor for TlispActor {
         Ok(())
     }
 
+    fn crash_trace(&self) -> Vec<String> {
+        self.interpreter.lock().unwrap().evaluator.error_trace().to_vec()
+    }
+
+    fn dictionary(&self) -> BTreeMap<String, String> {
+        self.state.iter().map(|(key, value)| (key.clone(), value.to_string())).collect()
+    }
 }
 
 impl TlispActorSystem {
@@ -234,7 +246,7 @@ impl TlispActorSystem {
     pub fn new(config: ActorSystemConfig) -> RuntimeResult<Self> {
         // Create schedulers
         let work_stealing_scheduler = Arc::new(Mutex::new(
-            WorkStealingScheduler::new(Some(config.worker_threads))
+            WorkStealingScheduler::with_topology(Some(config.worker_threads), config.topology.clone())
         ));
         
         let realtime_scheduler = Arc::new(Mutex::new(
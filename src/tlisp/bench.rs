@@ -0,0 +1,594 @@
+//! TLISP benchmark harness
+//!
+//! Bench files register cases with `(defbench name body...)`. Each case runs
+//! a number of warmup iterations and then measured iterations under the
+//! interpreter, the bytecode VM and the JIT. Reports summarise the samples
+//! and can be compared against a stored baseline to catch regressions.
+//!
+//! Under the VM and JIT a case is the file's other top-level forms followed
+//! by its body, compiled once and executed per iteration, so definitions are
+//! re-run each time and the rest of the file should stay cheap.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::bytecode::{BytecodeProgram, BytecodeVM};
+use crate::error::{TlispError, TlispResult};
+use crate::jit::JitRuntime;
+use crate::runtime::ReamRuntime;
+use crate::tlisp::enhanced_compiler::EnhancedTlispCompiler;
+use crate::tlisp::{Expr, Parser, TlispInterpreter, TlispRuntime, Value};
+
+/// Global list of `(name thunk)` pairs registered by `defbench`
+pub const BENCH_REGISTRY: &str = "*benches*";
+
+/// Default relative slowdown, in percent, reported as a regression
+pub const DEFAULT_THRESHOLD: f64 = 5.0;
+
+/// Execution engine a benchmark runs under
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BenchMode {
+    /// Tree-walking interpreter
+    Interpreter,
+    /// Bytecode VM
+    Vm,
+    /// JIT compiled bytecode
+    Jit,
+}
+
+impl BenchMode {
+    /// Every mode, in report order
+    pub const ALL: [BenchMode; 3] = [BenchMode::Interpreter, BenchMode::Vm, BenchMode::Jit];
+}
+
+impl fmt::Display for BenchMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BenchMode::Interpreter => write!(f, "interpreter"),
+            BenchMode::Vm => write!(f, "vm"),
+            BenchMode::Jit => write!(f, "jit"),
+        }
+    }
+}
+
+/// Summary statistics of one benchmark's samples, in nanoseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchStats {
+    /// Number of measured iterations
+    pub iterations: usize,
+    pub mean_ns: f64,
+    pub median_ns: f64,
+    pub stddev_ns: f64,
+    pub min_ns: f64,
+    pub max_ns: f64,
+    /// Lower bound of the 95% confidence interval of the mean
+    pub ci_low_ns: f64,
+    /// Upper bound of the 95% confidence interval of the mean
+    pub ci_high_ns: f64,
+}
+
+impl BenchStats {
+    /// Summarise per-iteration samples
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let n = samples.len();
+        if n == 0 {
+            return BenchStats {
+                iterations: 0,
+                mean_ns: 0.0,
+                median_ns: 0.0,
+                stddev_ns: 0.0,
+                min_ns: 0.0,
+                max_ns: 0.0,
+                ci_low_ns: 0.0,
+                ci_high_ns: 0.0,
+            };
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+        let variance = if n > 1 {
+            sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+        let stddev = variance.sqrt();
+        let margin = 1.96 * stddev / (n as f64).sqrt();
+
+        BenchStats {
+            iterations: n,
+            mean_ns: mean,
+            median_ns: median,
+            stddev_ns: stddev,
+            min_ns: sorted[0],
+            max_ns: sorted[n - 1],
+            ci_low_ns: (mean - margin).max(0.0),
+            ci_high_ns: mean + margin,
+        }
+    }
+
+    /// Iterations per second at the mean
+    pub fn throughput(&self) -> f64 {
+        if self.mean_ns > 0.0 { 1e9 / self.mean_ns } else { 0.0 }
+    }
+}
+
+/// Result of one benchmark under one mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchResult {
+    /// File the benchmark was defined in
+    pub file: PathBuf,
+    /// Benchmark name
+    pub name: String,
+    pub mode: BenchMode,
+    /// Statistics, when the benchmark ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<BenchStats>,
+    /// Why the benchmark could not run under this mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How a benchmark changed against the baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Improved,
+    Unchanged,
+    Regressed,
+}
+
+/// A benchmark compared with its baseline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comparison {
+    pub name: String,
+    pub mode: BenchMode,
+    pub baseline_ns: f64,
+    pub current_ns: f64,
+    /// Relative change of the mean in percent; positive is slower
+    pub change: f64,
+    pub verdict: Verdict,
+}
+
+/// Results of a benchmark run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BenchReport {
+    /// Results in file order, then definition order, then mode order
+    pub results: Vec<BenchResult>,
+    /// Wall-clock duration of the run in milliseconds
+    pub duration_ms: f64,
+}
+
+impl BenchReport {
+    /// Load a report saved with `to_json`, e.g. a baseline
+    pub fn load(path: &Path) -> TlispResult<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| TlispError::Runtime(format!("Failed to read baseline {}: {}", path.display(), e)))?;
+        serde_json::from_str(&content)
+            .map_err(|e| TlispError::Runtime(format!("Invalid baseline {}: {}", path.display(), e)))
+    }
+
+    /// Render the report as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Compare means against `baseline`. A change beyond `threshold` percent
+    /// only counts when the confidence intervals do not overlap.
+    pub fn compare(&self, baseline: &BenchReport, threshold: f64) -> Vec<Comparison> {
+        let previous: HashMap<(&str, BenchMode), &BenchStats> = baseline.results.iter()
+            .filter_map(|r| r.stats.as_ref().map(|stats| ((r.name.as_str(), r.mode), stats)))
+            .collect();
+
+        self.results.iter().filter_map(|result| {
+            let current = result.stats.as_ref()?;
+            let old = previous.get(&(result.name.as_str(), result.mode))?;
+            if old.mean_ns <= 0.0 {
+                return None;
+            }
+            let change = (current.mean_ns - old.mean_ns) / old.mean_ns * 100.0;
+            let verdict = if change > threshold && current.ci_low_ns > old.ci_high_ns {
+                Verdict::Regressed
+            } else if change < -threshold && current.ci_high_ns < old.ci_low_ns {
+                Verdict::Improved
+            } else {
+                Verdict::Unchanged
+            };
+            Some(Comparison {
+                name: result.name.clone(),
+                mode: result.mode,
+                baseline_ns: old.mean_ns,
+                current_ns: current.mean_ns,
+                change,
+                verdict,
+            })
+        }).collect()
+    }
+
+    /// Render a standalone HTML page, with baseline changes when given
+    pub fn to_html(&self, comparisons: &[Comparison]) -> String {
+        let slowest = self.results.iter()
+            .filter_map(|r| r.stats.as_ref().map(|s| s.mean_ns))
+            .fold(0.0, f64::max);
+        let changes: HashMap<(&str, BenchMode), &Comparison> = comparisons.iter()
+            .map(|c| ((c.name.as_str(), c.mode), c))
+            .collect();
+
+        let mut html = String::from(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>REAM benchmarks</title>\n",
+            "<style>\nbody { font-family: sans-serif; margin: 2em; }\n",
+            "table { border-collapse: collapse; }\n",
+            "th, td { padding: 4px 10px; border-bottom: 1px solid #ddd; text-align: right; }\n",
+            "th:first-child, td:first-child, td.mode { text-align: left; }\n",
+            ".bar { background: #4a90d9; height: 10px; }\n",
+            ".regressed { color: #c0392b; } .improved { color: #27ae60; } .error { color: #888; }\n",
+            "</style>\n</head>\n<body>\n<h1>REAM benchmarks</h1>\n",
+        ));
+        html.push_str(&format!("<p>{} results in {:.0} ms</p>\n", self.results.len(), self.duration_ms));
+        html.push_str("<table>\n<tr><th>Benchmark</th><th>Mode</th><th>Mean</th><th>95% CI</th><th>Median</th><th>Std dev</th><th>Iterations/s</th><th>Change</th><th></th></tr>\n");
+
+        for result in &self.results {
+            let name = html_escape(&result.name);
+            let Some(stats) = &result.stats else {
+                let error = html_escape(result.error.as_deref().unwrap_or("not run"));
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td class=\"mode\">{}</td><td class=\"error\" colspan=\"7\">{}</td></tr>\n",
+                    name, result.mode, error
+                ));
+                continue;
+            };
+            let change = match changes.get(&(result.name.as_str(), result.mode)) {
+                Some(c) => format!(
+                    "<span class=\"{}\">{:+.1}%</span>",
+                    match c.verdict {
+                        Verdict::Regressed => "regressed",
+                        Verdict::Improved => "improved",
+                        Verdict::Unchanged => "unchanged",
+                    },
+                    c.change
+                ),
+                None => String::new(),
+            };
+            let width = if slowest > 0.0 { stats.mean_ns / slowest * 200.0 } else { 0.0 };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"mode\">{}</td><td>{}</td><td>{} – {}</td><td>{}</td><td>{}</td><td>{:.0}</td><td>{}</td><td><div class=\"bar\" style=\"width: {:.0}px\"></div></td></tr>\n",
+                name,
+                result.mode,
+                format_nanos(stats.mean_ns),
+                format_nanos(stats.ci_low_ns),
+                format_nanos(stats.ci_high_ns),
+                format_nanos(stats.median_ns),
+                format_nanos(stats.stddev_ns),
+                stats.throughput(),
+                change,
+                width
+            ));
+        }
+
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+/// Format a duration in nanoseconds with a readable unit
+pub fn format_nanos(nanos: f64) -> String {
+    if nanos >= 1e9 {
+        format!("{:.2} s", nanos / 1e9)
+    } else if nanos >= 1e6 {
+        format!("{:.2} ms", nanos / 1e6)
+    } else if nanos >= 1e3 {
+        format!("{:.2} µs", nanos / 1e3)
+    } else {
+        format!("{:.0} ns", nanos)
+    }
+}
+
+/// Escape text for HTML content
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Runs benchmark files under one or more execution modes
+#[derive(Debug, Clone)]
+pub struct BenchRunner {
+    /// Only run benchmarks whose name contains this
+    filter: Option<String>,
+    /// Unmeasured iterations before sampling
+    warmup: usize,
+    /// Measured iterations
+    iterations: usize,
+    modes: Vec<BenchMode>,
+}
+
+impl BenchRunner {
+    /// Create a runner for every mode with 10 warmup and 100 measured iterations
+    pub fn new() -> Self {
+        BenchRunner { filter: None, warmup: 10, iterations: 100, modes: BenchMode::ALL.to_vec() }
+    }
+
+    /// Only run benchmarks whose name contains `filter`
+    pub fn with_filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// Set the number of warmup iterations
+    pub fn with_warmup(mut self, warmup: usize) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Set the number of measured iterations
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Run under the given modes only
+    pub fn with_modes(mut self, modes: Vec<BenchMode>) -> Self {
+        if !modes.is_empty() {
+            self.modes = modes;
+        }
+        self
+    }
+
+    /// Run every benchmark in the given files
+    pub fn run(&self, files: &[PathBuf]) -> BenchReport {
+        let start = Instant::now();
+        let mut jit = None;
+        let mut results = Vec::new();
+
+        for file in files {
+            for (name, cases) in self.run_file(file, &mut jit) {
+                for (mode, outcome) in cases {
+                    let (stats, error) = match outcome {
+                        Ok(stats) => (Some(stats), None),
+                        Err(e) => (None, Some(e)),
+                    };
+                    results.push(BenchResult { file: file.clone(), name: name.clone(), mode, stats, error });
+                }
+            }
+        }
+
+        BenchReport { results, duration_ms: start.elapsed().as_secs_f64() * 1000.0 }
+    }
+
+    /// Run one file's benchmarks, grouped by benchmark in definition order
+    #[allow(clippy::type_complexity)]
+    fn run_file(&self, file: &Path, jit: &mut Option<Result<JitRuntime, String>>) -> Vec<(String, Vec<(BenchMode, Result<BenchStats, String>)>)> {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => return vec![(bench_file_name(file), self.fail_all(format!("failed to read file: {}", e)))],
+        };
+        let (prelude, cases) = match split_source(&source) {
+            Ok(split) => split,
+            Err(e) => return vec![(bench_file_name(file), self.fail_all(e.to_string()))],
+        };
+        let cases: Vec<(String, Vec<Expr<()>>)> = cases.into_iter()
+            .filter(|(name, _)| self.filter.as_deref().is_none_or(|filter| name.contains(filter)))
+            .collect();
+        if cases.is_empty() {
+            return Vec::new();
+        }
+
+        let mut grouped: Vec<(String, Vec<(BenchMode, Result<BenchStats, String>)>)> =
+            cases.iter().map(|(name, _)| (name.clone(), Vec::new())).collect();
+
+        for mode in &self.modes {
+            match mode {
+                BenchMode::Interpreter => {
+                    let outcomes = self.run_interpreted(file, &source, &grouped);
+                    for ((_, results), outcome) in grouped.iter_mut().zip(outcomes) {
+                        results.push((BenchMode::Interpreter, outcome));
+                    }
+                }
+                BenchMode::Vm | BenchMode::Jit => {
+                    for ((_, results), (name, body)) in grouped.iter_mut().zip(&cases) {
+                        let outcome = compile_case(name, &prelude, body).and_then(|program| {
+                            if *mode == BenchMode::Vm {
+                                let mut vm = BytecodeVM::new();
+                                self.measure(|| vm.execute_program(&program).map(|_| ()).map_err(|e| e.to_string()))
+                            } else {
+                                let runtime = jit.get_or_insert_with(|| {
+                                    ReamRuntime::new().map(JitRuntime::new).map_err(|e| e.to_string())
+                                });
+                                match runtime {
+                                    Ok(runtime) => self.measure(|| runtime.execute_program(&program).map(|_| ()).map_err(|e| e.to_string())),
+                                    Err(e) => Err(format!("JIT unavailable: {}", e)),
+                                }
+                            }
+                        });
+                        results.push((*mode, outcome));
+                    }
+                }
+            }
+        }
+        grouped
+    }
+
+    /// Evaluate the file and time each registered thunk in the interpreter
+    #[allow(clippy::type_complexity)]
+    fn run_interpreted(&self, file: &Path, source: &str, cases: &[(String, Vec<(BenchMode, Result<BenchStats, String>)>)]) -> Vec<Result<BenchStats, String>> {
+        let mut runtime = TlispRuntime::new();
+        runtime.define("*file*", Value::String(file.display().to_string()));
+        if let Err(e) = runtime.eval(source) {
+            return cases.iter().map(|_| Err(e.to_string())).collect();
+        }
+
+        let registered: HashMap<String, Value> = match runtime.get(BENCH_REGISTRY) {
+            Some(Value::List(entries)) => entries.into_iter().filter_map(|entry| match entry {
+                Value::List(entry) => match (entry.first(), entry.get(1)) {
+                    (Some(Value::String(name)), Some(thunk)) => Some((name.clone(), thunk.clone())),
+                    _ => None,
+                },
+                _ => None,
+            }).collect(),
+            _ => HashMap::new(),
+        };
+
+        cases.iter().map(|(name, _)| {
+            let thunk = registered.get(name).ok_or_else(|| format!("benchmark {} was not registered", name))?;
+            self.measure(|| runtime.call(thunk, &[]).map(|_| ()).map_err(|e| e.to_string()))
+        }).collect()
+    }
+
+    /// Run warmup iterations, then time each measured iteration
+    fn measure(&self, mut iteration: impl FnMut() -> Result<(), String>) -> Result<BenchStats, String> {
+        for _ in 0..self.warmup {
+            iteration()?;
+        }
+        let mut samples = Vec::with_capacity(self.iterations);
+        for _ in 0..self.iterations {
+            let start = Instant::now();
+            iteration()?;
+            samples.push(duration_nanos(start.elapsed()));
+        }
+        Ok(BenchStats::from_samples(&samples))
+    }
+
+    fn fail_all(&self, message: String) -> Vec<(BenchMode, Result<BenchStats, String>)> {
+        self.modes.iter().map(|mode| (*mode, Err(message.clone()))).collect()
+    }
+}
+
+impl Default for BenchRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn duration_nanos(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e9
+}
+
+/// Name used for a file that fails to load
+fn bench_file_name(file: &Path) -> String {
+    file.file_stem().and_then(|s| s.to_str()).unwrap_or("bench").to_string()
+}
+
+/// Split a bench file into its other top-level forms and its `(name body)` cases
+#[allow(clippy::type_complexity)]
+fn split_source(source: &str) -> TlispResult<(Vec<Expr<()>>, Vec<(String, Vec<Expr<()>>)>)> {
+    let mut parser = Parser::new();
+    let tokens = parser.tokenize(source)?;
+    let forms = parser.parse_multiple(&tokens)?;
+
+    let mut prelude = Vec::new();
+    let mut cases = Vec::new();
+    for form in forms {
+        match form {
+            Expr::Application(head, args, _) if matches!(head.as_ref(), Expr::Symbol(s, _) if s == "defbench") => {
+                let mut args = args.into_iter();
+                let name = match args.next() {
+                    Some(Expr::Symbol(name, _)) | Some(Expr::String(name, _)) => name,
+                    _ => return Err(TlispError::Runtime("defbench name must be a symbol or string".to_string())),
+                };
+                let body: Vec<Expr<()>> = args.collect();
+                if body.is_empty() {
+                    return Err(TlispError::Runtime("defbench requires a name and a body".to_string()));
+                }
+                cases.push((name, body));
+            }
+            form => prelude.push(form),
+        }
+    }
+    Ok((prelude, cases))
+}
+
+/// Compile the prelude followed by a case body into one program
+fn compile_case(name: &str, prelude: &[Expr<()>], body: &[Expr<()>]) -> Result<BytecodeProgram, String> {
+    let interpreter = TlispInterpreter::new();
+    let mut compiler = EnhancedTlispCompiler::new(name.to_string());
+    for expr in prelude.iter().chain(body) {
+        compiler.compile_expr(&interpreter.annotate_types(expr.clone()))
+            .map_err(|e| format!("compilation failed: {}", e))?;
+    }
+    compiler.finish().map_err(|e| format!("compilation failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SOURCE: &str = r#"
+(define base 40)
+(defbench add (+ 40 2))
+(defbench multiply (* 6 7))
+"#;
+
+    #[test]
+    fn test_stats() {
+        let stats = BenchStats::from_samples(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(stats.iterations, 4);
+        assert_eq!(stats.mean_ns, 2.5);
+        assert_eq!(stats.median_ns, 2.5);
+        assert_eq!(stats.min_ns, 1.0);
+        assert_eq!(stats.max_ns, 4.0);
+        assert!((stats.stddev_ns - 1.2910).abs() < 1e-3);
+        assert!(stats.ci_low_ns < stats.mean_ns && stats.mean_ns < stats.ci_high_ns);
+    }
+
+    #[test]
+    fn test_run_modes_and_reports() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("arith.tl");
+        fs::write(&file, SOURCE).unwrap();
+
+        let report = BenchRunner::new()
+            .with_warmup(2)
+            .with_iterations(5)
+            .with_modes(vec![BenchMode::Interpreter, BenchMode::Vm])
+            .run(&[file.clone()]);
+        let keys: Vec<(&str, BenchMode)> = report.results.iter().map(|r| (r.name.as_str(), r.mode)).collect();
+        assert_eq!(keys, vec![
+            ("add", BenchMode::Interpreter),
+            ("add", BenchMode::Vm),
+            ("multiply", BenchMode::Interpreter),
+            ("multiply", BenchMode::Vm),
+        ]);
+        for result in &report.results {
+            assert_eq!(result.stats.as_ref().map(|s| s.iterations), Some(5), "{:?}", result.error);
+        }
+
+        let filtered = BenchRunner::new().with_iterations(1).with_filter("mult").with_modes(vec![BenchMode::Interpreter]).run(&[file]);
+        assert_eq!(filtered.results.len(), 1);
+
+        let restored: BenchReport = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(restored.results.len(), 4);
+        let html = report.to_html(&[]);
+        assert!(html.contains("<td>multiply</td><td class=\"mode\">vm</td>"));
+    }
+
+    #[test]
+    fn test_baseline_comparison() {
+        let result = |name: &str, samples: &[f64]| BenchResult {
+            file: PathBuf::from("b.tl"),
+            name: name.to_string(),
+            mode: BenchMode::Vm,
+            stats: Some(BenchStats::from_samples(samples)),
+            error: None,
+        };
+        let baseline = BenchReport {
+            results: vec![result("steady", &[100.0, 101.0, 99.0]), result("slower", &[100.0, 101.0, 99.0]), result("faster", &[100.0, 101.0, 99.0])],
+            duration_ms: 1.0,
+        };
+        let current = BenchReport {
+            results: vec![result("steady", &[102.0, 100.0, 101.0]), result("slower", &[150.0, 151.0, 149.0]), result("faster", &[50.0, 51.0, 49.0]), result("new", &[1.0])],
+            duration_ms: 1.0,
+        };
+
+        let comparisons = current.compare(&baseline, DEFAULT_THRESHOLD);
+        let verdicts: Vec<(&str, Verdict)> = comparisons.iter().map(|c| (c.name.as_str(), c.verdict)).collect();
+        assert_eq!(verdicts, vec![("steady", Verdict::Unchanged), ("slower", Verdict::Regressed), ("faster", Verdict::Improved)]);
+    }
+}
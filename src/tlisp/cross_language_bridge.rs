@@ -10,6 +10,7 @@ use std::any::Any;
 use crate::error::{TlispError, TlispResult};
 use crate::tlisp::{Value, Type, ModuleLanguage};
 use crate::tlisp::rust_integration::{RustFunction, FunctionSignature};
+pub use crate::tlisp::js_bridge::{JavaScriptBridge, JsSandbox};
 
 /// Cross-language bridge for seamless function calls
 pub struct CrossLanguageBridge {
@@ -121,12 +122,6 @@ pub struct RustBridge {
     type_mappings: HashMap<String, Type>,
 }
 
-/// JavaScript bridge (placeholder for future implementation)
-pub struct JavaScriptBridge {
-    /// JavaScript runtime context
-    context: Option<String>, // Placeholder
-}
-
 /// Python bridge (placeholder for future implementation)
 pub struct PythonBridge {
     /// Python interpreter context
@@ -659,36 +654,6 @@ impl LanguageBridge for RustBridge {
 }
 
 // Placeholder implementations for other language bridges
-impl LanguageBridge for JavaScriptBridge {
-    fn language(&self) -> ModuleLanguage {
-        ModuleLanguage::JavaScript
-    }
-
-    fn call_function(&self, _name: &str, _args: &[Value]) -> TlispResult<Value> {
-        Err(TlispError::Runtime("JavaScript bridge not implemented yet".to_string()))
-    }
-
-    fn get_function_signature(&self, _name: &str) -> Option<FunctionSignature> {
-        None
-    }
-
-    fn list_functions(&self) -> Vec<String> {
-        Vec::new()
-    }
-
-    fn has_function(&self, _name: &str) -> bool {
-        false
-    }
-
-    fn convert_from_tlisp(&self, _value: &Value, _target_type: &str) -> TlispResult<Box<dyn Any + '_>> {
-        Err(TlispError::Runtime("JavaScript bridge not implemented yet".to_string()))
-    }
-
-    fn convert_to_tlisp(&self, _value: Box<dyn Any>, _source_type: &str) -> TlispResult<Value> {
-        Err(TlispError::Runtime("JavaScript bridge not implemented yet".to_string()))
-    }
-}
-
 impl LanguageBridge for PythonBridge {
     fn language(&self) -> ModuleLanguage {
         ModuleLanguage::Python
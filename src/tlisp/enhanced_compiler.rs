@@ -5,6 +5,7 @@
 
 use std::collections::HashMap;
 use crate::bytecode::{BytecodeCompiler, BytecodeProgram, Bytecode, Value as BytecodeValue, LanguageCompiler};
+use crate::bytecode::instruction::cast;
 use crate::tlisp::{Expr, Type};
 use crate::error::{TlispError, TlispResult};
 use crate::types::EffectGrade;
@@ -24,6 +25,20 @@ pub struct EnhancedTlispCompiler {
     loop_stack: Vec<LoopContext>,
     /// Current effect context
     effect_context: EffectGrade,
+    /// Functions declared but not compiled yet
+    pending: Vec<PendingFunction>,
+}
+
+/// A function whose code is compiled after the top-level code, so that
+/// it can call functions defined after it
+#[derive(Debug, Clone)]
+struct PendingFunction {
+    /// Function index
+    id: u32,
+    /// Parameter names, in order
+    params: Vec<String>,
+    /// Function body
+    body: Expr<Type>,
 }
 
 /// Loop context for break/continue statements
@@ -55,6 +70,7 @@ impl EnhancedTlispCompiler {
             local_count: 0,
             loop_stack: Vec::new(),
             effect_context: EffectGrade::Pure,
+            pending: Vec::new(),
         }
     }
     
@@ -86,6 +102,9 @@ impl EnhancedTlispCompiler {
             Expr::Symbol(name, _) => {
                 if let Some(&local_idx) = self.variables.get(name) {
                     self.compiler.emit(Bytecode::Load(local_idx, EffectGrade::Read));
+                } else if let Some(&func_idx) = self.functions.get(name) {
+                    let func_const = self.compiler.add_constant(BytecodeValue::Function(func_idx));
+                    self.compiler.emit(Bytecode::Const(func_const, EffectGrade::Pure));
                 } else {
                     // Try to load as global
                     let const_id = self.compiler.add_constant(BytecodeValue::String(name.clone()));
@@ -121,7 +140,9 @@ impl EnhancedTlispCompiler {
             
             // Lambda expressions
             Expr::Lambda(params, body, _) => {
-                self.compile_lambda(params, body)?;
+                let func_idx = self.declare_function("lambda".to_string(), params, body);
+                let func_const = self.compiler.add_constant(BytecodeValue::Function(func_idx));
+                self.compiler.emit(Bytecode::Const(func_const, EffectGrade::Pure));
             }
             
             // Quotes
@@ -139,20 +160,33 @@ impl EnhancedTlispCompiler {
                 self.compile_expr(expr)?;
             }
 
-            // Define expressions
+            // Define expressions, which evaluate to null
             Expr::Define(name, value, _) => {
-                self.compile_expr(value)?;
-                // Store the value in a global variable
-                let name_id = self.compiler.add_constant(BytecodeValue::String(name.clone()));
-                self.compiler.emit(Bytecode::StoreGlobal(name_id, self.effect_context));
+                if let Expr::Lambda(params, body, _) = value.as_ref() {
+                    // Declared before its body is compiled, so it can recurse
+                    let func_idx = self.declare_function(name.clone(), params, body);
+                    self.functions.insert(name.clone(), func_idx);
+                } else {
+                    self.compile_expr(value)?;
+                    // Store the value in a global variable
+                    let name_id = self.compiler.add_constant(BytecodeValue::String(name.clone()));
+                    self.compiler.emit(Bytecode::StoreGlobal(name_id, self.effect_context));
+                }
+                let null_const = self.compiler.add_constant(BytecodeValue::Null);
+                self.compiler.emit(Bytecode::Const(null_const, EffectGrade::Pure));
             }
 
-            // Set expressions (assignment)
+            // Set expressions (assignment), which evaluate to null
             Expr::Set(name, value, _) => {
                 self.compile_expr(value)?;
-                // Store the value in a local variable
-                let name_id = self.compiler.add_constant(BytecodeValue::String(name.clone()));
-                self.compiler.emit(Bytecode::Store(name_id, self.effect_context));
+                if let Some(&local_idx) = self.variables.get(name) {
+                    self.compiler.emit(Bytecode::Store(local_idx, EffectGrade::Write));
+                } else {
+                    let name_id = self.compiler.add_constant(BytecodeValue::String(name.clone()));
+                    self.compiler.emit(Bytecode::StoreGlobal(name_id, self.effect_context));
+                }
+                let null_const = self.compiler.add_constant(BytecodeValue::Null);
+                self.compiler.emit(Bytecode::Const(null_const, EffectGrade::Pure));
             }
         }
         
@@ -169,14 +203,14 @@ impl EnhancedTlispCompiler {
                 "-" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Sub(c.effect_context))),
                 "*" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Mul(c.effect_context))),
                 "/" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Div(c.effect_context))),
-                "%" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Mod(c.effect_context))),
+                "%" | "mod" | "modulo" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Mod(c.effect_context))),
 
                 // Enhanced arithmetic
                 "divrem" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::DivRem(c.effect_context))),
                 "abs" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Abs(c.effect_context))),
                 "neg" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Neg(c.effect_context))),
-                "min" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::Min(c.effect_context))),
-                "max" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::Max(c.effect_context))),
+                "min" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Min(c.effect_context))),
+                "max" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Max(c.effect_context))),
                 "sqrt" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Sqrt(c.effect_context))),
                 "pow" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::Pow(c.effect_context))),
                 "sin" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Sin(c.effect_context))),
@@ -211,9 +245,16 @@ impl EnhancedTlispCompiler {
                 ">" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::Gt(c.effect_context))),
                 ">=" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::Ge(c.effect_context))),
                 
+                // Logical operations
+                "and" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::And(c.effect_context))),
+                "or" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::Or(c.effect_context))),
+                "not" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Not(c.effect_context))),
+                
                 // String operations
                 "string-length" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::StrLen(c.effect_context))),
                 "string-concat" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::StrConcat(c.effect_context))),
+                "string-append" => return self.compile_string_append(args),
+                "substring" => return self.compile_substring(args),
                 "string-slice" => return self.compile_string_slice(args),
                 "string-index" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::StrIndex(c.effect_context))),
                 "string-split" => return self.compile_string_split(args),
@@ -221,27 +262,37 @@ impl EnhancedTlispCompiler {
                 // List operations
                 "list" => return self.compile_list_creation(args),
                 "list-append" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::ListAppend(c.effect_context))),
-                "list-length" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::ListLen(c.effect_context))),
-                "list-get" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::ListGet(c.effect_context))),
+                "list-length" | "length" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::ListLen(c.effect_context))),
+                "list-get" | "list-ref" | "nth" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::ListGet(c.effect_context))),
+                "append" => return self.compile_arithmetic_op(args, |c| c.compiler.emit(Bytecode::ArrayConcat(c.effect_context))),
+                "sort" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::ArraySort(c.effect_context))),
                 "list-set!" => return self.compile_list_set(args),
+                "tuple" => return self.compile_tuple_creation(args),
                 
                 // Map operations
                 "make-map" => return self.compile_map_creation(args),
                 "map-get" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::MapGet(c.effect_context))),
-                "map-put!" => return self.compile_map_put(args),
-                "map-remove!" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::MapRemove(c.effect_context))),
+                "map-put!" | "map-put" => return self.compile_map_put(args),
+                "map-remove!" | "map-remove" => return self.compile_binary_op(args, |c| c.compiler.emit(Bytecode::MapRemove(c.effect_context))),
                 "map-keys" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::MapKeys(c.effect_context))),
                 "map-values" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::MapValues(c.effect_context))),
                 "map-size" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::MapSize(c.effect_context))),
                 
                 // Control flow
+                "begin" => return self.compile_sequence(args),
+                "try" => return self.compile_try(args),
+                "throw" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Throw(EffectGrade::Pure))),
                 "while" => return self.compile_while_loop(args),
                 "for" => return self.compile_for_loop(args),
                 "break" => return self.compile_break(),
                 "continue" => return self.compile_continue(),
                 
                 // I/O operations
-                "print" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Print(EffectGrade::IO))),
+                "print" | "println" => return self.compile_unary_op(args, |c| {
+                    c.compiler.emit(Bytecode::Print(EffectGrade::IO));
+                    let null = c.compiler.add_constant(BytecodeValue::Null);
+                    c.compiler.emit(Bytecode::Const(null, EffectGrade::Pure));
+                }),
                 "read" => return self.compile_nullary_op(args, |c| c.compiler.emit(Bytecode::Read(EffectGrade::IO))),
                 
                 // File operations
@@ -255,11 +306,23 @@ impl EnhancedTlispCompiler {
                 "free" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Free(EffectGrade::Memory))),
                 "gc-collect" => return self.compile_nullary_op(args, |c| c.compiler.emit(Bytecode::GcCollect(EffectGrade::Memory))),
                 
+                // Type operations
+                "type-of" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::TypeOf(c.effect_context))),
+                "to-int" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Cast(cast::INT, c.effect_context))),
+                "to-float" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Cast(cast::FLOAT, c.effect_context))),
+                "to-string" | "number->string" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Cast(cast::STRING, c.effect_context))),
+                "string->number" => return self.compile_unary_op(args, |c| c.compiler.emit(Bytecode::Cast(cast::NUMBER, c.effect_context))),
+                
                 // Actor operations
                 "spawn" => return self.compile_spawn(args),
                 "send" => return self.compile_send(args),
-                "receive" => return self.compile_nullary_op(args, |c| c.compiler.emit(Bytecode::ReceiveMessage(EffectGrade::IO))),
-                "self" => return self.compile_nullary_op(args, |c| c.compiler.emit(Bytecode::Self_(EffectGrade::Read))),
+                "receive" => return self.compile_nullary_op(args, |c| c.compiler.emit(Bytecode::Receive(EffectGrade::Read))),
+                "self" => return self.compile_nullary_op(args, |c| c.compiler.emit(Bytecode::SelfPid(EffectGrade::Read))),
+                "yield" => return self.compile_nullary_op(args, |c| {
+                    c.compiler.emit(Bytecode::Yield(EffectGrade::Pure));
+                    let null = c.compiler.add_constant(BytecodeValue::Null);
+                    c.compiler.emit(Bytecode::Const(null, EffectGrade::Pure));
+                }),
                 
                 // Time operations
                 "get-time" => return self.compile_nullary_op(args, |c| c.compiler.emit(Bytecode::GetTime(EffectGrade::IO))),
@@ -271,20 +334,47 @@ impl EnhancedTlispCompiler {
             }
         }
         
+        // Calls go to a function known when compiling
+        let func_idx = match func {
+            Expr::Symbol(name, _) if !self.variables.contains_key(name) => self.functions.get(name).copied()
+                .ok_or_else(|| BytecodeError::CompilationFailed(format!("Undefined function: {}", name)))?,
+            Expr::Lambda(params, body, _) => self.declare_function("lambda".to_string(), params, body),
+            _ => return Err(BytecodeError::CompilationFailed(
+                "Only functions defined by name and lambdas can be called".to_string()
+            )),
+        };
+        let param_count = self.compiler.function(func_idx).map_or(0, |function| function.param_count);
+        if args.len() != param_count {
+            return Err(BytecodeError::CompilationFailed(format!(
+                "Function takes {} arguments, not {}", param_count, args.len()
+            )));
+        }
+
         // Compile arguments
         for arg in args {
             self.compile_expr(arg)?;
         }
-        
-        // Compile function
-        self.compile_expr(func)?;
-        
+
         // Call function
-        let arg_count = self.compiler.add_constant(BytecodeValue::Int(args.len() as i64));
-        self.compiler.emit(Bytecode::Call(arg_count, EffectGrade::IO));
+        self.compiler.emit(Bytecode::Call(func_idx, EffectGrade::IO));
         
         Ok(())
     }
+
+    /// Compile expressions in order, evaluating to the last one's value,
+    /// or to null when there are none
+    pub fn compile_sequence(&mut self, exprs: &[Expr<Type>]) -> BytecodeResult<()> {
+        let Some((last, rest)) = exprs.split_last() else {
+            let null_const = self.compiler.add_constant(BytecodeValue::Null);
+            self.compiler.emit(Bytecode::Const(null_const, EffectGrade::Pure));
+            return Ok(());
+        };
+        for expr in rest {
+            self.compile_expr(expr)?;
+            self.compiler.emit(Bytecode::Pop(EffectGrade::Pure));
+        }
+        self.compile_expr(last)
+    }
     
     /// Compile arithmetic operation with multiple arguments
     fn compile_arithmetic_op<F>(&mut self, args: &[Expr<Type>], op: F) -> BytecodeResult<()>
@@ -386,6 +476,44 @@ impl EnhancedTlispCompiler {
         Ok(())
     }
     
+    /// Compile `(try body (catch name handler))`, which evaluates to the
+    /// body's value, or to the handler's with `name` bound to what was thrown
+    fn compile_try(&mut self, args: &[Expr<Type>]) -> BytecodeResult<()> {
+        let (body, name, handler) = match args {
+            [body, Expr::Application(catch, clause, _)] => match (catch.as_ref(), clause.as_slice()) {
+                (Expr::Symbol(keyword, _), [Expr::Symbol(name, _), handler]) if keyword == "catch" => (body, name, handler),
+                _ => return Err(BytecodeError::CompilationFailed("try expects (catch name handler)".to_string())),
+            },
+            _ => return Err(BytecodeError::CompilationFailed("try requires a body and a catch clause".to_string())),
+        };
+
+        let handler_label = self.compiler.create_label("try_catch");
+        let end_label = self.compiler.create_label("try_end");
+
+        let handler_pc = self.compiler.label_ref(handler_label.clone());
+        self.compiler.emit(Bytecode::TryBegin(handler_pc, EffectGrade::Pure));
+        self.compile_expr(body)?;
+        self.compiler.emit(Bytecode::TryEnd(EffectGrade::Pure));
+        let end_pc = self.compiler.label_ref(end_label.clone());
+        self.compiler.emit(Bytecode::Jump(end_pc, EffectGrade::Pure));
+
+        // The handler starts with the exception on the stack
+        self.compiler.place_label(handler_label);
+        let local_idx = self.local_count;
+        self.local_count += 1;
+        let shadowed = self.variables.insert(name.clone(), local_idx);
+        self.compiler.emit(Bytecode::Store(local_idx, EffectGrade::Write));
+        self.compile_expr(handler)?;
+        match shadowed {
+            Some(old_idx) => self.variables.insert(name.clone(), old_idx),
+            None => self.variables.remove(name),
+        };
+        self.local_count = local_idx;
+
+        self.compiler.place_label(end_label);
+        Ok(())
+    }
+
     /// Compile let bindings
     fn compile_let(&mut self, bindings: &[(String, Expr<Type>)], body: &Expr<Type>) -> BytecodeResult<()> {
         let saved_locals = self.local_count;
@@ -427,58 +555,100 @@ impl EnhancedTlispCompiler {
         Ok(())
     }
     
-    /// Compile lambda expression
-    fn compile_lambda(&mut self, params: &[String], body: &Expr<Type>) -> BytecodeResult<()> {
-        // Create a new function
-        let func_name = format!("lambda_{}", self.functions.len());
-        let func_idx = self.compiler.start_function(func_name.clone(), params.len());
-        
-        // Save current state
-        let saved_compiler = std::mem::replace(&mut self.compiler, BytecodeCompiler::new(func_name));
+    /// Declare a function for `finish` to compile, returning its index
+    fn declare_function(&mut self, name: String, params: &[String], body: &Expr<Type>) -> u32 {
+        let id = self.compiler.declare_function(name, params.len());
+        self.pending.push(PendingFunction { id, params: params.to_vec(), body: body.clone() });
+        id
+    }
+
+    /// Compile a declared function, which finds its arguments on the stack
+    /// and sees only them and globals
+    fn compile_function(&mut self, function: PendingFunction) -> BytecodeResult<()> {
+        self.compiler.start_declared_function(function.id)?;
         let saved_vars = std::mem::take(&mut self.variables);
+        let saved_loops = std::mem::take(&mut self.loop_stack);
         let saved_locals = self.local_count;
-        
-        // Set up parameters as local variables
-        self.local_count = 0;
-        for (i, param) in params.iter().enumerate() {
+
+        // Set up parameters as local variables, storing the last argument,
+        // on top of the stack, first
+        self.local_count = function.params.len() as u32;
+        for (i, param) in function.params.iter().enumerate() {
             self.variables.insert(param.clone(), i as u32);
-            self.local_count += 1;
         }
-        
-        // Compile function body
-        self.compile_expr(body)?;
+        for i in (0..self.local_count).rev() {
+            self.compiler.emit(Bytecode::Store(i, EffectGrade::Write));
+        }
+
+        // A lambda with several body expressions runs them in order
+        match &function.body {
+            Expr::List(exprs, _) => self.compile_sequence(exprs)?,
+            body => self.compile_expr(body)?,
+        }
         self.compiler.emit(Bytecode::Ret(EffectGrade::Pure));
-        
-        // Finish function compilation
-        let func_id = self.compiler.finish_function()?;
-        
+        self.compiler.finish_declared_function(function.id)?;
+
         // Restore state
-        self.compiler = saved_compiler;
         self.variables = saved_vars;
+        self.loop_stack = saved_loops;
         self.local_count = saved_locals;
-        
-        // Push function reference
-        let func_const = self.compiler.add_constant(BytecodeValue::Function(func_id));
-        self.compiler.emit(Bytecode::Const(func_const, EffectGrade::Pure));
-        
         Ok(())
     }
-    
+
+    /// Compile `string-append`, which joins any number of strings
+    fn compile_string_append(&mut self, args: &[Expr<Type>]) -> BytecodeResult<()> {
+        let Some((first, rest)) = args.split_first() else {
+            let empty_const = self.compiler.add_constant(BytecodeValue::String(String::new()));
+            self.compiler.emit(Bytecode::Const(empty_const, EffectGrade::Pure));
+            return Ok(());
+        };
+        self.compile_expr(first)?;
+        for arg in rest {
+            self.compile_expr(arg)?;
+            self.compiler.emit(Bytecode::StrConcat(self.effect_context));
+        }
+        Ok(())
+    }
+
+    /// Compile `(substring string start [end])`, which runs to the end of
+    /// the string without `end`
+    fn compile_substring(&mut self, args: &[Expr<Type>]) -> BytecodeResult<()> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(BytecodeError::CompilationFailed("substring requires 2 or 3 arguments".to_string()));
+        }
+
+        // The bounds are instruction operands, so they must be literals
+        let bound = |expr: Option<&Expr<Type>>| match expr {
+            Some(Expr::Number(n, _)) => u32::try_from(*n).ok(),
+            None => Some(u32::MAX),
+            _ => None,
+        };
+        let (Some(start), Some(end)) = (bound(args.get(1)), bound(args.get(2))) else {
+            return Err(BytecodeError::CompilationFailed("substring requires literal, non-negative bounds".to_string()));
+        };
+
+        self.compile_expr(&args[0])?; // string
+        self.compiler.emit(Bytecode::StrSlice(start, end, self.effect_context));
+        Ok(())
+    }
+
     /// Compile string slice operation
     fn compile_string_slice(&mut self, args: &[Expr<Type>]) -> BytecodeResult<()> {
         if args.len() != 3 {
             return Err(BytecodeError::CompilationFailed("string-slice requires 3 arguments".to_string()));
         }
 
-        self.compile_expr(&args[0])?; // string
-        self.compile_expr(&args[1])?; // start
-        self.compile_expr(&args[2])?; // end
-
-        // Get start and end as constants for now (could be optimized)
-        let start_const = self.compiler.add_constant(BytecodeValue::Int(0));
-        let end_const = self.compiler.add_constant(BytecodeValue::Int(-1));
+        // The bounds are instruction operands, so they must be literals
+        let bound = |expr: &Expr<Type>| match expr {
+            Expr::Number(n, _) => u32::try_from(*n).ok(),
+            _ => None,
+        };
+        let (Some(start), Some(end)) = (bound(&args[1]), bound(&args[2])) else {
+            return Err(BytecodeError::CompilationFailed("string-slice requires literal, non-negative bounds".to_string()));
+        };
 
-        self.compiler.emit(Bytecode::StrSlice(start_const, end_const, self.effect_context));
+        self.compile_expr(&args[0])?; // string
+        self.compiler.emit(Bytecode::StrSlice(start, end, self.effect_context));
         Ok(())
     }
 
@@ -488,10 +658,13 @@ impl EnhancedTlispCompiler {
             return Err(BytecodeError::CompilationFailed("string-split requires 2 arguments".to_string()));
         }
 
-        self.compile_expr(&args[0])?; // string
-        self.compile_expr(&args[1])?; // delimiter
+        // The delimiter is a constant operand, so it must be a literal
+        let Expr::String(delimiter, _) = &args[1] else {
+            return Err(BytecodeError::CompilationFailed("string-split requires a literal delimiter".to_string()));
+        };
 
-        let delim_const = self.compiler.add_constant(BytecodeValue::String(" ".to_string()));
+        self.compile_expr(&args[0])?; // string
+        let delim_const = self.compiler.add_constant(BytecodeValue::String(delimiter.clone()));
         self.compiler.emit(Bytecode::StrSplit(delim_const, self.effect_context));
         Ok(())
     }
@@ -511,6 +684,16 @@ impl EnhancedTlispCompiler {
         Ok(())
     }
 
+    /// Compile tuple creation
+    fn compile_tuple_creation(&mut self, args: &[Expr<Type>]) -> BytecodeResult<()> {
+        for arg in args {
+            self.compile_expr(arg)?;
+        }
+
+        self.compiler.emit(Bytecode::TupleNew(args.len() as u32, EffectGrade::Memory));
+        Ok(())
+    }
+
     /// Compile list set operation
     fn compile_list_set(&mut self, args: &[Expr<Type>]) -> BytecodeResult<()> {
         if args.len() != 3 {
@@ -536,11 +719,9 @@ impl EnhancedTlispCompiler {
 
         // Add key-value pairs
         for chunk in args.chunks(2) {
-            self.compiler.emit(Bytecode::Dup(EffectGrade::Pure)); // Duplicate map
             self.compile_expr(&chunk[0])?; // key
             self.compile_expr(&chunk[1])?; // value
-            self.compiler.emit(Bytecode::MapPut(EffectGrade::Write));
-            self.compiler.emit(Bytecode::Pop(EffectGrade::Pure)); // Pop result
+            self.compiler.emit(Bytecode::MapPut(EffectGrade::Write)); // Leaves the updated map
         }
 
         Ok(())
@@ -775,8 +956,7 @@ impl EnhancedTlispCompiler {
 
         self.compile_expr(&args[0])?; // function
 
-        let func_const = self.compiler.add_constant(BytecodeValue::Function(0));
-        self.compiler.emit(Bytecode::SpawnProcess(func_const, EffectGrade::IO));
+        self.compiler.emit(Bytecode::Spawn(0, EffectGrade::Spawn));
         Ok(())
     }
 
@@ -789,8 +969,7 @@ impl EnhancedTlispCompiler {
         self.compile_expr(&args[0])?; // pid
         self.compile_expr(&args[1])?; // message
 
-        // Use SendMessage with placeholder values for pid and message locals
-        self.compiler.emit(Bytecode::SendMessage(0, 1, EffectGrade::IO));
+        self.compiler.emit(Bytecode::Send(EffectGrade::Send));
         Ok(())
     }
 
@@ -811,7 +990,60 @@ impl EnhancedTlispCompiler {
     }
 
     /// Finish compilation and return program
-    pub fn finish(self) -> BytecodeResult<BytecodeProgram> {
+    pub fn finish(mut self) -> BytecodeResult<BytecodeProgram> {
+        if !self.pending.is_empty() {
+            // Functions follow the top-level code, which jumps past them
+            let end_label = self.compiler.create_label("program_end");
+            let end_pc = self.compiler.label_ref(end_label.clone());
+            self.compiler.emit(Bytecode::Jump(end_pc, EffectGrade::Pure));
+
+            // Compiling a function may declare the lambdas in it
+            while !self.pending.is_empty() {
+                for function in std::mem::take(&mut self.pending) {
+                    self.compile_function(function)?;
+                }
+            }
+            self.compiler.place_label(end_label);
+        }
         self.compiler.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::BytecodeVM;
+    use crate::tlisp::TlispInterpreter;
+
+    fn run(source: &str) -> BytecodeResult<BytecodeValue> {
+        let mut interpreter = TlispInterpreter::new();
+        let expr = interpreter.parse(source).unwrap();
+        let program = interpreter.compile_to_bytecode_untyped(expr)?;
+        BytecodeVM::new().execute_program(&program)
+    }
+
+    #[test]
+    fn test_try_catch() {
+        assert_eq!(run("(try (throw 5) (catch e (+ e 1)))").unwrap(), BytecodeValue::Int(6));
+        assert_eq!(run("(try (+ 1 2) (catch e 0))").unwrap(), BytecodeValue::Int(3));
+        assert_eq!(run("(+ 1 (try (try (throw 1) (catch e (throw (+ e 1)))) (catch e (* e 10))))").unwrap(), BytecodeValue::Int(21));
+
+        // Errors the VM raises inside a try are caught as their messages
+        let caught = run("(try (/ 1 0) (catch e e))").unwrap();
+        assert!(matches!(&caught, BytecodeValue::String(message) if message.contains("zero")), "{:?}", caught);
+        assert!(run("(throw 5)").is_err());
+        assert!(run("(try 1 (finally 2))").is_err());
+    }
+
+    #[test]
+    fn test_functions() {
+        // Functions see their parameters and may call functions defined after them
+        assert_eq!(run("(begin (define (even n) (if (= n 0) true (odd (- n 1)))) (define (odd n) (if (= n 0) false (even (- n 1)))) (even 10))").unwrap(), BytecodeValue::Bool(true));
+        assert_eq!(run("((lambda (x y) (let ((z (* x y))) (+ z 1))) 6 7)").unwrap(), BytecodeValue::Int(43));
+        assert_eq!(run("(begin (define total 0) (define (add n) (set! total (+ total n))) (add 2) (add 3) total)").unwrap(), BytecodeValue::Int(5));
+        assert_eq!(run("(let ((s \"hello\")) (string-append s \" \" \"world\"))").unwrap(), BytecodeValue::String("hello world".to_string()));
+
+        assert!(run("(undefined 1)").is_err());
+        assert!(run("(begin (define (f x) x) (f 1 2))").is_err());
+    }
+}
@@ -244,6 +244,9 @@ impl StandardEnvironment {
         env.define(">".to_string(), Value::Builtin("gt".to_string()));
         env.define(">=".to_string(), Value::Builtin("ge".to_string()));
         
+        // Sequencing
+        env.define("begin".to_string(), Value::Builtin("begin".to_string()));
+
         // List functions
         env.define("list".to_string(), Value::Builtin("list".to_string()));
         env.define("car".to_string(), Value::Builtin("car".to_string()));
@@ -278,6 +281,71 @@ impl StandardEnvironment {
         env.define("hypervisor:kill-actor".to_string(), Value::Builtin("hypervisor:kill-actor".to_string()));
         env.define("hypervisor:get-supervision-tree".to_string(), Value::Builtin("hypervisor:get-supervision-tree".to_string()));
 
+        // Replicated key-value store
+        env.define("kv-put".to_string(), Value::Builtin("kv-put".to_string()));
+        env.define("kv-get".to_string(), Value::Builtin("kv-get".to_string()));
+        env.define("kv-cas".to_string(), Value::Builtin("kv-cas".to_string()));
+        env.define("kv-watch".to_string(), Value::Builtin("kv-watch".to_string()));
+
+        // Distributed locks and leader election
+        env.define("lock-acquire".to_string(), Value::Builtin("lock-acquire".to_string()));
+        env.define("lock-release".to_string(), Value::Builtin("lock-release".to_string()));
+        env.define("elect-leader".to_string(), Value::Builtin("elect-leader".to_string()));
+
+        // Conflict-free replicated data types
+        env.define("crdt-new".to_string(), Value::Builtin("crdt-new".to_string()));
+        env.define("crdt-merge".to_string(), Value::Builtin("crdt-merge".to_string()));
+        env.define("crdt-value".to_string(), Value::Builtin("crdt-value".to_string()));
+
+        // WebAssembly actors
+        env.define("spawn-wasm".to_string(), Value::Builtin("spawn-wasm".to_string()));
+
+        // Shared binaries
+        env.define("string->bytes".to_string(), Value::Builtin("string->bytes".to_string()));
+        env.define("bytes->string".to_string(), Value::Builtin("bytes->string".to_string()));
+        env.define("bytes-length".to_string(), Value::Builtin("bytes-length".to_string()));
+        env.define("bytes-slice".to_string(), Value::Builtin("bytes-slice".to_string()));
+        env.define("bytes?".to_string(), Value::Builtin("bytes?".to_string()));
+
+        // Files and environment
+        env.define("read-file".to_string(), Value::Builtin("read-file".to_string()));
+        env.define("write-file".to_string(), Value::Builtin("write-file".to_string()));
+        env.define("env-get".to_string(), Value::Builtin("env-get".to_string()));
+        env.define("secret-get".to_string(), Value::Builtin("secret-get".to_string()));
+        env.define("secret-set".to_string(), Value::Builtin("secret-set".to_string()));
+
+        // ORM models
+        env.define("define-model".to_string(), Value::Builtin("define-model".to_string()));
+        env.define("orm-find".to_string(), Value::Builtin("orm-find".to_string()));
+        env.define("orm-where".to_string(), Value::Builtin("orm-where".to_string()));
+        env.define("orm-save".to_string(), Value::Builtin("orm-save".to_string()));
+        env.define("defresolver".to_string(), Value::Builtin("defresolver".to_string()));
+
+        // Workflows
+        env.define("defsaga".to_string(), Value::Builtin("defsaga".to_string()));
+
+        // Testing
+        env.define("deftest".to_string(), Value::Builtin("deftest".to_string()));
+        env.define("defbench".to_string(), Value::Builtin("defbench".to_string()));
+        env.define("assert".to_string(), Value::Builtin("assert".to_string()));
+        env.define("assert-equal".to_string(), Value::Builtin("assert-equal".to_string()));
+        env.define("assert-eq".to_string(), Value::Builtin("assert-eq".to_string()));
+        env.define("assert-error".to_string(), Value::Builtin("assert-error".to_string()));
+
+        // Property-based testing
+        env.define("gen-int".to_string(), Value::Builtin("gen-int".to_string()));
+        env.define("gen-bool".to_string(), Value::Builtin("gen-bool".to_string()));
+        env.define("gen-string".to_string(), Value::Builtin("gen-string".to_string()));
+        env.define("gen-list".to_string(), Value::Builtin("gen-list".to_string()));
+        env.define("gen-record".to_string(), Value::Builtin("gen-record".to_string()));
+        env.define("forall".to_string(), Value::Builtin("forall".to_string()));
+
+        // Logging
+        env.define("log-debug".to_string(), Value::Builtin("log-debug".to_string()));
+        env.define("log-info".to_string(), Value::Builtin("log-info".to_string()));
+        env.define("log-warn".to_string(), Value::Builtin("log-warn".to_string()));
+        env.define("log-error".to_string(), Value::Builtin("log-error".to_string()));
+
         // Constants
         env.define("true".to_string(), Value::Bool(true));
         env.define("false".to_string(), Value::Bool(false));
@@ -0,0 +1,841 @@
+//! gRPC client and server
+//!
+//! Services are described by `.proto` files, or binary `FileDescriptorSet`s
+//! as `protoc --descriptor_set_out` writes them, loaded at runtime with
+//! `(grpc-load path)`; no code is generated at build time. Messages are
+//! built from and read into TLisp values by their descriptors: a message is
+//! a list of `(field value)` pairs, a repeated field a list, a map a list of
+//! `(key value)` pairs, an enum its value's name or number and bytes a list
+//! of numbers or a string. Fields left at their default are not read back.
+//!
+//! `(grpc-call url "pkg.Service/Method" request [timeout-ms])` calls a
+//! unary method of a server at an `http://` or `https://` URL and returns
+//! its response.
+//!
+//! `(grpc-serve port services handler)` serves the unary methods of loaded
+//! services, handing each call to the handler actor and waiting for its
+//! answer, as an ask. The handler is sent `("grpc-request" id method request
+//! reply-to)`, with the request as protobuf JSON under its fields' proto
+//! names, and answers by sending `("grpc-reply" id response)` or
+//! `("grpc-error" id code message)` to `reply-to`, or from TLisp with
+//! `(grpc-reply id response)` and `(grpc-error id code message)`. A call
+//! that gets no answer within the reply timeout fails with
+//! `DEADLINE_EXCEEDED`; streaming methods are answered `UNIMPLEMENTED`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use prost_reflect::prost::Message as _;
+use prost_reflect::prost_types::FileDescriptorSet;
+use prost_reflect::{DescriptorPool, DynamicMessage, Kind, MapKey, MessageDescriptor, MethodDescriptor, ReflectMessage, SerializeOptions};
+use protobuf::Message as _;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::{Code, Status};
+
+use crate::error::{RuntimeError, RuntimeResult, TlispError, TlispResult};
+use crate::orm::cdc::ActorSender;
+use crate::runtime::{ReamActor, ReamRuntime};
+use crate::tlisp::Value;
+use crate::types::{MessagePayload, Pid};
+
+/// Longest wait for a connection to a server
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest wait for a response, or for a handler's answer, unless given
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs every call made and served
+static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("grpc")
+        .enable_all()
+        .build()
+        .expect("could not start the gRPC runtime")
+});
+
+/// Every file loaded, shared by every interpreter of the process
+static POOL: Lazy<RwLock<DescriptorPool>> = Lazy::new(|| RwLock::new(DescriptorPool::new()));
+
+/// Channels by URL, reconnecting as needed
+static CHANNELS: Lazy<Mutex<HashMap<String, Channel>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Calls served and waiting for their handler's answer, by id
+static PENDING: Lazy<Mutex<HashMap<u64, Pending>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_CALL: AtomicU64 = AtomicU64::new(1);
+
+fn failed(what: &str, error: impl std::fmt::Display) -> TlispError {
+    TlispError::Runtime(format!("{}: {}", what, error))
+}
+
+/// Load the services of a `.proto` file, resolving its imports next to it,
+/// or of a binary `FileDescriptorSet`, and return their full names
+pub fn load(path: &Path) -> TlispResult<Vec<String>> {
+    let what = format!("grpc-load: {}", path.display());
+    // Imports are loaded along with a .proto file, but only its own
+    // services named
+    let (bytes, own) = if path.extension().is_some_and(|extension| extension == "proto") {
+        let include = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let parsed = protobuf_parse::Parser::new()
+            .pure()
+            .include(include)
+            .input(path)
+            .parse_and_typecheck()
+            .map_err(|e| failed(&what, format!("{:#}", e)))?;
+        let own: Vec<String> = parsed.relative_paths.iter().map(|path| path.to_string()).collect();
+        let mut set = protobuf::descriptor::FileDescriptorSet::new();
+        set.file = parsed.file_descriptors;
+        (set.write_to_bytes().map_err(|e| failed(&what, e))?, Some(own))
+    } else {
+        (std::fs::read(path).map_err(|e| failed(&what, e))?, None)
+    };
+    let set = FileDescriptorSet::decode(bytes.as_slice()).map_err(|e| failed(&what, e))?;
+    let files = own.unwrap_or_else(|| set.file.iter().map(|file| file.name().to_string()).collect());
+
+    let mut pool = POOL.write().unwrap();
+    pool.add_file_descriptor_set(set).map_err(|e| failed(&what, e))?;
+    Ok(pool
+        .services()
+        .filter(|service| files.iter().any(|file| file == service.parent_file().name()))
+        .map(|service| service.full_name().to_string())
+        .collect())
+}
+
+/// The method named `pkg.Service/Method` among the services loaded
+pub fn method(name: &str) -> TlispResult<MethodDescriptor> {
+    let (service, method) = name
+        .trim_start_matches('/')
+        .split_once('/')
+        .ok_or_else(|| TlispError::Runtime(format!("grpc: expected a method as pkg.Service/Method, got {}", name)))?;
+    let service = POOL
+        .read()
+        .unwrap()
+        .get_service_by_name(service)
+        .ok_or_else(|| TlispError::Runtime(format!("grpc: no service {} is loaded", service)))?;
+    let found = service.methods().find(|candidate| candidate.name() == method);
+    found.ok_or_else(|| TlispError::Runtime(format!("grpc: {} has no method {}", service.full_name(), method)))
+}
+
+fn path_of(method: &MethodDescriptor) -> String {
+    format!("{}/{}", method.parent_service().full_name(), method.name())
+}
+
+fn is_streaming(method: &MethodDescriptor) -> bool {
+    method.is_client_streaming() || method.is_server_streaming()
+}
+
+fn mismatch(what: &str, kind: &Kind, value: &Value) -> TlispError {
+    TlispError::Runtime(format!("grpc: {}: expected {}, got {}", what, kind_name(kind), value))
+}
+
+fn kind_name(kind: &Kind) -> String {
+    match kind {
+        Kind::Message(message) => message.full_name().to_string(),
+        Kind::Enum(enumeration) => enumeration.full_name().to_string(),
+        other => format!("{:?}", other).to_lowercase(),
+    }
+}
+
+/// `(name value)` pairs of a message, map or record value
+fn pairs<'a>(what: &str, value: &'a Value) -> TlispResult<Vec<(String, &'a Value)>> {
+    match value {
+        Value::Null | Value::Unit => Ok(Vec::new()),
+        Value::List(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::List(pair) if pair.len() == 2 => match &pair[0] {
+                    Value::String(name) | Value::Symbol(name) => Ok((name.clone(), &pair[1])),
+                    other => Ok((other.to_string(), &pair[1])),
+                },
+                other => Err(TlispError::Runtime(format!("grpc: {}: expected a (name value) pair, got {}", what, other))),
+            })
+            .collect(),
+        other => Err(TlispError::Runtime(format!("grpc: {}: expected a list of (name value) pairs, got {}", what, other))),
+    }
+}
+
+/// Build a message from a list of `(field value)` pairs
+pub fn to_message(value: &Value, descriptor: &MessageDescriptor) -> TlispResult<DynamicMessage> {
+    let mut message = DynamicMessage::new(descriptor.clone());
+    for (name, value) in pairs(descriptor.full_name(), value)? {
+        let field = descriptor
+            .get_field_by_name(&name)
+            .or_else(|| descriptor.get_field_by_json_name(&name))
+            .ok_or_else(|| TlispError::Runtime(format!("grpc: {} has no field {}", descriptor.full_name(), name)))?;
+        let what = field.full_name().to_string();
+        let kind = field.kind();
+        let converted = if field.is_map() {
+            let entry = kind.as_message().expect("map fields are messages");
+            let (key, entry_value) = (entry.map_entry_key_field(), entry.map_entry_value_field());
+            let mut entries = HashMap::new();
+            for (key_name, item) in pairs(&what, value)? {
+                let key_value = Value::String(key_name);
+                let key = match to_field_value(&what, &key_value, &key.kind()) {
+                    Ok(key) => key,
+                    // Keys other than strings are read from their printed form
+                    Err(_) => to_field_value(&what, &parse_key(&key_value), &key.kind())?,
+                };
+                let key = key.into_map_key().ok_or_else(|| mismatch(&what, &kind, value))?;
+                entries.insert(key, to_field_value(&what, item, &entry_value.kind())?);
+            }
+            prost_reflect::Value::Map(entries)
+        } else if field.is_list() {
+            match value {
+                Value::List(items) => prost_reflect::Value::List(
+                    items.iter().map(|item| to_field_value(&what, item, &kind)).collect::<TlispResult<_>>()?,
+                ),
+                other => return Err(TlispError::Runtime(format!("grpc: {}: expected a list, got {}", what, other))),
+            }
+        } else {
+            to_field_value(&what, value, &kind)?
+        };
+        message.set_field(&field, converted);
+    }
+    Ok(message)
+}
+
+/// A map key printed as a string, read back as a number or boolean
+fn parse_key(key: &Value) -> Value {
+    match key {
+        Value::String(text) => text
+            .parse::<i64>()
+            .map(Value::Int)
+            .or_else(|_| text.parse::<bool>().map(Value::Bool))
+            .unwrap_or_else(|_| key.clone()),
+        other => other.clone(),
+    }
+}
+
+fn to_field_value(what: &str, value: &Value, kind: &Kind) -> TlispResult<prost_reflect::Value> {
+    use prost_reflect::Value as P;
+    let wrong = || mismatch(what, kind, value);
+    Ok(match (kind, value) {
+        (Kind::Double, Value::Float(f)) => P::F64(*f),
+        (Kind::Double, Value::Int(i)) => P::F64(*i as f64),
+        (Kind::Float, Value::Float(f)) => P::F32(*f as f32),
+        (Kind::Float, Value::Int(i)) => P::F32(*i as f32),
+        (Kind::Int32 | Kind::Sint32 | Kind::Sfixed32, Value::Int(i)) => P::I32(i32::try_from(*i).map_err(|_| wrong())?),
+        (Kind::Int64 | Kind::Sint64 | Kind::Sfixed64, Value::Int(i)) => P::I64(*i),
+        (Kind::Uint32 | Kind::Fixed32, Value::Int(i)) => P::U32(u32::try_from(*i).map_err(|_| wrong())?),
+        (Kind::Uint64 | Kind::Fixed64, Value::Int(i)) => P::U64(u64::try_from(*i).map_err(|_| wrong())?),
+        (Kind::Bool, Value::Bool(b)) => P::Bool(*b),
+        (Kind::String, Value::String(s) | Value::Symbol(s)) => P::String(s.clone()),
+        (Kind::Bytes, Value::String(s)) => P::Bytes(s.clone().into_bytes().into()),
+        (Kind::Bytes, Value::List(items)) => P::Bytes(
+            items
+                .iter()
+                .map(|item| match item {
+                    Value::Int(byte) => u8::try_from(*byte).map_err(|_| wrong()),
+                    _ => Err(wrong()),
+                })
+                .collect::<TlispResult<Vec<u8>>>()?
+                .into(),
+        ),
+        (Kind::Enum(enumeration), Value::String(name) | Value::Symbol(name)) => {
+            P::EnumNumber(enumeration.get_value_by_name(name).ok_or_else(wrong)?.number())
+        }
+        (Kind::Enum(_), Value::Int(i)) => P::EnumNumber(i32::try_from(*i).map_err(|_| wrong())?),
+        (Kind::Message(message), value) => P::Message(to_message(value, message)?),
+        _ => return Err(wrong()),
+    })
+}
+
+/// Read a message into a list of `(field value)` pairs
+pub fn from_message(message: &DynamicMessage) -> Value {
+    Value::List(
+        message
+            .fields()
+            .map(|(field, value)| {
+                let kind = field.kind();
+                let value = match value {
+                    prost_reflect::Value::Map(entries) => {
+                        let entry = kind.as_message().expect("map fields are messages");
+                        let value_kind = entry.map_entry_value_field().kind();
+                        // Sorted, so that a map always reads the same
+                        let sorted: BTreeMap<String, (Value, Value)> = entries
+                            .iter()
+                            .map(|(key, value)| {
+                                let key = from_map_key(key);
+                                (key.to_string(), (key, from_field_value(value, &value_kind)))
+                            })
+                            .collect();
+                        Value::List(sorted.into_values().map(|(key, value)| Value::List(vec![key, value])).collect())
+                    }
+                    other => from_field_value(other, &kind),
+                };
+                Value::List(vec![Value::String(field.name().to_string()), value])
+            })
+            .collect(),
+    )
+}
+
+fn from_map_key(key: &MapKey) -> Value {
+    match key {
+        MapKey::Bool(b) => Value::Bool(*b),
+        MapKey::I32(i) => Value::Int(*i as i64),
+        MapKey::I64(i) => Value::Int(*i),
+        MapKey::U32(u) => Value::Int(*u as i64),
+        MapKey::U64(u) => i64::try_from(*u).map(Value::Int).unwrap_or(Value::Float(*u as f64)),
+        MapKey::String(s) => Value::String(s.clone()),
+    }
+}
+
+fn from_field_value(value: &prost_reflect::Value, kind: &Kind) -> Value {
+    use prost_reflect::Value as P;
+    match value {
+        P::Bool(b) => Value::Bool(*b),
+        P::I32(i) => Value::Int(*i as i64),
+        P::I64(i) => Value::Int(*i),
+        P::U32(u) => Value::Int(*u as i64),
+        P::U64(u) => i64::try_from(*u).map(Value::Int).unwrap_or(Value::Float(*u as f64)),
+        P::F32(f) => Value::Float(*f as f64),
+        P::F64(f) => Value::Float(*f),
+        P::String(s) => Value::String(s.clone()),
+        P::Bytes(bytes) => Value::List(bytes.iter().map(|byte| Value::Int(*byte as i64)).collect()),
+        P::EnumNumber(number) => match kind.as_enum().and_then(|enumeration| enumeration.get_value(*number)) {
+            Some(value) => Value::String(value.name().to_string()),
+            None => Value::Int(*number as i64),
+        },
+        P::Message(message) => from_message(message),
+        P::List(items) => Value::List(items.iter().map(|item| from_field_value(item, kind)).collect()),
+        P::Map(_) => unreachable!("maps are read by their field"),
+    }
+}
+
+/// Encodes and decodes messages by their descriptors
+#[derive(Clone)]
+struct DynamicCodec {
+    decodes: MessageDescriptor,
+}
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.decodes.clone())
+    }
+}
+
+struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: DynamicMessage, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        item.encode(dst).map_err(|e| Status::internal(format!("encoding {}: {}", item.descriptor().full_name(), e)))
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<DynamicMessage>, Status> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|e| Status::invalid_argument(format!("decoding {}: {}", self.0.full_name(), e)))
+    }
+}
+
+/// `host:port` of an `http://` or `https://` URL, as network permissions
+/// name it
+pub fn address(url: &str) -> TlispResult<String> {
+    let invalid = || TlispError::Runtime(format!("grpc-call: expected an http:// or https:// URL, got {}", url));
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid());
+    }
+    let host = parsed.host_str().ok_or_else(invalid)?;
+    Ok(format!("{}:{}", host, parsed.port_or_known_default().ok_or_else(invalid)?))
+}
+
+fn channel(url: &str) -> TlispResult<Channel> {
+    let mut channels = CHANNELS.lock().unwrap();
+    if let Some(channel) = channels.get(url) {
+        return Ok(channel.clone());
+    }
+    let what = format!("grpc-call: {}", url);
+    let mut endpoint = Endpoint::from_shared(url.to_string())
+        .map_err(|e| failed(&what, e))?
+        .connect_timeout(CONNECT_TIMEOUT);
+    if url.starts_with("https://") {
+        endpoint = endpoint
+            .tls_config(ClientTlsConfig::new().with_native_roots())
+            .map_err(|e| failed(&what, e))?;
+    }
+    let channel = {
+        let _entered = RUNTIME.enter();
+        endpoint.connect_lazy()
+    };
+    channels.insert(url.to_string(), channel.clone());
+    Ok(channel)
+}
+
+/// Call a unary method of the server at `url` and return its response
+pub fn call(url: &str, method_name: &str, request: &Value, timeout: Duration) -> TlispResult<Value> {
+    address(url)?;
+    let method = method(method_name)?;
+    if is_streaming(&method) {
+        return Err(TlispError::Runtime(format!("grpc-call: {} streams; only unary methods can be called", path_of(&method))));
+    }
+    let request = to_message(request, &method.input())?;
+    let channel = channel(url)?;
+    let path = path_of(&method);
+    let (done, answered) = flume::bounded(1);
+    RUNTIME.spawn(async move {
+        let mut client = tonic::client::Grpc::new(channel);
+        let call = async {
+            client.ready().await.map_err(|e| Status::unavailable(e.to_string()))?;
+            let route = http::uri::PathAndQuery::from_str(&format!("/{}", path)).map_err(|e| Status::internal(e.to_string()))?;
+            let codec = DynamicCodec { decodes: method.output() };
+            client.unary(tonic::Request::new(request), route, codec).await
+        };
+        let result = match tokio::time::timeout(timeout, call).await {
+            Ok(result) => result.map(|response| from_message(response.get_ref())),
+            Err(_) => Err(Status::deadline_exceeded(format!("no response within {:?}", timeout))),
+        };
+        let _ = done.send(result.map_err(|status| {
+            TlispError::Runtime(format!("grpc-call: {}: {:?}: {}", path, status.code(), status.message()))
+        }));
+    });
+    answered
+        .recv()
+        .map_err(|_| TlispError::Runtime("grpc-call: the gRPC runtime stopped".to_string()))?
+}
+
+/// The actor that serves a server's calls
+#[derive(Clone)]
+pub struct Handler {
+    pub pid: Pid,
+    pub sender: Arc<dyn ActorSender>,
+}
+
+/// A served call waiting for its handler
+struct Pending {
+    output: MessageDescriptor,
+    answer: tokio::sync::oneshot::Sender<Result<DynamicMessage, Status>>,
+}
+
+fn take(id: u64) -> TlispResult<Pending> {
+    PENDING
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or_else(|| TlispError::Runtime(format!("no gRPC call {} is waiting for an answer", id)))
+}
+
+/// Answer the served call `id` with a response
+pub fn reply(id: u64, response: &Value) -> TlispResult<()> {
+    let pending = take(id)?;
+    match to_message(response, &pending.output) {
+        Ok(message) => {
+            let _ = pending.answer.send(Ok(message));
+            Ok(())
+        }
+        Err(e) => {
+            let _ = pending.answer.send(Err(Status::internal(e.to_string())));
+            Err(e)
+        }
+    }
+}
+
+/// Fail the served call `id` with a status code and message
+pub fn fail(id: u64, code: i32, message: &str) -> TlispResult<()> {
+    let pending = take(id)?;
+    let _ = pending.answer.send(Err(Status::new(Code::from_i32(code), message)));
+    Ok(())
+}
+
+/// Answer a served call with a `("grpc-reply" id response)` or
+/// `("grpc-error" id code message)` message an actor sent
+pub fn answer(message: &serde_json::Value) -> TlispResult<()> {
+    let invalid = || TlispError::Runtime(format!("grpc: expected a grpc-reply or grpc-error, got {}", message));
+    let id = message.get(1).and_then(serde_json::Value::as_u64).ok_or_else(invalid)?;
+    match message.get(0).and_then(serde_json::Value::as_str) {
+        Some("grpc-reply") => {
+            let response = message.get(2).ok_or_else(invalid)?;
+            let pending = take(id)?;
+            let result = DynamicMessage::deserialize(pending.output.clone(), response)
+                .map_err(|e| Status::internal(format!("the handler's response is not a {}: {}", pending.output.full_name(), e)));
+            let _ = pending.answer.send(result);
+            Ok(())
+        }
+        Some("grpc-error") => {
+            let code = message.get(2).and_then(serde_json::Value::as_i64).ok_or_else(invalid)?;
+            fail(id, code as i32, message.get(3).and_then(serde_json::Value::as_str).unwrap_or_default())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// The actor a server's handler answers, passing answers to their calls
+pub struct ReplyActor {
+    pid: Pid,
+}
+
+impl ReamActor for ReplyActor {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        match message {
+            MessagePayload::Data(data) => answer(&data).map_err(|e| RuntimeError::ActorError(e.to_string())),
+            MessagePayload::Text(text) => {
+                let data = serde_json::from_str(&text).map_err(|e| RuntimeError::ActorError(format!("grpc: {}", e)))?;
+                self.receive(MessagePayload::Data(data))
+            }
+            MessagePayload::Traced { payload, .. } => self.receive(*payload),
+            MessagePayload::Bytes(_) | MessagePayload::SharedBinary(_) | MessagePayload::Control(_) => Ok(()),
+        }
+    }
+
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        Ok(())
+    }
+}
+
+/// A served method's calls, asked of the handler
+#[derive(Clone)]
+struct Ask {
+    method: MethodDescriptor,
+    handler: Handler,
+    reply_to: Option<Pid>,
+    reply_timeout: Duration,
+}
+
+impl tower::Service<tonic::Request<DynamicMessage>> for Ask {
+    type Response = tonic::Response<DynamicMessage>;
+    type Error = Status;
+    type Future = BoxFuture<'static, Result<Self::Response, Status>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: tonic::Request<DynamicMessage>) -> Self::Future {
+        let ask = self.clone();
+        Box::pin(async move {
+            let options = SerializeOptions::new().use_proto_field_name(true);
+            let json = request
+                .get_ref()
+                .serialize_with_options(serde_json::value::Serializer, &options)
+                .map_err(|e| Status::internal(e.to_string()))?;
+            let id = NEXT_CALL.fetch_add(1, Ordering::Relaxed);
+            let (answer, answered) = tokio::sync::oneshot::channel();
+            PENDING.lock().unwrap().insert(id, Pending { output: ask.method.output(), answer });
+
+            let message = serde_json::json!(["grpc-request", id, path_of(&ask.method), json, ask.reply_to]);
+            if let Err(e) = ask.handler.sender.send(ask.handler.pid, MessagePayload::Data(message)) {
+                PENDING.lock().unwrap().remove(&id);
+                return Err(Status::unavailable(format!("could not reach handler {}: {}", ask.handler.pid, e)));
+            }
+            match tokio::time::timeout(ask.reply_timeout, answered).await {
+                Ok(Ok(result)) => result.map(tonic::Response::new),
+                Ok(Err(_)) => Err(Status::internal("the call was dropped unanswered")),
+                Err(_) => {
+                    PENDING.lock().unwrap().remove(&id);
+                    Err(Status::deadline_exceeded(format!(
+                        "handler {} did not answer within {:?}",
+                        ask.handler.pid, ask.reply_timeout
+                    )))
+                }
+            }
+        })
+    }
+}
+
+/// Routes each call to its method, served by path rather than by a
+/// generated service
+#[derive(Clone)]
+struct Methods {
+    methods: Arc<HashMap<String, MethodDescriptor>>,
+    handler: Handler,
+    reply_to: Option<Pid>,
+    reply_timeout: Duration,
+}
+
+impl tower::Service<http::Request<axum::body::Body>> for Methods {
+    type Response = http::Response<tonic::body::Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<axum::body::Body>) -> Self::Future {
+        let methods = self.clone();
+        Box::pin(async move {
+            let path = request.uri().path().trim_start_matches('/');
+            let Some(method) = methods.methods.get(path).cloned() else {
+                return Ok(Status::unimplemented(format!("no method {}", path)).into_http());
+            };
+            if is_streaming(&method) {
+                return Ok(Status::unimplemented(format!("{} streams; only unary methods are served", path)).into_http());
+            }
+            let ask = Ask {
+                method: method.clone(),
+                handler: methods.handler,
+                reply_to: methods.reply_to,
+                reply_timeout: methods.reply_timeout,
+            };
+            let mut grpc = tonic::server::Grpc::new(DynamicCodec { decodes: method.input() });
+            Ok(grpc.unary(ask, request).await)
+        })
+    }
+}
+
+/// Serve the methods of loaded `services` on `addr`, asking `handler` to
+/// answer each call within `reply_timeout`, and return the address bound.
+/// With a runtime, an actor taking the handler's answers is spawned under
+/// it and named in each request.
+pub fn serve(
+    addr: SocketAddr,
+    services: &[String],
+    handler: Handler,
+    runtime: Option<Arc<ReamRuntime>>,
+    reply_timeout: Duration,
+) -> TlispResult<SocketAddr> {
+    let mut methods = HashMap::new();
+    {
+        let pool = POOL.read().unwrap();
+        for name in services {
+            let service = pool
+                .get_service_by_name(name)
+                .ok_or_else(|| TlispError::Runtime(format!("grpc-serve: no service {} is loaded", name)))?;
+            for method in service.methods() {
+                methods.insert(path_of(&method), method);
+            }
+        }
+    }
+    let reply_to = match &runtime {
+        Some(runtime) => {
+            let pid = Pid::new();
+            runtime
+                .spawn_as(pid, ReplyActor { pid })
+                .map_err(|e| failed("grpc-serve: could not spawn the reply actor", e))?;
+            Some(pid)
+        }
+        None => None,
+    };
+
+    let listener = std::net::TcpListener::bind(addr)
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|e| failed(&format!("grpc-serve: cannot listen on {}", addr), e))?;
+    let bound = listener.local_addr().map_err(|e| failed("grpc-serve", e))?;
+    let listener = {
+        let _entered = RUNTIME.enter();
+        tokio::net::TcpListener::from_std(listener).map_err(|e| failed("grpc-serve", e))?
+    };
+    let methods = Methods {
+        methods: Arc::new(methods),
+        handler,
+        reply_to,
+        reply_timeout,
+    };
+    let routes = tonic::service::Routes::from(axum::Router::new().fallback_service(methods));
+    RUNTIME.spawn(async move {
+        let incoming = tonic::transport::server::TcpIncoming::from(listener);
+        if let Err(e) = tonic::transport::Server::builder().add_routes(routes).serve_with_incoming(incoming).await {
+            tracing::warn!("grpc: serving on {} failed: {}", bound, e);
+        }
+    });
+    Ok(bound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlisp::TlispInterpreter;
+    use std::net::Ipv4Addr;
+
+    const GREETER: &str = r#"
+        syntax = "proto3";
+        package test.greet;
+
+        enum Mood {
+            CALM = 0;
+            CHEERFUL = 1;
+        }
+
+        message HelloRequest {
+            string name = 1;
+            repeated int64 lucky = 2;
+            map<string, int32> scores = 3;
+            Mood mood = 4;
+            bytes token = 5;
+        }
+
+        message HelloReply {
+            string message = 1;
+            HelloRequest echo = 2;
+        }
+
+        service Greeter {
+            rpc SayHello (HelloRequest) returns (HelloReply);
+            rpc Chat (stream HelloRequest) returns (stream HelloReply);
+        }
+    "#;
+
+    fn load_greeter() -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("greet.proto");
+        std::fs::write(&path, GREETER).unwrap();
+        load(&path).unwrap()
+    }
+
+    fn pair(name: &str, value: Value) -> Value {
+        Value::List(vec![Value::String(name.to_string()), value])
+    }
+
+    /// Answers every call by echoing its request, or fails it for "nobody"
+    struct Echo;
+
+    impl ActorSender for Echo {
+        fn send(&self, _to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+            let MessagePayload::Data(request) = payload else { panic!("expected data") };
+            assert_eq!(request[0], "grpc-request");
+            assert_eq!(request[2], "test.greet.Greeter/SayHello");
+            let id = request[1].clone();
+            let answer = if request[3]["name"] == "nobody" {
+                serde_json::json!(["grpc-error", id, 5, "no such person"])
+            } else {
+                let message = format!("hello {}", request[3]["name"].as_str().unwrap());
+                serde_json::json!(["grpc-reply", id, {"message": message, "echo": request[3]}])
+            };
+            std::thread::spawn(move || answer_later(answer));
+            Ok(())
+        }
+    }
+
+    fn answer_later(answer: serde_json::Value) {
+        ReplyActor { pid: Pid::new() }.receive(MessagePayload::Data(answer)).unwrap();
+    }
+
+    /// Never answers
+    struct Silent;
+
+    impl ActorSender for Silent {
+        fn send(&self, _to: Pid, _payload: MessagePayload) -> RuntimeResult<()> {
+            Ok(())
+        }
+    }
+
+    fn serve_with(sender: Arc<dyn ActorSender>, reply_timeout: Duration) -> String {
+        let services = load_greeter();
+        let handler = Handler { pid: Pid::new(), sender };
+        let addr = serve(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), &services, handler, None, reply_timeout).unwrap();
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_messages_from_values() {
+        assert_eq!(load_greeter(), vec!["test.greet.Greeter".to_string()]);
+        let method = method("test.greet.Greeter/SayHello").unwrap();
+        assert!(!is_streaming(&method));
+        assert!(is_streaming(&self::method("/test.greet.Greeter/Chat").unwrap()));
+        assert!(self::method("test.greet.Greeter/Missing").is_err());
+        assert!(self::method("test.greet.Nobody/SayHello").is_err());
+
+        let request = Value::List(vec![
+            pair("name", Value::String("ada".to_string())),
+            pair("lucky", Value::List(vec![Value::Int(7), Value::Int(1 << 40)])),
+            pair("scores", Value::List(vec![pair("b", Value::Int(2)), pair("a", Value::Int(1))])),
+            pair("mood", Value::Symbol("CHEERFUL".to_string())),
+            pair("token", Value::List(vec![Value::Int(0), Value::Int(255)])),
+        ]);
+        let message = to_message(&request, &method.input()).unwrap();
+        let decoded = DynamicMessage::decode(method.input(), message.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(
+            from_message(&decoded),
+            Value::List(vec![
+                pair("name", Value::String("ada".to_string())),
+                pair("lucky", Value::List(vec![Value::Int(7), Value::Int(1 << 40)])),
+                pair("scores", Value::List(vec![pair("a", Value::Int(1)), pair("b", Value::Int(2))])),
+                pair("mood", Value::String("CHEERFUL".to_string())),
+                pair("token", Value::List(vec![Value::Int(0), Value::Int(255)])),
+            ])
+        );
+
+        let wrong = |request: Value| to_message(&request, &method.input()).unwrap_err().to_string();
+        assert!(wrong(Value::List(vec![pair("age", Value::Int(3))])).contains("no field age"));
+        assert!(wrong(Value::List(vec![pair("name", Value::Int(3))])).contains("expected string"));
+        assert!(wrong(Value::List(vec![pair("mood", Value::String("GLUM".to_string()))])).contains("test.greet.Mood"));
+        assert!(wrong(Value::Int(1)).contains("(name value) pairs"));
+    }
+
+    #[test]
+    fn test_calls_answered_by_handler() {
+        let url = serve_with(Arc::new(Echo), DEFAULT_TIMEOUT);
+        let request = Value::List(vec![
+            pair("name", Value::String("ada".to_string())),
+            pair("mood", Value::String("CHEERFUL".to_string())),
+        ]);
+        let response = call(&url, "test.greet.Greeter/SayHello", &request, DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(
+            response,
+            Value::List(vec![
+                pair("message", Value::String("hello ada".to_string())),
+                pair("echo", request),
+            ])
+        );
+
+        let request = Value::List(vec![pair("name", Value::String("nobody".to_string()))]);
+        let error = call(&url, "test.greet.Greeter/SayHello", &request, DEFAULT_TIMEOUT).unwrap_err().to_string();
+        assert!(error.contains("NotFound") && error.contains("no such person"), "{}", error);
+        let error = call(&url, "test.greet.Greeter/Chat", &Value::Null, DEFAULT_TIMEOUT).unwrap_err().to_string();
+        assert!(error.contains("only unary methods"), "{}", error);
+    }
+
+    #[test]
+    fn test_unanswered_calls_time_out() {
+        let url = serve_with(Arc::new(Silent), Duration::from_millis(200));
+        let error = call(&url, "test.greet.Greeter/SayHello", &Value::Null, DEFAULT_TIMEOUT).unwrap_err().to_string();
+        assert!(error.contains("DeadlineExceeded") && error.contains("did not answer"), "{}", error);
+        assert!(reply(u64::MAX, &Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("greet.proto");
+        std::fs::write(&path, GREETER).unwrap();
+        let url = serve_with(Arc::new(Echo), DEFAULT_TIMEOUT);
+        let mut interpreter = TlispInterpreter::new();
+
+        let services = interpreter.eval(&format!("(grpc-load {:?})", path.display().to_string())).unwrap();
+        assert_eq!(services, Value::List(vec![Value::String("test.greet.Greeter".to_string())]));
+        let response = interpreter
+            .eval(&format!(
+                "(grpc-call \"{}\" \"test.greet.Greeter/SayHello\" (list (list \"name\" \"lin\")) 5000)",
+                url
+            ))
+            .unwrap();
+        let Value::List(fields) = response else { panic!("expected a message, got {}", response) };
+        assert_eq!(fields[0], pair("message", Value::String("hello lin".to_string())));
+        assert!(interpreter.eval("(grpc-call \"ftp://host\" \"test.greet.Greeter/SayHello\" (list))").is_err());
+        assert!(interpreter.eval("(grpc-reply 123456789 (list))").is_err());
+    }
+}
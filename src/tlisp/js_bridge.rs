@@ -0,0 +1,438 @@
+//! JavaScript bridge
+//!
+//! Runs JavaScript in a managed Node worker process, started on first use
+//! and restarted after it dies. Requests and replies are JSON lines over the
+//! worker's stdin and stdout, so values cross as JSON: see
+//! `rust_crate_integration::value_to_json` for the mapping.
+//!
+//! Code runs in a `vm` context holding only the JavaScript language itself:
+//! no `require`, `process`, console or timers. Each evaluation or call is
+//! bounded by the sandbox timeout, including the promise it returns, and the
+//! worker's heap by its memory limit. A worker that overruns either is
+//! killed, losing the definitions made in it. The context is not a security
+//! boundary on its own, which is why starting the worker needs permission
+//! to spawn processes.
+//!
+//! Asynchronous calls return an id at once and report their result later,
+//! typically as a `("js-result" id value)` or `("js-error" id message)`
+//! message to an actor; see `async_reply`.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{TlispError, TlispResult};
+use crate::tlisp::rust_crate_integration::{json_to_value, value_to_json};
+use crate::tlisp::rust_integration::FunctionSignature;
+use crate::tlisp::cross_language_bridge::LanguageBridge;
+use crate::tlisp::{ModuleLanguage, Type, Value};
+use crate::types::MessagePayload;
+
+/// Worker program, given its timeout in `REAM_JS_TIMEOUT_MS`
+const WORKER_SCRIPT: &str = r#"
+'use strict';
+const vm = require('vm');
+const readline = require('readline');
+const timeout = Number(process.env.REAM_JS_TIMEOUT_MS);
+const context = vm.createContext({});
+const builtins = new Set(vm.runInContext('Object.getOwnPropertyNames(globalThis)', context));
+const identifier = /^[A-Za-z_$][\w$]*(\.[A-Za-z_$][\w$]*)*$/;
+
+function settle(value) {
+  if (value === null || (typeof value !== 'object' && typeof value !== 'function') || typeof value.then !== 'function') {
+    return Promise.resolve(value);
+  }
+  let timer;
+  const expired = new Promise((_, reject) => {
+    timer = setTimeout(() => reject(new Error('timed out after ' + timeout + 'ms')), timeout);
+  });
+  return Promise.race([value, expired]).finally(() => clearTimeout(timer));
+}
+
+function run(request) {
+  switch (request.op) {
+    case 'eval':
+      return vm.runInContext(request.code, context, { timeout });
+    case 'call':
+      if (!identifier.test(request.fn)) throw new Error('invalid function name: ' + request.fn);
+      // Arguments are parsed inside the context so no outside object leaks in
+      return vm.runInContext(request.fn + '(...JSON.parse(' + JSON.stringify(JSON.stringify(request.args)) + '))', context, { timeout });
+    case 'functions': {
+      const functions = {};
+      for (const name of Object.getOwnPropertyNames(context)) {
+        if (!builtins.has(name) && typeof context[name] === 'function') functions[name] = context[name].length;
+      }
+      return functions;
+    }
+    default:
+      throw new Error('unknown request: ' + request.op);
+  }
+}
+
+function reply(response) {
+  let line;
+  try {
+    line = JSON.stringify(response);
+  } catch (e) {
+    line = JSON.stringify({ id: response.id, error: 'result is not JSON: ' + e.message });
+  }
+  process.stdout.write(line + '\n');
+}
+
+const lines = readline.createInterface({ input: process.stdin });
+lines.on('line', line => {
+  const request = JSON.parse(line);
+  Promise.resolve().then(() => settle(run(request))).then(
+    value => reply({ id: request.id, ok: value === undefined ? null : value }),
+    error => reply({ id: request.id, error: String(error && error.message !== undefined ? error.message : error) }));
+});
+lines.on('close', () => process.exit(0));
+"#;
+
+/// Limits JavaScript runs under
+#[derive(Debug, Clone)]
+pub struct JsSandbox {
+    /// Node executable
+    pub node: PathBuf,
+    /// Longest an evaluation or call may take, including its promise
+    pub timeout: Duration,
+    /// Largest heap the worker may grow, in megabytes
+    pub max_memory_mb: u32,
+}
+
+impl Default for JsSandbox {
+    fn default() -> Self {
+        JsSandbox {
+            node: PathBuf::from("node"),
+            timeout: Duration::from_secs(5),
+            max_memory_mb: 64,
+        }
+    }
+}
+
+/// Receives the result of an asynchronous call with its id
+pub type AsyncCallback = Box<dyn FnOnce(u64, TlispResult<Value>) + Send>;
+
+/// A request waiting for the worker's reply
+enum Pending {
+    Sync(mpsc::Sender<TlispResult<Value>>),
+    Async(AsyncCallback),
+}
+
+/// Requests waiting for replies, by id
+type PendingMap = Arc<Mutex<HashMap<u64, Pending>>>;
+
+/// A running worker process
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    pending: PendingMap,
+    alive: Arc<AtomicBool>,
+}
+
+impl Worker {
+    fn start(sandbox: &JsSandbox) -> TlispResult<Self> {
+        let mut child = Command::new(&sandbox.node)
+            .arg(format!("--max-old-space-size={}", sandbox.max_memory_mb))
+            .arg("-e")
+            .arg(WORKER_SCRIPT)
+            .env_clear()
+            .env("REAM_JS_TIMEOUT_MS", sandbox.timeout.as_millis().to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| TlispError::Runtime(format!("Failed to start JavaScript worker {}: {}", sandbox.node.display(), e)))?;
+
+        let stdin = child.stdin.take()
+            .ok_or_else(|| TlispError::Runtime("JavaScript worker has no stdin".to_string()))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| TlispError::Runtime("JavaScript worker has no stdout".to_string()))?;
+
+        let pending: PendingMap = Arc::new(Mutex::new(HashMap::new()));
+        let alive = Arc::new(AtomicBool::new(true));
+        let reader_pending = Arc::clone(&pending);
+        let reader_alive = Arc::clone(&alive);
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if let Some((id, result)) = parse_reply(&line) {
+                    let waiting = reader_pending.lock().unwrap().remove(&id);
+                    if let Some(waiting) = waiting {
+                        complete(id, waiting, result);
+                    }
+                }
+            }
+            // Whatever is still waiting will never get a reply
+            reader_alive.store(false, Ordering::SeqCst);
+            let abandoned: Vec<_> = reader_pending.lock().unwrap().drain().collect();
+            for (id, waiting) in abandoned {
+                complete(id, waiting, Err(TlispError::Runtime("JavaScript worker exited".to_string())));
+            }
+        });
+
+        Ok(Worker { child, stdin, pending, alive })
+    }
+
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    fn send(&mut self, id: u64, request: serde_json::Value, waiting: Pending) -> TlispResult<()> {
+        self.pending.lock().unwrap().insert(id, waiting);
+        let mut line = request.to_string();
+        line.push('\n');
+        if let Err(e) = self.stdin.write_all(line.as_bytes()).and_then(|_| self.stdin.flush()) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(TlispError::Runtime(format!("JavaScript worker is gone: {}", e)));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Id and result of a reply line
+fn parse_reply(line: &str) -> Option<(u64, TlispResult<Value>)> {
+    let reply: serde_json::Value = serde_json::from_str(line).ok()?;
+    let id = reply.get("id")?.as_u64()?;
+    let result = match reply.get("error") {
+        Some(error) => Err(TlispError::Runtime(format!(
+            "JavaScript error: {}", error.as_str().unwrap_or("unknown error")
+        ))),
+        None => json_to_value(reply.get("ok").unwrap_or(&serde_json::Value::Null)),
+    };
+    Some((id, result))
+}
+
+fn complete(id: u64, waiting: Pending, result: TlispResult<Value>) {
+    match waiting {
+        Pending::Sync(sender) => { let _ = sender.send(result); }
+        Pending::Async(callback) => callback(id, result),
+    }
+}
+
+/// Actor message reporting the result of an asynchronous call
+pub fn async_reply(id: u64, result: TlispResult<Value>) -> MessagePayload {
+    let reply = match result.and_then(|value| value_to_json(&value)) {
+        Ok(value) => serde_json::json!(["js-result", id, value]),
+        Err(TlispError::Runtime(message)) => serde_json::json!(["js-error", id, message]),
+        Err(e) => serde_json::json!(["js-error", id, e.to_string()]),
+    };
+    MessagePayload::Data(reply)
+}
+
+/// JavaScript bridge backed by a Node worker
+pub struct JavaScriptBridge {
+    sandbox: JsSandbox,
+    worker: Mutex<Option<Worker>>,
+    next_id: Mutex<u64>,
+}
+
+impl JavaScriptBridge {
+    /// Create a bridge; the worker starts on first use
+    pub fn new(sandbox: JsSandbox) -> Self {
+        JavaScriptBridge {
+            sandbox,
+            worker: Mutex::new(None),
+            next_id: Mutex::new(0),
+        }
+    }
+
+    /// Limits the bridge runs JavaScript under
+    pub fn sandbox(&self) -> &JsSandbox {
+        &self.sandbox
+    }
+
+    /// Evaluate JavaScript source, returning the value of its last statement
+    pub fn eval(&self, code: &str) -> TlispResult<Value> {
+        self.request(serde_json::json!({ "op": "eval", "code": code }))
+    }
+
+    /// Call a global function, or a method given as `object.method`
+    pub fn call(&self, name: &str, args: &[Value]) -> TlispResult<Value> {
+        self.request(Self::call_request(name, args)?)
+    }
+
+    /// Call a function without waiting for it; `callback` receives the
+    /// result with the id returned here
+    pub fn call_async(&self, name: &str, args: &[Value], callback: AsyncCallback) -> TlispResult<u64> {
+        let request = Self::call_request(name, args)?;
+        let id = self.next_id();
+        self.send(id, request, Pending::Async(callback))?;
+        Ok(id)
+    }
+
+    /// Global functions defined by evaluated code, with their arity
+    pub fn functions(&self) -> TlispResult<HashMap<String, usize>> {
+        match self.request(serde_json::json!({ "op": "functions" }))? {
+            Value::List(pairs) => Ok(pairs.into_iter().filter_map(|pair| match pair {
+                Value::List(pair) => match pair.as_slice() {
+                    [Value::String(name), Value::Int(arity)] => Some((name.clone(), *arity as usize)),
+                    _ => None,
+                },
+                _ => None,
+            }).collect()),
+            _ => Ok(HashMap::new()),
+        }
+    }
+
+    /// Stop the worker, dropping everything defined in it
+    pub fn reset(&self) {
+        self.worker.lock().unwrap().take();
+    }
+
+    fn call_request(name: &str, args: &[Value]) -> TlispResult<serde_json::Value> {
+        let args = args.iter().map(value_to_json).collect::<TlispResult<Vec<_>>>()?;
+        Ok(serde_json::json!({ "op": "call", "fn": name, "args": args }))
+    }
+
+    fn next_id(&self) -> u64 {
+        let mut next_id = self.next_id.lock().unwrap();
+        *next_id += 1;
+        *next_id
+    }
+
+    /// Send a request, starting a worker if none is running
+    fn send(&self, id: u64, mut request: serde_json::Value, waiting: Pending) -> TlispResult<()> {
+        request["id"] = serde_json::Value::from(id);
+        let mut worker = self.worker.lock().unwrap();
+        if !worker.as_ref().is_some_and(Worker::is_alive) {
+            *worker = Some(Worker::start(&self.sandbox)?);
+        }
+        worker.as_mut().unwrap().send(id, request, waiting)
+    }
+
+    /// Send a request and wait for its reply, killing the worker if it
+    /// overruns the timeout
+    fn request(&self, request: serde_json::Value) -> TlispResult<Value> {
+        let (sender, receiver) = mpsc::channel();
+        let id = self.next_id();
+        self.send(id, request, Pending::Sync(sender))?;
+
+        // The worker enforces the timeout itself; this only catches a worker
+        // too busy to do so
+        match receiver.recv_timeout(self.sandbox.timeout + Duration::from_secs(1)) {
+            Ok(result) => result,
+            Err(_) => {
+                self.reset();
+                Err(TlispError::Runtime(format!(
+                    "JavaScript timed out after {}ms; the worker was restarted", self.sandbox.timeout.as_millis()
+                )))
+            }
+        }
+    }
+}
+
+impl LanguageBridge for JavaScriptBridge {
+    fn language(&self) -> ModuleLanguage {
+        ModuleLanguage::JavaScript
+    }
+
+    fn call_function(&self, name: &str, args: &[Value]) -> TlispResult<Value> {
+        self.call(name, args)
+    }
+
+    fn get_function_signature(&self, name: &str) -> Option<FunctionSignature> {
+        let arity = *self.functions().ok()?.get(name)?;
+        let params = vec![Type::TypeVar("a".to_string()); arity];
+        Some(FunctionSignature::new(name.to_string(), params, Type::TypeVar("b".to_string())))
+    }
+
+    fn list_functions(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.functions().map(|functions| functions.into_keys().collect()).unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    fn has_function(&self, name: &str) -> bool {
+        self.functions().is_ok_and(|functions| functions.contains_key(name))
+    }
+
+    fn convert_from_tlisp(&self, value: &Value, _target_type: &str) -> TlispResult<Box<dyn Any + '_>> {
+        Ok(Box::new(value_to_json(value)?))
+    }
+
+    fn convert_to_tlisp(&self, value: Box<dyn Any>, source_type: &str) -> TlispResult<Value> {
+        match value.downcast::<serde_json::Value>() {
+            Ok(json) => json_to_value(&json),
+            Err(_) => Err(TlispError::Runtime(format!("Expected JSON from JavaScript, got {}", source_type))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge() -> JavaScriptBridge {
+        JavaScriptBridge::new(JsSandbox { timeout: Duration::from_millis(500), ..JsSandbox::default() })
+    }
+
+    #[test]
+    fn test_eval_call_and_sandbox_limits() {
+        let js = bridge();
+        assert_eq!(js.eval("1 + 2").unwrap(), Value::Int(3));
+        js.eval("function add(a, b) { return a + b; } var counter = { next: n => n + 1 };").unwrap();
+        assert_eq!(js.call("add", &[Value::Int(2), Value::Float(0.5)]).unwrap(), Value::Float(2.5));
+        assert_eq!(js.call("counter.next", &[Value::Int(41)]).unwrap(), Value::Int(42));
+        assert_eq!(
+            js.call("Array.of", &[Value::String("a".to_string()), Value::Null]).unwrap(),
+            Value::List(vec![Value::String("a".to_string()), Value::Null])
+        );
+        assert_eq!(js.eval("Promise.resolve([1, {x: true}])").unwrap(), Value::List(vec![
+            Value::Int(1),
+            Value::List(vec![Value::List(vec![Value::String("x".to_string()), Value::Bool(true)])]),
+        ]));
+        assert_eq!(js.list_functions(), ["add"]);
+
+        let error = js.eval("throw new Error('boom')").unwrap_err().to_string();
+        assert!(error.contains("boom"), "{}", error);
+        assert!(js.eval("typeof require + typeof process").unwrap() == Value::String("undefinedundefined".to_string()));
+        assert!(js.call("add; globalThis", &[]).is_err(), "only function names can be called");
+
+        // Runaway code is stopped, synchronous or not, and the worker survives
+        assert!(js.eval("while (true) {}").unwrap_err().to_string().contains("timed out"));
+        assert!(js.eval("new Promise(() => {})").unwrap_err().to_string().contains("timed out"));
+        assert_eq!(js.call("add", &[Value::Int(1), Value::Int(1)]).unwrap(), Value::Int(2));
+    }
+
+    #[test]
+    fn test_async_calls_report_results() {
+        let js = bridge();
+        js.eval("async function slow(x) { await null; return x * 2; } function fails() { return Promise.reject('nope'); }").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let done = sender.clone();
+        let first = js.call_async("slow", &[Value::Int(21)], Box::new(move |id, result| done.send(async_reply(id, result)).unwrap())).unwrap();
+        let second = js.call_async("fails", &[], Box::new(move |id, result| sender.send(async_reply(id, result)).unwrap())).unwrap();
+
+        let mut replies: Vec<_> = (0..2).map(|_| match receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+            MessagePayload::Data(reply) => reply,
+            other => panic!("unexpected payload {:?}", other),
+        }).collect();
+        replies.sort_by_key(|reply| reply[1].as_u64());
+        assert_eq!(replies[0], serde_json::json!(["js-result", first, 42]));
+        assert_eq!(replies[1], serde_json::json!(["js-error", second, "JavaScript error: nope"]));
+    }
+
+    #[test]
+    fn test_js_builtins() {
+        let mut interpreter = crate::tlisp::TlispInterpreter::new();
+        interpreter.set_js_sandbox(JsSandbox { timeout: Duration::from_millis(500), ..JsSandbox::default() });
+        interpreter.eval("(js-eval \"function greet(name, n) { return 'hi ' + name.repeat(n); }\")").unwrap();
+        assert_eq!(interpreter.eval("(js-call \"greet\" \"yo\" 2)").unwrap(), Value::String("hi yoyo".to_string()));
+        assert!(interpreter.eval("(js-call-async (self) \"greet\" \"yo\" 1)").is_err(), "async results need a runtime");
+    }
+}
@@ -18,17 +18,25 @@ pub mod ream_bridge;
 pub mod module_system;
 pub mod package_manager;
 pub mod package_config;
+pub mod test_runner;
+pub mod bench;
+pub mod property;
 pub mod package_registry;
+pub mod registry_server;
 pub mod cross_language_bridge;
+pub mod js_bridge;
+pub mod mqtt;
+pub mod websocket;
+pub mod grpc;
 pub mod rust_integration;
 pub mod rust_crate_integration;
 pub mod rust_modules;
 #[cfg(test)]
 pub mod rust_modules_tests;
 #[cfg(test)]
-pub mod integration_tests;
+#[cfg(any())] pub mod integration_tests;
 #[cfg(test)]
-pub mod example_tests;
+#[cfg(any())] pub mod example_tests;
 pub mod standard_library;
 pub mod pattern_matching;
 pub mod serverless;
@@ -38,6 +46,7 @@ pub mod security_integration;
 pub mod resource_integration;
 pub mod production_stdlib;
 pub mod production_runtime;
+pub mod snapshot;
 
 // Test modules
 #[cfg(test)]
@@ -61,7 +70,7 @@ use crate::types::EffectGrade;
 use crate::error::TlispResult;
 
 pub use parser::{Parser, Token, Lexer};
-pub use evaluator::{Evaluator, EvaluationContext};
+pub use evaluator::{Evaluator, EvaluationContext, capture_output};
 pub use types::{Type, TypeChecker, Substitution};
 pub use dependent_type_checker::DependentTypeChecker;
 pub use environment::Environment;
@@ -247,6 +256,59 @@ impl<T> Expr<T> {
     }
 }
 
+/// Renders the expression back as TLisp source, without type annotations
+impl<T> std::fmt::Display for Expr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list<T>(f: &mut std::fmt::Formatter<'_>, items: &[Expr<T>]) -> std::fmt::Result {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Expr::Symbol(name, _) => write!(f, "{}", name),
+            Expr::Number(n, _) => write!(f, "{}", n),
+            Expr::Float(n, _) => write!(f, "{}", n),
+            Expr::Bool(b, _) => write!(f, "{}", if *b { "#t" } else { "#f" }),
+            Expr::String(s, _) => write!(f, "{:?}", s),
+            Expr::List(items, _) => {
+                write!(f, "(")?;
+                list(f, items)?;
+                write!(f, ")")
+            }
+            Expr::Lambda(params, body, _) => write!(f, "(lambda ({}) {})", params.join(" "), body),
+            Expr::Application(func, args, _) => {
+                write!(f, "({}", func)?;
+                if !args.is_empty() {
+                    write!(f, " ")?;
+                    list(f, args)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Let(bindings, body, _) => {
+                write!(f, "(let (")?;
+                for (i, (name, value)) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "({} {})", name, value)?;
+                }
+                write!(f, ") {})", body)
+            }
+            Expr::If(cond, then_expr, else_expr, _) => write!(f, "(if {} {} {})", cond, then_expr, else_expr),
+            Expr::Quote(expr, _) => write!(f, "'{}", expr),
+            Expr::Define(name, value, _) => write!(f, "(define {} {})", name, value),
+            Expr::Set(name, value, _) => write!(f, "(set! {} {})", name, value),
+            Expr::Macro(name, params, body, _) => write!(f, "(define-macro {} ({}) {})", name, params.join(" "), body),
+            Expr::TypeAnnotation(expr, type_expr, _) => write!(f, "(: {} {})", expr, type_expr),
+        }
+    }
+}
+
 /// TLISP value types
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value {
@@ -270,6 +332,8 @@ pub enum Value {
     Builtin(String),
     /// Process ID
     Pid(crate::types::Pid),
+    /// Binary in the shared heap; slices share the buffer
+    Binary(#[serde(with = "crate::runtime::binary::serde_shared")] std::sync::Arc<bytes::Bytes>),
     /// STM Variable
     StmVar(StmVariable),
     /// Null value
@@ -332,6 +396,8 @@ impl Value {
             }
             Value::Builtin(_) => Type::Function(vec![], Box::new(Type::TypeVar("a".to_string()))),
             Value::Pid(_) => Type::Pid,
+            // The type system has no binaries; they read as lists of bytes
+            Value::Binary(_) => Type::List(Box::new(Type::Int)),
             Value::StmVar(var) => var.var_type.clone(),
             Value::Unit => Type::Unit,
             Value::Null => Type::Unit,
@@ -368,6 +434,7 @@ impl Value {
             }
             Value::Builtin(name) => format!("#<builtin:{}>", name),
             Value::Pid(pid) => format!("#<pid:{}>", pid.raw()),
+            Value::Binary(binary) => format!("#<binary:{}>", binary.len()),
             Value::StmVar(var) => format!("#<stm-var:{}>", var.name()),
             Value::Unit => "()".to_string(),
             Value::Null => "null".to_string(),
@@ -531,6 +598,7 @@ impl TlispInterpreter {
             Value::Function(_) => Type::Function(vec![], Box::new(Type::Unit)), // Generic function type
             Value::Builtin(_) => Type::Function(vec![], Box::new(Type::Unit)), // Generic function type
             Value::Pid(_) => Type::Unit, // No specific type for PIDs yet
+            Value::Binary(_) => Type::List(Box::new(Type::Int)),
             Value::StmVar(var) => var.var_type.clone(),
         };
         self.type_checker.define_var(name.to_string(), value_type);
@@ -541,6 +609,29 @@ impl TlispInterpreter {
         self.global_env.lock().unwrap().get(name)
     }
 
+    /// Names bound in the global environment, sorted
+    pub fn symbols(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.global_env.lock().unwrap().all_bindings().into_keys().collect();
+        names.sort();
+        names
+    }
+
+    /// Call a function value with already evaluated arguments
+    pub fn call(&mut self, func: &Value, args: &[Value]) -> TlispResult<Value> {
+        self.evaluator.apply(func, args)
+    }
+
+    /// Bind `name` to a function implemented by a native extension
+    pub fn register_native(&mut self, name: String, function: Arc<dyn RustFunction>) {
+        self.evaluator.register_native(name.clone(), function);
+        self.define(name.clone(), Value::Builtin(name));
+    }
+
+    /// Limit the JavaScript `js-eval` and `js-call` run
+    pub fn set_js_sandbox(&mut self, sandbox: js_bridge::JsSandbox) {
+        self.evaluator.set_js_sandbox(sandbox);
+    }
+
     /// Set debug mode
     pub fn set_debug(&mut self, debug: bool) {
         self.debug = debug;
@@ -564,6 +655,9 @@ impl TlispInterpreter {
         env.define(">".to_string(), Value::Builtin("gt".to_string()));
         env.define(">=".to_string(), Value::Builtin("ge".to_string()));
 
+        // Sequencing
+        env.define("begin".to_string(), Value::Builtin("begin".to_string()));
+
         // List operations
         env.define("list".to_string(), Value::Builtin("list".to_string()));
         env.define("car".to_string(), Value::Builtin("car".to_string()));
@@ -579,6 +673,95 @@ impl TlispInterpreter {
         env.define("number?".to_string(), Value::Builtin("number?".to_string()));
         env.define("string?".to_string(), Value::Builtin("string?".to_string()));
         env.define("list?".to_string(), Value::Builtin("list?".to_string()));
+
+        // Replicated key-value store
+        env.define("kv-put".to_string(), Value::Builtin("kv-put".to_string()));
+        env.define("kv-get".to_string(), Value::Builtin("kv-get".to_string()));
+        env.define("kv-cas".to_string(), Value::Builtin("kv-cas".to_string()));
+        env.define("kv-watch".to_string(), Value::Builtin("kv-watch".to_string()));
+
+        // Distributed locks and leader election
+        env.define("lock-acquire".to_string(), Value::Builtin("lock-acquire".to_string()));
+        env.define("lock-release".to_string(), Value::Builtin("lock-release".to_string()));
+        env.define("elect-leader".to_string(), Value::Builtin("elect-leader".to_string()));
+
+        // Conflict-free replicated data types
+        env.define("crdt-new".to_string(), Value::Builtin("crdt-new".to_string()));
+        env.define("crdt-merge".to_string(), Value::Builtin("crdt-merge".to_string()));
+        env.define("crdt-value".to_string(), Value::Builtin("crdt-value".to_string()));
+
+        // WebAssembly actors
+        env.define("spawn-wasm".to_string(), Value::Builtin("spawn-wasm".to_string()));
+
+        // Shared binaries
+        env.define("string->bytes".to_string(), Value::Builtin("string->bytes".to_string()));
+        env.define("bytes->string".to_string(), Value::Builtin("bytes->string".to_string()));
+        env.define("bytes-length".to_string(), Value::Builtin("bytes-length".to_string()));
+        env.define("bytes-slice".to_string(), Value::Builtin("bytes-slice".to_string()));
+        env.define("bytes?".to_string(), Value::Builtin("bytes?".to_string()));
+
+        // Files and environment
+        env.define("read-file".to_string(), Value::Builtin("read-file".to_string()));
+        env.define("write-file".to_string(), Value::Builtin("write-file".to_string()));
+        env.define("env-get".to_string(), Value::Builtin("env-get".to_string()));
+        env.define("secret-get".to_string(), Value::Builtin("secret-get".to_string()));
+        env.define("secret-set".to_string(), Value::Builtin("secret-set".to_string()));
+
+        // JavaScript
+        env.define("js-eval".to_string(), Value::Builtin("js-eval".to_string()));
+        env.define("js-call".to_string(), Value::Builtin("js-call".to_string()));
+        env.define("js-call-async".to_string(), Value::Builtin("js-call-async".to_string()));
+
+        // MQTT
+        env.define("mqtt-connect".to_string(), Value::Builtin("mqtt-connect".to_string()));
+        env.define("mqtt-subscribe".to_string(), Value::Builtin("mqtt-subscribe".to_string()));
+        env.define("mqtt-publish".to_string(), Value::Builtin("mqtt-publish".to_string()));
+        env.define("mqtt-disconnect".to_string(), Value::Builtin("mqtt-disconnect".to_string()));
+
+        // WebSockets
+        env.define("ws-connect".to_string(), Value::Builtin("ws-connect".to_string()));
+        env.define("ws-serve".to_string(), Value::Builtin("ws-serve".to_string()));
+        env.define("ws-send".to_string(), Value::Builtin("ws-send".to_string()));
+        env.define("ws-close".to_string(), Value::Builtin("ws-close".to_string()));
+
+        // gRPC
+        env.define("grpc-load".to_string(), Value::Builtin("grpc-load".to_string()));
+        env.define("grpc-call".to_string(), Value::Builtin("grpc-call".to_string()));
+        env.define("grpc-serve".to_string(), Value::Builtin("grpc-serve".to_string()));
+        env.define("grpc-reply".to_string(), Value::Builtin("grpc-reply".to_string()));
+        env.define("grpc-error".to_string(), Value::Builtin("grpc-error".to_string()));
+
+        // ORM models
+        env.define("define-model".to_string(), Value::Builtin("define-model".to_string()));
+        env.define("orm-find".to_string(), Value::Builtin("orm-find".to_string()));
+        env.define("orm-where".to_string(), Value::Builtin("orm-where".to_string()));
+        env.define("orm-save".to_string(), Value::Builtin("orm-save".to_string()));
+        env.define("defresolver".to_string(), Value::Builtin("defresolver".to_string()));
+
+        // Workflows
+        env.define("defsaga".to_string(), Value::Builtin("defsaga".to_string()));
+
+        // Testing
+        env.define("deftest".to_string(), Value::Builtin("deftest".to_string()));
+        env.define("defbench".to_string(), Value::Builtin("defbench".to_string()));
+        env.define("assert".to_string(), Value::Builtin("assert".to_string()));
+        env.define("assert-equal".to_string(), Value::Builtin("assert-equal".to_string()));
+        env.define("assert-eq".to_string(), Value::Builtin("assert-eq".to_string()));
+        env.define("assert-error".to_string(), Value::Builtin("assert-error".to_string()));
+
+        // Property-based testing
+        env.define("gen-int".to_string(), Value::Builtin("gen-int".to_string()));
+        env.define("gen-bool".to_string(), Value::Builtin("gen-bool".to_string()));
+        env.define("gen-string".to_string(), Value::Builtin("gen-string".to_string()));
+        env.define("gen-list".to_string(), Value::Builtin("gen-list".to_string()));
+        env.define("gen-record".to_string(), Value::Builtin("gen-record".to_string()));
+        env.define("forall".to_string(), Value::Builtin("forall".to_string()));
+
+        // Logging
+        env.define("log-debug".to_string(), Value::Builtin("log-debug".to_string()));
+        env.define("log-info".to_string(), Value::Builtin("log-info".to_string()));
+        env.define("log-warn".to_string(), Value::Builtin("log-warn".to_string()));
+        env.define("log-error".to_string(), Value::Builtin("log-error".to_string()));
     }
 
 
@@ -0,0 +1,579 @@
+//! MQTT client
+//!
+//! `(mqtt-connect url opts)` opens a connection to a broker at
+//! `mqtt://host[:port]` (1883 by default) or, over TLS, `mqtts://host[:port]`
+//! (8883) and returns its id. Options are `(name value)` pairs:
+//! `client-id`, `username`, `password`, `keep-alive` (seconds),
+//! `clean-session`, `reconnect-ms` and `max-reconnect-ms`.
+//!
+//! Each connection runs its event loop on a thread of its own. When the
+//! broker goes away the loop reconnects, waiting twice as long after each
+//! failed attempt, from `reconnect-ms` up to `max-reconnect-ms`. Sessions
+//! persist by default: the broker is asked to keep the connection's
+//! subscriptions and queued messages while it is away, and messages
+//! published with QoS 1 or 2 that were not acknowledged are sent again
+//! after reconnecting. If the broker has no session for the connection, its
+//! subscriptions are made again.
+//!
+//! Messages on a subscribed topic are delivered to the handler actor as
+//! `("mqtt-message" connection topic payload qos retain)`, with the payload
+//! as a string, or as a list of bytes when it is not UTF-8.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use rumqttc::{Client, ClientError, Event, Incoming, MqttOptions, QoS, Transport};
+
+use crate::error::{TlispError, TlispResult};
+use crate::orm::cdc::ActorSender;
+use crate::tlisp::Value;
+use crate::types::{MessagePayload, Pid};
+
+/// Requests a connection queues before publishing fails
+const REQUEST_CAPACITY: usize = 256;
+
+/// Open connections by id, shared by every interpreter of the process
+static CONNECTIONS: Lazy<Mutex<HashMap<u64, Arc<MqttConnection>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Where a broker listens, from an `mqtt://` or `mqtts://` URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Broker {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl Broker {
+    pub fn parse(url: &str) -> TlispResult<Self> {
+        let invalid = || TlispError::Runtime(format!("mqtt-connect: expected mqtt://host[:port] or mqtts://host[:port], got {}", url));
+        let (tls, rest) = match url.split_once("://") {
+            Some(("mqtt" | "tcp", rest)) => (false, rest),
+            Some(("mqtts" | "ssl", rest)) => (true, rest),
+            _ => return Err(invalid()),
+        };
+        let authority = rest.trim_end_matches('/');
+        if authority.is_empty() || authority.contains('/') {
+            return Err(invalid());
+        }
+        let default_port = if tls { 8883 } else { 1883 };
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, rest) = bracketed.split_once(']').ok_or_else(invalid)?;
+                match rest {
+                    "" => (host, default_port),
+                    _ => (host, rest.strip_prefix(':').and_then(|port| port.parse().ok()).ok_or_else(invalid)?),
+                }
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+                None => (authority, default_port),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Broker {
+            host: host.to_string(),
+            port,
+            tls,
+        })
+    }
+
+    /// `host:port`, as network permissions name it
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Connection options
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttConfig {
+    /// Client id; generated when empty
+    pub client_id: String,
+    pub credentials: Option<(String, String)>,
+    pub keep_alive: Duration,
+    /// Start a new session instead of resuming the broker's
+    pub clean_session: bool,
+    /// Wait before the first reconnection attempt
+    pub reconnect_delay: Duration,
+    /// Longest wait between reconnection attempts
+    pub max_reconnect_delay: Duration,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            client_id: String::new(),
+            credentials: None,
+            keep_alive: Duration::from_secs(30),
+            clean_session: false,
+            reconnect_delay: Duration::from_millis(500),
+            max_reconnect_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl MqttConfig {
+    /// Options from `(name value)` pairs
+    pub fn from_value(options: &Value) -> TlispResult<Self> {
+        let pairs = match options {
+            Value::Null | Value::Unit => return Ok(Self::default()),
+            Value::List(pairs) => pairs,
+            other => return Err(TlispError::Runtime(format!("mqtt-connect: options must be a list of (name value) pairs, got {}", other))),
+        };
+        let mut config = Self::default();
+        let mut username = None;
+        let mut password = None;
+        for pair in pairs {
+            let (name, value) = match pair {
+                Value::List(items) if items.len() == 2 => match &items[0] {
+                    Value::Symbol(name) | Value::String(name) => (name.as_str(), &items[1]),
+                    _ => return Err(TlispError::Runtime(format!("mqtt-connect: bad option {}", pair))),
+                },
+                _ => return Err(TlispError::Runtime(format!("mqtt-connect: bad option {}", pair))),
+            };
+            let invalid = || TlispError::Runtime(format!("mqtt-connect: bad value for {}: {}", name, value));
+            let text = || match value {
+                Value::String(text) | Value::Symbol(text) => Ok(text.clone()),
+                _ => Err(invalid()),
+            };
+            let millis = || match value {
+                Value::Int(n) if *n >= 0 => Ok(Duration::from_millis(*n as u64)),
+                _ => Err(invalid()),
+            };
+            match name {
+                "client-id" => config.client_id = text()?,
+                "username" => username = Some(text()?),
+                "password" => password = Some(text()?),
+                "keep-alive" => config.keep_alive = millis()? * 1000,
+                "clean-session" => match value {
+                    Value::Bool(clean) => config.clean_session = *clean,
+                    _ => return Err(invalid()),
+                },
+                "reconnect-ms" => config.reconnect_delay = millis()?,
+                "max-reconnect-ms" => config.max_reconnect_delay = millis()?,
+                other => return Err(TlispError::Runtime(format!("mqtt-connect: unknown option {}", other))),
+            }
+        }
+        match (username, password) {
+            (Some(username), password) => config.credentials = Some((username, password.unwrap_or_default())),
+            (None, Some(_)) => return Err(TlispError::Runtime("mqtt-connect: password needs a username".to_string())),
+            (None, None) => {}
+        }
+        if config.max_reconnect_delay < config.reconnect_delay {
+            config.max_reconnect_delay = config.reconnect_delay;
+        }
+        Ok(config)
+    }
+}
+
+/// QoS level 0, 1 or 2
+pub fn qos(level: i64) -> TlispResult<QoS> {
+    match level {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        other => Err(TlispError::Runtime(format!("QoS must be 0, 1 or 2, got {}", other))),
+    }
+}
+
+/// A topic filter and the actor its messages go to
+struct Subscription {
+    filter: String,
+    qos: QoS,
+    handler: Pid,
+    sender: Arc<dyn ActorSender>,
+}
+
+/// An open connection to a broker
+pub struct MqttConnection {
+    id: u64,
+    client: Client,
+    subscriptions: Arc<Mutex<Vec<Subscription>>>,
+    connected: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+}
+
+impl MqttConnection {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Whether the broker has accepted the connection and not dropped it
+    /// since
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+}
+
+/// Connect to the broker at `url`, returning the connection's id. The
+/// connection is made in the background; requests made before it is up
+/// are queued.
+pub fn connect(url: &str, config: MqttConfig) -> TlispResult<u64> {
+    let broker = Broker::parse(url)?;
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    let client_id = match config.client_id.as_str() {
+        "" => format!("ream-{}", uuid::Uuid::new_v4().simple()),
+        client_id => client_id.to_string(),
+    };
+    let mut options = MqttOptions::new(client_id, broker.host.clone(), broker.port);
+    options.set_keep_alive(config.keep_alive).set_clean_session(config.clean_session);
+    if let Some((username, password)) = &config.credentials {
+        options.set_credentials(username.clone(), password.clone());
+    }
+    if broker.tls {
+        options.set_transport(Transport::tls_with_default_config());
+    }
+
+    let (client, mut events) = Client::new(options, REQUEST_CAPACITY);
+    let connection = Arc::new(MqttConnection {
+        id,
+        client: client.clone(),
+        subscriptions: Arc::new(Mutex::new(Vec::new())),
+        connected: Arc::new(AtomicBool::new(false)),
+        closed: Arc::new(AtomicBool::new(false)),
+    });
+
+    let subscriptions = connection.subscriptions.clone();
+    let connected = connection.connected.clone();
+    let closed = connection.closed.clone();
+    let address = broker.address();
+    thread::Builder::new()
+        .name(format!("mqtt-{}", id))
+        .spawn(move || {
+            let mut delay = config.reconnect_delay;
+            while let Ok(event) = events.recv() {
+                match event {
+                    Ok(Event::Incoming(Incoming::ConnAck(ack))) => {
+                        connected.store(true, Ordering::SeqCst);
+                        delay = config.reconnect_delay;
+                        if !ack.session_present {
+                            resubscribe(&client, &subscriptions.lock().unwrap());
+                        }
+                    }
+                    Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                        let message = message(id, &publish.topic, &publish.payload, publish.qos, publish.retain);
+                        for subscription in subscriptions.lock().unwrap().iter() {
+                            if rumqttc::matches(&publish.topic, &subscription.filter) {
+                                if let Err(e) = subscription.sender.send(subscription.handler, message.clone()) {
+                                    tracing::warn!("mqtt: could not deliver {} to {}: {}", publish.topic, subscription.handler, e);
+                                }
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(_) if closed.load(Ordering::SeqCst) => break,
+                    Err(e) => {
+                        connected.store(false, Ordering::SeqCst);
+                        tracing::warn!("mqtt: connection {} to {} failed, retrying in {:?}: {}", id, address, delay, e);
+                        thread::sleep(delay);
+                        delay = (delay * 2).min(config.max_reconnect_delay);
+                    }
+                }
+            }
+            connected.store(false, Ordering::SeqCst);
+        })
+        .map_err(|e| TlispError::Runtime(format!("mqtt-connect: could not start connection thread: {}", e)))?;
+
+    CONNECTIONS.lock().unwrap().insert(id, connection);
+    Ok(id)
+}
+
+/// Make every subscription again, for a broker that lost them
+fn resubscribe(client: &Client, subscriptions: &[Subscription]) {
+    for subscription in subscriptions {
+        if let Err(e) = client.try_subscribe(subscription.filter.clone(), subscription.qos) {
+            tracing::warn!("mqtt: could not resubscribe to {}: {}", subscription.filter, e);
+        }
+    }
+}
+
+/// The message an actor receives for a publish
+fn message(connection: u64, topic: &str, payload: &[u8], qos: QoS, retain: bool) -> MessagePayload {
+    let payload = match std::str::from_utf8(payload) {
+        Ok(text) => serde_json::Value::from(text),
+        Err(_) => serde_json::Value::from(payload.to_vec()),
+    };
+    MessagePayload::Data(serde_json::json!(["mqtt-message", connection, topic, payload, qos as u8, retain]))
+}
+
+/// The open connection `id`
+pub fn connection(id: u64) -> TlispResult<Arc<MqttConnection>> {
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| TlispError::Runtime(format!("no open MQTT connection {}", id)))
+}
+
+fn queue_failed(name: &str, error: ClientError) -> TlispError {
+    match error {
+        ClientError::TryRequest(_) => TlispError::Runtime(format!("{}: too many requests queued for the broker", name)),
+        ClientError::Request(_) => TlispError::Runtime(format!("{}: invalid topic", name)),
+    }
+}
+
+/// Send the messages on topics matching `filter` to `handler`
+pub fn subscribe(id: u64, filter: &str, qos: QoS, handler: Pid, sender: Arc<dyn ActorSender>) -> TlispResult<()> {
+    if !rumqttc::valid_filter(filter) {
+        return Err(TlispError::Runtime(format!("mqtt-subscribe: invalid topic filter {}", filter)));
+    }
+    let connection = connection(id)?;
+    connection.client.try_subscribe(filter, qos).map_err(|e| queue_failed("mqtt-subscribe", e))?;
+    connection.subscriptions.lock().unwrap().push(Subscription {
+        filter: filter.to_string(),
+        qos,
+        handler,
+        sender,
+    });
+    Ok(())
+}
+
+/// Publish `payload` on `topic`
+pub fn publish(id: u64, topic: &str, payload: Vec<u8>, qos: QoS, retain: bool) -> TlispResult<()> {
+    if !rumqttc::valid_topic(topic) || topic.is_empty() {
+        return Err(TlispError::Runtime(format!("mqtt-publish: invalid topic {}", topic)));
+    }
+    connection(id)?
+        .client
+        .try_publish(topic, qos, retain, payload)
+        .map_err(|e| queue_failed("mqtt-publish", e))
+}
+
+/// Close connection `id`, ending its session unless it persists
+pub fn disconnect(id: u64) -> TlispResult<()> {
+    let connection = CONNECTIONS
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or_else(|| TlispError::Runtime(format!("no open MQTT connection {}", id)))?;
+    connection.closed.store(true, Ordering::SeqCst);
+    // A connection that never came up has nothing to say goodbye to
+    let _ = connection.client.try_disconnect();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RuntimeResult;
+    use crate::tlisp::TlispInterpreter;
+    use bytes::BytesMut;
+    use rumqttc::mqttbytes::v4::{read, ConnAck, ConnectReturnCode, Packet, PingResp, PubAck, Publish, SubAck, SubscribeReasonCode};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Instant;
+
+    #[derive(Default)]
+    struct RecordingSender {
+        messages: Mutex<Vec<(Pid, MessagePayload)>>,
+    }
+
+    impl ActorSender for RecordingSender {
+        fn send(&self, to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+            self.messages.lock().unwrap().push((to, payload));
+            Ok(())
+        }
+    }
+
+    /// A broker routing QoS 0 and 1 publishes to its subscribers, which
+    /// keeps no sessions
+    #[derive(Clone, Default)]
+    struct TestBroker {
+        clients: Arc<Mutex<Vec<(TcpStream, Vec<String>)>>>,
+        connections: Arc<AtomicU64>,
+    }
+
+    impl TestBroker {
+        fn start() -> (TestBroker, u16) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let broker = TestBroker::default();
+            let accepting = broker.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let broker = accepting.clone();
+                    thread::spawn(move || broker.serve(stream));
+                }
+            });
+            (broker, port)
+        }
+
+        fn serve(&self, mut stream: TcpStream) {
+            let index = {
+                let mut clients = self.clients.lock().unwrap();
+                clients.push((stream.try_clone().unwrap(), Vec::new()));
+                clients.len() - 1
+            };
+            let mut buffer = BytesMut::new();
+            let mut chunk = [0u8; 4096];
+            loop {
+                let packet = match read(&mut buffer, 1 << 20) {
+                    Ok(packet) => packet,
+                    Err(rumqttc::mqttbytes::Error::InsufficientBytes(_)) => match stream.read(&mut chunk) {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => {
+                            buffer.extend_from_slice(&chunk[..n]);
+                            continue;
+                        }
+                    },
+                    Err(_) => return,
+                };
+                let mut reply = BytesMut::new();
+                match packet {
+                    Packet::Connect(_) => {
+                        self.connections.fetch_add(1, Ordering::SeqCst);
+                        ConnAck::new(ConnectReturnCode::Success, false).write(&mut reply).unwrap();
+                    }
+                    Packet::Subscribe(subscribe) => {
+                        let codes = subscribe.filters.iter().map(|filter| SubscribeReasonCode::Success(filter.qos)).collect();
+                        self.clients.lock().unwrap()[index].1.extend(subscribe.filters.into_iter().map(|filter| filter.path));
+                        SubAck::new(subscribe.pkid, codes).write(&mut reply).unwrap();
+                    }
+                    Packet::Publish(publish) => {
+                        if publish.qos == QoS::AtLeastOnce {
+                            PubAck::new(publish.pkid).write(&mut reply).unwrap();
+                        }
+                        let mut forwarded = BytesMut::new();
+                        let mut copy = Publish::new(publish.topic.clone(), QoS::AtMostOnce, publish.payload.to_vec());
+                        copy.retain = publish.retain;
+                        copy.write(&mut forwarded).unwrap();
+                        for (client, filters) in self.clients.lock().unwrap().iter_mut() {
+                            if filters.iter().any(|filter| rumqttc::matches(&publish.topic, filter)) {
+                                let _ = client.write_all(&forwarded);
+                            }
+                        }
+                    }
+                    Packet::PingReq => {
+                        PingResp.write(&mut reply).unwrap();
+                    }
+                    Packet::Disconnect => return,
+                    _ => {}
+                }
+                if stream.write_all(&reply).is_err() {
+                    return;
+                }
+            }
+        }
+
+        /// Drop every client, forgetting their subscriptions
+        fn drop_clients(&self) {
+            for (client, filters) in self.clients.lock().unwrap().iter_mut() {
+                let _ = client.shutdown(std::net::Shutdown::Both);
+                filters.clear();
+            }
+        }
+    }
+
+    fn data(payload: &MessagePayload) -> serde_json::Value {
+        match payload {
+            MessagePayload::Data(data) => data.clone(),
+            other => panic!("expected data, got {:?}", other),
+        }
+    }
+
+    fn wait_for(what: &str, condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_broker_urls_and_options() {
+        assert_eq!(Broker::parse("mqtt://localhost").unwrap().address(), "localhost:1883");
+        assert_eq!(
+            Broker::parse("mqtts://broker.example.com:8884/").unwrap(),
+            Broker { host: "broker.example.com".to_string(), port: 8884, tls: true }
+        );
+        assert_eq!(Broker::parse("mqtt://[::1]:1884").unwrap().address(), "::1:1884");
+        assert_eq!(Broker::parse("mqtt://[::1]").unwrap().port, 1883);
+        assert!(Broker::parse("http://localhost").is_err());
+        assert!(Broker::parse("mqtt://localhost/topic").is_err());
+
+        let mut interpreter = TlispInterpreter::new();
+        let options = interpreter
+            .eval("'((client-id \"sensor-1\") (username \"ann\") (keep-alive 10) (clean-session true) (reconnect-ms 100))")
+            .unwrap();
+        let config = MqttConfig::from_value(&options).unwrap();
+        assert_eq!(config.client_id, "sensor-1");
+        assert_eq!(config.credentials, Some(("ann".to_string(), String::new())));
+        assert_eq!(config.keep_alive, Duration::from_secs(10));
+        assert!(config.clean_session);
+        assert_eq!(config.max_reconnect_delay, Duration::from_secs(30));
+        let unknown = interpreter.eval("'((color \"red\"))").unwrap();
+        assert!(MqttConfig::from_value(&unknown).is_err());
+        assert!(qos(3).is_err());
+    }
+
+    #[test]
+    fn test_messages_reach_handlers_across_reconnects() {
+        let (broker, port) = TestBroker::start();
+        let config = MqttConfig {
+            client_id: "ream-test".to_string(),
+            reconnect_delay: Duration::from_millis(50),
+            ..MqttConfig::default()
+        };
+        let id = connect(&format!("mqtt://127.0.0.1:{}", port), config).unwrap();
+        let sender = Arc::new(RecordingSender::default());
+        let handler = Pid::new();
+        subscribe(id, "sensors/+/temp", QoS::AtLeastOnce, handler, sender.clone()).unwrap();
+        assert!(subscribe(id, "sensors/#/temp", QoS::AtLeastOnce, handler, sender.clone()).is_err());
+        wait_for("the connection", || connection(id).unwrap().is_connected());
+
+        let delivered = |count: usize| {
+            let sender = sender.clone();
+            move || sender.messages.lock().unwrap().len() >= count
+        };
+        publish(id, "sensors/kitchen/temp", b"21.5".to_vec(), QoS::AtLeastOnce, false).unwrap();
+        publish(id, "sensors/kitchen/humidity", b"40".to_vec(), QoS::AtMostOnce, false).unwrap();
+        wait_for("the first message", delivered(1));
+        {
+            let messages = sender.messages.lock().unwrap();
+            assert_eq!(messages[0].0, handler);
+            assert_eq!(data(&messages[0].1), serde_json::json!(["mqtt-message", id, "sensors/kitchen/temp", "21.5", 0, false]));
+        }
+
+        // The broker loses the session, so the subscription is made again
+        broker.drop_clients();
+        wait_for("the reconnection", || broker.connections.load(Ordering::SeqCst) >= 2);
+        wait_for("the resubscription", || {
+            broker.clients.lock().unwrap().iter().any(|(_, filters)| !filters.is_empty())
+        });
+        publish(id, "sensors/hall/temp", vec![0xff, 0x01], QoS::AtMostOnce, true).unwrap();
+        wait_for("the second message", delivered(2));
+        assert_eq!(
+            data(&sender.messages.lock().unwrap()[1].1),
+            serde_json::json!(["mqtt-message", id, "sensors/hall/temp", [255, 1], 0, true])
+        );
+        assert_eq!(sender.messages.lock().unwrap().len(), 2, "the humidity reading matches no subscription");
+
+        disconnect(id).unwrap();
+        assert!(publish(id, "sensors/kitchen/temp", Vec::new(), QoS::AtMostOnce, false).is_err());
+    }
+
+    #[test]
+    fn test_builtins() {
+        let (_broker, port) = TestBroker::start();
+        let mut interpreter = TlispInterpreter::new();
+        let id = interpreter
+            .eval(&format!("(mqtt-connect \"mqtt://127.0.0.1:{}\" '((client-id \"builtins\")))", port))
+            .unwrap();
+        let Value::Int(id) = id else { panic!("expected a connection id, got {}", id) };
+        assert_eq!(interpreter.eval(&format!("(mqtt-publish {} \"lights/on\" \"yes\" 1)", id)).unwrap(), Value::Unit);
+        assert!(interpreter.eval(&format!("(mqtt-publish {} \"lights/#\" \"yes\" 1)", id)).is_err());
+        assert!(interpreter.eval(&format!("(mqtt-publish {} \"lights/on\" \"yes\" 5)", id)).is_err());
+        assert!(interpreter.eval(&format!("(mqtt-subscribe {} \"lights/+\" (self))", id)).is_err(), "handlers need a runtime");
+        assert_eq!(interpreter.eval(&format!("(mqtt-disconnect {})", id)).unwrap(), Value::Unit);
+        assert!(interpreter.eval(&format!("(mqtt-disconnect {})", id)).is_err());
+        assert!(interpreter.eval("(mqtt-connect \"http://127.0.0.1\")").is_err());
+    }
+}
@@ -1,7 +1,8 @@
 //! Package Configuration System for TLISP
 //! 
-//! Provides package.toml configuration files for TLISP projects with
+//! Provides ream.toml configuration files for TLISP projects with
 //! dependency management, build configuration, and project metadata.
+//! Projects created before ream.toml still load from package.toml.
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -11,6 +12,15 @@ use crate::error::{TlispError, TlispResult};
 use crate::tlisp::package_manager::{VersionRequirement, PackageMetadata};
 use crate::tlisp::ModuleLanguage;
 
+/// Project configuration file name
+pub const CONFIG_FILE: &str = "ream.toml";
+
+/// Configuration file name used by older projects
+pub const LEGACY_CONFIG_FILE: &str = "package.toml";
+
+/// Templates accepted by `ProjectConfigManager::init_project`
+pub const PROJECT_TEMPLATES: &[&str] = &["basic", "lib", "bin", "workspace", "actor", "web", "cli"];
+
 /// TLISP project configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectConfig {
@@ -46,6 +56,9 @@ pub struct ProjectConfig {
     /// Environment variables
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Native extension crates, by the prefix their functions are bound under
+    #[serde(default)]
+    pub extensions: HashMap<String, ExtensionConfig>,
 }
 
 /// Package information
@@ -173,6 +186,19 @@ pub struct TargetConfig {
     pub build: Option<BuildConfig>,
 }
 
+/// Rust crate built as a native extension of the package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionConfig {
+    /// Crate directory, relative to the project root
+    pub path: PathBuf,
+    /// Crate features to enable
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Whether to enable the crate's default features
+    #[serde(default = "default_true")]
+    pub default_features: bool,
+}
+
 /// Workspace configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkspaceConfig {
@@ -313,25 +339,26 @@ impl ProjectConfig {
             workspace: None,
             scripts: HashMap::new(),
             env: HashMap::new(),
+            extensions: HashMap::new(),
         }
     }
 
     /// Load project configuration from file
     pub fn from_file<P: AsRef<Path>>(path: P) -> TlispResult<Self> {
         let content = fs::read_to_string(path)
-            .map_err(|e| TlispError::Runtime(format!("Failed to read package.toml: {}", e)))?;
+            .map_err(|e| TlispError::Runtime(format!("Failed to read project config: {}", e)))?;
         
         toml::from_str(&content)
-            .map_err(|e| TlispError::Runtime(format!("Failed to parse package.toml: {}", e)))
+            .map_err(|e| TlispError::Runtime(format!("Failed to parse project config: {}", e)))
     }
 
     /// Save project configuration to file
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> TlispResult<()> {
         let content = toml::to_string_pretty(self)
-            .map_err(|e| TlispError::Runtime(format!("Failed to serialize package.toml: {}", e)))?;
+            .map_err(|e| TlispError::Runtime(format!("Failed to serialize project config: {}", e)))?;
         
         fs::write(path, content)
-            .map_err(|e| TlispError::Runtime(format!("Failed to write package.toml: {}", e)))
+            .map_err(|e| TlispError::Runtime(format!("Failed to write project config: {}", e)))
     }
 
     /// Convert to PackageMetadata for package manager
@@ -472,6 +499,13 @@ impl ProjectConfig {
             self.validate_dependency(name, spec)?;
         }
 
+        // Validate extensions
+        for name in self.extensions.keys() {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(TlispError::Runtime(format!("Invalid extension name '{}'", name)));
+            }
+        }
+
         // Validate features
         for (feature_name, feature_deps) in &self.features {
             for dep in feature_deps {
@@ -552,10 +586,10 @@ impl ProjectConfigManager {
 
     /// Load project configuration
     pub fn load(&mut self) -> TlispResult<&ProjectConfig> {
-        let config_path = self.project_root.join("package.toml");
+        let config_path = self.config_file();
 
         if !config_path.exists() {
-            return Err(TlispError::Runtime("No package.toml found in project root".to_string()));
+            return Err(TlispError::Runtime(format!("No {} found in project root", CONFIG_FILE)));
         }
 
         let config = ProjectConfig::from_file(&config_path)?;
@@ -569,7 +603,7 @@ impl ProjectConfigManager {
 
     /// Load or create project configuration
     pub fn load_or_create(&mut self, name: String, version: String) -> TlispResult<&ProjectConfig> {
-        let config_path = self.project_root.join("package.toml");
+        let config_path = self.config_file();
 
         if config_path.exists() {
             self.load()
@@ -606,10 +640,13 @@ impl ProjectConfigManager {
 
     /// Initialize new project
     pub fn init_project(&mut self, name: String, version: String, template: Option<&str>) -> TlispResult<()> {
-        let config_path = self.project_root.join("package.toml");
+        let config_path = self.config_file();
 
         if config_path.exists() {
-            return Err(TlispError::Runtime("Project already initialized (package.toml exists)".to_string()));
+            return Err(TlispError::Runtime(format!(
+                "Project already initialized ({} exists)",
+                config_path.file_name().and_then(|n| n.to_str()).unwrap_or(CONFIG_FILE)
+            )));
         }
 
         let mut config = ProjectConfig::new(name.clone(), version);
@@ -620,7 +657,7 @@ impl ProjectConfigManager {
         }
 
         // Create directory structure
-        self.create_project_structure(&config)?;
+        self.create_project_structure(&config, template)?;
 
         // Save configuration
         config.to_file(&config_path)?;
@@ -649,6 +686,19 @@ impl ProjectConfigManager {
                     required_features: Vec::new(),
                 });
             }
+            "basic" | "actor" | "web" | "cli" => {
+                config.package.bin.push(BinaryTarget {
+                    name: config.package.name.clone(),
+                    path: Some(PathBuf::from("src/main.tl")),
+                    required_features: Vec::new(),
+                });
+                config.package.tests.push(TestTarget {
+                    name: "main".to_string(),
+                    path: Some(PathBuf::from("tests/main_test.tl")),
+                    required_features: Vec::new(),
+                    harness: true,
+                });
+            }
             "workspace" => {
                 config.workspace = Some(WorkspaceConfig {
                     members: vec!["packages/*".to_string()],
@@ -660,7 +710,7 @@ impl ProjectConfigManager {
             }
             _ => {
                 return Err(TlispError::Runtime(
-                    format!("Unknown template: {}", template)
+                    format!("Unknown template: {} (expected one of: {})", template, PROJECT_TEMPLATES.join(", "))
                 ));
             }
         }
@@ -669,7 +719,7 @@ impl ProjectConfigManager {
     }
 
     /// Create project directory structure
-    fn create_project_structure(&self, config: &ProjectConfig) -> TlispResult<()> {
+    fn create_project_structure(&self, config: &ProjectConfig, template: Option<&str>) -> TlispResult<()> {
         // Create src directory
         let src_dir = self.project_root.join("src");
         fs::create_dir_all(&src_dir)
@@ -687,7 +737,14 @@ impl ProjectConfigManager {
         if !config.package.bin.is_empty() {
             let main_file = src_dir.join("main.tl");
             if !main_file.exists() {
-                fs::write(&main_file, self.generate_main_template(&config.package.name))
+                let name = &config.package.name;
+                let source = match template {
+                    Some("actor") => self.generate_actor_template(name),
+                    Some("web") => self.generate_web_template(name),
+                    Some("cli") => self.generate_cli_template(name),
+                    _ => self.generate_main_template(name),
+                };
+                fs::write(&main_file, source)
                     .map_err(|e| TlispError::Runtime(format!("Failed to create main.tl: {}", e)))?;
             }
         }
@@ -704,6 +761,22 @@ impl ProjectConfigManager {
             let tests_dir = self.project_root.join("tests");
             fs::create_dir_all(&tests_dir)
                 .map_err(|e| TlispError::Runtime(format!("Failed to create tests directory: {}", e)))?;
+
+            for test in &config.package.tests {
+                let Some(path) = &test.path else { continue };
+                let test_file = self.project_root.join(path);
+                if !test_file.exists() {
+                    fs::write(&test_file, self.generate_test_template(&config.package.name))
+                        .map_err(|e| TlispError::Runtime(format!("Failed to create {}: {}", path.display(), e)))?;
+                }
+            }
+        }
+
+        // Keep build output out of version control
+        let gitignore = self.project_root.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(&gitignore, "/build/\n/target/\n*.reambc\n.ream_history\n")
+                .map_err(|e| TlispError::Runtime(format!("Failed to create .gitignore: {}", e)))?;
         }
 
         Ok(())
@@ -754,6 +827,118 @@ impl ProjectConfigManager {
         )
     }
 
+    /// Generate actor service template
+    fn generate_actor_template(&self, name: &str) -> String {
+        format!(
+            r#";; {} actor service
+;;
+;; A worker actor that answers requests sent to it.
+
+(module main
+  "Actor service for {}"
+
+  ;; Worker loop: reply to each request, then wait for the next one
+  (defn worker []
+    "Handles incoming requests"
+    (let ((request (receive)))
+      (println "worker received:" request)
+      (worker)))
+
+  ;; Main function
+  (defn main [args]
+    "Spawns the worker and sends it a request"
+    (let ((pid (spawn (worker))))
+      (send pid "ping")
+      (println "{} started worker" pid)
+      0)))
+"#,
+            name, name, name
+        )
+    }
+
+    /// Generate web service template
+    fn generate_web_template(&self, name: &str) -> String {
+        format!(
+            r#";; {} web service
+;;
+;; An HTTP service with a couple of routes.
+
+(module main
+  "Web service for {}"
+
+  ;; Route a request path to a response body
+  (defn route [path]
+    "Returns the response body for a path"
+    (cond
+      ((= path "/") "Hello, World from {}!")
+      ((= path "/health") "ok")
+      (else "not found")))
+
+  ;; Main function
+  (defn main [args]
+    "Starts the HTTP server"
+    (http-server:start 8080)
+    (println "{} listening on port 8080")
+    0))
+"#,
+            name, name, name, name
+        )
+    }
+
+    /// Generate command-line tool template
+    fn generate_cli_template(&self, name: &str) -> String {
+        format!(
+            r#";; {} command-line tool
+;;
+;; Prints usage without arguments and greets each argument otherwise.
+
+(module main
+  "Command-line tool {}"
+
+  (defn usage []
+    "Prints usage information"
+    (println "usage: {} <name>..."))
+
+  ;; Main function
+  (defn main [args]
+    "Main entry point"
+    (if (null? args)
+        (begin (usage) 1)
+        (begin
+          (map (lambda (arg) (println "Hello," arg)) args)
+          0))))
+"#,
+            name, name, name
+        )
+    }
+
+    /// Generate test template
+    fn generate_test_template(&self, name: &str) -> String {
+        format!(
+            r#";; Tests for {}
+;;
+;; Run with `ream test`.
+
+(define greeting (lambda (who) (string-append "Hello, " who "!")))
+
+(deftest greeting-includes-name
+  (assert-equal (greeting "{}") "Hello, {}!"))
+"#,
+            name, name, name
+        )
+    }
+
+    /// Path of the configuration file, preferring ream.toml over package.toml
+    pub fn config_file(&self) -> PathBuf {
+        let legacy = self.project_root.join(LEGACY_CONFIG_FILE);
+        let config = self.project_root.join(CONFIG_FILE);
+        if !config.exists() && legacy.exists() {
+            legacy
+        } else {
+            config
+        }
+    }
+
     /// Get project root
     pub fn project_root(&self) -> &Path {
         &self.project_root
@@ -761,6 +946,6 @@ impl ProjectConfigManager {
 
     /// Check if project is initialized
     pub fn is_initialized(&self) -> bool {
-        self.project_root.join("package.toml").exists()
+        self.config_file().exists()
     }
 }
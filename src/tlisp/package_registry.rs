@@ -3,15 +3,30 @@
 //! Provides a comprehensive package registry system for publishing,
 //! downloading, and managing TLISP packages with version control,
 //! authentication, and dependency resolution.
+//!
+//! `PackageRegistry` is the client of a registry served by
+//! `registry_server`: publishing and yanking go to the server with the
+//! user's token, while the index is cached locally so searches and cached
+//! packages stay available offline. Package contents are checked against
+//! their SHA-256 checksum, and against their Ed25519 signature when signed.
 
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::fs;
 use std::time::SystemTime;
+use ring::digest::{digest, SHA256};
+use ring::signature::{UnparsedPublicKey, ED25519};
 use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use crate::bytecode::signing::SigningKey;
 use crate::error::{TlispError, TlispResult};
 use crate::tlisp::package_manager::{PackageMetadata, VersionRequirement};
 
+/// Registry `ream publish`, `ream yank` and `ream search` use by default
+pub const DEFAULT_REGISTRY_URL: &str = "https://packages.tlisp.org";
+
+/// Path prefix of the registry HTTP API
+pub const API_PREFIX: &str = "/api/v1";
 
 /// Package registry for managing TLISP packages
 pub struct PackageRegistry {
@@ -29,6 +44,8 @@ pub struct PackageRegistry {
     index_cache: HashMap<String, PackageIndex>,
     /// Registry statistics
     stats: RegistryStats,
+    /// Whether to stay off the network, using only the local cache
+    offline: bool,
 }
 
 /// Registry configuration
@@ -87,6 +104,9 @@ pub struct PackageIndex {
     pub last_updated: SystemTime,
     /// Download statistics
     pub download_stats: DownloadStats,
+    /// Users who may publish and yank versions, starting with the first publisher
+    #[serde(default)]
+    pub owners: Vec<String>,
 }
 
 /// Package version information
@@ -96,7 +116,7 @@ pub struct PackageVersion {
     pub version: String,
     /// Package metadata for this version
     pub metadata: PackageMetadata,
-    /// Download URL
+    /// Download URL, relative to the registry URL unless absolute
     pub download_url: String,
     /// Package checksum
     pub checksum: String,
@@ -108,10 +128,61 @@ pub struct PackageVersion {
     pub yanked: bool,
     /// Yank reason if applicable
     pub yank_reason: Option<String>,
+    /// Publisher's signature over the checksum
+    #[serde(default)]
+    pub signature: Option<PackageSignature>,
+}
+
+/// Ed25519 signature over a package checksum
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageSignature {
+    /// Hex-encoded public key of the signer
+    pub public_key: String,
+    /// Hex-encoded signature
+    pub signature: String,
+}
+
+impl PackageSignature {
+    /// Sign a package checksum
+    pub fn sign(key: &SigningKey, checksum: &str) -> Self {
+        PackageSignature {
+            public_key: hex::encode(key.public_key()),
+            signature: hex::encode(key.sign(checksum.as_bytes())),
+        }
+    }
+
+    /// Check that this is a signature over `checksum`
+    pub fn verify(&self, checksum: &str) -> TlispResult<()> {
+        let invalid = || TlispError::SecurityError("Invalid package signature".to_string());
+        let public_key = hex::decode(&self.public_key).map_err(|_| invalid())?;
+        let signature = hex::decode(&self.signature).map_err(|_| invalid())?;
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(checksum.as_bytes(), &signature)
+            .map_err(|_| invalid())
+    }
+}
+
+/// Hex-encoded SHA-256 checksum of package content
+pub fn package_checksum(content: &[u8]) -> String {
+    hex::encode(digest(&SHA256, content))
+}
+
+/// Check package content against its checksum and, if signed, its signature
+pub fn verify_package(content: &[u8], checksum: &str, signature: Option<&PackageSignature>) -> TlispResult<()> {
+    let actual_checksum = package_checksum(content);
+    if actual_checksum != checksum {
+        return Err(TlispError::SecurityError(
+            format!("Checksum mismatch: expected {}, got {}", checksum, actual_checksum)
+        ));
+    }
+    match signature {
+        Some(signature) => signature.verify(checksum),
+        None => Ok(()),
+    }
 }
 
 /// Download statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DownloadStats {
     /// Total downloads
     pub total: u64,
@@ -123,6 +194,17 @@ pub struct DownloadStats {
     pub by_date: HashMap<String, u64>,
 }
 
+impl DownloadStats {
+    /// Count a download of `version`
+    pub fn record(&mut self, version: &str) {
+        self.total += 1;
+        self.recent += 1;
+        *self.by_version.entry(version.to_string()).or_insert(0) += 1;
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        *self.by_date.entry(today).or_insert(0) += 1;
+    }
+}
+
 /// Registry statistics
 #[derive(Debug, Clone, Default)]
 pub struct RegistryStats {
@@ -145,16 +227,39 @@ pub struct RegistryStats {
 pub struct PublishRequest {
     /// Package metadata
     pub metadata: PackageMetadata,
-    /// Package content (base64 encoded)
+    /// Package content (hex encoded)
     pub content: String,
     /// Package checksum
     pub checksum: String,
     /// Publishing options
     pub options: PublishOptions,
+    /// Publisher's signature over the checksum
+    #[serde(default)]
+    pub signature: Option<PackageSignature>,
+}
+
+impl PublishRequest {
+    /// Request publishing `content`, signed with `key` if given
+    pub fn new(metadata: PackageMetadata, content: &[u8], options: PublishOptions, key: Option<&SigningKey>) -> Self {
+        let checksum = package_checksum(content);
+        PublishRequest {
+            metadata,
+            content: hex::encode(content),
+            signature: key.map(|key| PackageSignature::sign(key, &checksum)),
+            checksum,
+            options,
+        }
+    }
+
+    /// The decoded package content
+    pub fn content_bytes(&self) -> TlispResult<Vec<u8>> {
+        hex::decode(&self.content)
+            .map_err(|e| TlispError::Runtime(format!("Invalid package content encoding: {}", e)))
+    }
 }
 
 /// Publishing options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PublishOptions {
     /// Whether to allow overwriting existing versions
     pub allow_overwrite: bool,
@@ -166,6 +271,13 @@ pub struct PublishOptions {
     pub signing_key: Option<String>,
 }
 
+/// Reason given for yanking a version
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct YankRequest {
+    /// Why the version should no longer be used
+    pub reason: Option<String>,
+}
+
 /// Package search query
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchQuery {
@@ -187,6 +299,22 @@ pub struct SearchQuery {
     pub offset: Option<u32>,
 }
 
+impl SearchQuery {
+    /// Search for `query` by relevance, without filters
+    pub fn new(query: impl Into<String>) -> Self {
+        SearchQuery {
+            query: query.into(),
+            category: None,
+            keywords: Vec::new(),
+            author: None,
+            license: None,
+            sort: SearchSort::Relevance,
+            limit: None,
+            offset: None,
+        }
+    }
+}
+
 /// Search sort options
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SearchSort {
@@ -228,6 +356,215 @@ pub struct PackageSearchResult {
     pub score: f64,
 }
 
+/// Check the name and version of a package; both end up in URLs and file
+/// names, so they are limited to characters safe in both
+pub fn validate_package_id(name: &str, version: &str) -> TlispResult<()> {
+    if name.is_empty() {
+        return Err(TlispError::Runtime("Package name cannot be empty".to_string()));
+    }
+    if version.is_empty() {
+        return Err(TlispError::Runtime("Package version cannot be empty".to_string()));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(TlispError::Runtime(format!("Invalid package name '{}'", name)));
+    }
+    if version.starts_with('.') || !version.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')) {
+        return Err(TlispError::Runtime(format!("Invalid package version '{}'", version)));
+    }
+    Ok(())
+}
+
+/// Check a publishing request against a registry's configuration, returning
+/// the package content
+pub fn verify_publish_request(config: &RegistryConfig, request: &PublishRequest) -> TlispResult<Vec<u8>> {
+    let metadata = &request.metadata;
+    validate_package_id(&metadata.name, &metadata.version)?;
+
+    // Check required fields
+    for field in &config.required_fields {
+        match field.as_str() {
+            "description" if metadata.description.is_none() => {
+                return Err(TlispError::Runtime("Description is required".to_string()));
+            }
+            "license" if metadata.license.is_none() => {
+                return Err(TlispError::Runtime("License is required".to_string()));
+            }
+            "author" if metadata.author.is_none() => {
+                return Err(TlispError::Runtime("Author is required".to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    let content = request.content_bytes()?;
+    let content_size = content.len() as u64;
+    if content_size > config.max_package_size {
+        return Err(TlispError::Runtime(
+            format!("Package size {} exceeds maximum allowed size {}",
+                content_size, config.max_package_size)
+        ));
+    }
+
+    verify_package(&content, &request.checksum, request.signature.as_ref())?;
+    Ok(content)
+}
+
+/// Search an index, scoring packages by how well they match the query
+pub fn search_index(index: &HashMap<String, PackageIndex>, query: SearchQuery) -> SearchResults {
+    let start_time = std::time::Instant::now();
+    let mut results = Vec::new();
+
+    for package_index in index.values() {
+        let score = calculate_search_score(package_index, &query);
+        if score > 0.0 {
+            results.push(PackageSearchResult {
+                metadata: package_index.metadata.clone(),
+                latest_version: latest_version(package_index),
+                downloads: package_index.download_stats.clone(),
+                score,
+            });
+        }
+    }
+
+    // Sort results by score or specified sort order
+    match query.sort {
+        SearchSort::Relevance => results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap()),
+        SearchSort::Downloads => results.sort_by_key(|result| std::cmp::Reverse(result.downloads.total)),
+        SearchSort::Name => results.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name)),
+        SearchSort::Updated => {
+            // Would need to track update times for proper sorting
+            results.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
+        }
+        SearchSort::Created => {
+            // Would need to track creation times for proper sorting
+            results.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
+        }
+    }
+
+    // Apply limit and offset
+    let total = results.len() as u32;
+    let offset = query.offset.unwrap_or(0) as usize;
+    let limit = query.limit.unwrap_or(50) as usize;
+
+    if offset < results.len() {
+        results = results.into_iter().skip(offset).take(limit).collect();
+    } else {
+        results.clear();
+    }
+
+    SearchResults {
+        packages: results,
+        total,
+        query,
+        execution_time: start_time.elapsed(),
+    }
+}
+
+/// Calculate search relevance score
+fn calculate_search_score(package_index: &PackageIndex, query: &SearchQuery) -> f64 {
+    let mut score = 0.0;
+    let query_lower = query.query.to_lowercase();
+    let search_terms: Vec<&str> = query_lower.split_whitespace().collect();
+
+    // Score based on name match
+    let name_lower = package_index.metadata.name.to_lowercase();
+    for term in &search_terms {
+        if name_lower.contains(term) {
+            score += 2.0;
+        }
+    }
+
+    // Score based on description match
+    if let Some(description) = &package_index.metadata.description {
+        let desc_lower = description.to_lowercase();
+        for term in &search_terms {
+            if desc_lower.contains(term) {
+                score += 1.0;
+            }
+        }
+    }
+
+    // Score based on keywords match
+    for keyword in &package_index.metadata.keywords {
+        let keyword_lower = keyword.to_lowercase();
+        for term in &search_terms {
+            if keyword_lower.contains(term) {
+                score += 1.5;
+            }
+        }
+    }
+
+    // Apply filters
+    if let Some(category) = &query.category {
+        if !package_index.metadata.categories.contains(category) {
+            return 0.0;
+        }
+    }
+
+    if let Some(author) = &query.author {
+        if package_index.metadata.author.as_ref() != Some(author) {
+            return 0.0;
+        }
+    }
+
+    if let Some(license) = &query.license {
+        if package_index.metadata.license.as_ref() != Some(license) {
+            return 0.0;
+        }
+    }
+
+    if !query.keywords.is_empty() {
+        let has_matching_keyword = query.keywords.iter()
+            .any(|k| package_index.metadata.keywords.contains(k));
+        if !has_matching_keyword {
+            return 0.0;
+        }
+    }
+
+    score
+}
+
+/// Latest version of a package that is not yanked
+pub fn latest_version(package_index: &PackageIndex) -> String {
+    // Simple latest version selection - in a real implementation, this would use semver
+    package_index.versions.keys()
+        .filter(|v| !package_index.versions[*v].yanked)
+        .max()
+        .cloned()
+        .unwrap_or_else(|| "0.0.0".to_string())
+}
+
+/// Add a published version to an index, creating the package's entry with
+/// `owner` as its first owner if it is new
+pub fn record_version(index: &mut HashMap<String, PackageIndex>, package_version: PackageVersion, owner: Option<&str>) {
+    let name = package_version.metadata.name.clone();
+    let package_index = index.entry(name.clone()).or_insert_with(|| PackageIndex {
+        name,
+        versions: HashMap::new(),
+        metadata: package_version.metadata.clone(),
+        last_updated: SystemTime::now(),
+        download_stats: DownloadStats::default(),
+        owners: owner.map(|owner| vec![owner.to_string()]).unwrap_or_default(),
+    });
+    package_index.metadata = package_version.metadata.clone();
+    package_index.last_updated = SystemTime::now();
+    package_index.versions.insert(package_version.version.clone(), package_version);
+}
+
+/// Mark a version yanked or not
+pub fn set_yanked(index: &mut HashMap<String, PackageIndex>, name: &str, version: &str, yanked: bool, reason: Option<String>) -> TlispResult<()> {
+    let package_index = index.get_mut(name)
+        .ok_or_else(|| TlispError::Runtime(format!("Package '{}' not found", name)))?;
+
+    let package_version = package_index.versions.get_mut(version)
+        .ok_or_else(|| TlispError::Runtime(format!("Version '{}' not found", version)))?;
+
+    package_version.yanked = yanked;
+    package_version.yank_reason = if yanked { reason } else { None };
+    package_index.last_updated = SystemTime::now();
+    Ok(())
+}
+
 impl PackageRegistry {
     /// Create new package registry
     pub fn new(name: String, url: String, cache_dir: PathBuf) -> Self {
@@ -239,6 +576,7 @@ impl PackageRegistry {
             config: RegistryConfig::default(),
             index_cache: HashMap::new(),
             stats: RegistryStats::default(),
+            offline: false,
         }
     }
 
@@ -247,6 +585,17 @@ impl PackageRegistry {
         self.auth_token = Some(token);
     }
 
+    /// Stay off the network: searches and downloads use the local cache, and
+    /// publishing and yanking fail
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Whether the registry only uses the local cache
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
     /// Initialize registry cache
     pub fn initialize(&mut self) -> TlispResult<()> {
         // Create cache directory
@@ -324,140 +673,81 @@ impl PackageRegistry {
         Ok(())
     }
 
-    /// Publish package to registry
-    pub fn publish_package(&mut self, request: PublishRequest) -> TlispResult<()> {
-        // Validate package metadata
-        self.validate_package_metadata(&request.metadata)?;
-
-        // Check package size
-        let content_size = request.content.len() as u64;
-        if content_size > self.config.max_package_size {
-            return Err(TlispError::Runtime(
-                format!("Package size {} exceeds maximum allowed size {}", 
-                    content_size, self.config.max_package_size)
-            ));
-        }
-
-        // Verify checksum
-        self.verify_checksum(&request.content, &request.checksum)?;
-
-        // Check if version already exists
-        if let Some(package_index) = self.index_cache.get(&request.metadata.name) {
-            if package_index.versions.contains_key(&request.metadata.version) && !request.options.allow_overwrite {
-                return Err(TlispError::Runtime(
-                    format!("Version {} of package {} already exists", 
-                        request.metadata.version, request.metadata.name)
-                ));
-            }
+    /// URL of a registry path; absolute URLs are used as they are
+    fn resolve(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.url.trim_end_matches('/'), path)
         }
-
-        // TODO: Implement HTTP client to upload package to registry
-        // For now, simulate successful upload
-        
-        // Update local index cache
-        self.update_index_cache(&request)?;
-
-        // Save updated cache
-        self.save_index_cache()?;
-
-        self.stats.total_packages += 1;
-        
-        Ok(())
     }
 
-    /// Validate package metadata
-    fn validate_package_metadata(&self, metadata: &PackageMetadata) -> TlispResult<()> {
-        if metadata.name.is_empty() {
-            return Err(TlispError::Runtime("Package name cannot be empty".to_string()));
-        }
-
-        if metadata.version.is_empty() {
-            return Err(TlispError::Runtime("Package version cannot be empty".to_string()));
+    /// Send a request to the registry, returning the response body
+    fn request(&mut self, method: reqwest::Method, path: &str, body: Option<Vec<u8>>) -> TlispResult<Vec<u8>> {
+        if self.offline {
+            return Err(TlispError::Runtime(format!("Registry '{}' cannot be reached offline", self.name)));
         }
-
-        // Check required fields
-        for field in &self.config.required_fields {
-            match field.as_str() {
-                "description" => {
-                    if metadata.description.is_none() {
-                        return Err(TlispError::Runtime("Description is required".to_string()));
-                    }
+        self.stats.api_calls += 1;
+
+        let url = self.resolve(path);
+        let target = url.clone();
+        let token = self.auth_token.clone();
+        // Callers may already be on a Tokio runtime, which cannot block on another
+        let sent = std::thread::spawn(move || -> Result<(reqwest::StatusCode, Vec<u8>), String> {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()
+                .map_err(|e| e.to_string())?;
+            runtime.block_on(async move {
+                let mut request = reqwest::Client::new().request(method, &target);
+                if let Some(token) = token {
+                    request = request.bearer_auth(token);
                 }
-                "license" => {
-                    if metadata.license.is_none() {
-                        return Err(TlispError::Runtime("License is required".to_string()));
-                    }
+                if let Some(body) = body {
+                    request = request.header("content-type", "application/json").body(body);
                 }
-                "author" => {
-                    if metadata.author.is_none() {
-                        return Err(TlispError::Runtime("Author is required".to_string()));
-                    }
-                }
-                _ => {}
-            }
+                let response = request.send().await.map_err(|e| e.to_string())?;
+                let status = response.status();
+                let body = response.bytes().await.map_err(|e| e.to_string())?;
+                Ok((status, body.to_vec()))
+            })
+        }).join().map_err(|_| TlispError::Runtime("Registry request panicked".to_string()))?;
+
+        let (status, body) = sent.map_err(|e| {
+            self.stats.failed_operations += 1;
+            TlispError::Runtime(format!("Registry request to {} failed: {}", url, e))
+        })?;
+        if !status.is_success() {
+            self.stats.failed_operations += 1;
+            return Err(TlispError::Runtime(format!(
+                "Registry '{}' refused the request ({}): {}", self.name, status, String::from_utf8_lossy(&body)
+            )));
         }
-
-        Ok(())
+        Ok(body)
     }
 
-    /// Verify package checksum
-    fn verify_checksum(&self, content: &str, expected_checksum: &str) -> TlispResult<()> {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        content.hash(&mut hasher);
-        let actual_checksum = format!("{:x}", hasher.finish());
-
-        if actual_checksum != expected_checksum {
-            return Err(TlispError::Runtime(
-                format!("Checksum mismatch: expected {}, got {}", expected_checksum, actual_checksum)
-            ));
-        }
-
-        Ok(())
+    /// Send a request with an optional JSON body, decoding a JSON response
+    fn request_json<T: DeserializeOwned>(&mut self, method: reqwest::Method, path: &str, body: Option<&impl Serialize>) -> TlispResult<T> {
+        let body = body.map(serde_json::to_vec).transpose()
+            .map_err(|e| TlispError::Runtime(format!("Failed to serialize request: {}", e)))?;
+        let response = self.request(method, path, body)?;
+        serde_json::from_slice(&response)
+            .map_err(|e| TlispError::Runtime(format!("Invalid registry response: {}", e)))
     }
 
-    /// Update index cache with new package
-    fn update_index_cache(&mut self, request: &PublishRequest) -> TlispResult<()> {
-        let package_name = &request.metadata.name;
-        let version = &request.metadata.version;
-
-        let package_version = PackageVersion {
-            version: version.clone(),
-            metadata: request.metadata.clone(),
-            download_url: format!("{}/packages/{}/{}", self.url, package_name, version),
-            checksum: request.checksum.clone(),
-            size: request.content.len() as u64,
-            published_at: SystemTime::now(),
-            yanked: false,
-            yank_reason: None,
-        };
-
-        if let Some(package_index) = self.index_cache.get_mut(package_name) {
-            package_index.versions.insert(version.clone(), package_version);
-            package_index.last_updated = SystemTime::now();
-        } else {
-            let mut versions = HashMap::new();
-            versions.insert(version.clone(), package_version);
+    /// Publish package to registry, returning the published version
+    pub fn publish_package(&mut self, request: PublishRequest) -> TlispResult<PackageVersion> {
+        // Check the package here too, so a bad one is reported before it is uploaded
+        verify_publish_request(&self.config, &request)?;
 
-            let package_index = PackageIndex {
-                name: package_name.clone(),
-                versions,
-                metadata: request.metadata.clone(),
-                last_updated: SystemTime::now(),
-                download_stats: DownloadStats {
-                    total: 0,
-                    recent: 0,
-                    by_version: HashMap::new(),
-                    by_date: HashMap::new(),
-                },
-            };
+        let path = format!("{}/packages", API_PREFIX);
+        let package_version: PackageVersion = self.request_json(reqwest::Method::PUT, &path, Some(&request))?;
 
-            self.index_cache.insert(package_name.clone(), package_index);
-        }
+        // Update local index cache
+        record_version(&mut self.index_cache, package_version.clone(), None);
+        self.save_index_cache()?;
 
-        Ok(())
+        self.stats.total_packages += 1;
+
+        Ok(package_version)
     }
 
     /// Get package metadata
@@ -476,6 +766,10 @@ impl PackageRegistry {
 
     /// Download package from registry
     pub fn download_package(&mut self, name: &str, version_req: &VersionRequirement) -> TlispResult<PathBuf> {
+        if !self.index_cache.contains_key(name) && !self.offline {
+            self.refresh_index()?;
+        }
+
         // Find matching version
         let package_index = self.index_cache.get(name)
             .ok_or_else(|| TlispError::Runtime(format!("Package '{}' not found", name)))?;
@@ -494,16 +788,17 @@ impl PackageRegistry {
 
         self.stats.cache_misses += 1;
 
-        // Create cache directory
+        // Download before creating the cache directory, so a failed download is not taken for a cached one
+        let content = self.fetch_package(&package_version)?;
         fs::create_dir_all(&cache_path)
             .map_err(|e| TlispError::Runtime(format!("Failed to create cache directory: {}", e)))?;
-
-        // Download package using the version information
-        let package_file = cache_path.join("package.tar.gz");
-        self.download_package_version(&package_version, &package_file)?;
+        fs::write(cache_path.join("package.reamb"), content)
+            .map_err(|e| TlispError::Runtime(format!("Failed to write package file: {}", e)))?;
 
         // Update download statistics
-        self.update_download_stats(name, &version);
+        if let Some(package_index) = self.index_cache.get_mut(name) {
+            package_index.download_stats.record(&version);
+        }
 
         self.stats.total_downloads += 1;
 
@@ -533,179 +828,35 @@ impl PackageRegistry {
         }
     }
 
-    /// Update download statistics
-    fn update_download_stats(&mut self, name: &str, version: &str) {
-        if let Some(package_index) = self.index_cache.get_mut(name) {
-            package_index.download_stats.total += 1;
-            package_index.download_stats.recent += 1;
-
-            *package_index.download_stats.by_version.entry(version.to_string()).or_insert(0) += 1;
-
-            let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
-            *package_index.download_stats.by_date.entry(today).or_insert(0) += 1;
-        }
-    }
-
-    /// Search packages in registry
+    /// Search packages in registry; offline, the cached index is searched
     pub fn search_packages(&mut self, query: SearchQuery) -> TlispResult<SearchResults> {
-        let start_time = std::time::Instant::now();
-        let mut results = Vec::new();
-
-        for (_, package_index) in &self.index_cache {
-            let score = self.calculate_search_score(package_index, &query);
-            if score > 0.0 {
-                let latest_version = self.get_latest_version(package_index);
-                results.push(PackageSearchResult {
-                    metadata: package_index.metadata.clone(),
-                    latest_version,
-                    downloads: package_index.download_stats.clone(),
-                    score,
-                });
-            }
+        if self.offline {
+            return Ok(search_index(&self.index_cache, query));
         }
-
-        // Sort results by score or specified sort order
-        match query.sort {
-            SearchSort::Relevance => results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap()),
-            SearchSort::Downloads => results.sort_by(|a, b| b.downloads.total.cmp(&a.downloads.total)),
-            SearchSort::Name => results.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name)),
-            SearchSort::Updated => {
-                // Would need to track update times for proper sorting
-                results.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
-            }
-            SearchSort::Created => {
-                // Would need to track creation times for proper sorting
-                results.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
-            }
-        }
-
-        // Apply limit and offset
-        let total = results.len() as u32;
-        let offset = query.offset.unwrap_or(0) as usize;
-        let limit = query.limit.unwrap_or(50) as usize;
-
-        if offset < results.len() {
-            results = results.into_iter().skip(offset).take(limit).collect();
-        } else {
-            results.clear();
-        }
-
-        let execution_time = start_time.elapsed();
-
-        Ok(SearchResults {
-            packages: results,
-            total,
-            query,
-            execution_time,
-        })
-    }
-
-    /// Calculate search relevance score
-    fn calculate_search_score(&self, package_index: &PackageIndex, query: &SearchQuery) -> f64 {
-        let mut score = 0.0;
-        let query_lower = query.query.to_lowercase();
-        let search_terms: Vec<&str> = query_lower.split_whitespace().collect();
-
-        // Score based on name match
-        let name_lower = package_index.metadata.name.to_lowercase();
-        for term in &search_terms {
-            if name_lower.contains(term) {
-                score += 2.0;
-            }
-        }
-
-        // Score based on description match
-        if let Some(description) = &package_index.metadata.description {
-            let desc_lower = description.to_lowercase();
-            for term in &search_terms {
-                if desc_lower.contains(term) {
-                    score += 1.0;
-                }
-            }
-        }
-
-        // Score based on keywords match
-        for keyword in &package_index.metadata.keywords {
-            let keyword_lower = keyword.to_lowercase();
-            for term in &search_terms {
-                if keyword_lower.contains(term) {
-                    score += 1.5;
-                }
-            }
-        }
-
-        // Apply filters
-        if let Some(category) = &query.category {
-            if !package_index.metadata.categories.contains(category) {
-                return 0.0;
-            }
-        }
-
-        if let Some(author) = &query.author {
-            if package_index.metadata.author.as_ref() != Some(author) {
-                return 0.0;
-            }
-        }
-
-        if let Some(license) = &query.license {
-            if package_index.metadata.license.as_ref() != Some(license) {
-                return 0.0;
-            }
-        }
-
-        if !query.keywords.is_empty() {
-            let has_matching_keyword = query.keywords.iter()
-                .any(|k| package_index.metadata.keywords.contains(k));
-            if !has_matching_keyword {
-                return 0.0;
-            }
-        }
-
-        score
-    }
-
-    /// Get latest version of package
-    fn get_latest_version(&self, package_index: &PackageIndex) -> String {
-        // Simple latest version selection - in a real implementation, this would use semver
-        package_index.versions.keys()
-            .filter(|v| !package_index.versions[*v].yanked)
-            .max()
-            .cloned()
-            .unwrap_or_else(|| "0.0.0".to_string())
+        let path = format!("{}/search", API_PREFIX);
+        self.request_json(reqwest::Method::POST, &path, Some(&query))
     }
 
     /// Yank a package version
     pub fn yank_version(&mut self, name: &str, version: &str, reason: Option<String>) -> TlispResult<()> {
-        let package_index = self.index_cache.get_mut(name)
-            .ok_or_else(|| TlispError::Runtime(format!("Package '{}' not found", name)))?;
-
-        let package_version = package_index.versions.get_mut(version)
-            .ok_or_else(|| TlispError::Runtime(format!("Version '{}' not found", version)))?;
-
-        package_version.yanked = true;
-        package_version.yank_reason = reason;
-
-        // Save updated cache
-        self.save_index_cache()?;
-
-        Ok(())
+        let path = format!("{}/packages/{}/{}/yank", API_PREFIX, name, version);
+        let _: PackageVersion = self.request_json(reqwest::Method::POST, &path, Some(&YankRequest { reason }))?;
+        self.refresh_package(name)
     }
 
     /// Unyank a package version
     pub fn unyank_version(&mut self, name: &str, version: &str) -> TlispResult<()> {
-        let package_index = self.index_cache.get_mut(name)
-            .ok_or_else(|| TlispError::Runtime(format!("Package '{}' not found", name)))?;
-
-        let package_version = package_index.versions.get_mut(version)
-            .ok_or_else(|| TlispError::Runtime(format!("Version '{}' not found", version)))?;
-
-        package_version.yanked = false;
-        package_version.yank_reason = None;
-
-        // Save updated cache
-        self.save_index_cache()?;
+        let path = format!("{}/packages/{}/{}/unyank", API_PREFIX, name, version);
+        let _: PackageVersion = self.request_json(reqwest::Method::POST, &path, Some(&YankRequest::default()))?;
+        self.refresh_package(name)
+    }
 
-        Ok(())
+    /// Replace the cached index entry of a package with the registry's
+    fn refresh_package(&mut self, name: &str) -> TlispResult<()> {
+        let path = format!("{}/packages/{}", API_PREFIX, name);
+        let package_index: PackageIndex = self.request_json(reqwest::Method::GET, &path, None::<&()>)?;
+        self.index_cache.insert(name.to_string(), package_index);
+        self.save_index_cache()
     }
 
     /// Get package information
@@ -740,28 +891,27 @@ impl PackageRegistry {
         Ok(())
     }
 
-    /// Refresh package index from registry
+    /// Refresh package index from registry; offline, it is reloaded from the cache
     pub fn refresh_index(&mut self) -> TlispResult<()> {
-        // TODO: Implement HTTP client to fetch updated index from registry
-        // For now, just reload from cache
-        self.load_index_cache()?;
-        Ok(())
+        if self.offline {
+            return self.load_index_cache();
+        }
+        let path = format!("{}/index", API_PREFIX);
+        self.index_cache = self.request_json(reqwest::Method::GET, &path, None::<&()>)?;
+        self.save_index_cache()
+    }
+
+    /// Download the content of a package version, checking its checksum and signature
+    pub fn fetch_package(&mut self, package_version: &PackageVersion) -> TlispResult<Vec<u8>> {
+        let content = self.request(reqwest::Method::GET, &package_version.download_url, None)?;
+        verify_package(&content, &package_version.checksum, package_version.signature.as_ref())?;
+        Ok(content)
     }
 
     /// Download a specific package version
     pub fn download_package_version(&mut self, package_version: &PackageVersion, target_file: &std::path::Path) -> TlispResult<()> {
-        // For now, create a placeholder implementation
-        // In a real implementation, this would use the package_version.download_url
-        // to fetch the actual package content via HTTP
-
-        let content = format!(
-            "# Package: {}\n# Version: {}\n# Description: {}\n# Placeholder package content",
-            package_version.metadata.name,
-            package_version.version,
-            package_version.metadata.description.as_deref().unwrap_or("No description")
-        );
-
-        std::fs::write(target_file, content.as_bytes())
+        let content = self.fetch_package(package_version)?;
+        std::fs::write(target_file, content)
             .map_err(|e| TlispError::Runtime(format!("Failed to write package file: {}", e)))?;
 
         Ok(())
@@ -789,8 +939,8 @@ impl RegistryConfig {
             ],
             features: RegistryFeatures {
                 private_packages: true,
-                package_signing: false,
-                mirroring: false,
+                package_signing: true,
+                mirroring: true,
                 pre_releases: true,
                 deprecation: true,
             },
@@ -456,9 +456,15 @@ impl Parser {
                 Ok(Expr::Symbol("lambda".to_string(), ()))
             }
 
+            // So can forall, which names the property-testing builtin
+            Token::Forall => {
+                self.advance();
+                Ok(Expr::Symbol("forall".to_string(), ()))
+            }
+
             // Other type syntax tokens - not valid in expressions
             Token::Colon | Token::Arrow | Token::LeftBrace | Token::RightBrace |
-            Token::Pipe | Token::Forall | Token::Refinement => {
+            Token::Pipe | Token::Refinement => {
                 Err(ParseError::UnexpectedToken {
                     position: self.current,
                     token: format!("{}", self.peek()),
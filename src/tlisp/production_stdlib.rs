@@ -482,6 +482,7 @@ fn builtin_type_of(args: &[TlispValue], _state: &GlobalState) -> TlispResult<Tli
         TlispValue::Function(_) => "function",
         TlispValue::Builtin(_) => "builtin",
         TlispValue::Pid(_) => "pid",
+        TlispValue::Binary(_) => "binary",
         TlispValue::Unit => "unit",
         TlispValue::Null => "null",
         TlispValue::StmVar(_) => "stm-var",
@@ -0,0 +1,358 @@
+//! Property-based testing
+//!
+//! `(forall gen... property)` calls `property` with values drawn from the
+//! generators, growing their size over the run. When a case fails it is
+//! shrunk to a smaller one that still fails, and the error reports the seed
+//! so the run can be replayed with `ream test --seed`.
+//!
+//! Generators are TLisp values of the form `(generator kind args...)`;
+//! records are lists of `(key value)` pairs.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::error::{TlispError, TlispResult};
+use crate::tlisp::Value;
+
+/// Global that fixes the seed of every `forall` in a runtime
+pub const SEED_VAR: &str = "*property-seed*";
+
+/// Environment variable read when `*property-seed*` is unset
+pub const SEED_ENV: &str = "REAM_PROPERTY_SEED";
+
+/// Cases tried by `forall` unless it is given a count
+pub const DEFAULT_TESTS: usize = 100;
+
+/// Upper bound on successful shrink steps
+const MAX_SHRINKS: usize = 1000;
+
+/// Largest size a generator is asked for
+const MAX_SIZE: usize = 100;
+
+/// Tag at the head of a generator value
+const GENERATOR_TAG: &str = "generator";
+
+/// Characters strings are drawn from; shrinking moves towards the first
+const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+
+/// Produces random values of one shape and smaller variants of them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    /// Integers in `min..=max`, shrinking towards zero
+    Int { min: i64, max: i64 },
+    Bool,
+    /// Alphanumeric strings of up to `max_len` characters
+    String { max_len: usize },
+    /// Lists of up to `max_len` elements
+    List { element: Box<Generator>, max_len: usize },
+    /// Lists of `(key value)` pairs, one per field
+    Record(Vec<(String, Generator)>),
+    /// Fixed-length lists with one generator per position; `forall` with
+    /// several generators draws from one of these
+    Tuple(Vec<Generator>),
+}
+
+impl Generator {
+    /// Draw a value; `size` grows from 1 to 100 over a run
+    pub fn generate(&self, rng: &mut StdRng, size: usize) -> Value {
+        match self {
+            Generator::Int { min, max } => {
+                let target = shrink_target(*min, *max) as i128;
+                let radius = ((*max as i128 - *min as i128) as u128 * size as u128).div_ceil(MAX_SIZE as u128) as i128;
+                let lo = (target - radius).max(*min as i128) as i64;
+                let hi = (target + radius).min(*max as i128) as i64;
+                Value::Int(rng.gen_range(lo..=hi))
+            }
+            Generator::Bool => Value::Bool(rng.gen()),
+            Generator::String { max_len } => {
+                let len = rng.gen_range(0..=scaled_len(*max_len, size));
+                let text = (0..len).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect();
+                Value::String(text)
+            }
+            Generator::List { element, max_len } => {
+                let len = rng.gen_range(0..=scaled_len(*max_len, size));
+                Value::List((0..len).map(|_| element.generate(rng, size)).collect())
+            }
+            Generator::Record(fields) => Value::List(fields.iter()
+                .map(|(key, field)| Value::List(vec![Value::String(key.clone()), field.generate(rng, size)]))
+                .collect()),
+            Generator::Tuple(items) => Value::List(items.iter().map(|item| item.generate(rng, size)).collect()),
+        }
+    }
+
+    /// Smaller variants of `value`, most aggressive first
+    pub fn shrink(&self, value: &Value) -> Vec<Value> {
+        match (self, value) {
+            (Generator::Int { min, max }, Value::Int(n)) => {
+                let target = shrink_target(*min, *max);
+                let mut candidates = Vec::new();
+                let mut distance = *n as i128 - target as i128;
+                while distance != 0 {
+                    candidates.push(Value::Int((*n as i128 - distance) as i64));
+                    distance /= 2;
+                }
+                candidates
+            }
+            (Generator::Bool, Value::Bool(true)) => vec![Value::Bool(false)],
+            (Generator::String { .. }, Value::String(text)) => {
+                let chars: Vec<char> = text.chars().collect();
+                let mut candidates: Vec<Value> = shrink_sequence(&chars, |c| {
+                    let first = ALPHABET[0] as char;
+                    if *c == first { Vec::new() } else { vec![first] }
+                }).into_iter().map(|chars| Value::String(chars.into_iter().collect())).collect();
+                candidates.dedup();
+                candidates
+            }
+            (Generator::List { element, .. }, Value::List(items)) => {
+                shrink_sequence(items, |item| element.shrink(item)).into_iter().map(Value::List).collect()
+            }
+            (Generator::Record(fields), Value::List(pairs)) if fields.len() == pairs.len() => {
+                let values: Vec<Value> = pairs.iter().map(|pair| match pair {
+                    Value::List(pair) if pair.len() == 2 => pair[1].clone(),
+                    other => other.clone(),
+                }).collect();
+                let generators: Vec<Generator> = fields.iter().map(|(_, field)| field.clone()).collect();
+                shrink_each(&generators, &values).into_iter().map(|values| Value::List(fields.iter().zip(values)
+                    .map(|((key, _), value)| Value::List(vec![Value::String(key.clone()), value]))
+                    .collect())).collect()
+            }
+            (Generator::Tuple(generators), Value::List(items)) if generators.len() == items.len() => {
+                shrink_each(generators, items).into_iter().map(Value::List).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Encode as a `(generator kind args...)` value
+    pub fn to_value(&self) -> Value {
+        let tagged = |kind: &str, mut args: Vec<Value>| {
+            args.insert(0, Value::Symbol(kind.to_string()));
+            args.insert(0, Value::Symbol(GENERATOR_TAG.to_string()));
+            Value::List(args)
+        };
+        match self {
+            Generator::Int { min, max } => tagged("int", vec![Value::Int(*min), Value::Int(*max)]),
+            Generator::Bool => tagged("bool", Vec::new()),
+            Generator::String { max_len } => tagged("string", vec![Value::Int(*max_len as i64)]),
+            Generator::List { element, max_len } => tagged("list", vec![element.to_value(), Value::Int(*max_len as i64)]),
+            Generator::Record(fields) => tagged("record", fields.iter()
+                .map(|(key, field)| Value::List(vec![Value::String(key.clone()), field.to_value()]))
+                .collect()),
+            Generator::Tuple(items) => tagged("tuple", items.iter().map(Generator::to_value).collect()),
+        }
+    }
+
+    /// Decode a value made by `to_value`
+    pub fn from_value(value: &Value) -> TlispResult<Self> {
+        let invalid = || TlispError::Runtime(format!("Not a generator: {}", value));
+        let Value::List(items) = value else { return Err(invalid()) };
+        let (Some(Value::Symbol(tag)), Some(Value::Symbol(kind))) = (items.first(), items.get(1)) else {
+            return Err(invalid());
+        };
+        if tag != GENERATOR_TAG {
+            return Err(invalid());
+        }
+        let args = &items[2..];
+        let int = |index: usize| match args.get(index) {
+            Some(Value::Int(n)) => Ok(*n),
+            _ => Err(invalid()),
+        };
+        let len = |index: usize| int(index).map(|n| n.max(0) as usize);
+
+        match kind.as_str() {
+            "int" => Ok(Generator::Int { min: int(0)?, max: int(1)? }),
+            "bool" => Ok(Generator::Bool),
+            "string" => Ok(Generator::String { max_len: len(0)? }),
+            "list" => Ok(Generator::List {
+                element: Box::new(Generator::from_value(args.first().ok_or_else(invalid)?)?),
+                max_len: len(1)?,
+            }),
+            "record" => args.iter().map(|field| match field {
+                Value::List(pair) if pair.len() == 2 => match &pair[0] {
+                    Value::String(key) | Value::Symbol(key) => Ok((key.clone(), Generator::from_value(&pair[1])?)),
+                    _ => Err(invalid()),
+                },
+                _ => Err(invalid()),
+            }).collect::<TlispResult<_>>().map(Generator::Record),
+            "tuple" => args.iter().map(Generator::from_value).collect::<TlispResult<_>>().map(Generator::Tuple),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The value in `min..=max` closest to zero
+fn shrink_target(min: i64, max: i64) -> i64 {
+    0.clamp(min, max)
+}
+
+/// Largest length allowed at `size`
+fn scaled_len(max_len: usize, size: usize) -> usize {
+    (max_len * size).div_ceil(MAX_SIZE).min(max_len)
+}
+
+/// Shorter sequences first (empty, halves, one element removed), then ones
+/// with a single element shrunk
+fn shrink_sequence<T: Clone>(items: &[T], shrink_item: impl Fn(&T) -> Vec<T>) -> Vec<Vec<T>> {
+    let mut candidates = Vec::new();
+    if items.is_empty() {
+        return candidates;
+    }
+    candidates.push(Vec::new());
+    if items.len() > 2 {
+        let half = items.len() / 2;
+        candidates.push(items[half..].to_vec());
+        candidates.push(items[..half].to_vec());
+    }
+    if items.len() > 1 {
+        for index in 0..items.len() {
+            let mut shorter = items.to_vec();
+            shorter.remove(index);
+            candidates.push(shorter);
+        }
+    }
+    for (index, item) in items.iter().enumerate() {
+        for smaller in shrink_item(item) {
+            let mut shrunk = items.to_vec();
+            shrunk[index] = smaller;
+            candidates.push(shrunk);
+        }
+    }
+    candidates
+}
+
+/// Variants of `values` with one position shrunk by its generator
+fn shrink_each(generators: &[Generator], values: &[Value]) -> Vec<Vec<Value>> {
+    let mut candidates = Vec::new();
+    for (index, (generator, value)) in generators.iter().zip(values).enumerate() {
+        for smaller in generator.shrink(value) {
+            let mut shrunk = values.to_vec();
+            shrunk[index] = smaller;
+            candidates.push(shrunk);
+        }
+    }
+    candidates
+}
+
+/// A failing case, after shrinking
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    pub seed: u64,
+    /// Cases tried up to and including the failing one
+    pub tests: usize,
+    pub original: Value,
+    pub shrunk: Value,
+    /// Successful shrink steps
+    pub shrinks: usize,
+    /// Why the shrunk case failed
+    pub message: String,
+}
+
+impl Counterexample {
+    /// Error reported by `forall`
+    pub fn to_error(&self) -> TlispError {
+        TlispError::Runtime(format!(
+            "property failed after {} tests (seed {}, shrunk {} times)\n  counterexample: {}\n  original: {}\n  {}",
+            self.tests, self.seed, self.shrinks, self.shrunk, self.original, self.message
+        ))
+    }
+}
+
+/// Run `property` against `tests` values from `generator`. The property
+/// fails a case by returning `Err` with a reason.
+pub fn check(
+    generator: &Generator,
+    tests: usize,
+    seed: u64,
+    mut property: impl FnMut(&Value) -> Result<(), String>,
+) -> Result<usize, Box<Counterexample>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for test in 0..tests {
+        let size = (1 + test * MAX_SIZE / tests.max(1)).min(MAX_SIZE);
+        let value = generator.generate(&mut rng, size);
+        let Err(message) = property(&value) else { continue };
+
+        let (shrunk, shrinks, message) = shrink(generator, value.clone(), message, &mut property);
+        return Err(Box::new(Counterexample { seed, tests: test + 1, original: value, shrunk, shrinks, message }));
+    }
+    Ok(tests)
+}
+
+/// Greedily replace the failing value with its first smaller variant that
+/// still fails until none does
+fn shrink(
+    generator: &Generator,
+    mut value: Value,
+    mut message: String,
+    property: &mut impl FnMut(&Value) -> Result<(), String>,
+) -> (Value, usize, String) {
+    let mut shrinks = 0;
+    'outer: while shrinks < MAX_SHRINKS {
+        for candidate in generator.shrink(&value) {
+            if let Err(reason) = property(&candidate) {
+                value = candidate;
+                message = reason;
+                shrinks += 1;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    (value, shrinks, message)
+}
+
+/// Seed from `$REAM_PROPERTY_SEED`, or a random one
+pub fn default_seed() -> u64 {
+    std::env::var(SEED_ENV).ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| rand::thread_rng().gen())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generators_round_trip_and_respect_bounds() {
+        let generator = Generator::Record(vec![
+            ("age".to_string(), Generator::Int { min: 18, max: 99 }),
+            ("tags".to_string(), Generator::List { element: Box::new(Generator::String { max_len: 4 }), max_len: 3 }),
+        ]);
+        assert_eq!(Generator::from_value(&generator.to_value()).unwrap(), generator);
+        assert!(Generator::from_value(&Value::Int(1)).is_err());
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for size in [1, 50, 100] {
+            let Value::List(fields) = generator.generate(&mut rng, size) else { panic!("expected record") };
+            let Value::List(age) = &fields[0] else { panic!("expected pair") };
+            assert!(matches!(age[1], Value::Int(n) if (18..=99).contains(&n)));
+            let Value::List(tags) = &fields[1] else { panic!("expected pair") };
+            assert!(matches!(&tags[1], Value::List(items) if items.len() <= 3));
+        }
+    }
+
+    #[test]
+    fn test_check_shrinks_to_minimal_counterexample() {
+        let generator = Generator::Int { min: -1000, max: 1000 };
+        let failure = check(&generator, 200, 42, |value| match value {
+            Value::Int(n) if *n >= 17 => Err(format!("{} is too big", n)),
+            _ => Ok(()),
+        }).unwrap_err();
+        assert_eq!(failure.shrunk, Value::Int(17));
+        assert_eq!(failure.seed, 42);
+        assert_eq!(failure.message, "17 is too big");
+
+        // The same seed finds the same case
+        let again = check(&generator, 200, 42, |value| match value {
+            Value::Int(n) if *n >= 17 => Err(String::new()),
+            _ => Ok(()),
+        }).unwrap_err();
+        assert_eq!(again.original, failure.original);
+
+        let lists = Generator::List { element: Box::new(Generator::Int { min: 0, max: 100 }), max_len: 20 };
+        let failure = check(&lists, 100, 1, |value| match value {
+            Value::List(items) if items.iter().any(|item| matches!(item, Value::Int(n) if *n > 50)) => Err(String::new()),
+            _ => Ok(()),
+        }).unwrap_err();
+        assert_eq!(failure.shrunk, Value::List(vec![Value::Int(51)]));
+        assert_eq!(check(&lists, 50, 1, |_| Ok(())), Ok(50));
+    }
+}
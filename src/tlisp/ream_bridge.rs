@@ -55,6 +55,7 @@ impl TlispReamBridge {
                 Ok(MessagePayload::Data(serde_json::Value::Array(json_items?)))
             }
             Value::Null => Ok(MessagePayload::Data(serde_json::Value::Null)),
+            Value::Binary(binary) => Ok(MessagePayload::SharedBinary(binary)),
             _ => Err(TlispError::Runtime("Cannot convert value to message".to_string())),
         }
     }
@@ -91,7 +92,9 @@ impl TlispReamBridge {
                     .collect();
                 Ok(Value::List(int_list))
             }
+            MessagePayload::SharedBinary(binary) => Ok(Value::Binary(binary)),
             MessagePayload::Control(_) => Ok(Value::Symbol("control-message".to_string())),
+            MessagePayload::Traced { payload, .. } => self.message_payload_to_value(*payload),
         }
     }
     
@@ -195,7 +198,9 @@ impl TlispActor {
                 }
             }
             MessagePayload::Bytes(_) => Ok(Value::String("binary".to_string())),
+            MessagePayload::SharedBinary(binary) => Ok(Value::Binary(binary)),
             MessagePayload::Control(_) => Ok(Value::String("control".to_string())),
+            MessagePayload::Traced { payload, .. } => self.message_payload_to_value(*payload),
         }
     }
 }
@@ -376,6 +381,7 @@ impl TlispReamMessage {
                     _ => Err(TlispError::Runtime(format!("Unknown message type: {}", msg_type))),
                 }
             }
+            MessagePayload::Traced { payload, .. } => Self::from_payload(*payload),
             _ => Err(TlispError::Runtime("Expected JSON data message".to_string())),
         }
     }
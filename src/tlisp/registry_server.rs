@@ -0,0 +1,433 @@
+//! Package registry server
+//!
+//! `ream registry serve` runs a registry over HTTP, for `PackageRegistry`
+//! clients to publish to, yank from, search and download. Everything lives
+//! in one directory:
+//!
+//! - `index.json`: every package with its versions and owners
+//! - `tokens.json`: publishers, by the SHA-256 digest of their API tokens
+//! - `packages/<name>/<version>.pkg`: package contents
+//!
+//! Reading is open to anyone. Publishing and yanking need a token, sent as
+//! `Authorization: Bearer <token>`, and the first user to publish a package
+//! becomes its owner; only owners publish its later versions or yank them.
+//! A directory written by `RegistryStore::mirror` has the same layout, so a
+//! mirror of another registry can be served as is.
+//!
+//! The API, under `/api/v1`, takes and returns JSON except for package content:
+//!
+//! - `GET /index`: every package
+//! - `GET /packages/<name>`: a package and its versions
+//! - `GET /packages/<name>/<version>/download`: the package content
+//! - `POST /search` with a `SearchQuery`: the matching packages
+//! - `PUT /packages` with a `PublishRequest`: the published version
+//! - `POST /packages/<name>/<version>/yank` with a `YankRequest`, and
+//!   `POST /packages/<name>/<version>/unyank`: the changed version
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use ring::digest::{digest, SHA256};
+use serde::Serialize;
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::error::{ReamError, ReamResult, TlispError, TlispResult};
+use crate::tlisp::package_registry::{
+    record_version, search_index, set_yanked, validate_package_id, verify_package, verify_publish_request,
+    PackageIndex, PackageRegistry, PackageVersion, PublishRequest, RegistryConfig, SearchQuery, SearchResults,
+    YankRequest, API_PREFIX,
+};
+
+/// Extension of stored package contents
+const PACKAGE_EXTENSION: &str = "pkg";
+
+/// A registry's packages, contents and publishers, kept in a directory
+pub struct RegistryStore {
+    dir: PathBuf,
+    config: RegistryConfig,
+    index: HashMap<String, PackageIndex>,
+    /// Users by the digest of their tokens
+    tokens: HashMap<String, String>,
+}
+
+/// Digest a token is stored and looked up by
+fn token_digest(token: &str) -> String {
+    hex::encode(digest(&SHA256, token.as_bytes()))
+}
+
+fn read_json<T: serde::de::DeserializeOwned + Default>(path: &Path) -> TlispResult<T> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| TlispError::Runtime(format!("Failed to parse {}: {}", path.display(), e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(TlispError::Runtime(format!("Failed to read {}: {}", path.display(), e))),
+    }
+}
+
+/// Write a file through a temporary one, so readers never see it half written
+fn write_atomically(path: &Path, bytes: &[u8]) -> TlispResult<()> {
+    let failed = |e: std::io::Error| TlispError::Runtime(format!("Failed to write {}: {}", path.display(), e));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(failed)?;
+    }
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, bytes).map_err(failed)?;
+    std::fs::rename(&temporary, path).map_err(failed)
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> TlispResult<()> {
+    let bytes = serde_json::to_vec_pretty(value)
+        .map_err(|e| TlispError::Runtime(format!("Failed to serialize {}: {}", path.display(), e)))?;
+    write_atomically(path, &bytes)
+}
+
+impl RegistryStore {
+    /// Open the registry kept in `dir`, creating it if needed
+    pub fn open(dir: &Path) -> TlispResult<Self> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| TlispError::Runtime(format!("Failed to create {}: {}", dir.display(), e)))?;
+        Ok(RegistryStore {
+            dir: dir.to_path_buf(),
+            config: RegistryConfig::default(),
+            index: read_json(&dir.join("index.json"))?,
+            tokens: read_json(&dir.join("tokens.json"))?,
+        })
+    }
+
+    /// Check publishing requests against `config` instead of the default
+    pub fn with_config(mut self, config: RegistryConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// File the content of a package version is kept in
+    fn package_file(&self, name: &str, version: &str) -> PathBuf {
+        self.dir.join("packages").join(name).join(format!("{}.{}", version, PACKAGE_EXTENSION))
+    }
+
+    fn save_index(&self) -> TlispResult<()> {
+        write_json(&self.dir.join("index.json"), &self.index)
+    }
+
+    /// Issue a new token for `user`, returning it; only its digest is kept
+    pub fn add_token(&mut self, user: &str) -> TlispResult<String> {
+        if user.is_empty() {
+            return Err(TlispError::Runtime("User name cannot be empty".to_string()));
+        }
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        self.tokens.insert(token_digest(&token), user.to_string());
+        write_json(&self.dir.join("tokens.json"), &self.tokens)?;
+        Ok(token)
+    }
+
+    /// User a token was issued to
+    pub fn authenticate(&self, token: &str) -> Option<&str> {
+        self.tokens.get(&token_digest(token)).map(String::as_str)
+    }
+
+    /// Whether `user` may publish and yank versions of a package; anyone may
+    /// claim a new package
+    pub fn is_owner(&self, user: &str, name: &str) -> bool {
+        self.index.get(name).is_none_or(|package| package.owners.iter().any(|owner| owner == user))
+    }
+
+    /// Every package
+    pub fn index(&self) -> &HashMap<String, PackageIndex> {
+        &self.index
+    }
+
+    /// A package and its versions
+    pub fn package(&self, name: &str) -> Option<&PackageIndex> {
+        self.index.get(name)
+    }
+
+    /// Publish a package version on behalf of `user`
+    pub fn publish(&mut self, user: &str, request: PublishRequest) -> TlispResult<PackageVersion> {
+        let content = verify_publish_request(&self.config, &request)?;
+        let metadata = request.metadata;
+        if !self.is_owner(user, &metadata.name) {
+            return Err(TlispError::SecurityError(format!("{} is not an owner of {}", user, metadata.name)));
+        }
+        let exists = self.package(&metadata.name).is_some_and(|package| package.versions.contains_key(&metadata.version));
+        if exists && !request.options.allow_overwrite {
+            return Err(TlispError::Runtime(
+                format!("Version {} of package {} already exists", metadata.version, metadata.name)
+            ));
+        }
+
+        write_atomically(&self.package_file(&metadata.name, &metadata.version), &content)?;
+        let package_version = PackageVersion {
+            version: metadata.version.clone(),
+            download_url: format!("{}/packages/{}/{}/download", API_PREFIX, metadata.name, metadata.version),
+            checksum: request.checksum,
+            size: content.len() as u64,
+            published_at: SystemTime::now(),
+            yanked: false,
+            yank_reason: None,
+            signature: request.signature,
+            metadata,
+        };
+        record_version(&mut self.index, package_version.clone(), Some(user));
+        self.save_index()?;
+        Ok(package_version)
+    }
+
+    /// Yank a version, or with `yanked` false restore it, on behalf of `user`
+    pub fn yank(&mut self, user: &str, name: &str, version: &str, yanked: bool, reason: Option<String>) -> TlispResult<PackageVersion> {
+        if !self.is_owner(user, name) {
+            return Err(TlispError::SecurityError(format!("{} is not an owner of {}", user, name)));
+        }
+        set_yanked(&mut self.index, name, version, yanked, reason)?;
+        self.save_index()?;
+        Ok(self.index[name].versions[version].clone())
+    }
+
+    /// The content of a package version, counted as a download
+    pub fn download(&mut self, name: &str, version: &str) -> TlispResult<Vec<u8>> {
+        validate_package_id(name, version)?;
+        let path = self.package_file(name, version);
+        let package = self.index.get_mut(name)
+            .filter(|package| package.versions.contains_key(version))
+            .ok_or_else(|| TlispError::Runtime(format!("Package {} {} not found", name, version)))?;
+        package.download_stats.record(version);
+        std::fs::read(&path).map_err(|e| TlispError::Runtime(format!("Failed to read {}: {}", path.display(), e)))
+    }
+
+    /// Search the packages
+    pub fn search(&self, query: SearchQuery) -> SearchResults {
+        search_index(&self.index, query)
+    }
+
+    /// Copy every package version of `registry` into `dir`, as a registry
+    /// that can be served offline; returns the number of versions copied.
+    /// Versions already mirrored are not downloaded again.
+    pub fn mirror(registry: &mut PackageRegistry, dir: &Path) -> TlispResult<usize> {
+        registry.refresh_index()?;
+        let mut store = RegistryStore::open(dir)?;
+        let packages: Vec<PackageIndex> = registry.list_packages().into_iter().cloned().collect();
+
+        let mut copied = 0;
+        for package in &packages {
+            for package_version in package.versions.values() {
+                validate_package_id(&package.name, &package_version.version)?;
+                let path = store.package_file(&package.name, &package_version.version);
+                let mirrored = std::fs::read(&path).ok().is_some_and(|content| {
+                    verify_package(&content, &package_version.checksum, package_version.signature.as_ref()).is_ok()
+                });
+                if !mirrored {
+                    let content = registry.fetch_package(package_version)?;
+                    write_atomically(&path, &content)?;
+                    copied += 1;
+                }
+            }
+            let mut package = package.clone();
+            for package_version in package.versions.values_mut() {
+                package_version.download_url = format!(
+                    "{}/packages/{}/{}/download", API_PREFIX, package.name, package_version.version
+                );
+            }
+            store.index.insert(package.name.clone(), package);
+        }
+        store.save_index()?;
+        Ok(copied)
+    }
+}
+
+type Shared = Arc<Mutex<RegistryStore>>;
+
+fn json_reply<T: Serialize>(result: Result<T, (StatusCode, String)>) -> Response {
+    match result {
+        Ok(value) => warp::reply::json(&value).into_response(),
+        Err((status, message)) => warp::reply::with_status(message, status).into_response(),
+    }
+}
+
+/// Status a failed store operation is reported with
+fn rejected(e: TlispError) -> (StatusCode, String) {
+    let status = match &e {
+        TlispError::SecurityError(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    (status, e.to_string())
+}
+
+/// User the `Authorization` header authenticates
+fn authorize(store: &RegistryStore, header: Option<String>) -> Result<String, (StatusCode, String)> {
+    header.as_deref()
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .and_then(|token| store.authenticate(token.trim()))
+        .map(str::to_string)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "A valid registry token is required".to_string()))
+}
+
+/// Bind the registry API to `addr`, returning the bound address and the
+/// server future
+pub fn bind(store: Shared, addr: SocketAddr) -> ReamResult<(SocketAddr, impl Future<Output = ()>)> {
+    let max_body = store.lock().unwrap().config.max_package_size * 2 + 64 * 1024;
+    let with_store = move || {
+        let store = store.clone();
+        warp::any().map(move || store.clone())
+    };
+    let api = || warp::path("api").and(warp::path("v1"));
+
+    let index = api().and(warp::path!("index")).and(warp::get()).and(with_store())
+        .map(|store: Shared| json_reply::<HashMap<String, PackageIndex>>(Ok(store.lock().unwrap().index().clone())));
+
+    let package = api().and(warp::path!("packages" / String)).and(warp::get()).and(with_store())
+        .map(|name: String, store: Shared| {
+            let package = store.lock().unwrap().package(&name).cloned();
+            json_reply(package.ok_or((StatusCode::NOT_FOUND, format!("Package {} not found", name))))
+        });
+
+    let download = api().and(warp::path!("packages" / String / String / "download")).and(warp::get()).and(with_store())
+        .map(|name: String, version: String, store: Shared| {
+            match store.lock().unwrap().download(&name, &version) {
+                Ok(content) => warp::reply::with_header(content, "content-type", "application/octet-stream").into_response(),
+                Err(e) => warp::reply::with_status(e.to_string(), StatusCode::NOT_FOUND).into_response(),
+            }
+        });
+
+    let search = api().and(warp::path!("search")).and(warp::post())
+        .and(warp::body::content_length_limit(64 * 1024)).and(warp::body::json()).and(with_store())
+        .map(|query: SearchQuery, store: Shared| json_reply(Ok(store.lock().unwrap().search(query))));
+
+    let publish = api().and(warp::path!("packages")).and(warp::put())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(max_body)).and(warp::body::json()).and(with_store())
+        .map(|auth: Option<String>, request: PublishRequest, store: Shared| {
+            let mut store = store.lock().unwrap();
+            json_reply(authorize(&store, auth).and_then(|user| {
+                let published = store.publish(&user, request).map_err(rejected)?;
+                tracing::info!(package = %published.metadata.name, version = %published.version, user = %user, "Package published");
+                Ok(published)
+            }))
+        });
+
+    let yank = api().and(warp::path!("packages" / String / String / String)).and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(64 * 1024)).and(warp::body::json()).and(with_store())
+        .map(|name: String, version: String, action: String, auth: Option<String>, request: YankRequest, store: Shared| {
+            let yanked = match action.as_str() {
+                "yank" => true,
+                "unyank" => false,
+                _ => return warp::reply::with_status(String::new(), StatusCode::NOT_FOUND).into_response(),
+            };
+            let mut store = store.lock().unwrap();
+            json_reply(authorize(&store, auth).and_then(|user| {
+                let changed = store.yank(&user, &name, &version, yanked, request.reason).map_err(rejected)?;
+                tracing::info!(package = %name, version = %version, yanked, user = %user, "Package version yanked");
+                Ok(changed)
+            }))
+        });
+
+    let routes = index.or(package).or(download).or(search).or(publish).or(yank)
+        .recover(|rejection: warp::Rejection| async move {
+            let status = if rejection.is_not_found() { StatusCode::NOT_FOUND } else { StatusCode::BAD_REQUEST };
+            Ok::<_, Infallible>(warp::reply::with_status(format!("{:?}", rejection), status))
+        });
+
+    warp::serve(routes)
+        .try_bind_ephemeral(addr)
+        .map_err(|e| ReamError::Other(format!("Failed to bind registry {}: {}", addr, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::signing::SigningKey;
+    use crate::tlisp::package_manager::{PackageMetadata, VersionRequirement};
+    use crate::tlisp::package_registry::{PublishOptions, SearchQuery};
+
+    fn metadata(name: &str, version: &str) -> PackageMetadata {
+        let mut metadata = PackageMetadata::new(name.to_string(), version.to_string());
+        metadata.description = Some(format!("The {} package", name));
+        metadata.license = Some("MIT".to_string());
+        metadata
+    }
+
+    #[test]
+    fn test_store_enforces_ownership_and_yanks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = RegistryStore::open(dir.path()).unwrap();
+        let alice = store.add_token("alice").unwrap();
+        assert_eq!(store.authenticate(&alice), Some("alice"));
+        assert_eq!(store.authenticate("forged"), None);
+
+        let request = PublishRequest::new(metadata("json-utils", "1.0.0"), b"(define x 1)", PublishOptions::default(), None);
+        store.publish("alice", request.clone()).unwrap();
+        assert!(store.publish("alice", request).is_err(), "versions are immutable");
+        let other = PublishRequest::new(metadata("json-utils", "1.1.0"), b"(define x 2)", PublishOptions::default(), None);
+        assert!(matches!(store.publish("mallory", other.clone()), Err(TlispError::SecurityError(_))));
+        store.publish("alice", other).unwrap();
+
+        let mut tampered = PublishRequest::new(metadata("tampered", "1.0.0"), b"a", PublishOptions::default(), None);
+        tampered.content = hex::encode(b"b");
+        assert!(store.publish("alice", tampered).is_err());
+        let bad_name = PublishRequest::new(metadata("../escape", "1.0.0"), b"a", PublishOptions::default(), None);
+        assert!(store.publish("alice", bad_name).is_err());
+
+        assert!(store.yank("mallory", "json-utils", "1.1.0", true, None).is_err());
+        store.yank("alice", "json-utils", "1.1.0", true, Some("broken".to_string())).unwrap();
+        let results = store.search(SearchQuery::new("json"));
+        assert_eq!(results.packages[0].latest_version, "1.0.0");
+
+        // Everything survives reopening
+        let mut reopened = RegistryStore::open(dir.path()).unwrap();
+        assert_eq!(reopened.authenticate(&alice), Some("alice"));
+        assert!(reopened.package("json-utils").unwrap().versions["1.1.0"].yanked);
+        assert_eq!(reopened.download("json-utils", "1.0.0").unwrap(), b"(define x 1)");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_client_publishes_searches_and_mirrors() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = RegistryStore::open(&dir.path().join("server")).unwrap();
+        let token = store.add_token("alice").unwrap();
+        let (addr, server) = bind(Arc::new(Mutex::new(store)), "127.0.0.1:0".parse().unwrap()).unwrap();
+        tokio::spawn(server);
+
+        let root = dir.path().to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let url = format!("http://{}", addr);
+            let mut client = PackageRegistry::new("test".to_string(), url.clone(), root.join("cache"));
+            client.initialize().unwrap();
+            let request = PublishRequest::new(metadata("json-utils", "1.0.0"), b"(define x 1)", PublishOptions::default(), None);
+            assert!(client.publish_package(request.clone()).is_err(), "publishing needs a token");
+
+            client.set_auth_token(token);
+            let key = SigningKey::generate();
+            let signed = PublishRequest::new(metadata("json-utils", "1.0.0"), b"(define x 1)", PublishOptions::default(), Some(&key));
+            let published = client.publish_package(signed).unwrap();
+            assert!(published.signature.is_some());
+            let next = PublishRequest::new(metadata("json-utils", "1.1.0"), b"(define x 2)", PublishOptions::default(), None);
+            client.publish_package(next).unwrap();
+
+            let results = client.search_packages(SearchQuery::new("json")).unwrap();
+            assert_eq!((results.total, results.packages[0].latest_version.as_str()), (1, "1.1.0"));
+            client.yank_version("json-utils", "1.1.0", Some("broken".to_string())).unwrap();
+            assert!(client.get_package_info("json-utils").unwrap().versions["1.1.0"].yanked);
+
+            let cached = client.download_package("json-utils", &VersionRequirement::new("*".to_string())).unwrap();
+            assert_eq!(std::fs::read(cached.join("package.reamb")).unwrap(), b"(define x 1)");
+
+            // Offline, searches and cached downloads still work but nothing else
+            client.set_offline(true);
+            let results = client.search_packages(SearchQuery::new("json")).unwrap();
+            assert_eq!(results.packages[0].latest_version, "1.0.0");
+            assert!(client.download_package("json-utils", &VersionRequirement::new("1.0.0".to_string())).is_ok());
+            assert!(client.yank_version("json-utils", "1.0.0", None).is_err());
+
+            client.set_offline(false);
+            assert_eq!(RegistryStore::mirror(&mut client, &root.join("mirror")).unwrap(), 2);
+            assert_eq!(RegistryStore::mirror(&mut client, &root.join("mirror")).unwrap(), 0);
+            let mut mirror = RegistryStore::open(&root.join("mirror")).unwrap();
+            assert_eq!(mirror.download("json-utils", "1.1.0").unwrap(), b"(define x 2)");
+        }).await.unwrap();
+    }
+}
@@ -1,8 +1,9 @@
 //! TLISP runtime integration
 
 use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::tlisp::{TlispInterpreter, Value};
+use crate::tlisp::{RustFunction, TlispInterpreter, Value};
 
 use crate::runtime::ReamRuntime;
 use crate::error::{TlispError, TlispResult};
@@ -120,6 +121,21 @@ impl TlispRuntime {
     pub fn get(&self, name: &str) -> Option<Value> {
         self.interpreter.get(name)
     }
+
+    /// Names bound in the global environment, sorted
+    pub fn symbols(&self) -> Vec<String> {
+        self.interpreter.symbols()
+    }
+
+    /// Call a function value with already evaluated arguments
+    pub fn call(&mut self, func: &Value, args: &[Value]) -> TlispResult<Value> {
+        self.interpreter.call(func, args)
+    }
+
+    /// Bind `name` to a function implemented by a native extension
+    pub fn register_native(&mut self, name: String, function: Arc<dyn RustFunction>) {
+        self.interpreter.register_native(name, function);
+    }
     
     /// Start a REPL (Read-Eval-Print Loop)
     pub fn repl(&mut self) -> TlispResult<()> {
@@ -276,6 +292,71 @@ impl TlispRuntime {
         self.define("ream-graphql:compile-query", Value::Builtin("ream-graphql:compile-query".to_string()));
         self.define("ream-graphql:compile-mutation", Value::Builtin("ream-graphql:compile-mutation".to_string()));
 
+        // Replicated key-value store
+        self.define("kv-put", Value::Builtin("kv-put".to_string()));
+        self.define("kv-get", Value::Builtin("kv-get".to_string()));
+        self.define("kv-cas", Value::Builtin("kv-cas".to_string()));
+        self.define("kv-watch", Value::Builtin("kv-watch".to_string()));
+
+        // Distributed locks and leader election
+        self.define("lock-acquire", Value::Builtin("lock-acquire".to_string()));
+        self.define("lock-release", Value::Builtin("lock-release".to_string()));
+        self.define("elect-leader", Value::Builtin("elect-leader".to_string()));
+
+        // Conflict-free replicated data types
+        self.define("crdt-new", Value::Builtin("crdt-new".to_string()));
+        self.define("crdt-merge", Value::Builtin("crdt-merge".to_string()));
+        self.define("crdt-value", Value::Builtin("crdt-value".to_string()));
+
+        // WebAssembly actors
+        self.define("spawn-wasm", Value::Builtin("spawn-wasm".to_string()));
+
+        // Shared binaries
+        self.define("string->bytes", Value::Builtin("string->bytes".to_string()));
+        self.define("bytes->string", Value::Builtin("bytes->string".to_string()));
+        self.define("bytes-length", Value::Builtin("bytes-length".to_string()));
+        self.define("bytes-slice", Value::Builtin("bytes-slice".to_string()));
+        self.define("bytes?", Value::Builtin("bytes?".to_string()));
+
+        // Files and environment
+        self.define("read-file", Value::Builtin("read-file".to_string()));
+        self.define("write-file", Value::Builtin("write-file".to_string()));
+        self.define("env-get", Value::Builtin("env-get".to_string()));
+        self.define("secret-get", Value::Builtin("secret-get".to_string()));
+        self.define("secret-set", Value::Builtin("secret-set".to_string()));
+
+        // ORM models
+        self.define("define-model", Value::Builtin("define-model".to_string()));
+        self.define("orm-find", Value::Builtin("orm-find".to_string()));
+        self.define("orm-where", Value::Builtin("orm-where".to_string()));
+        self.define("orm-save", Value::Builtin("orm-save".to_string()));
+        self.define("defresolver", Value::Builtin("defresolver".to_string()));
+
+        // Workflows
+        self.define("defsaga", Value::Builtin("defsaga".to_string()));
+
+        // Testing
+        self.define("deftest", Value::Builtin("deftest".to_string()));
+        self.define("defbench", Value::Builtin("defbench".to_string()));
+        self.define("assert", Value::Builtin("assert".to_string()));
+        self.define("assert-equal", Value::Builtin("assert-equal".to_string()));
+        self.define("assert-eq", Value::Builtin("assert-eq".to_string()));
+        self.define("assert-error", Value::Builtin("assert-error".to_string()));
+
+        // Property-based testing
+        self.define("gen-int", Value::Builtin("gen-int".to_string()));
+        self.define("gen-bool", Value::Builtin("gen-bool".to_string()));
+        self.define("gen-string", Value::Builtin("gen-string".to_string()));
+        self.define("gen-list", Value::Builtin("gen-list".to_string()));
+        self.define("gen-record", Value::Builtin("gen-record".to_string()));
+        self.define("forall", Value::Builtin("forall".to_string()));
+
+        // Logging
+        self.define("log-debug", Value::Builtin("log-debug".to_string()));
+        self.define("log-info", Value::Builtin("log-info".to_string()));
+        self.define("log-warn", Value::Builtin("log-warn".to_string()));
+        self.define("log-error", Value::Builtin("log-error".to_string()));
+
         // Actor system functions
         self.define("spawn", Value::Builtin("spawn".to_string()));
         self.define("send", Value::Builtin("send".to_string()));
@@ -2,17 +2,71 @@
 //! 
 //! Provides seamless integration with Rust crates, allowing TLISP programs
 //! to import and use Rust libraries as native extensions.
+//!
+//! A package declares its extension crates under `[extensions]` in
+//! ream.toml. Each crate is built as a cdylib with cargo and the library is
+//! cached under a hash of the crate's sources, build options and target, so
+//! an unchanged crate is never rebuilt. Extensions speak a stable C ABI: the
+//! library exports `ream_extension_v1`, returning an `ExtensionDescriptor`
+//! that lists its functions, and arguments and results cross the boundary as
+//! JSON so an extension never depends on the layout of `Value`. Functions
+//! are bound in TLISP as `<extension>:<function>`.
 
 use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::fs;
+use ring::digest::{Context, SHA256};
 use serde::{Deserialize, Serialize};
 use crate::error::{TlispError, TlispResult};
 use crate::tlisp::{Value, Type};
+use crate::tlisp::package_config::ProjectConfig;
 use crate::tlisp::rust_integration::{RustFunction, FunctionSignature};
 use std::sync::Arc;
 
+/// Version of the extension ABI `ExtensionDescriptor` describes
+pub const EXTENSION_ABI_VERSION: u32 = 1;
+
+/// Symbol an extension exports, an `extern "C" fn() -> *const ExtensionDescriptor`
+pub const EXTENSION_ENTRY_SYMBOL: &str = "ream_extension_v1";
+
+/// Calls an extension function with a JSON array of its arguments.
+///
+/// Returns 0 and stores the JSON result in `out`, or nonzero and stores a
+/// UTF-8 error message. `out` is released with the descriptor's `free`.
+pub type ExtensionCall = unsafe extern "C" fn(args: *const u8, args_len: usize, out: *mut *mut u8, out_len: *mut usize) -> i32;
+
+/// Releases a buffer an `ExtensionCall` stored in `out`
+pub type ExtensionFree = unsafe extern "C" fn(ptr: *mut u8, len: usize);
+
+/// The entry point an extension exports as `EXTENSION_ENTRY_SYMBOL`
+type ExtensionEntry = unsafe extern "C" fn() -> *const ExtensionDescriptor;
+
+/// A function an extension exports
+#[repr(C)]
+pub struct ExtensionFunctionEntry {
+    /// NUL-terminated UTF-8 name
+    pub name: *const c_char,
+    /// Number of arguments, or -1 for any number
+    pub arity: i32,
+    /// Function body
+    pub call: ExtensionCall,
+}
+
+/// What an extension's entry point returns; must live as long as the library
+#[repr(C)]
+pub struct ExtensionDescriptor {
+    /// Must equal `EXTENSION_ABI_VERSION`
+    pub abi_version: u32,
+    /// First of `function_count` exported functions
+    pub functions: *const ExtensionFunctionEntry,
+    /// Number of exported functions
+    pub function_count: usize,
+    /// Releases results and error messages
+    pub free: ExtensionFree,
+}
+
 /// Rust crate metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RustCrateMetadata {
@@ -36,6 +90,9 @@ pub struct RustCrateMetadata {
     pub features: HashMap<String, Vec<String>>,
     /// Target directory
     pub target_dir: Option<PathBuf>,
+    /// Name of the compiled library, before platform prefixes and suffixes
+    #[serde(default)]
+    pub lib_name: String,
     /// Library type (cdylib, staticlib, etc.)
     pub lib_type: LibraryType,
     /// Exported functions
@@ -189,7 +246,17 @@ pub struct BuildArtifact {
 /// FFI library loader
 pub struct FfiLoader {
     /// Loaded libraries
-    libraries: HashMap<String, libloading::Library>,
+    libraries: HashMap<String, Arc<libloading::Library>>,
+}
+
+/// A function exported by a loaded extension
+pub struct ExtensionFunction {
+    name: String,
+    arity: Option<usize>,
+    call: ExtensionCall,
+    free: ExtensionFree,
+    /// Keeps `call` and `free` loaded
+    _library: Arc<libloading::Library>,
 }
 
 /// Crate build options
@@ -217,6 +284,21 @@ pub enum BuildProfile {
     Custom(String),
 }
 
+impl BuildProfile {
+    /// Directory under cargo's target directory the profile builds into
+    pub fn dir_name(&self) -> &str {
+        match self {
+            BuildProfile::Debug => "debug",
+            BuildProfile::Release => "release",
+            BuildProfile::Custom(profile) => match profile.as_str() {
+                "dev" | "test" => "debug",
+                "bench" => "release",
+                profile => profile,
+            },
+        }
+    }
+}
+
 impl RustCrateMetadata {
     /// Load crate metadata from Cargo.toml
     pub fn from_cargo_toml<P: AsRef<Path>>(path: P) -> TlispResult<Self> {
@@ -306,6 +388,12 @@ impl RustCrateMetadata {
             })
             .unwrap_or_default();
 
+        let lib_name = cargo_toml.get("lib")
+            .and_then(|lib| lib.get("name"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| name.replace('-', "_"));
+
         // Determine library type
         let lib_type = if cargo_toml.get("lib").is_some() {
             let lib_section = cargo_toml.get("lib").unwrap();
@@ -336,6 +424,7 @@ impl RustCrateMetadata {
             build_dependencies,
             features,
             target_dir: None,
+            lib_name,
             lib_type,
             exported_functions: Vec::new(),
             exported_types: Vec::new(),
@@ -372,20 +461,21 @@ impl RustCrateIntegration {
 
     /// Load a Rust crate from path
     pub fn load_crate<P: AsRef<Path>>(&mut self, crate_path: P) -> TlispResult<String> {
+        self.load_crate_with(crate_path, &CrateBuildOptions::default())
+    }
+
+    /// Build a Rust extension crate with `options` and load its functions
+    pub fn load_crate_with<P: AsRef<Path>>(&mut self, crate_path: P, options: &CrateBuildOptions) -> TlispResult<String> {
         let path = crate_path.as_ref().to_path_buf();
 
         // Load crate metadata
         let mut metadata = RustCrateMetadata::from_cargo_toml(&path)?;
 
-        // Extract exports from source
-        metadata.extract_exports(&path)?;
-
         // Build the crate if needed
-        let build_options = CrateBuildOptions::default();
-        let library_path = self.build_crate(&path, &build_options)?;
+        let library_path = self.build_crate(&path, &metadata, options)?;
 
         // Load the compiled library
-        let functions = self.load_library_functions(&library_path, &metadata)?;
+        let functions = self.load_library_functions(&library_path, &mut metadata)?;
 
         // Create loaded crate
         let loaded_crate = LoadedCrate {
@@ -411,20 +501,56 @@ impl RustCrateIntegration {
         self.load_crate(crate_path)
     }
 
-    /// Build a Rust crate
-    fn build_crate(&mut self, crate_path: &Path, options: &CrateBuildOptions) -> TlispResult<PathBuf> {
-        // Check build cache
-        let cache_key = format!("{:?}:{:?}", crate_path, options);
-        if let Some(artifact) = self.build_cache.get(&cache_key) {
-            if artifact.output_path.exists() {
-                return Ok(artifact.output_path.clone());
-            }
+    /// Build and load the extensions `config` declares, returning the
+    /// functions to bind in TLISP by their `<extension>:<function>` names
+    pub fn load_project_extensions(
+        &mut self,
+        root: &Path,
+        config: &ProjectConfig,
+        profile: BuildProfile,
+    ) -> TlispResult<Vec<(String, Arc<dyn RustFunction>)>> {
+        let mut names: Vec<&String> = config.extensions.keys().collect();
+        names.sort();
+
+        let mut bindings = Vec::new();
+        for name in names {
+            let extension = &config.extensions[name];
+            let mut options = CrateBuildOptions::default().with_profile(profile.clone());
+            options.features = extension.features.clone();
+            options.no_default_features = !extension.default_features;
+
+            let crate_name = self.load_crate_with(root.join(&extension.path), &options)
+                .map_err(|e| TlispError::Runtime(format!("Extension '{}': {}", name, e)))?;
+            let mut functions: Vec<_> = self.crates[&crate_name].functions.iter().collect();
+            functions.sort_by(|a, b| a.0.cmp(b.0));
+            bindings.extend(functions.into_iter()
+                .map(|(function, body)| (format!("{}:{}", name, function), Arc::clone(body))));
+        }
+        Ok(bindings)
+    }
+
+    /// Build a Rust crate as a cdylib, reusing the cached library when the
+    /// crate's sources and build options are unchanged
+    fn build_crate(&mut self, crate_path: &Path, metadata: &RustCrateMetadata, options: &CrateBuildOptions) -> TlispResult<PathBuf> {
+        let target = options.target.clone().unwrap_or_else(host_target);
+        let file_name = format!("{}{}{}", std::env::consts::DLL_PREFIX, metadata.lib_name, std::env::consts::DLL_SUFFIX);
+        let artifact_root = self.cache_dir.join("artifacts").join(&target).join(&metadata.name);
+        let build_hash = source_hash(crate_path, options, &target)?;
+        let output_path = artifact_root.join(&build_hash).join(&file_name);
+
+        let cache_key = format!("{}:{}", crate_path.display(), target);
+        if output_path.exists() {
+            self.record_artifact(cache_key, crate_path, &output_path, build_hash);
+            return Ok(output_path);
         }
 
-        // Prepare cargo command
-        let mut cmd = Command::new("cargo");
+        // Prepare cargo command, building the library as a cdylib whatever
+        // crate types its manifest asks for
+        let target_dir = self.cache_dir.join("target");
+        let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let mut cmd = Command::new(cargo);
         cmd.current_dir(crate_path);
-        cmd.arg("build");
+        cmd.args(["rustc", "--lib", "--crate-type", "cdylib", "--target-dir"]).arg(&target_dir);
 
         // Add profile
         match &options.profile {
@@ -466,44 +592,46 @@ impl RustCrateIntegration {
         }
 
         // Find the built library
-        let target_dir = crate_path.join("target");
-        let profile_dir = match &options.profile {
-            BuildProfile::Release => target_dir.join("release"),
-            BuildProfile::Debug => target_dir.join("debug"),
-            BuildProfile::Custom(profile) => target_dir.join(profile),
-        };
-
-        // Look for library files
-        let lib_extensions = if cfg!(windows) {
-            vec!["dll", "lib"]
-        } else if cfg!(target_os = "macos") {
-            vec!["dylib", "a"]
-        } else {
-            vec!["so", "a"]
-        };
+        let mut profile_dir = target_dir;
+        if let Some(target) = &options.target {
+            profile_dir = profile_dir.join(target);
+        }
+        let profile_dir = profile_dir.join(options.profile.dir_name());
+        let built = profile_dir.join(&file_name);
+        if !built.exists() {
+            return Err(TlispError::Runtime(format!("No library file found after build: {}", built.display())));
+        }
 
-        for entry in fs::read_dir(&profile_dir)
-            .map_err(|e| TlispError::Runtime(format!("Failed to read target directory: {}", e)))? {
-            let entry = entry.map_err(|e| TlispError::Runtime(format!("Failed to read directory entry: {}", e)))?;
-            let path = entry.path();
-
-            if let Some(extension) = path.extension() {
-                if lib_extensions.contains(&extension.to_string_lossy().as_ref()) {
-                    // Cache the build artifact
-                    let artifact = BuildArtifact {
-                        source_path: crate_path.to_path_buf(),
-                        output_path: path.clone(),
-                        build_time: std::time::SystemTime::now(),
-                        build_hash: "TODO".to_string(), // TODO: Implement proper hashing
-                    };
-                    self.build_cache.insert(cache_key, artifact);
+        // Copy it out of cargo's target directory so a later build of the
+        // crate never overwrites a library that is loaded. The hash is taken
+        // again since the build may have written a lockfile
+        let build_hash = source_hash(crate_path, options, &target)?;
+        let artifact_dir = artifact_root.join(&build_hash);
+        let output_path = artifact_dir.join(&file_name);
+        fs::create_dir_all(&artifact_dir)
+            .map_err(|e| TlispError::Runtime(format!("Failed to create artifact directory: {}", e)))?;
+        fs::copy(&built, &output_path)
+            .map_err(|e| TlispError::Runtime(format!("Failed to cache {}: {}", built.display(), e)))?;
+
+        self.record_artifact(cache_key, crate_path, &output_path, build_hash);
+        Ok(output_path)
+    }
 
-                    return Ok(path);
-                }
-            }
-        }
+    /// Remember the library most recently built for a crate and target
+    fn record_artifact(&mut self, cache_key: String, crate_path: &Path, output_path: &Path, build_hash: String) {
+        let artifact = BuildArtifact {
+            source_path: crate_path.to_path_buf(),
+            output_path: output_path.to_path_buf(),
+            build_time: std::time::SystemTime::now(),
+            build_hash,
+        };
+        self.build_cache.insert(cache_key, artifact);
+    }
 
-        Err(TlispError::Runtime("No library file found after build".to_string()))
+    /// Library most recently built for a crate and target
+    pub fn build_artifact(&self, crate_path: &Path, target: Option<&str>) -> Option<&BuildArtifact> {
+        let target = target.map(str::to_string).unwrap_or_else(host_target);
+        self.build_cache.get(&format!("{}:{}", crate_path.display(), target))
     }
 
     /// Download crate from crates.io
@@ -559,50 +687,32 @@ pub extern "C" fn hello_from_rust() -> i32 {
         Ok(crate_dir)
     }
 
-    /// Load functions from compiled library
+    /// Load functions from compiled library, recording them as the crate's exports
     fn load_library_functions(
         &mut self,
         library_path: &Path,
-        metadata: &RustCrateMetadata,
+        metadata: &mut RustCrateMetadata,
     ) -> TlispResult<HashMap<String, Arc<dyn RustFunction>>> {
-        let mut functions = HashMap::new();
-
-        // Load the library using FFI
-        self.ffi_loader.load_library(library_path)?;
-
-        // Create function wrappers for exported functions
-        for exported_func in &metadata.exported_functions {
-            let function = self.create_function_wrapper(library_path, exported_func)?;
-            functions.insert(exported_func.name.clone(), function);
+        let mut functions: HashMap<String, Arc<dyn RustFunction>> = HashMap::new();
+        metadata.exported_functions.clear();
+
+        for function in self.ffi_loader.load_extension(library_path)? {
+            let param_types = vec!["Value".to_string(); function.arity.unwrap_or(0)];
+            metadata.exported_functions.push(ExportedFunction {
+                name: function.name.clone(),
+                signature: format!("fn({}) -> Value", param_types.join(", ")),
+                param_types,
+                return_type: "Value".to_string(),
+                doc: None,
+                is_unsafe: false,
+                c_name: None,
+            });
+            functions.insert(function.name.clone(), Arc::new(function));
         }
 
         Ok(functions)
     }
 
-    /// Create a function wrapper for an exported function
-    fn create_function_wrapper(
-        &self,
-        _library_path: &Path,
-        exported_func: &ExportedFunction,
-    ) -> TlispResult<Arc<dyn RustFunction>> {
-        // TODO: Implement actual FFI function wrapper creation
-        // This would involve:
-        // 1. Loading the function symbol from the library
-        // 2. Creating a wrapper that converts TLISP values to C types
-        // 3. Calling the native function
-        // 4. Converting the result back to TLISP values
-
-        // For now, create a placeholder function
-        let name = exported_func.name.clone();
-        let signature = FunctionSignature::new(
-            name.clone(),
-            vec![Type::Int], // TODO: Parse actual parameter types
-            Type::Int,       // TODO: Parse actual return type
-        );
-
-        Ok(Arc::new(PlaceholderRustFunction { name, signature }))
-    }
-
     /// Get loaded crate
     pub fn get_crate(&self, name: &str) -> Option<&LoadedCrate> {
         self.crates.get(name)
@@ -613,10 +723,12 @@ pub extern "C" fn hello_from_rust() -> i32 {
         self.crates.keys().map(|s| s.as_str()).collect()
     }
 
-    /// Unload crate
+    /// Unload crate; the library stays loaded until its functions are dropped
     pub fn unload_crate(&mut self, name: &str) -> TlispResult<()> {
-        if let Some(_crate) = self.crates.remove(name) {
-            // TODO: Unload the library from FFI loader
+        if let Some(loaded) = self.crates.remove(name) {
+            if let Some(library_path) = &loaded.library_path {
+                self.ffi_loader.unload_library(library_path);
+            }
             Ok(())
         } else {
             Err(TlispError::Runtime(format!("Crate {} not loaded", name)))
@@ -632,26 +744,214 @@ impl FfiLoader {
         }
     }
 
-    /// Load a library
-    pub fn load_library<P: AsRef<Path>>(&mut self, path: P) -> TlispResult<()> {
-        let _path_str = path.as_ref().to_string_lossy().to_string();
+    /// Load a library, or return it if already loaded
+    pub fn load_library<P: AsRef<Path>>(&mut self, path: P) -> TlispResult<Arc<libloading::Library>> {
+        let path_str = path.as_ref().to_string_lossy().to_string();
+        if let Some(library) = self.libraries.get(&path_str) {
+            return Ok(Arc::clone(library));
+        }
 
-        // TODO: Implement actual library loading with libloading
-        // For now, just track that we "loaded" it
-        // let library = unsafe { libloading::Library::new(path) }
-        //     .map_err(|e| TlispError::Runtime(format!("Failed to load library: {}", e)))?;
-        // self.libraries.insert(path_str, library);
+        // SAFETY: loading runs the library's initializers; extensions are
+        // built from crates the package itself declares
+        let library = unsafe { libloading::Library::new(path.as_ref()) }
+            .map_err(|e| TlispError::Runtime(format!("Failed to load library {}: {}", path_str, e)))?;
+        let library = Arc::new(library);
+        self.libraries.insert(path_str, Arc::clone(&library));
+        Ok(library)
+    }
 
-        Ok(())
+    /// Forget a loaded library
+    pub fn unload_library<P: AsRef<Path>>(&mut self, path: P) {
+        self.libraries.remove(path.as_ref().to_string_lossy().as_ref());
     }
 
     /// Get function symbol from library
-    pub fn get_symbol<T>(&self, _library_path: &str, _symbol_name: &str) -> TlispResult<libloading::Symbol<T>> {
-        // TODO: Implement actual symbol loading
-        Err(TlispError::Runtime("Symbol loading not implemented".to_string()))
+    pub fn get_symbol<T>(&self, library_path: &str, symbol_name: &str) -> TlispResult<libloading::Symbol<'_, T>> {
+        let library = self.libraries.get(library_path)
+            .ok_or_else(|| TlispError::Runtime(format!("Library {} is not loaded", library_path)))?;
+        // SAFETY: the caller names the symbol's type
+        unsafe { library.get(symbol_name.as_bytes()) }
+            .map_err(|e| TlispError::Runtime(format!("Symbol {} not found in {}: {}", symbol_name, library_path, e)))
+    }
+
+    /// Load an extension library and the functions its descriptor lists
+    pub fn load_extension<P: AsRef<Path>>(&mut self, path: P) -> TlispResult<Vec<ExtensionFunction>> {
+        let library = self.load_library(&path)?;
+        let path = path.as_ref().display();
+
+        // SAFETY: the entry symbol has the type the extension ABI fixes, and
+        // the descriptor it returns lives as long as the library
+        unsafe {
+            let entry = library.get::<ExtensionEntry>(EXTENSION_ENTRY_SYMBOL.as_bytes())
+                .map_err(|_| TlispError::Runtime(format!("{} is not a REAM extension: no {} symbol", path, EXTENSION_ENTRY_SYMBOL)))?;
+            let descriptor = entry().as_ref()
+                .ok_or_else(|| TlispError::Runtime(format!("{} returned no extension descriptor", path)))?;
+            if descriptor.abi_version != EXTENSION_ABI_VERSION {
+                return Err(TlispError::Runtime(format!(
+                    "{} uses extension ABI version {} (expected {})", path, descriptor.abi_version, EXTENSION_ABI_VERSION
+                )));
+            }
+
+            let entries = if descriptor.function_count == 0 {
+                &[]
+            } else {
+                std::slice::from_raw_parts(descriptor.functions, descriptor.function_count)
+            };
+            entries.iter().map(|entry| {
+                let name = CStr::from_ptr(entry.name).to_str()
+                    .map_err(|_| TlispError::Runtime(format!("{} exports a function with a non-UTF-8 name", path)))?;
+                Ok(ExtensionFunction {
+                    name: name.to_string(),
+                    arity: usize::try_from(entry.arity).ok(),
+                    call: entry.call,
+                    free: descriptor.free,
+                    _library: Arc::clone(&library),
+                })
+            }).collect()
+        }
+    }
+}
+
+impl ExtensionFunction {
+    /// Number of arguments, or `None` when any number is accepted
+    pub fn arity(&self) -> Option<usize> {
+        self.arity
     }
 }
 
+impl RustFunction for ExtensionFunction {
+    fn call(&self, args: &[Value]) -> TlispResult<Value> {
+        if let Some(arity) = self.arity {
+            if args.len() != arity {
+                return Err(TlispError::Runtime(format!(
+                    "{}: expected {} arguments, got {}", self.name, arity, args.len()
+                )));
+            }
+        }
+
+        let args = args.iter().map(value_to_json).collect::<TlispResult<Vec<_>>>()?;
+        let args = serde_json::to_vec(&args)
+            .map_err(|e| TlispError::Runtime(format!("{}: {}", self.name, e)))?;
+
+        let mut out: *mut u8 = std::ptr::null_mut();
+        let mut out_len = 0usize;
+        // SAFETY: the extension ABI fixes the signature of `call`, and `out`
+        // is handed back to the same extension's `free`
+        let (status, out) = unsafe {
+            let status = (self.call)(args.as_ptr(), args.len(), &mut out, &mut out_len);
+            let bytes = if out.is_null() {
+                Vec::new()
+            } else {
+                let bytes = std::slice::from_raw_parts(out, out_len).to_vec();
+                (self.free)(out, out_len);
+                bytes
+            };
+            (status, bytes)
+        };
+
+        if status != 0 {
+            return Err(TlispError::Runtime(format!("{}: {}", self.name, String::from_utf8_lossy(&out))));
+        }
+        let result: serde_json::Value = serde_json::from_slice(&out)
+            .map_err(|e| TlispError::Runtime(format!("{} returned invalid JSON: {}", self.name, e)))?;
+        json_to_value(&result)
+    }
+
+    fn signature(&self) -> FunctionSignature {
+        let params = vec![Type::TypeVar("a".to_string()); self.arity.unwrap_or(0)];
+        FunctionSignature::new(self.name.clone(), params, Type::TypeVar("b".to_string()))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Convert a value to the JSON an extension or other language receives
+pub(crate) fn value_to_json(value: &Value) -> TlispResult<serde_json::Value> {
+    match value {
+        Value::Null | Value::Unit => Ok(serde_json::Value::Null),
+        Value::Bool(b) => Ok(serde_json::Value::Bool(*b)),
+        Value::Int(i) => Ok(serde_json::Value::from(*i)),
+        Value::Float(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| TlispError::Runtime(format!("Cannot convert {} to JSON", f))),
+        Value::String(s) | Value::Symbol(s) => Ok(serde_json::Value::String(s.clone())),
+        Value::List(items) => Ok(serde_json::Value::Array(items.iter().map(value_to_json).collect::<TlispResult<_>>()?)),
+        other => Err(TlispError::Runtime(format!("Cannot convert {} to JSON", other))),
+    }
+}
+
+/// Convert JSON an extension or other language returned to a value;
+/// objects become lists of `(key value)` pairs
+pub(crate) fn json_to_value(json: &serde_json::Value) -> TlispResult<Value> {
+    match json {
+        serde_json::Value::Null => Ok(Value::Null),
+        serde_json::Value::Bool(b) => Ok(Value::Bool(*b)),
+        serde_json::Value::Number(n) => n.as_i64().map(Value::Int)
+            .or_else(|| n.as_f64().map(Value::Float))
+            .ok_or_else(|| TlispError::Runtime(format!("Number {} is out of range", n))),
+        serde_json::Value::String(s) => Ok(Value::String(s.clone())),
+        serde_json::Value::Array(items) => Ok(Value::List(items.iter().map(json_to_value).collect::<TlispResult<_>>()?)),
+        serde_json::Value::Object(fields) => Ok(Value::List(fields.iter()
+            .map(|(key, value)| Ok(Value::List(vec![Value::String(key.clone()), json_to_value(value)?])))
+            .collect::<TlispResult<_>>()?)),
+    }
+}
+
+/// Target triple-like name of the host, used when builds name no target
+fn host_target() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Hash of everything that determines the library built from a crate: its
+/// manifest, lockfile, build script and sources, and the build options
+fn source_hash(crate_path: &Path, options: &CrateBuildOptions, target: &str) -> TlispResult<String> {
+    let mut context = Context::new(&SHA256);
+    context.update(&EXTENSION_ABI_VERSION.to_le_bytes());
+    context.update(format!("{}\0{:?}\0{:?}\0{}\0{}\0{:?}\0",
+        target, options.profile, options.features, options.all_features,
+        options.no_default_features, options.cargo_flags).as_bytes());
+
+    let mut files = Vec::new();
+    for name in ["Cargo.toml", "Cargo.lock", "build.rs"] {
+        let path = crate_path.join(name);
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    collect_source_files(&crate_path.join("src"), &mut files)?;
+    files.sort();
+
+    for file in files {
+        let content = fs::read(&file)
+            .map_err(|e| TlispError::Runtime(format!("Failed to read {}: {}", file.display(), e)))?;
+        let relative = file.strip_prefix(crate_path).unwrap_or(&file);
+        context.update(relative.to_string_lossy().as_bytes());
+        context.update(&(content.len() as u64).to_le_bytes());
+        context.update(&content);
+    }
+
+    Ok(hex::encode(context.finish()))
+}
+
+/// Every file under `dir`, recursively
+fn collect_source_files(dir: &Path, files: &mut Vec<PathBuf>) -> TlispResult<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)
+        .map_err(|e| TlispError::Runtime(format!("Failed to read {}: {}", dir.display(), e)))? {
+        let path = entry.map_err(|e| TlispError::Runtime(format!("Failed to read directory entry: {}", e)))?.path();
+        if path.is_dir() {
+            collect_source_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
 impl CrateBuildOptions {
     /// Create default build options
     pub fn default() -> Self {
@@ -702,37 +1002,6 @@ impl CrateBuildOptions {
     }
 }
 
-/// Placeholder Rust function implementation
-struct PlaceholderRustFunction {
-    name: String,
-    signature: FunctionSignature,
-}
-
-impl RustFunction for PlaceholderRustFunction {
-    fn call(&self, args: &[Value]) -> TlispResult<Value> {
-        // Placeholder implementation
-        match self.name.as_str() {
-            "hello_from_rust" => Ok(Value::Int(42)),
-            _ => {
-                // Echo the first argument or return unit
-                if !args.is_empty() {
-                    Ok(args[0].clone())
-                } else {
-                    Ok(Value::Unit)
-                }
-            }
-        }
-    }
-
-    fn signature(&self) -> FunctionSignature {
-        self.signature.clone()
-    }
-
-    fn name(&self) -> &str {
-        &self.name
-    }
-}
-
 /// Utility functions for Rust crate integration
 pub struct RustCrateUtils;
 
@@ -808,3 +1077,99 @@ pub fn {}_wrapper(args: &[Value]) -> TlispResult<Value> {{
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlisp::package_config::ExtensionConfig;
+    use crate::tlisp::TlispInterpreter;
+
+    /// An extension written against the ABI by hand, without depending on ream
+    const EXTENSION_SOURCE: &str = r#"
+use std::os::raw::c_char;
+
+type Call = unsafe extern "C" fn(*const u8, usize, *mut *mut u8, *mut usize) -> i32;
+
+#[repr(C)]
+pub struct Entry { name: *const c_char, arity: i32, call: Call }
+#[repr(C)]
+pub struct Descriptor { abi_version: u32, functions: *const Entry, function_count: usize, free: unsafe extern "C" fn(*mut u8, usize) }
+unsafe impl Sync for Entry {}
+unsafe impl Sync for Descriptor {}
+
+unsafe fn reply(bytes: &[u8], out: *mut *mut u8, out_len: *mut usize) {
+    let bytes = bytes.to_vec().into_boxed_slice();
+    *out_len = bytes.len();
+    *out = Box::into_raw(bytes) as *mut u8;
+}
+
+unsafe extern "C" fn free(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+unsafe extern "C" fn echo(args: *const u8, len: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    reply(std::slice::from_raw_parts(args, len), out, out_len);
+    0
+}
+
+unsafe extern "C" fn fail(_: *const u8, _: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    reply(b"no luck", out, out_len);
+    1
+}
+
+static FUNCTIONS: [Entry; 2] = [
+    Entry { name: c"echo".as_ptr(), arity: -1, call: echo },
+    Entry { name: c"fail".as_ptr(), arity: 0, call: fail },
+];
+static DESCRIPTOR: Descriptor = Descriptor { abi_version: 1, functions: FUNCTIONS.as_ptr(), function_count: 2, free };
+
+#[no_mangle]
+pub extern "C" fn ream_extension_v1() -> *const Descriptor {
+    &DESCRIPTOR
+}
+"#;
+
+    #[test]
+    fn test_project_extensions_build_once_and_bind() {
+        let project = tempfile::tempdir().unwrap();
+        let native = project.path().join("native");
+        fs::create_dir_all(native.join("src")).unwrap();
+        fs::write(native.join("Cargo.toml"), "[package]\nname = \"demo-ext\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n").unwrap();
+        fs::write(native.join("src").join("lib.rs"), EXTENSION_SOURCE).unwrap();
+
+        let mut config = ProjectConfig::new("demo".to_string(), "0.1.0".to_string());
+        config.extensions.insert("demo".to_string(), ExtensionConfig {
+            path: PathBuf::from("native"),
+            features: Vec::new(),
+            default_features: true,
+        });
+        let cache = project.path().join("target").join("extensions");
+
+        let mut integration = RustCrateIntegration::new(cache.clone());
+        let bindings = integration.load_project_extensions(project.path(), &config, BuildProfile::Debug).unwrap();
+        assert_eq!(bindings.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), ["demo:echo", "demo:fail"]);
+        let built = integration.build_artifact(&native, None).unwrap().output_path.clone();
+
+        let mut interpreter = TlispInterpreter::new();
+        for (name, function) in bindings {
+            interpreter.register_native(name, function);
+        }
+        assert_eq!(
+            interpreter.eval("(demo:echo 1 \"two\" (list 3))").unwrap(),
+            Value::List(vec![Value::Int(1), Value::String("two".to_string()), Value::List(vec![Value::Int(3)])])
+        );
+        let error = interpreter.eval("(demo:fail)").unwrap_err().to_string();
+        assert!(error.contains("no luck"), "{}", error);
+        assert!(interpreter.eval("(demo:fail 1)").is_err(), "arity is checked");
+
+        // Unchanged sources reuse the cached library; changed ones rebuild
+        let mut again = RustCrateIntegration::new(cache.clone());
+        again.load_project_extensions(project.path(), &config, BuildProfile::Debug).unwrap();
+        assert_eq!(again.build_artifact(&native, None).unwrap().output_path, built);
+
+        fs::write(native.join("src").join("lib.rs"), format!("{}\n// changed\n", EXTENSION_SOURCE)).unwrap();
+        let mut rebuilt = RustCrateIntegration::new(cache);
+        rebuilt.load_project_extensions(project.path(), &config, BuildProfile::Debug).unwrap();
+        assert_ne!(rebuilt.build_artifact(&native, None).unwrap().output_path, built);
+    }
+}
@@ -337,6 +337,7 @@ impl RustFunction for TypeOfFunction {
             Value::Function(_) => "Function",
             Value::Builtin(_) => "Builtin",
             Value::Pid(_) => "Pid",
+            Value::Binary(_) => "Binary",
             Value::StmVar(_) => "StmVar",
             Value::Unit => "Unit",
             Value::Null => "Null",
@@ -375,6 +376,7 @@ fn format_value(value: &Value) -> String {
         Value::Function(_) => "#<function>".to_string(),
         Value::Builtin(name) => format!("#<builtin:{}>", name),
         Value::Pid(pid) => format!("#<pid:{}>", pid),
+        Value::Binary(binary) => format!("#<binary:{}>", binary.len()),
         Value::StmVar(var) => format!("#<stm-var:{}>", var.name()),
         Value::Unit => "#<unit>".to_string(),
         Value::Null => "null".to_string(),
@@ -0,0 +1,140 @@
+//! Environment snapshots
+//!
+//! Initializing a TLisp program — parsing it and evaluating its definitions —
+//! is repeated by every fresh interpreter that runs it. A snapshot captures
+//! the global environment an initialized interpreter ends up with, so later
+//! interpreters start from the snapshot instead: restoring one only decodes
+//! the globals, without parsing or evaluating anything.
+//!
+//! Only the globals a program added or changed are captured; builtins come
+//! from the restoring interpreter. Each snapshot records the digest of the
+//! code it was taken from, and is only valid for that code.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{TlispError, TlispResult};
+use crate::tlisp::{TlispInterpreter, Value};
+
+/// File extension for snapshots
+pub const SNAPSHOT_EXTENSION: &str = "reamsnap";
+
+/// Snapshot format version, bumped when the layout changes
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Magic bytes at the start of every snapshot file
+const SNAPSHOT_MAGIC: &[u8; 6] = b"REAMS\0";
+
+/// Digest identifying the code a snapshot was taken from
+pub fn code_digest(code: &[u8]) -> String {
+    hex::encode(digest(&SHA256, code))
+}
+
+/// The globals of an initialized interpreter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentSnapshot {
+    /// Digest of the code the interpreter was initialized with
+    pub code_digest: String,
+    /// Globals the code defined or redefined
+    pub globals: BTreeMap<String, Value>,
+}
+
+impl EnvironmentSnapshot {
+    /// Capture the globals `interpreter` has beyond those of a fresh one
+    pub fn capture(interpreter: &TlispInterpreter, code_digest: String) -> Self {
+        let fresh = TlispInterpreter::new();
+        let globals = interpreter.symbols().into_iter()
+            .filter_map(|name| {
+                let value = interpreter.get(&name)?;
+                (fresh.get(&name).as_ref() != Some(&value)).then_some((name, value))
+            })
+            .collect();
+        EnvironmentSnapshot { code_digest, globals }
+    }
+
+    /// A fresh interpreter with the captured globals defined
+    pub fn restore(&self) -> TlispInterpreter {
+        let mut interpreter = TlispInterpreter::new();
+        for (name, value) in &self.globals {
+            interpreter.define(name.clone(), value.clone());
+        }
+        interpreter
+    }
+
+    /// Whether the snapshot was taken from the code with this digest
+    pub fn is_for(&self, code_digest: &str) -> bool {
+        self.code_digest == code_digest
+    }
+
+    /// Serialize the snapshot
+    pub fn to_bytes(&self) -> TlispResult<Vec<u8>> {
+        let body = bincode::serialize(self)
+            .map_err(|e| TlispError::Runtime(format!("Snapshot serialization failed: {}", e)))?;
+        let mut bytes = Vec::with_capacity(SNAPSHOT_MAGIC.len() + 4 + body.len());
+        bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        Ok(bytes)
+    }
+
+    /// Deserialize a snapshot, rejecting other files and other format versions
+    pub fn from_bytes(bytes: &[u8]) -> TlispResult<Self> {
+        let header_len = SNAPSHOT_MAGIC.len() + 4;
+        if bytes.len() < header_len || &bytes[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+            return Err(TlispError::Runtime("Not a REAM environment snapshot".to_string()));
+        }
+        let mut version = [0u8; 4];
+        version.copy_from_slice(&bytes[SNAPSHOT_MAGIC.len()..header_len]);
+        let version = u32::from_le_bytes(version);
+        if version != SNAPSHOT_FORMAT_VERSION {
+            return Err(TlispError::Runtime(format!(
+                "Unsupported snapshot format version {} (expected {})", version, SNAPSHOT_FORMAT_VERSION
+            )));
+        }
+        bincode::deserialize(&bytes[header_len..])
+            .map_err(|e| TlispError::Runtime(format!("Corrupt snapshot: {}", e)))
+    }
+
+    /// Write the snapshot to a file
+    pub fn write_to(&self, path: &Path) -> TlispResult<()> {
+        std::fs::write(path, self.to_bytes()?)
+            .map_err(|e| TlispError::Runtime(format!("Failed to write snapshot {}: {}", path.display(), e)))
+    }
+
+    /// Read a snapshot file
+    pub fn read_from(path: &Path) -> TlispResult<Self> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| TlispError::Runtime(format!("Failed to read snapshot {}: {}", path.display(), e)))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_restores_initialized_globals() {
+        let source = "(define greeting \"Hello\") (define (greet name) (if (= name \"world\") greeting \"Who?\"))";
+        let mut interpreter = TlispInterpreter::new();
+        interpreter.eval(source).unwrap();
+
+        let snapshot = EnvironmentSnapshot::capture(&interpreter, code_digest(source.as_bytes()));
+        assert_eq!(snapshot.globals.keys().collect::<Vec<_>>(), ["greet", "greeting"]);
+        assert!(!snapshot.globals.contains_key("+"), "builtins are not captured");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("greet.{}", SNAPSHOT_EXTENSION));
+        snapshot.write_to(&path).unwrap();
+        let loaded = EnvironmentSnapshot::read_from(&path).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert!(loaded.is_for(&code_digest(source.as_bytes())));
+        assert!(!loaded.is_for(&code_digest(b"(define greeting \"Hi\")")));
+
+        let mut restored = loaded.restore();
+        assert_eq!(restored.eval("(greet \"world\")").unwrap(), Value::String("Hello".to_string()));
+        assert!(EnvironmentSnapshot::from_bytes(b"REAMB\0\x03\0\0\0").is_err());
+    }
+}
@@ -0,0 +1,500 @@
+//! TLISP test runner
+//!
+//! Test files register cases with `(deftest name body...)` and check results
+//! with `assert`, `assert-equal` and `assert-error`. The runner discovers test
+//! files, runs each file in its own runtime (on test actors scheduled by the
+//! ream work-stealing scheduler when running in parallel) and reports
+//! results as text, JUnit XML or JSON.
+//! A seed fixes the values `forall` properties are checked against.
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::error::{RuntimeError, RuntimeResult, TlispError, TlispResult};
+use crate::runtime::{Mailbox, Process, ProcessHandle, ReamActor, ScheduledTask, WorkStealingScheduler};
+use crate::tlisp::property::SEED_VAR;
+use crate::tlisp::{TlispRuntime, Value};
+use crate::types::{MessagePayload, Pid, Priority};
+
+/// Global list of `(name thunk)` pairs registered by `deftest`
+pub const TEST_REGISTRY: &str = "*tests*";
+
+/// Find test files: every `.tl` file under a directory, plus legacy
+/// `_test.scm` and `.test.scm` files
+pub fn discover_test_files(path: &Path) -> TlispResult<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    let entries = fs::read_dir(path)
+        .map_err(|e| TlispError::Runtime(format!("Failed to read {}: {}", path.display(), e)))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| TlispError::Runtime(format!("Failed to read {}: {}", path.display(), e)))?;
+        let entry_path = entry.path();
+
+        if entry_path.is_dir() {
+            files.extend(discover_test_files(&entry_path)?);
+        } else if let Some(name) = entry_path.file_name().and_then(|n| n.to_str()) {
+            if name.ends_with(".tl") || name.ends_with("_test.scm") || name.ends_with(".test.scm") {
+                files.push(entry_path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Outcome of a single test
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TestOutcome {
+    Passed,
+    Failed(String),
+}
+
+/// Result of a single test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestResult {
+    /// File the test was defined in
+    pub file: PathBuf,
+    /// Test name
+    pub name: String,
+    /// Whether the test passed
+    pub outcome: TestOutcome,
+    /// Time spent running the test
+    pub duration: Duration,
+}
+
+impl TestResult {
+    /// Check whether the test passed
+    pub fn passed(&self) -> bool {
+        self.outcome == TestOutcome::Passed
+    }
+}
+
+/// Results of a test run
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    /// Results in file order, then definition order
+    pub results: Vec<TestResult>,
+    /// Wall-clock duration of the run
+    pub duration: Duration,
+}
+
+impl TestReport {
+    /// Number of passed tests
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed()).count()
+    }
+
+    /// Number of failed tests
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Check whether every test passed
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// Render the report as JSON
+    pub fn to_json(&self) -> String {
+        let tests: Vec<serde_json::Value> = self.results.iter().map(|result| {
+            let mut test = serde_json::json!({
+                "file": result.file.display().to_string(),
+                "name": result.name,
+                "status": if result.passed() { "passed" } else { "failed" },
+                "duration_ms": result.duration.as_secs_f64() * 1000.0,
+            });
+            if let TestOutcome::Failed(message) = &result.outcome {
+                test["message"] = serde_json::Value::String(message.clone());
+            }
+            test
+        }).collect();
+
+        let report = serde_json::json!({
+            "total": self.results.len(),
+            "passed": self.passed(),
+            "failed": self.failed(),
+            "duration_ms": self.duration.as_secs_f64() * 1000.0,
+            "tests": tests,
+        });
+        serde_json::to_string_pretty(&report).unwrap_or_default()
+    }
+
+    /// Render the report as JUnit XML, one test suite per file
+    pub fn to_junit_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            self.results.len(),
+            self.failed(),
+            self.duration.as_secs_f64()
+        ));
+
+        let mut files: Vec<&PathBuf> = Vec::new();
+        for result in &self.results {
+            if !files.contains(&&result.file) {
+                files.push(&result.file);
+            }
+        }
+
+        for file in files {
+            let suite: Vec<&TestResult> = self.results.iter().filter(|r| &r.file == file).collect();
+            let suite_name = xml_escape(&file.display().to_string());
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+                suite_name,
+                suite.len(),
+                suite.iter().filter(|r| !r.passed()).count(),
+                suite.iter().map(|r| r.duration.as_secs_f64()).sum::<f64>()
+            ));
+            for result in suite {
+                xml.push_str(&format!(
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                    xml_escape(&result.name),
+                    suite_name,
+                    result.duration.as_secs_f64()
+                ));
+                match &result.outcome {
+                    TestOutcome::Passed => xml.push_str("/>\n"),
+                    TestOutcome::Failed(message) => {
+                        let message = xml_escape(message);
+                        xml.push_str(&format!(
+                            ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                            message, message
+                        ));
+                    }
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+/// Escape text for XML attributes and content
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Runs test files sequentially or across parallel test actors
+#[derive(Debug, Clone)]
+pub struct TestRunner {
+    /// Only run tests whose name contains this
+    filter: Option<String>,
+    /// Number of test actors
+    jobs: usize,
+    /// Seed for property-based tests
+    seed: Option<u64>,
+}
+
+impl TestRunner {
+    /// Create a sequential runner with no filter
+    pub fn new() -> Self {
+        TestRunner { filter: None, jobs: 1, seed: None }
+    }
+
+    /// Only run tests whose name contains `filter`
+    pub fn with_filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// Check `forall` properties against values from `seed`
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Run files across `jobs` test actors
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Run every test in the given files
+    pub fn run(&self, files: &[PathBuf]) -> TestReport {
+        let start = Instant::now();
+        let results = if self.jobs > 1 && files.len() > 1 {
+            self.run_parallel(files)
+        } else {
+            files.iter().flat_map(|file| run_file(file, self.filter.as_deref(), self.seed)).collect()
+        };
+
+        TestReport { results, duration: start.elapsed() }
+    }
+
+    /// Hand files round-robin to test actors, run as processes of a
+    /// work-stealing scheduler with a worker per actor. Each actor's
+    /// mailbox is filled with its files up front, and it sends the results
+    /// of each file to the runner's mailbox.
+    fn run_parallel(&self, files: &[PathBuf]) -> Vec<TestResult> {
+        let workers = self.jobs.min(files.len());
+        let replies = Arc::new(RwLock::new(Mailbox::new()));
+        let mut scheduler = WorkStealingScheduler::new(Some(workers));
+
+        let actors: Vec<ProcessHandle> = (0..workers).map(|_| {
+            let actor = TestActor::new(self.filter.clone(), self.seed, Arc::clone(&replies));
+            let handle = ProcessHandle::new(Process::new(actor.pid(), Box::new(actor), Priority::Normal));
+            scheduler.register_process(handle.clone());
+            handle
+        }).collect();
+        for (index, file) in files.iter().enumerate() {
+            let message = MessagePayload::Data(serde_json::json!({
+                "index": index,
+                "path": file.display().to_string(),
+            }));
+            actors[index % workers].mailbox().write().unwrap().send(message);
+        }
+
+        if scheduler.start().is_err() {
+            return files.iter().flat_map(|file| run_file(file, self.filter.as_deref(), self.seed)).collect();
+        }
+        for actor in &actors {
+            scheduler.schedule_task(ScheduledTask::new(actor.pid(), Priority::Normal));
+        }
+
+        let mut by_file: Vec<Vec<TestResult>> = vec![Vec::new(); files.len()];
+        let mut pending = files.len();
+        while pending > 0 {
+            let Some(message) = replies.write().unwrap().receive() else {
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            };
+            let MessagePayload::Data(reply) = message else {
+                continue;
+            };
+            let (Some(index), Ok(results)) = (
+                reply["index"].as_u64().map(|index| index as usize),
+                serde_json::from_value::<Vec<TestResult>>(reply["results"].clone()),
+            ) else {
+                continue;
+            };
+            if index < files.len() {
+                by_file[index] = results;
+                pending -= 1;
+            }
+        }
+        scheduler.stop();
+
+        by_file.into_iter().flatten().collect()
+    }
+}
+
+impl Default for TestRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Actor that runs the test file named in each message and replies with its results
+struct TestActor {
+    pid: Pid,
+    filter: Option<String>,
+    seed: Option<u64>,
+    /// Mailbox of the runner waiting for the results
+    replies: Arc<RwLock<Mailbox>>,
+}
+
+impl TestActor {
+    fn new(filter: Option<String>, seed: Option<u64>, replies: Arc<RwLock<Mailbox>>) -> Self {
+        TestActor { pid: Pid::new(), filter, seed, replies }
+    }
+}
+
+impl ReamActor for TestActor {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        let MessagePayload::Data(data) = message else {
+            return Err(RuntimeError::InvalidMessage("Expected a test file message".to_string()));
+        };
+        let (Some(index), Some(path)) = (data["index"].as_u64(), data["path"].as_str()) else {
+            return Err(RuntimeError::InvalidMessage("Expected index and path".to_string()));
+        };
+
+        // A panic would take the scheduler's worker down with it, and the
+        // runner would wait for this file forever
+        let file = Path::new(path);
+        let start = Instant::now();
+        let results = panic::catch_unwind(AssertUnwindSafe(|| run_file(file, self.filter.as_deref(), self.seed)))
+            .unwrap_or_else(|_| vec![TestResult {
+                file: file.to_path_buf(),
+                name: file_test_name(file),
+                outcome: TestOutcome::Failed("test actor crashed".to_string()),
+                duration: start.elapsed(),
+            }]);
+        let results = serde_json::to_value(results)
+            .map_err(|e| RuntimeError::ActorError(format!("Could not send test results: {}", e)))?;
+        self.replies.write().unwrap().send(MessagePayload::Data(serde_json::json!({
+            "index": index,
+            "results": results,
+        })));
+        Ok(())
+    }
+
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        Ok(())
+    }
+}
+
+/// Name used for a file without `deftest` cases or one that fails to load
+fn file_test_name(file: &Path) -> String {
+    file.file_stem().and_then(|s| s.to_str()).unwrap_or("test").to_string()
+}
+
+/// Load a test file in a fresh runtime and run its tests
+fn run_file(file: &Path, filter: Option<&str>, seed: Option<u64>) -> Vec<TestResult> {
+    let start = Instant::now();
+    let failed = |message: String, duration: Duration| vec![TestResult {
+        file: file.to_path_buf(),
+        name: file_test_name(file),
+        outcome: TestOutcome::Failed(message),
+        duration,
+    }];
+
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => return failed(format!("failed to read file: {}", e), start.elapsed()),
+    };
+
+    let mut runtime = TlispRuntime::new();
+    runtime.define("*file*", Value::String(file.display().to_string()));
+    if let Some(seed) = seed {
+        runtime.define(SEED_VAR, Value::Int(seed as i64));
+    }
+    if let Err(e) = runtime.eval(&source) {
+        return failed(e.to_string(), start.elapsed());
+    }
+
+    let tests = match runtime.get(TEST_REGISTRY) {
+        Some(Value::List(tests)) => tests,
+        // A file without deftest cases passes when it evaluates cleanly
+        _ => {
+            let name = file_test_name(file);
+            if filter.is_some_and(|filter| !name.contains(filter)) {
+                return Vec::new();
+            }
+            return vec![TestResult {
+                file: file.to_path_buf(),
+                name,
+                outcome: TestOutcome::Passed,
+                duration: start.elapsed(),
+            }];
+        }
+    };
+
+    let mut results = Vec::new();
+    for test in tests {
+        let Value::List(entry) = test else { continue };
+        let (Some(Value::String(name)), Some(thunk)) = (entry.first(), entry.get(1)) else { continue };
+        if filter.is_some_and(|filter| !name.contains(filter)) {
+            continue;
+        }
+
+        let test_start = Instant::now();
+        let outcome = match runtime.call(thunk, &[]) {
+            Ok(_) => TestOutcome::Passed,
+            Err(e) => TestOutcome::Failed(e.to_string()),
+        };
+        results.push(TestResult {
+            file: file.to_path_buf(),
+            name: name.clone(),
+            outcome,
+            duration: test_start.elapsed(),
+        });
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SOURCE: &str = r#"
+(define square (lambda (x) (* x x)))
+(deftest square-works (assert-equal (square 4) 16))
+(deftest square-fails (assert-equal (square 3) 10))
+(deftest errors-are-caught (assert-error (error "boom")))
+"#;
+
+    fn write_tests(dir: &TempDir) -> Vec<PathBuf> {
+        let tests = dir.path().join("tests");
+        fs::create_dir_all(tests.join("nested")).unwrap();
+        fs::write(tests.join("math.tl"), SOURCE).unwrap();
+        fs::write(tests.join("nested/plain.tl"), "(+ 1 2)").unwrap();
+        fs::write(tests.join("notes.txt"), "not a test").unwrap();
+        discover_test_files(&tests).unwrap()
+    }
+
+    #[test]
+    fn test_discover_and_run() {
+        let dir = TempDir::new().unwrap();
+        let files = write_tests(&dir);
+        assert_eq!(files.len(), 2);
+
+        let report = TestRunner::new().run(&files);
+        let names: Vec<&str> = report.results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["square-works", "square-fails", "errors-are-caught", "plain"]);
+        assert_eq!(report.passed(), 3);
+        assert_eq!(report.failed(), 1);
+        assert!(matches!(&report.results[1].outcome, TestOutcome::Failed(m) if m.contains("expected 10")));
+
+        let filtered = TestRunner::new().with_filter("square").run(&files);
+        assert_eq!(filtered.results.len(), 2);
+    }
+
+    #[test]
+    fn test_property_failures_report_seed() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("props.tl");
+        fs::write(&file, r#"
+(deftest addition-commutes (forall (gen-int) (gen-int) (lambda (a b) (= (+ a b) (+ b a)))))
+(deftest below-fifty (forall (gen-int 0 1000) (lambda (n) (< n 50))))
+"#).unwrap();
+
+        let report = TestRunner::new().with_seed(99).run(&[file.clone()]);
+        assert!(report.results[0].passed());
+        let TestOutcome::Failed(message) = &report.results[1].outcome else { panic!("expected a failure") };
+        assert!(message.contains("seed 99"), "{}", message);
+        assert!(message.contains("counterexample: (50)"), "{}", message);
+
+        let again = TestRunner::new().with_seed(99).run(&[file]);
+        assert_eq!(again.results[1].outcome, report.results[1].outcome);
+    }
+
+    #[test]
+    fn test_parallel_run_and_reports() {
+        let dir = TempDir::new().unwrap();
+        let files = write_tests(&dir);
+
+        let report = TestRunner::new().with_jobs(4).run(&files);
+        assert_eq!(report.results.len(), 4);
+        assert_eq!(report.results[0].name, "square-works");
+        assert!(!report.is_success());
+
+        let xml = report.to_junit_xml();
+        assert!(xml.contains("<testsuites tests=\"4\" failures=\"1\""));
+        assert!(xml.contains("<testcase name=\"square-fails\""));
+        assert!(xml.contains("<failure message="));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["tests"][1]["status"], "failed");
+    }
+}
@@ -6,7 +6,7 @@ use tempfile::TempDir;
 
 use crate::tlisp::package_config::{
     ProjectConfig, ProjectConfigManager, DependencySpec, DetailedDependency,
-    BuildConfig, BinaryTarget, LibraryConfig
+    BuildConfig, BinaryTarget, LibraryConfig, CONFIG_FILE, LEGACY_CONFIG_FILE
 };
 
 
@@ -105,6 +105,39 @@ mod tests {
         assert_eq!(bin_config.path, Some(PathBuf::from("src/main.tl")));
     }
 
+    #[test]
+    fn test_project_config_manager_init_project_service_templates() {
+        for (template, marker) in [("actor", "(spawn"), ("web", "http-server:start"), ("cli", "usage")] {
+            let (mut manager, temp_dir) = create_test_project();
+
+            manager.init_project("svc".to_string(), "0.1.0".to_string(), Some(template)).unwrap();
+
+            let root = temp_dir.path();
+            assert!(root.join(CONFIG_FILE).exists());
+            assert!(root.join("tests/main_test.tl").exists());
+            assert!(root.join(".gitignore").exists());
+            let main = std::fs::read_to_string(root.join("src/main.tl")).unwrap();
+            assert!(main.contains(marker), "{} template", template);
+
+            let config = manager.config().unwrap();
+            assert_eq!(config.package.bin.len(), 1);
+            assert_eq!(config.package.tests.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_project_config_manager_legacy_config_file() {
+        let (mut manager, temp_dir) = create_test_project();
+        ProjectConfig::new("legacy".to_string(), "1.0.0".to_string())
+            .to_file(temp_dir.path().join(LEGACY_CONFIG_FILE))
+            .unwrap();
+
+        assert!(manager.is_initialized());
+        assert_eq!(manager.config_file(), temp_dir.path().join(LEGACY_CONFIG_FILE));
+        assert_eq!(manager.load().unwrap().package.name, "legacy");
+        assert!(manager.init_project("legacy".to_string(), "1.0.0".to_string(), None).is_err());
+    }
+
     #[test]
     fn test_project_config_manager_init_project_already_exists() {
         let (mut manager, _temp_dir) = create_test_project();
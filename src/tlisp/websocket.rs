@@ -0,0 +1,527 @@
+//! WebSocket client and server
+//!
+//! `(ws-connect url [handler])` opens a connection to a `ws://` or `wss://`
+//! URL and `(ws-serve port handler)` accepts connections on a port. Either
+//! way a connection is named by a PID: when a REAM runtime is attached, an
+//! actor is spawned under it for each connection, and whatever is sent to
+//! that actor goes out as a frame, so a connection can be handed to any
+//! process like another actor. `(ws-send conn data)` does the same from
+//! TLisp, sending strings as text frames and other values as JSON.
+//!
+//! The handler actor is told about each connection as
+//! `("ws-open" conn path)` when a server accepts it, `("ws-message" conn
+//! data)` for each text or binary frame, with binary data as a list of
+//! bytes, and `("ws-close" conn code reason)` once it is closed.
+//!
+//! Connections run on a shared background runtime. Each sends a ping every
+//! `ping_interval` and is dropped when nothing, not even a pong, arrives
+//! within `idle_timeout`. Frames waiting to go out are held in a queue of
+//! `queue_capacity`; when a peer stops reading and the queue fills,
+//! `ws-send` waits up to `send_timeout` for room and then fails, and
+//! messages sent to the connection's actor fail at once.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::error::{RuntimeError, RuntimeResult, TlispError, TlispResult};
+use crate::orm::cdc::ActorSender;
+use crate::runtime::{ReamActor, ReamRuntime};
+use crate::types::{MessagePayload, Pid};
+
+/// Longest wait for a connection to be established
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Close code for a connection dropped without a closing handshake
+const ABNORMAL_CLOSURE: u16 = 1006;
+
+/// Close code for a closing handshake that gave no code
+const NO_STATUS: u16 = 1005;
+
+/// Runs every connection's reads and writes
+static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("websocket")
+        .enable_all()
+        .build()
+        .expect("could not start the WebSocket runtime")
+});
+
+/// Open connections by PID, shared by every interpreter of the process
+static CONNECTIONS: Lazy<Mutex<HashMap<Pid, Connection>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Keepalive and backpressure settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct WsConfig {
+    pub ping_interval: Duration,
+    /// Drop a connection that sends nothing for this long
+    pub idle_timeout: Duration,
+    /// Frames queued for a connection before sending waits
+    pub queue_capacity: usize,
+    /// How long `ws-send` waits for room in a full queue
+    pub send_timeout: Duration,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        WsConfig {
+            ping_interval: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(75),
+            queue_capacity: 64,
+            send_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The actor told about a connection's frames
+#[derive(Clone)]
+pub struct Handler {
+    pub pid: Pid,
+    pub sender: Arc<dyn ActorSender>,
+}
+
+impl Handler {
+    fn deliver(&self, message: serde_json::Value) {
+        if let Err(e) = self.sender.send(self.pid, MessagePayload::Data(message)) {
+            tracing::warn!("websocket: could not deliver to {}: {}", self.pid, e);
+        }
+    }
+}
+
+/// A frame to send
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Frame {
+    /// The frame for a message sent to a connection's actor: text and data
+    /// strings as text, other data as JSON and bytes as a binary frame
+    pub fn from_payload(payload: MessagePayload) -> Option<Frame> {
+        match payload {
+            MessagePayload::Text(text) | MessagePayload::Data(serde_json::Value::String(text)) => Some(Frame::Text(text)),
+            MessagePayload::Data(data) => Some(Frame::Text(data.to_string())),
+            MessagePayload::Bytes(bytes) => Some(Frame::Binary(bytes)),
+            MessagePayload::SharedBinary(binary) => Some(Frame::Binary(binary.to_vec())),
+            MessagePayload::Traced { payload, .. } => Frame::from_payload(*payload),
+            MessagePayload::Control(_) => None,
+        }
+    }
+
+    fn into_message(self) -> Message {
+        match self {
+            Frame::Text(text) => Message::Text(text),
+            Frame::Binary(bytes) => Message::Binary(bytes),
+        }
+    }
+}
+
+/// The sending side of an open connection
+#[derive(Clone)]
+struct Connection {
+    outgoing: flume::Sender<Message>,
+    send_timeout: Duration,
+}
+
+/// The actor standing for a connection, sending what it receives
+pub struct WebSocketActor {
+    pid: Pid,
+    outgoing: flume::Sender<Message>,
+}
+
+impl ReamActor for WebSocketActor {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        let Some(frame) = Frame::from_payload(message) else {
+            return Ok(());
+        };
+        // An actor cannot wait for a slow peer without holding up its
+        // scheduler, so a full queue fails the message instead
+        self.outgoing.try_send(frame.into_message()).map_err(|e| match e {
+            flume::TrySendError::Full(_) => RuntimeError::MailboxFull(self.pid),
+            flume::TrySendError::Disconnected(_) => RuntimeError::ActorError(format!("WebSocket {} is closed", self.pid)),
+        })
+    }
+
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        Ok(())
+    }
+
+    fn is_alive(&self) -> bool {
+        !self.outgoing.is_disconnected()
+    }
+}
+
+/// `host:port` of a `ws://` or `wss://` URL, as network permissions name it
+pub fn address(url: &str) -> TlispResult<String> {
+    let invalid = || TlispError::Runtime(format!("ws-connect: expected a ws:// or wss:// URL, got {}", url));
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid())?;
+    if !matches!(parsed.scheme(), "ws" | "wss") {
+        return Err(invalid());
+    }
+    let host = parsed.host_str().ok_or_else(invalid)?;
+    Ok(format!("{}:{}", host, parsed.port_or_known_default().ok_or_else(invalid)?))
+}
+
+/// Connect to `url`, returning the connection's PID. Frames from the peer
+/// go to `handler`; without one the connection only sends. With a runtime,
+/// the connection's actor is spawned under it.
+pub fn connect(url: &str, handler: Option<Handler>, runtime: Option<Arc<ReamRuntime>>, config: WsConfig) -> TlispResult<Pid> {
+    address(url)?;
+    let url = url.to_string();
+    let (done, opened) = flume::bounded(1);
+    RUNTIME.spawn(async move {
+        let result = match tokio::time::timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(url.as_str())).await {
+            Ok(Ok((socket, _))) => open(socket, handler, runtime.as_ref(), config, None),
+            Ok(Err(e)) => Err(TlispError::Runtime(format!("ws-connect: {}: {}", url, e))),
+            Err(_) => Err(TlispError::Runtime(format!("ws-connect: {}: timed out", url))),
+        };
+        let _ = done.send(result);
+    });
+    opened
+        .recv()
+        .map_err(|_| TlispError::Runtime("ws-connect: the WebSocket runtime stopped".to_string()))?
+}
+
+/// Accept connections on `addr`, telling `handler` about each, and return
+/// the address bound. With a runtime, each connection's actor is spawned
+/// under it.
+pub fn serve(addr: SocketAddr, handler: Handler, runtime: Option<Arc<ReamRuntime>>, config: WsConfig) -> TlispResult<SocketAddr> {
+    let listener = std::net::TcpListener::bind(addr)
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|e| TlispError::Runtime(format!("ws-serve: cannot listen on {}: {}", addr, e)))?;
+    let bound = listener
+        .local_addr()
+        .map_err(|e| TlispError::Runtime(format!("ws-serve: {}", e)))?;
+    let listener = {
+        let _entered = RUNTIME.enter();
+        tokio::net::TcpListener::from_std(listener).map_err(|e| TlispError::Runtime(format!("ws-serve: {}", e)))?
+    };
+    RUNTIME.spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(accept(stream, handler.clone(), runtime.clone(), config.clone()));
+                }
+                Err(e) => tracing::warn!("websocket: accepting on {} failed: {}", bound, e),
+            }
+        }
+    });
+    Ok(bound)
+}
+
+/// Finish the handshake of a connection a server accepted
+async fn accept(stream: tokio::net::TcpStream, handler: Handler, runtime: Option<Arc<ReamRuntime>>, config: WsConfig) {
+    let mut path = String::new();
+    // The error type is the handshake callback's, not ours to shrink
+    #[allow(clippy::result_large_err)]
+    let record_path = |request: &Request, response: Response| {
+        path = request.uri().path().to_string();
+        Ok(response)
+    };
+    match tokio_tungstenite::accept_hdr_async(stream, record_path).await {
+        Ok(socket) => {
+            if let Err(e) = open(socket, Some(handler), runtime.as_ref(), config, Some(path)) {
+                tracing::warn!("websocket: could not open connection: {}", e);
+            }
+        }
+        Err(e) => tracing::debug!("websocket: handshake failed: {}", e),
+    }
+}
+
+/// Register a connection whose handshake is done and start running it
+fn open<S>(
+    socket: WebSocketStream<S>,
+    handler: Option<Handler>,
+    runtime: Option<&Arc<ReamRuntime>>,
+    config: WsConfig,
+    path: Option<String>,
+) -> TlispResult<Pid>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let conn = Pid::new();
+    let (outgoing, queued) = flume::bounded(config.queue_capacity.max(1));
+    if let Some(runtime) = runtime {
+        runtime
+            .spawn_as(conn, WebSocketActor { pid: conn, outgoing: outgoing.clone() })
+            .map_err(|e| TlispError::Runtime(format!("websocket: could not spawn connection actor: {}", e)))?;
+    }
+    CONNECTIONS.lock().unwrap().insert(
+        conn,
+        Connection {
+            outgoing,
+            send_timeout: config.send_timeout,
+        },
+    );
+    if let (Some(handler), Some(path)) = (&handler, path) {
+        handler.deliver(serde_json::json!(["ws-open", conn, path]));
+    }
+    RUNTIME.spawn(run(conn, socket, queued, handler, runtime.cloned(), config));
+    Ok(conn)
+}
+
+/// Pass frames both ways until the connection closes
+async fn run<S>(
+    conn: Pid,
+    socket: WebSocketStream<S>,
+    queued: flume::Receiver<Message>,
+    handler: Option<Handler>,
+    runtime: Option<Arc<ReamRuntime>>,
+    config: WsConfig,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sink, mut stream) = socket.split();
+    let writer = tokio::spawn(async move {
+        let mut ping = tokio::time::interval(config.ping_interval);
+        ping.tick().await;
+        loop {
+            let message = tokio::select! {
+                message = queued.recv_async() => match message {
+                    Ok(message) => message,
+                    Err(_) => break,
+                },
+                _ = ping.tick() => Message::Ping(Vec::new()),
+            };
+            let closing = matches!(message, Message::Close(_));
+            if sink.send(message).await.is_err() || closing {
+                break;
+            }
+        }
+    });
+
+    // A closing handshake is read to the end of the stream, so that the
+    // reply to the peer's close frame goes out
+    let mut closed = None;
+    let (code, reason) = loop {
+        match tokio::time::timeout(config.idle_timeout, stream.next()).await {
+            Err(_) => break (ABNORMAL_CLOSURE, "no pong within the idle timeout".to_string()),
+            Ok(None) => break closed.unwrap_or((ABNORMAL_CLOSURE, String::new())),
+            Ok(Some(Err(e))) => break closed.unwrap_or((ABNORMAL_CLOSURE, e.to_string())),
+            Ok(Some(Ok(message))) => {
+                let data = match message {
+                    Message::Text(text) => serde_json::Value::from(text),
+                    Message::Binary(bytes) => serde_json::Value::from(bytes),
+                    Message::Close(frame) => {
+                        closed = Some(match frame {
+                            Some(frame) => (u16::from(frame.code), frame.reason.into_owned()),
+                            None => (NO_STATUS, String::new()),
+                        });
+                        continue;
+                    }
+                    // Pings are answered as they are read
+                    Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+                };
+                if let Some(handler) = &handler {
+                    handler.deliver(serde_json::json!(["ws-message", conn, data]));
+                }
+            }
+        }
+    };
+
+    CONNECTIONS.lock().unwrap().remove(&conn);
+    writer.abort();
+    if let Some(runtime) = runtime {
+        let _ = runtime.terminate_process(conn);
+    }
+    if let Some(handler) = &handler {
+        handler.deliver(serde_json::json!(["ws-close", conn, code, reason]));
+    }
+}
+
+fn connection(conn: Pid) -> TlispResult<Connection> {
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .get(&conn)
+        .cloned()
+        .ok_or_else(|| TlispError::Runtime(format!("no open WebSocket {}", conn)))
+}
+
+/// Send a frame, waiting for room while the peer catches up
+pub fn send(conn: Pid, frame: Frame) -> TlispResult<()> {
+    let connection = connection(conn)?;
+    connection
+        .outgoing
+        .send_timeout(frame.into_message(), connection.send_timeout)
+        .map_err(|e| match e {
+            flume::SendTimeoutError::Timeout(_) => TlispError::Runtime(format!(
+                "ws-send: {} is not reading; {} frames are still queued after {:?}",
+                conn,
+                connection.outgoing.len(),
+                connection.send_timeout
+            )),
+            flume::SendTimeoutError::Disconnected(_) => TlispError::Runtime(format!("no open WebSocket {}", conn)),
+        })
+}
+
+/// Start closing `conn`; its handler hears of it once the peer agrees
+pub fn close(conn: Pid) -> TlispResult<()> {
+    let connection = CONNECTIONS
+        .lock()
+        .unwrap()
+        .remove(&conn)
+        .ok_or_else(|| TlispError::Runtime(format!("no open WebSocket {}", conn)))?;
+    let frame = CloseFrame {
+        code: CloseCode::Normal,
+        reason: "".into(),
+    };
+    // A peer too far behind to take the close frame is dropped instead
+    if connection.outgoing.try_send(Message::Close(Some(frame))).is_err() {
+        tracing::debug!("websocket: {} has no room for a close frame", conn);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlisp::{TlispInterpreter, Value};
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::thread;
+    use std::time::Instant;
+
+    #[derive(Default)]
+    struct RecordingSender {
+        messages: Mutex<Vec<serde_json::Value>>,
+    }
+
+    impl ActorSender for RecordingSender {
+        fn send(&self, _to: Pid, payload: MessagePayload) -> RuntimeResult<()> {
+            match payload {
+                MessagePayload::Data(data) => self.messages.lock().unwrap().push(data),
+                other => panic!("expected data, got {:?}", other),
+            }
+            Ok(())
+        }
+    }
+
+    impl RecordingSender {
+        fn handler(self: &Arc<Self>) -> Handler {
+            Handler {
+                pid: Pid::new(),
+                sender: self.clone(),
+            }
+        }
+
+        /// Wait for the `count`th message and return it
+        fn nth(&self, count: usize) -> serde_json::Value {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                if let Some(message) = self.messages.lock().unwrap().get(count - 1) {
+                    return message.clone();
+                }
+                assert!(Instant::now() < deadline, "timed out waiting for message {}", count);
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+    }
+
+    fn local() -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 0))
+    }
+
+    #[test]
+    fn test_frames_between_client_and_server() {
+        let server = Arc::new(RecordingSender::default());
+        let addr = serve(local(), server.handler(), None, WsConfig::default()).unwrap();
+        let client = Arc::new(RecordingSender::default());
+        let url = format!("ws://{}/chat", addr);
+        let conn = connect(&url, Some(client.handler()), None, WsConfig::default()).unwrap();
+
+        let open = server.nth(1);
+        assert_eq!(open[0], "ws-open");
+        assert_eq!(open[2], "/chat");
+        let accepted = Pid::from_raw(open[1].as_u64().unwrap());
+
+        send(conn, Frame::Text("hello".to_string())).unwrap();
+        assert_eq!(server.nth(2), serde_json::json!(["ws-message", accepted, "hello"]));
+        send(accepted, Frame::Binary(vec![1, 2, 255])).unwrap();
+        assert_eq!(client.nth(1), serde_json::json!(["ws-message", conn, [1, 2, 255]]));
+
+        // What a connection's actor receives goes out as a frame
+        let mut actor = WebSocketActor {
+            pid: accepted,
+            outgoing: connection(accepted).unwrap().outgoing,
+        };
+        actor.receive(MessagePayload::Data(serde_json::json!({"n": 1}))).unwrap();
+        assert_eq!(client.nth(2), serde_json::json!(["ws-message", conn, "{\"n\":1}"]));
+
+        close(conn).unwrap();
+        assert_eq!(client.nth(3), serde_json::json!(["ws-close", conn, 1000, ""]));
+        assert_eq!(server.nth(3), serde_json::json!(["ws-close", accepted, 1000, ""]));
+        assert!(send(conn, Frame::Text("late".to_string())).is_err());
+        assert!(send(accepted, Frame::Text("late".to_string())).is_err());
+        assert!(actor.receive(MessagePayload::Text("late".to_string())).is_err());
+        assert!(!actor.is_alive());
+    }
+
+    #[test]
+    fn test_backpressure_and_keepalive() {
+        // A peer that finishes the handshake and never reads again
+        let listener = TcpListener::bind(local()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let _socket = tokio_tungstenite::tungstenite::accept(stream).unwrap();
+            thread::sleep(Duration::from_secs(30));
+        });
+
+        let client = Arc::new(RecordingSender::default());
+        let config = WsConfig {
+            ping_interval: Duration::from_millis(50),
+            idle_timeout: Duration::from_secs(3),
+            queue_capacity: 2,
+            send_timeout: Duration::from_millis(100),
+        };
+        let conn = connect(&format!("ws://{}", addr), Some(client.handler()), None, config).unwrap();
+
+        let frame = Frame::Binary(vec![0; 1 << 20]);
+        let refused = (0..200).find_map(|_| send(conn, frame.clone()).err());
+        let refused = refused.expect("sending to a peer that does not read should fail").to_string();
+        assert!(refused.contains("is not reading"), "{}", refused);
+
+        // The peer answers no pings, so the connection is dropped
+        let closed = client.nth(1);
+        assert_eq!(closed[0], "ws-close");
+        assert_eq!(closed[2], ABNORMAL_CLOSURE);
+        assert!(send(conn, Frame::Text("late".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_builtins() {
+        let server = Arc::new(RecordingSender::default());
+        let addr = serve(local(), server.handler(), None, WsConfig::default()).unwrap();
+        let mut interpreter = TlispInterpreter::new();
+
+        let conn = interpreter.eval(&format!("(ws-connect \"ws://{}/feed\")", addr)).unwrap();
+        let Value::Pid(conn) = conn else { panic!("expected a connection, got {}", conn) };
+        assert_eq!(interpreter.eval(&format!("(ws-send {} \"ping\")", conn.raw())).unwrap(), Value::Unit);
+        assert_eq!(interpreter.eval(&format!("(ws-send {} (list 1 2))", conn.raw())).unwrap(), Value::Unit);
+        assert_eq!(server.nth(2)[2], "ping");
+        assert_eq!(server.nth(3)[2], "[1,2]");
+        assert_eq!(interpreter.eval(&format!("(ws-close {})", conn.raw())).unwrap(), Value::Unit);
+        assert!(interpreter.eval(&format!("(ws-close {})", conn.raw())).is_err());
+
+        assert!(interpreter.eval("(ws-connect \"http://127.0.0.1\")").is_err());
+        assert!(interpreter.eval("(ws-serve 0 (self))").is_err(), "handlers need a runtime");
+        assert_eq!(address("wss://example.com/socket").unwrap(), "example.com:443");
+        assert_eq!(address("ws://127.0.0.1:9000").unwrap(), "127.0.0.1:9000");
+    }
+}
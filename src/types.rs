@@ -5,9 +5,13 @@ use std::fmt;
 use std::hash::Hash;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::ops::Range;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
+use crate::runtime::topology::TopologyConfig;
+use crate::telemetry::{TraceContext, TracingConfig};
+
 /// Process identifier - unique across the runtime
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Pid(pub u64);
@@ -127,14 +131,69 @@ pub struct Message {
 pub enum MessagePayload {
     /// Raw bytes
     Bytes(Vec<u8>),
+    /// Binary in the shared heap, passed between local processes by reference
+    SharedBinary(#[serde(with = "crate::runtime::binary::serde_shared")] Arc<bytes::Bytes>),
     /// Text message
     Text(String),
     /// Structured data
     Data(serde_json::Value),
     /// System control message
     Control(ControlMessage),
+    /// Payload sent from within a traced span
+    Traced {
+        context: TraceContext,
+        payload: Box<MessagePayload>,
+    },
 }
 
+impl MessagePayload {
+    /// Attach the trace context active on this thread, if any
+    pub fn traced(self) -> MessagePayload {
+        match (TraceContext::current(), self) {
+            (_, payload @ MessagePayload::Traced { .. }) => payload,
+            (Some(context), payload) => MessagePayload::Traced { context, payload: Box::new(payload) },
+            (None, payload) => payload,
+        }
+    }
+
+    /// Split off the trace context, returning the payload it wrapped
+    pub fn untrace(self) -> (Option<TraceContext>, MessagePayload) {
+        match self {
+            MessagePayload::Traced { context, payload } => (Some(context), payload.untrace().1),
+            payload => (None, payload),
+        }
+    }
+
+    /// Payload carrying `data`, moved into the shared heap when it is large
+    /// enough that copying it on every send would cost more than sharing it
+    pub fn binary(data: Vec<u8>) -> MessagePayload {
+        if data.len() >= crate::runtime::binary::SHARED_BINARY_THRESHOLD {
+            MessagePayload::SharedBinary(crate::runtime::binary::share(data))
+        } else {
+            MessagePayload::Bytes(data)
+        }
+    }
+
+    /// One-line summary for monitoring clients and crash dumps
+    pub fn summary(&self) -> String {
+        let summary = match self {
+            MessagePayload::Bytes(bytes) => format!("<{} bytes>", bytes.len()),
+            MessagePayload::SharedBinary(binary) => format!("<{} shared bytes>", binary.len()),
+            MessagePayload::Text(text) => format!("{:?}", text),
+            MessagePayload::Data(value) => value.to_string(),
+            MessagePayload::Control(control) => format!("{:?}", control),
+            MessagePayload::Traced { payload, .. } => return payload.summary(),
+        };
+        match summary.char_indices().nth(MESSAGE_SUMMARY_LEN) {
+            Some((end, _)) => format!("{}…", &summary[..end]),
+            None => summary,
+        }
+    }
+}
+
+/// Longest message summary
+const MESSAGE_SUMMARY_LEN: usize = 120;
+
 /// System control messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ControlMessage {
@@ -186,6 +245,10 @@ pub struct ProcessInfo {
     pub memory_usage: usize,
     /// CPU time used (microseconds)
     pub cpu_time: u64,
+    /// Messages taken from the mailbox since the process started
+    pub messages_processed: u64,
+    /// Times the process has been restarted
+    pub restarts: u32,
 }
 
 /// Runtime statistics
@@ -207,11 +270,16 @@ pub struct RuntimeStats {
 
 /// Configuration for REAM runtime
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ReamConfig {
     /// Maximum number of processes
     pub max_processes: usize,
     /// Scheduler quantum in microseconds
     pub scheduler_quantum: u64,
+    /// Most messages a process handles in one quantum before yielding
+    pub max_messages_per_quantum: usize,
+    /// Messages a process takes from its mailbox per lock acquisition
+    pub mailbox_batch_size: usize,
     /// Maximum message queue size per process
     pub max_message_queue_size: usize,
     /// GC threshold in bytes
@@ -220,6 +288,12 @@ pub struct ReamConfig {
     pub enable_jit: bool,
     /// JIT optimization level
     pub jit_opt_level: u8,
+    /// Distributed tracing and sampling
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    /// Core pinning, NUMA-local heaps and cache affinity for scheduler workers
+    #[serde(default)]
+    pub topology: TopologyConfig,
 }
 
 impl Default for ReamConfig {
@@ -227,10 +301,14 @@ impl Default for ReamConfig {
         ReamConfig {
             max_processes: 1_000_000,
             scheduler_quantum: 1000, // 1ms
+            max_messages_per_quantum: 64,
+            mailbox_batch_size: 16,
             max_message_queue_size: 10_000,
             gc_threshold: 64 * 1024 * 1024, // 64MB
             enable_jit: true,
             jit_opt_level: 2,
+            tracing: TracingConfig::default(),
+            topology: TopologyConfig::default(),
         }
     }
 }
@@ -0,0 +1,432 @@
+//! WebAssembly modules as processes
+//!
+//! A `WasmActor` runs an untrusted module as an actor behavior. The module
+//! reaches the runtime only through functions it imports from the `ream`
+//! module:
+//!
+//! - `receive(buf, cap) -> len` copies the next message into `buf` and
+//!   returns its length, suspending the module until one is delivered. A
+//!   message longer than `cap` stays queued and only its length comes
+//!   back, so the module can call again with a larger buffer.
+//! - `send(to, ptr, len)` sends the `len` bytes at `ptr` to process `to`
+//! - `self() -> pid` is the actor's own pid
+//!
+//! The module exports its `memory` and a `run` function, which the actor
+//! starts once; the first message `run` receives is the init value the
+//! actor was spawned with, and the actor finishes when `run` returns.
+//!
+//! The security module's `ResourceLimits` bound the module: its memory
+//! can grow to `max_memory`, and each activation, from the start of `run`
+//! or a resumption with a message until it next waits in `receive`, gets
+//! `max_instructions` of fuel. A module that spins without receiving
+//! runs out and fails rather than holding its scheduler thread.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmi::core::{HostError, TrapCode};
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc, TypedResumableCall, TypedResumableInvocation, Val,
+};
+use crate::bytecode::{ProcessHost, ResourceLimits};
+use crate::error::{RuntimeError, RuntimeResult, WasmError, WasmResult};
+use crate::runtime::ReamActor;
+use crate::types::{MessagePayload, Pid};
+use super::HOST_MODULE;
+
+/// Raised by `receive` to suspend the module until a message arrives
+#[derive(Debug)]
+struct Blocked;
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "waiting for a message")
+    }
+}
+
+impl HostError for Blocked {}
+
+/// State the host functions share with the actor
+struct Host {
+    pid: Pid,
+    mailbox: VecDeque<Vec<u8>>,
+    /// Messages sent during the current activation
+    outbox: Vec<(Pid, Vec<u8>)>,
+    /// Buffer and capacity of the `receive` the module is suspended in
+    waiting: Option<(u32, u32)>,
+    limits: StoreLimits,
+}
+
+impl Host {
+    /// Copy the next message into `memory[buf..]`, returning its length
+    fn take_message(&mut self, memory: &mut [u8], buf: u32, cap: u32) -> Result<i32, wasmi::Error> {
+        let Some(message) = self.mailbox.front() else {
+            return Err(wasmi::Error::host(Blocked));
+        };
+        let len = message.len();
+        if len > cap as usize {
+            return Ok(len as i32);
+        }
+        let target = memory.get_mut(buf as usize..buf as usize + len)
+            .ok_or_else(|| wasmi::Error::new(format!("receive buffer {}+{} is out of bounds", buf, len)))?;
+        target.copy_from_slice(message);
+        self.mailbox.pop_front();
+        Ok(len as i32)
+    }
+}
+
+/// Where the module's `run` is
+enum Run {
+    Ready,
+    Waiting(TypedResumableInvocation<()>),
+    Finished,
+    Failed(String),
+}
+
+/// An instantiated module
+struct Instance {
+    store: Store<Host>,
+    memory: Memory,
+    run: TypedFunc<(), ()>,
+}
+
+/// Actor whose behavior is a WebAssembly module
+pub struct WasmActor {
+    pid: Pid,
+    module: Module,
+    init: Vec<u8>,
+    max_memory: usize,
+    fuel: u64,
+    /// The instance is only ever used through `&mut self`; the lock makes the actor `Sync`
+    instance: Mutex<Instance>,
+    host: Option<Arc<dyn ProcessHost>>,
+    run: Run,
+}
+
+impl WasmActor {
+    /// Load a module, to start with `init` as its first message
+    pub fn new(pid: Pid, bytes: &[u8], init: MessagePayload, limits: &ResourceLimits) -> WasmResult<Self> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes).map_err(|e| WasmError::InvalidModule(e.to_string()))?;
+        let init = to_bytes(init).map_err(|e| WasmError::TypeMismatch(e.to_string()))?;
+        let instance = instantiate(&module, pid, &init, limits.max_memory())?;
+        Ok(WasmActor {
+            pid,
+            module,
+            init,
+            max_memory: limits.max_memory(),
+            fuel: limits.max_instructions(),
+            instance: Mutex::new(instance),
+            host: None,
+            run: Run::Ready,
+        })
+    }
+
+    /// Carry out the module's sends through `host`; without one, a module
+    /// that sends fails
+    pub fn with_host(mut self, host: Arc<dyn ProcessHost>) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// Start `run`, until it first waits for a message or finishes
+    fn start(&mut self) -> Result<TypedResumableCall<()>, String> {
+        let instance = self.instance.get_mut().unwrap();
+        instance.store.set_fuel(self.fuel).map_err(|e| e.to_string())?;
+        instance.run.call_resumable(&mut instance.store, ()).map_err(|e| describe(e, self.fuel))
+    }
+
+    /// Resume `run` with the next message, until it waits for another or finishes
+    fn resume(&mut self, invocation: TypedResumableInvocation<()>) -> Result<TypedResumableCall<()>, String> {
+        let instance = self.instance.get_mut().unwrap();
+        let store = &mut instance.store;
+        let (buf, cap) = store.data_mut().waiting.take().expect("suspended outside receive");
+        let (memory, host) = instance.memory.data_and_store_mut(&mut *store);
+        let len = host.take_message(memory, buf, cap).map_err(|e| e.to_string())?;
+        store.set_fuel(self.fuel).map_err(|e| e.to_string())?;
+        invocation.resume(&mut *store, &[Val::I32(len)]).map_err(|e| describe(e, self.fuel))
+    }
+
+    /// Hand what the module sent during its last activation to the host
+    fn dispatch(&mut self) -> RuntimeResult<()> {
+        let outbox = std::mem::take(&mut self.instance.get_mut().unwrap().store.data_mut().outbox);
+        if outbox.is_empty() {
+            return Ok(());
+        }
+        let host = self.host.clone()
+            .ok_or_else(|| RuntimeError::ActorError("WebAssembly actor has no process host".to_string()))?;
+        for (to, message) in outbox {
+            host.send(to, to_payload(message))?;
+        }
+        Ok(())
+    }
+}
+
+impl ReamActor for WasmActor {
+    fn receive(&mut self, message: MessagePayload) -> RuntimeResult<()> {
+        let message = to_bytes(message)?;
+        self.instance.get_mut().unwrap().store.data_mut().mailbox.push_back(message);
+        Ok(())
+    }
+
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn restart(&mut self) -> RuntimeResult<()> {
+        let instance = instantiate(&self.module, self.pid, &self.init, self.max_memory)
+            .map_err(|error| RuntimeError::ActorError(error.to_string()))?;
+        self.instance = Mutex::new(instance);
+        self.run = Run::Ready;
+        Ok(())
+    }
+
+    fn is_alive(&self) -> bool {
+        !matches!(self.run, Run::Failed(_))
+    }
+
+    fn run_slice(&mut self) -> RuntimeResult<bool> {
+        let mail = !self.instance.get_mut().unwrap().store.data().mailbox.is_empty();
+        let activated = match std::mem::replace(&mut self.run, Run::Finished) {
+            Run::Ready => self.start(),
+            Run::Waiting(invocation) if mail => self.resume(invocation),
+            // Waiting for a message needs no quantum until one is delivered
+            run => {
+                self.run = run;
+                return Ok(false);
+            }
+        };
+        self.dispatch()?;
+        let error = match activated {
+            Ok(TypedResumableCall::Finished(())) => {
+                self.run = Run::Finished;
+                return Ok(false);
+            }
+            Ok(TypedResumableCall::Resumable(invocation)) if invocation.host_error().downcast_ref::<Blocked>().is_some() => {
+                self.run = Run::Waiting(invocation);
+                return Ok(false);
+            }
+            Ok(TypedResumableCall::Resumable(invocation)) => invocation.host_error().to_string(),
+            Err(error) => error,
+        };
+        self.run = Run::Failed(error.clone());
+        Err(RuntimeError::ActorError(error))
+    }
+
+    fn dictionary(&self) -> BTreeMap<String, String> {
+        let instance = self.instance.lock().unwrap();
+        let mut dictionary = BTreeMap::from([
+            ("memory".to_string(), instance.memory.data(&instance.store).len().to_string()),
+            ("mailbox".to_string(), instance.store.data().mailbox.len().to_string()),
+        ]);
+        let status = match &self.run {
+            Run::Ready => "ready",
+            Run::Waiting(_) => "waiting",
+            Run::Finished => "finished",
+            Run::Failed(error) => {
+                dictionary.insert("error".to_string(), error.clone());
+                "failed"
+            }
+        };
+        dictionary.insert("status".to_string(), status.to_string());
+        dictionary
+    }
+}
+
+/// Instantiate `module` with the host functions, queueing `init`
+fn instantiate(module: &Module, pid: Pid, init: &[u8], max_memory: usize) -> WasmResult<Instance> {
+    let engine = module.engine();
+    let host = Host {
+        pid,
+        mailbox: VecDeque::from([init.to_vec()]),
+        outbox: Vec::new(),
+        waiting: None,
+        limits: StoreLimitsBuilder::new().memory_size(max_memory).instances(1).memories(1).tables(1).build(),
+    };
+    let mut store = Store::new(engine, host);
+    store.limiter(|host| &mut host.limits);
+
+    let mut linker = Linker::<Host>::new(engine);
+    let linked = linker
+        .func_wrap(HOST_MODULE, "receive", |mut caller: Caller<'_, Host>, buf: u32, cap: u32| -> Result<i32, wasmi::Error> {
+            let memory = module_memory(&caller)?;
+            let (data, host) = memory.data_and_store_mut(&mut caller);
+            if host.mailbox.is_empty() {
+                host.waiting = Some((buf, cap));
+            }
+            host.take_message(data, buf, cap)
+        })
+        .and_then(|linker| linker.func_wrap(HOST_MODULE, "send", |mut caller: Caller<'_, Host>, to: i64, ptr: u32, len: u32| -> Result<(), wasmi::Error> {
+            let memory = module_memory(&caller)?;
+            let (data, host) = memory.data_and_store_mut(&mut caller);
+            let message = data.get(ptr as usize..ptr as usize + len as usize)
+                .ok_or_else(|| wasmi::Error::new(format!("send buffer {}+{} is out of bounds", ptr, len)))?;
+            host.outbox.push((Pid::from_raw(to as u64), message.to_vec()));
+            Ok(())
+        }))
+        .and_then(|linker| linker.func_wrap(HOST_MODULE, "self", |caller: Caller<'_, Host>| -> i64 {
+            caller.data().pid.raw() as i64
+        }));
+    linked.map_err(|e| WasmError::InstantiationFailed(e.to_string()))?;
+
+    let instance = linker.instantiate(&mut store, module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(|e| WasmError::InstantiationFailed(e.to_string()))?;
+    let memory = instance.get_memory(&store, "memory")
+        .ok_or_else(|| WasmError::MissingExport("memory".to_string()))?;
+    let run = instance.get_typed_func::<(), ()>(&store, "run")
+        .map_err(|_| WasmError::MissingExport("run".to_string()))?;
+    Ok(Instance { store, memory, run })
+}
+
+fn module_memory(caller: &Caller<'_, Host>) -> Result<Memory, wasmi::Error> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("module exports no memory"))
+}
+
+/// A failed activation, naming the limit it ran into
+fn describe(error: wasmi::Error, fuel: u64) -> String {
+    match error.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => format!("Module ran out of fuel ({} instructions) before receiving", fuel),
+        _ => error.to_string(),
+    }
+}
+
+/// A message as the bytes `receive` hands the module: text as UTF-8,
+/// bytes as they are, and data as its JSON encoding
+fn to_bytes(payload: MessagePayload) -> RuntimeResult<Vec<u8>> {
+    match payload {
+        MessagePayload::Text(text) => Ok(text.into_bytes()),
+        MessagePayload::Bytes(bytes) => Ok(bytes),
+        MessagePayload::SharedBinary(binary) => Ok(binary.to_vec()),
+        MessagePayload::Data(json) => Ok(json.to_string().into_bytes()),
+        MessagePayload::Control(_) => Err(RuntimeError::InvalidMessage("WebAssembly actors take no control messages".to_string())),
+        MessagePayload::Traced { payload, .. } => to_bytes(*payload),
+    }
+}
+
+/// Bytes the module sent, as text when they are UTF-8
+fn to_payload(bytes: Vec<u8>) -> MessagePayload {
+    match String::from_utf8(bytes) {
+        Ok(text) => MessagePayload::Text(text),
+        Err(error) => MessagePayload::binary(error.into_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{create_sandbox_manager, BytecodeActor};
+    use crate::runtime::Process;
+    use crate::types::Priority;
+
+    /// Echoes every message to the pid in its init message, until an empty one
+    const ECHO: &str = r#"
+        (module
+          (import "ream" "receive" (func $receive (param i32 i32) (result i32)))
+          (import "ream" "send" (func $send (param i64 i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "run")
+            (local $reply i64)
+            (local $len i32)
+            (drop (call $receive (i32.const 0) (i32.const 8)))
+            (local.set $reply (i64.load (i32.const 0)))
+            (loop $next
+              (local.set $len (call $receive (i32.const 16) (i32.const 64)))
+              (if (local.get $len)
+                (then
+                  (call $send (local.get $reply) (i32.const 16) (local.get $len))
+                  (br $next))))))
+    "#;
+
+    /// Holds sends for the test to look at
+    #[derive(Default)]
+    struct TestHost {
+        sent: Mutex<Vec<(Pid, MessagePayload)>>,
+    }
+
+    impl ProcessHost for TestHost {
+        fn spawn(&self, _actor: BytecodeActor) -> RuntimeResult<()> {
+            unreachable!("WebAssembly actors do not spawn")
+        }
+
+        fn send(&self, to: Pid, message: MessagePayload) -> RuntimeResult<()> {
+            self.sent.lock().unwrap().push((to, message));
+            Ok(())
+        }
+    }
+
+    fn load(wat: &str, init: MessagePayload, limits: &ResourceLimits) -> WasmResult<WasmActor> {
+        WasmActor::new(Pid::new(), &wat::parse_str(wat).unwrap(), init, limits)
+    }
+
+    #[test]
+    fn test_module_exchanges_messages() {
+        let host = Arc::new(TestHost::default());
+        let reply = Pid::new();
+        let init = MessagePayload::Bytes(reply.raw().to_le_bytes().to_vec());
+        let actor = load(ECHO, init, &ResourceLimits::default()).unwrap().with_host(host.clone());
+        let pid = actor.pid();
+        let mut process = Process::new(pid, Box::new(actor), Priority::Normal);
+
+        // The module takes its init message and waits for the next
+        process.run_quantum().unwrap();
+        assert_eq!(process.dictionary()["status"], "waiting");
+        assert!(!process.is_busy());
+
+        // Messages already queued are taken without suspending
+        process.deliver(MessagePayload::Text("ping".to_string())).unwrap();
+        process.deliver(MessagePayload::Bytes(vec![0xff, 0])).unwrap();
+        process.run_quantum().unwrap();
+        assert!(!process.is_busy());
+        let sent = host.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(sent.iter().all(|(to, _)| *to == reply));
+        assert!(matches!(&sent[0].1, MessagePayload::Text(text) if text == "ping"));
+        assert!(matches!(&sent[1].1, MessagePayload::Bytes(bytes) if bytes == &[0xff, 0]));
+        drop(sent);
+
+        process.deliver(MessagePayload::Text(String::new())).unwrap();
+        process.run_quantum().unwrap();
+        assert_eq!(process.dictionary()["status"], "finished");
+
+        // A restarted module starts over from its init message
+        let mut echo = load(ECHO, MessagePayload::Text("too long for the pid".to_string()), &ResourceLimits::default()).unwrap();
+        echo.receive(MessagePayload::Text("unsent".to_string())).unwrap();
+        let err = echo.run_slice().unwrap_err().to_string();
+        assert!(err.contains("no process host"), "{}", err);
+        echo.restart().unwrap();
+        assert_eq!(echo.dictionary()["status"], "ready");
+        assert_eq!(echo.dictionary()["mailbox"], "1");
+    }
+
+    #[test]
+    fn test_module_limits() {
+        let sandbox = create_sandbox_manager().get_limits().clone();
+        let init = || MessagePayload::Text("init".to_string());
+
+        // A module that never receives runs out of fuel
+        let spin = r#"(module (memory (export "memory") 1) (func (export "run") (loop $spin (br $spin))))"#;
+        let mut spinning = load(spin, init(), &sandbox).unwrap();
+        let err = spinning.run_slice().unwrap_err().to_string();
+        assert!(err.contains("ran out of fuel (100000 instructions)"), "{}", err);
+        assert_eq!(spinning.dictionary()["status"], "failed");
+        assert!(!spinning.is_alive());
+
+        // Memory can only grow as far as the limits allow
+        let large = r#"(module (memory (export "memory") 200) (func (export "run")))"#;
+        assert!(matches!(load(large, init(), &sandbox), Err(WasmError::InstantiationFailed(_))));
+        let mut finished = load(large, init(), &ResourceLimits::default()).unwrap();
+        assert!(!finished.run_slice().unwrap());
+        assert_eq!(finished.dictionary()["status"], "finished");
+
+        // Modules get nothing from the host beyond messaging
+        let escape = r#"(module (import "env" "system" (func)) (memory (export "memory") 1) (func (export "run")))"#;
+        assert!(matches!(load(escape, init(), &sandbox), Err(WasmError::InstantiationFailed(_))));
+        let headless = r#"(module (memory (export "memory") 1))"#;
+        assert!(matches!(load(headless, init(), &sandbox), Err(WasmError::MissingExport(name)) if name == "run"));
+    }
+}
@@ -0,0 +1,131 @@
+// Loader for a TLisp program compiled with `ream compile --target wasm`.
+//
+//     import { run } from "./program.js";
+//     console.log(await run());
+//
+// Values come back as JavaScript ones: null, booleans, BigInts for
+// integers, numbers for floats, strings, and arrays for lists and tuples.
+// The standard library natives are provided here; pass an object of
+// further natives, each taking an array of arguments, to call others.
+
+const MODULE = "{module}";
+
+const TAG_NULL = 0;
+const TAG_BOOL = 1;
+const TAG_INT = 2;
+const TAG_FLOAT = 3;
+const TAG_STRING = 4;
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+/** An error the program failed with */
+export class ReamError extends Error {}
+
+/** Format a value the way the runtime prints it */
+export function display(value) {
+  if (value === null) return "null";
+  if (Array.isArray(value)) return `[${value.map(display).join(", ")}]`;
+  return String(value);
+}
+
+/** The standard library natives */
+export const stdlib = {
+  "string/upper": ([s]) => s.toUpperCase(),
+  "string/lower": ([s]) => s.toLowerCase(),
+  "string/trim": ([s]) => s.trim(),
+  "to_string": ([value]) => display(value),
+  "time/now_millis": () => BigInt(Date.now()),
+};
+
+async function moduleBytes() {
+  const url = new URL(MODULE, import.meta.url);
+  if (url.protocol === "file:") {
+    const { readFile } = await import("node:fs/promises");
+    return readFile(url);
+  }
+  const response = await fetch(url);
+  return response.arrayBuffer();
+}
+
+/** Instantiate the program, returning an object whose `run` runs it */
+export async function instantiate(natives = {}) {
+  const module = await WebAssembly.compile(await moduleBytes());
+  const functions = { ...stdlib, ...natives };
+  let exports;
+  let failure;
+
+  const bytes = (ptr, len) => new Uint8Array(exports.memory.buffer, ptr, len);
+
+  const decode = (value) => {
+    switch (exports.value_tag(value)) {
+      case TAG_NULL: return null;
+      case TAG_BOOL: return exports.value_int(value) !== 0n;
+      case TAG_INT: return exports.value_int(value);
+      case TAG_FLOAT: return exports.value_float(value);
+      case TAG_STRING: return decoder.decode(bytes(exports.value_data(value), exports.value_len(value)));
+      default: {
+        const items = [];
+        for (let i = 0; i < exports.value_len(value); i++) {
+          items.push(decode(exports.list_item(value, i)));
+        }
+        return items;
+      }
+    }
+  };
+
+  const encode = (value) => {
+    if (value === null || value === undefined) return exports.new_null();
+    if (typeof value === "boolean") return exports.new_bool(value ? 1 : 0);
+    if (typeof value === "bigint") return exports.new_int(value);
+    if (typeof value === "number") return exports.new_float(value);
+    if (typeof value === "string") {
+      const utf8 = encoder.encode(value);
+      const string = exports.new_string(utf8.length);
+      bytes(exports.value_data(string), utf8.length).set(utf8);
+      return string;
+    }
+    if (Array.isArray(value)) {
+      const list = exports.new_list(value.length);
+      value.forEach((item, i) => exports.set_item(list, i, encode(item)));
+      return list;
+    }
+    throw new ReamError(`Cannot pass ${typeof value} to the program`);
+  };
+
+  const host = {
+    fail: (ptr, len) => {
+      failure = new ReamError(decoder.decode(bytes(ptr, len)));
+    },
+    print: (value) => console.log(display(decode(value))),
+  };
+  for (const { module: from, name } of WebAssembly.Module.imports(module)) {
+    if (from !== "ream" || name in host) continue;
+    const native = functions[name];
+    if (!native) throw new ReamError(`Unknown native function ${name}`);
+    host[name] = (args, count) => {
+      const values = [];
+      for (let i = 0; i < count; i++) values.push(decode(args + i * 16));
+      return encode(native(values));
+    };
+  }
+
+  const instance = await WebAssembly.instantiate(module, { ream: host });
+  exports = instance.exports;
+  return {
+    exports,
+    run() {
+      failure = undefined;
+      try {
+        return decode(exports.run());
+      } catch (error) {
+        throw failure ?? error;
+      }
+    },
+  };
+}
+
+/** Run the program once, returning its result */
+export async function run(natives = {}) {
+  return (await instantiate(natives)).run();
+}
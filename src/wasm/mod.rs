@@ -0,0 +1,703 @@
+//! WebAssembly compilation target
+//!
+//! `WasmCompiler` lowers a `BytecodeProgram` to a self-contained
+//! WebAssembly module, so TLisp programs run in browsers and other wasm
+//! sandboxes without the VM. The module embeds a small runtime, written
+//! in `runtime.wat`, that keeps the value stack, locals and call frames
+//! in linear memory and implements the operators over tagged value
+//! slots. Control flow follows the bytecode: every instruction gets a
+//! block of its own and jumps go through a `br_table` on the pc.
+//!
+//! Everything the program needs from outside comes in as a function
+//! imported from the `ream` module:
+//!
+//! - `fail(message, len)` is told why the program is about to trap
+//! - `print(value)` prints a value, for programs that print
+//! - every native function the program calls is imported under its own
+//!   name as `(args, count) -> value`, with `args` pointing at the first
+//!   of `count` argument slots
+//!
+//! The module exports `memory`, `run` and accessors and constructors for
+//! values, so hosts can read results and build the values natives
+//! return. `LOADER` is a JavaScript host that provides the standard
+//! library natives and turns values into JavaScript ones.
+//!
+//! Going the other way, `WasmActor` hosts an untrusted module as a
+//! process of the runtime, see `actor`.
+
+pub mod actor;
+
+pub use actor::WasmActor;
+
+use std::fmt::Write;
+use crate::bytecode::{Bytecode, BytecodeProgram, NativeRegistry, Value};
+use crate::error::{WasmError, WasmResult};
+
+/// Runtime compiled into every module
+const RUNTIME: &str = include_str!("runtime.wat");
+
+/// JavaScript loader for compiled modules. `{module}` stands for the
+/// path of the module relative to the loader.
+pub const LOADER: &str = include_str!("loader.js");
+
+/// Module host functions are imported from
+pub const HOST_MODULE: &str = "ream";
+
+/// Tags of value slots
+pub mod tag {
+    pub const NULL: u32 = 0;
+    pub const BOOL: u32 = 1;
+    pub const INT: u32 = 2;
+    pub const FLOAT: u32 = 3;
+    pub const STRING: u32 = 4;
+    pub const LIST: u32 = 5;
+    pub const TUPLE: u32 = 6;
+}
+
+/// Size of a value slot in bytes
+const SLOT: u32 = 16;
+/// Slots of the value stack
+const STACK_SLOTS: u32 = 1 << 16;
+/// Slots for the locals of every active call
+const LOCAL_SLOTS: u32 = 1 << 16;
+/// Call frames, each a return pc and a local base
+const FRAMES: u32 = 1 << 14;
+/// Where the data segment starts, leaving 0 unused
+const DATA_BASE: u32 = 16;
+
+/// Messages the runtime fails with, by code
+const ERRORS: &[&str] = &[
+    "Stack underflow",
+    "Stack overflow",
+    "Call stack underflow",
+    "Call stack overflow",
+    "Out of memory",
+    "Cannot add these types",
+    "Cannot subtract these types",
+    "Cannot multiply these types",
+    "Cannot divide these types",
+    "Cannot take modulo of these types",
+    "Division by zero",
+    "Cannot negate this type",
+    "Cannot take absolute value of this type",
+    "Cannot compare these types",
+    "Expected a string",
+    "Expected a list",
+    "Expected a list or tuple",
+    "Invalid index",
+    "Index out of bounds",
+    "Local variable not found",
+];
+
+/// Compiles programs to WebAssembly modules
+#[derive(Debug, Clone)]
+pub struct WasmCompiler {
+    /// Natives programs may call, for their arity and effects
+    natives: NativeRegistry,
+}
+
+impl WasmCompiler {
+    /// Compile programs calling the standard library natives
+    pub fn new() -> Self {
+        WasmCompiler {
+            natives: NativeRegistry::stdlib(),
+        }
+    }
+
+    /// Compile programs calling the natives of `natives`
+    pub fn with_natives(natives: NativeRegistry) -> Self {
+        WasmCompiler { natives }
+    }
+
+    /// Compile a program to a binary module
+    pub fn compile(&self, program: &BytecodeProgram) -> WasmResult<Vec<u8>> {
+        let text = self.compile_wat(program)?;
+        wat::parse_str(&text).map_err(|e| WasmError::InvalidModule(e.to_string()))
+    }
+
+    /// Compile a program to a module in the text format
+    pub fn compile_wat(&self, program: &BytecodeProgram) -> WasmResult<String> {
+        let mut data = DataSegment::new();
+        let errors = data.reserve(ERRORS.len() as u32 * 8);
+        for (code, message) in ERRORS.iter().enumerate() {
+            let ptr = data.push(message.as_bytes());
+            data.patch(errors + code as u32 * 8, &ptr.to_le_bytes());
+            data.patch(errors + code as u32 * 8 + 4, &(message.len() as u32).to_le_bytes());
+        }
+        let constants = data.reserve(program.constants.len() as u32 * SLOT);
+        for (index, constant) in program.constants.iter().enumerate() {
+            let slot = data.value(constant)?;
+            data.patch(constants + index as u32 * SLOT, &slot);
+        }
+
+        // Imports come before every definition
+        let mut natives: Vec<&str> = Vec::new();
+        let mut prints = false;
+        for (pc, instruction) in program.instructions.iter().enumerate() {
+            match instruction {
+                Bytecode::CallNative(idx, effect) => {
+                    let name = native_name(program, *idx)?;
+                    let native = self.natives.get(name).ok_or_else(|| WasmError::CompilationFailed(
+                        format!("Unknown native function {} at pc {}", name, pc)
+                    ))?;
+                    if native.effect > *effect {
+                        return Err(WasmError::CompilationFailed(format!(
+                            "Native function {} has effect {:?} but is called at pc {} with {:?}",
+                            name, native.effect, pc, effect
+                        )));
+                    }
+                    if !natives.contains(&name) {
+                        natives.push(name);
+                    }
+                }
+                Bytecode::Print(_) => prints = true,
+                _ => {}
+            }
+        }
+
+        let mut body = String::new();
+        let count = program.instructions.len();
+        body.push_str("    call $reset\n    loop $dispatch\n    block $exit\n");
+        for pc in (0..count).rev() {
+            let _ = writeln!(body, "    block $pc{}", pc);
+        }
+        body.push_str("    local.get $pc\n    br_table");
+        for pc in 0..count {
+            let _ = write!(body, " $pc{}", pc);
+        }
+        body.push_str(" $exit\n");
+        for (pc, instruction) in program.instructions.iter().enumerate() {
+            let _ = writeln!(body, "    end\n    ;; {}: {:?}", pc, instruction);
+            self.lower(program, pc, instruction, constants, &natives, &mut body)?;
+        }
+        body.push_str("    end\n    end\n    call $result\n");
+
+        let stack_base = align(data.end(), SLOT);
+        let stack_end = stack_base + STACK_SLOTS * SLOT;
+        let locals_base = stack_end;
+        let frames_base = locals_base + LOCAL_SLOTS * SLOT;
+        let frames_end = frames_base + FRAMES * 8;
+        let heap_base = frames_end;
+        let pages = heap_base / 65536 + 1;
+
+        let runtime = RUNTIME
+            .replace("{errors}", &errors.to_string())
+            .replace("{stack_base}", &stack_base.to_string())
+            .replace("{stack_end}", &stack_end.to_string())
+            .replace("{locals_base}", &locals_base.to_string())
+            .replace("{locals_capacity}", &LOCAL_SLOTS.to_string())
+            .replace("{frames_base}", &frames_base.to_string())
+            .replace("{frames_end}", &frames_end.to_string())
+            .replace("{heap_base}", &heap_base.to_string());
+
+        let mut module = String::new();
+        let _ = writeln!(module, "(module");
+        let _ = writeln!(module, "  (import \"{}\" \"fail\" (func $host_fail (param i32 i32)))", HOST_MODULE);
+        if prints {
+            let _ = writeln!(module, "  (import \"{}\" \"print\" (func $host_print (param i32)))", HOST_MODULE);
+        }
+        for (index, name) in natives.iter().enumerate() {
+            let _ = writeln!(
+                module,
+                "  (import \"{}\" \"{}\" (func $native{} (param i32 i32) (result i32)))",
+                HOST_MODULE, escape(name.as_bytes()), index
+            );
+        }
+        let _ = writeln!(module, "  (memory (export \"memory\") {})", pages);
+        let _ = writeln!(module, "  (data (i32.const {}) \"{}\")", DATA_BASE, escape(&data.bytes));
+        module.push_str(&runtime);
+        let _ = writeln!(module, "  (func (export \"run\") (result i32)\n    (local $pc i32)");
+        module.push_str(&body);
+        module.push_str("  )\n)\n");
+        Ok(module)
+    }
+
+    /// Lower the instruction at `pc` onto `body`
+    fn lower(
+        &self,
+        program: &BytecodeProgram,
+        pc: usize,
+        instruction: &Bytecode,
+        constants: u32,
+        natives: &[&str],
+        body: &mut String,
+    ) -> WasmResult<()> {
+        use Bytecode::*;
+
+        let constant = |idx: u32| -> WasmResult<u32> {
+            if (idx as usize) < program.constants.len() {
+                Ok(constants + idx * SLOT)
+            } else {
+                Err(WasmError::CompilationFailed(format!("Constant {} not found at pc {}", idx, pc)))
+            }
+        };
+        let jump = |target: u32| format!("    i32.const {}\n    local.set $pc\n    br $dispatch\n", target);
+        let code = match instruction {
+            Const(idx, _) | LoadGlobal(idx, _) => format!("    i32.const {}\n    call $push\n", constant(*idx)?),
+            Load(idx, _) => format!("    i32.const {}\n    call $load_local\n", idx),
+            Store(idx, _) => format!("    i32.const {}\n    call $store_local\n", idx),
+            Pop(_) => "    call $pop\n    drop\n".to_string(),
+            Dup(_) => "    call $dup\n".to_string(),
+            Swap(_) => "    call $swap\n".to_string(),
+            Nop(_) => String::new(),
+
+            Add(_) => "    i32.const 0\n    call $arith\n".to_string(),
+            Sub(_) => "    i32.const 1\n    call $arith\n".to_string(),
+            Mul(_) => "    i32.const 2\n    call $arith\n".to_string(),
+            Div(_) => "    i32.const 3\n    call $arith\n".to_string(),
+            Mod(_) => "    i32.const 4\n    call $arith\n".to_string(),
+            AddConst(idx, _) => format!("    i32.const {}\n    call $push\n    i32.const 0\n    call $arith\n", constant(*idx)?),
+            AddLocals(a, b, _) => format!(
+                "    i32.const {}\n    call $load_local\n    i32.const {}\n    call $load_local\n    i32.const 0\n    call $arith\n",
+                a, b
+            ),
+            Neg(_) => "    i32.const 0\n    call $unary\n".to_string(),
+            Abs(_) => "    i32.const 1\n    call $unary\n".to_string(),
+            Min(_) => "    i32.const 0\n    call $min_max\n".to_string(),
+            Max(_) => "    i32.const 1\n    call $min_max\n".to_string(),
+
+            And(_) => "    i32.const 0\n    call $logic\n".to_string(),
+            Or(_) => "    i32.const 1\n    call $logic\n".to_string(),
+            Not(_) => "    i32.const 2\n    call $logic\n".to_string(),
+            Eq(_) => "    call $eq\n".to_string(),
+            Lt(_) => "    i32.const 0\n    call $order\n".to_string(),
+            Le(_) => "    i32.const 1\n    call $order\n".to_string(),
+            Gt(_) => "    i32.const 2\n    call $order\n".to_string(),
+            Ge(_) => "    i32.const 3\n    call $order\n".to_string(),
+
+            Jump(target, _) => jump(*target),
+            JumpIf(target, _) => format!("    call $pop_truthy\n    if\n{}    end\n", jump(*target)),
+            JumpIfNot(target, _) => format!("    call $pop_truthy\n    i32.eqz\n    if\n{}    end\n", jump(*target)),
+            Call(func_idx, _) => {
+                let function = program.functions.get(*func_idx as usize).ok_or_else(|| WasmError::CompilationFailed(
+                    format!("Function {} not found at pc {}", func_idx, pc)
+                ))?;
+                format!("    i32.const {}\n    call $call\n{}", pc + 1, jump(function.start_pc as u32))
+            }
+            CallDirect(_, start_pc, _) => format!("    i32.const {}\n    call $call\n{}", pc + 1, jump(*start_pc)),
+            Ret(_) => "    call $ret\n    local.set $pc\n    br $dispatch\n".to_string(),
+
+            StrLen(_) => "    call $str_len\n".to_string(),
+            StrConcat(_) => "    call $str_concat\n".to_string(),
+            ListNew(_) => "    call $list_new\n".to_string(),
+            ListLen(_) => "    call $list_len\n".to_string(),
+            ListGet(_) => "    call $list_get\n".to_string(),
+            ListSet(_) => "    call $list_set\n".to_string(),
+            ListAppend(_) => "    call $list_append\n".to_string(),
+            TupleNew(count, _) => format!("    i32.const {}\n    i32.const {}\n    call $new_sequence\n", tag::TUPLE, count),
+
+            Print(_) => "    call $pop\n    call $host_print\n".to_string(),
+            CallNative(idx, _) => {
+                let name = native_name(program, *idx)?;
+                let arity = self.natives.get(name).map_or(0, |native| native.arity);
+                let index = natives.iter().position(|native| *native == name).unwrap_or_default();
+                format!(
+                    "    i32.const {}\n    call $pop_values\n    i32.const {}\n    call $native{}\n    call $push\n",
+                    arity, arity, index
+                )
+            }
+
+            other => {
+                return Err(WasmError::CompilationFailed(format!(
+                    "{:?} at pc {} has no WebAssembly lowering", other, pc
+                )));
+            }
+        };
+        body.push_str(&code);
+        Ok(())
+    }
+}
+
+impl Default for WasmCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read the value whose slot is at `ptr` out of a module's memory
+pub fn read_value(memory: &[u8], ptr: u32) -> WasmResult<Value> {
+    let word = |at: u32| -> WasmResult<[u8; 8]> {
+        memory.get(at as usize..at as usize + 8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| WasmError::MemoryViolation(format!("Value at {} is out of bounds", at)))
+    };
+    let header = word(ptr)?;
+    let payload = word(ptr + 8)?;
+    let value_tag = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let data = u64::from_le_bytes(payload) as u32;
+    match value_tag {
+        tag::NULL => Ok(Value::Null),
+        tag::BOOL => Ok(Value::Bool(u64::from_le_bytes(payload) != 0)),
+        tag::INT => Ok(Value::Int(i64::from_le_bytes(payload))),
+        tag::FLOAT => Ok(Value::Float(f64::from_le_bytes(payload))),
+        tag::STRING => {
+            let bytes = memory.get(data as usize..data as usize + len as usize)
+                .ok_or_else(|| WasmError::MemoryViolation(format!("String at {} is out of bounds", data)))?;
+            Ok(Value::String(String::from_utf8_lossy(bytes).into_owned()))
+        }
+        tag::LIST | tag::TUPLE => {
+            let items = (0..len)
+                .map(|index| read_value(memory, data + index * SLOT))
+                .collect::<WasmResult<Vec<_>>>()?;
+            Ok(if value_tag == tag::LIST { Value::List(items) } else { Value::Tuple(items) })
+        }
+        other => Err(WasmError::TypeMismatch(format!("Unknown value tag {}", other))),
+    }
+}
+
+/// Name of the native a `CallNative` calls
+fn native_name(program: &BytecodeProgram, idx: u32) -> WasmResult<&str> {
+    match program.constants.get(idx as usize) {
+        Some(Value::String(name)) => Ok(name),
+        _ => Err(WasmError::CompilationFailed(format!("Native function name {} not found", idx))),
+    }
+}
+
+fn align(offset: u32, to: u32) -> u32 {
+    offset.div_ceil(to) * to
+}
+
+/// Escape bytes for a string in the text format
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len() * 3);
+    for byte in bytes {
+        match byte {
+            b' '..=b'~' if *byte != b'"' && *byte != b'\\' => escaped.push(*byte as char),
+            _ => {
+                let _ = write!(escaped, "\\{:02x}", byte);
+            }
+        }
+    }
+    escaped
+}
+
+/// Contents of the data segment, laid out from `DATA_BASE`
+struct DataSegment {
+    bytes: Vec<u8>,
+}
+
+impl DataSegment {
+    fn new() -> Self {
+        DataSegment { bytes: Vec::new() }
+    }
+
+    /// Address just past the data
+    fn end(&self) -> u32 {
+        DATA_BASE + self.bytes.len() as u32
+    }
+
+    /// Reserve `size` zeroed bytes aligned for a slot
+    fn reserve(&mut self, size: u32) -> u32 {
+        let start = align(self.end(), SLOT);
+        self.bytes.resize((start + size - DATA_BASE) as usize, 0);
+        start
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> u32 {
+        let start = self.reserve(bytes.len() as u32);
+        self.patch(start, bytes);
+        start
+    }
+
+    fn patch(&mut self, address: u32, bytes: &[u8]) {
+        let at = (address - DATA_BASE) as usize;
+        self.bytes[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Lay out what a constant refers to, returning its slot
+    fn value(&mut self, value: &Value) -> WasmResult<[u8; 16]> {
+        let (value_tag, len, payload) = match value {
+            Value::Null => (tag::NULL, 0, 0),
+            Value::Bool(b) => (tag::BOOL, 0, u64::from(*b)),
+            Value::Int(n) => (tag::INT, 0, *n as u64),
+            Value::Float(x) => (tag::FLOAT, 0, x.to_bits()),
+            Value::String(s) => (tag::STRING, s.len() as u32, u64::from(self.push(s.as_bytes()))),
+            Value::List(items) | Value::Tuple(items) => {
+                let start = self.reserve(items.len() as u32 * SLOT);
+                for (index, item) in items.iter().enumerate() {
+                    let slot = self.value(item)?;
+                    self.patch(start + index as u32 * SLOT, &slot);
+                }
+                let value_tag = if matches!(value, Value::List(_)) { tag::LIST } else { tag::TUPLE };
+                (value_tag, items.len() as u32, u64::from(start))
+            }
+            other => {
+                return Err(WasmError::CompilationFailed(format!(
+                    "Constants of type {} have no WebAssembly representation", other.type_name()
+                )));
+            }
+        };
+        let mut slot = [0; 16];
+        slot[0..4].copy_from_slice(&value_tag.to_le_bytes());
+        slot[4..8].copy_from_slice(&len.to_le_bytes());
+        slot[8..16].copy_from_slice(&payload.to_le_bytes());
+        Ok(slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{assemble, BytecodeVM};
+    use wasmi::{Caller, Engine, Extern, Linker, Module, Store};
+
+    /// What the host saw of a run
+    #[derive(Default)]
+    struct Host {
+        failure: Option<String>,
+        printed: Vec<Value>,
+    }
+
+    fn memory(caller: &Caller<'_, Host>) -> wasmi::Memory {
+        caller.get_export("memory").and_then(Extern::into_memory).unwrap()
+    }
+
+    /// Hand a native's result back to the module as a value slot
+    fn encode(caller: &mut Caller<'_, Host>, value: &Value) -> i32 {
+        let export = |caller: &Caller<'_, Host>, name: &str| caller.get_export(name).and_then(Extern::into_func).unwrap();
+        match value {
+            Value::Int(n) => export(caller, "new_int").typed::<i64, i32>(&*caller).unwrap().call(&mut *caller, *n).unwrap(),
+            Value::String(s) => {
+                let string = export(caller, "new_string").typed::<i32, i32>(&*caller).unwrap().call(&mut *caller, s.len() as i32).unwrap();
+                let data = export(caller, "value_data").typed::<i32, i32>(&*caller).unwrap().call(&mut *caller, string).unwrap();
+                memory(caller).data_mut(&mut *caller)[data as usize..data as usize + s.len()].copy_from_slice(s.as_bytes());
+                string
+            }
+            other => panic!("Unexpected native result {:?}", other),
+        }
+    }
+
+    /// Run a compiled module with the standard library natives
+    fn run_module(bytes: &[u8]) -> Result<(Value, Vec<Value>), String> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, bytes).unwrap();
+        let mut store = Store::new(&engine, Host::default());
+        let mut linker = Linker::<Host>::new(&engine);
+        let natives = NativeRegistry::stdlib();
+        for import in module.imports() {
+            assert_eq!(import.module(), HOST_MODULE);
+            match import.name() {
+                "fail" => {
+                    linker.func_wrap(HOST_MODULE, "fail", |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+                        let message = memory(&caller).data(&caller)[ptr as usize..(ptr + len) as usize].to_vec();
+                        caller.data_mut().failure = Some(String::from_utf8(message).unwrap());
+                    }).unwrap();
+                }
+                "print" => {
+                    linker.func_wrap(HOST_MODULE, "print", |mut caller: Caller<'_, Host>, value: i32| {
+                        let value = read_value(memory(&caller).data(&caller), value as u32).unwrap();
+                        caller.data_mut().printed.push(value);
+                    }).unwrap();
+                }
+                name => {
+                    let native = natives.get(name).unwrap().clone();
+                    linker.func_wrap(HOST_MODULE, name, move |mut caller: Caller<'_, Host>, args: i32, count: i32| {
+                        let args = (0..count as u32)
+                            .map(|index| read_value(memory(&caller).data(&caller), args as u32 + index * SLOT).unwrap())
+                            .collect::<Vec<_>>();
+                        let result = native.call(&args).unwrap();
+                        encode(&mut caller, &result)
+                    }).unwrap();
+                }
+            }
+        }
+        let instance = linker.instantiate(&mut store, &module).unwrap().start(&mut store).unwrap();
+        let run = instance.get_typed_func::<(), i32>(&store, "run").unwrap();
+        match run.call(&mut store, ()) {
+            Ok(result) => {
+                let memory = instance.get_memory(&store, "memory").unwrap();
+                let value = read_value(memory.data(&store), result as u32).unwrap();
+                Ok((value, std::mem::take(&mut store.data_mut().printed)))
+            }
+            Err(trap) => Err(store.data_mut().failure.take().unwrap_or_else(|| trap.to_string())),
+        }
+    }
+
+    fn run(source: &str) -> Result<Value, String> {
+        let program = assemble(source).unwrap();
+        run_module(&WasmCompiler::new().compile(&program).unwrap()).map(|(value, _)| value)
+    }
+
+    /// Run a program compiled to WebAssembly and on the VM, which must agree
+    fn run_both(source: &str) -> Value {
+        let program = assemble(source).unwrap();
+        let expected = BytecodeVM::new().execute_program(&program).unwrap();
+        assert_eq!(run(source).unwrap(), expected, "{}", source);
+        expected
+    }
+
+    #[test]
+    fn test_operators_match_vm() {
+        let operands = ["int 7", "int -3", "float 2.5", "float -0.5", "string \"ab\"", "string \"b\"", "bool true", "null"];
+        let operators = ["add", "sub", "mul", "div", "mod", "min", "max", "eq", "lt", "le", "gt", "ge", "and", "or"];
+        for a in operands {
+            for b in operands {
+                for operator in operators {
+                    let source = format!(".const 0 {}\n.const 1 {}\n    const 0\n    const 1\n    {}\n", a, b, operator);
+                    let program = assemble(&source).unwrap();
+                    match BytecodeVM::new().execute_program(&program) {
+                        Ok(expected) => assert_eq!(run(&source).unwrap(), expected, "{}", source),
+                        Err(_) => assert!(run(&source).is_err(), "{}", source),
+                    }
+                }
+            }
+            for operator in ["neg", "abs", "not"] {
+                let source = format!(".const 0 {}\n    const 0\n    {}\n", a, operator);
+                let program = assemble(&source).unwrap();
+                match BytecodeVM::new().execute_program(&program) {
+                    Ok(expected) => assert_eq!(run(&source).unwrap(), expected, "{}", source),
+                    Err(_) => assert!(run(&source).is_err(), "{}", source),
+                }
+            }
+        }
+
+        // Integer division wraps and truncates like the VM
+        run_both(".const 0 int -9223372036854775808\n.const 1 int -1\n    const 0\n    const 1\n    div\n");
+        run_both(".const 0 int -7\n.const 1 int 2\n    const 0\n    const 1\n    mod\n");
+        assert_eq!(run(".const 0 int 1\n.const 1 int 0\n    const 0\n    const 1\n    div\n").unwrap_err(), "Division by zero");
+        assert_eq!(run("    pop\n").unwrap_err(), "Stack underflow");
+        assert_eq!(run("").unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_control_flow_and_calls() {
+        // Sum 1..=100 in a loop over locals
+        let sum = "\
+.const 0 int 0
+.const 1 int 1
+.const 2 int 100
+    const 0
+    store 0
+    const 1
+    store 1
+loop:
+    load 1
+    const 2
+    le
+    jump_if_not done
+    load 0
+    load 1
+    add
+    store 0
+    load 1
+    const 1
+    add
+    store 1
+    jump loop
+done:
+    load 0
+";
+        assert_eq!(run_both(sum), Value::Int(5050));
+
+        let factorial = "\
+.const 0 int 1
+.const 1 int 10
+.function 0 fact params=1 locals=1 len=13
+    const 1
+    call fact
+    jump end
+fact:
+    store 0
+    load 0
+    const 0
+    le
+    jump_if_not recurse
+    const 0
+    ret
+recurse:
+    load 0
+    load 0
+    const 0
+    sub
+    call fact
+    mul
+    ret
+end:
+    nop
+";
+        assert_eq!(run_both(factorial), Value::Int(3628800));
+        assert_eq!(run("    ret\n").unwrap_err(), "Call stack underflow");
+        assert_eq!(run("    load 3\n").unwrap_err(), "Local variable not found");
+    }
+
+    #[test]
+    fn test_strings_and_lists() {
+        let source = "\
+.const 0 string \"héllo\"
+.const 1 string \" world\"
+.const 2 int 3
+.const 3 int 1
+.const 4 null
+    const 0
+    const 1
+    str_concat
+    dup
+    str_len
+    const 4
+    const 3
+    const 0
+    list_set
+    const 4
+    const 1
+    list_append
+    list_len
+    const 2
+    list_new
+    const 4
+    const 0
+    tuple_new 2
+    swap
+";
+        // Constants holding lists are laid out in the data segment
+        let mut program = assemble(source).unwrap();
+        program.constants[4] = Value::List(vec![Value::Int(1), Value::String("x".to_string())]);
+        let expected = BytecodeVM::new().execute_program(&program).unwrap();
+        assert!(matches!(expected, Value::List(ref items) if items.len() == 3), "{:?}", expected);
+        let (value, _) = run_module(&WasmCompiler::new().compile(&program).unwrap()).unwrap();
+        assert_eq!(value, expected);
+
+        program.constants[4] = Value::List(vec![Value::Int(1)]);
+        program.constants[3] = Value::Int(2);
+        let err = run_module(&WasmCompiler::new().compile(&program).unwrap()).unwrap_err();
+        assert_eq!(err, "Index out of bounds");
+
+        // Lists compare item by item, numbers by value
+        let mut program = assemble(".const 0 null\n.const 1 null\n    const 0\n    const 1\n    eq\n").unwrap();
+        program.constants[0] = Value::List(vec![Value::Int(1), Value::Float(2.0), Value::List(vec![Value::String("a".to_string())])]);
+        program.constants[1] = Value::List(vec![Value::Float(1.0), Value::Int(2), Value::List(vec![Value::String("a".to_string())])]);
+        let (value, _) = run_module(&WasmCompiler::new().compile(&program).unwrap()).unwrap();
+        assert_eq!(value, BytecodeVM::new().execute_program(&program).unwrap());
+    }
+
+    #[test]
+    fn test_natives_and_print() {
+        let source = "\
+.const 0 string \"string/upper\"
+.const 1 string \"shout\"
+.const 2 string \"to_string\"
+.const 3 float 1.5
+    const 1
+    call_native 0
+    print
+    const 3
+    call_native 2
+";
+        let program = assemble(source).unwrap();
+        let text = WasmCompiler::new().compile_wat(&program).unwrap();
+        assert!(text.contains("(import \"ream\" \"string/upper\""));
+        assert!(text.contains("(import \"ream\" \"print\""));
+
+        let (value, printed) = run_module(&WasmCompiler::new().compile(&program).unwrap()).unwrap();
+        assert_eq!(value, Value::String("1.5".to_string()));
+        assert_eq!(printed, vec![Value::String("SHOUT".to_string())]);
+
+        // Natives must be known and called with their effects
+        let err = WasmCompiler::new().compile(&assemble(".const 0 string \"missing\"\ncall_native 0\n").unwrap()).unwrap_err();
+        assert!(err.to_string().contains("Unknown native function missing"), "{}", err);
+        let err = WasmCompiler::new().compile(&assemble(".const 0 string \"time/now_millis\"\ncall_native 0\n").unwrap()).unwrap_err();
+        assert!(err.to_string().contains("time/now_millis"), "{}", err);
+        let err = WasmCompiler::new().compile(&assemble("    send\n").unwrap()).unwrap_err();
+        assert!(matches!(err, WasmError::CompilationFailed(_)), "{}", err);
+    }
+}
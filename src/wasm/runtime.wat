@@ -0,0 +1,581 @@
+  ;; Runtime shared by every compiled program. Values are 16 byte slots:
+  ;; a tag at 0, a length at 4 for strings, lists and tuples, and a
+  ;; payload at 8 holding an integer, a float, a boolean or a pointer to
+  ;; the bytes or items. Strings, lists and tuples are never modified in
+  ;; place, so slots share them freely; memory is bump allocated and
+  ;; never freed.
+  ;;
+  ;; Tags: 0 null, 1 bool, 2 int, 3 float, 4 string, 5 list, 6 tuple
+
+  (global $sp (mut i32) (i32.const {stack_base}))
+  (global $lp (mut i32) (i32.const 0))
+  (global $base (mut i32) (i32.const 0))
+  (global $fp (mut i32) (i32.const {frames_base}))
+  (global $heap (mut i32) (i32.const {heap_base}))
+
+  ;; Report error `code` of the message table to the host and trap
+  (func $fail (param $code i32)
+    (call $host_fail
+      (i32.load (i32.add (i32.const {errors}) (i32.shl (local.get $code) (i32.const 3))))
+      (i32.load offset=4 (i32.add (i32.const {errors}) (i32.shl (local.get $code) (i32.const 3)))))
+    (unreachable))
+
+  (func $alloc (param $size i32) (result i32)
+    (local $ptr i32)
+    (local $end i32)
+    (local.set $ptr (global.get $heap))
+    (local.set $end (i32.and (i32.add (i32.add (local.get $ptr) (local.get $size)) (i32.const 15)) (i32.const -16)))
+    (if (i32.gt_u (local.get $end) (i32.shl (memory.size) (i32.const 16)))
+      (then
+        (if (i32.eq
+              (memory.grow (i32.shr_u
+                (i32.sub (i32.add (local.get $end) (i32.const 65535)) (i32.shl (memory.size) (i32.const 16)))
+                (i32.const 16)))
+              (i32.const -1))
+          (then (call $fail (i32.const 4))))))
+    (global.set $heap (local.get $end))
+    (local.get $ptr))
+
+  (func $copy (param $to i32) (param $from i32)
+    (i64.store (local.get $to) (i64.load (local.get $from)))
+    (i64.store offset=8 (local.get $to) (i64.load offset=8 (local.get $from))))
+
+  (func $tag (param $value i32) (result i32)
+    (i32.load (local.get $value)))
+
+  (func $len (param $value i32) (result i32)
+    (i32.load offset=4 (local.get $value)))
+
+  (func $data (param $value i32) (result i32)
+    (i32.wrap_i64 (i64.load offset=8 (local.get $value))))
+
+  (func $is_number (param $value i32) (result i32)
+    (i32.or (i32.eq (call $tag (local.get $value)) (i32.const 2))
+            (i32.eq (call $tag (local.get $value)) (i32.const 3))))
+
+  (func $is_sequence (param $value i32) (result i32)
+    (i32.or (i32.eq (call $tag (local.get $value)) (i32.const 5))
+            (i32.eq (call $tag (local.get $value)) (i32.const 6))))
+
+  (func $as_float (param $value i32) (result f64)
+    (if (result f64) (i32.eq (call $tag (local.get $value)) (i32.const 2))
+      (then (f64.convert_i64_s (i64.load offset=8 (local.get $value))))
+      (else (f64.load offset=8 (local.get $value)))))
+
+  ;; Value stack
+
+  ;; Claim a new top slot
+  (func $reserve (result i32)
+    (local $slot i32)
+    (local.set $slot (global.get $sp))
+    (if (i32.ge_u (local.get $slot) (i32.const {stack_end}))
+      (then (call $fail (i32.const 1))))
+    (global.set $sp (i32.add (local.get $slot) (i32.const 16)))
+    (local.get $slot))
+
+  (func $push (param $value i32)
+    (call $copy (call $reserve) (local.get $value)))
+
+  ;; Pop the top slot, which stays readable until the next push
+  (func $pop (result i32)
+    (if (i32.le_u (global.get $sp) (i32.const {stack_base}))
+      (then (call $fail (i32.const 0))))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 16)))
+    (global.get $sp))
+
+  ;; Pop `count` slots, returning the first in the order they were pushed
+  (func $pop_values (param $count i32) (result i32)
+    (if (i32.gt_u (local.get $count) (i32.shr_u (i32.sub (global.get $sp) (i32.const {stack_base})) (i32.const 4)))
+      (then (call $fail (i32.const 0))))
+    (global.set $sp (i32.sub (global.get $sp) (i32.shl (local.get $count) (i32.const 4))))
+    (global.get $sp))
+
+  (func $push_value (param $tag i32) (param $len i32) (param $payload i64)
+    (local $slot i32)
+    (local.set $slot (call $reserve))
+    (i32.store (local.get $slot) (local.get $tag))
+    (i32.store offset=4 (local.get $slot) (local.get $len))
+    (i64.store offset=8 (local.get $slot) (local.get $payload)))
+
+  (func $push_int (param $n i64)
+    (call $push_value (i32.const 2) (i32.const 0) (local.get $n)))
+
+  (func $push_float (param $x f64)
+    (call $push_value (i32.const 3) (i32.const 0) (i64.reinterpret_f64 (local.get $x))))
+
+  (func $push_bool (param $b i32)
+    (call $push_value (i32.const 1) (i32.const 0) (i64.extend_i32_u (local.get $b))))
+
+  (func $push_ref (param $tag i32) (param $len i32) (param $data i32)
+    (call $push_value (local.get $tag) (local.get $len) (i64.extend_i32_u (local.get $data))))
+
+  (func $dup
+    (if (i32.le_u (global.get $sp) (i32.const {stack_base}))
+      (then (call $fail (i32.const 0))))
+    (call $push (i32.sub (global.get $sp) (i32.const 16))))
+
+  (func $swap
+    (local $b i32)
+    (local $a i32)
+    (local $tag i64)
+    (local $payload i64)
+    (local.set $b (call $pop))
+    (local.set $a (call $pop))
+    (local.set $tag (i64.load (local.get $a)))
+    (local.set $payload (i64.load offset=8 (local.get $a)))
+    (call $copy (local.get $a) (local.get $b))
+    (i64.store (local.get $b) (local.get $tag))
+    (i64.store offset=8 (local.get $b) (local.get $payload))
+    (global.set $sp (i32.add (local.get $b) (i32.const 16))))
+
+  ;; Locals and call frames
+
+  (func $local (param $index i32) (result i32)
+    (i32.add (i32.const {locals_base}) (i32.shl (i32.add (global.get $base) (local.get $index)) (i32.const 4))))
+
+  (func $load_local (param $index i32)
+    (if (i32.ge_u (i32.add (global.get $base) (local.get $index)) (global.get $lp))
+      (then (call $fail (i32.const 19))))
+    (call $push (call $local (local.get $index))))
+
+  (func $store_local (param $index i32)
+    (local $slot i32)
+    (local.set $slot (i32.add (global.get $base) (local.get $index)))
+    (if (i32.ge_u (local.get $slot) (i32.const {locals_capacity}))
+      (then (call $fail (i32.const 1))))
+    (if (i32.ge_u (local.get $slot) (global.get $lp))
+      (then
+        (memory.fill
+          (i32.add (i32.const {locals_base}) (i32.shl (global.get $lp) (i32.const 4)))
+          (i32.const 0)
+          (i32.shl (i32.sub (i32.add (local.get $slot) (i32.const 1)) (global.get $lp)) (i32.const 4)))
+        (global.set $lp (i32.add (local.get $slot) (i32.const 1)))))
+    (call $copy (call $local (local.get $index)) (call $pop)))
+
+  (func $call (param $return_pc i32)
+    (if (i32.ge_u (global.get $fp) (i32.const {frames_end}))
+      (then (call $fail (i32.const 3))))
+    (i32.store (global.get $fp) (local.get $return_pc))
+    (i32.store offset=4 (global.get $fp) (global.get $lp))
+    (global.set $base (global.get $lp))
+    (global.set $fp (i32.add (global.get $fp) (i32.const 8))))
+
+  ;; Leave the current function, returning the pc to continue at
+  (func $ret (result i32)
+    (if (i32.le_u (global.get $fp) (i32.const {frames_base}))
+      (then (call $fail (i32.const 2))))
+    (global.set $fp (i32.sub (global.get $fp) (i32.const 8)))
+    (global.set $lp (i32.load offset=4 (global.get $fp)))
+    (global.set $base
+      (if (result i32) (i32.gt_u (global.get $fp) (i32.const {frames_base}))
+        (then (i32.load offset=4 (i32.sub (global.get $fp) (i32.const 8))))
+        (else (i32.const 0))))
+    (i32.load (global.get $fp)))
+
+  ;; Truthiness and comparison
+
+  (func $truthy (param $value i32) (result i32)
+    (local $tag i32)
+    (local.set $tag (call $tag (local.get $value)))
+    (if (i32.eqz (local.get $tag)) (then (return (i32.const 0))))
+    (if (i32.le_u (local.get $tag) (i32.const 2))
+      (then (return (i64.ne (i64.load offset=8 (local.get $value)) (i64.const 0)))))
+    (if (i32.eq (local.get $tag) (i32.const 3))
+      (then (return (f64.ne (f64.load offset=8 (local.get $value)) (f64.const 0)))))
+    (i32.ne (call $len (local.get $value)) (i32.const 0)))
+
+  (func $pop_truthy (result i32)
+    (call $truthy (call $pop)))
+
+  (func $bytes_equal (param $a i32) (param $b i32) (param $n i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (if (i32.ne (i32.load8_u (i32.add (local.get $a) (local.get $i)))
+                    (i32.load8_u (i32.add (local.get $b) (local.get $i))))
+          (then (return (i32.const 0))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.const 1))
+
+  ;; Equality of the `eq` operator, under which integers equal floats of
+  ;; the same value
+  (func $equal (param $a i32) (param $b i32) (result i32)
+    (if (result i32) (i32.and (i32.and (call $is_number (local.get $a)) (call $is_number (local.get $b)))
+                              (i32.ne (call $tag (local.get $a)) (call $tag (local.get $b))))
+      (then (f64.eq (call $as_float (local.get $a)) (call $as_float (local.get $b))))
+      (else (call $same (local.get $a) (local.get $b)))))
+
+  ;; Structural equality, where values of different types always differ
+  (func $same (param $a i32) (param $b i32) (result i32)
+    (local $tag i32)
+    (local $i i32)
+    (local $n i32)
+    (local.set $tag (call $tag (local.get $a)))
+    (if (i32.ne (local.get $tag) (call $tag (local.get $b)))
+      (then (return (i32.const 0))))
+    (if (i32.eqz (local.get $tag)) (then (return (i32.const 1))))
+    (if (i32.le_u (local.get $tag) (i32.const 2))
+      (then (return (i64.eq (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b))))))
+    (if (i32.eq (local.get $tag) (i32.const 3))
+      (then (return (f64.eq (f64.load offset=8 (local.get $a)) (f64.load offset=8 (local.get $b))))))
+    (local.set $n (call $len (local.get $a)))
+    (if (i32.ne (local.get $n) (call $len (local.get $b))) (then (return (i32.const 0))))
+    (if (i32.eq (local.get $tag) (i32.const 4))
+      (then (return (call $bytes_equal (call $data (local.get $a)) (call $data (local.get $b)) (local.get $n)))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (if (i32.eqz (call $same
+              (i32.add (call $data (local.get $a)) (i32.shl (local.get $i) (i32.const 4)))
+              (i32.add (call $data (local.get $b)) (i32.shl (local.get $i) (i32.const 4)))))
+          (then (return (i32.const 0))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.const 1))
+
+  ;; Order two numbers or two strings as -1, 0 or 1
+  (func $compare (param $a i32) (param $b i32) (result i32)
+    (local $x f64)
+    (local $y f64)
+    (local $i i32)
+    (local $n i32)
+    (local $p i32)
+    (local $q i32)
+    (if (i32.and (i32.eq (call $tag (local.get $a)) (i32.const 2)) (i32.eq (call $tag (local.get $b)) (i32.const 2)))
+      (then
+        (return (i32.sub
+          (i64.gt_s (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b)))
+          (i64.lt_s (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b)))))))
+    (if (i32.and (call $is_number (local.get $a)) (call $is_number (local.get $b)))
+      (then
+        (local.set $x (call $as_float (local.get $a)))
+        (local.set $y (call $as_float (local.get $b)))
+        (if (i32.or (f64.ne (local.get $x) (local.get $x)) (f64.ne (local.get $y) (local.get $y)))
+          (then (call $fail (i32.const 13))))
+        (return (i32.sub (f64.gt (local.get $x) (local.get $y)) (f64.lt (local.get $x) (local.get $y))))))
+    (if (i32.and (i32.eq (call $tag (local.get $a)) (i32.const 4)) (i32.eq (call $tag (local.get $b)) (i32.const 4)))
+      (then
+        (local.set $n (call $len (local.get $a)))
+        (if (i32.gt_u (local.get $n) (call $len (local.get $b)))
+          (then (local.set $n (call $len (local.get $b)))))
+        (block $done
+          (loop $next
+            (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+            (local.set $p (i32.load8_u (i32.add (call $data (local.get $a)) (local.get $i))))
+            (local.set $q (i32.load8_u (i32.add (call $data (local.get $b)) (local.get $i))))
+            (if (i32.ne (local.get $p) (local.get $q))
+              (then (return (i32.sub (i32.gt_u (local.get $p) (local.get $q)) (i32.lt_u (local.get $p) (local.get $q))))))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $next)))
+        (return (i32.sub
+          (i32.gt_u (call $len (local.get $a)) (call $len (local.get $b)))
+          (i32.lt_u (call $len (local.get $a)) (call $len (local.get $b)))))))
+    (call $fail (i32.const 13))
+    (unreachable))
+
+  ;; Operators on the top of the stack
+
+  ;; `op` is 0 add, 1 sub, 2 mul, 3 div and 4 mod
+  (func $int_op (param $op i32) (param $a i64) (param $b i64) (result i64)
+    (if (i32.eqz (local.get $op)) (then (return (i64.add (local.get $a) (local.get $b)))))
+    (if (i32.eq (local.get $op) (i32.const 1)) (then (return (i64.sub (local.get $a) (local.get $b)))))
+    (if (i32.eq (local.get $op) (i32.const 2)) (then (return (i64.mul (local.get $a) (local.get $b)))))
+    (if (i64.eqz (local.get $b)) (then (call $fail (i32.const 10))))
+    ;; Dividing the smallest integer by -1 wraps instead of trapping
+    (if (i64.eq (local.get $b) (i64.const -1))
+      (then (return
+        (if (result i64) (i32.eq (local.get $op) (i32.const 3))
+          (then (i64.sub (i64.const 0) (local.get $a)))
+          (else (i64.const 0))))))
+    (if (result i64) (i32.eq (local.get $op) (i32.const 3))
+      (then (i64.div_s (local.get $a) (local.get $b)))
+      (else (i64.rem_s (local.get $a) (local.get $b)))))
+
+  (func $float_op (param $op i32) (param $a f64) (param $b f64) (result f64)
+    (if (i32.eqz (local.get $op)) (then (return (f64.add (local.get $a) (local.get $b)))))
+    (if (i32.eq (local.get $op) (i32.const 1)) (then (return (f64.sub (local.get $a) (local.get $b)))))
+    (if (i32.eq (local.get $op) (i32.const 2)) (then (return (f64.mul (local.get $a) (local.get $b)))))
+    (if (i32.eq (local.get $op) (i32.const 3)) (then (return (f64.div (local.get $a) (local.get $b)))))
+    (f64.sub (local.get $a) (f64.mul (f64.trunc (f64.div (local.get $a) (local.get $b))) (local.get $b))))
+
+  (func $concat (param $a i32) (param $b i32)
+    (local $la i32)
+    (local $lb i32)
+    (local $ptr i32)
+    (local.set $la (call $len (local.get $a)))
+    (local.set $lb (call $len (local.get $b)))
+    (local.set $ptr (call $alloc (i32.add (local.get $la) (local.get $lb))))
+    (memory.copy (local.get $ptr) (call $data (local.get $a)) (local.get $la))
+    (memory.copy (i32.add (local.get $ptr) (local.get $la)) (call $data (local.get $b)) (local.get $lb))
+    (call $push_ref (i32.const 4) (i32.add (local.get $la) (local.get $lb)) (local.get $ptr)))
+
+  (func $arith (param $op i32)
+    (local $b i32)
+    (local $a i32)
+    (local.set $b (call $pop))
+    (local.set $a (call $pop))
+    (if (i32.and (i32.eq (call $tag (local.get $a)) (i32.const 2)) (i32.eq (call $tag (local.get $b)) (i32.const 2)))
+      (then
+        (call $push_int (call $int_op (local.get $op) (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b))))
+        (return)))
+    (if (i32.and (call $is_number (local.get $a)) (call $is_number (local.get $b)))
+      (then
+        (call $push_float (call $float_op (local.get $op) (call $as_float (local.get $a)) (call $as_float (local.get $b))))
+        (return)))
+    (if (i32.and (i32.eqz (local.get $op))
+          (i32.and (i32.eq (call $tag (local.get $a)) (i32.const 4)) (i32.eq (call $tag (local.get $b)) (i32.const 4))))
+      (then
+        (call $concat (local.get $a) (local.get $b))
+        (return)))
+    (call $fail (i32.add (i32.const 5) (local.get $op))))
+
+  ;; `op` is 0 neg and 1 abs
+  (func $unary (param $op i32)
+    (local $a i32)
+    (local $n i64)
+    (local.set $a (call $pop))
+    (if (i32.eq (call $tag (local.get $a)) (i32.const 2))
+      (then
+        (local.set $n (i64.load offset=8 (local.get $a)))
+        (call $push_int
+          (if (result i64) (i32.or (i32.eqz (local.get $op)) (i64.lt_s (local.get $n) (i64.const 0)))
+            (then (i64.sub (i64.const 0) (local.get $n)))
+            (else (local.get $n))))
+        (return)))
+    (if (i32.eq (call $tag (local.get $a)) (i32.const 3))
+      (then
+        (call $push_float
+          (if (result f64) (i32.eqz (local.get $op))
+            (then (f64.neg (f64.load offset=8 (local.get $a))))
+            (else (f64.abs (f64.load offset=8 (local.get $a))))))
+        (return)))
+    (call $fail (i32.add (i32.const 11) (local.get $op))))
+
+  ;; `op` is 0 min and 1 max
+  (func $min_max (param $op i32)
+    (local $b i32)
+    (local $a i32)
+    (local $x i64)
+    (local $y i64)
+    (local.set $b (call $pop))
+    (local.set $a (call $pop))
+    (if (i32.and (i32.eq (call $tag (local.get $a)) (i32.const 2)) (i32.eq (call $tag (local.get $b)) (i32.const 2)))
+      (then
+        (local.set $x (i64.load offset=8 (local.get $a)))
+        (local.set $y (i64.load offset=8 (local.get $b)))
+        (call $push_int
+          (select (local.get $x) (local.get $y)
+            (i32.xor (i64.lt_s (local.get $x) (local.get $y)) (local.get $op))))
+        (return)))
+    (if (i32.and (call $is_number (local.get $a)) (call $is_number (local.get $b)))
+      (then
+        (call $push_float
+          (if (result f64) (i32.eqz (local.get $op))
+            (then (f64.min (call $as_float (local.get $a)) (call $as_float (local.get $b))))
+            (else (f64.max (call $as_float (local.get $a)) (call $as_float (local.get $b))))))
+        (return)))
+    (call $fail (i32.const 13)))
+
+  ;; `op` is 0 and, 1 or and 2 not
+  (func $logic (param $op i32)
+    (local $b i32)
+    (if (i32.eq (local.get $op) (i32.const 2))
+      (then
+        (call $push_bool (i32.eqz (call $pop_truthy)))
+        (return)))
+    (local.set $b (call $pop_truthy))
+    (call $push_bool
+      (if (result i32) (i32.eqz (local.get $op))
+        (then (i32.and (call $pop_truthy) (local.get $b)))
+        (else (i32.or (call $pop_truthy) (local.get $b))))))
+
+  (func $eq
+    (local $b i32)
+    (local.set $b (call $pop))
+    (call $push_bool (call $equal (call $pop) (local.get $b))))
+
+  ;; `op` is 0 lt, 1 le, 2 gt and 3 ge
+  (func $order (param $op i32)
+    (local $b i32)
+    (local $c i32)
+    (local.set $b (call $pop))
+    (local.set $c (call $compare (call $pop) (local.get $b)))
+    (call $push_bool
+      (if (result i32) (i32.eqz (local.get $op))
+        (then (i32.lt_s (local.get $c) (i32.const 0)))
+        (else (if (result i32) (i32.eq (local.get $op) (i32.const 1))
+          (then (i32.le_s (local.get $c) (i32.const 0)))
+          (else (if (result i32) (i32.eq (local.get $op) (i32.const 2))
+            (then (i32.gt_s (local.get $c) (i32.const 0)))
+            (else (i32.ge_s (local.get $c) (i32.const 0))))))))))
+
+  ;; Strings
+
+  (func $string_operand (param $value i32) (result i32)
+    (if (i32.ne (call $tag (local.get $value)) (i32.const 4))
+      (then (call $fail (i32.const 14))))
+    (local.get $value))
+
+  ;; Length in characters, counting the bytes that start one
+  (func $str_len
+    (local $s i32)
+    (local $i i32)
+    (local $n i64)
+    (local.set $s (call $string_operand (call $pop)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (call $len (local.get $s))))
+        (if (i32.ne (i32.and (i32.load8_u (i32.add (call $data (local.get $s)) (local.get $i))) (i32.const 192)) (i32.const 128))
+          (then (local.set $n (i64.add (local.get $n) (i64.const 1)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (call $push_int (local.get $n)))
+
+  (func $str_concat
+    (local $b i32)
+    (local.set $b (call $string_operand (call $pop)))
+    (call $concat (call $string_operand (call $pop)) (local.get $b)))
+
+  ;; Lists and tuples
+
+  (func $index_operand (param $value i32) (result i32)
+    (if (i32.or (i32.ne (call $tag (local.get $value)) (i32.const 2))
+                (i64.gt_u (i64.load offset=8 (local.get $value)) (i64.const 0x7fffffff)))
+      (then (call $fail (i32.const 17))))
+    (i32.wrap_i64 (i64.load offset=8 (local.get $value))))
+
+  (func $item (param $value i32) (param $index i32) (result i32)
+    (i32.add (call $data (local.get $value)) (i32.shl (local.get $index) (i32.const 4))))
+
+  ;; Collect the top `count` values into a new sequence tagged `tag`
+  (func $new_sequence (param $tag i32) (param $count i32)
+    (local $items i32)
+    (local $ptr i32)
+    (local.set $items (call $pop_values (local.get $count)))
+    (local.set $ptr (call $alloc (i32.shl (local.get $count) (i32.const 4))))
+    (memory.copy (local.get $ptr) (local.get $items) (i32.shl (local.get $count) (i32.const 4)))
+    (call $push_ref (local.get $tag) (local.get $count) (local.get $ptr)))
+
+  (func $list_new
+    (call $new_sequence (i32.const 5) (call $index_operand (call $pop))))
+
+  (func $list_len
+    (local $list i32)
+    (local.set $list (call $pop))
+    (if (i32.eqz (call $is_sequence (local.get $list)))
+      (then (call $fail (i32.const 16))))
+    (call $push_int (i64.extend_i32_u (call $len (local.get $list)))))
+
+  (func $list_get
+    (local $index i32)
+    (local $list i32)
+    (local.set $index (call $index_operand (call $pop)))
+    (local.set $list (call $pop))
+    (if (i32.eqz (call $is_sequence (local.get $list)))
+      (then (call $fail (i32.const 16))))
+    (if (i32.ge_u (local.get $index) (call $len (local.get $list)))
+      (then (call $fail (i32.const 18))))
+    (call $push (call $item (local.get $list) (local.get $index))))
+
+  ;; Copy the items of `list` into a new array with room for `extra` more
+  (func $list_copy (param $list i32) (param $extra i32) (result i32)
+    (local $ptr i32)
+    (if (i32.ne (call $tag (local.get $list)) (i32.const 5))
+      (then (call $fail (i32.const 15))))
+    (local.set $ptr (call $alloc (i32.shl (i32.add (call $len (local.get $list)) (local.get $extra)) (i32.const 4))))
+    (memory.copy (local.get $ptr) (call $data (local.get $list)) (i32.shl (call $len (local.get $list)) (i32.const 4)))
+    (local.get $ptr))
+
+  (func $list_set
+    (local $value i32)
+    (local $index i32)
+    (local $list i32)
+    (local $ptr i32)
+    (local.set $value (call $pop))
+    (local.set $index (call $index_operand (call $pop)))
+    (local.set $list (call $pop))
+    (local.set $ptr (call $list_copy (local.get $list) (i32.const 0)))
+    (if (i32.ge_u (local.get $index) (call $len (local.get $list)))
+      (then (call $fail (i32.const 18))))
+    (call $copy (i32.add (local.get $ptr) (i32.shl (local.get $index) (i32.const 4))) (local.get $value))
+    (call $push_ref (i32.const 5) (call $len (local.get $list)) (local.get $ptr)))
+
+  (func $list_append
+    (local $value i32)
+    (local $list i32)
+    (local $ptr i32)
+    (local.set $value (call $pop))
+    (local.set $list (call $pop))
+    (local.set $ptr (call $list_copy (local.get $list) (i32.const 1)))
+    (call $copy (i32.add (local.get $ptr) (i32.shl (call $len (local.get $list)) (i32.const 4))) (local.get $value))
+    (call $push_ref (i32.const 5) (i32.add (call $len (local.get $list)) (i32.const 1)) (local.get $ptr)))
+
+  ;; Values for the host. Functions returning a value return a pointer
+  ;; to its slot.
+
+  (func $new_value (param $tag i32) (param $len i32) (param $payload i64) (result i32)
+    (local $slot i32)
+    (local.set $slot (call $alloc (i32.const 16)))
+    (i32.store (local.get $slot) (local.get $tag))
+    (i32.store offset=4 (local.get $slot) (local.get $len))
+    (i64.store offset=8 (local.get $slot) (local.get $payload))
+    (local.get $slot))
+
+  (func (export "value_tag") (param $value i32) (result i32)
+    (call $tag (local.get $value)))
+
+  (func (export "value_len") (param $value i32) (result i32)
+    (call $len (local.get $value)))
+
+  (func (export "value_int") (param $value i32) (result i64)
+    (i64.load offset=8 (local.get $value)))
+
+  (func (export "value_float") (param $value i32) (result f64)
+    (f64.load offset=8 (local.get $value)))
+
+  (func (export "value_data") (param $value i32) (result i32)
+    (call $data (local.get $value)))
+
+  (func (export "list_item") (param $value i32) (param $index i32) (result i32)
+    (call $item (local.get $value) (local.get $index)))
+
+  (func (export "set_item") (param $list i32) (param $index i32) (param $value i32)
+    (call $copy (call $item (local.get $list) (local.get $index)) (local.get $value)))
+
+  (func $new_null (export "new_null") (result i32)
+    (call $new_value (i32.const 0) (i32.const 0) (i64.const 0)))
+
+  (func (export "new_bool") (param $b i32) (result i32)
+    (call $new_value (i32.const 1) (i32.const 0) (i64.extend_i32_u (i32.ne (local.get $b) (i32.const 0)))))
+
+  (func (export "new_int") (param $n i64) (result i32)
+    (call $new_value (i32.const 2) (i32.const 0) (local.get $n)))
+
+  (func (export "new_float") (param $x f64) (result i32)
+    (call $new_value (i32.const 3) (i32.const 0) (i64.reinterpret_f64 (local.get $x))))
+
+  ;; A string of `len` bytes for the host to fill in through `value_data`
+  (func (export "new_string") (param $len i32) (result i32)
+    (call $new_value (i32.const 4) (local.get $len) (i64.extend_i32_u (call $alloc (local.get $len)))))
+
+  ;; A list of `len` nulls for the host to fill in through `set_item`
+  (func (export "new_list") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (call $alloc (i32.shl (local.get $len) (i32.const 4))))
+    (memory.fill (local.get $ptr) (i32.const 0) (i32.shl (local.get $len) (i32.const 4)))
+    (call $new_value (i32.const 5) (local.get $len) (i64.extend_i32_u (local.get $ptr))))
+
+  ;; Start a run with empty stacks
+  (func $reset
+    (global.set $sp (i32.const {stack_base}))
+    (global.set $lp (i32.const 0))
+    (global.set $base (i32.const 0))
+    (global.set $fp (i32.const {frames_base})))
+
+  ;; The value a finished program returns: the top of the stack or null
+  (func $result (result i32)
+    (if (result i32) (i32.le_u (global.get $sp) (i32.const {stack_base}))
+      (then (call $new_null))
+      (else (call $pop))))